@@ -0,0 +1,323 @@
+/**
+ * # Arti embedding library header.
+ *
+ * (This is still a work in progress; please don't rely on it
+ * being the final API.)
+ *
+ * ## What this library does
+ *
+ * This library lets a C or C++ application run an Arti client
+ * inside its own process, and open anonymized connections with it.
+ * (If you want to talk to an Arti running in a separate process,
+ * use the Arti RPC library instead.)
+ *
+ * ## Using this library
+ *
+ * First, describe the client you want with an `ArtiClientConfigBuilder *`.
+ * Create one with `arti_client_config_builder_new()`,
+ * and adjust it with `arti_client_config_builder_set_state_dir()`,
+ * `arti_client_config_builder_set_cache_dir()`,
+ * and `arti_client_config_builder_set()`.
+ *
+ * Then, use `arti_client_create()` to make an `ArtiClient *`,
+ * and (optionally) `arti_client_bootstrap()` to connect it to the Tor network.
+ *
+ * Finally, use `arti_client_connect()` to open connections.
+ * Each connection is returned as a socket:
+ * read and write it as you would a TCP connection to the target.
+ *
+ * Except when noted otherwise, all functions in this library are thread-safe.
+ *
+ * ## Error handling
+ *
+ * On success, fallible functions return `ARTI_CLIENT_STATUS_SUCCESS`.  On failure,
+ * they return some other error code, and set an `* error_out` parameter
+ * to a newly allocated `ArtiClientError` object.
+ * (If `error_out==NULL`, then no error is allocated.)
+ *
+ * You can access information about an `ArtiClientError`
+ * by calling `arti_client_err_{status,message}()` on it.
+ * When you are done with an error, you should free it with
+ * `arti_client_err_free()`.
+ *
+ * The `error_out` parameter always appears last.
+ *
+ * ## Interface conventions
+ *
+ * This library follows the same conventions as the Arti RPC library;
+ * see `arti-rpc-client-core.h` for the full list.  In brief:
+ *
+ * - All functions check for NULL pointers in their arguments.
+ *   - `foo_free()` functions treat `foo_free(NULL)` as a no-op.
+ *
+ * - All input strings should be valid UTF-8.  (The library will check.)
+ *   All output strings will be valid UTF-8.
+ *
+ * - Newly allocated objects are returned via out-parameters,
+ *   with `out` in their names.
+ *   On failure, `*out` is set to NULL (or to an invalid socket).
+ *
+ * - When any object is exposed as a non-const pointer,
+ *   the application becomes the owner of that object,
+ *   and must eventually free it with the corresponding `arti_client_*_free()` function.
+ *
+ * - All identifiers are prefixed with `ARTI_CLIENT`, `ArtiClient`, or `arti_client`
+ *   as appropriate.
+ **/
+
+#ifndef ARTI_CLIENT_FFI_H_
+#define ARTI_CLIENT_FFI_H_
+
+/* Automatically generated by cbindgen. Don't modify manually. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+/**
+ * Type of a socket returned by `arti_client_connect()`.
+ *
+ * This a `SOCKET` on Windows, and an fd elsewhere.
+ **/
+#ifdef _WIN32
+typedef SOCKET ArtiClientRawSocket;
+#else
+typedef int ArtiClientRawSocket;
+#endif
+
+
+/**
+ * A status code returned by an Arti client function.
+ *
+ * On success, a function will return `ARTI_CLIENT_STATUS_SUCCESS (0)`.
+ * On failure, a function will return some other status code.
+ */
+typedef uint32_t ArtiClientStatus;
+
+/**
+ * An error returned by an Arti client function, exposed as an object.
+ *
+ * When a function returns an `ArtiClientStatus` other than `ARTI_CLIENT_STATUS_SUCCESS`,
+ * it will also expose a newly allocated value of this type
+ * via its `error_out` parameter.
+ */
+typedef struct ArtiClientError ArtiClientError;
+
+/**
+ * A configuration for an Arti client, under construction.
+ *
+ * Create one with `arti_client_config_builder_new()`,
+ * adjust it with the `arti_client_config_builder_set*()` functions,
+ * and use it to make a client with `arti_client_create()`.
+ *
+ * This is a thread-safe type: you may safely use it from multiple threads at once.
+ * Once you are done with it, you must free it with `arti_client_config_builder_free()`.
+ */
+typedef struct ArtiClientConfigBuilder ArtiClientConfigBuilder;
+
+/**
+ * An embedded Arti client.
+ *
+ * This is a thread-safe type: you may safely use it from multiple threads at once.
+ * Once you are no longer going to use this client at all, you must free
+ * it with `arti_client_free()`.
+ *
+ * Every client has its own set of background threads.
+ */
+typedef struct ArtiClient ArtiClient;
+
+/**
+ * The function has returned successfully.
+ */
+#define ARTI_CLIENT_STATUS_SUCCESS 0
+
+/**
+ * One or more of the inputs to a library function was invalid.
+ */
+#define ARTI_CLIENT_STATUS_INVALID_INPUT 1
+
+/**
+ * Tried to use some functionality that isn't available on this platform or build.
+ */
+#define ARTI_CLIENT_STATUS_NOT_SUPPORTED 2
+
+/**
+ * The configuration was not valid, or the client could not be created from it.
+ */
+#define ARTI_CLIENT_STATUS_BAD_CONFIG 3
+
+/**
+ * The client could not bootstrap a connection to the Tor network.
+ */
+#define ARTI_CLIENT_STATUS_BOOTSTRAP_FAILED 4
+
+/**
+ * The client could not open a connection to the requested target.
+ */
+#define ARTI_CLIENT_STATUS_CONNECT_FAILED 5
+
+/**
+ * An internal error occurred.
+ *
+ * This is likely a bug in Arti.
+ */
+#define ARTI_CLIENT_STATUS_INTERNAL 6
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Return a string representing the meaning of a given `ArtiClientStatus`.
+ *
+ * The result will always be non-NULL, even if the status is unrecognized.
+ */
+const char *arti_client_status_to_str(ArtiClientStatus status);
+
+/**
+ * Return the status code associated with a given error.
+ *
+ * If `err` is NULL, return `ARTI_CLIENT_STATUS_INVALID_INPUT`.
+ */
+ArtiClientStatus arti_client_err_status(const ArtiClientError *err);
+
+/**
+ * Return a human-readable error message associated with a given error.
+ *
+ * The format of these messages may change arbitrarily between versions of this library;
+ * it is a mistake to depend on the actual contents of this message.
+ *
+ * Return NULL if the input `err` is NULL.
+ *
+ * # Correctness requirements
+ *
+ * The resulting string pointer is valid only for as long as the input `err` is not freed.
+ */
+const char *arti_client_err_message(const ArtiClientError *err);
+
+/**
+ * Release storage held by a provided error.
+ */
+void arti_client_err_free(ArtiClientError *err);
+
+/**
+ * Return a newly allocated configuration builder, with every option at its default.
+ *
+ * # Ownership
+ *
+ * The caller is responsible for making sure that the returned object
+ * is eventually freed with `arti_client_config_builder_free()`.
+ */
+ArtiClientConfigBuilder *arti_client_config_builder_new(void);
+
+/**
+ * Set the directory where the client stores its persistent state.
+ *
+ * `path` is used literally: no `~` or `${VARIABLE}` expansion is done.
+ */
+ArtiClientStatus arti_client_config_builder_set_state_dir(const ArtiClientConfigBuilder *builder,
+                                                          const char *path,
+                                                          ArtiClientError **error_out);
+
+/**
+ * Set the directory where the client caches directory information.
+ *
+ * `path` is used literally: no `~` or `${VARIABLE}` expansion is done.
+ */
+ArtiClientStatus arti_client_config_builder_set_cache_dir(const ArtiClientConfigBuilder *builder,
+                                                          const char *path,
+                                                          ArtiClientError **error_out);
+
+/**
+ * Set an arbitrary configuration option.
+ *
+ * `key` names the option, using the same dotted names as Arti's configuration file:
+ * for example, `"address_filter.allow_local_addrs"` or `"stream_timeouts.connect_timeout"`.
+ *
+ * `value` is the new value for the option, as JSON: for example, `"true"`, `"30"`,
+ * or `"[\"192.0.2.1:9001\"]"`.
+ * If `value` is not valid JSON, it is treated as a string:
+ * so `"30 sec"` and `"\"30 sec\""` mean the same thing.
+ *
+ * Return `ARTI_CLIENT_STATUS_INVALID_INPUT` if there is no option called `key`,
+ * and `ARTI_CLIENT_STATUS_BAD_CONFIG` if `value` is not acceptable for that option.
+ * (Some problems are only detected when the configuration is used in `arti_client_create()`.)
+ */
+ArtiClientStatus arti_client_config_builder_set(const ArtiClientConfigBuilder *builder,
+                                                const char *key,
+                                                const char *value,
+                                                ArtiClientError **error_out);
+
+/**
+ * Release storage held by a configuration builder.
+ */
+void arti_client_config_builder_free(ArtiClientConfigBuilder *builder);
+
+/**
+ * Create a new Arti client, using the configuration in `builder`.
+ *
+ * The client is not yet connected to the Tor network:
+ * use `arti_client_bootstrap()` to connect it explicitly,
+ * or let `arti_client_connect()` do so on demand.
+ *
+ * On success, return `ARTI_CLIENT_STATUS_SUCCESS` and set `*client_out` to a new ArtiClient.
+ * Otherwise return some other status code, set `*client_out` to NULL, and set
+ * `*error_out` (if provided) to a newly allocated error object.
+ *
+ * # Ownership
+ *
+ * The caller is responsible for making sure that `*client_out` and `*error_out`,
+ * if set, are eventually freed.
+ */
+ArtiClientStatus arti_client_create(const ArtiClientConfigBuilder *builder,
+                                    ArtiClient **client_out,
+                                    ArtiClientError **error_out);
+
+/**
+ * Connect `client` to the Tor network, and wait until it is ready to use.
+ *
+ * It is not necessary to call this function before `arti_client_connect()`,
+ * but doing so lets you find out about network problems early.
+ * Calling it on a client that is already bootstrapped does nothing.
+ */
+ArtiClientStatus arti_client_bootstrap(const ArtiClient *client,
+                                       ArtiClientError **error_out);
+
+/**
+ * Open an anonymized connection to `hostname`:`port` over the Tor network,
+ * and return it as a socket.
+ *
+ * `hostname` may be a DNS name or an IP address.
+ * (Passing a DNS name, rather than resolving it yourself, avoids leaking the lookup.)
+ *
+ * On success, return `ARTI_CLIENT_STATUS_SUCCESS` and set `*socket_out` to a new socket.
+ * Bytes written to the socket are sent to the target over Tor,
+ * and bytes that the target sends can be read from it.
+ * Otherwise return some other status code, set `*socket_out` to an invalid socket,
+ * and set `*error_out` (if provided) to a newly allocated error object.
+ *
+ * This function is not yet supported on Windows.
+ *
+ * # Ownership
+ *
+ * The caller is responsible for closing `*socket_out`, if set,
+ * and for freeing `*error_out`, if set.
+ */
+ArtiClientStatus arti_client_connect(const ArtiClient *client,
+                                     const char *hostname,
+                                     uint16_t port,
+                                     ArtiClientRawSocket *socket_out,
+                                     ArtiClientError **error_out);
+
+/**
+ * Release storage held by a client, and shut it down.
+ *
+ * Connections returned by `arti_client_connect()` may stop working once their client is freed.
+ */
+void arti_client_free(ArtiClient *client);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* ARTI_CLIENT_FFI_H_ */
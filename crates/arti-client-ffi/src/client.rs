@@ -0,0 +1,260 @@
+//! Creating, bootstrapping, and using a client.
+
+use std::ffi::c_char;
+
+use arti_client::TorClient;
+use tor_rtcompat::{BlockOn as _, PreferredRuntime};
+
+use crate::config::ArtiClientConfigBuilder;
+use crate::err::{
+    handle_errors, ArtiClientError, ArtiClientStatus, ARTI_CLIENT_STATUS_BAD_CONFIG,
+    ARTI_CLIENT_STATUS_BOOTSTRAP_FAILED,
+};
+use crate::util::{in_ptr, in_str, write_out_ptr};
+
+/// An embedded Arti client.
+///
+/// This is a thread-safe type: you may safely use it from multiple threads at once.
+/// Once you are no longer going to use this client at all, you must free
+/// it with `arti_client_free()`.
+///
+/// Every client has its own set of background threads.
+pub struct ArtiClient {
+    /// The runtime on which the client runs.
+    runtime: PreferredRuntime,
+    /// The client itself.
+    client: TorClient<PreferredRuntime>,
+}
+
+/// The type of a socket returned by `arti_client_connect()`.
+/// (This is always `int` on Unix-like platforms,
+/// and SOCKET on Windows.)
+#[repr(transparent)]
+pub struct ArtiClientRawSocket(
+    #[cfg(windows)] std::os::windows::raw::SOCKET,
+    #[cfg(not(windows))] std::ffi::c_int,
+);
+
+/// Create a new Arti client, using the configuration in `builder`.
+///
+/// The client is not yet connected to the Tor network:
+/// use `arti_client_bootstrap()` to connect it explicitly,
+/// or let `arti_client_connect()` do so on demand.
+///
+/// On success, return `ARTI_CLIENT_STATUS_SUCCESS` and set `*client_out` to a new ArtiClient.
+/// Otherwise return some other status code, set `*client_out` to NULL, and set
+/// `*error_out` (if provided) to a newly allocated error object.
+///
+/// Return `ARTI_CLIENT_STATUS_INVALID_INPUT` without creating a client if `client_out` is NULL.
+///
+/// # Ownership
+///
+/// The caller is responsible for making sure that `*client_out` and `*error_out`,
+/// if set, are eventually freed.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn arti_client_create(
+    builder: *const ArtiClientConfigBuilder,
+    client_out: *mut *mut ArtiClient,
+    error_out: *mut *mut ArtiClientError,
+) -> ArtiClientStatus {
+    // Safety: `client_out` is valid for writes, or NULL.
+    unsafe { write_out_ptr(client_out, std::ptr::null_mut()) };
+    let body = || {
+        // Otherwise we would build a client that nobody could ever free.
+        if client_out.is_null() {
+            return Err(ArtiClientError::invalid_input("client_out was NULL"));
+        }
+        // Safety: `builder` is valid, or NULL.
+        let builder = unsafe { in_ptr(builder)? };
+        let config = builder.build()?;
+        let runtime = PreferredRuntime::create().map_err(ArtiClientError::internal)?;
+        // We create the client from within the runtime,
+        // in case anything it launches needs a runtime context.
+        let client = runtime
+            .block_on(async {
+                TorClient::with_runtime(runtime.clone())
+                    .config(config)
+                    .create_unbootstrapped()
+            })
+            .map_err(|e| ArtiClientError::from_error(ARTI_CLIENT_STATUS_BAD_CONFIG, &e))?;
+        let client = Box::new(ArtiClient { runtime, client });
+        // Safety: `client_out` is valid for writes, or NULL.
+        unsafe { write_out_ptr(client_out, Box::into_raw(client)) };
+        Ok(())
+    };
+    // Safety: `error_out` is valid for writes, or NULL.
+    unsafe { handle_errors(error_out, body) }
+}
+
+/// Connect `client` to the Tor network, and wait until it is ready to use.
+///
+/// It is not necessary to call this function before `arti_client_connect()`,
+/// but doing so lets you find out about network problems early.
+/// Calling it on a client that is already bootstrapped does nothing.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn arti_client_bootstrap(
+    client: *const ArtiClient,
+    error_out: *mut *mut ArtiClientError,
+) -> ArtiClientStatus {
+    let body = || {
+        // Safety: `client` is valid, or NULL.
+        let client = unsafe { in_ptr(client)? };
+        client
+            .runtime
+            .block_on(client.client.bootstrap())
+            .map_err(|e| ArtiClientError::from_error(ARTI_CLIENT_STATUS_BOOTSTRAP_FAILED, &e))
+    };
+    // Safety: `error_out` is valid for writes, or NULL.
+    unsafe { handle_errors(error_out, body) }
+}
+
+/// Open an anonymized connection to `hostname`:`port` over the Tor network,
+/// and return it as a socket.
+///
+/// `hostname` may be a DNS name or an IP address.
+/// (Passing a DNS name, rather than resolving it yourself, avoids leaking the lookup.)
+///
+/// On success, return `ARTI_CLIENT_STATUS_SUCCESS` and set `*socket_out` to a new socket.
+/// Bytes written to the socket are sent to the target over Tor,
+/// and bytes that the target sends can be read from it.
+/// Otherwise return some other status code, set `*socket_out` to an invalid socket,
+/// and set `*error_out` (if provided) to a newly allocated error object.
+///
+/// This function is not yet supported on Windows.
+///
+/// # Ownership
+///
+/// The caller is responsible for closing `*socket_out`, if set,
+/// and for freeing `*error_out`, if set.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn arti_client_connect(
+    client: *const ArtiClient,
+    hostname: *const c_char,
+    port: u16,
+    socket_out: *mut ArtiClientRawSocket,
+    error_out: *mut *mut ArtiClientError,
+) -> ArtiClientStatus {
+    // Safety: `socket_out` is valid for writes, or NULL.
+    unsafe { write_out_ptr(socket_out, ArtiClientRawSocket::INVALID) };
+    let body = || {
+        // Safety: `client` and `hostname` are valid, or NULL.
+        let (client, hostname) = unsafe { (in_ptr(client)?, in_str(hostname)?) };
+        let socket = client.connect_bridged(hostname, port)?;
+        // Safety: `socket_out` is valid for writes, or NULL.
+        unsafe { write_out_ptr(socket_out, socket) };
+        Ok(())
+    };
+    // Safety: `error_out` is valid for writes, or NULL.
+    unsafe { handle_errors(error_out, body) }
+}
+
+/// Release storage held by a client, and shut it down.
+///
+/// Connections returned by `arti_client_connect()` may stop working once their client is freed.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn arti_client_free(client: *mut ArtiClient) {
+    // Safety: `client` was returned by this library, or is NULL.
+    drop(unsafe { crate::util::consume_ptr(client) });
+}
+
+impl ArtiClientRawSocket {
+    /// A value that is never a valid socket.
+    #[cfg(windows)]
+    const INVALID: Self = Self(!0);
+    /// A value that is never a valid socket.
+    #[cfg(not(windows))]
+    const INVALID: Self = Self(-1);
+}
+
+impl ArtiClient {
+    /// Open a stream to `hostname`:`port`, and return one end of a socketpair,
+    /// the other end of which we connect to the stream.
+    #[cfg(unix)]
+    fn connect_bridged(
+        &self,
+        hostname: &str,
+        port: u16,
+    ) -> Result<ArtiClientRawSocket, ArtiClientError> {
+        use crate::err::ARTI_CLIENT_STATUS_CONNECT_FAILED;
+        use futures::task::SpawnExt as _;
+        use std::os::fd::IntoRawFd as _;
+        use std::os::unix::net::UnixStream;
+
+        self.runtime.block_on(async {
+            let mut stream =
+                self.client.connect((hostname, port)).await.map_err(|e| {
+                    ArtiClientError::from_error(ARTI_CLIENT_STATUS_CONNECT_FAILED, &e)
+                })?;
+            let (theirs, ours) = UnixStream::pair().map_err(ArtiClientError::internal)?;
+            ours.set_nonblocking(true)
+                .map_err(ArtiClientError::internal)?;
+            let mut ours =
+                tokio_crate::net::UnixStream::from_std(ours).map_err(ArtiClientError::internal)?;
+            self.runtime
+                .spawn(async move {
+                    // When either side closes, we're done;
+                    // there's nobody to tell about errors.
+                    let _ = tokio_crate::io::copy_bidirectional(&mut ours, &mut stream).await;
+                })
+                .map_err(ArtiClientError::internal)?;
+            Ok(ArtiClientRawSocket(theirs.into_raw_fd()))
+        })
+    }
+
+    /// Open a stream to `hostname`:`port`, and return one end of a socketpair,
+    /// the other end of which we connect to the stream.
+    #[cfg(not(unix))]
+    fn connect_bridged(
+        &self,
+        _hostname: &str,
+        _port: u16,
+    ) -> Result<ArtiClientRawSocket, ArtiClientError> {
+        Err(ArtiClientError::new(
+            crate::err::ARTI_CLIENT_STATUS_NOT_SUPPORTED,
+            "arti_client_connect is not yet supported on this platform",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+
+    use super::*;
+    use crate::config::{arti_client_config_builder_free, arti_client_config_builder_new};
+    use crate::err::{
+        arti_client_err_free, arti_client_err_status, ARTI_CLIENT_STATUS_INVALID_INPUT,
+    };
+
+    #[test]
+    fn create_null_client_out() {
+        let builder = arti_client_config_builder_new();
+        let mut err: *mut ArtiClientError = std::ptr::null_mut();
+        let status = unsafe { arti_client_create(builder, std::ptr::null_mut(), &mut err) };
+        assert_eq!(status, ARTI_CLIENT_STATUS_INVALID_INPUT);
+        assert_eq!(
+            unsafe { arti_client_err_status(err) },
+            ARTI_CLIENT_STATUS_INVALID_INPUT
+        );
+        unsafe {
+            arti_client_err_free(err);
+            arti_client_config_builder_free(builder);
+        }
+    }
+}
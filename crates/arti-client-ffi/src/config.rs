@@ -0,0 +1,285 @@
+//! Building a client configuration.
+
+use std::ffi::c_char;
+use std::sync::{Mutex, MutexGuard};
+
+use arti_client::config::{CfgPath, TorClientConfigBuilder};
+use arti_client::TorClientConfig;
+
+use crate::err::{handle_errors, ArtiClientError, ArtiClientStatus, ARTI_CLIENT_STATUS_BAD_CONFIG};
+use crate::util::{in_ptr, in_str};
+
+/// A configuration for an Arti client, under construction.
+///
+/// Create one with `arti_client_config_builder_new()`,
+/// adjust it with the `arti_client_config_builder_set*()` functions,
+/// and use it to make a client with `arti_client_create()`.
+///
+/// This is a thread-safe type: you may safely use it from multiple threads at once.
+/// Once you are done with it, you must free it with `arti_client_config_builder_free()`.
+//
+// We keep the builder behind a Mutex (rather than taking it via `*mut`)
+// so that no function ever needs an exclusive reference to a caller-owned object.
+pub struct ArtiClientConfigBuilder(Mutex<TorClientConfigBuilder>);
+
+impl ArtiClientConfigBuilder {
+    /// Lock the underlying builder.
+    ///
+    /// A panic while holding this lock can't leave the builder inconsistent,
+    /// so we ignore poisoning.
+    fn lock(&self) -> MutexGuard<'_, TorClientConfigBuilder> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Try to build a configuration from this builder.
+    pub(crate) fn build(&self) -> Result<TorClientConfig, ArtiClientError> {
+        self.lock()
+            .build()
+            .map_err(|e| ArtiClientError::new(ARTI_CLIENT_STATUS_BAD_CONFIG, e))
+    }
+}
+
+/// Return a newly allocated configuration builder, with every option at its default.
+///
+/// # Ownership
+///
+/// The caller is responsible for making sure that the returned object
+/// is eventually freed with `arti_client_config_builder_free()`.
+#[no_mangle]
+pub extern "C" fn arti_client_config_builder_new() -> *mut ArtiClientConfigBuilder {
+    let builder = ArtiClientConfigBuilder(Mutex::new(TorClientConfigBuilder::default()));
+    Box::into_raw(Box::new(builder))
+}
+
+/// Set the directory where the client stores its persistent state.
+///
+/// `path` is used literally: no `~` or `${VARIABLE}` expansion is done.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn arti_client_config_builder_set_state_dir(
+    builder: *const ArtiClientConfigBuilder,
+    path: *const c_char,
+    error_out: *mut *mut ArtiClientError,
+) -> ArtiClientStatus {
+    let body = || {
+        // Safety: `builder` and `path` are valid, or NULL.
+        let (builder, path) = unsafe { (in_ptr(builder)?, in_str(path)?) };
+        builder
+            .lock()
+            .storage()
+            .state_dir(CfgPath::new_literal(path));
+        Ok(())
+    };
+    // Safety: `error_out` is valid for writes, or NULL.
+    unsafe { handle_errors(error_out, body) }
+}
+
+/// Set the directory where the client caches directory information.
+///
+/// `path` is used literally: no `~` or `${VARIABLE}` expansion is done.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn arti_client_config_builder_set_cache_dir(
+    builder: *const ArtiClientConfigBuilder,
+    path: *const c_char,
+    error_out: *mut *mut ArtiClientError,
+) -> ArtiClientStatus {
+    let body = || {
+        // Safety: `builder` and `path` are valid, or NULL.
+        let (builder, path) = unsafe { (in_ptr(builder)?, in_str(path)?) };
+        builder
+            .lock()
+            .storage()
+            .cache_dir(CfgPath::new_literal(path));
+        Ok(())
+    };
+    // Safety: `error_out` is valid for writes, or NULL.
+    unsafe { handle_errors(error_out, body) }
+}
+
+/// Set an arbitrary configuration option.
+///
+/// `key` names the option, using the same dotted names as Arti's configuration file:
+/// for example, `"address_filter.allow_local_addrs"` or `"stream_timeouts.connect_timeout"`.
+///
+/// `value` is the new value for the option, as JSON: for example, `"true"`, `"30"`,
+/// or `"[\"192.0.2.1:9001\"]"`.
+/// If `value` is not valid JSON, it is treated as a string:
+/// so `"30 sec"` and `"\"30 sec\""` mean the same thing.
+///
+/// Return `ARTI_CLIENT_STATUS_INVALID_INPUT` if there is no option called `key`,
+/// and `ARTI_CLIENT_STATUS_BAD_CONFIG` if `value` is not acceptable for that option.
+/// (Some problems are only detected when the configuration is used in `arti_client_create()`.)
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn arti_client_config_builder_set(
+    builder: *const ArtiClientConfigBuilder,
+    key: *const c_char,
+    value: *const c_char,
+    error_out: *mut *mut ArtiClientError,
+) -> ArtiClientStatus {
+    let body = || {
+        // Safety: `builder`, `key`, and `value` are valid, or NULL.
+        let (builder, key, value) = unsafe { (in_ptr(builder)?, in_str(key)?, in_str(value)?) };
+        let value = serde_json::from_str(value)
+            .unwrap_or_else(|_| serde_json::Value::String(value.to_owned()));
+        let mut builder = builder.lock();
+        *builder = set_option(&builder, key, value)?;
+        Ok(())
+    };
+    // Safety: `error_out` is valid for writes, or NULL.
+    unsafe { handle_errors(error_out, body) }
+}
+
+/// Release storage held by a configuration builder.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn arti_client_config_builder_free(builder: *mut ArtiClientConfigBuilder) {
+    // Safety: `builder` was returned by this library, or is NULL.
+    drop(unsafe { crate::util::consume_ptr(builder) });
+}
+
+/// Return a copy of `builder`, with the option called `key` set to `value`.
+///
+/// We do this by way of the builder's serialized form,
+/// so that every option that can appear in a configuration file can be set.
+fn set_option(
+    builder: &TorClientConfigBuilder,
+    key: &str,
+    value: serde_json::Value,
+) -> Result<TorClientConfigBuilder, ArtiClientError> {
+    let unknown = || ArtiClientError::invalid_input(format!("No such option {:?}", key));
+
+    let mut tree = serde_json::to_value(builder).map_err(ArtiClientError::internal)?;
+    let mut node = &mut tree;
+    for component in key.split('.') {
+        // Unset sections serialize as null; treat them as empty.
+        if node.is_null() {
+            *node = serde_json::Value::Object(Default::default());
+        }
+        // The serialized builder lists every option, whether or not it is set,
+        // so anything we can't find here is not a real option.
+        node = node
+            .as_object_mut()
+            .and_then(|section| section.get_mut(component))
+            .ok_or_else(unknown)?;
+    }
+    *node = value;
+    remove_nulls(&mut tree);
+
+    serde_json::from_value(tree).map_err(|e| {
+        ArtiClientError::new(
+            ARTI_CLIENT_STATUS_BAD_CONFIG,
+            format!("Invalid value for {:?}: {}", key, e),
+        )
+    })
+}
+
+/// Remove every null member from the objects in `tree`.
+///
+/// Builders serialize their unset options as null,
+/// but not every option will deserialize from null.
+/// Leaving the option out entirely means "unset" for all of them.
+fn remove_nulls(tree: &mut serde_json::Value) {
+    match tree {
+        serde_json::Value::Object(map) => {
+            map.retain(|_, v| !v.is_null());
+            map.values_mut().for_each(remove_nulls);
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(remove_nulls),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+
+    use super::*;
+    use crate::err::ARTI_CLIENT_STATUS_INVALID_INPUT;
+    use std::time::Duration;
+
+    #[test]
+    fn options() {
+        let builder = TorClientConfigBuilder::default();
+
+        let builder = set_option(
+            &builder,
+            "address_filter.allow_local_addrs",
+            serde_json::json!(true),
+        )
+        .unwrap();
+        let builder = set_option(
+            &builder,
+            "stream_timeouts.connect_timeout",
+            serde_json::json!("42 sec"),
+        )
+        .unwrap();
+        let config = builder.build().unwrap();
+        let mut expected = TorClientConfigBuilder::default();
+        expected.address_filter().allow_local_addrs(true);
+        expected
+            .stream_timeouts()
+            .connect_timeout(Duration::from_secs(42));
+        assert_eq!(config, expected.build().unwrap());
+
+        let e = set_option(
+            &builder,
+            "address_filter.no_such_option",
+            serde_json::json!(1),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(e.status(), ARTI_CLIENT_STATUS_INVALID_INPUT);
+        let e = set_option(&builder, "address_filter", serde_json::json!(1))
+            .err()
+            .unwrap();
+        assert_eq!(e.status(), ARTI_CLIENT_STATUS_BAD_CONFIG);
+    }
+
+    #[test]
+    fn directories() {
+        let builder = arti_client_config_builder_new();
+        let mut err = std::ptr::null_mut();
+        unsafe {
+            let status = arti_client_config_builder_set_state_dir(
+                builder,
+                c"/var/lib/example/state".as_ptr(),
+                &mut err,
+            );
+            assert_eq!(status, crate::ARTI_CLIENT_STATUS_SUCCESS);
+            let status = arti_client_config_builder_set_cache_dir(
+                builder,
+                c"/var/lib/example/cache".as_ptr(),
+                &mut err,
+            );
+            assert_eq!(status, crate::ARTI_CLIENT_STATUS_SUCCESS);
+            let status =
+                arti_client_config_builder_set_cache_dir(builder, std::ptr::null(), &mut err);
+            assert_eq!(status, ARTI_CLIENT_STATUS_INVALID_INPUT);
+            crate::arti_client_err_free(err);
+
+            let config = (*builder).build().unwrap();
+            let expected = TorClientConfigBuilder::from_directories(
+                "/var/lib/example/state",
+                "/var/lib/example/cache",
+            )
+            .build()
+            .unwrap();
+            assert_eq!(config, expected);
+            arti_client_config_builder_free(builder);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! Error handling logic for our ffi code.
+
+use std::ffi::{c_char, CStr, CString};
+use std::fmt::Display;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use tor_error::ErrorReport as _;
+
+/// A status code returned by an Arti client function.
+///
+/// On success, a function will return `ARTI_CLIENT_STATUS_SUCCESS (0)`.
+/// On failure, a function will return some other status code.
+pub type ArtiClientStatus = u32;
+
+/// The function has returned successfully.
+pub const ARTI_CLIENT_STATUS_SUCCESS: ArtiClientStatus = 0;
+
+/// One or more of the inputs to a library function was invalid.
+pub const ARTI_CLIENT_STATUS_INVALID_INPUT: ArtiClientStatus = 1;
+
+/// Tried to use some functionality that isn't available on this platform or build.
+pub const ARTI_CLIENT_STATUS_NOT_SUPPORTED: ArtiClientStatus = 2;
+
+/// The configuration was not valid, or the client could not be created from it.
+pub const ARTI_CLIENT_STATUS_BAD_CONFIG: ArtiClientStatus = 3;
+
+/// The client could not bootstrap a connection to the Tor network.
+pub const ARTI_CLIENT_STATUS_BOOTSTRAP_FAILED: ArtiClientStatus = 4;
+
+/// The client could not open a connection to the requested target.
+pub const ARTI_CLIENT_STATUS_CONNECT_FAILED: ArtiClientStatus = 5;
+
+/// An internal error occurred.
+///
+/// This is likely a bug in Arti.
+pub const ARTI_CLIENT_STATUS_INTERNAL: ArtiClientStatus = 6;
+
+/// Return a string representing the meaning of a given `ArtiClientStatus`.
+///
+/// The result will always be non-NULL, even if the status is unrecognized.
+#[no_mangle]
+pub extern "C" fn arti_client_status_to_str(status: ArtiClientStatus) -> *const c_char {
+    match status {
+        ARTI_CLIENT_STATUS_SUCCESS => c"Success",
+        ARTI_CLIENT_STATUS_INVALID_INPUT => c"Invalid input",
+        ARTI_CLIENT_STATUS_NOT_SUPPORTED => c"Not supported",
+        ARTI_CLIENT_STATUS_BAD_CONFIG => c"Invalid configuration",
+        ARTI_CLIENT_STATUS_BOOTSTRAP_FAILED => c"Unable to bootstrap",
+        ARTI_CLIENT_STATUS_CONNECT_FAILED => c"Unable to connect",
+        ARTI_CLIENT_STATUS_INTERNAL => c"Internal error",
+        _ => c"(unrecognized status)",
+    }
+    .as_ptr()
+}
+
+/// An error returned by an Arti client function, exposed as an object.
+///
+/// When a function returns an [`ArtiClientStatus`] other than [`ARTI_CLIENT_STATUS_SUCCESS`],
+/// it will also expose a newly allocated value of this type
+/// via its `error_out` parameter.
+#[derive(Clone, Debug)]
+pub struct ArtiClientError {
+    /// The status code for this error.
+    status: ArtiClientStatus,
+    /// A human-readable message describing this error.
+    message: CString,
+}
+
+impl ArtiClientError {
+    /// Construct a new error with a given status and message.
+    pub(crate) fn new(status: ArtiClientStatus, message: impl Display) -> Self {
+        let message = message.to_string().replace('\0', "\\0");
+        let message = CString::new(message).expect("NUL remained after replacing NUL");
+        ArtiClientError { status, message }
+    }
+
+    /// Construct a new error with a given status, describing `err`.
+    pub(crate) fn from_error(status: ArtiClientStatus, err: &arti_client::Error) -> Self {
+        Self::new(status, err.report())
+    }
+
+    /// Construct a new error reporting invalid input.
+    pub(crate) fn invalid_input(message: impl Display) -> Self {
+        Self::new(ARTI_CLIENT_STATUS_INVALID_INPUT, message)
+    }
+
+    /// Construct a new error reporting an internal problem.
+    pub(crate) fn internal(message: impl Display) -> Self {
+        Self::new(ARTI_CLIENT_STATUS_INTERNAL, message)
+    }
+
+    /// Return the status code for this error.
+    pub(crate) fn status(&self) -> ArtiClientStatus {
+        self.status
+    }
+}
+
+/// Run `body`, catching panics, and report its outcome.
+///
+/// Return the status of `body`.
+/// A panic is reported as an internal error.
+/// If `error_out` is non-NULL, set `*error_out` to a newly allocated error on failure,
+/// and to NULL on success.
+///
+/// # Safety
+///
+/// If `error_out` is not NULL, it must be valid for writes of a `*mut ArtiClientError`.
+pub(crate) unsafe fn handle_errors<F>(
+    error_out: *mut *mut ArtiClientError,
+    body: F,
+) -> ArtiClientStatus
+where
+    F: FnOnce() -> Result<(), ArtiClientError>,
+{
+    // Safety: `error_out` is valid for writes, or NULL.
+    unsafe { crate::util::write_out_ptr(error_out, std::ptr::null_mut()) };
+
+    // The objects that `body` can reach are either behind locks that ignore poisoning,
+    // or (like `TorClient`) are kept consistent by Arti across panics in its own code.
+    let outcome = catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|_| {
+        Err(ArtiClientError::internal(
+            "Internal panic in arti-client-ffi",
+        ))
+    });
+    match outcome {
+        Ok(()) => ARTI_CLIENT_STATUS_SUCCESS,
+        Err(e) => {
+            let status = e.status();
+            // Safety: `error_out` is valid for writes, or NULL.
+            unsafe { crate::util::write_out_ptr(error_out, Box::into_raw(Box::new(e))) };
+            status
+        }
+    }
+}
+
+/// Return the status code associated with a given error.
+///
+/// If `err` is NULL, return [`ARTI_CLIENT_STATUS_INVALID_INPUT`].
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn arti_client_err_status(err: *const ArtiClientError) -> ArtiClientStatus {
+    // Safety: `err` is valid, or NULL.
+    match unsafe { err.as_ref() } {
+        Some(err) => err.status,
+        None => ARTI_CLIENT_STATUS_INVALID_INPUT,
+    }
+}
+
+/// Return a human-readable error message associated with a given error.
+///
+/// The format of these messages may change arbitrarily between versions of this library;
+/// it is a mistake to depend on the actual contents of this message.
+///
+/// Return NULL if the input `err` is NULL.
+///
+/// # Correctness requirements
+///
+/// The resulting string pointer is valid only for as long as the input `err` is not freed.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn arti_client_err_message(err: *const ArtiClientError) -> *const c_char {
+    // Safety: `err` is valid, or NULL.
+    match unsafe { err.as_ref() } {
+        Some(err) => CStr::as_ptr(&err.message),
+        None => std::ptr::null(),
+    }
+}
+
+/// Release storage held by a provided error.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn arti_client_err_free(err: *mut ArtiClientError) {
+    // Safety: `err` was returned by this library, or is NULL.
+    drop(unsafe { crate::util::consume_ptr(err) });
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+
+    use super::*;
+
+    #[test]
+    fn errors() {
+        let mut err: *mut ArtiClientError = std::ptr::null_mut();
+        let status = unsafe { handle_errors(&mut err, || Ok(())) };
+        assert_eq!(status, ARTI_CLIENT_STATUS_SUCCESS);
+        assert!(err.is_null());
+
+        let status = unsafe {
+            handle_errors(&mut err, || {
+                Err(ArtiClientError::invalid_input("No\0 thanks"))
+            })
+        };
+        assert_eq!(status, ARTI_CLIENT_STATUS_INVALID_INPUT);
+        assert!(!err.is_null());
+        unsafe {
+            assert_eq!(arti_client_err_status(err), status);
+            let msg = CStr::from_ptr(arti_client_err_message(err));
+            assert_eq!(msg.to_str().unwrap(), "No\\0 thanks");
+            arti_client_err_free(err);
+        }
+
+        let status = unsafe { handle_errors(std::ptr::null_mut(), || panic!("oops")) };
+        assert_eq!(status, ARTI_CLIENT_STATUS_INTERNAL);
+
+        let s = unsafe { CStr::from_ptr(arti_client_status_to_str(status)) };
+        assert_eq!(s.to_str().unwrap(), "Internal error");
+    }
+}
@@ -0,0 +1,72 @@
+#![doc = include_str!("../README.md")]
+// @@ begin lint list maintained by maint/add_warning @@
+#![allow(renamed_and_removed_lints)] // @@REMOVE_WHEN(ci_arti_stable)
+#![allow(unknown_lints)] // @@REMOVE_WHEN(ci_arti_nightly)
+#![warn(missing_docs)]
+#![warn(noop_method_call)]
+#![warn(unreachable_pub)]
+#![warn(clippy::all)]
+#![deny(clippy::await_holding_lock)]
+#![deny(clippy::cargo_common_metadata)]
+#![deny(clippy::cast_lossless)]
+#![deny(clippy::checked_conversions)]
+#![warn(clippy::cognitive_complexity)]
+#![deny(clippy::debug_assert_with_mut_call)]
+#![deny(clippy::exhaustive_enums)]
+#![deny(clippy::exhaustive_structs)]
+#![deny(clippy::expl_impl_clone_on_copy)]
+#![deny(clippy::fallible_impl_from)]
+#![deny(clippy::implicit_clone)]
+#![deny(clippy::large_stack_arrays)]
+#![warn(clippy::manual_ok_or)]
+#![deny(clippy::missing_docs_in_private_items)]
+#![warn(clippy::needless_borrow)]
+#![warn(clippy::needless_pass_by_value)]
+#![warn(clippy::option_option)]
+#![deny(clippy::print_stderr)]
+#![deny(clippy::print_stdout)]
+#![warn(clippy::rc_buffer)]
+#![deny(clippy::ref_option_ref)]
+#![warn(clippy::semicolon_if_nothing_returned)]
+#![warn(clippy::trait_duplication_in_bounds)]
+#![deny(clippy::unchecked_duration_subtraction)]
+#![deny(clippy::unnecessary_wraps)]
+#![warn(clippy::unseparated_literal_suffix)]
+#![deny(clippy::unwrap_used)]
+#![allow(clippy::let_unit_value)] // This can reasonably be done for explicitness
+#![allow(clippy::uninlined_format_args)]
+#![allow(clippy::significant_drop_in_scrutinee)] // arti/-/merge_requests/588/#note_2812945
+#![allow(clippy::result_large_err)] // temporary workaround for arti#587
+#![allow(clippy::needless_raw_string_hashes)] // complained-about code is fine, often best
+//! <!-- @@ end lint list maintained by maint/add_warning @@ -->
+
+// TODO: Possibly add this to our big list of lints.
+#![deny(unsafe_op_in_unsafe_fn)]
+
+//! # Exposed C APIs
+//!
+//! See the top-level documentation in `arti-client-ffi.h` for C conventions
+//! that affect the safety of these functions.
+//! (These include things like "all input pointers must be valid" and so on.)
+
+mod client;
+mod config;
+mod err;
+mod util;
+
+pub use client::{
+    arti_client_bootstrap, arti_client_connect, arti_client_create, arti_client_free, ArtiClient,
+    ArtiClientRawSocket,
+};
+pub use config::{
+    arti_client_config_builder_free, arti_client_config_builder_new,
+    arti_client_config_builder_set, arti_client_config_builder_set_cache_dir,
+    arti_client_config_builder_set_state_dir, ArtiClientConfigBuilder,
+};
+pub use err::{
+    arti_client_err_free, arti_client_err_message, arti_client_err_status,
+    arti_client_status_to_str, ArtiClientError, ArtiClientStatus, ARTI_CLIENT_STATUS_BAD_CONFIG,
+    ARTI_CLIENT_STATUS_BOOTSTRAP_FAILED, ARTI_CLIENT_STATUS_CONNECT_FAILED,
+    ARTI_CLIENT_STATUS_INTERNAL, ARTI_CLIENT_STATUS_INVALID_INPUT,
+    ARTI_CLIENT_STATUS_NOT_SUPPORTED, ARTI_CLIENT_STATUS_SUCCESS,
+};
@@ -0,0 +1,67 @@
+//! Helpers for working with FFI.
+
+use std::ffi::{c_char, CStr};
+
+use crate::err::ArtiClientError;
+
+/// Convert a possibly NULL `*const T` into a reference.
+///
+/// Return an error if `ptr` is NULL.
+///
+/// # Safety
+///
+/// See [`<*const T>::as_ref`](https://doc.rust-lang.org/std/primitive.pointer.html#method.as_ref).
+/// Informally: if `ptr` is not NULL, it must point to a valid, aligned instance of `T`,
+/// which must not be freed or modified while the reference is in use.
+pub(crate) unsafe fn in_ptr<'a, T>(ptr: *const T) -> Result<&'a T, ArtiClientError> {
+    // Safety: See function requirements.
+    unsafe { ptr.as_ref() }.ok_or_else(|| ArtiClientError::invalid_input("Argument was NULL"))
+}
+
+/// Convert a possibly NULL `*const c_char` into a `&str`.
+///
+/// Return an error if `ptr` is NULL, or if the string is not UTF-8.
+///
+/// # Safety
+///
+/// See [`CStr::from_ptr`].
+/// Informally: if `ptr` is not NULL, it must point to a nul-terminated string,
+/// which must not be freed or modified while the reference is in use.
+pub(crate) unsafe fn in_str<'a>(ptr: *const c_char) -> Result<&'a str, ArtiClientError> {
+    if ptr.is_null() {
+        return Err(ArtiClientError::invalid_input("String argument was NULL"));
+    }
+    // Safety: See function requirements.
+    let s = unsafe { CStr::from_ptr(ptr) };
+    s.to_str()
+        .map_err(|_| ArtiClientError::invalid_input("String argument was not UTF-8"))
+}
+
+/// If `out` is not NULL, set `*out` to `value`.
+///
+/// # Safety
+///
+/// If `out` is not NULL, it must be valid for writes of a `T`,
+/// and must not alias any other pointer in use.
+/// (Any previous value of `*out` is overwritten without being dropped.)
+pub(crate) unsafe fn write_out_ptr<T>(out: *mut T, value: T) {
+    if !out.is_null() {
+        // Safety: See function requirements.
+        unsafe { out.write(value) };
+    }
+}
+
+/// Take ownership of a possibly NULL `*mut T`.
+///
+/// # Safety
+///
+/// If `ptr` is not NULL, it must have been returned by `Box::<T>::into_raw`,
+/// and must not be used again.
+pub(crate) unsafe fn consume_ptr<T>(ptr: *mut T) -> Option<Box<T>> {
+    if ptr.is_null() {
+        None
+    } else {
+        // Safety: See function requirements.
+        Some(unsafe { Box::from_raw(ptr) })
+    }
+}
@@ -49,6 +49,10 @@ pub(crate) mod hs_dummy {
 #[cfg(not(feature = "onion-service-client"))]
 use hs_dummy::*;
 
+mod uri;
+
+pub use uri::{ConnectUri, ConnectUriError};
+
 // ----------------------------------------------------------------------
 
 /// An object that can be converted to a [`TorAddr`] with a minimum of risk.
@@ -168,7 +172,7 @@ pub struct TorAddr {
 /// This is a separate type, returned from `address.rs` to `client.rs`,
 /// so that we can test our "how to make a connection" logic and policy,
 /// in isolation, without a whole Tor client.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) enum StreamInstructions {
     /// Create an exit circuit suitable for port, and then make a stream to `hostname`
     Exit {
@@ -190,6 +194,22 @@ pub(crate) enum StreamInstructions {
     },
 }
 
+impl StreamInstructions {
+    /// Return true if a stream following `self` can use the same circuit
+    /// as a stream following `other`.
+    ///
+    /// This doesn't consider the exit policies of the relays:
+    /// all exit streams are considered compatible.
+    pub(crate) fn same_circuit_target(&self, other: &StreamInstructions) -> bool {
+        use StreamInstructions as SI;
+        match (self, other) {
+            (SI::Exit { .. }, SI::Exit { .. }) => true,
+            (SI::Hs { hsid: a, .. }, SI::Hs { hsid: b, .. }) => a == b,
+            (SI::Exit { .. }, SI::Hs { .. }) | (SI::Hs { .. }, SI::Exit { .. }) => false,
+        }
+    }
+}
+
 /// How to resolve this Tor host address into IP address(es)
 #[derive(PartialEq, Eq, Debug)]
 pub(crate) enum ResolveInstructions {
@@ -230,6 +250,12 @@ impl TorAddr {
         matches!(&self.host, Host::Ip(_))
     }
 
+    /// Return true if this is the address of an onion service.
+    #[cfg(all(feature = "onion-service-client", feature = "experimental-api"))]
+    pub(crate) fn is_onion(&self) -> bool {
+        matches!(&self.host, Host::Onion(_))
+    }
+
     /// Get instructions for how to make a stream to this address
     pub(crate) fn into_stream_instructions(
         self,
@@ -241,6 +267,9 @@ impl TorAddr {
         let port = self.port;
         Ok(match self.host {
             Host::Hostname(hostname) => StreamInstructions::Exit { hostname, port },
+            Host::Ip(ip) if !prefs.allows_ip_version(&ip) => {
+                return Err(ErrorDetail::IpVersionForbidden);
+            }
             Host::Ip(ip) => StreamInstructions::Exit {
                 hostname: ip.to_string(),
                 port,
@@ -303,6 +332,10 @@ impl TorAddr {
         #[allow(unused_variables)] // will only be used in certain configurations
         prefs: &StreamPrefs,
     ) -> Result<(), ErrorDetail> {
+        if cfg.onion_only && !matches!(self.host, Host::Onion(_)) {
+            return Err(ErrorDetail::ClearnetAddressForbidden);
+        }
+
         if !cfg.allow_local_addrs && self.is_local() {
             return Err(ErrorDetail::LocalAddress);
         }
@@ -725,6 +758,30 @@ mod test {
         }
     }
 
+    #[test]
+    fn same_circuit_target() {
+        fn sap(s: &str) -> StreamInstructions {
+            TorAddr::from(s)
+                .unwrap()
+                .into_stream_instructions(&Default::default(), &mk_stream_prefs())
+                .unwrap()
+        }
+
+        let exit = sap("example.com:80");
+        assert!(exit.same_circuit_target(&sap("[2001:db8::42]:22")));
+
+        #[cfg(feature = "onion-service-client")]
+        {
+            let b32 = "eweiibe6tdjsdprb4px6rqrzzcsi22m4koia44kc5pcjr7nec2rlxyad";
+            let other = "duckduckgogg42xjoc72x3sjasowoarfbgcmvfimaftt6twagswzczad";
+            let hs = sap(&format!("www.{b32}.onion:443"));
+            assert!(hs.same_circuit_target(&sap(&format!("{b32}.onion:80"))));
+            assert!(!hs.same_circuit_target(&sap(&format!("{other}.onion:443"))));
+            assert!(!hs.same_circuit_target(&exit));
+            assert!(!exit.same_circuit_target(&hs));
+        }
+    }
+
     #[test]
     fn resolve_instructions() {
         use ResolveInstructions as RI;
@@ -761,6 +818,34 @@ mod test {
         );
     }
 
+    #[test]
+    fn prefs_ip_version() {
+        use crate::err::ErrorDetailDiscriminants as EDD;
+        use crate::IpVersionPreference as IVP;
+
+        let check = |addr: &str, pref, ok: bool| {
+            let addr: TorAddr = addr.parse().unwrap();
+            let mut prefs = StreamPrefs::default();
+            prefs.ip_version(pref);
+            let got = addr.into_stream_instructions(&Default::default(), &prefs);
+            if ok {
+                assert!(got.is_ok(), "{prefs:?}");
+            } else {
+                let e = got.unwrap_err();
+                assert_eq!(EDD::from(&e), EDD::IpVersionForbidden);
+            }
+        };
+
+        check("192.0.2.1:443", IVP::Ipv4Only, true);
+        check("192.0.2.1:443", IVP::Ipv6Preferred, true);
+        check("192.0.2.1:443", IVP::Ipv6Only, false);
+        check("[2001:db8::1]:443", IVP::Ipv6Only, true);
+        check("[2001:db8::1]:443", IVP::Ipv4Preferred, true);
+        check("[2001:db8::1]:443", IVP::Ipv4Only, false);
+        check("www.example.com:443", IVP::Ipv4Only, true);
+        check("www.example.com:443", IVP::Ipv6Only, true);
+    }
+
     #[test]
     fn prefs_onion_services() {
         use crate::err::ErrorDetailDiscriminants;
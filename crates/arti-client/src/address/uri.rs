@@ -0,0 +1,342 @@
+//! Parsing of `tor+tcp://` and `tor+onion://` URIs.
+//!
+//! See [`ConnectUri`] for more details.
+
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use super::{Host, TorAddr, TorAddrError};
+use crate::StreamPrefs;
+
+/// A URI describing a stream to open over the Tor network.
+///
+/// Two schemes are supported:
+///
+///  * `tor+tcp://HOST:PORT`, for a stream to `HOST:PORT` through an exit relay.
+///    `HOST` is a hostname, an IPv4 address, or an IPv6 address in brackets
+///    (for example, `tor+tcp://[2001:db8::1]:22`).
+///  * `tor+onion://NAME.onion:PORT`, for a stream to an onion service.
+///
+/// The port is mandatory.
+/// The URI may end with a `/`, but can't otherwise have a path,
+/// and it can't have any user information or fragment.
+///
+/// The query string can contain the following options, separated by `&`:
+///
+///  * `ip=ipv4-only`, `ip=ipv6-only`, `ip=ipv4-preferred`, or `ip=ipv6-preferred`:
+///    which kind of address the exit relay should connect to
+///    (see [`StreamPrefs::ipv4_only`] and the related methods).
+///  * `exit_country=CC`: a two-letter country code the exit relay must be in
+///    (see `StreamPrefs::exit_country`).
+///    This requires the `geoip` feature.
+///  * `optimistic=true` or `optimistic=false`:
+///    whether to open the stream optimistically
+///    (see [`StreamPrefs::optimistic`]).
+///
+/// Exit options can't be used with `tor+onion` URIs.
+///
+/// ```
+/// use arti_client::ConnectUri;
+///
+/// let uri: ConnectUri = "tor+tcp://example.com:22?ip=ipv6-preferred".parse()?;
+/// assert_eq!(uri.addr().to_string(), "example.com:22");
+/// # Ok::<(), arti_client::ConnectUriError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConnectUri {
+    /// The address to connect to.
+    addr: TorAddr,
+    /// The preferences to use for the stream.
+    prefs: StreamPrefs,
+}
+
+/// The scheme of a `tor+tcp://` URI.
+const TCP_SCHEME: &str = "tor+tcp";
+
+/// The scheme of a `tor+onion://` URI.
+const ONION_SCHEME: &str = "tor+onion";
+
+impl ConnectUri {
+    /// Return the address this URI points to.
+    pub fn addr(&self) -> &TorAddr {
+        &self.addr
+    }
+
+    /// Return the stream preferences specified in this URI.
+    ///
+    /// Preferences that weren't specified have their default value.
+    pub fn prefs(&self) -> &StreamPrefs {
+        &self.prefs
+    }
+
+    /// Split this URI into its address and stream preferences.
+    pub fn into_parts(self) -> (TorAddr, StreamPrefs) {
+        (self.addr, self.prefs)
+    }
+}
+
+impl FromStr for ConnectUri {
+    type Err = ConnectUriError;
+
+    fn from_str(s: &str) -> Result<Self, ConnectUriError> {
+        let (scheme, rest) = s
+            .split_once("://")
+            .ok_or_else(|| ConnectUriError::BadScheme(s.to_owned()))?;
+        let onion = if scheme.eq_ignore_ascii_case(TCP_SCHEME) {
+            false
+        } else if scheme.eq_ignore_ascii_case(ONION_SCHEME) {
+            true
+        } else {
+            return Err(ConnectUriError::BadScheme(scheme.to_owned()));
+        };
+
+        if rest.contains('#') {
+            return Err(ConnectUriError::UnexpectedComponent("fragment"));
+        }
+        let (rest, query) = match rest.split_once('?') {
+            Some((rest, query)) => (rest, Some(query)),
+            None => (rest, None),
+        };
+        let authority = match rest.split_once('/') {
+            Some((authority, "")) => authority,
+            Some(_) => return Err(ConnectUriError::UnexpectedComponent("path")),
+            None => rest,
+        };
+        if authority.contains('@') {
+            return Err(ConnectUriError::UnexpectedComponent("user information"));
+        }
+
+        let addr = parse_authority(authority)?;
+        if matches!(addr.host, Host::Onion(_)) != onion {
+            return Err(ConnectUriError::SchemeMismatch);
+        }
+
+        let mut prefs = StreamPrefs::new();
+        for option in query.into_iter().flat_map(|q| q.split('&')) {
+            if option.is_empty() {
+                continue;
+            }
+            let (key, value) = option.split_once('=').unwrap_or((option, ""));
+            let bad_value = || ConnectUriError::BadOptionValue {
+                option: key.to_owned(),
+                value: value.to_owned(),
+            };
+            match key {
+                "ip" | "exit_country" if onion => {
+                    return Err(ConnectUriError::ExitOptionForOnion(key.to_owned()));
+                }
+                "ip" => match value {
+                    "ipv4-only" => prefs.ipv4_only(),
+                    "ipv6-only" => prefs.ipv6_only(),
+                    "ipv4-preferred" => prefs.ipv4_preferred(),
+                    "ipv6-preferred" => prefs.ipv6_preferred(),
+                    _ => return Err(bad_value()),
+                },
+                "exit_country" => {
+                    cfg_if::cfg_if! {
+                        if #[cfg(feature = "geoip")] {
+                            let country = value.parse().map_err(|_| bad_value())?;
+                            prefs.exit_country(country)
+                        } else {
+                            return Err(ConnectUriError::UnsupportedOption(key.to_owned()));
+                        }
+                    }
+                }
+                "optimistic" => match value {
+                    "true" => prefs.optimistic(),
+                    // There's no way to turn off optimistic streams, but they are off by default.
+                    "false" => &mut prefs,
+                    _ => return Err(bad_value()),
+                },
+                _ => return Err(ConnectUriError::UnknownOption(key.to_owned())),
+            };
+        }
+
+        Ok(ConnectUri { addr, prefs })
+    }
+}
+
+/// Parse the `HOST:PORT` authority of a URI.
+///
+/// Unlike the [`IntoTorAddr`](crate::IntoTorAddr) implementation for `&str`,
+/// this requires IPv6 addresses to be in brackets.
+fn parse_authority(authority: &str) -> Result<TorAddr, ConnectUriError> {
+    let (host, port) = if let Some(rest) = authority.strip_prefix('[') {
+        let (ip, port) = rest
+            .split_once("]:")
+            .ok_or(ConnectUriError::BadAddress(TorAddrError::NoPort))?;
+        let ip: IpAddr = ip
+            .parse()
+            .map_err(|_| ConnectUriError::BadAddress(TorAddrError::InvalidHostname))?;
+        if !ip.is_ipv6() {
+            return Err(ConnectUriError::BadAddress(TorAddrError::InvalidHostname));
+        }
+        (Host::Ip(ip), port)
+    } else {
+        let (host, port) = authority.rsplit_once(':').ok_or(TorAddrError::NoPort)?;
+        if host.contains(':') {
+            // An IPv6 address that isn't in brackets.
+            return Err(ConnectUriError::BadAddress(TorAddrError::InvalidHostname));
+        }
+        (host.parse()?, port)
+    };
+
+    let port = port.parse().map_err(|_| TorAddrError::BadPort)?;
+    Ok(TorAddr::new(host, port)?)
+}
+
+/// An error while parsing a [`ConnectUri`].
+#[derive(Debug, Error, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ConnectUriError {
+    /// The URI doesn't have a supported scheme.
+    #[error("Unsupported URI scheme {0:?} (expected tor+tcp or tor+onion)")]
+    BadScheme(String),
+    /// The host or port of the URI is invalid.
+    #[error("Invalid address in URI")]
+    BadAddress(#[from] TorAddrError),
+    /// A `tor+tcp` URI has an onion address, or a `tor+onion` URI doesn't.
+    #[error("Onion addresses must be used with tor+onion, and other addresses with tor+tcp")]
+    SchemeMismatch,
+    /// The URI has a component we don't support.
+    #[error("URI has an unexpected {0}")]
+    UnexpectedComponent(&'static str),
+    /// The query string contains an option we don't know.
+    #[error("Unknown URI option {0:?}")]
+    UnknownOption(String),
+    /// The query string contains an option that isn't supported by this build.
+    #[error("URI option {0:?} is not supported by this build of Arti")]
+    UnsupportedOption(String),
+    /// The query string contains an exit option, but the URI is a `tor+onion` URI.
+    #[error("URI option {0:?} can't be used with onion services")]
+    ExitOptionForOnion(String),
+    /// The query string contains an option with an invalid value.
+    #[error("Invalid value {value:?} for URI option {option:?}")]
+    BadOptionValue {
+        /// The option.
+        option: String,
+        /// The invalid value.
+        value: String,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+
+    const ONION: &str = "duckduckgogg42xjoc72x3sjasowoarfbgcmvfimaftt6twagswzczad.onion";
+
+    fn parse(s: &str) -> Result<ConnectUri, ConnectUriError> {
+        s.parse()
+    }
+
+    #[test]
+    fn addresses() {
+        let chk = |s: &str, expected: &str| {
+            assert_eq!(parse(s).unwrap().addr().to_string(), expected, "{s}");
+        };
+        chk("tor+tcp://example.com:22", "example.com:22");
+        chk("TOR+TCP://example.com:22/", "example.com:22");
+        chk("tor+tcp://192.0.2.1:80", "192.0.2.1:80");
+        chk("tor+tcp://[2001:db8::1]:443", "[2001:db8::1]:443");
+        chk(&format!("tor+onion://{ONION}:80"), &format!("{ONION}:80"));
+        chk(
+            &format!("tor+onion://www.{ONION}:80"),
+            &format!("www.{ONION}:80"),
+        );
+    }
+
+    #[test]
+    fn bad_addresses() {
+        use ConnectUriError as E;
+        use TorAddrError as TE;
+
+        let chk = |s: &str, expected: ConnectUriError| {
+            assert_eq!(parse(s).unwrap_err(), expected, "{s}");
+        };
+        chk("example.com:22", E::BadScheme("example.com:22".into()));
+        chk("https://example.com:22", E::BadScheme("https".into()));
+        chk("tor+tcp://example.com", E::BadAddress(TE::NoPort));
+        chk("tor+tcp://example.com:0", E::BadAddress(TE::BadPort));
+        chk("tor+tcp://example.com:ssh", E::BadAddress(TE::BadPort));
+        chk(
+            "tor+tcp://2001:db8::1:22",
+            E::BadAddress(TE::InvalidHostname),
+        );
+        chk(
+            "tor+tcp://[192.0.2.1]:22",
+            E::BadAddress(TE::InvalidHostname),
+        );
+        chk("tor+tcp://[2001:db8::1]", E::BadAddress(TE::NoPort));
+        chk(
+            "tor+tcp://user@example.com:22",
+            E::UnexpectedComponent("user information"),
+        );
+        chk(
+            "tor+tcp://example.com:22/index.html",
+            E::UnexpectedComponent("path"),
+        );
+        chk(
+            "tor+tcp://example.com:22#top",
+            E::UnexpectedComponent("fragment"),
+        );
+        chk(&format!("tor+tcp://{ONION}:80"), E::SchemeMismatch);
+        chk("tor+onion://example.com:80", E::SchemeMismatch);
+    }
+
+    #[test]
+    fn options() {
+        use ConnectUriError as E;
+
+        let uri = parse("tor+tcp://example.com:22?ip=ipv6-only&optimistic=true").unwrap();
+        assert!(uri.prefs().is_optimistic());
+        assert!(!parse("tor+tcp://example.com:22?optimistic=false")
+            .unwrap()
+            .prefs()
+            .is_optimistic());
+        assert!(parse("tor+tcp://example.com:22/?").is_ok());
+
+        let chk = |s: &str, expected: ConnectUriError| {
+            assert_eq!(parse(s).unwrap_err(), expected, "{s}");
+        };
+        chk(
+            "tor+tcp://example.com:22?ip=ipv5",
+            E::BadOptionValue {
+                option: "ip".into(),
+                value: "ipv5".into(),
+            },
+        );
+        chk(
+            "tor+tcp://example.com:22?socks=yes",
+            E::UnknownOption("socks".into()),
+        );
+        chk(
+            &format!("tor+onion://{ONION}:80?ip=ipv4-only"),
+            E::ExitOptionForOnion("ip".into()),
+        );
+
+        let exit_country = parse("tor+tcp://example.com:22?exit_country=de");
+        #[cfg(feature = "geoip")]
+        assert!(exit_country.is_ok());
+        #[cfg(not(feature = "geoip"))]
+        assert_eq!(
+            exit_country.unwrap_err(),
+            E::UnsupportedOption("exit_country".into())
+        );
+    }
+}
@@ -3,7 +3,8 @@
 #![allow(missing_docs, clippy::missing_docs_in_private_items)]
 
 use crate::{
-    err::ErrorDetail, BootstrapBehavior, InertTorClient, Result, TorClient, TorClientConfig,
+    client::KeystoreUnlock, err::ErrorDetail, BootstrapBehavior, InertTorClient, Result, TorClient,
+    TorClientConfig,
 };
 use std::{
     result::Result as StdResult,
@@ -48,6 +49,29 @@ impl<R: Runtime> DirProviderBuilder<R> for DirMgrBuilder {
     }
 }
 
+/// A DirProviderBuilder that constructs a [`PinnedDirProvider`](tor_dirmgr::PinnedDirProvider).
+#[cfg(feature = "pinned-consensus")]
+#[derive(Clone, Debug)]
+struct PinnedDirProviderBuilder {
+    /// The consensus to build the directory from.
+    pinned: tor_dirmgr::PinnedConsensus,
+}
+
+#[cfg(feature = "pinned-consensus")]
+impl<R: Runtime> DirProviderBuilder<R> for PinnedDirProviderBuilder {
+    fn build(
+        &self,
+        _runtime: R,
+        store: DirMgrStore<R>,
+        _circmgr: Arc<tor_circmgr::CircMgr<R>>,
+        config: DirMgrConfig,
+    ) -> Result<Arc<dyn tor_dirmgr::DirProvider + 'static>> {
+        let provider = tor_dirmgr::PinnedDirProvider::new(&self.pinned, &store, &config)
+            .map_err(ErrorDetail::DirMgrSetup)?;
+        Ok(Arc::new(provider))
+    }
+}
+
 /// An object for constructing a [`TorClient`].
 ///
 /// Returned by [`TorClient::builder()`].
@@ -74,6 +98,8 @@ pub struct TorClientBuilder<R: Runtime> {
     /// Only available when `arti-client` is built with the `dirfilter` and `experimental-api` features.
     #[cfg(feature = "dirfilter")]
     dirfilter: tor_dirmgr::filter::FilterConfig,
+    /// How to unlock the client's keystores, if they are encrypted.
+    keystore_unlock: KeystoreUnlock,
 }
 
 /// Longest allowable duration to wait for local resources to be available
@@ -98,6 +124,7 @@ impl<R: Runtime> TorClientBuilder<R> {
             local_resource_timeout: None,
             #[cfg(feature = "dirfilter")]
             dirfilter: None,
+            keystore_unlock: KeystoreUnlock::default(),
         }
     }
 
@@ -150,6 +177,23 @@ impl<R: Runtime> TorClientBuilder<R> {
         self
     }
 
+    /// Use a single, fixed consensus instead of downloading the network directory.
+    ///
+    /// The client builds its directory from `pinned`,
+    /// using the authority certificates and microdescriptors already in its cache
+    /// (see [`DirMgrStore::import_documents`]),
+    /// and never replaces it, even after it has expired.
+    /// This is meant for reproducible experiments against a snapshot of the network:
+    /// a client that uses it will eventually fail to work on the real network.
+    ///
+    /// Only available when compiled with the `pinned-consensus` feature: this
+    /// code is unstable and not recommended for production use.
+    #[cfg(feature = "pinned-consensus")]
+    pub fn pinned_consensus(mut self, pinned: tor_dirmgr::PinnedConsensus) -> Self {
+        self.dirmgr_builder = Arc::new(PinnedDirProviderBuilder { pinned });
+        self
+    }
+
     /// Install a [`DirFilter`](tor_dirmgr::filter::DirFilter) to
     ///
     /// Only available when compiled with the `dirfilter` feature: this code
@@ -163,6 +207,29 @@ impl<R: Runtime> TorClientBuilder<R> {
         self
     }
 
+    /// Set a hook for asking the user for the passphrase of an encrypted keystore.
+    ///
+    /// If the primary keystore is
+    /// [encrypted](tor_keymgr::config::ArtiKeystoreKind::Encrypted),
+    /// `prompt` is used to unlock it when the client is created.
+    /// If no prompt is set, the keystore stays locked,
+    /// and any attempt to use the keys in it will fail.
+    ///
+    /// To keep the passphrase in the credential store of the operating system
+    /// instead of asking the user for it, use a
+    /// `tor_keymgr::OsCredentialStore` (with the `os-keystore` feature).
+    ///
+    /// Only available when compiled with the `encrypted-keystore` feature: this code
+    /// is unstable.
+    #[cfg(feature = "encrypted-keystore")]
+    pub fn keystore_passphrase_prompt(
+        mut self,
+        prompt: Arc<dyn tor_keymgr::PassphrasePrompt>,
+    ) -> Self {
+        self.keystore_unlock.passphrase_prompt = Some(prompt);
+        self
+    }
+
     /// Create a `TorClient` from this builder, without automatically launching
     /// the bootstrap process.
     ///
@@ -250,6 +317,7 @@ impl<R: Runtime> TorClientBuilder<R> {
             self.bootstrap_behavior,
             self.dirmgr_builder.as_ref(),
             dirmgr_extensions,
+            &self.keystore_unlock,
         )
         .map_err(ErrorDetail::into);
 
@@ -311,7 +379,7 @@ impl<R: Runtime> TorClientBuilder<R> {
     // TODO(#1576): reach a decision here.
     #[allow(clippy::unnecessary_wraps)]
     pub fn create_inert(&self) -> Result<InertTorClient> {
-        Ok(InertTorClient::new(&self.config)?)
+        Ok(InertTorClient::new(&self.config, &self.keystore_unlock)?)
     }
 }
 
@@ -0,0 +1,149 @@
+//! Groups of streams that share a single circuit.
+//!
+//! Normally, Arti picks a circuit for each new stream as it sees fit:
+//! streams to the same host may end up on different circuits,
+//! for instance once a circuit has been in use for a while.
+//! For applications that open many small connections to the same host
+//! (such as chat or IMAP clients), this is wasteful,
+//! and doesn't make the connections any harder to link,
+//! since the host can link them anyway.
+//!
+//! A [`CircuitGroup`], created using
+//! [`TorClient::isolated_circuit_group`],
+//! pins all of its streams to a single circuit,
+//! which is chosen when the first stream is opened.
+//! If the circuit closes, the group moves to a new circuit:
+//! the streams that were open on the old circuit fail,
+//! but new streams (and a stream that was being opened when the circuit failed)
+//! use the new circuit.
+//!
+//! The streams of a group never share circuits with any other streams.
+
+use std::sync::Arc;
+
+use futures::lock::Mutex as AsyncMutex;
+use tor_proto::circuit::ClientCirc;
+use tor_proto::stream::DataStream;
+use tor_rtcompat::Runtime;
+use tracing::{debug, info};
+
+use crate::address::StreamInstructions;
+use crate::client::wrap_err;
+use crate::err::ErrorDetail;
+use crate::{IntoTorAddr, StreamPrefs, TorClient};
+
+/// A group of streams that share a single circuit.
+///
+/// Create one with [`TorClient::isolated_circuit_group`].
+/// See the [module-level documentation](self) for details.
+///
+/// All the streams of a group must be of the same kind:
+/// either they all go through an exit relay
+/// (in which case the exit chosen for the first stream
+/// must allow the ports of the others),
+/// or they all go to the same onion service.
+pub struct CircuitGroup<R: Runtime> {
+    /// The (isolated) client used to make circuits and streams.
+    client: TorClient<R>,
+    /// The preferences to use for new streams.
+    prefs: StreamPrefs,
+    /// The circuit the streams are pinned to, if we have one yet.
+    ///
+    /// This is a `futures::lock::Mutex`, so that concurrent connection attempts
+    /// wait for the circuit being built, rather than building another one.
+    pinned: AsyncMutex<Option<PinnedCircuit>>,
+}
+
+/// The circuit a [`CircuitGroup`] is pinned to.
+struct PinnedCircuit {
+    /// The instructions of the stream the circuit was built for.
+    instructions: StreamInstructions,
+    /// The circuit.
+    circ: Arc<ClientCirc>,
+}
+
+impl<R: Runtime> CircuitGroup<R> {
+    /// Create a new group, using `client` to make circuits and streams with `prefs`.
+    pub(crate) fn new(client: TorClient<R>, prefs: StreamPrefs) -> Self {
+        CircuitGroup {
+            client,
+            prefs,
+            pinned: AsyncMutex::new(None),
+        }
+    }
+
+    /// Open a stream to `target` on the circuit of this group.
+    ///
+    /// If this group doesn't have a circuit yet, or its circuit has closed,
+    /// a new circuit is built for `target`.
+    ///
+    /// Returns an error (of kind [`BadApiUsage`](crate::ErrorKind::BadApiUsage))
+    /// if `target` can't use the circuit of this group:
+    /// for instance, if the group is pinned to an exit circuit,
+    /// and `target` is an onion service.
+    pub async fn connect<A: IntoTorAddr>(&self, target: A) -> crate::Result<DataStream> {
+        let addr = target.into_tor_addr().map_err(wrap_err)?;
+        let instructions = self.client.stream_instructions(addr, &self.prefs)?;
+
+        let circ = self.circuit_for(&instructions).await?;
+        match self
+            .client
+            .begin_data_stream(&circ, &instructions, &self.prefs)
+            .await
+        {
+            Err(_) if circ.is_closing() => {
+                // The circuit died under us: try again (once) on a new circuit.
+                info!("Circuit of a circuit group failed; moving the group to a new circuit.");
+                let circ = self.circuit_for(&instructions).await?;
+                self.client
+                    .begin_data_stream(&circ, &instructions, &self.prefs)
+                    .await
+            }
+            res => res,
+        }
+    }
+
+    /// Return the circuit this group is currently pinned to, if any.
+    ///
+    /// The circuit may have closed since it was last used.
+    pub async fn circuit(&self) -> Option<Arc<ClientCirc>> {
+        self.pinned
+            .lock()
+            .await
+            .as_ref()
+            .map(|p| Arc::clone(&p.circ))
+    }
+
+    /// Return the circuit to use for a stream following `instructions`,
+    /// building a new one if necessary.
+    async fn circuit_for(
+        &self,
+        instructions: &StreamInstructions,
+    ) -> crate::Result<Arc<ClientCirc>> {
+        let mut pinned = self.pinned.lock().await;
+
+        if let Some(p) = &*pinned {
+            if !p.instructions.same_circuit_target(instructions) {
+                return Err(ErrorDetail::from(tor_error::bad_api_usage!(
+                    "Stream target can't use the circuit of its circuit group"
+                ))
+                .into());
+            }
+            if !p.circ.is_closing() {
+                return Ok(Arc::clone(&p.circ));
+            }
+            debug!("Circuit of a circuit group has closed; getting a new one.");
+        }
+
+        let circ = self
+            .client
+            .get_or_launch_stream_circ(instructions, &self.prefs)
+            .await?;
+        *pinned = Some(PinnedCircuit {
+            instructions: instructions.clone(),
+            circ: Arc::clone(&circ),
+        });
+
+        Ok(circ)
+    }
+}
@@ -0,0 +1,371 @@
+//! Automatic fallback between ways of reaching the Tor network.
+//!
+//! Where the Tor network is blocked, a client has to use bridges to reach it;
+//! where it isn't, bridges only make the client slower.
+//! Rather than making each application find out which is the case,
+//! a [`CircumventionStrategy`] lists the ways to try, in order,
+//! with how long to give each of them:
+//! [`TorClient::bootstrap_with_circumvention`] tries each in turn,
+//! until one of them lets the client bootstrap.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use arti_client::circumvention::{CircumventionStage, CircumventionStrategy};
+//! use arti_client::config::{BridgeConfigBuilder, TorClientConfigBuilder};
+//! use arti_client::TorClient;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let base = TorClientConfigBuilder::default();
+//! let config = base.build()?;
+//! let client = TorClient::builder()
+//!     .config(config)
+//!     .bootstrap_behavior(arti_client::BootstrapBehavior::Manual)
+//!     .create_unbootstrapped()?;
+//!
+//! // Try a direct connection, then the built-in bridges,
+//! // then some bridges the user gave us.
+//! let user_bridge: BridgeConfigBuilder =
+//!     "192.0.2.66:443 8C00FF00FF00FF00FF00FF00FF00FF00FF00FF00".parse()?;
+//! let mut strategy = CircumventionStrategy::auto(base);
+//! strategy.add_stage(
+//!     CircumventionStage::Bridges(vec![user_bridge]),
+//!     Duration::from_secs(60),
+//! );
+//!
+//! let stage = client.bootstrap_with_circumvention(&strategy).await?;
+//! println!("Connected using {stage}");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use futures::channel::mpsc;
+use futures::Stream;
+use tor_config::{BoolOrAuto, Reconfigure};
+use tor_rtcompat::{Runtime, SleepProviderExt as _};
+use tracing::{info, warn};
+
+use crate::config::{distro, BridgeConfigBuilder, TorClientConfigBuilder};
+use crate::err::ErrorDetail;
+use crate::TorClient;
+
+/// How long [`CircumventionStrategy::auto`] gives a direct connection.
+const DEFAULT_DIRECT_BUDGET: Duration = Duration::from_secs(30);
+
+/// How long [`CircumventionStrategy::auto`] gives the built-in bridges.
+const DEFAULT_BRIDGES_BUDGET: Duration = Duration::from_secs(60);
+
+/// One way of reaching the Tor network.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum CircumventionStage {
+    /// Connect to the Tor network directly, without bridges.
+    Direct,
+    /// Use the bridges built into this Arti, if any.
+    ///
+    /// These are the default bridges set with `ARTI_DISTRO_BRIDGES`
+    /// when Arti was built: see [`config::distro`](crate::config::distro).
+    /// If there are none, this stage is skipped.
+    BuiltinBridges,
+    /// Use these bridges.
+    ///
+    /// If the list is empty, this stage is skipped.
+    Bridges(Vec<BridgeConfigBuilder>),
+}
+
+impl fmt::Display for CircumventionStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircumventionStage::Direct => write!(f, "a direct connection"),
+            CircumventionStage::BuiltinBridges => write!(f, "the built-in bridges"),
+            CircumventionStage::Bridges(bridges) => write!(f, "{} given bridge(s)", bridges.len()),
+        }
+    }
+}
+
+/// Something that happened while following a [`CircumventionStrategy`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum CircumventionEvent {
+    /// We started trying a stage, and will give it `budget` to bootstrap.
+    StageStarted {
+        /// The stage we're trying.
+        stage: CircumventionStage,
+        /// How long we'll wait for it.
+        budget: Duration,
+    },
+    /// We skipped a stage, because it had no bridges.
+    StageSkipped {
+        /// The stage we skipped.
+        stage: CircumventionStage,
+    },
+    /// A stage failed, and we're moving on to the next one.
+    StageFailed {
+        /// The stage that failed.
+        stage: CircumventionStage,
+        /// Why it failed.
+        error: crate::Error,
+    },
+    /// A stage didn't bootstrap within its budget, and we're moving on to the next one.
+    StageTimedOut {
+        /// The stage that timed out.
+        stage: CircumventionStage,
+    },
+    /// A stage let us bootstrap: we're using it from now on.
+    Succeeded {
+        /// The stage that worked.
+        stage: CircumventionStage,
+    },
+}
+
+/// A list of ways to reach the Tor network, to try in order.
+///
+/// See the [module-level documentation](self) for details.
+#[derive(Debug)]
+pub struct CircumventionStrategy {
+    /// The configuration to use, apart from the bridges.
+    base: TorClientConfigBuilder,
+    /// The stages to try, with how long to give each of them.
+    stages: Vec<(CircumventionStage, Duration)>,
+    /// The senders for the streams returned by [`CircumventionStrategy::events`].
+    listeners: Mutex<Vec<mpsc::UnboundedSender<CircumventionEvent>>>,
+}
+
+impl CircumventionStrategy {
+    /// Return a new strategy, with no stages.
+    ///
+    /// Each stage will use the configuration in `base`,
+    /// with the bridges replaced by those of the stage.
+    pub fn new(base: TorClientConfigBuilder) -> Self {
+        Self {
+            base,
+            stages: Vec::new(),
+            listeners: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Return a new strategy that tries a direct connection for 30 seconds,
+    /// and then the built-in bridges for 60 seconds.
+    ///
+    /// See [`new`](CircumventionStrategy::new) for how `base` is used.
+    pub fn auto(base: TorClientConfigBuilder) -> Self {
+        let mut strategy = Self::new(base);
+        strategy
+            .add_stage(CircumventionStage::Direct, DEFAULT_DIRECT_BUDGET)
+            .add_stage(CircumventionStage::BuiltinBridges, DEFAULT_BRIDGES_BUDGET);
+        strategy
+    }
+
+    /// Add `stage` to the end of this strategy, with `budget` to bootstrap.
+    pub fn add_stage(&mut self, stage: CircumventionStage, budget: Duration) -> &mut Self {
+        self.stages.push((stage, budget));
+        self
+    }
+
+    /// Return the stages of this strategy, with their budgets.
+    pub fn stages(&self) -> &[(CircumventionStage, Duration)] {
+        &self.stages
+    }
+
+    /// Return a stream of the [`CircumventionEvent`]s from now on,
+    /// whenever this strategy is followed.
+    pub fn events(&self) -> CircumventionEvents {
+        let (tx, rx) = mpsc::unbounded();
+        self.listeners.lock().expect("lock poisoned").push(tx);
+        CircumventionEvents(rx)
+    }
+
+    /// Send `event` to every stream returned by [`CircumventionStrategy::events`].
+    fn note(&self, event: &CircumventionEvent) {
+        self.listeners
+            .lock()
+            .expect("lock poisoned")
+            .retain(|tx| tx.unbounded_send(event.clone()).is_ok());
+    }
+
+    /// Return the configuration for `stage`,
+    /// or `None` if the stage should be skipped.
+    fn config_for(
+        &self,
+        stage: &CircumventionStage,
+    ) -> Result<Option<crate::config::TorClientConfig>, ErrorDetail> {
+        let mut builder = self.base.clone();
+        let bridges = match stage {
+            CircumventionStage::Direct => None,
+            CircumventionStage::BuiltinBridges => Some(distro::bridges()),
+            CircumventionStage::Bridges(bridges) => Some(bridges.clone()),
+        };
+        match bridges {
+            None => {
+                builder.bridges().enabled(BoolOrAuto::Explicit(false));
+            }
+            Some(bridges) if bridges.is_empty() => return Ok(None),
+            Some(bridges) => {
+                builder.bridges().enabled(BoolOrAuto::Explicit(true));
+                *builder.bridges().bridges() = bridges;
+            }
+        }
+        Ok(Some(builder.build()?))
+    }
+}
+
+/// A [`Stream`] of [`CircumventionEvent`]s.
+///
+/// Returned by [`CircumventionStrategy::events`].
+#[derive(Debug)]
+pub struct CircumventionEvents(mpsc::UnboundedReceiver<CircumventionEvent>);
+
+impl Stream for CircumventionEvents {
+    type Item = CircumventionEvent;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        std::pin::Pin::new(&mut self.0).poll_next(cx)
+    }
+}
+
+impl<R: Runtime> TorClient<R> {
+    /// Bootstrap this client, trying each stage of `strategy` in turn.
+    ///
+    /// For each stage, the client is reconfigured with the bridges of that stage
+    /// (or without bridges), and given the stage's budget to [`bootstrap`](TorClient::bootstrap).
+    /// Stages without bridges are skipped.
+    /// We stop at the first stage that works, and return it:
+    /// the client keeps using the configuration of that stage.
+    ///
+    /// What happens at each stage is reported to the streams returned by
+    /// [`CircumventionStrategy::events`].
+    ///
+    /// Returns an error if no stage worked,
+    /// or if we couldn't reconfigure the client for a stage.
+    pub async fn bootstrap_with_circumvention(
+        &self,
+        strategy: &CircumventionStrategy,
+    ) -> crate::Result<CircumventionStage> {
+        for (stage, budget) in strategy.stages() {
+            let Some(config) = strategy.config_for(stage)? else {
+                info!("Skipping {}: no bridges", stage);
+                strategy.note(&CircumventionEvent::StageSkipped {
+                    stage: stage.clone(),
+                });
+                continue;
+            };
+            self.reconfigure(&config, Reconfigure::AllOrNothing)?;
+
+            info!("Trying to bootstrap using {}", stage);
+            strategy.note(&CircumventionEvent::StageStarted {
+                stage: stage.clone(),
+                budget: *budget,
+            });
+            match self.runtime().timeout(*budget, self.bootstrap()).await {
+                Ok(Ok(())) => {
+                    info!("Bootstrapped using {}", stage);
+                    strategy.note(&CircumventionEvent::Succeeded {
+                        stage: stage.clone(),
+                    });
+                    return Ok(stage.clone());
+                }
+                Ok(Err(error)) => {
+                    warn!("Unable to bootstrap using {}: {}", stage, error);
+                    strategy.note(&CircumventionEvent::StageFailed {
+                        stage: stage.clone(),
+                        error,
+                    });
+                }
+                Err(_) => {
+                    warn!("Unable to bootstrap using {} within {:?}", stage, budget);
+                    strategy.note(&CircumventionEvent::StageTimedOut {
+                        stage: stage.clone(),
+                    });
+                }
+            }
+        }
+
+        Err(ErrorDetail::CircumventionFailed.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use futures::StreamExt as _;
+    use tor_guardmgr::GuardMgrConfig as _;
+
+    /// A bridge line for testing.
+    const BRIDGE: &str = "192.0.2.66:443 8C00FF00FF00FF00FF00FF00FF00FF00FF00FF00";
+
+    #[test]
+    fn stage_configs() {
+        let mut base = TorClientConfigBuilder::default();
+        base.bridges().bridges().push(BRIDGE.parse().unwrap());
+        let mut strategy = CircumventionStrategy::auto(base);
+        strategy
+            .add_stage(CircumventionStage::Bridges(vec![]), Duration::from_secs(1))
+            .add_stage(
+                CircumventionStage::Bridges(vec![BRIDGE.parse().unwrap()]),
+                Duration::from_secs(1),
+            );
+        assert_eq!(strategy.stages().len(), 4);
+
+        let direct = strategy.config_for(&CircumventionStage::Direct).unwrap();
+        assert!(!direct.unwrap().bridges_enabled());
+
+        // Skipped unless this build has built-in bridges.
+        let builtin = strategy
+            .config_for(&CircumventionStage::BuiltinBridges)
+            .unwrap();
+        assert_eq!(builtin.is_some(), !distro::bridges().is_empty());
+
+        assert!(strategy
+            .config_for(&strategy.stages()[2].0)
+            .unwrap()
+            .is_none());
+        let bridges = strategy
+            .config_for(&strategy.stages()[3].0)
+            .unwrap()
+            .unwrap();
+        assert!(bridges.bridges_enabled());
+        assert_eq!(bridges.bridges().len(), 1);
+    }
+
+    #[test]
+    fn events() {
+        let strategy = CircumventionStrategy::new(TorClientConfigBuilder::default());
+        let mut events = strategy.events();
+        let dropped = strategy.events();
+        drop(dropped);
+
+        strategy.note(&CircumventionEvent::StageSkipped {
+            stage: CircumventionStage::BuiltinBridges,
+        });
+        assert_eq!(strategy.listeners.lock().unwrap().len(), 1);
+        drop(strategy);
+
+        let got: Vec<_> = futures::executor::block_on(events.by_ref().collect());
+        assert_eq!(got.len(), 1);
+        assert!(matches!(
+            got[0],
+            CircumventionEvent::StageSkipped {
+                stage: CircumventionStage::BuiltinBridges
+            }
+        ));
+    }
+}
@@ -8,18 +8,19 @@
 #[cfg(feature = "rpc")]
 use {derive_deftly::Deftly, tor_rpcbase::templates::*};
 
-use crate::address::{IntoTorAddr, ResolveInstructions, StreamInstructions};
+use crate::address::{IntoTorAddr, ResolveInstructions, StreamInstructions, TorAddr};
+use crate::dns_cache::DnsCache;
 
 use crate::config::{ClientAddrConfig, StreamTimeoutConfig, TorClientConfig};
 use safelog::{sensitive, Sensitive};
 use tor_async_utils::{DropNotifyWatchSender, PostageWatchSenderExt};
-use tor_circmgr::isolation::{Isolation, StreamIsolation};
+use tor_circmgr::isolation::{Isolation, IsolationLineage, StreamIsolation};
 use tor_circmgr::{isolation::StreamIsolationBuilder, IsolationToken, TargetPort};
 use tor_config::MutCfg;
 #[cfg(feature = "bridge-client")]
 use tor_dirmgr::bridgedesc::BridgeDescMgr;
 use tor_dirmgr::{DirMgrStore, Timeliness};
-use tor_error::{error_report, internal, Bug};
+use tor_error::{error_report, internal, into_internal, warn_report, Bug};
 use tor_guardmgr::{GuardMgr, RetireCircuits};
 use tor_keymgr::Keystore;
 use tor_memquota::MemoryQuotaTracker;
@@ -48,28 +49,42 @@ use tor_hsservice::HsIdKeypairSpecifier;
 #[cfg(all(feature = "onion-service-client", feature = "experimental-api"))]
 use {tor_hscrypto::pk::HsId, tor_hscrypto::pk::HsIdKeypair, tor_keymgr::KeystoreSelector};
 
-use tor_keymgr::{config::ArtiKeystoreKind, ArtiNativeKeystore, KeyMgr, KeyMgrBuilder};
+use tor_keymgr::{config::ArtiKeystoreKind, ArtiNativeKeystore, KeyMgr, KeyMgrBuilder, OpContext};
+
+#[cfg(all(feature = "experimental-api", feature = "keymgr"))]
+use tor_keymgr::{
+    ArtiPath, ConflictPolicy, CopyOutcome, KeyBundle, KeyPathPattern, KeyType, KeystoreId,
+    SyncedEntry,
+};
 
 #[cfg(feature = "ephemeral-keystore")]
 use tor_keymgr::ArtiEphemeralKeystore;
 
+#[cfg(feature = "encrypted-keystore")]
+use tor_keymgr::{ArtiEncryptedKeystore, ConfiguredPassphrase, PassphrasePrompt};
+
 #[cfg(feature = "ctor-keystore")]
 use tor_keymgr::{CTorClientKeystore, CTorServiceKeystore};
 
 use futures::lock::Mutex as AsyncMutex;
 use futures::task::SpawnExt;
 use futures::StreamExt as _;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::net::IpAddr;
 use std::path::PathBuf;
 use std::result::Result as StdResult;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use crate::err::ErrorDetail;
-use crate::{status, util, TorClientBuilder};
+use crate::onion_only::OnionOnlyCounters;
+use crate::stream_rotation::{rotate_streams, RotationLimits, StreamRotation};
+use crate::{health, status, util, TorClientBuilder};
 #[cfg(feature = "geoip")]
 use tor_geoip::CountryCode;
 use tor_rtcompat::scheduler::TaskHandle;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 /// An active client session on the Tor network.
 ///
@@ -173,6 +188,8 @@ pub struct TorClient<R: Runtime> {
     addrcfg: Arc<MutCfg<ClientAddrConfig>>,
     /// Client DNS configuration
     timeoutcfg: Arc<MutCfg<StreamTimeoutConfig>>,
+    /// Cache of the results of DNS lookups made via exits
+    dns_cache: Arc<DnsCache>,
     /// Mutex used to serialize concurrent attempts to reconfigure a TorClient.
     ///
     /// See [`TorClient::reconfigure`] for more information on its use.
@@ -185,6 +202,14 @@ pub struct TorClient<R: Runtime> {
     /// unobserved status change when the next status change occurs.)
     status_receiver: status::BootstrapEvents,
 
+    /// The problems that this client has noticed and that haven't gone away.
+    health: Arc<health::HealthMonitor>,
+
+    /// The requests that this client has rejected because of `address_filter.onion_only`.
+    onion_only: Arc<OnionOnlyCounters>,
+    /// The circuits that we must close because of `stream_rotation`.
+    stream_rotation: Arc<StreamRotation>,
+
     /// mutex used to prevent two tasks from trying to bootstrap at once.
     bootstrap_in_progress: Arc<AsyncMutex<()>>,
 
@@ -223,10 +248,29 @@ pub struct InertTorClient {
     keymgr: Option<Arc<KeyMgr>>,
 }
 
+/// How to unlock the keystores of a client, if they are encrypted.
+#[derive(Clone, Default)]
+pub(crate) struct KeystoreUnlock {
+    /// A hook for asking the user for the passphrase of an encrypted keystore.
+    ///
+    /// If this is `None`, encrypted keystores are left locked.
+    #[cfg(feature = "encrypted-keystore")]
+    pub(crate) passphrase_prompt: Option<Arc<dyn PassphrasePrompt>>,
+}
+
+/// How long each key store operation may take before we give up on it.
+///
+/// This stops a hung key store (for example, on an unreachable network mount)
+/// from blocking the client, and the onion services it runs, indefinitely.
+const KEYSTORE_OPERATION_TIMEOUT: Duration = Duration::from_secs(60);
+
 impl InertTorClient {
     /// Create an `InertTorClient` from a `TorClientConfig`.
-    pub(crate) fn new(config: &TorClientConfig) -> StdResult<Self, ErrorDetail> {
-        let keymgr = Self::create_keymgr(config)?;
+    pub(crate) fn new(
+        config: &TorClientConfig,
+        unlock: &KeystoreUnlock,
+    ) -> StdResult<Self, ErrorDetail> {
+        let keymgr = Self::create_keymgr(config, unlock)?;
 
         Ok(Self { keymgr })
     }
@@ -234,7 +278,11 @@ impl InertTorClient {
     /// Create a [`KeyMgr`] using the specified configuration.
     ///
     /// Returns `Ok(None)` if keystore use is disabled.
-    fn create_keymgr(config: &TorClientConfig) -> StdResult<Option<Arc<KeyMgr>>, ErrorDetail> {
+    fn create_keymgr(
+        config: &TorClientConfig,
+        #[cfg_attr(not(feature = "encrypted-keystore"), allow(unused_variables))]
+        unlock: &KeystoreUnlock,
+    ) -> StdResult<Option<Arc<KeyMgr>>, ErrorDetail> {
         let keystore = config.storage.keystore();
         let permissions = config.storage.permissions();
         let primary_store: Box<dyn Keystore> = match keystore.primary_kind() {
@@ -242,8 +290,11 @@ impl InertTorClient {
                 let (state_dir, _mistrust) = config.state_dir()?;
                 let key_store_dir = state_dir.join("keystore");
 
-                let native_store =
-                    ArtiNativeKeystore::from_path_and_mistrust(&key_store_dir, permissions)?;
+                let native_store = ArtiNativeKeystore::from_path_and_mistrust_with_ctx(
+                    &key_store_dir,
+                    permissions,
+                    &OpContext::new().with_timeout(KEYSTORE_OPERATION_TIMEOUT),
+                )?;
                 info!("Using keystore from {key_store_dir:?}");
 
                 Box::new(native_store)
@@ -255,6 +306,33 @@ impl InertTorClient {
                     ArtiEphemeralKeystore::new("ephemeral".to_string());
                 Box::new(ephemeral_store)
             }
+            #[cfg(feature = "encrypted-keystore")]
+            Some(ArtiKeystoreKind::Encrypted) => {
+                let (state_dir, _mistrust) = config.state_dir()?;
+                let key_store_dir = state_dir.join("keystore");
+
+                let encrypted_store = ArtiEncryptedKeystore::from_path_and_mistrust_with_ctx(
+                    &key_store_dir,
+                    permissions,
+                    &OpContext::new().with_timeout(KEYSTORE_OPERATION_TIMEOUT),
+                )?;
+                // A passphrase in the configuration takes precedence over the prompt.
+                let configured = keystore
+                    .primary_passphrase()
+                    .map(ConfiguredPassphrase::resolve)
+                    .transpose()
+                    .map_err(ErrorDetail::KeystorePassphrase)?;
+                match (&configured, &unlock.passphrase_prompt) {
+                    (Some(passphrase), _) => encrypted_store.unlock_with(passphrase)?,
+                    (None, Some(prompt)) => encrypted_store.unlock_with(prompt.as_ref())?,
+                    (None, None) => {
+                        warn!("No passphrase prompt configured: leaving the keystore locked");
+                    }
+                }
+                info!("Using encrypted keystore from {key_store_dir:?}");
+
+                Box::new(encrypted_store)
+            }
             None => {
                 info!("Running without a keystore");
                 return Ok(None);
@@ -262,7 +340,9 @@ impl InertTorClient {
             ty => return Err(internal!("unrecognized keystore type {ty:?}").into()),
         };
 
-        let mut builder = KeyMgrBuilder::default().primary_store(primary_store);
+        let mut builder = KeyMgrBuilder::default()
+            .primary_store(primary_store)
+            .operation_timeout(KEYSTORE_OPERATION_TIMEOUT);
 
         #[cfg(feature = "ctor-keystore")]
         for config in config.storage.keystore().ctor_svc_stores() {
@@ -468,6 +548,157 @@ impl InertTorClient {
             None => Ok(None),
         }
     }
+
+    /// Copy every key in the keystore with ID `from` to the keystore with ID `to`.
+    ///
+    /// Both keystores must be configured for this client.
+    /// This is useful for migrating keys between keystores,
+    /// for instance from a C Tor keystore to the native Arti one.
+    ///
+    /// See [`KeyMgr::sync_stores`] for details, including the meaning of `policy` and `dry_run`.
+    #[cfg(all(feature = "experimental-api", feature = "keymgr"))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(all(feature = "experimental-api", feature = "keymgr")))
+    )]
+    pub fn sync_keystores(
+        &self,
+        from: &KeystoreId,
+        to: &KeystoreId,
+        policy: ConflictPolicy,
+        dry_run: bool,
+    ) -> crate::Result<Vec<SyncedEntry<'_>>> {
+        let synced = self
+            .keymgr
+            .as_ref()
+            .ok_or(ErrorDetail::KeystoreRequired {
+                action: "sync keystores",
+            })?
+            .sync_stores(from.into(), to.into(), policy, dry_run)?;
+
+        Ok(synced)
+    }
+
+    /// Export the keys whose paths match `pat` from the keystore with ID `keystore`.
+    ///
+    /// See [`KeyMgr::export_keys`].
+    #[cfg(all(feature = "experimental-api", feature = "keymgr"))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(all(feature = "experimental-api", feature = "keymgr")))
+    )]
+    pub fn export_keys(
+        &self,
+        pat: &KeyPathPattern,
+        keystore: &KeystoreId,
+    ) -> crate::Result<KeyBundle> {
+        let bundle = self
+            .keymgr
+            .as_ref()
+            .ok_or(ErrorDetail::KeystoreRequired {
+                action: "export keys",
+            })?
+            .export_keys(pat, keystore.into())?;
+
+        Ok(bundle)
+    }
+
+    /// Import the keys in `bundle` into the keystore with ID `keystore`.
+    ///
+    /// See [`KeyMgr::import_keys`], including for the meaning of `policy`.
+    #[cfg(all(feature = "experimental-api", feature = "keymgr"))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(all(feature = "experimental-api", feature = "keymgr")))
+    )]
+    pub fn import_keys(
+        &self,
+        bundle: &KeyBundle,
+        keystore: &KeystoreId,
+        policy: ConflictPolicy,
+    ) -> crate::Result<Vec<CopyOutcome>> {
+        let outcomes = self
+            .keymgr
+            .as_ref()
+            .ok_or(ErrorDetail::KeystoreRequired {
+                action: "import keys",
+            })?
+            .import_keys(bundle, keystore.into(), policy)?;
+
+        Ok(outcomes)
+    }
+
+    /// Export the key with [`ArtiPath`] `path` and type `key_type`
+    /// from the keystore with ID `keystore`, as an OpenSSH key.
+    ///
+    /// Returns `Ok(None)` if the keystore does not have the key.
+    ///
+    /// See [`KeyMgr::export_openssh`].
+    #[cfg(all(feature = "experimental-api", feature = "keymgr"))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(all(feature = "experimental-api", feature = "keymgr")))
+    )]
+    pub fn export_openssh(
+        &self,
+        path: &ArtiPath,
+        key_type: &KeyType,
+        keystore: &KeystoreId,
+    ) -> crate::Result<Option<zeroize::Zeroizing<String>>> {
+        let encoded = self
+            .keymgr
+            .as_ref()
+            .ok_or(ErrorDetail::KeystoreRequired {
+                action: "export key",
+            })?
+            .export_openssh(path, key_type, keystore.into())?;
+
+        Ok(encoded)
+    }
+
+    /// Import the OpenSSH key `openssh` into the keystore with ID `keystore`,
+    /// as the key with [`ArtiPath`] `path` and type `key_type`.
+    ///
+    /// See [`KeyMgr::import_openssh`], including for the meaning of `policy`,
+    /// and for which types of key can be imported as which.
+    #[cfg(all(feature = "experimental-api", feature = "keymgr"))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(all(feature = "experimental-api", feature = "keymgr")))
+    )]
+    pub fn import_openssh(
+        &self,
+        openssh: &str,
+        path: &ArtiPath,
+        key_type: &KeyType,
+        keystore: &KeystoreId,
+        policy: ConflictPolicy,
+    ) -> crate::Result<CopyOutcome> {
+        let outcome = self
+            .keymgr
+            .as_ref()
+            .ok_or(ErrorDetail::KeystoreRequired {
+                action: "import key",
+            })?
+            .import_openssh(openssh, path, key_type, keystore.into(), policy)?;
+
+        Ok(outcome)
+    }
+}
+
+/// The version of the format we use in [`TorClient::snapshot_state`].
+const STATE_SNAPSHOT_VERSION: u32 = 1;
+
+/// A snapshot of a client's persistent state,
+/// as encoded by [`TorClient::snapshot_state`].
+#[derive(Serialize, Deserialize)]
+struct StateSnapshot {
+    /// The version of this format.
+    version: u32,
+    /// The contents of our state manager, by key.
+    state: BTreeMap<String, tor_persist::JsonValue>,
+    /// The documents from our directory cache.
+    directory: tor_dirmgr::CachedDocuments,
 }
 
 /// Preferences for whether a [`TorClient`] should bootstrap on its own or not.
@@ -508,6 +739,10 @@ pub struct StreamPrefs {
     isolation: StreamIsolationPreference,
     /// Whether to return the stream optimistically.
     optimistic_stream: bool,
+    /// The initial send window for the stream, if not the default.
+    initial_send_window: Option<u16>,
+    /// Stream rotation limits, if not the ones from the configuration.
+    rotation_limits: Option<RotationLimits>,
     // TODO GEOIP Ideally this would be unconditional, with CountryCode maybe being Void
     // This probably applies in many other places, so probably:   git grep 'cfg.*geoip'
     // and consider each one with a view to making it unconditional.  Background:
@@ -595,6 +830,24 @@ impl StreamPrefs {
         self
     }
 
+    /// Set which IP versions a stream may use, and which one we prefer.
+    ///
+    /// This is the same as calling [`ipv4_only`](Self::ipv4_only),
+    /// [`ipv4_preferred`](Self::ipv4_preferred),
+    /// [`ipv6_preferred`](Self::ipv6_preferred), or [`ipv6_only`](Self::ipv6_only).
+    ///
+    /// A preference is passed on to the exit relay, which uses it to choose
+    /// among the addresses of a hostname.
+    /// A requirement additionally restricts which exit relays we pick:
+    /// with [`IpVersionPreference::Ipv6Only`], we only use exits whose
+    /// exit policy allows IPv6 connections to the target port.
+    /// Connecting to an IP address of a version that is not allowed fails
+    /// immediately, without building a circuit.
+    pub fn ip_version(&mut self, pref: IpVersionPreference) -> &mut Self {
+        self.ip_ver_pref = pref;
+        self
+    }
+
     /// Indicate that a stream should appear to come from the given country.
     ///
     /// When this option is set, we will only pick exit relays that
@@ -645,6 +898,33 @@ impl StreamPrefs {
         self.optimistic_stream
     }
 
+    /// Limit how many DATA cells the stream may send before it waits
+    /// for the first SENDME from the exit.
+    ///
+    /// A smaller window limits how much data can be in flight on the stream,
+    /// which can help when diagnosing throughput problems, but it makes
+    /// streams slower on long or slow circuits.
+    ///
+    /// See [`StreamParameters::initial_send_window`] for the values allowed.
+    /// By default, the stream uses the largest window the protocol allows.
+    pub fn initial_send_window(&mut self, cells: u16) -> &mut Self {
+        self.initial_send_window = Some(cells);
+        self
+    }
+
+    /// Close the circuits used by streams with these preferences once they reach `limits`,
+    /// instead of the limits in the `stream_rotation` configuration section.
+    ///
+    /// When streams with different limits share a circuit,
+    /// the strictest limits apply to it.
+    /// Use [`RotationLimits::unlimited`] to exempt these streams from the configured limits.
+    ///
+    /// See the [`stream_rotation`](crate::stream_rotation) module for details.
+    pub fn rotation_limits(&mut self, limits: RotationLimits) -> &mut Self {
+        self.rotation_limits = Some(limits);
+        self
+    }
+
     /// Indicate whether connection to a hidden service (`.onion` service) should be allowed
     ///
     /// If `Explicit(false)`, attempts to connect to Onion Services will be forced to fail with
@@ -669,12 +949,26 @@ impl StreamPrefs {
         self.connect_to_onion_services = connect_to_onion_services;
         self
     }
+    /// Return true if these preferences allow connecting to `ip`.
+    pub(crate) fn allows_ip_version(&self, ip: &IpAddr) -> bool {
+        match (ip, self.ip_ver_pref) {
+            (IpAddr::V4(_), IpVersionPreference::Ipv6Only) => false,
+            (IpAddr::V6(_), IpVersionPreference::Ipv4Only) => false,
+            (_, _) => true,
+        }
+    }
+
     /// Return a TargetPort to describe what kind of exit policy our
-    /// target circuit needs to support.
-    fn wrap_target_port(&self, port: u16) -> TargetPort {
-        match self.ip_ver_pref {
-            IpVersionPreference::Ipv6Only => TargetPort::ipv6(port),
-            _ => TargetPort::ipv4(port),
+    /// target circuit needs to support, to connect to `hostname`.
+    ///
+    /// If `hostname` is an IP address, the exit has to support its IP version;
+    /// otherwise, it has to support whichever version we require.
+    fn wrap_target_port(&self, hostname: &str, port: u16) -> TargetPort {
+        match (hostname.parse(), self.ip_ver_pref) {
+            (Ok(IpAddr::V6(_)), _) | (Err(_), IpVersionPreference::Ipv6Only) => {
+                TargetPort::ipv6(port)
+            }
+            (_, _) => TargetPort::ipv4(port),
         }
     }
 
@@ -684,6 +978,9 @@ impl StreamPrefs {
         params
             .ip_version(self.ip_ver_pref)
             .optimistic(self.optimistic_stream);
+        if let Some(cells) = self.initial_send_window {
+            params.initial_send_window(cells);
+        }
         params
     }
 
@@ -737,6 +1034,47 @@ impl StreamPrefs {
         self
     }
 
+    /// Give connections with these preferences the same isolation as those made
+    /// with `parent`.
+    ///
+    /// Use this for a connection that an application makes on behalf of another,
+    /// and that should share circuits with it:
+    /// for example, an FTP data channel that should use the same exit as its control connection.
+    ///
+    /// If `parent` isolates every stream, so will these preferences.
+    /// (There is then no way for the two connections to share a circuit.)
+    pub fn inherit_isolation(&mut self, parent: &StreamPrefs) -> &mut Self {
+        self.isolation = parent.isolation.clone();
+        self
+    }
+
+    /// Give connections with these preferences an isolation derived from `parent`'s,
+    /// that keeps them apart from every other connection derived from `parent`.
+    ///
+    /// If `parent` uses an [`IsolationLineage`], these preferences use a new
+    /// [child](IsolationLineage::child) of it:
+    /// connections with these preferences may still share circuits with `parent`'s,
+    /// but never with those of other children.
+    /// Otherwise, these preferences get a new isolation group of their own,
+    /// as with [`new_isolation_group`](StreamPrefs::new_isolation_group).
+    pub fn nest_isolation(&mut self, parent: &StreamPrefs) -> &mut Self {
+        let lineage = match &parent.isolation {
+            StreamIsolationPreference::Explicit(isolation) => {
+                isolation.downcast_ref::<IsolationLineage>()
+            }
+            _ => None,
+        };
+        match lineage {
+            Some(lineage) => {
+                self.isolation = StreamIsolationPreference::Explicit(Box::new(lineage.child()));
+            }
+            None => {
+                self.new_isolation_group();
+            }
+        }
+        self
+    }
+
     /// Return an [`Isolation`] which separates according to these `StreamPrefs` (only)
     ///
     /// This describes which connections or operations might use
@@ -827,6 +1165,7 @@ impl<R: Runtime> TorClient<R> {
         autobootstrap: BootstrapBehavior,
         dirmgr_builder: &dyn crate::builder::DirProviderBuilder<R>,
         dirmgr_extensions: tor_dirmgr::config::DirMgrExtensions,
+        keystore_unlock: &KeystoreUnlock,
     ) -> StdResult<Self, ErrorDetail> {
         if crate::util::running_as_setuid() {
             return Err(tor_error::bad_api_usage!(
@@ -835,6 +1174,14 @@ impl<R: Runtime> TorClient<R> {
             .into());
         }
 
+        // Make sure the cryptography we were built with works before we rely on it.
+        tor_llcrypto::backend::self_test()?;
+        debug!(
+            "Using {} for AES and {} for SHA1",
+            tor_llcrypto::backend::aes_backend(),
+            tor_llcrypto::backend::sha1_backend()
+        );
+
         let memquota = MemoryQuotaTracker::new(&runtime, config.system.memory.clone())?;
 
         let (state_dir, mistrust) = config.state_dir()?;
@@ -864,6 +1211,7 @@ impl<R: Runtime> TorClient<R> {
             &NetParameters::from_map(&config.override_net_params),
             memquota.clone(),
         ));
+        chanmgr.set_track_bridges(tor_guardmgr::GuardMgrConfig::bridges_enabled(config));
         let guardmgr = tor_guardmgr::GuardMgr::new(runtime.clone(), statemgr.clone(), config)
             .map_err(ErrorDetail::GuardMgrSetup)?;
 
@@ -961,17 +1309,47 @@ impl<R: Runtime> TorClient<R> {
         let conn_status = chanmgr.bootstrap_events();
         let dir_status = dirmgr.bootstrap_events();
         let skew_status = circmgr.skew_events();
+        let circ_status = circmgr.circ_built_events();
         runtime
             .spawn(status::report_status(
                 status_sender,
                 conn_status,
                 dir_status,
                 skew_status,
+                circ_status,
             ))
             .map_err(|e| ErrorDetail::from_spawn("top-level status reporter", e))?;
 
+        let health = Arc::new(health::HealthMonitor::default());
+        runtime
+            .spawn(health::monitor_health(
+                runtime.clone(),
+                health.clone(),
+                status_receiver.clone(),
+            ))
+            .map_err(|e| ErrorDetail::from_spawn("health monitor", e))?;
+
         let client_isolation = IsolationToken::new();
-        let inert_client = InertTorClient::new(config)?;
+        let inert_client = InertTorClient::new(config, keystore_unlock)?;
+
+        #[cfg(feature = "keymgr")]
+        if let Some(keymgr) = &inert_client.keymgr {
+            runtime
+                .spawn(sweep_expired_keys(runtime.clone(), Arc::downgrade(keymgr)))
+                .map_err(|e| ErrorDetail::from_spawn("expired key sweeper", e))?;
+        }
+
+        let (stream_rotation, rotation_wakeup) =
+            StreamRotation::new(config.stream_rotation.clone());
+        let stream_rotation = Arc::new(stream_rotation);
+        runtime
+            .spawn(rotate_streams(
+                runtime.clone(),
+                Arc::downgrade(&stream_rotation),
+                Arc::downgrade(&circmgr),
+                rotation_wakeup,
+            ))
+            .map_err(|e| ErrorDetail::from_spawn("stream rotation", e))?;
 
         Ok(TorClient {
             runtime,
@@ -995,8 +1373,12 @@ impl<R: Runtime> TorClient<R> {
             statemgr,
             addrcfg: Arc::new(addr_cfg.into()),
             timeoutcfg: Arc::new(timeout_cfg.into()),
+            dns_cache: Arc::new(DnsCache::new(config.dns_cache.clone())),
             reconfigure_lock: Arc::new(Mutex::new(())),
             status_receiver,
+            health,
+            onion_only: Default::default(),
+            stream_rotation,
             bootstrap_in_progress: Arc::new(AsyncMutex::new(())),
             should_bootstrap: autobootstrap,
             dormant: Arc::new(Mutex::new(dormant_send)),
@@ -1224,8 +1606,17 @@ impl<R: Runtime> TorClient<R> {
             return Ok(());
         }
 
+        self.chanmgr
+            .set_track_bridges(tor_guardmgr::GuardMgrConfig::bridges_enabled(new_config));
         self.addrcfg.replace(addr_cfg.clone());
         self.timeoutcfg.replace(timeout_cfg.clone());
+        self.dns_cache.reconfigure(&new_config.dns_cache);
+        if retire_circuits != RetireCircuits::None {
+            // Like the circuits, the cached answers belong to our old identity.
+            self.dns_cache.flush();
+        }
+        self.stream_rotation
+            .reconfigure(&new_config.stream_rotation);
 
         Ok(())
     }
@@ -1252,6 +1643,138 @@ impl<R: Runtime> TorClient<R> {
         result
     }
 
+    /// Return a new [`CircuitGroup`](crate::circuit_group::CircuitGroup),
+    /// whose streams all share a single circuit.
+    ///
+    /// Like the streams of an [isolated client](TorClient::isolated_client),
+    /// the streams of the group never share circuits with any other streams.
+    /// The group uses the stream preferences of this `TorClient`.
+    ///
+    /// See the [`circuit_group`](crate::circuit_group) module for details.
+    #[cfg(feature = "experimental-api")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "experimental-api")))]
+    #[must_use]
+    pub fn isolated_circuit_group(&self) -> crate::circuit_group::CircuitGroup<R> {
+        crate::circuit_group::CircuitGroup::new(self.isolated_client(), self.connect_prefs.clone())
+    }
+
+    /// Forget the results of all previous DNS lookups.
+    ///
+    /// Arti caches the addresses returned by [`resolve`](TorClient::resolve)
+    /// for a short while (see the `dns_cache` configuration section).
+    /// Call this when switching to a new identity, so that nothing learned
+    /// under the old one is reused.
+    /// (We do this ourselves whenever a reconfiguration retires all our circuits.)
+    ///
+    /// This affects this `TorClient` and every handle that shares its internal
+    /// state, including those made with
+    /// [`isolated_client`](TorClient::isolated_client).
+    /// (Handles with different isolation never share cached results anyway.)
+    pub fn flush_dns_cache(&self) {
+        self.dns_cache.flush();
+    }
+
+    /// Return a stream of events reporting when stream rotation closes a circuit,
+    /// together with any streams that were still open on it.
+    ///
+    /// See the [`stream_rotation`](crate::stream_rotation) module,
+    /// and the `stream_rotation` configuration section.
+    ///
+    /// This includes circuits used by this `TorClient` and by every handle that
+    /// shares its internal state, including those made with
+    /// [`isolated_client`](TorClient::isolated_client).
+    pub fn stream_rotation_events(&self) -> crate::stream_rotation::StreamRotationEvents {
+        self.stream_rotation.events()
+    }
+
+    /// Return a snapshot of this client's persistent state,
+    /// as a single opaque blob.
+    ///
+    /// The snapshot includes our guards, our circuit build timeout estimates,
+    /// everything else in the state directory,
+    /// and the directory documents we'd need to rebuild our current network directory.
+    /// An application that can't rely on its state and cache directories
+    /// surviving from one launch to the next
+    /// (as on some mobile platforms)
+    /// can save this blob in platform storage,
+    /// and give it to [`restore_state`](TorClient::restore_state) at the next launch,
+    /// so as to bootstrap much faster.
+    ///
+    /// The snapshot contains our guards,
+    /// so it should be stored at least as carefully as the state directory.
+    /// Its format is not stable, and may change between versions of Arti.
+    //
+    // TODO: We would like to include our onion service descriptor caches too,
+    // but tor-hsclient only keeps parsed descriptors, not their text.
+    pub fn snapshot_state(&self) -> crate::Result<Vec<u8>> {
+        // Make sure that the state manager is up-to-date, if we're the ones writing to it.
+        self.circmgr
+            .store_persistent_state()
+            .map_err(ErrorDetail::CircMgrState)?;
+
+        let mut state = BTreeMap::new();
+        for key in self.statemgr.keys().map_err(ErrorDetail::StateAccess)? {
+            if let Some(value) = self
+                .statemgr
+                .load::<tor_persist::JsonValue>(&key)
+                .map_err(ErrorDetail::StateAccess)?
+            {
+                state.insert(key, value);
+            }
+        }
+        let directory = self
+            .dirmgr_store
+            .export_documents()
+            .map_err(ErrorDetail::DirCache)?;
+
+        let snapshot = StateSnapshot {
+            version: STATE_SNAPSHOT_VERSION,
+            state,
+            directory,
+        };
+        let snapshot = serde_json::to_vec(&snapshot)
+            .map_err(into_internal!("Unable to encode state snapshot"))
+            .map_err(ErrorDetail::from)?;
+        Ok(snapshot)
+    }
+
+    /// Replace this client's persistent state with the contents of `snapshot`,
+    /// as returned by [`snapshot_state`](TorClient::snapshot_state).
+    ///
+    /// Call this function on a newly created client, before bootstrapping it:
+    /// the directory documents from the snapshot are only used the next time
+    /// the client loads its directory from its cache.
+    /// Vanguards from the snapshot will not be used until
+    /// the next time a client is created with the same state directory.
+    ///
+    /// Fails if another process is using our state directory.
+    pub fn restore_state(&self, snapshot: &[u8]) -> crate::Result<()> {
+        let snapshot: StateSnapshot = serde_json::from_slice(snapshot)
+            .map_err(|e| ErrorDetail::BadStateSnapshot(e.to_string()))?;
+        if snapshot.version != STATE_SNAPSHOT_VERSION {
+            return Err(ErrorDetail::BadStateSnapshot(format!(
+                "unsupported version {}",
+                snapshot.version
+            ))
+            .into());
+        }
+
+        let _ignore_status = self.statemgr.try_lock().map_err(ErrorDetail::StateAccess)?;
+        for (key, value) in &snapshot.state {
+            self.statemgr
+                .store(key, value)
+                .map_err(ErrorDetail::StateAccess)?;
+        }
+        self.circmgr
+            .load_persistent_state()
+            .map_err(ErrorDetail::CircMgrState)?;
+        self.dirmgr_store
+            .import_documents(&snapshot.directory)
+            .map_err(ErrorDetail::DirCache)?;
+
+        Ok(())
+    }
+
     /// Launch an anonymized connection to the provided address and port over
     /// the Tor network.
     ///
@@ -1331,36 +1854,44 @@ impl<R: Runtime> TorClient<R> {
         prefs: &StreamPrefs,
     ) -> crate::Result<DataStream> {
         let addr = target.into_tor_addr().map_err(wrap_err)?;
-        let mut stream_parameters = prefs.stream_parameters();
+        let instructions = self.stream_instructions(addr, prefs)?;
+        let circ = self.get_or_launch_stream_circ(&instructions, prefs).await?;
+        self.begin_data_stream(&circ, &instructions, prefs).await
+    }
 
-        let (circ, addr, port) = match addr.into_stream_instructions(&self.addrcfg.get(), prefs)? {
-            StreamInstructions::Exit {
-                hostname: addr,
-                port,
-            } => {
-                let exit_ports = [prefs.wrap_target_port(port)];
+    /// Work out how to make a stream to `addr`, using `prefs`.
+    pub(crate) fn stream_instructions(
+        &self,
+        addr: TorAddr,
+        prefs: &StreamPrefs,
+    ) -> crate::Result<StreamInstructions> {
+        let instructions = addr.into_stream_instructions(&self.addrcfg.get(), prefs);
+        Ok(self.onion_only.note_stream(instructions)?)
+    }
+
+    /// Get or launch a circuit suitable for a stream following `instructions`.
+    pub(crate) async fn get_or_launch_stream_circ(
+        &self,
+        instructions: &StreamInstructions,
+        prefs: &StreamPrefs,
+    ) -> crate::Result<Arc<ClientCirc>> {
+        let circ = match instructions {
+            StreamInstructions::Exit { hostname, port } => {
+                let exit_ports = [prefs.wrap_target_port(hostname, *port)];
                 let circ = self
                     .get_or_launch_exit_circ(&exit_ports, prefs)
                     .await
                     .map_err(wrap_err)?;
-                debug!("Got a circuit for {}:{}", sensitive(&addr), port);
-                (circ, addr, port)
+                debug!("Got a circuit for {}:{}", sensitive(hostname), port);
+                circ
             }
 
             #[cfg(not(feature = "onion-service-client"))]
-            #[allow(unused_variables)] // for hostname and port
-            StreamInstructions::Hs {
-                hsid,
-                hostname,
-                port,
-            } => void::unreachable(hsid.0),
+            StreamInstructions::Hs { hsid, .. } => void::unreachable(hsid.0),
 
             #[cfg(feature = "onion-service-client")]
-            StreamInstructions::Hs {
-                hsid,
-                hostname,
-                port,
-            } => {
+            StreamInstructions::Hs { hsid, .. } => {
+                let hsid = *hsid;
                 self.wait_for_bootstrap().await?;
                 let netdir = self.netdir(Timeliness::Timely, "connect to a hidden service")?;
 
@@ -1386,8 +1917,7 @@ impl<R: Runtime> TorClient<R> {
                     .build()
                     .map_err(ErrorDetail::Configuration)?;
 
-                let circ = self
-                    .hsclient
+                self.hsclient
                     .get_or_launch_circuit(
                         &netdir,
                         hsid,
@@ -1398,7 +1928,25 @@ impl<R: Runtime> TorClient<R> {
                     .map_err(|cause| ErrorDetail::ObtainHsCircuit {
                         cause,
                         hsid: hsid.into(),
-                    })?;
+                    })?
+            }
+        };
+
+        Ok(circ)
+    }
+
+    /// Begin a data stream on `circ`, following `instructions`.
+    pub(crate) async fn begin_data_stream(
+        &self,
+        circ: &Arc<ClientCirc>,
+        instructions: &StreamInstructions,
+        prefs: &StreamPrefs,
+    ) -> crate::Result<DataStream> {
+        let mut stream_parameters = prefs.stream_parameters();
+
+        let (addr, port) = match instructions {
+            StreamInstructions::Exit { hostname, port } => (hostname, *port),
+            StreamInstructions::Hs { hostname, port, .. } => {
                 // On connections to onion services, we have to suppress
                 // everything except the port from the BEGIN message.  We also
                 // disable optimistic data.
@@ -1406,11 +1954,12 @@ impl<R: Runtime> TorClient<R> {
                     .suppress_hostname()
                     .suppress_begin_flags()
                     .optimistic(false);
-                (circ, hostname, port)
+                (hostname, *port)
             }
         };
+        let rotation_limits = self.stream_rotation.limits_for(addr, prefs.rotation_limits);
 
-        let stream_future = circ.begin_stream(&addr, port, Some(stream_parameters));
+        let stream_future = circ.begin_stream(addr, port, Some(stream_parameters));
         // This timeout is needless but harmless for optimistic streams.
         let stream = self
             .runtime
@@ -1421,10 +1970,107 @@ impl<R: Runtime> TorClient<R> {
                 cause,
                 kind: "data",
             })?;
+        self.stream_rotation
+            .note_stream(circ, rotation_limits, self.runtime.now());
 
         Ok(stream)
     }
 
+    /// Open an anonymized UDP "connection" to the provided address and
+    /// port over the Tor network.
+    ///
+    /// As with [`TorClient::connect()`], you should pass a hostname rather
+    /// than an IP address where possible.
+    ///
+    /// This only works with exits that support UDP (proposal 339), and
+    /// we don't yet know how to pick those, so expect this to fail
+    /// unless you are testing against such an exit.
+    /// UDP to onion services is not supported.
+    ///
+    /// See the [`udp`](crate::udp) module for how to use the result.
+    #[cfg(feature = "experimental-udp")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "experimental-udp")))]
+    pub async fn connect_udp<A: IntoTorAddr>(
+        &self,
+        target: A,
+    ) -> crate::Result<crate::udp::TorDatagramSocket> {
+        self.connect_udp_with_prefs(target, &self.connect_prefs)
+            .await
+    }
+
+    /// Open an anonymized UDP "connection" to the provided address and
+    /// port over the Tor network, with explicit connection preferences.
+    ///
+    /// See [`TorClient::connect_udp()`] for more information.
+    #[cfg(feature = "experimental-udp")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "experimental-udp")))]
+    pub async fn connect_udp_with_prefs<A: IntoTorAddr>(
+        &self,
+        target: A,
+        prefs: &StreamPrefs,
+    ) -> crate::Result<crate::udp::TorDatagramSocket> {
+        let addr = target.into_tor_addr().map_err(wrap_err)?;
+
+        let (addr, port) = match self.stream_instructions(addr, prefs)? {
+            StreamInstructions::Exit {
+                hostname: addr,
+                port,
+            } => (addr, port),
+            StreamInstructions::Hs { .. } => {
+                return Err(ErrorDetail::OnionServiceUdpNotSupported.into())
+            }
+        };
+
+        // TODO: Exit policies only talk about TCP ports, so this
+        // may pick an exit that refuses our UDP traffic.
+        let exit_ports = [prefs.wrap_target_port(&addr, port)];
+        let circ = self
+            .get_or_launch_exit_circ(&exit_ports, prefs)
+            .await
+            .map_err(wrap_err)?;
+        debug!("Got a circuit for UDP to {}:{}", sensitive(&addr), port);
+
+        let stream_future = circ.begin_udp_stream(&addr, port, Some(prefs.stream_parameters()));
+        let stream = self
+            .runtime
+            .timeout(self.timeoutcfg.get().connect_timeout, stream_future)
+            .await
+            .map_err(|_| ErrorDetail::ExitTimeout)?
+            .map_err(|cause| ErrorDetail::StreamFailed { cause, kind: "udp" })?;
+
+        Ok(crate::udp::TorDatagramSocket::new(stream))
+    }
+
+    /// Open a connection to an onion service that reconnects after
+    /// brief network outages.
+    ///
+    /// Returns the stream, and a receiver for [`ResumeEvent`](crate::resume::ResumeEvent)s
+    /// describing reconnections.
+    /// This only works with onion services that follow the convention
+    /// described in the [`resume`](crate::resume) module.
+    #[cfg(all(feature = "onion-service-client", feature = "experimental-api"))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(all(feature = "onion-service-client", feature = "experimental-api")))
+    )]
+    pub async fn connect_resumable<A: IntoTorAddr>(
+        &self,
+        target: A,
+        config: crate::resume::ResumeConfig,
+    ) -> crate::Result<(
+        crate::resume::ResumableStream<R>,
+        futures::channel::mpsc::UnboundedReceiver<crate::resume::ResumeEvent>,
+    )> {
+        let addr = target.into_tor_addr().map_err(wrap_err)?;
+        crate::resume::ResumableStream::connect(
+            self.clone(),
+            addr,
+            self.connect_prefs.clone(),
+            config,
+        )
+        .await
+    }
+
     /// Sets the default preferences for future connections made with this client.
     ///
     /// The preferences set with this function will be inherited by clones of this client, but
@@ -1464,8 +2110,20 @@ impl<R: Runtime> TorClient<R> {
         // should be a method on `Host`, not `TorAddr`.  -Diziet.
         let addr = (hostname, 1).into_tor_addr().map_err(wrap_err)?;
 
-        match addr.into_resolve_instructions(&self.addrcfg.get(), prefs)? {
+        let instructions = addr.into_resolve_instructions(&self.addrcfg.get(), prefs);
+        match self.onion_only.note_resolve(instructions)? {
             ResolveInstructions::Exit(hostname) => {
+                // A fresh isolation token is made for every request, so
+                // nothing could ever use what we would cache.
+                let cacheable = !matches!(prefs.isolation, StreamIsolationPreference::EveryStream);
+                let isolation = self.isolation(prefs);
+                if let Some(addrs) = self
+                    .dns_cache
+                    .get(&hostname, &isolation, self.runtime.now())
+                {
+                    return Ok(addrs);
+                }
+
                 let circ = self.get_or_launch_exit_circ(&[], prefs).await?;
 
                 let resolve_future = circ.resolve(&hostname);
@@ -1479,6 +2137,11 @@ impl<R: Runtime> TorClient<R> {
                         kind: "DNS lookup",
                     })?;
 
+                if cacheable {
+                    self.dns_cache
+                        .insert(&hostname, isolation, addrs.clone(), self.runtime.now());
+                }
+
                 Ok(addrs)
             }
             ResolveInstructions::Return(addrs) => Ok(addrs),
@@ -1500,6 +2163,12 @@ impl<R: Runtime> TorClient<R> {
         addr: IpAddr,
         prefs: &StreamPrefs,
     ) -> crate::Result<Vec<String>> {
+        if self.addrcfg.get().onion_only {
+            return Err(self
+                .onion_only
+                .note_resolve(Err(ErrorDetail::ClearnetAddressForbidden))?);
+        }
+
         let circ = self.get_or_launch_exit_circ(&[], prefs).await?;
 
         let resolve_ptr_future = circ.resolve_ptr(addr);
@@ -1537,6 +2206,18 @@ impl<R: Runtime> TorClient<R> {
         &self.circmgr
     }
 
+    /// Return a report on how diverse this client's guards are.
+    ///
+    /// See [`GuardDiversityConfig`](crate::config::guards::GuardDiversityConfig)
+    /// for how to configure limits on this.
+    ///
+    /// This function is unstable. It is only enabled if the crate was
+    /// built with the `experimental-api` feature.
+    #[cfg(feature = "experimental-api")]
+    pub fn guard_diversity_report(&self) -> tor_guardmgr::GuardDiversityReport {
+        self.guardmgr.diversity_report()
+    }
+
     /// Return a reference to this client's channel manager.
     ///
     /// This function is unstable. It is only enabled if the crate was
@@ -1931,7 +2612,7 @@ impl<R: Runtime> TorClient<R> {
         config: &TorClientConfig,
         svc_config: tor_hsservice::OnionServiceConfig,
     ) -> crate::Result<tor_hsservice::OnionService> {
-        let inert_client = InertTorClient::new(config)?;
+        let inert_client = InertTorClient::new(config, &KeystoreUnlock::default())?;
         let keymgr = inert_client.keymgr.ok_or(ErrorDetail::KeystoreRequired {
             action: "create onion service",
         })?;
@@ -1966,6 +2647,114 @@ impl<R: Runtime> TorClient<R> {
         self.status_receiver.clone()
     }
 
+    /// Return the addresses that relays have told this client
+    /// they see its connections coming from, most recently reported first.
+    ///
+    /// Relays report these addresses when we open channels to them.
+    /// This can help you find out which address your guard sees you at,
+    /// for example if you're behind a NAT.
+    ///
+    /// These reports are only as trustworthy as the relays that made them:
+    /// a relay can be mistaken, or lie.
+    /// Channels made through a pluggable transport or a proxy aren't included,
+    /// since their relays see the transport or the proxy rather than us.
+    /// See [`ExternalAddr`](crate::ExternalAddr) for more information.
+    ///
+    /// These addresses can identify you:
+    /// be careful about where you display or log them.
+    pub fn external_addrs(&self) -> Vec<crate::ExternalAddr> {
+        self.chanmgr.external_addrs()
+    }
+
+    /// Return how many requests this client has rejected
+    /// because of the `address_filter.onion_only` setting.
+    ///
+    /// A nonzero count means that something tried to reach an address
+    /// that isn't an onion service: this may be worth investigating.
+    /// See [`ClientAddrConfig`](crate::config::ClientAddrConfig).
+    pub fn onion_only_rejections(&self) -> crate::OnionOnlyRejections {
+        self.onion_only.get()
+    }
+
+    /// Return a list of the problems that this client has noticed,
+    /// and that haven't gone away yet.
+    ///
+    /// Each kind of problem appears at most once, no matter how often it was noticed:
+    /// see [`health`](crate::health) for more information.
+    pub fn health_warnings(&self) -> Vec<health::HealthWarning> {
+        #[cfg(feature = "keymgr")]
+        if let Some(keymgr) = &self.inert_client.keymgr {
+            let locked = keymgr.locked_keystores();
+            if locked.is_empty() {
+                self.health.clear(health::HealthWarningId::KeystoreLocked);
+            } else {
+                let locked: Vec<_> = locked.iter().map(|id| id.to_string()).collect();
+                let message = format!("Keystore locked: {}", locked.join(", "));
+                self.health.raise(
+                    health::HealthWarningId::KeystoreLocked,
+                    message,
+                    self.runtime.now(),
+                    self.runtime.wallclock(),
+                );
+            }
+        }
+
+        self.health.warnings()
+    }
+
+    /// Wait until the client has reached the specified bootstrap `milestone`,
+    /// or until `timeout` has elapsed.
+    ///
+    /// Returns immediately if the milestone has already been reached.
+    ///
+    /// This function does not itself start bootstrapping:
+    /// see [`bootstrap`](TorClient::bootstrap).
+    ///
+    /// # Failures
+    ///
+    /// Returns an error of kind [`TorNetworkTimeout`](crate::ErrorKind::TorNetworkTimeout)
+    /// if the milestone is not reached within `timeout`.
+    pub async fn wait_for(
+        &self,
+        milestone: status::BootstrapMilestone,
+        timeout: Duration,
+    ) -> crate::Result<()> {
+        let mut events = self.bootstrap_events();
+        let wait = async {
+            while let Some(status) = events.next().await {
+                if status.reached(milestone) {
+                    return Ok(());
+                }
+            }
+            Err(ErrorDetail::from(internal!(
+                "bootstrap status stream ended"
+            )))
+        };
+
+        self.runtime
+            .timeout(timeout, wait)
+            .await
+            .map_err(|_| ErrorDetail::BootstrapTimeout { milestone })?
+            .map_err(Into::into)
+    }
+
+    /// Blocking version of [`wait_for`](TorClient::wait_for).
+    ///
+    /// This is intended for callers (such as FFI bindings) that have no async runtime
+    /// of their own.
+    ///
+    /// # Panics
+    ///
+    /// Like [`BlockOn::block_on`](tor_rtcompat::BlockOn::block_on),
+    /// this may panic if called from within an asynchronous context.
+    pub fn wait_for_blocking(
+        &self,
+        milestone: status::BootstrapMilestone,
+        timeout: Duration,
+    ) -> crate::Result<()> {
+        self.runtime.block_on(self.wait_for(milestone, timeout))
+    }
+
     /// Change the client's current dormant mode, putting background tasks to sleep
     /// or waking them up as appropriate.
     ///
@@ -2033,6 +2822,39 @@ async fn tasks_monitor_dormant<R: Runtime>(
     }
 }
 
+/// How often we look for expired entries in the keystores.
+#[cfg(feature = "keymgr")]
+const EXPIRED_KEY_SWEEP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Periodically remove the expired entries from the keystores of `keymgr`.
+///
+/// See [`KeyMgr::set_expiry`].
+///
+/// This function is spawned as a task during client construction,
+/// and exits once the key manager is dropped.
+#[cfg(feature = "keymgr")]
+async fn sweep_expired_keys<R: Runtime>(runtime: R, keymgr: std::sync::Weak<KeyMgr>) {
+    loop {
+        let Some(mgr) = keymgr.upgrade() else {
+            break;
+        };
+        let result = mgr.sweep_expired(runtime.wallclock(), |entry, expires| {
+            info!(
+                "Removing key {} from keystore {}: it expired at {}",
+                entry.key_path(),
+                entry.keystore_id(),
+                humantime::format_rfc3339_seconds(expires),
+            );
+        });
+        if let Err(e) = result {
+            warn_report!(e, "Failed to remove expired keys");
+        }
+        drop(mgr);
+
+        runtime.sleep(EXPIRED_KEY_SWEEP_INTERVAL).await;
+    }
+}
+
 /// Alias for TorError::from(Error)
 pub(crate) fn wrap_err<T>(err: T) -> crate::Error
 where
@@ -2079,6 +2901,40 @@ mod test {
         });
     }
 
+    #[test]
+    fn snapshot_restore() {
+        tor_rtcompat::test_with_one_runtime!(|rt| async move {
+            let dirs: Vec<_> = (0..4).map(|_| tempfile::tempdir().unwrap()).collect();
+            let make_client = |state_dir, cache_dir| {
+                let cfg = TorClientConfigBuilder::from_directories(state_dir, cache_dir)
+                    .build()
+                    .unwrap();
+                TorClient::with_runtime(rt.clone())
+                    .config(cfg)
+                    .bootstrap_behavior(BootstrapBehavior::Manual)
+                    .create_unbootstrapped()
+                    .unwrap()
+            };
+            let client1 = make_client(&dirs[0], &dirs[1]);
+            let client2 = make_client(&dirs[2], &dirs[3]);
+
+            let snapshot = client1.snapshot_state().unwrap();
+            client2.restore_state(&snapshot).unwrap();
+            let parsed1: StateSnapshot = serde_json::from_slice(&snapshot).unwrap();
+            let parsed2: StateSnapshot =
+                serde_json::from_slice(&client2.snapshot_state().unwrap()).unwrap();
+            assert_eq!(parsed1.state, parsed2.state);
+            assert!(parsed1.state.contains_key("guards"));
+
+            let err = client2.restore_state(b"{}").unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::BadApiUsage);
+            let err = client2
+                .restore_state(br#"{"version":99,"state":{},"directory":{}}"#)
+                .unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::BadApiUsage);
+        });
+    }
+
     #[test]
     fn unbootstrapped_client_unusable() {
         tor_rtcompat::test_with_one_runtime!(|rt| async {
@@ -2098,6 +2954,53 @@ mod test {
         });
     }
 
+    #[test]
+    fn unbootstrapped_client_wait_for_times_out() {
+        tor_rtcompat::test_with_one_runtime!(|rt| async {
+            let state_dir = tempfile::tempdir().unwrap();
+            let cache_dir = tempfile::tempdir().unwrap();
+            let cfg = TorClientConfigBuilder::from_directories(state_dir, cache_dir)
+                .build()
+                .unwrap();
+            let client = TorClient::with_runtime(rt)
+                .config(cfg)
+                .bootstrap_behavior(BootstrapBehavior::Manual)
+                .create_unbootstrapped()
+                .unwrap();
+            let err = client
+                .wait_for(
+                    status::BootstrapMilestone::DirectoryLoaded,
+                    Duration::from_millis(10),
+                )
+                .await
+                .unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::TorNetworkTimeout);
+        });
+    }
+
+    #[test]
+    #[cfg(all(feature = "onion-service-client", feature = "experimental-api"))]
+    fn resumable_requires_onion() {
+        tor_rtcompat::test_with_one_runtime!(|rt| async {
+            let state_dir = tempfile::tempdir().unwrap();
+            let cache_dir = tempfile::tempdir().unwrap();
+            let cfg = TorClientConfigBuilder::from_directories(state_dir, cache_dir)
+                .build()
+                .unwrap();
+            let client = TorClient::with_runtime(rt)
+                .config(cfg)
+                .bootstrap_behavior(BootstrapBehavior::Manual)
+                .create_unbootstrapped()
+                .unwrap();
+            let err = client
+                .connect_resumable(("example.com", 443), Default::default())
+                .await
+                .err()
+                .unwrap();
+            assert_eq!(err.kind(), ErrorKind::BadApiUsage);
+        });
+    }
+
     #[test]
     fn streamprefs_isolate_every_stream() {
         let mut observed = StreamPrefs::new();
@@ -2151,6 +3054,42 @@ mod test {
         assert_eq!(observed.ip_ver_pref, IpVersionPreference::Ipv4Only);
     }
 
+    #[test]
+    fn streamprefs_ip_version() {
+        let mut observed = StreamPrefs::new();
+        observed.ip_version(IpVersionPreference::Ipv6Only);
+        assert_eq!(observed.ip_ver_pref, IpVersionPreference::Ipv6Only);
+        assert!(observed.allows_ip_version(&"::1".parse().unwrap()));
+        assert!(!observed.allows_ip_version(&"127.0.0.1".parse().unwrap()));
+        assert_eq!(
+            observed.wrap_target_port("www.example.com", 443),
+            TargetPort::ipv6(443)
+        );
+        assert_eq!(
+            observed.wrap_target_port("2001:db8::1", 443),
+            TargetPort::ipv6(443)
+        );
+
+        observed.ip_version(IpVersionPreference::Ipv4Preferred);
+        assert!(observed.allows_ip_version(&"::1".parse().unwrap()));
+        assert!(observed.allows_ip_version(&"127.0.0.1".parse().unwrap()));
+        assert_eq!(
+            observed.wrap_target_port("www.example.com", 443),
+            TargetPort::ipv4(443)
+        );
+        assert_eq!(
+            observed.wrap_target_port("2001:db8::1", 443),
+            TargetPort::ipv6(443)
+        );
+
+        observed.ip_version(IpVersionPreference::Ipv4Only);
+        assert!(!observed.allows_ip_version(&"::1".parse().unwrap()));
+        assert_eq!(
+            observed.wrap_target_port("192.0.2.1", 443),
+            TargetPort::ipv4(443)
+        );
+    }
+
     #[test]
     fn streamprefs_ipv4_preferred() {
         let mut observed = StreamPrefs::new();
@@ -2175,6 +3114,37 @@ mod test {
         };
     }
 
+    #[test]
+    fn streamprefs_derived_isolation() {
+        let mut parent = StreamPrefs::new();
+        parent.set_isolation(IsolationLineage::new());
+
+        let mut shared = StreamPrefs::new();
+        shared.inherit_isolation(&parent);
+        let mut nested_1 = StreamPrefs::new();
+        nested_1.nest_isolation(&parent);
+        let mut nested_2 = StreamPrefs::new();
+        nested_2.nest_isolation(&parent);
+
+        let parent_isol = parent.prefs_isolation().unwrap();
+        let shared_isol = shared.prefs_isolation().unwrap();
+        let nested_1_isol = nested_1.prefs_isolation().unwrap();
+        let nested_2_isol = nested_2.prefs_isolation().unwrap();
+        assert!(parent_isol.compatible(shared_isol.as_ref()));
+        assert!(parent_isol.compatible(nested_1_isol.as_ref()));
+        assert!(parent_isol.compatible(nested_2_isol.as_ref()));
+        assert!(!nested_1_isol.compatible(nested_2_isol.as_ref()));
+
+        // Without a lineage, nesting just makes a new isolation group.
+        let plain = StreamPrefs::new();
+        let mut nested_plain = StreamPrefs::new();
+        nested_plain.nest_isolation(&plain);
+        match nested_plain.isolation {
+            StreamIsolationPreference::Explicit(_) => (),
+            _ => panic!("unexpected isolation: {:?}", nested_plain.isolation),
+        };
+    }
+
     #[test]
     fn reconfigure_all_or_nothing() {
         tor_rtcompat::test_with_one_runtime!(|rt| async {
@@ -29,7 +29,13 @@ pub use tor_guardmgr::bridge::BridgeConfigBuilder;
 pub use tor_guardmgr::bridge::BridgeParseError;
 
 use tor_guardmgr::bridge::BridgeConfig;
-use tor_keymgr::config::{ArtiKeystoreConfig, ArtiKeystoreConfigBuilder};
+use tor_keymgr::config::{ArtiKeystoreConfig, ArtiKeystoreConfigBuilder, ArtiKeystoreKind};
+
+pub mod distro;
+pub mod preset;
+#[cfg(feature = "bridge-client")]
+#[cfg_attr(docsrs, doc(cfg(feature = "bridge-client")))]
+pub mod torrc;
 
 /// Types for configuring how Tor circuits are built.
 pub mod circ {
@@ -37,14 +43,18 @@ pub mod circ {
         CircMgrConfig, CircuitTiming, CircuitTimingBuilder, PathConfig, PathConfigBuilder,
         PreemptiveCircuitConfig, PreemptiveCircuitConfigBuilder,
     };
+
+    #[cfg(feature = "hs-endpoint-restrictions")]
+    pub use tor_circmgr::hspool::{HsEndpointConfig, HsEndpointConfigBuilder};
 }
 
 /// Types for configuring how Tor accesses its directory information.
 pub mod dir {
     pub use tor_dirmgr::{
-        Authority, AuthorityBuilder, DirMgrConfig, DirTolerance, DirToleranceBuilder,
-        DownloadSchedule, DownloadScheduleConfig, DownloadScheduleConfigBuilder, FallbackDir,
-        FallbackDirBuilder, NetworkConfig, NetworkConfigBuilder,
+        Authority, AuthorityBuilder, ConsensusTrustConfig, ConsensusTrustConfigBuilder,
+        DirMgrConfig, DirTolerance, DirToleranceBuilder, DownloadSchedule, DownloadScheduleConfig,
+        DownloadScheduleConfigBuilder, FallbackDir, FallbackDirBuilder, NetworkConfig,
+        NetworkConfigBuilder,
     };
 }
 
@@ -65,6 +75,11 @@ pub mod vanguards {
     pub use tor_guardmgr::{VanguardConfig, VanguardConfigBuilder};
 }
 
+/// Types for configuring how we choose guards.
+pub mod guards {
+    pub use tor_guardmgr::{GuardDiversityConfig, GuardDiversityConfigBuilder};
+}
+
 /// Configuration for client behavior relating to addresses.
 ///
 /// This type is immutable once constructed. To create an object of this type,
@@ -86,10 +101,27 @@ pub struct ClientAddrConfig {
 
     /// Should we allow attempts to connect to hidden services (`.onion` services)?
     ///
-    /// This option is on by default.
+    /// This option is on by default
+    /// (unless overridden at build time; see [`distro`]).
     #[cfg(feature = "onion-service-client")]
-    #[builder(default = "true")]
+    #[builder(default = "distro::allow_onion_addrs()")]
     pub(crate) allow_onion_addrs: bool,
+
+    /// Should we reject every request for an address that isn't an onion service?
+    ///
+    /// If this is set, the client refuses all connections and DNS lookups
+    /// for hostnames and IP addresses (including reverse lookups),
+    /// before building or using any circuit:
+    /// no traffic is ever sent through an exit relay.
+    /// Such requests fail with [`ErrorKind::ForbiddenClearnetTarget`](crate::ErrorKind::ForbiddenClearnetTarget),
+    /// and are counted in [`TorClient::onion_only_rejections`](crate::TorClient::onion_only_rejections).
+    ///
+    /// This takes precedence over every other setting, including [`StreamPrefs`](crate::StreamPrefs).
+    /// It is meant for applications that must never touch the public internet.
+    ///
+    /// This option is off by default.
+    #[builder(default)]
+    pub(crate) onion_only: bool,
 }
 impl_standard_builder! { ClientAddrConfig }
 
@@ -140,12 +172,196 @@ fn default_dns_resolve_ptr_timeout() -> Duration {
     Duration::new(10, 0)
 }
 
+/// Configuration for caching the results of DNS lookups made via the Tor network.
+///
+/// This type is immutable once constructed. To create an object of this type,
+/// use [`DnsCacheConfigBuilder`].
+///
+/// Cached results are only reused by requests that would have been allowed
+/// to share a circuit with the request that made the original lookup.
+/// The cache is emptied whenever we retire all our circuits.
+///
+/// You can replace this configuration on a running Arti client.  Doing so will
+/// affect new requests.  Disabling the cache discards everything in it.
+#[derive(Debug, Clone, Builder, Eq, PartialEq)]
+#[builder(build_fn(error = "ConfigBuildError"))]
+#[builder(derive(Debug, Serialize, Deserialize))]
+#[non_exhaustive]
+pub struct DnsCacheConfig {
+    /// Should we cache the results of DNS lookups at all?
+    ///
+    /// Off by default: a cached answer reveals that somebody looked up
+    /// the same name earlier, which can link activity that would otherwise
+    /// look unrelated.
+    #[builder(default)]
+    pub(crate) enabled: bool,
+
+    /// How long should we keep a cached result?
+    ///
+    /// Exits do not tell us reliably how long their answers are good for,
+    /// so every result is kept for this long, and no longer.
+    #[builder(default = "default_dns_cache_ttl()")]
+    #[builder_field_attr(serde(default, with = "humantime_serde::option"))]
+    pub(crate) ttl: Duration,
+
+    /// How many results should we cache at most?
+    #[builder(default = "default_dns_cache_max_entries()")]
+    pub(crate) max_entries: usize,
+}
+impl_standard_builder! { DnsCacheConfig }
+
+/// Return the default lifetime of a cached DNS result
+fn default_dns_cache_ttl() -> Duration {
+    Duration::new(60, 0)
+}
+
+/// Return the default maximum number of cached DNS results
+fn default_dns_cache_max_entries() -> usize {
+    256
+}
+
+/// Configuration for closing circuits, and the streams on them,
+/// once they have been in use for too long.
+///
+/// This type is immutable once constructed. To create an object of this type,
+/// use [`StreamRotationConfigBuilder`].
+///
+/// By default, Arti keeps using a circuit for as long as it is usable,
+/// and a stream stays open for as long as the application wants it.
+/// Deployments with strict unlinkability requirements can instead force
+/// rotation: once a circuit reaches its maximum lifetime, or has gone
+/// too long without a new stream, Arti closes it, together with every stream on it,
+/// and later streams use a fresh circuit.
+/// See [`TorClient::stream_rotation_events`](crate::TorClient::stream_rotation_events)
+/// to find out when this happens.
+///
+/// A duration of zero means "no limit".
+/// The limits can be overridden for particular destinations, with `rules`,
+/// and for particular streams, with
+/// [`StreamPrefs::rotation_limits`](crate::StreamPrefs::rotation_limits).
+///
+/// You can replace this configuration on a running Arti client.  Doing so will
+/// affect streams opened afterwards.
+#[derive(Debug, Clone, Builder, Eq, PartialEq)]
+#[builder(build_fn(error = "ConfigBuildError"))]
+#[builder(derive(Debug, Serialize, Deserialize))]
+#[non_exhaustive]
+pub struct StreamRotationConfig {
+    /// How long may a circuit be used, after its first stream was opened,
+    /// before we close it?
+    #[builder(default)]
+    #[builder_field_attr(serde(default, with = "humantime_serde::option"))]
+    pub(crate) max_lifetime: Duration,
+
+    /// How long may a circuit go without a new stream being opened on it,
+    /// before we close it?
+    ///
+    /// This is not an idle timeout: we don't measure whether the application
+    /// is still sending data on its streams, so a busy long-lived stream
+    /// doesn't keep its circuit open.
+    #[builder(default)]
+    #[builder_field_attr(serde(default, with = "humantime_serde::option"))]
+    pub(crate) max_since_last_stream: Duration,
+
+    /// Limits for particular destinations, which replace the ones above.
+    ///
+    /// For each stream, the first rule whose pattern matches its destination is used.
+    #[builder(sub_builder, setter(custom))]
+    #[builder_field_attr(serde(default))]
+    pub(crate) rules: StreamRotationRuleList,
+}
+impl_standard_builder! { StreamRotationConfig }
+
+/// A list of stream rotation rules (type alias for macrology).
+type StreamRotationRuleList = Vec<StreamRotationRule>;
+
+define_list_builder_helper! {
+    pub struct StreamRotationRuleListBuilder {
+        rules: [StreamRotationRuleBuilder],
+    }
+    built: StreamRotationRuleList = rules;
+    default = vec![];
+}
+
+define_list_builder_accessors! {
+    struct StreamRotationConfigBuilder {
+        pub rules: [StreamRotationRuleBuilder],
+    }
+}
+
+/// Stream rotation limits for the destinations that match a pattern.
+///
+/// To create an object of this type, use [`StreamRotationRuleBuilder`].
+#[derive(Debug, Clone, Builder, Eq, PartialEq)]
+#[builder(build_fn(validate = "Self::validate", error = "ConfigBuildError"))]
+#[builder(derive(Debug, Serialize, Deserialize))]
+#[non_exhaustive]
+pub struct StreamRotationRule {
+    /// Which destinations does this rule apply to?
+    ///
+    /// This is either a hostname (such as `example.com`),
+    /// a wildcard for a domain and everything beneath it (such as `*.example.com`),
+    /// or `*`, which matches every destination.
+    /// Matching ignores case.
+    #[builder(setter(into))]
+    pub(crate) pattern: String,
+
+    /// How long may a circuit to these destinations be used
+    /// before we close it?  Zero means "no limit".
+    #[builder(default)]
+    #[builder_field_attr(serde(default, with = "humantime_serde::option"))]
+    pub(crate) max_lifetime: Duration,
+
+    /// How long may a circuit to these destinations go without a new stream
+    /// before we close it?  Zero means "no limit".
+    #[builder(default)]
+    #[builder_field_attr(serde(default, with = "humantime_serde::option"))]
+    pub(crate) max_since_last_stream: Duration,
+}
+impl_standard_builder! { StreamRotationRule: !Default }
+
+impl StreamRotationRule {
+    /// Return true if this rule applies to streams to `hostname`.
+    pub(crate) fn matches(&self, hostname: &str) -> bool {
+        if self.pattern == "*" {
+            return true;
+        }
+        let pattern = self.pattern.to_ascii_lowercase();
+        let hostname = hostname.trim_end_matches('.').to_ascii_lowercase();
+        match pattern.strip_prefix("*.") {
+            Some(domain) => hostname == domain || hostname.ends_with(&format!(".{}", domain)),
+            None => hostname == pattern,
+        }
+    }
+}
+
+impl StreamRotationRuleBuilder {
+    /// Check that the pattern is one that we know how to match.
+    fn validate(&self) -> Result<(), ConfigBuildError> {
+        let Some(pattern) = &self.pattern else {
+            return Ok(());
+        };
+        let name = pattern.strip_prefix("*.").unwrap_or(pattern);
+        if pattern != "*" && (name.is_empty() || name.contains('*')) {
+            return Err(ConfigBuildError::Invalid {
+                field: "pattern".into(),
+                problem: format!(
+                    "{:?} is not a hostname, `*.` followed by a domain, or `*`",
+                    pattern
+                ),
+            });
+        }
+        Ok(())
+    }
+}
+
 /// Configuration for where information should be stored on disk.
 ///
 /// By default, cache information will be stored in `${ARTI_CACHE}`, and
 /// persistent state will be stored in `${ARTI_LOCAL_DATA}`.  That means that
 /// _all_ programs using these defaults will share their cache and state data.
 /// If that isn't what you want,  you'll need to override these directories.
+/// (Distributors can also change these defaults at build time; see [`distro`].)
 ///
 /// On unix, the default directories will typically expand to `~/.cache/arti`
 /// and `~/.local/share/arti/` respectively, depending on the user's
@@ -198,12 +414,12 @@ impl_standard_builder! { StorageConfig }
 
 /// Return the default cache directory.
 fn default_cache_dir() -> CfgPath {
-    CfgPath::new("${ARTI_CACHE}".to_owned())
+    distro::cache_dir()
 }
 
 /// Return the default state directory.
 fn default_state_dir() -> CfgPath {
-    CfgPath::new("${ARTI_LOCAL_DATA}".to_owned())
+    distro::state_dir()
 }
 
 /// Macro to avoid repeating code for `expand_*_dir` functions on StorageConfig
@@ -495,7 +711,7 @@ define_list_builder_helper! {
         bridges: [BridgeConfigBuilder],
     }
     built: BridgeList = bridges;
-    default = vec![];
+    default = distro::bridges();
     #[serde(try_from="MultilineListBuilder<BridgeConfigBuilder>")]
     #[serde(into="MultilineListBuilder<BridgeConfigBuilder>")]
 }
@@ -531,7 +747,7 @@ define_list_builder_accessors! {
 /// Finally, you can get fine-grained control over the members of a a
 /// TorClientConfig using [`TorClientConfigBuilder`].
 #[derive(Clone, Builder, Debug, Eq, PartialEq, AsRef)]
-#[builder(build_fn(error = "ConfigBuildError"))]
+#[builder(build_fn(validate = "Self::validate", error = "ConfigBuildError"))]
 #[builder(derive(Serialize, Deserialize, Debug))]
 #[non_exhaustive]
 pub struct TorClientConfig {
@@ -619,10 +835,26 @@ pub struct TorClientConfig {
     #[builder_field_attr(serde(default))]
     pub(crate) stream_timeouts: StreamTimeoutConfig,
 
+    /// Information about caching DNS results.
+    #[builder(sub_builder)]
+    #[builder_field_attr(serde(default))]
+    pub(crate) dns_cache: DnsCacheConfig,
+
+    /// Information about closing circuits once they have been in use for too long.
+    #[builder(sub_builder)]
+    #[builder_field_attr(serde(default))]
+    pub(crate) stream_rotation: StreamRotationConfig,
+
     /// Information about vanguards.
     #[builder(sub_builder)]
     #[builder_field_attr(serde(default))]
     pub(crate) vanguards: vanguards::VanguardConfig,
+
+    /// Limits on how diverse our guards must be.
+    #[as_ref]
+    #[builder(sub_builder)]
+    #[builder_field_attr(serde(default))]
+    guard_diversity: guards::GuardDiversityConfig,
 }
 impl_standard_builder! { TorClientConfig }
 
@@ -746,6 +978,30 @@ impl TorClientConfig {
         self.storage.keystore()
     }
 
+    /// Return the directories in which Arti keeps its data on disk,
+    /// each with a short description.
+    ///
+    /// These are the state directory, the cache directory,
+    /// and (if we use a keystore on disk) the keystore directory inside the state directory.
+    /// Some of them may not exist yet.
+    pub fn storage_dirs(&self) -> StdResult<Vec<(&'static str, PathBuf)>, ConfigBuildError> {
+        let state_dir = self.storage.expand_state_dir()?;
+        let mut dirs = vec![
+            ("cache directory", self.storage.expand_cache_dir()?),
+            ("state directory", state_dir.clone()),
+        ];
+        let keystore_on_disk = match self.storage.keystore().primary_kind() {
+            None => false,
+            #[cfg(feature = "ephemeral-keystore")]
+            Some(ArtiKeystoreKind::Ephemeral) => false,
+            Some(_) => true,
+        };
+        if keystore_on_disk {
+            dirs.push(("keystore", state_dir.join("keystore")));
+        }
+        Ok(dirs)
+    }
+
     /// Get the state directory and its corresponding
     /// [`Mistrust`] configuration.
     pub(crate) fn state_dir(&self) -> StdResult<(PathBuf, &fs_mistrust::Mistrust), ErrorDetail> {
@@ -787,6 +1043,29 @@ impl TorClientConfigBuilder {
 
         builder
     }
+
+    /// Check that the sections of this builder are consistent with one another.
+    #[allow(clippy::unnecessary_wraps)]
+    fn validate(&self) -> Result<(), ConfigBuildError> {
+        // A private network must not be mixed with the public one.
+        // Bridges that a distributor bundled with Arti lead into the public network,
+        // so a private network must list its own bridges (if it uses any).
+        #[cfg(feature = "bridge-client")]
+        if self.tor_network.is_private_network()
+            && self.bridges.bridges.bridges.is_none()
+            && bridges_enabled(self.bridges.enabled.unwrap_or_default(), &distro::bridges())
+        {
+            return Err(ConfigBuildError::Inconsistent {
+                fields: ["tor_network.consensus_trust.private_network", "bridges.bridges"]
+                    .map(Into::into)
+                    .into_iter()
+                    .collect(),
+                problem: "A private network cannot use the bridges bundled with Arti, which lead into the public Tor network".into(),
+            });
+        }
+
+        Ok(())
+    }
 }
 
 /// Return the filenames for the default user configuration files
@@ -938,6 +1217,42 @@ mod test {
         );
     }
 
+    #[test]
+    fn private_network() {
+        use dir::{Authority, FallbackDir};
+
+        let mut bld = TorClientConfig::builder();
+        bld.tor_network().set_authorities(vec![Authority::builder()
+            .name("private")
+            .v3ident([b'?'; 20].into())
+            .clone()]);
+        bld.tor_network().set_fallback_caches(vec![{
+            let mut bld = FallbackDir::builder();
+            bld.rsa_identity([b'x'; 20].into())
+                .ed_identity([b'y'; 32].into());
+            bld.orports().push("192.0.2.1:99".parse().unwrap());
+            bld
+        }]);
+        bld.tor_network()
+            .consensus_trust()
+            .signature_threshold(1)
+            .private_network(true);
+        let cfg = bld.build().unwrap();
+        assert!(cfg.tor_network.consensus_trust().private_network());
+
+        // The network's own bridges are fine.
+        #[cfg(feature = "bridge-client")]
+        {
+            bld.bridges().bridges().push(
+                "192.0.2.83:80 $0bac39417268b96b9f514ef763fa6fba1a788956"
+                    .parse()
+                    .unwrap(),
+            );
+            let cfg = bld.build().unwrap();
+            assert_eq!(cfg.bridges.bridges.len(), 1);
+        }
+    }
+
     #[test]
     fn check_default() {
         // We don't want to second-guess the directories crate too much
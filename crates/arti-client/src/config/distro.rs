@@ -0,0 +1,213 @@
+//! Build-time overrides of configuration defaults, for downstream distributors.
+//!
+//! Packagers sometimes need Arti to use different defaults from the ones
+//! we ship: a different location for the cache or state directory, a set of
+//! bundled bridges, or a feature that is off unless the user turns it on.
+//! Rather than patching our source, they can set these environment variables
+//! when compiling `arti-client`:
+//!
+//!  * `ARTI_DISTRO_CACHE_DIR`: default for `storage.cache_dir`.
+//!  * `ARTI_DISTRO_STATE_DIR`: default for `storage.state_dir`.
+//!  * `ARTI_DISTRO_BRIDGES`: default for `bridges.bridges`,
+//!    as bridge lines separated by newlines or `;`.
+//!  * `ARTI_DISTRO_ALLOW_ONION_ADDRS`: default for `address_filter.allow_onion_addrs`,
+//!    as `true` or `false`.
+//!
+//! Path values are interpreted as [`CfgPath`]s, so they may use
+//! variables like `${ARTI_CACHE}`.
+//! An empty value is treated as though the variable were unset.
+//!
+//! These only change the _defaults_: values from configuration files,
+//! or set on a builder, still take precedence.
+//! Use [`overridden_defaults`] to find out which defaults were replaced.
+
+use tor_config::{CfgPath, ConfigBuildError};
+use tor_guardmgr::bridge::BridgeConfigBuilder;
+use tracing::warn;
+
+/// A configuration default that a distributor can replace at build time.
+///
+/// See the [module-level documentation](self) for details.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum DistroDefault {
+    /// The default for `storage.cache_dir`.
+    CacheDir,
+    /// The default for `storage.state_dir`.
+    StateDir,
+    /// The default list of bridges.
+    Bridges,
+    /// The default for `address_filter.allow_onion_addrs`.
+    AllowOnionAddrs,
+}
+
+/// Every [`DistroDefault`], in a fixed order.
+const ALL: [DistroDefault; 4] = [
+    DistroDefault::CacheDir,
+    DistroDefault::StateDir,
+    DistroDefault::Bridges,
+    DistroDefault::AllowOnionAddrs,
+];
+
+impl DistroDefault {
+    /// Return the name of the build-time environment variable that overrides this default.
+    pub fn env_var(self) -> &'static str {
+        match self {
+            DistroDefault::CacheDir => "ARTI_DISTRO_CACHE_DIR",
+            DistroDefault::StateDir => "ARTI_DISTRO_STATE_DIR",
+            DistroDefault::Bridges => "ARTI_DISTRO_BRIDGES",
+            DistroDefault::AllowOnionAddrs => "ARTI_DISTRO_ALLOW_ONION_ADDRS",
+        }
+    }
+
+    /// Return the value this default was given at build time, if any.
+    ///
+    /// (`option_env!` needs a literal, so we can't use `env_var` here.)
+    pub fn value(self) -> Option<&'static str> {
+        let v = match self {
+            DistroDefault::CacheDir => option_env!("ARTI_DISTRO_CACHE_DIR"),
+            DistroDefault::StateDir => option_env!("ARTI_DISTRO_STATE_DIR"),
+            DistroDefault::Bridges => option_env!("ARTI_DISTRO_BRIDGES"),
+            DistroDefault::AllowOnionAddrs => option_env!("ARTI_DISTRO_ALLOW_ONION_ADDRS"),
+        };
+        v.filter(|v| !v.trim().is_empty())
+    }
+
+    /// Return true if a distributor replaced this default at build time.
+    pub fn is_overridden(self) -> bool {
+        self.value().is_some()
+    }
+}
+
+/// Return every default that was replaced at build time.
+pub fn overridden_defaults() -> Vec<DistroDefault> {
+    ALL.into_iter().filter(|d| d.is_overridden()).collect()
+}
+
+/// Check that every build-time default is well-formed.
+///
+/// Malformed values are otherwise ignored (with a warning) in favour of our
+/// usual defaults, since there is no way to report an error from a default.
+/// Distributors should call this from their tests, or at startup.
+pub fn check() -> Result<(), ConfigBuildError> {
+    let invalid = |d: DistroDefault, problem: String| ConfigBuildError::Invalid {
+        field: d.env_var().to_owned(),
+        problem,
+    };
+    if let Some(v) = DistroDefault::AllowOnionAddrs.value() {
+        parse_bool(v).ok_or_else(|| {
+            invalid(
+                DistroDefault::AllowOnionAddrs,
+                format!("expected `true` or `false`, found {:?}", v),
+            )
+        })?;
+    }
+    for line in bridge_lines() {
+        line.parse::<BridgeConfigBuilder>()
+            .map_err(|e| invalid(DistroDefault::Bridges, format!("{:?}: {}", line, e)))?;
+    }
+    Ok(())
+}
+
+/// Parse a boolean build-time value.
+fn parse_bool(v: &str) -> Option<bool> {
+    match v.trim() {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// Return the non-empty bridge lines in `ARTI_DISTRO_BRIDGES`.
+fn bridge_lines() -> impl Iterator<Item = &'static str> {
+    DistroDefault::Bridges
+        .value()
+        .unwrap_or_default()
+        .split(['\n', ';'])
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+}
+
+/// Return the default cache directory.
+pub(crate) fn cache_dir() -> CfgPath {
+    let v = DistroDefault::CacheDir.value().unwrap_or("${ARTI_CACHE}");
+    CfgPath::new(v.to_owned())
+}
+
+/// Return the default state directory.
+pub(crate) fn state_dir() -> CfgPath {
+    let v = DistroDefault::StateDir
+        .value()
+        .unwrap_or("${ARTI_LOCAL_DATA}");
+    CfgPath::new(v.to_owned())
+}
+
+/// Return the default list of bridges.
+pub(crate) fn bridges() -> Vec<BridgeConfigBuilder> {
+    bridge_lines()
+        .filter_map(|line| match line.parse() {
+            Ok(b) => Some(b),
+            Err(e) => {
+                warn!("Ignoring malformed bridge in ARTI_DISTRO_BRIDGES: {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Return the default for `allow_onion_addrs`.
+#[cfg(feature = "onion-service-client")]
+pub(crate) fn allow_onion_addrs() -> bool {
+    match DistroDefault::AllowOnionAddrs.value() {
+        None => true,
+        Some(v) => parse_bool(v).unwrap_or_else(|| {
+            warn!("Ignoring malformed ARTI_DISTRO_ALLOW_ONION_ADDRS {:?}", v);
+            true
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+
+    #[test]
+    fn builtin_defaults_valid() {
+        // This is the test a distributor's build will trip over
+        // if they have supplied something malformed.
+        check().unwrap();
+    }
+
+    #[test]
+    fn overrides_consistent() {
+        for d in ALL {
+            assert_eq!(overridden_defaults().contains(&d), d.value().is_some());
+        }
+        if !DistroDefault::CacheDir.is_overridden() {
+            assert_eq!(cache_dir(), CfgPath::new("${ARTI_CACHE}".into()));
+        }
+        if !DistroDefault::Bridges.is_overridden() {
+            assert!(bridges().is_empty());
+        }
+    }
+
+    #[test]
+    fn bools() {
+        assert_eq!(parse_bool("true"), Some(true));
+        assert_eq!(parse_bool(" false "), Some(false));
+        assert_eq!(parse_bool("yes"), None);
+    }
+}
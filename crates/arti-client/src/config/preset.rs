@@ -0,0 +1,170 @@
+//! Named bundles of configuration options for common deployment profiles.
+//!
+//! A [`ConfigPreset`] adjusts a handful of related options at once,
+//! so that (for example) an application on a phone doesn't need to know
+//! every setting that affects battery and bandwidth use.
+//! Start from [`TorClientConfigBuilder::preset`], and then override
+//! any individual values as usual:
+//!
+//! ```
+//! use arti_client::config::{preset::ConfigPreset, TorClientConfigBuilder};
+//!
+//! let mut builder = TorClientConfigBuilder::preset(ConfigPreset::Mobile);
+//! builder.preemptive_circuits().disable_at_threshold(8);
+//! let config = builder.build()?;
+//! # Ok::<(), arti_client::config::ConfigBuildError>(())
+//! ```
+//!
+//! Presets only change configuration.  Dormancy is not a configuration
+//! option: applications that go idle should also use
+//! [`TorClient::set_dormant`](crate::TorClient::set_dormant).
+
+use std::time::Duration;
+
+use tor_config::PaddingLevel;
+
+use super::TorClientConfigBuilder;
+
+/// A named set of configuration options, for a common deployment profile.
+///
+/// See the [module-level documentation](self) for details.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, derive_more::Display)]
+#[non_exhaustive]
+pub enum ConfigPreset {
+    /// Phones and other battery-powered devices with metered connections.
+    ///
+    /// Uses reduced channel padding, builds fewer circuits in advance,
+    /// and (with the `memquota` feature) limits memory use to 256 MiB.
+    #[display("mobile")]
+    Mobile,
+
+    /// Long-running processes on well-connected machines.
+    ///
+    /// Builds more circuits in advance, and remembers the ports
+    /// we have used for longer, to reduce latency for new streams.
+    #[display("server")]
+    Server,
+
+    /// Devices with very little memory.
+    ///
+    /// Like [`Mobile`](ConfigPreset::Mobile), but builds almost no circuits in
+    /// advance, and (with the `memquota` feature) limits memory use to 64 MiB.
+    #[display("low-memory")]
+    LowMemory,
+
+    /// Tests, and experiments on private networks.
+    ///
+    /// Disables channel padding and preemptive circuits, permits connecting
+    /// to local addresses, and disables filesystem permission checks.
+    /// Do not use this for anything that needs anonymity.
+    #[display("testing")]
+    Testing,
+}
+
+impl TorClientConfigBuilder {
+    /// Return a new builder, with the options of `preset` applied to the defaults.
+    pub fn preset(preset: ConfigPreset) -> Self {
+        let mut builder = Self::default();
+        builder.apply_preset(preset);
+        builder
+    }
+
+    /// Set all the options controlled by `preset`.
+    ///
+    /// Options not mentioned by the preset are left alone;
+    /// options it does mention are overwritten.
+    pub fn apply_preset(&mut self, preset: ConfigPreset) -> &mut Self {
+        /// One MiB.
+        const MIB: usize = 1024 * 1024;
+
+        match preset {
+            ConfigPreset::Mobile => {
+                self.channel().padding(PaddingLevel::Reduced);
+                self.preemptive_circuits()
+                    .disable_at_threshold(4)
+                    .min_exit_circs_for_port(1)
+                    .prediction_lifetime(Duration::from_secs(20 * 60));
+                self.set_memory_limit(256 * MIB);
+            }
+            ConfigPreset::Server => {
+                self.channel().padding(PaddingLevel::Normal);
+                self.preemptive_circuits()
+                    .disable_at_threshold(24)
+                    .min_exit_circs_for_port(3)
+                    .prediction_lifetime(Duration::from_secs(2 * 60 * 60));
+            }
+            ConfigPreset::LowMemory => {
+                self.channel().padding(PaddingLevel::Reduced);
+                self.preemptive_circuits()
+                    .disable_at_threshold(2)
+                    .min_exit_circs_for_port(1)
+                    .prediction_lifetime(Duration::from_secs(10 * 60))
+                    .set_initial_predicted_ports(vec![]);
+                self.set_memory_limit(64 * MIB);
+            }
+            ConfigPreset::Testing => {
+                self.channel().padding(PaddingLevel::None);
+                self.preemptive_circuits()
+                    .disable_at_threshold(0)
+                    .set_initial_predicted_ports(vec![]);
+                self.address_filter().allow_local_addrs(true);
+                self.storage().permissions().dangerously_trust_everyone();
+            }
+        }
+        self
+    }
+
+    /// Limit memory use to `max` bytes, if memory quota tracking is compiled in.
+    fn set_memory_limit(&mut self, max: usize) {
+        #[cfg(feature = "memquota")]
+        self.system().memory().max(max);
+        #[cfg(not(feature = "memquota"))]
+        let _ = max;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use crate::TorClientConfig;
+
+    #[test]
+    fn presets_build() {
+        for preset in [
+            ConfigPreset::Mobile,
+            ConfigPreset::Server,
+            ConfigPreset::LowMemory,
+            ConfigPreset::Testing,
+        ] {
+            TorClientConfigBuilder::preset(preset)
+                .build()
+                .unwrap_or_else(|e| panic!("{preset}: {e}"));
+        }
+    }
+
+    #[test]
+    fn override_preset() {
+        let mut builder = TorClientConfigBuilder::preset(ConfigPreset::Testing);
+        builder.address_filter().allow_local_addrs(false);
+        let config = builder.build().unwrap();
+        assert!(!config.address_filter.allow_local_addrs);
+        // The rest of the preset still applies.
+        assert_ne!(
+            config.preemptive_circuits,
+            TorClientConfig::default().preemptive_circuits
+        );
+    }
+}
@@ -0,0 +1,283 @@
+//! Import of bridge configuration from C Tor `torrc` snippets.
+//!
+//! Users migrating from C Tor (or from Tor Browser) usually have their
+//! bridges in the form of a `torrc` fragment, like this:
+//!
+//! ```text
+//! UseBridges 1
+//! ClientTransportPlugin obfs4 exec /usr/bin/obfs4proxy
+//! Bridge obfs4 192.0.2.55:38114 316E643333645F6D79216558614D3931657A5F5F cert=YXJlIGZyZXF1ZW50bHkgZnVsbCBvZiBsaXR0bGUgbWVzc2FnZXMgeW91IGNhbiBmaW5kLg iat-mode=0
+//! ```
+//!
+//! [`parse_torrc_bridges`] converts such a fragment into a
+//! [`BridgesConfigBuilder`].
+//!
+//! We understand the `Bridge`, `UseBridges` and `ClientTransportPlugin` options.
+//! Option names are case-insensitive, as in C Tor.
+//! Blank lines, comments, and other options are ignored
+//! (the latter with a warning), so that a complete `torrc` can be imported.
+
+use tor_config::BoolOrAuto;
+use tor_guardmgr::bridge::{BridgeConfigBuilder, BridgeParseError};
+use tracing::warn;
+
+use super::BridgesConfigBuilder;
+
+/// An error encountered while importing a `torrc` snippet.
+#[derive(Clone, Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum TorrcImportError {
+    /// A `Bridge` line could not be parsed.
+    #[error("line {line}: invalid Bridge")]
+    Bridge {
+        /// The line number (starting at 1) of the problem.
+        line: usize,
+        /// What was wrong with the bridge.
+        #[source]
+        error: BridgeParseError,
+    },
+
+    /// An option we understand had a value we could not parse.
+    #[error("line {line}: invalid {option}: {problem}")]
+    InvalidValue {
+        /// The line number (starting at 1) of the problem.
+        line: usize,
+        /// The name of the option.
+        option: &'static str,
+        /// What was wrong with its value.
+        problem: String,
+    },
+
+    /// A `ClientTransportPlugin` line was found,
+    /// but pluggable transport support is not compiled in.
+    #[error("line {line}: ClientTransportPlugin requires the `pt-client` feature")]
+    PluggableTransportsNotSupported {
+        /// The line number (starting at 1) of the problem.
+        line: usize,
+    },
+}
+
+/// Parse the bridge-related options of the `torrc` snippet in `text`.
+///
+/// See the [module-level documentation](self) for what we understand.
+///
+/// The result can be used as (or merged into) the `bridges` section of a
+/// [`TorClientConfigBuilder`](crate::config::TorClientConfigBuilder).
+pub fn parse_torrc_bridges(text: &str) -> Result<BridgesConfigBuilder, TorrcImportError> {
+    let mut out = BridgesConfigBuilder::default();
+
+    for (line, content) in logical_lines(text) {
+        let (option, value) = match content
+            .as_str()
+            .split_once(|c: char| c.is_ascii_whitespace())
+        {
+            Some((option, value)) => (option, value.trim()),
+            None => (content.as_str(), ""),
+        };
+
+        if option.eq_ignore_ascii_case("Bridge") {
+            let bridge: BridgeConfigBuilder = value
+                .parse()
+                .map_err(|error| TorrcImportError::Bridge { line, error })?;
+            out.bridges().push(bridge);
+        } else if option.eq_ignore_ascii_case("UseBridges") {
+            let enabled = match value {
+                "1" => true,
+                "0" => false,
+                _ => {
+                    return Err(TorrcImportError::InvalidValue {
+                        line,
+                        option: "UseBridges",
+                        problem: format!("expected 0 or 1, found {:?}", value),
+                    })
+                }
+            };
+            out.enabled(BoolOrAuto::Explicit(enabled));
+        } else if option.eq_ignore_ascii_case("ClientTransportPlugin") {
+            #[cfg(feature = "pt-client")]
+            out.transports().push(parse_transport_plugin(line, value)?);
+            #[cfg(not(feature = "pt-client"))]
+            return Err(TorrcImportError::PluggableTransportsNotSupported { line });
+        } else {
+            warn!(
+                "torrc line {}: ignoring unsupported option {:?}",
+                line, option
+            );
+        }
+    }
+
+    Ok(out)
+}
+
+/// Parse the value of a `ClientTransportPlugin` option.
+///
+/// This has one of the forms
+/// `transport[,transport...] exec path [args...]` (a managed transport),
+/// or `transport[,transport...] socks4|socks5 addr:port` (an unmanaged one).
+#[cfg(feature = "pt-client")]
+fn parse_transport_plugin(
+    line: usize,
+    value: &str,
+) -> Result<super::pt::TransportConfigBuilder, TorrcImportError> {
+    let invalid = |problem: String| TorrcImportError::InvalidValue {
+        line,
+        option: "ClientTransportPlugin",
+        problem,
+    };
+
+    let mut words = value.split_ascii_whitespace();
+    let (Some(names), Some(kind)) = (words.next(), words.next()) else {
+        return Err(invalid("expected a transport name and a method".into()));
+    };
+
+    let protocols = names
+        .split(',')
+        .map(|name| {
+            name.parse()
+                .map_err(|e| invalid(format!("bad transport name {:?}: {}", name, e)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut transport = super::pt::TransportConfigBuilder::default();
+    transport.protocols(protocols);
+
+    if kind.eq_ignore_ascii_case("exec") {
+        let path = words
+            .next()
+            .ok_or_else(|| invalid("missing path after `exec`".into()))?;
+        transport
+            .path(tor_config::CfgPath::new(path.to_owned()))
+            .arguments(words.map(str::to_owned).collect());
+    } else if kind.eq_ignore_ascii_case("socks4") || kind.eq_ignore_ascii_case("socks5") {
+        let addr = words
+            .next()
+            .ok_or_else(|| invalid(format!("missing address after `{}`", kind)))?;
+        let addr = addr
+            .parse()
+            .map_err(|e| invalid(format!("bad proxy address {:?}: {}", addr, e)))?;
+        if words.next().is_some() {
+            return Err(invalid("unexpected arguments after proxy address".into()));
+        }
+        transport.proxy_addr(addr);
+    } else {
+        return Err(invalid(format!(
+            "expected `exec`, `socks4` or `socks5`, found {:?}",
+            kind
+        )));
+    }
+
+    Ok(transport)
+}
+
+/// Split `text` into non-empty logical lines, with comments removed.
+///
+/// Like C Tor, we treat a line ending with a backslash as continuing onto the
+/// next line.
+/// Each line is returned with the (1-based) number of the physical line on
+/// which it starts.
+fn logical_lines(text: &str) -> Vec<(usize, String)> {
+    let mut lines = vec![];
+    let mut current: Option<(usize, String)> = None;
+
+    for (n, raw) in text.lines().enumerate() {
+        let raw = raw.split_once('#').map_or(raw, |(before, _)| before);
+        let (raw, continued) = match raw.trim_end().strip_suffix('\\') {
+            Some(raw) => (raw, true),
+            None => (raw, false),
+        };
+
+        let (_, buf) = current.get_or_insert_with(|| (n + 1, String::new()));
+        if !buf.is_empty() {
+            buf.push(' ');
+        }
+        buf.push_str(raw.trim());
+
+        if !continued {
+            if let Some((start, buf)) = current.take() {
+                if !buf.is_empty() {
+                    lines.push((start, buf));
+                }
+            }
+        }
+    }
+    lines.extend(current.filter(|(_, buf)| !buf.is_empty()));
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+
+    #[test]
+    fn lines() {
+        let text = "# a comment\n\nUseBridges 1 # trailing\nBridge \\\n  192.0.2.1:443\n";
+        assert_eq!(
+            logical_lines(text),
+            vec![
+                (3, "UseBridges 1".to_owned()),
+                (4, "Bridge 192.0.2.1:443".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn direct_bridges() {
+        let text = "
+            usebridges 1
+            SocksPort 9150
+            Bridge 192.0.2.1:443 316E643333645F6D79216558614D3931657A5F5F
+            bridge 192.0.2.2:9001 7DD62766BF2052432051D7B7E08A22F7E34A4543
+        ";
+        let mut b = parse_torrc_bridges(text).unwrap();
+        assert_eq!(b.bridges().len(), 2);
+        let cfg = b.build().unwrap();
+        assert_eq!(cfg.enabled, BoolOrAuto::Explicit(true));
+    }
+
+    #[test]
+    fn errors() {
+        let e = parse_torrc_bridges("UseBridges yes").unwrap_err();
+        assert!(matches!(e, TorrcImportError::InvalidValue { line: 1, .. }));
+
+        let e = parse_torrc_bridges("\nBridge").unwrap_err();
+        assert!(matches!(e, TorrcImportError::Bridge { line: 2, .. }));
+    }
+
+    #[test]
+    #[cfg(feature = "pt-client")]
+    fn transports() {
+        let text = r#"
+UseBridges 1
+ClientTransportPlugin obfs4,meek_lite exec /usr/bin/lyrebird -enableLogging
+ClientTransportPlugin snowflake socks5 127.0.0.1:9999
+Bridge obfs4 192.0.2.55:38114 316E643333645F6D79216558614D3931657A5F5F cert=YXJlIGZyZXF1ZW50bHkgZnVsbCBvZiBsaXR0bGUgbWVzc2FnZXMgeW91IGNhbiBmaW5kLg iat-mode=0
+"#;
+        let mut b = parse_torrc_bridges(text).unwrap();
+        let transports = b.transports();
+        assert_eq!(transports.len(), 2);
+        let names: Vec<_> = transports[0]
+            .get_protocols()
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        assert_eq!(names, ["obfs4", "meek_lite"]);
+        assert_eq!(transports[1].get_protocols()[0].to_string(), "snowflake");
+        assert_eq!(b.bridges().len(), 1);
+
+        let e = parse_torrc_bridges("ClientTransportPlugin obfs4 tunnel x").unwrap_err();
+        assert!(matches!(e, TorrcImportError::InvalidValue { line: 1, .. }));
+    }
+}
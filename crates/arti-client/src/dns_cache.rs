@@ -0,0 +1,313 @@
+//! A small cache for the results of DNS lookups made via exits.
+//!
+//! Applications often look up the same hostname several times in quick
+//! succession (for example, while loading a single web page).  Each lookup
+//! costs a round trip to the exit, so we remember the answers for a while.
+//!
+//! To avoid linking activity that the user wanted kept apart, a cached answer
+//! is only given to a request whose isolation is compatible with that of the
+//! request that fetched it: that is, to a request that could have used the
+//! very same circuit.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use tor_circmgr::isolation::{Isolation as _, StreamIsolation};
+
+use crate::config::DnsCacheConfig;
+
+/// A cache of DNS results, partitioned by stream isolation.
+pub(crate) struct DnsCache {
+    /// The cache contents, behind a lock.
+    inner: Mutex<Inner>,
+}
+
+/// The mutable state of a [`DnsCache`].
+struct Inner {
+    /// Our current configuration.
+    config: DnsCacheConfig,
+    /// Cached results, indexed by (lowercased) hostname.
+    ///
+    /// There can be several entries for a single hostname, with different isolation.
+    entries: HashMap<String, Vec<Entry>>,
+    /// The total number of entries in `entries`.
+    n_entries: usize,
+}
+
+/// A single cached result.
+struct Entry {
+    /// The isolation of the request that fetched this result.
+    isolation: StreamIsolation,
+    /// The addresses that the exit gave us.
+    addrs: Vec<IpAddr>,
+    /// When this entry stops being usable.
+    expires: Instant,
+}
+
+impl DnsCache {
+    /// Create a new, empty cache.
+    pub(crate) fn new(config: DnsCacheConfig) -> Self {
+        DnsCache {
+            inner: Mutex::new(Inner {
+                config,
+                entries: HashMap::new(),
+                n_entries: 0,
+            }),
+        }
+    }
+
+    /// Replace our configuration with `config`.
+    ///
+    /// Discards any entries that the new configuration doesn't have room for.
+    pub(crate) fn reconfigure(&self, config: &DnsCacheConfig) {
+        let mut inner = self.lock();
+        inner.config = config.clone();
+        if !config.enabled {
+            inner.clear();
+        }
+        while inner.n_entries > config.max_entries {
+            inner.remove_soonest_expiring();
+        }
+    }
+
+    /// Return the cached addresses for `hostname`, if we have a live entry
+    /// that a request with `isolation` may use.
+    pub(crate) fn get(
+        &self,
+        hostname: &str,
+        isolation: &StreamIsolation,
+        now: Instant,
+    ) -> Option<Vec<IpAddr>> {
+        let inner = self.lock();
+        if !inner.config.enabled {
+            return None;
+        }
+        inner
+            .entries
+            .get(&hostname.to_ascii_lowercase())?
+            .iter()
+            .find(|ent| ent.expires > now && isolation.compatible(&ent.isolation))
+            .map(|ent| ent.addrs.clone())
+    }
+
+    /// Remember that a request with `isolation` resolved `hostname` to `addrs`.
+    pub(crate) fn insert(
+        &self,
+        hostname: &str,
+        isolation: StreamIsolation,
+        addrs: Vec<IpAddr>,
+        now: Instant,
+    ) {
+        let mut inner = self.lock();
+        let max_entries = inner.config.max_entries;
+        if !inner.config.enabled || max_entries == 0 || addrs.is_empty() {
+            return;
+        }
+        let Some(expires) = now.checked_add(inner.config.ttl) else {
+            return;
+        };
+        if inner.n_entries >= max_entries {
+            inner.remove_expired(now);
+        }
+        while inner.n_entries >= max_entries {
+            inner.remove_soonest_expiring();
+        }
+        inner
+            .entries
+            .entry(hostname.to_ascii_lowercase())
+            .or_default()
+            .push(Entry {
+                isolation,
+                addrs,
+                expires,
+            });
+        inner.n_entries += 1;
+    }
+
+    /// Discard every cached result.
+    pub(crate) fn flush(&self) {
+        self.lock().clear();
+    }
+
+    /// Lock the inner state.
+    ///
+    /// A panic while holding this lock can't leave the state inconsistent,
+    /// so we ignore poisoning.
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Inner {
+    /// Discard every entry.
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.n_entries = 0;
+    }
+
+    /// Discard every entry that has expired by `now`.
+    fn remove_expired(&mut self, now: Instant) {
+        self.entries.retain(|_, ents| {
+            ents.retain(|ent| ent.expires > now);
+            !ents.is_empty()
+        });
+        self.n_entries = self.entries.values().map(Vec::len).sum();
+    }
+
+    /// Discard the entry that would expire first.
+    fn remove_soonest_expiring(&mut self) {
+        let Some((hostname, idx)) = self
+            .entries
+            .iter()
+            .flat_map(|(hostname, ents)| {
+                ents.iter()
+                    .enumerate()
+                    .map(move |(idx, ent)| (hostname, idx, ent.expires))
+            })
+            .min_by_key(|(_, _, expires)| *expires)
+            .map(|(hostname, idx, _)| (hostname.clone(), idx))
+        else {
+            return;
+        };
+        let ents = self
+            .entries
+            .get_mut(&hostname)
+            .expect("entry vanished while we held the lock");
+        ents.remove(idx);
+        if ents.is_empty() {
+            self.entries.remove(&hostname);
+        }
+        self.n_entries -= 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use std::time::Duration;
+    use tor_circmgr::isolation::StreamIsolationBuilder;
+    use tor_circmgr::IsolationToken;
+
+    fn isolation(owner: IsolationToken) -> StreamIsolation {
+        StreamIsolationBuilder::new()
+            .owner_token(owner)
+            .build()
+            .unwrap()
+    }
+
+    fn enabled() -> DnsCacheConfig {
+        DnsCacheConfig::builder().enabled(true).build().unwrap()
+    }
+
+    fn addrs(s: &str) -> Vec<IpAddr> {
+        vec![s.parse().unwrap()]
+    }
+
+    #[test]
+    fn lookup_and_expiry() {
+        let cache = DnsCache::new(enabled());
+        let now = Instant::now();
+        let owner = IsolationToken::new();
+
+        assert!(cache.get("example.com", &isolation(owner), now).is_none());
+        cache.insert("Example.COM", isolation(owner), addrs("192.0.2.1"), now);
+        assert_eq!(
+            cache.get("example.com", &isolation(owner), now),
+            Some(addrs("192.0.2.1"))
+        );
+
+        let later = now + Duration::from_secs(59);
+        assert!(cache.get("EXAMPLE.com", &isolation(owner), later).is_some());
+        let later = now + Duration::from_secs(60);
+        assert!(cache.get("example.com", &isolation(owner), later).is_none());
+    }
+
+    #[test]
+    fn isolated() {
+        let cache = DnsCache::new(enabled());
+        let now = Instant::now();
+        let owner_1 = IsolationToken::new();
+        let owner_2 = IsolationToken::new();
+
+        cache.insert("example.com", isolation(owner_1), addrs("192.0.2.1"), now);
+        assert!(cache.get("example.com", &isolation(owner_2), now).is_none());
+
+        cache.insert("example.com", isolation(owner_2), addrs("192.0.2.2"), now);
+        assert_eq!(
+            cache.get("example.com", &isolation(owner_1), now),
+            Some(addrs("192.0.2.1"))
+        );
+        assert_eq!(
+            cache.get("example.com", &isolation(owner_2), now),
+            Some(addrs("192.0.2.2"))
+        );
+
+        cache.flush();
+        assert!(cache.get("example.com", &isolation(owner_1), now).is_none());
+        assert!(cache.get("example.com", &isolation(owner_2), now).is_none());
+    }
+
+    #[test]
+    fn bounded() {
+        let mut cfg = DnsCacheConfig::builder();
+        cfg.enabled(true).max_entries(2);
+        let cache = DnsCache::new(cfg.build().unwrap());
+        let now = Instant::now();
+        let owner = IsolationToken::new();
+        let sec = Duration::from_secs(1);
+
+        cache.insert("a.example", isolation(owner), addrs("192.0.2.1"), now);
+        cache.insert("b.example", isolation(owner), addrs("192.0.2.2"), now + sec);
+        cache.insert(
+            "c.example",
+            isolation(owner),
+            addrs("192.0.2.3"),
+            now + sec * 2,
+        );
+        assert!(cache.get("a.example", &isolation(owner), now).is_none());
+        assert!(cache.get("b.example", &isolation(owner), now).is_some());
+        assert!(cache.get("c.example", &isolation(owner), now).is_some());
+
+        cfg.max_entries(1);
+        cache.reconfigure(&cfg.build().unwrap());
+        assert!(cache.get("b.example", &isolation(owner), now).is_none());
+        assert!(cache.get("c.example", &isolation(owner), now).is_some());
+    }
+
+    #[test]
+    fn disabled() {
+        let cache = DnsCache::new(enabled());
+        let now = Instant::now();
+        let owner = IsolationToken::new();
+        cache.insert("example.com", isolation(owner), addrs("192.0.2.1"), now);
+
+        let mut cfg = DnsCacheConfig::builder();
+        cfg.enabled(false);
+        cache.reconfigure(&cfg.build().unwrap());
+        assert!(cache.get("example.com", &isolation(owner), now).is_none());
+        cache.insert("example.com", isolation(owner), addrs("192.0.2.1"), now);
+
+        cache.reconfigure(&enabled());
+        assert!(cache.get("example.com", &isolation(owner), now).is_none());
+
+        // The cache is off by default.
+        let cache = DnsCache::new(DnsCacheConfig::default());
+        cache.insert("example.com", isolation(owner), addrs("192.0.2.1"), now);
+        assert!(cache.get("example.com", &isolation(owner), now).is_none());
+    }
+}
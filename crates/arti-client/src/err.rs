@@ -147,6 +147,10 @@ enum ErrorDetail {
     #[error("Error setting up the vanguard manager")]
     VanguardMgrSetup(#[source] tor_guardmgr::VanguardMgrError),
 
+    /// One of our cryptographic backends failed its self-test.
+    #[error("Cryptographic self-test failed")]
+    CryptoSelfTest(#[from] tor_llcrypto::backend::SelfTestError),
+
     /// Error setting up the circuit manager
     // TODO: should "circmgr setup error" be its own type in tor-circmgr?
     #[error("Error setting up the circuit manager")]
@@ -213,10 +217,29 @@ enum ErrorDetail {
     #[error("Error while trying to access persistent state")]
     StateAccess(#[source] tor_persist::Error),
 
+    /// The circuit manager was unable to load or store its persistent state.
+    #[error("Problem with circuit manager's persistent state")]
+    CircMgrState(#[source] tor_circmgr::Error),
+
+    /// We were unable to access the directory cache.
+    #[error("Problem accessing directory cache")]
+    DirCache(#[source] tor_dirmgr::Error),
+
+    /// We were given a state snapshot that we couldn't use.
+    #[error("Invalid state snapshot: {0}")]
+    BadStateSnapshot(String),
+
     /// We asked an exit to do something, and waited too long for an answer.
     #[error("Timed out while waiting for answer from exit")]
     ExitTimeout,
 
+    /// We waited too long for the client to reach a bootstrap milestone.
+    #[error("Timed out waiting for bootstrap milestone: {milestone}")]
+    BootstrapTimeout {
+        /// The milestone we were waiting for.
+        milestone: crate::status::BootstrapMilestone,
+    },
+
     /// Onion services are not compiled in, but we were asked to connect to one.
     #[error("Rejecting .onion address; feature onion-service-client not compiled in")]
     OnionAddressNotSupported,
@@ -233,6 +256,16 @@ enum ErrorDetail {
     #[error("Rejecting .onion address; allow_onion_addrs disabled in stream preferences")]
     OnionAddressDisabled,
 
+    /// We were asked to open a UDP stream to an onion service.
+    #[cfg(feature = "experimental-udp")]
+    #[error("UDP streams to onion services are not supported")]
+    OnionServiceUdpNotSupported,
+
+    /// A resumable stream failed, and we could not make a replacement.
+    #[cfg(all(feature = "onion-service-client", feature = "experimental-api"))]
+    #[error("Resumable stream failed")]
+    ResumableStreamFailed(#[source] Arc<std::io::Error>),
+
     /// Error when trying to find the IP address of a hidden service
     #[error("A .onion address cannot be resolved to an IP address")]
     OnionAddressResolveRequest,
@@ -250,6 +283,14 @@ enum ErrorDetail {
     #[error("Rejecting hostname as invalid")]
     InvalidHostname,
 
+    /// Address was not an onion service, and we only permit connecting to those.
+    #[error("Rejecting non-onion address; onion_only is enabled in the address filter")]
+    ClearnetAddressForbidden,
+
+    /// Address was an IP address of a version that our stream preferences forbid.
+    #[error("Cannot connect to this IP address: the stream preferences forbid its IP version")]
+    IpVersionForbidden,
+
     /// Address was local, and we don't permit connecting to those over Tor.
     #[error("Cannot connect to a local-only address without enabling allow_local_addrs")]
     LocalAddress,
@@ -258,6 +299,11 @@ enum ErrorDetail {
     #[error("Problem with configuration")]
     Configuration(#[from] tor_config::ConfigBuildError),
 
+    /// Every stage of a circumvention strategy failed to bootstrap.
+    #[cfg(feature = "bridge-client")]
+    #[error("Unable to bootstrap with any stage of the circumvention strategy")]
+    CircumventionFailed,
+
     /// Unable to change configuration.
     #[error("Unable to change configuration")]
     Reconfigure(#[from] tor_config::ReconfigureError),
@@ -304,6 +350,11 @@ enum ErrorDetail {
     #[error("Error while trying to access a key store")]
     Keystore(#[from] tor_keymgr::Error),
 
+    /// We couldn't find the keystore passphrase given in the configuration.
+    #[cfg(feature = "encrypted-keystore")]
+    #[error("Unable to obtain the keystore passphrase (storage.keystore.primary.passphrase)")]
+    KeystorePassphrase(#[source] tor_config::CfgSecretError),
+
     /// Attempted to use a `TorClient` for something that
     /// requires the keystore to be enabled in the configuration.
     #[error("Cannot {action} without enabling storage.keystore")]
@@ -357,6 +408,15 @@ impl Error {
 }
 
 impl Error {
+    /// Return advice about what the user might do about this error, if we have any.
+    ///
+    /// This is a shortcut for calling [`ErrorKind::remediation`] on our
+    /// [`kind`](`tor_error::HasKind::kind`).
+    /// Front-ends can use it to show actionable guidance.
+    pub fn remediation(&self) -> Option<tor_error::Remediation> {
+        self.kind().remediation()
+    }
+
     /// Consume this error and return the underlying error detail object.
     pub(crate) fn into_detail(self) -> ErrorDetail {
         *self.detail
@@ -394,6 +454,7 @@ impl tor_error::HasKind for ErrorDetail {
             #[cfg(feature = "onion-service-client")]
             E::ObtainHsCircuit { cause, .. } => cause.kind(),
             E::ExitTimeout => EK::RemoteNetworkTimeout,
+            E::BootstrapTimeout { .. } => EK::TorNetworkTimeout,
             E::BootstrapRequired { .. } => EK::BootstrapRequired,
             E::MemquotaSetup(e) => e.kind(),
             E::MemquotaDuringStartup(e) => e.kind(),
@@ -415,10 +476,18 @@ impl tor_error::HasKind for ErrorDetail {
             E::PluggableTransport(e) => e.kind(),
             E::StreamFailed { cause, .. } => cause.kind(),
             E::StateAccess(e) => e.kind(),
+            E::CircMgrState(e) => e.kind(),
+            E::DirCache(e) => e.kind(),
+            E::BadStateSnapshot(_) => EK::BadApiUsage,
             E::Configuration(e) => e.kind(),
             E::Reconfigure(e) => e.kind(),
             E::Spawn { cause, .. } => cause.kind(),
             E::OnionAddressNotSupported => EK::FeatureDisabled,
+            #[cfg(feature = "experimental-udp")]
+            E::OnionServiceUdpNotSupported => EK::NotImplemented,
+            E::CryptoSelfTest(_) => EK::Internal,
+            #[cfg(all(feature = "onion-service-client", feature = "experimental-api"))]
+            E::ResumableStreamFailed(_) => EK::CircuitCollapse,
             E::OnionAddressResolveRequest => EK::NotImplemented,
             #[cfg(feature = "onion-service-client")]
             E::OnionAddressDisabled => EK::ForbiddenStreamTarget,
@@ -429,9 +498,15 @@ impl tor_error::HasKind for ErrorDetail {
             // TODO Should delegate to TorAddrError EK
             E::Address(_) | E::InvalidHostname => EK::InvalidStreamTarget,
             E::LocalAddress => EK::ForbiddenStreamTarget,
+            E::IpVersionForbidden => EK::ForbiddenStreamTarget,
+            #[cfg(feature = "bridge-client")]
+            E::CircumventionFailed => EK::TorAccessFailed,
+            E::ClearnetAddressForbidden => EK::ForbiddenClearnetTarget,
             E::ChanMgrSetup(e) => e.kind(),
             E::NoDir { error, .. } => error.kind(),
             E::Keystore(e) => e.kind(),
+            #[cfg(feature = "encrypted-keystore")]
+            E::KeystorePassphrase(e) => e.kind(),
             E::KeystoreRequired { .. } => EK::InvalidConfig,
             E::BadClientSpecifier(_) => EK::InvalidConfig,
             E::FsMistrust(_) => EK::FsPermissions,
@@ -586,4 +661,12 @@ mod test {
         }
         check(); // doesn't do anything, but avoids "unused function" warnings.
     }
+
+    #[test]
+    fn remediation() {
+        let e: Error = ErrorDetail::ExitTimeout.into();
+        assert_eq!(e.remediation(), Some(tor_error::Remediation::RetryLater));
+        let e: Error = ErrorDetail::Bug(tor_error::internal!("oops")).into();
+        assert_eq!(e.remediation(), Some(tor_error::Remediation::ReportBug));
+    }
 }
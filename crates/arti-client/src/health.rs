@@ -0,0 +1,334 @@
+//! Deduplicated reports of ongoing problems ("health warnings").
+//!
+//! Many of the problems that stop a client from working well,
+//! such as a skewed clock or a filtered internet connection,
+//! are noticed over and over again for as long as they last.
+//! Rather than logging each occurrence,
+//! we keep a single [`HealthWarning`] for each kind of problem,
+//! with a stable [`HealthWarningId`].
+//! These are logged when they first appear and (rarely) while they persist,
+//! and they can be inspected at any time with
+//! [`TorClient::health_warnings`](crate::TorClient::health_warnings).
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use futures::StreamExt as _;
+use tracing::{info, warn};
+
+use crate::status::{BlockageKind, BootstrapEvents, BootstrapStatus};
+
+/// How often do we log a health warning while it persists?
+const LOG_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// A stable identifier for a kind of [`HealthWarning`].
+///
+/// Each identifier has a fixed string form (see [`HealthWarningId::as_str`]),
+/// which will not change between Arti versions:
+/// programs can rely on it to recognize particular problems.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[non_exhaustive]
+pub enum HealthWarningId {
+    /// Our clock seems to be set incorrectly.
+    ClockSkew,
+    /// We can't seem to make any connections to the internet.
+    Offline,
+    /// Our connections to the Tor network seem to be filtered.
+    ConnectionsFiltered,
+    /// We can't reach the Tor network for some other reason,
+    /// for instance because our guards keep failing.
+    CantReachTor,
+    /// We can't get the directory information we need.
+    DirectoryStuck,
+    /// One or more of our keystores is locked,
+    /// so we can't use the keys in it.
+    KeystoreLocked,
+}
+
+impl HealthWarningId {
+    /// Return the stable string form of this identifier.
+    pub fn as_str(&self) -> &'static str {
+        use HealthWarningId as H;
+        match self {
+            H::ClockSkew => "clock-skew",
+            H::Offline => "offline",
+            H::ConnectionsFiltered => "connections-filtered",
+            H::CantReachTor => "cant-reach-tor",
+            H::DirectoryStuck => "directory-stuck",
+            H::KeystoreLocked => "keystore-locked",
+        }
+    }
+}
+
+impl fmt::Display for HealthWarningId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<BlockageKind> for HealthWarningId {
+    fn from(kind: BlockageKind) -> Self {
+        match kind {
+            BlockageKind::Offline => HealthWarningId::Offline,
+            BlockageKind::Filtering => HealthWarningId::ConnectionsFiltered,
+            BlockageKind::CantReachTor => HealthWarningId::CantReachTor,
+            BlockageKind::ClockSkewed => HealthWarningId::ClockSkew,
+            BlockageKind::CantBootstrap => HealthWarningId::DirectoryStuck,
+        }
+    }
+}
+
+/// A problem that a client has noticed, and which has not yet gone away.
+///
+/// Returned by [`TorClient::health_warnings`](crate::TorClient::health_warnings).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HealthWarning {
+    /// What kind of problem is this?
+    id: HealthWarningId,
+    /// A human-readable description of the most recent occurrence of the problem.
+    message: String,
+    /// When did we first notice the problem?
+    first_seen: SystemTime,
+    /// When did we most recently notice the problem?
+    last_seen: SystemTime,
+    /// How many times have we noticed the problem?
+    count: u64,
+}
+
+impl HealthWarning {
+    /// Return the identifier for the kind of problem this is.
+    pub fn id(&self) -> HealthWarningId {
+        self.id
+    }
+
+    /// Return a human-readable description of the problem.
+    ///
+    /// This message is meant for people: it may change between Arti versions,
+    /// and as the problem develops.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Return the time when we first noticed this problem.
+    pub fn first_seen(&self) -> SystemTime {
+        self.first_seen
+    }
+
+    /// Return the time when we most recently noticed this problem.
+    pub fn last_seen(&self) -> SystemTime {
+        self.last_seen
+    }
+
+    /// Return the number of times that we have noticed this problem.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+/// A health warning, and when we last logged it.
+#[derive(Debug)]
+struct Tracked {
+    /// The warning itself.
+    warning: HealthWarning,
+    /// When did we last log this warning?
+    last_logged: Instant,
+}
+
+/// The set of health warnings that are currently active for a client.
+#[derive(Debug, Default)]
+pub(crate) struct HealthMonitor {
+    /// The active warnings, by identifier.
+    warnings: Mutex<BTreeMap<HealthWarningId, Tracked>>,
+}
+
+impl HealthMonitor {
+    /// Record that we have noticed the problem `id`, described by `message`.
+    ///
+    /// Logs the problem if it's new, or if we haven't logged it for a while.
+    pub(crate) fn raise(
+        &self,
+        id: HealthWarningId,
+        message: String,
+        now: Instant,
+        wallclock: SystemTime,
+    ) {
+        let mut warnings = self.warnings.lock().expect("health monitor lock poisoned");
+        match warnings.get_mut(&id) {
+            None => {
+                warn!("Problem detected ({}): {}", id, message);
+                let warning = HealthWarning {
+                    id,
+                    message,
+                    first_seen: wallclock,
+                    last_seen: wallclock,
+                    count: 1,
+                };
+                warnings.insert(
+                    id,
+                    Tracked {
+                        warning,
+                        last_logged: now,
+                    },
+                );
+            }
+            Some(tracked) => {
+                let warning = &mut tracked.warning;
+                warning.message = message;
+                warning.last_seen = wallclock;
+                warning.count = warning.count.saturating_add(1);
+                if now.saturating_duration_since(tracked.last_logged) >= LOG_INTERVAL {
+                    warn!(
+                        "Problem persists ({}): {} (noticed {} times since {})",
+                        id,
+                        warning.message,
+                        warning.count,
+                        humantime::format_rfc3339_seconds(warning.first_seen),
+                    );
+                    tracked.last_logged = now;
+                }
+            }
+        }
+    }
+
+    /// Record that the problem `id` has gone away.
+    pub(crate) fn clear(&self, id: HealthWarningId) {
+        let mut warnings = self.warnings.lock().expect("health monitor lock poisoned");
+        if warnings.remove(&id).is_some() {
+            info!("Problem resolved ({})", id);
+        }
+    }
+
+    /// Raise or clear the warnings that can be inferred from a bootstrap `status`.
+    pub(crate) fn update_from_bootstrap(
+        &self,
+        status: &BootstrapStatus,
+        now: Instant,
+        wallclock: SystemTime,
+    ) {
+        /// The warnings that we infer from a bootstrap status.
+        const FROM_BOOTSTRAP: &[HealthWarningId] = &[
+            HealthWarningId::ClockSkew,
+            HealthWarningId::Offline,
+            HealthWarningId::ConnectionsFiltered,
+            HealthWarningId::CantReachTor,
+            HealthWarningId::DirectoryStuck,
+        ];
+
+        let mut active = BTreeMap::new();
+        if let Some(skew) = status.noteworthy_skew() {
+            active.insert(HealthWarningId::ClockSkew, format!("Clock is {}", skew));
+        }
+        if let Some(blockage) = status.blocked() {
+            active.insert(blockage.kind().into(), blockage.message().to_string());
+        }
+
+        for id in FROM_BOOTSTRAP {
+            match active.remove(id) {
+                Some(message) => self.raise(*id, message, now, wallclock),
+                None => self.clear(*id),
+            }
+        }
+    }
+
+    /// Return a list of the currently active warnings, ordered by identifier.
+    pub(crate) fn warnings(&self) -> Vec<HealthWarning> {
+        let warnings = self.warnings.lock().expect("health monitor lock poisoned");
+        warnings.values().map(|t| t.warning.clone()).collect()
+    }
+}
+
+/// Task that runs until the client's status stops changing,
+/// updating `monitor` whenever it does.
+pub(crate) async fn monitor_health<R: tor_rtcompat::SleepProvider>(
+    runtime: R,
+    monitor: Arc<HealthMonitor>,
+    mut events: BootstrapEvents,
+) {
+    while let Some(status) = events.next().await {
+        monitor.update_from_bootstrap(&status, runtime.now(), runtime.wallclock());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+
+    use super::*;
+
+    #[test]
+    fn dedup() {
+        let monitor = HealthMonitor::default();
+        let now = Instant::now();
+        let wallclock = SystemTime::now();
+        let later = wallclock + Duration::from_secs(30);
+
+        assert!(monitor.warnings().is_empty());
+        monitor.raise(HealthWarningId::Offline, "one".into(), now, wallclock);
+        monitor.raise(
+            HealthWarningId::KeystoreLocked,
+            "locked".into(),
+            now,
+            wallclock,
+        );
+        monitor.raise(HealthWarningId::Offline, "two".into(), now, later);
+
+        let warnings = monitor.warnings();
+        assert_eq!(warnings.len(), 2);
+        let offline = &warnings[0];
+        assert_eq!(offline.id(), HealthWarningId::Offline);
+        assert_eq!(offline.id().to_string(), "offline");
+        assert_eq!(offline.message(), "two");
+        assert_eq!(offline.count(), 2);
+        assert_eq!(offline.first_seen(), wallclock);
+        assert_eq!(offline.last_seen(), later);
+        assert_eq!(warnings[1].id(), HealthWarningId::KeystoreLocked);
+
+        monitor.clear(HealthWarningId::Offline);
+        monitor.clear(HealthWarningId::ClockSkew);
+        let warnings = monitor.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].id(), HealthWarningId::KeystoreLocked);
+    }
+
+    #[test]
+    fn from_bootstrap() {
+        let monitor = HealthMonitor::default();
+        monitor.raise(
+            HealthWarningId::DirectoryStuck,
+            "stuck".into(),
+            Instant::now(),
+            SystemTime::now(),
+        );
+        monitor.raise(
+            HealthWarningId::KeystoreLocked,
+            "locked".into(),
+            Instant::now(),
+            SystemTime::now(),
+        );
+
+        // A fresh status reports no problems,
+        // so the warnings inferred from our bootstrap status go away,
+        // but the others don't.
+        monitor.update_from_bootstrap(
+            &BootstrapStatus::default(),
+            Instant::now(),
+            SystemTime::now(),
+        );
+        let ids: Vec<_> = monitor.warnings().iter().map(|w| w.id()).collect();
+        assert_eq!(ids, [HealthWarningId::KeystoreLocked]);
+    }
+}
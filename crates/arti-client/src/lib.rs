@@ -46,23 +46,47 @@
 
 mod address;
 mod builder;
+#[cfg(feature = "experimental-api")]
+#[cfg_attr(docsrs, doc(cfg(feature = "experimental-api")))]
+pub mod circuit_group;
+#[cfg(feature = "bridge-client")]
+#[cfg_attr(docsrs, doc(cfg(feature = "bridge-client")))]
+pub mod circumvention;
 mod client;
+mod dns_cache;
+mod onion_only;
+#[cfg(all(feature = "onion-service-client", feature = "experimental-api"))]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(all(feature = "onion-service-client", feature = "experimental-api")))
+)]
+pub mod resume;
 #[cfg(feature = "rpc")]
 pub mod rpc;
+#[cfg(feature = "experimental-udp")]
+#[cfg_attr(docsrs, doc(cfg(feature = "experimental-udp")))]
+pub mod udp;
 mod util;
 
 pub mod config;
+pub mod health;
+pub mod prelude;
 pub mod status;
+pub mod stream_rotation;
 
-pub use address::{DangerouslyIntoTorAddr, IntoTorAddr, TorAddr, TorAddrError};
+pub use address::{
+    ConnectUri, ConnectUriError, DangerouslyIntoTorAddr, IntoTorAddr, TorAddr, TorAddrError,
+};
 pub use builder::{TorClientBuilder, MAX_LOCAL_RESOURCE_TIMEOUT};
 pub use client::{BootstrapBehavior, DormantMode, InertTorClient, StreamPrefs, TorClient};
 pub use config::TorClientConfig;
+pub use onion_only::OnionOnlyRejections;
 
+pub use tor_chanmgr::ExternalAddr;
 pub use tor_circmgr::isolation;
 pub use tor_circmgr::IsolationToken;
-pub use tor_error::{ErrorKind, HasKind};
-pub use tor_proto::stream::{DataReader, DataStream, DataWriter};
+pub use tor_error::{ErrorKind, HasKind, Remediation};
+pub use tor_proto::stream::{DataReader, DataStream, DataWriter, IpVersionPreference};
 
 mod err;
 pub use err::{Error, ErrorHint, HintableError};
@@ -76,16 +100,31 @@ pub type Result<T> = std::result::Result<T, Error>;
 #[cfg(feature = "experimental-api")]
 pub use builder::DirProviderBuilder;
 
+#[cfg(feature = "pinned-consensus")]
+#[cfg_attr(docsrs, doc(cfg(feature = "pinned-consensus")))]
+pub use tor_dirmgr::PinnedConsensus;
+
 #[cfg(all(feature = "onion-service-client", feature = "experimental-api"))]
 #[cfg_attr(
     docsrs,
     doc(cfg(all(feature = "onion-service-client", feature = "experimental-api")))
 )]
 pub use {
+    tor_hsclient::{CredentialParseError, HsClientCredential},
     tor_hscrypto::pk::{HsClientDescEncKey, HsId},
     tor_keymgr::KeystoreSelector,
 };
 
+#[cfg(all(feature = "experimental-api", feature = "keymgr"))]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(all(feature = "experimental-api", feature = "keymgr")))
+)]
+pub use tor_keymgr::{
+    ArtiPath, BundledKey, ConflictPolicy, CopyOutcome, KeyBundle, KeyBundleError, KeyPathPattern,
+    KeyType, KeystoreId, SyncedEntry,
+};
+
 #[cfg(feature = "geoip")]
 #[cfg_attr(docsrs, doc(cfg(feature = "geoip")))]
 pub use tor_geoip::CountryCode;
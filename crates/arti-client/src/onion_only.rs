@@ -0,0 +1,142 @@
+//! Bookkeeping for the "onion-only" address filter.
+//!
+//! When `address_filter.onion_only` is set, a [`TorClient`](crate::TorClient)
+//! rejects every request for an address that isn't an onion service,
+//! and counts the requests it rejected here,
+//! so that applications can notice that something tried to leave the onion space.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::err::ErrorDetail;
+
+/// The number of requests that a client rejected because of its onion-only mode.
+///
+/// Returned by [`TorClient::onion_only_rejections`](crate::TorClient::onion_only_rejections).
+///
+/// These counts are shared by a client and all of its isolated clones,
+/// and they only ever go up.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct OnionOnlyRejections {
+    /// How many attempts to open a stream were rejected.
+    streams: u64,
+    /// How many DNS lookups (including reverse lookups) were rejected.
+    resolves: u64,
+}
+
+impl OnionOnlyRejections {
+    /// Return how many attempts to open a stream were rejected.
+    pub fn streams(&self) -> u64 {
+        self.streams
+    }
+
+    /// Return how many DNS lookups (including reverse lookups) were rejected.
+    pub fn resolves(&self) -> u64 {
+        self.resolves
+    }
+}
+
+/// The counters behind [`OnionOnlyRejections`].
+#[derive(Debug, Default)]
+pub(crate) struct OnionOnlyCounters {
+    /// See [`OnionOnlyRejections::streams`].
+    streams: AtomicU64,
+    /// See [`OnionOnlyRejections::resolves`].
+    resolves: AtomicU64,
+}
+
+impl OnionOnlyCounters {
+    /// Count the stream attempt that led to `result`, if it was rejected by the onion-only mode.
+    pub(crate) fn note_stream<T>(&self, result: Result<T, ErrorDetail>) -> Result<T, ErrorDetail> {
+        Self::note(&self.streams, result)
+    }
+
+    /// Count the DNS lookup that led to `result`, if it was rejected by the onion-only mode.
+    pub(crate) fn note_resolve<T>(&self, result: Result<T, ErrorDetail>) -> Result<T, ErrorDetail> {
+        Self::note(&self.resolves, result)
+    }
+
+    /// Return the current values of these counters.
+    pub(crate) fn get(&self) -> OnionOnlyRejections {
+        OnionOnlyRejections {
+            streams: self.streams.load(Ordering::Relaxed),
+            resolves: self.resolves.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Increment `counter` if `result` is an onion-only rejection.
+    fn note<T>(counter: &AtomicU64, result: Result<T, ErrorDetail>) -> Result<T, ErrorDetail> {
+        if let Err(ErrorDetail::ClearnetAddressForbidden) = &result {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use crate::config::ClientAddrConfigBuilder;
+    use crate::{IntoTorAddr as _, StreamPrefs};
+    use tor_error::{ErrorKind, HasKind as _};
+
+    #[test]
+    fn onion_only() {
+        let cfg = ClientAddrConfigBuilder::default()
+            .onion_only(true)
+            .build()
+            .unwrap();
+        let prefs = StreamPrefs::new();
+        let counters = OnionOnlyCounters::default();
+
+        for addr in ["www.torproject.org:443", "192.0.2.1:80", "[::1]:80"] {
+            let addr = addr.into_tor_addr().unwrap();
+            let err = counters
+                .note_stream(addr.into_stream_instructions(&cfg, &prefs))
+                .unwrap_err();
+            assert!(matches!(err, ErrorDetail::ClearnetAddressForbidden));
+            assert_eq!(err.kind(), ErrorKind::ForbiddenClearnetTarget);
+        }
+        let addr = ("www.torproject.org", 1).into_tor_addr().unwrap();
+        assert!(counters
+            .note_resolve(addr.into_resolve_instructions(&cfg, &prefs))
+            .is_err());
+
+        #[cfg(feature = "onion-service-client")]
+        {
+            let onion = "eweiibe6tdjsdprb4px6rqrzzcsi22m4koia44kc5pcjr7nec2rlxyad.onion:443"
+                .into_tor_addr()
+                .unwrap();
+            assert!(counters
+                .note_stream(onion.into_stream_instructions(&cfg, &prefs))
+                .is_ok());
+        }
+
+        assert_eq!(
+            counters.get(),
+            OnionOnlyRejections {
+                streams: 3,
+                resolves: 1,
+            }
+        );
+
+        // Without onion-only mode, nothing is counted.
+        let addr = "www.torproject.org:443".into_tor_addr().unwrap();
+        assert!(counters
+            .note_stream(addr.into_stream_instructions(&Default::default(), &prefs))
+            .is_ok());
+        assert_eq!(counters.get().streams(), 3);
+    }
+}
@@ -0,0 +1,35 @@
+//! A small, stable subset of the `arti-client` API.
+//!
+//! Most applications only need a few parts of this crate: a [`TorClient`],
+//! a way to configure it, a way to say how streams should be made,
+//! and a way to tell what went wrong.
+//! This module re-exports exactly those:
+//!
+//! ```
+//! use arti_client::prelude::*;
+//! ```
+//!
+//! Unlike the rest of this crate, which sometimes changes between minor versions,
+//! everything re-exported here is covered by a stronger promise:
+//! we will not remove anything from this module, or change the signature of
+//! any function or method re-exported here that `tests/prelude-api.rs` exercises,
+//! except in a breaking (semver-incompatible) release.
+//! That file is a compile-time snapshot of the promised API,
+//! so CI fails if a change would break it.
+//!
+//! Experimental APIs (those behind the `experimental-api` feature, or marked
+//! as unstable) are never part of the prelude.
+//! If you need something that isn't here, you can of course still use it
+//! from the rest of the crate; it just comes with the usual, weaker promise.
+
+pub use crate::address::{DangerouslyIntoTorAddr, IntoTorAddr, TorAddr, TorAddrError};
+pub use crate::builder::TorClientBuilder;
+pub use crate::client::{BootstrapBehavior, StreamPrefs, TorClient};
+pub use crate::config::{
+    CfgPath, ConfigBuildError, Reconfigure, TorClientConfig, TorClientConfigBuilder,
+};
+pub use crate::err::Error;
+pub use crate::Result;
+pub use tor_circmgr::IsolationToken;
+pub use tor_error::{ErrorKind, HasKind};
+pub use tor_proto::stream::{DataReader, DataStream, DataWriter};
@@ -0,0 +1,282 @@
+//! Streams to onion services that survive brief network outages.
+//!
+//! On a mobile device, a change of network usually kills every circuit,
+//! and with them every stream.  A [`ResumableStream`] notices this,
+//! makes a new rendezvous with the onion service, and opens an equivalent
+//! stream, reporting a [`ResumeEvent`] rather than failing.
+//!
+//! # The resumption convention
+//!
+//! Tor itself has no notion of resuming a stream: each new stream is a new
+//! connection as far as the onion service can tell.  So this only works
+//! with services that cooperate using the following application-level
+//! convention.
+//!
+//! The first line sent on every stream, before any application data, is
+//!
+//! ```text
+//! RESUME <token> <attempt>\r\n
+//! ```
+//!
+//! where `<token>` is the [`ResumptionToken`] of the session, in hex,
+//! and `<attempt>` is `0` for the first stream and counts up with each
+//! reconnection.  A service that sees a token it knows should treat the
+//! stream as a continuation of the earlier one.
+//!
+//! Data that was in flight when the old stream failed may have been lost,
+//! or may be delivered twice: the application protocol must be able to
+//! recover from that (for example, by acknowledging messages).
+
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::channel::mpsc;
+use futures::{AsyncReadExt as _, AsyncWriteExt as _};
+use rand::Rng as _;
+use tor_proto::stream::DataStream;
+use tor_rtcompat::Runtime;
+use tracing::{debug, info};
+
+use crate::err::ErrorDetail;
+use crate::{StreamPrefs, TorAddr, TorClient};
+
+/// An identifier for a resumable session, shared with the onion service.
+///
+/// See the [module-level documentation](self) for how this is used.
+#[derive(Clone, Copy, Eq, PartialEq, Hash)]
+pub struct ResumptionToken([u8; 16]);
+
+impl ResumptionToken {
+    /// Return a new random token.
+    fn new_random() -> Self {
+        ResumptionToken(rand::thread_rng().gen())
+    }
+
+    /// Return the bytes of this token.
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+}
+
+impl fmt::Display for ResumptionToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{:02x}", b))
+    }
+}
+
+impl fmt::Debug for ResumptionToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The token identifies the session, so don't log it.
+        f.write_str("ResumptionToken(..)")
+    }
+}
+
+/// How hard a [`ResumableStream`] should try to reconnect.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct ResumeConfig {
+    /// How many times to try to reconnect after a failure, before giving up.
+    pub max_attempts: u32,
+    /// How long to wait before each attempt.
+    pub retry_delay: Duration,
+}
+
+impl Default for ResumeConfig {
+    fn default() -> Self {
+        ResumeConfig {
+            max_attempts: 5,
+            retry_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Something that happened to a [`ResumableStream`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum ResumeEvent {
+    /// The stream failed, and was replaced with a new one.
+    Reconnected {
+        /// The number of this connection (the first connection is 0).
+        attempt: u32,
+    },
+    /// The stream failed, and we could not replace it.
+    GaveUp {
+        /// The number of connections we tried.
+        attempts: u32,
+    },
+}
+
+/// A stream to an onion service, which reconnects after failures.
+///
+/// Create one with [`TorClient::connect_resumable`].
+/// See the [module-level documentation](self) for details.
+pub struct ResumableStream<R: Runtime> {
+    /// The client used to make new streams.
+    client: TorClient<R>,
+    /// The address we're connected to.
+    target: TorAddr,
+    /// The preferences to use for new streams.
+    prefs: StreamPrefs,
+    /// The token for this session.
+    token: ResumptionToken,
+    /// Our reconnection policy.
+    config: ResumeConfig,
+    /// The number of the most recent connection attempt.
+    attempt: u32,
+    /// The current stream.
+    stream: DataStream,
+    /// Where to report what happened.
+    events: mpsc::UnboundedSender<ResumeEvent>,
+}
+
+impl<R: Runtime> ResumableStream<R> {
+    /// Open a new resumable stream to `target`.
+    pub(crate) async fn connect(
+        client: TorClient<R>,
+        target: TorAddr,
+        prefs: StreamPrefs,
+        config: ResumeConfig,
+    ) -> crate::Result<(Self, mpsc::UnboundedReceiver<ResumeEvent>)> {
+        if !target.is_onion() {
+            return Err(ErrorDetail::from(tor_error::bad_api_usage!(
+                "Resumable streams are only supported to onion services"
+            ))
+            .into());
+        }
+        let token = ResumptionToken::new_random();
+        let stream = open(&client, &target, &prefs, &token, 0).await?;
+        let (events, rx) = mpsc::unbounded();
+        let stream = ResumableStream {
+            client,
+            target,
+            prefs,
+            token,
+            config,
+            attempt: 0,
+            stream,
+            events,
+        };
+        Ok((stream, rx))
+    }
+
+    /// Return the token identifying this session.
+    pub fn token(&self) -> &ResumptionToken {
+        &self.token
+    }
+
+    /// Read some bytes into `buf`, reconnecting if the stream fails.
+    ///
+    /// Returns 0 once the service has closed the stream.
+    pub async fn read(&mut self, buf: &mut [u8]) -> crate::Result<usize> {
+        loop {
+            match self.stream.read(buf).await {
+                Ok(n) => return Ok(n),
+                Err(e) => self.resume(e).await?,
+            }
+        }
+    }
+
+    /// Write all of `buf` and flush it, reconnecting if the stream fails.
+    ///
+    /// If we reconnect, all of `buf` is sent again on the new stream.
+    pub async fn write_all(&mut self, buf: &[u8]) -> crate::Result<()> {
+        loop {
+            let res = async {
+                self.stream.write_all(buf).await?;
+                self.stream.flush().await
+            }
+            .await;
+            match res {
+                Ok(()) => return Ok(()),
+                Err(e) => self.resume(e).await?,
+            }
+        }
+    }
+
+    /// Replace our failed stream with a new one.
+    async fn resume(&mut self, cause: io::Error) -> crate::Result<()> {
+        info!("Resumable stream failed ({}); reconnecting", cause);
+        let mut last_error = None;
+        for _ in 0..self.config.max_attempts {
+            self.client.runtime().sleep(self.config.retry_delay).await;
+            self.attempt += 1;
+            match open(
+                &self.client,
+                &self.target,
+                &self.prefs,
+                &self.token,
+                self.attempt,
+            )
+            .await
+            {
+                Ok(stream) => {
+                    self.stream = stream;
+                    let _ = self.events.unbounded_send(ResumeEvent::Reconnected {
+                        attempt: self.attempt,
+                    });
+                    return Ok(());
+                }
+                Err(e) => {
+                    debug!("Reconnection attempt {} failed: {}", self.attempt, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        let _ = self.events.unbounded_send(ResumeEvent::GaveUp {
+            attempts: self.config.max_attempts,
+        });
+        Err(last_error.unwrap_or_else(|| io_err(cause)))
+    }
+}
+
+/// Open a stream to `target`, and send the resumption preamble on it.
+async fn open<R: Runtime>(
+    client: &TorClient<R>,
+    target: &TorAddr,
+    prefs: &StreamPrefs,
+    token: &ResumptionToken,
+    attempt: u32,
+) -> crate::Result<DataStream> {
+    let mut stream = client.connect_with_prefs(target.clone(), prefs).await?;
+    let preamble = format!("RESUME {} {}\r\n", token, attempt);
+    let res = async {
+        stream.write_all(preamble.as_bytes()).await?;
+        stream.flush().await
+    }
+    .await;
+    res.map_err(io_err)?;
+    Ok(stream)
+}
+
+/// Wrap an error from a stream we could not resume.
+fn io_err(e: io::Error) -> crate::Error {
+    ErrorDetail::ResumableStreamFailed(Arc::new(e)).into()
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+
+    #[test]
+    fn token_format() {
+        let token = ResumptionToken([0xab; 16]);
+        assert_eq!(token.to_string(), "ab".repeat(16));
+        assert_eq!(format!("{:?}", token), "ResumptionToken(..)");
+        assert_ne!(ResumptionToken::new_random(), ResumptionToken::new_random());
+    }
+}
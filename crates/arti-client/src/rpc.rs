@@ -21,6 +21,8 @@ impl<R: Runtime> TorClient<R> {
         rpc::invoker_ent_list![
             get_client_status::<R>,
             watch_client_status::<R>,
+            get_health_warnings::<R>,
+            get_external_addrs::<R>,
             isolated_client::<R>,
             @special client_connect_with_prefs::<R>,
             @special client_resolve_with_prefs::<R>,
@@ -126,6 +128,132 @@ async fn watch_client_status<R: Runtime>(
     Ok(rpc::NIL)
 }
 
+/// Return the problems that a client has noticed, and that haven't gone away yet.
+///
+/// Each kind of problem is reported at most once,
+/// with a stable identifier (such as `clock-skew` or `keystore-locked`)
+/// that programs may rely on.
+#[derive(Deftly, Debug, Serialize, Deserialize)]
+#[derive_deftly(rpc::DynMethod)]
+#[deftly(rpc(method_name = "arti:get_health_warnings"))]
+struct GetHealthWarnings {}
+
+impl rpc::RpcMethod for GetHealthWarnings {
+    type Output = HealthWarningsInfo;
+    type Update = rpc::NoUpdates;
+}
+
+/// The reply to a [`GetHealthWarnings`] request.
+#[derive(Serialize, Deserialize)]
+struct HealthWarningsInfo {
+    /// The active warnings, ordered by identifier.
+    warnings: Vec<HealthWarningInfo>,
+}
+
+/// A single health warning, as reported over RPC.
+#[derive(Serialize, Deserialize)]
+struct HealthWarningInfo {
+    /// The stable identifier for this kind of problem.
+    id: String,
+    /// A human-readable description of the problem.
+    message: String,
+    /// When the problem was first noticed, in RFC 3339 format.
+    first_seen: String,
+    /// When the problem was most recently noticed, in RFC 3339 format.
+    last_seen: String,
+    /// How many times the problem has been noticed.
+    count: u64,
+}
+
+impl From<crate::health::HealthWarning> for HealthWarningInfo {
+    fn from(w: crate::health::HealthWarning) -> Self {
+        Self {
+            id: w.id().as_str().to_owned(),
+            message: w.message().to_owned(),
+            first_seen: humantime::format_rfc3339_seconds(w.first_seen()).to_string(),
+            last_seen: humantime::format_rfc3339_seconds(w.last_seen()).to_string(),
+            count: w.count(),
+        }
+    }
+}
+
+/// Invocable function to run [`GetHealthWarnings`] on a [`TorClient`].
+async fn get_health_warnings<R: Runtime>(
+    client: Arc<TorClient<R>>,
+    _method: Box<GetHealthWarnings>,
+    _ctx: Arc<dyn rpc::Context>,
+) -> Result<HealthWarningsInfo, rpc::RpcError> {
+    let warnings = client
+        .health_warnings()
+        .into_iter()
+        .map(HealthWarningInfo::from)
+        .collect();
+    Ok(HealthWarningsInfo { warnings })
+}
+
+/// Return the addresses that relays have told a client they see its connections coming from.
+///
+/// These are only as trustworthy as the relays that reported them.
+/// See [`TorClient::external_addrs`] for more caveats.
+///
+/// Since these addresses can identify the user,
+/// this method is not available to observers.
+#[derive(Deftly, Debug, Serialize, Deserialize)]
+#[derive_deftly(rpc::DynMethod)]
+#[deftly(rpc(method_name = "arti:get_external_addrs"))]
+struct GetExternalAddrs {}
+
+impl rpc::RpcMethod for GetExternalAddrs {
+    type Output = ExternalAddrsInfo;
+    type Update = rpc::NoUpdates;
+}
+
+/// The reply to a [`GetExternalAddrs`] request.
+#[derive(Serialize, Deserialize)]
+struct ExternalAddrsInfo {
+    /// The reported addresses, most recently reported first.
+    addrs: Vec<ExternalAddrInfo>,
+}
+
+/// A single reported external address, as reported over RPC.
+#[derive(Serialize, Deserialize)]
+struct ExternalAddrInfo {
+    /// The address that the relays reported.
+    addr: IpAddr,
+    /// How many channels have reported this address.
+    count: usize,
+    /// When the address was most recently reported, in RFC 3339 format.
+    last_reported: String,
+    /// The Ed25519 identity of the relay that most recently reported this address,
+    /// if it is known.
+    last_reporter: Option<String>,
+}
+
+impl From<crate::ExternalAddr> for ExternalAddrInfo {
+    fn from(a: crate::ExternalAddr) -> Self {
+        Self {
+            addr: a.addr(),
+            count: a.n_reports(),
+            last_reported: humantime::format_rfc3339_seconds(a.last_reported()).to_string(),
+            last_reporter: a.last_reporter().map(|id| id.to_string()),
+        }
+    }
+}
+
+/// Invocable function to run [`GetExternalAddrs`] on a [`TorClient`].
+async fn get_external_addrs<R: Runtime>(
+    client: Arc<TorClient<R>>,
+    _method: Box<GetExternalAddrs>,
+    _ctx: Arc<dyn rpc::Context>,
+) -> Result<ExternalAddrsInfo, rpc::RpcError> {
+    let addrs = client
+        .external_addrs()
+        .into_iter()
+        .map(ExternalAddrInfo::from)
+        .collect();
+    Ok(ExternalAddrsInfo { addrs })
+}
+
 /// Create a new isolated client instance.
 ///
 /// Returned ObjectID is a handle for a new `TorClient`,
@@ -7,7 +7,9 @@ use educe::Educe;
 use futures::{Stream, StreamExt};
 use tor_basic_utils::skip_fmt;
 use tor_chanmgr::{ConnBlockage, ConnStatus, ConnStatusEvents};
-use tor_circmgr::{ClockSkewEvents, SkewEstimate};
+
+pub use tor_chanmgr::{BridgeAttempt, BridgeAttemptPhase};
+use tor_circmgr::{CircBuiltEvents, ClockSkewEvents, SkewEstimate};
 use tor_dirmgr::{DirBlockage, DirBootstrapStatus};
 use tracing::debug;
 
@@ -31,6 +33,8 @@ pub struct BootstrapStatus {
     dir_status: DirBootstrapStatus,
     /// Current estimate of our clock skew.
     skew: Option<SkewEstimate>,
+    /// Whether we have built a circuit for user traffic.
+    circ_built: bool,
 }
 
 impl BootstrapStatus {
@@ -53,6 +57,33 @@ impl BootstrapStatus {
         self.conn_status.usable() && self.dir_status.usable_at(now)
     }
 
+    /// Return true if the client has reached the specified bootstrap `milestone`.
+    ///
+    /// Like the rest of the status, this is not monotonic:
+    /// a client that has reached a milestone can later fall back behind it.
+    pub fn reached(&self, milestone: BootstrapMilestone) -> bool {
+        let now = SystemTime::now();
+        match milestone {
+            BootstrapMilestone::NetworkReachable => self.conn_status.usable(),
+            BootstrapMilestone::DirectoryLoaded => self.dir_status.usable_at(now),
+            BootstrapMilestone::CircuitsUsable => self.circ_built && self.ready_for_traffic(),
+        }
+    }
+
+    /// Return our most recent attempt to connect to each bridge,
+    /// most recently updated first.
+    ///
+    /// This is empty unless the client is configured to use bridges.
+    ///
+    /// Each attempt says how far we got with that bridge
+    /// (launching its pluggable transport, connecting to it, handshaking with it,
+    /// or building a circuit through it), and the error that ended it, if it failed.
+    /// It's meant to help a user interface tell the user which bridges are
+    /// working, and why the others aren't.
+    pub fn bridge_attempts(&self) -> &[BridgeAttempt] {
+        self.conn_status.bridge_attempts()
+    }
+
     /// If the client is unable to make forward progress for some reason, return
     /// that reason.
     ///
@@ -108,10 +139,41 @@ impl BootstrapStatus {
         self.skew = status;
     }
 
+    /// Adjust this status based on whether we have built a circuit for user traffic.
+    fn apply_circ_built(&mut self, built: bool) {
+        self.circ_built = built;
+    }
+
     /// Return true if our current clock skew estimate is considered noteworthy.
     fn skew_is_noteworthy(&self) -> bool {
         matches!(&self.skew, Some(s) if s.noteworthy())
     }
+
+    /// Return our current clock skew estimate, if it is considered noteworthy.
+    pub(crate) fn noteworthy_skew(&self) -> Option<&SkewEstimate> {
+        self.skew.as_ref().filter(|s| s.noteworthy())
+    }
+}
+
+/// A point in the bootstrap process that a client can wait for.
+///
+/// See [`TorClient::wait_for`](crate::TorClient::wait_for).
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, derive_more::Display)]
+#[non_exhaustive]
+pub enum BootstrapMilestone {
+    /// We have managed to connect to the Tor network.
+    #[display("network reachable")]
+    NetworkReachable,
+    /// We have enough directory information to build circuits.
+    #[display("directory loaded")]
+    DirectoryLoaded,
+    /// We have built our first circuit for user traffic,
+    /// and we can still connect to the network and build more.
+    ///
+    /// This is later than [`BootstrapStatus::ready_for_traffic`],
+    /// which only says that we expect to be able to build circuits.
+    #[display("circuits usable")]
+    CircuitsUsable,
 }
 
 /// A reason why a client believes it is stuck.
@@ -219,6 +281,7 @@ pub(crate) async fn report_status(
     conn_status: ConnStatusEvents,
     dir_status: impl Stream<Item = DirBootstrapStatus> + Send + Unpin,
     skew_status: ClockSkewEvents,
+    circ_status: CircBuiltEvents,
 ) {
     /// Internal enumeration to combine incoming status changes.
     #[allow(clippy::large_enum_variant)]
@@ -229,11 +292,14 @@ pub(crate) async fn report_status(
         Dir(DirBootstrapStatus),
         /// A clock skew change
         Skew(Option<SkewEstimate>),
+        /// A change in whether we have built a circuit
+        Circ(bool),
     }
     let mut stream = futures::stream::select_all(vec![
         conn_status.map(Event::Conn).boxed(),
         dir_status.map(Event::Dir).boxed(),
         skew_status.map(Event::Skew).boxed(),
+        circ_status.map(Event::Circ).boxed(),
     ]);
 
     while let Some(event) = stream.next().await {
@@ -242,6 +308,7 @@ pub(crate) async fn report_status(
             Event::Conn(e) => b.apply_conn_status(e),
             Event::Dir(e) => b.apply_dir_status(e),
             Event::Skew(e) => b.apply_skew_estimate(e),
+            Event::Circ(e) => b.apply_circ_built(e),
         }
         debug!("{}", *b);
     }
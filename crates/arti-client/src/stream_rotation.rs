@@ -0,0 +1,500 @@
+//! Forced rotation of circuits, for deployments that need unlinkability.
+//!
+//! Normally, a [`TorClient`](crate::TorClient) keeps using a circuit for as long
+//! as it is usable, and streams stay open for as long as the application wants.
+//! Streams made over the same circuit share an exit, and can be linked to one
+//! another by that exit.
+//! Some deployments would rather pay for extra circuits than allow
+//! that linkage to go on indefinitely: for them, the `stream_rotation`
+//! configuration section sets a maximum lifetime, and a maximum time since
+//! the last new stream, for the circuits that carry user streams,
+//! either for every destination or for destinations matching a pattern.
+//! [`StreamPrefs::rotation_limits`](crate::StreamPrefs::rotation_limits)
+//! overrides the limits for the streams made with a particular set of
+//! preferences (and so, typically, for a particular isolation group).
+//!
+//! When a circuit reaches one of its limits, we stop using it for new streams,
+//! and close it, together with any streams that are still open on it.
+//! The application sees those streams fail;
+//! it can learn why from [`TorClient::stream_rotation_events`](crate::TorClient::stream_rotation_events).
+//!
+//! The limits are measured from the streams that we open on a circuit:
+//! its lifetime starts when its first stream is opened,
+//! and the time since its last stream counts from the most recent stream opened on it.
+//! We don't measure whether the application is still using its streams,
+//! so a busy long-lived stream doesn't keep a circuit open:
+//! this is not an idle timeout.
+//! Circuits that carry only directory or onion service traffic of our own are not affected.
+
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use futures::channel::mpsc;
+use futures::{future, FutureExt as _, Stream, StreamExt as _};
+use tor_circmgr::CircMgr;
+use tor_proto::circuit::ClientCirc;
+use tor_rtcompat::Runtime;
+use tracing::info;
+
+use crate::config::StreamRotationConfig;
+
+/// Limits on how long a circuit may be used for a stream.
+///
+/// The limits for a circuit are the strictest ones of any stream that has been opened on it.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct RotationLimits {
+    /// How long the circuit may be used after its first stream was opened.
+    max_lifetime: Option<Duration>,
+    /// How long the circuit may go without a new stream being opened on it.
+    max_since_last_stream: Option<Duration>,
+}
+
+impl RotationLimits {
+    /// Return a new `RotationLimits`.
+    ///
+    /// `None`, or a duration of zero, means "no limit".
+    pub fn new(max_lifetime: Option<Duration>, max_since_last_stream: Option<Duration>) -> Self {
+        let nonzero = |d: Option<Duration>| d.filter(|d| !d.is_zero());
+        RotationLimits {
+            max_lifetime: nonzero(max_lifetime),
+            max_since_last_stream: nonzero(max_since_last_stream),
+        }
+    }
+
+    /// Return limits that never close a circuit.
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// Return how long a circuit may be used after its first stream was opened.
+    pub fn max_lifetime(&self) -> Option<Duration> {
+        self.max_lifetime
+    }
+
+    /// Return how long a circuit may go without a new stream being opened on it.
+    pub fn max_since_last_stream(&self) -> Option<Duration> {
+        self.max_since_last_stream
+    }
+
+    /// Return true if these limits never close a circuit.
+    fn is_unlimited(&self) -> bool {
+        self.max_lifetime.is_none() && self.max_since_last_stream.is_none()
+    }
+
+    /// Return the strictest combination of these limits and `other`.
+    fn strictest(self, other: RotationLimits) -> RotationLimits {
+        /// Return the smaller of two limits, where `None` is infinite.
+        fn min(a: Option<Duration>, b: Option<Duration>) -> Option<Duration> {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            }
+        }
+        RotationLimits {
+            max_lifetime: min(self.max_lifetime, other.max_lifetime),
+            max_since_last_stream: min(self.max_since_last_stream, other.max_since_last_stream),
+        }
+    }
+}
+
+/// Why a circuit was closed by stream rotation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum RotationReason {
+    /// The circuit reached its maximum lifetime.
+    MaxLifetime,
+    /// No new stream was opened on the circuit for its maximum time since the last stream.
+    MaxSinceLastStream,
+}
+
+/// A report that stream rotation closed a circuit, and any streams still open on it.
+///
+/// Returned by [`StreamRotationEvents`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct StreamRotationEvent {
+    /// Why we closed the circuit.
+    pub reason: RotationReason,
+    /// How long ago the first stream on the circuit was opened.
+    pub age: Duration,
+    /// How many streams we opened on the circuit, in total.
+    ///
+    /// Some of them may have been closed already.
+    pub n_streams: usize,
+}
+
+/// A [`Stream`] of [`StreamRotationEvent`]s.
+///
+/// Returned by [`TorClient::stream_rotation_events`](crate::TorClient::stream_rotation_events).
+#[derive(Debug)]
+pub struct StreamRotationEvents(mpsc::UnboundedReceiver<StreamRotationEvent>);
+
+impl Stream for StreamRotationEvents {
+    type Item = StreamRotationEvent;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        std::pin::Pin::new(&mut self.0).poll_next(cx)
+    }
+}
+
+/// The circuits that carry user streams, and when they must be closed.
+pub(crate) struct StreamRotation<C = ClientCirc> {
+    /// Our mutable state, behind a lock.
+    inner: Mutex<Inner<C>>,
+    /// Senders for the streams returned by [`StreamRotation::events`].
+    listeners: Mutex<Vec<mpsc::UnboundedSender<StreamRotationEvent>>>,
+    /// Used to tell [`rotate_streams`] that there is a new deadline.
+    wakeup: mpsc::Sender<()>,
+}
+
+/// The mutable state of a [`StreamRotation`].
+struct Inner<C> {
+    /// Our current configuration.
+    config: StreamRotationConfig,
+    /// The circuits that have limits.
+    ///
+    /// We expect only a handful of these at a time, so a list is fine.
+    circs: Vec<Tracked<C>>,
+}
+
+/// A circuit that has rotation limits.
+struct Tracked<C> {
+    /// The circuit itself.
+    circ: Weak<C>,
+    /// When we opened the first stream on it.
+    first_stream: Instant,
+    /// When we most recently opened a stream on it.
+    last_stream: Instant,
+    /// How many streams we've opened on it.
+    n_streams: usize,
+    /// The strictest limits of any of those streams.
+    limits: RotationLimits,
+}
+
+impl<C> Tracked<C> {
+    /// Return when this circuit must be closed, and why.
+    ///
+    /// The limits of a tracked circuit are never unlimited,
+    /// so this returns `None` only if the deadline is too far away to represent.
+    fn deadline(&self) -> Option<(Instant, RotationReason)> {
+        let lifetime = self
+            .limits
+            .max_lifetime
+            .and_then(|d| self.first_stream.checked_add(d))
+            .map(|t| (t, RotationReason::MaxLifetime));
+        let since_last_stream = self
+            .limits
+            .max_since_last_stream
+            .and_then(|d| self.last_stream.checked_add(d))
+            .map(|t| (t, RotationReason::MaxSinceLastStream));
+        lifetime
+            .into_iter()
+            .chain(since_last_stream)
+            .min_by_key(|(t, _)| *t)
+    }
+}
+
+impl<C> StreamRotation<C> {
+    /// Create a new `StreamRotation`, tracking no circuits.
+    ///
+    /// Returns the receiver that [`rotate_streams`] should watch for new deadlines.
+    pub(crate) fn new(config: StreamRotationConfig) -> (Self, mpsc::Receiver<()>) {
+        let (wakeup, wakeup_rx) = mpsc::channel(0);
+        let rotation = StreamRotation {
+            inner: Mutex::new(Inner {
+                config,
+                circs: Vec::new(),
+            }),
+            listeners: Mutex::new(Vec::new()),
+            wakeup,
+        };
+        (rotation, wakeup_rx)
+    }
+
+    /// Replace our configuration with `config`.
+    ///
+    /// Circuits that already have limits keep them.
+    pub(crate) fn reconfigure(&self, config: &StreamRotationConfig) {
+        self.lock().config = config.clone();
+    }
+
+    /// Return the limits for a stream to `hostname`.
+    ///
+    /// `prefs_limits`, if present, overrides our configuration.
+    pub(crate) fn limits_for(
+        &self,
+        hostname: &str,
+        prefs_limits: Option<RotationLimits>,
+    ) -> RotationLimits {
+        if let Some(limits) = prefs_limits {
+            return limits;
+        }
+        let inner = self.lock();
+        let config = &inner.config;
+        match config.rules.iter().find(|rule| rule.matches(hostname)) {
+            Some(rule) => {
+                RotationLimits::new(Some(rule.max_lifetime), Some(rule.max_since_last_stream))
+            }
+            None => RotationLimits::new(
+                Some(config.max_lifetime),
+                Some(config.max_since_last_stream),
+            ),
+        }
+    }
+
+    /// Remember that we opened a stream with `limits` on `circ`, at `now`.
+    pub(crate) fn note_stream(&self, circ: &Arc<C>, limits: RotationLimits, now: Instant) {
+        if limits.is_unlimited() {
+            return;
+        }
+        let mut inner = self.lock();
+        let weak = Arc::downgrade(circ);
+        match inner
+            .circs
+            .iter_mut()
+            .find(|t| Weak::ptr_eq(&t.circ, &weak))
+        {
+            Some(tracked) => {
+                tracked.last_stream = now;
+                tracked.n_streams += 1;
+                tracked.limits = tracked.limits.strictest(limits);
+            }
+            None => inner.circs.push(Tracked {
+                circ: weak,
+                first_stream: now,
+                last_stream: now,
+                n_streams: 1,
+                limits,
+            }),
+        }
+        drop(inner);
+        // If the background task is already awake, it will see the new deadline anyway.
+        let _ = self.wakeup.clone().try_send(());
+    }
+
+    /// Stop tracking every circuit that must be closed by `now`,
+    /// and return them, with a description of why.
+    pub(crate) fn take_expired(&self, now: Instant) -> Vec<(Weak<C>, StreamRotationEvent)> {
+        let mut expired = Vec::new();
+        self.lock()
+            .circs
+            .retain(|tracked| match tracked.deadline() {
+                Some((when, reason)) if when <= now => {
+                    expired.push((
+                        tracked.circ.clone(),
+                        StreamRotationEvent {
+                            reason,
+                            age: now.saturating_duration_since(tracked.first_stream),
+                            n_streams: tracked.n_streams,
+                        },
+                    ));
+                    false
+                }
+                Some(_) => true,
+                // This circuit will never reach its limits.
+                None => false,
+            });
+        expired
+    }
+
+    /// Return the next time at which a circuit must be closed, if any.
+    pub(crate) fn next_deadline(&self) -> Option<Instant> {
+        self.lock()
+            .circs
+            .iter()
+            .filter_map(|tracked| tracked.deadline())
+            .map(|(when, _)| when)
+            .min()
+    }
+
+    /// Return a new stream of [`StreamRotationEvent`]s.
+    pub(crate) fn events(&self) -> StreamRotationEvents {
+        let (tx, rx) = mpsc::unbounded();
+        self.listeners.lock().expect("lock poisoned").push(tx);
+        StreamRotationEvents(rx)
+    }
+
+    /// Tell every listener about `event`.
+    fn note(&self, event: &StreamRotationEvent) {
+        self.listeners
+            .lock()
+            .expect("lock poisoned")
+            .retain(|tx| tx.unbounded_send(event.clone()).is_ok());
+    }
+
+    /// Lock the inner state.
+    ///
+    /// A panic while holding this lock can't leave the state inconsistent,
+    /// so we ignore poisoning.
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner<C>> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Close the circuits in `rotation` as they reach their limits.
+///
+/// This function is spawned as a task during client construction,
+/// and exits once `rotation` is dropped.
+pub(crate) async fn rotate_streams<R: Runtime>(
+    runtime: R,
+    rotation: Weak<StreamRotation>,
+    circmgr: Weak<CircMgr<R>>,
+    mut wakeup: mpsc::Receiver<()>,
+) {
+    loop {
+        let Some(rot) = rotation.upgrade() else {
+            break;
+        };
+        for (circ, event) in rot.take_expired(runtime.now()) {
+            let Some(circ) = circ.upgrade() else {
+                continue;
+            };
+            if circ.is_closing() {
+                continue;
+            }
+            if let Some(circmgr) = circmgr.upgrade() {
+                circmgr.retire_circ(&circ.unique_id());
+            }
+            info!(
+                "{}: closing after {} streams, because of stream rotation ({:?})",
+                circ.unique_id(),
+                event.n_streams,
+                event.reason,
+            );
+            circ.terminate();
+            rot.note(&event);
+        }
+        let next = rot.next_deadline();
+        drop(rot);
+
+        let sleep = match next {
+            Some(when) => runtime
+                .sleep(when.saturating_duration_since(runtime.now()))
+                .left_future(),
+            None => future::pending::<()>().right_future(),
+        };
+        futures::select_biased! {
+            woken = wakeup.next() => {
+                if woken.is_none() {
+                    // The StreamRotation is gone.
+                    break;
+                }
+            }
+            () = sleep.fuse() => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use crate::config::{StreamRotationConfigBuilder, StreamRotationRuleBuilder};
+
+    const SEC: Duration = Duration::from_secs(1);
+
+    fn config() -> StreamRotationConfig {
+        let mut cfg = StreamRotationConfigBuilder::default();
+        cfg.max_lifetime(SEC * 600);
+        let mut rule = StreamRotationRuleBuilder::default();
+        rule.pattern("*.example.com")
+            .max_since_last_stream(SEC * 10);
+        cfg.rules().push(rule);
+        let mut rule = StreamRotationRuleBuilder::default();
+        rule.pattern("unlimited.example.org");
+        cfg.rules().push(rule);
+        cfg.build().unwrap()
+    }
+
+    #[test]
+    fn limits() {
+        let (rot, _wakeup) = StreamRotation::<u8>::new(config());
+        assert_eq!(
+            rot.limits_for("www.Example.com", None),
+            RotationLimits::new(None, Some(SEC * 10))
+        );
+        assert_eq!(
+            rot.limits_for("example.com.", None),
+            RotationLimits::new(None, Some(SEC * 10))
+        );
+        assert_eq!(
+            rot.limits_for("notexample.com", None),
+            RotationLimits::new(Some(SEC * 600), None)
+        );
+        assert_eq!(
+            rot.limits_for("unlimited.example.org", None),
+            RotationLimits::unlimited()
+        );
+        let mine = RotationLimits::new(Some(SEC), Some(SEC));
+        assert_eq!(rot.limits_for("www.example.com", Some(mine)), mine);
+
+        assert!(StreamRotationRuleBuilder::default()
+            .pattern("www.*.com")
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn expiry() {
+        let (rot, _wakeup) = StreamRotation::new(config());
+        let mut events = rot.events();
+        let now = Instant::now();
+        let circ_1 = Arc::new(1_u8);
+        let circ_2 = Arc::new(2_u8);
+        let circ_3 = Arc::new(3_u8);
+
+        rot.note_stream(&circ_1, rot.limits_for("www.example.com", None), now);
+        rot.note_stream(&circ_2, rot.limits_for("www.torproject.org", None), now);
+        rot.note_stream(&circ_3, RotationLimits::unlimited(), now);
+        assert_eq!(rot.next_deadline(), Some(now + SEC * 10));
+
+        // Another stream postpones circ_1's deadline; a stricter one shortens circ_2's life.
+        rot.note_stream(
+            &circ_1,
+            rot.limits_for("www.example.com", None),
+            now + SEC * 5,
+        );
+        rot.note_stream(
+            &circ_2,
+            RotationLimits::new(Some(SEC * 60), None),
+            now + SEC * 5,
+        );
+        assert!(rot.take_expired(now + SEC * 10).is_empty());
+        assert_eq!(rot.next_deadline(), Some(now + SEC * 15));
+
+        let expired = rot.take_expired(now + SEC * 15);
+        assert_eq!(expired.len(), 1);
+        let (circ, event) = &expired[0];
+        assert!(Weak::ptr_eq(circ, &Arc::downgrade(&circ_1)));
+        assert_eq!(event.reason, RotationReason::MaxSinceLastStream);
+        assert_eq!(event.age, SEC * 15);
+        assert_eq!(event.n_streams, 2);
+
+        let expired = rot.take_expired(now + SEC * 60);
+        assert_eq!(expired.len(), 1);
+        assert!(Weak::ptr_eq(&expired[0].0, &Arc::downgrade(&circ_2)));
+        assert_eq!(expired[0].1.reason, RotationReason::MaxLifetime);
+        assert_eq!(rot.next_deadline(), None);
+
+        rot.note(&expired[0].1);
+        drop(rot);
+        let got: Vec<_> = futures::executor::block_on(events.by_ref().collect());
+        assert_eq!(got.len(), 1);
+        assert_eq!(got[0].reason, RotationReason::MaxLifetime);
+    }
+}
@@ -0,0 +1,85 @@
+//! Experimental support for UDP over Tor.
+//!
+//! A [`TorDatagramSocket`] carries datagrams to and from a single destination,
+//! through an exit that supports UDP streams (proposal 339).
+//! Use [`TorClient::connect_udp`](crate::TorClient::connect_udp) to make one.
+//!
+//! # Using with QUIC
+//!
+//! `TorDatagramSocket` is meant to be usable as the transport for a QUIC
+//! implementation, such as `quinn`, so that HTTP/3 clients can be tested over
+//! Tor.  To plug it into `quinn`, implement `quinn::AsyncUdpSocket` with
+//! [`send`](TorDatagramSocket::send) and [`recv`](TorDatagramSocket::recv),
+//! and report a fixed placeholder address as the peer:
+//! the socket is already connected to its destination, and the exit
+//! does not tell us the address of whoever sent each datagram.
+//!
+//! QUIC packets must fit within [`TorDatagramSocket::MAX_DATAGRAM_LEN`]:
+//! configure the QUIC implementation with a matching maximum UDP payload size,
+//! and disable path MTU discovery.
+//!
+//! # Congestion control
+//!
+//! Datagrams count towards the circuit's SENDME window, as stream data does,
+//! but there is no per-stream window: a sender is only slowed down once the
+//! whole circuit's window is used up, and that window is shared with any
+//! other streams on the circuit.
+//! Datagrams we can't send right away are queued in the circuit's buffers,
+//! and an exit may drop datagrams it can't forward.
+//! QUIC's own congestion control is therefore what should keep the sender's
+//! rate in check.  Expect its round-trip estimates to be high and noisy:
+//! every datagram crosses three relays, and shares the circuit with any
+//! other streams on it.
+
+use futures::lock::Mutex;
+use tor_proto::stream::{DatagramReader, DatagramStream, DatagramWriter};
+
+use crate::err::ErrorDetail;
+
+/// A connected UDP socket, whose traffic is carried over Tor.
+///
+/// See the [module-level documentation](self) for more information.
+#[derive(Debug)]
+pub struct TorDatagramSocket {
+    /// The receiving half of the underlying stream.
+    reader: Mutex<DatagramReader>,
+    /// The sending half of the underlying stream.
+    writer: Mutex<DatagramWriter>,
+}
+
+impl TorDatagramSocket {
+    /// The largest datagram that can be sent or received on a `TorDatagramSocket`.
+    pub const MAX_DATAGRAM_LEN: usize = DatagramStream::MAX_DATAGRAM_LEN;
+
+    /// Wrap a connected [`DatagramStream`].
+    pub(crate) fn new(stream: DatagramStream) -> Self {
+        let (reader, writer) = stream.split();
+        TorDatagramSocket {
+            reader: Mutex::new(reader),
+            writer: Mutex::new(writer),
+        }
+    }
+
+    /// Send a single datagram to the destination of this socket.
+    ///
+    /// Returns an error if `datagram` is empty or longer than
+    /// [`MAX_DATAGRAM_LEN`](Self::MAX_DATAGRAM_LEN).
+    pub async fn send(&self, datagram: &[u8]) -> crate::Result<()> {
+        self.writer
+            .lock()
+            .await
+            .send(datagram)
+            .await
+            .map_err(stream_err)
+    }
+
+    /// Receive a single datagram from the destination of this socket.
+    pub async fn recv(&self) -> crate::Result<Vec<u8>> {
+        self.reader.lock().await.recv().await.map_err(stream_err)
+    }
+}
+
+/// Wrap a `tor_proto::Error` from a UDP stream.
+fn stream_err(cause: tor_proto::Error) -> crate::Error {
+    ErrorDetail::StreamFailed { kind: "udp", cause }.into()
+}
@@ -0,0 +1,89 @@
+//! Compile-time snapshot of the API promised by `arti_client::prelude`.
+//!
+//! Everything exercised here must keep working, unchanged, for as long as the
+//! major version of `arti-client` stays the same.
+//! If this file stops compiling, a change has broken that promise:
+//! undo the change, or save it for the next major version.
+//!
+//! Only ever add to this file; don't edit what's already here
+//! unless the major version has changed.
+
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use arti_client::prelude::*;
+use tor_rtcompat::Runtime;
+
+/// The functions and methods that don't need a runtime.
+#[test]
+fn signatures() {
+    let _: fn() -> StreamPrefs = StreamPrefs::new;
+    let _: fn(&mut StreamPrefs) -> &mut StreamPrefs = StreamPrefs::ipv6_preferred;
+    let _: fn(&mut StreamPrefs) -> &mut StreamPrefs = StreamPrefs::ipv6_only;
+    let _: fn(&mut StreamPrefs) -> &mut StreamPrefs = StreamPrefs::ipv4_preferred;
+    let _: fn(&mut StreamPrefs) -> &mut StreamPrefs = StreamPrefs::ipv4_only;
+    let _: fn(&mut StreamPrefs) -> &mut StreamPrefs = StreamPrefs::optimistic;
+    let _: fn(&mut StreamPrefs) -> &mut StreamPrefs = StreamPrefs::new_isolation_group;
+    let _: fn(&mut StreamPrefs) -> &mut StreamPrefs = StreamPrefs::isolate_every_stream;
+    let _: fn(&mut StreamPrefs, IsolationToken) -> &mut StreamPrefs =
+        StreamPrefs::set_isolation::<IsolationToken>;
+
+    let _: fn() -> IsolationToken = IsolationToken::new;
+
+    let _: fn() -> TorClientConfigBuilder = TorClientConfig::builder;
+    let _: fn(&TorClientConfigBuilder) -> std::result::Result<TorClientConfig, ConfigBuildError> =
+        TorClientConfigBuilder::build;
+    let _: fn(PathBuf, PathBuf) -> TorClientConfigBuilder =
+        TorClientConfigBuilder::from_directories::<PathBuf, PathBuf>;
+    let _: fn(String) -> CfgPath = CfgPath::new;
+
+    let _: fn(&Error) -> ErrorKind = <Error as HasKind>::kind;
+
+    let _: fn((&'static str, u16)) -> std::result::Result<TorAddr, TorAddrError> =
+        <(&'static str, u16) as IntoTorAddr>::into_tor_addr;
+    let _: fn(String) -> std::result::Result<TorAddr, TorAddrError> =
+        <String as IntoTorAddr>::into_tor_addr;
+    let _: fn(std::net::SocketAddr) -> std::result::Result<TorAddr, TorAddrError> =
+        <std::net::SocketAddr as DangerouslyIntoTorAddr>::into_tor_addr_dangerously;
+
+    let _: fn(DataStream) -> (DataReader, DataWriter) = DataStream::split;
+
+    let _ = [BootstrapBehavior::OnDemand, BootstrapBehavior::Manual];
+    let _ = [
+        Reconfigure::AllOrNothing,
+        Reconfigure::WarnOnFailures,
+        Reconfigure::CheckAllOrNothing,
+    ];
+}
+
+/// The methods that need a runtime.
+///
+/// This function is never run: it only needs to compile.
+#[allow(dead_code)]
+async fn client_api<R: Runtime>(runtime: R, config: TorClientConfig) -> Result<()> {
+    let builder: TorClientBuilder<R> = TorClient::with_runtime(runtime);
+    let mut client: TorClient<R> = builder
+        .config(config.clone())
+        .bootstrap_behavior(BootstrapBehavior::Manual)
+        .create_unbootstrapped()?;
+    client.bootstrap().await?;
+
+    let stream: DataStream = client.connect(("example.com", 80)).await?;
+    let _: (DataReader, DataWriter) = stream.split();
+    let _: DataStream = client
+        .connect_with_prefs(("example.com", 443), &StreamPrefs::new())
+        .await?;
+    let _: Vec<IpAddr> = client.resolve("example.com").await?;
+    let _: Vec<String> = client.resolve_ptr("192.0.2.1".parse().unwrap()).await?;
+
+    let _: TorClient<R> = client.isolated_client();
+    let _: TorClient<R> = client.clone_with_prefs(StreamPrefs::new());
+    client.set_stream_prefs(StreamPrefs::new());
+    client.reconfigure(&config, Reconfigure::WarnOnFailures)?;
+
+    let _: TorClient<R> = TorClient::with_runtime(client.runtime().clone())
+        .config(config)
+        .create_bootstrapped()
+        .await?;
+    Ok(())
+}
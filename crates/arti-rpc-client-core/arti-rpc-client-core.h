@@ -199,11 +199,69 @@ typedef struct ArtiRpcStr ArtiRpcStr;
  */
 typedef struct ArtiRpcHandle ArtiRpcHandle;
 
+/**
+ * A pool of open connections to Arti.
+ *
+ * This is a thread-safe type: you may safely use it from multiple threads at once.
+ *
+ * Once you are no longer going to use any of its connections, you must free
+ * it with [`arti_rpc_conn_pool_free`]
+ */
+typedef struct ArtiRpcConnPool ArtiRpcConnPool;
+
 /**
  * The type of a message returned by an RPC request.
  */
 typedef int ArtiRpcResponseType;
 
+/**
+ * A function to be called when an asynchronous RPC request completes.
+ *
+ * It receives the status of the request; a response string, or NULL;
+ * an error object, or NULL; and the `user_data` pointer that was passed along with it.
+ *
+ * On success, `status` is `ARTI_RPC_STATUS_SUCCESS`, `response` is a newly allocated string
+ * containing the JSON response to the request, and `error` is NULL.
+ * Otherwise, `status` is some other status code, `response` is NULL,
+ * and `error` is a newly allocated error object.
+ *
+ * The function takes ownership of `response` and `error`:
+ * it is responsible for making sure that they are eventually freed.
+ */
+typedef void (*ArtiRpcExecuteCallback)(ArtiRpcStatus status,
+                                       ArtiRpcStr *response,
+                                       ArtiRpcError *error,
+                                       void *user_data);
+
+/**
+ * A function to be called when an asynchronous RPC request receives an update.
+ *
+ * It receives a newly allocated string containing the JSON update
+ * (including its `id` and `update` fields),
+ * and the `user_data` pointer that was passed along with the request.
+ *
+ * The function takes ownership of `update`:
+ * it is responsible for making sure that it is eventually freed.
+ */
+typedef void (*ArtiRpcUpdateCallback)(ArtiRpcStr *update, void *user_data);
+
+/**
+ * A function to be called when an `ArtiRpcConn` replaces its connection to Arti.
+ *
+ * It receives a newly allocated string containing a JSON object
+ * with two members:
+ * `abandoned_requests`, a list of the IDs of the requests
+ * that were in progress on the old connection,
+ * and `invalidated_objects`, a list of the object IDs
+ * that Arti had given us on the old connection, which are no longer valid.
+ * It also receives the `user_data` pointer that was passed to
+ * `arti_rpc_connect_with_reconnect`.
+ *
+ * The function takes ownership of `report`:
+ * it is responsible for making sure that it is eventually freed.
+ */
+typedef void (*ArtiRpcReconnectCallback)(ArtiRpcStr *report, void *user_data);
+
 /**
  * A constant indicating that a message is a final result.
  *
@@ -323,6 +381,24 @@ typedef int ArtiRpcResponseType;
  */
 #define ARTI_RPC_STATUS_NOT_AUTHENTICATED 12
 
+/**
+ * The peer reports that one of our requests was cancelled before it could finish.
+ *
+ * (This error was sent by the peer, in response to one of our requests;
+ * typically, because we asked for it to be cancelled with `arti_rpc_handle_cancel`.
+ * No further responses to that request will be received or accepted.)
+ */
+#define ARTI_RPC_STATUS_REQUEST_CANCELLED 13
+
+/**
+ * We gave up waiting for the response to one of our requests,
+ * because it did not arrive before the request's timeout.
+ *
+ * (We also asked the peer to cancel the request, but we did not wait to learn
+ * whether it did.  See `arti_rpc_conn_set_default_timeout`.)
+ */
+#define ARTI_RPC_STATUS_TIMED_OUT 14
+
 
 
 
@@ -346,6 +422,10 @@ extern "C" {
  *
  * The location of the instance and the method to connect to it are described in
  * `connection_string`.
+ * If `connection_string` is NULL, search for a running Arti instead,
+ * as described in the documentation for `arti_rpc_client_core::discovery`.
+ * If no Arti is found, the error message explains why each location we tried
+ * could not be used.
  *
  * (TODO RPC: Document the format of this string better!)
  *
@@ -363,6 +443,46 @@ ArtiRpcStatus arti_rpc_connect(const char *connection_string,
                                ArtiRpcConn **rpc_conn_out,
                                ArtiRpcError **error_out);
 
+/**
+ * Try to open a new connection to an Arti instance,
+ * which reconnects automatically if Arti closes it.
+ *
+ * This behaves as `arti_rpc_connect`, except for what happens after Arti closes the connection.
+ * Ordinarily, every later request on the connection would fail.
+ * Instead, the next request makes a new connection to the same location
+ * (trying up to `max_attempts` times, one second apart),
+ * authenticates again, and is sent on the new connection.
+ * Requests addressed to the session ID from `arti_rpc_conn_get_session_id`
+ * are sent to the new session.
+ *
+ * Requests that were in progress when the old connection closed still fail,
+ * and every other object ID from the old connection becomes invalid.
+ * If `on_reconnect` is not NULL, it is called with a report of what was lost,
+ * and with `user_data`, each time we reconnect.
+ * (See [`ArtiRpcReconnectCallback`] for the arguments it receives.)
+ * It is called on the thread that sent the request that caused us to reconnect,
+ * before that request is sent;
+ * it must not send requests on the connection.
+ * Both it and `user_data` must be safe to use from any thread.
+ *
+ * If we cannot reconnect, the request fails with `ARTI_RPC_STATUS_SHUTDOWN`,
+ * and the next request tries again.
+ *
+ * # Ownership
+ *
+ * The caller is responsible for making sure that `*rpc_conn_out` and `*error_out`,
+ * if set, are eventually freed.
+ *
+ * The callback is responsible for making sure that the `report` passed to it
+ * is eventually freed.
+ */
+ArtiRpcStatus arti_rpc_connect_with_reconnect(const char *connection_string,
+                                              uint32_t max_attempts,
+                                              ArtiRpcReconnectCallback on_reconnect,
+                                              void *user_data,
+                                              ArtiRpcConn **rpc_conn_out,
+                                              ArtiRpcError **error_out);
+
 /**
  * Given a pointer to an RPC connection, return the object ID for its negotiated session.
  *
@@ -393,6 +513,10 @@ const char *arti_rpc_conn_get_session_id(const ArtiRpcConn *rpc_conn);
  *
  * (If response_out is set to NULL, then any successful response will be ignored.)
  *
+ * If a default timeout has been set with `arti_rpc_conn_set_default_timeout`,
+ * and no response arrives before it expires,
+ * return `ARTI_RPC_STATUS_TIMED_OUT`, and ask Arti to cancel the request.
+ *
  * # Ownership
  *
  * The caller is responsible for making sure that `*error_out`, if set, is eventually freed.
@@ -404,6 +528,103 @@ ArtiRpcStatus arti_rpc_conn_execute(const ArtiRpcConn *rpc_conn,
                                     ArtiRpcStr **response_out,
                                     ArtiRpcError **error_out);
 
+/**
+ * Run an RPC request over `rpc_conn`, and wait no longer than `timeout_msec` milliseconds
+ * for a successful response.
+ *
+ * This function behaves like `arti_rpc_conn_execute`,
+ * except that it uses `timeout_msec` instead of the connection's default timeout.
+ * If `timeout_msec` is 0, it waits for as long as it takes.
+ *
+ * If no response arrives in time, return `ARTI_RPC_STATUS_TIMED_OUT`.
+ * In that case, we also ask Arti to cancel the request,
+ * but we don't wait to find out whether it did.
+ *
+ * # Ownership
+ *
+ * The caller is responsible for making sure that `*error_out`, if set, is eventually freed.
+ *
+ * The caller is responsible for making sure that `*response_out`, if set, is eventually freed.
+ */
+ArtiRpcStatus arti_rpc_conn_execute_with_timeout(const ArtiRpcConn *rpc_conn,
+                                                 const char *msg,
+                                                 uint64_t timeout_msec,
+                                                 ArtiRpcStr **response_out,
+                                                 ArtiRpcError **error_out);
+
+/**
+ * Set how long `arti_rpc_conn_execute` waits for a response on `rpc_conn`
+ * before giving up with `ARTI_RPC_STATUS_TIMED_OUT`.
+ *
+ * If `timeout_msec` is 0, it waits for as long as it takes.
+ * This is the default.
+ *
+ * The timeout does not apply to functions that return a handle, or that use a callback:
+ * you can cancel those requests yourself, with `arti_rpc_handle_cancel`.
+ *
+ * On success, return `ARTI_RPC_STATUS_SUCCESS`.
+ * Otherwise return some other status code,
+ * and set `*error_out` (if provided) to a newly allocated error object.
+ *
+ * # Ownership
+ *
+ * The caller is responsible for making sure that `*error_out`, if set, is eventually freed.
+ */
+ArtiRpcStatus arti_rpc_conn_set_default_timeout(const ArtiRpcConn *rpc_conn,
+                                                uint64_t timeout_msec,
+                                                ArtiRpcError **error_out);
+
+/**
+ * Ask Arti which RPC methods it recognizes, and which objects they apply to.
+ *
+ * On success, return `ARTI_RPC_STATUS_SUCCESS` and set `*table_out` to a newly allocated string
+ * containing a JSON object with two members:
+ * `methods`, mapping each method name to a description of the method,
+ * and `delegations`, mapping each type of object to the types of object it delegates to.
+ * (This is the `result` of Arti's `arti:x_list_all_rpc_methods` method.)
+ *
+ * We ask Arti only once per connection, and remember the answer;
+ * if the connection reconnects, we ask again.
+ *
+ * Otherwise return some other status code, set `*table_out` to NULL,
+ * and set `*error_out` (if provided) to a newly allocated error object.
+ *
+ * # Ownership
+ *
+ * The caller is responsible for making sure that `*error_out`, if set, is eventually freed.
+ *
+ * The caller is responsible for making sure that `*table_out`, if set, is eventually freed.
+ */
+ArtiRpcStatus arti_rpc_conn_get_method_table(const ArtiRpcConn *rpc_conn,
+                                             ArtiRpcStr **table_out,
+                                             ArtiRpcError **error_out);
+
+/**
+ * Ask Arti which proxies it offers to RPC applications,
+ * such as its SOCKS ports and its DNS resolvers.
+ *
+ * On success, return `ARTI_RPC_STATUS_SUCCESS` and set `*proxy_info_out` to a newly allocated string
+ * containing a JSON object with one member, `proxies`:
+ * a list of proxies, in Arti's order of preference.
+ * Each proxy has a `listener` member, such as
+ * `{"socks5": {"tcp_address": "127.0.0.1:9150"}}`
+ * or `{"dns": {"udp_address": "127.0.0.1:9053"}}`.
+ * Applications should ignore any kind of listener that they don't recognize.
+ * (This is the `result` of Arti's `arti:get_rpc_proxy_info` method.)
+ *
+ * Otherwise return some other status code, set `*proxy_info_out` to NULL,
+ * and set `*error_out` (if provided) to a newly allocated error object.
+ *
+ * # Ownership
+ *
+ * The caller is responsible for making sure that `*error_out`, if set, is eventually freed.
+ *
+ * The caller is responsible for making sure that `*proxy_info_out`, if set, is eventually freed.
+ */
+ArtiRpcStatus arti_rpc_conn_get_proxy_info(const ArtiRpcConn *rpc_conn,
+                                           ArtiRpcStr **proxy_info_out,
+                                           ArtiRpcError **error_out);
+
 /**
  * Send an RPC request over `rpc_conn`, and return a handle that can wait for a successful response.
  *
@@ -428,6 +649,91 @@ ArtiRpcStatus arti_rpc_conn_execute_with_handle(const ArtiRpcConn *rpc_conn,
                                                 ArtiRpcHandle **handle_out,
                                                 ArtiRpcError **error_out);
 
+/**
+ * Send an RPC request over `rpc_conn`, and arrange for `callback` to be called
+ * with its response, without waiting for that response.
+ *
+ * The message `msg` should be a valid RPC request in JSON format.
+ * If you omit its `id` field, one will be generated: this is typically the best way to use this function.
+ *
+ * On success, return `ARTI_RPC_STATUS_SUCCESS`.
+ * Later, once the request has completed, `callback` will be invoked exactly once,
+ * from a thread owned by this library,
+ * with the outcome of the request and with `user_data`.
+ * (See [`ArtiRpcExecuteCallback`] for the arguments it receives.)
+ * Any updates that Arti sends for the request are discarded:
+ * to receive them, use `arti_rpc_conn_execute_with_updates_async`.
+ *
+ * If `handle_out` is not NULL, then on success, also set `*handle_out` to a newly allocated
+ * `ArtiRpcHandle` for the request, which you can pass to `arti_rpc_handle_cancel`.
+ * You must not wait on this handle: its responses are delivered to `callback`.
+ *
+ * Otherwise, if the request could not be sent, return some other status code,
+ * set `*handle_out` (if provided) to NULL,
+ * and set `*error_out` (if provided) to a newly allocated error object.
+ * In this case, `callback` will never be invoked.
+ *
+ * Because `callback` runs on another thread, it must not block for long,
+ * and both it and `user_data` must be safe to use from any thread.
+ * Applications with a main-thread event loop will typically have `callback`
+ * post a message to that loop.
+ *
+ * The request keeps running even if `rpc_conn` is freed before it completes;
+ * in that case, `callback` will most likely receive an error.
+ *
+ * # Ownership
+ *
+ * The caller is responsible for making sure that `*error_out`, if set, is eventually freed.
+ *
+ * The caller is responsible for making sure that `*handle_out`, if set, is eventually freed.
+ *
+ * The callback is responsible for making sure that the `response` and `error` objects
+ * passed to it are eventually freed.
+ */
+ArtiRpcStatus arti_rpc_conn_execute_async(const ArtiRpcConn *rpc_conn,
+                                          const char *msg,
+                                          ArtiRpcExecuteCallback callback,
+                                          void *user_data,
+                                          ArtiRpcHandle **handle_out,
+                                          ArtiRpcError **error_out);
+
+/**
+ * Send an RPC request over `rpc_conn`, and arrange for `update_callback` to be called
+ * with each update to it as it arrives, and for `callback` to be called with its response,
+ * without waiting for either.
+ *
+ * This function behaves like `arti_rpc_conn_execute_async`,
+ * except that every update Arti sends for the request is passed to `update_callback`,
+ * along with `user_data`.
+ * (See [`ArtiRpcUpdateCallback`] for the arguments it receives.)
+ *
+ * Arti only sends updates for requests that ask for them:
+ * to receive updates, include `"meta": {"updates": true}` in `msg`.
+ *
+ * `update_callback` is invoked from the same thread as `callback`,
+ * once for each update, in the order that the updates arrive,
+ * and never after `callback`.
+ * It has the same restrictions as `callback`.
+ *
+ * If the request could not be sent, neither callback will ever be invoked.
+ *
+ * # Ownership
+ *
+ * The caller is responsible for making sure that `*error_out`, if set, is eventually freed.
+ *
+ * The caller is responsible for making sure that `*handle_out`, if set, is eventually freed.
+ *
+ * The callbacks are responsible for making sure that the `update`, `response`, and `error`
+ * objects passed to them are eventually freed.
+ */
+ArtiRpcStatus arti_rpc_conn_execute_with_updates_async(const ArtiRpcConn *rpc_conn,
+                                                       const char *msg,
+                                                       ArtiRpcUpdateCallback update_callback,
+                                                       ArtiRpcExecuteCallback callback,
+                                                       void *user_data,
+                                                       ArtiRpcHandle **handle_out,
+                                                       ArtiRpcError **error_out);
+
 /**
  * Wait until some response arrives on an arti_rpc_handle, or until an error occurs.
  *
@@ -461,11 +767,38 @@ ArtiRpcStatus arti_rpc_handle_wait(const ArtiRpcHandle *handle,
                                    ArtiRpcResponseType *response_type_out,
                                    ArtiRpcError **error_out);
 
+/**
+ * Ask Arti to cancel the request associated with `handle`, which was sent over `rpc_conn`.
+ *
+ * This function waits for Arti to acknowledge the cancellation, but not for the request to stop.
+ *
+ * On success, return `ARTI_RPC_STATUS_SUCCESS`.
+ * The request will then finish "reasonably quickly", most likely with an error
+ * from Arti whose code is 4 ("Request cancelled").
+ * (`arti_rpc_handle_wait` delivers this error as an `ARTI_RPC_RESPONSE_TYPE_ERROR` response;
+ * the other functions report it with the status `ARTI_RPC_STATUS_REQUEST_CANCELLED`.)
+ * The request may still finish in some other way, if it was about to finish anyway,
+ * so you should keep waiting on `handle` (or for your callback to be invoked)
+ * to learn how it finished.
+ *
+ * Otherwise return some other status code,
+ * and set `*error_out` (if provided) to a newly allocated error object.
+ * If Arti knew of no such request in progress (usually, because it had already finished),
+ * the status is `ARTI_RPC_STATUS_REQUEST_COMPLETED`.
+ *
+ * # Ownership
+ *
+ * The caller is responsible for making sure that `*error_out`, if set, is eventually freed.
+ */
+ArtiRpcStatus arti_rpc_handle_cancel(const ArtiRpcConn *rpc_conn,
+                                     const ArtiRpcHandle *handle,
+                                     ArtiRpcError **error_out);
+
 /**
  * Release storage held by an `ArtiRpcHandle`.
  *
  * NOTE, TODO: This does not cancel the request, but that is not guaranteed.
- * Once we implement cancellation, this may behave differently.
+ * (To cancel a request, use `arti_rpc_handle_cancel` before freeing its handle.)
  */
 void arti_rpc_handle_free(ArtiRpcHandle *handle);
 
@@ -492,6 +825,48 @@ const char *arti_rpc_str_get(const ArtiRpcStr *string);
  */
 void arti_rpc_conn_free(ArtiRpcConn *rpc_conn);
 
+/**
+ * Try to open a pool of `n_conns` connections to an Arti instance.
+ *
+ * Each connection is opened as with `arti_rpc_connect`, using `connection_string`.
+ * (The pool always has at least one connection.)
+ * Use `arti_rpc_conn_pool_get` to choose a connection for each task.
+ *
+ * Each connection has its own session,
+ * and object IDs that Arti returns on one connection are not valid on the others.
+ *
+ * On success, return `ARTI_RPC_STATUS_SUCCESS` and set `*pool_out` to a new ArtiRpcConnPool.
+ * Otherwise return some other status code, set `*pool_out` to NULL, and set
+ * `*error_out` (if provided) to a newly allocated error object.
+ *
+ * # Ownership
+ *
+ * The caller is responsible for making sure that `*pool_out` and `*error_out`,
+ * if set, are eventually freed.
+ */
+ArtiRpcStatus arti_rpc_conn_pool_new(const char *connection_string,
+                                     uintptr_t n_conns,
+                                     ArtiRpcConnPool **pool_out,
+                                     ArtiRpcError **error_out);
+
+/**
+ * Return the connection in `pool` with the fewest requests in progress.
+ *
+ * Return NULL if `pool` is NULL.
+ *
+ * # Ownership
+ *
+ * The resulting connection belongs to the pool:
+ * it lives for no longer than the underlying `ArtiRpcConnPool` object,
+ * and it must not be passed to `arti_rpc_conn_free`.
+ */
+const ArtiRpcConn *arti_rpc_conn_pool_get(const ArtiRpcConnPool *pool);
+
+/**
+ * Close and free a pool of Arti RPC connections.
+ */
+void arti_rpc_conn_pool_free(ArtiRpcConnPool *pool);
+
 /**
  * Try to open an anonymized data stream over Arti.
  *
@@ -588,6 +963,25 @@ ArtiRpcStatus arti_rpc_err_status(const ArtiRpcError *err);
  */
 int arti_rpc_err_os_error_code(const ArtiRpcError *err);
 
+/**
+ * Return a short identifier for what the user might do about a given error.
+ *
+ * The result is one of `"retry-later"`, `"check-clock"`, `"check-network"`,
+ * `"fix-config"`, or `"report-bug"`;
+ * more values may be added in future versions.
+ * Front-ends can use this to show actionable guidance.
+ *
+ * For errors reported by Arti, this is the advice that Arti sent;
+ * for errors generated by this library, it depends on the error's status.
+ *
+ * Return NULL if we have no particular advice, or if the input `err` is NULL.
+ *
+ * # Correctness requirements
+ *
+ * The resulting string pointer is static; it does not need to be freed.
+ */
+const char *arti_rpc_err_remediation(const ArtiRpcError *err);
+
 /**
  * Return a human-readable error message associated with a given error.
  *
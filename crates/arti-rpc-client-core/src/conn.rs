@@ -5,14 +5,17 @@
 
 use std::{
     io::{self, BufReader},
-    path::PathBuf,
+    net::SocketAddr,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
 };
 
 use crate::{
+    discovery::{self, DiscoveryReport, EntryOrigin, SearchEntry},
     llconn,
+    methods::{self, Method, MethodTable, ProxyInfo},
     msgs::{
-        request::InvalidRequestError,
+        request::{InvalidRequestError, Request},
         response::{ResponseKind, RpcError, ValidatedResponse},
         AnyRequestId, ObjectId,
     },
@@ -21,11 +24,21 @@ use crate::{
 
 mod auth;
 mod connimpl;
+#[cfg(windows)]
+mod pipe;
+mod pool;
+mod reconnect;
+mod session;
 mod stream;
+#[cfg(feature = "tls")]
+mod tls;
 
 use crate::util::Utf8CString;
 pub use connimpl::RpcConn;
-use serde::{de::DeserializeOwned, Deserialize};
+pub use pool::RpcConnPool;
+pub use reconnect::{ReconnectPolicy, Reconnected};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+pub use session::RpcSession;
 pub use stream::StreamError;
 
 /// A handle to an open request.
@@ -84,6 +97,20 @@ impl SuccessResponse {
         let response: Response<D> = serde_json::from_str(self.as_ref())?;
         Ok(response.result)
     }
+
+    /// Helper: If the result of this response is an object with an `id` member,
+    /// return that ID.
+    ///
+    /// (See [`ValidatedResponse::result_object_id`].)
+    pub(crate) fn result_object_id(&self) -> Option<ObjectId> {
+        /// A result that has an `id` member.
+        #[derive(Deserialize)]
+        struct ResultWithId {
+            /// The ID of the object named in the result.
+            id: ObjectId,
+        }
+        self.decode::<ResultWithId>().ok().map(|r| r.id)
+    }
 }
 
 /// An Update Response from Arti, with information about the progress of a request.
@@ -155,22 +182,59 @@ pub enum AnyResponse {
 // TODO RPC: DODGY TYPES END.
 
 /// Information about how to construct a connection to an Arti instance.
+///
+/// A builder either connects to a single location given explicitly
+/// (see [`from_connect_string`](RpcConnBuilder::from_connect_string),
+/// [`new_unix_socket`](RpcConnBuilder::new_unix_socket),
+/// [`new_named_pipe`](RpcConnBuilder::new_named_pipe),
+/// and [`new_tls`](RpcConnBuilder::new_tls)),
+/// or searches for a running Arti
+/// (see [`new`](RpcConnBuilder::new) and the [`discovery`](crate::discovery) module).
+#[derive(Clone, Debug, Default)]
 pub struct RpcConnBuilder {
-    /// A path to a unix domain socket at which Arti is listening.
-    // TODO RPC: Right now this is the only kind of supported way to connect.
-    unix_socket: PathBuf,
-    // todo RPC: include selector for how to connect.
+    /// A single location at which Arti is listening.
+    ///
+    /// If this is set, we connect here, and do not search for Arti.
+    target: Option<Target>,
+    /// Search path entries added by the application, to try before the defaults.
+    search_prefix: Vec<SearchEntry>,
+    /// If set, connections from this builder reconnect as this says when Arti closes them.
+    reconnect: Option<ReconnectPolicy>,
     //
     // TODO RPC: Possibly kill off the builder entirely.
 }
 
+/// A single location at which to connect to Arti, given explicitly to an [`RpcConnBuilder`].
+#[derive(Clone, Debug)]
+enum Target {
+    /// An AF_UNIX socket.
+    Unix(PathBuf),
+    /// A Windows named pipe.
+    NamedPipe(String),
+    /// A TLS listener, and the SHA-256 digest of its certificate.
+    Tls(SocketAddr, [u8; 32]),
+}
+
 // TODO: For FFI purposes, define a slightly higher level API that
 // tries to do this all at once, possibly decoding a "connect string"
 // and some optional secret stuff?
 impl RpcConnBuilder {
+    /// Create a Builder that searches for a running Arti.
+    ///
+    /// See the [`discovery`](crate::discovery) module for the search path we use.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
     /// Create a Builder from a connect string.
     ///
-    /// (Right now the only supported string type is "unix:" followed by a path.)
+    /// Right now the supported string types are:
+    ///  - "unix:" followed by a path.
+    ///  - "pipe:" followed by the name of a local Windows named pipe.
+    ///    (Example: `pipe:\\.\pipe\arti\SOCKET`.)
+    ///  - "tls:" followed by a socket address, "#",
+    ///    and the hex-encoded SHA-256 digest of the server's certificate.
+    ///    (Example: `tls:192.0.2.7:9180#5b0e...`.)
     //
     // TODO RPC: Should this take an OsString?
     //
@@ -181,10 +245,26 @@ impl RpcConnBuilder {
         let (kind, location) = s
             .split_once(':')
             .ok_or(BuilderError::InvalidConnectString)?;
-        if kind == "unix" {
-            Ok(Self::new_unix_socket(location))
-        } else {
-            Err(BuilderError::InvalidConnectString)
+        match kind {
+            "unix" => Ok(Self::new_unix_socket(location)),
+            "pipe" => {
+                if !discovery::is_local_pipe_name(location) {
+                    return Err(BuilderError::InvalidConnectString);
+                }
+                Ok(Self::new_named_pipe(location))
+            }
+            "tls" => {
+                let (addr, digest) = location
+                    .rsplit_once('#')
+                    .ok_or(BuilderError::InvalidConnectString)?;
+                let addr = addr
+                    .parse()
+                    .map_err(|_| BuilderError::InvalidConnectString)?;
+                let digest = discovery::parse_cert_digest(digest)
+                    .ok_or(BuilderError::InvalidConnectString)?;
+                Ok(Self::new_tls(addr, digest))
+            }
+            _ => Err(BuilderError::InvalidConnectString),
         }
     }
 
@@ -195,33 +275,184 @@ impl RpcConnBuilder {
     /// the `connect` attempt will later fail with `SchemeNotSupported`.
     pub fn new_unix_socket(addr: impl Into<PathBuf>) -> Self {
         Self {
-            unix_socket: addr.into(),
+            target: Some(Target::Unix(addr.into())),
+            search_prefix: vec![],
+            reconnect: None,
+        }
+    }
+
+    /// Create a Builder to connect to a Windows named pipe with the name `name`,
+    /// like `\\.\pipe\arti\SOCKET`.
+    ///
+    /// Note that this function succeeds even on platforms other than Windows.
+    /// On those platforms, the `connect` attempt will later fail with `SchemeNotSupported`.
+    pub fn new_named_pipe(name: impl Into<String>) -> Self {
+        Self {
+            target: Some(Target::NamedPipe(name.into())),
+            search_prefix: vec![],
+            reconnect: None,
+        }
+    }
+
+    /// Create a Builder to connect over TLS to an Arti RPC listener at `addr`.
+    ///
+    /// We accept only a server certificate whose SHA-256 digest
+    /// (of its DER encoding) is `server_cert_sha256`,
+    /// and we don't check the certificate in any other way.
+    ///
+    /// Note that this function succeeds even if this crate was built
+    /// without the `tls` feature.
+    /// In that case, the `connect` attempt will later fail with `SchemeNotSupported`.
+    pub fn new_tls(addr: SocketAddr, server_cert_sha256: [u8; 32]) -> Self {
+        Self {
+            target: Some(Target::Tls(addr, server_cert_sha256)),
+            search_prefix: vec![],
+            reconnect: None,
+        }
+    }
+
+    /// Add `entries` to the start of the search path that this builder uses.
+    ///
+    /// The entries are tried in the order given,
+    /// after any entries from `ARTI_RPC_CONNECT_PATH_OVERRIDE`,
+    /// but before any entries from `ARTI_RPC_CONNECT_PATH` and before the defaults.
+    ///
+    /// Has no effect if this builder was created to connect to a single given location.
+    pub fn prepend_search_path(
+        &mut self,
+        entries: impl IntoIterator<Item = SearchEntry>,
+    ) -> &mut Self {
+        let mut entries: Vec<_> = entries.into_iter().collect();
+        entries.append(&mut self.search_prefix);
+        self.search_prefix = entries;
+        self
+    }
+
+    /// Make every connection from this builder reconnect to Arti, following `policy`,
+    /// when Arti closes it.
+    ///
+    /// See [`ReconnectPolicy`] for what this does and does not preserve.
+    pub fn reconnect_policy(&mut self, policy: ReconnectPolicy) -> &mut Self {
+        self.reconnect = Some(policy);
+        self
+    }
+
+    /// Return every entry in the search path that this builder would try, in order.
+    ///
+    /// If this builder was created to connect to a single given location,
+    /// the result contains only that location.
+    pub fn search_path(&self) -> Vec<(EntryOrigin, SearchEntry)> {
+        match &self.target {
+            Some(Target::Unix(path)) => vec![(
+                EntryOrigin::Application,
+                SearchEntry::UnixSocket(path.clone()),
+            )],
+            Some(Target::NamedPipe(name)) => vec![(
+                EntryOrigin::Application,
+                discovery::named_pipe_connect_point(name),
+            )],
+            Some(Target::Tls(addr, digest)) => vec![(
+                EntryOrigin::Application,
+                discovery::tls_connect_point(*addr, digest),
+            )],
+            None => discovery::search_path(&self.search_prefix, &discovery::real_env),
         }
     }
 
     /// Try to connect to an Arti process as specified by this Builder.
+    ///
+    /// If this builder searches for Arti, and no Arti can be found,
+    /// the error is [`ConnectError::NoArtiFound`],
+    /// which explains why each connect point could not be used.
     pub fn connect(&self) -> Result<RpcConn, ConnectError> {
-        #[cfg(not(unix))]
-        {
-            return Err(ConnectError::SchemeNotSupported);
-        }
-        #[cfg(unix)]
-        {
-            let sock = std::os::unix::net::UnixStream::connect(&self.unix_socket)
-                .map_err(|e| ConnectError::CannotConnect(Arc::new(e)))?;
-            let sock_dup = sock
-                .try_clone()
-                .map_err(|e| ConnectError::CannotConnect(Arc::new(e)))?;
-            let mut conn = RpcConn::new(
-                llconn::Reader::new(Box::new(BufReader::new(sock))),
-                llconn::Writer::new(Box::new(sock_dup)),
-            );
-
-            let session_id = conn.authenticate_inherent("inherent:unix_path")?;
-            conn.session = Some(session_id);
-
-            Ok(conn)
+        let mut conn = match &self.target {
+            Some(Target::Unix(path)) => connect_unix(path),
+            Some(Target::NamedPipe(name)) => connect_named_pipe(name),
+            Some(Target::Tls(addr, digest)) => connect_tls(*addr, digest),
+            None => discovery::search(self.search_path(), &discovery::real_env)
+                .map_err(|report| ConnectError::NoArtiFound(Arc::new(report))),
+        }?;
+        if let Some(policy) = &self.reconnect {
+            // (We reconnect with a builder that doesn't reconnect on its own.)
+            let builder = Self {
+                reconnect: None,
+                ..self.clone()
+            };
+            conn.set_reconnector(reconnect::Reconnector::new(builder, policy.clone()));
         }
+        Ok(conn)
+    }
+}
+
+/// Try to connect to an Arti process listening on the AF_UNIX socket at `path`.
+pub(crate) fn connect_unix(path: &Path) -> Result<RpcConn, ConnectError> {
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        return Err(ConnectError::SchemeNotSupported);
+    }
+    #[cfg(unix)]
+    {
+        let sock = std::os::unix::net::UnixStream::connect(path)
+            .map_err(|e| ConnectError::CannotConnect(Arc::new(e)))?;
+        let sock_dup = sock
+            .try_clone()
+            .map_err(|e| ConnectError::CannotConnect(Arc::new(e)))?;
+        let mut conn = RpcConn::new(
+            llconn::Reader::new(Box::new(BufReader::new(sock))),
+            llconn::Writer::new(Box::new(sock_dup)),
+        );
+
+        conn.authenticate("inherent:unix_path")?;
+
+        Ok(conn)
+    }
+}
+
+/// Try to connect to an Arti process listening on the Windows named pipe `name`.
+pub(crate) fn connect_named_pipe(name: &str) -> Result<RpcConn, ConnectError> {
+    #[cfg(not(windows))]
+    {
+        let _ = name;
+        Err(ConnectError::SchemeNotSupported)
+    }
+    #[cfg(windows)]
+    {
+        pipe::connect(name)
+    }
+}
+
+/// Try to connect to an Arti process listening on the localhost TCP address `addr`.
+pub(crate) fn connect_tcp_localhost(addr: SocketAddr) -> Result<RpcConn, ConnectError> {
+    let sock =
+        std::net::TcpStream::connect(addr).map_err(|e| ConnectError::CannotConnect(Arc::new(e)))?;
+    let sock_dup = sock
+        .try_clone()
+        .map_err(|e| ConnectError::CannotConnect(Arc::new(e)))?;
+    let mut conn = RpcConn::new(
+        llconn::Reader::new(Box::new(BufReader::new(sock))),
+        llconn::Writer::new(Box::new(sock_dup)),
+    );
+
+    conn.authenticate("inherent:tcp_localhost")?;
+
+    Ok(conn)
+}
+
+/// Try to connect to an Arti RPC listener over TLS at `addr`,
+/// accepting only a server certificate whose SHA-256 digest is `cert_sha256`.
+pub(crate) fn connect_tls(
+    addr: SocketAddr,
+    cert_sha256: &[u8; 32],
+) -> Result<RpcConn, ConnectError> {
+    #[cfg(not(feature = "tls"))]
+    {
+        let _ = (addr, cert_sha256);
+        Err(ConnectError::SchemeNotSupported)
+    }
+    #[cfg(feature = "tls")]
+    {
+        tls::connect(addr, cert_sha256)
     }
 }
 
@@ -248,6 +479,22 @@ impl AnyResponse {
     }
 }
 
+/// Arguments to an `rpc:cancel` request.
+#[derive(Serialize, Debug)]
+struct CancelParams<'a> {
+    /// The request to cancel.
+    request_id: &'a AnyRequestId,
+}
+
+/// Helper: Return an `rpc:cancel` request for the request with the ID `id`.
+fn cancel_request(id: &AnyRequestId) -> Request<CancelParams<'_>> {
+    Request::new(
+        ObjectId::connection_id(),
+        "rpc:cancel",
+        CancelParams { request_id: id },
+    )
+}
+
 impl RpcConn {
     /// Return the ObjectId for the negotiated Session.
     ///
@@ -273,6 +520,89 @@ impl RpcConn {
         hnd.wait()
     }
 
+    /// Run a typed request, and wait for success or failure.
+    ///
+    /// As with [`execute`](RpcConn::execute), this returns `Err(.)` only if
+    /// sending the request or getting a response failed.
+    /// If Arti reported an error, this returns `Ok(Err(.))`.
+    /// On success, it decodes the `result` field of the response as `M::Output`;
+    /// if that isn't possible, it returns [`ProtoError::CannotDecodeResult`].
+    pub fn execute_typed<M: Method>(
+        &self,
+        request: &methods::Request<M>,
+    ) -> Result<Result<M::Output, RpcError>, ProtoError> {
+        Ok(self.execute_typed_raw(request)?.map_err(|e| e.decode()))
+    }
+
+    /// Helper: Behaves like `execute_typed`, but returns Arti's errors undecoded.
+    fn execute_typed_raw<M: Method>(
+        &self,
+        request: &methods::Request<M>,
+    ) -> Result<Result<M::Output, ErrorResponse>, ProtoError> {
+        let cmd = request.encode()?;
+        match self.execute(&cmd)? {
+            Ok(success) => match success.decode::<M::Output>() {
+                Ok(result) => Ok(Ok(result)),
+                Err(json_error) => Err(ProtoError::CannotDecodeResult(UnexpectedReply {
+                    request: cmd,
+                    reply: Utf8CString::from(success).to_string(),
+                    problem: UnexpectedReplyProblem::CannotDecode(Arc::new(json_error)),
+                })),
+            },
+            Err(error) => Ok(Err(error)),
+        }
+    }
+
+    /// Return a description of the RPC methods that Arti recognizes,
+    /// and of the objects that they apply to.
+    ///
+    /// The first time this is called, we ask Arti with
+    /// [`ListAllRpcMethods`](methods::ListAllRpcMethods);
+    /// after that, we return the same answer,
+    /// until Arti closes the connection and we reconnect.
+    ///
+    /// As with [`execute_typed`](RpcConn::execute_typed), this returns `Ok(Err(.))`
+    /// if Arti reports an error: for example, if it is too old to support this method.
+    pub fn method_table(&self) -> Result<Result<Arc<MethodTable>, RpcError>, ProtoError> {
+        Ok(self.method_table_raw()?.map_err(|e| e.decode()))
+    }
+
+    /// Helper: Behaves like `method_table`, but returns Arti's errors undecoded.
+    pub(crate) fn method_table_raw(
+        &self,
+    ) -> Result<Result<Arc<MethodTable>, ErrorResponse>, ProtoError> {
+        if let Some(table) = self.cached_method_table() {
+            return Ok(Ok(table));
+        }
+        let session = self.session().ok_or(ProtoError::NotAuthenticated)?.clone();
+        let request = methods::Request::new(session, methods::ListAllRpcMethods::new());
+        let table = match self.execute_typed_raw(&request)? {
+            Ok(table) => Arc::new(table),
+            Err(error) => return Ok(Err(error)),
+        };
+        self.cache_method_table(Arc::clone(&table));
+        Ok(Ok(table))
+    }
+
+    /// Return the proxies that Arti offers to RPC applications,
+    /// such as its SOCKS ports and its DNS resolvers.
+    ///
+    /// We ask Arti with [`GetRpcProxyInfo`](methods::GetRpcProxyInfo) each time this is called,
+    /// since Arti's listeners can change when it is reconfigured.
+    ///
+    /// As with [`execute_typed`](RpcConn::execute_typed), this returns `Ok(Err(.))`
+    /// if Arti reports an error.
+    pub fn proxy_info(&self) -> Result<Result<ProxyInfo, RpcError>, ProtoError> {
+        Ok(self.proxy_info_raw()?.map_err(|e| e.decode()))
+    }
+
+    /// Helper: Behaves like `proxy_info`, but returns Arti's errors undecoded.
+    pub(crate) fn proxy_info_raw(&self) -> Result<Result<ProxyInfo, ErrorResponse>, ProtoError> {
+        let session = self.session().ok_or(ProtoError::NotAuthenticated)?.clone();
+        let request = methods::Request::new(session, methods::GetRpcProxyInfo::new());
+        self.execute_typed_raw(&request)
+    }
+
     /// Helper for executing internally-generated requests and decoding their results.
     ///
     /// Behaves like `execute`, except on success, where it tries to decode the `result` field
@@ -319,9 +649,32 @@ impl RpcConn {
         }
     }
 
-    /// Cancel a request by ID.
-    pub fn cancel(&self, _id: &AnyRequestId) -> Result<(), ProtoError> {
-        todo!()
+    /// Ask Arti to cancel the request with the ID `id`.
+    ///
+    /// On success, Arti has agreed to cancel the request:
+    /// its handle will receive an error reply with the code
+    /// [`RpcErrorCode::REQUEST_CANCELLED`](crate::RpcErrorCode::REQUEST_CANCELLED).
+    /// (It's still possible, though, for the request to finish before the cancellation takes effect.)
+    ///
+    /// Returns [`ProtoError::RequestCompleted`] if Arti knows of no such request in progress:
+    /// usually, this is because it has already finished.
+    pub fn cancel(&self, id: &AnyRequestId) -> Result<(), ProtoError> {
+        /// Response to a successful `rpc:cancel` request.
+        #[derive(Deserialize, Debug)]
+        struct Cancelled {}
+
+        match self.execute_internal::<Cancelled>(&cancel_request(id).encode()?)? {
+            Ok(Cancelled {}) => Ok(()),
+            Err(_) => Err(ProtoError::RequestCompleted),
+        }
+    }
+
+    /// Ask Arti to cancel the request with the ID `id`, without waiting for its answer.
+    ///
+    /// Returns a handle for the `rpc:cancel` request itself.
+    #[cfg(feature = "ffi")]
+    pub(crate) fn send_cancel(&self, id: &AnyRequestId) -> Result<RequestHandle, ProtoError> {
+        self.execute_with_handle(&cancel_request(id).encode()?)
     }
     /// Like `execute`, but don't wait.  This lets the caller see the
     /// request ID and  maybe cancel it.
@@ -355,6 +708,17 @@ impl RequestHandle {
     pub fn id(&self) -> &AnyRequestId {
         &self.id
     }
+    /// Return a new handle for the same request.
+    ///
+    /// Each response goes to whichever handle waits for it first,
+    /// so at most one of the two handles should be waited on.
+    #[cfg(feature = "ffi")]
+    pub(crate) fn duplicate(&self) -> RequestHandle {
+        RequestHandle {
+            conn: Mutex::new(Arc::clone(&self.conn.lock().expect("Poisoned lock"))),
+            id: self.id.clone(),
+        }
+    }
     /// Wait for success or failure, and return what happened.
     ///
     /// (Ignores any update messages that are received.)
@@ -466,6 +830,27 @@ pub enum ProtoError {
     /// We got a response to some internally generated request that wasn't what we expected.
     #[error("{0}")]
     InternalRequestFailed(#[source] UnexpectedReply),
+
+    /// We got a successful response to a typed request,
+    /// but couldn't decode its result as the type we expected.
+    ///
+    /// (This probably means that Arti and this library disagree
+    /// about the method's definition.)
+    #[error("{0}")]
+    CannotDecodeResult(#[source] UnexpectedReply),
+
+    /// Arti closed the RPC connection, and we were unable to reconnect.
+    #[error("Unable to reconnect to Arti: {0}")]
+    ReconnectFailed(#[source] Arc<ConnectError>),
+
+    /// We needed a session to send a request, but this connection was never authenticated.
+    #[error("Not authenticated")]
+    NotAuthenticated,
+
+    /// We tried to send a request on an [`RpcSession`]
+    /// to an object that does not belong to that session.
+    #[error("Object {0:?} does not belong to this session")]
+    ForeignObject(ObjectId),
 }
 
 /// An error while trying to connect to the Arti process.
@@ -479,6 +864,12 @@ pub enum ConnectError {
     /// IO error while connecting to Arti.
     #[error("Unable to make a connection: {0}")]
     CannotConnect(#[source] Arc<std::io::Error>),
+    /// We couldn't establish a TLS session with Arti.
+    ///
+    /// This includes the case where the server's certificate
+    /// was not the one we expected.
+    #[error("TLS handshake failed: {0}")]
+    TlsHandshakeFailed(#[source] Arc<std::io::Error>),
     /// One of our authentication messages was rejected.
     #[error("Arti rejected our authentication: {0:?}")]
     AuthenticationRejected(ErrorResponse),
@@ -488,6 +879,9 @@ pub enum ConnectError {
     /// A protocol error occurred during negotiations.
     #[error("Error while negotiating with Arti: {0}")]
     ProtoError(#[from] ProtoError),
+    /// We searched for a running Arti, and didn't find one that we could use.
+    #[error("Could not find a running Arti: {0}")]
+    NoArtiFound(Arc<DiscoveryReport>),
 }
 define_from_for_arc!(serde_json::Error => ConnectError [BadMessage]);
 
@@ -870,4 +1264,66 @@ mod test {
             )
         });
     }
+
+    #[test]
+    fn sessions() {
+        let (mut conn, sock) = dummy_connected();
+        conn.auth_scheme = Some("inherent:unix_path");
+        let conn = Arc::new(conn);
+
+        let fake_arti_thread = thread::spawn(move || {
+            let mut sock = BufReader::new(sock);
+            let mut reply = |result: serde_json::Value| {
+                let mut s = String::new();
+                let _len = sock.read_line(&mut s).unwrap();
+                let request = ValidatedRequest::from_string_strict(s.as_ref()).unwrap();
+                let response = serde_json::json!({
+                    "id": request.id().clone(),
+                    "result": result,
+                });
+                write_val(sock.get_mut(), &response);
+                (request.obj().clone(), request.method().to_string())
+            };
+            let auth = reply(serde_json::json!({ "session": "session-2" }));
+            let new_obj = reply(serde_json::json!({ "id": "obj-1" }));
+            let use_obj = reply(serde_json::json!({}));
+            let release = reply(serde_json::json!({}));
+            (auth, new_obj, use_obj, release, sock)
+        });
+
+        let session = RpcSession::open(Arc::clone(&conn)).unwrap();
+        assert_eq!(session.id().as_ref(), "session-2");
+
+        let request = |obj: &str, method: &str| {
+            serde_json::json!({ "obj": obj, "method": method, "params": {} }).to_string()
+        };
+        let foreign =
+            |r: Result<FinalResponse, ProtoError>| matches!(r, Err(ProtoError::ForeignObject(_)));
+        // Objects that we haven't been given are refused, without being sent.
+        assert!(foreign(session.execute(&request("obj-1", "arti:x-frob"))));
+        assert!(foreign(
+            session.execute(&request("connection", "rpc:cancel"))
+        ));
+
+        session
+            .execute(&request("session-2", "arti:x-new"))
+            .unwrap()
+            .unwrap();
+        session
+            .execute(&request("obj-1", "arti:x-frob"))
+            .unwrap()
+            .unwrap();
+        session
+            .execute(&request("obj-1", "rpc:release"))
+            .unwrap()
+            .unwrap();
+        assert!(foreign(session.execute(&request("obj-1", "arti:x-frob"))));
+
+        let (auth, new_obj, use_obj, release, _sock) = fake_arti_thread.join().unwrap();
+        assert_eq!(auth.0.as_ref(), "connection");
+        assert_eq!(auth.1, "auth:authenticate");
+        assert_eq!(new_obj.0.as_ref(), "session-2");
+        assert_eq!(use_obj.0.as_ref(), "obj-1");
+        assert_eq!(release.1, "rpc:release");
+    }
 }
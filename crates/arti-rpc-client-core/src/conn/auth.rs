@@ -20,6 +20,15 @@ struct Authenticated {
 }
 
 impl RpcConn {
+    /// Negotiate "inherent" authentication, using the provided scheme name,
+    /// and use the resulting session as this connection's session.
+    pub(crate) fn authenticate(&mut self, scheme_name: &'static str) -> Result<(), ConnectError> {
+        let session_id = self.authenticate_inherent(scheme_name)?;
+        self.session = Some(session_id);
+        self.auth_scheme = Some(scheme_name);
+        Ok(())
+    }
+
     /// Try to negotiate "inherent" authentication, using the provided scheme name.
     ///
     /// (Inherent authentication is available whenever the client proves that they
@@ -8,12 +8,17 @@
 //! Except if noted otherwise, these invariants only hold when nobody
 //! is holding the lock on [`RequestState`].
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
+    io,
     sync::{Arc, Condvar, Mutex, MutexGuard},
 };
 
+#[cfg(feature = "ffi")]
+use std::time::Duration;
+
 use crate::{
     llconn,
+    methods::MethodTable,
     msgs::{
         request::{IdGenerator, ValidatedRequest},
         response::ValidatedResponse,
@@ -21,7 +26,10 @@ use crate::{
     },
 };
 
-use super::{ProtoError, ShutdownError};
+use super::{
+    reconnect::{Reconnected, Reconnector},
+    ProtoError, ShutdownError,
+};
 
 /// State held by the [`RpcConn`] for a single request ID.
 #[derive(Default)]
@@ -43,12 +51,18 @@ struct RequestState {
     /// * The condvar is Some if (and only if) some thread is waiting
     ///   on it.
     waiter: Option<Arc<Condvar>>,
+    /// If set, this request was sent on a connection that has since been replaced,
+    /// and this is the error that closed that connection.
+    ///
+    /// No more responses will arrive for this request.
+    lost: Option<ShutdownError>,
 }
 
 impl RequestState {
     /// Helper: Pop and return the next message for this request.
     ///
-    /// If there are no queued messages, but a fatal error has occurred on the connection,
+    /// If there are no queued messages, but the connection that this request was sent on
+    /// has been replaced, or a fatal error has occurred on the connection,
     /// return that.
     ///
     /// If there are no queued messages and no fatal error, return None.
@@ -59,7 +73,10 @@ impl RequestState {
         if let Some(m) = self.queue.pop_front() {
             Some(Ok(m))
         } else {
-            fatal.as_ref().map(|f| Err(f.clone()))
+            self.lost
+                .as_ref()
+                .or(fatal.as_ref())
+                .map(|f| Err(f.clone()))
         }
     }
 }
@@ -89,6 +106,18 @@ struct ReceiverState {
     ///
     /// (Therefore, when it becomes Some, we must signal a cv, if any is set.)
     reader: Option<crate::llconn::Reader>,
+    /// How many times this connection has been replaced by a new one.
+    ///
+    /// Every reader and writer belongs to a single generation.
+    /// We use this to recognize a reader that belongs to a connection we have replaced.
+    generation: u64,
+    /// If we are keeping track of them,
+    /// the objects that Arti has returned to us on this connection,
+    /// and that we have not released.
+    ///
+    /// (We only do this if we might reconnect,
+    /// so that we can say which objects were lost.)
+    handles: Option<HashSet<ObjectId>>,
 }
 
 impl ReceiverState {
@@ -154,13 +183,48 @@ pub struct RpcConn {
     /// This lock does not nest with the`receiver` lock.  You must never hold
     /// both at the same time.
     ///
-    /// (For now, this lock is _ONLY_ held in the send_request method.)
+    /// (For now, this lock is _ONLY_ held in the send_request method,
+    /// and when we replace the connection in adopt_transport.)
     #[educe(Debug(ignore))]
-    writer: Mutex<llconn::Writer>,
+    writer: Mutex<WriterState>,
 
     /// If set, we are authenticated and we have negotiated a session that has
     /// this ObjectID.
+    ///
+    /// (If we have reconnected since then, this is still the ID of the first session:
+    /// see [`Reconnector`].)
     pub(super) session: Option<ObjectId>,
+
+    /// If we are authenticated, the scheme that we used to authenticate.
+    ///
+    /// We use this to authenticate again, when we open another session.
+    pub(super) auth_scheme: Option<&'static str>,
+
+    /// If set, we replace our connection to Arti according to this when Arti closes it.
+    #[educe(Debug(ignore))]
+    reconnector: Option<Reconnector>,
+
+    /// Arti's answer to `arti:x_list_all_rpc_methods`, if we have asked for it
+    /// since we last (re)connected.
+    #[educe(Debug(ignore))]
+    method_table: Mutex<Option<Arc<MethodTable>>>,
+
+    /// How long the FFI functions that wait for a response should wait, by default,
+    /// if there is a limit.
+    ///
+    /// (See `arti_rpc_conn_set_default_timeout`.)
+    #[cfg(feature = "ffi")]
+    default_timeout: Mutex<Option<Duration>>,
+}
+
+/// The writer for an RpcConn, and the generation of the connection that it belongs to.
+struct WriterState {
+    /// A writer that we use to send requests to Arti.
+    writer: llconn::Writer,
+    /// The generation of the connection that `writer` belongs to.
+    ///
+    /// See [`ReceiverState::generation`].
+    generation: u64,
 }
 
 /// Instruction to alert some additional condvar(s) before releasing our lock and returning
@@ -195,11 +259,131 @@ impl RpcConn {
                     fatal: None,
                     pending: HashMap::new(),
                     reader: Some(reader),
+                    generation: 0,
+                    handles: None,
                 }),
             }),
-            writer: Mutex::new(writer),
+            writer: Mutex::new(WriterState {
+                writer,
+                generation: 0,
+            }),
             session: None,
+            auth_scheme: None,
+            reconnector: None,
+            method_table: Mutex::new(None),
+            #[cfg(feature = "ffi")]
+            default_timeout: Mutex::new(None),
+        }
+    }
+
+    /// Make this RpcConn replace its connection as `reconnector` says, when Arti closes it.
+    pub(super) fn set_reconnector(&mut self, reconnector: Reconnector) {
+        self.receiver.state.lock().expect("poisoned").handles = Some(HashSet::new());
+        self.reconnector = Some(reconnector);
+    }
+
+    /// Return the object we use to replace this connection, if we do so.
+    pub(super) fn reconnector(&self) -> Option<&Reconnector> {
+        self.reconnector.as_ref()
+    }
+
+    /// Return true if a fatal error has occurred on this connection.
+    pub(super) fn is_shut_down(&self) -> bool {
+        self.receiver
+            .state
+            .lock()
+            .expect("poisoned")
+            .fatal
+            .is_some()
+    }
+
+    /// Return the method table that we have cached for this connection, if any.
+    pub(super) fn cached_method_table(&self) -> Option<Arc<MethodTable>> {
+        self.method_table.lock().expect("poisoned").clone()
+    }
+
+    /// Remember `table` as the method table for this connection.
+    pub(super) fn cache_method_table(&self, table: Arc<MethodTable>) {
+        *self.method_table.lock().expect("poisoned") = Some(table);
+    }
+
+    /// Return how long the FFI functions should wait for a response, by default.
+    #[cfg(feature = "ffi")]
+    pub(crate) fn default_timeout(&self) -> Option<Duration> {
+        *self.default_timeout.lock().expect("poisoned")
+    }
+
+    /// Set how long the FFI functions should wait for a response, by default.
+    #[cfg(feature = "ffi")]
+    pub(crate) fn set_default_timeout(&self, timeout: Option<Duration>) {
+        *self.default_timeout.lock().expect("poisoned") = timeout;
+    }
+
+    /// Return the number of requests that are in progress on this connection.
+    pub(super) fn n_pending(&self) -> usize {
+        self.receiver.state.lock().expect("poisoned").pending.len()
+    }
+
+    /// Replace the connection that this RpcConn uses with the one that `fresh` uses.
+    ///
+    /// `fresh` must be a newly opened connection on which no request is in progress.
+    ///
+    /// Every request still in progress on our old connection
+    /// fails with the error that closed that connection (if any),
+    /// and every object that Arti gave us on it is forgotten.
+    /// Returns a report of what we lost.
+    pub(super) fn adopt_transport(&self, fresh: RpcConn) -> Reconnected {
+        let RpcConn {
+            receiver: fresh_receiver,
+            writer: fresh_writer,
+            ..
+        } = fresh;
+        let reader = fresh_receiver
+            .state
+            .lock()
+            .expect("poisoned")
+            .reader
+            .take()
+            .expect("Nobody should be reading from a fresh connection");
+        let writer = fresh_writer.into_inner().expect("poisoned").writer;
+        // The Arti on the other end may be a different version.
+        *self.method_table.lock().expect("poisoned") = None;
+
+        // Replace the writer first, so that no request for the new generation
+        // can go out on the old connection.
+        //
+        // (The two locks must not overlap.)
+        let generation = {
+            let mut writer_state = self.writer.lock().expect("poisoned");
+            writer_state.writer = writer;
+            writer_state.generation += 1;
+            writer_state.generation
+        };
+
+        let mut state = self.receiver.state.lock().expect("poisoned");
+        let cause = state.fatal.take();
+        state.generation = generation;
+        // If somebody is still reading from the old connection,
+        // they will notice that the generation has changed, and discard their reader.
+        state.reader = Some(reader);
+        let lost = cause.clone().unwrap_or(ShutdownError::ConnectionClosed);
+        let mut abandoned_requests = Vec::new();
+        for (id, ent) in state.pending.iter_mut() {
+            if ent.lost.is_none() {
+                ent.lost = Some(lost.clone());
+                abandoned_requests.push(id.clone());
+            }
         }
+        let invalidated_objects = state
+            .handles
+            .as_mut()
+            .map(|handles| handles.drain().collect())
+            .unwrap_or_default();
+        // Wake everybody who is waiting on the old connection, so that they learn it is gone.
+        // (This also makes sure that somebody takes the new reader, if anybody needs it.)
+        state.alert_everybody();
+
+        Reconnected::new(cause, abandoned_requests, invalidated_objects)
     }
 
     /// Send the request in `msg` on this connection, and return a RequestHandle
@@ -214,6 +398,11 @@ impl RpcConn {
     pub(super) fn send_request(&self, msg: &str) -> Result<super::RequestHandle, ProtoError> {
         use std::collections::hash_map::Entry::*;
 
+        // If Arti has closed our connection, and we are supposed to replace it, do so.
+        // (This must happen before we take any of our own locks.)
+        self.reconnect_if_needed()?;
+        let live_session = self.live_session();
+
         let mut state = self.receiver.state.lock().expect("poisoned");
         if let Some(f) = &state.fatal {
             // If there's been a fatal error we don't even try to send the request.
@@ -221,8 +410,14 @@ impl RpcConn {
         }
 
         // Convert this request into validated form (with an ID) and re-encode it.
-        let valid: ValidatedRequest =
+        let mut valid: ValidatedRequest =
             ValidatedRequest::from_string_loose(msg, || state.id_gen.next_id())?;
+        // If we have reconnected, requests to our original session go to the current one.
+        if let (Some(live), Some(original)) = (&live_session, &self.session) {
+            if valid.obj() == original {
+                valid = valid.redirected(live)?;
+            }
+        }
 
         // Do the necessary housekeeping before we send the request, so that
         // we'll be able to understand the replies.
@@ -233,18 +428,38 @@ impl RpcConn {
                 v.insert(RequestState::default());
             }
         }
+        if valid.method() == "rpc:release" {
+            if let Some(handles) = &mut state.handles {
+                handles.remove(valid.obj());
+            }
+        }
+        let generation = state.generation;
         // Release the lock on the ReceiverState here; the two locks must not overlap.
         drop(state);
 
-        // NOTE: This is the only block of code that holds the writer lock!
-        let write_outcome = { self.writer.lock().expect("poisoned").send_valid(&valid) };
+        // NOTE: Apart from adopt_transport, this is the only block of code
+        // that holds the writer lock!
+        let write_outcome = {
+            let mut writer_state = self.writer.lock().expect("poisoned");
+            if writer_state.generation == generation {
+                writer_state.writer.send_valid(&valid)
+            } else {
+                // The connection was replaced after we registered this request,
+                // so it belongs to a connection that is gone.
+                Err(io::Error::new(
+                    io::ErrorKind::NotConnected,
+                    "RPC connection was replaced",
+                ))
+            }
+        };
 
         match write_outcome {
             Err(e) => {
-                // A failed write is a fatal error for everybody.
+                // A failed write is a fatal error for everybody
+                // (unless the connection has been replaced since).
                 let e = ShutdownError::Write(Arc::new(e));
                 let mut state = self.receiver.state.lock().expect("poisoned");
-                if state.fatal.is_none() {
+                if state.generation == generation && state.fatal.is_none() {
                     state.fatal = Some(e.clone());
                     state.alert_everybody();
                 }
@@ -300,6 +515,11 @@ impl Receiver {
                 state.pending.remove(id);
             }
 
+            // Remember any object that Arti has given us, so we can report it if it is lost.
+            if let (Ok(r), Some(handles)) = (&result, &mut state.handles) {
+                handles.extend(r.result_object_id());
+            }
+
             match should_alert {
                 AlertWhom::Nobody => {}
                 AlertWhom::Anybody if state.reader.is_none() => {}
@@ -350,7 +570,7 @@ impl Receiver {
             return (Err(ProtoError::RequestCompleted), state_lock, should_alert);
         };
 
-        let mut reader = loop {
+        let (mut reader, generation) = loop {
             // Note: It might be nice to use a hash_map::Entry here, but it
             // doesn't really work the way we want.  The `entry()` API is always
             // ready to insert, and requires that we clone `id`.  But what we
@@ -373,7 +593,7 @@ impl Receiver {
 
             if let Some(r) = state.reader.take() {
                 // Nobody else is reading; we have to do it.
-                break r;
+                break (r, state.generation);
             }
 
             // Somebody else is reading; register a condvar.
@@ -396,9 +616,11 @@ impl Receiver {
         };
 
         let (result, mut state_lock, should_alert) =
-            self.read_until_message_for(state_lock, &mut reader, id);
-        // Put the reader back.
-        state_lock.reader = Some(reader);
+            self.read_until_message_for(state_lock, &mut reader, generation, id);
+        // Put the reader back, unless it belongs to a connection that we have replaced.
+        if state_lock.generation == generation {
+            state_lock.reader = Some(reader);
+        }
 
         (result.map_err(ProtoError::from), state_lock, should_alert)
     }
@@ -411,12 +633,18 @@ impl Receiver {
     /// The caller is responsible for restoring the following state before
     /// dropping the `MutexGuard`:
     ///
-    /// - Putting `reader` back into the `reader` field.
+    /// - Putting `reader` back into the `reader` field,
+    ///   if the connection is still at `generation`.
     /// - Other invariants as discussed in wait_on_message_for_impl.
+    ///
+    /// `generation` is the generation of the connection that `reader` belongs to.
+    /// If the connection is replaced while we are reading,
+    /// we return the error that closed the old one.
     fn read_until_message_for<'a>(
         &'a self,
         mut state_lock: MutexGuard<'a, ReceiverState>,
         reader: &mut llconn::Reader,
+        generation: u64,
         id: &AnyRequestId,
     ) -> (
         Result<ValidatedResponse, ShutdownError>,
@@ -437,6 +665,17 @@ impl Receiver {
             state_lock = self.state.lock().expect("poisoned lock");
             let state = &mut state_lock;
 
+            if state.generation != generation {
+                // Our connection was replaced while we were reading from it,
+                // so whatever we read is no longer of interest to anybody.
+                let lost = state
+                    .pending
+                    .get(id)
+                    .and_then(|ent| ent.lost.clone())
+                    .unwrap_or(ShutdownError::ConnectionClosed);
+                return (Err(lost), state_lock, AlertWhom::Anybody);
+            }
+
             match result {
                 Ok(m) if m.id() == id => {
                     // This only is for us, so there's no need to alert anybody
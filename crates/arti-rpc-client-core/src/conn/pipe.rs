@@ -0,0 +1,195 @@
+//! Connecting to Arti over a Windows named pipe.
+//!
+//! An [`RpcConn`] reads from one thread while it writes from another.
+//! But Windows serializes I/O on a pipe that was opened for synchronous use,
+//! so a read that is waiting for a response would keep us from sending any request.
+//! Instead, we open the pipe for overlapped I/O,
+//! and each half of the connection waits for its own operations to finish.
+
+use std::{
+    ffi::OsStr,
+    fs::{File, OpenOptions},
+    io::{self, BufReader, Read, Write},
+    mem,
+    os::windows::{
+        ffi::OsStrExt as _,
+        fs::OpenOptionsExt as _,
+        io::{AsRawHandle as _, FromRawHandle as _, OwnedHandle},
+    },
+    ptr,
+    sync::Arc,
+};
+
+use winapi::{
+    shared::{
+        minwindef::{BOOL, DWORD, FALSE, TRUE},
+        winerror::{ERROR_BROKEN_PIPE, ERROR_IO_PENDING, ERROR_PIPE_BUSY},
+    },
+    um::{
+        fileapi::{ReadFile, WriteFile},
+        ioapiset::GetOverlappedResult,
+        minwinbase::OVERLAPPED,
+        namedpipeapi::WaitNamedPipeW,
+        synchapi::CreateEventW,
+        winbase::{FILE_FLAG_OVERLAPPED, SECURITY_IDENTIFICATION},
+        winnt::HANDLE,
+    },
+};
+
+use super::{ConnectError, RpcConn};
+use crate::llconn;
+
+/// How long to wait (in milliseconds) for an instance of a busy pipe to become available.
+const BUSY_TIMEOUT_MSEC: DWORD = 5_000;
+
+/// How many times to try again if a pipe is busy.
+const BUSY_RETRIES: usize = 3;
+
+/// Try to connect to an Arti process listening on the named pipe at `path`.
+pub(super) fn connect(path: &str) -> Result<RpcConn, ConnectError> {
+    let cannot_connect = |e| ConnectError::CannotConnect(Arc::new(e));
+
+    let pipe = Arc::new(OwnedHandle::from(open(path).map_err(cannot_connect)?));
+    let reader = PipeReader(PipeHalf::new(pipe.clone()).map_err(cannot_connect)?);
+    let writer = PipeWriter(PipeHalf::new(pipe).map_err(cannot_connect)?);
+    let mut conn = RpcConn::new(
+        llconn::Reader::new(Box::new(BufReader::new(reader))),
+        llconn::Writer::new(Box::new(writer)),
+    );
+
+    conn.authenticate("inherent:named_pipe")?;
+
+    Ok(conn)
+}
+
+/// Open the named pipe at `path` for overlapped I/O,
+/// waiting a while if every instance of it is busy.
+fn open(path: &str) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options
+        .read(true)
+        .write(true)
+        .custom_flags(FILE_FLAG_OVERLAPPED)
+        // Don't let the server act on our behalf: it only needs to know who we are.
+        .security_qos_flags(SECURITY_IDENTIFICATION);
+
+    let mut retries = 0;
+    loop {
+        match options.open(path) {
+            Err(e)
+                if e.raw_os_error() == Some(ERROR_PIPE_BUSY as i32) && retries < BUSY_RETRIES =>
+            {
+                retries += 1;
+                let name: Vec<u16> = OsStr::new(path).encode_wide().chain([0]).collect();
+                // SAFETY: `name` is a NUL-terminated wide string that outlives the call.
+                if unsafe { WaitNamedPipeW(name.as_ptr(), BUSY_TIMEOUT_MSEC) } == FALSE {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            result => return result,
+        }
+    }
+}
+
+/// One half of a connection over a named pipe.
+///
+/// Each half has its own event, so that it can wait for its own operations.
+struct PipeHalf {
+    /// The pipe, which we share with the other half.
+    pipe: Arc<OwnedHandle>,
+    /// A manual-reset event that is signaled when our current operation finishes.
+    event: OwnedHandle,
+}
+
+impl PipeHalf {
+    /// Create a new `PipeHalf` on `pipe`, with a new event.
+    fn new(pipe: Arc<OwnedHandle>) -> io::Result<Self> {
+        // SAFETY: We pass no security attributes and no name;
+        // both are optional.
+        let event = unsafe { CreateEventW(ptr::null_mut(), TRUE, FALSE, ptr::null()) };
+        if event.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: We just created `event`, and nothing else owns it.
+        let event = unsafe { OwnedHandle::from_raw_handle(event.cast()) };
+        Ok(Self { pipe, event })
+    }
+
+    /// Start an overlapped operation on the pipe with `start`,
+    /// wait for it to finish, and return the number of bytes it transferred.
+    ///
+    /// `start` is called with the pipe and an `OVERLAPPED` that uses our event.
+    fn run<F>(&self, start: F) -> io::Result<usize>
+    where
+        F: FnOnce(HANDLE, *mut OVERLAPPED) -> BOOL,
+    {
+        let pipe: HANDLE = self.pipe.as_raw_handle().cast();
+        // SAFETY: OVERLAPPED is plain data, for which all-zeroes is the correct initial value.
+        let mut overlapped: OVERLAPPED = unsafe { mem::zeroed() };
+        overlapped.hEvent = self.event.as_raw_handle().cast();
+
+        if start(pipe, &mut overlapped) == FALSE {
+            let e = io::Error::last_os_error();
+            if e.raw_os_error() != Some(ERROR_IO_PENDING as i32) {
+                return Err(e);
+            }
+        }
+        let mut transferred: DWORD = 0;
+        // SAFETY: `overlapped` describes an operation that we started on `pipe`.
+        // Since we wait for it to finish, `overlapped` (and the caller's buffer)
+        // outlive the operation.
+        if unsafe { GetOverlappedResult(pipe, &mut overlapped, &mut transferred, TRUE) } == FALSE {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(transferred as usize)
+    }
+}
+
+/// Return the length of `buf`, limited to what a single read or write can transfer.
+fn io_len(buf: &[u8]) -> DWORD {
+    DWORD::try_from(buf.len()).unwrap_or(DWORD::MAX)
+}
+
+/// The reading half of a connection over a named pipe.
+struct PipeReader(PipeHalf);
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = io_len(buf);
+        let result = self.0.run(|pipe, overlapped| {
+            // SAFETY: `buf` is valid for `len` bytes, and `run` waits for the read to finish.
+            unsafe {
+                ReadFile(
+                    pipe,
+                    buf.as_mut_ptr().cast(),
+                    len,
+                    ptr::null_mut(),
+                    overlapped,
+                )
+            }
+        });
+        match result {
+            // This is how we learn that Arti closed the pipe.
+            Err(e) if e.raw_os_error() == Some(ERROR_BROKEN_PIPE as i32) => Ok(0),
+            other => other,
+        }
+    }
+}
+
+/// The writing half of a connection over a named pipe.
+struct PipeWriter(PipeHalf);
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = io_len(buf);
+        self.0.run(|pipe, overlapped| {
+            // SAFETY: `buf` is valid for `len` bytes, and `run` waits for the write to finish.
+            unsafe { WriteFile(pipe, buf.as_ptr().cast(), len, ptr::null_mut(), overlapped) }
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // We don't buffer anything, and every write has finished by the time it returns.
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+//! A small pool of connections to Arti.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use super::{ConnectError, RpcConn, RpcConnBuilder};
+
+/// A fixed set of [`RpcConn`]s to the same Arti instance.
+///
+/// An `RpcConn` is already safe to use from many threads at once,
+/// but every request on it goes out through a single writer,
+/// and its responses come back through a single reader.
+/// A program that keeps many requests in flight
+/// (for example, a language binding with green threads)
+/// can spread them over several connections instead,
+/// using [`get`](RpcConnPool::get) to choose one for each task.
+///
+/// Each connection has its own session,
+/// and objects that Arti returns on one connection are not valid on the others.
+/// So a task that uses an object ID should keep using the connection that gave it that ID.
+#[derive(Debug)]
+pub struct RpcConnPool {
+    /// The connections in this pool.
+    conns: Vec<Arc<RpcConn>>,
+    /// Where to start looking for an idle connection next time.
+    ///
+    /// (We rotate this so that ties are spread across the pool.)
+    next: AtomicUsize,
+}
+
+impl RpcConnPool {
+    /// Open a pool of `n_conns` connections with `builder`.
+    ///
+    /// (The pool always has at least one connection.)
+    ///
+    /// Fails if any of the connections can't be opened.
+    pub fn new(builder: &RpcConnBuilder, n_conns: usize) -> Result<Self, ConnectError> {
+        let conns = (0..n_conns.max(1))
+            .map(|_| builder.connect())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::from_conns(conns))
+    }
+
+    /// Construct a new pool from a nonempty list of connections.
+    fn from_conns(conns: Vec<RpcConn>) -> Self {
+        assert!(!conns.is_empty());
+        Self {
+            conns: conns.into_iter().map(Arc::new).collect(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Return the connection in this pool with the fewest requests in progress.
+    pub fn get(&self) -> &Arc<RpcConn> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % self.conns.len();
+        let (first, rest) = (&self.conns[start..], &self.conns[..start]);
+        first
+            .iter()
+            .chain(rest)
+            .min_by_key(|conn| conn.n_pending())
+            .expect("Pool was empty")
+    }
+
+    /// Return every connection in this pool.
+    pub fn conns(&self) -> &[Arc<RpcConn>] {
+        &self.conns
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+
+    use std::io::BufReader;
+
+    use socketpair::SocketpairStream;
+
+    use super::*;
+    use crate::{llconn, ObjectId};
+
+    /// helper: Return a dummy RpcConn with the session `session`,
+    /// along with the other end of its socket.
+    fn dummy_connected(session: &str) -> (RpcConn, SocketpairStream) {
+        let (s1, s2) = socketpair::socketpair_stream().unwrap();
+        let s1_w = s1.try_clone().unwrap();
+        let mut conn = RpcConn::new(
+            llconn::Reader::new(BufReader::new(s1)),
+            llconn::Writer::new(s1_w),
+        );
+        conn.session = Some(ObjectId::try_from(session.to_string()).unwrap());
+        (conn, s2)
+    }
+
+    #[test]
+    fn shareable() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<RpcConn>();
+        assert_send_sync::<RpcConnPool>();
+    }
+
+    #[test]
+    fn least_busy() {
+        let (c1, _s1) = dummy_connected("sess-1");
+        let (c2, _s2) = dummy_connected("sess-2");
+        let pool = RpcConnPool::from_conns(vec![c1, c2]);
+
+        // With nothing in progress, we take turns.
+        let a = pool.get().session().unwrap().clone();
+        let b = pool.get().session().unwrap().clone();
+        assert_ne!(a, b);
+
+        // Once one connection is busy, we prefer the other.
+        let busy = &pool.conns()[0];
+        let _h = busy
+            .execute_with_handle(r#"{"obj":"sess-1","method":"arti:x-echo","params":{}}"#)
+            .unwrap();
+        for _ in 0..4 {
+            assert_eq!(pool.get().session().unwrap().as_ref(), "sess-2");
+        }
+    }
+}
@@ -0,0 +1,342 @@
+//! Replacing an RpcConn's connection to Arti after Arti closes it.
+
+use std::{
+    panic::RefUnwindSafe,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use super::{ConnectError, ProtoError, RpcConn, RpcConnBuilder, ShutdownError};
+use crate::msgs::{AnyRequestId, ObjectId};
+
+/// A rule for replacing an [`RpcConn`]'s connection to Arti if Arti closes it.
+///
+/// Without a reconnect policy (the default), once Arti closes a connection,
+/// every request on it fails with [`ProtoError::Shutdown`].
+///
+/// With a reconnect policy, the next request we send after Arti closes the connection
+/// makes a new connection (in the same way as the original one),
+/// authenticates again, and then goes out on the new connection.
+/// Requests to [`RpcConn::session`] are sent to the new session.
+///
+/// Requests that were in progress when the old connection closed still fail,
+/// since we can't know whether Arti acted on them.
+/// Every other object that Arti gave us on the old connection is no longer valid.
+/// To learn which requests and objects were lost,
+/// use [`on_reconnect`](ReconnectPolicy::on_reconnect).
+#[derive(Clone, educe::Educe)]
+#[educe(Debug)]
+pub struct ReconnectPolicy {
+    /// How many times to try connecting, each time we reconnect.
+    max_attempts: u32,
+    /// How long to wait after a failed attempt before trying again.
+    retry_delay: Duration,
+    /// A function to call every time we reconnect.
+    #[educe(Debug(ignore))]
+    on_reconnect: Option<ReconnectCallback>,
+}
+
+/// A function to call every time a [`ReconnectPolicy`] reconnects.
+type ReconnectCallback = Arc<dyn Fn(&Reconnected) + Send + Sync + RefUnwindSafe>;
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            retry_delay: Duration::from_secs(1),
+            on_reconnect: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Return a new `ReconnectPolicy` with default settings.
+    ///
+    /// By default, we try to connect 3 times, one second apart.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Try to connect at most `n` times each time we reconnect.
+    ///
+    /// (We always try at least once.)
+    pub fn max_attempts(mut self, n: u32) -> Self {
+        self.max_attempts = n;
+        self
+    }
+
+    /// Wait for `delay` after a failed attempt to connect, before trying again.
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Call `f` every time we reconnect, with a report of what was lost.
+    ///
+    /// `f` is called from whichever thread sent the request that caused us to reconnect,
+    /// before that request is sent.
+    /// It must not send requests on the `RpcConn`.
+    pub fn on_reconnect<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Reconnected) + Send + Sync + RefUnwindSafe + 'static,
+    {
+        self.on_reconnect = Some(Arc::new(f));
+        self
+    }
+}
+
+/// A report of what was lost when an [`RpcConn`] replaced its connection to Arti.
+#[derive(Clone, Debug)]
+pub struct Reconnected {
+    /// The error that closed the old connection, if we saw one.
+    cause: Option<ShutdownError>,
+    /// The requests that were in progress on the old connection.
+    abandoned_requests: Vec<AnyRequestId>,
+    /// The objects that Arti gave us on the old connection, and that we had not released.
+    invalidated_objects: Vec<ObjectId>,
+}
+
+impl Reconnected {
+    /// Construct a new `Reconnected` report.
+    pub(super) fn new(
+        cause: Option<ShutdownError>,
+        abandoned_requests: Vec<AnyRequestId>,
+        invalidated_objects: Vec<ObjectId>,
+    ) -> Self {
+        Self {
+            cause,
+            abandoned_requests,
+            invalidated_objects,
+        }
+    }
+
+    /// Return the error that closed the old connection, if we saw one.
+    pub fn cause(&self) -> Option<&ShutdownError> {
+        self.cause.as_ref()
+    }
+
+    /// Return the IDs of the requests that were in progress on the old connection,
+    /// and whose failure had not yet been reported.
+    ///
+    /// Each of these requests fails with [`ProtoError::Shutdown`].
+    /// Arti may or may not have acted on them.
+    pub fn abandoned_requests(&self) -> &[AnyRequestId] {
+        &self.abandoned_requests
+    }
+
+    /// Return the IDs of the objects that Arti gave us on the old connection,
+    /// and that we had not released.
+    ///
+    /// These IDs are no longer valid.
+    ///
+    /// (We only know about objects that Arti returned as `{"result": {"id": ...}}`.)
+    pub fn invalidated_objects(&self) -> &[ObjectId] {
+        &self.invalidated_objects
+    }
+}
+
+/// The information that an [`RpcConn`] needs in order to replace its connection.
+pub(super) struct Reconnector {
+    /// A builder to make new connections.
+    ///
+    /// (This builder has no reconnect policy of its own.)
+    builder: RpcConnBuilder,
+    /// The policy we follow when we reconnect.
+    policy: ReconnectPolicy,
+    /// If we have reconnected, the ID of the session on our current connection.
+    ///
+    /// We hold this lock for as long as we are reconnecting,
+    /// so that only one thread does so at a time.
+    /// It must never be taken while holding any other lock on the `RpcConn`.
+    live_session: Mutex<Option<ObjectId>>,
+}
+
+impl Reconnector {
+    /// Construct a new `Reconnector` to make connections with `builder`,
+    /// as `policy` says.
+    pub(super) fn new(builder: RpcConnBuilder, policy: ReconnectPolicy) -> Self {
+        Self {
+            builder,
+            policy,
+            live_session: Mutex::new(None),
+        }
+    }
+
+    /// Try to open a new connection, as many times as our policy allows.
+    fn dial(&self) -> Result<RpcConn, ConnectError> {
+        let mut attempts = 1;
+        loop {
+            match self.builder.connect() {
+                Ok(conn) => return Ok(conn),
+                Err(e) if attempts >= self.policy.max_attempts => return Err(e),
+                Err(_) => {
+                    attempts += 1;
+                    thread::sleep(self.policy.retry_delay);
+                }
+            }
+        }
+    }
+}
+
+impl RpcConn {
+    /// If Arti has closed this connection, and we have a reconnect policy,
+    /// open a new connection and use it from now on.
+    ///
+    /// Must not be called while holding any lock on this `RpcConn`.
+    pub(super) fn reconnect_if_needed(&self) -> Result<(), ProtoError> {
+        let Some(reconnector) = self.reconnector() else {
+            return Ok(());
+        };
+        if !self.is_shut_down() {
+            return Ok(());
+        }
+
+        let mut live_session = reconnector.live_session.lock().expect("poisoned");
+        // Somebody else may have reconnected while we were waiting for the lock.
+        if !self.is_shut_down() {
+            return Ok(());
+        }
+        let fresh = reconnector
+            .dial()
+            .map_err(|e| ProtoError::ReconnectFailed(Arc::new(e)))?;
+        let fresh_session = fresh.session.clone();
+        let report = self.adopt_transport(fresh);
+        *live_session = fresh_session;
+        drop(live_session);
+
+        if let Some(on_reconnect) = &reconnector.policy.on_reconnect {
+            on_reconnect(&report);
+        }
+        Ok(())
+    }
+
+    /// If we have reconnected, return the ID of the session on our current connection.
+    pub(super) fn live_session(&self) -> Option<ObjectId> {
+        self.reconnector()?
+            .live_session
+            .lock()
+            .expect("poisoned")
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+
+    use std::io::{BufRead as _, BufReader, Write as _};
+
+    use socketpair::SocketpairStream;
+
+    use super::*;
+    use crate::{llconn, msgs::request::ValidatedRequest};
+
+    /// helper: Return a dummy RpcConn with the session `session`,
+    /// along with a socketpair for it to talk to.
+    fn dummy_connected(session: &str) -> (RpcConn, BufReader<SocketpairStream>) {
+        let (s1, s2) = socketpair::socketpair_stream().unwrap();
+        let s1_w = s1.try_clone().unwrap();
+        let mut conn = RpcConn::new(
+            llconn::Reader::new(BufReader::new(s1)),
+            llconn::Writer::new(s1_w),
+        );
+        conn.session = Some(ObjectId::try_from(session.to_string()).unwrap());
+        (conn, BufReader::new(s2))
+    }
+
+    /// helper: Read the next request from `sock`.
+    fn read_request(sock: &mut BufReader<SocketpairStream>) -> ValidatedRequest {
+        let mut s = String::new();
+        let _len = sock.read_line(&mut s).unwrap();
+        ValidatedRequest::from_string_strict(&s).unwrap()
+    }
+
+    /// helper: Answer `request` on `sock` with `result`.
+    fn reply(
+        sock: &mut BufReader<SocketpairStream>,
+        request: &ValidatedRequest,
+        result: serde_json::Value,
+    ) {
+        let mut enc = serde_json::json!({ "id": request.id(), "result": result }).to_string();
+        enc.push('\n');
+        sock.get_mut().write_all(enc.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn adopt_transport() {
+        let (mut conn, mut sock1) = dummy_connected("sess-1");
+        conn.set_reconnector(Reconnector::new(
+            RpcConnBuilder::new(),
+            ReconnectPolicy::new(),
+        ));
+
+        // Arti gives us an object, and then closes the connection
+        // while two more requests are in progress.
+        let h1 = conn
+            .execute_with_handle(r#"{"obj":"sess-1","method":"arti:x-new","params":{}}"#)
+            .unwrap();
+        let req1 = read_request(&mut sock1);
+        reply(&mut sock1, &req1, serde_json::json!({ "id": "thing-1" }));
+        h1.wait().unwrap().unwrap();
+
+        let h2 = conn
+            .execute_with_handle(r#"{"obj":"sess-1","method":"arti:x-echo","params":{}}"#)
+            .unwrap();
+        let h3 = conn
+            .execute_with_handle(r#"{"obj":"sess-1","method":"arti:x-echo","params":{}}"#)
+            .unwrap();
+        let h3_id = h3.id().clone();
+        // (Read the requests first: closing a socket with unread data resets it.)
+        let _req2 = read_request(&mut sock1);
+        let _req3 = read_request(&mut sock1);
+        drop(sock1);
+        assert!(matches!(
+            h2.wait(),
+            Err(ProtoError::Shutdown(ShutdownError::ConnectionClosed))
+        ));
+        assert!(conn.is_shut_down());
+
+        // Now we switch to a new connection.
+        let (fresh, mut sock2) = dummy_connected("sess-2");
+        let fresh_session = fresh.session.clone();
+        let report = conn.adopt_transport(fresh);
+        *conn.reconnector().unwrap().live_session.lock().unwrap() = fresh_session;
+        assert!(!conn.is_shut_down());
+        assert!(matches!(
+            report.cause(),
+            Some(ShutdownError::ConnectionClosed)
+        ));
+        assert_eq!(report.abandoned_requests(), &[h3_id]);
+        assert_eq!(report.invalidated_objects().len(), 1);
+        assert_eq!(report.invalidated_objects()[0].as_ref(), "thing-1");
+
+        // The request we never waited for is still lost.
+        assert!(matches!(
+            h3.wait(),
+            Err(ProtoError::Shutdown(ShutdownError::ConnectionClosed))
+        ));
+
+        // New requests go to the new connection, and to the new session.
+        assert_eq!(conn.session().unwrap().as_ref(), "sess-1");
+        let h4 = conn
+            .execute_with_handle(r#"{"obj":"sess-1","method":"arti:x-echo","params":{}}"#)
+            .unwrap();
+        let req4 = read_request(&mut sock2);
+        assert_eq!(req4.obj().as_ref(), "sess-2");
+        reply(&mut sock2, &req4, serde_json::json!({}));
+        h4.wait().unwrap().unwrap();
+    }
+}
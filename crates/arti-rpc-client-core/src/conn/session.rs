@@ -0,0 +1,96 @@
+//! Several independent sessions on one connection to Arti.
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use crate::msgs::{request::ValidatedRequest, AnyRequestId, ObjectId};
+
+use super::{ConnectError, FinalResponse, ProtoError, RpcConn};
+
+/// An additional, separately authenticated session on an [`RpcConn`].
+///
+/// Every `RpcConn` negotiates a session when it connects (see [`RpcConn::session`]).
+/// Arti lets a client authenticate more than once on the same connection:
+/// each time, it gets a new session.
+/// `RpcSession` uses this to let several independent users share a single connection
+/// (for example, the plugins of a plugin host),
+/// without letting them use each other's objects.
+///
+/// Each `RpcSession` remembers the objects that Arti has given it,
+/// and will only send requests that are addressed to its own session,
+/// or to one of those objects.
+/// Any other request fails with [`ProtoError::ForeignObject`], without being sent.
+/// (This includes requests to the connection itself,
+/// since they could affect the other sessions.)
+///
+/// We only learn about an object when a successful response has a result
+/// of the form `{"id": "..."}`, as Arti's methods conventionally return new objects.
+///
+/// If the connection reconnects (see [`ReconnectPolicy`](crate::ReconnectPolicy)),
+/// the session and its objects are lost: open a new session instead.
+#[derive(educe::Educe)]
+#[educe(Debug)]
+pub struct RpcSession {
+    /// The connection that this session uses.
+    #[educe(Debug(ignore))]
+    conn: Arc<RpcConn>,
+    /// The ID of this session.
+    id: ObjectId,
+    /// The objects that Arti has given to this session, and that it hasn't released.
+    objects: Mutex<HashSet<ObjectId>>,
+}
+
+impl RpcSession {
+    /// Authenticate again on `conn`, and return the resulting session.
+    ///
+    /// We authenticate with the same scheme that `conn` used when it connected.
+    pub fn open(conn: Arc<RpcConn>) -> Result<Self, ConnectError> {
+        let scheme = conn.auth_scheme.ok_or(ProtoError::NotAuthenticated)?;
+        let id = conn.authenticate_inherent(scheme)?;
+        Ok(Self {
+            conn,
+            id,
+            objects: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// Return the ID of this session.
+    ///
+    /// As with [`RpcConn::session`], requests to the rest of Arti's functionality
+    /// start here.
+    pub fn id(&self) -> &ObjectId {
+        &self.id
+    }
+
+    /// Return the connection that this session uses.
+    pub fn conn(&self) -> &Arc<RpcConn> {
+        &self.conn
+    }
+
+    /// Run a command on behalf of this session, and wait for success or failure.
+    ///
+    /// This behaves as [`RpcConn::execute`],
+    /// except that it fails with [`ProtoError::ForeignObject`]
+    /// if the command is addressed to an object that doesn't belong to this session.
+    pub fn execute(&self, cmd: &str) -> Result<FinalResponse, ProtoError> {
+        // We only need to know where the command is addressed:
+        // the connection gives it a real ID when it sends it.
+        let request = ValidatedRequest::from_string_loose(cmd, || AnyRequestId::Number(0))?;
+        let obj = request.obj();
+        if obj != &self.id && !self.objects.lock().expect("poisoned").contains(obj) {
+            return Err(ProtoError::ForeignObject(obj.clone()));
+        }
+
+        let response = self.conn.execute(cmd)?;
+        if let Ok(success) = &response {
+            let mut objects = self.objects.lock().expect("poisoned");
+            if request.method() == "rpc:release" {
+                objects.remove(obj);
+            }
+            objects.extend(success.result_object_id());
+        }
+        Ok(response)
+    }
+}
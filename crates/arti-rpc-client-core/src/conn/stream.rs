@@ -9,7 +9,11 @@ use std::{
 use serde::{Deserialize, Serialize};
 
 use super::{ErrorResponse, RpcConn};
-use crate::{msgs::request::Request, ObjectId};
+use crate::{
+    methods::{self, ProxyInfo},
+    msgs::request::Request,
+    ObjectId,
+};
 
 use tor_error::ErrorReport as _;
 
@@ -92,57 +96,6 @@ struct SingleIdResponse {
 #[derive(Deserialize, Debug)]
 struct EmptyResponse {}
 
-/// Representation of a single proxy, as delivered by the RPC API.
-// TODO RPC: This is duplicated from proxyinfo.rs; decide on our strategy for this stuff.
-#[derive(Deserialize, Clone, Debug)]
-pub(super) struct Proxy {
-    /// Where the proxy is listening, and what protocol-specific options it expects.
-    pub(super) listener: ProxyListener,
-}
-
-/// Representation of a single proxy's listener location, as delivered by the RPC API.
-#[derive(Deserialize, Clone, Debug)]
-// TODO RPC: This is duplicated from proxyinfo.rs; decide on our strategy for this stuff.
-pub(super) enum ProxyListener {
-    /// A SOCKS5 proxy.
-    #[serde(rename = "socks5")]
-    Socks5 {
-        /// The address at which we're listening for SOCKS connections.
-        tcp_address: Option<SocketAddr>,
-    },
-    /// Some other (unrecognized) listener type.
-    #[serde(untagged)]
-    Unrecognized {},
-}
-
-impl Proxy {
-    /// If this is a SOCKS proxy, return its address.
-    fn socks_addr(&self) -> Option<SocketAddr> {
-        match self.listener {
-            ProxyListener::Socks5 { tcp_address } => tcp_address,
-            ProxyListener::Unrecognized {} => None,
-        }
-    }
-}
-
-impl ProxyInfo {
-    /// Choose a SOCKS5 address to use from this list of proxies.
-    fn find_socks_addr(&self) -> Option<SocketAddr> {
-        // We choose the first usable Proxy.
-        self.proxies.iter().find_map(Proxy::socks_addr)
-    }
-}
-
-/// A representation of the set of proxy addresses available from the RPC API.
-// TODO RPC: This is duplicated from proxyinfo.rs; decide on our strategy for this stuff.
-#[derive(Deserialize, Clone, Debug)]
-pub(super) struct ProxyInfo {
-    /// A list of the supported proxies.
-    ///
-    /// (So far, only SOCKS proxies are listed, but other kinds may be listed in the future.)
-    pub(super) proxies: Vec<Proxy>,
-}
-
 impl RpcConn {
     /// Open a new data stream, registering the stream with the RPC system.
     ///
@@ -218,10 +171,9 @@ impl RpcConn {
     fn lookup_socks_proxy_addr(&self) -> Result<SocketAddr, StreamError> {
         let session_id = self.session_id_required()?.clone();
 
-        let proxy_info_request: Request<NoParameters> =
-            Request::new(session_id, "arti:get_rpc_proxy_info", NoParameters {});
+        let proxy_info_request = methods::Request::new(session_id, methods::GetRpcProxyInfo::new());
         let proxy_info = self.execute_internal_ok::<ProxyInfo>(&proxy_info_request.encode()?)?;
-        let socks_proxy_addr = proxy_info.find_socks_addr().ok_or(StreamError::NoProxy)?;
+        let socks_proxy_addr = proxy_info.socks_addr().ok_or(StreamError::NoProxy)?;
 
         Ok(socks_proxy_addr)
     }
@@ -322,6 +274,7 @@ mod test {
     //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
 
     use super::*;
+    use crate::methods::ProxyListener;
 
     #[test]
     fn unexpected_proxies() {
@@ -331,14 +284,14 @@ mod test {
             "#,
         )
         .unwrap();
-        assert_eq!(p.proxies.len(), 1);
-        match p.proxies[0].listener {
+        assert_eq!(p.proxies().len(), 1);
+        match p.proxies()[0].listener() {
             ProxyListener::Socks5 {
                 tcp_address: address,
             } => {
                 assert_eq!(address.unwrap(), "127.0.0.1:9090".parse().unwrap());
             }
-            ref other => panic!("{:?}", other),
+            other => panic!("{:?}", other),
         };
 
         let p: ProxyInfo = serde_json::from_str(
@@ -352,9 +305,6 @@ mod test {
             "#,
         )
         .unwrap();
-        assert_eq!(
-            p.find_socks_addr().unwrap(),
-            "127.0.0.1:9090".parse().unwrap()
-        );
+        assert_eq!(p.socks_addr().unwrap(), "127.0.0.1:9090".parse().unwrap());
     }
 }
@@ -0,0 +1,255 @@
+//! Connecting to Arti over TLS, with a pinned server certificate.
+//!
+//! This is how a client reaches an Arti RPC listener on another host.
+//! We don't use the web PKI at all:
+//! instead, the connect point names the SHA-256 digest of the server's certificate,
+//! and we accept that certificate and no other.
+//!
+//! An [`RpcConn`] reads and writes from different threads,
+//! but a TLS session is a single object.
+//! So the reading and writing halves of the connection share a [`ClientConnection`]
+//! behind a mutex.
+//! The reading half waits for data from the socket without holding the lock;
+//! the writing half holds it while it sends, so that records go out in order.
+//! (This is fine so long as the server reads and writes independently, as Arti does.)
+
+use std::{
+    io::{self, BufReader, Read, Write},
+    net::{SocketAddr, TcpStream},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{self, CryptoProvider},
+    pki_types::{CertificateDer, ServerName, UnixTime},
+    CertificateError, ClientConfig, ClientConnection, DigitallySignedStruct, OtherError,
+    SignatureScheme,
+};
+use sha2::{Digest as _, Sha256};
+
+use super::{ConnectError, RpcConn};
+use crate::llconn;
+
+/// The size of the buffer we use when reading TLS records from the socket.
+const READ_BUF_LEN: usize = 16 * 1024;
+
+/// Try to connect to an Arti RPC listener over TLS at `addr`,
+/// accepting only a server certificate whose SHA-256 digest is `cert_sha256`.
+pub(super) fn connect(addr: SocketAddr, cert_sha256: &[u8; 32]) -> Result<RpcConn, ConnectError> {
+    let handshake_failed = |e| ConnectError::TlsHandshakeFailed(Arc::new(e));
+    let tls_error = |e| handshake_failed(io::Error::new(io::ErrorKind::InvalidInput, e));
+
+    let provider = Arc::new(crypto::ring::default_provider());
+    let verifier = PinnedCertVerifier {
+        cert_sha256: *cert_sha256,
+        provider: provider.clone(),
+    };
+    let config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(tls_error)?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+    // (We use the address as the server name, so we don't send SNI.)
+    let mut session =
+        ClientConnection::new(Arc::new(config), ServerName::from(addr.ip())).map_err(tls_error)?;
+
+    let mut sock =
+        TcpStream::connect(addr).map_err(|e| ConnectError::CannotConnect(Arc::new(e)))?;
+    // Finish the handshake before we hand the connection to an RpcConn,
+    // so that if the certificate is wrong, we find out before we send anything.
+    while session.is_handshaking() {
+        session.complete_io(&mut sock).map_err(handshake_failed)?;
+    }
+    let sock_dup = sock
+        .try_clone()
+        .map_err(|e| ConnectError::CannotConnect(Arc::new(e)))?;
+
+    let shared = Arc::new(Shared {
+        session: Mutex::new(session),
+        sock: sock_dup,
+    });
+    let reader = TlsReader {
+        shared: shared.clone(),
+        sock,
+        buf: vec![0; READ_BUF_LEN].into_boxed_slice(),
+        start: 0,
+        end: 0,
+    };
+    let writer = TlsWriter { shared };
+    let mut conn = RpcConn::new(
+        llconn::Reader::new(Box::new(BufReader::new(reader))),
+        llconn::Writer::new(Box::new(writer)),
+    );
+
+    // Arti doesn't speak TLS itself: a TLS listener is a proxy
+    // that forwards connections to one of Arti's localhost TCP ports.
+    //
+    // TODO RPC: That means that anybody who can reach the proxy can use Arti.
+    // We should support client certificates, or some other real authentication.
+    conn.authenticate("inherent:tcp_localhost")?;
+
+    Ok(conn)
+}
+
+/// The state shared between the reading and writing halves of a TLS connection.
+struct Shared {
+    /// The TLS session.
+    session: Mutex<ClientConnection>,
+    /// A handle to the socket, for sending TLS records.
+    ///
+    /// We only write to this while holding the lock on `session`.
+    sock: TcpStream,
+}
+
+impl Shared {
+    /// Lock the TLS session.
+    fn lock(&self) -> MutexGuard<'_, ClientConnection> {
+        self.session.lock().expect("poisoned lock")
+    }
+}
+
+/// Send every TLS record that `session` has ready on `sock`.
+fn flush_records(session: &mut ClientConnection, mut sock: &TcpStream) -> io::Result<()> {
+    while session.wants_write() {
+        session.write_tls(&mut sock)?;
+    }
+    Ok(())
+}
+
+/// The reading half of a TLS connection.
+struct TlsReader {
+    /// The state we share with the writing half.
+    shared: Arc<Shared>,
+    /// A handle to the socket, for receiving TLS records.
+    sock: TcpStream,
+    /// Data that we have received from the socket.
+    buf: Box<[u8]>,
+    /// The start of the data in `buf` that we haven't yet given to the session.
+    start: usize,
+    /// The end of the data in `buf`.
+    end: usize,
+}
+
+impl Read for TlsReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let mut session = self.shared.lock();
+            match session.reader().read(out) {
+                // (Zero means that the server closed the session.)
+                Ok(n) => return Ok(n),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+
+            if self.start == self.end {
+                // Wait for more records, without holding the lock.
+                drop(session);
+                self.end = self.sock.read(&mut self.buf)?;
+                self.start = 0;
+                session = self.shared.lock();
+            }
+
+            // (If the socket was closed, this tells the session so.)
+            let mut records = &self.buf[self.start..self.end];
+            self.start += session.read_tls(&mut records)?;
+            session
+                .process_new_packets()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            // Processing records can give us something to send, like an alert.
+            flush_records(&mut session, &self.shared.sock)?;
+        }
+    }
+}
+
+/// The writing half of a TLS connection.
+struct TlsWriter {
+    /// The state we share with the reading half.
+    shared: Arc<Shared>,
+}
+
+impl Write for TlsWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut session = self.shared.lock();
+        let n = session.writer().write(buf)?;
+        flush_records(&mut session, &self.shared.sock)?;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut session = self.shared.lock();
+        session.writer().flush()?;
+        flush_records(&mut session, &self.shared.sock)
+    }
+}
+
+/// The server's certificate was not the one that the connect point told us to expect.
+#[derive(Clone, Debug, thiserror::Error)]
+#[error("Server certificate does not match the pinned SHA-256 digest")]
+struct CertificateMismatch;
+
+/// A certificate verifier that accepts a single certificate, identified by its SHA-256 digest.
+///
+/// We don't look at the certificate's names, issuer, or lifetime:
+/// the connect point told us exactly which certificate to expect.
+#[derive(Debug)]
+struct PinnedCertVerifier {
+    /// The SHA-256 digest of the DER encoding of the certificate we expect.
+    cert_sha256: [u8; 32],
+    /// The cryptography we use to check handshake signatures.
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if Sha256::digest(end_entity.as_ref()).as_slice() == self.cert_sha256 {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::InvalidCertificate(CertificateError::Other(
+                OtherError(Arc::new(CertificateMismatch)),
+            )))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
@@ -0,0 +1,1095 @@
+//! Finding a running Arti: connect points and the search path.
+//!
+//! A "connect point" is a small JSON document that says how to reach an Arti RPC server.
+//! To find one, we try the entries of a "search path" in order,
+//! until one of them lets us connect and authenticate.
+//! The search path is, from first to last:
+//!
+//!  1. The entries in `ARTI_RPC_CONNECT_PATH_OVERRIDE`, if it is set.
+//!  2. Any entries the application added with [`RpcConnBuilder::prepend_search_path`].
+//!  3. The entries in `ARTI_RPC_CONNECT_PATH`, if it is set.
+//!  4. The platform-specific defaults:
+//!     `${ARTI_LOCAL_DATA}/rpc/connect.d/`,
+//!     then (on Unix) `/etc/arti-rpc/connect.d/`,
+//!     then a few well-known socket locations.
+//!
+//! Paths in search path entries and in connect points may use the variables
+//! `${ARTI_LOCAL_DATA}` and `${USER_HOME}`,
+//! which expand to the same directories that Arti itself uses.
+//!
+//! A connect point may ask for cookie authentication,
+//! naming a "cookie file" that holds a secret shared with Arti.
+//! We locate and check that file as described in
+//! `doc/dev/rpc-book/src/rpc-cookie-sketch.md`.
+//!
+//! Each attempt to use an entry succeeds, "declines" (and we move on to the next entry),
+//! or "aborts" (and the whole search fails).
+//! We remember the outcome of every attempt in a [`DiscoveryReport`],
+//! so that applications can explain to their users why no Arti could be found.
+//!
+//! The format and the rules are described in more detail in
+//! `doc/dev/rpc-book/src/rpc-connect-sketch.md`.
+//
+// TODO RPC: We do not yet check the permissions on connect point files,
+// or refuse to run in a setuid environment.
+
+use std::{
+    ffi::OsString,
+    fmt, fs, io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::Deserialize;
+
+use crate::{conn::ConnectError, RpcConn};
+
+#[cfg(doc)]
+use crate::RpcConnBuilder;
+
+/// Environment variable: search path entries to try before those chosen by the application.
+pub const ENV_CONNECT_PATH_OVERRIDE: &str = "ARTI_RPC_CONNECT_PATH_OVERRIDE";
+
+/// Environment variable: search path entries to try before the built-in defaults.
+pub const ENV_CONNECT_PATH: &str = "ARTI_RPC_CONNECT_PATH";
+
+/// The connect point for a system-wide Arti.
+const SYSTEM_DEFAULT: &str =
+    r#"{ "connect": { "socket": "unix:/var/run/arti-rpc/arti_rpc_socket", "auth": "none" } }"#;
+
+/// One entry in a connect point search path.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum SearchEntry {
+    /// A connect point, given as JSON text.
+    Literal(String),
+    /// An absolute path to a connect point file,
+    /// or to a directory of connect point files.
+    ///
+    /// The path may use the variables `${ARTI_LOCAL_DATA}` and `${USER_HOME}`.
+    ///
+    /// Within a directory, we try every file with the extension `.json`
+    /// (ignoring hidden files), in lexicographical order.
+    Path(PathBuf),
+    /// An AF_UNIX socket at which Arti is listening,
+    /// and which accepts `inherent:unix_path` authentication.
+    UnixSocket(PathBuf),
+}
+
+impl fmt::Display for SearchEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchEntry::Literal(s) => write!(f, "connect point {:?}", s),
+            SearchEntry::Path(p) => write!(f, "connect point file {:?}", p),
+            SearchEntry::UnixSocket(p) => write!(f, "socket {:?}", p),
+        }
+    }
+}
+
+/// Where an entry in the search path came from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum EntryOrigin {
+    /// The entry was listed in `ARTI_RPC_CONNECT_PATH_OVERRIDE`.
+    OverrideEnvironment,
+    /// The entry was added by the application.
+    Application,
+    /// The entry was listed in `ARTI_RPC_CONNECT_PATH`.
+    Environment,
+    /// The entry is one of our built-in defaults.
+    Default,
+}
+
+/// The outcome of trying to use a single connect point.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum AttemptOutcome {
+    /// We connected to Arti.
+    Connected,
+    /// We could not use this connect point, and went on to the next one.
+    Declined(ConnectPointError),
+    /// We could not use this connect point, and gave up searching.
+    Aborted(ConnectPointError),
+}
+
+/// A record of our attempt to use a single connect point.
+#[derive(Clone, Debug)]
+pub struct ConnectAttempt {
+    /// Where the search path entry came from.
+    origin: EntryOrigin,
+    /// The search path entry.
+    entry: SearchEntry,
+    /// If `entry` is a directory, the file within it that we tried.
+    file: Option<PathBuf>,
+    /// What happened.
+    outcome: AttemptOutcome,
+}
+
+impl ConnectAttempt {
+    /// Return where the search path entry came from.
+    pub fn origin(&self) -> EntryOrigin {
+        self.origin
+    }
+
+    /// Return the search path entry that we tried.
+    pub fn entry(&self) -> &SearchEntry {
+        &self.entry
+    }
+
+    /// If the search path entry was a directory, return the file within it that we tried.
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    /// Return what happened when we tried this connect point.
+    pub fn outcome(&self) -> &AttemptOutcome {
+        &self.outcome
+    }
+}
+
+impl fmt::Display for ConnectAttempt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "connect point file {:?}", file)?,
+            None => write!(f, "{}", self.entry)?,
+        }
+        match &self.outcome {
+            AttemptOutcome::Connected => write!(f, ": connected"),
+            AttemptOutcome::Declined(e) => write!(f, ": {}", e),
+            AttemptOutcome::Aborted(e) => write!(f, ": {} (search aborted)", e),
+        }
+    }
+}
+
+/// A record of every connect point we tried while looking for Arti.
+#[derive(Clone, Debug, Default)]
+pub struct DiscoveryReport {
+    /// The attempts we made, in order.
+    attempts: Vec<ConnectAttempt>,
+}
+
+impl DiscoveryReport {
+    /// Return every attempt we made, in the order we made them.
+    pub fn attempts(&self) -> &[ConnectAttempt] {
+        &self.attempts
+    }
+
+    /// Return true if the search stopped early because a connect point told us to abort.
+    pub fn aborted(&self) -> bool {
+        matches!(
+            self.attempts.last().map(ConnectAttempt::outcome),
+            Some(AttemptOutcome::Aborted(_))
+        )
+    }
+}
+
+impl fmt::Display for DiscoveryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.attempts.is_empty() {
+            return write!(f, "search path was empty");
+        }
+        for (idx, attempt) in self.attempts.iter().enumerate() {
+            if idx > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", attempt)?;
+        }
+        Ok(())
+    }
+}
+
+/// A problem that prevented us from using a connect point.
+#[derive(Clone, Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ConnectPointError {
+    /// The connect point was given as a relative path.
+    #[error("Path is not absolute")]
+    RelativePath,
+    /// We couldn't read the connect point file or directory.
+    #[error("Unable to read: {0}")]
+    Unreadable(#[source] Arc<io::Error>),
+    /// The connect point directory contained no connect points.
+    #[error("Directory contains no connect points")]
+    EmptyDirectory,
+    /// The connect point was not valid JSON.
+    #[error("Not valid JSON: {0}")]
+    NotJson(#[source] Arc<serde_json::Error>),
+    /// The connect point was of a recognized type, but was not well-formed.
+    #[error("Invalid connect point: {0}")]
+    Invalid(String),
+    /// The connect point was of a type we don't recognize.
+    ///
+    /// (It may have been written by a newer version of Arti.)
+    #[error("Unrecognized type of connect point")]
+    UnrecognizedType,
+    /// The connect point asked for an embedded Arti, and there isn't one.
+    #[error("No embedded Arti is available")]
+    NoEmbeddedArti,
+    /// The connect point told us to stop searching.
+    #[error("Connect point says to stop searching")]
+    ExplicitAbort,
+    /// The connect point described a kind of socket we can't use.
+    #[error("Unsupported socket address {0:?}")]
+    UnsupportedSocket(String),
+    /// The connect point described a kind of authentication we can't use.
+    #[error("Unsupported authentication method")]
+    UnsupportedAuth,
+    /// A path used a `${VARIABLE}` that we don't know, or can't expand here.
+    #[error("Cannot expand variable ${{{0}}}")]
+    UnknownVariable(String),
+    /// The cookie file was not in the expected format.
+    #[error("Malformed cookie file")]
+    MalformedCookie,
+    /// We couldn't connect to Arti at the location the connect point described.
+    #[error("{0}")]
+    Connect(#[source] ConnectError),
+}
+
+impl ConnectPointError {
+    /// Return true if this problem should end our search,
+    /// rather than making us move on to the next connect point.
+    fn aborts_search(&self) -> bool {
+        use ConnectPointError as E;
+        match self {
+            E::Unreadable(e) => !matches!(
+                e.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied
+            ),
+            E::NotJson(_) | E::Invalid(_) | E::ExplicitAbort | E::MalformedCookie => true,
+            // (Note that a TLS handshake failure aborts the search:
+            // it may mean that somebody is impersonating Arti.)
+            E::Connect(e) => !matches!(
+                e,
+                ConnectError::CannotConnect(_) | ConnectError::SchemeNotSupported
+            ),
+            E::RelativePath
+            | E::EmptyDirectory
+            | E::UnrecognizedType
+            | E::NoEmbeddedArti
+            | E::UnsupportedSocket(_)
+            | E::UnsupportedAuth
+            | E::UnknownVariable(_) => false,
+        }
+    }
+}
+
+/// A function that looks up an environment variable.
+///
+/// (We take this as an argument so that we can test our search path logic.)
+pub(crate) type EnvLookup<'a> = &'a dyn Fn(&str) -> Option<OsString>;
+
+/// Look up an environment variable in our real environment.
+pub(crate) fn real_env(name: &str) -> Option<OsString> {
+    std::env::var_os(name)
+}
+
+/// Return the full search path for a client that added the entries in `application`.
+pub(crate) fn search_path(
+    application: &[SearchEntry],
+    env: EnvLookup<'_>,
+) -> Vec<(EntryOrigin, SearchEntry)> {
+    let from_env = |var, origin| {
+        env(var)
+            .map(|value| parse_env_search_path(&value))
+            .unwrap_or_default()
+            .into_iter()
+            .map(move |entry| (origin, entry))
+    };
+    from_env(ENV_CONNECT_PATH_OVERRIDE, EntryOrigin::OverrideEnvironment)
+        .chain(
+            application
+                .iter()
+                .map(|entry| (EntryOrigin::Application, entry.clone())),
+        )
+        .chain(from_env(ENV_CONNECT_PATH, EntryOrigin::Environment))
+        .chain(
+            default_search_path(env)
+                .into_iter()
+                .map(|entry| (EntryOrigin::Default, entry)),
+        )
+        .collect()
+}
+
+/// Parse the value of a search path environment variable.
+///
+/// Entries are separated with `:` on Unix and `;` on Windows.
+/// An entry that begins with `{` or `%` is a URL-encoded literal connect point;
+/// anything else is a path.
+fn parse_env_search_path(value: &OsString) -> Vec<SearchEntry> {
+    std::env::split_paths(value)
+        .filter(|p| !p.as_os_str().is_empty())
+        .map(|p| match p.to_str() {
+            Some(s) if s.starts_with(['{', '%']) => {
+                // If we can't decode it, pass it on as-is:
+                // the attempt to parse it will fail, and abort the search.
+                SearchEntry::Literal(percent_decode(s).unwrap_or_else(|| s.to_owned()))
+            }
+            _ => SearchEntry::Path(p),
+        })
+        .collect()
+}
+
+/// Decode a URL-encoded string.
+///
+/// Return None if the encoding is invalid, or if the result is not UTF-8.
+fn percent_decode(s: &str) -> Option<String> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hi = char::from(bytes.next()?).to_digit(16)?;
+            let lo = char::from(bytes.next()?).to_digit(16)?;
+            out.push(u8::try_from(hi * 16 + lo).ok()?);
+        } else {
+            out.push(b);
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Return the directory that Arti uses for its local data: `${ARTI_LOCAL_DATA}`.
+///
+/// (This matches the value that `tor_config::CfgPath` uses.)
+fn arti_local_data(env: EnvLookup<'_>) -> Option<PathBuf> {
+    let absolute = |v: OsString| Some(PathBuf::from(v)).filter(|p| p.is_absolute());
+    if cfg!(windows) {
+        absolute(env("LOCALAPPDATA")?).map(|p| p.join(r"torproject\Arti\data"))
+    } else if cfg!(target_os = "macos") {
+        absolute(env("HOME")?).map(|p| p.join("Library/Application Support/org.torproject.Arti"))
+    } else if let Some(dir) = env("XDG_DATA_HOME").and_then(absolute) {
+        Some(dir.join("arti"))
+    } else {
+        absolute(env("HOME")?).map(|p| p.join(".local/share/arti"))
+    }
+}
+
+/// Return the user's home directory: `${USER_HOME}`.
+fn user_home(env: EnvLookup<'_>) -> Option<PathBuf> {
+    let var = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
+    Some(PathBuf::from(env(var)?)).filter(|p| p.is_absolute())
+}
+
+/// Return the value of the path variable `${name}`, if we know it.
+fn path_variable(name: &str, env: EnvLookup<'_>) -> Option<PathBuf> {
+    match name {
+        "ARTI_LOCAL_DATA" => arti_local_data(env),
+        "USER_HOME" => user_home(env),
+        _ => None,
+    }
+}
+
+/// Expand every `${VARIABLE}` in `path`.
+///
+/// Paths that aren't UTF-8 can't contain variables, and are returned unchanged.
+fn expand_path(path: &Path, env: EnvLookup<'_>) -> Result<PathBuf, ConnectPointError> {
+    let Some(mut rest) = path.to_str() else {
+        return Ok(path.to_owned());
+    };
+    let mut out = String::new();
+    while let Some((before, after)) = rest.split_once("${") {
+        let (name, after) = after
+            .split_once('}')
+            .ok_or_else(|| ConnectPointError::UnknownVariable(after.to_owned()))?;
+        let value = path_variable(name, env)
+            .and_then(|p| p.into_os_string().into_string().ok())
+            .ok_or_else(|| ConnectPointError::UnknownVariable(name.to_owned()))?;
+        out.push_str(before);
+        out.push_str(&value);
+        rest = after;
+    }
+    out.push_str(rest);
+    Ok(out.into())
+}
+
+/// The fixed string at the start of every cookie file.
+const COOKIE_PREFIX: &[u8; 32] = b"====== arti-rpc-cookie-v1 ======";
+
+/// Read the secret cookie from the cookie file at `path`.
+///
+/// As with connect point files, we decline if the file is missing or forbidden to us,
+/// and abort if it is unreadable for any other reason, or malformed.
+fn read_cookie(path: &Path) -> Result<[u8; 32], ConnectPointError> {
+    let contents = fs::read(path).map_err(|e| ConnectPointError::Unreadable(Arc::new(e)))?;
+    contents
+        .strip_prefix(COOKIE_PREFIX)
+        .and_then(|cookie| <[u8; 32]>::try_from(cookie).ok())
+        .ok_or(ConnectPointError::MalformedCookie)
+}
+
+/// Return a literal connect point for an AF_UNIX socket at `path`,
+/// if `path` can be represented in one.
+fn unix_connect_point(path: &Path) -> Option<SearchEntry> {
+    let connect_point = serde_json::json!({
+        "connect": {
+            "socket": format!("unix:{}", path.to_str()?),
+            "auth": "none",
+        }
+    });
+    Some(SearchEntry::Literal(connect_point.to_string()))
+}
+
+/// The prefix of every named pipe on the local machine.
+const LOCAL_PIPE_PREFIX: &str = r"\\.\pipe\";
+
+/// The named pipe where Arti listens on Windows,
+/// if its `rpc.rpc_listen` option is left at its default.
+const DEFAULT_NAMED_PIPE: &str = r"\\.\pipe\arti\SOCKET";
+
+/// Return true if `name` is the name of a named pipe on the local machine.
+///
+/// (We don't connect to named pipes on other machines:
+/// `inherent:named_pipe` authentication only makes sense locally.)
+pub(crate) fn is_local_pipe_name(name: &str) -> bool {
+    name.len() > LOCAL_PIPE_PREFIX.len()
+        && name
+            .get(..LOCAL_PIPE_PREFIX.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(LOCAL_PIPE_PREFIX))
+}
+
+/// Return a literal connect point for the Windows named pipe `name`.
+pub(crate) fn named_pipe_connect_point(name: &str) -> SearchEntry {
+    let connect_point = serde_json::json!({
+        "connect": {
+            "socket": format!("pipe:{}", name),
+            "auth": "none",
+        }
+    });
+    SearchEntry::Literal(connect_point.to_string())
+}
+
+/// Return a literal connect point for a TLS listener at `addr`,
+/// whose certificate has the SHA-256 digest `cert_sha256`.
+pub(crate) fn tls_connect_point(addr: SocketAddr, cert_sha256: &[u8; 32]) -> SearchEntry {
+    let digest: String = cert_sha256.iter().map(|b| format!("{:02x}", b)).collect();
+    let connect_point = serde_json::json!({
+        "connect": {
+            "socket": format!("inet:{}", addr),
+            "auth": "none",
+            "tls": { "server_cert_sha256": digest },
+        }
+    });
+    SearchEntry::Literal(connect_point.to_string())
+}
+
+/// Decode the hex-encoded SHA-256 digest of a certificate.
+///
+/// Pairs of digits may be separated with colons,
+/// as in the output of `openssl x509 -fingerprint -sha256`.
+pub(crate) fn parse_cert_digest(s: &str) -> Option<[u8; 32]> {
+    let digits: Vec<u8> = s.bytes().filter(|b| *b != b':').collect();
+    if digits.len() != 64 {
+        return None;
+    }
+    let mut digest = [0_u8; 32];
+    for (byte, pair) in digest.iter_mut().zip(digits.chunks_exact(2)) {
+        let hi = char::from(pair[0]).to_digit(16)?;
+        let lo = char::from(pair[1]).to_digit(16)?;
+        *byte = u8::try_from(hi * 16 + lo).ok()?;
+    }
+    Some(digest)
+}
+
+/// Return the built-in default search path.
+fn default_search_path(env: EnvLookup<'_>) -> Vec<SearchEntry> {
+    let local_data = arti_local_data(env);
+    let mut path = vec![];
+    if let Some(dir) = &local_data {
+        path.push(SearchEntry::Path(dir.join("rpc").join("connect.d")));
+    }
+    if cfg!(unix) {
+        path.push(SearchEntry::Path("/etc/arti-rpc/connect.d".into()));
+    }
+    if let Some(dir) = &local_data {
+        path.extend(unix_connect_point(&dir.join("rpc").join("arti_rpc_socket")));
+    }
+    // This is where Arti listens if its `rpc.rpc_listen` option is left at its default.
+    if cfg!(windows) {
+        path.push(named_pipe_connect_point(DEFAULT_NAMED_PIPE));
+    } else if let Some(home) = env("HOME").map(PathBuf::from).filter(|p| p.is_absolute()) {
+        path.extend(unix_connect_point(&home.join(".local/run/arti/SOCKET")));
+    }
+    path.push(SearchEntry::Literal(SYSTEM_DEFAULT.into()));
+    path
+}
+
+/// A parsed connect point.
+#[derive(Debug, Eq, PartialEq)]
+enum ConnectPoint {
+    /// Connect to an AF_UNIX socket, with `inherent:unix_path` authentication.
+    Unix(PathBuf),
+    /// Connect to a local Windows named pipe, with `inherent:named_pipe` authentication.
+    NamedPipe(String),
+    /// Connect to a localhost TCP port, with `inherent:tcp_localhost` authentication.
+    TcpLocalhost(SocketAddr),
+    /// Connect to a TCP port over TLS, accepting only a certificate with the given
+    /// SHA-256 digest, and authenticate with `inherent:tcp_localhost`.
+    Tls(SocketAddr, [u8; 32]),
+    /// Connect to an AF_UNIX socket or a localhost TCP port,
+    /// and use cookie authentication with the cookie file at `cookie_path`.
+    Cookie {
+        /// The socket address, as given in the connect point.
+        socket: String,
+        /// The location of the cookie file.
+        cookie_path: PathBuf,
+    },
+    /// Use an embedded Arti.
+    Embedded,
+    /// Stop searching.
+    Abort,
+}
+
+/// The `connect` member of a regular connect point.
+#[derive(Deserialize, Debug)]
+struct ConnectMember {
+    /// How to open a connection to Arti.
+    socket: String,
+    /// How to authenticate.
+    auth: serde_json::Value,
+    /// If present, we must use TLS on the socket.
+    tls: Option<TlsMember>,
+}
+
+/// The `cookie` member of an `auth` object.
+#[derive(Deserialize, Debug)]
+struct CookieMember {
+    /// The location of the cookie file.
+    cookie_path: PathBuf,
+}
+
+/// The `tls` member of a socket-connection object.
+#[derive(Deserialize, Debug)]
+struct TlsMember {
+    /// The hex-encoded SHA-256 digest of the server's certificate.
+    server_cert_sha256: String,
+}
+
+/// Parse the text of a connect point, expanding variables with `env`.
+fn parse_connect_point(text: &str, env: EnvLookup<'_>) -> Result<ConnectPoint, ConnectPointError> {
+    use ConnectPointError as E;
+
+    let value: serde_json::Value =
+        serde_json::from_str(text).map_err(|e| E::NotJson(Arc::new(e)))?;
+    let builtin = value.get("builtin");
+    let connect = value.get("connect");
+    let connect = match (builtin, connect) {
+        (Some(_), Some(_)) => {
+            return Err(E::Invalid(
+                "both \"builtin\" and \"connect\" are present".into(),
+            ))
+        }
+        (Some(builtin), None) => {
+            return match builtin.as_str() {
+                Some("embedded") => Ok(ConnectPoint::Embedded),
+                Some("abort") => Ok(ConnectPoint::Abort),
+                _ => Err(E::UnrecognizedType),
+            }
+        }
+        (None, Some(connect)) => connect,
+        (None, None) => return Err(E::UnrecognizedType),
+    };
+    let connect = ConnectMember::deserialize(connect).map_err(|e| E::Invalid(e.to_string()))?;
+    if let Some(cookie) = connect.auth.get("cookie") {
+        return parse_cookie_connect_point(connect.socket, cookie, env);
+    }
+    if connect.auth.as_str() != Some("none") {
+        return Err(E::UnsupportedAuth);
+    }
+
+    if let Some(tls) = &connect.tls {
+        let digest = parse_cert_digest(&tls.server_cert_sha256)
+            .ok_or_else(|| E::Invalid("malformed server_cert_sha256".into()))?;
+        let addr = connect
+            .socket
+            .strip_prefix("inet:")
+            .unwrap_or(&connect.socket);
+        // With a pinned certificate, the server can be anywhere.
+        return match addr.parse::<SocketAddr>() {
+            Ok(addr) => Ok(ConnectPoint::Tls(addr, digest)),
+            Err(_) => Err(E::UnsupportedSocket(connect.socket)),
+        };
+    }
+
+    if let Some(path) = connect.socket.strip_prefix("unix:") {
+        let path = expand_path(Path::new(path), env)?;
+        if !path.is_absolute() {
+            return Err(E::UnsupportedSocket(connect.socket));
+        }
+        return Ok(ConnectPoint::Unix(path));
+    }
+    if let Some(name) = connect.socket.strip_prefix("pipe:") {
+        if !is_local_pipe_name(name) {
+            return Err(E::UnsupportedSocket(connect.socket));
+        }
+        return Ok(ConnectPoint::NamedPipe(name.to_owned()));
+    }
+    let addr = connect
+        .socket
+        .strip_prefix("inet:")
+        .unwrap_or(&connect.socket);
+    match addr.parse::<SocketAddr>() {
+        // Arti only accepts inherent authentication on localhost TCP ports.
+        Ok(addr) if addr.ip().is_loopback() => Ok(ConnectPoint::TcpLocalhost(addr)),
+        _ => Err(E::UnsupportedSocket(connect.socket)),
+    }
+}
+
+/// Parse a connect point that uses cookie authentication,
+/// given its `socket` and the `cookie` member of its `auth`.
+fn parse_cookie_connect_point(
+    socket: String,
+    cookie: &serde_json::Value,
+    env: EnvLookup<'_>,
+) -> Result<ConnectPoint, ConnectPointError> {
+    use ConnectPointError as E;
+
+    let cookie = CookieMember::deserialize(cookie).map_err(|e| E::Invalid(e.to_string()))?;
+    let cookie_path = expand_path(&cookie.cookie_path, env)?;
+    if !cookie_path.is_absolute() {
+        return Err(E::RelativePath);
+    }
+    // As a matter of policy, we only use cookie authentication on local transports.
+    let local = match socket.strip_prefix("unix:") {
+        Some(path) => expand_path(Path::new(path), env)?.is_absolute(),
+        None => socket
+            .strip_prefix("inet:")
+            .unwrap_or(&socket)
+            .parse::<SocketAddr>()
+            .is_ok_and(|addr| addr.ip().is_loopback()),
+    };
+    if !local {
+        return Err(E::UnsupportedSocket(socket));
+    }
+    Ok(ConnectPoint::Cookie {
+        socket,
+        cookie_path,
+    })
+}
+
+/// Try to connect to Arti as described by the connect point `text`.
+fn connect_literal(text: &str, env: EnvLookup<'_>) -> Result<RpcConn, ConnectPointError> {
+    match parse_connect_point(text, env)? {
+        ConnectPoint::Unix(path) => crate::conn::connect_unix(&path),
+        ConnectPoint::NamedPipe(name) => crate::conn::connect_named_pipe(&name),
+        ConnectPoint::TcpLocalhost(addr) => crate::conn::connect_tcp_localhost(addr),
+        ConnectPoint::Tls(addr, digest) => crate::conn::connect_tls(addr, &digest),
+        ConnectPoint::Cookie { cookie_path, .. } => {
+            // We check the cookie file now, so that a missing or malformed cookie
+            // declines or aborts as the specification requires.
+            //
+            // TODO RPC: Perform the cookie handshake, once Arti's RPC server supports it.
+            let _cookie = read_cookie(&cookie_path)?;
+            return Err(ConnectPointError::UnsupportedAuth);
+        }
+        ConnectPoint::Embedded => return Err(ConnectPointError::NoEmbeddedArti),
+        ConnectPoint::Abort => return Err(ConnectPointError::ExplicitAbort),
+    }
+    .map_err(ConnectPointError::Connect)
+}
+
+/// Try to connect to Arti as described by the connect point file at `path`.
+fn connect_file(path: &Path, env: EnvLookup<'_>) -> Result<RpcConn, ConnectPointError> {
+    let text = fs::read_to_string(path).map_err(|e| ConnectPointError::Unreadable(Arc::new(e)))?;
+    connect_literal(&text, env)
+}
+
+/// Return the connect point files in the directory `dir`, in the order we should try them.
+fn connect_point_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        if name.starts_with('.') || !name.ends_with(".json") {
+            continue;
+        }
+        files.push(entry.path());
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Try to connect to Arti using the connect point file or directory at `path`.
+///
+/// Return a list of the files we tried (if `path` is a directory), and what happened.
+fn connect_path(
+    path: &Path,
+    env: EnvLookup<'_>,
+) -> Vec<(Option<PathBuf>, Result<RpcConn, ConnectPointError>)> {
+    if !path.is_absolute() {
+        return vec![(None, Err(ConnectPointError::RelativePath))];
+    }
+    if !path.is_dir() {
+        return vec![(None, connect_file(path, env))];
+    }
+    match connect_point_files(path) {
+        Ok(files) if files.is_empty() => {
+            vec![(None, Err(ConnectPointError::EmptyDirectory))]
+        }
+        Ok(files) => {
+            // Don't try the remaining files once one succeeds or aborts.
+            let mut results = vec![];
+            for file in files {
+                let result = connect_file(&file, env);
+                let done = match &result {
+                    Ok(_) => true,
+                    Err(e) => e.aborts_search(),
+                };
+                results.push((Some(file), result));
+                if done {
+                    break;
+                }
+            }
+            results
+        }
+        Err(e) => vec![(None, Err(ConnectPointError::Unreadable(Arc::new(e))))],
+    }
+}
+
+/// Try every entry in `path`, in order, until we connect to Arti.
+///
+/// We use `env` to expand variables in paths.
+/// On failure, return a report of every attempt we made.
+pub(crate) fn search(
+    path: Vec<(EntryOrigin, SearchEntry)>,
+    env: EnvLookup<'_>,
+) -> Result<RpcConn, DiscoveryReport> {
+    let mut report = DiscoveryReport::default();
+
+    for (origin, entry) in path {
+        // Each entry gives us a list of (file, result) pairs.
+        let results = match &entry {
+            SearchEntry::Literal(text) => vec![(None, connect_literal(text, env))],
+            SearchEntry::UnixSocket(p) => vec![(
+                None,
+                expand_path(p, env).and_then(|p| {
+                    crate::conn::connect_unix(&p).map_err(ConnectPointError::Connect)
+                }),
+            )],
+            SearchEntry::Path(p) => match expand_path(p, env) {
+                Ok(p) => connect_path(&p, env),
+                Err(e) => vec![(None, Err(e))],
+            },
+        };
+
+        for (file, result) in results {
+            let (outcome, conn) = match result {
+                Ok(conn) => (AttemptOutcome::Connected, Some(conn)),
+                Err(e) if e.aborts_search() => (AttemptOutcome::Aborted(e), None),
+                Err(e) => (AttemptOutcome::Declined(e), None),
+            };
+            let aborted = matches!(outcome, AttemptOutcome::Aborted(_));
+            report.attempts.push(ConnectAttempt {
+                origin,
+                entry: entry.clone(),
+                file,
+                outcome,
+            });
+            if let Some(conn) = conn {
+                return Ok(conn);
+            }
+            if aborted {
+                return Err(report);
+            }
+        }
+    }
+
+    Err(report)
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn parse() {
+        use ConnectPoint as CP;
+        let env = |name: &str| (name == "HOME").then(|| OsString::from("/home/user"));
+        let p = |s: &str| parse_connect_point(s, &env);
+
+        assert_eq!(
+            p(r#"{"connect":{"socket":"unix:/a/b","auth":"none"}}"#).unwrap(),
+            CP::Unix("/a/b".into())
+        );
+        assert_eq!(
+            p(r#"{"connect":{"socket":"inet:127.0.0.1:9180","auth":"none"}}"#).unwrap(),
+            CP::TcpLocalhost("127.0.0.1:9180".parse().unwrap())
+        );
+        assert_eq!(
+            p(r#"{"connect":{"socket":"[::1]:9180","auth":"none","x":1}}"#).unwrap(),
+            CP::TcpLocalhost("[::1]:9180".parse().unwrap())
+        );
+        let mut digest = [0_u8; 32];
+        digest[31] = 0xff;
+        let hex = "00000000000000000000000000000000000000000000000000000000000000ff";
+        assert_eq!(
+            p(&format!(
+                r#"{{"connect":{{"socket":"inet:192.0.2.1:9180","auth":"none","tls":{{"server_cert_sha256":"{hex}"}}}}}}"#
+            ))
+            .unwrap(),
+            CP::Tls("192.0.2.1:9180".parse().unwrap(), digest)
+        );
+        assert_eq!(
+            p(r#"{"connect":{"socket":"pipe:\\\\.\\pipe\\arti\\SOCKET","auth":"none"}}"#).unwrap(),
+            CP::NamedPipe(r"\\.\pipe\arti\SOCKET".into())
+        );
+        assert_eq!(
+            p(r#"{"connect":{"socket":"unix:${USER_HOME}/s","auth":"none"}}"#).unwrap(),
+            CP::Unix("/home/user/s".into())
+        );
+        assert_eq!(
+            p(r#"{"connect":{"socket":"inet:[::1]:9191","auth":{"cookie":{"cookie_path":"/c"}}}}"#)
+                .unwrap(),
+            CP::Cookie {
+                socket: "inet:[::1]:9191".into(),
+                cookie_path: "/c".into()
+            }
+        );
+        assert_eq!(p(r#"{"builtin":"abort"}"#).unwrap(), CP::Abort);
+        assert_eq!(p(r#"{"builtin":"embedded"}"#).unwrap(), CP::Embedded);
+
+        let declined = |s: &str| {
+            let e = p(s).unwrap_err();
+            assert!(!e.aborts_search(), "{s}: {e}");
+        };
+        declined(r#"{"builtin":"launch-a-rocket"}"#);
+        declined(r#"{"telepathy":{}}"#);
+        declined(r#"{"connect":{"socket":"unix:a/b","auth":"none"}}"#);
+        declined(r#"{"connect":{"socket":"inet:192.0.2.1:9180","auth":"none"}}"#);
+        declined(r#"{"connect":{"socket":"carrier-pigeon:7","auth":"none"}}"#);
+        declined(r#"{"connect":{"socket":"pipe:\\\\server\\pipe\\arti","auth":"none"}}"#);
+        declined(r#"{"connect":{"socket":"pipe:\\\\.\\pipe\\","auth":"none"}}"#);
+        declined(r#"{"connect":{"socket":"unix:/a/b","auth":{"password":{}}}}"#);
+        declined(r#"{"connect":{"socket":"unix:${NONESUCH}/b","auth":"none"}}"#);
+        declined(
+            r#"{"connect":{"socket":"inet:192.0.2.1:9191","auth":{"cookie":{"cookie_path":"/c"}}}}"#,
+        );
+        declined(r#"{"connect":{"socket":"unix:/a/b","auth":{"cookie":{"cookie_path":"c"}}}}"#);
+        declined(
+            r#"{"connect":{"socket":"unix:/a/b","auth":"none","tls":{"server_cert_sha256":"00000000000000000000000000000000000000000000000000000000000000ff"}}}"#,
+        );
+
+        let aborted = |s: &str| {
+            let e = p(s).unwrap_err();
+            assert!(e.aborts_search(), "{s}: {e}");
+        };
+        aborted("{");
+        aborted(r#"{"connect":{"auth":"none"}}"#);
+        aborted(
+            r#"{"connect":{"socket":"inet:192.0.2.1:9180","auth":"none","tls":{"server_cert_sha256":"00ff"}}}"#,
+        );
+        aborted(r#"{"builtin":"abort","connect":{"socket":"unix:/a/b","auth":"none"}}"#);
+        aborted(r#"{"connect":{"socket":"unix:/a/b","auth":{"cookie":{}}}}"#);
+    }
+
+    #[test]
+    fn variables() {
+        let env = |name: &str| match name {
+            "HOME" | "USERPROFILE" => Some(OsString::from("/home/user")),
+            "XDG_DATA_HOME" => Some(OsString::from("/home/user/data")),
+            "LOCALAPPDATA" => Some(OsString::from("/home/user/appdata")),
+            _ => None,
+        };
+        let x = |s: &str| expand_path(Path::new(s), &env);
+
+        assert_eq!(x("/a/b").unwrap(), Path::new("/a/b"));
+        assert_eq!(
+            x("${USER_HOME}/a/${USER_HOME}").unwrap(),
+            Path::new("/home/user/a//home/user")
+        );
+        assert_eq!(
+            x("${ARTI_LOCAL_DATA}/rpc").unwrap(),
+            arti_local_data(&env).unwrap().join("rpc")
+        );
+        assert!(matches!(
+            x("${NONESUCH}/a"),
+            Err(ConnectPointError::UnknownVariable(v)) if v == "NONESUCH"
+        ));
+        assert!(matches!(
+            x("/a/${USER_HOME"),
+            Err(ConnectPointError::UnknownVariable(_))
+        ));
+        // Without a home directory, we can't expand these variables.
+        assert!(expand_path(Path::new("${USER_HOME}"), &|_| None).is_err());
+    }
+
+    #[test]
+    fn cookie() {
+        let dir = std::env::temp_dir().join(format!("arti-rpc-cookie-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let e = read_cookie(&dir.join("nonesuch")).unwrap_err();
+        assert!(!e.aborts_search(), "{e}");
+
+        let good = dir.join("good");
+        let mut contents = COOKIE_PREFIX.to_vec();
+        contents.extend([7_u8; 32]);
+        fs::write(&good, &contents).unwrap();
+        assert_eq!(read_cookie(&good).unwrap(), [7_u8; 32]);
+
+        let short = dir.join("short");
+        fs::write(&short, &contents[..63]).unwrap();
+        let e = read_cookie(&short).unwrap_err();
+        assert!(matches!(e, ConnectPointError::MalformedCookie));
+        assert!(e.aborts_search());
+
+        let wrong_prefix = dir.join("wrong_prefix");
+        contents[0] = b'!';
+        fs::write(&wrong_prefix, &contents).unwrap();
+        assert!(matches!(
+            read_cookie(&wrong_prefix),
+            Err(ConnectPointError::MalformedCookie)
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cert_digest() {
+        let mut digest = [0_u8; 32];
+        digest[0] = 0xab;
+        digest[31] = 0x01;
+        let hex = "AB000000000000000000000000000000000000000000000000000000000000:01";
+        assert_eq!(parse_cert_digest(hex), Some(digest));
+        assert_eq!(parse_cert_digest(&hex.to_lowercase()), Some(digest));
+        assert_eq!(parse_cert_digest("ab"), None);
+        assert_eq!(parse_cert_digest(&hex.replace('A', "g")), None);
+
+        let addr: SocketAddr = "[2001:db8::7]:9180".parse().unwrap();
+        let SearchEntry::Literal(text) = tls_connect_point(addr, &digest) else {
+            panic!("not a literal");
+        };
+        assert_eq!(
+            parse_connect_point(&text, &|_| None).unwrap(),
+            ConnectPoint::Tls(addr, digest)
+        );
+    }
+
+    #[test]
+    fn env_entries() {
+        assert_eq!(percent_decode("%7B%22a%22%3A1%7d").unwrap(), r#"{"a":1}"#);
+        assert!(percent_decode("%7").is_none());
+        assert!(percent_decode("%zz").is_none());
+
+        let sep = if cfg!(windows) { ";" } else { ":" };
+        let value = [
+            "/etc/arti-rpc/mine.json",
+            "",
+            "%7B%22builtin%22%3A%22abort%22%7D",
+        ]
+        .join(sep);
+        assert_eq!(
+            parse_env_search_path(&value.into()),
+            vec![
+                SearchEntry::Path("/etc/arti-rpc/mine.json".into()),
+                SearchEntry::Literal(r#"{"builtin":"abort"}"#.into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn order() {
+        let vars: HashMap<&str, &str> = [
+            (ENV_CONNECT_PATH_OVERRIDE, "/override"),
+            (ENV_CONNECT_PATH, "/env"),
+            ("HOME", "/home/user"),
+            ("XDG_DATA_HOME", "/home/user/data"),
+            ("LOCALAPPDATA", "/home/user/appdata"),
+        ]
+        .into_iter()
+        .collect();
+        let env = |name: &str| vars.get(name).map(OsString::from);
+        let app = vec![SearchEntry::Path("/app".into())];
+
+        let path = search_path(&app, &env);
+        assert_eq!(
+            path[..3],
+            [
+                (
+                    EntryOrigin::OverrideEnvironment,
+                    SearchEntry::Path("/override".into())
+                ),
+                (EntryOrigin::Application, SearchEntry::Path("/app".into())),
+                (EntryOrigin::Environment, SearchEntry::Path("/env".into())),
+            ]
+        );
+        assert!(path[3..]
+            .iter()
+            .all(|(origin, _)| *origin == EntryOrigin::Default));
+        assert_eq!(
+            path.last().unwrap().1,
+            SearchEntry::Literal(SYSTEM_DEFAULT.into())
+        );
+
+        // With no environment at all, we still have some defaults.
+        let path = search_path(&[], &|_| None);
+        assert!(!path.is_empty());
+    }
+
+    #[test]
+    fn report() {
+        let path = vec![
+            (
+                EntryOrigin::Application,
+                SearchEntry::Path("relative".into()),
+            ),
+            (
+                EntryOrigin::Application,
+                SearchEntry::Literal(r#"{"builtin":"embedded"}"#.into()),
+            ),
+            (
+                EntryOrigin::Application,
+                SearchEntry::Literal(r#"{"builtin":"abort"}"#.into()),
+            ),
+            (
+                EntryOrigin::Default,
+                SearchEntry::Literal(SYSTEM_DEFAULT.into()),
+            ),
+        ];
+        let report = search(path, &|_| None).err().unwrap();
+        assert!(report.aborted());
+        let attempts = report.attempts();
+        assert_eq!(attempts.len(), 3);
+        assert!(matches!(
+            attempts[0].outcome(),
+            AttemptOutcome::Declined(ConnectPointError::RelativePath)
+        ));
+        assert!(matches!(
+            attempts[1].outcome(),
+            AttemptOutcome::Declined(ConnectPointError::NoEmbeddedArti)
+        ));
+        assert!(matches!(
+            attempts[2].outcome(),
+            AttemptOutcome::Aborted(ConnectPointError::ExplicitAbort)
+        ));
+        assert_eq!(
+            report.to_string(),
+            concat!(
+                r#"connect point file "relative": Path is not absolute; "#,
+                r#"connect point "{\"builtin\":\"embedded\"}": No embedded Arti is available; "#,
+                r#"connect point "{\"builtin\":\"abort\"}": Connect point says to stop searching (search aborted)"#,
+            )
+        );
+
+        let report = search(vec![], &|_| None).err().unwrap();
+        assert!(!report.aborted());
+        assert_eq!(report.to_string(), "search path was empty");
+    }
+}
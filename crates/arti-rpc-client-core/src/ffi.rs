@@ -4,19 +4,24 @@
 //! (These include things like "all input pointers must be valid" and so on.)
 
 pub mod err;
+mod executor;
 mod util;
 
-use err::{ArtiRpcError, InvalidInput};
-use std::ffi::{c_char, c_int};
+use err::{ArtiRpcError, InvalidInput, SpawnFailed, TimedOut, ARTI_RPC_STATUS_SUCCESS};
+use std::ffi::{c_char, c_int, c_void};
+use std::ops::ControlFlow;
+use std::panic::AssertUnwindSafe;
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
 use util::{
     ffi_body_raw, ffi_body_with_err, OptOutPtrExt as _, OptOutValExt, OutPtr, OutSocketOwned,
     OutVal,
 };
 
 use crate::{
-    conn::{AnyResponse, RequestHandle},
+    conn::{AnyResponse, RequestHandle, SuccessResponse},
     util::Utf8CString,
-    ObjectId, RpcConnBuilder,
+    ObjectId, ReconnectPolicy, RpcConnBuilder,
 };
 
 /// A status code returned by an Arti RPC function.
@@ -47,6 +52,14 @@ pub type ArtiRpcStr = Utf8CString;
 /// You can wait for the next message with `arti_rpc_handle_wait`.
 pub type ArtiRpcHandle = RequestHandle;
 
+/// A pool of open connections to Arti.
+///
+/// This is a thread-safe type: you may safely use it from multiple threads at once.
+///
+/// Once you are no longer going to use any of its connections, you must free
+/// it with [`arti_rpc_conn_pool_free`]
+pub type ArtiRpcConnPool = crate::RpcConnPool;
+
 /// The type of a message returned by an RPC request.
 pub type ArtiRpcResponseType = c_int;
 
@@ -78,6 +91,10 @@ impl Default for ArtiRpcRawSocket {
 ///
 /// The location of the instance and the method to connect to it are described in
 /// `connection_string`.
+/// If `connection_string` is NULL, search for a running Arti instead,
+/// as described in the documentation for `arti_rpc_client_core::discovery`.
+/// If no Arti is found, the error message explains why each location we tried
+/// could not be used.
 ///
 /// (TODO RPC: Document the format of this string better!)
 ///
@@ -103,10 +120,120 @@ pub unsafe extern "C" fn arti_rpc_connect(
             let rpc_conn_out: Option<OutPtr<ArtiRpcConn>> [out_ptr_opt];
             err error_out : Option<OutPtr<ArtiRpcError>>;
         } in {
-            let connection_string = connection_string
-                .ok_or(InvalidInput::NullPointer)?;
+            let builder = match connection_string {
+                Some(s) => RpcConnBuilder::from_connect_string(s)?,
+                None => RpcConnBuilder::new(),
+            };
+
+            let conn = builder.connect()?;
+
+            rpc_conn_out.write_boxed_value_if_ptr_set(conn);
+        }
+    )
+}
+
+/// A function to be called when an `ArtiRpcConn` replaces its connection to Arti.
+///
+/// It receives a newly allocated string containing a JSON object
+/// with two members:
+/// `abandoned_requests`, a list of the IDs of the requests
+/// that were in progress on the old connection,
+/// and `invalidated_objects`, a list of the object IDs
+/// that Arti had given us on the old connection, which are no longer valid.
+/// It also receives the `user_data` pointer that was passed to
+/// `arti_rpc_connect_with_reconnect`.
+///
+/// The function takes ownership of `report`:
+/// it is responsible for making sure that it is eventually freed.
+pub type ArtiRpcReconnectCallback =
+    Option<unsafe extern "C" fn(report: *mut ArtiRpcStr, user_data: *mut c_void)>;
+
+/// The reconnect callback for a connection, along with the `user_data` to pass to it.
+struct ReconnectNotifier {
+    /// The function to call when we reconnect.
+    callback: unsafe extern "C" fn(*mut ArtiRpcStr, *mut c_void),
+    /// The pointer to pass to `callback`.
+    user_data: *mut c_void,
+}
+
+// Safety: We never dereference `user_data`; we only hand it back to the callback.
+// The caller of `arti_rpc_connect_with_reconnect` promises that the callback and `user_data`
+// may be used from any thread that uses the connection.
+unsafe impl Send for ReconnectNotifier {}
+unsafe impl Sync for ReconnectNotifier {}
+
+impl ReconnectNotifier {
+    /// Report `reconnected` to the callback, as a newly allocated JSON string.
+    fn notify(&self, reconnected: &crate::Reconnected) {
+        let report = serde_json::json!({
+            "abandoned_requests": reconnected.abandoned_requests(),
+            "invalidated_objects": reconnected.invalidated_objects(),
+        });
+        let report =
+            Utf8CString::try_from(report.to_string()).expect("JSON encoding contained a NUL byte");
+        // Safety: The caller of `arti_rpc_connect_with_reconnect` promised that
+        // `callback` was safe to invoke in this way.
+        unsafe { (self.callback)(Box::into_raw(Box::new(report)), self.user_data) }
+    }
+}
 
-            let builder = RpcConnBuilder::from_connect_string(connection_string)?;
+/// Try to open a new connection to an Arti instance,
+/// which reconnects automatically if Arti closes it.
+///
+/// This behaves as `arti_rpc_connect`, except for what happens after Arti closes the connection.
+/// Ordinarily, every later request on the connection would fail.
+/// Instead, the next request makes a new connection to the same location
+/// (trying up to `max_attempts` times, one second apart),
+/// authenticates again, and is sent on the new connection.
+/// Requests addressed to the session ID from `arti_rpc_conn_get_session_id`
+/// are sent to the new session.
+///
+/// Requests that were in progress when the old connection closed still fail,
+/// and every other object ID from the old connection becomes invalid.
+/// If `on_reconnect` is not NULL, it is called with a report of what was lost,
+/// and with `user_data`, each time we reconnect.
+/// (See [`ArtiRpcReconnectCallback`] for the arguments it receives.)
+/// It is called on the thread that sent the request that caused us to reconnect,
+/// before that request is sent;
+/// it must not send requests on the connection.
+/// Both it and `user_data` must be safe to use from any thread.
+///
+/// If we cannot reconnect, the request fails with `ARTI_RPC_STATUS_SHUTDOWN`,
+/// and the next request tries again.
+///
+/// # Ownership
+///
+/// The caller is responsible for making sure that `*rpc_conn_out` and `*error_out`,
+/// if set, are eventually freed.
+///
+/// The callback is responsible for making sure that the `report` passed to it
+/// is eventually freed.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn arti_rpc_connect_with_reconnect(
+    connection_string: *const c_char,
+    max_attempts: u32,
+    on_reconnect: ArtiRpcReconnectCallback,
+    user_data: *mut c_void,
+    rpc_conn_out: *mut *mut ArtiRpcConn,
+    error_out: *mut *mut ArtiRpcError,
+) -> ArtiRpcStatus {
+    ffi_body_with_err!(
+        {
+            let connection_string: Option<&str> [in_str_opt];
+            let rpc_conn_out: Option<OutPtr<ArtiRpcConn>> [out_ptr_opt];
+            err error_out : Option<OutPtr<ArtiRpcError>>;
+        } in {
+            let mut builder = match connection_string {
+                Some(s) => RpcConnBuilder::from_connect_string(s)?,
+                None => RpcConnBuilder::new(),
+            };
+            let mut policy = ReconnectPolicy::new().max_attempts(max_attempts);
+            if let Some(callback) = on_reconnect {
+                let notifier = ReconnectNotifier { callback, user_data };
+                policy = policy.on_reconnect(move |reconnected| notifier.notify(reconnected));
+            }
+            builder.reconnect_policy(policy);
 
             let conn = builder.connect()?;
 
@@ -159,6 +286,10 @@ pub unsafe extern "C" fn arti_rpc_conn_get_session_id(
 ///
 /// (If response_out is set to NULL, then any successful response will be ignored.)
 ///
+/// If a default timeout has been set with `arti_rpc_conn_set_default_timeout`,
+/// and no response arrives before it expires,
+/// return `ARTI_RPC_STATUS_TIMED_OUT`, and ask Arti to cancel the request.
+///
 /// # Ownership
 ///
 /// The caller is responsible for making sure that `*error_out`, if set, is eventually freed.
@@ -182,12 +313,228 @@ pub unsafe extern "C" fn arti_rpc_conn_execute(
             let rpc_conn = rpc_conn.ok_or(InvalidInput::NullPointer)?;
             let msg = msg.ok_or(InvalidInput::NullPointer)?;
 
-            let success = rpc_conn.execute(msg)??;
+            let success = execute_with_timeout(rpc_conn, msg, rpc_conn.default_timeout())?;
+            response_out.write_boxed_value_if_ptr_set(Utf8CString::from(success));
+        }
+    )
+}
+
+/// Run an RPC request over `rpc_conn`, and wait no longer than `timeout_msec` milliseconds
+/// for a successful response.
+///
+/// This function behaves like `arti_rpc_conn_execute`,
+/// except that it uses `timeout_msec` instead of the connection's default timeout.
+/// If `timeout_msec` is 0, it waits for as long as it takes.
+///
+/// If no response arrives in time, return `ARTI_RPC_STATUS_TIMED_OUT`.
+/// In that case, we also ask Arti to cancel the request,
+/// but we don't wait to find out whether it did.
+///
+/// # Ownership
+///
+/// The caller is responsible for making sure that `*error_out`, if set, is eventually freed.
+///
+/// The caller is responsible for making sure that `*response_out`, if set, is eventually freed.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn arti_rpc_conn_execute_with_timeout(
+    rpc_conn: *const ArtiRpcConn,
+    msg: *const c_char,
+    timeout_msec: u64,
+    response_out: *mut *mut ArtiRpcStr,
+    error_out: *mut *mut ArtiRpcError,
+) -> ArtiRpcStatus {
+    ffi_body_with_err!(
+        {
+            let rpc_conn: Option<&ArtiRpcConn> [in_ptr_opt];
+            let msg: Option<&str> [in_str_opt];
+            let response_out: Option<OutPtr<ArtiRpcStr>> [out_ptr_opt];
+            err error_out: Option<OutPtr<ArtiRpcError>>;
+        } in {
+            let rpc_conn = rpc_conn.ok_or(InvalidInput::NullPointer)?;
+            let msg = msg.ok_or(InvalidInput::NullPointer)?;
+
+            let success = execute_with_timeout(rpc_conn, msg, timeout_from_msec(timeout_msec))?;
             response_out.write_boxed_value_if_ptr_set(Utf8CString::from(success));
         }
     )
 }
 
+/// Set how long `arti_rpc_conn_execute` waits for a response on `rpc_conn`
+/// before giving up with `ARTI_RPC_STATUS_TIMED_OUT`.
+///
+/// If `timeout_msec` is 0, it waits for as long as it takes.
+/// This is the default.
+///
+/// The timeout does not apply to functions that return a handle, or that use a callback:
+/// you can cancel those requests yourself, with `arti_rpc_handle_cancel`.
+///
+/// On success, return `ARTI_RPC_STATUS_SUCCESS`.
+/// Otherwise return some other status code,
+/// and set `*error_out` (if provided) to a newly allocated error object.
+///
+/// # Ownership
+///
+/// The caller is responsible for making sure that `*error_out`, if set, is eventually freed.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn arti_rpc_conn_set_default_timeout(
+    rpc_conn: *const ArtiRpcConn,
+    timeout_msec: u64,
+    error_out: *mut *mut ArtiRpcError,
+) -> ArtiRpcStatus {
+    ffi_body_with_err!(
+        {
+            let rpc_conn: Option<&ArtiRpcConn> [in_ptr_opt];
+            err error_out: Option<OutPtr<ArtiRpcError>>;
+        } in {
+            let rpc_conn = rpc_conn.ok_or(InvalidInput::NullPointer)?;
+            rpc_conn.set_default_timeout(timeout_from_msec(timeout_msec));
+        }
+    )
+}
+
+/// Helper: Convert a timeout in milliseconds, where 0 means "no timeout", to a `Duration`.
+fn timeout_from_msec(timeout_msec: u64) -> Option<Duration> {
+    (timeout_msec != 0).then(|| Duration::from_millis(timeout_msec))
+}
+
+/// Helper: Run `msg` over `rpc_conn`, and wait for its response,
+/// giving up after `timeout` (if any).
+///
+/// If we give up, we ask Arti to cancel the request (without waiting for it to do so),
+/// and return [`TimedOut`].
+fn execute_with_timeout(
+    rpc_conn: &ArtiRpcConn,
+    msg: &str,
+    timeout: Option<Duration>,
+) -> Result<SuccessResponse, ArtiRpcError> {
+    let Some(timeout) = timeout else {
+        return Ok(rpc_conn.execute(msg)??);
+    };
+
+    // We can't interrupt a thread that is waiting for a response,
+    // so we wait on the executor, and only wait for the executor here.
+    let handle = rpc_conn.execute_with_handle(msg)?;
+    let id = handle.id().clone();
+    let (tx, rx) = mpsc::channel();
+    executor::Executor::global()
+        .spawn(move || {
+            // (If waiting panics, we abort, so we never drop `tx` without sending.)
+            let response = err::abort_on_panic(AssertUnwindSafe(|| handle.wait()));
+            // If this fails, our caller has stopped waiting: the response is of no use.
+            let _ = tx.send(response);
+        })
+        .map_err(|e| SpawnFailed(Arc::new(e)))?;
+
+    match rx.recv_timeout(timeout) {
+        Ok(response) => Ok(response??),
+        Err(_) => {
+            // This is best-effort: if we can't ask Arti to cancel the request,
+            // there's nothing else for us to do.
+            // Whatever Arti says in reply, we wait for it on the executor,
+            // so that its state is cleaned up.
+            if let Ok(cancel) = rpc_conn.send_cancel(&id) {
+                let _ = executor::Executor::global().spawn(move || {
+                    let _ = err::abort_on_panic(AssertUnwindSafe(|| cancel.wait()));
+                });
+            }
+            Err(TimedOut.into())
+        }
+    }
+}
+
+/// Ask Arti which RPC methods it recognizes, and which objects they apply to.
+///
+/// On success, return `ARTI_RPC_STATUS_SUCCESS` and set `*table_out` to a newly allocated string
+/// containing a JSON object with two members:
+/// `methods`, mapping each method name to a description of the method,
+/// and `delegations`, mapping each type of object to the types of object it delegates to.
+/// (This is the `result` of Arti's `arti:x_list_all_rpc_methods` method.)
+///
+/// We ask Arti only once per connection, and remember the answer;
+/// if the connection reconnects, we ask again.
+///
+/// Otherwise return some other status code, set `*table_out` to NULL,
+/// and set `*error_out` (if provided) to a newly allocated error object.
+///
+/// # Ownership
+///
+/// The caller is responsible for making sure that `*error_out`, if set, is eventually freed.
+///
+/// The caller is responsible for making sure that `*table_out`, if set, is eventually freed.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn arti_rpc_conn_get_method_table(
+    rpc_conn: *const ArtiRpcConn,
+    table_out: *mut *mut ArtiRpcStr,
+    error_out: *mut *mut ArtiRpcError,
+) -> ArtiRpcStatus {
+    ffi_body_with_err!(
+        {
+            let rpc_conn: Option<&ArtiRpcConn> [in_ptr_opt];
+            let table_out: Option<OutPtr<ArtiRpcStr>> [out_ptr_opt];
+            err error_out: Option<OutPtr<ArtiRpcError>>;
+        } in {
+            let rpc_conn = rpc_conn.ok_or(InvalidInput::NullPointer)?;
+            let table_out = table_out.ok_or(InvalidInput::NullPointer)?;
+
+            let table = rpc_conn.method_table_raw()??;
+            let json = serde_json::to_string(&*table)
+                .map_err(|e| crate::ProtoError::CouldNotEncode(Arc::new(e)))?;
+            let json = Utf8CString::try_from(json)
+                .expect("JSON somehow contained NUL.");
+            table_out.write_value_boxed(json);
+        }
+    )
+}
+
+/// Ask Arti which proxies it offers to RPC applications,
+/// such as its SOCKS ports and its DNS resolvers.
+///
+/// On success, return `ARTI_RPC_STATUS_SUCCESS` and set `*proxy_info_out` to a newly allocated string
+/// containing a JSON object with one member, `proxies`:
+/// a list of proxies, in Arti's order of preference.
+/// Each proxy has a `listener` member, such as
+/// `{"socks5": {"tcp_address": "127.0.0.1:9150"}}`
+/// or `{"dns": {"udp_address": "127.0.0.1:9053"}}`.
+/// Applications should ignore any kind of listener that they don't recognize.
+/// (This is the `result` of Arti's `arti:get_rpc_proxy_info` method.)
+///
+/// Otherwise return some other status code, set `*proxy_info_out` to NULL,
+/// and set `*error_out` (if provided) to a newly allocated error object.
+///
+/// # Ownership
+///
+/// The caller is responsible for making sure that `*error_out`, if set, is eventually freed.
+///
+/// The caller is responsible for making sure that `*proxy_info_out`, if set, is eventually freed.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn arti_rpc_conn_get_proxy_info(
+    rpc_conn: *const ArtiRpcConn,
+    proxy_info_out: *mut *mut ArtiRpcStr,
+    error_out: *mut *mut ArtiRpcError,
+) -> ArtiRpcStatus {
+    ffi_body_with_err!(
+        {
+            let rpc_conn: Option<&ArtiRpcConn> [in_ptr_opt];
+            let proxy_info_out: Option<OutPtr<ArtiRpcStr>> [out_ptr_opt];
+            err error_out: Option<OutPtr<ArtiRpcError>>;
+        } in {
+            let rpc_conn = rpc_conn.ok_or(InvalidInput::NullPointer)?;
+            let proxy_info_out = proxy_info_out.ok_or(InvalidInput::NullPointer)?;
+
+            let proxy_info = rpc_conn.proxy_info_raw()??;
+            let json = serde_json::to_string(&proxy_info)
+                .map_err(|e| crate::ProtoError::CouldNotEncode(Arc::new(e)))?;
+            let json = Utf8CString::try_from(json)
+                .expect("JSON somehow contained NUL.");
+            proxy_info_out.write_value_boxed(json);
+        }
+    )
+}
+
 /// Send an RPC request over `rpc_conn`, and return a handle that can wait for a successful response.
 ///
 /// The message `msg` should be a valid RPC request in JSON format.
@@ -230,6 +577,248 @@ pub unsafe extern "C" fn arti_rpc_conn_execute_with_handle(
     )
 }
 
+/// A function to be called when an asynchronous RPC request completes.
+///
+/// It receives the status of the request; a response string, or NULL;
+/// an error object, or NULL; and the `user_data` pointer that was passed along with it.
+///
+/// On success, `status` is `ARTI_RPC_STATUS_SUCCESS`, `response` is a newly allocated string
+/// containing the JSON response to the request, and `error` is NULL.
+/// Otherwise, `status` is some other status code, `response` is NULL,
+/// and `error` is a newly allocated error object.
+///
+/// The function takes ownership of `response` and `error`:
+/// it is responsible for making sure that they are eventually freed.
+pub type ArtiRpcExecuteCallback = Option<
+    unsafe extern "C" fn(
+        status: ArtiRpcStatus,
+        response: *mut ArtiRpcStr,
+        error: *mut ArtiRpcError,
+        user_data: *mut c_void,
+    ),
+>;
+
+/// A function to be called when an asynchronous RPC request receives an update.
+///
+/// It receives a newly allocated string containing the JSON update
+/// (including its `id` and `update` fields),
+/// and the `user_data` pointer that was passed along with the request.
+///
+/// The function takes ownership of `update`:
+/// it is responsible for making sure that it is eventually freed.
+pub type ArtiRpcUpdateCallback =
+    Option<unsafe extern "C" fn(update: *mut ArtiRpcStr, user_data: *mut c_void)>;
+
+/// The callbacks for an asynchronous request, along with the `user_data` to pass to them.
+struct Completion {
+    /// The function to call when the request completes.
+    callback: unsafe extern "C" fn(ArtiRpcStatus, *mut ArtiRpcStr, *mut ArtiRpcError, *mut c_void),
+    /// The function to call with each update, if any.
+    on_update: Option<unsafe extern "C" fn(*mut ArtiRpcStr, *mut c_void)>,
+    /// The pointer to pass to `callback` and `on_update`.
+    user_data: *mut c_void,
+}
+
+// Safety: We never dereference `user_data`; we only hand it back to the callbacks.
+// The caller of `arti_rpc_conn_execute_async` (or `arti_rpc_conn_execute_with_updates_async`)
+// promises that the callbacks and `user_data` may be used from a thread other than
+// the one that called it.
+unsafe impl Send for Completion {}
+
+impl Completion {
+    /// Wait for every response to the request on `handle`, and report them to the callbacks.
+    fn run(self, handle: &RequestHandle) {
+        loop {
+            // (If waiting panics, we abort, so the handle's state is never observed again.)
+            let step = err::abort_on_panic(AssertUnwindSafe(|| match handle.wait_with_updates() {
+                Ok(AnyResponse::Update(update)) => ControlFlow::Continue(update.into()),
+                Ok(AnyResponse::Success(success)) => ControlFlow::Break(Ok(success.into())),
+                Ok(AnyResponse::Error(error_response)) => {
+                    ControlFlow::Break(Err(ArtiRpcError::from(error_response)))
+                }
+                Err(proto_error) => ControlFlow::Break(Err(ArtiRpcError::from(proto_error))),
+            }));
+            match step {
+                ControlFlow::Continue(update) => self.update(update),
+                ControlFlow::Break(result) => return self.complete(result),
+            }
+        }
+    }
+
+    /// Report `update` to the update callback (if any), transferring ownership of it.
+    fn update(&self, update: ArtiRpcStr) {
+        if let Some(on_update) = self.on_update {
+            // Safety: The caller of `arti_rpc_conn_execute_with_updates_async` promised that
+            // `on_update` was safe to invoke in this way.
+            unsafe { on_update(Box::into_raw(Box::new(update)), self.user_data) }
+        }
+    }
+
+    /// Report `result` to the callback, transferring ownership of any objects it holds.
+    fn complete(self, result: Result<ArtiRpcStr, ArtiRpcError>) {
+        let (status, response, error) = match result {
+            Ok(response) => (
+                ARTI_RPC_STATUS_SUCCESS,
+                Box::into_raw(Box::new(response)),
+                std::ptr::null_mut(),
+            ),
+            Err(error) => (
+                error.status,
+                std::ptr::null_mut(),
+                Box::into_raw(Box::new(error)),
+            ),
+        };
+        // Safety: The caller of `arti_rpc_conn_execute_async` promised that
+        // `callback` was safe to invoke in this way.
+        unsafe { (self.callback)(status, response, error, self.user_data) }
+    }
+}
+
+/// Helper: Send `msg` over `rpc_conn`, and have the executor report its responses to `completion`.
+///
+/// If `want_handle` is true, return a handle that the caller can use to cancel the request.
+fn execute_async(
+    rpc_conn: &ArtiRpcConn,
+    msg: &str,
+    completion: Completion,
+    want_handle: bool,
+) -> Result<Option<RequestHandle>, ArtiRpcError> {
+    // We send the request here, so that any problem with it is reported to our caller.
+    // Only the wait for its responses happens on the executor.
+    let handle = rpc_conn.execute_with_handle(msg)?;
+    let caller_handle = want_handle.then(|| handle.duplicate());
+    executor::Executor::global()
+        .spawn(move || completion.run(&handle))
+        .map_err(|e| SpawnFailed(Arc::new(e)))?;
+    Ok(caller_handle)
+}
+
+/// Send an RPC request over `rpc_conn`, and arrange for `callback` to be called
+/// with its response, without waiting for that response.
+///
+/// The message `msg` should be a valid RPC request in JSON format.
+/// If you omit its `id` field, one will be generated: this is typically the best way to use this function.
+///
+/// On success, return `ARTI_RPC_STATUS_SUCCESS`.
+/// Later, once the request has completed, `callback` will be invoked exactly once,
+/// from a thread owned by this library,
+/// with the outcome of the request and with `user_data`.
+/// (See [`ArtiRpcExecuteCallback`] for the arguments it receives.)
+/// Any updates that Arti sends for the request are discarded:
+/// to receive them, use `arti_rpc_conn_execute_with_updates_async`.
+///
+/// If `handle_out` is not NULL, then on success, also set `*handle_out` to a newly allocated
+/// `ArtiRpcHandle` for the request, which you can pass to `arti_rpc_handle_cancel`.
+/// You must not wait on this handle: its responses are delivered to `callback`.
+///
+/// Otherwise, if the request could not be sent, return some other status code,
+/// set `*handle_out` (if provided) to NULL,
+/// and set `*error_out` (if provided) to a newly allocated error object.
+/// In this case, `callback` will never be invoked.
+///
+/// Because `callback` runs on another thread, it must not block for long,
+/// and both it and `user_data` must be safe to use from any thread.
+/// Applications with a main-thread event loop will typically have `callback`
+/// post a message to that loop.
+///
+/// The request keeps running even if `rpc_conn` is freed before it completes;
+/// in that case, `callback` will most likely receive an error.
+///
+/// # Ownership
+///
+/// The caller is responsible for making sure that `*error_out`, if set, is eventually freed.
+///
+/// The caller is responsible for making sure that `*handle_out`, if set, is eventually freed.
+///
+/// The callback is responsible for making sure that the `response` and `error` objects
+/// passed to it are eventually freed.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn arti_rpc_conn_execute_async(
+    rpc_conn: *const ArtiRpcConn,
+    msg: *const c_char,
+    callback: ArtiRpcExecuteCallback,
+    user_data: *mut c_void,
+    handle_out: *mut *mut ArtiRpcHandle,
+    error_out: *mut *mut ArtiRpcError,
+) -> ArtiRpcStatus {
+    ffi_body_with_err!(
+        {
+            let rpc_conn: Option<&ArtiRpcConn> [in_ptr_opt];
+            let msg: Option<&str> [in_str_opt];
+            let handle_out: Option<OutPtr<ArtiRpcHandle>> [out_ptr_opt];
+            err error_out: Option<OutPtr<ArtiRpcError>>;
+        } in {
+            let rpc_conn = rpc_conn.ok_or(InvalidInput::NullPointer)?;
+            let msg = msg.ok_or(InvalidInput::NullPointer)?;
+            let callback = callback.ok_or(InvalidInput::NullPointer)?;
+            let completion = Completion { callback, on_update: None, user_data };
+
+            if let Some(handle) = execute_async(rpc_conn, msg, completion, handle_out.is_some())? {
+                handle_out.write_boxed_value_if_ptr_set(handle);
+            }
+        }
+    )
+}
+
+/// Send an RPC request over `rpc_conn`, and arrange for `update_callback` to be called
+/// with each update to it as it arrives, and for `callback` to be called with its response,
+/// without waiting for either.
+///
+/// This function behaves like `arti_rpc_conn_execute_async`,
+/// except that every update Arti sends for the request is passed to `update_callback`,
+/// along with `user_data`.
+/// (See [`ArtiRpcUpdateCallback`] for the arguments it receives.)
+///
+/// Arti only sends updates for requests that ask for them:
+/// to receive updates, include `"meta": {"updates": true}` in `msg`.
+///
+/// `update_callback` is invoked from the same thread as `callback`,
+/// once for each update, in the order that the updates arrive,
+/// and never after `callback`.
+/// It has the same restrictions as `callback`.
+///
+/// If the request could not be sent, neither callback will ever be invoked.
+///
+/// # Ownership
+///
+/// The caller is responsible for making sure that `*error_out`, if set, is eventually freed.
+///
+/// The caller is responsible for making sure that `*handle_out`, if set, is eventually freed.
+///
+/// The callbacks are responsible for making sure that the `update`, `response`, and `error`
+/// objects passed to them are eventually freed.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn arti_rpc_conn_execute_with_updates_async(
+    rpc_conn: *const ArtiRpcConn,
+    msg: *const c_char,
+    update_callback: ArtiRpcUpdateCallback,
+    callback: ArtiRpcExecuteCallback,
+    user_data: *mut c_void,
+    handle_out: *mut *mut ArtiRpcHandle,
+    error_out: *mut *mut ArtiRpcError,
+) -> ArtiRpcStatus {
+    ffi_body_with_err!(
+        {
+            let rpc_conn: Option<&ArtiRpcConn> [in_ptr_opt];
+            let msg: Option<&str> [in_str_opt];
+            let handle_out: Option<OutPtr<ArtiRpcHandle>> [out_ptr_opt];
+            err error_out: Option<OutPtr<ArtiRpcError>>;
+        } in {
+            let rpc_conn = rpc_conn.ok_or(InvalidInput::NullPointer)?;
+            let msg = msg.ok_or(InvalidInput::NullPointer)?;
+            let on_update = Some(update_callback.ok_or(InvalidInput::NullPointer)?);
+            let callback = callback.ok_or(InvalidInput::NullPointer)?;
+            let completion = Completion { callback, on_update, user_data };
+
+            if let Some(handle) = execute_async(rpc_conn, msg, completion, handle_out.is_some())? {
+                handle_out.write_boxed_value_if_ptr_set(handle);
+            }
+        }
+    )
+}
+
 /// A constant indicating that a message is a final result.
 ///
 /// After a result has been received, a handle will not return any more responses,
@@ -308,10 +897,52 @@ pub unsafe extern "C" fn arti_rpc_handle_wait(
     }
 }
 
+/// Ask Arti to cancel the request associated with `handle`, which was sent over `rpc_conn`.
+///
+/// This function waits for Arti to acknowledge the cancellation, but not for the request to stop.
+///
+/// On success, return `ARTI_RPC_STATUS_SUCCESS`.
+/// The request will then finish "reasonably quickly", most likely with an error
+/// from Arti whose code is 4 ("Request cancelled").
+/// (`arti_rpc_handle_wait` delivers this error as an `ARTI_RPC_RESPONSE_TYPE_ERROR` response;
+/// the other functions report it with the status `ARTI_RPC_STATUS_REQUEST_CANCELLED`.)
+/// The request may still finish in some other way, if it was about to finish anyway,
+/// so you should keep waiting on `handle` (or for your callback to be invoked)
+/// to learn how it finished.
+///
+/// Otherwise return some other status code,
+/// and set `*error_out` (if provided) to a newly allocated error object.
+/// If Arti knew of no such request in progress (usually, because it had already finished),
+/// the status is `ARTI_RPC_STATUS_REQUEST_COMPLETED`.
+///
+/// # Ownership
+///
+/// The caller is responsible for making sure that `*error_out`, if set, is eventually freed.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn arti_rpc_handle_cancel(
+    rpc_conn: *const ArtiRpcConn,
+    handle: *const ArtiRpcHandle,
+    error_out: *mut *mut ArtiRpcError,
+) -> ArtiRpcStatus {
+    ffi_body_with_err! {
+        {
+            let rpc_conn: Option<&ArtiRpcConn> [in_ptr_opt];
+            let handle: Option<&ArtiRpcHandle> [in_ptr_opt];
+            err error_out: Option<OutPtr<ArtiRpcError>>;
+        } in {
+            let rpc_conn = rpc_conn.ok_or(InvalidInput::NullPointer)?;
+            let handle = handle.ok_or(InvalidInput::NullPointer)?;
+
+            rpc_conn.cancel(handle.id())?;
+        }
+    }
+}
+
 /// Release storage held by an `ArtiRpcHandle`.
 ///
 /// NOTE, TODO: This does not cancel the request, but that is not guaranteed.
-/// Once we implement cancellation, this may behave differently.
+/// (To cancel a request, use `arti_rpc_handle_cancel` before freeing its handle.)
 #[allow(clippy::missing_safety_doc)]
 #[no_mangle]
 pub unsafe extern "C" fn arti_rpc_handle_free(handle: *mut ArtiRpcHandle) {
@@ -384,6 +1015,90 @@ pub unsafe extern "C" fn arti_rpc_conn_free(rpc_conn: *mut ArtiRpcConn) {
     );
 }
 
+/// Try to open a pool of `n_conns` connections to an Arti instance.
+///
+/// Each connection is opened as with `arti_rpc_connect`, using `connection_string`.
+/// (The pool always has at least one connection.)
+/// Use `arti_rpc_conn_pool_get` to choose a connection for each task.
+///
+/// Each connection has its own session,
+/// and object IDs that Arti returns on one connection are not valid on the others.
+///
+/// On success, return `ARTI_RPC_STATUS_SUCCESS` and set `*pool_out` to a new ArtiRpcConnPool.
+/// Otherwise return some other status code, set `*pool_out` to NULL, and set
+/// `*error_out` (if provided) to a newly allocated error object.
+///
+/// # Ownership
+///
+/// The caller is responsible for making sure that `*pool_out` and `*error_out`,
+/// if set, are eventually freed.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn arti_rpc_conn_pool_new(
+    connection_string: *const c_char,
+    n_conns: usize,
+    pool_out: *mut *mut ArtiRpcConnPool,
+    error_out: *mut *mut ArtiRpcError,
+) -> ArtiRpcStatus {
+    ffi_body_with_err!(
+        {
+            let connection_string: Option<&str> [in_str_opt];
+            let pool_out: Option<OutPtr<ArtiRpcConnPool>> [out_ptr_opt];
+            err error_out : Option<OutPtr<ArtiRpcError>>;
+        } in {
+            let builder = match connection_string {
+                Some(s) => RpcConnBuilder::from_connect_string(s)?,
+                None => RpcConnBuilder::new(),
+            };
+
+            let pool = crate::RpcConnPool::new(&builder, n_conns)?;
+
+            pool_out.write_boxed_value_if_ptr_set(pool);
+        }
+    )
+}
+
+/// Return the connection in `pool` with the fewest requests in progress.
+///
+/// Return NULL if `pool` is NULL.
+///
+/// # Ownership
+///
+/// The resulting connection belongs to the pool:
+/// it lives for no longer than the underlying `ArtiRpcConnPool` object,
+/// and it must not be passed to `arti_rpc_conn_free`.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn arti_rpc_conn_pool_get(
+    pool: *const ArtiRpcConnPool,
+) -> *const ArtiRpcConn {
+    ffi_body_raw! {
+        {
+            let pool: Option<&ArtiRpcConnPool> [in_ptr_opt];
+        } in {
+            pool.map(|pool| Arc::as_ptr(pool.get()))
+                .unwrap_or(std::ptr::null())
+            // Safety: returned pointer is null, or semantically borrowed from `pool`.
+        }
+    }
+}
+
+/// Close and free a pool of Arti RPC connections.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn arti_rpc_conn_pool_free(pool: *mut ArtiRpcConnPool) {
+    ffi_body_raw!(
+        {
+            let pool: Option<Box<ArtiRpcConnPool>> [in_ptr_consume_opt];
+        } in {
+            drop(pool);
+            // Safety: Return value is (); trivially safe.
+            ()
+
+        }
+    );
+}
+
 /// Try to open an anonymized data stream over Arti.
 ///
 /// Use the proxy information associated with `rpc_conn` to make the stream,
@@ -6,6 +6,7 @@ use std::ffi::{c_char, c_int, CStr};
 use std::fmt::Display;
 use std::io::Error as IoError;
 use std::panic::{catch_unwind, UnwindSafe};
+use std::sync::Arc;
 
 use crate::conn::ErrorResponse;
 use crate::util::Utf8CString;
@@ -156,9 +157,49 @@ pub(crate) enum FfiStatus {
     /// but that may change in the future.)
     [c"Not authenticated"]
     NotAuthenticated = 12,
+
+    /// The peer reports that one of our requests was cancelled before it could finish.
+    ///
+    /// (This error was sent by the peer, in response to one of our requests;
+    /// typically, because we asked for it to be cancelled with `arti_rpc_handle_cancel`.
+    /// No further responses to that request will be received or accepted.)
+    [c"Request was cancelled"]
+    RequestCancelled = 13,
+
+    /// We gave up waiting for the response to one of our requests,
+    /// because it did not arrive before the request's timeout.
+    ///
+    /// (We also asked the peer to cancel the request, but we did not wait to learn
+    /// whether it did.  See `arti_rpc_conn_set_default_timeout`.)
+    [c"Request timed out"]
+    TimedOut = 14,
 }
 }
 
+impl FfiStatus {
+    /// Return advice about what the user might do about an error with this status,
+    /// if we have any.
+    ///
+    /// This is only used for errors generated by the library;
+    /// for errors from our peer, we use the advice that Arti sent.
+    fn remediation(self) -> Option<tor_error::Remediation> {
+        use tor_error::Remediation as R;
+        use FfiStatus as S;
+        match self {
+            S::ConnectIo | S::BadAuth | S::NotSupported => Some(R::FixConfig),
+            S::Shutdown | S::TimedOut => Some(R::RetryLater),
+            S::PeerProtocolViolation | S::Internal | S::InvalidInput => Some(R::ReportBug),
+            S::Success
+            | S::RequestFailed
+            | S::RequestCompleted
+            | S::ProxyIo
+            | S::ProxyStreamFailed
+            | S::NotAuthenticated
+            | S::RequestCancelled => None,
+        }
+    }
+}
+
 /// An error as returned by the Arti FFI code.
 #[derive(Debug, Clone)]
 pub struct FfiError {
@@ -172,6 +213,8 @@ pub struct FfiError {
     //
     // (Actually, this should be RawOsError, but that type isn't stable.)
     os_error_code: Option<i32>,
+    /// If present, advice about what the user might do about this error.
+    remediation: Option<tor_error::Remediation>,
 }
 
 impl FfiError {
@@ -227,12 +270,18 @@ impl<T: IntoFfiError> From<T> for FfiError {
             .try_into()
             .expect("Error message had a NUL?");
         let os_error_code = value.os_error_code();
+        let local_remediation = value.status().remediation();
         let error_response = value.into_error_response();
+        let remediation = error_response
+            .as_ref()
+            .and_then(|r| r.decode().remediation())
+            .or(local_remediation);
         Self {
             status,
             message,
             error_response,
             os_error_code,
+            remediation,
         }
     }
 }
@@ -273,6 +322,34 @@ impl IntoFfiError for InvalidInput {
     }
 }
 
+/// Unable to start a thread to wait for the response to an asynchronous request.
+#[derive(Clone, Debug, thiserror::Error)]
+#[error("Unable to start a thread to wait for the response")]
+pub(super) struct SpawnFailed(#[source] pub(super) Arc<IoError>);
+
+impl IntoFfiError for SpawnFailed {
+    fn status(&self) -> FfiStatus {
+        FfiStatus::Internal
+    }
+    fn as_error(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self)
+    }
+}
+
+/// A request's response did not arrive before its timeout.
+#[derive(Clone, Debug, thiserror::Error)]
+#[error("Timed out while waiting for a response")]
+pub(super) struct TimedOut;
+
+impl IntoFfiError for TimedOut {
+    fn status(&self) -> FfiStatus {
+        FfiStatus::TimedOut
+    }
+    fn as_error(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self)
+    }
+}
+
 impl IntoFfiError for crate::ConnectError {
     fn status(&self) -> FfiStatus {
         use crate::ConnectError as E;
@@ -280,9 +357,11 @@ impl IntoFfiError for crate::ConnectError {
         match self {
             E::SchemeNotSupported => F::NotSupported,
             E::CannotConnect(_) => F::ConnectIo,
+            E::TlsHandshakeFailed(_) => F::ConnectIo,
             E::AuthenticationRejected(_) => F::BadAuth,
             E::BadMessage(_) => F::PeerProtocolViolation,
             E::ProtoError(e) => e.status(),
+            E::NoArtiFound(_) => F::ConnectIo,
         }
     }
 
@@ -338,6 +417,10 @@ impl IntoFfiError for crate::ProtoError {
             E::DuplicateWait => F::Internal,
             E::CouldNotEncode(_) => F::Internal,
             E::InternalRequestFailed(_) => F::PeerProtocolViolation,
+            E::CannotDecodeResult(_) => F::PeerProtocolViolation,
+            E::ReconnectFailed(_) => F::Shutdown,
+            E::NotAuthenticated => F::NotAuthenticated,
+            E::ForeignObject(_) => F::InvalidInput,
         }
     }
     fn as_error(&self) -> Option<&(dyn StdError + 'static)> {
@@ -360,7 +443,11 @@ impl IntoFfiError for crate::BuilderError {
 
 impl IntoFfiError for ErrorResponse {
     fn status(&self) -> FfiStatus {
-        FfiStatus::RequestFailed
+        if self.decode().code() == crate::RpcErrorCode::REQUEST_CANCELLED {
+            FfiStatus::RequestCancelled
+        } else {
+            FfiStatus::RequestFailed
+        }
     }
     fn into_error_response(self) -> Option<ErrorResponse> {
         Some(self)
@@ -416,6 +503,51 @@ pub unsafe extern "C" fn arti_rpc_err_os_error_code(err: *const ArtiRpcError) ->
     )
 }
 
+/// Return a short identifier for what the user might do about a given error.
+///
+/// The result is one of `"retry-later"`, `"check-clock"`, `"check-network"`,
+/// `"fix-config"`, or `"report-bug"`;
+/// more values may be added in future versions.
+/// Front-ends can use this to show actionable guidance.
+///
+/// For errors reported by Arti, this is the advice that Arti sent;
+/// for errors generated by this library, it depends on the error's status.
+///
+/// Return NULL if we have no particular advice, or if the input `err` is NULL.
+///
+/// # Correctness requirements
+///
+/// The resulting string pointer is static; it does not need to be freed.
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn arti_rpc_err_remediation(err: *const ArtiRpcError) -> *const c_char {
+    ffi_body_raw!(
+        {
+            let err: Option<&ArtiRpcError> [in_ptr_opt];
+        } in {
+            err.and_then(|e| e.remediation)
+               .and_then(remediation_to_cstr)
+               .map(CStr::as_ptr)
+               .unwrap_or(std::ptr::null())
+            // Safety: returned pointer is null, or a static string.
+            // The caller is not allowed to modify it.
+        }
+    )
+}
+
+/// Helper: Return a static C string for a `Remediation`.
+fn remediation_to_cstr(remediation: tor_error::Remediation) -> Option<&'static CStr> {
+    use tor_error::Remediation as R;
+    match remediation {
+        R::RetryLater => Some(c"retry-later"),
+        R::CheckClock => Some(c"check-clock"),
+        R::CheckNetwork => Some(c"check-network"),
+        R::FixConfig => Some(c"fix-config"),
+        R::ReportBug => Some(c"report-bug"),
+        _ => None,
+    }
+}
+
 /// Return a human-readable error message associated with a given error.
 ///
 /// The format of these messages may change arbitrarily between versions of this library;
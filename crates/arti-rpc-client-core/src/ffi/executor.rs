@@ -0,0 +1,200 @@
+//! A pool of threads for running blocking work on behalf of asynchronous FFI functions.
+//!
+//! Functions like `arti_rpc_conn_execute_async` must return right away,
+//! but waiting for an RPC response blocks.
+//! So they hand that waiting off to the [`Executor`],
+//! which runs it on a thread of its own.
+//!
+//! The pool starts out empty.
+//! A new thread is started whenever work arrives and every existing thread is busy
+//! (up to [`MAX_THREADS`]; beyond that, work waits in a queue),
+//! and threads exit after they have been idle for [`IDLE_TIMEOUT`].
+
+use std::collections::VecDeque;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+/// The largest number of threads the executor will run at once.
+///
+/// Each pending request occupies a thread while we wait for its response,
+/// so this is also the largest number of requests we wait for at once.
+const MAX_THREADS: usize = 16;
+
+/// How long a thread waits for more work before exiting.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A unit of work for the executor.
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A pool of threads that run [`Job`]s.
+#[derive(Default)]
+pub(super) struct Executor {
+    /// The state of the pool.
+    state: Mutex<State>,
+    /// Signalled when a job is added to the queue.
+    work_available: Condvar,
+}
+
+/// The mutable state of an [`Executor`].
+#[derive(Default)]
+struct State {
+    /// Jobs that no thread has started yet.
+    queue: VecDeque<Job>,
+    /// The number of threads currently running.
+    threads: usize,
+    /// The number of threads currently waiting for a job.
+    idle: usize,
+}
+
+impl Executor {
+    /// Return the executor shared by every FFI function.
+    pub(super) fn global() -> &'static Executor {
+        /// The global executor.
+        static EXECUTOR: OnceLock<Executor> = OnceLock::new();
+        EXECUTOR.get_or_init(Executor::default)
+    }
+
+    /// Arrange for `job` to be run on one of the executor's threads.
+    ///
+    /// Returns an error if the job could not be queued because we couldn't start a thread,
+    /// in which case `job` is dropped without being run.
+    pub(super) fn spawn(&'static self, job: impl FnOnce() + Send + 'static) -> std::io::Result<()> {
+        let mut state = self.state.lock().expect("poisoned lock");
+
+        // An idle thread only stops counting as idle once it wakes up and takes a job,
+        // so some of the idle threads may already be spoken for by jobs in the queue.
+        // We need a new thread unless there's an idle thread left over for this job.
+        if state.queue.len() >= state.idle && state.threads < MAX_THREADS {
+            thread::Builder::new()
+                .name("arti-rpc-executor".into())
+                .spawn(move || self.run_worker())?;
+            state.threads += 1;
+        }
+
+        state.queue.push_back(Box::new(job));
+        drop(state);
+        self.work_available.notify_one();
+        Ok(())
+    }
+
+    /// Run jobs from the queue, until we have been idle for [`IDLE_TIMEOUT`].
+    fn run_worker(&self) {
+        let mut state = self.state.lock().expect("poisoned lock");
+        loop {
+            if let Some(job) = state.queue.pop_front() {
+                drop(state);
+                // A panicking job mustn't take the thread (and our count of threads) with it.
+                // Our own jobs catch their panics anyway: this is just a backstop.
+                let _ = catch_unwind(AssertUnwindSafe(job));
+                state = self.state.lock().expect("poisoned lock");
+                continue;
+            }
+
+            state.idle += 1;
+            let (new_state, timeout) = self
+                .work_available
+                .wait_timeout(state, IDLE_TIMEOUT)
+                .expect("poisoned lock");
+            state = new_state;
+            state.idle -= 1;
+
+            if timeout.timed_out() && state.queue.is_empty() {
+                state.threads -= 1;
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn runs_jobs() {
+        let executor: &'static Executor = Box::leak(Box::default());
+        let (tx, rx) = mpsc::channel();
+
+        // More jobs than threads, all blocked until we say so.
+        let (go_tx, go_rx) = mpsc::channel::<()>();
+        let go_rx = std::sync::Arc::new(Mutex::new(go_rx));
+        let n_jobs = MAX_THREADS * 2;
+        for i in 0..n_jobs {
+            let tx = tx.clone();
+            let go_rx = go_rx.clone();
+            executor
+                .spawn(move || {
+                    go_rx.lock().unwrap().recv().unwrap();
+                    tx.send(i).unwrap();
+                })
+                .unwrap();
+        }
+        assert!(executor.state.lock().unwrap().threads <= MAX_THREADS);
+
+        for _ in 0..n_jobs {
+            go_tx.send(()).unwrap();
+        }
+        let mut done: Vec<usize> = rx.iter().take(n_jobs).collect();
+        done.sort_unstable();
+        assert_eq!(done, (0..n_jobs).collect::<Vec<_>>());
+
+        // A panicking job doesn't stop the executor.
+        executor.spawn(|| panic!("oops")).unwrap();
+        executor.spawn(move || tx.send(n_jobs).unwrap()).unwrap();
+        assert_eq!(rx.recv().unwrap(), n_jobs);
+    }
+
+    #[test]
+    fn one_idle_thread_two_jobs() {
+        let executor: &'static Executor = Box::leak(Box::default());
+
+        // Get a single thread, and wait for it to become idle.
+        let (tx, rx) = mpsc::channel();
+        executor.spawn(move || tx.send(()).unwrap()).unwrap();
+        rx.recv().unwrap();
+        while executor.state.lock().unwrap().idle == 0 {
+            thread::yield_now();
+        }
+        assert_eq!(executor.state.lock().unwrap().threads, 1);
+
+        // Two jobs that block until we say so, spawned back to back:
+        // both must start, so the second must not wait behind the first.
+        let (started_tx, started_rx) = mpsc::channel();
+        let (go_tx, go_rx) = mpsc::channel::<()>();
+        let go_rx = std::sync::Arc::new(Mutex::new(go_rx));
+        for _ in 0..2 {
+            let started_tx = started_tx.clone();
+            let go_rx = go_rx.clone();
+            executor
+                .spawn(move || {
+                    started_tx.send(()).unwrap();
+                    let _ = go_rx.lock().unwrap().recv_timeout(Duration::from_secs(60));
+                })
+                .unwrap();
+        }
+        for _ in 0..2 {
+            started_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        }
+        assert_eq!(executor.state.lock().unwrap().threads, 2);
+
+        go_tx.send(()).unwrap();
+        go_tx.send(()).unwrap();
+    }
+}
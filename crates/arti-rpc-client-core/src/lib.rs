@@ -44,12 +44,21 @@
 #![deny(unsafe_op_in_unsafe_fn)]
 
 mod conn;
+pub mod discovery;
 #[cfg(feature = "ffi")]
 pub mod ffi;
 pub mod llconn;
+pub mod methods;
 mod msgs;
 #[macro_use]
 mod util;
 
-pub use conn::{BuilderError, ConnectError, ProtoError, RpcConn, RpcConnBuilder, StreamError};
-pub use msgs::{request::InvalidRequestError, response::RpcError, AnyRequestId, ObjectId};
+pub use conn::{
+    BuilderError, ConnectError, ProtoError, ReconnectPolicy, Reconnected, RpcConn, RpcConnBuilder,
+    RpcConnPool, RpcSession, ShutdownError, StreamError,
+};
+pub use msgs::{
+    request::InvalidRequestError,
+    response::{RpcError, RpcErrorCode},
+    AnyRequestId, ObjectId,
+};
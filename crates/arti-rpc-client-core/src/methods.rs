@@ -0,0 +1,720 @@
+//! Typed requests for Arti's stable RPC methods.
+//!
+//! Instead of assembling a JSON request by hand, and picking apart the JSON reply,
+//! you can build a [`Request`] from one of the method types in this module,
+//! and send it with [`RpcConn::execute_typed`](crate::RpcConn::execute_typed).
+//! The parameters are encoded for you, and the result is decoded as the method's
+//! [`Output`](Method::Output) type.
+//!
+//! ```no_run
+//! # fn demo(conn: &arti_rpc_client_core::RpcConn) -> Result<(), Box<dyn std::error::Error>> {
+//! use arti_rpc_client_core::methods::{GetClient, GetClientStatus, Request};
+//!
+//! let session = conn.session().expect("not authenticated").clone();
+//! // Sending the request can fail; so can the method itself.
+//! let client = match conn.execute_typed(&Request::new(session, GetClient::new()))? {
+//!     Ok(client) => client,
+//!     Err(e) => panic!("Arti said: {}", e.message()),
+//! };
+//! let status = conn.execute_typed(&Request::new(client.into(), GetClientStatus::new()))?;
+//! if let Ok(status) = status {
+//!     println!("Ready: {}", status.ready());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Methods that are not listed here can still be invoked with
+//! [`RpcConn::execute`](crate::RpcConn::execute),
+//! or by implementing [`Method`] for a type of your own.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::{IpAddr, SocketAddr};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{conn::ProtoError, msgs::request, ObjectId};
+
+/// An RPC method, along with its parameters.
+///
+/// The `Serialize` implementation must produce a JSON object:
+/// it becomes the `params` field of the request.
+pub trait Method: Serialize {
+    /// The name of this method, such as `arti:get_client`.
+    const NAME: &'static str;
+
+    /// The type of the `result` field in a successful reply to this method.
+    type Output: DeserializeOwned;
+}
+
+/// A request to invoke the method `M` on some object.
+#[derive(Clone, Debug)]
+pub struct Request<M> {
+    /// The object to which this request is addressed.
+    obj: ObjectId,
+    /// The method to invoke, with its parameters.
+    method: M,
+}
+
+impl<M: Method> Request<M> {
+    /// Construct a new request to invoke `method` on `obj`.
+    pub fn new(obj: ObjectId, method: M) -> Self {
+        Self { obj, method }
+    }
+
+    /// Return the object to which this request is addressed.
+    pub fn obj(&self) -> &ObjectId {
+        &self.obj
+    }
+
+    /// Return the method that this request invokes.
+    pub fn method(&self) -> &M {
+        &self.method
+    }
+
+    /// Encode this request as a JSON string, without an `id` field.
+    pub(crate) fn encode(&self) -> Result<String, ProtoError> {
+        request::Request::new(self.obj.clone(), M::NAME, &self.method).encode()
+    }
+}
+
+/// Return the default client for a session.
+///
+/// Arti replies with a new ID for the session's `TorClient`.
+#[derive(Serialize, Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct GetClient {}
+
+impl GetClient {
+    /// Return a new `GetClient` method.
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Method for GetClient {
+    const NAME: &'static str = "arti:get_client";
+    type Output = SingleIdResponse;
+}
+
+/// Create a new client that is isolated from the client to which the request is addressed.
+///
+/// Streams made with the new client never share circuits
+/// with streams made with any other client.
+#[derive(Serialize, Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct NewIsolatedClient {}
+
+impl NewIsolatedClient {
+    /// Return a new `NewIsolatedClient` method.
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Method for NewIsolatedClient {
+    const NAME: &'static str = "arti:new_isolated_client";
+    type Output = SingleIdResponse;
+}
+
+/// Release the object to which the request is addressed.
+///
+/// After this succeeds, the object's ID is no longer valid.
+#[derive(Serialize, Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct Release {}
+
+impl Release {
+    /// Return a new `Release` method.
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Method for Release {
+    const NAME: &'static str = "rpc:release";
+    type Output = Nil;
+}
+
+/// Return the current bootstrap and health information for a client.
+#[derive(Serialize, Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct GetClientStatus {}
+
+impl GetClientStatus {
+    /// Return a new `GetClientStatus` method.
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Method for GetClientStatus {
+    const NAME: &'static str = "arti:get_client_status";
+    type Output = ClientStatus;
+}
+
+/// Return the problems that a client has noticed, and that haven't gone away yet.
+#[derive(Serialize, Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct GetHealthWarnings {}
+
+impl GetHealthWarnings {
+    /// Return a new `GetHealthWarnings` method.
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Method for GetHealthWarnings {
+    const NAME: &'static str = "arti:get_health_warnings";
+    type Output = HealthWarnings;
+}
+
+/// Return the addresses that relays have told a client they see its connections coming from.
+#[derive(Serialize, Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct GetExternalAddrs {}
+
+impl GetExternalAddrs {
+    /// Return a new `GetExternalAddrs` method.
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Method for GetExternalAddrs {
+    const NAME: &'static str = "arti:get_external_addrs";
+    type Output = ExternalAddrs;
+}
+
+/// Return the proxies that an RPC application should use to reach the Tor network.
+///
+/// [`RpcConn::proxy_info`](crate::RpcConn::proxy_info) sends this request for you.
+#[derive(Serialize, Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct GetRpcProxyInfo {}
+
+impl GetRpcProxyInfo {
+    /// Return a new `GetRpcProxyInfo` method.
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Method for GetRpcProxyInfo {
+    const NAME: &'static str = "arti:get_rpc_proxy_info";
+    type Output = ProxyInfo;
+}
+
+/// Return a description of every RPC method that Arti recognizes,
+/// and of which kinds of object delegate to which others.
+///
+/// [`RpcConn::method_table`](crate::RpcConn::method_table) sends this request
+/// for you, and remembers the answer.
+///
+/// **This method is experimental**: Arti may change or remove it.
+#[derive(Serialize, Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct ListAllRpcMethods {}
+
+impl ListAllRpcMethods {
+    /// Return a new `ListAllRpcMethods` method.
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Method for ListAllRpcMethods {
+    const NAME: &'static str = "arti:x_list_all_rpc_methods";
+    type Output = MethodTable;
+}
+
+/// A result that carries no information.
+#[derive(Deserialize, Clone, Debug)]
+#[non_exhaustive]
+pub struct Nil {}
+
+/// A result that holds the ID of a single object.
+#[derive(Deserialize, Clone, Debug)]
+pub struct SingleIdResponse {
+    /// The ID of the object.
+    id: ObjectId,
+}
+
+impl SingleIdResponse {
+    /// Return the ID of the object.
+    pub fn id(&self) -> &ObjectId {
+        &self.id
+    }
+}
+
+impl From<SingleIdResponse> for ObjectId {
+    fn from(r: SingleIdResponse) -> ObjectId {
+        r.id
+    }
+}
+
+/// Bootstrap and health information for a client.
+#[derive(Deserialize, Clone, Debug)]
+pub struct ClientStatus {
+    /// True if the client is ready for traffic.
+    ready: bool,
+    /// A rough estimate of how close the client is to being ready for traffic.
+    fraction: f32,
+    /// A description of what may be stopping the client from using the Tor network.
+    blocked: Option<String>,
+}
+
+impl ClientStatus {
+    /// Return true if the client is ready for traffic.
+    pub fn ready(&self) -> bool {
+        self.ready
+    }
+
+    /// Return a rough estimate, between 0 and 1, of how close the client is
+    /// to being ready for traffic.
+    ///
+    /// This is not guaranteed to increase monotonically.
+    pub fn fraction(&self) -> f32 {
+        self.fraction
+    }
+
+    /// If the client seems to be stuck, return a description of
+    /// what may be stopping it from using the Tor network.
+    pub fn blocked(&self) -> Option<&str> {
+        self.blocked.as_deref()
+    }
+}
+
+/// The problems that a client has noticed.
+#[derive(Deserialize, Clone, Debug)]
+pub struct HealthWarnings {
+    /// The active warnings, ordered by identifier.
+    warnings: Vec<HealthWarning>,
+}
+
+impl HealthWarnings {
+    /// Return the active warnings, ordered by identifier.
+    pub fn warnings(&self) -> &[HealthWarning] {
+        &self.warnings
+    }
+}
+
+/// A single problem that a client has noticed.
+#[derive(Deserialize, Clone, Debug)]
+pub struct HealthWarning {
+    /// The stable identifier for this kind of problem.
+    id: String,
+    /// A human-readable description of the problem.
+    message: String,
+    /// When the problem was first noticed, in RFC 3339 format.
+    first_seen: String,
+    /// When the problem was most recently noticed, in RFC 3339 format.
+    last_seen: String,
+    /// How many times the problem has been noticed.
+    count: u64,
+}
+
+impl HealthWarning {
+    /// Return the stable identifier for this kind of problem,
+    /// such as `clock-skew` or `keystore-locked`.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Return a human-readable description of the problem.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Return when the problem was first noticed, in RFC 3339 format.
+    pub fn first_seen(&self) -> &str {
+        &self.first_seen
+    }
+
+    /// Return when the problem was most recently noticed, in RFC 3339 format.
+    pub fn last_seen(&self) -> &str {
+        &self.last_seen
+    }
+
+    /// Return how many times the problem has been noticed.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+/// The addresses that relays have told a client they see its connections coming from.
+#[derive(Deserialize, Clone, Debug)]
+pub struct ExternalAddrs {
+    /// The reported addresses, most recently reported first.
+    addrs: Vec<ExternalAddr>,
+}
+
+impl ExternalAddrs {
+    /// Return the reported addresses, most recently reported first.
+    pub fn addrs(&self) -> &[ExternalAddr] {
+        &self.addrs
+    }
+}
+
+/// A single external address, as reported by relays.
+#[derive(Deserialize, Clone, Debug)]
+pub struct ExternalAddr {
+    /// The address that the relays reported.
+    addr: IpAddr,
+    /// How many channels have reported this address.
+    count: usize,
+    /// When the address was most recently reported, in RFC 3339 format.
+    last_reported: String,
+    /// The Ed25519 identity of the relay that most recently reported this address.
+    last_reporter: Option<String>,
+}
+
+impl ExternalAddr {
+    /// Return the address that the relays reported.
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// Return how many channels have reported this address.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Return when the address was most recently reported, in RFC 3339 format.
+    pub fn last_reported(&self) -> &str {
+        &self.last_reported
+    }
+
+    /// Return the Ed25519 identity of the relay that most recently reported
+    /// this address, if it is known.
+    pub fn last_reporter(&self) -> Option<&str> {
+        self.last_reporter.as_deref()
+    }
+}
+
+/// The proxies that Arti offers to RPC applications.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProxyInfo {
+    /// The proxies, in Arti's order of preference.
+    proxies: Vec<Proxy>,
+}
+
+impl ProxyInfo {
+    /// Return the proxies, in Arti's order of preference.
+    pub fn proxies(&self) -> &[Proxy] {
+        &self.proxies
+    }
+
+    /// Return the address of the first SOCKS5 proxy, if there is one.
+    pub fn socks_addr(&self) -> Option<SocketAddr> {
+        self.proxies.iter().find_map(|p| match p.listener {
+            ProxyListener::Socks5 { tcp_address } => tcp_address,
+            _ => None,
+        })
+    }
+
+    /// Return the addresses of the DNS resolvers.
+    pub fn dns_addrs(&self) -> impl Iterator<Item = SocketAddr> + '_ {
+        self.proxies.iter().filter_map(|p| match p.listener {
+            ProxyListener::Dns { udp_address } => udp_address,
+            _ => None,
+        })
+    }
+}
+
+/// A single proxy that Arti offers.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Proxy {
+    /// Where the proxy is listening, and what protocol it speaks.
+    listener: ProxyListener,
+}
+
+impl Proxy {
+    /// Return where the proxy is listening, and what protocol it speaks.
+    pub fn listener(&self) -> &ProxyListener {
+        &self.listener
+    }
+}
+
+/// Where a proxy is listening, and what protocol it speaks.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ProxyListener {
+    /// A SOCKS5 proxy.
+    #[serde(rename = "socks5")]
+    Socks5 {
+        /// The TCP address at which the proxy is listening, if it has one.
+        tcp_address: Option<SocketAddr>,
+    },
+    /// A DNS resolver.
+    #[serde(rename = "dns")]
+    Dns {
+        /// The UDP address at which the resolver is listening, if it has one.
+        udp_address: Option<SocketAddr>,
+    },
+    /// Some kind of proxy that we don't recognize.
+    ///
+    /// (It may have been added in a newer version of Arti.)
+    #[serde(untagged)]
+    Unrecognized {},
+}
+
+/// A description of the RPC methods that Arti recognizes,
+/// and of the objects that they apply to.
+///
+/// Objects are described by the names of the Rust types that implement them in Arti.
+/// These are good for finding the relevant part of Arti's documentation,
+/// and for comparing with one another,
+/// but they can change from one version of Arti to the next.
+///
+/// Not every method listed here is necessarily usable:
+/// depending on the session's access level,
+/// we might not be able to reach any object that it applies to.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MethodTable {
+    /// A map from method name (such as `arti:get_client`) to a description of that method.
+    methods: BTreeMap<String, MethodDescription>,
+    /// A map from an object type to the object types that it can delegate to.
+    #[serde(default)]
+    delegations: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl MethodTable {
+    /// Return every recognized method, indexed by name.
+    pub fn methods(&self) -> &BTreeMap<String, MethodDescription> {
+        &self.methods
+    }
+
+    /// Return a description of the method called `name`, if Arti recognizes it.
+    pub fn method(&self, name: &str) -> Option<&MethodDescription> {
+        self.methods.get(name)
+    }
+
+    /// Return the object types that each object type can delegate to.
+    ///
+    /// A method that doesn't apply to an object directly
+    /// may still be invoked on it, if it applies to an object that it delegates to.
+    pub fn delegations(&self) -> &BTreeMap<String, BTreeSet<String>> {
+        &self.delegations
+    }
+
+    /// Return true if the method called `name` can be invoked on an object
+    /// of type `object_type`, either directly or by delegation.
+    pub fn can_invoke(&self, name: &str, object_type: &str) -> bool {
+        let Some(method) = self.method(name) else {
+            return false;
+        };
+        let mut seen = BTreeSet::new();
+        let mut pending = vec![object_type];
+        while let Some(ty) = pending.pop() {
+            if !seen.insert(ty) {
+                continue;
+            }
+            if method.applies_to_object_types.contains(ty) {
+                return true;
+            }
+            pending.extend(
+                self.delegations
+                    .get(ty)
+                    .into_iter()
+                    .flatten()
+                    .map(String::as_str),
+            );
+        }
+        false
+    }
+}
+
+/// A description of a single RPC method.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MethodDescription {
+    /// The type whose fields are the method's parameters.
+    method_type: String,
+    /// The type that the method returns on success.
+    output_type: String,
+    /// The type of the method's incremental updates, if it sends any.
+    update_type: Option<String>,
+    /// The types of object that the method applies to directly.
+    applies_to_object_types: BTreeSet<String>,
+    /// The version of Arti that introduced the method, if Arti recorded it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    introduced_in: Option<String>,
+    /// The version of Arti that deprecated the method, if it is deprecated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    deprecated_since: Option<String>,
+    /// What to use instead of the method, if it is deprecated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    deprecation_note: Option<String>,
+}
+
+impl MethodDescription {
+    /// Return the type whose fields are the method's parameters.
+    pub fn method_type(&self) -> &str {
+        &self.method_type
+    }
+
+    /// Return the type that the method returns on success.
+    pub fn output_type(&self) -> &str {
+        &self.output_type
+    }
+
+    /// Return the type of the method's incremental updates, if it sends any.
+    pub fn update_type(&self) -> Option<&str> {
+        self.update_type.as_deref()
+    }
+
+    /// Return the types of object that the method applies to directly,
+    /// without delegation.
+    pub fn applies_to_object_types(&self) -> &BTreeSet<String> {
+        &self.applies_to_object_types
+    }
+
+    /// Return the version of Arti that introduced the method, if Arti recorded it.
+    pub fn introduced_in(&self) -> Option<&str> {
+        self.introduced_in.as_deref()
+    }
+
+    /// Return the version of Arti that deprecated the method, if it is deprecated.
+    ///
+    /// Deprecated methods still work, but may be removed in a future version.
+    pub fn deprecated_since(&self) -> Option<&str> {
+        self.deprecated_since.as_deref()
+    }
+
+    /// Return what to use instead of the method, if it is deprecated.
+    pub fn deprecation_note(&self) -> Option<&str> {
+        self.deprecation_note.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+
+    use super::*;
+
+    #[test]
+    fn encode() {
+        let obj = ObjectId::try_from("sess-1".to_string()).unwrap();
+        let req = Request::new(obj, GetClient::new());
+        let json: serde_json::Value = serde_json::from_str(&req.encode().unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "obj": "sess-1",
+                "method": "arti:get_client",
+                "params": {},
+            })
+        );
+    }
+
+    #[test]
+    fn decode() {
+        let status: ClientStatus = serde_json::from_str(
+            r#"{"ready": false, "fraction": 0.5, "blocked": "clock skew", "extra": 7}"#,
+        )
+        .unwrap();
+        assert!(!status.ready());
+        assert_eq!(status.fraction(), 0.5);
+        assert_eq!(status.blocked(), Some("clock skew"));
+
+        let addrs: ExternalAddrs = serde_json::from_str(
+            r#"{"addrs": [{"addr": "192.0.2.7", "count": 3,
+                "last_reported": "2024-10-01T00:00:00Z", "last_reporter": null}]}"#,
+        )
+        .unwrap();
+        assert_eq!(addrs.addrs().len(), 1);
+        assert_eq!(
+            addrs.addrs()[0].addr(),
+            "192.0.2.7".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(addrs.addrs()[0].count(), 3);
+        assert_eq!(addrs.addrs()[0].last_reporter(), None);
+
+        let id: SingleIdResponse = serde_json::from_str(r#"{"id": "client-3"}"#).unwrap();
+        assert_eq!(ObjectId::from(id).as_ref(), "client-3");
+    }
+
+    #[test]
+    fn proxy_info() {
+        let info: ProxyInfo = serde_json::from_str(
+            r#"{
+                "proxies": [
+                    {"listener": {"carrier-pigeon": {"loft": 7}}},
+                    {"listener": {"dns": {"udp_address": "127.0.0.1:9053"}}},
+                    {"listener": {"socks5": {"tcp_address": "127.0.0.1:9150"}}},
+                    {"listener": {"socks5": {"tcp_address": "[::1]:9150"}}}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(info.proxies().len(), 4);
+        assert_eq!(
+            info.proxies()[0].listener(),
+            &ProxyListener::Unrecognized {}
+        );
+        assert_eq!(info.socks_addr(), Some("127.0.0.1:9150".parse().unwrap()));
+        assert_eq!(
+            info.dns_addrs().collect::<Vec<_>>(),
+            vec!["127.0.0.1:9053".parse::<SocketAddr>().unwrap()]
+        );
+    }
+
+    #[test]
+    fn method_table() {
+        let table: MethodTable = serde_json::from_str(
+            r#"{
+                "methods": {
+                    "arti:get_client_status": {
+                        "method_type": "arti_client::rpc::GetClientStatus",
+                        "output_type": "arti_client::rpc::ClientStatusInfo",
+                        "update_type": null,
+                        "applies_to_object_types": ["arti_client::TorClient<R>"]
+                    },
+                    "rpc:release": {
+                        "method_type": "tor_rpcbase::builtin::RpcRelease",
+                        "output_type": "tor_rpcbase::Nil",
+                        "update_type": null,
+                        "applies_to_object_types": ["arti_rpcserver::session::RpcSession"],
+                        "deprecated_since": "1.4.0",
+                        "deprecation_note": "Use something else."
+                    }
+                },
+                "delegations": {
+                    "arti_rpcserver::session::RpcSession": ["arti_client::TorClient<R>"]
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(table.methods().len(), 2);
+        let status = table.method("arti:get_client_status").unwrap();
+        assert_eq!(status.output_type(), "arti_client::rpc::ClientStatusInfo");
+        assert_eq!(status.update_type(), None);
+        assert_eq!(status.deprecated_since(), None);
+        let release = table.method("rpc:release").unwrap();
+        assert_eq!(release.deprecated_since(), Some("1.4.0"));
+        assert_eq!(release.deprecation_note(), Some("Use something else."));
+        assert!(table.method("arti:nonesuch").is_none());
+
+        let session = "arti_rpcserver::session::RpcSession";
+        let client = "arti_client::TorClient<R>";
+        assert!(table.can_invoke("arti:get_client_status", client));
+        assert!(table.can_invoke("arti:get_client_status", session));
+        assert!(table.can_invoke("rpc:release", session));
+        assert!(!table.can_invoke("rpc:release", client));
+        assert!(!table.can_invoke("arti:nonesuch", session));
+    }
+}
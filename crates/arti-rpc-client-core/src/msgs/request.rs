@@ -76,7 +76,7 @@ impl<T: Serialize> Request<T> {
 /// We use this type to validate outbound requests from the application.
 #[derive(Deserialize, Debug)]
 // Don't implement Serialize here; this is not for generating requests!
-#[allow(dead_code)] // The fields here are only used for validating serde objects.
+#[allow(dead_code)] // Some of the fields here are only used for validating serde objects.
 struct ParsedRequestFields {
     /// The identifier for this request.
     ///
@@ -103,6 +103,10 @@ pub(crate) struct ValidatedRequest {
     msg: String,
     /// The ID for this request.
     id: AnyRequestId,
+    /// The object to which this request is addressed.
+    obj: ObjectId,
+    /// The name of the method that this request invokes.
+    method: String,
 }
 
 impl ValidatedRequest {
@@ -111,6 +115,28 @@ impl ValidatedRequest {
         &self.id
     }
 
+    /// Return the object to which this request is addressed.
+    pub(crate) fn obj(&self) -> &ObjectId {
+        &self.obj
+    }
+
+    /// Return the name of the method that this request invokes.
+    pub(crate) fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// Return a copy of this request, addressed to `obj` instead.
+    ///
+    /// All other fields are unchanged.
+    pub(crate) fn redirected(&self, obj: &ObjectId) -> Result<Self, InvalidRequestError> {
+        let mut value: serde_json::Value = serde_json::from_str(&self.msg)
+            .map_err(|e| InvalidRequestError::InvalidJson(Arc::new(e)))?;
+        if let Some(fields) = value.as_object_mut() {
+            fields.insert("obj".into(), serde_json::Value::String(obj.as_ref().into()));
+        }
+        Self::from_json_value(value)
+    }
+
     /// Try to construct a validated request from a `serde_json::Value`.
     fn from_json_value(val: serde_json::Value) -> Result<Self, InvalidRequestError> {
         let mut msg = serde_json::to_string(&val)
@@ -120,9 +146,16 @@ impl ValidatedRequest {
 
         let req: ParsedRequestFields = serde_json::from_value(val)
             .map_err(|e| InvalidRequestError::InvalidFormat(Arc::new(e)))?;
-        let id = req.id;
+        let ParsedRequestFields {
+            id, obj, method, ..
+        } = req;
 
-        Ok(ValidatedRequest { id, msg })
+        Ok(ValidatedRequest {
+            id,
+            obj,
+            method,
+            msg,
+        })
     }
 
     /// Try to construct a validated request using `s`.
@@ -303,4 +336,18 @@ mod test {
             }"#;
         assert_same_json!(validated.as_ref(), expected_with_id);
     }
+
+    #[test]
+    fn redirect_requests() {
+        let validated = ValidatedRequest::from_string_strict(REQ3).unwrap();
+        assert_eq!(validated.obj().as_ref(), "hi");
+        assert_eq!(validated.method(), "twiddle");
+
+        let there = ObjectId::try_from("there".to_string()).unwrap();
+        let redirected = validated.redirected(&there).unwrap();
+        assert_eq!(redirected.obj(), &there);
+        assert_eq!(redirected.id(), validated.id());
+        let expected = r#"{"id":"fred", "obj": "there", "method":"twiddle", "params":{},"unrecognized":"waffles"}"#;
+        assert_same_json!(redirected.as_ref(), expected);
+    }
 }
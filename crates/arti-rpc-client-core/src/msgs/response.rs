@@ -4,7 +4,7 @@ use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 
-use super::{AnyRequestId, JsonAnyObj};
+use super::{AnyRequestId, JsonAnyObj, ObjectId};
 use crate::{
     conn::ErrorResponse,
     util::{define_from_for_arc, Utf8CString},
@@ -102,6 +102,31 @@ impl ValidatedResponse {
     pub(crate) fn id(&self) -> &AnyRequestId {
         &self.meta.id
     }
+
+    /// If this is a successful response whose result names a single object,
+    /// as in `{"result": {"id": "..."}}`, return the ID of that object.
+    ///
+    /// (This is how Arti's methods conventionally return a new object handle.)
+    pub(crate) fn result_object_id(&self) -> Option<ObjectId> {
+        /// A response with a result that has an `id` member.
+        #[derive(Deserialize)]
+        struct WithResult {
+            /// The result of the request.
+            result: ResultWithId,
+        }
+        /// A result that has an `id` member.
+        #[derive(Deserialize)]
+        struct ResultWithId {
+            /// The ID of the object named in the result.
+            id: ObjectId,
+        }
+
+        if !matches!(self.meta.kind, ResponseKind::Success) {
+            return None;
+        }
+        let response: WithResult = serde_json::from_str(self.msg.as_ref()).ok()?;
+        Some(response.result.id)
+    }
 }
 
 /// Metadata extracted from a response while decoding it.
@@ -224,6 +249,12 @@ pub struct RpcError {
     code: RpcErrorCode,
     /// One or more `ErrorKind`s, encoded as strings.
     kinds: Vec<String>,
+    /// Additional information about the error.
+    ///
+    /// This should be a map from namespaced keyword to value,
+    /// but we don't insist on that.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data: Option<serde_json::Value>,
 }
 
 impl RpcError {
@@ -242,6 +273,17 @@ impl RpcError {
     pub fn kinds_iter(&self) -> impl Iterator<Item = &'_ str> {
         self.kinds.iter().map(|s| s.as_ref())
     }
+    /// Return Arti's advice about what the user might do about this error, if any.
+    ///
+    /// Returns `None` if Arti sent no advice, or advice we don't recognize.
+    pub fn remediation(&self) -> Option<tor_error::Remediation> {
+        self.data
+            .as_ref()?
+            .get("arti:remediation")?
+            .as_str()?
+            .parse()
+            .ok()
+    }
 }
 
 caret::caret_int! {
@@ -261,6 +303,10 @@ caret::caret_int! {
         REQUEST_ERROR = 2,
         /// This method exists, but wasn't implemented on this object.
         METHOD_NOT_IMPL = 3,
+        /// This request was cancelled before it could finish.
+        REQUEST_CANCELLED = 4,
+        /// This request listed a required feature that doesn't exist.
+        FEATURE_NOT_PRESENT = 5,
     }
 }
 
@@ -368,6 +414,22 @@ mod test {
         );
     }
 
+    #[test]
+    fn result_object_id() {
+        let validate = |s: &str| UnparsedResponse::new(s.to_string()).try_validate().unwrap();
+
+        let r = validate(r#"{"id":7, "result": {"id": "stream-3", "other": 1}}"#);
+        assert_eq!(r.result_object_id().unwrap().as_ref(), "stream-3");
+
+        for s in [
+            r#"{"id":7, "result": {}}"#,
+            r#"{"id":7, "result": {"id": 3}}"#,
+            r#"{"id":7, "update": {"id": "stream-3"}}"#,
+        ] {
+            assert!(validate(s).result_object_id().is_none(), "{s}");
+        }
+    }
+
     #[test]
     fn bad_json() {
         // we rely on the json parser rejecting some things.
@@ -404,4 +466,28 @@ mod test {
         // we cannot rely on the order of the fields.
         assert_eq!(json_orig, json_reencoded);
     }
+
+    #[test]
+    fn remediation() {
+        let err: RpcError = serde_json::from_str(
+            r#"{"message":"slow", "code":2, "kinds": ["arti:TorNetworkTimeout"],
+                "data": {"arti:remediation": "retry-later"}}"#,
+        )
+        .unwrap();
+        assert_eq!(err.remediation(), Some(tor_error::Remediation::RetryLater));
+
+        for data in [
+            r#""#,
+            r#", "data": {}"#,
+            r#", "data": {"arti:remediation": "pray"}"#,
+            r#", "data": "5:0""#,
+        ] {
+            let err: RpcError = serde_json::from_str(&format!(
+                r#"{{"message":"m", "code":2, "kinds": []{}}}"#,
+                data
+            ))
+            .unwrap();
+            assert_eq!(err.remediation(), None);
+        }
+    }
 }
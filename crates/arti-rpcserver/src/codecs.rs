@@ -5,6 +5,7 @@ use std::marker::PhantomData;
 use asynchronous_codec::JsonCodec;
 use bytes::BytesMut;
 use serde::Serialize;
+use tor_basic_utils::alloc_tag;
 
 use crate::msgs::BoxedResponse;
 use crate::msgs::FlexibleRequest;
@@ -19,6 +20,8 @@ pub(crate) type RequestStream<T> =
 /// object.
 #[derive(Clone)]
 pub(crate) struct JsonLinesEncoder<T> {
+    /// If true, we encode objects as canonical JSON.
+    canonical: bool,
     /// We consume objects of type T.
     _phantom: PhantomData<fn(T) -> ()>,
 }
@@ -26,6 +29,17 @@ pub(crate) struct JsonLinesEncoder<T> {
 impl<T> Default for JsonLinesEncoder<T> {
     fn default() -> Self {
         Self {
+            canonical: false,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> JsonLinesEncoder<T> {
+    /// Return a new encoder that encodes every object as canonical JSON.
+    pub(crate) fn canonical() -> Self {
+        Self {
+            canonical: true,
             _phantom: PhantomData,
         }
     }
@@ -41,7 +55,12 @@ where
 
     fn encode(&mut self, item: Self::Item<'_>, dst: &mut BytesMut) -> Result<(), Self::Error> {
         use std::fmt::Write as _;
-        let j = serde_json::to_string(&item)?;
+        let _tag = alloc_tag::enter(alloc_tag::Subsystem::Rpc);
+        let j = if self.canonical {
+            tor_rpcbase::to_canonical_json(&item)?
+        } else {
+            serde_json::to_string(&item)?
+        };
         // The jsonlines format won't work if serde_json starts adding newlines in the middle.
         debug_assert!(!j.contains('\n'));
         writeln!(dst, "{}", j).expect("write! of string on BytesMut failed");
@@ -119,4 +138,28 @@ mod test {
         // Make sure that the output is what we expected.
         assert_eq!(std::str::from_utf8(&buf).unwrap(), &expect);
     }
+
+    #[async_test]
+    async fn check_canonical_sink() {
+        #[derive(serde::Serialize)]
+        struct Unsorted {
+            b: u8,
+            a: u8,
+        }
+
+        let mut buf = Vec::new();
+        {
+            let mut sink = ResponseSink::new(&mut buf, JsonLinesEncoder::canonical());
+            sink.send(BoxedResponse {
+                id: Some(RequestId::Int(7)),
+                body: ResponseBody::Success(Box::new(Unsorted { b: 2, a: 1 })),
+            })
+            .await
+            .unwrap();
+        }
+        assert_eq!(
+            std::str::from_utf8(&buf).unwrap(),
+            "{\"id\":7,\"result\":{\"a\":1,\"b\":2}}\n"
+        );
+    }
 }
@@ -3,10 +3,13 @@
 pub(crate) mod auth;
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     io::Error as IoError,
     pin::Pin,
-    sync::{Arc, Mutex, RwLock, Weak},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock, Weak,
+    },
 };
 
 use asynchronous_codec::JsonCodecError;
@@ -19,6 +22,7 @@ use futures::{
 use rpc::dispatch::BoxedUpdateSink;
 use serde_json::error::Category as JsonErrorCategory;
 use tor_async_utils::{mpsc_channel_no_memquota, SinkExt as _};
+use tracing::Instrument as _;
 
 use crate::{
     cancel::{Cancel, CancelHandle},
@@ -26,7 +30,7 @@ use crate::{
     globalid::{GlobalId, MacKey},
     msgs::{BoxedResponse, FlexibleRequest, ReqMeta, Request, RequestId, ResponseBody},
     objmap::{GenIdx, ObjMap},
-    RpcMgr,
+    RpcListenerPolicy, RpcMgr,
 };
 
 use tor_rpcbase::templates::*;
@@ -72,12 +76,21 @@ pub struct Connection {
     /// from e.g. a SOCKS session so that clients can attach streams to it.
     connection_id: ConnectionId,
 
+    /// A small number identifying this connection in log messages.
+    ///
+    /// Unlike `connection_id`, this is not secret, and is safe to log.
+    /// It is the `session` field of every request's tracing span.
+    session_id: u64,
+
     /// A `MacKey` used to create `GlobalIds` for the objects whose identifiers
     /// need to exist outside this connection.
     global_id_mac_key: MacKey,
 
     /// A reference to the manager associated with this session.
     mgr: Weak<RpcMgr>,
+
+    /// The rules of the listener that accepted this connection.
+    policy: RpcListenerPolicy,
 }
 
 /// The inner, lock-protected part of an RPC connection.
@@ -99,11 +112,20 @@ struct Inner {
     ///
     /// TODO RPC: Maybe there is an easier way to do this while keeping `context` object-save?
     this_connection: Option<Weak<Connection>>,
+
+    /// If true, the client has asked us to reject deprecated methods.
+    reject_deprecated: bool,
+
+    /// The names of the deprecated methods we've already warned about on this connection.
+    warned_deprecated: HashSet<&'static str>,
 }
 
 /// How many updates can be pending, per connection, before they start to block?
 const UPDATE_CHAN_SIZE: usize = 128;
 
+/// The `session_id` to give the next [`Connection`] we create.
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(1);
+
 /// A type-erased [`FusedStream`] yielding [`Request`]s.
 //
 // (We name this type and [`BoxedResponseSink`] below so as to keep the signature for run_loop
@@ -152,20 +174,49 @@ impl Connection {
         dispatch_table: Arc<RwLock<rpc::DispatchTable>>,
         global_id_mac_key: MacKey,
         mgr: Weak<RpcMgr>,
+        policy: RpcListenerPolicy,
     ) -> Arc<Self> {
         Arc::new_cyclic(|this_connection| Self {
             inner: Mutex::new(Inner {
                 inflight: HashMap::new(),
                 objects: ObjMap::new(),
                 this_connection: Some(Weak::clone(this_connection)),
+                reject_deprecated: false,
+                warned_deprecated: HashSet::new(),
             }),
             dispatch_table,
             connection_id,
+            session_id: NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed),
             global_id_mac_key,
             mgr,
+            policy,
         })
     }
 
+    /// Return the rules of the listener that accepted this connection.
+    pub(crate) fn policy(&self) -> &RpcListenerPolicy {
+        &self.policy
+    }
+
+    /// Encode `local_id` as an `ObjectId` to give to the client.
+    ///
+    /// If `use_global_id` is true, the `ObjectId` will be usable outside of this connection.
+    fn encode_id(&self, local_id: GenIdx, use_global_id: bool) -> rpc::ObjectId {
+        let stable = self.policy.deterministic_output();
+        if use_global_id {
+            let global_id = GlobalId::new(self.connection_id, local_id);
+            if stable {
+                global_id.encode_stable(&self.global_id_mac_key)
+            } else {
+                global_id.encode(&self.global_id_mac_key)
+            }
+        } else if stable {
+            local_id.encode_stable()
+        } else {
+            local_id.encode()
+        }
+    }
+
     /// If possible, convert an `ObjectId` into a `GenIdx` that can be used in
     /// this connection's ObjMap.
     fn id_into_local_idx(&self, id: &rpc::ObjectId) -> Result<GenIdx, rpc::LookupError> {
@@ -237,6 +288,23 @@ impl Connection {
         inner.inflight.remove(id);
     }
 
+    /// Cancel the request `id`, if it is in progress.
+    ///
+    /// Return true if there was such a request.
+    fn cancel_request(&self, id: &RequestId) -> bool {
+        let handle = {
+            let mut inner = self.inner.lock().expect("lock poisoned");
+            inner.inflight.remove(id)
+        };
+        match handle {
+            Some(handle) => {
+                handle.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Register the request `id` as a cancellable request.
     fn register_request(&self, id: RequestId, handle: CancelHandle) {
         let mut inner = self.inner.lock().expect("lock poisoned");
@@ -254,10 +322,12 @@ impl Connection {
         IN: futures::AsyncRead + Send + Sync + Unpin + 'static,
         OUT: futures::AsyncWrite + Send + Sync + Unpin + 'static,
     {
-        let write = Box::pin(asynchronous_codec::FramedWrite::new(
-            output,
-            crate::codecs::JsonLinesEncoder::<BoxedResponse>::default(),
-        ));
+        let encoder = if self.policy.deterministic_output() {
+            crate::codecs::JsonLinesEncoder::<BoxedResponse>::canonical()
+        } else {
+            crate::codecs::JsonLinesEncoder::<BoxedResponse>::default()
+        };
+        let write = Box::pin(asynchronous_codec::FramedWrite::new(output, encoder));
 
         let read = Box::pin(
             asynchronous_codec::FramedRead::new(
@@ -356,7 +426,10 @@ impl Connection {
                             Some(Ok(FlexibleRequest::Valid(req))) => {
                                 // We have a request. Time to launch it!
                                 let tx = tx_response.clone();
-                                let fut = self.run_method_and_deliver_response(tx, req);
+                                let span = self.request_span(&req);
+                                let fut = self
+                                    .run_method_and_deliver_response(tx, req)
+                                    .instrument(span);
                                 finished_requests.push(fut.boxed());
                                 Continue
                             }
@@ -373,6 +446,23 @@ impl Connection {
         }
     }
 
+    /// Return a new tracing span for running `request`.
+    ///
+    /// Everything logged while the request is running happens inside this span,
+    /// so that its messages can be told apart from those of other requests.
+    /// The `method` and `object_type` fields are recorded
+    /// once [`run_method_lowlevel`](Self::run_method_lowlevel) knows them.
+    fn request_span(&self, request: &Request) -> tracing::Span {
+        tracing::info_span!(
+            "rpc_request",
+            session = self.session_id,
+            id = ?request.id,
+            obj = request.obj.as_ref(),
+            method = tracing::field::Empty,
+            object_type = tracing::field::Empty,
+        )
+    }
+
     /// Invoke `request` and send all of its responses to `tx_response`.
     async fn run_method_and_deliver_response(
         self: &Arc<Self>,
@@ -421,7 +511,10 @@ impl Connection {
                 }
                 ResponseBody::Error(Box::new(err))
             }
-            Err(_cancelled) => ResponseBody::Error(Box::new(rpc::RpcError::from(RequestCancelled))),
+            Err(_cancelled) => ResponseBody::Error(Box::new(RpcError::new(
+                RequestCancelled.to_string(),
+                rpc::RpcErrorKind::RequestCancelled,
+            ))),
         };
 
         // Send the response.
@@ -454,7 +547,25 @@ impl Connection {
         method: Box<dyn rpc::DeserMethod>,
         meta: ReqMeta,
     ) -> Result<Box<dyn erased_serde::Serialize + Send + 'static>, rpc::RpcError> {
+        let method = method.upcast_box();
+        let span = tracing::Span::current();
+        if let Some(name) = rpc::method_name(method.as_ref()) {
+            span.record("method", name);
+        }
+        let profile = self.policy.profile();
+        match rpc::method_name(method.as_ref()) {
+            Some(name) if profile.permits(name) => {}
+            name => {
+                return Err(MethodNotPermittedError(name.unwrap_or("(unnamed)").to_owned()).into())
+            }
+        }
+
+        if let Some(name) = rpc::method_name(method.as_ref()) {
+            self.check_deprecation(name, method.as_ref())?;
+        }
+
         let obj = self.lookup_object(&obj_id)?;
+        span.record("object_type", obj.object_type_name());
 
         if !meta.require.is_empty() {
             // TODO RPC: Eventually, we will need a way to tell which "features" are actually
@@ -465,13 +576,42 @@ impl Connection {
 
         let context: Arc<dyn rpc::Context> = self.clone() as Arc<_>;
 
-        let invoke_future =
-            rpc::invoke_rpc_method(context, &obj_id, obj, method.upcast_box(), tx_updates)?;
+        let invoke_future = rpc::invoke_rpc_method(context, &obj_id, obj, method, tx_updates)?;
 
         // Note that we drop the read lock before we await this future!
         invoke_future.await
     }
 
+    /// Reject every later request on this connection that invokes a deprecated method.
+    pub(crate) fn reject_deprecated_methods(&self) {
+        self.inner.lock().expect("lock poisoned").reject_deprecated = true;
+    }
+
+    /// If `method` (named `name`) is deprecated, either reject it,
+    /// or warn about it (once per connection), depending on what the client asked for.
+    fn check_deprecation(
+        &self,
+        name: &'static str,
+        method: &dyn rpc::DynMethod,
+    ) -> Result<(), DeprecatedMethodError> {
+        let Some(version) = rpc::method_version_info(method).filter(|v| v.is_deprecated()) else {
+            return Ok(());
+        };
+        let mut inner = self.inner.lock().expect("lock poisoned");
+        if inner.reject_deprecated {
+            return Err(DeprecatedMethodError { name, version });
+        }
+        if inner.warned_deprecated.insert(name) {
+            tracing::warn!(
+                "RPC client used deprecated method {} (deprecated since Arti {}). {}",
+                name,
+                version.deprecated_since().unwrap_or("(unknown)"),
+                version.deprecation_note().unwrap_or(""),
+            );
+        }
+        Ok(())
+    }
+
     /// Try to get a strong reference to the RpcMgr for this connection, and
     /// return an error if we can't.
     pub(crate) fn mgr(&self) -> Result<Arc<RpcMgr>, MgrDisappearedError> {
@@ -501,6 +641,40 @@ impl From<MissingFeaturesError> for RpcError {
     }
 }
 
+/// An error returned when an RPC request invokes a method that the
+/// connection's capability profile does not allow.
+#[derive(Clone, Debug, thiserror::Error)]
+#[error("Method {0} is not permitted on this connection")]
+struct MethodNotPermittedError(
+    /// The name of the method that was requested.
+    String,
+);
+
+impl From<MethodNotPermittedError> for RpcError {
+    fn from(err: MethodNotPermittedError) -> Self {
+        RpcError::new(err.to_string(), tor_rpcbase::RpcErrorKind::RequestError)
+    }
+}
+
+/// An error returned when an RPC request invokes a deprecated method,
+/// and the client asked us to reject deprecated methods.
+#[derive(Clone, Debug, thiserror::Error)]
+#[error("Method {name} is deprecated")]
+struct DeprecatedMethodError {
+    /// The name of the method that was requested.
+    name: &'static str,
+    /// When the method was deprecated, and what to use instead.
+    version: rpc::MethodVersionInfo,
+}
+
+impl From<DeprecatedMethodError> for RpcError {
+    fn from(err: DeprecatedMethodError) -> Self {
+        let mut e = RpcError::new(err.to_string(), tor_rpcbase::RpcErrorKind::RequestError);
+        e.set_datum("arti:deprecation".to_string(), err.version);
+        e
+    }
+}
+
 /// A failure that results in closing a [`Connection`].
 #[derive(Clone, Debug, thiserror::Error)]
 #[non_exhaustive]
@@ -593,11 +767,7 @@ impl rpc::Context for Connection {
         // objects whose IDs are _ever_ exported for use in SOCKS requests.  Some
         // alternatives would be to use GlobalId conditionally, or to have a
         // separate Method to create a new GlobalId given an existing LocalId.
-        if use_global_id {
-            GlobalId::new(self.connection_id, local_id).encode(&self.global_id_mac_key)
-        } else {
-            local_id.encode()
-        }
+        self.encode_id(local_id, use_global_id)
     }
 
     fn register_weak(&self, object: Arc<dyn rpc::Object>) -> rpc::ObjectId {
@@ -608,11 +778,7 @@ impl rpc::Context for Connection {
             .expect("Lock poisoned")
             .objects
             .insert_weak(object);
-        if use_global_id {
-            GlobalId::new(self.connection_id, local_id).encode(&self.global_id_mac_key)
-        } else {
-            local_id.encode()
-        }
+        self.encode_id(local_id, use_global_id)
     }
 
     fn release_owned(&self, id: &rpc::ObjectId) -> Result<(), rpc::LookupError> {
@@ -664,3 +830,51 @@ impl tor_error::HasKind for RequestCancelled {
         tor_error::ErrorKind::Other
     }
 }
+
+/// Cancel a request that is in progress on this connection.
+///
+/// If the request is cancelled, it will fail with a "request cancelled" error,
+/// and this method will succeed.
+/// The request may finish in some other way if it was about to finish anyway.
+///
+/// Fails if there is no such request in progress:
+/// usually, because it has already finished.
+#[derive(Debug, serde::Deserialize, Deftly)]
+#[derive_deftly(DynMethod)]
+#[deftly(rpc(method_name = "rpc:cancel"))]
+struct RpcCancel {
+    /// The ID of the request to cancel.
+    request_id: RequestId,
+}
+
+impl rpc::RpcMethod for RpcCancel {
+    type Output = rpc::Nil;
+    type Update = rpc::NoUpdates;
+}
+
+/// An error given when asked to cancel a request that isn't in progress.
+#[derive(thiserror::Error, Clone, Debug, serde::Serialize)]
+#[error("No such request is in progress")]
+struct NoSuchRequest;
+impl tor_error::HasKind for NoSuchRequest {
+    fn kind(&self) -> tor_error::ErrorKind {
+        // TODO RPC: Can we do better here?
+        tor_error::ErrorKind::Other
+    }
+}
+
+/// Implement `rpc:cancel` on a connection.
+async fn conn_cancel(
+    conn: Arc<Connection>,
+    method: Box<RpcCancel>,
+    _ctx: Arc<dyn rpc::Context>,
+) -> Result<rpc::Nil, RpcError> {
+    if conn.cancel_request(&method.request_id) {
+        Ok(rpc::NIL)
+    } else {
+        Err(NoSuchRequest.into())
+    }
+}
+rpc::static_rpc_invoke_fn! {
+    conn_cancel;
+}
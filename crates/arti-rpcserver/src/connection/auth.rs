@@ -4,9 +4,10 @@
 //! on the special "connection" object, which gives you an RPC _session_ as a
 //! result.  The RPC session is the root for all other capabilities.
 
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 
 use super::Connection;
+use crate::RpcCapabilityProfile;
 use derive_deftly::Deftly;
 use tor_rpcbase as rpc;
 use tor_rpcbase::templates::*;
@@ -70,24 +71,44 @@ mod get_rpc_protocol {
 
 /// Information about how an RPC session has been authenticated.
 ///
-/// Currently, this isn't actually used for anything, since there's only one way
-/// to authenticate a connection.  It exists so that later we can pass
-/// information to the session-creator function.
+/// This is passed to the session-creator function given to
+/// [`RpcMgr::new`](crate::RpcMgr::new).
 #[derive(Clone, Debug)]
 #[non_exhaustive]
-pub struct RpcAuthentication {}
+pub struct RpcAuthentication {
+    /// What the session is allowed to do.
+    profile: RpcCapabilityProfile,
+}
+
+impl RpcAuthentication {
+    /// Return the capability profile of the listener that the session
+    /// authenticated on.
+    ///
+    /// The connection enforces this profile itself;
+    /// it is provided here for information.
+    pub fn profile(&self) -> RpcCapabilityProfile {
+        self.profile
+    }
+}
 
 /// The authentication scheme as enumerated in the spec.
 ///
 /// Conceptually, an authentication scheme answers the question "How can the
 /// Arti process know you have permissions to use or administer it?"
 ///
-/// TODO RPC: The only supported one for now is "inherent:unix_path"
-#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
-enum AuthenticationScheme {
+/// Which schemes a connection accepts depends on the
+/// [`RpcListenerPolicy`](crate::RpcListenerPolicy) of the listener it arrived on.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub(crate) enum AuthenticationScheme {
     /// Inherent authority based on the ability to access an AF_UNIX address.
     #[serde(rename = "inherent:unix_path")]
-    InherentUnixPath,
+    UnixPath,
+    /// Inherent authority based on the ability to connect to a TCP port on localhost.
+    #[serde(rename = "inherent:tcp_localhost")]
+    TcpLocalhost,
+    /// Inherent authority based on the ability to open a local Windows named pipe.
+    #[serde(rename = "inherent:named_pipe")]
+    NamedPipe,
 }
 
 /// Ask which authentication methods are supported.
@@ -120,13 +141,12 @@ impl rpc::RpcMethod for AuthQuery {
 }
 /// Implement `auth:AuthQuery` on a connection.
 async fn conn_authquery(
-    _conn: Arc<Connection>,
+    conn: Arc<Connection>,
     _query: Box<AuthQuery>,
     _ctx: Arc<dyn rpc::Context>,
 ) -> Result<SupportedAuth, rpc::RpcError> {
-    // Right now, every connection supports the same scheme.
     Ok(SupportedAuth {
-        schemes: vec![AuthenticationScheme::InherentUnixPath],
+        schemes: conn.policy().auth_schemes().to_vec(),
     })
 }
 rpc::static_rpc_invoke_fn! {
@@ -138,7 +158,9 @@ rpc::static_rpc_invoke_fn! {
 /// After connecting to Arti, clients use this method to create a Session,
 /// which they then use to access other functionality.
 ///
-/// For now, only the `inherent:unix_path` method is supported;
+/// For now, only the `inherent:unix_path`, `inherent:tcp_localhost`,
+/// and `inherent:named_pipe` methods are supported,
+/// each on its own kind of listener;
 /// other methods will be implemented in the future.
 ///
 /// You typically won't need to invoke this method yourself;
@@ -149,9 +171,14 @@ rpc::static_rpc_invoke_fn! {
 #[deftly(rpc(method_name = "auth:authenticate"))]
 struct Authenticate {
     /// The authentication scheme as enumerated in the spec.
-    ///
-    /// TODO RPC: The only supported one for now is "inherent:unix_path"
     scheme: AuthenticationScheme,
+    /// If true, reject every later request on this connection
+    /// that invokes a deprecated method.
+    ///
+    /// Otherwise, deprecated methods still work, but Arti logs a warning
+    /// the first time each one is used.
+    #[serde(default)]
+    reject_deprecated: bool,
 }
 
 /// A reply from the `Authenticate` method.
@@ -159,6 +186,43 @@ struct Authenticate {
 struct AuthenticateReply {
     /// An handle for a `Session` object.
     session: rpc::ObjectId,
+    /// Information about the protocol spoken on this connection.
+    protocol: ProtocolInfo,
+}
+
+/// The version of the RPC protocol that this server speaks.
+///
+/// Things might break between here and the stable protocol.
+const RPC_PROTOCOL_VERSION: &str = "alpha";
+
+/// What clients may rely on about the form of our output.
+///
+/// We include this in every [`ProtocolInfo`], so that people writing clients
+/// (and test harnesses) can find out without reading our source.
+const COMPATIBILITY_NOTE: &str = "Methods, their parameters, and their results may change \
+    between versions of the alpha protocol. \
+    When deterministic_output is true, every message on this connection is canonical JSON \
+    (no insignificant whitespace; object members sorted by key), \
+    and each object has the same ObjectId every time it is returned on this connection. \
+    ObjectIds are never stable across connections or Arti versions.";
+
+/// Information about the protocol spoken on a connection,
+/// as returned by the `Authenticate` method.
+#[derive(Debug, serde::Serialize)]
+struct ProtocolInfo {
+    /// The version of the RPC protocol in use.
+    version: &'static str,
+    /// True if this connection gives deterministic output.
+    ///
+    /// See [`RpcListenerPolicy::with_deterministic_output`](crate::RpcListenerPolicy::with_deterministic_output).
+    deterministic_output: bool,
+    /// A human-readable description of what clients can rely on.
+    compatibility: &'static str,
+    /// Every deprecated method that this Arti provides,
+    /// with the version that deprecated it.
+    ///
+    /// Clients should stop using these methods: they may be removed in a future version.
+    deprecated_methods: BTreeMap<&'static str, rpc::MethodVersionInfo>,
 }
 
 impl rpc::RpcMethod for Authenticate {
@@ -168,8 +232,11 @@ impl rpc::RpcMethod for Authenticate {
 
 /// An error during authentication.
 #[derive(Debug, Clone, thiserror::Error, serde::Serialize)]
-#[allow(dead_code)] // TODO RPC
-enum AuthenticationFailure {}
+enum AuthenticationFailure {
+    /// The client asked for a scheme that this connection's listener doesn't accept.
+    #[error("Authentication scheme not accepted on this connection")]
+    SchemeNotPermitted,
+}
 
 impl tor_error::HasKind for AuthenticationFailure {
     fn kind(&self) -> tor_error::ErrorKind {
@@ -187,20 +254,33 @@ async fn authenticate_connection(
     method: Box<Authenticate>,
     ctx: Arc<dyn rpc::Context>,
 ) -> Result<AuthenticateReply, rpc::RpcError> {
-    match method.scheme {
-        // For now, we only support AF_UNIX connections, and we assume that if
-        // you have permission to open such a connection to us, you have
-        // permission to use Arti. We will refine this later on!
-        AuthenticationScheme::InherentUnixPath => {}
+    // For now, every scheme we support is "inherent": we assume that if
+    // you have permission to open a connection to a listener, you have
+    // permission to use Arti as that listener's policy allows.
+    // We will refine this later on!
+    let policy = unauth.policy();
+    if !policy.auth_schemes().contains(&method.scheme) {
+        return Err(AuthenticationFailure::SchemeNotPermitted.into());
     }
 
-    let auth = RpcAuthentication {};
+    let auth = RpcAuthentication {
+        profile: policy.profile(),
+    };
     let session = {
         let mgr = unauth.mgr()?;
         mgr.create_session(&auth)
     };
     let session = ctx.register_owned(session);
-    Ok(AuthenticateReply { session })
+    if method.reject_deprecated {
+        unauth.reject_deprecated_methods();
+    }
+    let protocol = ProtocolInfo {
+        version: RPC_PROTOCOL_VERSION,
+        deterministic_output: policy.deterministic_output(),
+        compatibility: COMPATIBILITY_NOTE,
+        deprecated_methods: rpc::iter_deprecated_methods().collect(),
+    };
+    Ok(AuthenticateReply { session, protocol })
 }
 rpc::static_rpc_invoke_fn! {
     authenticate_connection;
@@ -96,6 +96,13 @@ impl GlobalId {
         B64::encode_string(&bytes[..]).into()
     }
 
+    /// As `encode`, but produce the same string every time.
+    pub(crate) fn encode_stable(&self, key: &MacKey) -> ObjectId {
+        use base64ct::{Base64Unpadded as B64, Encoding};
+        let bytes = self.encode_as_bytes(key, &mut rand::rngs::mock::StepRng::new(0, 0));
+        B64::encode_string(&bytes[..]).into()
+    }
+
     /// As `encode`, but do not base64-encode the result.
     fn encode_as_bytes<R: rand::RngCore>(&self, key: &MacKey, rng: &mut R) -> Vec<u8> {
         let mut bytes = Vec::with_capacity(Self::ENCODED_LEN);
@@ -197,6 +204,11 @@ mod test {
 
         assert_eq!(enc1.as_ref().len(), GlobalId::B64_ENCODED_LEN);
         assert_eq!(enc2.as_ref().len(), GlobalId::B64_ENCODED_LEN);
+
+        let stable = gid1.encode_stable(&mac_key);
+        assert_eq!(stable, gid1.encode_stable(&mac_key));
+        assert_eq!(gid1, GlobalId::try_decode(&mac_key, &stable).unwrap());
+        assert_eq!(stable.as_ref().len(), GlobalId::B64_ENCODED_LEN);
     }
 
     #[test]
@@ -48,11 +48,13 @@ mod globalid;
 mod mgr;
 mod msgs;
 mod objmap;
+mod policy;
 mod session;
 mod stream;
 
 pub use connection::{auth::RpcAuthentication, Connection, ConnectionError};
 pub use mgr::RpcMgr;
+pub use policy::{RpcCapabilityProfile, RpcListenerPolicy};
 pub use session::RpcSession;
 
 /// Return a list of RPC methods that will be needed to use `arti-rpcserver` with the given runtime.
@@ -11,7 +11,7 @@ use weak_table::WeakValueHashMap;
 use crate::{
     connection::{Connection, ConnectionId},
     globalid::{GlobalId, MacKey},
-    RpcAuthentication,
+    RpcAuthentication, RpcListenerPolicy,
 };
 
 /// A function we use to construct Session objects in response to authentication.
@@ -167,13 +167,26 @@ impl RpcMgr {
     }
 
     /// Start a new session based on this RpcMgr, with a given TorClient.
+    ///
+    /// The connection uses the default [`RpcListenerPolicy`]:
+    /// see [`new_connection_with_policy`](RpcMgr::new_connection_with_policy).
     pub fn new_connection(self: &Arc<Self>) -> Arc<Connection> {
+        self.new_connection_with_policy(RpcListenerPolicy::default())
+    }
+
+    /// Start a new session based on this RpcMgr, for a connection that
+    /// arrived on a listener with the rules in `policy`.
+    pub fn new_connection_with_policy(
+        self: &Arc<Self>,
+        policy: RpcListenerPolicy,
+    ) -> Arc<Connection> {
         let connection_id = ConnectionId::from(rand::thread_rng().gen::<[u8; 16]>());
         let connection = Connection::new(
             connection_id,
             self.dispatch_table.clone(),
             self.global_id_mac_key.clone(),
             Arc::downgrade(self),
+            policy,
         );
 
         let mut inner = self.inner.lock().expect("poisoned lock");
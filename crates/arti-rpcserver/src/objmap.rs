@@ -179,6 +179,8 @@ impl TaggedAddr {
 /// where applications depend on the details of our ObjectIds, or hardcode the
 /// ObjectIds they expect, or rely on the same  weak generational index getting
 /// encoded the same way every time they see it.
+/// (The exception is [`GenIdx::encode_stable`], which we only use on connections
+/// that have explicitly asked for deterministic output.)
 ///
 /// The encoding is deliberately non-cryptographic: we do not want to imply
 /// that this gives any security. It is just a mild deterrent to misuse.
@@ -200,6 +202,11 @@ impl GenIdx {
         self.encode_with_rng(&mut rand::thread_rng())
     }
 
+    /// Encode `self` into an rpc::ObjectId that is the same every time.
+    pub(crate) fn encode_stable(self) -> rpc::ObjectId {
+        self.encode_with_rng(&mut rand::rngs::mock::StepRng::new(0, 0))
+    }
+
     /// As `encode`, but take a Rng as an argument. For testing.
     fn encode_with_rng<R: rand::RngCore>(self, rng: &mut R) -> rpc::ObjectId {
         use base64ct::Encoding;
@@ -686,6 +693,9 @@ mod test {
             assert_ne!(s1, s2);
             assert_eq!(idx, GenIdx::try_decode(&s1).unwrap());
             assert_eq!(idx, GenIdx::try_decode(&s2).unwrap());
+            let stable = idx.encode_stable();
+            assert_eq!(stable, idx.encode_stable());
+            assert_eq!(idx, GenIdx::try_decode(&stable).unwrap());
         }
         let mut rng = tor_basic_utils::test_rng::testing_rng();
 
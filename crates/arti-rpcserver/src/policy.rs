@@ -0,0 +1,171 @@
+//! Per-listener rules about who may use an RPC connection, and for what.
+//!
+//! Arti can listen for RPC connections in more than one place at once
+//! (for example, on an AF_UNIX socket and on a localhost TCP port).
+//! Each listener can accept a different set of authentication schemes,
+//! and grant a different [`RpcCapabilityProfile`] to the sessions that
+//! authenticate on it.
+
+use crate::connection::auth::AuthenticationScheme;
+
+/// The set of RPC methods that a connection is allowed to invoke.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum RpcCapabilityProfile {
+    /// Every method is allowed.
+    #[default]
+    Admin,
+    /// Only methods that report on Arti's state are allowed.
+    ///
+    /// Observers cannot open streams, create clients,
+    /// or change anything about how Arti behaves.
+    Observer,
+}
+
+/// The methods that an [`RpcCapabilityProfile::Observer`] may invoke.
+///
+/// Methods are denied unless they are listed here,
+/// so that newly added methods are not exposed to observers by accident.
+const OBSERVER_METHODS: &[&str] = &[
+    "auth:authenticate",
+    "auth:query",
+    "rpc:release",
+    "rpc:cancel",
+    "arti:get_client",
+    "arti:get_client_status",
+    "arti:watch_client_status",
+    "arti:get_health_warnings",
+    "arti:x_get_memory_detail",
+    "arti:get_proxy_info",
+    "arti:get_rpc_proxy_info",
+    "arti:x_list_all_rpc_methods",
+];
+
+impl RpcCapabilityProfile {
+    /// Return true if a connection with this profile may invoke the method
+    /// called `method_name`.
+    pub fn permits(self, method_name: &str) -> bool {
+        match self {
+            RpcCapabilityProfile::Admin => true,
+            RpcCapabilityProfile::Observer => OBSERVER_METHODS.contains(&method_name),
+        }
+    }
+}
+
+/// Rules for the connections accepted by a single RPC listener.
+///
+/// Pass one of these to [`RpcMgr::new_connection_with_policy`](crate::RpcMgr::new_connection_with_policy).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RpcListenerPolicy {
+    /// The authentication schemes that clients may use.
+    auth_schemes: Vec<AuthenticationScheme>,
+    /// What authenticated clients may do.
+    profile: RpcCapabilityProfile,
+    /// Whether to encode responses as canonical JSON, with stable object IDs.
+    deterministic_output: bool,
+}
+
+impl RpcListenerPolicy {
+    /// Return a policy for a listener on an AF_UNIX socket.
+    ///
+    /// Clients authenticate with `inherent:unix_path`:
+    /// the ability to open the socket is taken as proof of authority.
+    pub fn unix_socket(profile: RpcCapabilityProfile) -> Self {
+        Self {
+            auth_schemes: vec![AuthenticationScheme::UnixPath],
+            profile,
+            deterministic_output: false,
+        }
+    }
+
+    /// Return a policy for a listener on a Windows named pipe.
+    ///
+    /// Clients authenticate with `inherent:named_pipe`:
+    /// the ability to open the pipe is taken as proof of authority.
+    pub fn named_pipe(profile: RpcCapabilityProfile) -> Self {
+        Self {
+            auth_schemes: vec![AuthenticationScheme::NamedPipe],
+            profile,
+            deterministic_output: false,
+        }
+    }
+
+    /// Return a policy for a listener on a localhost TCP port.
+    ///
+    /// Clients authenticate with `inherent:tcp_localhost`:
+    /// the ability to connect to the port is taken as proof of authority.
+    /// Since every local user can do that,
+    /// this is usually only appropriate with [`RpcCapabilityProfile::Observer`].
+    pub fn tcp_localhost(profile: RpcCapabilityProfile) -> Self {
+        Self {
+            auth_schemes: vec![AuthenticationScheme::TcpLocalhost],
+            profile,
+            deterministic_output: false,
+        }
+    }
+
+    /// Return the profile granted to connections that use this policy.
+    pub fn profile(&self) -> RpcCapabilityProfile {
+        self.profile
+    }
+
+    /// Return a copy of this policy that does (or does not) ask for deterministic output.
+    ///
+    /// With deterministic output, every response and update is encoded as canonical JSON
+    /// (see [`to_canonical_json`](tor_rpcbase::to_canonical_json)),
+    /// and each object is given the same ObjectId every time it is returned
+    /// on the same connection.
+    /// This is meant for test harnesses, and for clients that cache or compare responses.
+    ///
+    /// It is off by default, since it makes encoding slower,
+    /// and since applications should not normally depend on the form of our ObjectIds.
+    pub fn with_deterministic_output(mut self, deterministic_output: bool) -> Self {
+        self.deterministic_output = deterministic_output;
+        self
+    }
+
+    /// Return true if connections that use this policy get deterministic output.
+    pub fn deterministic_output(&self) -> bool {
+        self.deterministic_output
+    }
+
+    /// Return the authentication schemes that clients may use.
+    pub(crate) fn auth_schemes(&self) -> &[AuthenticationScheme] {
+        &self.auth_schemes
+    }
+}
+
+impl Default for RpcListenerPolicy {
+    fn default() -> Self {
+        Self::unix_socket(RpcCapabilityProfile::Admin)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+
+    #[test]
+    fn profiles() {
+        use RpcCapabilityProfile as P;
+        assert!(P::Admin.permits("arti:new_stream_handle"));
+        assert!(P::Observer.permits("arti:get_client_status"));
+        assert!(!P::Observer.permits("arti:new_stream_handle"));
+        assert!(!P::Observer.permits("arti:new_isolated_client"));
+        assert!(!P::Observer.permits("x-test:no_such_method"));
+    }
+}
@@ -14,12 +14,17 @@ use crate::onion_proxy::{
 #[cfg(not(feature = "onion-service-service"))]
 use crate::onion_proxy_disabled::{OnionServiceProxyConfigMap, OnionServiceProxyConfigMapBuilder};
 use arti_client::TorClientConfig;
-#[cfg(feature = "onion-service-service")]
+#[cfg(any(feature = "onion-service-service", feature = "rpc"))]
 use tor_config::define_list_builder_accessors;
 use tor_config::resolve_alternative_specs;
-#[cfg(feature = "rpc")]
-use tor_config::CfgPath;
 pub(crate) use tor_config::{impl_standard_builder, ConfigBuildError, Listen};
+#[cfg(feature = "rpc")]
+use {
+    crate::rpc::RpcListenAddr,
+    arti_rpcserver::RpcCapabilityProfile,
+    tor_config::{define_list_builder_helper, CfgPath},
+    tor_rtcompat::{general, unix},
+};
 
 use crate::{LoggingConfig, LoggingConfigBuilder};
 
@@ -80,9 +85,32 @@ pub struct ApplicationConfig {
     /// This has no effect on Windows.
     #[builder(default)]
     pub(crate) allow_running_as_root: bool,
+
+    /// What to do about problems with the ownership or permissions of our
+    /// state, cache, and keystore directories, which we check at startup.
+    ///
+    /// (Problems that we find later, when we use those directories,
+    /// are errors regardless of this option.)
+    #[builder(default)]
+    pub(crate) storage_audit: StorageAudit,
 }
 impl_standard_builder! { ApplicationConfig }
 
+/// What to do about problems that we find when we check our storage
+/// directories at startup.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+#[non_exhaustive]
+#[serde(rename_all = "lowercase")]
+pub enum StorageAudit {
+    /// Don't check our storage directories at startup.
+    Off,
+    /// Log a warning about each problem, and start anyway.
+    Warn,
+    /// Log each problem, and refuse to start.
+    #[default]
+    Refuse,
+}
+
 /// Resolves values from `$field_listen` and `$field_port` (compat) into a `Listen`
 ///
 /// For `dns` and `proxy`.
@@ -201,8 +229,166 @@ fn default_max_files() -> u64 {
 #[non_exhaustive]
 pub struct RpcConfig {
     /// Location to listen for incoming RPC connections.
+    ///
+    /// Connections here may use every RPC method.
+    ///
+    /// On Windows, this is the name of a named pipe,
+    /// like `\\.\pipe\arti\SOCKET`.
     #[builder(default = "default_rpc_path()")]
     pub(crate) rpc_listen: Option<CfgPath>,
+
+    /// Additional places to listen for incoming RPC connections,
+    /// each with its own rules.
+    #[builder(sub_builder, setter(custom))]
+    #[builder_field_attr(serde(default))]
+    pub(crate) listeners: RpcListenerList,
+}
+
+/// Local type alias, mostly helpful for derive_builder to DTRT
+#[cfg(feature = "rpc")]
+type RpcListenerList = Vec<RpcListenerConfig>;
+
+#[cfg(feature = "rpc")]
+define_list_builder_helper! {
+    struct RpcListenerListBuilder {
+        listeners: [RpcListenerConfigBuilder],
+    }
+    built: RpcListenerList = listeners;
+    default = vec![];
+}
+
+#[cfg(feature = "rpc")]
+define_list_builder_accessors! {
+    struct RpcConfigBuilder {
+        pub listeners: [RpcListenerConfigBuilder],
+    }
+}
+
+/// Configuration for a single additional RPC listener.
+#[cfg(feature = "rpc")]
+#[derive(Debug, Clone, Builder, Eq, PartialEq)]
+#[builder(build_fn(error = "ConfigBuildError", validate = "Self::validate"))]
+#[builder(derive(Debug, Serialize, Deserialize))]
+#[non_exhaustive]
+pub struct RpcListenerConfig {
+    /// Where to listen.
+    ///
+    /// Either `unix:` followed by a path (which may use the usual
+    /// path variables, like `${ARTI_LOCAL_DATA}`),
+    /// `pipe:` followed by the name of a Windows named pipe,
+    /// like `pipe:\\.\pipe\arti\rpc`,
+    /// or a TCP address on localhost, like `127.0.0.1:9180`.
+    #[builder(setter(into))]
+    pub(crate) address: String,
+
+    /// What connections on this listener may do.
+    ///
+    /// If not set, connections on `unix:` and `pipe:` listeners may do anything,
+    /// and connections on TCP listeners are only allowed to observe.
+    #[builder(default)]
+    pub(crate) profile: Option<RpcCapabilityProfile>,
+
+    /// If set, a file to which we write a connect point
+    /// describing how to reach this listener.
+    #[builder(default)]
+    pub(crate) connect_point: Option<CfgPath>,
+
+    /// If true, connections on this listener get deterministic output:
+    /// canonical JSON, and stable object IDs.
+    ///
+    /// This is intended for test harnesses,
+    /// and for clients that cache or compare responses.
+    #[builder(default)]
+    pub(crate) deterministic_output: bool,
+}
+#[cfg(feature = "rpc")]
+impl_standard_builder! { RpcListenerConfig: !Default }
+
+#[cfg(feature = "rpc")]
+impl RpcListenerConfigBuilder {
+    /// Check that `address` is an acceptable place for an RPC listener.
+    fn validate(&self) -> Result<(), ConfigBuildError> {
+        let Some(address) = &self.address else {
+            return Ok(());
+        };
+        let invalid = |problem: String| ConfigBuildError::Invalid {
+            field: "address".to_owned(),
+            problem,
+        };
+        if address.starts_with("unix:") {
+            return Ok(());
+        }
+        if let Some(name) = address.strip_prefix("pipe:") {
+            return if is_local_pipe_name(name) {
+                Ok(())
+            } else {
+                Err(invalid(format!(
+                    "RPC listeners on named pipes must use a local name, starting with {}",
+                    LOCAL_PIPE_PREFIX
+                )))
+            };
+        }
+        match address.parse::<general::SocketAddr>() {
+            Ok(general::SocketAddr::Inet(a)) if a.ip().is_loopback() => Ok(()),
+            Ok(general::SocketAddr::Inet(_)) => Err(invalid(
+                "RPC listeners on TCP must use a localhost address".to_owned(),
+            )),
+            Ok(_) => Err(invalid("Unsupported kind of address".to_owned())),
+            Err(e) => Err(invalid(e.to_string())),
+        }
+    }
+}
+
+#[cfg(feature = "rpc")]
+impl RpcListenerConfig {
+    /// Return true if this listener is on an AF_UNIX socket.
+    pub(crate) fn is_unix(&self) -> bool {
+        self.address.starts_with("unix:")
+    }
+
+    /// Return true if this listener is on a Windows named pipe.
+    pub(crate) fn is_named_pipe(&self) -> bool {
+        self.address.starts_with("pipe:")
+    }
+
+    /// Return the capability profile for connections on this listener.
+    pub(crate) fn profile(&self) -> RpcCapabilityProfile {
+        self.profile
+            .unwrap_or(if self.is_unix() || self.is_named_pipe() {
+                RpcCapabilityProfile::Admin
+            } else {
+                RpcCapabilityProfile::Observer
+            })
+    }
+
+    /// Return the address to listen on, with any path variables expanded.
+    pub(crate) fn listen_addr(&self) -> anyhow::Result<RpcListenAddr> {
+        if let Some(name) = self.address.strip_prefix("pipe:") {
+            return Ok(RpcListenAddr::NamedPipe(name.to_owned()));
+        }
+        match self.address.strip_prefix("unix:") {
+            Some(path) => {
+                let path = CfgPath::new(path.to_owned()).path()?;
+                Ok(RpcListenAddr::Socket(
+                    unix::SocketAddr::from_pathname(path)?.into(),
+                ))
+            }
+            None => Ok(RpcListenAddr::Socket(self.address.parse()?)),
+        }
+    }
+}
+
+/// The prefix of every named pipe on the local machine.
+#[cfg(feature = "rpc")]
+const LOCAL_PIPE_PREFIX: &str = r"\\.\pipe\";
+
+/// Return true if `name` is the name of a named pipe on the local machine.
+#[cfg(feature = "rpc")]
+pub(crate) fn is_local_pipe_name(name: &str) -> bool {
+    name.len() > LOCAL_PIPE_PREFIX.len()
+        && name
+            .get(..LOCAL_PIPE_PREFIX.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(LOCAL_PIPE_PREFIX))
 }
 
 /// Return the default value for our configuration path.
@@ -528,11 +714,27 @@ mod test {
             &[
                 // Keys that are newer than the oldest-supported example, but otherwise normal.
                 "application.allow_running_as_root",
+                "application.storage_audit",
                 "bridges",
                 "logging.time_granularity",
+                "logging.redaction",
+                "logging.redaction.address",
+                "logging.redaction.relay_identity",
+                "logging.redaction.onion_service",
+                "logging.redaction.other",
                 "path_rules.long_lived_ports",
                 "proxy.socks_listen",
                 "proxy.dns_listen",
+                "dns_cache",
+                "dns_cache.enabled",
+                "dns_cache.ttl",
+                "dns_cache.max_entries",
+                "stream_rotation",
+                "stream_rotation.max_lifetime",
+                "stream_rotation.max_since_last_stream",
+                "stream_rotation.rules",
+                "guard_diversity",
+                "address_filter.onion_only",
             ],
         );
 
@@ -544,6 +746,13 @@ mod test {
                 // Examples exist but are not auto-testable
                 "tor_network.authorities",
                 "tor_network.fallback_caches",
+                "tor_network.consensus_trust",
+                "tor_network.consensus_trust.signature_threshold",
+                "tor_network.consensus_trust.private_network",
+                "preemptive_circuits.profile",
+                "guard_diversity.max_per_family",
+                "guard_diversity.max_per_subnet",
+                "guard_diversity.max_per_country",
             ],
         );
 
@@ -579,6 +788,8 @@ mod test {
             &[
                 // Settings only available with experimental-api support
                 "storage.keystore",
+                // Settings only available with hs-endpoint-restrictions support
+                "path_rules.hs_endpoints",
             ],
         );
 
@@ -591,6 +802,8 @@ mod test {
                 "system.memory",
                 "system.memory.max",
                 "system.memory.low_water",
+                "system.memory.max_per_circuit",
+                "system.memory.max_per_stream",
             ],
         );
 
@@ -612,6 +825,8 @@ mod test {
                 "address_filter.allow_onion_addrs",
                 "circuit_timing.hs_desc_fetch_attempts",
                 "circuit_timing.hs_intro_rend_attempts",
+                "circuit_timing.hs_desc_fetch_parallelism",
+                "circuit_timing.hs_desc_fetch_stagger",
             ],
         );
 
@@ -623,6 +838,7 @@ mod test {
                 // RPC-only settings
                 "rpc",
                 "rpc.rpc_listen",
+                "rpc.listeners",
             ],
         );
 
@@ -660,6 +876,16 @@ mod test {
             ],
         );
 
+        declare_exceptions(
+            None,
+            None, // described in the example config, but has no default to show
+            FeatureDependent,
+            &[
+                // encrypted-keystore only settings
+                "storage.keystore.primary.passphrase",
+            ],
+        );
+
         out.sort();
 
         let dupes = out.iter().map(|exc| &exc.key).duplicates().collect_vec();
@@ -1233,12 +1459,19 @@ example config file {which:?}, uncommented={uncommented:?}
 
                 // Test that the example low_water is the default
                 // value for the example max.
-                let defaulted_low = tor_memquota::Config::builder()
-                    .max(*inner.max)
-                    .build()
-                    .unwrap();
+                let mut defaulted_low = tor_memquota::Config::builder();
+                defaulted_low.max(*inner.max);
+                if let Some(max_per_circuit) = inner.max_per_circuit {
+                    defaulted_low.max_per_circuit(*max_per_circuit);
+                }
+                if let Some(max_per_stream) = inner.max_per_stream {
+                    defaulted_low.max_per_stream(*max_per_stream);
+                }
+                let defaulted_low = defaulted_low.build().unwrap();
                 let inner_defaulted_low = defaulted_low.inner().unwrap();
                 assert_eq!(inner, inner_defaulted_low);
+                assert!(inner.max_per_circuit.is_some());
+                assert!(inner.max_per_stream.is_some());
             } else if #[cfg(arti_features_precise)] {
                 // Test that requesting memory quota tracking generates a config error
                 // if support is compiled out.
@@ -51,6 +51,8 @@ pub mod cfg;
 pub mod logging;
 #[cfg(not(feature = "onion-service-service"))]
 mod onion_proxy_disabled;
+#[cfg(feature = "encrypted-keystore")]
+mod passphrase;
 
 mod subcommands;
 
@@ -86,6 +88,7 @@ semipublic_mod! {
     mod exit;
     #[cfg(feature="onion-service-service")]
     mod onion_proxy;
+    mod one_shot;
     mod process;
     mod reload_cfg;
     mod socks;
@@ -99,7 +102,8 @@ use std::fmt::Write;
 
 pub use cfg::{
     ApplicationConfig, ApplicationConfigBuilder, ArtiCombinedConfig, ArtiConfig, ArtiConfigBuilder,
-    ProxyConfig, ProxyConfigBuilder, SystemConfig, SystemConfigBuilder, ARTI_EXAMPLE_CONFIG,
+    ProxyConfig, ProxyConfigBuilder, StorageAudit, SystemConfig, SystemConfigBuilder,
+    ARTI_EXAMPLE_CONFIG,
 };
 pub use logging::{LoggingConfig, LoggingConfigBuilder};
 
@@ -115,7 +119,6 @@ use clap::{value_parser, Arg, ArgAction, Command};
 #[allow(unused_imports)]
 use tracing::{error, info, warn};
 
-#[cfg(any(feature = "hsc", feature = "onion-service-service"))]
 use clap::Subcommand as _;
 
 #[cfg(feature = "experimental-api")]
@@ -135,15 +138,14 @@ fn create_runtime() -> std::io::Result<impl Runtime> {
         } else if #[cfg(all(feature="tokio", feature="rustls"))] {
             use tor_rtcompat::tokio::TokioRustlsRuntime as ChosenRuntime;
             let _idempotent_ignore = rustls_crate::crypto::CryptoProvider::install_default(
-                rustls_crate::crypto::ring::default_provider(),
-
+                rustls_crypto_provider(),
             );
         } else if #[cfg(all(feature="async-std", feature="native-tls"))] {
             use tor_rtcompat::async_std::AsyncStdNativeTlsRuntime as ChosenRuntime;
         } else if #[cfg(all(feature="async-std", feature="rustls"))] {
             use tor_rtcompat::async_std::AsyncStdRustlsRuntime as ChosenRuntime;
             let _idempotent_ignore = rustls_crate::crypto::CryptoProvider::install_default(
-                rustls_crate::crypto::ring::default_provider(),
+                rustls_crypto_provider(),
             );
         } else {
             compile_error!("You must configure both an async runtime and a TLS stack. See doc/TROUBLESHOOTING.md for more.");
@@ -152,6 +154,25 @@ fn create_runtime() -> std::io::Result<impl Runtime> {
     ChosenRuntime::create()
 }
 
+/// Return the rustls `CryptoProvider` selected by our Cargo features.
+///
+/// (This is only used when `create_runtime` picks a rustls runtime.)
+#[cfg(all(
+    feature = "rustls",
+    not(feature = "native-tls"),
+    not(feature = "rpc"),
+    any(feature = "tokio", feature = "async-std")
+))]
+fn rustls_crypto_provider() -> rustls_crate::crypto::CryptoProvider {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "accel-aws-lc")] {
+            rustls_crate::crypto::aws_lc_rs::default_provider()
+        } else {
+            rustls_crate::crypto::ring::default_provider()
+        }
+    }
+}
+
 /// Return a (non-exhaustive) array of enabled Cargo features, for version printing purposes.
 fn list_enabled_features() -> &'static [&'static str] {
     // HACK(eta): We can't get this directly, so we just do this awful hack instead.
@@ -164,6 +185,12 @@ fn list_enabled_features() -> &'static [&'static str] {
         "static-sqlite",
         #[cfg(any(feature = "static-native-tls", feature = "static"))]
         "static-native-tls",
+        #[cfg(feature = "accel-openssl")]
+        "accel-openssl",
+        #[cfg(feature = "accel-aws-lc")]
+        "accel-aws-lc",
+        #[cfg(feature = "accel-ring")]
+        "accel-ring",
     ]
 }
 
@@ -260,6 +287,14 @@ where
                     .action(ArgAction::SetTrue)
                     .help("Don't check permissions on the files we use."),
             )
+            .arg(
+                Arg::new("fix-permissions")
+                    .long("fix-permissions")
+                    .global(true)
+                    .action(ArgAction::SetTrue)
+                    .conflicts_with("disable-fs-permission-checks")
+                    .help("Before starting, remove any permissions that are too broad from our state, cache, and keystore directories."),
+            )
             .subcommand(
                 Command::new("proxy")
                     .about(
@@ -279,6 +314,14 @@ where
                             .value_name("PORT")
                             .help("Port to listen on for DNS request (overrides the port in the config if specified).")
                     )
+                    .arg(
+                        Arg::new("one-shot")
+                            .long("one-shot")
+                            .action(ArgAction::Set)
+                            .value_name("HOST:PORT")
+                            .conflicts_with_all(["socks-port", "dns-port"])
+                            .help("Instead of running a proxy, connect to HOST:PORT, relay the connection to stdin and stdout, and exit when it is closed. No state is kept: this is suitable as an ssh ProxyCommand.")
+                    )
             )
             .subcommand_required(true)
             .arg_required_else_help(true);
@@ -286,6 +329,14 @@ where
     // When adding a subcommand, it may be necessary to add an entry in
     // `maint/check-cli-help`, to the function `help_arg`.
 
+    let clap_app = subcommands::connect::ConnectSubcommands::augment_subcommands(clap_app);
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "bridge-client")] {
+            let clap_app = subcommands::bridges::BridgesSubcommands::augment_subcommands(clap_app);
+        }
+    }
+
     cfg_if::cfg_if! {
         if #[cfg(feature = "onion-service-service")] {
             let clap_app = subcommands::hss::HssSubcommands::augment_subcommands(clap_app);
@@ -298,6 +349,24 @@ where
         }
     }
 
+    cfg_if::cfg_if! {
+        if #[cfg(all(feature = "keymgr", feature = "experimental-api"))] {
+            let clap_app = subcommands::keys::KeysSubcommands::augment_subcommands(clap_app);
+        }
+    }
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "experimental-api")] {
+            let clap_app = subcommands::netdir::NetdirSubcommands::augment_subcommands(clap_app);
+        }
+    }
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "rpc")] {
+            let clap_app = subcommands::status::StatusSubcommands::augment_subcommands(clap_app);
+        }
+    }
+
     // Tracing doesn't log anything when there is no subscriber set.  But we want to see
     // logging messages from config parsing etc.  We can't set the global default subscriber
     // because we can only set it once.  The other ways involve a closure.  So we have a
@@ -340,6 +409,32 @@ where
             override_options.push("storage.permissions.dangerously_trust_everyone=true".to_owned());
         }
 
+        // `proxy --one-shot` must not leave any state behind, so it keeps its state
+        // and cache in a temporary directory that's deleted when we exit.
+        let one_shot = matches
+            .subcommand_matches("proxy")
+            .is_some_and(|m| m.get_one::<String>("one-shot").is_some());
+        let ephemeral_dir = if one_shot {
+            let dir = one_shot::EphemeralDir::new()?;
+            let path = dir
+                .path()
+                .to_str()
+                .context("temporary state directory path is not valid UTF-8")?;
+            for (option, subdir) in [
+                ("storage.state_dir", "state"),
+                ("storage.cache_dir", "cache"),
+            ] {
+                let value = toml::Value::String(format!("{path}/{subdir}"));
+                override_options.push(format!("{option}={value}"));
+            }
+            // We don't want to create (or unlock) a keystore we'll throw away.
+            #[cfg(feature = "keymgr")]
+            override_options.push("storage.keystore.enabled=false".to_owned());
+            Some(dir)
+        } else {
+            None
+        };
+
         let cfg_sources = {
             let mut cfg_sources = ConfigurationSources::try_from_cmdline(
                 || default_config_files().context("identify default config file locations"),
@@ -358,16 +453,26 @@ where
 
         let log_mistrust = client_config.fs_mistrust().clone();
 
-        Ok::<_, Error>((matches, cfg_sources, config, client_config, log_mistrust))
+        Ok::<_, Error>((
+            matches,
+            cfg_sources,
+            config,
+            client_config,
+            log_mistrust,
+            ephemeral_dir,
+        ))
     })?;
     // Sadly I don't seem to be able to persuade rustfmt to format the two lists of
     // variable names identically.
-    let (matches, cfg_sources, config, client_config, log_mistrust) = pre_config_logging_ret;
+    let (matches, cfg_sources, config, client_config, log_mistrust, ephemeral_dir) =
+        pre_config_logging_ret;
 
     let _log_guards = logging::setup_logging(
         config.logging(),
         &log_mistrust,
         matches.get_one::<String>("loglevel").map(|s| s.as_str()),
+        // When relaying a stream, stdout is used for the stream.
+        ephemeral_dir.is_some() || matches.subcommand_matches("connect").is_some(),
     )?;
 
     if !config.application().allow_running_as_root {
@@ -382,11 +487,31 @@ where
         }
     }
 
+    process::audit_storage(
+        config.application().storage_audit,
+        &client_config,
+        matches.get_flag("fix-permissions"),
+    )?;
+
     // Check for the "proxy" subcommand.
     if let Some(proxy_matches) = matches.subcommand_matches("proxy") {
         return subcommands::proxy::run(runtime, proxy_matches, cfg_sources, config, client_config);
     }
 
+    // Check for the "connect" subcommand.
+    if let Some(connect_matches) = matches.subcommand_matches("connect") {
+        return subcommands::connect::run(runtime, connect_matches, client_config);
+    }
+
+    // Check for the optional "bridges" subcommand.
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "bridge-client")] {
+            if let Some(bridges_matches) = matches.subcommand_matches("bridges") {
+                return subcommands::bridges::run(bridges_matches);
+            }
+        }
+    }
+
     // Check for the optional "hss" subcommand.
     cfg_if::cfg_if! {
         if #[cfg(feature = "onion-service-service")] {
@@ -405,6 +530,33 @@ where
         }
     }
 
+    // Check for the optional "keys" subcommand.
+    cfg_if::cfg_if! {
+        if #[cfg(all(feature = "keymgr", feature = "experimental-api"))] {
+            if let Some(keys_matches) = matches.subcommand_matches("keys") {
+                return subcommands::keys::run(runtime, keys_matches, &client_config);
+            }
+        }
+    }
+
+    // Check for the optional "netdir" subcommand.
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "experimental-api")] {
+            if let Some(netdir_matches) = matches.subcommand_matches("netdir") {
+                return subcommands::netdir::run(runtime, netdir_matches, &client_config);
+            }
+        }
+    }
+
+    // Check for the optional "status" subcommand.
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "rpc")] {
+            if let Some(status_matches) = matches.subcommand_matches("status") {
+                return subcommands::status::run(status_matches);
+            }
+        }
+    }
+
     panic!("Subcommand added to clap subcommand list, but not yet implemented");
 }
 
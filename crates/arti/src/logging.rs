@@ -3,6 +3,7 @@
 use anyhow::{anyhow, Context, Result};
 use derive_builder::Builder;
 use fs_mistrust::Mistrust;
+use safelog::RedactionPolicy;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::str::FromStr;
@@ -10,10 +11,14 @@ use tor_config::impl_standard_builder;
 use tor_config::{define_list_builder_accessors, define_list_builder_helper};
 use tor_config::{CfgPath, ConfigBuildError};
 use tor_error::warn_report;
-use tracing::{error, Subscriber};
+use tracing::{error, Event, Level, Subscriber};
 use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::fmt::format::{self, FormatEvent, FormatFields};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::fmt::FmtContext;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::prelude::*;
+use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::{filter::Targets, fmt, registry, Layer};
 
 mod time;
@@ -64,6 +69,17 @@ pub struct LoggingConfig {
     #[builder(default)]
     log_sensitive_information: bool,
 
+    /// How to redact each category of sensitive information,
+    /// when `log_sensitive_information` is false.
+    ///
+    /// By default, everything is always redacted.
+    ///
+    /// Unlike the other logging options, this one can be changed while Arti is running
+    /// (and it can be adjusted over RPC).
+    #[builder_field_attr(serde(default))]
+    #[builder(default)]
+    pub(crate) redaction: RedactionPolicy,
+
     /// An approximate granularity with which log times should be displayed.
     ///
     /// This value controls every log time that arti outputs; it doesn't have any
@@ -132,6 +148,32 @@ pub enum LogRotation {
     Never,
 }
 
+/// An event formatter that tells `safelog` about the severity of each event it formats.
+///
+/// This is what makes [`safelog::Redaction::InfoAndAbove`] work.
+struct SeverityAwareFormat<F>(F);
+
+impl<S, N, F> FormatEvent<S, N> for SeverityAwareFormat<F>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    N: for<'writer> FormatFields<'writer> + 'static,
+    F: FormatEvent<S, N>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        writer: format::Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        // (In `tracing`, more verbose levels compare as greater.)
+        if *event.metadata().level() >= Level::DEBUG {
+            safelog::with_debug_severity(|| self.0.format_event(ctx, writer, event))
+        } else {
+            self.0.format_event(ctx, writer, event)
+        }
+    }
+}
+
 /// As [`Targets::from_str`], but wrapped in an [`anyhow::Result`].
 //
 // (Note that we have to use `Targets`, not `EnvFilter`: see comment in
@@ -149,8 +191,13 @@ fn filt_from_opt_str(s: &Option<String>, source: &str) -> Result<Option<Targets>
     })
 }
 
-/// Try to construct a tracing [`Layer`] for logging to stdout.
-fn console_layer<S>(config: &LoggingConfig, cli: Option<&str>) -> Result<impl Layer<S>>
+/// Try to construct a tracing [`Layer`] for logging to stdout
+/// (or to stderr, if `to_stderr` is true).
+fn console_layer<S>(
+    config: &LoggingConfig,
+    cli: Option<&str>,
+    to_stderr: bool,
+) -> Result<impl Layer<S>>
 where
     S: Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
 {
@@ -163,7 +210,15 @@ where
     // feature: we cannot be certain that the console really is volatile. Even
     // if isatty() returns true on the console, we can't be sure that the
     // terminal isn't saving backlog to disk or something like that.
-    Ok(fmt::Layer::default().with_timer(timer).with_filter(filter))
+    let writer = if to_stderr {
+        BoxMakeWriter::new(std::io::stderr)
+    } else {
+        BoxMakeWriter::new(std::io::stdout)
+    };
+    Ok(fmt::Layer::default()
+        .event_format(SeverityAwareFormat(fmt::format().with_timer(timer)))
+        .with_writer(writer)
+        .with_filter(filter))
 }
 
 /// Try to construct a tracing [`Layer`] for logging to journald, if one is
@@ -218,8 +273,8 @@ where
     let (nonblocking, guard) = non_blocking(appender);
     let layer = fmt::layer()
         .with_ansi(false)
+        .event_format(SeverityAwareFormat(fmt::format().with_timer(timer)))
         .with_writer(nonblocking)
-        .with_timer(timer)
         .with_filter(filter);
     Ok((layer, guard))
 }
@@ -306,12 +361,16 @@ pub(crate) struct LogGuards {
 ///
 /// Note that the returned LogGuard must be dropped precisely when the program
 /// quits; they're used to ensure that all the log messages are flushed.
+///
+/// If `console_to_stderr` is true, the console log is written to stderr instead of stdout
+/// (for instance, because stdout is used for something else).
 #[cfg_attr(feature = "experimental-api", visibility::make(pub))]
 #[cfg_attr(docsrs, doc(cfg(feature = "experimental-api")))]
 pub(crate) fn setup_logging(
     config: &LoggingConfig,
     mistrust: &Mistrust,
     cli: Option<&str>,
+    console_to_stderr: bool,
 ) -> Result<LogGuards> {
     // Important: We have to make sure that the individual layers we add here
     // are not filters themselves.  That means, for example, that we can't add
@@ -321,7 +380,7 @@ pub(crate) fn setup_logging(
     // that apply to the entire registry, see
     // https://docs.rs/tracing-subscriber/0.3.5/tracing_subscriber/layer/index.html#global-filtering
 
-    let registry = registry().with(console_layer(config, cli)?);
+    let registry = registry().with(console_layer(config, cli, console_to_stderr)?);
 
     #[cfg(feature = "journald")]
     let registry = registry.with(journald_layer(config)?);
@@ -331,6 +390,8 @@ pub(crate) fn setup_logging(
 
     registry.init();
 
+    safelog::set_redaction_policy(&config.redaction);
+
     let safelog_guard = if config.log_sensitive_information {
         match safelog::disable_safe_logging() {
             Ok(guard) => Some(guard),
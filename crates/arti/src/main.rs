@@ -43,6 +43,12 @@
 #![allow(clippy::needless_raw_string_hashes)] // complained-about code is fine, often best
 //! <!-- @@ end lint list maintained by maint/add_warning @@ -->
 
+/// The global allocator, which attributes allocations to subsystems.
+#[cfg(feature = "alloc-tags")]
+#[global_allocator]
+static ALLOC: tor_basic_utils::alloc_tag::TaggingAllocator =
+    tor_basic_utils::alloc_tag::TaggingAllocator::new(std::alloc::System);
+
 fn main() {
     arti::main();
 }
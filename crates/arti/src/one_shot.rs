@@ -0,0 +1,251 @@
+//! Relay a single stream over Tor to stdin and stdout.
+//!
+//! This implements `arti proxy --one-shot` and `arti connect`,
+//! which can be used (for example) as an ssh `ProxyCommand`.
+
+use std::io::{self, Read as _, Write as _};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::SystemTime;
+
+use anyhow::{anyhow, Context, Result};
+use futures::channel::mpsc;
+use futures::future::{self, Either};
+use futures::io::{AsyncReadExt as _, AsyncWriteExt as _};
+use futures::{SinkExt as _, StreamExt as _};
+use tracing::{info, warn};
+
+use arti_client::{StreamPrefs, TorAddr, TorClient, TorClientConfig};
+use fs_mistrust::anon_home::PathExt as _;
+use tor_rtcompat::Runtime;
+
+/// The size of the buffers used for copying data.
+const BUF_LEN: usize = 4096;
+
+/// The number of buffers that can be queued between stdin or stdout and the Tor stream.
+const CHANNEL_LEN: usize = 16;
+
+/// A private temporary directory, deleted together with its contents when dropped.
+///
+/// `proxy --one-shot` keeps its state and cache in one of these,
+/// so that it doesn't leave anything behind.
+#[derive(Debug)]
+pub(crate) struct EphemeralDir(PathBuf);
+
+impl EphemeralDir {
+    /// Create a new, empty directory in the system's temporary directory.
+    ///
+    /// Only the current user can access it.
+    pub(crate) fn new() -> Result<Self> {
+        let base = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.subsec_nanos());
+        for attempt in 0..16_u32 {
+            let name = format!("arti-one-shot-{}-{}-{}", std::process::id(), nanos, attempt);
+            let path = base.join(name);
+            let mut builder = std::fs::DirBuilder::new();
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::DirBuilderExt as _;
+                builder.mode(0o700);
+            }
+            // `create` fails if anything already exists at `path`, so nobody
+            // else can have prepared this directory (or a symlink) for us.
+            match builder.create(&path) {
+                Ok(()) => return Ok(EphemeralDir(path)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e).context("create temporary state directory"),
+            }
+        }
+        Err(anyhow!("could not find a name for a temporary state directory"))
+    }
+
+    /// Return the path of this directory.
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for EphemeralDir {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.0) {
+            warn!(
+                "Unable to remove temporary state directory {}: {}",
+                self.0.anonymize_home(),
+                e
+            );
+        }
+    }
+}
+
+/// Connect to `target` over Tor, using `prefs`,
+/// and relay the stream to stdin and stdout until the stream is closed.
+///
+/// Reaching the end of stdin doesn't close the stream,
+/// since Tor streams can't be half-closed:
+/// the stream stays open until the other side closes it.
+#[cfg_attr(feature = "experimental-api", visibility::make(pub))]
+pub(crate) async fn run_one_shot<R: Runtime>(
+    tor_client: TorClient<R>,
+    target: TorAddr,
+    prefs: &StreamPrefs,
+) -> Result<()> {
+    tor_client.bootstrap().await.context("bootstrap")?;
+    info!("Sufficiently bootstrapped; connecting to target.");
+
+    let stream = tor_client
+        .connect_with_prefs(target.clone(), prefs)
+        .await
+        .with_context(|| format!("connect to {target}"))?;
+    let (mut reader, mut writer) = stream.split();
+
+    let (stdin_tx, mut stdin_rx) = mpsc::channel(CHANNEL_LEN);
+    let (mut stdout_tx, stdout_rx) = mpsc::channel(CHANNEL_LEN);
+
+    // Reading from stdin and writing to stdout block, so we do them in threads of their own.
+    //
+    // We don't wait for the stdin thread when we're done:
+    // it might be blocked reading from stdin forever.
+    thread::Builder::new()
+        .name("arti-stdin".into())
+        .spawn(move || {
+            if let Err(e) = read_stdin(stdin_tx) {
+                warn!("Error reading from stdin: {e}");
+            }
+        })
+        .context("spawn stdin thread")?;
+    let stdout_thread = thread::Builder::new()
+        .name("arti-stdout".into())
+        .spawn(move || write_stdout(stdout_rx))
+        .context("spawn stdout thread")?;
+
+    let upload = async {
+        while let Some(data) = stdin_rx.next().await {
+            writer.write_all(&data).await?;
+            writer.flush().await?;
+        }
+        // We've reached the end of stdin: wait for the other side to close the stream.
+        future::pending::<io::Result<()>>().await
+    };
+    let download = async {
+        let mut buf = vec![0; BUF_LEN];
+        loop {
+            let n = reader.read(&mut buf).await?;
+            if n == 0 || stdout_tx.send(buf[..n].to_vec()).await.is_err() {
+                // The stream was closed, or stdout was.
+                return Ok::<_, io::Error>(());
+            }
+        }
+    };
+
+    let res = match future::select(Box::pin(upload), Box::pin(download)).await {
+        Either::Left((res, _)) => res.context("write to stream"),
+        Either::Right((res, _)) => res.context("read from stream"),
+    };
+
+    // Let the stdout thread flush whatever it has left.
+    drop(stdout_tx);
+    match stdout_thread.join() {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) if e.kind() == io::ErrorKind::BrokenPipe => {}
+        Ok(Err(e)) => return Err(e).context("write to stdout"),
+        Err(_) => return Err(anyhow::anyhow!("stdout thread panicked")),
+    }
+
+    res
+}
+
+/// Create a [`TorClient`] with `client_config`, connect to `target` using `prefs`,
+/// and relay the stream to stdin and stdout,
+/// until it is closed or we are interrupted.
+pub(crate) async fn run<R: Runtime>(
+    runtime: R,
+    target: TorAddr,
+    prefs: StreamPrefs,
+    client_config: TorClientConfig,
+) -> Result<()> {
+    use futures::FutureExt as _;
+
+    let client_builder = TorClient::with_runtime(runtime).config(client_config);
+    #[cfg(feature = "encrypted-keystore")]
+    let client_builder = client_builder.keystore_passphrase_prompt(std::sync::Arc::new(
+        crate::passphrase::TerminalPassphrasePrompt,
+    ));
+    let client = client_builder.create_unbootstrapped_async().await?;
+
+    futures::select!(
+        r = crate::exit::wait_for_ctrl_c().fuse()
+            => r.context("waiting for termination signal"),
+        r = run_one_shot(client, target, &prefs).fuse()
+            => r,
+    )
+}
+
+/// Send the data read from stdin to `tx`, until we reach the end of stdin
+/// or `tx` is closed.
+fn read_stdin(mut tx: mpsc::Sender<Vec<u8>>) -> io::Result<()> {
+    let mut stdin = io::stdin().lock();
+    let mut buf = vec![0; BUF_LEN];
+    loop {
+        let n = match stdin.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if futures::executor::block_on(tx.send(buf[..n].to_vec())).is_err() {
+            // We're done with the stream.
+            return Ok(());
+        }
+    }
+}
+
+/// Write the data received from `rx` to stdout, until `rx` is closed.
+fn write_stdout(mut rx: mpsc::Receiver<Vec<u8>>) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    while let Some(data) = futures::executor::block_on(rx.next()) {
+        stdout.write_all(&data)?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+
+    #[test]
+    fn ephemeral_dir() {
+        let dir = EphemeralDir::new().unwrap();
+        let other = EphemeralDir::new().unwrap();
+        assert_ne!(dir.path(), other.path());
+
+        let path = dir.path().to_owned();
+        std::fs::create_dir(path.join("state")).unwrap();
+        std::fs::write(path.join("state/file"), b"x").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt as _;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+
+        drop(dir);
+        assert!(!path.try_exists().unwrap());
+        assert!(other.path().try_exists().unwrap());
+    }
+}
@@ -0,0 +1,29 @@
+//! Asking the user for the passphrase of an encrypted keystore.
+
+use std::io;
+
+use tor_keymgr::{KeystoreId, PassphrasePrompt};
+use zeroize::Zeroizing;
+
+/// A [`PassphrasePrompt`] that asks for the passphrase on the terminal.
+pub(crate) struct TerminalPassphrasePrompt;
+
+impl PassphrasePrompt for TerminalPassphrasePrompt {
+    fn passphrase(&self, id: &KeystoreId, new: bool) -> io::Result<Zeroizing<String>> {
+        let prompt = if new {
+            format!("Choose a passphrase for the {id} keystore")
+        } else {
+            format!("Passphrase for the {id} keystore")
+        };
+
+        let mut input = dialoguer::Password::new().with_prompt(prompt);
+        if new {
+            input = input.with_confirmation("Repeat passphrase", "Passphrases do not match");
+        }
+
+        match input.interact() {
+            Ok(passphrase) => Ok(Zeroizing::new(passphrase)),
+            Err(dialoguer::Error::IO(e)) => Err(e),
+        }
+    }
+}
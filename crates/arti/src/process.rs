@@ -1,8 +1,9 @@
 //! Code to adjust process-related parameters.
 
-use tracing::error;
+use arti_client::TorClientConfig;
+use tracing::{error, info, warn};
 
-use crate::ArtiConfig;
+use crate::{ArtiConfig, StorageAudit};
 
 /// Set our current maximum-file limit to a large value, if we can.
 ///
@@ -62,6 +63,88 @@ pub(crate) fn exit_if_root() {
     }
 }
 
+/// Check the ownership and permissions of the directories where we keep our
+/// data (and of everything in them), and act on any problems as `policy` says.
+///
+/// If `fix` is true, first remove any permissions that are too broad.
+/// (We do this even if `policy` is [`StorageAudit::Off`].)
+///
+/// Directories that don't exist yet are skipped: we'll create them with
+/// suitable permissions when we need them.
+///
+/// # Limitations
+///
+/// On Windows, we can find problems with access control lists,
+/// but we can't fix them.
+pub(crate) fn audit_storage(
+    policy: StorageAudit,
+    client_config: &TorClientConfig,
+    fix: bool,
+) -> anyhow::Result<()> {
+    use anyhow::Context as _;
+    use fs_mistrust::anon_home::PathExt as _;
+
+    if policy == StorageAudit::Off && !fix {
+        return Ok(());
+    }
+
+    let mistrust = client_config.fs_mistrust();
+    let dirs = client_config
+        .storage_dirs()
+        .context("find storage directories")?;
+
+    let mut n_problems = 0;
+    for (description, dir) in dirs {
+        let verifier = || mistrust.verifier().all_errors().check_content();
+
+        if fix {
+            match verifier().fix_permissions(&dir) {
+                Ok(fixes) => {
+                    for f in fixes {
+                        info!(
+                            "Changed permissions on {} from {:o} to {:o}",
+                            f.path.anonymize_home(),
+                            f.old_mode,
+                            f.new_mode
+                        );
+                    }
+                }
+                Err(fs_mistrust::Error::NotFound(_)) => continue,
+                Err(e) => {
+                    return Err(e).with_context(|| format!("fix permissions on {}", description))
+                }
+            }
+        }
+
+        let problems = match verifier().check(&dir) {
+            Ok(()) | Err(fs_mistrust::Error::NotFound(_)) => continue,
+            Err(e) => e,
+        };
+        for problem in problems.errors() {
+            let problem = match problem {
+                fs_mistrust::Error::Content(inner) => inner.as_ref(),
+                other => other,
+            };
+            n_problems += 1;
+            if policy == StorageAudit::Refuse {
+                error!("Problem with our {}: {}", description, problem);
+            } else {
+                warn!("Problem with our {}: {}", description, problem);
+            }
+        }
+    }
+
+    if n_problems > 0 && policy == StorageAudit::Refuse {
+        return Err(anyhow::anyhow!(
+            "Found {} problem(s) with the ownership or permissions of our storage. \
+             Fix them, run with --fix-permissions to remove permissions that are too broad, \
+             or set application.storage_audit to \"warn\".",
+            n_problems
+        ));
+    }
+    Ok(())
+}
+
 /// Return true if we seem to be running as root.
 fn running_as_root() -> bool {
     #[cfg(target_family = "unix")]
@@ -245,9 +245,13 @@ impl ReconfigurableModule for Application {
         if config.proxy() != original.proxy() {
             warn!("Can't (yet) reconfigure proxy settings while arti is running.");
         }
-        if config.logging() != original.logging() {
+        // The redaction policy is the only logging setting we can change.
+        let mut logging = config.logging().clone();
+        logging.redaction = original.logging().redaction;
+        if &logging != original.logging() {
             warn!("Can't (yet) reconfigure logging settings while arti is running.");
         }
+        safelog::set_redaction_policy(&config.logging().redaction);
         if config.application().permit_debugging && !original.application().permit_debugging {
             warn!("Cannot disable application hardening when it has already been enabled.");
         }
@@ -1,85 +1,251 @@
 //! Experimental RPC support.
 
-use anyhow::Result;
-use arti_rpcserver::RpcMgr;
-use futures::task::SpawnExt;
+use anyhow::{anyhow, Result};
+use arti_rpcserver::{RpcCapabilityProfile, RpcListenerPolicy, RpcMgr};
+use futures::{task::SpawnExt, AsyncReadExt as _, StreamExt as _};
 use session::ArtiRpcSession;
-use std::{path::Path, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use arti_client::TorClient;
-use tor_rtcompat::Runtime;
+use tor_rtcompat::{general, NetStreamListener as _, Runtime};
+
+use crate::cfg::{is_local_pipe_name, RpcConfig, RpcListenerConfig};
 
 pub(crate) mod conntarget;
+mod logging;
+#[cfg(feature = "alloc-tags")]
+mod memory;
+#[cfg(all(windows, feature = "tokio"))]
+mod pipe;
 mod proxyinfo;
 mod session;
 
 pub(crate) use session::{RpcStateSender, RpcVisibleArtiState};
 
-cfg_if::cfg_if! {
-    if #[cfg(all(feature="tokio", not(target_os="windows")))] {
-        use tokio_crate::net::UnixListener ;
-        use tokio_util::compat::{TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt};
-    } else if #[cfg(all(feature="async-std", not(target_os="windows")))] {
-        use async_std::os::unix::net::UnixListener;
-    } else if #[cfg(target_os="windows")] {
-        compile_error!("Sorry, no windows support for RPC yet.");
-        // TODO RPC: Tokio has a named pipe API; AsyncStd should let us construct
-        // one via FromRawHandle.
-    } else {
-        compile_error!("You need to have tokio or async-std.");
+#[cfg(all(windows, feature = "tokio"))]
+use pipe::launch_named_pipe_listener;
+
+/// An address at which we can listen for RPC connections.
+#[derive(Clone, Debug)]
+pub(crate) enum RpcListenAddr {
+    /// A TCP or AF_UNIX socket.
+    Socket(general::SocketAddr),
+    /// A Windows named pipe, like `\\.\pipe\arti\SOCKET`.
+    NamedPipe(String),
+}
+
+/// A place where we have been told to listen for RPC connections.
+pub(crate) struct RpcListenerSpec {
+    /// The address to listen on.
+    addr: RpcListenAddr,
+    /// The rules for connections that arrive here.
+    policy: RpcListenerPolicy,
+    /// A file to which we should write a connect point for this listener, if any.
+    connect_point: Option<PathBuf>,
+}
+
+impl RpcListenerSpec {
+    /// Find every place that `config` tells us to listen for RPC connections.
+    ///
+    /// Makes sure that the directories for any AF_UNIX sockets and connect point files
+    /// exist with appropriate permissions, and removes any stale sockets.
+    pub(crate) fn from_config(
+        config: &RpcConfig,
+        mistrust: &fs_mistrust::Mistrust,
+    ) -> Result<Vec<Self>> {
+        let mut specs = Vec::new();
+
+        if let Some(path) = &config.rpc_listen {
+            let path = path.path()?;
+            // (On Windows, this is a named pipe by default.)
+            let spec = match path.to_str().filter(|name| is_local_pipe_name(name)) {
+                Some(name) => RpcListenerSpec {
+                    addr: RpcListenAddr::NamedPipe(name.to_owned()),
+                    policy: RpcListenerPolicy::named_pipe(RpcCapabilityProfile::Admin),
+                    connect_point: None,
+                },
+                None => RpcListenerSpec {
+                    addr: RpcListenAddr::Socket(
+                        tor_rtcompat::unix::SocketAddr::from_pathname(path)?.into(),
+                    ),
+                    policy: RpcListenerPolicy::default(),
+                    connect_point: None,
+                },
+            };
+            specs.push(spec);
+        }
+        for listener in &config.listeners {
+            specs.push(Self::from_listener_config(listener)?);
+        }
+
+        for spec in &specs {
+            if let RpcListenAddr::Socket(general::SocketAddr::Unix(addr)) = &spec.addr {
+                if let Some(path) = addr.as_pathname() {
+                    prepare_parent_dir(path, mistrust)?;
+                    // It's just a unix thing; if we leave this sitting around, binding to it won't
+                    // work right.  There is probably a better solution.
+                    if path.try_exists()? {
+                        std::fs::remove_file(path)?;
+                    }
+                }
+            }
+            if let Some(path) = &spec.connect_point {
+                prepare_parent_dir(path, mistrust)?;
+            }
+        }
+
+        Ok(specs)
+    }
+
+    /// Construct an `RpcListenerSpec` from the configuration for a single listener.
+    fn from_listener_config(config: &RpcListenerConfig) -> Result<Self> {
+        let profile = config.profile();
+        let policy = if config.is_unix() {
+            RpcListenerPolicy::unix_socket(profile)
+        } else if config.is_named_pipe() {
+            RpcListenerPolicy::named_pipe(profile)
+        } else {
+            RpcListenerPolicy::tcp_localhost(profile)
+        }
+        .with_deterministic_output(config.deterministic_output);
+        Ok(RpcListenerSpec {
+            addr: config.listen_addr()?,
+            policy,
+            connect_point: config
+                .connect_point
+                .as_ref()
+                .map(|p| p.path())
+                .transpose()?,
+        })
     }
 }
 
-/// Run an RPC listener task to accept incoming connections at the Unix
-/// socket address of `path`.
-pub(crate) fn launch_rpc_listener<R: Runtime>(
-    runtime: &R,
-    path: impl AsRef<Path>,
+/// Make sure that the parent directory of `path` exists,
+/// and that its permissions are acceptable to `mistrust`.
+fn prepare_parent_dir(path: &std::path::Path, mistrust: &fs_mistrust::Mistrust) -> Result<()> {
+    let parent = path
+        .parent()
+        .ok_or_else(|| anyhow!("No parent directory for {:?}", path))?;
+    mistrust.verifier().make_secure_dir(parent)?;
+    Ok(())
+}
+
+/// Create the [`RpcMgr`] that handles all of our RPC connections.
+pub(crate) fn new_rpc_mgr<R: Runtime>(
     client: TorClient<R>,
     rpc_state: Arc<RpcVisibleArtiState>,
 ) -> Result<Arc<RpcMgr>> {
-    // TODO RPC: there should be an error return instead.
-
-    // TODO RPC: Maybe the UnixListener functionality belongs in tor-rtcompat?
-    // But I certainly don't want to make breaking changes there if we can help
-    // it.
-    let listener = UnixListener::bind(path)?;
     let rpc_mgr = RpcMgr::new(move |auth| ArtiRpcSession::new(auth, &client, &rpc_state))?;
     // Register methods. Needed since TorClient is generic.
     //
     // TODO: If we accumulate a large number of generics like this, we should do this elsewhere.
     rpc_mgr.register_rpc_methods(TorClient::<R>::rpc_methods());
     rpc_mgr.register_rpc_methods(arti_rpcserver::rpc_methods::<R>());
+    Ok(rpc_mgr)
+}
+
+/// Run an RPC listener task to accept incoming connections at the address in `spec`.
+///
+/// If `spec` asks for one, writes a connect point file once we are listening.
+pub(crate) async fn launch_rpc_listener<R: Runtime>(
+    runtime: &R,
+    spec: RpcListenerSpec,
+    rpc_mgr: Arc<RpcMgr>,
+) -> Result<()> {
+    let RpcListenerSpec {
+        addr,
+        policy,
+        connect_point,
+    } = spec;
+    let addr = match addr {
+        RpcListenAddr::Socket(addr) => addr,
+        RpcListenAddr::NamedPipe(name) => {
+            return launch_named_pipe_listener(runtime, name, policy, connect_point, rpc_mgr);
+        }
+    };
+
+    let listener = runtime.listen(&addr).await?;
+    if let Some(path) = &connect_point {
+        write_connect_point(path, &socket_string(&listener.local_addr()?)?)?;
+    }
 
     let rt_clone = runtime.clone();
-    let rpc_mgr_clone = rpc_mgr.clone();
 
     // TODO: Using spawn in this way makes it hard to report whether we
     // succeeded or not. This is something we should fix when we refactor
     // our service-launching code.
-    runtime.spawn(async {
-        let result = run_rpc_listener(rt_clone, listener, rpc_mgr_clone).await;
+    runtime.spawn(async move {
+        let result = run_rpc_listener(rt_clone, listener, rpc_mgr, policy).await;
         if let Err(e) = result {
             tracing::warn!("RPC manager quit with an error: {}", e);
         }
     })?;
-    Ok(rpc_mgr)
+    Ok(())
+}
+
+/// Report that we can't listen on the named pipe `name` in this build.
+#[cfg(not(all(windows, feature = "tokio")))]
+#[allow(clippy::needless_pass_by_value)] // Same signature as the real one in `pipe`.
+fn launch_named_pipe_listener<R: Runtime>(
+    _runtime: &R,
+    name: String,
+    _policy: RpcListenerPolicy,
+    _connect_point: Option<PathBuf>,
+    _rpc_mgr: Arc<RpcMgr>,
+) -> Result<()> {
+    Err(anyhow!(
+        "Cannot listen for RPC connections on {:?}: named pipes are only supported on Windows, with the tokio runtime",
+        name
+    ))
+}
+
+/// Write a connect point file at `path`, telling clients how to reach a listener at `socket`.
+fn write_connect_point(path: &Path, socket: &str) -> Result<()> {
+    std::fs::write(path, connect_point_json(socket)?)?;
+    Ok(())
+}
+
+/// Return the form of `addr` that we use in connect points.
+fn socket_string(addr: &general::SocketAddr) -> Result<String> {
+    addr.try_to_string().ok_or_else(|| {
+        anyhow!(
+            "Cannot represent {} in a connect point",
+            addr.display_lossy()
+        )
+    })
+}
+
+/// Return the text of a connect point telling clients how to reach a listener at `socket`.
+///
+/// The format is described in `doc/dev/rpc-book/src/rpc-connect-sketch.md`.
+fn connect_point_json(socket: &str) -> Result<String> {
+    let connect_point = serde_json::json!({
+        "connect": {
+            "socket": socket,
+            // Every scheme we currently support is "inherent":
+            // clients need nothing more than the ability to connect.
+            "auth": "none",
+        }
+    });
+    Ok(serde_json::to_string_pretty(&connect_point)? + "\n")
 }
 
 /// Backend function to implement an RPC listener: runs in a loop.
 async fn run_rpc_listener<R: Runtime>(
     runtime: R,
-    listener: UnixListener,
+    listener: general::Listener,
     rpc_mgr: Arc<RpcMgr>,
+    policy: RpcListenerPolicy,
 ) -> Result<()> {
-    loop {
-        let (stream, _addr) = listener.accept().await?;
+    let mut incoming = listener.incoming();
+    while let Some(stream) = incoming.next().await {
+        let (stream, _addr) = stream?;
         // TODO RPC: Perhaps we should have rpcmgr hold the client reference?
-        let connection = rpc_mgr.new_connection();
-        let (input, output) = stream.into_split();
-
-        #[cfg(feature = "tokio")]
-        let (input, output) = (input.compat(), output.compat_write());
+        let connection = rpc_mgr.new_connection_with_policy(policy.clone());
+        let (input, output) = stream.split();
 
         runtime.spawn(async {
             let result = connection.run(input, output).await;
@@ -88,6 +254,7 @@ async fn run_rpc_listener<R: Runtime>(
             }
         })?;
     }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -106,6 +273,8 @@ mod test {
     #![allow(clippy::needless_pass_by_value)]
     //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
 
+    use super::*;
+
     #[test]
     fn rpc_method_names() {
         // We run this from a nice high level module, to ensure that as many method names as
@@ -117,4 +286,79 @@ mod test {
         }
         assert!(problems.is_empty());
     }
+
+    #[test]
+    fn connect_point() {
+        let addr: general::SocketAddr = "127.0.0.1:9180".parse().unwrap();
+        let socket = socket_string(&addr).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&connect_point_json(&socket).unwrap()).unwrap();
+        assert_eq!(json["connect"]["socket"], "inet:127.0.0.1:9180");
+        assert_eq!(json["connect"]["auth"], "none");
+
+        let json: serde_json::Value =
+            serde_json::from_str(&connect_point_json(r"pipe:\\.\pipe\arti\rpc").unwrap()).unwrap();
+        assert_eq!(json["connect"]["socket"], r"pipe:\\.\pipe\arti\rpc");
+    }
+
+    #[test]
+    fn listener_policies() {
+        let tcp = RpcListenerConfig::builder()
+            .address("127.0.0.1:9180")
+            .build()
+            .unwrap();
+        let spec = RpcListenerSpec::from_listener_config(&tcp).unwrap();
+        assert_eq!(
+            spec.policy,
+            RpcListenerPolicy::tcp_localhost(RpcCapabilityProfile::Observer)
+        );
+
+        let unix = RpcListenerConfig::builder()
+            .address("unix:/var/run/arti/rpc_socket")
+            .build()
+            .unwrap();
+        let spec = RpcListenerSpec::from_listener_config(&unix).unwrap();
+        assert_eq!(
+            spec.policy,
+            RpcListenerPolicy::unix_socket(RpcCapabilityProfile::Admin)
+        );
+
+        let pipe = RpcListenerConfig::builder()
+            .address(r"pipe:\\.\pipe\arti\rpc")
+            .build()
+            .unwrap();
+        let spec = RpcListenerSpec::from_listener_config(&pipe).unwrap();
+        assert!(
+            matches!(&spec.addr, RpcListenAddr::NamedPipe(name) if name == r"\\.\pipe\arti\rpc")
+        );
+        assert_eq!(
+            spec.policy,
+            RpcListenerPolicy::named_pipe(RpcCapabilityProfile::Admin)
+        );
+
+        let admin_tcp = RpcListenerConfig::builder()
+            .address("[::1]:9180")
+            .profile(Some(RpcCapabilityProfile::Admin))
+            .build()
+            .unwrap();
+        assert_eq!(admin_tcp.profile(), RpcCapabilityProfile::Admin);
+
+        let deterministic = RpcListenerConfig::builder()
+            .address("127.0.0.1:9180")
+            .deterministic_output(true)
+            .build()
+            .unwrap();
+        let spec = RpcListenerSpec::from_listener_config(&deterministic).unwrap();
+        assert!(spec.policy.deterministic_output());
+
+        for bad in [
+            "0.0.0.0:9180",
+            "192.0.2.1:9180",
+            "wombat:9180",
+            r"pipe:\\server\pipe\arti",
+            r"pipe:\\.\pipe\",
+        ] {
+            assert!(RpcListenerConfig::builder().address(bad).build().is_err());
+        }
+    }
 }
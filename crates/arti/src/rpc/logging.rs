@@ -0,0 +1,64 @@
+//! Implement RPC functionality for adjusting how our logs are redacted.
+
+use safelog::RedactionPolicy;
+use std::{convert::Infallible, sync::Arc};
+use tor_rpcbase::{self as rpc};
+
+use super::session::ArtiRpcSession;
+
+/// Get the policy that says how each category of sensitive information
+/// is redacted in our logs.
+///
+/// This is initially the `logging.redaction` section of the configuration.
+///
+/// The policy doesn't apply when `logging.log_sensitive_information` is set:
+/// in that case, nothing is redacted.
+#[derive(Debug, serde::Deserialize, derive_deftly::Deftly)]
+#[derive_deftly(rpc::DynMethod)]
+#[deftly(rpc(method_name = "arti:get_log_redaction"))]
+struct GetLogRedaction {}
+
+/// Replace the policy that says how each category of sensitive information
+/// is redacted in our logs.
+///
+/// The new policy lasts until Arti exits, or until its configuration is reloaded.
+///
+/// Any category missing from the policy is always redacted.
+#[derive(Debug, serde::Deserialize, derive_deftly::Deftly)]
+#[derive_deftly(rpc::DynMethod)]
+#[deftly(rpc(method_name = "arti:set_log_redaction"))]
+struct SetLogRedaction {
+    /// The new policy.
+    redaction: RedactionPolicy,
+}
+
+impl rpc::RpcMethod for GetLogRedaction {
+    type Output = RedactionPolicy;
+    type Update = rpc::NoUpdates;
+}
+
+impl rpc::RpcMethod for SetLogRedaction {
+    type Output = rpc::Nil;
+    type Update = rpc::NoUpdates;
+}
+
+/// Implementation for GetLogRedaction on ArtiRpcSession.
+async fn rpc_session_get_log_redaction(
+    _session: Arc<ArtiRpcSession>,
+    _method: Box<GetLogRedaction>,
+    _ctx: Arc<dyn rpc::Context>,
+) -> Result<RedactionPolicy, Infallible> {
+    Ok(safelog::redaction_policy())
+}
+rpc::static_rpc_invoke_fn! {rpc_session_get_log_redaction;}
+
+/// Implementation for SetLogRedaction on ArtiRpcSession.
+async fn rpc_session_set_log_redaction(
+    _session: Arc<ArtiRpcSession>,
+    method: Box<SetLogRedaction>,
+    _ctx: Arc<dyn rpc::Context>,
+) -> Result<rpc::Nil, Infallible> {
+    safelog::set_redaction_policy(&method.redaction);
+    Ok(rpc::NIL)
+}
+rpc::static_rpc_invoke_fn! {rpc_session_set_log_redaction;}
@@ -0,0 +1,85 @@
+//! Implement RPC functionality for reporting which subsystems are using our heap.
+
+use std::sync::Arc;
+use tor_basic_utils::alloc_tag;
+use tor_error::{ErrorKind, HasKind};
+use tor_rpcbase::{self as rpc};
+
+use super::session::ArtiRpcSession;
+
+/// Report how much heap memory is attributed to each of Arti's subsystems.
+///
+/// Unlike the memory quota system, which only knows about our queues,
+/// this covers every heap allocation;
+/// but only a few subsystems are tagged, so most memory is reported as `untagged`.
+///
+/// This method is only available when Arti was built with the `alloc-tags` feature.
+#[derive(Debug, serde::Deserialize, derive_deftly::Deftly)]
+#[derive_deftly(rpc::DynMethod)]
+#[deftly(rpc(method_name = "arti:x_get_memory_detail"))]
+struct GetMemoryDetail {}
+
+impl rpc::RpcMethod for GetMemoryDetail {
+    type Output = MemoryDetail;
+    type Update = rpc::NoUpdates;
+}
+
+/// The reply to a [`GetMemoryDetail`] request.
+#[derive(serde::Serialize, Clone, Debug)]
+struct MemoryDetail {
+    /// The usage of each subsystem, starting with the untagged allocations.
+    subsystems: Vec<SubsystemDetail>,
+}
+
+/// The heap usage attributed to a single subsystem, as reported over RPC.
+#[derive(serde::Serialize, Clone, Debug)]
+struct SubsystemDetail {
+    /// The name of the subsystem, or `untagged`.
+    name: &'static str,
+    /// The number of bytes currently allocated.
+    live_bytes: usize,
+    /// The number of allocations made since Arti started.
+    total_allocs: u64,
+}
+
+impl From<alloc_tag::SubsystemUsage> for SubsystemDetail {
+    fn from(u: alloc_tag::SubsystemUsage) -> Self {
+        Self {
+            name: u.subsystem.map_or("untagged", alloc_tag::Subsystem::as_str),
+            live_bytes: u.live_bytes,
+            total_allocs: u.total_allocs,
+        }
+    }
+}
+
+/// An error encountered while reporting our heap usage.
+#[derive(Clone, Debug, thiserror::Error)]
+enum GetMemoryDetailError {
+    /// We aren't using the tagging allocator.
+    ///
+    /// (This can happen if Arti is being used as a library
+    /// by a program with its own global allocator.)
+    #[error("Heap allocations are not being tagged")]
+    NotTagging,
+}
+impl HasKind for GetMemoryDetailError {
+    fn kind(&self) -> ErrorKind {
+        use GetMemoryDetailError as E;
+        match self {
+            E::NotTagging => ErrorKind::FeatureDisabled,
+        }
+    }
+}
+
+/// Implementation for GetMemoryDetail on ArtiRpcSession.
+async fn rpc_session_get_memory_detail(
+    _session: Arc<ArtiRpcSession>,
+    _method: Box<GetMemoryDetail>,
+    _ctx: Arc<dyn rpc::Context>,
+) -> Result<MemoryDetail, GetMemoryDetailError> {
+    let usage = alloc_tag::usage().ok_or(GetMemoryDetailError::NotTagging)?;
+    Ok(MemoryDetail {
+        subsystems: usage.into_iter().map(SubsystemDetail::from).collect(),
+    })
+}
+rpc::static_rpc_invoke_fn! {rpc_session_get_memory_detail;}
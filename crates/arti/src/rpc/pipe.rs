@@ -0,0 +1,95 @@
+//! RPC listeners on Windows named pipes.
+
+use anyhow::{anyhow, Result};
+use arti_rpcserver::{RpcListenerPolicy, RpcMgr};
+use futures::{task::SpawnExt as _, AsyncReadExt as _};
+use std::{path::PathBuf, sync::Arc};
+use tokio_crate::net::windows::named_pipe::{NamedPipeServer, ServerOptions};
+use tokio_util::compat::TokioAsyncReadCompatExt as _;
+
+use tor_rtcompat::Runtime;
+
+use super::write_connect_point;
+
+/// Run an RPC listener task to accept incoming connections on the named pipe `name`.
+///
+/// If `connect_point` is set, writes a connect point file there once we are listening.
+///
+/// Each instance of a named pipe serves a single client,
+/// so we create a new instance every time a client opens the previous one.
+/// We give the instances the default security descriptor,
+/// which only lets administrators and the user running Arti open them for writing.
+pub(crate) fn launch_named_pipe_listener<R: Runtime>(
+    runtime: &R,
+    name: String,
+    policy: RpcListenerPolicy,
+    connect_point: Option<PathBuf>,
+    rpc_mgr: Arc<RpcMgr>,
+) -> Result<()> {
+    // Pipe instances are driven by tokio's reactor, so we can't use them anywhere else.
+    if tokio_crate::runtime::Handle::try_current().is_err() {
+        return Err(anyhow!(
+            "Cannot listen for RPC connections on {:?}: not running on a tokio runtime",
+            name
+        ));
+    }
+
+    // (If some other process already has a pipe with this name,
+    // this fails, rather than letting us share the name with it.)
+    let server = pipe_options()
+        .first_pipe_instance(true)
+        .create(&name)
+        .map_err(|e| anyhow!("Cannot create named pipe {:?}: {}", name, e))?;
+    if let Some(path) = &connect_point {
+        write_connect_point(path, &format!("pipe:{}", name))?;
+    }
+
+    let rt_clone = runtime.clone();
+
+    // TODO: As in `launch_rpc_listener`, using spawn in this way
+    // makes it hard to report whether we succeeded or not.
+    runtime.spawn(async move {
+        let result = run_named_pipe_listener(rt_clone, name, server, rpc_mgr, policy).await;
+        if let Err(e) = result {
+            tracing::warn!("RPC manager quit with an error: {}", e);
+        }
+    })?;
+    Ok(())
+}
+
+/// Return the options that we use for every instance of a named pipe.
+fn pipe_options() -> ServerOptions {
+    let mut options = ServerOptions::new();
+    // `inherent:named_pipe` authentication only makes sense for local clients.
+    options.reject_remote_clients(true);
+    options
+}
+
+/// Backend function to implement a named pipe RPC listener: runs in a loop.
+///
+/// `server` is the first instance of the pipe called `name`.
+async fn run_named_pipe_listener<R: Runtime>(
+    runtime: R,
+    name: String,
+    mut server: NamedPipeServer,
+    rpc_mgr: Arc<RpcMgr>,
+    policy: RpcListenerPolicy,
+) -> Result<()> {
+    loop {
+        server.connect().await?;
+        // Make the next instance before we hand off this one,
+        // so that there is always an instance for clients to open.
+        let next = pipe_options().create(&name)?;
+        let stream = std::mem::replace(&mut server, next);
+
+        let connection = rpc_mgr.new_connection_with_policy(policy.clone());
+        let (input, output) = stream.compat().split();
+
+        runtime.spawn(async {
+            let result = connection.run(input, output).await;
+            if let Err(e) = result {
+                tracing::warn!("RPC session ended with an error: {}", e);
+            }
+        })?;
+    }
+}
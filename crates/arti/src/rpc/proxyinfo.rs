@@ -24,6 +24,12 @@ pub(super) enum ProxyListener {
         /// The address at which we're listening for SOCKS connections.
         tcp_address: Option<SocketAddr>,
     },
+    /// A DNS resolver.
+    #[serde(rename = "dns")]
+    Dns {
+        /// The address at which we're listening for DNS requests.
+        udp_address: Option<SocketAddr>,
+    },
 }
 
 /// A representation of the set of proxy addresses available from the RPC API.
@@ -32,7 +38,8 @@ pub(super) enum ProxyListener {
 pub(super) struct ProxyInfo {
     /// A list of the supported proxies.
     ///
-    /// (So far, only SOCKS proxies are listed, but other kinds may be listed in the future.)
+    /// (So far, only SOCKS proxies and DNS resolvers are listed,
+    /// but other kinds may be listed in the future.)
     pub(super) proxies: Vec<Proxy>,
 }
 
@@ -52,7 +52,7 @@ pub(super) struct ArtiRpcSession {
 pub(crate) struct RpcVisibleArtiState {
     /// A `ProxyInfo` that we hand out when asked to list our proxy ports.
     ///
-    /// Right now it only lists Socks; in the future it may list more.
+    /// Right now it lists SOCKS proxies and DNS resolvers; in the future it may list more.
     proxy_info: postage::watch::Receiver<ProxyInfoState>,
 }
 
@@ -62,6 +62,9 @@ pub(crate) struct RpcVisibleArtiState {
 pub(crate) struct RpcStateSender {
     /// Sender for setting our list of proxy ports.
     proxy_info_sender: DropNotifyWatchSender<ProxyInfoState>,
+
+    /// The addresses of our DNS resolvers, to list along with our SOCKS listeners.
+    dns_listeners: Vec<SocketAddr>,
 }
 
 impl ArtiRpcSession {
@@ -111,7 +114,10 @@ impl RpcVisibleArtiState {
         let proxy_info_sender = DropNotifyWatchSender::new(proxy_info_sender);
         (
             Arc::new(Self { proxy_info }),
-            RpcStateSender { proxy_info_sender },
+            RpcStateSender {
+                proxy_info_sender,
+                dns_listeners: Vec::new(),
+            },
         )
     }
 
@@ -134,18 +140,31 @@ impl RpcVisibleArtiState {
 }
 
 impl RpcStateSender {
+    /// Set the list of DNS resolver addresses on this state.
+    ///
+    /// They are not visible until [`set_socks_listeners`](Self::set_socks_listeners)
+    /// is called, so this method must be called before that one.
+    pub(crate) fn set_dns_listeners(&mut self, addrs: &[SocketAddr]) {
+        self.dns_listeners = addrs.to_vec();
+    }
+
     /// Set the list of socks listener addresses on this state.
     ///
     /// This method may only be called once per state.
     pub(crate) fn set_socks_listeners(&mut self, addrs: &[SocketAddr]) {
+        let socks = addrs.iter().map(|a| proxyinfo::ProxyListener::Socks5 {
+            tcp_address: Some(*a),
+        });
+        let dns = self
+            .dns_listeners
+            .iter()
+            .map(|a| proxyinfo::ProxyListener::Dns {
+                udp_address: Some(*a),
+            });
         let info = ProxyInfo {
-            proxies: addrs
-                .iter()
-                .map(|a| proxyinfo::Proxy {
-                    listener: proxyinfo::ProxyListener::Socks5 {
-                        tcp_address: Some(*a),
-                    },
-                })
+            proxies: socks
+                .chain(dns)
+                .map(|listener| proxyinfo::Proxy { listener })
                 .collect(),
         };
         *self.proxy_info_sender.borrow_mut() = ProxyInfoState::Set(Arc::new(info));
@@ -178,11 +197,27 @@ mod test {
         MockRuntime::test_with_various(|rt| async move {
             let (state, mut sender) = RpcVisibleArtiState::new();
             let _task = rt.clone().spawn_with_handle(async move {
+                sender.set_dns_listeners(&["127.0.0.1:53".parse().unwrap()]);
                 sender.set_socks_listeners(&["8.8.4.4:99".parse().unwrap()]);
                 sender // keep sender alive
             });
 
             let value = state.get_proxy_info().await;
+            assert_eq!(
+                value.as_ref().unwrap().proxies,
+                vec![
+                    proxyinfo::Proxy {
+                        listener: proxyinfo::ProxyListener::Socks5 {
+                            tcp_address: Some("8.8.4.4:99".parse().unwrap())
+                        }
+                    },
+                    proxyinfo::Proxy {
+                        listener: proxyinfo::ProxyListener::Dns {
+                            udp_address: Some("127.0.0.1:53".parse().unwrap())
+                        }
+                    },
+                ]
+            );
 
             // At this point, we've returned once, so this will test that we get a fresh answer even
             // if we already set the inner value.
@@ -674,6 +674,7 @@ where
     // We need to send an error. See what kind it is.
     let status = match error {
         EK::RemoteNetworkFailed => S::TTL_EXPIRED,
+        EK::ForbiddenClearnetTarget => S::NOT_ALLOWED,
 
         #[cfg(feature = "onion-service-client")]
         EK::OnionServiceNotFound => S::HS_DESC_NOT_FOUND,
@@ -1,9 +1,23 @@
 //! Arti CLI subcommands.
 
+#[cfg(feature = "bridge-client")]
+pub(crate) mod bridges;
+
+pub(crate) mod connect;
+
 #[cfg(feature = "onion-service-service")]
 pub(crate) mod hss;
 
 #[cfg(feature = "hsc")]
 pub(crate) mod hsc;
 
+#[cfg(all(feature = "keymgr", feature = "experimental-api"))]
+pub(crate) mod keys;
+
+#[cfg(feature = "experimental-api")]
+pub(crate) mod netdir;
+
 pub(crate) mod proxy;
+
+#[cfg(feature = "rpc")]
+pub(crate) mod status;
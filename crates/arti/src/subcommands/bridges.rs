@@ -0,0 +1,78 @@
+//! The `bridges` subcommand.
+
+use std::io::Read as _;
+
+use anyhow::Context;
+use arti_client::config::torrc::parse_torrc_bridges;
+use arti_client::config::BridgesConfigBuilder;
+use clap::{ArgMatches, Args, FromArgMatches, Parser, Subcommand};
+use serde::Serialize;
+
+use crate::Result;
+
+/// The bridges subcommands the arti CLI will be augmented with.
+#[derive(Parser, Debug)]
+pub(crate) enum BridgesSubcommands {
+    /// Manage bridge configuration.
+    #[command(subcommand)]
+    Bridges(BridgesSubcommand),
+}
+
+/// The `bridges` subcommands.
+#[derive(Debug, Subcommand)]
+pub(crate) enum BridgesSubcommand {
+    /// Convert the bridges in a C Tor torrc file into Arti configuration.
+    ///
+    /// The `Bridge`, `UseBridges` and `ClientTransportPlugin` options are converted,
+    /// and the result is printed as TOML, ready to be added to `arti.toml`
+    /// or to a file in your `arti.d` configuration directory.
+    #[command(arg_required_else_help = true)]
+    ImportTorrc(ImportTorrcArgs),
+}
+
+/// The arguments of the [`ImportTorrc`](BridgesSubcommand::ImportTorrc) subcommand.
+#[derive(Debug, Clone, Args)]
+pub(crate) struct ImportTorrcArgs {
+    /// The torrc file to read. Use - to read from stdin
+    #[arg(name = "FILE")]
+    file: String,
+}
+
+/// The configuration we print: a `[bridges]` section.
+#[derive(Serialize)]
+struct ImportedConfig {
+    /// The imported bridges and transports.
+    bridges: BridgesConfigBuilder,
+}
+
+/// Run the `bridges` subcommand.
+pub(crate) fn run(bridges_matches: &ArgMatches) -> Result<()> {
+    let subcommand = BridgesSubcommand::from_arg_matches(bridges_matches)
+        .expect("Could not parse bridges subcommand");
+
+    match subcommand {
+        BridgesSubcommand::ImportTorrc(args) => run_import_torrc(&args),
+    }
+}
+
+/// Run the `bridges import-torrc` subcommand.
+fn run_import_torrc(args: &ImportTorrcArgs) -> Result<()> {
+    let text = if args.file == "-" {
+        let mut text = String::new();
+        std::io::stdin()
+            .read_to_string(&mut text)
+            .context("Failed to read torrc from stdin")?;
+        text
+    } else {
+        std::fs::read_to_string(&args.file)
+            .with_context(|| format!("Failed to read {}", args.file))?
+    };
+
+    let bridges = parse_torrc_bridges(&text)
+        .with_context(|| format!("Failed to import bridges from {}", args.file))?;
+    let toml = toml::to_string(&ImportedConfig { bridges })
+        .context("Failed to serialize bridge configuration")?;
+    print!("{toml}");
+
+    Ok(())
+}
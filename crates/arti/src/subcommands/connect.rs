@@ -0,0 +1,62 @@
+//! The `connect` subcommand.
+
+use anyhow::Context;
+use arti_client::{ConnectUri, TorClientConfig};
+use clap::{ArgMatches, Args, FromArgMatches, Parser};
+use tor_rtcompat::Runtime;
+use tracing::info;
+
+use crate::{one_shot, Result};
+
+/// The connect subcommand the arti CLI will be augmented with.
+#[derive(Parser, Debug)]
+pub(crate) enum ConnectSubcommands {
+    /// Connect to a host over Tor, and relay the connection to stdin and stdout.
+    ///
+    /// This exits when the connection is closed,
+    /// so it can be used as an ssh ProxyCommand, or instead of ncat:
+    /// for example, `ssh -o ProxyCommand='arti connect tor+tcp://%h:%p' example.com`.
+    #[command(arg_required_else_help = true)]
+    Connect(ConnectArgs),
+}
+
+/// The arguments of the [`Connect`](ConnectSubcommands::Connect) subcommand.
+#[derive(Debug, Clone, Args)]
+pub(crate) struct ConnectArgs {
+    /// The URI of the host to connect to.
+    ///
+    /// This is either `tor+tcp://HOST:PORT` or `tor+onion://NAME.onion:PORT`.
+    /// IPv6 addresses must be in brackets, as in `tor+tcp://[2001:db8::1]:22`.
+    ///
+    /// The following options can be given in the query string:
+    /// `ip=ipv4-only|ipv6-only|ipv4-preferred|ipv6-preferred`,
+    /// `exit_country=CC`, and `optimistic=true|false`
+    /// (for example, `tor+tcp://example.com:22?exit_country=de&ip=ipv6-preferred`).
+    #[arg(value_name = "URI")]
+    uri: String,
+}
+
+/// Run the `connect` subcommand.
+pub(crate) fn run<R: Runtime>(
+    runtime: R,
+    connect_matches: &ArgMatches,
+    client_config: TorClientConfig,
+) -> Result<()> {
+    let args =
+        ConnectArgs::from_arg_matches(connect_matches).expect("Could not parse connect subcommand");
+    let uri: ConnectUri = args
+        .uri
+        .parse()
+        .with_context(|| format!("invalid URI {}", args.uri))?;
+    let (target, prefs) = uri.into_parts();
+
+    info!(
+        "Starting Arti {}, connecting to {} ...",
+        env!("CARGO_PKG_VERSION"),
+        target
+    );
+
+    runtime
+        .clone()
+        .block_on(one_shot::run(runtime, target, prefs, client_config))
+}
@@ -3,12 +3,19 @@
 use crate::{Result, TorClient};
 
 use anyhow::{anyhow, Context};
-use arti_client::{HsClientDescEncKey, HsId, InertTorClient, KeystoreSelector, TorClientConfig};
+use arti_client::{
+    HsClientCredential, HsClientDescEncKey, HsId, InertTorClient, KeystoreSelector, TorClientConfig,
+};
 use clap::{ArgMatches, Args, FromArgMatches, Parser, Subcommand, ValueEnum};
+use tor_basic_utils::PathExt as _;
 use tor_rtcompat::Runtime;
 
-use std::fs::OpenOptions;
+use std::fs::{self, OpenOptions};
 use std::io;
+use std::path::{Path, PathBuf};
+
+/// The extension of the files in C Tor's `ClientOnionAuthDir`.
+const C_TOR_AUTH_PRIVATE_EXTENSION: &str = "auth_private";
 
 /// The hsc subcommands the arti CLI will be augmented with.
 #[derive(Parser, Debug)]
@@ -31,6 +38,17 @@ pub(crate) enum HscSubcommand {
     /// Key management subcommands.
     #[command(subcommand)]
     Key(KeySubcommand),
+    /// Import a service discovery credential for connecting
+    /// to a service running in restricted discovery mode.
+    ///
+    /// Accepts Arti credential files, and C Tor `.auth_private` files.
+    /// Given a directory (such as C Tor's `ClientOnionAuthDir`),
+    /// imports every `.auth_private` file in it.
+    ///
+    /// A running Arti uses imported credentials from its next connection
+    /// to the service: there is no need to restart it.
+    #[command(arg_required_else_help = true)]
+    ImportCred(ImportCredArgs),
 }
 
 #[derive(Debug, Subcommand)]
@@ -146,6 +164,17 @@ pub(crate) struct RemoveKeyArgs {
     force: bool,
 }
 
+/// The arguments of the [`ImportCred`](HscSubcommand::ImportCred) subcommand.
+#[derive(Debug, Clone, Args)]
+pub(crate) struct ImportCredArgs {
+    /// The credential file, or directory of `.auth_private` files, to import.
+    path: PathBuf,
+
+    /// Do not prompt before replacing an existing key.
+    #[arg(long, short)]
+    force: bool,
+}
+
 /// Run the `hsc` subcommand.
 pub(crate) fn run<R: Runtime>(
     runtime: R,
@@ -156,9 +185,12 @@ pub(crate) fn run<R: Runtime>(
 
     let subcommand =
         HscSubcommand::from_arg_matches(hsc_matches).expect("Could not parse hsc subcommand");
-    let client = TorClient::with_runtime(runtime)
-        .config(config.clone())
-        .create_inert()?;
+    let client_builder = TorClient::with_runtime(runtime).config(config.clone());
+    #[cfg(feature = "encrypted-keystore")]
+    let client_builder = client_builder.keystore_passphrase_prompt(std::sync::Arc::new(
+        crate::passphrase::TerminalPassphrasePrompt,
+    ));
+    let client = client_builder.create_inert()?;
 
     match subcommand {
         HscSubcommand::GetKey(args) => {
@@ -170,6 +202,7 @@ pub(crate) fn run<R: Runtime>(
             }
         }
         HscSubcommand::Key(subcommand) => run_key(subcommand, &client),
+        HscSubcommand::ImportCred(args) => import_credentials(&args, &client),
     }
 }
 
@@ -288,6 +321,70 @@ fn remove_service_discovery_key(args: &RemoveKeyArgs, client: &InertTorClient) -
     Ok(())
 }
 
+/// Run the `hsc import-cred` subcommand.
+fn import_credentials(args: &ImportCredArgs, client: &InertTorClient) -> Result<()> {
+    let files = if args.path.is_dir() {
+        let mut files = vec![];
+        for entry in fs::read_dir(&args.path)
+            .with_context(|| format!("could not read {}", args.path.display_lossy()))?
+        {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|ext| ext == C_TOR_AUTH_PRIVATE_EXTENSION)
+            {
+                files.push(path);
+            }
+        }
+        if files.is_empty() {
+            return Err(anyhow!(
+                "no .{C_TOR_AUTH_PRIVATE_EXTENSION} files found in {}",
+                args.path.display_lossy()
+            ));
+        }
+        files.sort();
+        files
+    } else {
+        vec![args.path.clone()]
+    };
+
+    for file in files {
+        import_credential(&file, args.force, client)?;
+    }
+
+    Ok(())
+}
+
+/// Import the service discovery credential in `file`.
+fn import_credential(file: &Path, force: bool, client: &InertTorClient) -> Result<()> {
+    let text = fs::read_to_string(file)
+        .with_context(|| format!("could not read {}", file.display_lossy()))?;
+    let cred = HsClientCredential::parse(&text)
+        .with_context(|| format!("could not parse credential in {}", file.display_lossy()))?;
+    let hsid = cred.hsid();
+    let name = match cred.nickname() {
+        Some(nickname) => format!("{hsid} ({nickname})"),
+        None => hsid.to_string(),
+    };
+
+    if client.get_service_discovery_key(hsid)?.is_some() {
+        let msg = format!("replace existing client restricted discovery key for {name}?");
+        if !force && !prompt(&msg)? {
+            return Ok(());
+        }
+        client.remove_service_discovery_key(KeystoreSelector::default(), hsid)?;
+    }
+
+    let _key = client.insert_service_discovery_key(
+        KeystoreSelector::default(),
+        hsid,
+        cred.into_secret(),
+    )?;
+    println!("Imported client restricted discovery key for {name}");
+
+    Ok(())
+}
+
 /// Prompt the user to confirm by typing yes or no.
 ///
 /// Loops until the user confirms or declines,
@@ -0,0 +1,412 @@
+//! The `keys` subcommand.
+
+use crate::{Result, TorClient};
+
+use anyhow::{anyhow, Context};
+use arti_client::{
+    ArtiPath, ConflictPolicy, CopyOutcome, InertTorClient, KeyBundle, KeyPathPattern, KeyType,
+    KeystoreId, TorClientConfig,
+};
+use clap::{ArgMatches, Args, FromArgMatches, Parser, Subcommand, ValueEnum};
+use tor_rtcompat::Runtime;
+use zeroize::Zeroizing;
+
+use std::fs::OpenOptions;
+use std::io::{self, Read as _, Write as _};
+
+/// The keys subcommands the arti CLI will be augmented with.
+#[derive(Parser, Debug)]
+pub(crate) enum KeysSubcommands {
+    /// Manage the keys in the configured keystores.
+    #[command(subcommand)]
+    Keys(KeysSubcommand),
+}
+
+/// The `keys` subcommands.
+#[derive(Debug, Subcommand)]
+pub(crate) enum KeysSubcommand {
+    /// Copy all the keys from one keystore to another.
+    ///
+    /// This can be used to migrate keys between different kinds of keystore,
+    /// for instance from a C Tor keystore to the native Arti one.
+    /// Both keystores must be configured.
+    #[command(arg_required_else_help = true)]
+    Migrate(MigrateArgs),
+
+    /// Export some keys to a bundle of OpenSSH keys.
+    ///
+    /// The secret keys in the bundle are not encrypted: handle it with care.
+    #[command(arg_required_else_help = true)]
+    Export(ExportArgs),
+
+    /// Import the keys in a bundle created by `arti keys export`.
+    #[command(arg_required_else_help = true)]
+    Import(ImportArgs),
+
+    /// Export a single key as a standard OpenSSH key file.
+    ///
+    /// Secret keys are not encrypted: handle the file with care.
+    #[command(arg_required_else_help = true)]
+    ExportOpenssh(ExportOpensshArgs),
+
+    /// Import a single OpenSSH key file, such as one generated by `ssh-keygen -t ed25519`.
+    #[command(arg_required_else_help = true)]
+    ImportOpenssh(ImportOpensshArgs),
+}
+
+/// What to do when the destination keystore already has a key.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+enum OnConflict {
+    /// Keep the key already in the destination keystore.
+    #[default]
+    Skip,
+    /// Replace the key already in the destination keystore.
+    Overwrite,
+    /// Don't copy any keys.
+    Fail,
+}
+
+impl From<OnConflict> for ConflictPolicy {
+    fn from(on_conflict: OnConflict) -> Self {
+        match on_conflict {
+            OnConflict::Skip => ConflictPolicy::Skip,
+            OnConflict::Overwrite => ConflictPolicy::Overwrite,
+            OnConflict::Fail => ConflictPolicy::Fail,
+        }
+    }
+}
+
+/// The arguments of the [`Migrate`](KeysSubcommand::Migrate) subcommand.
+#[derive(Debug, Clone, Args)]
+pub(crate) struct MigrateArgs {
+    /// The ID of the keystore to copy the keys from.
+    #[arg(long, value_name = "KEYSTORE_ID")]
+    from: KeystoreId,
+
+    /// The ID of the keystore to copy the keys to.
+    ///
+    /// The native Arti keystore has the ID "arti".
+    #[arg(long, value_name = "KEYSTORE_ID", default_value = "arti")]
+    to: KeystoreId,
+
+    /// Only report what would be copied: don't write anything.
+    #[arg(long)]
+    dry_run: bool,
+
+    /// What to do when the destination keystore already has a key.
+    #[arg(long, default_value_t = OnConflict::Skip, value_enum)]
+    on_conflict: OnConflict,
+}
+
+/// The arguments of the [`Export`](KeysSubcommand::Export) subcommand.
+#[derive(Debug, Clone, Args)]
+pub(crate) struct ExportArgs {
+    /// The ID of the keystore to export the keys from.
+    #[arg(long, value_name = "KEYSTORE_ID", default_value = "arti")]
+    keystore: KeystoreId,
+
+    /// Which keys to export.
+    #[command(flatten)]
+    keys: ExportedKeys,
+
+    /// Write the bundle to FILE. Use - to write to stdout
+    #[arg(long, value_name = "FILE")]
+    output: String,
+
+    /// Whether to overwrite the output file if it already exists
+    #[arg(long)]
+    overwrite: bool,
+}
+
+/// The keys to export.
+#[derive(Debug, Clone, Args)]
+#[group(required = true, multiple = false)]
+pub(crate) struct ExportedKeys {
+    /// Export the keys whose paths match PATTERN
+    /// (for instance, `hss/allium-cepa/ks_hs_id`).
+    #[arg(long, value_name = "PATTERN")]
+    path: Option<String>,
+
+    /// Export all the keys of the onion service with the specified nickname.
+    #[arg(long, value_name = "NICKNAME")]
+    service: Option<String>,
+}
+
+/// The arguments of the [`Import`](KeysSubcommand::Import) subcommand.
+#[derive(Debug, Clone, Args)]
+pub(crate) struct ImportArgs {
+    /// The ID of the keystore to import the keys into.
+    #[arg(long, value_name = "KEYSTORE_ID", default_value = "arti")]
+    keystore: KeystoreId,
+
+    /// What to do when the keystore already has a key.
+    #[arg(long, default_value_t = OnConflict::Skip, value_enum)]
+    on_conflict: OnConflict,
+
+    /// The bundle to import. Use - to read from stdin
+    #[arg(value_name = "FILE")]
+    input: String,
+}
+
+/// The arguments of the [`ExportOpenssh`](KeysSubcommand::ExportOpenssh) subcommand.
+#[derive(Debug, Clone, Args)]
+pub(crate) struct ExportOpensshArgs {
+    /// The ID of the keystore to export the key from.
+    #[arg(long, value_name = "KEYSTORE_ID", default_value = "arti")]
+    keystore: KeystoreId,
+
+    /// The path of the key (for instance, `hss/allium-cepa/ks_hs_id`).
+    #[arg(long, value_name = "ARTI_PATH")]
+    path: String,
+
+    /// The type of the key, as the extension of its file in the Arti keystore
+    /// (for instance, `ed25519_expanded_private`).
+    #[arg(long, value_name = "TYPE")]
+    key_type: String,
+
+    /// Write the key to FILE. Use - to write to stdout
+    #[arg(long, value_name = "FILE")]
+    output: String,
+
+    /// Whether to overwrite the output file if it already exists
+    #[arg(long)]
+    overwrite: bool,
+}
+
+/// The arguments of the [`ImportOpenssh`](KeysSubcommand::ImportOpenssh) subcommand.
+#[derive(Debug, Clone, Args)]
+pub(crate) struct ImportOpensshArgs {
+    /// The ID of the keystore to import the key into.
+    #[arg(long, value_name = "KEYSTORE_ID", default_value = "arti")]
+    keystore: KeystoreId,
+
+    /// The path to store the key under (for instance, `hss/allium-cepa/ks_hs_id`).
+    #[arg(long, value_name = "ARTI_PATH")]
+    path: String,
+
+    /// The type to store the key as, as the extension of its file in the Arti keystore
+    /// (for instance, `ed25519_expanded_private`).
+    ///
+    /// The key in FILE must be of this type,
+    /// except that an ed25519 private key can be stored as `ed25519_expanded_private`.
+    #[arg(long, value_name = "TYPE")]
+    key_type: String,
+
+    /// What to do when the keystore already has the key.
+    #[arg(long, default_value_t = OnConflict::Skip, value_enum)]
+    on_conflict: OnConflict,
+
+    /// The key to import. Use - to read from stdin
+    #[arg(value_name = "FILE")]
+    input: String,
+}
+
+/// Run the `keys` subcommand.
+pub(crate) fn run<R: Runtime>(
+    runtime: R,
+    keys_matches: &ArgMatches,
+    config: &TorClientConfig,
+) -> Result<()> {
+    let subcommand =
+        KeysSubcommand::from_arg_matches(keys_matches).expect("Could not parse keys subcommand");
+    let client_builder = TorClient::with_runtime(runtime).config(config.clone());
+    #[cfg(feature = "encrypted-keystore")]
+    let client_builder = client_builder.keystore_passphrase_prompt(std::sync::Arc::new(
+        crate::passphrase::TerminalPassphrasePrompt,
+    ));
+    let client = client_builder.create_inert()?;
+
+    match subcommand {
+        KeysSubcommand::Migrate(args) => run_migrate(&args, &client),
+        KeysSubcommand::Export(args) => run_export(&args, &client),
+        KeysSubcommand::Import(args) => run_import(&args, &client),
+        KeysSubcommand::ExportOpenssh(args) => run_export_openssh(&args, &client),
+        KeysSubcommand::ImportOpenssh(args) => run_import_openssh(&args, &client),
+    }
+}
+
+/// Run the `keys migrate` subcommand.
+fn run_migrate(args: &MigrateArgs, client: &InertTorClient) -> Result<()> {
+    let synced =
+        client.sync_keystores(&args.from, &args.to, args.on_conflict.into(), args.dry_run)?;
+
+    let mut failed = 0_usize;
+    for synced in &synced {
+        let entry = synced.entry();
+        let path = entry.key_path();
+        let ext = entry.key_type().arti_extension();
+        match synced.outcome() {
+            Ok(outcome) => {
+                let outcome = match (outcome, args.dry_run) {
+                    (CopyOutcome::Copied, false) => "copied",
+                    (CopyOutcome::Copied, true) => "would copy",
+                    (CopyOutcome::Overwritten, false) => "overwritten",
+                    (CopyOutcome::Overwritten, true) => "would overwrite",
+                    (CopyOutcome::Skipped, _) => "skipped (already exists)",
+                    (_, _) => "unknown outcome",
+                };
+                println!("{path} ({ext}): {outcome}");
+            }
+            Err(e) => {
+                failed += 1;
+                println!("{path} ({ext}): not copied: {e}");
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(anyhow!(
+            "{failed} of {} keys could not be copied from {} to {}",
+            synced.len(),
+            args.from,
+            args.to
+        ));
+    }
+    Ok(())
+}
+
+/// Run the `keys export` subcommand.
+fn run_export(args: &ExportArgs, client: &InertTorClient) -> Result<()> {
+    let pattern = match (&args.keys.path, &args.keys.service) {
+        (Some(path), _) => path.clone(),
+        (None, Some(nickname)) => format!("hss/{nickname}/**"),
+        (None, None) => return Err(anyhow!("no keys specified")),
+    };
+    let bundle = client.export_keys(&KeyPathPattern::Arti(pattern.clone()), &args.keystore)?;
+    if bundle.keys().is_empty() {
+        return Err(anyhow!(
+            "no keys matching {pattern} found in keystore {}",
+            args.keystore
+        ));
+    }
+    let encoded = bundle.to_openssh()?;
+    write_secret_output(&args.output, args.overwrite, &encoded, "key bundle")?;
+
+    for key in bundle.keys() {
+        eprintln!(
+            "{} ({}): exported",
+            key.path(),
+            key.key_type().arti_extension()
+        );
+    }
+
+    Ok(())
+}
+
+/// Run the `keys import` subcommand.
+fn run_import(args: &ImportArgs, client: &InertTorClient) -> Result<()> {
+    let encoded = read_input(&args.input, "key bundle")?;
+    let bundle = KeyBundle::from_openssh(&encoded).context("invalid key bundle")?;
+
+    let outcomes = client.import_keys(&bundle, &args.keystore, args.on_conflict.into())?;
+    for (key, outcome) in bundle.keys().iter().zip(outcomes) {
+        let outcome = match outcome {
+            CopyOutcome::Copied => "imported",
+            CopyOutcome::Overwritten => "imported (overwritten)",
+            CopyOutcome::Skipped => "skipped (already exists)",
+            _ => "unknown outcome",
+        };
+        let ext = key.key_type().arti_extension();
+        println!("{} ({ext}): {outcome}", key.path());
+    }
+
+    Ok(())
+}
+
+/// Run the `keys export-openssh` subcommand.
+fn run_export_openssh(args: &ExportOpensshArgs, client: &InertTorClient) -> Result<()> {
+    let path = parse_arti_path(&args.path)?;
+    let key_type = parse_key_type(&args.key_type)?;
+    let Some(encoded) = client.export_openssh(&path, &key_type, &args.keystore)? else {
+        return Err(anyhow!(
+            "no {} key at {path} found in keystore {}",
+            args.key_type,
+            args.keystore
+        ));
+    };
+    write_secret_output(&args.output, args.overwrite, &encoded, "key")?;
+
+    eprintln!("{path} ({}): exported", key_type.arti_extension());
+    Ok(())
+}
+
+/// Run the `keys import-openssh` subcommand.
+fn run_import_openssh(args: &ImportOpensshArgs, client: &InertTorClient) -> Result<()> {
+    let path = parse_arti_path(&args.path)?;
+    let key_type = parse_key_type(&args.key_type)?;
+    let encoded = read_input(&args.input, "key")?;
+
+    let outcome = client.import_openssh(
+        &encoded,
+        &path,
+        &key_type,
+        &args.keystore,
+        args.on_conflict.into(),
+    )?;
+    let outcome = match outcome {
+        CopyOutcome::Copied => "imported",
+        CopyOutcome::Overwritten => "imported (overwritten)",
+        CopyOutcome::Skipped => "skipped (already exists)",
+        _ => "unknown outcome",
+    };
+    println!("{path} ({}): {outcome}", key_type.arti_extension());
+    Ok(())
+}
+
+/// Parse `path` as an [`ArtiPath`].
+fn parse_arti_path(path: &str) -> Result<ArtiPath> {
+    ArtiPath::new(path.into()).with_context(|| format!("invalid key path {path:?}"))
+}
+
+/// Parse `ext` as the extension of a [`KeyType`] that we recognize.
+fn parse_key_type(ext: &str) -> Result<KeyType> {
+    match KeyType::from(ext) {
+        KeyType::Unknown { .. } => Err(anyhow!("unrecognized key type {ext:?}")),
+        key_type => Ok(key_type),
+    }
+}
+
+/// Write the secret `contents` (a `what`) to `output`, or to stdout if `output` is `-`.
+///
+/// A new file is only readable by its owner.
+/// An existing file is only replaced if `overwrite` is true.
+fn write_secret_output(output: &str, overwrite: bool, contents: &str, what: &str) -> Result<()> {
+    match output {
+        "-" => io::stdout().write_all(contents.as_bytes())?,
+        filename => {
+            let mut options = OpenOptions::new();
+            options
+                .create(true)
+                .create_new(!overwrite)
+                .write(true)
+                .truncate(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+            let res = options
+                .open(filename)
+                .and_then(|mut f| f.write_all(contents.as_bytes()));
+            if let Err(e) = res {
+                if e.kind() == io::ErrorKind::AlreadyExists {
+                    return Err(anyhow!(
+                        "{filename} already exists. Move it, or rerun with --overwrite to overwrite it"
+                    ));
+                }
+                return Err(e).with_context(|| format!("could not write {what} to {filename}"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Read a `what` from `input`, or from stdin if `input` is `-`.
+fn read_input(input: &str, what: &str) -> Result<Zeroizing<String>> {
+    let mut contents = Zeroizing::new(String::new());
+    match input {
+        "-" => io::stdin().read_to_string(&mut contents)?,
+        filename => std::fs::File::open(filename)
+            .and_then(|mut f| f.read_to_string(&mut contents))
+            .with_context(|| format!("could not read {what} from {filename}"))?,
+    };
+    Ok(contents)
+}
@@ -0,0 +1,238 @@
+//! The `netdir` subcommand.
+
+use anyhow::Context as _;
+use arti_client::{TorClient, TorClientConfig};
+use clap::{ArgMatches, Args, FromArgMatches, Parser, Subcommand, ValueEnum};
+use tor_netdir::{NetDir, RelayWeight, RelayWeightDetails, WeightRole};
+use tor_rtcompat::Runtime;
+
+use crate::Result;
+
+/// The netdir subcommands the arti CLI will be augmented with.
+#[derive(Parser, Debug)]
+pub(crate) enum NetdirSubcommands {
+    /// Inspect the network directory that Arti uses to build circuits.
+    #[command(subcommand)]
+    Netdir(NetdirSubcommand),
+}
+
+/// The `netdir` subcommands.
+#[derive(Debug, Subcommand)]
+pub(crate) enum NetdirSubcommand {
+    /// Show how relays are weighted when they are selected for each position in a circuit.
+    ///
+    /// For each relay, this prints the bandwidth that Arti uses for it,
+    /// the bandwidth-weights (`Wgg`, `Wmd`, `Wee`, ...) that apply to it for each position,
+    /// and the resulting probability of selecting it for that position,
+    /// so that they can be compared with those of C Tor for the same consensus.
+    ///
+    /// The probabilities only take the relays' flags into account:
+    /// they ignore the other restrictions (families, exit policies, and so on)
+    /// that apply when building a particular circuit.
+    Weights(WeightsArgs),
+}
+
+/// The arguments of the [`Weights`](NetdirSubcommand::Weights) subcommand.
+#[derive(Debug, Clone, Args)]
+pub(crate) struct WeightsArgs {
+    /// Sort the relays by their probability of being selected for this position,
+    /// most likely first.
+    ///
+    /// By default, the relays are listed in consensus order.
+    #[arg(long, value_enum)]
+    sort: Option<Position>,
+
+    /// Only show this many relays.
+    #[arg(long)]
+    limit: Option<usize>,
+}
+
+/// A position in a circuit.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum Position {
+    /// The first hop (the guard).
+    Guard,
+    /// A middle hop.
+    Middle,
+    /// The last hop, for exit traffic.
+    Exit,
+    /// The only hop of a one-hop directory circuit.
+    Dir,
+}
+
+impl Position {
+    /// All the positions, in the order we print them in.
+    const ALL: [Position; 4] = [
+        Position::Guard,
+        Position::Middle,
+        Position::Exit,
+        Position::Dir,
+    ];
+
+    /// Return the [`WeightRole`] used to select a relay for this position.
+    fn role(self) -> WeightRole {
+        match self {
+            Position::Guard => WeightRole::Guard,
+            Position::Middle => WeightRole::Middle,
+            Position::Exit => WeightRole::Exit,
+            Position::Dir => WeightRole::BeginDir,
+        }
+    }
+
+    /// Return true if a relay weighted as described by `details`
+    /// can be selected for this position.
+    fn allows(self, details: &RelayWeightDetails) -> bool {
+        match self {
+            Position::Guard => details.has_guard_flag(),
+            Position::Exit => details.has_exit_flag(),
+            Position::Middle | Position::Dir => true,
+        }
+    }
+}
+
+/// The weights of a single relay.
+struct RelayRow {
+    /// The RSA identity of the relay, as an uppercase hex fingerprint (like C Tor's).
+    fingerprint: String,
+    /// The nickname of the relay.
+    nickname: String,
+    /// How the relay is weighted.
+    details: RelayWeightDetails,
+}
+
+impl RelayRow {
+    /// Return the probability of selecting this relay for `pos`,
+    /// given the `total` weight of all the relays that can be selected for it.
+    fn probability(&self, pos: Position, total: RelayWeight) -> f64 {
+        if !pos.allows(&self.details) {
+            return 0.0;
+        }
+        self.details
+            .weight(pos.role())
+            .checked_div(total)
+            .unwrap_or(0.0)
+    }
+}
+
+/// Run the `netdir` subcommand.
+pub(crate) fn run<R: Runtime>(
+    runtime: R,
+    netdir_matches: &ArgMatches,
+    config: &TorClientConfig,
+) -> Result<()> {
+    let subcommand = NetdirSubcommand::from_arg_matches(netdir_matches)
+        .expect("Could not parse netdir subcommand");
+
+    let client = runtime.clone().block_on(
+        TorClient::with_runtime(runtime)
+            .config(config.clone())
+            .create_bootstrapped(),
+    )?;
+    let netdir = client
+        .dirmgr()
+        .timely_netdir()
+        .context("No usable network directory")?;
+
+    match subcommand {
+        NetdirSubcommand::Weights(args) => run_weights(&args, &netdir),
+    }
+    Ok(())
+}
+
+/// Run the `netdir weights` subcommand.
+fn run_weights(args: &WeightsArgs, netdir: &NetDir) {
+    let info = netdir.weight_set_info();
+    println!(
+        "bandwidth source: {:?}, shift: {}, bwweightscale: {}",
+        info.bandwidth_fn(),
+        info.shift(),
+        netdir.params().bw_weight_scale.get(),
+    );
+
+    let mut rows = netdir
+        .relays()
+        .map(|relay| RelayRow {
+            fingerprint: relay
+                .rsa_id()
+                .as_bytes()
+                .iter()
+                .map(|b| format!("{b:02X}"))
+                .collect(),
+            nickname: relay.rs().nickname().to_string(),
+            details: netdir.relay_weight_details(&relay),
+        })
+        .collect::<Vec<_>>();
+
+    let totals = Position::ALL.map(|pos| {
+        rows.iter()
+            .filter(|row| pos.allows(&row.details))
+            .map(|row| row.details.weight(pos.role()))
+            .sum::<RelayWeight>()
+    });
+    let probabilities = |row: &RelayRow| {
+        Position::ALL
+            .iter()
+            .zip(totals)
+            .map(|(pos, total)| row.probability(*pos, total))
+            .collect::<Vec<_>>()
+    };
+
+    if let Some(pos) = args.sort {
+        let idx = Position::ALL
+            .iter()
+            .position(|p| *p == pos)
+            .expect("position not in Position::ALL");
+        rows.sort_by(|a, b| {
+            let (a, b) = (
+                a.probability(pos, totals[idx]),
+                b.probability(pos, totals[idx]),
+            );
+            b.total_cmp(&a)
+        });
+    }
+
+    println!(
+        "{:40} {:19} {:5} {:>10} {:>6} {:>6} {:>6} {:>6} {:>9} {:>9} {:>9} {:>9}",
+        "fingerprint",
+        "nickname",
+        "flags",
+        "bandwidth",
+        "Wg",
+        "Wm",
+        "We",
+        "Wd",
+        "P(guard)",
+        "P(middle)",
+        "P(exit)",
+        "P(dir)"
+    );
+    for row in rows.iter().take(args.limit.unwrap_or(usize::MAX)) {
+        let d = &row.details;
+        let flags = [
+            (d.has_guard_flag(), 'G'),
+            (d.has_exit_flag(), 'E'),
+            (d.has_v2dir_flag(), 'D'),
+        ]
+        .iter()
+        .map(|(set, c)| if *set { *c } else { '-' })
+        .collect::<String>();
+        let factors = Position::ALL.map(|pos| d.factor(pos.role()));
+        let p = probabilities(row);
+
+        println!(
+            "{:40} {:19} {:5} {:>10} {:>6} {:>6} {:>6} {:>6} {:>9.6} {:>9.6} {:>9.6} {:>9.6}",
+            row.fingerprint,
+            row.nickname,
+            flags,
+            d.bandwidth(),
+            factors[0],
+            factors[1],
+            factors[2],
+            factors[3],
+            p[0],
+            p[1],
+            p[2],
+            p[3],
+        );
+    }
+}
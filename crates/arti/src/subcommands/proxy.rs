@@ -6,13 +6,13 @@ use anyhow::{Context, Result};
 use clap::ArgMatches;
 use tracing::{info, warn};
 
-use arti_client::TorClientConfig;
+use arti_client::{IntoTorAddr as _, StreamPrefs, TorClientConfig};
 use tor_config::{ConfigurationSources, Listen};
 use tor_rtcompat::Runtime;
 
 #[cfg(feature = "dns-proxy")]
 use crate::dns;
-use crate::{exit, process, reload_cfg, socks, ArtiConfig, TorClient};
+use crate::{exit, one_shot, process, reload_cfg, socks, ArtiConfig, TorClient};
 
 #[cfg(feature = "rpc")]
 use crate::rpc;
@@ -43,6 +43,23 @@ pub(crate) fn run<R: Runtime>(
         None => config.proxy().dns_listen.clone(),
     };
 
+    if let Some(target) = proxy_matches.get_one::<String>("one-shot") {
+        info!(
+            "Starting Arti {} in one-shot mode, connecting to {} ...",
+            env!("CARGO_PKG_VERSION"),
+            target
+        );
+        let target = target
+            .into_tor_addr()
+            .with_context(|| format!("invalid target address {target}"))?;
+        return runtime.clone().block_on(one_shot::run(
+            runtime,
+            target,
+            StreamPrefs::default(),
+            client_config,
+        ));
+    }
+
     if !socks_listen.is_empty() {
         info!(
             "Starting Arti {} in SOCKS proxy mode on {} ...",
@@ -51,6 +68,14 @@ pub(crate) fn run<R: Runtime>(
         );
     }
 
+    let distro_defaults = arti_client::config::distro::overridden_defaults();
+    if !distro_defaults.is_empty() {
+        info!(
+            "Using distributor-supplied defaults for {:?}",
+            distro_defaults
+        );
+    }
+
     process::use_max_file_limit(&config);
 
     let rt_copy = runtime.clone();
@@ -87,31 +112,15 @@ async fn run_proxy<R: Runtime>(
     use futures::FutureExt;
 
     #[cfg(feature = "rpc")]
-    let rpc_path = {
-        if let Some(path) = &arti_config.rpc().rpc_listen {
-            let path = path.path()?;
-            let parent = path
-                .parent()
-                .ok_or(anyhow::anyhow!("No parent directory for rpc_listen path?"))?;
-            client_config
-                .fs_mistrust()
-                .verifier()
-                .make_secure_dir(parent)?;
-            // It's just a unix thing; if we leave this sitting around, binding to it won't
-            // work right.  There is probably a better solution.
-            if path.try_exists()? {
-                std::fs::remove_file(&path)?;
-            }
-
-            Some(path)
-        } else {
-            None
-        }
-    };
+    let rpc_listeners =
+        rpc::RpcListenerSpec::from_config(arti_config.rpc(), client_config.fs_mistrust())?;
 
     let client_builder = TorClient::with_runtime(runtime.clone())
         .config(client_config)
         .bootstrap_behavior(OnDemand);
+    #[cfg(feature = "encrypted-keystore")]
+    let client_builder = client_builder
+        .keystore_passphrase_prompt(Arc::new(crate::passphrase::TerminalPassphrasePrompt));
     let client = client_builder.create_unbootstrapped_async().await?;
 
     #[allow(unused_mut)]
@@ -147,11 +156,21 @@ async fn run_proxy<R: Runtime>(
     #[cfg(all(feature = "rpc", feature = "tokio"))]
     let rpc_data = {
         // TODO RPC This code doesn't really belong here; it's just an example.
-        if let Some(listen_path) = rpc_path {
-            let (rpc_state, rpc_state_sender) = rpc::RpcVisibleArtiState::new();
-            // TODO Conceivably this listener belongs on a renamed "proxy" list.
-            let rpc_mgr =
-                rpc::launch_rpc_listener(&runtime, listen_path, client.clone(), rpc_state)?;
+        if !rpc_listeners.is_empty() {
+            #[cfg_attr(not(feature = "dns-proxy"), allow(unused_mut))]
+            let (rpc_state, mut rpc_state_sender) = rpc::RpcVisibleArtiState::new();
+            // We list the DNS addresses we're configured to use.
+            // (The DNS resolver fails if it can't listen on one of them,
+            // unless its address family isn't supported at all.)
+            #[cfg(feature = "dns-proxy")]
+            if let Ok(addrs) = dns_listen.ip_addrs() {
+                rpc_state_sender.set_dns_listeners(&addrs.flatten().collect::<Vec<_>>());
+            }
+            let rpc_mgr = rpc::new_rpc_mgr(client.clone(), rpc_state)?;
+            // TODO Conceivably these listeners belong on a renamed "proxy" list.
+            for spec in rpc_listeners {
+                rpc::launch_rpc_listener(&runtime, spec, rpc_mgr.clone()).await?;
+            }
             Some((rpc_mgr, rpc_state_sender))
         } else {
             None
@@ -0,0 +1,218 @@
+//! The `status` subcommand.
+
+use anyhow::{anyhow, Context};
+use arti_rpc_client_core::{ObjectId, RpcConn, RpcConnBuilder};
+use clap::{ArgMatches, Args, FromArgMatches, Parser};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::Result;
+
+/// The status subcommand the arti CLI will be augmented with.
+#[derive(Parser, Debug)]
+pub(crate) enum StatusSubcommands {
+    /// Ask a running Arti instance (over RPC) how it is doing,
+    /// and whether it has noticed any problems.
+    Status(StatusArgs),
+}
+
+/// The arguments of the [`Status`](StatusSubcommands::Status) subcommand.
+#[derive(Debug, Clone, Args)]
+pub(crate) struct StatusArgs {
+    /// A connect string describing how to reach Arti's RPC server.
+    ///
+    /// If this is not given, the default connect points are tried in order.
+    #[arg(long, value_name = "CONNECT_STRING")]
+    connect: Option<String>,
+
+    /// Also show the addresses that relays report seeing our connections coming from.
+    ///
+    /// These addresses can identify you, so they aren't shown by default.
+    #[arg(long)]
+    external_addrs: bool,
+
+    /// Also show how much heap memory is attributed to each of Arti's subsystems.
+    ///
+    /// This only works if Arti was built with the `alloc-tags` feature.
+    #[arg(long)]
+    memory_detail: bool,
+}
+
+/// The reply to `arti:get_client`.
+#[derive(Deserialize)]
+struct ClientId {
+    /// The object ID of the client.
+    id: ObjectId,
+}
+
+/// The reply to `arti:get_client_status`.
+#[derive(Deserialize)]
+struct ClientStatus {
+    /// True if the client is ready for traffic.
+    ready: bool,
+    /// How close the client is to being ready for traffic.
+    fraction: f32,
+    /// A description of whatever is stopping the client from bootstrapping, if anything.
+    blocked: Option<String>,
+}
+
+/// The reply to `arti:get_health_warnings`.
+#[derive(Deserialize)]
+struct HealthWarnings {
+    /// The active warnings.
+    warnings: Vec<HealthWarning>,
+}
+
+/// A single health warning.
+#[derive(Deserialize)]
+struct HealthWarning {
+    /// The stable identifier for this kind of problem.
+    id: String,
+    /// A human-readable description of the problem.
+    message: String,
+    /// When the problem was first noticed.
+    first_seen: String,
+    /// How many times the problem has been noticed.
+    count: u64,
+}
+
+/// The reply to `arti:get_external_addrs`.
+#[derive(Deserialize)]
+struct ExternalAddrs {
+    /// The reported addresses, most recently reported first.
+    addrs: Vec<ExternalAddr>,
+}
+
+/// A single reported external address.
+#[derive(Deserialize)]
+struct ExternalAddr {
+    /// The address that the relays reported.
+    addr: String,
+    /// How many channels have reported this address.
+    count: usize,
+    /// When the address was most recently reported.
+    last_reported: String,
+}
+
+/// The reply to `arti:x_get_memory_detail`.
+#[derive(Deserialize)]
+struct MemoryDetail {
+    /// The usage of each subsystem, starting with the untagged allocations.
+    subsystems: Vec<SubsystemDetail>,
+}
+
+/// The heap usage attributed to a single subsystem.
+#[derive(Deserialize)]
+struct SubsystemDetail {
+    /// The name of the subsystem.
+    name: String,
+    /// The number of bytes currently allocated.
+    live_bytes: usize,
+    /// The number of allocations made since Arti started.
+    total_allocs: u64,
+}
+
+/// Run the `status` subcommand.
+pub(crate) fn run(status_matches: &ArgMatches) -> Result<()> {
+    let args =
+        StatusArgs::from_arg_matches(status_matches).expect("Could not parse status subcommand");
+
+    let builder = match &args.connect {
+        Some(s) => RpcConnBuilder::from_connect_string(s).context("Invalid connect string")?,
+        None => RpcConnBuilder::new(),
+    };
+    let conn = builder
+        .connect()
+        .context("Unable to connect to Arti's RPC server. Is Arti running, with RPC enabled?")?;
+    let session = conn
+        .session()
+        .ok_or_else(|| anyhow!("RPC connection has no session"))?;
+
+    let client: ClientId = call(&conn, session, "arti:get_client")?;
+    let status: ClientStatus = call(&conn, &client.id, "arti:get_client_status")?;
+    let health: HealthWarnings = call(&conn, &client.id, "arti:get_health_warnings")?;
+
+    if status.ready {
+        println!("Arti is ready for traffic.");
+    } else {
+        println!(
+            "Arti is bootstrapping ({:.0}% done).",
+            status.fraction * 100.0
+        );
+    }
+    if let Some(blocked) = status.blocked {
+        println!("Bootstrapping is blocked: {}", blocked);
+    }
+
+    if health.warnings.is_empty() {
+        println!("No problems detected.");
+    } else {
+        println!("Problems detected:");
+        for w in &health.warnings {
+            println!(
+                "  [{}] {} (seen {} times since {})",
+                w.id, w.message, w.count, w.first_seen
+            );
+        }
+    }
+
+    if args.external_addrs {
+        // This method isn't available to every RPC connection,
+        // so we don't give up on the whole command if it fails.
+        match call::<ExternalAddrs>(&conn, &client.id, "arti:get_external_addrs") {
+            Ok(reply) if reply.addrs.is_empty() => {
+                println!("No relay has reported our external address yet.");
+            }
+            Ok(reply) => {
+                println!("Relays report seeing us at (this may be a NAT, and relays can lie):");
+                for a in &reply.addrs {
+                    println!(
+                        "  {} (reported {} times, most recently at {})",
+                        a.addr, a.count, a.last_reported
+                    );
+                }
+            }
+            Err(e) => println!("Unable to get our external addresses: {:#}", e),
+        }
+    }
+
+    if args.memory_detail {
+        match call::<MemoryDetail>(&conn, session, "arti:x_get_memory_detail") {
+            Ok(reply) => {
+                println!("Heap memory by subsystem:");
+                for s in &reply.subsystems {
+                    println!(
+                        "  {:<10} {:>12} bytes ({} allocations so far)",
+                        s.name, s.live_bytes, s.total_allocs
+                    );
+                }
+            }
+            Err(e) => println!("Unable to get heap memory detail: {:#}", e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Invoke the parameterless RPC `method` on `obj`, and decode its result.
+fn call<T: DeserializeOwned>(conn: &RpcConn, obj: &ObjectId, method: &str) -> Result<T> {
+    let request = serde_json::json!({
+        "obj": obj,
+        "method": method,
+        "params": {},
+    });
+    let response = conn
+        .execute(&request.to_string())
+        .with_context(|| format!("Unable to invoke {}", method))?
+        .map_err(|e| anyhow!("{} failed: {}", method, e))?;
+
+    /// Helper for decoding the `result` field of a response.
+    #[derive(Deserialize)]
+    struct Response<T> {
+        /// The decoded value.
+        result: T,
+    }
+    let response: Response<T> = serde_json::from_str(response.as_ref())
+        .with_context(|| format!("Unexpected reply to {}", method))?;
+    Ok(response.result)
+}
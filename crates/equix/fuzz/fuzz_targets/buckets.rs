@@ -19,6 +19,8 @@ enum Op {
     Range(BucketIdx),
     Value(BucketIdx, ItemIdx),
     Insert(BucketIdx, Val, Val),
+    Iter(BucketIdx),
+    Clear,
     DropFirst,
 }
 
@@ -122,6 +124,31 @@ impl<'a, 'b, const N: usize, const CAP: usize> Sim<'a, 'b, N, CAP> {
                 }
                 self
             }
+            Op::Iter(bi) => {
+                let bi = self.idx(bi);
+                match &self {
+                    Sim::Single { b, s } => {
+                        assert!(b.iter_bucket(bi).eq(s.0[bi].iter().copied()));
+                    }
+                    Sim::Pair { b, s } => {
+                        assert!(b.iter_bucket(bi).eq(s.0[bi].iter().copied()));
+                    }
+                }
+                self
+            }
+            Op::Clear => {
+                match &mut self {
+                    Sim::Single { b, s } => {
+                        b.clear();
+                        *s = Default::default();
+                    }
+                    Sim::Pair { b, s } => {
+                        b.clear();
+                        *s = Default::default();
+                    }
+                }
+                self
+            }
             Op::DropFirst => match self {
                 Sim::Pair { b, s } => {
                     let b2 = b.drop_first();
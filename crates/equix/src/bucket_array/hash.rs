@@ -123,6 +123,26 @@ impl<'k, 'v, const N: usize, const CAP: usize, C: Count, K: Key, KS: KeyStorage<
     pub(crate) fn drop_key_storage(self) -> ValueBucketArray<'v, N, CAP, C, K, V> {
         ValueBucketArray(self.0.drop_first(), self.1)
     }
+
+    /// Iterate over the full keys and values of every item in one bucket.
+    ///
+    /// Items are returned in order of insertion, so the position of each
+    /// item in this iterator matches its item index within the bucket.
+    // Not used by the current solver, but kept alongside the other accessors.
+    #[allow(dead_code)]
+    #[inline(always)]
+    pub(crate) fn iter_bucket(&self, bucket: usize) -> impl Iterator<Item = (K, V)> + '_ {
+        self.0
+            .iter_bucket(bucket)
+            .map(move |(key, value)| (self.join_wide_key(bucket, key.into_key()), value))
+    }
+
+    /// Remove every item, keeping the backing memory so the array can be refilled.
+    #[allow(dead_code)]
+    #[inline(always)]
+    pub(crate) fn clear(&mut self) {
+        self.0.clear();
+    }
 }
 
 /// Concrete bucket array with a single [`BucketArrayMemory`] for value storage
@@ -151,6 +171,21 @@ impl<'v, const N: usize, const CAP: usize, C: Count, K: Key, V: Copy>
     pub(crate) fn new(value_mem: &'v mut BucketArrayMemory<N, CAP, V>) -> Self {
         Self(mem::BucketArray::new(value_mem), PhantomData)
     }
+
+    /// Iterate over the values of every item in one bucket.
+    ///
+    /// Items are returned in order of insertion, so the position of each
+    /// item in this iterator matches its item index within the bucket.
+    #[inline(always)]
+    pub(crate) fn iter_bucket(&self, bucket: usize) -> impl Iterator<Item = V> + '_ {
+        self.0.iter_bucket(bucket)
+    }
+
+    /// Remove every item, keeping the backing memory so the array can be refilled.
+    #[inline(always)]
+    pub(crate) fn clear(&mut self) {
+        self.0.clear();
+    }
 }
 
 impl<'k, 'v, const N: usize, const CAP: usize, C: Count, K: Key, KS: KeyStorage<K>, V: Copy>
@@ -349,3 +384,64 @@ where
 }
 
 impl<T: Copy + Zero + Not<Output = Self> + TryFrom<K> + TryInto<K>, K: Key> KeyStorage<K> for T {}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use crate::bucket_array::mem::Uninit;
+
+    #[derive(Copy, Clone)]
+    struct Mem {
+        keys: BucketArrayMemory<4, 3, u8>,
+        values: BucketArrayMemory<4, 3, u32>,
+    }
+
+    // SAFETY: Made only from BucketArrayMemory.
+    unsafe impl Uninit for Mem {}
+
+    #[test]
+    fn iter_and_clear() {
+        let mut mem = Mem::alloc();
+        let mut array = KeyValueBucketArray::<'_, '_, 4, 3, u8, u16, u8, u32>::new(
+            &mut mem.keys,
+            &mut mem.values,
+        );
+
+        for key in [1_u16, 5, 9, 13, 2, 6] {
+            let _ = array.insert(key, u32::from(key) * 10);
+        }
+        // Bucket 1 is full, so 13 was discarded.
+        assert_eq!(
+            array.iter_bucket(1).collect::<Vec<_>>(),
+            vec![(1, 10), (5, 50), (9, 90)]
+        );
+        assert_eq!(
+            array.iter_bucket(2).collect::<Vec<_>>(),
+            vec![(2, 20), (6, 60)]
+        );
+        assert_eq!(array.iter_bucket(0).count(), 0);
+
+        array.clear();
+        assert!((0..4).all(|b| array.iter_bucket(b).next().is_none()));
+        array.insert(13, 130).unwrap();
+        assert_eq!(array.iter_bucket(1).collect::<Vec<_>>(), vec![(13, 130)]);
+
+        let mut array = array.drop_key_storage();
+        assert_eq!(array.iter_bucket(1).collect::<Vec<_>>(), vec![130]);
+        array.clear();
+        assert_eq!(array.iter_bucket(1).count(), 0);
+    }
+}
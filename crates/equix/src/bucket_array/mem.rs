@@ -30,7 +30,9 @@
 //! Internally, a [`BucketState`] tracks how many items are in each bucket.
 //! The only supported write operation is appending to an underfull bucket.
 //! Once initialized by such a write, bucket items may be randomly accessed
-//! via the methods on the [`BucketArray`] or [`BucketArrayPair`].
+//! via the methods on the [`BucketArray`] or [`BucketArrayPair`], or iterated
+//! over in order of insertion. Clearing the array resets every bucket to
+//! empty, returning all of its memory to the assumed-uninitialized state.
 //!
 //! It's critical for memory safety that we only read from a [`MaybeUninit`]
 //! that has definitely been initialized. The static lifetimes of the mutable
@@ -164,6 +166,36 @@ impl<const N: usize, const CAP: usize, C: Count> BucketState<N, CAP, C> {
     fn item_range(&self, bucket: usize) -> Range<usize> {
         0..self.counts[bucket].into()
     }
+
+    /// Mark every bucket as empty.
+    ///
+    /// This never makes an uninitialized item appear initialized, so it's
+    /// always safe: it only shrinks the set of items we may read.
+    #[inline(always)]
+    fn clear(&mut self) {
+        self.counts = [C::zero(); N];
+    }
+}
+
+/// Iterate over the values of the items in `range`, within one bucket.
+///
+/// Panics if the bucket index or the range is out of bounds.
+///
+/// # Safety
+///
+/// Every item in `range` must have been initialized. Callers get this from
+/// a [`BucketState`] associated with `mem`, and the shared borrow held by
+/// the iterator keeps that state from changing until it's dropped.
+#[inline(always)]
+unsafe fn iter_initialized<const N: usize, const CAP: usize, T: Copy>(
+    mem: &BucketArrayMemory<N, CAP, T>,
+    bucket: usize,
+    range: Range<usize>,
+) -> impl Iterator<Item = T> + '_ {
+    mem.0[bucket][range].iter().map(|item| {
+        // SAFETY: Our caller promises that all items in range are initialized.
+        unsafe { item.assume_init() }
+    })
 }
 
 /// Concrete binding between one [`BucketState`] and one [`BucketArrayMemory`]
@@ -217,6 +249,24 @@ impl<'a, const N: usize, const CAP: usize, C: Count, A: Copy> BucketArray<'a, N,
         unsafe { self.mem.0[bucket][item].assume_init() }
     }
 
+    /// Iterate over the values of every item in one bucket, in order of insertion.
+    ///
+    /// Panics if the bucket index is out of range.
+    #[cfg_attr(feature = "bucket-array", visibility::make(pub))]
+    #[inline(always)]
+    pub(crate) fn iter_bucket(&self, bucket: usize) -> impl Iterator<Item = A> + '_ {
+        // SAFETY: This requires that our [`BucketState`] instance accurately
+        //         represents which fields in [`mem`] have been initialized.
+        unsafe { iter_initialized(self.mem, bucket, self.state.item_range(bucket)) }
+    }
+
+    /// Remove all items from every bucket, so the array can be refilled.
+    #[cfg_attr(feature = "bucket-array", visibility::make(pub))]
+    #[inline(always)]
+    pub(crate) fn clear(&mut self) {
+        self.state.clear();
+    }
+
     /// Append a new item to a bucket.
     ///
     /// If the bucket is full, returns `Err(())` and makes no changes.
@@ -308,6 +358,29 @@ impl<'a, 'b, const N: usize, const CAP: usize, C: Count, A: Copy, B: Copy>
         unsafe { self.mem_b.0[bucket][item].assume_init() }
     }
 
+    /// Iterate over the value pairs of every item in one bucket, in order of insertion.
+    ///
+    /// Panics if the bucket index is out of range.
+    #[cfg_attr(feature = "bucket-array", visibility::make(pub))]
+    #[inline(always)]
+    pub(crate) fn iter_bucket(&self, bucket: usize) -> impl Iterator<Item = (A, B)> + '_ {
+        let range = self.state.item_range(bucket);
+        // SAFETY: This requires that our [`BucketState`] instance accurately
+        //         represents which fields in [`mem_a`] and [`mem_b`] have
+        //         been initialized.
+        unsafe {
+            iter_initialized(self.mem_a, bucket, range.clone())
+                .zip(iter_initialized(self.mem_b, bucket, range))
+        }
+    }
+
+    /// Remove all items from every bucket, so the array can be refilled.
+    #[cfg_attr(feature = "bucket-array", visibility::make(pub))]
+    #[inline(always)]
+    pub(crate) fn clear(&mut self) {
+        self.state.clear();
+    }
+
     /// Append a new item pair to a bucket.
     ///
     /// If the bucket is full, returns Err(()) and makes no changes.
@@ -33,10 +33,12 @@ pub(crate) fn search<const TEMP_N: usize, const TEMP_CAP: usize, A, F, C, K, KS>
     K: Key,
     KS: KeyStorage<K>,
 {
+    let mut paired_item_hash =
+        ValueBucketArray::<'_, { TEMP_N }, { TEMP_CAP }, u8, K, C>::new(scratchpad);
+
     for first_bucket in 0..=(A::NUM_BUCKETS / 2) {
         let second_bucket = first_bucket.wrapping_neg() % A::NUM_BUCKETS;
-        let mut paired_item_hash =
-            ValueBucketArray::<'_, { TEMP_N }, { TEMP_CAP }, u8, K, C>::new(scratchpad);
+        paired_item_hash.clear();
 
         // Collect into paired_item_hash a mapping from the remainder portion
         // of the key, to the item index in the bucket where we found that key.
@@ -58,10 +60,7 @@ pub(crate) fn search<const TEMP_N: usize, const TEMP_CAP: usize, A, F, C, K, KS>
             let (bucket_in_paired_hash, _) =
                 paired_item_hash.split_wide_key(hash_complement_remainder);
 
-            for first_item in paired_item_hash
-                .item_range(bucket_in_paired_hash)
-                .map(|item| paired_item_hash.item_value(bucket_in_paired_hash, item))
-            {
+            for first_item in paired_item_hash.iter_bucket(bucket_in_paired_hash) {
                 let first_item: usize = first_item.into();
                 let first_hash = array.item_full_key(first_bucket, first_item);
                 let sum = first_hash.wrapping_add(&second_hash);
@@ -53,12 +53,13 @@ mod solver;
 pub use bucket_array::mem::{BucketArray, BucketArrayMemory, BucketArrayPair, Count, Uninit};
 
 use hashx::{HashX, HashXBuilder};
+use std::time::{Duration, Instant};
 
 pub use hashx::{Runtime, RuntimeOption};
 
 pub use err::{Error, HashError};
 pub use solution::{Solution, SolutionArray, SolutionByteArray, SolutionItem, SolutionItemArray};
-pub use solver::SolverMemory;
+pub use solver::{LayerStats, SolverMemory, SolverStats};
 
 /// One Equi-X instance, customized for a challenge string
 ///
@@ -117,9 +118,20 @@ impl EquiX {
     /// several solve operations in rapid succession, such as in the common case
     /// of layering an effort adjustment protocol above Equi-X.
     pub fn solve_with_memory(&self, mem: &mut SolverMemory) -> SolutionArray {
+        self.solve_with_stats(mem).0
+    }
+
+    /// Search for solutions, using the provided [`SolverMemory`], and
+    /// report statistics about the search.
+    ///
+    /// Returns the same solutions as [`Self::solve_with_memory()`], along
+    /// with a [`SolverStats`] describing how much work each layer of the
+    /// solver did, and how long it took.
+    pub fn solve_with_stats(&self, mem: &mut SolverMemory) -> (SolutionArray, SolverStats) {
         let mut result = Default::default();
-        solver::find_solutions(&self.hash, mem, &mut result);
-        result
+        let mut stats = Default::default();
+        solver::find_solutions(&self.hash, mem, &mut result, &mut stats);
+        (result, stats)
     }
 }
 
@@ -197,6 +209,36 @@ impl EquiXBuilder {
     pub fn verify_bytes(&self, challenge: &[u8], array: &SolutionByteArray) -> Result<(), Error> {
         self.verify(challenge, &Solution::try_from_bytes(array)?)
     }
+
+    /// Estimate how many challenges per second we can solve on this device,
+    /// with the selected options.
+    ///
+    /// This runs the solver on a series of arbitrary challenges until `budget`
+    /// has elapsed, always completing at least one solve, and returns the
+    /// measured rate. The time to build each [`EquiX`] instance is included,
+    /// since callers solving a real puzzle need to do that once per attempt.
+    ///
+    /// Solve rates vary with the challenge and with system load, so
+    /// this is only an approximation. Larger budgets give a better estimate.
+    pub fn estimate_solve_rate(&self, budget: Duration) -> f64 {
+        let mut mem = SolverMemory::new();
+        let start = Instant::now();
+        let mut solves: u32 = 0;
+        for nonce in 0_u64.. {
+            let elapsed = start.elapsed();
+            if solves > 0 && elapsed >= budget {
+                return f64::from(solves) / elapsed.as_secs_f64();
+            }
+            let mut challenge = *b"equix calibration\0\0\0\0\0\0\0\0";
+            challenge[17..].copy_from_slice(&nonce.to_le_bytes());
+            // Some challenges are unusable; a real solver would skip them too.
+            if let Ok(equix) = self.build(&challenge) {
+                let _ = equix.solve_with_memory(&mut mem);
+                solves += 1;
+            }
+        }
+        unreachable!("ran out of calibration challenges")
+    }
 }
 
 impl Default for EquiXBuilder {
@@ -11,6 +11,7 @@ use crate::collision::{self, PackedCollision};
 use crate::solution::{self, HashValue, Solution, SolutionArray, SolutionItem, EQUIHASH_N};
 use arrayvec::ArrayVec;
 use hashx::HashX;
+use std::time::{Duration, Instant};
 
 // The hash table bucket counts here are mostly constrained by the shape of
 // the Equihash tree, but the bucket capacities are somewhat arbitrary. Larger
@@ -127,7 +128,23 @@ struct OverlaySecond {
 
 /// Search for solutions, iterating the entire [`SolutionItem`] space and using
 /// temporary memory to locate partial sum collisions at each tree layer.
-pub(crate) fn find_solutions(func: &HashX, mem: &mut SolverMemory, results: &mut SolutionArray) {
+///
+/// Statistics about the search are written to `stats`.
+pub(crate) fn find_solutions(
+    func: &HashX,
+    mem: &mut SolverMemory,
+    results: &mut SolutionArray,
+    stats: &mut SolverStats,
+) {
+    *stats = SolverStats::default();
+    let mut timer = Instant::now();
+    let mut lap = |layer: &mut LayerStats| {
+        let now = Instant::now();
+        layer.elapsed = now - timer;
+        timer = now;
+    };
+    let [stats0, stats1, stats2, stats3] = &mut stats.layers;
+
     // Use the first memory overlay layout.
     let overlay = mem.heap.overlay.first();
 
@@ -135,16 +152,18 @@ pub(crate) fn find_solutions(func: &HashX, mem: &mut SolverMemory, results: &mut
     let mut layer0 = Layer0::new(&mut overlay.layer0_keys, &mut mem.heap.layer0_values);
     for item in SolutionItem::MIN..=SolutionItem::MAX {
         let hash = solution::item_hash(func, item);
-        let _ = layer0.insert(hash, item);
+        stats0.record(layer0.insert(hash, item));
     }
+    lap(stats0);
 
     // Now form the first layer of the Equihash tree,
     // with collisions in the low N/4 (15) bits
     let layer1_n = EQUIHASH_N / 4;
     let mut layer1 = Layer1::new(&mut mem.heap.layer1_keys, &mut mem.heap.layer1_values);
     collision::search(&layer0, &mut mem.heap.temp, layer1_n, |sum, loc| {
-        let _ = layer1.insert(sum, Layer0Collision::pack(&loc).into_inner());
+        stats1.record(layer1.insert(sum, Layer0Collision::pack(&loc).into_inner()));
     });
+    lap(stats1);
 
     // Once we finish searching a layer for collisions,
     // we can drop the key data and make the rest immutable.
@@ -162,9 +181,10 @@ pub(crate) fn find_solutions(func: &HashX, mem: &mut SolverMemory, results: &mut
         &mut mem.heap.temp,
         layer2_n - layer1_n,
         |sum, loc| {
-            let _ = layer2.insert(sum as u32, Layer1Collision::pack(&loc).into_inner());
+            stats2.record(layer2.insert(sum as u32, Layer1Collision::pack(&loc).into_inner()));
         },
     );
+    lap(stats2);
 
     // Final layer, match the entire N bits and assemble complete solutions
     let layer3_n = EQUIHASH_N;
@@ -193,10 +213,64 @@ pub(crate) fn find_solutions(func: &HashX, mem: &mut SolverMemory, results: &mut
                     .expect("always collected a full SolutionItem tree"),
             );
             if results.last() != Some(&solution) {
-                let _ = results.try_push(solution);
+                stats3.record(results.try_push(solution).map_err(|_| ()));
             }
         },
     );
+    lap(stats3);
+}
+
+/// Statistics about one run of the Equi-X solver
+///
+/// Returned by [`crate::EquiX::solve_with_stats()`]. These are meant for
+/// diagnostics and for tuning: the numbers of items discarded depend on
+/// fixed bucket capacities, so any nonzero overflow count means that the
+/// solver may have missed some solutions for this challenge.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct SolverStats {
+    /// Statistics for each layer of the solution tree, starting at the leaves
+    ///
+    /// Layer 0 holds the hash of every [`SolutionItem`], layers 1 and 2
+    /// hold partial sum collisions from the layer below, and layer 3 is the
+    /// list of complete solutions. There, [`LayerStats::overflowed`] counts
+    /// solutions that did not fit in a [`SolutionArray`].
+    pub layers: [LayerStats; 4],
+}
+
+impl SolverStats {
+    /// Total time spent in the solver, not including HashX program generation
+    pub fn elapsed(&self) -> Duration {
+        self.layers.iter().map(|layer| layer.elapsed).sum()
+    }
+
+    /// Total number of items discarded because there was no room for them
+    pub fn overflowed(&self) -> usize {
+        self.layers.iter().map(|layer| layer.overflowed).sum()
+    }
+}
+
+/// Statistics about one layer in a run of the Equi-X solver
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct LayerStats {
+    /// Number of candidate items we found for this layer
+    pub candidates: usize,
+    /// Number of candidates discarded because their bucket was full
+    pub overflowed: usize,
+    /// Time spent finding candidates and building this layer
+    pub elapsed: Duration,
+}
+
+impl LayerStats {
+    /// Count one candidate, given the result of trying to store it.
+    #[inline(always)]
+    fn record(&mut self, result: Result<(), ()>) {
+        self.candidates += 1;
+        if result.is_err() {
+            self.overflowed += 1;
+        }
+    }
 }
 
 /// Temporary memory used by the Equi-X solver
@@ -0,0 +1,27 @@
+//! Tests for solver statistics and calibration
+
+use equix::{EquiX, EquiXBuilder, SolverMemory};
+use std::time::Duration;
+
+#[test]
+fn stats_match_solutions() {
+    let equix = EquiX::new(b"equi-x stats").unwrap();
+    let mut mem = SolverMemory::new();
+    let (solutions, stats) = equix.solve_with_stats(&mut mem);
+
+    assert_eq!(solutions, equix.solve_with_memory(&mut mem));
+    assert_eq!(stats.layers[0].candidates, 1 << 16);
+    for layer in &stats.layers {
+        assert!(layer.overflowed <= layer.candidates);
+    }
+    let l3 = &stats.layers[3];
+    assert_eq!(solutions.len(), l3.candidates - l3.overflowed);
+    assert!(stats.elapsed() >= stats.layers[0].elapsed);
+}
+
+#[test]
+fn calibrate() {
+    let rate = EquiXBuilder::new().estimate_solve_rate(Duration::ZERO);
+    assert!(rate.is_finite());
+    assert!(rate > 0.0);
+}
@@ -0,0 +1,132 @@
+//! Inspecting Windows access control lists.
+//!
+//! On Windows, the mode bits that we check on Unix don't exist.
+//! Instead, we look at each object's discretionary access control list (DACL),
+//! and complain if it grants access to a group that includes users
+//! other than the owner and administrators.
+
+use std::{ffi::c_void, io, os::windows::ffi::OsStrExt as _, path::Path, ptr};
+
+use winapi::{
+    shared::{
+        minwindef::{DWORD, FALSE},
+        winerror::ERROR_SUCCESS,
+    },
+    um::{
+        accctrl::SE_FILE_OBJECT,
+        aclapi::GetNamedSecurityInfoW,
+        securitybaseapi::{GetAce, IsWellKnownSid},
+        winbase::LocalFree,
+        winnt::{
+            WinAnonymousSid, WinAuthenticatedUserSid, WinBuiltinGuestsSid, WinBuiltinUsersSid,
+            WinWorldSid, ACCESS_ALLOWED_ACE, ACCESS_ALLOWED_ACE_TYPE, ACE_HEADER,
+            DACL_SECURITY_INFORMATION, DELETE, FILE_APPEND_DATA, FILE_DELETE_CHILD, FILE_READ_DATA,
+            FILE_WRITE_DATA, GENERIC_ALL, GENERIC_READ, GENERIC_WRITE, INHERIT_ONLY_ACE, PACL,
+            PSECURITY_DESCRIPTOR, PSID, WELL_KNOWN_SID_TYPE, WRITE_DAC, WRITE_OWNER,
+        },
+    },
+};
+
+/// Groups of users that must not have access to a private object,
+/// with a description of each.
+const BROAD_TRUSTEES: &[(WELL_KNOWN_SID_TYPE, &str)] = &[
+    (WinWorldSid, "everyone"),
+    (WinAnonymousSid, "anonymous users"),
+    (WinAuthenticatedUserSid, "all authenticated users"),
+    (WinBuiltinUsersSid, "all local users"),
+    (WinBuiltinGuestsSid, "guests"),
+];
+
+/// Access rights that would let somebody change an object,
+/// or (for a directory) change what it contains.
+pub(crate) const MODIFY_ACCESS: DWORD = FILE_WRITE_DATA
+    | FILE_APPEND_DATA
+    | FILE_DELETE_CHILD
+    | DELETE
+    | WRITE_DAC
+    | WRITE_OWNER
+    | GENERIC_WRITE
+    | GENERIC_ALL;
+
+/// Access rights that would let somebody read an object,
+/// or (for a directory) list what it contains.
+pub(crate) const READ_ACCESS: DWORD = FILE_READ_DATA | GENERIC_READ | GENERIC_ALL;
+
+/// Frees a security descriptor that Windows allocated for us.
+struct DescriptorGuard(PSECURITY_DESCRIPTOR);
+
+impl Drop for DescriptorGuard {
+    fn drop(&mut self) {
+        // SAFETY: GetNamedSecurityInfoW allocated this descriptor with
+        // LocalAlloc, and nothing else frees it.
+        unsafe {
+            LocalFree(self.0.cast());
+        }
+    }
+}
+
+/// Return every grant of access in the DACL of `path` to one of our
+/// [`BROAD_TRUSTEES`], as a description of the trustee and the access mask
+/// that it is granted.
+///
+/// Entries that only apply to objects created inside `path` are ignored.
+pub(crate) fn broad_grants(path: &Path) -> io::Result<Vec<(&'static str, DWORD)>> {
+    let name: Vec<u16> = path.as_os_str().encode_wide().chain([0]).collect();
+    let mut dacl: PACL = ptr::null_mut();
+    let mut descriptor: PSECURITY_DESCRIPTOR = ptr::null_mut();
+    // SAFETY: `name` is a NUL-terminated wide string that outlives the call.
+    // We only ask for the DACL, so the other out-pointers may be null.
+    let status = unsafe {
+        GetNamedSecurityInfoW(
+            name.as_ptr(),
+            SE_FILE_OBJECT,
+            DACL_SECURITY_INFORMATION,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            &mut dacl,
+            ptr::null_mut(),
+            &mut descriptor,
+        )
+    };
+    if status != ERROR_SUCCESS {
+        return Err(io::Error::from_raw_os_error(status as i32));
+    }
+    // `dacl` points into `descriptor`, so it is valid for as long as this is.
+    let _guard = DescriptorGuard(descriptor);
+
+    if dacl.is_null() {
+        // A missing DACL grants everybody full access.
+        return Ok(vec![(BROAD_TRUSTEES[0].1, GENERIC_ALL)]);
+    }
+
+    let mut grants = Vec::new();
+    // SAFETY: `dacl` is a valid ACL.
+    let n_aces = unsafe { (*dacl).AceCount };
+    for idx in 0..DWORD::from(n_aces) {
+        let mut ace: *mut c_void = ptr::null_mut();
+        // SAFETY: `idx` is less than the number of entries in `dacl`.
+        if unsafe { GetAce(dacl, idx, &mut ace) } == FALSE {
+            return Err(io::Error::last_os_error());
+        }
+        let header: *const ACE_HEADER = ace.cast();
+        // SAFETY: Every entry in an ACL starts with an ACE_HEADER.
+        let (ace_type, ace_flags) = unsafe { ((*header).AceType, (*header).AceFlags) };
+        // Deny entries can only make things safer,
+        // and inherit-only entries don't affect this object.
+        if ace_type != ACCESS_ALLOWED_ACE_TYPE || ace_flags & INHERIT_ONLY_ACE != 0 {
+            continue;
+        }
+        let allowed: *const ACCESS_ALLOWED_ACE = ace.cast();
+        // SAFETY: This entry is an ACCESS_ALLOWED_ACE, whose SID begins
+        // at its SidStart field.
+        let (mask, sid) = unsafe { ((*allowed).Mask, ptr::addr_of!((*allowed).SidStart) as PSID) };
+        for (sid_type, trustee) in BROAD_TRUSTEES {
+            // SAFETY: `sid` is a valid SID within `dacl`.
+            if unsafe { IsWellKnownSid(sid, *sid_type) } != FALSE {
+                grants.push((*trustee, mask));
+            }
+        }
+    }
+
+    Ok(grants)
+}
@@ -58,6 +58,20 @@ pub enum Error {
     #[error("Bad owner (UID {1}) on file or directory {}", _0.anonymize_home())]
     BadOwner(PathBuf, u32),
 
+    /// A target (or its contents) had a Windows access control list that
+    /// grants access to a broad group of users, such as "Everyone".
+    ///
+    /// Only generated on Windows.
+    #[error("Incorrect access control: {} lets {trustee} {access} it", path.anonymize_home())]
+    BadAccessControl {
+        /// The file or directory with the problem.
+        path: PathBuf,
+        /// A description of the group of users that has access.
+        trustee: &'static str,
+        /// The kind of access that they have: "read" or "modify".
+        access: &'static str,
+    },
+
     /// A target (or one of its ancestors) had the wrong type.
     ///
     /// Ordinarily, the target may be anything at all, though you can override
@@ -175,6 +189,7 @@ impl Error {
                 Error::NotFound(pb) => pb,
                 Error::BadPermission(pb, ..) => pb,
                 Error::BadOwner(pb, _) => pb,
+                Error::BadAccessControl { path: pb, .. } => pb,
                 Error::BadType(pb) => pb,
                 Error::CouldNotInspect(pb, _) => pb,
                 Error::Io { filename: pb, .. } => pb,
@@ -202,7 +217,10 @@ impl Error {
     /// us from looking at permissions in the first place)
     pub fn is_bad_permission(&self) -> bool {
         match self {
-            Error::BadPermission(..) | Error::BadOwner(_, _) | Error::BadType(_) => true,
+            Error::BadPermission(..)
+            | Error::BadOwner(_, _)
+            | Error::BadAccessControl { .. }
+            | Error::BadType(_) => true,
 
             Error::NotFound(_)
             | Error::CouldNotInspect(_, _)
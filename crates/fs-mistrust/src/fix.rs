@@ -0,0 +1,103 @@
+//! Correcting permission problems that a [`Verifier`] finds.
+
+use std::path::{Path, PathBuf};
+
+use crate::{Result, Verifier};
+
+/// A change that [`Verifier::fix_permissions`] made to the permissions of a
+/// file or directory.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct PermissionFix {
+    /// The file or directory whose permissions we changed.
+    pub path: PathBuf,
+    /// Its permission bits before we changed them.
+    pub old_mode: u32,
+    /// Its permission bits after we changed them.
+    pub new_mode: u32,
+}
+
+impl<'a> Verifier<'a> {
+    /// Try to correct the permissions of `path` (and, if
+    /// [`check_content`](Verifier::check_content) is set, of its contents),
+    /// so that they conform to the requirements of this `Verifier`.
+    ///
+    /// We only ever _remove_ permission bits, and only from `path` itself and
+    /// from the objects within it.  We never change ownership, and we never
+    /// touch the ancestors of `path`: they often belong to other users or to
+    /// the system, and changing them could have surprising effects elsewhere.
+    ///
+    /// Returns a list of the changes we made.  Problems that we can't fix are
+    /// left alone: call [`check`](Verifier::check) afterwards to find them.
+    ///
+    /// On platforms other than Unix, this function does nothing.
+    pub fn fix_permissions<P: AsRef<Path>>(self, path: P) -> Result<Vec<PermissionFix>> {
+        #[cfg(target_family = "unix")]
+        {
+            self.fix_unix_permissions(path.as_ref())
+        }
+        #[cfg(not(target_family = "unix"))]
+        {
+            let _ = path;
+            Ok(Vec::new())
+        }
+    }
+
+    /// Unix implementation for `fix_permissions`.
+    #[cfg(target_family = "unix")]
+    fn fix_unix_permissions(&self, path: &Path) -> Result<Vec<PermissionFix>> {
+        use crate::Error;
+        use std::fs::{self, Permissions};
+        use std::os::unix::fs::{MetadataExt as _, PermissionsExt as _};
+
+        if self.mistrust.is_disabled() {
+            return Ok(Vec::new());
+        }
+
+        let canonical = path
+            .canonicalize()
+            .map_err(|e| Error::inspecting(e, path))?;
+
+        // Collect the problems before changing anything, so that we aren't
+        // walking the tree while we modify it.
+        let problems: Vec<Error> = self
+            .check_errors(path)
+            .chain(self.check_content_errors(path))
+            .collect();
+
+        let mut fixes = Vec::new();
+        for problem in problems {
+            let problem = match problem {
+                Error::Content(inner) => *inner,
+                other => other,
+            };
+            let Error::BadPermission(bad_path, _, bad_bits) = problem else {
+                continue;
+            };
+            // (Problems in the contents are reported relative to `path`;
+            // problems in `path` itself are reported relative to `canonical`.)
+            if !bad_path.starts_with(path) && !bad_path.starts_with(&canonical) {
+                continue;
+            }
+
+            // Look again, so that we keep any bits (like the sticky bit)
+            // that the error doesn't report.
+            let meta =
+                fs::symlink_metadata(&bad_path).map_err(|e| Error::inspecting(e, &bad_path))?;
+            if meta.file_type().is_symlink() {
+                continue;
+            }
+            let old_mode = meta.mode() & 0o7777;
+            let new_mode = old_mode & !bad_bits;
+            fs::set_permissions(&bad_path, Permissions::from_mode(new_mode))
+                .map_err(|e| Error::io(e, &bad_path, "change permissions"))?;
+            fixes.push(PermissionFix {
+                path: bad_path,
+                old_mode,
+                new_mode,
+            });
+        }
+
+        Ok(fixes)
+    }
+}
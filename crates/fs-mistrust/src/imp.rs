@@ -134,6 +134,8 @@ impl<'a> super::Verifier<'a> {
         self.check_type(path, path_type, meta, &mut errors);
         #[cfg(target_family = "unix")]
         self.check_permissions(path, path_type, meta, &mut errors);
+        #[cfg(windows)]
+        self.check_access_control(path, path_type, &mut errors);
         errors
     }
 
@@ -255,6 +257,47 @@ impl<'a> super::Verifier<'a> {
             ));
         }
     }
+
+    /// Check whether a given file's Windows access control list grants access
+    /// to a broad group of users, and push errors into `errors` if so. Other
+    /// inputs are as for `check_one`.
+    ///
+    /// We only look at the target and its contents.  Ancestor directories
+    /// routinely let every user add entries (the root of the system drive
+    /// does, for example), so checking them would reject nearly every path.
+    #[cfg(windows)]
+    fn check_access_control(&self, path: &Path, path_type: PathType, errors: &mut Vec<Error>) {
+        use crate::acl::{broad_grants, MODIFY_ACCESS, READ_ACCESS};
+
+        if !matches!(path_type, PathType::Final | PathType::Content) {
+            return;
+        }
+        // As on Unix, we allow content to be readable, since we check that
+        // the target directory itself is not.
+        let check_read = !self.readable_okay && path_type == PathType::Final;
+
+        let grants = match broad_grants(path) {
+            Ok(grants) => grants,
+            Err(e) => {
+                errors.push(Error::inspecting(e, path));
+                return;
+            }
+        };
+        for (trustee, mask) in grants {
+            let access = if mask & MODIFY_ACCESS != 0 {
+                "modify"
+            } else if check_read && mask & READ_ACCESS != 0 {
+                "read"
+            } else {
+                continue;
+            };
+            errors.push(Error::BadAccessControl {
+                path: path.into(),
+                trustee,
+                access,
+            });
+        }
+    }
 }
 
 impl super::Type {
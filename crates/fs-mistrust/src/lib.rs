@@ -49,15 +49,19 @@
 
 // This crate used to have unsafe code to interact with various libc functions.
 // Nowadays we use pwd_grp, which is tested with miri.
-// This #[forbid] assures us that we have removed all direct unsafe libc access.
+// This #[deny] assures us that we have removed all direct unsafe libc access.
 //
-// If this crate grows some other reason to want some unsafe, it is OK to remove this,
-// subject to all the usual considerations when writing unsafe.
-#![forbid(unsafe_code)]
+// The only unsafe code left is in the `acl` module, which needs to call
+// Windows APIs to inspect access control lists.
+#![deny(unsafe_code)]
 
+#[cfg(windows)]
+#[allow(unsafe_code)]
+mod acl;
 mod dir;
 mod disable;
 mod err;
+mod fix;
 mod imp;
 #[cfg(all(
     target_family = "unix",
@@ -83,6 +87,7 @@ use std::{
 pub use dir::CheckedDir;
 pub use disable::GLOBAL_DISABLE_VAR;
 pub use err::{format_access_bits, Error};
+pub use fix::PermissionFix;
 
 /// A result type as returned by this crate
 pub type Result<T> = std::result::Result<T, Error>;
@@ -895,6 +900,60 @@ mod test {
         assert_eq!(e.path().unwrap(), d.path("a/b/c/d"));
     }
 
+    #[cfg(target_family = "unix")]
+    #[cfg(feature = "walkdir")]
+    #[test]
+    fn fix_permissions() {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let d = Dir::new();
+        d.dir("a/b/c");
+        d.file("a/b/c/d");
+        d.chmod("a", 0o777);
+        d.chmod("a/b", 0o770);
+        d.chmod("a/b/c", 0o755);
+        d.chmod("a/b/c/d", 0o666);
+        let mode = |p: &str| std::fs::metadata(d.path(p)).unwrap().permissions().mode() & 0o7777;
+
+        let m = mistrust_build(&[
+            MistrustOp::IgnorePrefix(d.canonical_root()),
+            MistrustOp::TrustNoGroupId(),
+        ]);
+
+        let fixes = m
+            .verifier()
+            .check_content()
+            .fix_permissions(d.path("a/b"))
+            .unwrap();
+        let mut fixes: Vec<_> = fixes
+            .iter()
+            .map(|f| (f.path.canonicalize().unwrap(), f.old_mode, f.new_mode))
+            .collect();
+        fixes.sort();
+        let root = d.canonical_root();
+        assert_eq!(
+            fixes,
+            vec![
+                (root.join("a/b"), 0o770, 0o700),
+                (root.join("a/b/c/d"), 0o666, 0o644),
+            ]
+        );
+        assert_eq!(mode("a/b"), 0o700);
+        assert_eq!(mode("a/b/c"), 0o755);
+        assert_eq!(mode("a/b/c/d"), 0o644);
+
+        // We never touch ancestors, so the world-writable "a" is still a problem.
+        assert_eq!(mode("a"), 0o777);
+        let e = m
+            .verifier()
+            .all_errors()
+            .check_content()
+            .check(d.path("a/b"))
+            .unwrap_err();
+        assert_eq!(1, e.errors().count());
+        assert_eq!(e.path().unwrap(), root.join("a"));
+    }
+
     #[test]
     fn trust_everyone() {
         let d = Dir::new();
@@ -4,6 +4,7 @@
 //! (with [`disable_safe_logging`]) and locally (with
 //! [`with_safe_logging_suppressed`]).
 
+use crate::policy::{self, Category};
 use crate::{Error, Result};
 use fluid_let::fluid_let;
 use std::sync::atomic::{AtomicIsize, Ordering};
@@ -20,12 +21,21 @@ fluid_let!(
     static SAFE_LOGGING_SUPPRESSED_IN_THREAD: bool
 );
 
-/// Returns true if we are displaying sensitive values, false otherwise.
+/// Returns true if we are displaying all sensitive values, false otherwise.
 pub(crate) fn unsafe_logging_enabled() -> bool {
     LOGGING_STATE.load(Ordering::Relaxed) < 0
         || SAFE_LOGGING_SUPPRESSED_IN_THREAD.get(|v| v == Some(&true))
 }
 
+/// Returns true if we are displaying sensitive values in `category`, false otherwise.
+///
+/// Unless safe logging is disabled or enforced, this is decided by the
+/// [`RedactionPolicy`](crate::RedactionPolicy).
+pub(crate) fn unsafe_logging_enabled_for(category: Category) -> bool {
+    unsafe_logging_enabled()
+        || (LOGGING_STATE.load(Ordering::Relaxed) == 0 && policy::policy_shows(category))
+}
+
 /// Run a given function with the regular `safelog` functionality suppressed.
 ///
 /// The provided function, and everything it calls, will display
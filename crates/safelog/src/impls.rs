@@ -1,6 +1,6 @@
 //! Implement `Redactable` for various types.
 
-use super::Redactable;
+use super::{Category, Redactable};
 use std::fmt::{self, Formatter};
 
 // Network types.
@@ -9,12 +9,18 @@ impl Redactable for std::net::Ipv4Addr {
     fn display_redacted(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "{}.x.x.x", self.octets()[0])
     }
+    fn category(&self) -> Category {
+        Category::Address
+    }
 }
 
 impl Redactable for std::net::Ipv6Addr {
     fn display_redacted(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:x}:x:x:…", self.segments()[0])
     }
+    fn category(&self) -> Category {
+        Category::Address
+    }
 }
 
 impl Redactable for std::net::IpAddr {
@@ -24,18 +30,27 @@ impl Redactable for std::net::IpAddr {
             std::net::IpAddr::V6(v6) => v6.display_redacted(f),
         }
     }
+    fn category(&self) -> Category {
+        Category::Address
+    }
 }
 
 impl Redactable for std::net::SocketAddrV4 {
     fn display_redacted(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}:{}", self.ip().redacted(), self.port())
     }
+    fn category(&self) -> Category {
+        Category::Address
+    }
 }
 
 impl Redactable for std::net::SocketAddrV6 {
     fn display_redacted(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "[{}]:{}", self.ip().redacted(), self.port())
     }
+    fn category(&self) -> Category {
+        Category::Address
+    }
 }
 
 impl Redactable for std::net::SocketAddr {
@@ -45,6 +60,9 @@ impl Redactable for std::net::SocketAddr {
             std::net::SocketAddr::V6(v6) => v6.display_redacted(f),
         }
     }
+    fn category(&self) -> Category {
+        Category::Address
+    }
 }
 
 #[cfg(test)]
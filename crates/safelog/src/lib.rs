@@ -50,9 +50,14 @@ use serde::{Deserialize, Serialize};
 mod err;
 mod flags;
 mod impls;
+mod policy;
 
 pub use err::Error;
 pub use flags::{disable_safe_logging, enforce_safe_logging, with_safe_logging_suppressed, Guard};
+pub use policy::{
+    redaction_policy, set_redaction_policy, with_debug_severity, Category, Redaction,
+    RedactionPolicy,
+};
 
 use std::ops::Deref;
 
@@ -66,7 +71,8 @@ pub type Result<T> = std::result::Result<T, Error>;
 /// the string `[scrubbed]`.
 ///
 /// This behavior can be overridden locally by using
-/// [`with_safe_logging_suppressed`] and globally with [`disable_safe_logging`].
+/// [`with_safe_logging_suppressed`] and globally with [`disable_safe_logging`],
+/// or with a [`RedactionPolicy`] for [`Category::Other`].
 #[derive(Educe, Clone, Copy)]
 #[educe(
     Default(bound),
@@ -134,7 +140,7 @@ macro_rules! impl_display_traits {
     $(
         impl<T: std::fmt::$trait> std::fmt::$trait for Sensitive<T> {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                if flags::unsafe_logging_enabled() {
+                if flags::unsafe_logging_enabled_for(Category::Other) {
                     std::fmt::$trait::fmt(&self.0, f)
                 } else {
                     write!(f, "[scrubbed]")
@@ -221,6 +227,13 @@ pub trait Redactable: std::fmt::Display + std::fmt::Debug {
     fn debug_redacted(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.display_redacted(f)
     }
+    /// Return the category of sensitive information this object belongs to.
+    ///
+    /// The [`RedactionPolicy`] for this category decides
+    /// whether the object is actually redacted.
+    fn category(&self) -> Category {
+        Category::Other
+    }
     /// Return a smart pointer that will display or debug this object as its
     /// redacted form.
     fn redacted(&self) -> Redacted<&Self> {
@@ -240,6 +253,9 @@ impl<'a, T: Redactable + ?Sized> Redactable for &'a T {
     fn display_redacted(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         (*self).display_redacted(f)
     }
+    fn category(&self) -> Category {
+        (*self).category()
+    }
 }
 
 /// A wrapper around a `Redactable` that displays it in redacted format.
@@ -286,7 +302,7 @@ impl<T: Redactable> Redacted<T> {
 
 impl<T: Redactable> std::fmt::Display for Redacted<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if flags::unsafe_logging_enabled() {
+        if flags::unsafe_logging_enabled_for(self.0.category()) {
             std::fmt::Display::fmt(&self.0, f)
         } else {
             self.0.display_redacted(f)
@@ -296,7 +312,7 @@ impl<T: Redactable> std::fmt::Display for Redacted<T> {
 
 impl<T: Redactable> std::fmt::Debug for Redacted<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if flags::unsafe_logging_enabled() {
+        if flags::unsafe_logging_enabled_for(self.0.category()) {
             std::fmt::Debug::fmt(&self.0, f)
         } else {
             self.0.debug_redacted(f)
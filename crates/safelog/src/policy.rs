@@ -0,0 +1,238 @@
+//! A process-wide policy for redacting different categories of sensitive information.
+//!
+//! By default, every [`Sensitive`](crate::Sensitive) and [`Redacted`](crate::Redacted)
+//! value is redacted.
+//! With [`set_redaction_policy`], some [`Category`]s of information can instead be
+//! redacted only in high-severity messages, or not at all
+//! (for example, on a private test network, where nothing is confidential).
+//!
+//! The guards from [`disable_safe_logging`](crate::disable_safe_logging) and
+//! [`enforce_safe_logging`](crate::enforce_safe_logging),
+//! and [`with_safe_logging_suppressed`](crate::with_safe_logging_suppressed),
+//! take precedence over this policy.
+
+use fluid_let::fluid_let;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A category of sensitive information.
+///
+/// The category of a [`Redactable`](crate::Redactable) value is given by
+/// [`Redactable::category`](crate::Redactable::category);
+/// [`Sensitive`](crate::Sensitive) values are always in [`Category::Other`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum Category {
+    /// Network addresses, like IP addresses and the addresses of bridges.
+    Address,
+    /// Identities of relays.
+    RelayIdentity,
+    /// Onion service addresses.
+    OnionService,
+    /// Everything else.
+    Other,
+}
+
+impl Category {
+    /// Return the index of this category in [`POLICY`].
+    fn index(self) -> usize {
+        match self {
+            Category::Address => 0,
+            Category::RelayIdentity => 1,
+            Category::OnionService => 2,
+            Category::Other => 3,
+        }
+    }
+}
+
+/// How to treat one [`Category`] of sensitive information.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum Redaction {
+    /// Always redact this information.
+    #[default]
+    Always,
+    /// Redact this information in messages with a severity of `INFO` or higher,
+    /// but show it in `DEBUG` and `TRACE` messages.
+    ///
+    /// The severity of a message is known only to the code that formats it:
+    /// see [`with_debug_severity`].
+    /// Elsewhere, this is the same as [`Redaction::Always`].
+    InfoAndAbove,
+    /// Never redact this information.
+    ///
+    /// This is only suitable for private test networks.
+    Never,
+}
+
+impl Redaction {
+    /// Return the representation of this value in [`POLICY`].
+    fn to_u8(self) -> u8 {
+        match self {
+            Redaction::Always => 0,
+            Redaction::InfoAndAbove => 1,
+            Redaction::Never => 2,
+        }
+    }
+
+    /// Inverse of [`Redaction::to_u8`].
+    fn from_u8(v: u8) -> Self {
+        match v {
+            1 => Redaction::InfoAndAbove,
+            2 => Redaction::Never,
+            _ => Redaction::Always,
+        }
+    }
+}
+
+/// How to treat each [`Category`] of sensitive information.
+///
+/// The default policy is to always redact everything.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[non_exhaustive]
+pub struct RedactionPolicy {
+    /// How to treat [`Category::Address`].
+    pub address: Redaction,
+    /// How to treat [`Category::RelayIdentity`].
+    pub relay_identity: Redaction,
+    /// How to treat [`Category::OnionService`].
+    pub onion_service: Redaction,
+    /// How to treat [`Category::Other`].
+    pub other: Redaction,
+}
+
+impl RedactionPolicy {
+    /// Return how this policy treats `category`.
+    pub fn get(&self, category: Category) -> Redaction {
+        match category {
+            Category::Address => self.address,
+            Category::RelayIdentity => self.relay_identity,
+            Category::OnionService => self.onion_service,
+            Category::Other => self.other,
+        }
+    }
+}
+
+/// The current policy, as the [`Redaction::to_u8`] of each category,
+/// indexed by [`Category::index`].
+static POLICY: [AtomicU8; 4] = [
+    AtomicU8::new(0),
+    AtomicU8::new(0),
+    AtomicU8::new(0),
+    AtomicU8::new(0),
+];
+
+fluid_let!(
+    /// A dynamic variable set while we are formatting a DEBUG or TRACE message.
+    static DEBUG_SEVERITY_IN_THREAD: bool
+);
+
+/// Replace the process-wide [`RedactionPolicy`].
+///
+/// The new policy applies to every value formatted from now on,
+/// in every thread.
+pub fn set_redaction_policy(policy: &RedactionPolicy) {
+    for category in [
+        Category::Address,
+        Category::RelayIdentity,
+        Category::OnionService,
+        Category::Other,
+    ] {
+        POLICY[category.index()].store(policy.get(category).to_u8(), Ordering::Relaxed);
+    }
+}
+
+/// Return the current process-wide [`RedactionPolicy`].
+pub fn redaction_policy() -> RedactionPolicy {
+    let get =
+        |category: Category| Redaction::from_u8(POLICY[category.index()].load(Ordering::Relaxed));
+    RedactionPolicy {
+        address: get(Category::Address),
+        relay_identity: get(Category::RelayIdentity),
+        onion_service: get(Category::OnionService),
+        other: get(Category::Other),
+    }
+}
+
+/// Run a given function as if it were formatting a message of
+/// `DEBUG` or `TRACE` severity.
+///
+/// While the function runs (on the current thread), the categories for which the
+/// policy is [`Redaction::InfoAndAbove`] are not redacted.
+///
+/// This is meant to be called by logging backends, around the code that
+/// formats each low-severity message.
+pub fn with_debug_severity<F, V>(func: F) -> V
+where
+    F: FnOnce() -> V,
+{
+    DEBUG_SEVERITY_IN_THREAD.set(true, func)
+}
+
+/// Return true if the current policy says to show values in `category`.
+pub(crate) fn policy_shows(category: Category) -> bool {
+    match Redaction::from_u8(POLICY[category.index()].load(Ordering::Relaxed)) {
+        Redaction::Always => false,
+        Redaction::InfoAndAbove => DEBUG_SEVERITY_IN_THREAD.get(|v| v == Some(&true)),
+        Redaction::Never => true,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use crate::{enforce_safe_logging, Redactable as _, Sensitive};
+    use serial_test::serial;
+
+    #[test]
+    #[serial]
+    fn policy() {
+        let localhost = std::net::Ipv4Addr::LOCALHOST;
+        let secret = Sensitive::new("swordfish");
+        let closure = || format!("{} {}", localhost.redacted(), secret);
+        assert_eq!(redaction_policy(), RedactionPolicy::default());
+        assert_eq!(closure(), "127.x.x.x [scrubbed]");
+
+        let mut policy = RedactionPolicy {
+            address: Redaction::Never,
+            ..Default::default()
+        };
+        set_redaction_policy(&policy);
+        assert_eq!(redaction_policy(), policy);
+        assert_eq!(closure(), "127.0.0.1 [scrubbed]");
+
+        // Enforcing safe logging overrides the policy.
+        {
+            let _g = enforce_safe_logging().unwrap();
+            assert_eq!(closure(), "127.x.x.x [scrubbed]");
+        }
+
+        policy.address = Redaction::InfoAndAbove;
+        policy.other = Redaction::InfoAndAbove;
+        set_redaction_policy(&policy);
+        assert_eq!(closure(), "127.x.x.x [scrubbed]");
+        assert_eq!(with_debug_severity(closure), "127.0.0.1 swordfish");
+
+        set_redaction_policy(&RedactionPolicy::default());
+        assert_eq!(with_debug_severity(closure), "127.x.x.x [scrubbed]");
+    }
+}
@@ -0,0 +1,311 @@
+//! Attributing heap allocations to the subsystems that made them, for profiling.
+//!
+//! The memory quota system (`tor-memquota`) only knows about the memory in our queues.
+//! When diagnosing real memory bloat, it can be useful to know which part of Arti
+//! is responsible for the rest of the heap, too.
+//!
+//! Code that allocates on behalf of one [`Subsystem`] calls [`enter`],
+//! and holds the returned [`TagGuard`] while it does so.
+//! Every allocation made on that thread while the guard is held is attributed to that subsystem.
+//!
+//! Tagging only has any effect when the `alloc-tags` cargo feature is enabled,
+//! *and* the program has installed a [`TaggingAllocator`] as its global allocator.
+//! Otherwise, [`enter`] does nothing.
+//!
+//! ### Limitations
+//!
+//! The current subsystem is tracked per thread.
+//! So a [`TagGuard`] may not be held across an `.await`
+//! (it isn't `Send`, which helps to prevent that).
+//! Allocations are attributed to the subsystem which was current when they were made,
+//! even if the memory is later handed on to some other part of the program.
+
+use std::marker::PhantomData;
+
+#[cfg(feature = "alloc-tags")]
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+};
+
+/// A part of Arti to which heap allocations can be attributed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum Subsystem {
+    /// Encoding and decoding channel and relay cells.
+    Cells,
+    /// Parsing network documents (consensuses, microdescriptors, and so on).
+    Netdoc,
+    /// Loading, generating, and storing keys.
+    Keystore,
+    /// Encoding and decoding RPC messages.
+    Rpc,
+}
+
+impl Subsystem {
+    /// Every `Subsystem`, in order.
+    pub const ALL: [Subsystem; 4] = [
+        Subsystem::Cells,
+        Subsystem::Netdoc,
+        Subsystem::Keystore,
+        Subsystem::Rpc,
+    ];
+
+    /// Return a short lowercase name for this subsystem, suitable for reports.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Subsystem::Cells => "cells",
+            Subsystem::Netdoc => "netdoc",
+            Subsystem::Keystore => "keystore",
+            Subsystem::Rpc => "rpc",
+        }
+    }
+
+    /// Return the tag we record for this subsystem.
+    ///
+    /// Tag 0 means "untagged".
+    #[cfg(feature = "alloc-tags")]
+    fn tag(self) -> u8 {
+        match self {
+            Subsystem::Cells => 1,
+            Subsystem::Netdoc => 2,
+            Subsystem::Keystore => 3,
+            Subsystem::Rpc => 4,
+        }
+    }
+}
+
+/// Number of distinct tags, including 0 ("untagged").
+#[cfg(feature = "alloc-tags")]
+const N_TAGS: usize = Subsystem::ALL.len() + 1;
+
+#[cfg(feature = "alloc-tags")]
+thread_local! {
+    /// The tag for allocations made on this thread right now.
+    ///
+    /// This must not need a destructor, or allocate when it's first used:
+    /// it is read from within the allocator.
+    static CURRENT: Cell<u8> = const { Cell::new(0) };
+}
+
+/// Bytes currently allocated under each tag.
+#[cfg(feature = "alloc-tags")]
+static LIVE_BYTES: [AtomicUsize; N_TAGS] = {
+    #[allow(clippy::declare_interior_mutable_const)] // Only used to initialise the array
+    const ZERO: AtomicUsize = AtomicUsize::new(0);
+    [ZERO; N_TAGS]
+};
+
+/// Number of allocations ever made under each tag.
+#[cfg(feature = "alloc-tags")]
+static N_ALLOCS: [AtomicU64; N_TAGS] = {
+    #[allow(clippy::declare_interior_mutable_const)] // Only used to initialise the array
+    const ZERO: AtomicU64 = AtomicU64::new(0);
+    [ZERO; N_TAGS]
+};
+
+/// Set once a [`TaggingAllocator`] has made any allocation.
+#[cfg(feature = "alloc-tags")]
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Guard returned by [`enter`]
+///
+/// While this is held, allocations on this thread are attributed to a [`Subsystem`].
+/// When it is dropped, the previous subsystem (if any) becomes current again.
+#[must_use = "allocations are only attributed to the subsystem while the guard is held"]
+#[derive(Debug)]
+pub struct TagGuard {
+    /// The tag to restore when we are dropped.
+    #[cfg(feature = "alloc-tags")]
+    prev: u8,
+    /// Make this type `!Send`, since the tag is per-thread.
+    _not_send: PhantomData<*const ()>,
+}
+
+/// Attribute allocations on this thread to `subsystem`, until the returned guard is dropped
+///
+/// Does nothing unless the `alloc-tags` feature is enabled.
+pub fn enter(subsystem: Subsystem) -> TagGuard {
+    #[cfg(not(feature = "alloc-tags"))]
+    let _ = subsystem;
+    TagGuard {
+        #[cfg(feature = "alloc-tags")]
+        prev: CURRENT
+            .try_with(|c| c.replace(subsystem.tag()))
+            .unwrap_or(0),
+        _not_send: PhantomData,
+    }
+}
+
+#[cfg(feature = "alloc-tags")]
+impl Drop for TagGuard {
+    fn drop(&mut self) {
+        let _ = CURRENT.try_with(|c| c.set(self.prev));
+    }
+}
+
+/// A global allocator that attributes allocations to [`Subsystem`]s
+///
+/// Install it in a binary with
+/// `#[global_allocator] static ALLOC: TaggingAllocator = TaggingAllocator::new(System);`.
+///
+/// Each allocation is a little larger than requested,
+/// since we record the tag alongside it.
+#[cfg(feature = "alloc-tags")]
+#[derive(Debug, Default)]
+pub struct TaggingAllocator<A = System> {
+    /// The allocator that actually provides the memory.
+    inner: A,
+}
+
+#[cfg(feature = "alloc-tags")]
+impl<A> TaggingAllocator<A> {
+    /// Return a new `TaggingAllocator` that obtains memory from `inner`.
+    pub const fn new(inner: A) -> Self {
+        TaggingAllocator { inner }
+    }
+
+    /// Return the layout to ask `inner` for, and the offset of the caller's memory within it
+    ///
+    /// The tag is stored in the `usize` immediately before the caller's memory.
+    fn outer_layout(layout: Layout) -> Option<(Layout, usize)> {
+        let header = layout.align().max(std::mem::size_of::<usize>());
+        let size = layout.size().checked_add(header)?;
+        let align = layout.align().max(std::mem::align_of::<usize>());
+        Some((Layout::from_size_align(size, align).ok()?, header))
+    }
+}
+
+// SAFETY: We pass on the requirements of `GlobalAlloc` to `inner`,
+// with a layout which has room for our header in front of the caller's memory,
+// and which is aligned at least as strictly as the caller's layout.
+#[cfg(feature = "alloc-tags")]
+unsafe impl<A: GlobalAlloc> GlobalAlloc for TaggingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let Some((outer, header)) = Self::outer_layout(layout) else {
+            return std::ptr::null_mut();
+        };
+        // SAFETY: `outer` has nonzero size, since it includes the header.
+        let base = unsafe { self.inner.alloc(outer) };
+        if base.is_null() {
+            return base;
+        }
+        let tag = CURRENT.try_with(|c| c.get()).unwrap_or(0);
+        // SAFETY: `header` is within the allocation, and is a multiple of
+        // `usize`'s alignment, as is `base`; so the `usize` before it is in bounds
+        // and aligned.
+        unsafe {
+            let ptr = base.add(header);
+            ptr.cast::<usize>().sub(1).write(usize::from(tag));
+            INSTALLED.store(true, Ordering::Relaxed);
+            LIVE_BYTES[usize::from(tag)].fetch_add(layout.size(), Ordering::Relaxed);
+            N_ALLOCS[usize::from(tag)].fetch_add(1, Ordering::Relaxed);
+            ptr
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let (outer, header) =
+            Self::outer_layout(layout).expect("layout was valid when we allocated it");
+        // SAFETY: `ptr` came from `alloc`, with the same `layout`,
+        // so it is `header` bytes into an allocation of `outer` from `inner`,
+        // with the tag written just before it.
+        unsafe {
+            let tag = ptr.cast::<usize>().sub(1).read();
+            LIVE_BYTES[tag].fetch_sub(layout.size(), Ordering::Relaxed);
+            self.inner.dealloc(ptr.sub(header), outer);
+        }
+    }
+}
+
+/// The heap usage attributed to one subsystem, as reported by [`usage`]
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct SubsystemUsage {
+    /// The subsystem, or `None` for allocations made outside any subsystem.
+    pub subsystem: Option<Subsystem>,
+    /// The number of bytes currently allocated.
+    pub live_bytes: usize,
+    /// The number of allocations made since the program started.
+    pub total_allocs: u64,
+}
+
+/// Report the heap usage attributed to each subsystem
+///
+/// The untagged allocations come first, followed by each subsystem in [`Subsystem::ALL`] order.
+///
+/// Returns `None` if no [`TaggingAllocator`] is in use.
+#[cfg(feature = "alloc-tags")]
+pub fn usage() -> Option<Vec<SubsystemUsage>> {
+    if !INSTALLED.load(Ordering::Relaxed) {
+        return None;
+    }
+    let subsystems = std::iter::once(None).chain(Subsystem::ALL.into_iter().map(Some));
+    Some(
+        subsystems
+            .zip(LIVE_BYTES.iter().zip(N_ALLOCS.iter()))
+            .map(|(subsystem, (live, n))| SubsystemUsage {
+                subsystem,
+                live_bytes: live.load(Ordering::Relaxed),
+                total_allocs: n.load(Ordering::Relaxed),
+            })
+            .collect(),
+    )
+}
+
+#[cfg(all(test, feature = "alloc-tags"))]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+
+    use super::*;
+
+    #[test]
+    fn tags_nest() {
+        let get = || CURRENT.with(|c| c.get());
+        assert_eq!(get(), 0);
+        {
+            let _g = enter(Subsystem::Netdoc);
+            assert_eq!(get(), Subsystem::Netdoc.tag());
+            {
+                let _g = enter(Subsystem::Cells);
+                assert_eq!(get(), Subsystem::Cells.tag());
+            }
+            assert_eq!(get(), Subsystem::Netdoc.tag());
+        }
+        assert_eq!(get(), 0);
+    }
+
+    #[test]
+    fn alloc_and_free() {
+        let a = TaggingAllocator::new(System);
+        for align in [1, 8, 64] {
+            let layout = Layout::from_size_align(100, align).unwrap();
+            let before = LIVE_BYTES[usize::from(Subsystem::Rpc.tag())].load(Ordering::Relaxed);
+            let ptr = {
+                let _g = enter(Subsystem::Rpc);
+                // SAFETY: layout has nonzero size
+                unsafe { a.alloc(layout) }
+            };
+            assert!(!ptr.is_null());
+            assert_eq!(ptr as usize % align, 0);
+            let during = LIVE_BYTES[usize::from(Subsystem::Rpc.tag())].load(Ordering::Relaxed);
+            assert!(during >= before + 100);
+            // SAFETY: ptr came from a.alloc(layout)
+            unsafe { a.dealloc(ptr, layout) };
+        }
+        assert!(usage().unwrap().iter().any(|u| u.subsystem == Some(Subsystem::Rpc)));
+    }
+}
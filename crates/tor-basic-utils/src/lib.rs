@@ -48,6 +48,7 @@ use std::ops::{RangeInclusive, RangeToInclusive};
 use std::path::Path;
 use std::time::Duration;
 
+pub mod alloc_tag;
 pub mod iter;
 pub mod n_key_list;
 pub mod n_key_set;
@@ -2,6 +2,7 @@
 
 use std::borrow::Cow;
 use std::num::NonZeroUsize;
+use std::sync::Arc;
 
 use derive_deftly::{define_derive_deftly, Deftly};
 use safelog::Sensitive;
@@ -143,6 +144,12 @@ pub enum EncodeError {
     /// unwrap() from within encoding implementations.
     #[error("Internal error")]
     Bug(#[from] Bug),
+    /// We couldn't sign the object.
+    ///
+    /// This can happen when the signing key is not held in memory,
+    /// but by some device or service that can fail.
+    #[error("Unable to sign object")]
+    Signing(#[source] Arc<dyn std::error::Error + Send + Sync + 'static>),
 }
 
 impl EncodeError {
@@ -155,7 +162,9 @@ impl EncodeError {
     pub fn always_bug(self) -> Bug {
         match self {
             EncodeError::Bug(bug) => bug,
-            EncodeError::BadLengthValue => into_internal!("EncodingError")(self),
+            EncodeError::BadLengthValue | EncodeError::Signing(_) => {
+                into_internal!("EncodingError")(self)
+            }
         }
     }
 }
@@ -165,7 +174,9 @@ impl From<EncodeError> for Bug {
     fn from(error: EncodeError) -> Bug {
         match error {
             EncodeError::Bug(bug) => bug,
-            EncodeError::BadLengthValue => into_internal!("EncodingError")(error),
+            EncodeError::BadLengthValue | EncodeError::Signing(_) => {
+                into_internal!("EncodingError")(error)
+            }
         }
     }
 }
@@ -3,6 +3,7 @@
 use super::{ChanCell, CELL_DATA_LEN};
 use crate::chancell::{ChanCmd, ChanMsg, CircId};
 use crate::Error;
+use tor_basic_utils::alloc_tag;
 use tor_bytes::{self, Reader, Writer};
 use tor_error::internal;
 
@@ -119,6 +120,7 @@ impl ChannelCodec {
             return Ok(None);
         }
 
+        let _tag = alloc_tag::enter(alloc_tag::Subsystem::Cells);
         let cell = src.split_to(cell_len).freeze();
         //trace!("{:?} cell body ({}) is {:?}", cmd, cell.len(), &cell[..]);
         let mut r = Reader::from_bytes(&cell);
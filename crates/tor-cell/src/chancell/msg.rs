@@ -640,6 +640,11 @@ impl Netinfo {
             Some(SystemTime::UNIX_EPOCH + Duration::from_secs(self.timestamp.into()))
         }
     }
+    /// Return the address that the sender of this NETINFO cell
+    /// reported seeing the recipient at, if any.
+    pub fn their_addr(&self) -> Option<&IpAddr> {
+        self.their_addr.as_ref()
+    }
 }
 impl Body for Netinfo {
     fn encode_onto<W: Writer + ?Sized>(self, w: &mut W) -> EncodeResult<()> {
@@ -14,6 +14,7 @@ use caret::caret_int;
 use rand::{CryptoRng, Rng};
 
 pub mod extend;
+pub mod extlist;
 #[cfg(feature = "hs")]
 pub mod hs;
 pub mod msg;
@@ -122,6 +123,8 @@ impl RelayCmd {
             | RelayCmd::TRUNCATE
             | RelayCmd::TRUNCATED
             | RelayCmd::DROP
+            | RelayCmd::PADDING_NEGOTIATE
+            | RelayCmd::PADDING_NEGOTIATED
             | RelayCmd::EXTEND2
             | RelayCmd::EXTENDED2
             | RelayCmd::ESTABLISH_INTRO
@@ -1,11 +1,13 @@
 //! Types and encodings used during circuit extension.
 
+use crate::relaycell::extlist::{ExtGroup, ExtList};
 use crate::{Error, Result};
 use caret::caret_int;
 use tor_bytes::{EncodeResult, Readable, Reader, Writeable, Writer};
 
 caret_int! {
     /// A type of ntor v3 extension data (`EXT_FIELD_TYPE`).
+    #[derive(Ord, PartialOrd)]
     pub struct NtorV3ExtensionType(u8) {
         /// Request congestion control be enabled for a circuit.
         CC_REQUEST = 1,
@@ -41,89 +43,145 @@ pub enum NtorV3Extension {
 impl NtorV3Extension {
     /// Encode a set of extensions into a "message" for an ntor v3 handshake.
     pub fn write_many_onto<W: Writer>(exts: &[NtorV3Extension], out: &mut W) -> EncodeResult<()> {
-        let n_extensions =
-            u8::try_from(exts.len()).map_err(|_| tor_bytes::EncodeError::BadLengthValue)?;
-        out.write_u8(n_extensions);
-        exts.iter().try_for_each(|x| x.write_onto(out))
+        ExtList::from(exts.to_vec()).write_onto(out)
     }
 
     /// Decode a slice of bytes representing the "message" of an ntor v3 handshake into a set of
     /// extensions.
     pub fn decode(message: &[u8]) -> Result<Vec<Self>> {
         let mut reader = Reader::from_slice(message);
-        let mut ret = vec![];
-        let n_extensions = reader.take_u8().map_err(|e| Error::BytesErr {
-            err: e,
-            parsed: "n_extensions",
-        })?;
-        for _ in 0..n_extensions {
-            ret.push(
-                NtorV3Extension::take_from(&mut reader).map_err(|err| Error::BytesErr {
-                    err,
-                    parsed: "an ntor extension",
-                })?,
-            );
-        }
-        if reader.remaining() > 0 {
-            return Err(Error::BytesErr {
-                err: tor_bytes::Error::ExtraneousBytes,
+        let exts: ExtList<Self> = reader
+            .extract()
+            .and_then(|exts| {
+                reader.should_be_exhausted()?;
+                Ok(exts)
+            })
+            .map_err(|err| Error::BytesErr {
+                err,
                 parsed: "ntor extensions set",
-            });
+            })?;
+        Ok(exts.into())
+    }
+}
+
+impl ExtGroup for NtorV3Extension {
+    type Id = NtorV3ExtensionType;
+
+    fn type_id(&self) -> NtorV3ExtensionType {
+        match self {
+            NtorV3Extension::RequestCongestionControl => NtorV3ExtensionType::CC_REQUEST,
+            NtorV3Extension::AckCongestionControl { .. } => NtorV3ExtensionType::CC_RESPONSE,
+            NtorV3Extension::Unrecognized { field_type, .. } => *field_type,
         }
-        Ok(ret)
     }
 }
 
 impl Writeable for NtorV3Extension {
     fn write_onto<W: Writer + ?Sized>(&self, out: &mut W) -> EncodeResult<()> {
+        out.write_u8(self.type_id().get());
+        let mut body = out.write_nested_u8len();
         match self {
-            NtorV3Extension::RequestCongestionControl => {
-                out.write_all(&[1, 0]);
-            }
+            NtorV3Extension::RequestCongestionControl => {}
             NtorV3Extension::AckCongestionControl { sendme_inc } => {
-                out.write_all(&[2, 1, *sendme_inc]);
+                body.write_u8(*sendme_inc);
             }
-            NtorV3Extension::Unrecognized { field_type, data } => {
-                // FIXME(eta): This will break if you try and fill `data` with more than 255 bytes.
-                //             This is only a problem if you construct your own `Unrecognized`, though.
-                out.write_all(&[field_type.get(), data.len() as u8]);
-                out.write_all(data);
+            NtorV3Extension::Unrecognized { data, .. } => {
+                body.write_all(data);
             }
         }
-        Ok(())
+        body.finish()
     }
 }
 
 impl Readable for NtorV3Extension {
     fn take_from(reader: &mut Reader<'_>) -> tor_bytes::Result<Self> {
         let tag: NtorV3ExtensionType = reader.take_u8()?.into();
-        let len = reader.take_u8()?;
-        Ok(match tag {
-            NtorV3ExtensionType::CC_REQUEST => {
-                if len != 0 {
-                    return Err(tor_bytes::Error::InvalidMessage(
-                        "invalid length for RequestCongestionControl".into(),
-                    ));
-                }
-                NtorV3Extension::RequestCongestionControl
-            }
-            NtorV3ExtensionType::CC_RESPONSE => {
-                if len != 1 {
-                    return Err(tor_bytes::Error::InvalidMessage(
-                        "invalid length for AckCongestionControl".into(),
-                    ));
-                }
-                let sendme_inc = reader.take_u8()?;
-                NtorV3Extension::AckCongestionControl { sendme_inc }
-            }
-            x => {
-                let mut data = vec![0; len as usize];
-                reader.take_into(&mut data)?;
-                NtorV3Extension::Unrecognized {
+        reader.read_nested_u8len(|body| {
+            let ext = match tag {
+                NtorV3ExtensionType::CC_REQUEST => NtorV3Extension::RequestCongestionControl,
+                NtorV3ExtensionType::CC_RESPONSE => NtorV3Extension::AckCongestionControl {
+                    sendme_inc: body.take_u8()?,
+                },
+                x => NtorV3Extension::Unrecognized {
                     field_type: x,
-                    data,
-                }
-            }
+                    data: body.take_rest().into(),
+                },
+            };
+            Ok(ext)
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+
+    use super::*;
+    use hex_literal::hex;
+
+    #[test]
+    fn roundtrip() {
+        let exts = vec![
+            NtorV3Extension::AckCongestionControl { sendme_inc: 31 },
+            NtorV3Extension::Unrecognized {
+                field_type: 77.into(),
+                data: b"hello".to_vec(),
+            },
+            NtorV3Extension::RequestCongestionControl,
+        ];
+        let mut encoded = Vec::new();
+        NtorV3Extension::write_many_onto(&exts, &mut encoded).unwrap();
+        assert_eq!(encoded, hex!("03 01 00 02 01 1f 4d 05 68656c6c6f").to_vec());
+
+        // Extensions are encoded in order of their types;
+        // unrecognized extensions survive a round trip unchanged.
+        let decoded = NtorV3Extension::decode(&encoded).unwrap();
+        assert_eq!(decoded.len(), 3);
+        assert_eq!(decoded[0], exts[2]);
+        assert_eq!(decoded[1], exts[0]);
+        assert_eq!(decoded[2], exts[1]);
+
+        let list = ExtList::from(decoded);
+        assert_eq!(
+            list.by_type(NtorV3ExtensionType::CC_RESPONSE),
+            Some(&NtorV3Extension::AckCongestionControl { sendme_inc: 31 })
+        );
+        assert!(list.by_type(78.into()).is_none());
+    }
+
+    #[test]
+    fn bad_encodings() {
+        // A CC_RESPONSE with a trailing byte.
+        assert!(NtorV3Extension::decode(&hex!("01 02 02 1f 00")).is_err());
+        // An extension that is longer than the message.
+        assert!(NtorV3Extension::decode(&hex!("01 4d 05 6868")).is_err());
+        // Trailing bytes after the extensions.
+        assert!(NtorV3Extension::decode(&hex!("01 01 00 00")).is_err());
+        // A message without even an extension count.
+        assert!(NtorV3Extension::decode(&[]).is_err());
+    }
+
+    #[test]
+    fn long_extension() {
+        // Extensions whose bodies don't fit in a one-byte length are rejected
+        // when encoding, rather than silently truncated.
+        let ext = NtorV3Extension::Unrecognized {
+            field_type: 9.into(),
+            data: vec![0; 256],
+        };
+        let mut encoded = Vec::new();
+        assert!(NtorV3Extension::write_many_onto(&[ext], &mut encoded).is_err());
+    }
+}
@@ -1,14 +1,20 @@
-//! Helpers to manage lists of HS cell extensions.
-//
-// TODO: We might generalize this even more in the future to handle other
-// similar lists in our cell protocol.
+//! Helpers to manage lists of typed extensions.
+//!
+//! Several messages in the Tor protocol carry a list of extensions
+//! in a common type-length-value format:
+//! the onion service messages, and the messages exchanged during an
+//! ntor v3 handshake, among others.
+//! This module provides a single implementation of that format,
+//! so that each message only needs to describe the extensions it knows about.
+//! Extensions that we don't recognize are preserved as [`UnrecognizedExt`],
+//! and are re-encoded unchanged.
 
 use derive_deftly::Deftly;
 use tor_bytes::{EncodeError, EncodeResult, Readable, Reader, Result, Writeable, Writer};
 use tor_memquota::{derive_deftly_template_HasMemoryCost, HasMemoryCostStructural};
 
-/// A list of extensions, represented in a common format used by many HS-related
-/// message.
+/// A list of extensions, represented in a common format used by many
+/// messages.
 ///
 /// The common format is:
 /// ```text
@@ -28,7 +34,7 @@ use tor_memquota::{derive_deftly_template_HasMemoryCost, HasMemoryCostStructural
 #[derive(Clone, Debug, derive_more::Deref, derive_more::DerefMut, Deftly)]
 #[derive_deftly(HasMemoryCost)]
 #[deftly(has_memory_cost(bounds = "T: HasMemoryCostStructural"))]
-pub(super) struct ExtList<T> {
+pub struct ExtList<T> {
     /// The extensions themselves.
     extensions: Vec<T>,
 }
@@ -39,19 +45,19 @@ impl<T> Default for ExtList<T> {
         }
     }
 }
-/// An kind of extension that can be used with some kind of HS-related message.
+/// A kind of extension that can be used with some kind of message.
 ///
 /// Each extendible message will likely define its own enum,
 /// implementing this trait,
 /// representing the possible extensions.
-pub(super) trait ExtGroup: Readable + Writeable {
+pub trait ExtGroup: Readable + Writeable {
     /// An identifier kind used with this sort of extension
     type Id: From<u8> + Into<u8> + Eq + PartialEq + Ord + Copy;
     /// The field-type id for this particular extension.
     fn type_id(&self) -> Self::Id;
 }
-/// A single typed extension that can be used with some kind of HS-related message.
-pub(super) trait Ext: Sized {
+/// A single typed extension that can be used with some kind of message.
+pub trait Ext: Sized {
     /// An identifier kind used with this sort of extension.
     ///
     /// Typically defined with caret_int.
@@ -87,16 +93,33 @@ impl<T: ExtGroup> Writeable for ExtList<T> {
         Ok(())
     }
 }
+impl<T> From<Vec<T>> for ExtList<T> {
+    fn from(extensions: Vec<T>) -> Self {
+        Self { extensions }
+    }
+}
+impl<T> From<ExtList<T>> for Vec<T> {
+    fn from(list: ExtList<T>) -> Self {
+        list.extensions
+    }
+}
 impl<T: ExtGroup> ExtList<T> {
     /// Insert `ext` into this list of extensions, replacing any previous
     /// extension with the same field type ID.
-    pub(super) fn replace_by_type(&mut self, ext: T) {
+    pub fn replace_by_type(&mut self, ext: T) {
         self.retain(|e| e.type_id() != ext.type_id());
         self.push(ext);
     }
+
+    /// Return the first extension in this list with the field type ID `type_id`, if any.
+    ///
+    /// (Parties must ignore all but the first extension of any given type.)
+    pub fn by_type(&self, type_id: T::Id) -> Option<&T> {
+        self.iter().find(|e| e.type_id() == type_id)
+    }
 }
 
-/// An unrecognized or unencoded extension for some HS-related message.
+/// An unrecognized or unencoded extension for some message.
 #[derive(Clone, Debug, Deftly)]
 #[derive_deftly(HasMemoryCost)]
 // Use `Copy + 'static` and `#[deftly(has_memory_cost(copy))]` so that we don't
@@ -105,9 +128,9 @@ impl<T: ExtGroup> ExtList<T> {
 pub struct UnrecognizedExt<ID> {
     /// The field type ID for this extension.
     #[deftly(has_memory_cost(copy))]
-    pub(super) type_id: ID,
+    pub(crate) type_id: ID,
     /// The body of this extension.
-    pub(super) body: Vec<u8>,
+    pub(crate) body: Vec<u8>,
 }
 
 impl<ID> UnrecognizedExt<ID> {
@@ -124,13 +147,26 @@ impl<ID> UnrecognizedExt<ID> {
             body: body.into(),
         }
     }
+
+    /// Return the body of this extension.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+}
+
+impl<ID: Copy> UnrecognizedExt<ID> {
+    /// Return the field type ID of this extension.
+    pub fn type_id(&self) -> ID {
+        self.type_id
+    }
 }
 
 /// Declare an Extension group that takes a given identifier.
 //
-// TODO: This is rather similar to restrict_msg(), isn't it?  Also, We use this
-// pattern of (number, (cmd, length, body)*) a few of times in Tor outside the
-// hs module.  Perhaps we can extend and unify our code here...
+// TODO: This is rather similar to restrict_msg(), isn't it?
+//
+// (Currently, only the onion service messages use this macro.)
+#[cfg_attr(not(feature = "hs"), allow(unused_macros))]
 macro_rules! decl_extension_group {
     {
         $( #[$meta:meta] )*
@@ -217,4 +253,5 @@ macro_rules! decl_extension_group {
         }
 }}
 }
-pub(super) use decl_extension_group;
+#[cfg_attr(not(feature = "hs"), allow(unused_imports))]
+pub(crate) use decl_extension_group;
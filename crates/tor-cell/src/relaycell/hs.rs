@@ -1,6 +1,6 @@
 //! Encoding and decoding for relay messages related to onion services.
 
-use self::ext::{decl_extension_group, ExtGroup, ExtList};
+use super::extlist::{decl_extension_group, ExtGroup, ExtList};
 
 use super::msg::{self, Body};
 use caret::caret_int;
@@ -12,11 +12,10 @@ use tor_llcrypto::pk::rsa::RsaIdentity;
 use tor_memquota::derive_deftly_template_HasMemoryCost;
 
 pub mod est_intro;
-mod ext;
 pub mod intro_payload;
 pub mod pow;
 
-pub use ext::UnrecognizedExt;
+pub use super::extlist::UnrecognizedExt;
 
 caret_int! {
     /// The type of the introduction point auth key
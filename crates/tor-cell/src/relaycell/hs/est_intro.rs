@@ -13,7 +13,7 @@ use tor_llcrypto::{
 use tor_memquota::derive_deftly_template_HasMemoryCost;
 use tor_units::BoundedInt32;
 
-use crate::relaycell::{hs::ext::*, hs::AuthKeyType, msg};
+use crate::relaycell::{extlist::*, hs::AuthKeyType, msg};
 
 caret_int! {
     /// The introduction protocol extension type.
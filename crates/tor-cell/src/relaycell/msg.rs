@@ -67,6 +67,10 @@ pub enum AnyRelayMsg : RelayMsg {
     Resolved,
     /// Start a directory stream
     BeginDir,
+    /// Start or stop a circuit padding machine
+    PaddingNegotiate,
+    /// Response to a PaddingNegotiate message
+    PaddingNegotiated,
     /// Start a UDP stream.
     [feature = "experimental-udp"]
     ConnectUdp,
@@ -1243,6 +1247,165 @@ empty_body! {
     pub struct BeginDir {}
 }
 
+caret_int! {
+    /// A command in a [`PaddingNegotiate`] or [`PaddingNegotiated`] message.
+    #[derive(Deftly)]
+    #[derive_deftly(HasMemoryCost)]
+    pub struct CircPadCmd(u8) {
+        /// Stop a padding machine.
+        STOP = 1,
+        /// Start a padding machine.
+        START = 2,
+    }
+}
+
+caret_int! {
+    /// The outcome of a [`PaddingNegotiate`] message, as reported in a [`PaddingNegotiated`] message.
+    #[derive(Deftly)]
+    #[derive_deftly(HasMemoryCost)]
+    pub struct CircPadResponse(u8) {
+        /// The command was carried out.
+        OK = 1,
+        /// The command failed (for instance, because the machine is unknown).
+        ERR = 2,
+    }
+}
+
+/// The only version of the circuit padding negotiation messages that we know.
+const CIRCPAD_VERSION: u8 = 0;
+
+/// A PaddingNegotiate message asks a relay to start or stop a circuit padding machine.
+///
+/// The relay answers with a [`PaddingNegotiated`] message.
+#[derive(Debug, Clone, Deftly)]
+#[derive_deftly(HasMemoryCost)]
+pub struct PaddingNegotiate {
+    /// Whether to start or stop the machine.
+    command: CircPadCmd,
+    /// The type of the padding machine.
+    machine_type: u8,
+    /// A counter identifying this instance of the machine on the circuit.
+    machine_ctr: u32,
+}
+impl PaddingNegotiate {
+    /// Construct a new PaddingNegotiate message.
+    pub fn new(command: CircPadCmd, machine_type: u8, machine_ctr: u32) -> Self {
+        PaddingNegotiate {
+            command,
+            machine_type,
+            machine_ctr,
+        }
+    }
+    /// Return the command of this message.
+    pub fn command(&self) -> CircPadCmd {
+        self.command
+    }
+    /// Return the type of the padding machine this message is about.
+    pub fn machine_type(&self) -> u8 {
+        self.machine_type
+    }
+    /// Return the counter of the padding machine this message is about.
+    pub fn machine_ctr(&self) -> u32 {
+        self.machine_ctr
+    }
+}
+impl Body for PaddingNegotiate {
+    fn decode_from_reader(r: &mut Reader<'_>) -> Result<Self> {
+        if r.take_u8()? != CIRCPAD_VERSION {
+            return Err(Error::InvalidMessage(
+                "Unrecognized PADDING_NEGOTIATE version.".into(),
+            ));
+        }
+        let command = r.take_u8()?.into();
+        let machine_type = r.take_u8()?;
+        // The echo_request field is never used.
+        let _echo_request = r.take_u8()?;
+        let machine_ctr = r.take_u32()?;
+        Ok(PaddingNegotiate {
+            command,
+            machine_type,
+            machine_ctr,
+        })
+    }
+    fn encode_onto<W: Writer + ?Sized>(self, w: &mut W) -> EncodeResult<()> {
+        w.write_u8(CIRCPAD_VERSION);
+        w.write_u8(self.command.into());
+        w.write_u8(self.machine_type);
+        w.write_u8(0);
+        w.write_u32(self.machine_ctr);
+        Ok(())
+    }
+}
+
+/// A PaddingNegotiated message is a relay's response to a [`PaddingNegotiate`] message.
+#[derive(Debug, Clone, Deftly)]
+#[derive_deftly(HasMemoryCost)]
+pub struct PaddingNegotiated {
+    /// The command of the PaddingNegotiate message we are responding to.
+    command: CircPadCmd,
+    /// Whether the command was carried out.
+    response: CircPadResponse,
+    /// The type of the padding machine.
+    machine_type: u8,
+    /// A counter identifying this instance of the machine on the circuit.
+    machine_ctr: u32,
+}
+impl PaddingNegotiated {
+    /// Construct a new PaddingNegotiated message.
+    pub fn new(
+        command: CircPadCmd,
+        response: CircPadResponse,
+        machine_type: u8,
+        machine_ctr: u32,
+    ) -> Self {
+        PaddingNegotiated {
+            command,
+            response,
+            machine_type,
+            machine_ctr,
+        }
+    }
+    /// Return the command this message is responding to.
+    pub fn command(&self) -> CircPadCmd {
+        self.command
+    }
+    /// Return whether the command was carried out.
+    pub fn response(&self) -> CircPadResponse {
+        self.response
+    }
+    /// Return the type of the padding machine this message is about.
+    pub fn machine_type(&self) -> u8 {
+        self.machine_type
+    }
+    /// Return the counter of the padding machine this message is about.
+    pub fn machine_ctr(&self) -> u32 {
+        self.machine_ctr
+    }
+}
+impl Body for PaddingNegotiated {
+    fn decode_from_reader(r: &mut Reader<'_>) -> Result<Self> {
+        if r.take_u8()? != CIRCPAD_VERSION {
+            return Err(Error::InvalidMessage(
+                "Unrecognized PADDING_NEGOTIATED version.".into(),
+            ));
+        }
+        Ok(PaddingNegotiated {
+            command: r.take_u8()?.into(),
+            response: r.take_u8()?.into(),
+            machine_type: r.take_u8()?,
+            machine_ctr: r.take_u32()?,
+        })
+    }
+    fn encode_onto<W: Writer + ?Sized>(self, w: &mut W) -> EncodeResult<()> {
+        w.write_u8(CIRCPAD_VERSION);
+        w.write_u8(self.command.into());
+        w.write_u8(self.response.into());
+        w.write_u8(self.machine_type);
+        w.write_u32(self.machine_ctr);
+        Ok(())
+    }
+}
+
 /// Helper: declare a RelayMsg implementation for a message type that has a
 /// fixed command.
 //
@@ -1287,8 +1450,23 @@ macro_rules! msg_impl_relaymsg {
 }
 
 msg_impl_relaymsg!(
-    Begin, Data, End, Connected, Sendme, Extend, Extended, Extend2, Extended2, Truncate, Truncated,
-    Drop, Resolve, Resolved, BeginDir,
+    Begin,
+    Data,
+    End,
+    Connected,
+    Sendme,
+    Extend,
+    Extended,
+    Extend2,
+    Extended2,
+    Truncate,
+    Truncated,
+    Drop,
+    Resolve,
+    Resolved,
+    BeginDir,
+    PaddingNegotiate,
+    PaddingNegotiated,
 );
 
 #[cfg(feature = "experimental-udp")]
@@ -165,6 +165,42 @@ fn test_drop() {
     msg(cmd, "", &msg::AnyRelayMsg::Drop(Default::default()));
 }
 
+#[test]
+fn test_padding_negotiate() {
+    use msg::{CircPadCmd, CircPadResponse};
+
+    let cmd = RelayCmd::PADDING_NEGOTIATE;
+    assert_eq!(Into::<u8>::into(cmd), 41_u8);
+
+    // hand-generated, following circpad_negotiate in the C tor source.
+    msg(
+        cmd,
+        "00 02 01 00 00000003",
+        &msg::PaddingNegotiate::new(CircPadCmd::START, 1, 3).into(),
+    );
+    // echo_request is ignored.
+    msg_noncanonical(
+        cmd,
+        "00 01 ff 01 00000000",
+        "00 01 ff 00 00000000",
+        &msg::PaddingNegotiate::new(CircPadCmd::STOP, 255, 0).into(),
+    );
+    msg_error(
+        cmd,
+        "01 02 01 00 00000003",
+        BytesError::InvalidMessage("Unrecognized PADDING_NEGOTIATE version.".into()),
+    );
+
+    let cmd = RelayCmd::PADDING_NEGOTIATED;
+    assert_eq!(Into::<u8>::into(cmd), 42_u8);
+
+    msg(
+        cmd,
+        "00 02 02 ff 00000000",
+        &msg::PaddingNegotiated::new(CircPadCmd::START, CircPadResponse::ERR, 255, 0).into(),
+    );
+}
+
 #[test]
 fn test_end() {
     let cmd = RelayCmd::END;
@@ -6,6 +6,7 @@ use crate::{
     CertEncodeError, CertExt, Ed25519Cert, Ed25519CertConstructor, ExtType, SignedWithEd25519Ext,
     UnrecognizedExt,
 };
+use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 use tor_bytes::{EncodeResult, Writeable, Writer};
 use tor_llcrypto::pk::ed25519::{self, Ed25519PublicKey};
@@ -124,7 +125,7 @@ impl Ed25519CertConstructor {
     /// valid.
     pub fn encode_and_sign<S>(&self, skey: &S) -> Result<EncodedEd25519Cert, CertEncodeError>
     where
-        S: Ed25519PublicKey + ed25519::Signer<ed25519::Signature>,
+        S: ?Sized + Ed25519PublicKey + ed25519::Signer<ed25519::Signature>,
     {
         let Ed25519CertConstructor {
             exp_hours,
@@ -166,7 +167,9 @@ impl Ed25519CertConstructor {
             e.write_onto(&mut w)?;
         }
 
-        let signature = skey.sign(&w[..]);
+        let signature = skey
+            .try_sign(&w[..])
+            .map_err(|e| CertEncodeError::Signing(Arc::new(e)))?;
         w.write(&signature)?;
         Ok(EncodedEd25519Cert(w))
     }
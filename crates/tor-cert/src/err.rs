@@ -3,7 +3,12 @@
 //! Most of the encoding/decoding functions here return [`tor_bytes::Error`],
 //! but many of them (related to certificate-specific operations) do not.
 
+#[cfg(feature = "encode")]
+use std::sync::Arc;
+
 use thiserror::Error;
+#[cfg(feature = "encode")]
+use tor_llcrypto::pk::ed25519;
 
 /// An error related to checking or validating a certificate
 #[derive(Clone, Debug, Error, Eq, PartialEq)]
@@ -51,4 +56,11 @@ pub enum CertEncodeError {
     /// probably a bug in the calling code.
     #[error("Tried to generate a cert we couldn't encode.")]
     Bytes(#[from] tor_bytes::EncodeError),
+
+    /// The signing key was unable to produce a signature.
+    ///
+    /// This can only happen with keys that are not held in memory,
+    /// such as keys held by a hardware token.
+    #[error("Unable to sign certificate")]
+    Signing(#[source] Arc<ed25519::SignatureError>),
 }
@@ -3,13 +3,16 @@
 use std::io;
 use std::sync::{Arc, Mutex};
 
+use crate::event::{BridgeAttemptPhase, ChanMgrEventSender};
 use crate::factory::{BootstrapReporter, ChannelFactory, IncomingChannelFactory};
 use crate::transport::TransportImplHelper;
-use crate::{event::ChanMgrEventSender, Error};
+use crate::Error;
 
 use std::time::Duration;
 use tor_error::internal;
-use tor_linkspec::{BridgeAddr, HasChanMethod, IntoOwnedChanTarget, OwnedChanTarget};
+use tor_linkspec::{
+    BridgeAddr, ChannelMethod, HasChanMethod, HasRelayIds, IntoOwnedChanTarget, OwnedChanTarget,
+};
 use tor_proto::channel::params::ChannelPaddingInstructionsUpdates;
 use tor_proto::memquota::ChannelAccount;
 use tor_rtcompat::{tls::TlsConnector, Runtime, TlsProvider};
@@ -39,6 +42,11 @@ where
     transport: H,
     /// Object to build TLS connections.
     tls_connector: <R as TlsProvider<H::Stream>>::Connector,
+    /// Our identity as a relay, if we have one.
+    ///
+    /// We need this in order to accept incoming channels.
+    #[cfg(feature = "relay")]
+    relay_identity: Mutex<Option<Arc<crate::relay::RelayChannelIdentity>>>,
 }
 
 impl<R: Runtime, H: TransportImplHelper> ChanBuilder<R, H>
@@ -52,8 +60,16 @@ where
             runtime,
             transport,
             tls_connector,
+            #[cfg(feature = "relay")]
+            relay_identity: Mutex::new(None),
         }
     }
+
+    /// Set the identity that we use to accept incoming channels.
+    #[cfg(feature = "relay")]
+    pub(crate) fn set_relay_identity(&self, identity: Arc<crate::relay::RelayChannelIdentity>) {
+        *self.relay_identity.lock().expect("Lock poisoned") = Some(identity);
+    }
 }
 #[async_trait]
 impl<R: Runtime, H: TransportImplHelper> ChannelFactory for ChanBuilder<R, H>
@@ -99,22 +115,59 @@ where
         &self,
         peer: std::net::SocketAddr,
         stream: Self::Stream,
-        _memquota: ChannelAccount,
+        memquota: ChannelAccount,
     ) -> crate::Result<Arc<tor_proto::channel::Channel>> {
+        use tor_proto::channel::ChannelBuilder;
+
         let map_ioe = |ioe, action| Error::Io {
             action,
             peer: Some(BridgeAddr::new_addr_from_sockaddr(peer).into()),
             source: ioe,
         };
+        let identity = self
+            .relay_identity
+            .lock()
+            .expect("Lock poisoned")
+            .clone()
+            .ok_or_else(|| {
+                Error::Internal(internal!(
+                    "Accepting a channel, but we have no relay identity"
+                ))
+            })?;
 
-        let _tls = self
+        // TODO RELAY: This must be the server side of TLS, presenting our link
+        // certificate; but tor-rtcompat can only give us the client side for now.
+        let tls = self
             .tls_connector
             .negotiate_unvalidated(stream, "ignored")
             .await
             .map_err(|e| map_ioe(e.into(), "TLS negotiation"))?;
 
-        // TODO RELAY: do handshake and build channel
-        todo!();
+        let (chan, reactor) = ChannelBuilder::new()
+            .accept(
+                tls,
+                Some(peer),
+                identity.certs().clone(),
+                identity.addrs().to_vec(),
+                self.runtime.clone(),
+                memquota,
+            )
+            .accept(|| self.runtime.wallclock())
+            .await
+            .map_err(|source| {
+                let target = OwnedChanTarget::builder()
+                    .addrs(vec![peer])
+                    .build()
+                    .expect("OwnedChanTarget builder failed");
+                Error::from_proto_no_skew(source, &target)
+            })?;
+
+        self.runtime
+            .spawn(async {
+                let _ = reactor.run().await;
+            })
+            .map_err(|e| Error::from_spawn("channel reactor", e))?;
+        Ok(chan)
     }
 }
 
@@ -141,6 +194,7 @@ where
 
         let (using_target, stream) = self.transport.connect(target).await?;
         let using_method = using_target.chan_method();
+        let is_direct = matches!(using_method, ChannelMethod::Direct(_));
         let peer = using_target.chan_method().target_addr();
         let peer_ref = &peer;
 
@@ -160,10 +214,9 @@ where
             // TODO(nickm): At some point, it would be helpful to the
             // bootstrapping logic if we could distinguish which
             // transport just succeeded.
-            event_sender
-                .lock()
-                .expect("Lock poisoned")
-                .record_tcp_success();
+            let mut event_sender = event_sender.lock().expect("Lock poisoned");
+            event_sender.record_tcp_success();
+            event_sender.record_bridge_phase(target, BridgeAttemptPhase::Handshaking);
         }
 
         // 1b. Negotiate TLS.
@@ -230,6 +283,20 @@ where
                 .record_handshake_done();
         }
 
+        // The address that the relay sees us at is only meaningful if we
+        // connected to it directly: otherwise, it's the address of the proxy
+        // or pluggable transport.
+        if let (true, Some(addr)) = (is_direct, chan.observed_addr()) {
+            event_sender
+                .lock()
+                .expect("Lock poisoned")
+                .record_external_addr(
+                    addr,
+                    chan.target().ed_identity().copied(),
+                    self.runtime.wallclock(),
+                );
+        }
+
         // 3. Launch a task to run the channel reactor.
         self.runtime
             .spawn(async {
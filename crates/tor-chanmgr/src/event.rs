@@ -6,9 +6,13 @@ use futures::{Stream, StreamExt};
 use postage::watch;
 use std::{
     fmt,
-    time::{Duration, Instant},
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
 };
 use tor_basic_utils::skip_fmt;
+use tor_linkspec::{HasRelayIds, OwnedChanTarget};
+use tor_llcrypto::pk::ed25519::Ed25519Identity;
 
 /// The status of our connection to the internet.
 #[derive(Default, Debug, Clone)]
@@ -36,6 +40,9 @@ pub struct ConnStatus {
     /// None if we haven't succeeded yet, but it's too early to say if
     /// that's a problem.
     handshake_works: Option<bool>,
+
+    /// Our most recent attempts to connect to each bridge, if we're using bridges.
+    bridge_attempts: Arc<[BridgeAttempt]>,
 }
 
 /// A problem detected while connecting to the Tor network.
@@ -82,6 +89,7 @@ impl ConnStatus {
                 online: Some(true),
                 auth_works: Some(true),
                 handshake_works: Some(true),
+                ..
             } => 1.0,
             Self {
                 online: Some(true), ..
@@ -90,6 +98,14 @@ impl ConnStatus {
         }
     }
 
+    /// Return our most recent attempt to connect to each bridge,
+    /// most recently updated first.
+    ///
+    /// This is empty unless we're using bridges.
+    pub fn bridge_attempts(&self) -> &[BridgeAttempt] {
+        &self.bridge_attempts
+    }
+
     /// Return the cause of why we aren't able to connect to the Tor network,
     /// if we think we're stuck.
     pub fn blockage(&self) -> Option<ConnBlockage> {
@@ -144,6 +160,144 @@ impl fmt::Display for ConnStatus {
     }
 }
 
+/// How far an attempt to connect to a bridge has progressed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, derive_more::Display)]
+#[non_exhaustive]
+pub enum BridgeAttemptPhase {
+    /// We're launching the pluggable transport for this bridge.
+    #[display("launching pluggable transport")]
+    LaunchingTransport,
+    /// The pluggable transport for this bridge is running,
+    /// and we're connecting to the bridge through it.
+    #[display("connecting through pluggable transport")]
+    TransportReady,
+    /// We're making a direct connection to this bridge.
+    #[display("connecting to bridge")]
+    Connecting,
+    /// We're negotiating TLS, and then a Tor channel, with this bridge.
+    #[display("handshaking with bridge")]
+    Handshaking,
+    /// We have an open channel to this bridge.
+    #[display("connected to bridge")]
+    Connected,
+    /// We've built a circuit through this bridge.
+    #[display("built a circuit through bridge")]
+    CircuitBuilt,
+}
+
+/// The most recent attempt to connect to a single bridge.
+///
+/// See [`ConnStatus::bridge_attempts`].
+#[derive(Clone, Debug)]
+pub struct BridgeAttempt {
+    /// The bridge we tried to connect to.
+    target: OwnedChanTarget,
+    /// The last phase this attempt reached.
+    phase: BridgeAttemptPhase,
+    /// The error that ended this attempt, if it failed.
+    error: Option<crate::Error>,
+    /// When did this attempt last change?
+    updated: SystemTime,
+}
+
+impl BridgeAttempt {
+    /// Return the bridge we tried to connect to.
+    pub fn target(&self) -> &OwnedChanTarget {
+        &self.target
+    }
+
+    /// Return the last phase this attempt reached.
+    ///
+    /// If the attempt failed, this is the phase in which it failed.
+    pub fn phase(&self) -> BridgeAttemptPhase {
+        self.phase
+    }
+
+    /// Return the error that ended this attempt, if it failed.
+    pub fn error(&self) -> Option<&crate::Error> {
+        self.error.as_ref()
+    }
+
+    /// Return the time when this attempt last changed.
+    pub fn updated(&self) -> SystemTime {
+        self.updated
+    }
+}
+
+/// The most recent attempt to connect to each bridge.
+#[derive(Debug, Clone, Default)]
+struct BridgeAttempts {
+    /// The attempts, most recently updated first.
+    attempts: Vec<BridgeAttempt>,
+}
+
+impl BridgeAttempts {
+    /// The maximum number of bridges to remember attempts for.
+    ///
+    /// When we try more bridges than this, we forget the one we tried
+    /// least recently.
+    const MAX_BRIDGES: usize = 32;
+
+    /// Note that our attempt to connect to `target` reached `phase` at time `now`.
+    ///
+    /// This starts a new attempt if we had not yet started one,
+    /// or if the last attempt had failed or completed.
+    fn record_phase(
+        &mut self,
+        target: &OwnedChanTarget,
+        phase: BridgeAttemptPhase,
+        now: SystemTime,
+    ) {
+        self.attempts.retain(|a| !a.target.same_relay_ids(target));
+        self.attempts.insert(
+            0,
+            BridgeAttempt {
+                target: target.clone(),
+                phase,
+                error: None,
+                updated: now,
+            },
+        );
+        self.attempts.truncate(Self::MAX_BRIDGES);
+    }
+
+    /// Note that our current attempt to connect to `target` failed with `error` at time `now`.
+    fn record_failure(&mut self, target: &OwnedChanTarget, error: &crate::Error, now: SystemTime) {
+        let Some(idx) = self
+            .attempts
+            .iter()
+            .position(|a| a.target.same_relay_ids(target))
+        else {
+            return;
+        };
+        let mut attempt = self.attempts.remove(idx);
+        attempt.error = Some(error.clone());
+        attempt.updated = now;
+        self.attempts.insert(0, attempt);
+    }
+
+    /// Note that we built a circuit through `first_hop` at time `now`.
+    ///
+    /// Return true if `first_hop` is a bridge that we've connected to.
+    fn record_circuit<T>(&mut self, first_hop: &T, now: SystemTime) -> bool
+    where
+        T: HasRelayIds + ?Sized,
+    {
+        let Some(idx) = self.attempts.iter().position(|a| {
+            a.error.is_none()
+                && a.phase == BridgeAttemptPhase::Connected
+                && a.target.has_any_relay_id_from(first_hop)
+        }) else {
+            return false;
+        };
+        let mut attempt = self.attempts.remove(idx);
+        attempt.phase = BridgeAttemptPhase::CircuitBuilt;
+        attempt.updated = now;
+        self.attempts.insert(0, attempt);
+        true
+    }
+}
+
 /// A stream of [`ConnStatus`] events describing changes in our connected-ness.
 ///
 /// This stream is lossy; a reader might not see some events on the stream, if
@@ -174,6 +328,84 @@ impl Stream for ConnStatusEvents {
     }
 }
 
+/// An address that relays have told us they see our connections coming from.
+///
+/// Relays report this address in the NETINFO cell of each channel handshake.
+/// It is only as trustworthy as the relays that reported it:
+/// a relay can be mistaken, or lie.
+/// If we're behind a NAT, this is (at best) the address of the NAT.
+///
+/// Only channels that we connected directly are taken into account:
+/// the address reported over a pluggable transport or a proxy
+/// is the address of the transport or proxy, not ours.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ExternalAddr {
+    /// The address that was reported.
+    addr: IpAddr,
+    /// How many channels have reported this address.
+    n_reports: usize,
+    /// When was this address last reported?
+    last_reported: SystemTime,
+    /// The Ed25519 identity of the relay that last reported this address, if known.
+    last_reporter: Option<Ed25519Identity>,
+}
+
+impl ExternalAddr {
+    /// Return the address that was reported.
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// Return the number of channels that have reported this address.
+    pub fn n_reports(&self) -> usize {
+        self.n_reports
+    }
+
+    /// Return the time when this address was last reported.
+    pub fn last_reported(&self) -> SystemTime {
+        self.last_reported
+    }
+
+    /// Return the Ed25519 identity of the relay that last reported this address,
+    /// if it is known.
+    pub fn last_reporter(&self) -> Option<&Ed25519Identity> {
+        self.last_reporter.as_ref()
+    }
+}
+
+/// The set of [`ExternalAddr`]s that relays have reported to us.
+#[derive(Debug, Clone, Default)]
+struct ExternalAddrs {
+    /// The reported addresses, most recently reported first.
+    addrs: Vec<ExternalAddr>,
+}
+
+impl ExternalAddrs {
+    /// The maximum number of distinct addresses to remember.
+    ///
+    /// When we learn about more than this many, we forget the one that was
+    /// reported least recently.
+    const MAX_ADDRS: usize = 8;
+
+    /// Note that `reporter` told us at time `now` that it sees us at `addr`.
+    fn record(&mut self, addr: IpAddr, reporter: Option<Ed25519Identity>, now: SystemTime) {
+        let n_reports = match self.addrs.iter().position(|a| a.addr == addr) {
+            Some(idx) => self.addrs.remove(idx).n_reports,
+            None => 0,
+        };
+        self.addrs.insert(
+            0,
+            ExternalAddr {
+                addr,
+                n_reports: n_reports + 1,
+                last_reported: now,
+                last_reporter: reporter,
+            },
+        );
+        self.addrs.truncate(Self::MAX_ADDRS);
+    }
+}
+
 /// Crate-internal view of "how connected are we to the internet?"
 ///
 /// This is a more complex and costly structure than ConnStatus, so we track
@@ -271,6 +503,7 @@ impl ChanMgrStatus {
             online,
             auth_works,
             handshake_works,
+            bridge_attempts: Arc::new([]),
         }
     }
 
@@ -316,6 +549,13 @@ pub(crate) struct ChanMgrEventSender {
     mgr_status: ChanMgrStatus,
     /// The channel that we use for sending ConnStatus information.
     sender: watch::Sender<ConnStatus>,
+    /// The addresses that relays have told us they see us at.
+    external_addrs: ExternalAddrs,
+    /// If true, every channel we build is to a bridge, and we keep track of
+    /// our attempts to connect to each one.
+    track_bridges: bool,
+    /// Our most recent attempts to connect to each bridge.
+    bridge_attempts: BridgeAttempts,
 }
 
 impl ChanMgrEventSender {
@@ -333,7 +573,8 @@ impl ChanMgrEventSender {
     /// building circuits, which will launch connection attempts until one
     /// succeeds or the client gives up entirely.  
     fn push_at(&mut self, now: Instant) {
-        let status = self.mgr_status.conn_status_at(now);
+        let mut status = self.mgr_status.conn_status_at(now);
+        status.bridge_attempts = Arc::clone(&self.last_conn_status.bridge_attempts);
         if !status.eq(&self.last_conn_status) {
             self.last_conn_status = status.clone();
             let mut b = self.sender.borrow_mut();
@@ -341,6 +582,67 @@ impl ChanMgrEventSender {
         }
     }
 
+    /// Tell any listeners about a change in our attempts to connect to bridges.
+    fn push_bridge_attempts(&mut self) {
+        let mut status = self.last_conn_status.clone();
+        status.bridge_attempts = self.bridge_attempts.attempts.clone().into();
+        self.last_conn_status = status.clone();
+        let mut b = self.sender.borrow_mut();
+        *b = status;
+    }
+
+    /// Set whether every channel we build is to a bridge.
+    ///
+    /// If so, we keep track of our attempts to connect to each one.
+    pub(crate) fn set_track_bridges(&mut self, track_bridges: bool) {
+        if self.track_bridges == track_bridges {
+            return;
+        }
+        self.track_bridges = track_bridges;
+        self.bridge_attempts = BridgeAttempts::default();
+        self.push_bridge_attempts();
+    }
+
+    /// If `target` is a bridge, note that our attempt to connect to it reached `phase`.
+    pub(crate) fn record_bridge_phase(
+        &mut self,
+        target: &OwnedChanTarget,
+        phase: BridgeAttemptPhase,
+    ) {
+        if !self.track_bridges {
+            return;
+        }
+        self.bridge_attempts
+            .record_phase(target, phase, SystemTime::now());
+        self.push_bridge_attempts();
+    }
+
+    /// If `target` is a bridge, note that our attempt to connect to it failed with `error`.
+    pub(crate) fn record_bridge_failure(&mut self, target: &OwnedChanTarget, error: &crate::Error) {
+        if !self.track_bridges {
+            return;
+        }
+        self.bridge_attempts
+            .record_failure(target, error, SystemTime::now());
+        self.push_bridge_attempts();
+    }
+
+    /// If `first_hop` is a bridge, note that we built a circuit through it.
+    pub(crate) fn record_bridge_circuit<T>(&mut self, first_hop: &T)
+    where
+        T: HasRelayIds + ?Sized,
+    {
+        if !self.track_bridges {
+            return;
+        }
+        if self
+            .bridge_attempts
+            .record_circuit(first_hop, SystemTime::now())
+        {
+            self.push_bridge_attempts();
+        }
+    }
+
     /// Note that an attempt to connect has been started.
     pub(crate) fn record_attempt(&mut self) {
         self.mgr_status.record_attempt();
@@ -380,6 +682,23 @@ impl ChanMgrEventSender {
         self.mgr_status.record_handshake_done(now);
         self.push_at(now);
     }
+
+    /// Note that the relay `reporter` told us at time `now`
+    /// that it sees our connection coming from `addr`.
+    pub(crate) fn record_external_addr(
+        &mut self,
+        addr: IpAddr,
+        reporter: Option<Ed25519Identity>,
+        now: SystemTime,
+    ) {
+        self.external_addrs.record(addr, reporter, now);
+    }
+
+    /// Return the addresses that relays have told us they see us at,
+    /// most recently reported first.
+    pub(crate) fn external_addrs(&self) -> Vec<ExternalAddr> {
+        self.external_addrs.addrs.clone()
+    }
 }
 
 /// Create a new channel for sending connectivity status events to other crates.
@@ -390,6 +709,9 @@ pub(crate) fn channel() -> (ChanMgrEventSender, ConnStatusEvents) {
         last_conn_status: ConnStatus::default(),
         mgr_status: ChanMgrStatus::new_at(Instant::now()),
         sender,
+        external_addrs: ExternalAddrs::default(),
+        track_bridges: false,
+        bridge_attempts: BridgeAttempts::default(),
     };
     (sender, receiver)
 }
@@ -429,6 +751,7 @@ mod test {
             online: Some(false),
             auth_works: None,
             handshake_works: None,
+            ..Default::default()
         };
         assert_eq!(s2.to_string(), "unable to connect to the internet");
         assert_float_eq!(s2.frac(), 0.0, abs <= TOL);
@@ -445,6 +768,7 @@ mod test {
             online: Some(true),
             auth_works: None,
             handshake_works: None,
+            ..Default::default()
         };
         assert_eq!(s3.to_string(), "handshaking with Tor relays");
         assert_float_eq!(s3.frac(), 0.5, abs <= TOL);
@@ -456,6 +780,7 @@ mod test {
             online: Some(true),
             auth_works: Some(false),
             handshake_works: Some(false),
+            ..Default::default()
         };
         assert_eq!(s4.to_string(), "unable to handshake with Tor relays");
         assert_float_eq!(s4.frac(), 0.5, abs <= TOL);
@@ -474,6 +799,7 @@ mod test {
             online: Some(true),
             auth_works: Some(true),
             handshake_works: Some(true),
+            ..Default::default()
         };
         assert_eq!(s5.to_string(), "connecting successfully");
         assert_float_eq!(s5.frac(), 1.0, abs <= TOL);
@@ -549,4 +875,99 @@ mod test {
             assert_float_eq!(s.frac(), 1.0, abs <= TOL);
         }
     }
+
+    #[test]
+    fn external_addrs() {
+        let (mut snd, _rcv) = channel();
+        assert!(snd.external_addrs().is_empty());
+
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let t1 = t0 + Duration::from_secs(10);
+        let relay1 = Ed25519Identity::from([1; 32]);
+        let relay2 = Ed25519Identity::from([2; 32]);
+        let addr1: IpAddr = "192.0.2.1".parse().unwrap();
+        let addr2: IpAddr = "2001:db8::1".parse().unwrap();
+
+        snd.record_external_addr(addr1, Some(relay1), t0);
+        snd.record_external_addr(addr2, None, t0);
+        snd.record_external_addr(addr1, Some(relay2), t1);
+
+        let addrs = snd.external_addrs();
+        assert_eq!(addrs.len(), 2);
+        assert_eq!(addrs[0].addr(), addr1);
+        assert_eq!(addrs[0].n_reports(), 2);
+        assert_eq!(addrs[0].last_reported(), t1);
+        assert_eq!(addrs[0].last_reporter(), Some(&relay2));
+        assert_eq!(addrs[1].addr(), addr2);
+        assert_eq!(addrs[1].n_reports(), 1);
+        assert_eq!(addrs[1].last_reporter(), None);
+
+        // We only remember a bounded number of addresses.
+        for i in 0..20 {
+            let addr = IpAddr::from([198, 51, 100, i]);
+            snd.record_external_addr(addr, None, t1);
+        }
+        let addrs = snd.external_addrs();
+        assert_eq!(addrs.len(), ExternalAddrs::MAX_ADDRS);
+        assert_eq!(addrs[0].addr(), IpAddr::from([198, 51, 100, 19]));
+    }
+
+    #[test]
+    fn bridge_attempts() {
+        use tor_linkspec::IntoOwnedChanTarget as _;
+
+        let target = |id: u8| {
+            OwnedChanTarget::builder()
+                .ed_identity([id; 32].into())
+                .rsa_identity([id; 20].into())
+                .build()
+                .unwrap()
+        };
+        let (bridge1, bridge2) = (target(1), target(2));
+        let (mut snd, rcv) = channel();
+        let attempts = || rcv.inner.borrow().bridge_attempts().to_vec();
+
+        // We don't keep track of attempts unless we're told we're using bridges.
+        snd.record_bridge_phase(&bridge1, BridgeAttemptPhase::Connecting);
+        assert!(attempts().is_empty());
+
+        snd.set_track_bridges(true);
+        snd.record_bridge_phase(&bridge1, BridgeAttemptPhase::Connecting);
+        snd.record_bridge_phase(&bridge2, BridgeAttemptPhase::LaunchingTransport);
+        snd.record_bridge_phase(&bridge2, BridgeAttemptPhase::TransportReady);
+        let a = attempts();
+        assert_eq!(a.len(), 2);
+        assert!(a[0].target().same_relay_ids(&bridge2));
+        assert_eq!(a[0].phase(), BridgeAttemptPhase::TransportReady);
+        assert_eq!(a[1].phase(), BridgeAttemptPhase::Connecting);
+
+        // Failures are reported with their error and phase.
+        let error = crate::Error::ChanTimeout {
+            peer: bridge1.clone().to_logged(),
+        };
+        snd.record_bridge_failure(&bridge1, &error);
+        let a = attempts();
+        assert!(a[0].target().same_relay_ids(&bridge1));
+        assert_eq!(a[0].phase(), BridgeAttemptPhase::Connecting);
+        assert!(matches!(
+            a[0].error(),
+            Some(crate::Error::ChanTimeout { .. })
+        ));
+
+        // Only bridges we're connected to can have circuits.
+        snd.record_bridge_circuit(&bridge2);
+        assert_eq!(attempts()[1].phase(), BridgeAttemptPhase::TransportReady);
+        snd.record_bridge_phase(&bridge2, BridgeAttemptPhase::Handshaking);
+        snd.record_bridge_phase(&bridge2, BridgeAttemptPhase::Connected);
+        snd.record_bridge_circuit(&tor_linkspec::RelayIds::from_relay_ids(&bridge2));
+        let a = attempts();
+        assert_eq!(a[0].phase(), BridgeAttemptPhase::CircuitBuilt);
+        assert!(a[0].error().is_none());
+
+        // The connection status itself is unaffected.
+        assert_float_eq!(rcv.inner.borrow().frac(), 0.0, abs <= TOL);
+
+        snd.set_track_bridges(false);
+        assert!(attempts().is_empty());
+    }
 }
@@ -3,7 +3,7 @@
 
 use std::sync::{Arc, Mutex};
 
-use crate::event::ChanMgrEventSender;
+use crate::event::{BridgeAttemptPhase, ChanMgrEventSender};
 use async_trait::async_trait;
 use tor_error::{internal, HasKind, HasRetryTime};
 use tor_linkspec::{HasChanMethod, OwnedChanTarget, PtTransportName};
@@ -174,19 +174,64 @@ impl<CF: ChannelFactory> ChannelFactory for CompoundFactory<CF> {
         target: &OwnedChanTarget,
         reporter: BootstrapReporter,
         memquota: ChannelAccount,
+    ) -> crate::Result<Arc<Channel>> {
+        let result = self
+            .connect_via_transport_inner(target, reporter.clone(), memquota)
+            .await;
+
+        let mut events = reporter.0.lock().expect("Lock poisoned");
+        match &result {
+            Ok(_) => events.record_bridge_phase(target, BridgeAttemptPhase::Connected),
+            Err(e) => events.record_bridge_failure(target, e),
+        }
+        drop(events);
+
+        result
+    }
+}
+
+impl<CF: ChannelFactory> CompoundFactory<CF> {
+    /// Helper for `connect_via_transport`: pick a factory for `target`, and use it.
+    ///
+    /// (If `target` is a bridge, this reports the phases of the attempt to `reporter`,
+    /// up to when we start handshaking; the caller reports the outcome.)
+    async fn connect_via_transport_inner(
+        &self,
+        target: &OwnedChanTarget,
+        reporter: BootstrapReporter,
+        memquota: ChannelAccount,
     ) -> crate::Result<Arc<Channel>> {
         use tor_linkspec::ChannelMethod::*;
+        let record_phase = |phase| {
+            reporter
+                .0
+                .lock()
+                .expect("Lock poisoned")
+                .record_bridge_phase(target, phase);
+        };
         let factory = match target.chan_method() {
-            Direct(_) => self.default_factory.clone(),
+            Direct(_) => {
+                record_phase(BridgeAttemptPhase::Connecting);
+                self.default_factory.clone()
+            }
             #[cfg(feature = "pt-client")]
-            Pluggable(a) => match self.ptmgr.as_ref() {
-                Some(mgr) => mgr
-                    .factory_for_transport(a.transport())
-                    .await
-                    .map_err(crate::Error::Pt)?
-                    .ok_or_else(|| crate::Error::NoSuchTransport(a.transport().clone().into()))?,
-                None => return Err(crate::Error::NoSuchTransport(a.transport().clone().into())),
-            },
+            Pluggable(a) => {
+                record_phase(BridgeAttemptPhase::LaunchingTransport);
+                let factory = match self.ptmgr.as_ref() {
+                    Some(mgr) => mgr
+                        .factory_for_transport(a.transport())
+                        .await
+                        .map_err(crate::Error::Pt)?
+                        .ok_or_else(|| {
+                            crate::Error::NoSuchTransport(a.transport().clone().into())
+                        })?,
+                    None => {
+                        return Err(crate::Error::NoSuchTransport(a.transport().clone().into()))
+                    }
+                };
+                record_phase(BridgeAttemptPhase::TransportReady);
+                factory
+            }
             #[allow(unreachable_patterns)]
             _ => {
                 return Err(crate::Error::Internal(internal!(
@@ -237,4 +282,10 @@ impl<CF: ChannelFactory + 'static> CompoundFactory<CF> {
     pub(crate) fn replace_ptmgr(&mut self, ptmgr: Arc<dyn AbstractPtMgr + 'static>) {
         self.ptmgr = Some(ptmgr);
     }
+
+    /// Return the factory we use for everything other than pluggable transports.
+    #[cfg(feature = "relay")]
+    pub(crate) fn default_factory(&self) -> &Arc<CF> {
+        &self.default_factory
+    }
 }
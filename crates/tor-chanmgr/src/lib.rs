@@ -47,6 +47,8 @@ mod err;
 mod event;
 pub mod factory;
 mod mgr;
+#[cfg(feature = "relay")]
+mod relay;
 #[cfg(test)]
 mod testing;
 pub mod transport;
@@ -59,7 +61,7 @@ use std::sync::{Arc, Weak};
 use std::time::Duration;
 use tor_config::ReconfigureError;
 use tor_error::error_report;
-use tor_linkspec::{ChanTarget, OwnedChanTarget};
+use tor_linkspec::{ChanTarget, HasRelayIds, OwnedChanTarget};
 use tor_netdir::{params::NetParameters, NetDirProvider};
 use tor_proto::channel::Channel;
 #[cfg(feature = "experimental-api")]
@@ -71,6 +73,8 @@ use void::{ResultVoidErrExt, Void};
 pub use err::Error;
 
 pub use config::{ChannelConfig, ChannelConfigBuilder};
+#[cfg(feature = "relay")]
+pub use relay::RelayChannelIdentity;
 
 use tor_rtcompat::Runtime;
 
@@ -78,7 +82,9 @@ use tor_rtcompat::Runtime;
 pub type Result<T> = std::result::Result<T, Error>;
 
 use crate::factory::BootstrapReporter;
-pub use event::{ConnBlockage, ConnStatus, ConnStatusEvents};
+pub use event::{
+    BridgeAttempt, BridgeAttemptPhase, ConnBlockage, ConnStatus, ConnStatusEvents, ExternalAddr,
+};
 use tor_rtcompat::scheduler::{TaskHandle, TaskSchedule};
 
 /// An object that remembers a set of live channels, and launches new ones on
@@ -125,6 +131,10 @@ pub struct ChanMgr<R: Runtime> {
     /// Stream of [`ConnStatus`] events.
     bootstrap_status: event::ConnStatusEvents,
 
+    /// The object that records information about our connection status,
+    /// shared with the channel builders.
+    event_sender: Arc<std::sync::Mutex<event::ChanMgrEventSender>>,
+
     /// This currently isn't actually used, but we're keeping a PhantomData here
     /// since probably we'll want it again, sooner or later.
     runtime: std::marker::PhantomData<fn(R) -> R>,
@@ -220,7 +230,7 @@ impl<R: Runtime> ChanMgr<R> {
     {
         let (sender, receiver) = event::channel();
         let sender = Arc::new(std::sync::Mutex::new(sender));
-        let reporter = BootstrapReporter(sender);
+        let reporter = BootstrapReporter(Arc::clone(&sender));
         let transport = transport::DefaultTransport::new(runtime.clone());
         let builder = builder::ChanBuilder::new(runtime, transport);
         let factory = factory::CompoundFactory::new(
@@ -233,6 +243,7 @@ impl<R: Runtime> ChanMgr<R> {
         ChanMgr {
             mgr,
             bootstrap_status: receiver,
+            event_sender: sender,
             runtime: std::marker::PhantomData,
         }
     }
@@ -309,6 +320,47 @@ impl<R: Runtime> ChanMgr<R> {
         self.bootstrap_status.clone()
     }
 
+    /// Return the addresses that relays have told us they see our connections
+    /// coming from, most recently reported first.
+    ///
+    /// These addresses come from the NETINFO cells of the channels we've built,
+    /// so they are only as trustworthy as the relays that reported them.
+    /// See [`ExternalAddr`] for more caveats.
+    ///
+    /// Only a bounded number of the most recently reported addresses are kept.
+    pub fn external_addrs(&self) -> Vec<ExternalAddr> {
+        self.event_sender
+            .lock()
+            .expect("Lock poisoned")
+            .external_addrs()
+    }
+
+    /// Set whether every channel we build is to a bridge.
+    ///
+    /// If so, we keep track of our attempts to connect to each bridge,
+    /// and report them in [`ConnStatus::bridge_attempts`].
+    /// Otherwise, we report no bridge attempts.
+    pub fn set_track_bridges(&self, track_bridges: bool) {
+        self.event_sender
+            .lock()
+            .expect("Lock poisoned")
+            .set_track_bridges(track_bridges);
+    }
+
+    /// Note that we've built a circuit whose first hop is `first_hop`.
+    ///
+    /// If `first_hop` is a bridge we're connected to, this is reported
+    /// in [`ConnStatus::bridge_attempts`].
+    pub fn note_circuit_built<T>(&self, first_hop: &T)
+    where
+        T: HasRelayIds + ?Sized,
+    {
+        self.event_sender
+            .lock()
+            .expect("Lock poisoned")
+            .record_bridge_circuit(first_hop);
+    }
+
     /// Expire all channels that have been unused for too long.
     ///
     /// Return the duration from now until next channel expires.
@@ -341,6 +393,77 @@ impl<R: Runtime> ChanMgr<R> {
         Ok(r?)
     }
 
+    /// Set the identity that we use to answer the channel handshakes of incoming connections.
+    ///
+    /// This must be called before any incoming connection is handled.
+    #[cfg(feature = "relay")]
+    pub fn set_relay_identity(&self, identity: RelayChannelIdentity) {
+        let identity = Arc::new(identity);
+        self.mgr
+            .with_mut_builder(|f| f.default_factory().set_relay_identity(identity));
+    }
+
+    /// Launch a task that accepts connections on `listener`, as a relay's ORPort,
+    /// and builds a channel for each of them using [`handle_incoming`](ChanMgr::handle_incoming).
+    ///
+    /// The task exits when the listener fails, or when this `ChanMgr` is dropped.
+    ///
+    /// You must call [`set_relay_identity`](ChanMgr::set_relay_identity) first.
+    #[cfg(feature = "relay")]
+    pub fn launch_orport_listener(
+        self: &Arc<Self>,
+        runtime: &R,
+        listener: <R as tor_rtcompat::NetStreamProvider>::Listener,
+    ) -> Result<()> {
+        runtime
+            .spawn(Self::run_orport_listener(
+                runtime.clone(),
+                listener,
+                Arc::downgrade(self),
+            ))
+            .map_err(|e| Error::from_spawn("ORPort listener", e))
+    }
+
+    /// Accept connections on `listener`, and handle each one in a new task.
+    ///
+    /// This is a daemon task that runs until `listener` fails or `chanmgr` is dropped.
+    #[cfg(feature = "relay")]
+    async fn run_orport_listener(
+        runtime: R,
+        listener: <R as tor_rtcompat::NetStreamProvider>::Listener,
+        chanmgr: Weak<Self>,
+    ) {
+        use tor_rtcompat::NetStreamListener as _;
+
+        let mut incoming = listener.incoming();
+        while let Some(accepted) = incoming.next().await {
+            let (stream, addr) = match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    tracing::warn!("ORPort listener failed: {}", e);
+                    return;
+                }
+            };
+            let Some(chanmgr) = Weak::upgrade(&chanmgr) else {
+                // channel manager is closed.
+                return;
+            };
+            let handle = async move {
+                if let Err(e) = chanmgr.handle_incoming(addr, stream).await {
+                    tor_error::debug_report!(
+                        e,
+                        "Unable to accept a channel from {}",
+                        safelog::sensitive(addr)
+                    );
+                }
+            };
+            if let Err(e) = runtime.spawn(handle) {
+                error_report!(e, "Unable to spawn a task for an incoming channel");
+                return;
+            }
+        }
+    }
+
     /// Replace the transport registry with one that may know about
     /// more transports.
     ///
@@ -159,12 +159,16 @@ impl<CF: AbstractChannelFactory + Clone> AbstractChanMgr<CF> {
     ) -> Result<Arc<CF::Channel>> {
         let chan_builder = self.channels.builder();
         let memquota = ChannelAccount::new(&self.memquota)?;
-        let _outcome = chan_builder
+        let chan = chan_builder
             .build_channel_using_incoming(src, stream, memquota)
             .await?;
 
-        // TODO RELAY: we need to do something with the channel here now that we've created it
-        todo!();
+        // So far we only accept channels from clients, which have no relay identities,
+        // so there is nothing to look them up by: we don't add them to our map.
+        //
+        // TODO RELAY: Once we accept authenticated channels from other relays,
+        // we'll need to register those here (and check for duplicates).
+        Ok(chan)
     }
 
     /// Get a channel corresponding to the identities of `target`.
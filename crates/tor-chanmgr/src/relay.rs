@@ -0,0 +1,41 @@
+//! Support for accepting channels as a relay.
+//!
+//! This is experimental scaffolding for relay and bridge modes:
+//! we can answer the channel handshake from a client,
+//! but we don't yet handle any circuits on the resulting channels.
+
+use std::net::IpAddr;
+
+use tor_cell::chancell::msg;
+
+/// The information that a relay needs in order to answer channel handshakes.
+#[derive(Clone, Debug)]
+pub struct RelayChannelIdentity {
+    /// The CERTS cell that proves our identity to the initiator.
+    certs: msg::Certs,
+    /// The addresses at which we believe that we can be reached.
+    addrs: Vec<IpAddr>,
+}
+
+impl RelayChannelIdentity {
+    /// Construct a new `RelayChannelIdentity`.
+    ///
+    /// `certs` is the CERTS cell we send in every handshake.
+    /// It is not checked here: it must hold certificates
+    /// that authenticate our identity keys and our TLS certificate.
+    ///
+    /// `addrs` are the addresses that we report in our NETINFO cells.
+    pub fn new(certs: msg::Certs, addrs: Vec<IpAddr>) -> Self {
+        Self { certs, addrs }
+    }
+
+    /// Return the CERTS cell that we send in each handshake.
+    pub fn certs(&self) -> &msg::Certs {
+        &self.certs
+    }
+
+    /// Return the addresses that we report in each handshake.
+    pub fn addrs(&self) -> &[IpAddr] {
+        &self.addrs
+    }
+}
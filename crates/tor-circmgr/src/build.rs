@@ -276,6 +276,7 @@ impl<R: Runtime, C: Buildable + Sync + Send + 'static> Builder<R, C> {
                 self.timeouts
                     .note_hop_completed(0, self.runtime.now() - start_time, true);
                 n_hops_built.fetch_add(1, Ordering::SeqCst);
+                self.chanmgr.note_circuit_built(&target);
                 Ok(circ)
             }
             OwnedPath::Normal(p) => {
@@ -309,6 +310,7 @@ impl<R: Runtime, C: Buildable + Sync + Send + 'static> Builder<R, C> {
                     );
                     hop_num += 1;
                 }
+                self.chanmgr.note_circuit_built(&p[0]);
                 Ok(circ)
             }
         }
@@ -457,6 +459,16 @@ impl<R: Runtime> CircuitBuilder<R> {
         Ok(())
     }
 
+    /// Replace our state with the contents of the state manager,
+    /// whether or not we have storage permission.
+    pub(crate) fn load_state(&self) -> Result<()> {
+        if self.storage.can_store() {
+            self.upgrade_to_owned_state()
+        } else {
+            self.reload_state()
+        }
+    }
+
     /// Reconfigure this builder using the latest set of network parameters.
     ///
     /// (NOTE: for now, this only affects circuit timeout estimation.)
@@ -53,6 +53,14 @@ pub struct PathConfig {
     #[builder(sub_builder, setter(custom))]
     #[builder_field_attr(serde(default))]
     pub(crate) reachable_addrs: ReachableAddrs,
+
+    /// Restrictions on which relays we use as rendezvous and introduction points.
+    ///
+    /// (Experimental.)
+    #[cfg(feature = "hs-endpoint-restrictions")]
+    #[builder(sub_builder)]
+    #[builder_field_attr(serde(default))]
+    pub(crate) hs_endpoints: crate::hspool::HsEndpointConfig,
 }
 impl_standard_builder! { PathConfig }
 
@@ -163,7 +171,7 @@ impl PathConfig {
 ///
 /// Except as noted, this configuration can be changed on a running Arti client.
 #[derive(Debug, Clone, Builder, Eq, PartialEq)]
-#[builder(build_fn(error = "ConfigBuildError"))]
+#[builder(build_fn(validate = "Self::validate", error = "ConfigBuildError"))]
 #[builder(derive(Debug, Serialize, Deserialize))]
 pub struct PreemptiveCircuitConfig {
     /// If we have at least this many available circuits, we suspend
@@ -195,9 +203,44 @@ pub struct PreemptiveCircuitConfig {
     /// predicted exit port?
     #[builder(default = "default_preemptive_min_exit_circs_for_port()")]
     pub(crate) min_exit_circs_for_port: usize,
+
+    /// The name under which to remember which exit ports the client has used.
+    ///
+    /// If this is set, the ports that the client has recently used are saved
+    /// in the state directory, and predicted again the next time a client
+    /// with the same profile starts up.
+    /// Applications that share a state directory but not their usage patterns
+    /// (say, a messenger and a web browser) should use different profiles.
+    ///
+    /// Profile names may contain only ASCII letters, digits, `-` and `_`.
+    ///
+    /// This value cannot be changed on a running Arti client.
+    ///
+    /// The default is not to remember used ports at all.
+    #[builder(default)]
+    pub(crate) profile: Option<String>,
 }
 impl_standard_builder! { PreemptiveCircuitConfig }
 
+impl PreemptiveCircuitConfigBuilder {
+    /// Check that the profile name (if any) is acceptable.
+    fn validate(&self) -> Result<(), ConfigBuildError> {
+        if let Some(Some(profile)) = &self.profile {
+            let valid = !profile.is_empty()
+                && profile
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if !valid {
+                return Err(ConfigBuildError::Invalid {
+                    field: "profile".to_owned(),
+                    problem: format!("{:?} is not a valid profile name", profile),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
 /// Configuration for circuit timeouts, expiration, and so on.
 ///
 /// This type is immutable once constructed. To create an object of this type,
@@ -264,6 +307,28 @@ pub struct CircuitTiming {
     #[builder(default = "default_hs_max_attempts()")]
     #[getter(as_copy)]
     pub(crate) hs_intro_rend_attempts: u32,
+
+    /// When fetching an HS descriptor, we race up to this many hsdirs at once
+    ///
+    /// The default is 1: we ask the hsdirs one at a time, as C Tor does.
+    //
+    // This parameter is honoured by tor-hsclient, not here.
+    #[cfg(feature = "hs-client")]
+    #[builder(default = "default_hs_desc_fetch_parallelism()")]
+    #[getter(as_copy)]
+    pub(crate) hs_desc_fetch_parallelism: u32,
+
+    /// When fetching an HS descriptor, we ask another hsdir if the one
+    /// we're waiting for hasn't answered after this long
+    ///
+    /// (Up to `hs_desc_fetch_parallelism` hsdirs at once.)
+    //
+    // This parameter is honoured by tor-hsclient, not here.
+    #[cfg(feature = "hs-client")]
+    #[builder(default = "default_hs_desc_fetch_stagger()")]
+    #[builder_field_attr(serde(default, with = "humantime_serde::option"))]
+    #[getter(as_copy)]
+    pub(crate) hs_desc_fetch_stagger: Duration,
 }
 impl_standard_builder! { CircuitTiming }
 
@@ -329,6 +394,20 @@ fn default_hs_max_attempts() -> u32 {
     6
 }
 
+/// Return the default value for `hs_desc_fetch_parallelism`.
+#[cfg(feature = "hs-client")]
+fn default_hs_desc_fetch_parallelism() -> u32 {
+    // Like C Tor, we ask one hsdir at a time unless the user opts in:
+    // asking several may make us more vulnerable to traffic analysis.
+    1
+}
+
+/// Return the default value for `hs_desc_fetch_stagger`.
+#[cfg(feature = "hs-client")]
+fn default_hs_desc_fetch_stagger() -> Duration {
+    Duration::from_secs(2)
+}
+
 /// Return the default request loyalty timeout.
 fn default_request_loyalty() -> Duration {
     Duration::from_millis(50)
@@ -384,6 +463,7 @@ pub(crate) mod test_config {
     use super::*;
     use crate::*;
     use tor_guardmgr::bridge::BridgeConfig;
+    use tor_guardmgr::GuardDiversityConfig;
     #[cfg(all(feature = "vanguards", feature = "hs-common"))]
     use tor_guardmgr::VanguardConfig;
 
@@ -410,6 +490,11 @@ pub(crate) mod test_config {
             &self.guardmgr.fallbacks
         }
     }
+    impl AsRef<GuardDiversityConfig> for TestConfig {
+        fn as_ref(&self) -> &GuardDiversityConfig {
+            &self.guardmgr.guard_diversity
+        }
+    }
     impl GuardMgrConfig for TestConfig {
         fn bridges_enabled(&self) -> bool {
             self.guardmgr.bridges_enabled()
@@ -0,0 +1,44 @@
+//! Code to notify other crates about changes in the status of the `CircMgr`.
+
+use std::{pin::Pin, task::Poll};
+
+use educe::Educe;
+use futures::{Stream, StreamExt};
+use tor_basic_utils::skip_fmt;
+
+/// A stream of events telling whether we have built a circuit for user traffic yet.
+///
+/// The value is `false` until the first circuit that can carry exit traffic
+/// has been built, and `true` from then on.
+/// (Circuits for directory connections don't count.)
+///
+/// Note that this stream can be lossy: if several events trigger before you
+/// read from it, you will only get the most recent value.
+#[derive(Clone, Educe)]
+#[educe(Debug)]
+pub struct CircBuiltEvents {
+    /// The `postage::watch::Receiver` that we're wrapping.
+    ///
+    /// We wrap this type so that we don't expose its entire API, and so that we
+    /// can migrate to some other implementation in the future if we want.
+    #[educe(Debug(method = "skip_fmt"))]
+    pub(crate) inner: postage::watch::Receiver<bool>,
+}
+
+impl Stream for CircBuiltEvents {
+    type Item = bool;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+impl CircBuiltEvents {
+    /// Return true if we have built a circuit for user traffic.
+    pub fn get(&self) -> bool {
+        *self.inner.borrow()
+    }
+}
@@ -34,11 +34,16 @@ use tracing::{debug, trace, warn};
 use std::result::Result as StdResult;
 
 pub use config::HsCircPoolConfig;
+#[cfg(feature = "hs-endpoint-restrictions")]
+pub use config::{HsEndpointConfig, HsEndpointConfigBuilder};
 
 use self::pool::HsCircPrefs;
 
 #[cfg(all(feature = "vanguards", feature = "hs-common"))]
-use crate::path::hspath::select_middle_for_vanguard_circ;
+use {crate::path::hspath::select_middle_for_vanguard_circ, tor_relay_selection::RelayRestriction};
+
+#[cfg(feature = "hs-endpoint-restrictions")]
+use tor_relay_selection::RelaySelector;
 
 /// The (onion-service-related) purpose for which a given circuit is going to be
 /// used.
@@ -79,6 +84,15 @@ impl HsCircKind {
     }
 }
 
+/// What we're going to do with a circuit stem we take from the pool.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum StemUse {
+    /// Extend it to a target chosen by somebody else.
+    Extend,
+    /// Use its last hop as our rendezvous point.
+    Rendezvous,
+}
+
 /// A hidden service circuit stem.
 ///
 /// This represents a hidden service circuit that has not yet been extended to a target.
@@ -233,6 +247,20 @@ impl<R: Runtime> HsCircPool<R> {
         self.0.get_or_launch_client_rend(netdir).await
     }
 
+    /// Add the configured restrictions on rendezvous and introduction points to `selector`.
+    ///
+    /// Onion services should call this when they choose introduction points.
+    /// (We apply the restrictions to rendezvous points ourselves,
+    /// when we choose the last hop of each circuit stem.)
+    #[cfg(feature = "hs-endpoint-restrictions")]
+    pub fn restrict_endpoint_selector<'a>(
+        &self,
+        selector: &mut RelaySelector<'a>,
+        netdir: &'a NetDir,
+    ) {
+        self.0.restrict_endpoint_selector(selector, netdir);
+    }
+
     /// Return an estimate-based delay for how long a given
     /// [`Action`](timeouts::Action) should be allowed to complete.
     ///
@@ -351,11 +379,18 @@ impl<B: AbstractCircBuilder<R> + 'static, R: Runtime> HsCircPoolInner<B, R> {
         // Note that we aren't using any special rules for the last hop here; we
         // are relying on the fact that:
         //   * all suitable middle relays that we use in these circuit stems are
-        //     suitable renedezvous points, and
+        //     suitable renedezvous points,
         //   * the weighting rules for selecting rendezvous points are the same
-        //     as those for selecting an arbitrary middle relay.
+        //     as those for selecting an arbitrary middle relay, and
+        //   * the last hop of every stem we build obeys any configured
+        //     `hs_endpoints` restrictions.
         let circ = self
-            .take_or_launch_stem_circuit::<OwnedCircTarget>(netdir, None, HsCircStemKind::Guarded)
+            .take_or_launch_stem_circuit::<OwnedCircTarget>(
+                netdir,
+                None,
+                HsCircStemKind::Guarded,
+                StemUse::Rendezvous,
+            )
             .await?;
 
         #[cfg(all(feature = "vanguards", feature = "hs-common"))]
@@ -415,7 +450,7 @@ impl<B: AbstractCircBuilder<R> + 'static, R: Runtime> HsCircPoolInner<B, R> {
 
         // Get an unfinished circuit that's compatible with our target.
         let circ = self
-            .take_or_launch_stem_circuit(netdir, Some(&target), wanted_kind)
+            .take_or_launch_stem_circuit(netdir, Some(&target), wanted_kind, StemUse::Extend)
             .await?;
 
         #[cfg(all(feature = "vanguards", feature = "hs-common"))]
@@ -501,6 +536,7 @@ impl<B: AbstractCircBuilder<R> + 'static, R: Runtime> HsCircPoolInner<B, R> {
         netdir: &NetDir,
         avoid_target: Option<&T>,
         kind: HsCircStemKind,
+        stem_use: StemUse,
     ) -> Result<HsCircStem<B::Circ>>
     where
         // TODO #504: It would be better if this were a type that had to include
@@ -531,6 +567,15 @@ impl<B: AbstractCircBuilder<R> + 'static, R: Runtime> HsCircPoolInner<B, R> {
             let mut inner = self.inner.lock().expect("lock poisoned");
 
             let restrictions = |circ: &HsCircStem<B::Circ>| {
+                // The stem might have been built before our `hs_endpoints`
+                // configuration changed, so its last hop might not be a
+                // permissible rendezvous point any more.
+                if stem_use == StemUse::Rendezvous
+                    && !self.last_hop_is_permitted_rend(netdir, circ, kind)
+                {
+                    return false;
+                }
+
                 // If vanguards are enabled, we no longer apply same-family or same-subnet
                 // restrictions, and we allow the guard to appear as either of the last
                 // two hope of the circuit.
@@ -660,6 +705,7 @@ impl<B: AbstractCircBuilder<R> + 'static, R: Runtime> HsCircPoolInner<B, R> {
                     &hops,
                     netdir,
                     &target_exclusion,
+                    &self.final_hop_restrictions(netdir),
                     &mut rand::thread_rng(),
                 )?;
 
@@ -786,6 +832,84 @@ impl<B: AbstractCircBuilder<R> + 'static, R: Runtime> HsCircPoolInner<B, R> {
         }
     }
 
+    /// Internal implementation for [`HsCircPool::restrict_endpoint_selector`].
+    #[cfg(feature = "hs-endpoint-restrictions")]
+    pub(crate) fn restrict_endpoint_selector<'a>(
+        &self,
+        selector: &mut RelaySelector<'a>,
+        netdir: &'a NetDir,
+    ) {
+        let path_cfg = self.circmgr.builder().path_config();
+        let cfg = path_cfg.relay_selection_config();
+        path_cfg
+            .hs_endpoints
+            .restrict_selector(selector, netdir, &cfg);
+    }
+
+    /// Return the restrictions that the last hop of a stem must obey.
+    ///
+    /// These are the same restrictions that our path builder applies
+    /// to the last hop of every stem it builds.
+    #[cfg(all(feature = "vanguards", feature = "hs-common"))]
+    #[cfg_attr(
+        not(feature = "hs-endpoint-restrictions"),
+        allow(unused_variables, clippy::unused_self)
+    )]
+    fn final_hop_restrictions<'a>(&self, netdir: &'a NetDir) -> Vec<RelayRestriction<'a>> {
+        #[cfg(feature = "hs-endpoint-restrictions")]
+        {
+            let path_cfg = self.circmgr.builder().path_config();
+            let cfg = path_cfg.relay_selection_config();
+            path_cfg.hs_endpoints.restrictions(netdir, &cfg)
+        }
+        #[cfg(not(feature = "hs-endpoint-restrictions"))]
+        vec![]
+    }
+
+    /// Return true if we could use the last hop of `circ` as a rendezvous point,
+    /// once we have turned it into a stem of the specified `kind`.
+    #[cfg_attr(
+        not(feature = "hs-endpoint-restrictions"),
+        allow(unused_variables, clippy::unused_self)
+    )]
+    fn last_hop_is_permitted_rend(
+        &self,
+        netdir: &NetDir,
+        circ: &HsCircStem<B::Circ>,
+        kind: HsCircStemKind,
+    ) -> bool {
+        #[cfg(feature = "hs-endpoint-restrictions")]
+        {
+            #[cfg(all(feature = "vanguards", feature = "hs-common"))]
+            if self.vanguard_mode() == VanguardMode::Full
+                && circ.kind == HsCircStemKind::Naive
+                && kind == HsCircStemKind::Guarded
+            {
+                // We'll extend this stem with a new last hop,
+                // which will obey our restrictions.
+                return true;
+            }
+
+            let path_cfg = self.circmgr.builder().path_config();
+            if path_cfg.hs_endpoints.is_unrestricted() {
+                return true;
+            }
+            let cfg = path_cfg.relay_selection_config();
+            let path = circ.path_ref();
+            let Some(relay) = path
+                .hops()
+                .last()
+                .and_then(|hop| hop.as_chan_target())
+                .and_then(|ct| netdir.by_ids(ct))
+            else {
+                return false;
+            };
+            path_cfg.hs_endpoints.permits_relay(&relay, netdir, &cfg)
+        }
+        #[cfg(not(feature = "hs-endpoint-restrictions"))]
+        true
+    }
+
     /// Internal implementation for [`HsCircPool::estimate_timeout`].
     pub(crate) fn estimate_timeout(
         &self,
@@ -2,6 +2,25 @@
 
 use tor_basic_utils::define_accessor_trait;
 
+#[cfg(feature = "hs-endpoint-restrictions")]
+use {
+    derive_builder::Builder,
+    serde::{Deserialize, Serialize},
+    tor_config::{
+        define_list_builder_accessors, define_list_builder_helper, impl_standard_builder,
+        ConfigBuildError,
+    },
+    tor_llcrypto::pk::rsa::RsaIdentity,
+    tor_netdir::{NetDir, Relay},
+    tor_relay_selection::{
+        LowLevelRelayPredicate, RelayExclusion, RelayRestriction, RelaySelectionConfig,
+        RelaySelector,
+    },
+};
+
+#[cfg(all(feature = "hs-endpoint-restrictions", feature = "geoip"))]
+use tor_geoip::CountryCode;
+
 define_accessor_trait! {
     /// Configuration for an `HsCircPool`.
     ///
@@ -23,3 +42,169 @@ define_accessor_trait! {
         fn vanguard_config(&self) -> &tor_guardmgr::VanguardConfig;
     }
 }
+
+/// Restrictions on which relays we use as rendezvous and introduction points.
+///
+/// These are for operators with specific threat models.
+/// Every restriction makes the set of relays we choose from smaller,
+/// which may make our onion service circuits easier to distinguish,
+/// and may make it impossible to build them at all.
+/// By default, there are no restrictions beyond Tor's usual rules.
+///
+/// This configuration is experimental, and may change or be removed in future versions.
+///
+/// These restrictions also apply to the last hop of every onion service circuit stem we build,
+/// so that a client can use any stem's last hop as its rendezvous point
+/// without extending the circuit further.
+///
+/// You may change this configuration on a running Arti client:
+/// it affects the rendezvous and introduction points we choose in the future.
+#[cfg(feature = "hs-endpoint-restrictions")]
+#[derive(Debug, Clone, Builder, Eq, PartialEq)]
+#[builder(build_fn(error = "ConfigBuildError"))]
+#[builder(derive(Debug, Serialize, Deserialize))]
+pub struct HsEndpointConfig {
+    /// Only use relays whose consensus bandwidth weight is at least this.
+    #[builder(default)]
+    pub(crate) min_bandwidth_weight: u32,
+
+    /// Only use relays that have the Stable flag.
+    #[builder(default)]
+    pub(crate) require_stable: bool,
+
+    /// Never use a relay in the same family as any of these relays (given by RSA identity).
+    #[builder(sub_builder, setter(custom))]
+    #[builder_field_attr(serde(default))]
+    pub(crate) exclude_families: ExcludedFamilies,
+
+    /// Never use a relay that appears to be in any of these countries
+    /// (given by two-letter country code).
+    #[cfg(feature = "geoip")]
+    #[builder(sub_builder, setter(custom))]
+    #[builder_field_attr(serde(default))]
+    pub(crate) exclude_countries: ExcludedCountries,
+}
+#[cfg(feature = "hs-endpoint-restrictions")]
+impl_standard_builder! { HsEndpointConfig }
+
+/// Type alias for a list of relays whose families we exclude.
+#[cfg(feature = "hs-endpoint-restrictions")]
+type ExcludedFamilies = Vec<RsaIdentity>;
+
+#[cfg(feature = "hs-endpoint-restrictions")]
+define_list_builder_helper! {
+    pub struct ExcludedFamiliesBuilder {
+        pub(crate) exclude_families: [RsaIdentity],
+    }
+    built: ExcludedFamilies = exclude_families;
+    default = vec![];
+    item_build: |id| Ok(*id);
+}
+
+#[cfg(feature = "hs-endpoint-restrictions")]
+define_list_builder_accessors! {
+    struct HsEndpointConfigBuilder {
+        pub exclude_families: [RsaIdentity],
+    }
+}
+
+/// Type alias for a list of countries that we exclude.
+#[cfg(all(feature = "hs-endpoint-restrictions", feature = "geoip"))]
+type ExcludedCountries = Vec<CountryCode>;
+
+#[cfg(all(feature = "hs-endpoint-restrictions", feature = "geoip"))]
+define_list_builder_helper! {
+    pub struct ExcludedCountriesBuilder {
+        pub(crate) exclude_countries: [String],
+    }
+    built: ExcludedCountries = exclude_countries;
+    default = vec![];
+    item_build: |cc| cc.parse::<CountryCode>().map_err(|e| ConfigBuildError::Invalid {
+        field: "exclude_countries".to_owned(),
+        problem: format!("{:?} is not a country code: {}", cc, e),
+    });
+}
+
+#[cfg(all(feature = "hs-endpoint-restrictions", feature = "geoip"))]
+define_list_builder_accessors! {
+    struct HsEndpointConfigBuilder {
+        pub exclude_countries: [String],
+    }
+}
+
+#[cfg(feature = "hs-endpoint-restrictions")]
+impl HsEndpointConfig {
+    /// Return true if this configuration doesn't restrict our choice of relays at all.
+    pub(crate) fn is_unrestricted(&self) -> bool {
+        #[cfg(feature = "geoip")]
+        if !self.exclude_countries.is_empty() {
+            return false;
+        }
+        self.min_bandwidth_weight == 0 && !self.require_stable && self.exclude_families.is_empty()
+    }
+
+    /// Return the restrictions in this configuration.
+    ///
+    /// `cfg` says which relays count as being in the same family.
+    pub(crate) fn restrictions<'a>(
+        &self,
+        netdir: &'a NetDir,
+        cfg: &RelaySelectionConfig<'_>,
+    ) -> Vec<RelayRestriction<'a>> {
+        let mut restrictions = vec![];
+        if self.min_bandwidth_weight > 0 {
+            restrictions.push(RelayRestriction::require_min_bandwidth_weight(
+                self.min_bandwidth_weight,
+            ));
+        }
+        if self.require_stable {
+            restrictions.push(RelayRestriction::require_stable());
+        }
+        if !self.exclude_families.is_empty() {
+            // A relay that isn't in the netdir can't be chosen,
+            // and we can't learn its family; so there's nothing to do for it.
+            let relays = self
+                .exclude_families
+                .iter()
+                .filter_map(|id| netdir.by_id(id))
+                .collect();
+            restrictions
+                .push(RelayExclusion::exclude_relays_in_same_family(cfg, relays).into());
+        }
+        #[cfg(feature = "geoip")]
+        if !self.exclude_countries.is_empty() {
+            restrictions.push(RelayRestriction::exclude_country_codes(
+                self.exclude_countries.clone(),
+            ));
+        }
+        restrictions
+    }
+
+    /// Add the restrictions in this configuration to `selector`.
+    ///
+    /// `cfg` says which relays count as being in the same family.
+    pub(crate) fn restrict_selector<'a>(
+        &self,
+        selector: &mut RelaySelector<'a>,
+        netdir: &'a NetDir,
+        cfg: &RelaySelectionConfig<'_>,
+    ) {
+        for restriction in self.restrictions(netdir, cfg) {
+            selector.push_restriction(restriction);
+        }
+    }
+
+    /// Return true if `relay` obeys the restrictions in this configuration.
+    ///
+    /// `cfg` says which relays count as being in the same family.
+    pub(crate) fn permits_relay(
+        &self,
+        relay: &Relay<'_>,
+        netdir: &NetDir,
+        cfg: &RelaySelectionConfig<'_>,
+    ) -> bool {
+        self.restrictions(netdir, cfg)
+            .iter()
+            .all(|r| r.low_level_predicate_permits_relay(relay))
+    }
+}
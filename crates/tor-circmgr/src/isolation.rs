@@ -2,6 +2,7 @@
 use downcast_rs::{impl_downcast, Downcast};
 use dyn_clone::{clone_trait_object, DynClone};
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
 /// A type that can make isolation decisions about streams it is attached to.
 ///
@@ -338,6 +339,96 @@ tuple_impls! {
     }
 }
 
+/// An isolation value for streams that are made on behalf of other streams.
+///
+/// Some protocols open new connections on behalf of an existing one:
+/// an FTP data channel belongs to its control connection,
+/// and a redirect that an application follows belongs to the original request.
+/// A lineage lets the application say whether such a connection
+/// may share a circuit with the one it came from.
+///
+/// Every lineage descends from a root made with [`IsolationLineage::new`].
+/// Two lineages are compatible if they are the same,
+/// or if one of them is an ancestor of the other.
+/// So a stream using [`child()`](IsolationLineage::child)
+/// may share a circuit with streams using its parent (or any other ancestor),
+/// but never with streams using its siblings or their descendants.
+/// To share everything with the parent, just use a clone of the parent.
+///
+/// Lineages from different roots are never compatible,
+/// and neither is a lineage with any other kind of [`Isolation`].
+///
+/// # Examples
+///
+/// ```rust
+/// # use tor_circmgr::isolation::IsolationLineage;
+/// let control = IsolationLineage::new();
+/// let data_1 = control.child();
+/// let data_2 = control.child();
+///
+/// assert!(control.is_ancestor_of(&data_1));
+/// assert!(!data_1.is_ancestor_of(&data_2));
+/// assert_eq!(data_1.parent(), Some(control));
+/// ```
+// # Semver note
+//
+// This type is re-exported by `arti-client`: any changes to it must be
+// reflected in `arti-client`'s version.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IsolationLineage(Arc<[IsolationToken]>);
+
+#[allow(clippy::new_without_default)]
+impl IsolationLineage {
+    /// Create a new root lineage, incompatible with every lineage that
+    /// does not descend from it.
+    pub fn new() -> Self {
+        IsolationLineage(Arc::from([IsolationToken::new()]))
+    }
+
+    /// Create a new child of this lineage.
+    ///
+    /// The child is compatible with this lineage and its ancestors,
+    /// but not with any other child of this lineage.
+    pub fn child(&self) -> Self {
+        IsolationLineage(
+            self.0
+                .iter()
+                .copied()
+                .chain(std::iter::once(IsolationToken::new()))
+                .collect(),
+        )
+    }
+
+    /// Return the parent of this lineage, or `None` if it is a root.
+    pub fn parent(&self) -> Option<Self> {
+        match &self.0[..] {
+            [] | [_] => None,
+            [ancestors @ .., _] => Some(IsolationLineage(Arc::from(ancestors))),
+        }
+    }
+
+    /// Return true if this lineage is a strict ancestor of `other`.
+    pub fn is_ancestor_of(&self, other: &Self) -> bool {
+        other.0.len() > self.0.len() && other.0.starts_with(&self.0)
+    }
+}
+
+impl IsolationHelper for IsolationLineage {
+    fn compatible_same_type(&self, other: &Self) -> bool {
+        self.0.starts_with(&other.0) || other.0.starts_with(&self.0)
+    }
+    fn join_same_type(&self, other: &Self) -> Option<Self> {
+        // The join is the more specific of the two.
+        if other.0.starts_with(&self.0) {
+            Some(other.clone())
+        } else if self.0.starts_with(&other.0) {
+            Some(self.clone())
+        } else {
+            None
+        }
+    }
+}
+
 /// A set of information about how a stream should be isolated.
 ///
 /// If two streams are isolated from one another, they may not share
@@ -547,6 +638,46 @@ pub(crate) mod test {
         }
     }
 
+    #[test]
+    fn isolation_lineage() {
+        let root = IsolationLineage::new();
+        let child_1 = root.child();
+        let child_2 = root.child();
+        let grandchild = child_1.child();
+        let other_root = IsolationLineage::new();
+
+        assert!(root.compatible_same_type(&root));
+        assert!(root.compatible_same_type(&child_1));
+        assert!(child_1.compatible_same_type(&root));
+        assert!(root.compatible_same_type(&grandchild));
+        assert!(!child_1.compatible_same_type(&child_2));
+        assert!(!child_2.compatible_same_type(&grandchild));
+        assert!(!root.compatible_same_type(&other_root));
+
+        assert_eq!(root.join_same_type(&root), Some(root.clone()));
+        assert_eq!(root.join_same_type(&child_1), Some(child_1.clone()));
+        assert_eq!(child_1.join_same_type(&root), Some(child_1.clone()));
+        assert_eq!(child_1.join_same_type(&child_2), None);
+
+        // Once a child has used a circuit, its parent may still use it,
+        // but a sibling may not.
+        let circ = root.join_same_type(&child_1).unwrap();
+        assert!(circ.compatible_same_type(&root));
+        assert!(!circ.compatible_same_type(&child_2));
+
+        assert_eq!(grandchild.parent(), Some(child_1.clone()));
+        assert_eq!(child_1.parent(), Some(root.clone()));
+        assert_eq!(root.parent(), None);
+        assert!(root.is_ancestor_of(&grandchild));
+        assert!(!root.is_ancestor_of(&root));
+        assert!(!grandchild.is_ancestor_of(&root));
+
+        let root_dyn: Box<dyn Isolation> = Box::new(root.clone());
+        let token: Box<dyn Isolation> = Box::new(IsolationToken::new());
+        assert!(root_dyn.compatible(&child_1));
+        assert!(!root_dyn.compatible(token.as_ref()));
+    }
+
     #[test]
     fn build_isolation() {
         let no_isolation = StreamIsolation::no_isolation();
@@ -70,6 +70,7 @@ pub use config::test_config::TestConfig;
 pub mod build;
 mod config;
 mod err;
+mod events;
 #[cfg(feature = "hs-common")]
 pub mod hspool;
 mod impls;
@@ -83,6 +84,7 @@ pub mod timeouts;
 mod usage;
 
 pub use err::Error;
+pub use events::CircBuiltEvents;
 pub use isolation::IsolationToken;
 use tor_guardmgr::fallback::FallbackList;
 pub use tor_guardmgr::{ClockSkewEvents, GuardMgrConfig, SkewEstimate};
@@ -119,6 +121,14 @@ type TimeoutStateHandle = tor_persist::DynStorageHandle<timeouts::pareto::Pareto
 /// Key used to load timeout state information.
 const PARETO_TIMEOUT_DATA_KEY: &str = "circuit_timeouts";
 
+/// Type alias for dynamic StorageHandle that can handle our predicted port usage.
+type PredictorStateHandle = tor_persist::DynStorageHandle<preemptive::PredictedUsageState>;
+
+/// Prefix for the keys used to load predicted port usage for a profile.
+///
+/// The full key is this prefix, followed by `_` and the profile name.
+const PREDICTED_USAGE_KEY_PREFIX: &str = "predicted_ports";
+
 /// Represents what we know about the Tor network.
 ///
 /// This can either be a complete directory, or a list of fallbacks.
@@ -303,6 +313,14 @@ impl<R: Runtime> CircMgr<R> {
         self.0.skew_events()
     }
 
+    /// Return a stream of events telling whether we have built a circuit for
+    /// user traffic yet.
+    ///
+    /// See [`CircBuiltEvents`] for details.
+    pub fn circ_built_events(&self) -> CircBuiltEvents {
+        self.0.mgr.circ_built_events()
+    }
+
     /// Try to change our configuration settings to `new_config`.
     ///
     /// The actual behavior here will depend on the value of `how`.
@@ -345,6 +363,26 @@ impl<R: Runtime> CircMgr<R> {
         self.0.estimate_timeout(timeout_action)
     }
 
+    /// Flush our guard and circuit timeout state to the state manager,
+    /// if we hold the lock on it.
+    ///
+    /// This happens periodically in the background anyway;
+    /// call this function if you need the state manager to be up-to-date right now.
+    ///
+    /// Return true if we saved something; false if we didn't have the lock.
+    pub fn store_persistent_state(&self) -> Result<bool> {
+        self.0.store_persistent_state()
+    }
+
+    /// Replace our guard and circuit timeout state with the contents of the state manager.
+    ///
+    /// Use this function after changing the state manager's contents
+    /// from outside of this circuit manager.
+    /// Any unsaved state in this circuit manager is lost.
+    pub fn load_persistent_state(&self) -> Result<()> {
+        self.0.builder().load_state()
+    }
+
     /// Return a reference to the associated CircuitBuilder that this CircMgr
     /// will use to create its circuits.
     #[cfg(feature = "experimental-api")]
@@ -360,6 +398,9 @@ pub(crate) struct CircMgrInner<B: AbstractCircBuilder<R> + 'static, R: Runtime>
     mgr: Arc<mgr::AbstractCircMgr<B, R>>,
     /// A preemptive circuit predictor, for, uh, building circuits preemptively.
     predictor: Arc<Mutex<PreemptiveCircuitPredictor>>,
+    /// Where to save the predictor's usage information,
+    /// if we have been given a profile to save it under.
+    predictor_storage: Option<PredictorStateHandle>,
 }
 
 impl<R: Runtime> CircMgrInner<CircuitBuilder<R>, R> {
@@ -394,6 +435,15 @@ impl<R: Runtime> CircMgrInner<CircuitBuilder<R>, R> {
             )?
         };
 
+        let predictor_storage = config
+            .preemptive_circuits()
+            .profile
+            .as_ref()
+            .map(|profile| {
+                storage
+                    .clone()
+                    .create_handle(format!("{}_{}", PREDICTED_USAGE_KEY_PREFIX, profile))
+            });
         let storage_handle = storage.create_handle(PARETO_TIMEOUT_DATA_KEY);
 
         let builder = build::CircuitBuilder::new(
@@ -406,7 +456,9 @@ impl<R: Runtime> CircMgrInner<CircuitBuilder<R>, R> {
             vanguardmgr,
         );
 
-        Ok(Self::new_generic(config, runtime, guardmgr, builder))
+        let circmgr = Self::new_generic(config, runtime, guardmgr, builder, predictor_storage);
+        circmgr.load_predictor_state()?;
+        Ok(circmgr)
     }
 }
 
@@ -417,6 +469,7 @@ impl<B: AbstractCircBuilder<R> + 'static, R: Runtime> CircMgrInner<B, R> {
         runtime: &R,
         guardmgr: &tor_guardmgr::GuardMgr<R>,
         builder: B,
+        predictor_storage: Option<PredictorStateHandle>,
     ) -> Self {
         let preemptive = Arc::new(Mutex::new(PreemptiveCircuitPredictor::new(
             config.preemptive_circuits().clone(),
@@ -430,6 +483,7 @@ impl<B: AbstractCircBuilder<R> + 'static, R: Runtime> CircMgrInner<B, R> {
         CircMgrInner {
             mgr: Arc::new(mgr),
             predictor: preemptive,
+            predictor_storage,
         }
     }
 
@@ -818,6 +872,7 @@ impl<B: AbstractCircBuilder<R> + 'static, R: Runtime> CircMgrInner<B, R> {
     /// Requires that we hold the lock on the state files.
     pub(crate) fn upgrade_to_owned_persistent_state(&self) -> Result<()> {
         self.mgr.peek_builder().upgrade_to_owned_state()?;
+        self.load_predictor_state()?;
         Ok(())
     }
 
@@ -827,6 +882,43 @@ impl<B: AbstractCircBuilder<R> + 'static, R: Runtime> CircMgrInner<B, R> {
     /// files.  If we have the lock, we only want to save.
     pub(crate) fn reload_persistent_state(&self) -> Result<()> {
         self.mgr.peek_builder().reload_state()?;
+        self.load_predictor_state()?;
+        Ok(())
+    }
+
+    /// Add any predicted port usage in the state manager to our predictor.
+    ///
+    /// Does nothing if we have no profile to load usage information for.
+    fn load_predictor_state(&self) -> Result<()> {
+        let Some(storage) = &self.predictor_storage else {
+            return Ok(());
+        };
+        if let Some(state) = storage.load()? {
+            let wallclock = self.mgr.peek_runtime().wallclock();
+            self.predictor
+                .lock()
+                .expect("preemptive lock poisoned")
+                .restore_usage_state(&state, Instant::now(), wallclock);
+        }
+        Ok(())
+    }
+
+    /// Save our predictor's port usage to the state manager,
+    /// if we have a profile to save it under and we hold the lock.
+    fn store_predictor_state(&self) -> Result<()> {
+        let Some(storage) = &self.predictor_storage else {
+            return Ok(());
+        };
+        if !storage.can_store() {
+            return Ok(());
+        }
+        let wallclock = self.mgr.peek_runtime().wallclock();
+        let state = self
+            .predictor
+            .lock()
+            .expect("preemptive lock poisoned")
+            .usage_state(Instant::now(), wallclock);
+        storage.store(&state)?;
         Ok(())
     }
 
@@ -997,7 +1089,11 @@ impl<B: AbstractCircBuilder<R> + 'static, R: Runtime> CircMgrInner<B, R> {
     ///
     /// Return true if we saved something; false if we didn't have the lock.
     pub(crate) fn store_persistent_state(&self) -> Result<bool> {
-        self.mgr.peek_builder().save_state()
+        let saved = self.mgr.peek_builder().save_state()?;
+        if saved {
+            self.store_predictor_state()?;
+        }
+        Ok(saved)
     }
 
     /// Expire every circuit that has been dirty for too long.
@@ -1157,7 +1253,7 @@ mod test {
             &tor_guardmgr::TestConfig::default(),
         );
         let circmgr = Arc::new(CircMgrInner::new_generic(
-            &config, &runtime, &guardmgr, builder,
+            &config, &runtime, &guardmgr, builder, None,
         ));
         let netdir = Arc::new(TestNetDirProvider::new());
         CircMgrInner::launch_background_tasks(&circmgr, &runtime, &netdir, statemgr)
@@ -1192,4 +1288,48 @@ mod test {
             ret_rx.await.unwrap().unwrap();
         });
     }
+
+    #[test]
+    fn predictor_state_persists() {
+        tor_rtmock::MockRuntime::test_with_various(|runtime| async move {
+            let config = crate::config::test_config::TestConfig::default();
+            let statemgr = TestingStateMgr::new();
+            assert!(statemgr.try_lock().unwrap().held());
+            let guardmgr = GuardMgr::new(runtime.clone(), statemgr.clone(), &config).unwrap();
+            let new_circmgr = || {
+                let builder = FakeBuilder::new(
+                    &runtime,
+                    statemgr.clone(),
+                    &tor_guardmgr::TestConfig::default(),
+                );
+                let storage = statemgr.clone().create_handle("predicted_ports_test");
+                let circmgr =
+                    CircMgrInner::new_generic(&config, &runtime, &guardmgr, builder, Some(storage));
+                circmgr.load_predictor_state().unwrap();
+                circmgr
+            };
+            let port = TargetPort::ipv4(5555);
+            let predicts_port = |circmgr: &CircMgrInner<FakeBuilder<_>, _>| {
+                circmgr
+                    .predictor
+                    .lock()
+                    .unwrap()
+                    .predict(&PathConfig::default())
+                    .iter()
+                    .any(|u| matches!(u, TargetCircUsage::Preemptive { port: Some(p), .. } if *p == port))
+            };
+
+            let circmgr = new_circmgr();
+            assert!(!predicts_port(&circmgr));
+            circmgr
+                .predictor
+                .lock()
+                .unwrap()
+                .note_usage(Some(port), Instant::now());
+            circmgr.store_predictor_state().unwrap();
+
+            let circmgr = new_circmgr();
+            assert!(predicts_port(&circmgr));
+        });
+    }
 }
@@ -23,7 +23,7 @@
 
 use crate::config::CircuitTiming;
 use crate::usage::{SupportedCircUsage, TargetCircUsage};
-use crate::{timeouts, DirInfo, Error, PathConfig, Result};
+use crate::{timeouts, CircBuiltEvents, DirInfo, Error, PathConfig, Result};
 
 use retry_error::RetryError;
 use tor_async_utils::mpsc_channel_no_memquota;
@@ -731,6 +731,11 @@ pub(crate) struct AbstractCircMgr<B: AbstractCircBuilder<R>, R: Runtime> {
     ///
     /// Derived from the network parameters.
     unused_timing: sync::Mutex<UnusedTimings>,
+
+    /// Sender to tell our [`CircBuiltEvents`] once we have built an exit circuit.
+    send_circ_built: sync::Mutex<postage::watch::Sender<bool>>,
+    /// Receiver for the events sent with `send_circ_built`.
+    recv_circ_built: CircBuiltEvents,
 }
 
 /// An action to take in order to satisfy a request for a circuit.
@@ -751,15 +756,25 @@ impl<B: AbstractCircBuilder<R> + 'static, R: Runtime> AbstractCircMgr<B, R> {
         let circs = sync::Mutex::new(CircList::new());
         let dflt_params = tor_netdir::params::NetParameters::default();
         let unused_timing = (&dflt_params).into();
+        let (send_circ_built, recv_circ_built) = postage::watch::channel();
         AbstractCircMgr {
             builder,
             runtime,
             circs,
             circuit_timing: circuit_timing.into(),
             unused_timing: sync::Mutex::new(unused_timing),
+            send_circ_built: sync::Mutex::new(send_circ_built),
+            recv_circ_built: CircBuiltEvents {
+                inner: recv_circ_built,
+            },
         }
     }
 
+    /// Return a stream of events telling whether we have built an exit circuit yet.
+    pub(crate) fn circ_built_events(&self) -> CircBuiltEvents {
+        self.recv_circ_built.clone()
+    }
+
     /// Reconfigure this manager using the latest set of network parameters.
     pub(crate) fn update_network_parameters(&self, p: &tor_netdir::params::NetParameters) {
         let mut u = self
@@ -1300,6 +1315,15 @@ impl<B: AbstractCircBuilder<R> + 'static, R: Runtime> AbstractCircMgr<B, R> {
         wait_on_future
     }
 
+    /// Tell our [`CircBuiltEvents`] that we have built an exit circuit,
+    /// if we haven't told them already.
+    fn note_circ_built(&self) {
+        let mut send = self.send_circ_built.lock().expect("poisoned lock");
+        if !*send.borrow() {
+            *send.borrow_mut() = true;
+        }
+    }
+
     /// Run in the background to launch a circuit. Return a 2-tuple of the new
     /// circuit spec and the outcome that should be sent to the initiator.
     async fn do_launch(
@@ -1338,6 +1362,9 @@ impl<B: AbstractCircBuilder<R> + 'static, R: Runtime> AbstractCircMgr<B, R> {
                     // no longer give this circuit to a client.)
                     if list.circ_is_pending(&pending) {
                         list.add_open(open_ent);
+                        if matches!(new_spec, SupportedCircUsage::Exit { .. }) {
+                            self.note_circ_built();
+                        }
                         // We drop our reference to 'pending' here:
                         // this should make all the weak references to
                         // the `PendingEntry` become dangling.
@@ -1605,11 +1632,14 @@ mod test {
             // Check initialization.
             assert_eq!(mgr.n_circs(), 0);
             assert!(mgr.peek_builder().script.lock().unwrap().is_empty());
+            let circ_built = mgr.circ_built_events();
+            assert!(!circ_built.get());
 
             // Launch a circuit; make sure we get it.
             let c1 = rt.wait_for(mgr.get_or_launch(&webports, di())).await;
             let c1 = c1.unwrap().0;
             assert_eq!(mgr.n_circs(), 1);
+            assert!(circ_built.get());
 
             // Make sure we get the one we already made if we ask for it.
             let port80 = TargetCircUsage::new_from_ipv4_ports(&[80]);
@@ -67,10 +67,15 @@ use rand::Rng;
 use tor_error::internal;
 use tor_linkspec::{HasRelayIds, OwnedChanTarget};
 use tor_netdir::{NetDir, Relay};
-use tor_relay_selection::{RelayExclusion, RelaySelectionConfig, RelaySelector, RelayUsage};
+use tor_relay_selection::{
+    RelayExclusion, RelayRestriction, RelaySelectionConfig, RelaySelector, RelayUsage,
+};
 
 use crate::{hspool::HsCircStemKind, Error, Result};
 
+#[cfg(feature = "hs-endpoint-restrictions")]
+use crate::hspool::HsEndpointConfig;
+
 use super::AnonymousPathBuilder;
 
 use {
@@ -106,6 +111,10 @@ pub(crate) struct HsPathBuilder {
     /// This is only used if `vanguards` are enabled.
     #[cfg_attr(not(feature = "vanguards"), allow(dead_code))]
     kind: HsCircStemKind,
+    /// Restrictions that the final hop of the path must obey,
+    /// so that it can be used as a rendezvous point.
+    #[cfg(feature = "hs-endpoint-restrictions")]
+    endpoints: Option<HsEndpointConfig>,
 }
 
 impl HsPathBuilder {
@@ -120,7 +129,32 @@ impl HsPathBuilder {
         Self {
             compatible_with,
             kind,
+            #[cfg(feature = "hs-endpoint-restrictions")]
+            endpoints: None,
+        }
+    }
+
+    /// Require the final hop of the path to obey the restrictions in `endpoints`.
+    #[cfg(feature = "hs-endpoint-restrictions")]
+    pub(crate) fn restrict_final_hop(&mut self, endpoints: &HsEndpointConfig) {
+        self.endpoints = (!endpoints.is_unrestricted()).then(|| endpoints.clone());
+    }
+
+    /// Return the restrictions that the final hop of the path must obey.
+    #[cfg_attr(
+        not(feature = "hs-endpoint-restrictions"),
+        allow(unused_variables, clippy::unused_self)
+    )]
+    fn final_hop_restrictions<'a>(
+        &self,
+        netdir: &'a NetDir,
+        rs_cfg: &RelaySelectionConfig<'_>,
+    ) -> Vec<RelayRestriction<'a>> {
+        #[cfg(feature = "hs-endpoint-restrictions")]
+        if let Some(endpoints) = &self.endpoints {
+            return endpoints.restrictions(netdir, rs_cfg);
         }
+        vec![]
     }
 
     /// Try to create and return a path for a hidden service circuit stem.
@@ -161,7 +195,10 @@ impl HsPathBuilder {
             compatible_with: self.compatible_with.clone(),
         };
 
-        vanguard_path_builder.pick_path(rng, netdir, guards, vanguards)
+        let rs_cfg = config.relay_selection_config();
+        vanguard_path_builder.pick_path(rng, netdir, guards, vanguards, |netdir| {
+            self.final_hop_restrictions(netdir, &rs_cfg)
+        })
     }
 }
 
@@ -179,12 +216,15 @@ impl AnonymousPathBuilder for HsPathBuilder {
         rng: &mut R,
         netdir: &'a NetDir,
         guard_exclusion: RelayExclusion<'a>,
-        _rs_cfg: &RelaySelectionConfig<'_>,
+        rs_cfg: &RelaySelectionConfig<'_>,
     ) -> Result<(Relay<'a>, RelayUsage)> {
         // TODO: This usage is a bit convoluted, and some onion-service-
         // related circuits don't need this much stability.
         let usage = RelayUsage::middle_relay(Some(&RelayUsage::new_intro_point()));
-        let selector = RelaySelector::new(usage, guard_exclusion);
+        let mut selector = RelaySelector::new(usage, guard_exclusion);
+        for restriction in self.final_hop_restrictions(netdir, rs_cfg) {
+            selector.push_restriction(restriction);
+        }
 
         let (relay, info) = selector.select_relay(rng, netdir);
         let relay = relay.ok_or_else(|| Error::NoRelay {
@@ -212,12 +252,16 @@ struct VanguardHsPathBuilder {
 #[cfg(feature = "vanguards")]
 impl VanguardHsPathBuilder {
     /// Try to create and return a path for a hidden service circuit stem.
+    ///
+    /// `final_hop` returns the restrictions that the last hop must obey,
+    /// if it is a middle relay.
     fn pick_path<'a, R: Rng, RT: Runtime>(
         &self,
         rng: &mut R,
         netdir: DirInfo<'a>,
         guards: &GuardMgr<RT>,
         vanguards: &VanguardMgr<RT>,
+        final_hop: impl FnOnce(&'a NetDir) -> Vec<RelayRestriction<'a>>,
     ) -> Result<(TorPath<'a>, GuardMonitor, GuardUsable)> {
         let netdir = match netdir {
             DirInfo::Directory(d) => d,
@@ -241,14 +285,25 @@ impl VanguardHsPathBuilder {
             RelayExclusion::no_relays_excluded()
         };
 
+        let final_hop = final_hop(netdir);
         let mode = vanguards.mode();
         let path = match mode {
-            VanguardMode::Lite => {
-                self.pick_lite_vanguard_path(rng, netdir, vanguards, l1_guard, &target_exclusion)?
-            }
-            VanguardMode::Full => {
-                self.pick_full_vanguard_path(rng, netdir, vanguards, l1_guard, &target_exclusion)?
-            }
+            VanguardMode::Lite => self.pick_lite_vanguard_path(
+                rng,
+                netdir,
+                vanguards,
+                l1_guard,
+                &target_exclusion,
+                &final_hop,
+            )?,
+            VanguardMode::Full => self.pick_full_vanguard_path(
+                rng,
+                netdir,
+                vanguards,
+                l1_guard,
+                &target_exclusion,
+                &final_hop,
+            )?,
             VanguardMode::Disabled => {
                 return Err(internal!(
                     "VanguardHsPathBuilder::pick_path called, but vanguards are disabled?!"
@@ -283,6 +338,7 @@ impl VanguardHsPathBuilder {
         vanguards: &VanguardMgr<RT>,
         l1_guard: MaybeOwnedRelay<'n>,
         target_exclusion: &RelayExclusion<'n>,
+        final_hop: &[RelayRestriction<'n>],
     ) -> Result<TorPath<'n>> {
         // NOTE: if the we are using full vanguards and building an GUARDED circuit stem,
         // we do *not* exclude the target from occurring as the second hop
@@ -303,8 +359,11 @@ impl VanguardHsPathBuilder {
                 // If full vanguards are enabled, we need an extra hop for the GUARDED stem:
                 //     NAIVE   = G -> L2 -> L3
                 //     GUARDED = G -> L2 -> L3 -> M
-                path.add_middle(target_exclusion)?.build()
+                path.add_middle(target_exclusion, final_hop)?.build()
             }
+            // The last hop of a NAIVE stem is a vanguard, so we can't restrict it.
+            // If we need a GUARDED stem, HsCircPool will extend this one
+            // with a middle relay that obeys `final_hop`.
             HsCircStemKind::Naive => path.build(),
         }
     }
@@ -317,10 +376,11 @@ impl VanguardHsPathBuilder {
         vanguards: &VanguardMgr<RT>,
         l1_guard: MaybeOwnedRelay<'n>,
         target_exclusion: &RelayExclusion<'n>,
+        final_hop: &[RelayRestriction<'n>],
     ) -> Result<TorPath<'n>> {
         vanguards::PathBuilder::new(rng, netdir, vanguards, l1_guard)
             .add_vanguard(target_exclusion, Layer::Layer2)?
-            .add_middle(target_exclusion)?
+            .add_middle(target_exclusion, final_hop)?
             .build()
     }
 }
@@ -504,6 +564,19 @@ mod test {
         stem_kind: HsCircStemKind,
         mode: VanguardMode,
         target: Option<&OwnedChanTarget>,
+    ) -> Result<TorPath<'a>> {
+        let config = PathConfig::default();
+        pick_vanguard_path_with_config(runtime, netdir, stem_kind, mode, target, &config).await
+    }
+
+    /// Helper for calling `HsPathBuilder::pick_path_with_vanguards` with a specific `config`.
+    async fn pick_vanguard_path_with_config<'a>(
+        runtime: &MockRuntime,
+        netdir: &'a NetDir,
+        stem_kind: HsCircStemKind,
+        mode: VanguardMode,
+        target: Option<&OwnedChanTarget>,
+        config: &PathConfig,
     ) -> Result<TorPath<'a>> {
         let vanguardmgr = VanguardMgr::new_testing(runtime, mode).unwrap();
         let _provider = vanguardmgr.init_vanguard_sets(netdir).await.unwrap();
@@ -519,11 +592,10 @@ mod test {
         netdir_provider.set_netdir(netdir.clone());
         let netdir_provider: Arc<dyn NetDirProvider> = netdir_provider;
         guards.install_netdir_provider(&netdir_provider).unwrap();
-        let config = PathConfig::default();
         let now = SystemTime::now();
         let dirinfo = (netdir).into();
-        HsPathBuilder::new(target.cloned(), stem_kind)
-            .pick_path_with_vanguards(&mut rng, dirinfo, &guards, &vanguardmgr, &config, now)
+        hs_path_builder(target, stem_kind, config)
+            .pick_path_with_vanguards(&mut rng, dirinfo, &guards, &vanguardmgr, config, now)
             .map(|res| res.0)
     }
 
@@ -531,9 +603,17 @@ mod test {
     fn pick_hs_path_no_vanguards<'a>(
         netdir: &'a NetDir,
         target: Option<&OwnedChanTarget>,
+    ) -> Result<TorPath<'a>> {
+        pick_hs_path_no_vanguards_with_config(netdir, target, &PathConfig::default())
+    }
+
+    /// Helper for calling `HsPathBuilder::pick_path` with a specific `config`.
+    fn pick_hs_path_no_vanguards_with_config<'a>(
+        netdir: &'a NetDir,
+        target: Option<&OwnedChanTarget>,
+        config: &PathConfig,
     ) -> Result<TorPath<'a>> {
         let mut rng = testing_rng();
-        let config = PathConfig::default();
         let now = SystemTime::now();
         let dirinfo = (netdir).into();
         let guards = tor_guardmgr::GuardMgr::new(
@@ -546,11 +626,25 @@ mod test {
         netdir_provider.set_netdir(netdir.clone());
         let netdir_provider: Arc<dyn NetDirProvider> = netdir_provider;
         guards.install_netdir_provider(&netdir_provider).unwrap();
-        HsPathBuilder::new(target.cloned(), HsCircStemKind::Naive)
-            .pick_path(&mut rng, dirinfo, &guards, &config, now)
+        hs_path_builder(target, HsCircStemKind::Naive, config)
+            .pick_path(&mut rng, dirinfo, &guards, config, now)
             .map(|res| res.0)
     }
 
+    /// Return an `HsPathBuilder` configured the way `TargetCircUsage::build_path` configures it.
+    #[cfg_attr(not(feature = "hs-endpoint-restrictions"), allow(unused_variables))]
+    fn hs_path_builder(
+        target: Option<&OwnedChanTarget>,
+        kind: HsCircStemKind,
+        config: &PathConfig,
+    ) -> HsPathBuilder {
+        #[allow(unused_mut)]
+        let mut builder = HsPathBuilder::new(target.cloned(), kind);
+        #[cfg(feature = "hs-endpoint-restrictions")]
+        builder.restrict_final_hop(&config.hs_endpoints);
+        builder
+    }
+
     /// Return an `OwnedChanTarget` to use as the target of a circuit.
     ///
     /// This will correspond to the "first" relay from the test network
@@ -785,4 +879,74 @@ mod test {
             }
         });
     }
+
+    /// Construct a test network in which only relays 20 and above have a high bandwidth weight.
+    #[cfg(feature = "hs-endpoint-restrictions")]
+    fn low_bandwidth_test_network() -> NetDir {
+        construct_test_network(MAX_NET_SIZE, |pos, nb| {
+            nb.md.family(hex::encode([pos as u8; 20]).parse().unwrap());
+            if pos < 20 {
+                nb.rs.weight(RelayWeight::Measured(1_000));
+            }
+        })
+    }
+
+    /// Return a `PathConfig` that only permits high-bandwidth rendezvous points.
+    #[cfg(feature = "hs-endpoint-restrictions")]
+    fn high_bandwidth_endpoints_config() -> PathConfig {
+        let mut builder = PathConfig::builder();
+        builder.hs_endpoints().min_bandwidth_weight(5_000);
+        builder.build().unwrap()
+    }
+
+    /// Assert that the last hop of `path` has a high bandwidth weight.
+    #[cfg(feature = "hs-endpoint-restrictions")]
+    fn assert_last_hop_high_bandwidth(path: &TorPath, netdir: &NetDir) {
+        let hops = path_hops(path);
+        let last = netdir.by_ids(hops.last().unwrap()).unwrap();
+        assert!(
+            last.low_level_details().consensus_bandwidth_weight() >= 5_000,
+            "last hop {} doesn't obey our restrictions",
+            last.display_relay_ids()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "hs-endpoint-restrictions")]
+    fn hs_path_no_vanguards_restricted_final_hop() {
+        let netdir = low_bandwidth_test_network();
+        let config = high_bandwidth_endpoints_config();
+        for _ in 0..100 {
+            let path = pick_hs_path_no_vanguards_with_config(&netdir, None, &config).unwrap();
+            assert_hs_path_ok(&path, None);
+            assert_last_hop_high_bandwidth(&path, &netdir);
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "vanguards", feature = "hs-endpoint-restrictions"))]
+    fn vanguard_path_restricted_final_hop() {
+        MockRuntime::test_with_various(|runtime| async move {
+            let netdir = low_bandwidth_test_network();
+            let config = high_bandwidth_endpoints_config();
+
+            for (mode, stem_kind) in [
+                (VanguardMode::Lite, HsCircStemKind::Naive),
+                (VanguardMode::Lite, HsCircStemKind::Guarded),
+                // The last hop of a full-vanguard NAIVE stem is a vanguard,
+                // so we only restrict GUARDED stems.
+                (VanguardMode::Full, HsCircStemKind::Guarded),
+            ] {
+                for _ in 0..10 {
+                    let path = pick_vanguard_path_with_config(
+                        &runtime, &netdir, stem_kind, mode, None, &config,
+                    )
+                    .await
+                    .unwrap();
+                    assert_vanguard_path_ok(&path, stem_kind, mode, None);
+                    assert_last_hop_high_bandwidth(&path, &netdir);
+                }
+            }
+        });
+    }
 }
@@ -8,7 +8,7 @@ use tor_error::{internal, Bug};
 use tor_guardmgr::vanguards::{Layer, VanguardMgr};
 use tor_linkspec::HasRelayIds;
 use tor_netdir::{NetDir, Relay};
-use tor_relay_selection::{RelayExclusion, RelaySelector, RelayUsage};
+use tor_relay_selection::{RelayExclusion, RelayRestriction, RelaySelector, RelayUsage};
 use tor_rtcompat::Runtime;
 
 use crate::path::{MaybeOwnedRelay, TorPath};
@@ -79,11 +79,20 @@ impl<'n, 'a, RT: Runtime, R: Rng> PathBuilder<'n, 'a, RT, R> {
         Ok(self)
     }
 
-    /// Extend the path with a middle relay.
-    pub(super) fn add_middle(mut self, target_exclusion: &RelayExclusion<'n>) -> Result<Self> {
-        let middle =
-            select_middle_for_vanguard_circ(&self.hops, self.netdir, target_exclusion, self.rng)?
-                .into();
+    /// Extend the path with a middle relay that obeys `restrictions`.
+    pub(super) fn add_middle(
+        mut self,
+        target_exclusion: &RelayExclusion<'n>,
+        restrictions: &[RelayRestriction<'n>],
+    ) -> Result<Self> {
+        let middle = select_middle_for_vanguard_circ(
+            &self.hops,
+            self.netdir,
+            target_exclusion,
+            restrictions,
+            self.rng,
+        )?
+        .into();
         let () = self.add_hop(middle, HopKind::Middle)?;
         Ok(self)
     }
@@ -179,10 +188,13 @@ fn exclude_neighbors<'n, T: HasRelayIds + 'n>(hops: &[T]) -> RelayExclusion<'n>
 ///
 /// If full vanguards are enabled, this is also used by [`HsCircPool`](crate::hspool::HsCircPool),
 /// for extending NAIVE circuits to become GUARDED circuits.
+///
+/// The relay we select obeys every one of `restrictions`.
 pub(crate) fn select_middle_for_vanguard_circ<'n, R: Rng, T: HasRelayIds + 'n>(
     hops: &[T],
     netdir: &'n NetDir,
     target_exclusion: &RelayExclusion<'n>,
+    restrictions: &[RelayRestriction<'n>],
     rng: &mut R,
 ) -> Result<Relay<'n>> {
     let mut neighbor_exclusion = exclude_neighbors(hops);
@@ -191,7 +203,10 @@ pub(crate) fn select_middle_for_vanguard_circ<'n, R: Rng, T: HasRelayIds + 'n>(
     // TODO: this usage has need_stable = true, but we probably
     // don't necessarily need a stable relay here.
     let usage = RelayUsage::middle_relay(None);
-    let selector = RelaySelector::new(usage, neighbor_exclusion);
+    let mut selector = RelaySelector::new(usage, neighbor_exclusion);
+    for restriction in restrictions {
+        selector.push_restriction(restriction.clone());
+    }
 
     let (extra_hop, info) = selector.select_relay(rng, netdir);
     extra_hop.ok_or_else(|| Error::NoRelay {
@@ -1,9 +1,10 @@
 //! Tools for determining what circuits to preemptively build.
 
 use crate::{PathConfig, PreemptiveCircuitConfig, TargetCircUsage, TargetPort};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Instant, SystemTime};
 use tracing::warn;
 
 /// Predicts what circuits might be used in future based on past activity, and suggests
@@ -50,6 +51,7 @@ impl PreemptiveCircuitPredictor {
             new_config
                 .initial_predicted_ports
                 .clone_from(&cfg.initial_predicted_ports);
+            new_config.profile.clone_from(&cfg.profile);
             new_config
         });
     }
@@ -89,6 +91,73 @@ impl PreemptiveCircuitPredictor {
     pub(crate) fn note_usage(&mut self, port: Option<TargetPort>, time: Instant) {
         self.usages.insert(port, time);
     }
+
+    /// Return a persistable record of the usages that are still within our
+    /// prediction lifetime, as of `now` (which is `wallclock` in real time).
+    pub(crate) fn usage_state(&self, now: Instant, wallclock: SystemTime) -> PredictedUsageState {
+        let lifetime = self.config().prediction_lifetime;
+        let usages = self
+            .usages
+            .iter()
+            .filter_map(|(&port, &time)| {
+                let age = now.saturating_duration_since(time);
+                if age >= lifetime {
+                    return None;
+                }
+                let last_used = wallclock.checked_sub(age)?;
+                Some(PersistedUsage { port, last_used })
+            })
+            .collect();
+        PredictedUsageState { usages }
+    }
+
+    /// Add the usages recorded in `state` to this predictor,
+    /// as of `now` (which is `wallclock` in real time).
+    ///
+    /// Usages that are older than our prediction lifetime are ignored,
+    /// and usages that we already know about more recently are left alone.
+    pub(crate) fn restore_usage_state(
+        &mut self,
+        state: &PredictedUsageState,
+        now: Instant,
+        wallclock: SystemTime,
+    ) {
+        let lifetime = self.config().prediction_lifetime;
+        for usage in &state.usages {
+            // A usage from the future (according to our clock) is treated as current.
+            let age = wallclock
+                .duration_since(usage.last_used)
+                .unwrap_or_default();
+            if age >= lifetime {
+                continue;
+            }
+            let Some(time) = now.checked_sub(age) else {
+                continue;
+            };
+            let entry = self.usages.entry(usage.port).or_insert(time);
+            if *entry < time {
+                *entry = time;
+            }
+        }
+    }
+}
+
+/// The usages remembered by a [`PreemptiveCircuitPredictor`],
+/// in a form that can be stored persistently.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub(crate) struct PredictedUsageState {
+    /// The ports that were used, and when.
+    usages: Vec<PersistedUsage>,
+}
+
+/// A single persistent record of a port being used.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct PersistedUsage {
+    /// The port that was used, or `None` for a DNS resolution.
+    port: Option<TargetPort>,
+    /// When the port was last used.
+    #[serde(with = "humantime_serde")]
+    last_used: SystemTime,
 }
 
 #[cfg(test)]
@@ -110,7 +179,7 @@ mod test {
         PathConfig, PreemptiveCircuitConfig, PreemptiveCircuitPredictor, TargetCircUsage,
         TargetPort,
     };
-    use std::time::{Duration, Instant};
+    use std::time::{Duration, Instant, SystemTime};
 
     use crate::isolation::test::{assert_isoleq, IsolationTokenEq};
 
@@ -191,6 +260,68 @@ mod test {
             })));
     }
 
+    #[test]
+    fn persists_usages() {
+        let path_config = PathConfig::default();
+        let mut cfg = PreemptiveCircuitConfig::builder();
+        cfg.set_initial_predicted_ports(vec![]);
+        cfg.prediction_lifetime(Duration::from_secs(60));
+        let cfg = cfg.build().unwrap();
+        let mut predictor = PreemptiveCircuitPredictor::new(cfg.clone());
+
+        let now = Instant::now();
+        let wallclock = SystemTime::now();
+        predictor.note_usage(Some(TargetPort::ipv4(22)), now - Duration::from_secs(10));
+        predictor.note_usage(Some(TargetPort::ipv6(6697)), now - Duration::from_secs(90));
+
+        let state = predictor.usage_state(now, wallclock);
+        // The IPv6 usage is too old to save.
+        assert_eq!(state.usages.len(), 2);
+
+        // Restore to a predictor that starts up 20 seconds later.
+        let mut restored = PreemptiveCircuitPredictor::new(cfg);
+        let later = Instant::now();
+        restored.restore_usage_state(&state, later, wallclock + Duration::from_secs(20));
+
+        let results = restored.predict(&path_config);
+        assert_eq!(results.len(), 2);
+        assert!(results
+            .iter()
+            .any(|r| r.isol_eq(&TargetCircUsage::Preemptive {
+                port: Some(TargetPort::ipv4(22)),
+                circs: 2,
+                require_stability: true,
+            })));
+
+        // After another 40 seconds, the restored usage has expired.
+        let mut expired = PreemptiveCircuitPredictor::new(
+            PreemptiveCircuitConfig::builder()
+                .prediction_lifetime(Duration::from_secs(60))
+                .build()
+                .unwrap(),
+        );
+        expired.restore_usage_state(&state, later, wallclock + Duration::from_secs(60));
+        assert!(!expired.predict(&path_config).iter().any(|r| r.isol_eq(
+            &TargetCircUsage::Preemptive {
+                port: Some(TargetPort::ipv4(22)),
+                circs: 2,
+                require_stability: true,
+            }
+        )));
+    }
+
+    #[test]
+    fn profile_names() {
+        let mut cfg = PreemptiveCircuitConfig::builder();
+        cfg.profile(Some("messenger_2-b".into()));
+        assert!(cfg.build().is_ok());
+        for bad in ["", "a/b", "über", "two words"] {
+            let mut cfg = PreemptiveCircuitConfig::builder();
+            cfg.profile(Some(bad.into()));
+            assert!(cfg.build().is_err());
+        }
+    }
+
     #[test]
     fn does_not_predict_old_ports() {
         let path_config = PathConfig::default();
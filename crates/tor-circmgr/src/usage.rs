@@ -358,7 +358,10 @@ impl TargetCircUsage {
                 compatible_with_target,
                 kind,
             } => {
-                let path_builder = HsPathBuilder::new(compatible_with_target.clone(), *kind);
+                #[allow(unused_mut)]
+                let mut path_builder = HsPathBuilder::new(compatible_with_target.clone(), *kind);
+                #[cfg(feature = "hs-endpoint-restrictions")]
+                path_builder.restrict_final_hop(&config.hs_endpoints);
                 cfg_if::cfg_if! {
                     if #[cfg(all(feature = "vanguards", feature = "hs-common"))] {
                         let (path, mon, usable) = path_builder
@@ -52,6 +52,7 @@ mod misc;
 pub mod mistrust;
 mod mut_cfg;
 mod path;
+mod secret;
 pub mod sources;
 #[cfg(feature = "testing")]
 pub mod testing;
@@ -76,6 +77,7 @@ pub use load::{resolve, resolve_ignore_warnings, resolve_return_results};
 pub use misc::*;
 pub use mut_cfg::MutCfg;
 pub use path::{CfgPath, CfgPathError};
+pub use secret::{CfgSecret, CfgSecretError};
 pub use sources::{ConfigurationSource, ConfigurationSources};
 
 use itertools::Itertools;
@@ -0,0 +1,334 @@
+//! A secret exposed from the configuration crate
+//!
+//! Secrets such as passphrases should not be written into configuration files
+//! in plain text.  This type allows the user to say where to find a secret instead:
+//! in an environment variable, in the credential store of the operating system,
+//! or in the output of some other program.
+
+use std::fmt;
+use std::io;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use tor_error::{ErrorKind, HasKind};
+
+/// A secret, such as a passphrase, in a configuration file.
+///
+/// Usually, a `CfgSecret` is a string referring to a secret that is kept elsewhere.
+/// The supported references are:
+///   * `env:NAME`: the value of the environment variable `NAME`.
+///   * `keyring:SERVICE/ACCOUNT`: the password stored for `ACCOUNT` under `SERVICE`
+///     in the credential store of the operating system
+///     (the macOS Keychain, the Windows Credential Manager, or the freedesktop Secret Service).
+///     Only supported with the `keyring-secrets` feature.
+///   * `cmd:PROGRAM ARGS...`: the output of running `PROGRAM`,
+///     with the whitespace-separated `ARGS`, with any trailing newline removed.
+///     No shell is involved: there is no quoting, expansion, or redirection.
+///
+/// References are followed by [`resolve`](CfgSecret::resolve),
+/// which should be called when the configuration is loaded,
+/// so that a missing secret is reported straight away.
+///
+/// Alternatively, a `CfgSecret` can be written as a table `{ literal = "..." }`
+/// containing the secret itself.
+/// This is not recommended, since it leaves the secret in plain text in the configuration.
+#[derive(Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(transparent)]
+pub struct CfgSecret(SecretInner);
+
+/// Inner implementation of CfgSecret
+///
+/// `SecretInner` exists to avoid making the variants part of the public Rust API
+#[derive(Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(untagged)]
+enum SecretInner {
+    /// A secret written in the configuration.
+    Literal(LiteralSecret),
+    /// A reference to a secret, to be resolved.
+    Reference(String),
+}
+
+/// Inner implementation of SecretInner:Literal
+///
+/// `LiteralSecret` exists to arrange that `SecretInner::Literal`'s (de)serialization
+/// does not overlap with `SecretInner::Reference`'s.
+#[derive(Clone, Serialize, Deserialize, Eq, PartialEq)]
+struct LiteralSecret {
+    /// The secret.
+    literal: String,
+}
+
+/// An error that has occurred while resolving a [`CfgSecret`].
+///
+/// None of these errors contain the secret itself.
+#[derive(thiserror::Error, Debug, Clone)]
+#[non_exhaustive]
+pub enum CfgSecretError {
+    /// The reference wasn't one we recognize.
+    #[error("Unrecognized secret reference {0:?} (expected env:NAME, keyring:SERVICE/ACCOUNT, or cmd:PROGRAM)")]
+    BadReference(String),
+    /// The environment variable named by an `env:` reference was unset, or not UTF-8.
+    #[error("Environment variable {0} is not set (or is not valid UTF-8), so the secret env:{0} is unavailable")]
+    NoSuchVar(String),
+    /// We couldn't read a secret from the credential store.
+    #[error("Unable to read the secret {reference} from the OS credential store")]
+    Keyring {
+        /// The reference we were trying to resolve.
+        reference: String,
+        /// What went wrong.
+        #[source]
+        cause: Arc<dyn std::error::Error + Send + Sync + 'static>,
+    },
+    /// A `keyring:` reference was used, but support for it isn't compiled in.
+    #[error("Secret {0} refers to the OS credential store, which is not supported (tor-config/keyring-secrets feature disabled)")]
+    KeyringNotSupported(String),
+    /// We couldn't run the program named by a `cmd:` reference.
+    #[error("Unable to run {program:?} to obtain a secret")]
+    CommandFailed {
+        /// The program we tried to run.
+        program: String,
+        /// What went wrong.
+        #[source]
+        cause: Arc<io::Error>,
+    },
+    /// The program named by a `cmd:` reference reported failure.
+    #[error("{program:?}, run to obtain a secret, failed ({status})")]
+    CommandStatus {
+        /// The program we ran.
+        program: String,
+        /// How it exited.
+        status: ExitStatus,
+    },
+    /// The secret was not valid UTF-8.
+    #[error("The secret {0} is not valid UTF-8")]
+    BadUtf8(String),
+}
+
+impl HasKind for CfgSecretError {
+    fn kind(&self) -> ErrorKind {
+        use CfgSecretError as E;
+        use ErrorKind as EK;
+        match self {
+            E::BadReference(_) | E::NoSuchVar(_) | E::BadUtf8(_) => EK::InvalidConfig,
+            E::KeyringNotSupported(_) => EK::FeatureDisabled,
+            E::Keyring { .. } | E::CommandFailed { .. } | E::CommandStatus { .. } => {
+                EK::ExternalToolFailed
+            }
+        }
+    }
+}
+
+impl CfgSecret {
+    /// Create a new `CfgSecret` from a reference such as `env:NAME`
+    ///
+    /// The reference is only checked when the secret is [resolved](CfgSecret::resolve).
+    pub fn new(reference: String) -> Self {
+        CfgSecret(SecretInner::Reference(reference))
+    }
+
+    /// Create a new `CfgSecret` containing the secret `secret` itself
+    pub fn new_literal(secret: String) -> Self {
+        CfgSecret(SecretInner::Literal(LiteralSecret { literal: secret }))
+    }
+
+    /// If this `CfgSecret` is a reference to a secret, return the reference
+    ///
+    /// Returns `None` if the secret was given literally.
+    pub fn as_reference(&self) -> Option<&str> {
+        match &self.0 {
+            SecretInner::Reference(r) => Some(r),
+            SecretInner::Literal(_) => None,
+        }
+    }
+
+    /// Return the secret designated by this `CfgSecret`.
+    ///
+    /// This may read environment variables, access the credential store of the
+    /// operating system, or run a program, depending on the kind of reference.
+    pub fn resolve(&self) -> Result<Zeroizing<String>, CfgSecretError> {
+        let reference = match &self.0 {
+            SecretInner::Literal(LiteralSecret { literal }) => {
+                return Ok(Zeroizing::new(literal.clone()))
+            }
+            SecretInner::Reference(r) => r,
+        };
+        let bad = || CfgSecretError::BadReference(reference.clone());
+
+        let (scheme, rest) = reference.split_once(':').ok_or_else(bad)?;
+        match scheme {
+            "env" => std::env::var(rest)
+                .map(Zeroizing::new)
+                .map_err(|_| CfgSecretError::NoSuchVar(rest.into())),
+            "keyring" => {
+                let (service, account) = rest
+                    .split_once('/')
+                    .filter(|(s, a)| !s.is_empty() && !a.is_empty())
+                    .ok_or_else(bad)?;
+                from_keyring(reference, service, account)
+            }
+            "cmd" => {
+                let mut words = rest.split_whitespace();
+                let program = words.next().ok_or_else(bad)?;
+                from_command(reference, program, words)
+            }
+            _ => Err(bad()),
+        }
+    }
+}
+
+impl fmt::Debug for CfgSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            SecretInner::Reference(r) => f.debug_tuple("CfgSecret").field(r).finish(),
+            SecretInner::Literal(_) => f.write_str("CfgSecret(<literal>)"),
+        }
+    }
+}
+
+/// Helper: read the secret for `account` under `service` from the OS credential store.
+#[cfg(feature = "keyring-secrets")]
+fn from_keyring(
+    reference: &str,
+    service: &str,
+    account: &str,
+) -> Result<Zeroizing<String>, CfgSecretError> {
+    keyring::Entry::new(service, account)
+        .and_then(|entry| entry.get_password())
+        .map(Zeroizing::new)
+        .map_err(|e| CfgSecretError::Keyring {
+            reference: reference.into(),
+            cause: Arc::new(e),
+        })
+}
+
+/// Helper: report that we can't use the OS credential store.
+#[cfg(not(feature = "keyring-secrets"))]
+fn from_keyring(
+    reference: &str,
+    _service: &str,
+    _account: &str,
+) -> Result<Zeroizing<String>, CfgSecretError> {
+    Err(CfgSecretError::KeyringNotSupported(reference.into()))
+}
+
+/// Helper: run `program` with `args`, and return what it writes to stdout.
+fn from_command<'a>(
+    reference: &str,
+    program: &str,
+    args: impl Iterator<Item = &'a str>,
+) -> Result<Zeroizing<String>, CfgSecretError> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| CfgSecretError::CommandFailed {
+            program: program.into(),
+            cause: Arc::new(e),
+        })?;
+    let stdout = Zeroizing::new(output.stdout);
+    if !output.status.success() {
+        return Err(CfgSecretError::CommandStatus {
+            program: program.into(),
+            status: output.status,
+        });
+    }
+
+    let secret =
+        std::str::from_utf8(&stdout).map_err(|_| CfgSecretError::BadUtf8(reference.into()))?;
+    let secret = secret.strip_suffix('\n').unwrap_or(secret);
+    let secret = secret.strip_suffix('\r').unwrap_or(secret);
+    Ok(Zeroizing::new(secret.to_owned()))
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct TestConfigFile {
+        p: CfgSecret,
+    }
+
+    fn parse(s: &str) -> CfgSecret {
+        toml::from_str::<TestConfigFile>(s).unwrap().p
+    }
+
+    #[test]
+    fn literal() {
+        let p = parse(r#"p = { literal = "hunter2" }"#);
+        assert_eq!(p.as_reference(), None);
+        assert_eq!(&*p.resolve().unwrap(), "hunter2");
+        // The secret is not revealed by Debug.
+        assert!(!format!("{p:?}").contains("hunter2"));
+    }
+
+    #[test]
+    fn env() {
+        let p = parse(r#"p = "env:PATH""#);
+        assert_eq!(p.as_reference(), Some("env:PATH"));
+        assert_eq!(*p.resolve().unwrap(), std::env::var("PATH").unwrap());
+
+        let p = CfgSecret::new("env:ARTI_TEST_NO_SUCH_VARIABLE_PLEASE".into());
+        assert!(matches!(p.resolve(), Err(CfgSecretError::NoSuchVar(_))));
+    }
+
+    #[test]
+    fn bad_reference() {
+        for r in [
+            "hunter2",
+            "ftp:x",
+            "keyring:noaccount",
+            "keyring:/x",
+            "cmd:  ",
+        ] {
+            let err = CfgSecret::new(r.into()).resolve().unwrap_err();
+            assert!(matches!(err, CfgSecretError::BadReference(_)), "{r}");
+            assert_eq!(err.kind(), ErrorKind::InvalidConfig);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command() {
+        let p = CfgSecret::new("cmd:echo hello   world".into());
+        assert_eq!(&*p.resolve().unwrap(), "hello world");
+
+        let p = CfgSecret::new("cmd:false".into());
+        assert!(matches!(
+            p.resolve(),
+            Err(CfgSecretError::CommandStatus { .. })
+        ));
+
+        let p = CfgSecret::new("cmd:/nonexistent/arti-test-program".into());
+        let err = p.resolve().unwrap_err();
+        assert!(matches!(err, CfgSecretError::CommandFailed { .. }));
+        assert_eq!(err.kind(), ErrorKind::ExternalToolFailed);
+    }
+
+    #[cfg(not(feature = "keyring-secrets"))]
+    #[test]
+    fn keyring_disabled() {
+        let p = CfgSecret::new("keyring:org.torproject.arti/test".into());
+        assert!(matches!(
+            p.resolve(),
+            Err(CfgSecretError::KeyringNotSupported(_))
+        ));
+    }
+}
@@ -15,7 +15,7 @@ use crate::Result;
 use tor_checkable::timed::TimerangeBound;
 use tor_config::{define_list_builder_accessors, impl_standard_builder, ConfigBuildError};
 use tor_guardmgr::fallback::FallbackDirBuilder;
-use tor_netdoc::doc::netstatus::{self, Lifetime};
+use tor_netdoc::doc::netstatus::{self, Lifetime, UnvalidatedConsensus};
 
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
@@ -60,6 +60,15 @@ pub struct NetworkConfig {
     /// whose identities and public keys are shipped as part of the Arti source code.
     #[builder(sub_builder, setter(custom))]
     pub(crate) authorities: AuthorityList,
+
+    /// Rules for how far we trust the `authorities` when we validate a consensus.
+    ///
+    /// Only networks with non-default authorities can change these.
+    ///
+    /// This section cannot be changed in a running Arti client.
+    #[builder(sub_builder)]
+    #[builder_field_attr(serde(default))]
+    pub(crate) consensus_trust: ConsensusTrustConfig,
 }
 
 impl_standard_builder! { NetworkConfig }
@@ -76,6 +85,11 @@ impl NetworkConfig {
     pub fn fallback_caches(&self) -> &tor_guardmgr::fallback::FallbackList {
         &self.fallback_caches
     }
+
+    /// Return the rules for how far we trust the authorities in this configuration.
+    pub fn consensus_trust(&self) -> &ConsensusTrustConfig {
+        &self.consensus_trust
+    }
 }
 
 impl NetworkConfigBuilder {
@@ -89,8 +103,120 @@ impl NetworkConfigBuilder {
             });
         }
 
+        let trust = &self.consensus_trust;
+        let private = trust.private_network.unwrap_or(false);
+        let threshold = trust.signature_threshold;
+        let inconsistent = |field: &str, problem: &str| ConfigBuildError::Inconsistent {
+            fields: vec![
+                "authorities".to_owned(),
+                format!("consensus_trust.{}", field),
+            ],
+            problem: problem.to_owned(),
+        };
+
+        let Some(authorities) = self.opt_authorities() else {
+            // The public network's authorities are trusted in the usual way, and no other.
+            if private {
+                return Err(inconsistent(
+                    "private_network",
+                    "A private network must have its own authorities",
+                ));
+            }
+            if threshold.is_some() {
+                return Err(inconsistent(
+                    "signature_threshold",
+                    "The signature threshold can only be changed along with the authorities",
+                ));
+            }
+            return Ok(());
+        };
+
+        if let Some(threshold) = threshold {
+            if threshold == 0 {
+                return Err(ConfigBuildError::Invalid {
+                    field: "consensus_trust.signature_threshold".to_owned(),
+                    problem: "A consensus must be signed by at least one authority".to_owned(),
+                });
+            }
+            let n_authorities = authorities.len();
+            if usize::from(threshold) > n_authorities {
+                return Err(inconsistent(
+                    "signature_threshold",
+                    &format!(
+                        "Requires {} signatures, but there are only {} authorities",
+                        threshold, n_authorities
+                    ),
+                ));
+            }
+            // On anything that might be part of the public network,
+            // no minority of the authorities may speak for the rest.
+            if !private && usize::from(threshold) <= n_authorities / 2 {
+                return Err(inconsistent(
+                    "signature_threshold",
+                    "Only a private network may accept a consensus signed by half or fewer of its authorities",
+                ));
+            }
+        }
+
         Ok(())
     }
+
+    /// Return true if this builder describes a private network.
+    ///
+    /// (This is for checking other configuration that depends on the network.)
+    pub fn is_private_network(&self) -> bool {
+        self.consensus_trust.private_network.unwrap_or(false)
+    }
+}
+
+/// Rules for how far we trust a network's directory authorities.
+///
+/// These are meant for private (for example, enterprise-internal) Tor networks,
+/// which can only be used with their own `authorities`.
+///
+/// This type is immutable once constructed. To make one, use
+/// [`ConsensusTrustConfigBuilder`], or deserialize it from a string.
+#[derive(Debug, Clone, Builder, Eq, PartialEq)]
+#[builder(build_fn(error = "ConfigBuildError"))]
+#[builder(derive(Debug, Serialize, Deserialize))]
+pub struct ConsensusTrustConfig {
+    /// How many of the authorities must sign a consensus for us to accept it.
+    ///
+    /// The default is to require more than half of them.
+    /// This may not be more than the number of authorities,
+    /// and unless `private_network` is set, it may not be half of them or fewer.
+    #[builder(
+        setter(strip_option),
+        field(type = "Option<u16>", build = "self.signature_threshold")
+    )]
+    #[builder_field_attr(serde(default))]
+    pub(crate) signature_threshold: Option<u16>,
+
+    /// Whether this network is separate from the public Tor network.
+    ///
+    /// A private network relaxes the checks that protect users of the public network:
+    /// it may set any `signature_threshold`.
+    /// In exchange, it must never be mixed with the public network:
+    /// for example, it cannot use bridges that were bundled with Arti.
+    ///
+    /// Defaults to false.
+    #[builder(default)]
+    #[builder_field_attr(serde(default))]
+    pub(crate) private_network: bool,
+}
+
+impl_standard_builder! { ConsensusTrustConfig }
+
+impl ConsensusTrustConfig {
+    /// Return the number of authorities that must sign a consensus, if it was configured.
+    pub fn signature_threshold(&self) -> Option<u16> {
+        self.signature_threshold
+    }
+
+    /// Return true if this network is separate from the public Tor network.
+    pub fn private_network(&self) -> bool {
+        self.private_network
+    }
 }
 
 /// Configuration information for how exactly we download documents from the
@@ -284,6 +410,19 @@ impl DirMgrConfig {
         &self.network.fallback_caches
     }
 
+    /// Tell `consensus` how many authorities we believe in,
+    /// and how many of them must have signed it.
+    pub(crate) fn prepare_to_validate<RS>(
+        &self,
+        consensus: UnvalidatedConsensus<RS>,
+    ) -> UnvalidatedConsensus<RS> {
+        let consensus = consensus.set_n_authorities(self.authorities().len() as u16);
+        match self.network.consensus_trust.signature_threshold {
+            Some(threshold) => consensus.set_signature_threshold(threshold),
+            None => consensus,
+        }
+    }
+
     /// Construct a new configuration object where all replaceable fields in
     /// `self` are replaced with those from  `new_config`.
     ///
@@ -295,6 +434,7 @@ impl DirMgrConfig {
             network: NetworkConfig {
                 fallback_caches: new_config.network.fallback_caches.clone(),
                 authorities: self.network.authorities.clone(),
+                consensus_trust: self.network.consensus_trust.clone(),
             },
             schedule: new_config.schedule.clone(),
             tolerance: new_config.tolerance.clone(),
@@ -399,6 +539,62 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn build_consensus_trust() {
+        use tor_guardmgr::fallback::FallbackDir;
+
+        // The public network can't change its trust rules.
+        let mut bld = NetworkConfig::builder();
+        bld.consensus_trust().signature_threshold(1);
+        assert!(bld.build().is_err());
+        let mut bld = NetworkConfig::builder();
+        bld.consensus_trust().private_network(true);
+        assert!(bld.build().is_err());
+
+        let mut bld = NetworkConfig::builder();
+        bld.set_authorities(
+            ["a", "b", "c", "d"]
+                .into_iter()
+                .enumerate()
+                .map(|(i, name)| {
+                    Authority::builder()
+                        .name(name)
+                        .v3ident([i as u8; 20].into())
+                        .clone()
+                })
+                .collect(),
+        );
+        bld.set_fallback_caches(vec![{
+            let mut bld = FallbackDir::builder();
+            bld.rsa_identity([b'x'; 20].into())
+                .ed_identity([b'y'; 32].into());
+            bld.orports().push("127.0.0.1:99".parse().unwrap());
+            bld
+        }]);
+        let cfg = bld.build().unwrap();
+        assert_eq!(cfg.consensus_trust().signature_threshold(), None);
+        assert!(!cfg.consensus_trust().private_network());
+
+        // A majority is fine anywhere...
+        bld.consensus_trust().signature_threshold(3);
+        let cfg = bld.build().unwrap();
+        assert_eq!(cfg.consensus_trust().signature_threshold(), Some(3));
+        // ... but a minority is only fine on a private network.
+        bld.consensus_trust().signature_threshold(2);
+        assert!(bld.build().is_err());
+        bld.consensus_trust().private_network(true);
+        assert!(bld.is_private_network());
+        let cfg = bld.build().unwrap();
+        assert_eq!(cfg.consensus_trust().signature_threshold(), Some(2));
+        assert!(cfg.consensus_trust().private_network());
+
+        // We can never require nothing, or more than there is.
+        bld.consensus_trust().signature_threshold(0);
+        assert!(bld.build().is_err());
+        bld.consensus_trust().signature_threshold(5);
+        assert!(bld.build().is_err());
+    }
+
     #[test]
     fn build_schedule() -> Result<()> {
         use std::time::Duration;
@@ -69,6 +69,10 @@ pub enum Error {
         #[source]
         error: Arc<std::io::Error>,
     },
+    /// We couldn't write to the directory cache, because another process
+    /// is using it.
+    #[error("Directory cache is in use by another process")]
+    CacheLocked,
     /// An error given by the consensus diff crate.
     #[error("Problem applying consensus diff")]
     ConsensusDiffError(#[from] tor_consdiff::Error),
@@ -135,6 +139,24 @@ pub enum Error {
         cause: Arc<SpawnError>,
     },
 
+    /// A pinned consensus did not have the digest we were told to expect.
+    #[error("Pinned consensus has digest {actual}, not {expected}")]
+    PinnedConsensusMismatch {
+        /// The SHA3-256 digest that we expected, in hex.
+        expected: String,
+        /// The SHA3-256 digest of the consensus we got, in hex.
+        actual: String,
+    },
+    /// We couldn't read a pinned consensus from disk.
+    #[error("Unable to read pinned consensus from {}", fname.anonymize_home())]
+    PinnedConsensusFile {
+        /// The file that we were trying to read.
+        fname: std::path::PathBuf,
+        /// The underlying IO error.
+        #[source]
+        error: Arc<std::io::Error>,
+    },
+
     /// Other error from an external directory provider
     #[error("Error from external directory provider")]
     ExternalDirProvider {
@@ -241,12 +263,15 @@ impl Error {
             | Error::ManagerDropped
             | Error::CantAdvanceState
             | Error::LockFile { .. }
+            | Error::CacheLocked
             | Error::CacheFile { .. }
             | Error::BadUtf8InCache(_)
             | Error::BadHexInCache(_)
             | Error::OfflineMode
             | Error::Spawn { .. }
             | Error::NetDirOlder
+            | Error::PinnedConsensusMismatch { .. }
+            | Error::PinnedConsensusFile { .. }
             | Error::Bug(_) => false,
 
             // For this one, we delegate.
@@ -310,12 +335,15 @@ impl Error {
             | Error::UnrecognizedSchema { .. }
             | Error::ManagerDropped
             | Error::LockFile { .. }
+            | Error::CacheLocked
             | Error::CacheFile { .. }
             | Error::BadUtf8InCache(_)
             | Error::BadHexInCache(_)
             | Error::CachePermissions(_)
             | Error::CacheAccess(_)
             | Error::Spawn { .. }
+            | Error::PinnedConsensusMismatch { .. }
+            | Error::PinnedConsensusFile { .. }
             | Error::ExternalDirProvider { .. } => BootstrapAction::Fatal,
 
             // These should actually be impossible during the bootstrap process.
@@ -361,6 +389,7 @@ impl HasKind for Error {
             E::ManagerDropped => EK::ArtiShuttingDown,
             E::CantAdvanceState => EK::TorAccessFailed,
             E::LockFile { .. } => EK::CacheAccessFailed,
+            E::CacheLocked => EK::LocalResourceAlreadyInUse,
             E::CacheFile { .. } => EK::CacheAccessFailed,
             E::ConsensusDiffError(_) => EK::TorProtocolViolation,
             E::NetDocError { source, .. } => match source {
@@ -376,6 +405,8 @@ impl HasKind for Error {
             E::SignatureError(_) => EK::TorProtocolViolation,
             E::OfflineMode => EK::BadApiUsage,
             E::Spawn { cause, .. } => cause.kind(),
+            E::PinnedConsensusMismatch { .. } => EK::InvalidConfig,
+            E::PinnedConsensusFile { .. } => EK::InvalidConfig,
             E::ExternalDirProvider { kind, .. } => *kind,
             E::Bug(e) => e.kind(),
         }
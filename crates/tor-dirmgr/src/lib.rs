@@ -53,8 +53,11 @@ mod docid;
 mod docmeta;
 mod err;
 mod event;
+#[cfg(feature = "pinned-consensus")]
+mod pinned;
 mod retry;
 mod shared_ref;
+mod snapshot;
 mod state;
 mod storage;
 
@@ -98,12 +101,16 @@ use std::{fmt::Debug, time::SystemTime};
 use crate::state::{DirState, NetDirChange};
 pub use authority::{Authority, AuthorityBuilder};
 pub use config::{
-    DirMgrConfig, DirTolerance, DirToleranceBuilder, DownloadScheduleConfig,
-    DownloadScheduleConfigBuilder, NetworkConfig, NetworkConfigBuilder,
+    ConsensusTrustConfig, ConsensusTrustConfigBuilder, DirMgrConfig, DirTolerance,
+    DirToleranceBuilder, DownloadScheduleConfig, DownloadScheduleConfigBuilder, NetworkConfig,
+    NetworkConfigBuilder,
 };
 pub use docid::DocId;
 pub use err::Error;
 pub use event::{DirBlockage, DirBootstrapEvents, DirBootstrapStatus};
+#[cfg(feature = "pinned-consensus")]
+pub use pinned::{PinnedConsensus, PinnedDirProvider};
+pub use snapshot::CachedDocuments;
 pub use storage::DocumentText;
 pub use tor_guardmgr::fallback::{FallbackDir, FallbackDirBuilder};
 pub use tor_netdir::Timeliness;
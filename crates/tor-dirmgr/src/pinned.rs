@@ -0,0 +1,340 @@
+//! A directory provider that never changes its consensus.
+//!
+//! A [`PinnedDirProvider`] builds a single network directory from a consensus
+//! chosen by the user (identified by its digest),
+//! and then refuses to download or accept any other one.
+//! This lets researchers run path-selection and performance experiments
+//! against a fixed snapshot of the network, and get the same directory every time.
+//!
+//! The consensus is usually stale by the time it is used,
+//! so its validity (and that of its authority certificates) is checked
+//! at a time of the caller's choosing, rather than at the current time.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use futures::stream::{self, BoxStream, StreamExt as _};
+use tor_checkable::{ExternallySigned as _, SelfSigned as _, Timebound as _};
+use tor_netdir::params::NetParameters;
+use tor_netdir::{DirEvent, MdReceiver as _, NetDir, NetDirProvider, PartialNetDir, Timeliness};
+use tor_netdoc::doc::authcert::AuthCert;
+use tor_netdoc::doc::microdesc::MicrodescReader;
+use tor_netdoc::doc::netstatus::{Lifetime, MdConsensus};
+use tor_netdoc::AllowAnnotations;
+use tor_rtcompat::Runtime;
+
+#[cfg(feature = "geoip")]
+use tor_geoip::GeoipDb;
+
+use crate::bootstrap::AttemptId;
+use crate::docmeta::ConsensusMeta;
+use crate::event::DirProgress;
+use crate::{DirBootstrapStatus, DirMgrConfig, DirMgrStore, DirProvider, DocSource, Error, Result};
+
+/// A microdescriptor consensus, and the digest that the user expects it to have.
+///
+/// Used to construct a [`PinnedDirProvider`].
+#[derive(Clone, Debug)]
+pub struct PinnedConsensus {
+    /// The text of the consensus.
+    text: String,
+    /// The time at which we check that the consensus and its certificates are valid.
+    ///
+    /// If `None`, we use the consensus's `valid-after` time.
+    valid_at: Option<SystemTime>,
+}
+
+impl PinnedConsensus {
+    /// Check that `text` is a microdescriptor consensus
+    /// whose SHA3-256 digest (computed over the whole document) is `sha3_256`,
+    /// and return it as a `PinnedConsensus`.
+    ///
+    /// Fails with [`Error::PinnedConsensusMismatch`] if the digest is not the expected one.
+    pub fn new(text: String, sha3_256: [u8; 32]) -> Result<Self> {
+        let (signed, remainder, unvalidated) =
+            MdConsensus::parse(&text).map_err(|e| Error::from_netdoc(DocSource::LocalCache, e))?;
+        // We only want the digest here; the consensus is checked properly
+        // when we build a directory from it.
+        let unvalidated = unvalidated.dangerously_assume_timely();
+        let meta = ConsensusMeta::from_unvalidated(signed, remainder, &unvalidated);
+        if meta.sha3_256_of_whole() != &sha3_256 {
+            return Err(Error::PinnedConsensusMismatch {
+                expected: hex::encode(sha3_256),
+                actual: hex::encode(meta.sha3_256_of_whole()),
+            });
+        }
+
+        Ok(PinnedConsensus {
+            text,
+            valid_at: None,
+        })
+    }
+
+    /// Read a microdescriptor consensus from the file at `path`,
+    /// and check its digest as in [`PinnedConsensus::new`].
+    pub fn from_file(path: impl AsRef<Path>, sha3_256: [u8; 32]) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|error| Error::PinnedConsensusFile {
+            fname: path.to_owned(),
+            error: Arc::new(error),
+        })?;
+        Self::new(text, sha3_256)
+    }
+
+    /// Check the validity of the consensus, and of its authority certificates,
+    /// at `when` instead of at the consensus's `valid-after` time.
+    pub fn valid_at(mut self, when: SystemTime) -> Self {
+        self.valid_at = Some(when);
+        self
+    }
+}
+
+/// A [`DirProvider`] that only ever provides the directory built from a [`PinnedConsensus`].
+///
+/// Unlike a [`DirMgr`](crate::DirMgr), this provider never downloads anything:
+/// the authority certificates and microdescriptors it needs
+/// must already be in the directory cache
+/// (see [`DirMgrStore::import_documents`]).
+///
+/// Its directory is returned whatever [`Timeliness`] is asked for,
+/// and it reports itself as usable at every time,
+/// even though the consensus may have expired long ago.
+#[derive(Clone, Debug)]
+pub struct PinnedDirProvider {
+    /// The directory built from the pinned consensus.
+    netdir: Arc<NetDir>,
+    /// Our (unchanging) bootstrap status.
+    status: DirBootstrapStatus,
+}
+
+impl PinnedDirProvider {
+    /// Build the directory for `pinned`,
+    /// using the authority certificates and microdescriptors in `store`.
+    ///
+    /// The consensus must be signed by enough of the authorities in `config`.
+    /// Fails with [`Error::DirectoryNotPresent`] if `store` doesn't have enough
+    /// microdescriptors to build circuits with this consensus.
+    pub fn new<R: Runtime>(
+        pinned: &PinnedConsensus,
+        store: &DirMgrStore<R>,
+        config: &DirMgrConfig,
+    ) -> Result<Self> {
+        let (_, _, unvalidated) = MdConsensus::parse(&pinned.text)
+            .map_err(|e| Error::from_netdoc(DocSource::LocalCache, e))?;
+        let when = pinned
+            .valid_at
+            .unwrap_or_else(|| unvalidated.dangerously_peek().peek_lifetime().valid_after());
+        let unvalidated = config.prepare_to_validate(unvalidated.check_valid_at(&when)?);
+
+        let authority_ids: Vec<_> = config.authorities().iter().map(|a| &a.v3ident).collect();
+        if !unvalidated.authorities_are_correct(&authority_ids[..]) {
+            return Err(Error::UnrecognizedAuthorities);
+        }
+
+        let store = store.store.lock().expect("Directory storage lock poisoned");
+
+        let cert_ids: Vec<_> = unvalidated.signing_cert_ids().collect();
+        let mut certs = Vec::new();
+        for text in store.authcerts(&cert_ids)?.values() {
+            let cert = AuthCert::parse(text)
+                .map_err(|e| Error::from_netdoc(DocSource::LocalCache, e))?
+                .check_signature()?
+                .check_valid_at(&when)?;
+            certs.push(cert);
+        }
+        let consensus =
+            unvalidated
+                .check_signature(&certs[..])
+                .map_err(|cause| Error::ConsensusInvalid {
+                    source: DocSource::LocalCache,
+                    cause,
+                })?;
+        let lifetime = consensus.lifetime().clone();
+
+        let params = &config.override_net_params;
+        #[cfg(not(feature = "geoip"))]
+        let mut partial = PartialNetDir::new(consensus, Some(params));
+        #[cfg(feature = "geoip")]
+        let mut partial =
+            PartialNetDir::new_with_geoip(consensus, Some(params), &GeoipDb::new_embedded());
+
+        let digests: Vec<_> = partial.missing_microdescs().copied().collect();
+        let n_wanted = digests.len();
+        for text in store.microdescs(&digests)?.values() {
+            for md in MicrodescReader::new(text, &AllowAnnotations::AnnotationsNotAllowed) {
+                let md = md.map_err(|e| Error::from_netdoc(DocSource::LocalCache, e))?;
+                partial.add_microdesc(md.into_microdesc());
+            }
+        }
+        let n_missing = partial.n_missing();
+        let netdir = partial
+            .unwrap_if_sufficient()
+            .map_err(|_| Error::DirectoryNotPresent)?;
+
+        // Our directory is usable for as long as anybody could want.
+        let usable_lifetime = {
+            let end = UNIX_EPOCH + Duration::from_secs(u32::MAX.into());
+            Lifetime::new(UNIX_EPOCH, end - Duration::from_secs(1), end)
+                .expect("Lifetime with increasing times was invalid")
+        };
+        let n_wanted = u32::try_from(n_wanted).unwrap_or(u32::MAX);
+        let n_missing = u32::try_from(n_missing).unwrap_or(u32::MAX);
+        let mut status = DirBootstrapStatus::default();
+        status.update_progress(
+            AttemptId::next(),
+            DirProgress::Validated {
+                lifetime,
+                usable_lifetime,
+                n_mds: (n_wanted.saturating_sub(n_missing), n_wanted),
+                usable: true,
+            },
+        );
+
+        Ok(PinnedDirProvider {
+            netdir: Arc::new(netdir),
+            status,
+        })
+    }
+}
+
+impl NetDirProvider for PinnedDirProvider {
+    fn netdir(&self, _timeliness: Timeliness) -> tor_netdir::Result<Arc<NetDir>> {
+        Ok(Arc::clone(&self.netdir))
+    }
+
+    fn events(&self) -> BoxStream<'static, DirEvent> {
+        // Our directory never changes.
+        stream::pending().boxed()
+    }
+
+    fn params(&self) -> Arc<dyn AsRef<NetParameters>> {
+        Arc::clone(&self.netdir) as _
+    }
+}
+
+#[async_trait]
+impl DirProvider for PinnedDirProvider {
+    fn reconfigure(
+        &self,
+        _new_config: &DirMgrConfig,
+        _how: tor_config::Reconfigure,
+    ) -> std::result::Result<(), tor_config::ReconfigureError> {
+        // The directory is fixed when we're created: there's nothing to change.
+        Ok(())
+    }
+
+    async fn bootstrap(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn bootstrap_events(&self) -> BoxStream<'static, DirBootstrapStatus> {
+        stream::once(futures::future::ready(self.status.clone()))
+            .chain(stream::pending())
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use crate::{Authority, CachedDocuments};
+    use tempfile::TempDir;
+    use time::macros::datetime;
+    use tor_llcrypto::d::Sha3_256;
+    use tor_llcrypto::pk::rsa::RsaIdentity;
+
+    use digest::Digest as _;
+
+    const CONSENSUS: &str = include_str!("../testdata/mdconsensus1.txt");
+    const AUTHCERT_5696: &str = include_str!("../testdata/cert-5696.txt");
+    const AUTHCERT_5A23: &str = include_str!("../testdata/cert-5A23.txt");
+
+    /// A time at which `CONSENSUS` and its certificates are valid.
+    fn test_time() -> SystemTime {
+        datetime!(2020-08-07 12:42:45 UTC).into()
+    }
+
+    /// Return a configuration whose authorities signed `CONSENSUS`.
+    fn test_config(dir: &TempDir) -> DirMgrConfig {
+        let a = |s| {
+            Authority::builder()
+                .name("ignore")
+                .v3ident(RsaIdentity::from_hex(s).unwrap())
+                .clone()
+        };
+        let mut netcfg = crate::NetworkConfig::builder();
+        netcfg.set_fallback_caches(vec![]);
+        netcfg.set_authorities(vec![
+            a("5696AB38CB3852AFA476A5C07B2D4788963D5567"),
+            a("5A23BA701776C9C1AB1C06E734E92AB3D5350D64"),
+        ]);
+        DirMgrConfig {
+            cache_dir: dir.path().into(),
+            network: netcfg.build().unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn digest_mismatch() {
+        let digest: [u8; 32] = Sha3_256::digest(CONSENSUS.as_bytes()).into();
+        assert!(PinnedConsensus::new(CONSENSUS.to_owned(), digest).is_ok());
+
+        let err = PinnedConsensus::new(CONSENSUS.to_owned(), [0; 32]).unwrap_err();
+        assert!(matches!(err, Error::PinnedConsensusMismatch { .. }));
+
+        let err = PinnedConsensus::from_file("/this/file/does/not/exist", digest).unwrap_err();
+        assert!(matches!(err, Error::PinnedConsensusFile { .. }));
+    }
+
+    #[test]
+    fn validity_and_completeness() {
+        tor_rtcompat::test_with_one_runtime!(|rt| async {
+            let dir = TempDir::new().unwrap();
+            let config = test_config(&dir);
+            let store = DirMgrStore::new(&config, rt, false).unwrap();
+            let digest: [u8; 32] = Sha3_256::digest(CONSENSUS.as_bytes()).into();
+            let pinned = PinnedConsensus::new(CONSENSUS.to_owned(), digest).unwrap();
+
+            // Without the certificates, we can't check the signatures.
+            let err = PinnedDirProvider::new(&pinned, &store, &config).unwrap_err();
+            assert!(matches!(err, Error::ConsensusInvalid { .. }));
+
+            store
+                .import_documents(&CachedDocuments {
+                    authcerts: vec![AUTHCERT_5696.to_owned(), AUTHCERT_5A23.to_owned()],
+                    ..Default::default()
+                })
+                .unwrap();
+
+            // The consensus is checked at its valid-after time by default.
+            // The signatures are fine, but we have none of the microdescriptors.
+            let err = PinnedDirProvider::new(&pinned, &store, &config).unwrap_err();
+            assert!(matches!(err, Error::DirectoryNotPresent));
+            let err =
+                PinnedDirProvider::new(&pinned.clone().valid_at(test_time()), &store, &config)
+                    .unwrap_err();
+            assert!(matches!(err, Error::DirectoryNotPresent));
+
+            // The consensus is not valid at all a year later.
+            let pinned = pinned.valid_at(test_time() + Duration::from_secs(86400 * 365));
+            let err = PinnedDirProvider::new(&pinned, &store, &config).unwrap_err();
+            assert!(matches!(err, Error::UntimelyObject(_)));
+        });
+    }
+}
@@ -0,0 +1,247 @@
+//! Exporting and importing the contents of a directory cache.
+//!
+//! This lets an application carry the documents it needs to bootstrap
+//! from one installation (or one launch) to the next,
+//! even when the cache directory itself does not survive.
+
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use tor_checkable::{ExternallySigned as _, SelfSigned as _, Timebound as _};
+use tor_netdoc::doc::authcert::AuthCert;
+use tor_netdoc::doc::microdesc::MicrodescReader;
+use tor_netdoc::doc::netstatus::{ConsensusFlavor, MdConsensus};
+use tor_netdoc::AllowAnnotations;
+use tor_rtcompat::Runtime;
+
+use crate::docmeta::{AuthCertMeta, ConsensusMeta};
+use crate::{DirMgrStore, DocSource, Error, Result};
+
+/// The directory documents needed to build a network directory,
+/// as exported from a directory cache with [`DirMgrStore::export_documents`].
+///
+/// All documents are kept in their original text form,
+/// so that they can be checked again when they are imported.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct CachedDocuments {
+    /// The most recent usable microdescriptor consensus, if we had one.
+    pub consensus: Option<String>,
+    /// The authority certificates that signed `consensus`.
+    pub authcerts: Vec<String>,
+    /// The microdescriptors listed in `consensus` that we had in the cache.
+    pub microdescs: Vec<String>,
+}
+
+impl<R: Runtime> DirMgrStore<R> {
+    /// Return a copy of the documents in this cache
+    /// that would be needed to build our latest usable directory.
+    ///
+    /// If we have no usable consensus, the result is empty.
+    pub fn export_documents(&self) -> Result<CachedDocuments> {
+        let store = self.store.lock().expect("Directory storage lock poisoned");
+        let Some(consensus) = store.latest_consensus(ConsensusFlavor::Microdesc, Some(false))?
+        else {
+            return Ok(CachedDocuments::default());
+        };
+        let consensus = consensus.as_str()?.to_owned();
+
+        // We only parse the consensus here to find out which other
+        // documents it needs.  It was validated when we first stored it as
+        // usable, and it will be validated again when it's imported, so it's
+        // okay not to check it here.
+        let (_, _, unvalidated) = MdConsensus::parse(&consensus)
+            .map_err(|e| Error::from_netdoc(DocSource::LocalCache, e))?;
+        let unvalidated = unvalidated.dangerously_assume_timely();
+        let cert_ids: Vec<_> = unvalidated.signing_cert_ids().collect();
+        let md_digests: Vec<_> = unvalidated
+            .dangerously_assume_wellsigned()
+            .relays()
+            .iter()
+            .map(|rs| *rs.md_digest())
+            .collect();
+
+        let authcerts = store.authcerts(&cert_ids)?.into_values().collect();
+        let microdescs = store.microdescs(&md_digests)?.into_values().collect();
+
+        Ok(CachedDocuments {
+            consensus: Some(consensus),
+            authcerts,
+            microdescs,
+        })
+    }
+
+    /// Add the documents in `docs` to this cache.
+    ///
+    /// The consensus is stored as "pending", so that it will be fully validated
+    /// (and, if it is no longer acceptable, replaced)
+    /// the next time a directory manager bootstraps from this cache.
+    /// Certificates with bad signatures and unparseable documents are rejected.
+    ///
+    /// Fails with [`Error::CacheLocked`] if some other process is using the cache.
+    pub fn import_documents(&self, docs: &CachedDocuments) -> Result<()> {
+        let mut store = self.store.lock().expect("Directory storage lock poisoned");
+        if !store.upgrade_to_readwrite()? {
+            return Err(Error::CacheLocked);
+        }
+
+        let mut listed_at = SystemTime::now();
+        if let Some(consensus) = &docs.consensus {
+            let (signed, remainder, unvalidated) = MdConsensus::parse(consensus)
+                .map_err(|e| Error::from_netdoc(DocSource::LocalCache, e))?;
+            // Timeliness is checked when we bootstrap, not here:
+            // we only need the consensus's lifetime to build its metadata.
+            let unvalidated = unvalidated.dangerously_assume_timely();
+            let meta = ConsensusMeta::from_unvalidated(signed, remainder, &unvalidated);
+            listed_at = meta.lifetime().valid_after();
+            store.store_consensus(&meta, ConsensusFlavor::Microdesc, true, consensus)?;
+        }
+
+        let mut certs = Vec::new();
+        for text in &docs.authcerts {
+            for parsed in AuthCert::parse_multiple(text) {
+                let parsed = parsed.map_err(|e| Error::from_netdoc(DocSource::LocalCache, e))?;
+                let cert_text = parsed
+                    .within(text)
+                    .expect("Certificate was not in input as expected");
+                // As with the consensus, expired certificates are
+                // discarded later on, when we expire the cache.
+                let cert = parsed.check_signature()?.dangerously_assume_timely();
+                certs.push((AuthCertMeta::from_authcert(&cert), cert_text));
+            }
+        }
+        store.store_authcerts(&certs)?;
+
+        let mut mds = Vec::new();
+        for text in &docs.microdescs {
+            for anno in MicrodescReader::new(text, &AllowAnnotations::AnnotationsNotAllowed) {
+                let anno = anno.map_err(|e| Error::from_netdoc(DocSource::LocalCache, e))?;
+                let md_text = anno
+                    .within(text)
+                    .expect("microdesc not from within text as expected");
+                mds.push((md_text, *anno.into_microdesc().digest()));
+            }
+        }
+        let mds: Vec<_> = mds.iter().map(|(text, digest)| (*text, digest)).collect();
+        store.store_microdescs(&mds, listed_at)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+
+    use super::*;
+    use crate::DirMgrConfig;
+    use tempfile::TempDir;
+
+    const CONSENSUS: &str = include_str!("../testdata/mdconsensus2.txt");
+    const AUTHCERTS: [&str; 3] = [
+        include_str!("../testdata/cert-5696.txt"),
+        include_str!("../testdata/cert-5A23.txt"),
+        include_str!("../testdata/cert-7C47.txt"),
+    ];
+    const MICRODESCS: &str = include_str!("../testdata/microdescs.txt");
+
+    fn new_store<R: Runtime>(runtime: R) -> (TempDir, DirMgrStore<R>) {
+        let dir = TempDir::new().unwrap();
+        let config = DirMgrConfig {
+            cache_dir: dir.path().into(),
+            ..Default::default()
+        };
+        let store = DirMgrStore::new(&config, runtime, false).unwrap();
+        (dir, store)
+    }
+
+    #[test]
+    fn export_import() {
+        tor_rtcompat::test_with_one_runtime!(|rt| async {
+            let (_dir1, store1) = new_store(rt.clone());
+            let (_dir2, store2) = new_store(rt);
+
+            // Nothing to export from an empty cache.
+            assert_eq!(
+                store1.export_documents().unwrap(),
+                CachedDocuments::default()
+            );
+
+            let docs = CachedDocuments {
+                consensus: Some(CONSENSUS.to_owned()),
+                authcerts: AUTHCERTS.iter().map(|s| s.to_string()).collect(),
+                microdescs: vec![MICRODESCS.to_owned()],
+            };
+            store1.import_documents(&docs).unwrap();
+
+            // An imported consensus is pending until we bootstrap with it,
+            // so there's still nothing to export.
+            assert_eq!(
+                store1.export_documents().unwrap(),
+                CachedDocuments::default()
+            );
+            {
+                let mut store = store1.store.lock().unwrap();
+                let text = store
+                    .latest_consensus(ConsensusFlavor::Microdesc, Some(true))
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(text.as_str().unwrap(), CONSENSUS);
+                let (signed, remainder, parsed) = MdConsensus::parse(CONSENSUS).unwrap();
+                let meta = ConsensusMeta::from_unvalidated(
+                    signed,
+                    remainder,
+                    &parsed.dangerously_assume_timely(),
+                );
+                store.mark_consensus_usable(&meta).unwrap();
+            }
+
+            let exported = store1.export_documents().unwrap();
+            assert_eq!(exported.consensus.as_deref(), Some(CONSENSUS));
+            // (None of our test certificates signed this consensus.)
+            assert!(exported.authcerts.is_empty());
+            assert_eq!(exported.microdescs.len(), 4);
+
+            store2.import_documents(&exported).unwrap();
+            let store = store2.store.lock().unwrap();
+            let text = store
+                .latest_consensus(ConsensusFlavor::Microdesc, Some(true))
+                .unwrap()
+                .unwrap();
+            assert_eq!(text.as_str().unwrap(), CONSENSUS);
+            let digests: Vec<_> =
+                MicrodescReader::new(MICRODESCS, &AllowAnnotations::AnnotationsNotAllowed)
+                    .map(|anno| *anno.unwrap().into_microdesc().digest())
+                    .collect();
+            assert_eq!(store.microdescs(&digests).unwrap().len(), 4);
+        });
+    }
+
+    #[test]
+    fn import_bad_documents() {
+        tor_rtcompat::test_with_one_runtime!(|rt| async {
+            let (_dir, store) = new_store(rt);
+
+            let docs = CachedDocuments {
+                consensus: Some("this is not a consensus".to_owned()),
+                ..Default::default()
+            };
+            assert!(matches!(
+                store.import_documents(&docs),
+                Err(Error::NetDocError { .. })
+            ));
+        });
+    }
+}
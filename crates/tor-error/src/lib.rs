@@ -55,6 +55,9 @@ pub use retriable::*;
 mod misc;
 pub use misc::*;
 
+mod remediation;
+pub use remediation::*;
+
 #[cfg(feature = "tracing")]
 pub mod tracing;
 
@@ -614,6 +617,19 @@ pub enum ErrorKind {
     #[display("target address disabled locally")]
     ForbiddenStreamTarget,
 
+    /// We were asked to make an anonymous connection to an address that isn't
+    /// an onion service, while configured to only connect to onion services.
+    ///
+    /// This kind of error happens when a client with an "onion-only" address
+    /// filter is asked to connect to (or resolve) a hostname or an IP address:
+    /// such a request would have to go through an exit relay.
+    ///
+    /// This is kept separate from [`ErrorKind::ForbiddenStreamTarget`] so that
+    /// applications can tell a possible leak that was stopped
+    /// from other locally disabled addresses.
+    #[display("non-onion target address disabled by onion-only mode")]
+    ForbiddenClearnetTarget,
+
     /// An operation failed in a transient way.
     ///
     /// This kind of error indicates that some kind of operation failed in a way
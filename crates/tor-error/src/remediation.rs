@@ -0,0 +1,181 @@
+//! Declare the `Remediation` enumeration, and its relationship to `ErrorKind`.
+
+use derive_more::Display;
+
+use crate::ErrorKind;
+
+/// Advice about what a user might do in response to an error.
+///
+/// This is a coarse, machine-readable hint, meant to let a front-end show
+/// actionable guidance ("check your network connection") rather than
+/// an [`ErrorKind`] the user may not understand.
+/// Use [`ErrorKind::remediation`] to find the remediation for a kind of error.
+///
+/// The [`Display`](std::fmt::Display) implementation gives a short
+/// human-readable suggestion; [`as_str`](Remediation::as_str) gives a stable
+/// identifier, suitable for use in protocols.
+///
+/// This is advisory only: it says what is _likely_ to help,
+/// and there is no guarantee that following the advice will fix the problem.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Display,
+    strum::EnumString,
+    strum::IntoStaticStr,
+    strum::EnumIter,
+)]
+#[non_exhaustive]
+pub enum Remediation {
+    /// The problem is probably temporary: wait a while, and try again.
+    #[display("wait a while and try again")]
+    #[strum(serialize = "retry-later")]
+    RetryLater,
+
+    /// The problem may be caused by an incorrect system clock.
+    #[display("check that the system clock is correct")]
+    #[strum(serialize = "check-clock")]
+    CheckClock,
+
+    /// The problem may be caused by the local network connection.
+    #[display("check the network connection")]
+    #[strum(serialize = "check-network")]
+    CheckNetwork,
+
+    /// The problem is probably caused by Arti's configuration,
+    /// or by the environment (filesystem, permissions) it was configured to use.
+    #[display("check the configuration")]
+    #[strum(serialize = "fix-config")]
+    FixConfig,
+
+    /// The problem is probably a bug in Arti, or in the program using it.
+    #[display("please report this as a bug")]
+    #[strum(serialize = "report-bug")]
+    ReportBug,
+}
+
+impl Remediation {
+    /// Return a stable identifier for this remediation, like `retry-later`.
+    ///
+    /// This can be turned back into a `Remediation` with [`str::parse`].
+    pub fn as_str(self) -> &'static str {
+        self.into()
+    }
+}
+
+impl ErrorKind {
+    /// Return the action most likely to help with an error of this kind, if any.
+    ///
+    /// Returns `None` if there is nothing in particular we can suggest:
+    /// for example, when a remote host refused our connection.
+    pub fn remediation(self) -> Option<Remediation> {
+        use ErrorKind as EK;
+        use Remediation as R;
+        Some(match self {
+            EK::TorAccessFailed | EK::LocalNetworkError => R::CheckNetwork,
+
+            EK::ClockSkew | EK::DirectoryExpired => R::CheckClock,
+
+            EK::PersistentStateAccessFailed
+            | EK::LocalResourceAlreadyInUse
+            | EK::FsPermissions
+            | EK::CacheAccessFailed
+            | EK::KeystoreAccessFailed
+            | EK::InvalidConfig
+            | EK::InvalidConfigTransition
+            | EK::NoHomeDirectory
+            | EK::ExternalToolFailed
+            | EK::OnionServiceMissingClientAuth
+            | EK::OnionServiceWrongClientAuth
+            | EK::ForbiddenStreamTarget => R::FixConfig,
+
+            EK::Internal | EK::BadApiUsage | EK::LocalProtocolViolation => R::ReportBug,
+
+            EK::BootstrapRequired
+            | EK::RemoteNetworkTimeout
+            | EK::TorProtocolViolation
+            | EK::LocalResourceExhausted
+            | EK::CircuitCollapse
+            | EK::TorNetworkTimeout
+            | EK::TorDirectoryError
+            | EK::ExitTimeout
+            | EK::RemoteNetworkFailed
+            | EK::OnionServiceNotFound
+            | EK::OnionServiceNotRunning
+            | EK::OnionServiceConnectionFailed
+            | EK::RemoteHostResolutionFailed
+            | EK::RelayTooBusy
+            | EK::TransientFailure
+            | EK::CircuitRefused
+            | EK::NoPath
+            | EK::NoExit
+            | EK::TorDirectoryUnusable => R::RetryLater,
+
+            EK::PersistentStateCorrupted
+            | EK::CacheCorrupted
+            | EK::KeystoreCorrupted
+            | EK::ReactorShuttingDown
+            | EK::ArtiShuttingDown
+            | EK::NotImplemented
+            | EK::FeatureDisabled
+            | EK::RelayIdMismatch
+            | EK::RemoteStreamClosed
+            | EK::RemoteStreamReset
+            | EK::RemoteStreamError
+            | EK::RemoteConnectionRefused
+            | EK::ExitPolicyRejected
+            | EK::RemoteHostNotFound
+            | EK::OnionServiceProtocolViolation
+            | EK::OnionServiceAddressInvalid
+            | EK::RemoteProtocolViolation
+            | EK::InvalidStreamTarget
+            | EK::ForbiddenClearnetTarget
+            | EK::Other => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use strum::IntoEnumIterator as _;
+
+    #[test]
+    fn roundtrip() {
+        for r in Remediation::iter() {
+            assert_eq!(r.as_str().parse::<Remediation>().unwrap(), r);
+        }
+        assert_eq!(Remediation::CheckClock.as_str(), "check-clock");
+        assert!("reboot".parse::<Remediation>().is_err());
+    }
+
+    #[test]
+    fn kinds() {
+        assert_eq!(
+            ErrorKind::ClockSkew.remediation(),
+            Some(Remediation::CheckClock)
+        );
+        assert_eq!(
+            ErrorKind::Internal.remediation(),
+            Some(Remediation::ReportBug)
+        );
+        assert_eq!(ErrorKind::RemoteHostNotFound.remediation(), None);
+    }
+}
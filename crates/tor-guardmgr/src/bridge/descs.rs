@@ -233,6 +233,7 @@ impl Universe for BridgeSet {
                 full_dir_info: bridge_relay.has_descriptor(),
                 owned_target: OwnedChanTarget::from_chan_target(&bridge_relay),
                 sensitivity: crate::guard::DisplayRule::Redacted,
+                diversity: Default::default(),
             }),
             CandidateStatus::Absent => CandidateStatus::Absent,
             CandidateStatus::Uncertain => CandidateStatus::Uncertain,
@@ -293,6 +294,7 @@ impl Universe for BridgeSet {
                         full_dir_info: relay.has_descriptor(),
                         owned_target: OwnedChanTarget::from_chan_target(&relay),
                         sensitivity: crate::guard::DisplayRule::Redacted,
+                        diversity: Default::default(),
                     },
                     RelayWeight::from(0),
                 )
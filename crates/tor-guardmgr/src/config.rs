@@ -4,6 +4,7 @@ use tor_basic_utils::define_accessor_trait;
 
 use crate::bridge::BridgeConfig;
 use crate::fallback::FallbackList;
+use crate::GuardDiversityConfig;
 
 define_accessor_trait! {
     /// Configuration for a guard manager
@@ -15,6 +16,7 @@ define_accessor_trait! {
     pub trait GuardMgrConfig {
         fallbacks: FallbackList,
         bridges: [BridgeConfig],
+        guard_diversity: GuardDiversityConfig,
         +
         /// Should the bridges be used?
         ///
@@ -58,6 +60,8 @@ pub(crate) mod testing {
         #[as_ref]
         pub fallbacks: FallbackList,
         pub bridges: Vec<BridgeConfig>,
+        #[as_ref]
+        pub guard_diversity: GuardDiversityConfig,
     }
     impl AsRef<[BridgeConfig]> for TestConfig {
         fn as_ref(&self) -> &[BridgeConfig] {
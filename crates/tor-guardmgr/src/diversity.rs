@@ -0,0 +1,431 @@
+//! Optional constraints on how diverse our guards must be.
+//!
+//! By default, we pick guards purely by bandwidth weight, as the guard
+//! specification says.  A [`GuardDiversityConfig`] can additionally limit how
+//! many of our guards may belong to the same relay family, the same subnet,
+//! or (with the `geoip` feature) the same country.
+//!
+//! The limits are applied in two places:
+//!
+//!  * When we extend our guard sample, we skip any candidate that would
+//!    exceed a limit within the sample.
+//!  * When we select our primary guards, we skip any guard that would
+//!    exceed a limit among the primary guards chosen so far,
+//!    and pick the next one in preference order instead.
+//!    (This matters for guards that were sampled before the limits were
+//!    configured, or that were sampled when we had less information about
+//!    them.)
+//!
+//! These limits are a preference, not a hard requirement: if we cannot find
+//! enough primary guards that obey them, we fill in the remaining slots with
+//! guards that don't, rather than running with fewer primary guards.
+//!
+//! We do not currently support limits by autonomous system, since the
+//! GeoIP data that we ship does not contain AS numbers.
+
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+
+use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
+use tor_config::{impl_standard_builder, ConfigBuildError};
+use tor_llcrypto::pk::rsa::RsaIdentity;
+use tor_netdir::{NetDir, Relay, SubnetConfig};
+
+#[cfg(feature = "geoip")]
+use tor_geoip::{CountryCode, HasCountryCode as _};
+
+/// Configuration for how diverse our guards must be.
+///
+/// Each option limits how many of our guards may share some property.
+/// All of the limits are disabled by default.
+///
+/// These limits only apply to guards taken from the network directory:
+/// they are ignored when we are using bridges.
+#[derive(Debug, Clone, Eq, PartialEq, Builder)]
+#[builder(build_fn(validate = "Self::validate", error = "ConfigBuildError"))]
+#[builder(derive(Debug, Serialize, Deserialize))]
+pub struct GuardDiversityConfig {
+    /// The largest number of guards that may be in the same relay family.
+    #[builder(default, setter(strip_option))]
+    #[builder_field_attr(serde(default))]
+    max_per_family: Option<NonZeroUsize>,
+
+    /// The largest number of guards that may be in the same subnet.
+    ///
+    /// Two guards are in the same subnet if they have IPv4 addresses in the
+    /// same /16, or IPv6 addresses in the same /32.
+    #[builder(default, setter(strip_option))]
+    #[builder_field_attr(serde(default))]
+    max_per_subnet: Option<NonZeroUsize>,
+
+    /// The largest number of guards that may be in the same country.
+    ///
+    /// Setting this option requires the `geoip` feature.
+    /// Guards whose country we do not know are not limited.
+    #[builder(default, setter(strip_option))]
+    #[builder_field_attr(serde(default))]
+    max_per_country: Option<NonZeroUsize>,
+}
+impl_standard_builder! { GuardDiversityConfig }
+
+impl GuardDiversityConfigBuilder {
+    /// Check that this builder will give a reasonable configuration.
+    fn validate(&self) -> Result<(), ConfigBuildError> {
+        if cfg!(not(feature = "geoip")) && matches!(self.max_per_country, Some(Some(_))) {
+            return Err(ConfigBuildError::NoCompileTimeSupport {
+                field: "max_per_country".into(),
+                problem: "max_per_country set, but geoip feature not enabled".into(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl GuardDiversityConfig {
+    /// Return the largest number of guards that may be in the same relay family.
+    pub fn max_per_family(&self) -> Option<NonZeroUsize> {
+        self.max_per_family
+    }
+
+    /// Return the largest number of guards that may be in the same subnet.
+    pub fn max_per_subnet(&self) -> Option<NonZeroUsize> {
+        self.max_per_subnet
+    }
+
+    /// Return the largest number of guards that may be in the same country.
+    pub fn max_per_country(&self) -> Option<NonZeroUsize> {
+        self.max_per_country
+    }
+
+    /// Return true if any of the limits in this configuration are enabled.
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.max_per_family.is_some()
+            || self.max_per_subnet.is_some()
+            || self.max_per_country.is_some()
+    }
+
+    /// Return true if adding `new` to a set of guards that already contains
+    /// `existing` would obey every limit in this configuration.
+    pub(crate) fn permits<'a>(
+        &self,
+        new: &DiversityView<'_>,
+        existing: impl IntoIterator<Item = DiversityView<'a>>,
+    ) -> bool {
+        if !self.is_enabled() {
+            return true;
+        }
+
+        let (mut n_family, mut n_subnet, mut n_country) = (0, 0, 0);
+        for other in existing {
+            if other.is_same_relay(new) {
+                continue;
+            }
+            n_family += usize::from(new.in_same_family(&other));
+            n_subnet += usize::from(new.in_same_subnet(&other));
+            n_country += usize::from(new.in_same_country(&other));
+        }
+
+        /// Return true if `n` other guards are too many for `limit`.
+        fn at_limit(limit: Option<NonZeroUsize>, n: usize) -> bool {
+            limit.is_some_and(|limit| n >= limit.get())
+        }
+
+        !(at_limit(self.max_per_family, n_family)
+            || at_limit(self.max_per_subnet, n_subnet)
+            || at_limit(self.max_per_country, n_country))
+    }
+
+    /// Return true if every member of `guards` obeys every limit in this
+    /// configuration with respect to the others.
+    pub(crate) fn all_permitted(&self, guards: &[DiversityView<'_>]) -> bool {
+        guards
+            .iter()
+            .all(|g| self.permits(g, guards.iter().cloned()))
+    }
+}
+
+/// Information about a relay that we need to check diversity constraints,
+/// beyond what is in its [`ChanTarget`](tor_linkspec::ChanTarget).
+///
+/// This is derived from the network directory, and never persisted.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DiversityInfo {
+    /// The RSA identities of the relays that we know to be in this relay's
+    /// family (not including itself).
+    family: Vec<RsaIdentity>,
+    /// The country that this relay is in, if we know it.
+    #[cfg(feature = "geoip")]
+    country: Option<CountryCode>,
+}
+
+impl DiversityInfo {
+    /// Return the `DiversityInfo` for `relay`, as listed in `netdir`.
+    pub(crate) fn from_relay(netdir: &NetDir, relay: &Relay<'_>) -> Self {
+        DiversityInfo {
+            family: netdir
+                .known_family_members(relay)
+                .map(|member| *member.rsa_id())
+                .collect(),
+            #[cfg(feature = "geoip")]
+            country: relay.country_code(),
+        }
+    }
+}
+
+/// A view of a guard (or a guard candidate) with everything needed to check
+/// it against a [`GuardDiversityConfig`].
+#[derive(Clone, Debug)]
+pub(crate) struct DiversityView<'a> {
+    /// The RSA identity of the guard, if it has one.
+    pub(crate) rsa_id: Option<&'a RsaIdentity>,
+    /// The addresses of the guard.
+    pub(crate) addrs: &'a [SocketAddr],
+    /// The other information that we know about the guard.
+    pub(crate) info: &'a DiversityInfo,
+}
+
+impl<'a> DiversityView<'a> {
+    /// Return true if `self` and `other` have the same RSA identity.
+    fn is_same_relay(&self, other: &DiversityView<'_>) -> bool {
+        matches!((self.rsa_id, other.rsa_id), (Some(a), Some(b)) if a == b)
+    }
+
+    /// Return true if `self` and `other` are in the same family.
+    ///
+    /// We only need one of the two relays to list the other, since
+    /// [`NetDir::known_family_members`] has already checked that the family
+    /// membership is mutual.
+    fn in_same_family(&self, other: &DiversityView<'_>) -> bool {
+        let lists = |a: &DiversityView<'_>, b: &DiversityView<'_>| {
+            b.rsa_id.is_some_and(|id| a.info.family.contains(id))
+        };
+        lists(self, other) || lists(other, self)
+    }
+
+    /// Return true if `self` and `other` have any addresses in the same subnet.
+    fn in_same_subnet(&self, other: &DiversityView<'_>) -> bool {
+        let subnets = SubnetConfig::default();
+        self.addrs.iter().any(|a| {
+            other
+                .addrs
+                .iter()
+                .any(|b| subnets.addrs_in_same_subnet(&a.ip(), &b.ip()))
+        })
+    }
+
+    /// Return true if `self` and `other` are known to be in the same country.
+    #[allow(clippy::unused_self)]
+    fn in_same_country(&self, other: &DiversityView<'_>) -> bool {
+        #[cfg(feature = "geoip")]
+        {
+            matches!((self.info.country, other.info.country), (Some(a), Some(b)) if a == b)
+        }
+        #[cfg(not(feature = "geoip"))]
+        {
+            let _ = other;
+            false
+        }
+    }
+}
+
+/// A summary of how diverse a set of guards is.
+#[derive(Clone, Debug, amplify::Getters)]
+#[non_exhaustive]
+pub struct GuardDistribution {
+    /// The number of guards in the set.
+    #[getter(as_copy)]
+    n_guards: usize,
+    /// The largest number of guards in the set that are in the same family
+    /// as any single guard (counting that guard).
+    #[getter(as_copy)]
+    largest_family: usize,
+    /// The largest number of guards in the set that are in the same subnet
+    /// as any single guard (counting that guard).
+    #[getter(as_copy)]
+    largest_subnet: usize,
+    /// The number of guards in each country, from the most to the least
+    /// common.
+    #[cfg(feature = "geoip")]
+    countries: Vec<(CountryCode, usize)>,
+    /// The number of guards whose country we do not know.
+    #[cfg(feature = "geoip")]
+    #[getter(as_copy)]
+    n_unknown_country: usize,
+}
+
+impl GuardDistribution {
+    /// Compute the distribution of `guards`.
+    pub(crate) fn from_views(guards: &[DiversityView<'_>]) -> Self {
+        let largest = |same: fn(&DiversityView<'_>, &DiversityView<'_>) -> bool| {
+            guards
+                .iter()
+                .map(|g| {
+                    1 + guards
+                        .iter()
+                        .filter(|other| !g.is_same_relay(other) && same(g, other))
+                        .count()
+                })
+                .max()
+                .unwrap_or(0)
+        };
+
+        #[cfg(feature = "geoip")]
+        let (countries, n_unknown_country) = {
+            let mut countries: Vec<(CountryCode, usize)> = Vec::new();
+            let mut n_unknown = 0;
+            for g in guards {
+                match g.info.country {
+                    Some(cc) => match countries.iter_mut().find(|(c, _)| *c == cc) {
+                        Some((_, n)) => *n += 1,
+                        None => countries.push((cc, 1)),
+                    },
+                    None => n_unknown += 1,
+                }
+            }
+            countries.sort_by(|(a_cc, a_n), (b_cc, b_n)| {
+                b_n.cmp(a_n).then_with(|| a_cc.get().cmp(b_cc.get()))
+            });
+            (countries, n_unknown)
+        };
+
+        GuardDistribution {
+            n_guards: guards.len(),
+            largest_family: largest(|a, b| a.in_same_family(b)),
+            largest_subnet: largest(|a, b| a.in_same_subnet(b)),
+            #[cfg(feature = "geoip")]
+            countries,
+            #[cfg(feature = "geoip")]
+            n_unknown_country,
+        }
+    }
+}
+
+/// A report on the diversity of the guards in the current guard sample.
+///
+/// Returned by [`GuardMgr::diversity_report`](crate::GuardMgr::diversity_report).
+#[derive(Clone, Debug, amplify::Getters)]
+#[non_exhaustive]
+pub struct GuardDiversityReport {
+    /// The distribution of all the guards in the sample.
+    sampled: GuardDistribution,
+    /// The distribution of the primary guards.
+    primary: GuardDistribution,
+    /// True if the primary guards obey every limit in the configured
+    /// [`GuardDiversityConfig`].
+    ///
+    /// This can be false if there were not enough suitable guards in the
+    /// sample to obey the limits.
+    #[getter(as_copy)]
+    primary_constraints_met: bool,
+}
+
+impl GuardDiversityReport {
+    /// Construct a new `GuardDiversityReport`.
+    pub(crate) fn new(
+        sampled: GuardDistribution,
+        primary: GuardDistribution,
+        primary_constraints_met: bool,
+    ) -> Self {
+        Self {
+            sampled,
+            primary,
+            primary_constraints_met,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+
+    #[test]
+    fn limits() {
+        let ids: Vec<RsaIdentity> = (1..=4).map(|n| [n; 20].into()).collect();
+        let addrs: Vec<[SocketAddr; 1]> = [
+            "192.0.2.1:9001",
+            "192.0.99.1:9001",
+            "198.51.100.1:9001",
+            "203.0.113.1:9001",
+        ]
+        .iter()
+        .map(|a| [a.parse().unwrap()])
+        .collect();
+        // Relays 0 and 2 are in the same family.
+        let infos = vec![
+            DiversityInfo {
+                family: vec![ids[2]],
+                ..Default::default()
+            },
+            DiversityInfo::default(),
+            DiversityInfo {
+                family: vec![ids[0]],
+                ..Default::default()
+            },
+            DiversityInfo::default(),
+        ];
+        let views: Vec<DiversityView<'_>> = (0..4)
+            .map(|i| DiversityView {
+                rsa_id: Some(&ids[i]),
+                addrs: &addrs[i],
+                info: &infos[i],
+            })
+            .collect();
+
+        // With no limits, everything is permitted.
+        let cfg = GuardDiversityConfig::default();
+        assert!(!cfg.is_enabled());
+        assert!(cfg.all_permitted(&views));
+
+        let one = NonZeroUsize::new(1).unwrap();
+        let cfg = GuardDiversityConfig::builder()
+            .max_per_family(one)
+            .build()
+            .unwrap();
+        assert!(cfg.permits(&views[1], views[..1].iter().cloned()));
+        assert!(!cfg.permits(&views[2], views[..2].iter().cloned()));
+        assert!(cfg.permits(&views[3], views[..3].iter().cloned()));
+        assert!(!cfg.all_permitted(&views));
+        // A guard doesn't conflict with itself.
+        assert!(cfg.permits(&views[0], views[..1].iter().cloned()));
+
+        let cfg = GuardDiversityConfig::builder()
+            .max_per_subnet(one)
+            .build()
+            .unwrap();
+        assert!(!cfg.permits(&views[1], views[..1].iter().cloned()));
+        assert!(cfg.permits(&views[2], views[..1].iter().cloned()));
+        assert!(cfg.all_permitted(&views[1..]));
+
+        let dist = GuardDistribution::from_views(&views);
+        assert_eq!(dist.n_guards(), 4);
+        assert_eq!(dist.largest_family(), 2);
+        assert_eq!(dist.largest_subnet(), 2);
+        let dist = GuardDistribution::from_views(&[]);
+        assert_eq!(dist.n_guards(), 0);
+        assert_eq!(dist.largest_family(), 0);
+    }
+
+    #[test]
+    #[cfg(not(feature = "geoip"))]
+    fn country_needs_geoip() {
+        let err = GuardDiversityConfig::builder()
+            .max_per_country(NonZeroUsize::new(2).unwrap())
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, ConfigBuildError::NoCompileTimeSupport { .. }));
+    }
+}
@@ -9,6 +9,7 @@ use std::time::{Duration, Instant, SystemTime};
 use tracing::{info, trace, warn};
 
 use crate::dirstatus::DirStatus;
+use crate::diversity::{DiversityInfo, DiversityView};
 use crate::sample::Candidate;
 use crate::skew::SkewObservation;
 use crate::util::randomize_time;
@@ -232,6 +233,11 @@ pub(crate) struct Guard {
     #[serde(skip)]
     sensitivity: DisplayRule,
 
+    /// Information needed to check this guard against our diversity
+    /// constraints, as of the last time we saw it in a universe.
+    #[serde(skip)]
+    diversity: DiversityInfo,
+
     /// Fields from the state file that was used to make this `Guard` that
     /// this version of Arti doesn't understand.
     #[serde(flatten)]
@@ -268,12 +274,14 @@ impl Guard {
             is_dir_cache,
             full_dir_info,
             owned_target,
+            diversity,
             ..
         } = candidate;
 
         Guard {
             is_dir_cache,
             dir_info_missing: !full_dir_info,
+            diversity,
             ..Self::from_chan_target(&owned_target, now, params)
         }
     }
@@ -335,6 +343,7 @@ impl Guard {
             clock_skew: None,
             unknown_fields: Default::default(),
             sensitivity: DisplayRule::Sensitive,
+            diversity: DiversityInfo::default(),
         }
     }
 
@@ -431,6 +440,7 @@ impl Guard {
             dir_status: other.dir_status,
             clock_skew: other.clock_skew,
             sensitivity: other.sensitivity,
+            diversity: other.diversity,
             // Note that we _could_ remove either of the above blocks and add
             // `..self` or `..other`, but that would be risky: it would increase
             // the odds that we would forget to add some persistent or
@@ -568,6 +578,7 @@ impl Guard {
                 full_dir_info,
                 owned_target,
                 sensitivity,
+                diversity,
             }) => {
                 // Update address information.
                 self.orports = owned_target.addrs().into();
@@ -584,6 +595,7 @@ impl Guard {
                 self.id = GuardId(RelayIds::from_relay_ids(&owned_target));
                 self.dir_info_missing = !full_dir_info;
                 self.sensitivity = sensitivity;
+                self.diversity = diversity;
 
                 listed_as_guard
             }
@@ -804,6 +816,15 @@ impl Guard {
         self.clock_skew.as_ref()
     }
 
+    /// Return a view of this guard for checking diversity constraints.
+    pub(crate) fn diversity_view(&self) -> DiversityView<'_> {
+        DiversityView {
+            rsa_id: self.id.0.rsa_identity(),
+            addrs: &self.orports,
+            info: &self.diversity,
+        }
+    }
+
     /// Testing only: Return true if this guard was ever contacted successfully.
     #[cfg(test)]
     pub(crate) fn confirmed(&self) -> bool {
@@ -77,6 +77,7 @@ pub mod bridge;
 mod config;
 mod daemon;
 mod dirstatus;
+mod diversity;
 mod err;
 mod events;
 pub mod fallback;
@@ -101,6 +102,9 @@ pub use config::testing::TestConfig;
 use oneshot_fused_workaround as oneshot;
 
 pub use config::GuardMgrConfig;
+pub use diversity::{
+    GuardDistribution, GuardDiversityConfig, GuardDiversityConfigBuilder, GuardDiversityReport,
+};
 pub use err::{GuardMgrConfigError, GuardMgrError, PickGuardError};
 pub use events::ClockSkewEvents;
 pub use filter::GuardFilter;
@@ -164,6 +168,12 @@ struct GuardMgrInner {
     /// This is updated whenever the consensus parameters change.
     params: GuardParams,
 
+    /// The configured limits on how diverse our guards must be.
+    ///
+    /// These are applied to the active [`GuardSet`], unless it is a set of
+    /// bridges.
+    diversity: GuardDiversityConfig,
+
     /// A mpsc channel, used to tell the task running in
     /// [`daemon::report_status_events`] about a new event to monitor.
     ///
@@ -345,6 +355,7 @@ impl<R: Runtime> GuardMgr<R> {
             filter: GuardFilter::unfiltered(),
             last_primary_retry_time: runtime.now(),
             params: GuardParams::default(),
+            diversity: config.guard_diversity().clone(),
             ctrl,
             pending: HashMap::new(),
             waiting: Vec::new(),
@@ -547,6 +558,12 @@ impl<R: Runtime> GuardMgr<R> {
             std::mem::swap(&mut inner.fallbacks, &mut fallbacks);
             inner.fallbacks.take_status_from(fallbacks);
         }
+        // Change our diversity constraints.  Existing circuits remain usable:
+        // this only affects which guards we choose from now on.
+        if config.guard_diversity() != &inner.diversity {
+            inner.diversity = config.guard_diversity().clone();
+            inner.update(self.runtime.wallclock(), self.runtime.now());
+        }
         // If we are built to use bridges, change the bridge configuration.
         #[cfg(feature = "bridge-client")]
         {
@@ -700,6 +717,14 @@ impl<R: Runtime> GuardMgr<R> {
         inner.recv_skew.clone()
     }
 
+    /// Return a report on how diverse the guards in our current sample are,
+    /// and whether our primary guards obey the configured
+    /// [`GuardDiversityConfig`].
+    pub fn diversity_report(&self) -> GuardDiversityReport {
+        let inner = self.inner.lock().expect("Poisoned lock");
+        inner.guards.active_guards().diversity_report()
+    }
+
     /// Ensure that the message queue is flushed before proceeding to
     /// the next step.  Used for testing.
     #[cfg(test)]
@@ -984,6 +1009,15 @@ impl GuardMgrInner {
                 .active_guards_mut()
                 .set_filter(self.filter.clone(), restrictive);
         }
+
+        // Change the diversity constraints.  We don't apply these to bridges,
+        // since the user chose those explicitly.
+        let diversity = match self.guards.active_set.universe_type() {
+            UniverseType::NetDir => self.diversity.clone(),
+            #[cfg(feature = "bridge-client")]
+            UniverseType::BridgeSet => GuardDiversityConfig::default(),
+        };
+        self.guards.active_guards_mut().set_diversity(diversity);
     }
 
     /// Update the status of every guard in `active_guards`, and expand it as
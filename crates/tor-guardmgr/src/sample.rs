@@ -3,6 +3,9 @@
 
 mod candidate;
 
+use crate::diversity::{
+    DiversityView, GuardDistribution, GuardDiversityConfig, GuardDiversityReport,
+};
 use crate::filter::GuardFilter;
 use crate::guard::{Guard, NewlyConfirmed, Reachable};
 use crate::skew::SkewObservation;
@@ -132,6 +135,9 @@ pub(crate) struct GuardSet {
     /// If true, the active filter is "very restrictive".
     filter_is_restrictive: bool,
 
+    /// Currently active limits on how diverse our guards must be.
+    diversity: GuardDiversityConfig,
+
     /// Set to 'true' whenever something changes that would force us
     /// to call 'select_primary_guards()', and cleared whenever we call it.
     primary_guards_invalidated: bool,
@@ -254,6 +260,33 @@ impl GuardSet {
         &self.active_filter
     }
 
+    /// Replace the diversity constraints used by this `GuardSet` with `diversity`.
+    ///
+    /// Does nothing if the constraints are unchanged.
+    pub(crate) fn set_diversity(&mut self, diversity: GuardDiversityConfig) {
+        if self.diversity != diversity {
+            self.diversity = diversity;
+            self.primary_guards_invalidated = true;
+        }
+    }
+
+    /// Return a report on how diverse the guards in this `GuardSet` are.
+    pub(crate) fn diversity_report(&self) -> GuardDiversityReport {
+        let views = |ids: &[GuardId]| -> Vec<DiversityView<'_>> {
+            ids.iter()
+                .filter_map(|id| self.guards.by_all_ids(id))
+                .map(Guard::diversity_view)
+                .collect()
+        };
+        let sampled = views(&self.sample);
+        let primary = views(&self.primary);
+        GuardDiversityReport::new(
+            GuardDistribution::from_views(&sampled),
+            GuardDistribution::from_views(&primary),
+            self.diversity.all_permitted(&primary),
+        )
+    }
+
     /// Copy non-persistent status from every guard shared with `other`.
     ///
     /// This is used as part of our reload process when we don't own our state
@@ -310,6 +343,7 @@ impl GuardSet {
             primary,
             active_filter: GuardFilter::default(),
             filter_is_restrictive: false,
+            diversity: GuardDiversityConfig::default(),
             primary_guards_invalidated: true,
             unknown_fields: state.remaining,
         };
@@ -419,7 +453,7 @@ impl GuardSet {
 
         // Ask the netdir for a set of guards we could use.
         let no_filter = GuardFilter::unfiltered();
-        let (n_candidates, pre_filter) =
+        let (mut n_candidates, pre_filter) =
             if self.filter_is_restrictive || self.active_filter.is_unfiltered() {
                 (n_to_add, &self.active_filter)
             } else {
@@ -427,6 +461,10 @@ impl GuardSet {
                 // before filtering, so we make this larger on an ad-hoc basis.
                 (n_to_add * 3, &no_filter)
             };
+        if self.diversity.is_enabled() {
+            // Likewise, our diversity constraints may reject some candidates.
+            n_candidates *= 3;
+        }
 
         let candidates = dir.sample(&self.guards, pre_filter, n_candidates);
 
@@ -449,6 +487,13 @@ impl GuardSet {
                 // We've reached our target; no need to add more.
                 break;
             }
+            if !self.diversity.permits(
+                &candidate.diversity_view(),
+                self.guards.values().map(Guard::diversity_view),
+            ) {
+                // This candidate is too similar to guards we already have.
+                continue;
+            }
             if self.active_filter.permits(&candidate.owned_target) {
                 n_filtered_usable += 1;
             }
@@ -526,7 +571,7 @@ impl GuardSet {
         // Only for logging.
         let old_primary = self.primary.clone();
 
-        self.primary = self
+        let eligible: Vec<GuardId> = self
             // First, we look at the confirmed guards.
             .confirmed
             .iter()
@@ -549,10 +594,12 @@ impl GuardSet {
                     None
                 }
             })
-            // The first n_primary guards on that list are primary!
-            .take(params.n_primary)
             .collect();
 
+        // The first n_primary guards on that list are primary!  (Unless that
+        // would violate our diversity constraints.)
+        self.primary = self.choose_diverse_primary(eligible, params.n_primary);
+
         if self.primary != old_primary {
             debug!(old=?old_primary, new=?self.primary, "Updated primary guards.");
         }
@@ -571,6 +618,52 @@ impl GuardSet {
         self.primary_guards_invalidated = false;
     }
 
+    /// Choose up to `n_primary` primary guards from `eligible`, in order,
+    /// respecting our diversity constraints if we can.
+    ///
+    /// We skip any guard that would violate our constraints when combined with
+    /// the guards chosen before it.  If that leaves us with too few guards, we
+    /// fill the remaining slots with the skipped guards, in order.
+    fn choose_diverse_primary(&self, eligible: Vec<GuardId>, n_primary: usize) -> Vec<GuardId> {
+        if !self.diversity.is_enabled() {
+            return eligible.into_iter().take(n_primary).collect();
+        }
+
+        let guard = |id: &GuardId| {
+            self.guards
+                .by_all_ids(id)
+                .expect("Inconsistent guard state")
+        };
+        let mut chosen: Vec<GuardId> = Vec::with_capacity(n_primary);
+        let mut skipped = Vec::new();
+        for id in eligible {
+            if chosen.len() >= n_primary {
+                break;
+            }
+            let permitted = self.diversity.permits(
+                &guard(&id).diversity_view(),
+                chosen.iter().map(|c| guard(c).diversity_view()),
+            );
+            if permitted {
+                chosen.push(id);
+            } else {
+                skipped.push(id);
+            }
+        }
+
+        if chosen.len() < n_primary && !skipped.is_empty() {
+            info!(
+                n_primary,
+                n_diverse = chosen.len(),
+                "Not enough guards in our sample to satisfy diversity constraints; relaxing them."
+            );
+            let n_missing = n_primary - chosen.len();
+            chosen.extend(skipped.into_iter().take(n_missing));
+        }
+
+        chosen
+    }
+
     /// Remove all guards which should expire `now`, according to the settings
     /// in `params`.
     pub(crate) fn expire_old_guards(&mut self, params: &GuardParams, now: SystemTime) {
@@ -1217,6 +1310,52 @@ mod test {
         assert_eq!(&guards.primary, &[id3, id1, p4, p3]);
     }
 
+    #[test]
+    fn diversity() {
+        // In the test network, the relays' addresses are in only five
+        // different /16s.
+        let netdir = netdir();
+        let one_per_subnet = GuardDiversityConfig::builder()
+            .max_per_subnet(1.try_into().unwrap())
+            .build()
+            .unwrap();
+        let t1 = SystemTime::now();
+
+        // If we have constraints when we sample, the sample obeys them.
+        let params = GuardParams {
+            min_filtered_sample_size: 5,
+            n_primary: 3,
+            ..GuardParams::default()
+        };
+        let mut guards = GuardSet::default();
+        guards.set_diversity(one_per_subnet.clone());
+        guards.extend_sample_as_needed(t1, &params, &netdir);
+        guards.select_primary_guards(&params);
+        let report = guards.diversity_report();
+        assert!(report.sampled().n_guards() <= 5);
+        assert_eq!(report.sampled().largest_subnet(), 1);
+        assert_eq!(report.primary().n_guards(), 3);
+        assert!(report.primary_constraints_met());
+
+        // If the sample can't obey the constraints, we still pick enough
+        // primary guards, but report that the constraints were not met.
+        let params = GuardParams {
+            min_filtered_sample_size: 7,
+            n_primary: 6,
+            ..GuardParams::default()
+        };
+        let mut guards = GuardSet::default();
+        guards.extend_sample_as_needed(t1, &params, &netdir);
+        assert!(guards.sample.len() >= 7);
+        guards.set_diversity(one_per_subnet);
+        assert!(guards.primary_guards_invalidated);
+        guards.select_primary_guards(&params);
+        let report = guards.diversity_report();
+        assert_eq!(report.primary().n_guards(), 6);
+        assert!(report.primary().largest_subnet() > 1);
+        assert!(!report.primary_constraints_met());
+    }
+
     #[test]
     fn expiration() {
         let netdir = netdir();
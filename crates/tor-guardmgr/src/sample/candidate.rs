@@ -3,10 +3,11 @@
 
 use std::{sync::Arc, time::SystemTime};
 
-use tor_linkspec::{ByRelayIds, ChanTarget, HasRelayIds, OwnedChanTarget};
+use tor_linkspec::{ByRelayIds, ChanTarget, HasAddrs, HasRelayIds, OwnedChanTarget};
 use tor_netdir::{NetDir, Relay, RelayWeight};
 use tor_relay_selection::{RelayExclusion, RelaySelector, RelayUsage};
 
+use crate::diversity::{DiversityInfo, DiversityView};
 use crate::{GuardFilter, GuardParams};
 
 /// A "Universe" is a source from which guard candidates are drawn, and from
@@ -79,6 +80,20 @@ pub(crate) struct Candidate {
     pub(crate) owned_target: OwnedChanTarget,
     /// How should we display information about this candidate if we select it?
     pub(crate) sensitivity: crate::guard::DisplayRule,
+    /// Information needed to check this candidate against our diversity
+    /// constraints.
+    pub(crate) diversity: DiversityInfo,
+}
+
+impl Candidate {
+    /// Return a view of this candidate for checking diversity constraints.
+    pub(crate) fn diversity_view(&self) -> DiversityView<'_> {
+        DiversityView {
+            rsa_id: self.owned_target.rsa_identity(),
+            addrs: self.owned_target.addrs(),
+            info: &self.diversity,
+        }
+    }
 }
 
 /// Information about how much of the universe we are using in a guard sample,
@@ -116,6 +131,7 @@ impl Universe for NetDir {
                 owned_target: OwnedChanTarget::from_chan_target(&relay),
                 full_dir_info: true,
                 sensitivity: crate::guard::DisplayRule::Sensitive,
+                diversity: DiversityInfo::from_relay(self, &relay),
             }),
             None => match NetDir::ids_listed(self, guard) {
                 Some(true) => panic!("ids_listed said true, but by_ids said none!"),
@@ -196,6 +212,7 @@ impl Universe for NetDir {
                         full_dir_info: true,
                         owned_target: OwnedChanTarget::from_chan_target(relay),
                         sensitivity: crate::guard::DisplayRule::Sensitive,
+                        diversity: DiversityInfo::from_relay(self, relay),
                     },
                     // TODO: It would be better not to need this function.
                     weight(self, relay).unwrap_or_else(|| RelayWeight::from(0)),
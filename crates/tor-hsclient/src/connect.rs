@@ -10,7 +10,9 @@ use std::time::Instant;
 
 use async_trait::async_trait;
 use educe::Educe;
-use futures::{AsyncRead, AsyncWrite};
+use futures::future::{BoxFuture, Fuse};
+use futures::stream::FuturesUnordered;
+use futures::{select_biased, AsyncRead, AsyncWrite, FutureExt as _, StreamExt as _};
 use itertools::Itertools;
 use rand::Rng;
 use tor_bytes::Writeable;
@@ -20,10 +22,10 @@ use tor_cell::relaycell::msg::{AnyRelayMsg, Introduce1, Rendezvous2};
 use tor_error::{debug_report, warn_report, Bug};
 use tor_hscrypto::Subcredential;
 use tor_proto::circuit::handshake::hs_ntor;
-use tracing::{debug, trace};
+use tracing::{debug, info, trace};
 
 use retry_error::RetryError;
-use safelog::Sensitive;
+use safelog::{Redactable as _, Sensitive};
 use tor_cell::relaycell::hs::{
     AuthKeyType, EstablishRendezvous, IntroduceAck, RendezvousEstablished,
 };
@@ -49,6 +51,7 @@ use tor_rtcompat::{Runtime, SleepProviderExt as _, TimeoutError};
 use crate::pow::HsPowClient;
 use crate::proto_oneshot;
 use crate::relay_info::ipt_to_circtarget;
+use crate::events::{EventSender, HsClientEvent};
 use crate::state::MockableConnectorData;
 use crate::Config;
 use crate::{rend_pt_identity_for_error, FailedAttemptError, IntroPtIndex, RendPtIdentityForError};
@@ -92,6 +95,10 @@ macro_rules! ClientCirc { { $R:ty, $M:ty } => {
 pub struct Data {
     /// The latest known onion service descriptor for this service.
     desc: DataHsDesc,
+    /// The hsdir from which we obtained `desc`, if we have one.
+    desc_hsdir: Option<Ed25519Identity>,
+    /// How our most recent descriptor fetch from each hsdir went.
+    hsdirs: DataHsDirs,
     /// Information about the latest status of trying to connect to this service
     /// through each of its introduction points.
     ipts: DataIpts,
@@ -103,6 +110,24 @@ type DataHsDesc = Option<TimerangeBound<HsDesc>>;
 /// Part of `Data` that relates to our information about introduction points
 type DataIpts = HashMap<RelayIdForExperience, IptExperience>;
 
+/// Part of `Data` that relates to our information about hsdirs
+type DataHsDirs = HashMap<Ed25519Identity, HsDirExperience>;
+
+/// How things went last time we fetched the descriptor from this hsdir
+///
+/// We record this for every fetch that finishes, successfully or not.
+/// A fetch that we cancel, because another hsdir answered first, has no outcome,
+/// so we record nothing for it.
+///
+/// Choosing which hsdir to ask first is done by obtaining an `HsDirSortKey` from this.
+#[derive(Debug)]
+struct HsDirExperience {
+    /// How long the fetch took, including any timeout
+    duration: Duration,
+    /// Whether we got a valid descriptor
+    succeeded: bool,
+}
+
 /// How things went last time we tried to use this introduction point
 ///
 /// Neither this data structure, nor [`Data`], is responsible for arranging that we expire this
@@ -164,6 +189,7 @@ pub(crate) async fn connect<R: Runtime>(
         config,
         hsid,
         secret_keys,
+        connector.events.clone(),
         (),
     )?
     .connect(data)
@@ -200,6 +226,8 @@ struct Context<'c, R: Runtime, M: MocksForConnect<R>> {
     hs_blind_id: HsBlindId,
     /// The subcredential to use during this time period
     subcredential: Subcredential,
+    /// Where to report [`HsClientEvent`]s
+    events: EventSender,
     /// Mock data
     mocks: M,
 }
@@ -356,8 +384,46 @@ impl From<Option<&IptExperience>> for IptSortKeyOutcome {
     }
 }
 
+/// Sort key for an hsdir, for selecting which hsdirs to ask first
+///
+/// Ordering is most preferable first.
+///
+/// Unlike for IPTs, we don't need a random tiebreak:
+/// the list of hsdirs is already in random order, and we sort it with a stable sort.
+#[derive(Ord, PartialOrd, Eq, PartialEq, Debug)]
+enum HsDirSortKey {
+    /// Prefer hsdirs that gave us a descriptor
+    Success {
+        /// Prefer quick ones
+        duration: Duration,
+    },
+    /// Failing that, try one we don't know to have failed
+    Untried,
+    /// Failing that, it'll have to be ones that didn't work last time
+    Failed,
+}
+
+impl From<Option<&HsDirExperience>> for HsDirSortKey {
+    fn from(experience: Option<&HsDirExperience>) -> HsDirSortKey {
+        use HsDirSortKey as K;
+        match experience {
+            None => K::Untried,
+            Some(HsDirExperience {
+                duration,
+                succeeded: true,
+            }) => K::Success {
+                duration: *duration,
+            },
+            Some(HsDirExperience {
+                succeeded: false, ..
+            }) => K::Failed,
+        }
+    }
+}
+
 impl<'c, R: Runtime, M: MocksForConnect<R>> Context<'c, R, M> {
     /// Make a new `Context` from the input data
+    #[allow(clippy::too_many_arguments)]
     fn new(
         runtime: &'c R,
         circpool: &'c M::HsCircPool,
@@ -365,6 +431,7 @@ impl<'c, R: Runtime, M: MocksForConnect<R>> Context<'c, R, M> {
         config: Arc<Config>,
         hsid: HsId,
         secret_keys: HsClientSecretKeys,
+        events: EventSender,
         mocks: M,
     ) -> Result<Self, ConnError> {
         let time_period = netdir.hs_time_period();
@@ -388,6 +455,7 @@ impl<'c, R: Runtime, M: MocksForConnect<R>> Context<'c, R, M> {
             circpool,
             runtime,
             secret_keys,
+            events,
             mocks,
         })
     }
@@ -414,11 +482,105 @@ impl<'c, R: Runtime, M: MocksForConnect<R>> Context<'c, R, M> {
 
         let mocks = self.mocks.clone();
 
-        let desc = self.descriptor_ensure(&mut data.desc).await?;
+        // Maximum number of hsdir connection and retrieval attempts we'll make,
+        // including any refetch after every introduction point has failed.
+        let mut desc_attempts_left = self
+            .config
+            .retry
+            .hs_desc_fetch_attempts()
+            .try_into()
+            // User specified a very large u32.  We must be downcasting it to 16bit!
+            // let's give them as many retries as we can manage.
+            .unwrap_or(usize::MAX);
+
+        let desc = self
+            .descriptor_ensure(
+                &mut data.desc,
+                &mut data.desc_hsdir,
+                &mut data.hsdirs,
+                None,
+                &mut desc_attempts_left,
+            )
+            .await?;
 
         mocks.test_got_desc(desc);
 
-        let circ = self.intro_rend_connect(desc, &mut data.ipts).await?;
+        let errors = match self.intro_rend_connect(desc, &mut data.ipts).await {
+            Ok(circ) => {
+                mocks.test_got_circ(&circ);
+                return Ok(circ);
+            }
+            Err(CE::Failed(errors))
+                if desc_attempts_left > 0
+                    && errors.sources().any(|e| e.intro_index().is_some()) =>
+            {
+                errors
+            }
+            Err(e) => return Err(e),
+        };
+
+        // We couldn't get through any of the introduction points.  Perhaps the service
+        // has published a new descriptor, with new introduction points, and ours is stale
+        // (or was served to us by an hsdir that is behind).
+        // So discard our descriptor and fetch it again, preferring a different hsdir.
+        //
+        // We do this at most once for each connection request,
+        // and the refetch shares that request's budget of descriptor fetch attempts.
+        info!(
+            "hs conn to {}: all introduction attempts failed; refetching descriptor",
+            self.hsid.redacted()
+        );
+        data.desc = None;
+        let stale_hsdir = data.desc_hsdir.take();
+        let desc = match self
+            .descriptor_ensure(
+                &mut data.desc,
+                &mut data.desc_hsdir,
+                &mut data.hsdirs,
+                stale_hsdir.as_ref(),
+                &mut desc_attempts_left,
+            )
+            .await
+        {
+            Ok(desc) => desc,
+            Err(error) => {
+                debug_report!(
+                    &error,
+                    "hs conn to {}: descriptor refetch failed",
+                    &self.hsid
+                );
+                self.events.send(&HsClientEvent::IntroPointsExhausted {
+                    hsid: self.hsid.into(),
+                    refetched: false,
+                });
+                return Err(CE::Failed(errors));
+            }
+        };
+        self.events.send(&HsClientEvent::IntroPointsExhausted {
+            hsid: self.hsid.into(),
+            refetched: true,
+        });
+        debug!(
+            "hs conn to {}: refetched descriptor; retrying introduction",
+            &self.hsid
+        );
+
+        mocks.test_got_desc(desc);
+
+        let circ = self
+            .intro_rend_connect(desc, &mut data.ipts)
+            .await
+            .map_err(|error| match error {
+                // Report the failures from before the refetch, too.
+                CE::Failed(more) => {
+                    let mut errors = errors;
+                    for e in more.sources() {
+                        errors.push(e.clone());
+                    }
+                    CE::Failed(errors)
+                }
+                other => other,
+            })?;
         mocks.test_got_circ(&circ);
 
         Ok(circ)
@@ -429,21 +591,28 @@ impl<'c, R: Runtime, M: MocksForConnect<R>> Context<'c, R, M> {
     /// If we have a previously-downloaded descriptor, which is still valid,
     /// just returns a reference to it.
     ///
-    /// Otherwise, tries to obtain the descriptor by downloading it from hsdir(s).
+    /// Otherwise, tries to obtain the descriptor by downloading it from hsdir(s),
+    /// and records the hsdir that gave it to us in `source`.
+    /// We try the hsdirs that answered quickly last time first,
+    /// and the ones that failed last time last;
+    /// if `avoid` is provided, we try that hsdir last of all.
+    ///
+    /// If an hsdir is slow to answer, we ask the next one too,
+    /// and use whichever answers first, cancelling the others.
+    /// (See `hs_desc_fetch_parallelism` and `hs_desc_fetch_stagger`.)
+    /// We record how long each completed fetch took in `experience`.
     ///
-    /// Does all necessary retries and timeouts.
+    /// Does all necessary retries and timeouts,
+    /// making no more than `attempts_left` attempts, and deducting the ones we make.
     /// Returns an error if no valid descriptor could be found.
-    async fn descriptor_ensure<'d>(&self, data: &'d mut DataHsDesc) -> Result<&'d HsDesc, CE> {
-        // Maximum number of hsdir connection and retrieval attempts we'll make
-        let max_total_attempts = self
-            .config
-            .retry
-            .hs_desc_fetch_attempts()
-            .try_into()
-            // User specified a very large u32.  We must be downcasting it to 16bit!
-            // let's give them as many retries as we can manage.
-            .unwrap_or(usize::MAX);
-
+    async fn descriptor_ensure<'d>(
+        &self,
+        data: &'d mut DataHsDesc,
+        source: &mut Option<Ed25519Identity>,
+        experience: &mut DataHsDirs,
+        avoid: Option<&Ed25519Identity>,
+        attempts_left: &mut usize,
+    ) -> Result<&'d HsDesc, CE> {
         // Limit on the duration of each retrieval attempt
         let each_timeout = self.estimate_timeout(&[
             (1, TimeoutsAction::BuildCircuit { length: HOPS }), // build circuit
@@ -470,11 +639,16 @@ impl<'c, R: Runtime, M: MocksForConnect<R>> Context<'c, R, M> {
             // Seems to be not valid now.  Try to fetch a fresh one.
         }
 
-        let hs_dirs = self.netdir.hs_dirs_download(
+        let mut hs_dirs = self.netdir.hs_dirs_download(
             self.hs_blind_id,
             self.netdir.hs_time_period(),
             &mut self.mocks.thread_rng(),
         )?;
+        // (These are stable sorts, so hsdirs we know nothing about keep their random order.)
+        hs_dirs.sort_by_key(|relay| HsDirSortKey::from(experience.get(relay.id())));
+        if let Some(avoid) = avoid {
+            hs_dirs.sort_by_key(|relay| relay.id() == avoid);
+        }
 
         trace!(
             "HS desc fetch for {}, using {} hsdirs",
@@ -482,34 +656,84 @@ impl<'c, R: Runtime, M: MocksForConnect<R>> Context<'c, R, M> {
             hs_dirs.len()
         );
 
-        // We might consider launching requests to multiple HsDirs in parallel.
-        //   https://gitlab.torproject.org/tpo/core/arti/-/merge_requests/1118#note_2894463
-        // But C Tor doesn't and our HS experts don't consider that important:
+        // C Tor asks one HsDir at a time, and our HS experts don't consider asking several
+        // important, since it may make us more vulnerable to traffic analysis:
         //   https://gitlab.torproject.org/tpo/core/arti/-/issues/913#note_2914436
-        // (Additionally, making multiple HSDir requests at once may make us
-        // more vulnerable to traffic analysis.)
-        let mut attempts = hs_dirs.iter().cycle().take(max_total_attempts);
+        // But one slow or unresponsive HsDir can then delay us for the whole `each_timeout`.
+        // So we ask another HsDir only if the one we're waiting for is slow to answer;
+        // usually, we still make just one request.
+        //   https://gitlab.torproject.org/tpo/core/arti/-/merge_requests/1118#note_2894463
+        let parallelism = usize::try_from(self.config.retry.hs_desc_fetch_parallelism())
+            .unwrap_or(usize::MAX)
+            .max(1);
+        let stagger = self.config.retry.hs_desc_fetch_stagger();
+
+        let mut attempts = hs_dirs.iter().cycle().take(*attempts_left).peekable();
         let mut errors = RetryError::in_attempt_to("retrieve hidden service descriptor");
+        // The attempts in progress.  Dropping one cancels it.
+        let mut running = FuturesUnordered::new();
+        // The hsdirs that `running` is waiting for.
+        let mut running_hsdirs = Vec::<Ed25519Identity>::new();
+        // Fires when we should stop waiting for the most recent attempt alone.
+        let mut stagger_timer: Fuse<BoxFuture<'static, ()>> = Fuse::terminated();
+        // We go round this loop at the start, and then whenever an attempt fails
+        // or the stagger timer fires: each time, we may ask another hsdir.
         let desc = loop {
-            let relay = match attempts.next() {
-                Some(relay) => relay,
-                None => {
-                    return Err(if errors.is_empty() {
-                        CE::NoHsDirs
-                    } else {
-                        CE::DescriptorDownload(errors)
-                    })
+            if running.len() < parallelism {
+                // Don't ask an hsdir that we're already waiting for:
+                // wait for one of the running attempts to finish instead.
+                if let Some(relay) = attempts.next_if(|relay| !running_hsdirs.contains(relay.id()))
+                {
+                    *attempts_left -= 1;
+                    running_hsdirs.push(*relay.id());
+                    let started = self.runtime.now();
+                    running.push(async move {
+                        let outcome = self
+                            .runtime
+                            .timeout(each_timeout, self.descriptor_fetch_attempt(relay))
+                            .await
+                            .unwrap_or(Err(DescriptorErrorDetail::Timeout));
+                        (relay, started, outcome)
+                    });
+                    stagger_timer = self.runtime.sleep(stagger).boxed().fuse();
                 }
+            }
+
+            if running.is_empty() {
+                return Err(if errors.is_empty() {
+                    CE::NoHsDirs
+                } else {
+                    CE::DescriptorDownload(errors)
+                });
+            }
+
+            let (relay, started, outcome) = select_biased! {
+                finished = running.select_next_some() => finished,
+                () = stagger_timer => continue,
             };
-            let hsdir_for_error: Sensitive<Ed25519Identity> = (*relay.id()).into();
-            match self
-                .runtime
-                .timeout(each_timeout, self.descriptor_fetch_attempt(relay))
-                .await
-                .unwrap_or(Err(DescriptorErrorDetail::Timeout))
-            {
-                Ok(desc) => break desc,
+            running_hsdirs.retain(|id| id != relay.id());
+            match self.runtime.now().checked_duration_since(started) {
+                Some(duration) => {
+                    experience.insert(
+                        *relay.id(),
+                        HsDirExperience {
+                            duration,
+                            succeeded: outcome.is_ok(),
+                        },
+                    );
+                }
+                None => warn_report!(
+                    internal!("clock overflow calculating hsdir fetch duration"),
+                    "error recording HS hsdir experience"
+                ),
+            }
+            match outcome {
+                Ok(desc) => {
+                    *source = Some(*relay.id());
+                    break desc;
+                }
                 Err(error) => {
+                    let hsdir_for_error: Sensitive<Ed25519Identity> = (*relay.id()).into();
                     debug_report!(
                         &error,
                         "failed hsdir desc fetch for {} from {}",
@@ -520,9 +744,13 @@ impl<'c, R: Runtime, M: MocksForConnect<R>> Context<'c, R, M> {
                         hsdir: hsdir_for_error,
                         error,
                     }));
+                    // Go round again without waiting for the stagger:
+                    // this attempt isn't going to answer.
                 }
             }
         };
+        // Dropping `running` cancels any attempts that were still waiting.
+        drop(running);
 
         // Store the bounded value in the cache for reuse,
         // but return a reference to the unwrapped `HsDesc`.
@@ -620,6 +848,10 @@ impl<'c, R: Runtime, M: MocksForConnect<R>> Context<'c, R, M> {
         desc: &HsDesc,
         data: &mut DataIpts,
     ) -> Result<Arc<ClientCirc!(R, M)>, CE> {
+        if let Some(outcome) = self.mocks.test_intro_rend_outcome(desc) {
+            return outcome;
+        }
+
         // Maximum number of rendezvous/introduction attempts we'll make
         let max_total_attempts = self
             .config
@@ -1355,6 +1587,19 @@ trait MocksForConnect<R>: Clone {
     fn test_got_circ(&self, _: &Arc<ClientCirc!(R, Self)>) {}
     /// Tell tests we have obtained and sorted the intros like this
     fn test_got_ipts(&self, _: &[UsableIntroPt]) {}
+    /// Let tests decide the outcome of our introduction and rendezvous attempts
+    /// with this descriptor
+    ///
+    /// The mock circuits in our tests can't carry the introduction and rendezvous
+    /// handshakes, so tests that need those to succeed or fail use this instead.
+    /// If this returns `None`, we make the attempts as usual.
+    #[allow(clippy::type_complexity)]
+    fn test_intro_rend_outcome(
+        &self,
+        _: &HsDesc,
+    ) -> Option<Result<Arc<ClientCirc!(R, Self)>, ConnError>> {
+        None
+    }
 
     /// Return a random number generator
     fn thread_rng(&self) -> Self::Rng;
@@ -1517,7 +1762,6 @@ mod test {
 
     use super::*;
     use crate::*;
-    use futures::FutureExt as _;
     use std::ops::{Bound, RangeBounds};
     use std::{iter, panic::AssertUnwindSafe};
     use tokio_crate as tokio;
@@ -1535,6 +1779,13 @@ mod test {
     struct MocksGlobal {
         hsdirs_asked: Vec<OwnedCircTarget>,
         got_desc: Option<HsDesc>,
+        /// If `Some(n)`, every introduction point fails in the first `n` rounds
+        /// of introduction attempts, and then we succeed.
+        fail_intro_rounds: Option<usize>,
+        /// How many rounds of introduction attempts we've made.
+        intro_rounds: usize,
+        /// The first this many hsdirs we ask never answer.
+        slow_hsdirs: usize,
     }
     #[derive(Clone, Debug)]
     struct Mocks<I> {
@@ -1561,6 +1812,23 @@ mod test {
 
         fn test_got_ipts(&self, desc: &[UsableIntroPt]) {}
 
+        fn test_intro_rend_outcome(
+            &self,
+            _: &HsDesc,
+        ) -> Option<Result<Arc<Mocks<()>>, ConnError>> {
+            let mut mglobal = self.mglobal.lock().unwrap();
+            let fail_rounds = mglobal.fail_intro_rounds?;
+            mglobal.intro_rounds += 1;
+            if mglobal.intro_rounds > fail_rounds {
+                return Some(Ok(Arc::new(self.clone())));
+            }
+            let mut errors = RetryError::in_attempt_to("make circuit to to hidden service");
+            errors.push(FAE::IntroductionTimeout {
+                intro_index: 0.into(),
+            });
+            Some(Err(CE::Failed(errors)))
+        }
+
         fn thread_rng(&self) -> Self::Rng {
             testing_rng()
         }
@@ -1577,7 +1845,14 @@ mod test {
         ) -> tor_circmgr::Result<Arc<Self::ClientCirc>> {
             assert_eq!(kind, HsCircKind::ClientHsDir);
             let target = OwnedCircTarget::from_circ_target(&target);
-            self.mglobal.lock().unwrap().hsdirs_asked.push(target);
+            let slow = {
+                let mut mglobal = self.mglobal.lock().unwrap();
+                mglobal.hsdirs_asked.push(target);
+                mglobal.hsdirs_asked.len() <= mglobal.slow_hsdirs
+            };
+            if slow {
+                futures::future::pending::<()>().await;
+            }
             // Adding the `Arc` here is a little ugly, but that's what we get
             // for using the same Mocks for everything.
             Ok(Arc::new(self.clone()))
@@ -1633,6 +1908,40 @@ mod test {
         }
     }
 
+    /// Make a testing netdir, and a runtime whose clock is set to suit our test descriptor.
+    ///
+    /// Also returns the runtime's sleep provider, so that the test can advance the clock.
+    fn test_netdir_and_runtime() -> (Arc<NetDir>, impl Runtime, MockSleepProvider) {
+        let valid_after = humantime::parse_rfc3339("2023-02-09T12:00:00Z").unwrap();
+        let fresh_until = valid_after + humantime::parse_duration("1 hours").unwrap();
+        let valid_until = valid_after + humantime::parse_duration("24 hours").unwrap();
+        let lifetime = Lifetime::new(valid_after, fresh_until, valid_until).unwrap();
+
+        let netdir = tor_netdir::testnet::construct_custom_netdir_with_params(
+            tor_netdir::testnet::simple_net_func,
+            iter::empty::<(&str, _)>(),
+            Some(lifetime),
+        )
+        .expect("failed to build default testing netdir");
+
+        let netdir = Arc::new(netdir.unwrap_if_sufficient().unwrap());
+        let runtime = TokioNativeTlsRuntime::current().unwrap();
+        let mock_sp = MockSleepProvider::new(valid_after);
+        let runtime = runtime
+            .with_sleep_provider(mock_sp.clone())
+            .with_coarse_time_provider(mock_sp.clone());
+        (netdir, runtime, mock_sp)
+    }
+
+    /// Make the secret keys that let us decrypt our test descriptor.
+    fn test_secret_keys() -> HsClientSecretKeys {
+        let pk: HsClientDescEncKey = curve25519::PublicKey::from(test_data::TEST_PUBKEY_2).into();
+        let sk = curve25519::StaticSecret::from(test_data::TEST_SECKEY_2).into();
+        let mut secret_keys_builder = HsClientSecretKeysBuilder::default();
+        secret_keys_builder.ks_hsc_desc_enc(HsClientDescEncKeypair::new(pk, sk));
+        secret_keys_builder.build().unwrap()
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn test_connect() {
@@ -1676,6 +1985,7 @@ mod test {
             Default::default(),
             hsid,
             secret_keys,
+            EventSender::default(),
             mocks.clone(),
         )
         .unwrap();
@@ -1704,6 +2014,16 @@ mod test {
 
         let mglobal = mocks.mglobal.lock().unwrap();
         assert_eq!(mglobal.hsdirs_asked.len(), 1);
+        // We remember which hsdir gave us the descriptor, so a refetch can try another one
+        assert_eq!(
+            data.desc_hsdir.as_ref(),
+            mglobal.hsdirs_asked[0].ed_identity()
+        );
+        // The hsdir answered at once, so we didn't race it against another,
+        // and we recorded how it went
+        let hsdir = mglobal.hsdirs_asked[0].ed_identity().unwrap();
+        assert!(data.hsdirs[hsdir].succeeded);
+        assert_eq!(data.hsdirs.len(), 1);
         // TODO hs: here and in other places, consider implementing PartialEq instead, or creating
         // an assert_dbg_eq macro (which would be part of a test_helpers crate or something)
         assert_eq!(
@@ -1726,6 +2046,122 @@ mod test {
         // TODO HS TESTS: continue with this
     }
 
+    #[traced_test]
+    #[tokio::test]
+    async fn test_connect_refetch_after_intro_failure() {
+        let (netdir, runtime, _) = test_netdir_and_runtime();
+
+        let mglobal = Arc::new(Mutex::new(MocksGlobal {
+            fail_intro_rounds: Some(1),
+            ..Default::default()
+        }));
+        let mocks = Mocks { mglobal, id: () };
+        let hsid = test_data::TEST_HSID_2.into();
+        let mut data = Data::default();
+
+        let events = EventSender::default();
+        let mut event_stream = events.subscribe();
+
+        let ctx = Context::new(
+            &runtime,
+            &mocks,
+            netdir,
+            Default::default(),
+            hsid,
+            test_secret_keys(),
+            events,
+            mocks.clone(),
+        )
+        .unwrap();
+
+        // Every introduction point in the first descriptor fails;
+        // we refetch the descriptor, and then we succeed.
+        let _: Arc<Mocks<()>> = ctx.connect(&mut data).await.unwrap();
+
+        let mglobal = mocks.mglobal.lock().unwrap();
+        assert_eq!(mglobal.intro_rounds, 2);
+        // We asked a different hsdir the second time.
+        assert_eq!(mglobal.hsdirs_asked.len(), 2);
+        let [first, second] = &mglobal.hsdirs_asked[..] else {
+            panic!("asked {} hsdirs", mglobal.hsdirs_asked.len());
+        };
+        assert_ne!(first.ed_identity(), second.ed_identity());
+        assert_eq!(data.desc_hsdir.as_ref(), second.ed_identity());
+
+        // And we reported the fallback.
+        let event = event_stream.next().now_or_never().unwrap().unwrap();
+        assert!(
+            matches!(
+                event,
+                HsClientEvent::IntroPointsExhausted {
+                    ref hsid,
+                    refetched: true,
+                } if hsid.as_inner() == &test_data::TEST_HSID_2.into()
+            ),
+            "{event:?}"
+        );
+        assert!(event_stream.next().now_or_never().is_none());
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn test_connect_slow_hsdir() {
+        let (netdir, runtime, mock_sp) = test_netdir_and_runtime();
+
+        let mglobal = Arc::new(Mutex::new(MocksGlobal {
+            fail_intro_rounds: Some(0),
+            slow_hsdirs: 1,
+            ..Default::default()
+        }));
+        let mocks = Mocks { mglobal, id: () };
+        let hsid = test_data::TEST_HSID_2.into();
+        let mut data = Data::default();
+
+        let mut retry = tor_circmgr::CircuitTiming::builder();
+        retry.hs_desc_fetch_parallelism(2);
+        let config = Config {
+            retry: retry.build().unwrap(),
+        };
+        let stagger = config.retry.hs_desc_fetch_stagger();
+
+        let ctx = Context::new(
+            &runtime,
+            &mocks,
+            netdir,
+            Arc::new(config),
+            hsid,
+            test_secret_keys(),
+            EventSender::default(),
+            mocks.clone(),
+        )
+        .unwrap();
+
+        // The first hsdir never answers, so once the stagger has elapsed,
+        // we ask the second one too, which answers at once.
+        let (got, ()) = futures::join!(ctx.connect(&mut data), mock_sp.advance(stagger));
+        let _: Arc<Mocks<()>> = got.unwrap();
+
+        let (slow, fast) = {
+            let mglobal = mocks.mglobal.lock().unwrap();
+            let [slow, fast] = &mglobal.hsdirs_asked[..] else {
+                panic!("asked {} hsdirs", mglobal.hsdirs_asked.len());
+            };
+            (*slow.ed_identity().unwrap(), *fast.ed_identity().unwrap())
+        };
+        assert_ne!(slow, fast);
+        assert_eq!(data.desc_hsdir, Some(fast));
+        // We cancelled the slow fetch, so we recorded only the fast one.
+        assert!(data.hsdirs[&fast].succeeded);
+        assert_eq!(data.hsdirs.len(), 1);
+
+        // Next time, we ask the hsdir that answered quickly first.
+        data.desc = None;
+        let _: Arc<Mocks<()>> = ctx.connect(&mut data).await.unwrap();
+        let mglobal = mocks.mglobal.lock().unwrap();
+        assert_eq!(mglobal.hsdirs_asked.len(), 3);
+        assert_eq!(mglobal.hsdirs_asked[2].ed_identity(), Some(&fast));
+    }
+
     // TODO HS TESTS: Test IPT state management and expiry:
     //   - obtain a test descriptor with only a broken ipt
     //     (broken in the sense that intro can be attempted, but will fail somehow)
@@ -0,0 +1,368 @@
+//! Files holding client authorization credentials for onion services.
+//!
+//! A credential is what a client needs in order to connect to an onion service
+//! running in restricted discovery mode:
+//! the service's onion address, and the client's x25519 descriptor decryption key
+//! (`KS_hsc_desc_enc`).
+//!
+//! We understand two file formats.
+//!
+//! Arti's own format has one `keyword value` item per line.
+//! Blank lines, and lines starting with `#`, are ignored.
+//! The `onion-address` and `x25519-private-key` items are required;
+//! `nickname` is optional.
+//!
+//! ```text
+//! # Credential for my photo gallery
+//! onion-address mnyizjj7m3hpcr7i5afph3zt7maa65johyu2ruis6z7cmnjmaj3h6tad.onion
+//! x25519-private-key descriptor:x25519:2ICSO6MM2FOEY4RHSOXJXJ6JYMRYY2RZVE6FVNTIWAPJPFKPBXYA
+//! nickname gallery
+//! ```
+//!
+//! C Tor's format (as found in the files of its `ClientOnionAuthDir`, named `*.auth_private`)
+//! is a single line, holding the onion address without its `.onion` suffix,
+//! followed by the key:
+//!
+//! ```text
+//! mnyizjj7m3hpcr7i5afph3zt7maa65johyu2ruis6z7cmnjmaj3h6tad:descriptor:x25519:2ICSO6MM2FOEY4RHSOXJXJ6JYMRYY2RZVE6FVNTIWAPJPFKPBXYA
+//! ```
+
+use std::str::FromStr;
+
+use thiserror::Error;
+use tor_hscrypto::pk::{HsClientDescEncSecretKey, HsId, HsIdParseError};
+use tor_llcrypto::pk::curve25519;
+
+/// The keyword for the onion address in an Arti credential file.
+const ONION_ADDRESS: &str = "onion-address";
+/// The keyword for the private key in an Arti credential file.
+const X25519_PRIVATE_KEY: &str = "x25519-private-key";
+/// The keyword for the nickname in an Arti credential file.
+const NICKNAME: &str = "nickname";
+
+/// A client authorization credential for an onion service.
+///
+/// See the [module documentation](self) for the file formats this can be parsed from.
+#[derive(Debug)]
+pub struct HsClientCredential {
+    /// The onion service this credential is for.
+    hsid: HsId,
+    /// The client's descriptor decryption key for that service.
+    secret: HsClientDescEncSecretKey,
+    /// A human-readable name for the credential, if it has one.
+    nickname: Option<String>,
+}
+
+impl HsClientCredential {
+    /// Parse a credential in either of the formats we understand.
+    ///
+    /// A file whose only item contains a `:`, and no whitespace, is taken to be in C Tor's format.
+    pub fn parse(s: &str) -> Result<Self, CredentialParseError> {
+        let mut items = items(s);
+        match (items.next(), items.next()) {
+            (Some((_, line)), None)
+                if !line.contains(char::is_whitespace) && line.contains(':') =>
+            {
+                Self::parse_c_tor(s)
+            }
+            _ => Self::parse_arti(s),
+        }
+    }
+
+    /// Parse a credential in Arti's format.
+    pub fn parse_arti(s: &str) -> Result<Self, CredentialParseError> {
+        use CredentialParseError as E;
+
+        let mut hsid = None;
+        let mut secret = None;
+        let mut nickname = None;
+
+        for (lineno, line) in items(s) {
+            let (keyword, value) = line
+                .split_once(char::is_whitespace)
+                .map(|(k, v)| (k, v.trim()))
+                .ok_or(E::MissingValue(lineno))?;
+            let set_once = |slot_is_set: bool| {
+                if slot_is_set {
+                    Err(E::DuplicateItem(keyword.into()))
+                } else {
+                    Ok(())
+                }
+            };
+            match keyword {
+                ONION_ADDRESS => {
+                    set_once(hsid.is_some())?;
+                    hsid = Some(HsId::from_str(value).map_err(E::InvalidHsId)?);
+                }
+                X25519_PRIVATE_KEY => {
+                    set_once(secret.is_some())?;
+                    secret = Some(parse_secret_key(value)?);
+                }
+                NICKNAME => {
+                    set_once(nickname.is_some())?;
+                    nickname = Some(value.to_owned());
+                }
+                _ => return Err(E::UnknownKeyword(keyword.into())),
+            }
+        }
+
+        Ok(Self {
+            hsid: hsid.ok_or(E::MissingItem(ONION_ADDRESS))?,
+            secret: secret.ok_or(E::MissingItem(X25519_PRIVATE_KEY))?,
+            nickname,
+        })
+    }
+
+    /// Parse a credential in C Tor's `ClientOnionAuthDir` format.
+    ///
+    /// C Tor's credentials don't have nicknames.
+    pub fn parse_c_tor(s: &str) -> Result<Self, CredentialParseError> {
+        use CredentialParseError as E;
+
+        let mut items = items(s);
+        let (_, line) = items.next().ok_or(E::Empty)?;
+        if let Some((lineno, _)) = items.next() {
+            return Err(E::TrailingData(lineno));
+        }
+
+        let (onion, key) = line.split_once(':').ok_or(E::InvalidFormat)?;
+        let hsid = HsId::from_str(&format!("{onion}.onion")).map_err(E::InvalidHsId)?;
+        let secret = parse_secret_key(key)?;
+
+        Ok(Self {
+            hsid,
+            secret,
+            nickname: None,
+        })
+    }
+
+    /// Return the onion service this credential is for.
+    pub fn hsid(&self) -> HsId {
+        self.hsid
+    }
+
+    /// Return the client's descriptor decryption key for the service.
+    pub fn secret(&self) -> &HsClientDescEncSecretKey {
+        &self.secret
+    }
+
+    /// Return the nickname of this credential, if it has one.
+    pub fn nickname(&self) -> Option<&str> {
+        self.nickname.as_deref()
+    }
+
+    /// Consume this credential, returning its descriptor decryption key.
+    pub fn into_secret(self) -> HsClientDescEncSecretKey {
+        self.secret
+    }
+}
+
+impl FromStr for HsClientCredential {
+    type Err = CredentialParseError;
+
+    fn from_str(s: &str) -> Result<Self, CredentialParseError> {
+        Self::parse(s)
+    }
+}
+
+/// Return the (1-based) line number and trimmed contents of each line of `s`
+/// that isn't blank or a comment.
+fn items(s: &str) -> impl Iterator<Item = (usize, &str)> {
+    s.lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// Parse a private key of the form `descriptor:x25519:<base32-encoded-x25519-private-key>`.
+fn parse_secret_key(s: &str) -> Result<HsClientDescEncSecretKey, CredentialParseError> {
+    use CredentialParseError as E;
+
+    let (auth_type, key_type, encoded_key) = match s.split(':').collect::<Vec<_>>()[..] {
+        [auth_type, key_type, encoded_key] => (auth_type, key_type, encoded_key),
+        _ => return Err(E::InvalidFormat),
+    };
+    if auth_type != "descriptor" {
+        return Err(E::InvalidAuthType(auth_type.into()));
+    }
+    if key_type != "x25519" {
+        return Err(E::InvalidKeyType(key_type.into()));
+    }
+
+    // Like C Tor, we accept base32 in either case.
+    let encoded_key = encoded_key.to_ascii_uppercase();
+    let key = data_encoding::BASE32_NOPAD
+        .decode(encoded_key.as_bytes())
+        .map_err(|_| E::InvalidKeyMaterial)?;
+    let key: [u8; 32] = key.try_into().map_err(|_| E::InvalidKeyMaterial)?;
+
+    Ok(curve25519::StaticSecret::from(key).into())
+}
+
+/// An error while parsing an [`HsClientCredential`].
+#[derive(Error, Clone, Debug)]
+#[non_exhaustive]
+pub enum CredentialParseError {
+    /// The credential was empty.
+    #[error("Credential is empty")]
+    Empty,
+
+    /// A C Tor credential had more than one line.
+    #[error("Unexpected data on line {0}")]
+    TrailingData(usize),
+
+    /// An item in an Arti credential had no value.
+    #[error("Missing value on line {0}")]
+    MissingValue(usize),
+
+    /// An Arti credential had an item we don't recognize.
+    #[error("Unrecognized item {0:?}")]
+    UnknownKeyword(String),
+
+    /// An Arti credential had the same item more than once.
+    #[error("Item {0:?} appears more than once")]
+    DuplicateItem(String),
+
+    /// An Arti credential was missing a required item.
+    #[error("Missing required item {0:?}")]
+    MissingItem(&'static str),
+
+    /// The onion address was invalid.
+    #[error("Invalid onion address")]
+    InvalidHsId(#[source] HsIdParseError),
+
+    /// The key was not in the `descriptor:x25519:<base32-encoded-private-key>` format.
+    #[error("Invalid key format")]
+    InvalidFormat,
+
+    /// The auth type of the key was not "descriptor".
+    #[error("Invalid auth type {0:?}")]
+    InvalidAuthType(String),
+
+    /// The key type of the key was not "x25519".
+    #[error("Invalid key type {0:?}")]
+    InvalidKeyType(String),
+
+    /// The key material was not 32 bytes of valid base32.
+    #[error("Invalid key material")]
+    InvalidKeyMaterial,
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+
+    use super::*;
+    use tor_hscrypto::pk::HsClientDescEncKey;
+
+    const ONION: &str = "mnyizjj7m3hpcr7i5afph3zt7maa65johyu2ruis6z7cmnjmaj3h6tad";
+    const KEY: &str = "descriptor:x25519:2ICSO6MM2FOEY4RHSOXJXJ6JYMRYY2RZVE6FVNTIWAPJPFKPBXYA";
+
+    fn public(cred: &HsClientCredential) -> String {
+        HsClientDescEncKey::from(cred.secret()).to_string()
+    }
+
+    #[test]
+    fn arti_format() {
+        let s = format!(
+            "# A comment\n\n{ONION_ADDRESS} {ONION}.onion\n  {X25519_PRIVATE_KEY} {KEY}\n{NICKNAME} my gallery\n"
+        );
+        let expected_hsid = HsId::from_str(&format!("{ONION}.onion")).unwrap();
+        for cred in [
+            HsClientCredential::parse_arti(&s).unwrap(),
+            HsClientCredential::parse(&s).unwrap(),
+        ] {
+            assert_eq!(cred.hsid(), expected_hsid);
+            assert_eq!(cred.nickname(), Some("my gallery"));
+        }
+
+        // Nicknames are optional.
+        let s = format!("{X25519_PRIVATE_KEY} {KEY}\n{ONION_ADDRESS} {ONION}.onion\n");
+        let cred = HsClientCredential::parse(&s).unwrap();
+        assert_eq!(cred.nickname(), None);
+
+        // The key is the same whichever case its base32 is in.
+        let lower = format!(
+            "{ONION_ADDRESS} {ONION}.onion\n{X25519_PRIVATE_KEY} {}\n",
+            KEY.to_lowercase()
+        );
+        let cred_lower = HsClientCredential::parse(&lower).unwrap();
+        assert_eq!(public(&cred), public(&cred_lower));
+    }
+
+    #[test]
+    fn c_tor_format() {
+        let s = format!("{ONION}:{KEY}\n");
+        let expected_hsid = HsId::from_str(&format!("{ONION}.onion")).unwrap();
+        for cred in [
+            HsClientCredential::parse_c_tor(&s).unwrap(),
+            HsClientCredential::parse(&s).unwrap(),
+        ] {
+            assert_eq!(cred.hsid(), expected_hsid);
+            assert_eq!(cred.nickname(), None);
+        }
+
+        // Both formats give the same key.
+        let arti = format!("{ONION_ADDRESS} {ONION}.onion\n{X25519_PRIVATE_KEY} {KEY}\n");
+        let arti = HsClientCredential::parse(&arti).unwrap();
+        let c_tor = HsClientCredential::parse(&s).unwrap();
+        assert_eq!(public(&arti), public(&c_tor));
+    }
+
+    #[test]
+    fn errors() {
+        use CredentialParseError as E;
+
+        macro_rules! assert_err {
+            { $s:expr, $pat:pat } => {
+                let err = HsClientCredential::parse(&$s).unwrap_err();
+                assert!(matches!(err, $pat), "{:?}", err);
+            }
+        }
+
+        assert_err!("", E::MissingItem(ONION_ADDRESS));
+        assert_err!(
+            format!("{ONION_ADDRESS} {ONION}.onion"),
+            E::MissingItem(X25519_PRIVATE_KEY)
+        );
+        assert_err!(
+            format!("{ONION_ADDRESS}\n{X25519_PRIVATE_KEY} {KEY}"),
+            E::MissingValue(1)
+        );
+        assert_err!(
+            format!("{ONION_ADDRESS} {ONION}.onion\n{ONION_ADDRESS} {ONION}.onion"),
+            E::DuplicateItem(_)
+        );
+        assert_err!(format!("color {ONION}"), E::UnknownKeyword(_));
+        assert_err!(format!("{ONION_ADDRESS} {ONION}"), E::InvalidHsId(_));
+        assert_err!(format!("{ONION}:descriptor:x25519"), E::InvalidFormat);
+        assert_err!(format!("{ONION}:foo:x25519:AAAA"), E::InvalidAuthType(_));
+        assert_err!(
+            format!("{ONION}:descriptor:ed25519:AAAA"),
+            E::InvalidKeyType(_)
+        );
+        assert_err!(
+            format!("{ONION}:descriptor:x25519:AAAA"),
+            E::InvalidKeyMaterial
+        );
+        assert_err!(format!("xyz:{KEY}"), E::InvalidHsId(_));
+
+        let err =
+            HsClientCredential::parse_c_tor(&format!("{ONION}:{KEY}\n{ONION}:{KEY}")).unwrap_err();
+        assert!(matches!(err, E::TrailingData(2)));
+        let err = HsClientCredential::parse_c_tor("# nothing here").unwrap_err();
+        assert!(matches!(err, E::Empty));
+    }
+}
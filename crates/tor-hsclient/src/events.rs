@@ -0,0 +1,80 @@
+//! Events reported by an [`HsClientConnector`](crate::HsClientConnector).
+
+use std::sync::{Arc, Mutex};
+
+use futures::channel::mpsc;
+use futures::StreamExt as _;
+use safelog::Sensitive;
+use tor_hscrypto::pk::HsId;
+
+/// A notable thing that happened while we were connecting to an onion service.
+///
+/// These events let an application see how its connections fared
+/// (for example, to notice that a service's descriptor was often stale),
+/// even when the connection attempt eventually succeeded.
+///
+/// Events are delivered by an [`HsClientEventStream`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum HsClientEvent {
+    /// Every introduction point in our descriptor for an onion service failed,
+    /// so we discarded the descriptor and tried to fetch a new one
+    /// (preferring a different hsdir).
+    ///
+    /// If we got a new descriptor, we tried its introduction points
+    /// as part of the same connection attempt.
+    IntroPointsExhausted {
+        /// The onion service.
+        hsid: Sensitive<HsId>,
+        /// Whether we managed to fetch a new descriptor.
+        refetched: bool,
+    },
+}
+
+/// A stream of [`HsClientEvent`]s, returned by [`HsClientConnector::events`](crate::HsClientConnector::events).
+///
+/// If the receiver falls too far behind, new events are discarded
+/// until it catches up.
+//
+// We define this so that we aren't exposing futures::channel in our public API.
+pub struct HsClientEventStream(mpsc::Receiver<HsClientEvent>);
+
+impl futures::Stream for HsClientEventStream {
+    type Item = HsClientEvent;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        self.0.poll_next_unpin(cx)
+    }
+}
+
+/// The number of events that can be waiting in an [`HsClientEventStream`]
+/// before we start discarding new ones.
+const EVENT_QUEUE_LEN: usize = 64;
+
+/// A shared handle that we use to send [`HsClientEvent`]s to every subscriber.
+#[derive(Clone, Default)]
+pub(crate) struct EventSender(Arc<Mutex<Vec<mpsc::Sender<HsClientEvent>>>>);
+
+impl EventSender {
+    /// Send `event` to every subscriber.
+    ///
+    /// Subscribers whose queue is full miss the event.
+    pub(crate) fn send(&self, event: &HsClientEvent) {
+        let mut subscribers = self.0.lock().expect("Poisoned lock");
+        subscribers.retain_mut(|tx| match tx.try_send(event.clone()) {
+            Ok(()) => true,
+            // Forget about the subscribers that went away.
+            Err(e) => e.is_full(),
+        });
+    }
+
+    /// Return a new [`HsClientEventStream`] that receives the events sent from now on.
+    pub(crate) fn subscribe(&self) -> HsClientEventStream {
+        let (tx, rx) = mpsc::channel(EVENT_QUEUE_LEN);
+        self.0.lock().expect("Poisoned lock").push(tx);
+        HsClientEventStream(rx)
+    }
+}
@@ -152,6 +152,7 @@ impl HsClientSecretKeysBuilder {
 #[deftly(role = "KS_hsc_desc_enc")]
 #[deftly(summary = "Descriptor decryption key")]
 #[deftly(ctor_path = "client_desc_enc_keypair_key_specifier_ctor_path")]
+#[deftly(from_ctor_path = "client_desc_enc_keypair_key_specifier_from_ctor_path")]
 /// A key for deriving keys for decrypting HS descriptors (KS_hsc_desc_enc).
 pub struct HsClientDescEncKeypairSpecifier {
     /// The hidden service this authorization key is for.
@@ -163,3 +164,13 @@ fn client_desc_enc_keypair_key_specifier_ctor_path(
 ) -> CTorPath {
     CTorPath::ClientHsDescEncKey(spec.hs_id)
 }
+
+/// The `HsClientDescEncKeypairSpecifier` with the specified `CTorPath`
+fn client_desc_enc_keypair_key_specifier_from_ctor_path(
+    path: &CTorPath,
+) -> Option<HsClientDescEncKeypairSpecifier> {
+    match path {
+        CTorPath::ClientHsDescEncKey(hs_id) => Some(HsClientDescEncKeypairSpecifier::new(*hs_id)),
+        _ => None,
+    }
+}
@@ -42,7 +42,9 @@
 //! <!-- @@ end lint list maintained by maint/add_warning @@ -->
 
 mod connect;
+mod cred;
 mod err;
+mod events;
 mod isol_map;
 mod keys;
 mod pow;
@@ -68,13 +70,16 @@ use tor_netdir::NetDir;
 use tor_proto::circuit::ClientCirc;
 use tor_rtcompat::Runtime;
 
+pub use cred::{CredentialParseError, HsClientCredential};
 pub use err::FailedAttemptError;
 pub use err::{ConnError, DescriptorError, DescriptorErrorDetail, StartupError};
+pub use events::{HsClientEvent, HsClientEventStream};
 pub use keys::{HsClientDescEncKeypairSpecifier, HsClientSecretKeys, HsClientSecretKeysBuilder};
 pub use relay_info::InvalidTarget;
 pub use state::HsClientConnectorConfig;
 
 use err::{rend_pt_identity_for_error, IntroPtIndex, RendPtIdentityForError};
+use events::EventSender;
 use state::{Config, MockableConnectorData, Services};
 
 /// An object that negotiates connections with onion services
@@ -98,6 +103,8 @@ pub struct HsClientConnector<R: Runtime, D: state::MockableConnectorData = conne
     circpool: Arc<HsCircPool<R>>,
     /// Information we are remembering about different onion services.
     services: Arc<Mutex<state::Services<D>>>,
+    /// The subscribers to our [`HsClientEvent`]s.
+    events: EventSender,
     /// For mocking in tests of `state.rs`
     mock_for_state: D::MockGlobalState,
 }
@@ -128,6 +135,7 @@ impl<R: Runtime> HsClientConnector<R, connect::Data> {
             runtime,
             circpool,
             services: Arc::new(Mutex::new(Services::new(config))),
+            events: EventSender::default(),
             mock_for_state: (),
         };
         connector.spawn_housekeeping_task(housekeeping_prompt)?;
@@ -169,6 +177,14 @@ impl<R: Runtime> HsClientConnector<R, connect::Data> {
         Services::get_or_launch_connection(self, netdir, hs_id, isolation, secret_keys)
     }
 
+    /// Return a stream of the notable events that happen while we connect to onion services
+    /// (such as discarding a descriptor because none of its introduction points worked).
+    ///
+    /// The stream only receives the events that happen after this function is called.
+    pub fn events(&self) -> HsClientEventStream {
+        self.events.subscribe()
+    }
+
     /// A deprecated alias for `get_or_launch_circuit`.
     ///
     /// We renamed it to be
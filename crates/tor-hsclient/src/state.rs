@@ -887,6 +887,7 @@ pub(crate) mod test {
             runtime,
             circpool,
             services: Default::default(),
+            events: Default::default(),
             mock_for_state,
         };
         let keys = HsClientSecretKeysBuilder::default().build().unwrap();
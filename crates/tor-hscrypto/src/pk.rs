@@ -158,6 +158,10 @@ impl safelog::Redactable for HsId {
 
         write!(f, "???{}", &unredacted[DATA - 3..])
     }
+
+    fn category(&self) -> safelog::Category {
+        safelog::Category::OnionService
+    }
 }
 
 impl FromStr for HsId {
@@ -229,6 +229,13 @@ pub enum FatalError {
         path: tor_keymgr::ArtiPath,
     },
 
+    /// The descriptor signing key was unable to sign our descriptor.
+    ///
+    /// This can only happen with keys that are not held in memory,
+    /// such as keys held by a hardware token.
+    #[error("unable to sign descriptor")]
+    DescriptorSigning(#[source] Arc<dyn std::error::Error + Send + Sync + 'static>),
+
     /// The identity keypair of the service could not be found in the keystore.
     #[error("Hidden service identity key not found: {0}")]
     MissingHsIdKeypair(HsNickname),
@@ -282,6 +289,7 @@ impl HasKind for FatalError {
             FE::Keystore(e) => e.kind(),
             FE::MissingHsIdKeypair(_) => EK::Internal, // TODO (#1256) This is not always right.
             FE::KeystoreRace { .. } => EK::KeystoreAccessFailed,
+            FE::DescriptorSigning(_) => EK::KeystoreAccessFailed,
             FE::IptKeysFoundUnexpectedly(_) => EK::Internal, // This is indeed quite bad.
             FE::NetdirProviderShutdown(e) => e.kind(),
             FE::MissingField(_) => EK::BadApiUsage,
@@ -135,6 +135,7 @@ pub(crate) use {
     crate::replay::ReplayLog,
     crate::status::PublisherStatusSender,
     crate::status::State,
+    crate::status::{EventSender, OnionServiceEvent, OnionServiceEventStream},
     crate::status::{IptMgrStatusSender, State as IptMgrState},
     crate::status::{OnionServiceStatus, OnionServiceStatusStream, StatusSender},
     crate::time_store,
@@ -220,6 +220,9 @@ pub(crate) struct IptParameters {
     /// for rendezvous circuits.
     #[educe(Debug(ignore))]
     pub(crate) introduce_tx: mpsc::Sender<RendRequest>,
+    /// A sender that we'll use to report the introduction requests that we drop.
+    #[educe(Debug(ignore))]
+    pub(crate) events: EventSender,
     /// Opaque local ID for this introduction point.
     ///
     /// This ID does not change within the lifetime of an [`IptEstablisher`].
@@ -285,6 +288,7 @@ impl IptEstablisher {
             config_rx,
             netdir_provider,
             introduce_tx,
+            events,
             lid,
             target,
             k_sid,
@@ -323,6 +327,7 @@ impl IptEstablisher {
             target,
             k_sid,
             introduce_tx,
+            events,
             extensions: EstIntroExtensionSet {
                 // Updates to this are handled by the IPT manager: when it changes,
                 // this IPT will be replaced with one with the correct parameters.
@@ -603,6 +608,9 @@ struct Reactor<R: Runtime> {
     /// The stream that will receive INTRODUCE2 messages.
     introduce_tx: mpsc::Sender<RendRequest>,
 
+    /// The sender we use to report the INTRODUCE2 messages that we drop.
+    events: EventSender,
+
     /// Mutable state shared with the Establisher, Reactor, and MsgHandler.
     state: Arc<Mutex<EstablisherState>>,
 
@@ -776,8 +784,10 @@ impl<R: Runtime> Reactor<R> {
         let handler = IptMsgHandler {
             established_tx: Some(established_tx),
             introduce_tx: self.introduce_tx.clone(),
+            events: self.events.clone(),
             state: self.state.clone(),
             lid: self.lid,
+            target: self.target.clone(),
             request_context: self.request_context.clone(),
             replay_log,
         };
@@ -832,6 +842,9 @@ struct IptMsgHandler {
     /// A channel used to report Introduce2 messages.
     introduce_tx: mpsc::Sender<RendRequest>,
 
+    /// A sender used to report the Introduce2 messages that we drop.
+    events: EventSender,
+
     /// Keys that we'll need to answer the introduction requests.
     request_context: Arc<RendRequestContext>,
 
@@ -842,6 +855,9 @@ struct IptMsgHandler {
     /// keys).  Used to tag requests.
     lid: IptLocalId,
 
+    /// The introduction point.  Used to report dropped requests.
+    target: RelayIds,
+
     /// A replay log used to detect replayed introduction requests.
     replay_log: futures::lock::OwnedMutexGuard<ReplayLog>,
 }
@@ -966,6 +982,9 @@ impl tor_proto::circuit::MsgHandler for IptMsgHandler {
                             //
                             // See discussion at
                             // https://gitlab.torproject.org/tpo/core/arti/-/merge_requests/1465#note_2928349
+                            self.events.send(&OnionServiceEvent::RendRequestDropped {
+                                relay: self.target.clone(),
+                            });
                             Ok(())
                         }
                     }
@@ -472,6 +472,7 @@ impl Ipt {
             config_rx: new_configs.clone(),
             netdir_provider: imm.dirprovider.clone(),
             introduce_tx: imm.output_rend_reqs.clone(),
+            events: imm.status_tx.event_sender(),
             lid,
             target: relay.clone(),
             k_sid: k_sid.clone(),
@@ -1342,12 +1343,9 @@ impl<R: Runtime, M: Mockable<R>> IptManager<R, M> {
 
         // Keys
 
-        let pat = IptKeySpecifierPattern {
-            nick: Some(self.imm.nick.clone()),
-            role: None,
-            lid: None,
-        }
-        .arti_pattern()?;
+        let pat = IptKeySpecifierPattern::new_any()
+            .nick(self.imm.nick.clone())
+            .arti_pattern()?;
 
         let found = self.imm.keymgr.list_matching(&pat)?;
 
@@ -1592,10 +1590,16 @@ impl<R: Runtime, M: Mockable<R>> IptManager<R, M> {
 
 impl<R: Runtime, M: Mockable<R>> State<R, M> {
     /// Find the `Ipt` with persistent local id `lid`
-    fn ipt_by_lid_mut(&mut self, needle: IptLocalId) -> Option<&mut Ipt> {
-        self.irelays
-            .iter_mut()
-            .find_map(|ir| ir.ipts.iter_mut().find(|ipt| ipt.lid == needle))
+    ///
+    /// Returns the IPT along with the relay it is at.
+    fn ipt_by_lid_mut(&mut self, needle: IptLocalId) -> Option<(&RelayIds, &mut Ipt)> {
+        self.irelays.iter_mut().find_map(|ir| {
+            let relay = &ir.relay;
+            ir.ipts
+                .iter_mut()
+                .find(|ipt| ipt.lid == needle)
+                .map(|ipt| (relay, ipt))
+        })
     }
 
     /// Choose a new relay to use for IPTs
@@ -1606,25 +1610,28 @@ impl<R: Runtime, M: Mockable<R>> State<R, M> {
     ) -> Result<(), ChooseIptError> {
         let netdir = imm.dirprovider.timely_netdir()?;
 
-        let mut rng = self.mockable.thread_rng();
-
-        let relay = {
+        let selector = {
             let exclude_ids = self
                 .irelays
                 .iter()
                 .flat_map(|e| e.relay.identities())
                 .map(|id| id.to_owned())
                 .collect();
-            let selector = RelaySelector::new(
+            let mut selector = RelaySelector::new(
                 RelayUsage::new_intro_point(),
                 RelayExclusion::exclude_identities(exclude_ids),
             );
+            self.mockable.restrict_ipt_selector(&mut selector, &netdir);
             selector
-                .select_relay(&mut rng, &netdir)
-                .0 // TODO: Someday we might want to report why we rejected everything on failure.
-                .ok_or(ChooseIptError::TooFewUsableRelays)?
         };
 
+        let mut rng = self.mockable.thread_rng();
+
+        let relay = selector
+            .select_relay(&mut rng, &netdir)
+            .0 // TODO: Someday we might want to report why we rejected everything on failure.
+            .ok_or(ChooseIptError::TooFewUsableRelays)?;
+
         let lifetime_low = netdir
             .params()
             .hs_intro_min_lifetime
@@ -1662,7 +1669,7 @@ impl<R: Runtime, M: Mockable<R>> State<R, M> {
 
     /// Update `self`'s status tracking for one introduction point
     fn handle_ipt_status_update(&mut self, imm: &Immutable<R>, lid: IptLocalId, update: IptStatus) {
-        let Some(ipt) = self.ipt_by_lid_mut(lid) else {
+        let Some((relay, ipt)) = self.ipt_by_lid_mut(lid) else {
             // update from now-withdrawn IPT, ignore it (can happen due to the IPT being a task)
             return;
         };
@@ -1683,6 +1690,7 @@ impl<R: Runtime, M: Mockable<R>> State<R, M> {
 
         let now = || imm.runtime.now();
 
+        let was_good = matches!(ipt.status_last, TS::Good { .. });
         let started = match &ipt.status_last {
             TS::Establishing { started, .. } => Ok(*started),
             TS::Faulty { started, .. } => *started,
@@ -1709,6 +1717,17 @@ impl<R: Runtime, M: Mockable<R>> State<R, M> {
             }
             ISS::Faulty(error) => TS::Faulty { started, error },
         };
+
+        let relay = relay.clone();
+        match (was_good, &ipt.status_last) {
+            (false, TS::Good { .. }) => imm
+                .status_tx
+                .note_event(OnionServiceEvent::IntroPointEstablished { relay }),
+            (true, TS::Establishing { .. } | TS::Faulty { .. }) => imm
+                .status_tx
+                .note_event(OnionServiceEvent::IntroPointLost { relay }),
+            _ => {}
+        }
     }
 }
 
@@ -1738,6 +1757,11 @@ pub(crate) trait Mockable<R>: Debug + Send + Sync + Sized + 'static {
     /// Call `IptEstablisher::start_accepting`
     fn start_accepting(&self, establisher: &ErasedIptEstablisher);
 
+    /// Call `HsCircPool::restrict_endpoint_selector`
+    ///
+    /// (Unless the `hs-endpoint-restrictions` feature is enabled, this does nothing.)
+    fn restrict_ipt_selector<'a>(&self, selector: &mut RelaySelector<'a>, netdir: &'a NetDir);
+
     /// Allow tests to see when [`IptManager::expire_old_ipts_external_persistent_state`]
     /// is called.
     ///
@@ -1771,6 +1795,15 @@ impl<R: Runtime> Mockable<R> for Real<R> {
         establisher.start_accepting();
     }
 
+    #[cfg_attr(
+        not(feature = "hs-endpoint-restrictions"),
+        allow(unused_variables, clippy::unused_self)
+    )]
+    fn restrict_ipt_selector<'a>(&self, selector: &mut RelaySelector<'a>, netdir: &'a NetDir) {
+        #[cfg(feature = "hs-endpoint-restrictions")]
+        self.circ_pool.restrict_endpoint_selector(selector, netdir);
+    }
+
     fn expire_old_ipts_external_persistent_state_hook(&self) {}
 }
 
@@ -1864,6 +1897,13 @@ mod test {
 
         fn start_accepting(&self, _establisher: &ErasedIptEstablisher) {}
 
+        fn restrict_ipt_selector<'a>(
+            &self,
+            _selector: &mut RelaySelector<'a>,
+            _netdir: &'a NetDir,
+        ) {
+        }
+
         fn expire_old_ipts_external_persistent_state_hook(&self) {
             let mut expect = self.expect_expire_ipts_calls.lock().unwrap();
             eprintln!("expire_old_ipts_external_persistent_state_hook, expect={expect}");
@@ -1889,6 +1929,7 @@ mod test {
         #[allow(dead_code)] // ensures temp dir lifetime; paths stored in self
         temp_dir: &'d TestTempDir,
         expect_expire_ipts_calls: Arc<Mutex<usize>>, // use usize::MAX to not mind
+        events: OnionServiceEventStream,
     }
 
     impl<'d> MockedIptManager<'d> {
@@ -1937,7 +1978,8 @@ mod test {
 
             let keymgr = create_keymgr(temp_dir);
             let keymgr = keymgr.into_untracked(); // OK because our return value captures 'd
-            let status_tx = StatusSender::new(OnionServiceStatus::new_shutdown()).into();
+            let status_tx = StatusSender::new(OnionServiceStatus::new_shutdown());
+            let events = status_tx.subscribe_events();
             let mgr = IptManager::new(
                 runtime.clone(),
                 Arc::new(dir),
@@ -1948,7 +1990,7 @@ mod test {
                 &state_handle,
                 mocks,
                 keymgr,
-                status_tx,
+                status_tx.into(),
             )
             .unwrap();
 
@@ -1961,6 +2003,7 @@ mod test {
                 cfg_tx,
                 temp_dir,
                 expect_expire_ipts_calls,
+                events,
             }
         }
 
@@ -2127,4 +2170,48 @@ mod test {
             m.shutdown_check_no_tasks(&runtime).await;
         });
     }
+
+    #[test]
+    fn test_mgr_events() {
+        MockRuntime::test_with_various(|runtime| async move {
+            let temp_dir = test_temp_dir!();
+
+            let mut m = MockedIptManager::startup(runtime.clone(), &temp_dir, 0, 1);
+            runtime.progress_until_stalled().await;
+            assert!(m.events.next().now_or_never().is_none());
+
+            let (id, target) = {
+                let estabs = m.estabs.lock().unwrap();
+                let (id, estab) = estabs.iter().next().unwrap();
+                (id, estab.params.target.clone())
+            };
+            let estabs = m.estabs.clone();
+            let set_status = |status| {
+                estabs.lock().unwrap()[id].st_tx.borrow_mut().status = status;
+            };
+
+            // The IPT becomes good...
+            set_status(IptStatusStatus::Good(GoodIptDetails {
+                link_specifiers: vec![],
+                ipt_kp_ntor: [0x55; 32].into(),
+            }));
+            runtime.progress_until_stalled().await;
+            match m.events.next().now_or_never().flatten().unwrap() {
+                OnionServiceEvent::IntroPointEstablished { relay } => assert_eq!(relay, target),
+                other => panic!("{other:?}"),
+            }
+            assert!(m.events.next().now_or_never().is_none());
+
+            // ...and then stops working.
+            set_status(IptStatusStatus::Faulty(None));
+            runtime.progress_until_stalled().await;
+            match m.events.next().now_or_never().flatten().unwrap() {
+                OnionServiceEvent::IntroPointLost { relay } => assert_eq!(relay, target),
+                other => panic!("{other:?}"),
+            }
+            assert!(m.events.next().now_or_never().is_none());
+
+            m.shutdown_check_no_tasks(&runtime).await;
+        });
+    }
 }
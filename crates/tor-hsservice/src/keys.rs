@@ -47,6 +47,7 @@ define_derive_deftly! {
 #[deftly(summary = "Public part of the identity key")]
 #[deftly(keypair_specifier = "HsIdKeypairSpecifier")]
 #[deftly(ctor_path = "hsid_public_key_specifier_ctor_path")]
+#[deftly(from_ctor_path = "hsid_public_key_specifier_from_ctor_path")]
 /// The public part of the identity key of the service.
 pub struct HsIdPublicKeySpecifier {
     /// The nickname of the  hidden service.
@@ -61,24 +62,30 @@ fn hsid_public_key_specifier_ctor_path(spec: &HsIdPublicKeySpecifier) -> CTorPat
     }
 }
 
+/// The `HsIdPublicKeySpecifier` with the specified `CTorPath`
+fn hsid_public_key_specifier_from_ctor_path(path: &CTorPath) -> Option<HsIdPublicKeySpecifier> {
+    match path {
+        CTorPath::Service {
+            nickname,
+            path: CTorServicePath::PublicKey,
+        } => Some(HsIdPublicKeySpecifier::new(nickname.clone())),
+        _ => None,
+    }
+}
+
 #[derive(Deftly, PartialEq, Debug, Constructor)]
 #[derive_deftly(KeySpecifier)]
 #[deftly(prefix = "hss")]
 #[deftly(role = "KS_hs_id")]
 #[deftly(summary = "Long-term identity keypair")]
 #[deftly(ctor_path = "hsid_keypair_key_specifier_ctor_path")]
+#[deftly(from_ctor_path = "hsid_keypair_key_specifier_from_ctor_path")]
 /// The long-term identity keypair of the service.
 pub struct HsIdKeypairSpecifier {
     /// The nickname of the  hidden service.
     pub(crate) nickname: HsNickname,
 }
 
-impl From<&HsIdPublicKeySpecifier> for HsIdKeypairSpecifier {
-    fn from(hs_id_public_key_specifier: &HsIdPublicKeySpecifier) -> HsIdKeypairSpecifier {
-        HsIdKeypairSpecifier::new(hs_id_public_key_specifier.nickname.clone())
-    }
-}
-
 /// The `CTorPath` of HsIdKeypairKeySpecifier
 fn hsid_keypair_key_specifier_ctor_path(spec: &HsIdKeypairSpecifier) -> CTorPath {
     CTorPath::Service {
@@ -87,6 +94,17 @@ fn hsid_keypair_key_specifier_ctor_path(spec: &HsIdKeypairSpecifier) -> CTorPath
     }
 }
 
+/// The `HsIdKeypairSpecifier` with the specified `CTorPath`
+fn hsid_keypair_key_specifier_from_ctor_path(path: &CTorPath) -> Option<HsIdKeypairSpecifier> {
+    match path {
+        CTorPath::Service {
+            nickname,
+            path: CTorServicePath::PrivateKey,
+        } => Some(HsIdKeypairSpecifier::new(nickname.clone())),
+        _ => None,
+    }
+}
+
 #[derive(Deftly, PartialEq, Debug, Constructor)]
 #[derive_deftly(KeySpecifier, HsTimePeriodKeySpecifier)]
 #[deftly(prefix = "hss")]
@@ -116,17 +134,6 @@ pub struct BlindIdPublicKeySpecifier {
     pub(crate) period: TimePeriod,
 }
 
-impl From<&BlindIdPublicKeySpecifier> for BlindIdKeypairSpecifier {
-    fn from(
-        hs_blind_id_public_key_specifier: &BlindIdPublicKeySpecifier,
-    ) -> BlindIdKeypairSpecifier {
-        BlindIdKeypairSpecifier::new(
-            hs_blind_id_public_key_specifier.nickname.clone(),
-            hs_blind_id_public_key_specifier.period,
-        )
-    }
-}
-
 #[derive(Deftly, PartialEq, Debug, Constructor)]
 #[derive_deftly(KeySpecifier, HsTimePeriodKeySpecifier)]
 #[deftly(prefix = "hss")]
@@ -179,43 +186,49 @@ pub(crate) fn expire_publisher_keys(
     // Only remove the keys of the hidden service
     // that concerns us
     let arti_pat = tor_keymgr::KeyPathPattern::Arti(format!("hss/{}/*", &nickname));
-    let possibly_relevant_keys = keymgr.list_matching(&arti_pat)?;
 
-    for entry in possibly_relevant_keys {
+    let outcome = keymgr.sweep(&arti_pat, |entry| {
         let key_path = entry.key_path();
-        // Remove the key identified by `spec` if it's no longer relevant
-        let remove_if_expired = |spec: &dyn HsTimePeriodKeySpecifier| {
+        // Check whether the key identified by `spec` is no longer relevant
+        let is_expired = |spec: &dyn HsTimePeriodKeySpecifier| {
             if spec.nickname() != nickname {
                 return Err(internal!(
                     "keymgr gave us key {spec:?} that doesn't match our pattern {arti_pat:?}"
                 )
                 .into());
             }
-            let is_expired = relevant_periods
-                .iter()
-                .all(|p| &p.time_period() != spec.period());
-
-            if is_expired {
-                keymgr.remove_entry(&entry)?;
-            }
 
-            tor_keymgr::Result::Ok(())
+            tor_keymgr::Result::Ok(
+                relevant_periods
+                    .iter()
+                    .all(|p| &p.time_period() != spec.period()),
+            )
         };
 
-        /// Remove the specified key, if it's no longer relevant.
-        macro_rules! remove_if_expired {
+        /// Return whether the specified key is no longer relevant,
+        /// if it's a key of type `$K`.
+        macro_rules! check_expired {
             ($K:ty) => {{
                 if let Ok(spec) = <$K>::try_from(key_path) {
-                    remove_if_expired(&spec)?;
+                    return is_expired(&spec);
                 }
             }};
         }
 
         // TODO: any invalid/malformed keys are ignored (rather than
         // removed).
-        remove_if_expired!(BlindIdPublicKeySpecifier);
-        remove_if_expired!(BlindIdKeypairSpecifier);
-        remove_if_expired!(DescSigningKeypairSpecifier);
+        check_expired!(BlindIdPublicKeySpecifier);
+        check_expired!(BlindIdKeypairSpecifier);
+        check_expired!(DescSigningKeypairSpecifier);
+
+        Ok(false)
+    })?;
+
+    if !outcome.unrecognized().is_empty() {
+        debug!(
+            "{nickname}: not removing {} unrecognized key(s)",
+            outcome.unrecognized().len()
+        );
     }
 
     Ok(())
@@ -447,6 +447,18 @@ impl RunningOnionService {
             .subscribe()
     }
 
+    /// Return a stream of the notable events in the life of this onion service
+    /// (introduction points established or lost, descriptors uploaded, and so on).
+    ///
+    /// The stream only receives the events that happen after this function is called.
+    pub fn events(&self) -> OnionServiceEventStream {
+        self.inner
+            .lock()
+            .expect("poisoned lock")
+            .status_tx
+            .subscribe_events()
+    }
+
     /// Tell this onion service to begin running, and return a
     /// stream of rendezvous requests on the service.
     ///
@@ -457,6 +457,7 @@ mod test {
             };
 
             let mut status_rx = status_tx.subscribe();
+            let mut events_rx = status_tx.subscribe_events();
             let publisher: Publisher<MockRuntime, MockReactorState<_>> = Publisher::new(
                 runtime.clone(),
                 nickname,
@@ -498,6 +499,17 @@ mod test {
                 // The test network doesn't have an SRV for the previous TP,
                 // so we are "unreachable".
                 assert_eq!(State::DegradedUnreachable, status.state());
+
+                // Each HsDir reported a successful upload.
+                let events: Vec<_> =
+                    iter::from_fn(|| events_rx.next().now_or_never().flatten()).collect();
+                assert_eq!(events.len(), expected_upload_count);
+                for event in events {
+                    assert!(
+                        matches!(event, OnionServiceEvent::DescriptorUploaded { .. }),
+                        "{event:?}"
+                    );
+                }
             }
             assert!(status.current_problem().is_none());
 
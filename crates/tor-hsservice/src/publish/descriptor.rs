@@ -2,7 +2,10 @@
 
 use super::*;
 use crate::config::OnionServiceConfigPublisherView;
+use tor_bytes::EncodeError;
 use tor_cell::chancell::msg::HandshakeType;
+use tor_keymgr::{Ed25519Signer, Ed25519SignerAdapter};
+use tor_llcrypto::pk::ed25519::Ed25519PublicKey as _;
 
 /// Build the descriptor.
 ///
@@ -59,11 +62,24 @@ pub(super) fn build_sign<Rng: RngCore + CryptoRng>(
     let subcredential = hsid.compute_subcredential(&blind_id_key, period);
 
     let hs_desc_sign_key_spec = DescSigningKeypairSpecifier::new(nickname.clone(), period);
-    let hs_desc_sign = keymgr.get_or_generate::<HsDescSigningKeypair>(
-        &hs_desc_sign_key_spec,
-        keystore_selector,
-        rng,
-    )?;
+    // The descriptor signing key may be held somewhere we can't read it from
+    // (a hardware token, say), so we only ask the keystore for something that can sign.
+    let hs_desc_sign: Box<dyn Ed25519Signer> =
+        match keymgr.get_ed25519_signer::<HsDescSigningKeypair>(&hs_desc_sign_key_spec)? {
+            Some(signer) => signer,
+            None => {
+                let keypair: ed25519::Keypair = keymgr
+                    .generate::<HsDescSigningKeypair>(
+                        &hs_desc_sign_key_spec,
+                        keystore_selector,
+                        rng,
+                        false,
+                    )?
+                    .into();
+                Box::new(keypair)
+            }
+        };
+    let hs_desc_sign = Ed25519SignerAdapter::new(&*hs_desc_sign);
 
     // TODO #1028: support introduction-layer authentication.
     let auth_required = None;
@@ -105,7 +121,7 @@ pub(super) fn build_sign<Rng: RngCore + CryptoRng>(
     }
 
     let desc_signing_key_cert = create_desc_sign_key_cert(
-        &hs_desc_sign.as_ref().verifying_key(),
+        hs_desc_sign.public_key(),
         &blind_id_kp,
         hs_desc_sign_cert_expiry,
     )
@@ -115,7 +131,7 @@ pub(super) fn build_sign<Rng: RngCore + CryptoRng>(
 
     let desc = HsDescBuilder::default()
         .blinded_id(&(&blind_id_kp).into())
-        .hs_desc_sign(hs_desc_sign.as_ref())
+        .hs_desc_sign(&hs_desc_sign)
         .hs_desc_sign_cert(desc_signing_key_cert)
         .create2_formats(CREATE2_FORMATS)
         .auth_required(auth_required)
@@ -128,7 +144,10 @@ pub(super) fn build_sign<Rng: RngCore + CryptoRng>(
         .subcredential(subcredential)
         .auth_clients(auth_clients.as_deref())
         .build_sign(rng)
-        .map_err(|e| into_internal!("failed to build descriptor")(e))?;
+        .map_err(|e| match e {
+            EncodeError::Signing(cause) => FatalError::DescriptorSigning(cause),
+            e => into_internal!("failed to build descriptor")(e).into(),
+        })?;
 
     Ok(VersionedDescriptor {
         desc,
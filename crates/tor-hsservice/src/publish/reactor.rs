@@ -852,6 +852,20 @@ impl<R: Runtime, M: Mockable> Reactor<R, M> {
                 continue;
             };
 
+            let hsdir = upload_res.relay_ids.clone();
+            let revision_counter = upload_res.revision_counter.into();
+            self.imm.status_tx.note_event(match &upload_res.upload_res {
+                Ok(()) => OnionServiceEvent::DescriptorUploaded {
+                    hsdir,
+                    revision_counter,
+                },
+                Err(e) => OnionServiceEvent::DescriptorUploadFailed {
+                    hsdir,
+                    revision_counter,
+                    error: e.clone(),
+                },
+            });
+
             if upload_res.upload_res.is_ok() {
                 let update_last_successful = match period.last_successful {
                     None => true,
@@ -97,11 +97,9 @@ impl RendRequestContext {
             .get::<HsIdKey>(&hsid_key_spec)?
             .ok_or_else(|| FatalError::MissingHsIdKeypair(self.nickname.clone()))?;
 
-        let pattern = BlindIdKeypairSpecifierPattern {
-            nickname: Some(self.nickname.clone()),
-            period: None,
-        }
-        .arti_pattern()?;
+        let pattern = BlindIdKeypairSpecifierPattern::new_any()
+            .nickname(self.nickname.clone())
+            .arti_pattern()?;
 
         let blind_id_kps: Vec<(HsBlindIdKeypair, TimePeriod)> = self
             .keymgr
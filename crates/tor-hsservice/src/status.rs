@@ -259,14 +259,135 @@ impl futures::Stream for OnionServiceStatusStream {
     }
 }
 
-/// A shared handle to a postage::watch::Sender that we can use to update an OnionServiceStatus.
+/// A notable change in the state of an onion service.
+///
+/// Unlike an [`OnionServiceStatus`], which summarizes the current state of the service,
+/// an `OnionServiceEvent` is reported for each change as it happens,
+/// so that monitoring tools can notice (and alert on) availability regressions
+/// that a summary would hide.
+///
+/// Events are delivered by an [`OnionServiceEventStream`].
+///
+/// This type implements [`Serialize`](serde::Serialize), so that the events can be
+/// forwarded as-is to RPC clients or to a log.
+//
+// TODO: Report changes in the proof-of-work effort, once we implement
+// proof-of-work on the service side.
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum OnionServiceEvent {
+    /// We established an introduction point.
+    IntroPointEstablished {
+        /// The relay that is acting as the introduction point.
+        relay: RelayIds,
+    },
+    /// An introduction point that we had established stopped working.
+    ///
+    /// We will try to re-establish it, or to replace it with another one.
+    IntroPointLost {
+        /// The relay that was acting as the introduction point.
+        relay: RelayIds,
+    },
+    /// We uploaded our descriptor to an HsDir.
+    DescriptorUploaded {
+        /// The HsDir.
+        hsdir: RelayIds,
+        /// The revision counter of the descriptor we uploaded.
+        revision_counter: u64,
+    },
+    /// We gave up trying to upload our descriptor to an HsDir.
+    DescriptorUploadFailed {
+        /// The HsDir.
+        hsdir: RelayIds,
+        /// The revision counter of the descriptor we tried to upload.
+        revision_counter: u64,
+        /// Why the upload failed.
+        #[serde(serialize_with = "serialize_report")]
+        error: DescUploadRetryError,
+    },
+    /// We dropped an introduction request,
+    /// because we already had too many requests waiting to be handled.
+    ///
+    /// This usually means that the service is under a denial-of-service attack,
+    /// or that it is not handling rendezvous requests fast enough.
+    RendRequestDropped {
+        /// The relay at which we received the introduction request.
+        relay: RelayIds,
+    },
+}
+
+/// Serialize an error as the (string) report of its chain of causes.
+fn serialize_report<S: serde::Serializer>(
+    error: &DescUploadRetryError,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&error.report())
+}
+
+/// A stream of [`OnionServiceEvent`]s, returned by an onion service.
+///
+/// Unlike an [`OnionServiceStatusStream`], events are not coalesced.
+/// Instead, if the receiver falls too far behind, new events are discarded
+/// until it catches up.
+//
+// We define this so that we aren't exposing futures::channel in our public API.
+pub struct OnionServiceEventStream(mpsc::Receiver<OnionServiceEvent>);
+
+impl futures::Stream for OnionServiceEventStream {
+    type Item = OnionServiceEvent;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        self.0.poll_next_unpin(cx)
+    }
+}
+
+/// The number of events that can be waiting in an [`OnionServiceEventStream`]
+/// before we start discarding new ones.
+const EVENT_QUEUE_LEN: usize = 64;
+
+/// A shared handle that we use to send [`OnionServiceEvent`]s to every subscriber.
+#[derive(Clone, Default)]
+pub(crate) struct EventSender(Arc<Mutex<Vec<mpsc::Sender<OnionServiceEvent>>>>);
+
+impl EventSender {
+    /// Send `event` to every subscriber.
+    ///
+    /// Subscribers whose queue is full miss the event.
+    pub(crate) fn send(&self, event: &OnionServiceEvent) {
+        let mut subscribers = self.0.lock().expect("Poisoned lock");
+        subscribers.retain_mut(|tx| match tx.try_send(event.clone()) {
+            Ok(()) => true,
+            // Forget about the subscribers that went away.
+            Err(e) => e.is_full(),
+        });
+    }
+
+    /// Return a new [`OnionServiceEventStream`] that receives the events sent from now on.
+    pub(crate) fn subscribe(&self) -> OnionServiceEventStream {
+        let (tx, rx) = mpsc::channel(EVENT_QUEUE_LEN);
+        self.0.lock().expect("Poisoned lock").push(tx);
+        OnionServiceEventStream(rx)
+    }
+}
+
+/// A shared handle to a postage::watch::Sender that we can use to update an OnionServiceStatus,
+/// and to the [`EventSender`] of the service.
 //
 // TODO: Possibly, we don't need this to be Clone: as we implement the code
 // that adjusts the status, we might find that only a single location needs to
 // hold the Sender.  If that turns out to be the case, we should remove the
 // `Arc<Mutex<.>>` here.  If not, we should remove this comment.
 #[derive(Clone)]
-pub(crate) struct StatusSender(Arc<Mutex<postage::watch::Sender<OnionServiceStatus>>>);
+pub(crate) struct StatusSender {
+    /// The sender for the status.
+    status: Arc<Mutex<postage::watch::Sender<OnionServiceStatus>>>,
+    /// The sender for the events.
+    events: EventSender,
+}
 
 /// A handle that can be used by the [`IptManager`]
 /// to update the [`OnionServiceStatus`].
@@ -316,12 +437,17 @@ macro_rules! impl_status_sender {
             /// and notifies all listeners.
             pub(crate) fn send(&self, state: State, err: Option<Problem>) {
                 let sender = &self.0;
-                let mut tx = sender.0.lock().expect("Poisoned lock");
+                let mut tx = sender.status.lock().expect("Poisoned lock");
                 let mut svc_status = tx.borrow().clone();
                 svc_status.$field.state = state;
                 svc_status.$field.latest_error = err;
                 tx.maybe_send(|_| svc_status);
             }
+
+            /// Report `event` to all the subscribers to the events of the service.
+            pub(crate) fn note_event(&self, event: OnionServiceEvent) {
+                self.0.events.send(&event);
+            }
         }
     };
 }
@@ -333,17 +459,32 @@ impl StatusSender {
     /// Create a new StatusSender with a given initial status.
     pub(crate) fn new(initial_status: OnionServiceStatus) -> Self {
         let (tx, _) = postage::watch::channel_with(initial_status);
-        StatusSender(Arc::new(Mutex::new(tx)))
+        StatusSender {
+            status: Arc::new(Mutex::new(tx)),
+            events: EventSender::default(),
+        }
     }
 
     /// Return a copy of the current status.
     pub(crate) fn get(&self) -> OnionServiceStatus {
-        self.0.lock().expect("Poisoned lock").borrow().clone()
+        self.status.lock().expect("Poisoned lock").borrow().clone()
     }
 
     /// Return a new OnionServiceStatusStream to return events from this StatusSender.
     pub(crate) fn subscribe(&self) -> OnionServiceStatusStream {
-        OnionServiceStatusStream(self.0.lock().expect("Poisoned lock").subscribe())
+        OnionServiceStatusStream(self.status.lock().expect("Poisoned lock").subscribe())
+    }
+
+    /// Return a new OnionServiceEventStream to return the events sent through this StatusSender.
+    pub(crate) fn subscribe_events(&self) -> OnionServiceEventStream {
+        self.events.subscribe()
+    }
+}
+
+impl IptMgrStatusSender {
+    /// Return the [`EventSender`] of the service.
+    pub(crate) fn event_sender(&self) -> EventSender {
+        self.0.events.clone()
     }
 }
 
@@ -353,6 +494,11 @@ impl PublisherStatusSender {
     pub(crate) fn subscribe(&self) -> OnionServiceStatusStream {
         self.0.subscribe()
     }
+
+    /// Return a new OnionServiceEventStream to return the events sent through this StatusSender.
+    pub(crate) fn subscribe_events(&self) -> OnionServiceEventStream {
+        self.0.subscribe_events()
+    }
 }
 
 #[cfg(test)]
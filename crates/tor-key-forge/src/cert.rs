@@ -0,0 +1,389 @@
+//! Creation and validation of Tor ed25519 certificates.
+//!
+//! These functions sign with an [`Ed25519Signer`], rather than with a key
+//! held in memory, so that certificates can be minted from keys in any
+//! key store, including those from which keys cannot be extracted.
+
+use std::time::SystemTime;
+
+use tor_cert::{
+    CertEncodeError, CertError, CertType, CertifiedKey, Ed25519Cert, Ed25519CertConstructor,
+    EncodedEd25519Cert,
+};
+use tor_checkable::{SelfSigned as _, TimeValidityError, Timebound as _};
+use tor_llcrypto::pk::ed25519;
+
+use crate::{Ed25519Signer, Ed25519SignerAdapter, Error, KeyType, Result};
+
+/// Create a certificate of type `cert_type` for `subject`, signed by `signer`.
+///
+/// The certificate expires at `expiration`, and includes the signing key
+/// in a `signed-with-ed25519-key` extension, as is usual for the
+/// certificates in onion service descriptors and relay link authentication.
+///
+/// Use [`sign_ed25519_cert`] if you need more control over the certificate.
+pub fn create_ed25519_cert(
+    signer: &dyn Ed25519Signer,
+    cert_type: CertType,
+    subject: CertifiedKey,
+    expiration: SystemTime,
+) -> Result<EncodedEd25519Cert> {
+    let signing_key = signer.public_key().into();
+    sign_ed25519_cert(
+        Ed25519Cert::constructor()
+            .cert_type(cert_type)
+            .expiration(expiration)
+            .signing_key(signing_key)
+            .cert_key(subject),
+        signer,
+    )
+}
+
+/// Encode the certificate described by `constructor`, and sign it with `signer`.
+pub fn sign_ed25519_cert(
+    constructor: &Ed25519CertConstructor,
+    signer: &dyn Ed25519Signer,
+) -> Result<EncodedEd25519Cert> {
+    Ok(constructor.encode_and_sign(&Ed25519SignerAdapter::new(signer))?)
+}
+
+/// Decode the certificate in `encoded`, and check that it is a valid
+/// certificate of type `cert_type` at time `now`.
+///
+/// If `signing_key` is provided, the certificate must be signed by that key;
+/// otherwise, it must contain its own signing key.
+pub fn validate_ed25519_cert(
+    encoded: &[u8],
+    cert_type: CertType,
+    signing_key: Option<&ed25519::Ed25519Identity>,
+    now: SystemTime,
+) -> std::result::Result<Ed25519Cert, CertValidationError> {
+    let cert = Ed25519Cert::decode(encoded)?;
+    let found = cert.peek_cert_type();
+    if found != cert_type {
+        return Err(CertValidationError::WrongType {
+            expected: cert_type,
+            found,
+        });
+    }
+    let cert = match signing_key {
+        Some(key) => cert.should_be_signed_with(key)?,
+        None => cert.should_have_signing_key()?,
+    };
+    Ok(cert.check_signature()?.check_valid_at(&now)?)
+}
+
+/// An error from [`validate_ed25519_cert`].
+#[derive(thiserror::Error, Debug, Clone)]
+#[non_exhaustive]
+pub enum CertValidationError {
+    /// The certificate could not be decoded.
+    #[error("Unable to decode certificate")]
+    Decode(#[from] tor_bytes::Error),
+
+    /// The certificate was not of the expected type.
+    #[error("Expected a certificate of type {expected}, found {found}")]
+    WrongType {
+        /// The type we wanted.
+        expected: CertType,
+        /// The type of the certificate.
+        found: CertType,
+    },
+
+    /// The certificate had the wrong key, or a bad signature.
+    #[error("Invalid certificate")]
+    Invalid(#[from] CertError),
+
+    /// The certificate was not valid at the time we asked about.
+    #[error("Certificate is not currently valid")]
+    Time(#[from] TimeValidityError),
+}
+
+/// The label of the armored encoding of a certificate.
+///
+/// See [`EncodableCert::to_armored`].
+const CERT_ARMOR_LABEL: &str = "TOR CERTIFICATE";
+
+/// A certificate that can be stored in a key store.
+///
+/// This is the counterpart of [`EncodableKey`](crate::EncodableKey) for certificates.
+/// Key stores persist certificates in the format given by
+/// [`to_entry_bytes`](EncodableCert::to_entry_bytes),
+/// and hand them back to be decoded with [`from_entry_bytes`](EncodableCert::from_entry_bytes).
+pub trait EncodableCert: Sized {
+    /// The type of key store entry this certificate is stored in.
+    fn key_type() -> KeyType;
+
+    /// Encode this certificate as the contents of a key store entry.
+    fn to_entry_bytes(&self) -> Vec<u8>;
+
+    /// Decode the contents of a key store entry written by
+    /// [`to_entry_bytes`](EncodableCert::to_entry_bytes).
+    ///
+    /// Returns an error if `entry` is not a certificate of this type,
+    /// or if the certificate can't be decoded.
+    /// The certificate is not validated.
+    fn from_entry_bytes(entry: &[u8]) -> Result<Self>;
+
+    /// Encode this certificate as text, in the armored format
+    /// OpenSSH uses for its private keys.
+    ///
+    /// The text is the [key store entry](EncodableCert::to_entry_bytes), base64-encoded,
+    /// between `-----BEGIN TOR CERTIFICATE-----` and `-----END TOR CERTIFICATE-----` lines.
+    fn to_armored(&self) -> Result<String> {
+        pem_rfc7468::encode_string(
+            CERT_ARMOR_LABEL,
+            pem_rfc7468::LineEnding::LF,
+            &self.to_entry_bytes(),
+        )
+        .map_err(Error::MalformedArmoredCert)
+    }
+
+    /// Decode a certificate encoded with [`to_armored`](EncodableCert::to_armored).
+    ///
+    /// The certificate is not validated.
+    fn from_armored(armored: &str) -> Result<Self> {
+        let (label, entry) =
+            pem_rfc7468::decode_vec(armored.as_bytes()).map_err(Error::MalformedArmoredCert)?;
+        if label != CERT_ARMOR_LABEL {
+            return Err(Error::MalformedArmoredCert(pem_rfc7468::Error::Label));
+        }
+        Self::from_entry_bytes(&entry)
+    }
+}
+
+/// A certificate that can be converted to an [`EncodableCert`].
+///
+/// This is the counterpart of [`ToEncodableKey`](crate::ToEncodableKey) for certificates:
+/// it lets a type that wraps a certificate be stored in a key store,
+/// in the format of its [`Cert`](ToEncodableCert::Cert).
+pub trait ToEncodableCert: Sized {
+    /// The certificate type this can be converted to/from.
+    type Cert: EncodableCert;
+
+    /// Convert this certificate to a type that implements [`EncodableCert`].
+    fn to_encodable_cert(self) -> Self::Cert;
+
+    /// Convert an [`EncodableCert`] to this type.
+    fn from_encodable_cert(cert: Self::Cert) -> Self;
+}
+
+/// The tag stored certificates begin with.
+///
+/// This is the tag C Tor uses for the certificates it stores on disk.
+const CERT_ENTRY_TAG: &[u8] = b"== ed25519v1-cert: type4 ==\0\0\0\0\0";
+
+/// An encoded Tor ed25519 certificate, as stored in a key store.
+///
+/// Unlike an [`EncodedEd25519Cert`], which can only be obtained by signing
+/// a new certificate, a `StoredEd25519Cert` can be read back from a key store,
+/// so it has not necessarily been validated:
+/// use [`validate`](StoredEd25519Cert::validate) before relying on it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoredEd25519Cert(Vec<u8>);
+
+impl StoredEd25519Cert {
+    /// Return the encoded certificate.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Decode this certificate, and check that it is a valid
+    /// certificate of type `cert_type` at time `now`.
+    ///
+    /// See [`validate_ed25519_cert`].
+    pub fn validate(
+        &self,
+        cert_type: CertType,
+        signing_key: Option<&ed25519::Ed25519Identity>,
+        now: SystemTime,
+    ) -> std::result::Result<Ed25519Cert, CertValidationError> {
+        validate_ed25519_cert(&self.0, cert_type, signing_key, now)
+    }
+}
+
+impl EncodableCert for StoredEd25519Cert {
+    fn key_type() -> KeyType {
+        KeyType::Ed25519TorCert
+    }
+
+    /// Encode this certificate as the contents of a key store entry.
+    ///
+    /// The entry is in the same format C Tor uses for its certificate files:
+    /// a 32-byte tag, followed by the encoded certificate.
+    fn to_entry_bytes(&self) -> Vec<u8> {
+        [CERT_ENTRY_TAG, &self.0].concat()
+    }
+
+    fn from_entry_bytes(entry: &[u8]) -> Result<Self> {
+        let cert = entry.strip_prefix(CERT_ENTRY_TAG).ok_or_else(|| {
+            Error::MalformedCert(tor_bytes::Error::InvalidMessage(
+                "missing certificate tag".into(),
+            ))
+        })?;
+        let _ = Ed25519Cert::decode(cert).map_err(Error::MalformedCert)?;
+
+        Ok(Self(cert.to_vec()))
+    }
+}
+
+impl ToEncodableCert for StoredEd25519Cert {
+    type Cert = StoredEd25519Cert;
+
+    fn to_encodable_cert(self) -> Self::Cert {
+        self
+    }
+
+    fn from_encodable_cert(cert: Self::Cert) -> Self {
+        cert
+    }
+}
+
+impl From<EncodedEd25519Cert> for StoredEd25519Cert {
+    fn from(cert: EncodedEd25519Cert) -> Self {
+        Self(cert.into())
+    }
+}
+
+impl From<CertEncodeError> for Error {
+    fn from(e: CertEncodeError) -> Self {
+        Error::CertEncode(e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use std::time::Duration;
+    use tor_basic_utils::test_rng::testing_rng;
+
+    /// A signer that refuses to sign, like a hardware token that was unplugged.
+    struct Unplugged(ed25519::PublicKey);
+
+    impl Ed25519Signer for Unplugged {
+        fn public_key(&self) -> ed25519::PublicKey {
+            self.0
+        }
+
+        fn sign(&self, _msg: &[u8]) -> Result<ed25519::Signature> {
+            Err(tor_error::internal!("token unplugged").into())
+        }
+    }
+
+    #[test]
+    fn create_and_validate() {
+        let mut rng = testing_rng();
+        let signer = ed25519::Keypair::generate(&mut rng);
+        let subject = ed25519::Keypair::generate(&mut rng);
+        let signer_id: ed25519::Ed25519Identity = signer.verifying_key().into();
+        let now = SystemTime::now();
+        let day = Duration::from_secs(86400);
+
+        let encoded = create_ed25519_cert(
+            &signer,
+            CertType::HS_IP_V_SIGNING,
+            CertifiedKey::Ed25519(subject.verifying_key().into()),
+            now + day,
+        )
+        .unwrap();
+
+        let cert = validate_ed25519_cert(&encoded, CertType::HS_IP_V_SIGNING, None, now).unwrap();
+        assert_eq!(cert.signing_key(), Some(&signer_id));
+        let cert =
+            validate_ed25519_cert(&encoded, CertType::HS_IP_V_SIGNING, Some(&signer_id), now)
+                .unwrap();
+        assert_eq!(
+            cert.subject_key().as_ed25519(),
+            Some(&subject.verifying_key().into())
+        );
+
+        let e = validate_ed25519_cert(&encoded, CertType::HS_BLINDED_ID_V_SIGNING, None, now)
+            .unwrap_err();
+        assert!(matches!(e, CertValidationError::WrongType { .. }));
+
+        let other: ed25519::Ed25519Identity = subject.verifying_key().into();
+        let e = validate_ed25519_cert(&encoded, CertType::HS_IP_V_SIGNING, Some(&other), now)
+            .unwrap_err();
+        assert!(matches!(
+            e,
+            CertValidationError::Invalid(CertError::KeyMismatch)
+        ));
+
+        let e = validate_ed25519_cert(&encoded, CertType::HS_IP_V_SIGNING, None, now + day * 2)
+            .unwrap_err();
+        assert!(matches!(e, CertValidationError::Time(_)));
+    }
+
+    #[test]
+    fn stored_cert() {
+        let mut rng = testing_rng();
+        let signer = ed25519::Keypair::generate(&mut rng);
+        let now = SystemTime::now();
+
+        let encoded = create_ed25519_cert(
+            &signer,
+            CertType::HS_IP_V_SIGNING,
+            CertifiedKey::Ed25519(signer.verifying_key().into()),
+            now + Duration::from_secs(86400),
+        )
+        .unwrap();
+        let stored = StoredEd25519Cert::from(encoded.clone());
+        assert_eq!(stored.as_bytes(), &encoded[..]);
+
+        let entry = stored.to_entry_bytes();
+        assert_eq!(entry.len(), 32 + encoded.len());
+        let decoded = StoredEd25519Cert::from_entry_bytes(&entry).unwrap();
+        assert_eq!(decoded, stored);
+        assert!(decoded
+            .validate(CertType::HS_IP_V_SIGNING, None, now)
+            .is_ok());
+
+        // The tag is required
+        let e = StoredEd25519Cert::from_entry_bytes(&encoded).unwrap_err();
+        assert!(matches!(e, Error::MalformedCert(_)));
+        // ...and so is a certificate
+        let e = StoredEd25519Cert::from_entry_bytes(&entry[..40]).unwrap_err();
+        assert!(matches!(e, Error::MalformedCert(_)));
+
+        // Certificates can be round-tripped through their armored encoding.
+        let armored = stored.to_armored().unwrap();
+        assert!(armored.starts_with("-----BEGIN TOR CERTIFICATE-----\n"));
+        assert!(armored
+            .trim_end()
+            .ends_with("-----END TOR CERTIFICATE-----"));
+        assert_eq!(StoredEd25519Cert::from_armored(&armored).unwrap(), stored);
+
+        let e = StoredEd25519Cert::from_armored(&armored.replace("TOR CERTIFICATE", "TOR CERT"))
+            .unwrap_err();
+        assert!(matches!(e, Error::MalformedArmoredCert(_)));
+        let e = StoredEd25519Cert::from_armored("not a certificate").unwrap_err();
+        assert!(matches!(e, Error::MalformedArmoredCert(_)));
+    }
+
+    #[test]
+    fn signer_failure() {
+        let mut rng = testing_rng();
+        let key = ed25519::Keypair::generate(&mut rng);
+        let e = create_ed25519_cert(
+            &Unplugged(key.verifying_key()),
+            CertType::HS_IP_V_SIGNING,
+            CertifiedKey::Ed25519(key.verifying_key().into()),
+            SystemTime::now(),
+        )
+        .unwrap_err();
+        assert!(matches!(e, Error::CertEncode(CertEncodeError::Signing(_))));
+    }
+}
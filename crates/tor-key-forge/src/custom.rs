@@ -0,0 +1,303 @@
+//! Key types defined outside this crate.
+//!
+//! Applications that embed Arti sometimes want to keep their own keys
+//! (for example, secp256k1 keys) in the same keystore as Arti's.
+//! They can do that by registering a [`CustomKeyType`]
+//! with [`register_custom_key_type`], before using the keystore.
+//!
+//! Keys of a custom type are stored as OpenSSH keys
+//! with the algorithm name of the custom type,
+//! in the same way as our own x25519 keys.
+//! The application's [`EncodableKey`](crate::EncodableKey) implementation
+//! builds their [`SshKeyData`](crate::SshKeyData) with
+//! [`SshKeyData::custom_keypair`](crate::SshKeyData::custom_keypair)
+//! or [`SshKeyData::custom_public_key`](crate::SshKeyData::custom_public_key),
+//! and the registered decoder turns them back into keys.
+
+use std::sync::RwLock;
+
+use ssh_key::AlgorithmName;
+
+use crate::{ssh::SshKeyAlgorithm, ErasedKey, Error, KeyType, Result};
+
+/// The custom key types registered so far.
+static REGISTRY: RwLock<Vec<CustomKeyType>> = RwLock::new(Vec::new());
+
+/// A function that decodes the public and secret parts of a custom keypair.
+pub type CustomKeypairDecoder = fn(public: &[u8], secret: &[u8]) -> Result<ErasedKey>;
+
+/// A function that decodes a custom public key.
+pub type CustomPublicKeyDecoder = fn(public: &[u8]) -> Result<ErasedKey>;
+
+/// A key type defined outside this crate
+///
+/// See the [module documentation](self).
+#[derive(Clone, Debug)]
+pub struct CustomKeyType {
+    /// The extension used for keys of this type in an Arti keystore.
+    arti_extension: String,
+    /// The OpenSSH algorithm name of keys of this type.
+    algorithm_name: String,
+    /// How to decode keys of this type.
+    decoder: Decoder,
+}
+
+/// How to decode a [`CustomKeyType`]
+#[derive(Clone, Debug)]
+enum Decoder {
+    /// Entries of this type are keypairs.
+    Keypair(CustomKeypairDecoder),
+    /// Entries of this type are public keys.
+    Public(CustomPublicKeyDecoder),
+}
+
+impl CustomKeyType {
+    /// Describe a custom keypair type.
+    ///
+    /// Keys of this type are stored with the `arti_extension` in Arti's keystore,
+    /// as OpenSSH private keys with the algorithm `algorithm_name`
+    /// (which must be of the form `name@domain`).
+    ///
+    /// `decode` is given the public and secret parts of a stored key,
+    /// and should return the application's key type, as an [`ErasedKey`].
+    pub fn keypair(
+        arti_extension: impl Into<String>,
+        algorithm_name: impl Into<String>,
+        decode: CustomKeypairDecoder,
+    ) -> Self {
+        Self {
+            arti_extension: arti_extension.into(),
+            algorithm_name: algorithm_name.into(),
+            decoder: Decoder::Keypair(decode),
+        }
+    }
+
+    /// Describe a custom public key type.
+    ///
+    /// As [`CustomKeyType::keypair`], but for public keys,
+    /// which are stored as OpenSSH public keys.
+    /// A keypair type and a public key type may share an `algorithm_name`.
+    pub fn public_key(
+        arti_extension: impl Into<String>,
+        algorithm_name: impl Into<String>,
+        decode: CustomPublicKeyDecoder,
+    ) -> Self {
+        Self {
+            arti_extension: arti_extension.into(),
+            algorithm_name: algorithm_name.into(),
+            decoder: Decoder::Public(decode),
+        }
+    }
+
+    /// The extension used for keys of this type in an Arti keystore.
+    pub fn arti_extension(&self) -> &str {
+        &self.arti_extension
+    }
+
+    /// The OpenSSH algorithm name of keys of this type.
+    pub fn algorithm_name(&self) -> &str {
+        &self.algorithm_name
+    }
+
+    /// Return true if entries of this type are keypairs, rather than public keys.
+    pub fn is_keypair(&self) -> bool {
+        matches!(self.decoder, Decoder::Keypair(_))
+    }
+
+    /// Return the [`KeyType`] of keys of this type.
+    pub fn key_type(&self) -> KeyType {
+        KeyType::Custom {
+            arti_extension: self.arti_extension.clone(),
+        }
+    }
+}
+
+/// Register a custom key type, so that keys of that type can be stored in the keystore
+///
+/// Registrations last until the program exits.
+///
+/// Returns an error if the extension or algorithm name of `key_type`
+/// is used by one of our own key types, or by a key type which is already registered;
+/// or if the algorithm name isn't a valid OpenSSH algorithm name.
+/// (A keypair type and a public key type may share an algorithm name.)
+pub fn register_custom_key_type(key_type: CustomKeyType) -> Result<()> {
+    let conflict = |what: &str| {
+        Err(Error::BadCustomKeyType(format!(
+            "{what} of {:?} is already in use",
+            key_type.arti_extension
+        )))
+    };
+
+    let algo = AlgorithmName::new(&key_type.algorithm_name).map_err(|_| {
+        Error::BadCustomKeyType(format!(
+            "invalid algorithm name {:?}",
+            key_type.algorithm_name
+        ))
+    })?;
+    if !matches!(
+        SshKeyAlgorithm::from(ssh_key::Algorithm::Other(algo)),
+        SshKeyAlgorithm::Unknown(_)
+    ) {
+        return conflict("algorithm name");
+    }
+
+    let mut registry = REGISTRY.write().expect("poisoned lock");
+    // Check this with the lock held, since `KeyType::from` consults the registry.
+    if !matches!(
+        KeyType::from_builtin(&key_type.arti_extension),
+        KeyType::Unknown { .. }
+    ) || registry
+        .iter()
+        .any(|t| t.arti_extension == key_type.arti_extension)
+    {
+        return conflict("extension");
+    }
+    if registry.iter().any(|t| {
+        t.algorithm_name == key_type.algorithm_name && t.is_keypair() == key_type.is_keypair()
+    }) {
+        return conflict("algorithm name");
+    }
+
+    registry.push(key_type);
+    Ok(())
+}
+
+/// Return the registered custom key type with the extension `arti_extension`, if any.
+pub fn registered_key_type(arti_extension: &str) -> Option<CustomKeyType> {
+    REGISTRY
+        .read()
+        .expect("poisoned lock")
+        .iter()
+        .find(|t| t.arti_extension == arti_extension)
+        .cloned()
+}
+
+/// Return the registered custom key type with the specified algorithm, if any.
+///
+/// `keypair` says whether we want a keypair type or a public key type.
+pub(crate) fn by_algorithm(algorithm_name: &str, keypair: bool) -> Option<CustomKeyType> {
+    REGISTRY
+        .read()
+        .expect("poisoned lock")
+        .iter()
+        .find(|t| t.algorithm_name == algorithm_name && t.is_keypair() == keypair)
+        .cloned()
+}
+
+impl CustomKeyType {
+    /// Decode a keypair of this type.
+    pub(crate) fn decode_keypair(&self, public: &[u8], secret: &[u8]) -> Result<ErasedKey> {
+        match self.decoder {
+            Decoder::Keypair(decode) => decode(public, secret),
+            Decoder::Public(_) => Err(tor_error::internal!("not a keypair type").into()),
+        }
+    }
+
+    /// Decode a public key of this type.
+    pub(crate) fn decode_public(&self, public: &[u8]) -> Result<ErasedKey> {
+        match self.decoder {
+            Decoder::Public(decode) => decode(public),
+            Decoder::Keypair(_) => Err(tor_error::internal!("not a public key type").into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use crate::{EncodableKey, SecretBuffer, SshKeyData};
+
+    /// The algorithm name of our test keys.
+    const ALGORITHM: &str = "test-secp256k1@example.com";
+
+    /// A toy custom keypair.
+    #[derive(Debug, PartialEq)]
+    struct ToyKeypair {
+        public: Vec<u8>,
+        secret: Vec<u8>,
+    }
+
+    impl EncodableKey for ToyKeypair {
+        fn key_type() -> KeyType {
+            KeyType::from("toy_private")
+        }
+
+        fn as_ssh_key_data(&self) -> Result<SshKeyData> {
+            SshKeyData::custom_keypair(
+                ALGORITHM,
+                &self.public,
+                SecretBuffer::new(self.secret.clone()),
+            )
+        }
+    }
+
+    fn toy_keypair(public: &[u8], secret: &[u8]) -> ErasedKey {
+        Box::new(ToyKeypair {
+            public: public.to_vec(),
+            secret: secret.to_vec(),
+        })
+    }
+
+    /// A [`CustomKeypairDecoder`] for [`ToyKeypair`]s (which never fails to decode).
+    const DECODE_TOY: CustomKeypairDecoder = |public, secret| Ok(toy_keypair(public, secret));
+
+    #[test]
+    fn register_and_roundtrip() {
+        // Not registered yet.
+        assert!(matches!(
+            KeyType::from("toy_private"),
+            KeyType::Unknown { .. }
+        ));
+
+        register_custom_key_type(CustomKeyType::keypair("toy_private", ALGORITHM, DECODE_TOY))
+            .unwrap();
+
+        assert_eq!(
+            KeyType::from("toy_private"),
+            KeyType::Custom {
+                arti_extension: "toy_private".into()
+            }
+        );
+
+        let key = ToyKeypair {
+            public: vec![1, 2, 3],
+            secret: vec![4, 5, 6],
+        };
+        let data = key.as_ssh_key_data().unwrap();
+        assert_eq!(data.key_type().unwrap(), ToyKeypair::key_type());
+
+        let encoded = data.to_openssh_string("test").unwrap();
+        let parsed = ssh_key::PrivateKey::from_openssh(&*encoded).unwrap();
+        let data = SshKeyData::try_from_keypair_data(parsed.key_data().clone()).unwrap();
+        let Ok(decoded) = data.into_erased().unwrap().downcast::<ToyKeypair>() else {
+            panic!("decoded to the wrong type");
+        };
+        assert_eq!(*decoded, key);
+
+        // Conflicts are rejected.
+        for bad in [
+            CustomKeyType::keypair("toy_private", "other@example.com", DECODE_TOY),
+            CustomKeyType::keypair("other_private", ALGORITHM, DECODE_TOY),
+            CustomKeyType::keypair("x25519_private", "other@example.com", DECODE_TOY),
+            CustomKeyType::keypair("other_private", "x25519@spec.torproject.org", DECODE_TOY),
+        ] {
+            assert!(matches!(
+                register_custom_key_type(bad),
+                Err(Error::BadCustomKeyType(_))
+            ));
+        }
+    }
+}
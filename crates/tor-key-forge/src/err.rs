@@ -12,6 +12,35 @@ pub enum Error {
     #[error("Unsupported key algorithm {0}")]
     UnsupportedKeyAlgorithm(SshKeyAlgorithm),
 
+    /// Unable to encode or sign a certificate.
+    #[cfg(feature = "cert")]
+    #[error("Unable to create certificate")]
+    CertEncode(#[source] tor_cert::CertEncodeError),
+
+    /// A stored certificate could not be decoded.
+    #[cfg(feature = "cert")]
+    #[error("Malformed stored certificate")]
+    MalformedCert(#[source] tor_bytes::Error),
+
+    /// An armored certificate could not be decoded.
+    #[cfg(feature = "cert")]
+    #[error("Malformed armored certificate")]
+    MalformedArmoredCert(#[source] pem_rfc7468::Error),
+
+    /// A key could not be encoded as, or decoded from, DER or PEM.
+    #[cfg(feature = "pkcs8")]
+    #[error("Malformed DER-encoded key")]
+    MalformedDerKey(#[from] pkcs8::der::Error),
+
+    /// A DER- or PEM-encoded key was well-formed, but not the kind of key we expected.
+    #[cfg(feature = "pkcs8")]
+    #[error("Unexpected DER-encoded key: {0}")]
+    UnexpectedDerKey(String),
+
+    /// A custom key type could not be registered.
+    #[error("Cannot register custom key type: {0}")]
+    BadCustomKeyType(String),
+
     /// An internal error.
     #[error("Internal error")]
     Bug(#[from] tor_error::Bug),
@@ -24,6 +53,17 @@ impl HasKind for Error {
 
         match self {
             E::UnsupportedKeyAlgorithm(_) => EK::BadApiUsage,
+            E::BadCustomKeyType(_) => EK::BadApiUsage,
+            #[cfg(feature = "cert")]
+            E::CertEncode(tor_cert::CertEncodeError::Signing(_)) => EK::KeystoreAccessFailed,
+            #[cfg(feature = "cert")]
+            E::CertEncode(_) => EK::BadApiUsage,
+            #[cfg(feature = "cert")]
+            E::MalformedCert(_) => EK::KeystoreCorrupted,
+            #[cfg(feature = "cert")]
+            E::MalformedArmoredCert(_) => EK::BadApiUsage,
+            #[cfg(feature = "pkcs8")]
+            E::MalformedDerKey(_) | E::UnexpectedDerKey(_) => EK::BadApiUsage,
             E::Bug(e) => e.kind(),
         }
     }
@@ -41,6 +41,7 @@ use ssh_key::public::KeyData;
 use ssh_key::Algorithm;
 use tor_error::internal;
 
+use crate::custom;
 use crate::ssh::{ED25519_EXPANDED_ALGORITHM_NAME, X25519_ALGORITHM_NAME};
 use crate::Result;
 
@@ -53,7 +54,8 @@ use crate::Result;
 /// The `str_repr` is also used for implementing `From<&str>` for `KeyType`.
 /// Note `KeyType` implements `From<&str>` rather than `FromStr`,
 /// because the conversion from string is infallible
-/// (unrecognized strings are mapped to `KeyType::Unknown`)
+/// (the extensions of registered [custom key types](crate::custom) are mapped to `KeyType::Custom`,
+/// and other unrecognized strings are mapped to `KeyType::Unknown`)
 macro_rules! declare_key_type {
     {
         $(#[$enum_meta:meta])*
@@ -72,6 +74,13 @@ macro_rules! declare_key_type {
                 $variant,
             )*
 
+            /// A key type registered with
+            /// [`register_custom_key_type`](crate::custom::register_custom_key_type).
+            Custom {
+                /// The extension used for keys of this type in an Arti keystore.
+                arti_extension: String,
+            },
+
             /// An unrecognized key type.
             Unknown {
                 /// The extension used for keys of this type in an Arti keystore.
@@ -88,13 +97,14 @@ macro_rules! declare_key_type {
                     $(
                         $variant => $str_repr.into(),
                     )*
-                    Unknown { arti_extension } => arti_extension.clone(),
+                    Custom { arti_extension } | Unknown { arti_extension } => {
+                        arti_extension.clone()
+                    }
                 }
             }
-        }
 
-        impl From<&str> for KeyType {
-            fn from(key_type: &str) -> Self {
+            /// Convert `key_type` to a `KeyType`, ignoring any custom key types.
+            pub(crate) fn from_builtin(key_type: &str) -> Self {
                 use KeyType::*;
 
                 match key_type {
@@ -107,6 +117,20 @@ macro_rules! declare_key_type {
                 }
             }
         }
+
+        impl From<&str> for KeyType {
+            fn from(key_type: &str) -> Self {
+                match KeyType::from_builtin(key_type) {
+                    KeyType::Unknown { arti_extension } => {
+                        match crate::custom::registered_key_type(&arti_extension) {
+                            Some(custom) => custom.key_type(),
+                            None => KeyType::Unknown { arti_extension },
+                        }
+                    }
+                    builtin => builtin,
+                }
+            }
+        }
     }
 }
 
@@ -120,6 +144,10 @@ impl KeyType {
             Algorithm::Other(algo) if algo.as_str() == X25519_ALGORITHM_NAME => {
                 Ok(KeyType::X25519PublicKey)
             }
+            Algorithm::Other(algo) => match custom::by_algorithm(algo.as_str(), false) {
+                Some(custom) => Ok(custom.key_type()),
+                None => Err(internal!("invalid key data").into()),
+            },
             _ => Err(internal!("invalid key data").into()),
         }
     }
@@ -157,6 +185,19 @@ declare_key_type! {
         X25519PublicKey => "x25519_public",
         /// An expanded Ed25519 keypair.
         Ed25519ExpandedKeypair => "ed25519_expanded_private",
+        /// A Tor Ed25519 certificate (see `cert-spec.txt`).
+        ///
+        /// Entries of this type are not keys, and can't be read with [`EncodableKey`]s.
+        ///
+        /// [`EncodableKey`]: crate::EncodableKey
+        Ed25519TorCert => "tor_ed25519_cert",
+    }
+}
+
+impl KeyType {
+    /// Return true if entries of this type are certificates rather than keys.
+    pub fn is_cert(&self) -> bool {
+        matches!(self, KeyType::Ed25519TorCert)
     }
 }
 
@@ -41,14 +41,25 @@
 #![allow(clippy::needless_raw_string_hashes)] // complained-about code is fine, often best
 //! <!-- @@ end lint list maintained by maint/add_warning @@ -->
 
+#[cfg(feature = "cert")]
+pub mod cert;
+pub mod custom;
+#[cfg(feature = "pkcs8")]
+mod der;
 mod err;
 mod key_type;
 mod macros;
+mod secret;
+mod signer;
 mod ssh;
 mod traits;
 
+#[cfg(feature = "pkcs8")]
+pub use der::DerEncodableKey;
 pub use err::Error;
 pub use key_type::KeyType;
+pub use secret::SecretBuffer;
+pub use signer::{into_ed25519_signer, Ed25519Signer, Ed25519SignerAdapter};
 pub use ssh::{SshKeyAlgorithm, SshKeyData};
 pub use traits::{EncodableKey, Keygen, KeygenRng, ToEncodableKey};
 
@@ -63,4 +74,6 @@ pub use macros::deps as macro_deps;
 pub type Result<T> = std::result::Result<T, Error>;
 
 /// A type-erased key. Used by the tor-keymgr.
+///
+/// The secret key material of a type-erased keypair is zeroed when it is dropped.
 pub type ErasedKey = Box<dyn traits::EncodableKey>;
@@ -169,6 +169,10 @@ define_derive_deftly! {
         }
     }
 
+    // The inner keypair zeroes its secret key on drop, so we do too.
+    impl $crate::macro_deps::ZeroizeOnDrop for $ttype {}
+    const _: () = $crate::macro_deps::assert_zeroize_on_drop::<$crate::macro_deps::ed25519::Keypair>();
+
     impl $crate::Keygen for $ttype {
         fn generate(mut rng: &mut dyn $crate::KeygenRng) -> $crate::Result<Self>
         where
@@ -316,6 +320,10 @@ define_derive_deftly! {
         }
     }
 
+    // The inner keypair zeroes its secret key on drop, so we do too.
+    impl $crate::macro_deps::ZeroizeOnDrop for $ttype {}
+    const _: () = $crate::macro_deps::assert_zeroize_and_drop::<$crate::macro_deps::curve25519::StaticSecret>();
+
     impl $crate::Keygen for $ttype {
         fn generate(mut rng: &mut dyn $crate::KeygenRng) -> $crate::Result<Self>
         where
@@ -341,6 +349,18 @@ pub mod deps {
     pub use paste::paste;
     pub use signature;
     pub use tor_llcrypto::pk::{curve25519, ed25519, ValidatableSignature};
+    pub use zeroize::ZeroizeOnDrop;
+
+    /// Fail to compile unless `T` zeroes its secret key material on drop.
+    pub const fn assert_zeroize_on_drop<T: ZeroizeOnDrop>() {}
+
+    /// Fail to compile unless `T` can be zeroed, and does something on drop.
+    ///
+    /// This is for types like [`curve25519::StaticSecret`], which zero themselves on drop
+    /// without implementing [`ZeroizeOnDrop`].
+    pub const fn assert_zeroize_and_drop<T: zeroize::Zeroize>() {
+        assert!(std::mem::needs_drop::<T>());
+    }
 }
 
 #[cfg(test)]
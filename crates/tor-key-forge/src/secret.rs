@@ -0,0 +1,129 @@
+//! A buffer for secret key material.
+
+use std::fmt;
+
+use zeroize::{Zeroize as _, Zeroizing};
+
+/// A buffer holding secret key material, such as the contents of a keystore entry.
+///
+/// The contents are zeroed when the buffer is dropped,
+/// and are never shown in its `Debug` output.
+///
+/// Converting a `String` or `Vec<u8>` into a `SecretBuffer` takes ownership of
+/// its allocation, without copying it.
+#[derive(Clone, Default)]
+pub struct SecretBuffer(Zeroizing<Vec<u8>>);
+
+impl SecretBuffer {
+    /// Create a new `SecretBuffer` holding `data`.
+    pub fn new(data: Vec<u8>) -> Self {
+        Self(Zeroizing::new(data))
+    }
+
+    /// Return the contents of this buffer.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Return the contents of this buffer as a string,
+    /// or an error if they are not valid UTF-8.
+    pub fn as_str(&self) -> Result<&str, std::str::Utf8Error> {
+        std::str::from_utf8(&self.0)
+    }
+
+    /// Return the length of the contents of this buffer.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Return true if this buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Zero the contents of this buffer, and empty it.
+    pub fn clear(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for SecretBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretBuffer([{} bytes])", self.len())
+    }
+}
+
+impl From<Vec<u8>> for SecretBuffer {
+    fn from(data: Vec<u8>) -> Self {
+        Self::new(data)
+    }
+}
+
+impl From<String> for SecretBuffer {
+    fn from(data: String) -> Self {
+        Self::new(data.into_bytes())
+    }
+}
+
+impl From<&str> for SecretBuffer {
+    fn from(data: &str) -> Self {
+        Self::new(data.as_bytes().to_vec())
+    }
+}
+
+impl From<Zeroizing<Vec<u8>>> for SecretBuffer {
+    fn from(data: Zeroizing<Vec<u8>>) -> Self {
+        Self(data)
+    }
+}
+
+impl From<Zeroizing<String>> for SecretBuffer {
+    fn from(mut data: Zeroizing<String>) -> Self {
+        let data = std::mem::take(&mut *data);
+        Self::new(data.into_bytes())
+    }
+}
+
+impl AsRef<[u8]> for SecretBuffer {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl zeroize::ZeroizeOnDrop for SecretBuffer {}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+
+    #[test]
+    fn secret_buffer() {
+        let s = String::from("hunter2");
+        let ptr = s.as_ptr();
+        let mut buf = SecretBuffer::from(s);
+        // Converting a String doesn't copy it.
+        assert_eq!(buf.as_bytes().as_ptr(), ptr);
+        assert_eq!(buf.as_str().unwrap(), "hunter2");
+        assert_eq!(format!("{:?}", buf), "SecretBuffer([7 bytes])");
+
+        let buf2 = SecretBuffer::from(Zeroizing::new(String::from("hunter2")));
+        assert_eq!(buf2.as_bytes(), buf.as_bytes());
+
+        buf.clear();
+        assert!(buf.is_empty());
+        assert!(SecretBuffer::new(vec![0xff]).as_str().is_err());
+    }
+}
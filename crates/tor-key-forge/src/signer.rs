@@ -0,0 +1,105 @@
+//! Signing with keys whose secret part may not be available to us.
+
+use std::fmt;
+
+use tor_llcrypto::pk::ed25519::{self, Ed25519PublicKey};
+
+use crate::{ErasedKey, Result};
+
+/// A key that can make ed25519 signatures.
+///
+/// Unlike [`ed25519::Signer`], this does not assume that the secret key is
+/// held in memory: signing can fail, so that implementations can delegate to
+/// a hardware token, an agent, or some other key store from which keys can't
+/// be extracted.
+///
+/// Keystores hand out signers through `Keystore::ed25519_signer` in
+/// `tor-keymgr`.
+pub trait Ed25519Signer: Send + Sync {
+    /// Return the public part of this key.
+    fn public_key(&self) -> ed25519::PublicKey;
+
+    /// Sign `msg` with this key.
+    fn sign(&self, msg: &[u8]) -> Result<ed25519::Signature>;
+}
+
+impl Ed25519Signer for ed25519::Keypair {
+    fn public_key(&self) -> ed25519::PublicKey {
+        self.verifying_key()
+    }
+
+    fn sign(&self, msg: &[u8]) -> Result<ed25519::Signature> {
+        Ok(ed25519::Signer::sign(self, msg))
+    }
+}
+
+impl Ed25519Signer for ed25519::ExpandedKeypair {
+    fn public_key(&self) -> ed25519::PublicKey {
+        *self.public()
+    }
+
+    fn sign(&self, msg: &[u8]) -> Result<ed25519::Signature> {
+        Ok(ed25519::ExpandedKeypair::sign(self, msg))
+    }
+}
+
+/// Convert `key` into an [`Ed25519Signer`], if it is an ed25519 keypair.
+///
+/// Returns `None` for any other kind of key.
+pub fn into_ed25519_signer(key: ErasedKey) -> Option<Box<dyn Ed25519Signer>> {
+    let key = match key.downcast::<ed25519::Keypair>() {
+        Ok(key) => return Some(key),
+        Err(key) => key,
+    };
+    match key.downcast::<ed25519::ExpandedKeypair>() {
+        Ok(key) => Some(key),
+        Err(_) => None,
+    }
+}
+
+/// An [`Ed25519Signer`], presented as an [`ed25519::Signer`] with an [`Ed25519PublicKey`].
+///
+/// This is the form in which `tor-cert` and `tor-netdoc` expect signing keys.
+/// Errors from the underlying signer are reported as [`ed25519::SignatureError`]s,
+/// with the original error as their source.
+pub struct Ed25519SignerAdapter<'a> {
+    /// The public key of `signer`.
+    public: ed25519::PublicKey,
+    /// The underlying signer.
+    signer: &'a dyn Ed25519Signer,
+}
+
+impl<'a> Ed25519SignerAdapter<'a> {
+    /// Wrap `signer`.
+    pub fn new(signer: &'a dyn Ed25519Signer) -> Self {
+        Self {
+            public: signer.public_key(),
+            signer,
+        }
+    }
+}
+
+impl fmt::Debug for Ed25519SignerAdapter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ed25519SignerAdapter")
+            .field("public", &self.public)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Ed25519PublicKey for Ed25519SignerAdapter<'_> {
+    fn public_key(&self) -> &ed25519::PublicKey {
+        &self.public
+    }
+}
+
+impl ed25519::Signer<ed25519::Signature> for Ed25519SignerAdapter<'_> {
+    fn try_sign(
+        &self,
+        msg: &[u8],
+    ) -> std::result::Result<ed25519::Signature, ed25519::SignatureError> {
+        self.signer
+            .sign(msg)
+            .map_err(ed25519::SignatureError::from_source)
+    }
+}
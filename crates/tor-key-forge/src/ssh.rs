@@ -1,12 +1,15 @@
 //! Shared OpenSSH helpers.
 
 use ssh_key::{
-    private::KeypairData, public::KeyData, Algorithm, LineEnding, PrivateKey, PublicKey,
+    private::{KeypairData, OpaqueKeypair},
+    public::{KeyData, OpaquePublicKey},
+    Algorithm, AlgorithmName, LineEnding, PrivateKey, PublicKey,
 };
 use tor_error::{internal, into_internal};
 use tor_llcrypto::pk::{curve25519, ed25519};
+use zeroize::Zeroizing;
 
-use crate::{ErasedKey, Error, KeyType, Result};
+use crate::{ErasedKey, Error, KeyType, Result, SecretBuffer};
 
 /// The algorithm string for x25519 SSH keys.
 ///
@@ -65,41 +68,27 @@ impl From<Algorithm> for SshKeyAlgorithm {
     }
 }
 
-/// Convert ssh_key KeyData or KeypairData to one of our key types.
+/// Convert ssh_key KeyData to one of our key types.
 macro_rules! ssh_to_internal_erased {
-    (PRIVATE $key:expr, $algo:expr) => {{
-        ssh_to_internal_erased!(
-            $key,
-            $algo,
-            convert_ed25519_kp,
-            convert_expanded_ed25519_kp,
-            convert_x25519_kp,
-            KeypairData
-        )
-    }};
-
     (PUBLIC $key:expr, $algo:expr) => {{
-        ssh_to_internal_erased!(
-            $key,
-            $algo,
-            convert_ed25519_pk,
-            convert_expanded_ed25519_pk,
-            convert_x25519_pk,
-            KeyData
-        )
-    }};
-
-    ($key:expr, $algo:expr, $ed25519_fn:path, $expanded_ed25519_fn:path, $x25519_fn:path, $key_data_ty:tt) => {{
         let key = $key;
         let algo = SshKeyAlgorithm::from($algo);
 
         // Build the expected key type (i.e. convert ssh_key key types to the key types
         // we're using internally).
         match key {
-            $key_data_ty::Ed25519(key) => Ok($ed25519_fn(&key).map(Box::new)?),
-            $key_data_ty::Other(other) => match algo {
-                SshKeyAlgorithm::X25519 => Ok($x25519_fn(&other).map(Box::new)?),
-                SshKeyAlgorithm::Ed25519Expanded => Ok($expanded_ed25519_fn(&other).map(Box::new)?),
+            KeyData::Ed25519(key) => Ok(convert_ed25519_pk(&key).map(Box::new)?),
+            KeyData::Other(other) => match algo {
+                SshKeyAlgorithm::X25519 => Ok(convert_x25519_pk(&other).map(Box::new)?),
+                SshKeyAlgorithm::Ed25519Expanded => {
+                    Ok(convert_expanded_ed25519_pk(&other).map(Box::new)?)
+                }
+                SshKeyAlgorithm::Unknown(Algorithm::Other(ref name)) => {
+                    match crate::custom::by_algorithm(name.as_str(), false) {
+                        Some(custom) => custom.decode_public(other.as_ref()),
+                        None => Err(Error::UnsupportedKeyAlgorithm(algo)),
+                    }
+                }
                 _ => Err(Error::UnsupportedKeyAlgorithm(algo)),
             },
             _ => Err(Error::UnsupportedKeyAlgorithm(algo)),
@@ -114,44 +103,42 @@ macro_rules! ssh_to_internal_erased {
 // but is that really right?
 #[allow(clippy::unnecessary_fallible_conversions)]
 fn convert_ed25519_kp(key: &ssh_key::private::Ed25519Keypair) -> Result<ed25519::Keypair> {
-    Ok(ed25519::Keypair::try_from(&key.private.to_bytes())
-        .map_err(|_| internal!("bad ed25519 keypair"))?)
+    Ok(
+        ed25519::Keypair::try_from(&*Zeroizing::new(key.private.to_bytes()))
+            .map_err(|_| internal!("bad ed25519 keypair"))?,
+    )
 }
 
-/// Try to convert an [`OpaqueKeypair`](ssh_key::private::OpaqueKeypair) to a [`curve25519::StaticKeypair`].
-fn convert_x25519_kp(key: &ssh_key::private::OpaqueKeypair) -> Result<curve25519::StaticKeypair> {
-    let public: [u8; 32] = key
-        .public
-        .as_ref()
+/// Try to convert the parts of an opaque x25519 keypair to a [`curve25519::StaticKeypair`].
+fn convert_x25519_kp(public: &[u8], secret: &[u8]) -> Result<curve25519::StaticKeypair> {
+    let public: [u8; 32] = public
         .try_into()
         .map_err(|_| internal!("bad x25519 public key length"))?;
 
-    let secret: [u8; 32] = key
-        .private
-        .as_ref()
-        .try_into()
-        .map_err(|_| internal!("bad x25519 secret key length"))?;
+    let secret: Zeroizing<[u8; 32]> = Zeroizing::new(
+        secret
+            .try_into()
+            .map_err(|_| internal!("bad x25519 secret key length"))?,
+    );
 
     Ok(curve25519::StaticKeypair {
         public: public.into(),
-        secret: secret.into(),
+        secret: (*secret).into(),
     })
 }
 
-/// Try to convert an [`OpaqueKeypair`](ssh_key::private::OpaqueKeypair) to an [`ed25519::ExpandedKeypair`].
-fn convert_expanded_ed25519_kp(
-    key: &ssh_key::private::OpaqueKeypair,
-) -> Result<ed25519::ExpandedKeypair> {
-    let public = ed25519::PublicKey::try_from(key.public.as_ref())
+/// Try to convert the parts of an opaque expanded ed25519 keypair to an [`ed25519::ExpandedKeypair`].
+fn convert_expanded_ed25519_kp(public: &[u8], secret: &[u8]) -> Result<ed25519::ExpandedKeypair> {
+    let public = ed25519::PublicKey::try_from(public)
         .map_err(|_| internal!("bad expanded ed25519 public key "))?;
 
-    let keypair = ed25519::ExpandedKeypair::from_secret_key_bytes(
-        key.private
-            .as_ref()
+    let secret: Zeroizing<[u8; 64]> = Zeroizing::new(
+        secret
             .try_into()
             .map_err(|_| internal!("bad length on expanded ed25519 secret key ",))?,
-    )
-    .ok_or_else(|| internal!("bad expanded ed25519 secret key "))?;
+    );
+    let keypair = ed25519::ExpandedKeypair::from_secret_key_bytes(*secret)
+        .ok_or_else(|| internal!("bad expanded ed25519 secret key "))?;
 
     if &public != keypair.public() {
         return Err(internal!("mismatched ed25519 keypair",).into());
@@ -191,6 +178,8 @@ fn convert_x25519_pk(key: &ssh_key::public::OpaquePublicKey) -> Result<curve2551
 }
 
 /// A public key or a keypair.
+///
+/// The secret key material of a keypair is zeroed when this is dropped.
 #[derive(Clone, Debug)]
 #[non_exhaustive]
 pub struct SshKeyData(SshKeyDataInner);
@@ -201,8 +190,20 @@ pub struct SshKeyData(SshKeyDataInner);
 enum SshKeyDataInner {
     /// The [`KeyData`] of a public key.
     Public(KeyData),
-    /// The [`KeypairData`] of a private key.
+    /// The [`KeypairData`] of an ed25519 private key.
+    ///
+    /// `ssh_key` zeroes the secret part of this on drop.
     Private(KeypairData),
+    /// An opaque (x25519 or expanded ed25519) keypair.
+    ///
+    /// `ssh_key` doesn't zero the secret part of an [`OpaqueKeypair`] on drop,
+    /// so we keep it in a [`SecretBuffer`] instead.
+    Opaque {
+        /// The public part of the keypair.
+        public: OpaquePublicKey,
+        /// The secret part of the keypair.
+        secret: SecretBuffer,
+    },
 }
 
 impl SshKeyData {
@@ -215,6 +216,11 @@ impl SshKeyData {
             KeyData::Ed25519(_) => Ok(()),
             KeyData::Other(_) => match algo {
                 SshKeyAlgorithm::X25519 => Ok(()),
+                SshKeyAlgorithm::Unknown(Algorithm::Other(ref name))
+                    if crate::custom::by_algorithm(name.as_str(), false).is_some() =>
+                {
+                    Ok(())
+                }
                 _ => Err(Error::UnsupportedKeyAlgorithm(algo)),
             },
             _ => Err(Error::UnsupportedKeyAlgorithm(algo)),
@@ -231,41 +237,116 @@ impl SshKeyData {
             key.algorithm()
                 .map_err(into_internal!("encrypted keys are not yet supported"))?,
         );
-        let () = match key {
-            KeypairData::Ed25519(_) => Ok(()),
-            KeypairData::Other(_) => match algo {
-                SshKeyAlgorithm::X25519 => Ok(()),
-                SshKeyAlgorithm::Ed25519Expanded => Ok(()),
-                _ => Err(Error::UnsupportedKeyAlgorithm(algo)),
-            },
+        match key {
+            KeypairData::Ed25519(_) => Ok(Self(SshKeyDataInner::Private(key))),
+            KeypairData::Other(key) => {
+                Self::check_opaque_algorithm(&key.public)?;
+                Ok(Self(SshKeyDataInner::Opaque {
+                    secret: SecretBuffer::new(key.private.as_ref().to_vec()),
+                    public: key.public,
+                }))
+            }
             _ => Err(Error::UnsupportedKeyAlgorithm(algo)),
-        }?;
+        }
+    }
+
+    /// Build an opaque keypair with the algorithm `algorithm_name`
+    /// from its `public` and `secret` parts.
+    ///
+    /// Unlike [`SshKeyData::try_from_keypair_data`], this never copies
+    /// the secret key material into a buffer that isn't zeroed on drop.
+    pub(crate) fn from_opaque_keypair(
+        algorithm_name: &str,
+        public: &[u8],
+        secret: SecretBuffer,
+    ) -> Result<Self> {
+        let algorithm_name =
+            AlgorithmName::new(algorithm_name).map_err(|_| internal!("invalid algorithm name"))?;
+        let public = OpaquePublicKey::new(public.to_vec(), Algorithm::Other(algorithm_name));
+
+        Self::check_opaque_algorithm(&public)?;
+        Ok(Self(SshKeyDataInner::Opaque { public, secret }))
+    }
 
-        Ok(Self(SshKeyDataInner::Private(key)))
+    /// Build a keypair of a [custom key type](crate::custom)
+    /// with the algorithm `algorithm_name`, from its `public` and `secret` parts.
+    ///
+    /// Returns an error if no custom keypair type with that algorithm has been registered.
+    pub fn custom_keypair(
+        algorithm_name: &str,
+        public: &[u8],
+        secret: SecretBuffer,
+    ) -> Result<Self> {
+        Self::from_opaque_keypair(algorithm_name, public, secret)
+    }
+
+    /// Build a public key of a [custom key type](crate::custom)
+    /// with the algorithm `algorithm_name`.
+    ///
+    /// Returns an error if no custom public key type with that algorithm has been registered.
+    pub fn custom_public_key(algorithm_name: &str, public: &[u8]) -> Result<Self> {
+        let algorithm_name =
+            AlgorithmName::new(algorithm_name).map_err(|_| internal!("invalid algorithm name"))?;
+        let key = OpaquePublicKey::new(public.to_vec(), Algorithm::Other(algorithm_name));
+        Self::try_from_key_data(KeyData::Other(key))
+    }
+
+    /// Check that `public` is of an opaque keypair algorithm we support.
+    ///
+    /// As well as our own opaque keypairs,
+    /// these include any registered [custom keypair types](crate::custom).
+    fn check_opaque_algorithm(public: &OpaquePublicKey) -> Result<SshKeyAlgorithm> {
+        match SshKeyAlgorithm::from(public.algorithm()) {
+            algo @ (SshKeyAlgorithm::X25519 | SshKeyAlgorithm::Ed25519Expanded) => Ok(algo),
+            SshKeyAlgorithm::Unknown(Algorithm::Other(ref name))
+                if crate::custom::by_algorithm(name.as_str(), true).is_some() =>
+            {
+                Ok(SshKeyAlgorithm::Unknown(Algorithm::Other(name.clone())))
+            }
+            algo => Err(Error::UnsupportedKeyAlgorithm(algo)),
+        }
+    }
+
+    /// Return the registered custom keypair type of `algo`, if any.
+    fn custom_keypair_type(algo: &SshKeyAlgorithm) -> Option<crate::custom::CustomKeyType> {
+        match algo {
+            SshKeyAlgorithm::Unknown(Algorithm::Other(name)) => {
+                crate::custom::by_algorithm(name.as_str(), true)
+            }
+            _ => None,
+        }
     }
 
     /// Encode this key as an OpenSSH-formatted key using the specified `comment`
-    pub fn to_openssh_string(&self, comment: &str) -> Result<String> {
-        let openssh_key = match &self.0 {
+    ///
+    /// The encoding of a keypair contains its secret key material,
+    /// so it is returned in a buffer that is zeroed on drop.
+    pub fn to_openssh_string(&self, comment: &str) -> Result<Zeroizing<String>> {
+        let private_to_openssh = |keypair: KeypairData| -> Result<Zeroizing<String>> {
+            let openssh_key = PrivateKey::new(keypair, comment)
+                .map_err(|_| tor_error::internal!("failed to create SSH private key"))?;
+
+            Ok(openssh_key
+                .to_openssh(LineEnding::LF)
+                .map_err(|_| tor_error::internal!("failed to encode SSH key"))?)
+        };
+
+        match &self.0 {
             SshKeyDataInner::Public(key_data) => {
                 let openssh_key = PublicKey::new(key_data.clone(), comment);
 
-                openssh_key
-                    .to_openssh()
-                    .map_err(|_| tor_error::internal!("failed to encode SSH key"))?
+                Ok(Zeroizing::new(openssh_key.to_openssh().map_err(|_| {
+                    tor_error::internal!("failed to encode SSH key")
+                })?))
             }
-            SshKeyDataInner::Private(keypair) => {
-                let openssh_key = PrivateKey::new(keypair.clone(), comment)
-                    .map_err(|_| tor_error::internal!("failed to create SSH private key"))?;
-
-                openssh_key
-                    .to_openssh(LineEnding::LF)
-                    .map_err(|_| tor_error::internal!("failed to encode SSH key"))?
-                    .to_string()
+            SshKeyDataInner::Private(keypair) => private_to_openssh(keypair.clone()),
+            SshKeyDataInner::Opaque { public, secret } => {
+                // TODO: ssh_key won't zero this copy of the secret key material on drop.
+                // There's no way to encode an opaque keypair without one.
+                let keypair = OpaqueKeypair::new(secret.as_bytes().to_vec(), public.clone());
+                private_to_openssh(KeypairData::Other(keypair))
             }
-        };
-
-        Ok(openssh_key)
+        }
     }
 
     /// Convert the key material into a known key type,
@@ -273,12 +354,31 @@ impl SshKeyData {
     ///
     /// The caller is expected to downcast the value returned to the correct concrete type.
     pub fn into_erased(self) -> Result<ErasedKey> {
-        match self.0 {
+        match &self.0 {
+            SshKeyDataInner::Private(KeypairData::Ed25519(key)) => {
+                Ok(Box::new(convert_ed25519_kp(key)?))
+            }
             SshKeyDataInner::Private(key) => {
                 let algorithm = key
                     .algorithm()
                     .map_err(into_internal!("unsupported key type"))?;
-                ssh_to_internal_erased!(PRIVATE key, algorithm)
+                Err(Error::UnsupportedKeyAlgorithm(algorithm.into()))
+            }
+            SshKeyDataInner::Opaque { public, secret } => {
+                match Self::check_opaque_algorithm(public)? {
+                    SshKeyAlgorithm::X25519 => Ok(Box::new(convert_x25519_kp(
+                        public.as_ref(),
+                        secret.as_bytes(),
+                    )?)),
+                    SshKeyAlgorithm::Ed25519Expanded => Ok(Box::new(convert_expanded_ed25519_kp(
+                        public.as_ref(),
+                        secret.as_bytes(),
+                    )?)),
+                    algo => match Self::custom_keypair_type(&algo) {
+                        Some(custom) => custom.decode_keypair(public.as_ref(), secret.as_bytes()),
+                        None => Err(Error::UnsupportedKeyAlgorithm(algo)),
+                    },
+                }
             }
             SshKeyDataInner::Public(key) => {
                 let algorithm = key.algorithm();
@@ -295,6 +395,14 @@ impl SshKeyData {
         match &self.0 {
             SshKeyDataInner::Public(k) => KeyType::try_from_key_data(k),
             SshKeyDataInner::Private(k) => KeyType::try_from_keypair_data(k),
+            SshKeyDataInner::Opaque { public, .. } => match Self::check_opaque_algorithm(public)? {
+                SshKeyAlgorithm::X25519 => Ok(KeyType::X25519StaticKeypair),
+                SshKeyAlgorithm::Ed25519Expanded => Ok(KeyType::Ed25519ExpandedKeypair),
+                algo => match Self::custom_keypair_type(&algo) {
+                    Some(custom) => Ok(custom.key_type()),
+                    None => Err(Error::UnsupportedKeyAlgorithm(algo)),
+                },
+            },
         }
     }
 }
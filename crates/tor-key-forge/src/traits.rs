@@ -3,7 +3,7 @@
 use downcast_rs::{impl_downcast, Downcast};
 use rand::RngCore;
 use ssh_key::{
-    private::{Ed25519Keypair, Ed25519PrivateKey, KeypairData, OpaqueKeypair},
+    private::{Ed25519Keypair, Ed25519PrivateKey, KeypairData},
     public::{Ed25519PublicKey, KeyData, OpaquePublicKey},
     rand_core::CryptoRng,
     Algorithm, AlgorithmName,
@@ -14,12 +14,19 @@ use tor_hscrypto::pk::{
     HsIdKeypair, HsIntroPtSessionIdKeypair, HsSvcNtorKeypair,
 };
 use tor_llcrypto::pk::{curve25519, ed25519};
+use zeroize::Zeroizing;
 
 use crate::{
+    macros::deps::{assert_zeroize_and_drop, assert_zeroize_on_drop},
     ssh::{SshKeyData, ED25519_EXPANDED_ALGORITHM_NAME, X25519_ALGORITHM_NAME},
-    KeyType, Result,
+    KeyType, Result, SecretBuffer,
 };
 
+// The keypairs we encode must zero their secret key material on drop,
+// so that an [`ErasedKey`](crate::ErasedKey) does too.
+const _: () = assert_zeroize_on_drop::<ed25519::Keypair>();
+const _: () = assert_zeroize_and_drop::<curve25519::StaticSecret>();
+
 /// A random number generator for generating [`EncodableKey`]s.
 pub trait KeygenRng: RngCore + CryptoRng {}
 
@@ -31,6 +38,21 @@ pub trait Keygen {
     fn generate(rng: &mut dyn KeygenRng) -> Result<Self>
     where
         Self: Sized;
+
+    /// Generate `n` new keys of this type, all from the same `rng`.
+    ///
+    /// This is useful for generating keys ahead of time
+    /// (for example, the session keys of our future introduction points).
+    fn generate_batch(rng: &mut dyn KeygenRng, n: usize) -> Result<Vec<Self>>
+    where
+        Self: Sized,
+    {
+        let mut keys = Vec::with_capacity(n);
+        for _ in 0..n {
+            keys.push(Self::generate(rng)?);
+        }
+        Ok(keys)
+    }
 }
 
 /// A key that can be serialized to, and deserialized from.
@@ -87,6 +109,18 @@ where
 
     /// Convert an [`EncodableKey`] to another key type.
     fn from_encodable_key(key: Self::Key) -> Self;
+
+    /// Derive this key from a borrowed [`KeyPair`](ToEncodableKey::KeyPair).
+    ///
+    /// For public keys, this returns the public part of `keypair`,
+    /// without consuming `keypair`, or converting it to (and back from)
+    /// its [`EncodableKey`] representation.
+    ///
+    /// Returns `None` if this key can't be derived from a borrowed keypair.
+    /// This is the case for keypairs, which are deliberately not clonable.
+    fn from_keypair_ref(_keypair: &Self::KeyPair) -> Option<Self> {
+        None
+    }
 }
 
 impl Keygen for curve25519::StaticKeypair {
@@ -110,16 +144,11 @@ impl EncodableKey for curve25519::StaticKeypair {
     }
 
     fn as_ssh_key_data(&self) -> Result<SshKeyData> {
-        let algorithm_name = AlgorithmName::new(X25519_ALGORITHM_NAME)
-            .map_err(|_| internal!("invalid algorithm name"))?;
-
-        let ssh_public = OpaquePublicKey::new(
-            self.public.to_bytes().to_vec(),
-            Algorithm::Other(algorithm_name),
-        );
-        let keypair = OpaqueKeypair::new(self.secret.to_bytes().to_vec(), ssh_public);
-
-        SshKeyData::try_from_keypair_data(KeypairData::Other(keypair))
+        SshKeyData::from_opaque_keypair(
+            X25519_ALGORITHM_NAME,
+            self.public.as_bytes(),
+            SecretBuffer::new(Zeroizing::new(self.secret.to_bytes()).to_vec()),
+        )
     }
 }
 
@@ -204,17 +233,11 @@ impl EncodableKey for ed25519::ExpandedKeypair {
     }
 
     fn as_ssh_key_data(&self) -> Result<SshKeyData> {
-        let algorithm_name = AlgorithmName::new(ED25519_EXPANDED_ALGORITHM_NAME)
-            .map_err(|_| internal!("invalid algorithm name"))?;
-
-        let ssh_public = OpaquePublicKey::new(
-            self.public().to_bytes().to_vec(),
-            Algorithm::Other(algorithm_name),
-        );
-
-        let keypair = OpaqueKeypair::new(self.to_secret_key_bytes().to_vec(), ssh_public);
-
-        SshKeyData::try_from_keypair_data(KeypairData::Other(keypair))
+        SshKeyData::from_opaque_keypair(
+            ED25519_EXPANDED_ALGORITHM_NAME,
+            self.public().as_bytes(),
+            SecretBuffer::new(Zeroizing::new(self.to_secret_key_bytes()).to_vec()),
+        )
     }
 }
 
@@ -259,6 +282,10 @@ impl ToEncodableKey for HsBlindIdKey {
     fn from_encodable_key(key: Self::Key) -> Self {
         HsBlindIdKey::from(key)
     }
+
+    fn from_keypair_ref(keypair: &Self::KeyPair) -> Option<Self> {
+        Some(keypair.into())
+    }
 }
 
 impl ToEncodableKey for HsIdKeypair {
@@ -285,6 +312,10 @@ impl ToEncodableKey for HsIdKey {
     fn from_encodable_key(key: Self::Key) -> Self {
         HsIdKey::from(key)
     }
+
+    fn from_keypair_ref(keypair: &Self::KeyPair) -> Option<Self> {
+        Some(keypair.into())
+    }
 }
 
 impl ToEncodableKey for HsDescSigningKeypair {
@@ -325,3 +356,50 @@ impl ToEncodableKey for HsSvcNtorKeypair {
         key.into()
     }
 }
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use std::collections::HashSet;
+    use tor_basic_utils::test_rng::testing_rng;
+    use tor_hscrypto::pk::HsId;
+
+    #[test]
+    fn generate_batch() {
+        let mut rng = testing_rng();
+        let keys = ed25519::Keypair::generate_batch(&mut rng, 8).unwrap();
+        assert_eq!(keys.len(), 8);
+        let distinct: HashSet<_> = keys.iter().map(|k| k.verifying_key().to_bytes()).collect();
+        assert_eq!(distinct.len(), 8);
+
+        let keys = curve25519::StaticKeypair::generate_batch(&mut rng, 0).unwrap();
+        assert!(keys.is_empty());
+    }
+
+    #[test]
+    fn from_keypair_ref() {
+        let mut rng = testing_rng();
+        let keypair =
+            HsIdKeypair::from(<ed25519::ExpandedKeypair as Keygen>::generate(&mut rng).unwrap());
+        let public = HsIdKey::from_keypair_ref(&keypair).unwrap();
+        assert_eq!(HsId::from(public), HsId::from(HsIdKey::from(keypair)));
+
+        let keypair = HsIntroPtSessionIdKeypair::from(
+            <ed25519::Keypair as Keygen>::generate(&mut rng).unwrap(),
+        );
+        assert!(HsIntroPtSessionIdKeypair::from_keypair_ref(&keypair).is_none());
+    }
+}
@@ -1,6 +1,9 @@
 //! Configuration options for types implementing [`Keystore`](crate::Keystore)
 
-pub use tor_config::{CfgPath, CfgPathError, ConfigBuildError, ConfigurationSource, Reconfigure};
+pub use tor_config::{
+    CfgPath, CfgPathError, CfgSecret, CfgSecretError, ConfigBuildError, ConfigurationSource,
+    Reconfigure,
+};
 
 use amplify::Getters;
 use derive_builder::Builder;
@@ -26,6 +29,9 @@ pub enum ArtiKeystoreKind {
     /// Use the [`ArtiEphemeralKeystore`](crate::ArtiEphemeralKeystore).
     #[cfg(feature = "ephemeral-keystore")]
     Ephemeral,
+    /// Use the [`ArtiEncryptedKeystore`](crate::ArtiEncryptedKeystore).
+    #[cfg(feature = "encrypted-keystore")]
+    Encrypted,
 }
 impl_not_auto_value! {ArtiKeystoreKind}
 
@@ -48,9 +54,11 @@ pub struct ArtiKeystoreConfig {
 
     /// Optionally configure C Tor keystores for arti to use.
     ///
-    /// Note: The keystores listed here are read-only (keys are only
-    /// ever written to the primary keystore, configured in
-    /// `storage.keystore.primary`).
+    /// Note: Arti never writes to the keystores listed here of its own accord
+    /// (newly generated keys are only ever written to the primary keystore,
+    /// configured in `storage.keystore.primary`).
+    /// Keys are only written to a C Tor keystore if it is explicitly selected
+    /// using its [`KeystoreId`].
     ///
     /// Each C Tor keystore **must** have a unique identifier.
     /// It is an error to configure multiple keystores with the same [`KeystoreId`].
@@ -88,6 +96,18 @@ pub struct PrimaryKeystoreConfig {
     #[builder_field_attr(serde(default))]
     #[builder(default)]
     kind: ExplicitOrAuto<ArtiKeystoreKind>,
+
+    /// Where to find the passphrase of the keystore, if it is
+    /// [encrypted](ArtiKeystoreKind::Encrypted).
+    ///
+    /// This is usually a reference to a secret kept outside the configuration,
+    /// such as `"env:ARTI_KEYSTORE_PASSPHRASE"`: see [`CfgSecret`].
+    /// If it is set, it is used instead of asking the user for the passphrase.
+    ///
+    /// It is an error to set this for a keystore that isn't encrypted.
+    #[builder_field_attr(serde(default))]
+    #[builder(default)]
+    passphrase: Option<CfgSecret>,
 }
 
 /// C Tor [`ArtiNativeKeystore`](crate::ArtiNativeKeystore) configuration
@@ -300,6 +320,11 @@ impl ArtiKeystoreConfig {
         Some(kind)
     }
 
+    /// Where to find the passphrase of the primary keystore, if it is configured
+    pub fn primary_passphrase(&self) -> Option<&CfgSecret> {
+        self.primary.passphrase.as_ref()
+    }
+
     /// The ctor keystore configs
     pub fn ctor_svc_stores(&self) -> impl Iterator<Item = &CTorServiceKeystoreConfig> {
         self.ctor.services.values()
@@ -343,8 +368,21 @@ impl ArtiKeystoreConfigBuilder {
 
     /// Check that the keystore configuration is valid
     #[cfg(feature = "keymgr")]
-    #[allow(clippy::unnecessary_wraps)]
     fn validate(&self) -> Result<(), ConfigBuildError> {
+        // Only the encrypted keystore has a passphrase.
+        #[allow(clippy::match_like_matches_macro)]
+        let encrypted = match self.primary.kind {
+            #[cfg(feature = "encrypted-keystore")]
+            Some(ExplicitOrAuto::Explicit(ArtiKeystoreKind::Encrypted)) => true,
+            _ => false,
+        };
+        if matches!(self.primary.passphrase, Some(Some(_))) && !encrypted {
+            return Err(ConfigBuildError::Inconsistent {
+                fields: ["kind", "passphrase"].map(Into::into).into_iter().collect(),
+                problem: "passphrase configured, but the keystore is not encrypted".into(),
+            });
+        }
+
         Ok(())
     }
 
@@ -563,6 +601,35 @@ mod test {
         );
     }
 
+    #[test]
+    #[cfg(feature = "keymgr")]
+    fn passphrase() {
+        let mut builder = ArtiKeystoreConfigBuilder::default();
+        builder
+            .primary()
+            .passphrase(Some(CfgSecret::new("env:ARTI_TEST_PASSPHRASE".into())));
+        let err = builder.build().unwrap_err();
+        assert_config_error!(
+            err,
+            Inconsistent,
+            "passphrase configured, but the keystore is not encrypted"
+        );
+
+        #[cfg(feature = "encrypted-keystore")]
+        {
+            builder
+                .primary()
+                .kind(ExplicitOrAuto::Explicit(ArtiKeystoreKind::Encrypted));
+            let config = builder.build().unwrap();
+            assert_eq!(
+                config
+                    .primary_passphrase()
+                    .and_then(CfgSecret::as_reference),
+                Some("env:ARTI_TEST_PASSPHRASE")
+            );
+        }
+    }
+
     #[test]
     #[cfg(feature = "ctor-keystore")]
     fn valid_config() {
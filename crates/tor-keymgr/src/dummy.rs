@@ -15,6 +15,7 @@ use fs_mistrust::Mistrust;
 use std::any::Any;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
 /// A dummy key manager implementation.
 ///
@@ -32,6 +33,9 @@ pub struct KeyMgr {
     /// The secondary key stores.
     #[builder(default, setter(custom))]
     secondary_stores: Vec<BoxedKeystore>,
+    /// How long each key store operation may take.
+    #[builder(default, setter(strip_option))]
+    operation_timeout: Option<Duration>,
 }
 
 // TODO: auto-generate using define_list_builder_accessors/define_list_builder_helper
@@ -112,6 +116,33 @@ impl ArtiNativeKeystore {
     pub fn from_path_and_mistrust(_: impl AsRef<Path>, _: &Mistrust) -> Result<Self> {
         Ok(Self)
     }
+
+    /// Create a new [`ArtiNativeKeystore`].
+    #[allow(clippy::unnecessary_wraps)]
+    pub fn from_path_and_mistrust_with_ctx(
+        _: impl AsRef<Path>,
+        _: &Mistrust,
+        _: &OpContext,
+    ) -> Result<Self> {
+        Ok(Self)
+    }
+}
+
+/// A dummy `OpContext`.
+#[non_exhaustive]
+#[derive(Clone, Debug, Default)]
+pub struct OpContext;
+
+impl OpContext {
+    /// Create a new [`OpContext`].
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// A dummy `with_timeout` implementation that ignores the timeout.
+    pub fn with_timeout(self, _: Duration) -> Self {
+        self
+    }
 }
 
 impl Keystore for ArtiNativeKeystore {}
@@ -154,3 +185,4 @@ impl KeyMgr {
 }
 
 inventory::collect!(&'static dyn crate::KeyPathInfoExtractor);
+inventory::collect!(&'static dyn crate::KeyPathTranslator);
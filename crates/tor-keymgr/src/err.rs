@@ -34,10 +34,53 @@ pub enum Error {
     #[error("Key already exists")]
     KeyAlreadyExists,
 
+    /// The raw contents of a keystore entry are not an OpenSSH key.
+    ///
+    /// Returned by [`RawKeyData::to_ssh_key_data`](crate::RawKeyData::to_ssh_key_data).
+    #[error("Raw key data is not an OpenSSH key")]
+    NotAnSshKey,
+
+    /// A key was not of the type that it was expected to be.
+    ///
+    /// Returned by [`KeyMgr::import_openssh`](crate::KeyMgr::import_openssh).
+    #[error(
+        "Expected a {} key, but found a {} key",
+        .expected.arti_extension(),
+        .found.arti_extension()
+    )]
+    WrongKeyType {
+        /// The type of key we expected.
+        expected: tor_key_forge::KeyType,
+        /// The type of key we found.
+        found: tor_key_forge::KeyType,
+    },
+
     /// Error coming from the tor-key-forgecrate
     #[error("{0}")]
     KeyForge(#[from] tor_key_forge::Error),
 
+    /// A key store was asked to record the expiration time of an entry,
+    /// but it doesn't support expiration times.
+    ///
+    /// Returned by [`KeyMgr::set_expiry`](crate::KeyMgr::set_expiry).
+    #[error("Key store {0} does not support expiration times")]
+    ExpiryNotSupported(crate::KeystoreId),
+
+    /// Failed to watch the key stores for changes.
+    ///
+    /// Returned by [`KeyMgr::watch`](crate::KeyMgr::watch).
+    #[cfg(feature = "watch")]
+    #[error("Failed to watch the key stores for changes")]
+    Watch(#[source] Arc<notify::Error>),
+
+    /// A key store operation did not complete by the deadline of its `OpContext`.
+    #[error("Key store operation timed out")]
+    Timeout,
+
+    /// A key store operation was cancelled through the `CancellationToken` of its `OpContext`.
+    #[error("Key store operation cancelled")]
+    Cancelled,
+
     /// An internal error.
     #[error("Internal error")]
     Bug(#[from] tor_error::Bug),
@@ -59,6 +102,13 @@ impl HasKind for Error {
             E::Corruption(_) => EK::KeystoreCorrupted,
             E::KeyAlreadyExists => EK::BadApiUsage, // TODO: not strictly right
             E::KeyForge(_) => EK::BadApiUsage,
+            E::NotAnSshKey => EK::BadApiUsage,
+            E::WrongKeyType { .. } => EK::BadApiUsage,
+            E::ExpiryNotSupported(_) => EK::NotImplemented,
+            #[cfg(feature = "watch")]
+            E::Watch(_) => EK::KeystoreAccessFailed,
+            E::Timeout => EK::KeystoreAccessFailed,
+            E::Cancelled => EK::Other,
             E::Bug(e) => e.kind(),
         }
     }
@@ -19,6 +19,14 @@ use crate::{ArtiPath, ArtiPathSyntaxError};
 // #[doc(hidden)] applied at crate toplevel
 #[macro_use]
 pub mod derive;
+mod pattern;
+mod template;
+
+pub use pattern::{ArtiPathPatternBuilder, ArtiPathPatternError};
+pub use template::{
+    KeyPathTemplate, KeyPathTemplateError, KeySpecifierBuilder, TemplateMatch,
+    TemplatedKeySpecifier,
+};
 
 /// The identifier of a key.
 #[derive(Clone, Debug, PartialEq, Eq, Hash, From, derive_more::Display)]
@@ -291,6 +299,72 @@ pub trait KeyPathInfoExtractor: Send + Sync {
     fn describe(&self, path: &KeyPath) -> StdResult<KeyPathInfo, KeyPathError>;
 }
 
+/// A [`KeyPathInfoExtractor`] for the [`ArtiPath`]s that match a glob pattern.
+///
+/// This is useful for describing keys whose paths have a fixed shape,
+/// but which aren't stored using a derived [`KeySpecifier`]:
+/// `describe` is only called for paths that match `pattern`,
+/// and is given the ranges of the path that matched each wildcard
+/// (see [`ArtiPath::matches`]).
+///
+/// ### Example
+/// ```
+/// # use tor_keymgr::{ArtiPath, ArtiPathRange, KeyPathError, KeyPathInfo};
+/// # use tor_keymgr::{PatternKeyInfoExtractor, register_key_info_extractor};
+/// fn describe_backup(
+///     path: &ArtiPath,
+///     ranges: &[ArtiPathRange],
+/// ) -> Result<KeyPathInfo, KeyPathError> {
+///     let name = ranges.first().and_then(|r| path.substring(r)).unwrap_or_default();
+///     Ok(KeyPathInfo::builder()
+///         .summary("Backup key".into())
+///         .role("backup".into())
+///         .extra_info("name", name)
+///         .build()
+///         .expect("missing fields"))
+/// }
+///
+/// register_key_info_extractor!(PatternKeyInfoExtractor::new("backup/*", describe_backup));
+/// ```
+#[derive(Clone, Copy)]
+pub struct PatternKeyInfoExtractor {
+    /// The pattern that the paths we describe must match.
+    pattern: &'static str,
+    /// The function that turns a matching path into a [`KeyPathInfo`].
+    describe: fn(&ArtiPath, &[ArtiPathRange]) -> StdResult<KeyPathInfo, KeyPathError>,
+}
+
+impl PatternKeyInfoExtractor {
+    /// Create an extractor that uses `describe` to describe the paths that match `pattern`.
+    ///
+    /// `pattern` has the syntax described in [`KeyPathPattern`].
+    pub const fn new(
+        pattern: &'static str,
+        describe: fn(&ArtiPath, &[ArtiPathRange]) -> StdResult<KeyPathInfo, KeyPathError>,
+    ) -> Self {
+        Self { pattern, describe }
+    }
+}
+
+impl fmt::Debug for PatternKeyInfoExtractor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PatternKeyInfoExtractor")
+            .field("pattern", &self.pattern)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KeyPathInfoExtractor for PatternKeyInfoExtractor {
+    fn describe(&self, path: &KeyPath) -> StdResult<KeyPathInfo, KeyPathError> {
+        let unrecognized = || KeyPathError::Unrecognized(path.clone());
+        let arti_path = path.arti().ok_or_else(unrecognized)?;
+        let ranges = arti_path
+            .matches(&KeyPathPattern::Arti(self.pattern.into()))
+            .ok_or_else(unrecognized)?;
+        (self.describe)(arti_path, &ranges)
+    }
+}
+
 /// Register a [`KeyPathInfoExtractor`] for use with [`KeyMgr`](crate::KeyMgr).
 #[macro_export]
 macro_rules! register_key_info_extractor {
@@ -299,6 +373,28 @@ macro_rules! register_key_info_extractor {
     }};
 }
 
+/// A trait for converting between the [`ArtiPath`] and the [`CTorPath`] of a key.
+///
+/// This trait is used by [`KeyMgr::sync_stores`](crate::KeyMgr::sync_stores)
+/// to work out where a key read from an Arti key store belongs in a C Tor key store,
+/// and vice versa.
+///
+/// An implementation is generated (and registered) by the
+/// [`KeySpecifier`](crate::derive_deftly_template_KeySpecifier) derive
+/// for each key specifier that has a `ctor_path`.
+pub trait KeyPathTranslator: Send + Sync {
+    /// Return the [`CTorPath`] of the key with the specified [`ArtiPath`].
+    ///
+    /// Returns `None` if `path` is not recognized by this translator,
+    /// or if the key it identifies can't be stored in a C Tor key store.
+    fn ctor_path(&self, path: &ArtiPath) -> Option<CTorPath>;
+
+    /// Return the [`ArtiPath`] of the key with the specified [`CTorPath`].
+    ///
+    /// Returns `None` if `path` is not recognized by this translator.
+    fn arti_path(&self, path: &CTorPath) -> Option<ArtiPath>;
+}
+
 /// A pattern that can be used to match [`ArtiPath`]s or [`CTorPath`]s.
 ///
 /// Create a new `KeyPathPattern`.
@@ -327,6 +423,65 @@ pub enum KeyPathPattern {
     CTor(CTorPath),
 }
 
+/// A set of [`KeyPathPattern`]s.
+///
+/// A [`KeyPath`] matches the set if it matches any of its patterns.
+/// The empty set doesn't match anything.
+///
+/// ### Example
+/// ```
+/// # use tor_keymgr::{ArtiPath, KeyPath, KeyPathPattern, KeyPathPatternSet, ArtiPathSyntaxError};
+/// # fn demo() -> Result<(), ArtiPathSyntaxError> {
+/// let set: KeyPathPatternSet = [
+///     KeyPathPattern::Arti("hss/*/ks_hs_id".into()),
+///     KeyPathPattern::Arti("hss/*/ks_hs_blind_id+*".into()),
+/// ]
+/// .into_iter()
+/// .collect();
+/// assert!(set.matches(&KeyPath::Arti(ArtiPath::new("hss/foo/ks_hs_id".into())?)));
+/// assert!(!set.matches(&KeyPath::Arti(ArtiPath::new("client/foo/ks_hs_id".into())?)));
+/// # Ok(())
+/// # }
+/// #
+/// # demo().unwrap();
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeyPathPatternSet(Vec<KeyPathPattern>);
+
+impl KeyPathPatternSet {
+    /// Create an empty set of patterns.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `pat` to this set.
+    pub fn push(&mut self, pat: KeyPathPattern) {
+        self.0.push(pat);
+    }
+
+    /// Return the patterns in this set.
+    pub fn patterns(&self) -> &[KeyPathPattern] {
+        &self.0
+    }
+
+    /// Check whether `path` matches any of the patterns in this set.
+    pub fn matches(&self, path: &KeyPath) -> bool {
+        self.0.iter().any(|pat| path.matches(pat))
+    }
+}
+
+impl From<KeyPathPattern> for KeyPathPatternSet {
+    fn from(pat: KeyPathPattern) -> Self {
+        Self(vec![pat])
+    }
+}
+
+impl FromIterator<KeyPathPattern> for KeyPathPatternSet {
+    fn from_iter<I: IntoIterator<Item = KeyPathPattern>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
 /// The path of a key in the C Tor key store.
 #[derive(Clone, Debug, PartialEq, Eq, Hash, derive_more::Display)] //
 #[non_exhaustive]
@@ -1034,6 +1189,60 @@ KeyPathInfo {
             .unwrap(),
             KeyPathPattern::Arti("encabulator/logarithmic/prefabulating/fan+*+*+*".into())
         );
+
+        assert_eq!(
+            TestSpecifierPattern::new_any()
+                .bearings("spurving".into())
+                .length(2000)
+                .arti_pattern()
+                .unwrap(),
+            KeyPathPattern::Arti("encabulator/*/spurving/fan+*+2000+*".into())
+        );
+    }
+
+    #[test]
+    fn define_key_specifier_keypair_specifier() {
+        #[derive(Deftly, Debug, PartialEq)]
+        #[derive_deftly(KeySpecifier)]
+        #[deftly(prefix = "encabulator")]
+        #[deftly(role = "ks_fan")]
+        #[deftly(summary = "test keypair")]
+        struct TestKeypairSpecifier {
+            casing: String,
+            #[deftly(denotator)]
+            count: usize,
+        }
+
+        #[derive(Deftly, Debug, PartialEq)]
+        #[derive_deftly(KeySpecifier)]
+        #[deftly(prefix = "encabulator")]
+        #[deftly(role = "kp_fan")]
+        #[deftly(summary = "test public key")]
+        #[deftly(keypair_specifier = "TestKeypairSpecifier")]
+        struct TestPublicKeySpecifier {
+            casing: String,
+            #[deftly(denotator)]
+            count: usize,
+        }
+
+        let key_spec = TestPublicKeySpecifier {
+            casing: "logarithmic".into(),
+            count: 6,
+        };
+        check_key_specifier(&key_spec, "encabulator/logarithmic/kp_fan+6");
+
+        let keypair_spec = key_spec.keypair_specifier().unwrap();
+        assert_eq!(
+            keypair_spec.arti_path().unwrap().as_str(),
+            "encabulator/logarithmic/ks_fan+6"
+        );
+        assert_eq!(
+            TestKeypairSpecifier::from(&key_spec),
+            TestKeypairSpecifier {
+                casing: "logarithmic".into(),
+                count: 6,
+            }
+        );
     }
 
     #[test]
@@ -1094,6 +1303,57 @@ KeyPathInfo {
         );
     }
 
+    #[test]
+    fn define_key_specifier_path_translator() {
+        #[derive(Deftly, Debug, Eq, PartialEq)]
+        #[derive_deftly(KeySpecifier)]
+        #[deftly(prefix = "p")]
+        #[deftly(role = "r")]
+        #[deftly(ctor_path = "Self::ctp")]
+        #[deftly(from_ctor_path = "TestSpecifier::from_ctp")]
+        #[deftly(summary = "test key")]
+        struct TestSpecifier {
+            nickname: HsNickname,
+        }
+
+        impl TestSpecifier {
+            fn ctp(&self) -> CTorPath {
+                CTorPath::service(self.nickname.clone(), CTorServicePath::PublicKey)
+            }
+
+            fn from_ctp(path: &CTorPath) -> Option<Self> {
+                match path {
+                    CTorPath::Service {
+                        nickname,
+                        path: CTorServicePath::PublicKey,
+                    } => Some(TestSpecifier {
+                        nickname: nickname.clone(),
+                    }),
+                    _ => None,
+                }
+            }
+        }
+
+        let nickname = HsNickname::from_str("allium-cepa").unwrap();
+        let arti_path = ArtiPath::new("p/allium-cepa/r".into()).unwrap();
+        let ctor_path = CTorPath::service(nickname, CTorServicePath::PublicKey);
+        let translator = TestSpecifierPathTranslator;
+
+        assert_eq!(translator.ctor_path(&arti_path), Some(ctor_path.clone()));
+        assert_eq!(translator.arti_path(&ctor_path), Some(arti_path));
+
+        let other_nickname = HsNickname::from_str("acutus-cepa").unwrap();
+        let private_key = CTorPath::service(other_nickname, CTorServicePath::PrivateKey);
+        assert_eq!(translator.arti_path(&private_key), None);
+        let unrelated = ArtiPath::new("q/allium-cepa/r".into()).unwrap();
+        assert_eq!(translator.ctor_path(&unrelated), None);
+
+        // The translator is registered with inventory
+        assert!(inventory::iter::<&'static dyn KeyPathTranslator>
+            .into_iter()
+            .any(|t| t.arti_path(&ctor_path).is_some()));
+    }
+
     #[test]
     fn define_key_specifier_fixed_path_component() {
         #[derive(Deftly, Debug, Eq, PartialEq)]
@@ -1196,4 +1456,37 @@ KeyPathInfo {
 
         assert_extra_info_eq!(key_info, [("time period", "100"), ("type", "service"),]);
     }
+
+    #[test]
+    fn pattern_info_extractor() {
+        #[allow(clippy::unnecessary_wraps)] // The signature is required by PatternKeyInfoExtractor
+        fn describe(
+            path: &ArtiPath,
+            ranges: &[ArtiPathRange],
+        ) -> StdResult<KeyPathInfo, KeyPathError> {
+            let parts = ranges.iter().map(|r| path.substring(r).unwrap()).join(",");
+            Ok(KeyPathInfo::builder()
+                .summary("Vote signing key".into())
+                .role("KS_vote".into())
+                .extra_info("parts", parts)
+                .build()
+                .unwrap())
+        }
+        let extractor = PatternKeyInfoExtractor::new("dirauth/*/ks_vote+*", describe);
+
+        let path = KeyPath::Arti(ArtiPath::new("dirauth/moria1/ks_vote+42".into()).unwrap());
+        let info = extractor.describe(&path).unwrap();
+        assert_eq!(info.role(), "KS_vote");
+        assert_eq!(info.extra_info()["parts"], "moria1,42");
+
+        for unrecognized in [
+            KeyPath::Arti(ArtiPath::new("dirauth/moria1/ks_sign".into()).unwrap()),
+            KeyPath::CTor(CTorPath::ClientHsDescEncKey(HsId::from([0; 32]))),
+        ] {
+            assert!(matches!(
+                extractor.describe(&unrecognized),
+                Err(KeyPathError::Unrecognized(p)) if p == unrecognized
+            ));
+        }
+    }
 }
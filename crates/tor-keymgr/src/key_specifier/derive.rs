@@ -344,9 +344,19 @@ define_derive_deftly! {
     ///    a derived struct which contains an `Option` for each field.
     ///    `None` in the pattern means "any".
     ///  * `impl `[`KeySpecifierPattern`]` for SomeKeySpecPattern`
+    ///  * For each field `f`, a method `SomeKeySpecPattern::f(self, value)`
+    ///    which restricts the pattern to keys whose `f` is `value`.
+    ///    Starting from [`KeySpecifierPattern::new_any`],
+    ///    these can be used to build a pattern that has wildcards
+    ///    for any subset of the fields (for instance, for every denotator).
     ///  * `impl TryFrom<`[`KeyPath`]> for SomeKeySpec`
     ///  * Registration of an impl of [`KeyPathInfoExtractor`]
     ///    (on a private unit struct `SomeKeySpecInfoExtractor`)
+    ///  * If `#[deftly(keypair_specifier)]` is specified,
+    ///    `impl From<&SomeKeySpec> for` the keypair specifier type
+    ///  * If `#[deftly(ctor_path)]` is specified,
+    ///    registration of an impl of [`KeyPathTranslator`]
+    ///    (on a private unit struct `SomeKeySpecPathTranslator`)
     ///
     /// ### Custom attributes
     ///
@@ -360,7 +370,7 @@ define_derive_deftly! {
     ///    Must be a literal string.
     ///    This or the field-level `#[deftly(role)]` must be specified.
     ///
-    ///  * **`[adhoc(role)]` (field):
+    ///  * **`#[deftly(role)]`** (field):
     ///    Specifies that the role is determined at runtime.
     ///    The field type must implement [`KeyDenotator`].
     ///
@@ -381,15 +391,31 @@ define_derive_deftly! {
     ///    If not specified, the generated [`KeySpecifier::ctor_path`]
     ///    implementation will always return `None`.
     ///
+    ///  * **`#[deftly(from_ctor_path = "expression")]`** (toplevel):
+    ///    Provides an expression for recovering the key specifier
+    ///    from a [`CTorPath`](crate::CTorPath).
+    ///    The expression should have type `impl Fn(&CTorPath) -> Option<Self>`,
+    ///    returning `None` for paths that don't belong to this kind of key.
+    ///    Only meaningful if `ctor_path` is specified.
+    ///
+    ///    If not specified, the generated [`KeyPathTranslator`]
+    ///    can't translate `CTorPath`s to `ArtiPath`s,
+    ///    so keys of this kind can't be copied from C Tor key stores
+    ///    by [`KeyMgr::sync_stores`](crate::KeyMgr::sync_stores).
+    ///
     ///  * **`#[deftly(fixed_path_component = "component")]`** (field):
     ///    Before this field insert a fixed path component `component`.
     ///    (Can be even used before a denotator component,
     ///    to add a final fixed path component.)
     ///
-    ///  * **`#[deftly(key_specifier = "type")]`** (field):
+    ///  * **`#[deftly(keypair_specifier = "type")]`** (toplevel):
     ///    If this is the specifier for a public key, the specifier for
     ///    the corresponding keypair type.
     ///
+    ///    The keypair specifier type must be a struct
+    ///    with the same fields as this one (all of which must be `Clone`):
+    ///    the conversion from this specifier to the keypair specifier is generated.
+    ///
     ///    If not specified, the generated [`KeySpecifier::keypair_specifier`]
     ///    implementation will always return `None`.
     //
@@ -540,6 +566,33 @@ define_derive_deftly! {
         }
     }
 
+    impl<$tgens> $<$tname Pattern><$tdefgens>
+    where $twheres
+    {
+      $(
+        #[doc = concat!("Restrict this pattern to keys whose `", stringify!($fname), "` is `value`.")]
+        ///
+        /// Fields that have not been restricted match any value.
+        #[allow(dead_code)] // Not everyone will need every field
+        $fvis fn $fname(mut self, value: $ftype) -> Self {
+            self.$fname = Some(value);
+            self
+        }
+      )
+    }
+
+    ${if tmeta(keypair_specifier) {
+        impl<$tgens> std::convert::From<&$ttype> for ${tmeta(keypair_specifier) as token_stream}
+        where $twheres
+        {
+            fn from(spec: &$ttype) -> Self {
+                Self {
+                    $( $fname: std::clone::Clone::clone(&spec.$fname), )
+                }
+            }
+        }
+    }}
+
     struct $< $tname InfoExtractor >;
 
     impl<$tgens> $crate::KeyPathInfoExtractor for $< $tname InfoExtractor >
@@ -630,4 +683,30 @@ define_derive_deftly! {
 
     // Register the info extractor with `KeyMgr`.
     $crate::inventory::submit!(&$< $tname InfoExtractor > as &dyn $crate::KeyPathInfoExtractor);
+
+    ${if tmeta(ctor_path) {
+        struct $< $tname PathTranslator >;
+
+        impl<$tgens> $crate::KeyPathTranslator for $< $tname PathTranslator >
+        where $twheres
+        {
+            fn ctor_path(&self, path: &$crate::ArtiPath) -> Option<$crate::CTorPath> {
+                let spec = $ttype::try_from(&$crate::KeyPath::Arti(path.clone())).ok()?;
+                $crate::KeySpecifier::ctor_path(&spec)
+            }
+
+            fn arti_path(&self, path: &$crate::CTorPath) -> Option<$crate::ArtiPath> {
+                ${if tmeta(from_ctor_path) {
+                    let spec: $ttype = ${tmeta(from_ctor_path) as token_stream} (path)?;
+                    $crate::KeySpecifier::arti_path(&spec).ok()
+                } else {
+                    let _ = path;
+                    None
+                }}
+            }
+        }
+
+        // Register the path translator with `KeyMgr`.
+        $crate::inventory::submit!(&$< $tname PathTranslator > as &dyn $crate::KeyPathTranslator);
+    }}
 }
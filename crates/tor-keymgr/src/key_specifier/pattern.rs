@@ -0,0 +1,292 @@
+//! A typed builder for [`KeyPathPattern`]s.
+
+use std::result::Result as StdResult;
+
+use tor_error::Bug;
+use tor_persist::slug::{BadSlug, Slug};
+
+use super::{KeyPathPattern, KeySpecifierComponent};
+use crate::arti_path::PATH_SEP;
+use crate::DENOTATOR_SEP;
+
+/// A builder for [`KeyPathPattern::Arti`] patterns.
+///
+/// Unlike a pattern written by hand,
+/// a pattern built this way has the same shape as an [`ArtiPath`](crate::ArtiPath):
+/// it is a nonempty sequence of path components separated by `/`,
+/// optionally followed by some `+`-separated denotators.
+/// Each path component and denotator is either a literal [`Slug`]
+/// (which can't contain any glob metacharacters),
+/// or a wildcard.
+///
+/// Returned by [`KeyPathPattern::arti_builder`].
+///
+/// ### Example
+/// ```
+/// # use tor_keymgr::KeyPathPattern;
+/// # fn demo() -> Result<(), tor_keymgr::ArtiPathPatternError> {
+/// let pattern = KeyPathPattern::arti_builder()
+///     .literal("hss")?
+///     .any_component()
+///     .literal("ks_hs_blind_id")?
+///     .any_denotator()
+///     .build()?;
+/// assert_eq!(pattern, KeyPathPattern::Arti("hss/*/ks_hs_blind_id+*".into()));
+/// # Ok(())
+/// # }
+/// #
+/// # demo().unwrap();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ArtiPathPatternBuilder {
+    /// The path components.
+    path: Vec<PatternComponent>,
+    /// The denotators of the last path component.
+    denotators: Vec<PatternComponent>,
+    /// Whether a path component was added after a denotator.
+    component_after_denotator: bool,
+}
+
+/// A path component or denotator of an [`ArtiPathPatternBuilder`].
+#[derive(Clone, Debug)]
+enum PatternComponent {
+    /// A literal value.
+    Literal(Slug),
+    /// Any single path component or denotator (`*`).
+    Any,
+    /// Any number of path components, including none (`**`).
+    ///
+    /// Never used for denotators.
+    AnyComponents,
+}
+
+impl PatternComponent {
+    /// Return the glob syntax for this component.
+    fn as_glob(&self) -> &str {
+        match self {
+            PatternComponent::Literal(slug) => slug.as_str(),
+            PatternComponent::Any => "*",
+            PatternComponent::AnyComponents => "**",
+        }
+    }
+}
+
+/// An error caused by an invalid [`ArtiPathPatternBuilder`].
+#[derive(thiserror::Error, Debug, Clone)]
+#[non_exhaustive]
+pub enum ArtiPathPatternError {
+    /// A literal component of the pattern is not a valid [`Slug`].
+    #[error("Invalid component {0:?} in key path pattern")]
+    InvalidLiteral(String, #[source] BadSlug),
+
+    /// The pattern has no path components.
+    #[error("Key path pattern has no path components")]
+    Empty,
+
+    /// A path component was added after a denotator.
+    #[error("Key path pattern has a path component after a denotator")]
+    ComponentAfterDenotator,
+
+    /// The pattern has denotators, but its last path component is a `**` wildcard.
+    #[error("Key path pattern has denotators after a `**` wildcard")]
+    DenotatorAfterAnyComponents,
+
+    /// An internal error.
+    #[error("Internal error")]
+    Bug(#[from] Bug),
+}
+
+impl KeyPathPattern {
+    /// Start to build a [`KeyPathPattern::Arti`] pattern.
+    ///
+    /// See [`ArtiPathPatternBuilder`].
+    pub fn arti_builder() -> ArtiPathPatternBuilder {
+        ArtiPathPatternBuilder::default()
+    }
+}
+
+impl ArtiPathPatternBuilder {
+    /// Append a literal path component.
+    ///
+    /// Returns an error if `comp` is not a valid [`Slug`].
+    pub fn literal(&mut self, comp: &str) -> StdResult<&mut Self, ArtiPathPatternError> {
+        let slug = Slug::new(comp.to_owned())
+            .map_err(|e| ArtiPathPatternError::InvalidLiteral(comp.to_owned(), e))?;
+        Ok(self.push_component(PatternComponent::Literal(slug)))
+    }
+
+    /// Append a path component with the value `value`.
+    pub fn component(
+        &mut self,
+        value: &dyn KeySpecifierComponent,
+    ) -> StdResult<&mut Self, ArtiPathPatternError> {
+        let slug = value.to_slug()?;
+        Ok(self.push_component(PatternComponent::Literal(slug)))
+    }
+
+    /// Append a wildcard matching any single path component.
+    ///
+    /// If this is the last path component, and the pattern has no denotators,
+    /// the wildcard also matches the denotators of the path, if there are any.
+    pub fn any_component(&mut self) -> &mut Self {
+        self.push_component(PatternComponent::Any)
+    }
+
+    /// Append a wildcard matching any number of path components, including none.
+    pub fn any_components(&mut self) -> &mut Self {
+        self.push_component(PatternComponent::AnyComponents)
+    }
+
+    /// Append a denotator with the value `value` to the last path component.
+    pub fn denotator(
+        &mut self,
+        value: &dyn KeySpecifierComponent,
+    ) -> StdResult<&mut Self, ArtiPathPatternError> {
+        let slug = value.to_slug()?;
+        self.denotators.push(PatternComponent::Literal(slug));
+        Ok(self)
+    }
+
+    /// Append a wildcard matching any single denotator to the last path component.
+    pub fn any_denotator(&mut self) -> &mut Self {
+        self.denotators.push(PatternComponent::Any);
+        self
+    }
+
+    /// Build the pattern.
+    ///
+    /// Returns an error if the pattern doesn't have the shape of an
+    /// [`ArtiPath`](crate::ArtiPath).
+    pub fn build(&self) -> StdResult<KeyPathPattern, ArtiPathPatternError> {
+        if self.component_after_denotator {
+            return Err(ArtiPathPatternError::ComponentAfterDenotator);
+        }
+        let last = self.path.last().ok_or(ArtiPathPatternError::Empty)?;
+        if !self.denotators.is_empty() && matches!(last, PatternComponent::AnyComponents) {
+            return Err(ArtiPathPatternError::DenotatorAfterAnyComponents);
+        }
+
+        let mut pattern = itertools::join(
+            self.path.iter().map(PatternComponent::as_glob),
+            &PATH_SEP.to_string(),
+        );
+        for denotator in &self.denotators {
+            pattern.push(DENOTATOR_SEP);
+            pattern.push_str(denotator.as_glob());
+        }
+
+        Ok(KeyPathPattern::Arti(pattern))
+    }
+
+    /// Append `comp` to the path components.
+    fn push_component(&mut self, comp: PatternComponent) -> &mut Self {
+        if !self.denotators.is_empty() {
+            self.component_after_denotator = true;
+        }
+        self.path.push(comp);
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use crate::{ArtiPath, KeyPath};
+    use tor_hscrypto::time::TimePeriod;
+    use tor_persist::hsnickname::HsNickname;
+
+    #[test]
+    fn build() {
+        let nickname = HsNickname::new("shallot".into()).unwrap();
+        let period = TimePeriod::from_parts(1, 2, 3);
+
+        let pat = KeyPathPattern::arti_builder()
+            .literal("hss")
+            .unwrap()
+            .component(&nickname)
+            .unwrap()
+            .literal("ks_hs_blind_id")
+            .unwrap()
+            .denotator(&period)
+            .unwrap()
+            .build()
+            .unwrap();
+        let path = ArtiPath::new(format!(
+            "hss/shallot/ks_hs_blind_id+{}",
+            period.to_slug().unwrap()
+        ))
+        .unwrap();
+        assert!(KeyPath::Arti(path).matches(&pat));
+
+        let pat = KeyPathPattern::arti_builder()
+            .any_components()
+            .literal("ks_hs_id")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(pat, KeyPathPattern::Arti("**/ks_hs_id".into()));
+        for (path, expected) in [
+            ("ks_hs_id", true),
+            ("hss/shallot/ks_hs_id", true),
+            ("hss/shallot/ks_hs_id+1", false),
+            ("hss/shallot/ks_hs_idx", false),
+        ] {
+            let path = KeyPath::Arti(ArtiPath::new(path.into()).unwrap());
+            assert_eq!(path.matches(&pat), expected, "{path}");
+        }
+
+        let pat = KeyPathPattern::arti_builder()
+            .literal("retired")
+            .unwrap()
+            .any_component()
+            .any_denotator()
+            .any_denotator()
+            .build()
+            .unwrap();
+        assert_eq!(pat, KeyPathPattern::Arti("retired/*+*+*".into()));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(matches!(
+            KeyPathPattern::arti_builder().literal("*"),
+            Err(ArtiPathPatternError::InvalidLiteral(s, _)) if s == "*"
+        ));
+        assert!(matches!(
+            KeyPathPattern::arti_builder().literal("a/b"),
+            Err(ArtiPathPatternError::InvalidLiteral(..))
+        ));
+        assert!(matches!(
+            KeyPathPattern::arti_builder().any_denotator().build(),
+            Err(ArtiPathPatternError::Empty)
+        ));
+        assert!(matches!(
+            KeyPathPattern::arti_builder()
+                .any_component()
+                .any_denotator()
+                .any_component()
+                .build(),
+            Err(ArtiPathPatternError::ComponentAfterDenotator)
+        ));
+        assert!(matches!(
+            KeyPathPattern::arti_builder()
+                .any_components()
+                .any_denotator()
+                .build(),
+            Err(ArtiPathPatternError::DenotatorAfterAnyComponents)
+        ));
+    }
+}
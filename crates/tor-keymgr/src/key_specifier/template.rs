@@ -0,0 +1,454 @@
+//! Key specifiers built at runtime from a declarative path template.
+//!
+//! Key specifiers whose shape is known at compile time should use the
+//! [`KeySpecifier`](crate::derive_deftly_template_KeySpecifier) derive-deftly macro instead.
+
+use std::collections::HashMap;
+use std::result::Result as StdResult;
+use std::str::FromStr;
+
+use tor_error::Bug;
+use tor_persist::slug::{BadSlug, Slug};
+
+use super::derive::{
+    arti_path_from_components, arti_pattern_from_components, parse_key_path,
+    RawKeySpecifierComponent, RawKeySpecifierComponentParser,
+};
+use super::{
+    ArtiPathUnavailableError, CTorPath, InvalidKeyPathComponentValue, KeyPath, KeyPathError,
+    KeyPathPattern, KeySpecifier, KeySpecifierComponent,
+};
+use crate::{ArtiPath, DENOTATOR_SEP};
+
+/// A declarative template for the [`ArtiPath`]s of a family of keys.
+///
+/// A template looks like an [`ArtiPath`],
+/// except that any of its components (or denotators)
+/// can be replaced by a field name in braces:
+/// for example, `hss/{nickname}/ks_hs_blind_id+{period}`.
+///
+/// Use [`KeyPathTemplate::builder`] to fill in the fields,
+/// and obtain a [`KeySpecifier`] or a matching [`KeyPathPattern`];
+/// use [`KeyPathTemplate::parse`] to recover the fields from a [`KeyPath`].
+/// Since all three are derived from the same template,
+/// they can't disagree about the shape of the path.
+///
+/// This is intended for applications that only know the shape of their key paths at runtime.
+/// Key specifiers whose shape is known at compile time should use the
+/// [`KeySpecifier`](crate::derive_deftly_template_KeySpecifier) derive-deftly macro instead.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyPathTemplate {
+    /// The components before the last `/`.
+    path: Vec<TemplateComponent>,
+    /// The `+`-separated parts of the last component.
+    leaf: Vec<TemplateComponent>,
+}
+
+/// A component of a [`KeyPathTemplate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum TemplateComponent {
+    /// A fixed component.
+    Literal(SlugComponent),
+    /// A field, filled in at runtime.
+    Field(String),
+}
+
+/// An error caused by an invalid [`KeyPathTemplate`], or by misusing one.
+#[derive(thiserror::Error, Debug, Clone)]
+#[non_exhaustive]
+pub enum KeyPathTemplateError {
+    /// A fixed component of the template is not a valid [`Slug`].
+    #[error("Invalid component {0:?} in key path template")]
+    InvalidLiteral(String, #[source] BadSlug),
+
+    /// A field name is not a valid identifier.
+    #[error("Invalid field name {0:?} in key path template")]
+    InvalidFieldName(String),
+
+    /// A field appears more than once in the template.
+    #[error("Field {0:?} appears more than once in key path template")]
+    DuplicateField(String),
+
+    /// Tried to set a field that is not in the template.
+    #[error("Key path template has no field {0:?}")]
+    UnknownField(String),
+
+    /// Tried to build a key specifier without setting one of the fields.
+    #[error("Field {0:?} of key path template was not set")]
+    MissingField(String),
+
+    /// An internal error.
+    #[error("Internal error")]
+    Bug(#[from] Bug),
+}
+
+impl FromStr for KeyPathTemplate {
+    type Err = KeyPathTemplateError;
+
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        let (path, leaf) = match s.rsplit_once('/') {
+            Some((path, leaf)) => (path.split('/').collect(), leaf),
+            None => (vec![], s),
+        };
+        let leaf: Vec<_> = leaf.split(DENOTATOR_SEP).collect();
+
+        let mut fields: Vec<String> = vec![];
+        let mut parse = |comp: &str| {
+            let Some(name) = comp
+                .strip_prefix('{')
+                .and_then(|comp| comp.strip_suffix('}'))
+            else {
+                return Slug::new(comp.to_owned())
+                    .map(|slug| TemplateComponent::Literal(SlugComponent(slug)))
+                    .map_err(|e| KeyPathTemplateError::InvalidLiteral(comp.to_owned(), e));
+            };
+            let is_ident = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !is_ident {
+                return Err(KeyPathTemplateError::InvalidFieldName(name.to_owned()));
+            }
+            if fields.iter().any(|field| field == name) {
+                return Err(KeyPathTemplateError::DuplicateField(name.to_owned()));
+            }
+            fields.push(name.to_owned());
+            Ok(TemplateComponent::Field(name.to_owned()))
+        };
+
+        Ok(KeyPathTemplate {
+            path: path
+                .into_iter()
+                .map(&mut parse)
+                .collect::<StdResult<_, _>>()?,
+            leaf: leaf
+                .into_iter()
+                .map(&mut parse)
+                .collect::<StdResult<_, _>>()?,
+        })
+    }
+}
+
+impl KeyPathTemplate {
+    /// Return the names of the fields of this template, in the order they appear.
+    pub fn fields(&self) -> impl Iterator<Item = &str> + '_ {
+        self.path
+            .iter()
+            .chain(&self.leaf)
+            .filter_map(|comp| match comp {
+                TemplateComponent::Literal(_) => None,
+                TemplateComponent::Field(name) => Some(name.as_str()),
+            })
+    }
+
+    /// Return a new [`KeySpecifierBuilder`] for filling in the fields of this template.
+    pub fn builder(&self) -> KeySpecifierBuilder<'_> {
+        KeySpecifierBuilder {
+            template: self,
+            values: HashMap::new(),
+            ctor_path: None,
+        }
+    }
+
+    /// Parse `path` according to this template.
+    ///
+    /// Returns [`KeyPathError::PatternNotMatched`] if `path` does not have the shape
+    /// described by this template.
+    pub fn parse(&self, path: &KeyPath) -> StdResult<TemplateMatch, KeyPathError> {
+        /// A parser for one component of the template.
+        enum Parser<'a> {
+            /// A fixed component, which must match exactly.
+            Literal(&'a str),
+            /// A field, and (once parsed) its value.
+            Field(&'a str, Option<SlugComponent>),
+        }
+
+        /// Make a parser for each of `comps`.
+        fn parsers(comps: &[TemplateComponent]) -> Vec<Parser<'_>> {
+            comps
+                .iter()
+                .map(|comp| match comp {
+                    TemplateComponent::Literal(lit) => Parser::Literal(lit.0.as_str()),
+                    TemplateComponent::Field(name) => Parser::Field(name, None),
+                })
+                .collect()
+        }
+
+        /// Borrow each of `parsers`, in the form `parse_key_path` wants.
+        fn borrow<'p>(
+            parsers: &'p mut [Parser<'_>],
+        ) -> Vec<&'p mut dyn RawKeySpecifierComponentParser> {
+            parsers
+                .iter_mut()
+                .map(|parser| match parser {
+                    Parser::Literal(lit) => lit as &mut dyn RawKeySpecifierComponentParser,
+                    Parser::Field(_, value) => value as &mut dyn RawKeySpecifierComponentParser,
+                })
+                .collect()
+        }
+
+        let mut path_parsers = parsers(&self.path);
+        let mut leaf_parsers = parsers(&self.leaf);
+        let fields: Vec<&str> = self.fields().collect();
+        parse_key_path(
+            path,
+            &fields.as_slice(),
+            &mut borrow(&mut path_parsers),
+            &mut borrow(&mut leaf_parsers),
+        )?;
+
+        let values = path_parsers
+            .into_iter()
+            .chain(leaf_parsers)
+            .filter_map(|parser| match parser {
+                Parser::Literal(_) => None,
+                Parser::Field(name, value) => Some((name.to_owned(), value?)),
+            })
+            .collect();
+        Ok(TemplateMatch { values })
+    }
+
+    /// Return the components of this template, with the fields filled in from `values`,
+    /// or with `missing` if they aren't present.
+    ///
+    /// Returns an error if a field is missing and `missing` is `None`.
+    fn components<'a>(
+        comps: &'a [TemplateComponent],
+        values: &'a HashMap<String, SlugComponent>,
+        missing: Option<&'a &'static str>,
+    ) -> StdResult<Vec<&'a dyn RawKeySpecifierComponent>, KeyPathTemplateError> {
+        comps
+            .iter()
+            .map(|comp| match comp {
+                TemplateComponent::Literal(lit) => Ok(lit as &dyn RawKeySpecifierComponent),
+                TemplateComponent::Field(name) => match (values.get(name), missing) {
+                    (Some(value), _) => Ok(value as &dyn RawKeySpecifierComponent),
+                    (None, Some(missing)) => Ok(missing as &dyn RawKeySpecifierComponent),
+                    (None, None) => Err(KeyPathTemplateError::MissingField(name.clone())),
+                },
+            })
+            .collect()
+    }
+}
+
+/// A builder for a [`KeySpecifier`] described by a [`KeyPathTemplate`].
+///
+/// Returned by [`KeyPathTemplate::builder`].
+#[derive(Clone, Debug)]
+pub struct KeySpecifierBuilder<'t> {
+    /// The template we're filling in.
+    template: &'t KeyPathTemplate,
+    /// The values of the fields that have been set so far.
+    values: HashMap<String, SlugComponent>,
+    /// The C Tor path of the key, if it has one.
+    ctor_path: Option<CTorPath>,
+}
+
+impl KeySpecifierBuilder<'_> {
+    /// Set the field called `name` to `value`.
+    ///
+    /// Returns an error if the template has no such field.
+    pub fn set(
+        &mut self,
+        name: &str,
+        value: &dyn KeySpecifierComponent,
+    ) -> StdResult<&mut Self, KeyPathTemplateError> {
+        if !self.template.fields().any(|field| field == name) {
+            return Err(KeyPathTemplateError::UnknownField(name.to_owned()));
+        }
+        let _ = self
+            .values
+            .insert(name.to_owned(), SlugComponent(value.to_slug()?));
+        Ok(self)
+    }
+
+    /// Set the path of the key in the C Tor key store.
+    ///
+    /// If this is not set, the key will have no C Tor path.
+    pub fn ctor_path(&mut self, path: CTorPath) -> &mut Self {
+        self.ctor_path = Some(path);
+        self
+    }
+
+    /// Build the key specifier.
+    ///
+    /// Returns [`KeyPathTemplateError::MissingField`] if any of the fields were not set.
+    pub fn build(&self) -> StdResult<TemplatedKeySpecifier, KeyPathTemplateError> {
+        let path = KeyPathTemplate::components(&self.template.path, &self.values, None)?;
+        let leaf = KeyPathTemplate::components(&self.template.leaf, &self.values, None)?;
+        let arti_path = arti_path_from_components(&path, &leaf).map_err(|e| match e {
+            ArtiPathUnavailableError::Bug(bug) => bug,
+            e => tor_error::internal!("{e}"),
+        })?;
+
+        Ok(TemplatedKeySpecifier {
+            arti_path,
+            ctor_path: self.ctor_path.clone(),
+        })
+    }
+
+    /// Return a [`KeyPathPattern`] matching the keys described by the template,
+    /// whose fields have the values set so far.
+    ///
+    /// The fields that have not been set match any value.
+    pub fn pattern(&self) -> StdResult<KeyPathPattern, KeyPathTemplateError> {
+        let any = &"*";
+        let path = KeyPathTemplate::components(&self.template.path, &self.values, Some(any))?;
+        let leaf = KeyPathTemplate::components(&self.template.leaf, &self.values, Some(any))?;
+        Ok(arti_pattern_from_components(&path, &leaf)?)
+    }
+}
+
+/// A [`KeySpecifier`] built from a [`KeyPathTemplate`].
+///
+/// Returned by [`KeySpecifierBuilder::build`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TemplatedKeySpecifier {
+    /// The path of the key in the Arti key store.
+    arti_path: ArtiPath,
+    /// The path of the key in the C Tor key store, if it has one.
+    ctor_path: Option<CTorPath>,
+}
+
+impl KeySpecifier for TemplatedKeySpecifier {
+    fn arti_path(&self) -> StdResult<ArtiPath, ArtiPathUnavailableError> {
+        Ok(self.arti_path.clone())
+    }
+
+    fn ctor_path(&self) -> Option<CTorPath> {
+        self.ctor_path.clone()
+    }
+
+    fn keypair_specifier(&self) -> Option<Box<dyn KeySpecifier>> {
+        None
+    }
+}
+
+/// The values of the fields of a [`KeyPath`] that matched a [`KeyPathTemplate`].
+///
+/// Returned by [`KeyPathTemplate::parse`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TemplateMatch {
+    /// The value of each field.
+    values: HashMap<String, SlugComponent>,
+}
+
+impl TemplateMatch {
+    /// Return the value of the field called `name`, as a `T`.
+    ///
+    /// Returns `None` if the template has no such field.
+    pub fn get<T: KeySpecifierComponent>(
+        &self,
+        name: &str,
+    ) -> Option<StdResult<T, InvalidKeyPathComponentValue>> {
+        self.values.get(name).map(|value| T::from_slug(&value.0))
+    }
+}
+
+/// A [`Slug`] used as a component of a [`KeyPathTemplate`].
+///
+/// (We don't know the real types of the fields of a template,
+/// so we keep their values as slugs until someone asks for them.)
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct SlugComponent(Slug);
+
+impl KeySpecifierComponent for SlugComponent {
+    fn to_slug(&self) -> StdResult<Slug, Bug> {
+        Ok(self.0.clone())
+    }
+
+    fn from_slug(s: &Slug) -> StdResult<Self, InvalidKeyPathComponentValue>
+    where
+        Self: Sized,
+    {
+        Ok(SlugComponent(s.clone()))
+    }
+
+    fn fmt_pretty(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.0, f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+
+    use super::*;
+    use tor_persist::hsnickname::HsNickname;
+
+    #[test]
+    fn template() {
+        let template: KeyPathTemplate = "hss/{nickname}/ks_hs_blind_id+{count}".parse().unwrap();
+        assert_eq!(template.fields().collect::<Vec<_>>(), ["nickname", "count"]);
+
+        let nickname = HsNickname::new("shallot".into()).unwrap();
+        let mut builder = template.builder();
+        builder.set("nickname", &nickname).unwrap();
+        assert!(matches!(
+            builder.build(),
+            Err(KeyPathTemplateError::MissingField(f)) if f == "count"
+        ));
+        assert_eq!(
+            builder.pattern().unwrap(),
+            KeyPathPattern::Arti("hss/shallot/ks_hs_blind_id+*".into())
+        );
+        assert!(matches!(
+            builder.set("wombat", &7_usize),
+            Err(KeyPathTemplateError::UnknownField(f)) if f == "wombat"
+        ));
+
+        builder.set("count", &7_usize).unwrap();
+        let spec = builder.build().unwrap();
+        let path = KeyPath::Arti(spec.arti_path().unwrap());
+        assert_eq!(path.to_string(), "hss/shallot/ks_hs_blind_id+7");
+        assert!(spec.ctor_path().is_none());
+        assert!(path.matches(&template.builder().pattern().unwrap()));
+        assert!(path.matches(&builder.pattern().unwrap()));
+
+        let parsed = template.parse(&path).unwrap();
+        assert_eq!(
+            parsed.get::<HsNickname>("nickname").unwrap().unwrap(),
+            nickname
+        );
+        assert_eq!(parsed.get::<usize>("count").unwrap().unwrap(), 7);
+        assert!(parsed.get::<usize>("nickname").unwrap().is_err());
+        assert!(parsed.get::<usize>("wombat").is_none());
+
+        for other in [
+            "hss/shallot/ks_hs_id",
+            "hss/shallot/ks_hs_blind_id",
+            "hss/ks_hs_blind_id+7",
+        ] {
+            let other = KeyPath::Arti(ArtiPath::new(other.into()).unwrap());
+            assert!(matches!(
+                template.parse(&other),
+                Err(KeyPathError::PatternNotMatched(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn bad_templates() {
+        for (bad, expected) in [
+            ("hss/{nickname}/{nickname}", "DuplicateField"),
+            ("hss/{nick name}/role", "InvalidFieldName"),
+            ("hss/{}/role", "InvalidFieldName"),
+            ("hss//role", "InvalidLiteral"),
+            ("hss/{nickname/role", "InvalidLiteral"),
+            ("hss/role+", "InvalidLiteral"),
+        ] {
+            let err = bad.parse::<KeyPathTemplate>().unwrap_err();
+            assert!(format!("{err:?}").starts_with(expected), "{bad}: {err:?}");
+        }
+    }
+}
@@ -1,6 +1,7 @@
 //! The [`Keystore`] trait and its implementations.
 
 pub(crate) mod arti;
+pub(crate) mod context;
 #[cfg(feature = "ctor-keystore")]
 pub(crate) mod ctor;
 pub(crate) mod fs_utils;
@@ -8,11 +9,25 @@ pub(crate) mod fs_utils;
 #[cfg(feature = "ephemeral-keystore")]
 pub(crate) mod ephemeral;
 
-use tor_key_forge::{EncodableKey, ErasedKey, KeyType};
+#[cfg(feature = "remote-keystore")]
+pub(crate) mod remote;
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use tor_error::{bad_api_usage, internal};
+use tor_key_forge::{Ed25519Signer, EncodableKey, ErasedKey, KeyType, SecretBuffer, SshKeyData};
 
 use crate::{KeyPath, KeySpecifier, KeystoreId, Result};
 
+pub use context::{CancellationToken, OpContext};
+
 /// A generic key store.
+///
+/// The operations that may access the underlying storage take an [`OpContext`].
+/// Implementations should return [`Error::Timeout`](crate::Error::Timeout)
+/// or [`Error::Cancelled`](crate::Error::Cancelled) instead of blocking past its deadline
+/// or after it has been cancelled.
 pub trait Keystore: Send + Sync + 'static {
     /// An identifier for this key store instance.
     ///
@@ -21,13 +36,23 @@ pub trait Keystore: Send + Sync + 'static {
     fn id(&self) -> &KeystoreId;
 
     /// Check if the key identified by `key_spec` exists in this key store.
-    fn contains(&self, key_spec: &dyn KeySpecifier, key_type: &KeyType) -> Result<bool>;
+    fn contains(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<bool>;
 
     /// Retrieve the key identified by `key_spec`.
     ///
     /// Returns `Ok(Some(key))` if the key was successfully retrieved. Returns `Ok(None)` if the
     /// key does not exist in this key store.
-    fn get(&self, key_spec: &dyn KeySpecifier, key_type: &KeyType) -> Result<Option<ErasedKey>>;
+    fn get(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<ErasedKey>>;
 
     /// Write `key` to the key store.
     //
@@ -51,6 +76,7 @@ pub trait Keystore: Send + Sync + 'static {
         key: &dyn EncodableKey,
         key_spec: &dyn KeySpecifier,
         key_type: &KeyType,
+        ctx: &OpContext,
     ) -> Result<()>;
 
     /// Remove the specified key.
@@ -59,8 +85,335 @@ pub trait Keystore: Send + Sync + 'static {
     /// `Ok(Some(())` means the key was successfully removed.
     ///
     /// Returns `Err` if an error occurred while trying to remove the key.
-    fn remove(&self, key_spec: &dyn KeySpecifier, key_type: &KeyType) -> Result<Option<()>>;
+    fn remove(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<()>>;
 
     /// List all the keys in this keystore.
-    fn list(&self) -> Result<Vec<(KeyPath, KeyType)>>;
+    fn list(&self, ctx: &OpContext) -> Result<Vec<(KeyPath, KeyType)>>;
+
+    /// Retrieve the raw contents of the entry identified by `key_path` and `key_type`,
+    /// without trying to parse it.
+    ///
+    /// Unlike [`get`](Keystore::get), this works for entries
+    /// whose [`KeyType`] or [`KeyPath`] this version of Arti does not recognize.
+    ///
+    /// Returns `Ok(None)` if the entry does not exist in this key store.
+    fn get_raw(
+        &self,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<RawKeyData>>;
+
+    /// Write the raw contents of an entry to the key store, under `key_path` and `key_type`.
+    ///
+    /// Where possible, the contents are written as-is: the key store does not check
+    /// that `data` is a valid key of type `key_type`.
+    /// Key stores that don't store their entries in raw form
+    /// may reject any `data` they cannot parse.
+    fn insert_raw(
+        &self,
+        data: &RawKeyData,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<()>;
+
+    /// Return the time at which the entry identified by `key_path` and `key_type` was created.
+    ///
+    /// Returns `Ok(None)` if the entry does not exist in this key store,
+    /// or if this key store doesn't know when it was created.
+    /// Key stores that replace an entry when it is rewritten
+    /// may report the time it was last written instead.
+    ///
+    /// The default implementation always returns `Ok(None)`.
+    fn created(
+        &self,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<SystemTime>> {
+        let _ = (key_path, key_type, ctx);
+        Ok(None)
+    }
+
+    /// Return the time at which the entry identified by `key_path` and `key_type` expires.
+    ///
+    /// Returns `Ok(None)` if the entry does not exist in this key store,
+    /// or if it has no expiration time.
+    ///
+    /// The default implementation always returns `Ok(None)`.
+    fn expires(
+        &self,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<SystemTime>> {
+        let _ = (key_path, key_type, ctx);
+        Ok(None)
+    }
+
+    /// Return what this key store knows about the entry identified by `key_path` and `key_type`,
+    /// without reading the entry itself.
+    ///
+    /// Returns `Ok(None)` if the entry does not exist in this key store.
+    ///
+    /// The default implementation reports the time returned by [`Keystore::created`],
+    /// and nothing else.
+    fn metadata(
+        &self,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<EntryMetadata>> {
+        if !self.contains(key_path, key_type, ctx)? {
+            return Ok(None);
+        }
+
+        Ok(Some(EntryMetadata {
+            created: self.created(key_path, key_type, ctx)?,
+            ..Default::default()
+        }))
+    }
+
+    /// Set the time at which the entry identified by `key_path` and `key_type` expires,
+    /// or, if `expires` is `None`, remove its expiration time.
+    ///
+    /// Key stores are not expected to remove expired entries themselves:
+    /// that is done by [`KeyMgr::sweep_expired`](crate::KeyMgr::sweep_expired).
+    /// The expiration time of an entry is kept if the entry is overwritten,
+    /// and discarded when it is removed.
+    ///
+    /// A return value of `Ok(None)` indicates the entry doesn't exist in this key store,
+    /// whereas `Ok(Some(())` means its expiration time was set.
+    ///
+    /// The default implementation returns [`Error::ExpiryNotSupported`](crate::Error::ExpiryNotSupported).
+    fn set_expiry(
+        &self,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        expires: Option<SystemTime>,
+        ctx: &OpContext,
+    ) -> Result<Option<()>> {
+        let _ = (key_path, key_type, expires, ctx);
+        Err(crate::Error::ExpiryNotSupported(self.id().clone()))
+    }
+
+    /// Return the directory that holds the entries of this key store,
+    /// if it keeps them on disk.
+    ///
+    /// [`KeyMgr::watch`](crate::KeyMgr::watch) watches this directory (and its subdirectories)
+    /// to learn about the changes made to the key store by other processes.
+    ///
+    /// The default implementation returns `None`.
+    fn dir(&self) -> Option<&Path> {
+        None
+    }
+
+    /// Return an [`Ed25519Signer`] for the ed25519 keypair identified by `key_spec`.
+    ///
+    /// Returns `Ok(None)` if the key does not exist in this key store,
+    /// and an error if `key_type` is not an ed25519 keypair type.
+    ///
+    /// The default implementation retrieves the key with [`get`](Keystore::get).
+    /// Key stores that cannot (or will not) hand out their secret keys,
+    /// such as those backed by hardware tokens, should override this
+    /// to sign in place.
+    fn ed25519_signer(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<Box<dyn Ed25519Signer>>> {
+        if !matches!(
+            key_type,
+            KeyType::Ed25519Keypair | KeyType::Ed25519ExpandedKeypair
+        ) {
+            return Err(bad_api_usage!("{:?} is not an ed25519 keypair type", key_type).into());
+        }
+        let Some(key) = self.get(key_spec, key_type, ctx)? else {
+            return Ok(None);
+        };
+        let signer = tor_key_forge::into_ed25519_signer(key)
+            .ok_or_else(|| internal!("{:?} key is not an ed25519 keypair", key_type))?;
+        Ok(Some(signer))
+    }
+
+    /// Return true if this key store is locked.
+    ///
+    /// The keys of a locked key store can't be read or written
+    /// until it is unlocked with [`unlock`](Keystore::unlock).
+    ///
+    /// Key stores that don't encrypt their keys are never locked.
+    fn is_locked(&self) -> bool {
+        false
+    }
+
+    /// Unlock this key store using `passphrase`.
+    ///
+    /// Returns an error if `passphrase` is incorrect.
+    ///
+    /// Key stores that don't encrypt their keys ignore this.
+    fn unlock(&self, passphrase: &str) -> Result<()> {
+        let _ = passphrase;
+        Ok(())
+    }
+
+    /// Lock this key store, forgetting any secrets obtained by [`unlock`](Keystore::unlock).
+    ///
+    /// Key stores that don't encrypt their keys ignore this.
+    fn lock(&self) {}
+
+    /// Acquire an advisory lock on the entry identified by `key_spec` and `key_type`,
+    /// blocking until it is available (or until `ctx` gives up).
+    ///
+    /// The lock is held until the returned [`EntryLock`] is dropped.
+    /// It only excludes other holders of the same lock
+    /// (possibly in other processes using the same key store):
+    /// it doesn't prevent anyone from reading or writing the entry.
+    ///
+    /// Returns `Ok(None)` if this key store doesn't support locking,
+    /// or if `key_spec` can't identify an entry in this key store.
+    ///
+    /// The default implementation always returns `Ok(None)`.
+    ///
+    /// Not to be confused with [`lock`](Keystore::lock),
+    /// which is about the encryption of the key store.
+    fn lock_entry(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<EntryLock>> {
+        let _ = (key_spec, key_type, ctx);
+        Ok(None)
+    }
+}
+
+/// An advisory lock on a keystore entry.
+///
+/// The lock is released when this is dropped.
+///
+/// Returned by [`Keystore::lock_entry`].
+pub struct EntryLock(
+    /// The underlying lock (for example, a lock file guard).
+    #[allow(dead_code)] // only held for its Drop impl
+    Box<dyn Send + Sync>,
+);
+
+impl EntryLock {
+    /// Create an [`EntryLock`] that holds `guard` until it is dropped.
+    pub fn new(guard: impl Send + Sync + 'static) -> Self {
+        Self(Box::new(guard))
+    }
+}
+
+impl std::fmt::Debug for EntryLock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EntryLock").finish_non_exhaustive()
+    }
+}
+
+/// What a key store knows about one of its entries.
+///
+/// Returned by [`Keystore::metadata`].
+///
+/// Every field is optional, since not every key store keeps track of this information.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct EntryMetadata {
+    /// The time at which the entry was created.
+    ///
+    /// See [`Keystore::created`].
+    pub created: Option<SystemTime>,
+    /// The time at which the entry was last written.
+    pub modified: Option<SystemTime>,
+    /// The file that holds the entry, if the key store keeps its entries on disk.
+    pub path: Option<PathBuf>,
+}
+
+/// The raw, unparsed contents of a keystore entry.
+///
+/// Returned by [`Keystore::get_raw`].
+///
+/// The contents are zeroed on drop.
+#[derive(Clone, Debug)]
+pub struct RawKeyData(SecretBuffer);
+
+impl RawKeyData {
+    /// Create a new `RawKeyData` from the contents of a keystore entry.
+    pub fn new(data: Vec<u8>) -> Self {
+        Self(SecretBuffer::new(data))
+    }
+
+    /// Return the contents of the entry.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+
+    /// Return the contents of the entry, as a [`SecretBuffer`].
+    pub fn into_secret(self) -> SecretBuffer {
+        self.0
+    }
+
+    /// Try to interpret the contents of the entry as an OpenSSH-encoded key.
+    ///
+    /// Returns an error if the entry is not an OpenSSH private or public key,
+    /// or if the algorithm of the key is not supported.
+    pub fn to_ssh_key_data(&self) -> Result<SshKeyData> {
+        use ssh_key::{PrivateKey, PublicKey};
+
+        let s = self.0.as_str().map_err(|_| crate::Error::NotAnSshKey)?;
+
+        if let Ok(key) = PrivateKey::from_openssh(s) {
+            return Ok(SshKeyData::try_from_keypair_data(key.key_data().clone())?);
+        }
+
+        let key = PublicKey::from_openssh(s).map_err(|_| crate::Error::NotAnSshKey)?;
+        Ok(SshKeyData::try_from_key_data(key.key_data().clone())?)
+    }
+
+    /// Try to interpret the contents of the entry as a stored Tor ed25519 certificate.
+    ///
+    /// Returns an error if the entry is not a certificate.
+    /// The certificate is not validated.
+    #[cfg(feature = "cert")]
+    pub fn to_ed25519_cert(&self) -> Result<tor_key_forge::cert::StoredEd25519Cert> {
+        self.to_cert()
+    }
+
+    /// Try to interpret the contents of the entry as a certificate of type `C`.
+    ///
+    /// Returns an error if the entry is not a certificate of that type.
+    /// The certificate is not validated.
+    #[cfg(feature = "cert")]
+    pub fn to_cert<C: tor_key_forge::cert::EncodableCert>(&self) -> Result<C> {
+        Ok(C::from_entry_bytes(self.as_bytes())?)
+    }
+
+    /// Check that the contents of the entry can be parsed as an entry of type `key_type`.
+    ///
+    /// Used by the key stores that only store raw data they'll be able to parse later.
+    #[allow(dead_code)] // not used if none of the key stores that need it are enabled
+    pub(crate) fn check_parseable(&self, key_type: &KeyType) -> Result<()> {
+        #[cfg(feature = "cert")]
+        if key_type.is_cert() {
+            let _ = self.to_ed25519_cert()?;
+            return Ok(());
+        }
+
+        let _ = key_type;
+        let _: SshKeyData = self.to_ssh_key_data()?;
+        Ok(())
+    }
+}
+
+impl From<SecretBuffer> for RawKeyData {
+    fn from(data: SecretBuffer) -> Self {
+        Self(data)
+    }
 }
@@ -2,27 +2,39 @@
 //!
 //! See the [`ArtiNativeKeystore`] docs for more details.
 
+#[cfg(feature = "encrypted-keystore")]
+pub(crate) mod encrypted;
 pub(crate) mod err;
+mod migrate;
 pub(crate) mod ssh;
+#[cfg(feature = "tpm-keystore")]
+pub(crate) mod tpm;
 
 use std::io::{self, ErrorKind};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::result::Result as StdResult;
 use std::str::FromStr;
+use std::time::SystemTime;
 
 use crate::keystore::fs_utils::{checked_op, FilesystemAction, FilesystemError, RelKeyPath};
-use crate::keystore::{EncodableKey, ErasedKey, KeySpecifier, Keystore};
+use crate::keystore::{
+    EncodableKey, EntryLock, EntryMetadata, ErasedKey, KeySpecifier, Keystore, OpContext,
+    RawKeyData,
+};
 use crate::{arti_path, ArtiPath, ArtiPathUnavailableError, KeyPath, KeystoreId, Result};
 use err::ArtiNativeKeystoreError;
 use ssh::UnparsedOpenSshKey;
 
 use fs_mistrust::{CheckedDir, Mistrust};
+use fslock_guard::LockFileGuard;
 use itertools::Itertools;
 use tor_key_forge::KeyType;
 use walkdir::WalkDir;
 
 use tor_basic_utils::PathExt as _;
 
+pub use migrate::{KeystoreMigrationStep, KeystoreUpgradePlan};
+
 /// The Arti key store.
 ///
 /// This is a disk-based key store that encodes keys in OpenSSH format.
@@ -39,6 +51,15 @@ use tor_basic_utils::PathExt as _;
 ///
 /// See [SSH protocol extensions] for more details.
 ///
+/// The root of the key store may contain a marker recording the version of its on-disk layout
+/// (key stores without one use the original layout).
+/// When the key store is opened, its layout is upgraded to the latest version we support,
+/// if it isn't already
+/// (the files an upgrade modifies are backed up until the upgrade is complete).
+/// A key store that is already up to date is never written to when it is opened:
+/// use [`plan_upgrade`](Self::plan_upgrade) to find out whether an upgrade would change anything.
+/// Key stores whose layout is newer than the latest version we support are refused.
+///
 /// [algorithm name]: https://www.iana.org/assignments/ssh-parameters/ssh-parameters.xhtml#ssh-parameters-19
 /// [RFC4251 § 6]: https://www.rfc-editor.org/rfc/rfc4251.html#section-6
 /// [SSH protocol extensions]: https://spec.torproject.org/ssh-protocols.html
@@ -64,22 +85,70 @@ impl ArtiNativeKeystore {
         keystore_dir: impl AsRef<Path>,
         mistrust: &Mistrust,
     ) -> Result<Self> {
+        Self::from_path_and_mistrust_with_ctx(keystore_dir, mistrust, &OpContext::default())
+    }
+
+    /// Like [`from_path_and_mistrust`](Self::from_path_and_mistrust),
+    /// but giving up if `ctx` is cancelled or its deadline passes.
+    pub fn from_path_and_mistrust_with_ctx(
+        keystore_dir: impl AsRef<Path>,
+        mistrust: &Mistrust,
+        ctx: &OpContext,
+    ) -> Result<Self> {
+        let keystore_dir = keystore_dir.as_ref().to_path_buf();
+        let mistrust = mistrust.clone();
+        ctx.run_blocking(move || Self::open(&keystore_dir, &mistrust))?
+    }
+
+    /// Open the key store rooted at `keystore_dir`, creating it if needed.
+    ///
+    /// See [`from_path_and_mistrust`](Self::from_path_and_mistrust).
+    fn open(keystore_dir: &Path, mistrust: &Mistrust) -> Result<Self> {
         let keystore_dir = mistrust
             .verifier()
             .check_content()
-            .make_secure_dir(&keystore_dir)
+            .make_secure_dir(keystore_dir)
             .map_err(|e| FilesystemError::FsMistrust {
                 action: FilesystemAction::Init,
-                path: keystore_dir.as_ref().into(),
+                path: keystore_dir.into(),
                 err: e.into(),
             })
             .map_err(ArtiNativeKeystoreError::Filesystem)?;
 
+        let _version: u32 = migrate::upgrade(&keystore_dir)?;
+
         // TODO: load the keystore ID from config.
         let id = KeystoreId::from_str("arti")?;
         Ok(Self { keystore_dir, id })
     }
 
+    /// Return the changes that opening the key store rooted at `keystore_dir`
+    /// would make to its on-disk layout, without making them.
+    ///
+    /// This can be used to find out how a key store would be upgraded
+    /// before opening it with [`from_path_and_mistrust`](Self::from_path_and_mistrust).
+    ///
+    /// Returns an error if `keystore_dir` doesn't exist,
+    /// if it does not conform to the requirements of the specified `Mistrust`,
+    /// or if its layout is newer than the latest version we support.
+    pub fn plan_upgrade(
+        keystore_dir: impl AsRef<Path>,
+        mistrust: &Mistrust,
+    ) -> Result<KeystoreUpgradePlan> {
+        let keystore_dir = mistrust
+            .verifier()
+            .check_content()
+            .secure_dir(&keystore_dir)
+            .map_err(|e| FilesystemError::FsMistrust {
+                action: FilesystemAction::Read,
+                path: keystore_dir.as_ref().into(),
+                err: e.into(),
+            })
+            .map_err(ArtiNativeKeystoreError::Filesystem)?;
+
+        Ok(migrate::plan_upgrade(&keystore_dir)?)
+    }
+
     /// The path on disk of the key with the specified identity and type, relative to
     /// `keystore_dir`.
     fn rel_path(
@@ -89,6 +158,174 @@ impl ArtiNativeKeystore {
     ) -> StdResult<RelKeyPath, ArtiPathUnavailableError> {
         RelKeyPath::arti(&self.keystore_dir, key_spec, key_type)
     }
+
+    /// Run the filesystem operation `op` on `path`,
+    /// giving up if `ctx` is cancelled or its deadline passes.
+    ///
+    /// `op` may run on another thread, so that a hung filesystem
+    /// (such as an unreachable network mount) can't block the caller indefinitely.
+    fn fs_op<T: Send + 'static>(
+        &self,
+        ctx: &OpContext,
+        path: &RelKeyPath,
+        op: impl FnOnce(&RelKeyPath<'_>) -> T + Send + 'static,
+    ) -> Result<T> {
+        let dir = self.keystore_dir.clone();
+        let path = path.rel_path_unchecked().to_path_buf();
+        ctx.run_blocking(move || op(&RelKeyPath::from_parts(&dir, path)))
+    }
+
+    /// Write `contents` to the file at `path`,
+    /// creating its parent directories as needed.
+    fn write_file(path: &RelKeyPath, contents: impl AsRef<[u8]>) -> Result<()> {
+        let unchecked_path = path.rel_path_unchecked();
+
+        // Create the parent directories as needed
+        if let Some(parent) = unchecked_path.parent() {
+            path.checked_dir()
+                .make_directory(parent)
+                .map_err(|err| FilesystemError::FsMistrust {
+                    action: FilesystemAction::Write,
+                    path: parent.to_path_buf(),
+                    err: err.into(),
+                })
+                .map_err(ArtiNativeKeystoreError::Filesystem)?;
+        }
+
+        Ok(checked_op!(write_and_replace, path, contents)
+            .map_err(|err| FilesystemError::FsMistrust {
+                action: FilesystemAction::Write,
+                path: unchecked_path.into(),
+                err: err.into(),
+            })
+            .map_err(ArtiNativeKeystoreError::Filesystem)?)
+    }
+
+    /// Remove the file at `path`.
+    ///
+    /// Returns `Ok(None)` if the file doesn't exist.
+    fn remove_file(path: &RelKeyPath) -> Result<Option<()>> {
+        match checked_op!(remove_file, path) {
+            Ok(()) => Ok(Some(())),
+            Err(fs_mistrust::Error::NotFound(_)) => Ok(None),
+            Err(e) => Err(ArtiNativeKeystoreError::Filesystem(
+                FilesystemError::FsMistrust {
+                    action: FilesystemAction::Remove,
+                    path: path.rel_path_unchecked().into(),
+                    err: e.into(),
+                },
+            ))?,
+        }
+    }
+
+    /// List the entries of the key store rooted at `keystore_dir`.
+    ///
+    /// See [`Keystore::list`].
+    fn list_entries(keystore_dir: &CheckedDir) -> Result<Vec<(KeyPath, KeyType)>> {
+        WalkDir::new(keystore_dir.as_path())
+            .into_iter()
+            // Skip over the version marker and migration backups, which aren't keys
+            .filter_entry(|e| !(e.depth() == 1 && migrate::is_reserved_name(e.file_name())))
+            .map(|entry| {
+                let entry = entry
+                    .map_err(|e| {
+                        let msg = e.to_string();
+                        FilesystemError::Io {
+                            action: FilesystemAction::Read,
+                            path: keystore_dir.as_path().into(),
+                            err: e
+                                .into_io_error()
+                                .unwrap_or_else(|| {
+                                    io::Error::new(ErrorKind::Other, msg.to_string())
+                                })
+                                .into(),
+                        }
+                    })
+                    .map_err(ArtiNativeKeystoreError::Filesystem)?;
+
+                let path = entry.path();
+
+                // Skip over directories as they won't be valid arti-paths
+                //
+                // TODO (#1118): provide a mechanism for warning about unrecognized keys?
+                if entry.file_type().is_dir() {
+                    return Ok(None);
+                }
+
+                let path = path.strip_prefix(keystore_dir.as_path()).map_err(|_| {
+                    /* This error should be impossible. */
+                    tor_error::internal!(
+                        "found key {} outside of keystore_dir {}?!",
+                        path.display_lossy(),
+                        keystore_dir.as_path().display_lossy()
+                    )
+                })?;
+
+                if let Some(parent) = path.parent() {
+                    // Check the properties of the parent directory by attempting to list its
+                    // contents.
+                    keystore_dir
+                        .read_directory(parent)
+                        .map_err(|e| FilesystemError::FsMistrust {
+                            action: FilesystemAction::Read,
+                            path: parent.into(),
+                            err: e.into(),
+                        })
+                        .map_err(ArtiNativeKeystoreError::Filesystem)?;
+                }
+
+                let malformed_err = |path: &Path, err| ArtiNativeKeystoreError::MalformedPath {
+                    path: path.into(),
+                    err,
+                };
+
+                let extension = path
+                    .extension()
+                    .ok_or_else(|| malformed_err(path, err::MalformedPathError::NoExtension))?
+                    .to_str()
+                    .ok_or_else(|| malformed_err(path, err::MalformedPathError::Utf8))?;
+
+                let key_type = KeyType::from(extension);
+                // Strip away the file extension
+                let path = path.with_extension("");
+                // Construct slugs in platform-independent way
+                let slugs = path
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join(&arti_path::PATH_SEP.to_string());
+                ArtiPath::new(slugs)
+                    .map(|path| Some((path.into(), key_type)))
+                    .map_err(|e| {
+                        malformed_err(&path, err::MalformedPathError::InvalidArtiPath(e)).into()
+                    })
+            })
+            .flatten_ok()
+            .collect()
+    }
+
+    /// The path of the file recording the expiration time of the entry at `path`.
+    ///
+    /// Like the lock files, these live in a separate directory,
+    /// so that they don't show up as (unrecognized) entries.
+    fn expiry_path<'a>(&'a self, path: &RelKeyPath) -> RelKeyPath<'a> {
+        let mut expiry_path: PathBuf =
+            Path::new(migrate::EXPIRY_DIR).join(path.rel_path_unchecked());
+        expiry_path.as_mut_os_string().push(".expiry");
+        RelKeyPath::from_parts(&self.keystore_dir, expiry_path)
+    }
+}
+
+/// Return the [`KeyPath`] of `key_spec`, or `None` if it does not have an `ArtiPath`.
+///
+/// Used by the key stores that wrap an [`ArtiNativeKeystore`].
+#[cfg(any(feature = "encrypted-keystore", feature = "tpm-keystore"))]
+fn arti_key_path(key_spec: &dyn KeySpecifier) -> Result<Option<KeyPath>> {
+    match key_spec.arti_path() {
+        Ok(path) => Ok(Some(path.into())),
+        Err(ArtiPathUnavailableError::ArtiPathUnavailable) => Ok(None),
+        Err(e) => Err(tor_error::internal!("invalid ArtiPath: {e}").into()),
+    }
 }
 
 /// Extract the key path (relative to the keystore root) from the specified result `res`,
@@ -113,10 +350,15 @@ impl Keystore for ArtiNativeKeystore {
         &self.id
     }
 
-    fn contains(&self, key_spec: &dyn KeySpecifier, key_type: &KeyType) -> Result<bool> {
+    fn contains(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<bool> {
         let path = rel_path_if_supported!(self.rel_path(key_spec, key_type), Ok(false));
 
-        let meta = match checked_op!(metadata, path) {
+        let meta = match self.fs_op(ctx, &path, |path| checked_op!(metadata, path))? {
             Ok(meta) => meta,
             Err(fs_mistrust::Error::NotFound(_)) => return Ok(false),
             Err(e) => {
@@ -142,10 +384,15 @@ impl Keystore for ArtiNativeKeystore {
         }
     }
 
-    fn get(&self, key_spec: &dyn KeySpecifier, key_type: &KeyType) -> Result<Option<ErasedKey>> {
+    fn get(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<ErasedKey>> {
         let path = rel_path_if_supported!(self.rel_path(key_spec, key_type), Ok(None));
 
-        let inner = match checked_op!(read_to_string, path) {
+        let inner = match self.fs_op(ctx, &path, |path| checked_op!(read, path))? {
             Err(fs_mistrust::Error::NotFound(_)) => return Ok(None),
             res => res
                 .map_err(|err| FilesystemError::FsMistrust {
@@ -159,7 +406,7 @@ impl Keystore for ArtiNativeKeystore {
         let abs_path = path
             .checked_path()
             .map_err(ArtiNativeKeystoreError::Filesystem)?;
-        UnparsedOpenSshKey::new(inner, abs_path)
+        UnparsedOpenSshKey::new(inner.into(), abs_path)
             .parse_ssh_format_erased(key_type)
             .map(Some)
     }
@@ -169,23 +416,11 @@ impl Keystore for ArtiNativeKeystore {
         key: &dyn EncodableKey,
         key_spec: &dyn KeySpecifier,
         key_type: &KeyType,
+        ctx: &OpContext,
     ) -> Result<()> {
         let path = self
             .rel_path(key_spec, key_type)
             .map_err(|e| tor_error::internal!("{e}"))?;
-        let unchecked_path = path.rel_path_unchecked();
-
-        // Create the parent directories as needed
-        if let Some(parent) = unchecked_path.parent() {
-            self.keystore_dir
-                .make_directory(parent)
-                .map_err(|err| FilesystemError::FsMistrust {
-                    action: FilesystemAction::Write,
-                    path: parent.to_path_buf(),
-                    err: err.into(),
-                })
-                .map_err(ArtiNativeKeystoreError::Filesystem)?;
-        }
 
         let key = key.as_ssh_key_data()?;
         // TODO (#1095): decide what information, if any, to put in the comment
@@ -193,114 +428,231 @@ impl Keystore for ArtiNativeKeystore {
 
         let openssh_key = key.to_openssh_string(comment)?;
 
-        Ok(checked_op!(write_and_replace, path, openssh_key)
-            .map_err(|err| FilesystemError::FsMistrust {
-                action: FilesystemAction::Write,
-                path: unchecked_path.into(),
-                err: err.into(),
-            })
-            .map_err(ArtiNativeKeystoreError::Filesystem)?)
+        self.fs_op(ctx, &path, move |path| {
+            Self::write_file(path, &*openssh_key)
+        })?
     }
 
-    fn remove(&self, key_spec: &dyn KeySpecifier, key_type: &KeyType) -> Result<Option<()>> {
+    fn remove(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<()>> {
         let rel_path = self
             .rel_path(key_spec, key_type)
             .map_err(|e| tor_error::internal!("{e}"))?;
+        let expiry_path = self
+            .expiry_path(&rel_path)
+            .rel_path_unchecked()
+            .to_path_buf();
+
+        self.fs_op(ctx, &rel_path, move |path| {
+            let removed = Self::remove_file(path)?;
+            if removed.is_some() {
+                // The expiration time belongs to the entry we just removed:
+                // it mustn't apply to a new entry with the same path.
+                Self::remove_file(&RelKeyPath::from_parts(path.checked_dir(), expiry_path))?;
+            }
+            Ok(removed)
+        })?
+    }
 
-        match checked_op!(remove_file, rel_path) {
-            Ok(()) => Ok(Some(())),
+    fn list(&self, ctx: &OpContext) -> Result<Vec<(KeyPath, KeyType)>> {
+        let keystore_dir = self.keystore_dir.clone();
+        ctx.run_blocking(move || Self::list_entries(&keystore_dir))?
+    }
+
+    fn get_raw(
+        &self,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<RawKeyData>> {
+        let path = rel_path_if_supported!(self.rel_path(key_path, key_type), Ok(None));
+
+        match self.fs_op(ctx, &path, |path| checked_op!(read, path))? {
+            Ok(data) => Ok(Some(RawKeyData::new(data))),
             Err(fs_mistrust::Error::NotFound(_)) => Ok(None),
-            Err(e) => Err(ArtiNativeKeystoreError::Filesystem(
+            Err(err) => Err(ArtiNativeKeystoreError::Filesystem(
                 FilesystemError::FsMistrust {
-                    action: FilesystemAction::Remove,
-                    path: rel_path.rel_path_unchecked().into(),
-                    err: e.into(),
+                    action: FilesystemAction::Read,
+                    path: path.rel_path_unchecked().into(),
+                    err: err.into(),
                 },
             ))?,
         }
     }
 
-    fn list(&self) -> Result<Vec<(KeyPath, KeyType)>> {
-        WalkDir::new(self.keystore_dir.as_path())
-            .into_iter()
-            .map(|entry| {
-                let entry = entry
-                    .map_err(|e| {
-                        let msg = e.to_string();
-                        FilesystemError::Io {
-                            action: FilesystemAction::Read,
-                            path: self.keystore_dir.as_path().into(),
-                            err: e
-                                .into_io_error()
-                                .unwrap_or_else(|| {
-                                    io::Error::new(ErrorKind::Other, msg.to_string())
-                                })
-                                .into(),
-                        }
-                    })
-                    .map_err(ArtiNativeKeystoreError::Filesystem)?;
+    fn insert_raw(
+        &self,
+        data: &RawKeyData,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<()> {
+        let path = self
+            .rel_path(key_path, key_type)
+            .map_err(|e| tor_error::bad_api_usage!("{e}"))?;
+        let data = data.clone();
 
-                let path = entry.path();
+        self.fs_op(ctx, &path, move |path| {
+            Self::write_file(path, data.as_bytes())
+        })?
+    }
 
-                // Skip over directories as they won't be valid arti-paths
-                //
-                // TODO (#1118): provide a mechanism for warning about unrecognized keys?
-                if entry.file_type().is_dir() {
-                    return Ok(None);
-                }
+    fn created(
+        &self,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<SystemTime>> {
+        Ok(self
+            .metadata(key_path, key_type, ctx)?
+            .and_then(|metadata| metadata.created))
+    }
 
-                let path = path
-                    .strip_prefix(self.keystore_dir.as_path())
-                    .map_err(|_| {
-                        /* This error should be impossible. */
-                        tor_error::internal!(
-                            "found key {} outside of keystore_dir {}?!",
-                            path.display_lossy(),
-                            self.keystore_dir.as_path().display_lossy()
-                        )
-                    })?;
+    fn metadata(
+        &self,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<EntryMetadata>> {
+        let path = rel_path_if_supported!(self.rel_path(key_path, key_type), Ok(None));
+
+        match self.fs_op(ctx, &path, |path| checked_op!(metadata, path))? {
+            Ok(meta) => {
+                let mut metadata = EntryMetadata::default();
+                metadata.modified = meta.modified().ok();
+                // Not every filesystem records creation times.
+                // Since we never modify key files in place,
+                // their modification time is a good substitute.
+                metadata.created = meta.created().ok().or(metadata.modified);
+                metadata.path = Some(
+                    path.checked_path()
+                        .map_err(ArtiNativeKeystoreError::Filesystem)?,
+                );
+                Ok(Some(metadata))
+            }
+            Err(fs_mistrust::Error::NotFound(_)) => Ok(None),
+            Err(err) => Err(ArtiNativeKeystoreError::Filesystem(
+                FilesystemError::FsMistrust {
+                    action: FilesystemAction::Read,
+                    path: path.rel_path_unchecked().into(),
+                    err: err.into(),
+                },
+            ))?,
+        }
+    }
 
-                if let Some(parent) = path.parent() {
-                    // Check the properties of the parent directory by attempting to list its
-                    // contents.
-                    self.keystore_dir
-                        .read_directory(parent)
-                        .map_err(|e| FilesystemError::FsMistrust {
-                            action: FilesystemAction::Read,
-                            path: parent.into(),
-                            err: e.into(),
-                        })
-                        .map_err(ArtiNativeKeystoreError::Filesystem)?;
+    fn expires(
+        &self,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<SystemTime>> {
+        let path = rel_path_if_supported!(self.rel_path(key_path, key_type), Ok(None));
+        let expiry_path = self.expiry_path(&path);
+
+        let value = match self.fs_op(ctx, &expiry_path, |path| checked_op!(read_to_string, path))? {
+            Ok(value) => value,
+            Err(fs_mistrust::Error::NotFound(_)) => return Ok(None),
+            Err(err) => Err(ArtiNativeKeystoreError::Filesystem(
+                FilesystemError::FsMistrust {
+                    action: FilesystemAction::Read,
+                    path: expiry_path.rel_path_unchecked().into(),
+                    err: err.into(),
+                },
+            ))?,
+        };
+        // The entry might have been removed by something other than this key store
+        // (or by an older version of Arti), leaving its expiration time behind.
+        if !self.contains(key_path, key_type, ctx)? {
+            return Ok(None);
+        }
+
+        humantime::parse_rfc3339(value.trim())
+            .map(Some)
+            .map_err(|_| {
+                ArtiNativeKeystoreError::MalformedExpiry {
+                    path: expiry_path.rel_path_unchecked().into(),
+                    value,
                 }
+                .into()
+            })
+    }
 
-                let malformed_err = |path: &Path, err| ArtiNativeKeystoreError::MalformedPath {
-                    path: path.into(),
-                    err,
-                };
+    fn set_expiry(
+        &self,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        expires: Option<SystemTime>,
+        ctx: &OpContext,
+    ) -> Result<Option<()>> {
+        let path = self
+            .rel_path(key_path, key_type)
+            .map_err(|e| tor_error::bad_api_usage!("{e}"))?;
+        if !self.contains(key_path, key_type, ctx)? {
+            return Ok(None);
+        }
 
-                let extension = path
-                    .extension()
-                    .ok_or_else(|| malformed_err(path, err::MalformedPathError::NoExtension))?
-                    .to_str()
-                    .ok_or_else(|| malformed_err(path, err::MalformedPathError::Utf8))?;
+        let expiry_path = self.expiry_path(&path);
+        self.fs_op(ctx, &expiry_path, move |path| match expires {
+            Some(expires) => Self::write_file(
+                path,
+                format!("{}\n", humantime::format_rfc3339_seconds(expires)),
+            ),
+            None => Self::remove_file(path).map(|_| ()),
+        })??;
+        Ok(Some(()))
+    }
 
-                let key_type = KeyType::from(extension);
-                // Strip away the file extension
-                let path = path.with_extension("");
-                // Construct slugs in platform-independent way
-                let slugs = path
-                    .components()
-                    .map(|component| component.as_os_str().to_string_lossy())
-                    .collect::<Vec<_>>()
-                    .join(&arti_path::PATH_SEP.to_string());
-                ArtiPath::new(slugs)
-                    .map(|path| Some((path.into(), key_type)))
-                    .map_err(|e| {
-                        malformed_err(&path, err::MalformedPathError::InvalidArtiPath(e)).into()
-                    })
+    fn dir(&self) -> Option<&Path> {
+        Some(self.keystore_dir.as_path())
+    }
+
+    fn lock_entry(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<EntryLock>> {
+        let path = rel_path_if_supported!(self.rel_path(key_spec, key_type), Ok(None));
+
+        // The lock files live in a separate directory, so that they don't show up
+        // as (unrecognized) entries.
+        let mut lock_path = Path::new(migrate::LOCK_DIR).join(path.rel_path_unchecked());
+        lock_path.as_mut_os_string().push(".lock");
+
+        let fs_err = |path: &Path, err: fs_mistrust::Error| {
+            ArtiNativeKeystoreError::Filesystem(FilesystemError::FsMistrust {
+                action: FilesystemAction::Lock,
+                path: path.into(),
+                err: err.into(),
             })
-            .flatten_ok()
-            .collect()
+        };
+
+        let keystore_dir = self.keystore_dir.clone();
+        ctx.run_blocking(move || {
+            if let Some(parent) = lock_path.parent() {
+                keystore_dir
+                    .make_directory(parent)
+                    .map_err(|err| fs_err(parent, err))?;
+            }
+            let abs_lock_path = keystore_dir
+                .join(&lock_path)
+                .map_err(|err| fs_err(&lock_path, err))?;
+
+            let guard = LockFileGuard::lock(abs_lock_path).map_err(|err| {
+                ArtiNativeKeystoreError::Filesystem(FilesystemError::Io {
+                    action: FilesystemAction::Lock,
+                    path: lock_path,
+                    err: err.into(),
+                })
+            })?;
+
+            Ok(Some(EntryLock::new(guard)))
+        })?
     }
 }
 
@@ -449,7 +801,11 @@ mod tests {
         // Make the permissions of the test key too permissive
         fs::set_permissions(&key_path, fs::Permissions::from_mode(0o777)).unwrap();
         assert!(key_store
-            .get(&TestSpecifier::default(), &KeyType::Ed25519Keypair)
+            .get(
+                &TestSpecifier::default(),
+                &KeyType::Ed25519Keypair,
+                &OpContext::default()
+            )
             .is_err());
 
         // Make the permissions of the parent directory too lax
@@ -459,13 +815,13 @@ mod tests {
         )
         .unwrap();
 
-        assert!(key_store.list().is_err());
+        assert!(key_store.list(&OpContext::default()).is_err());
 
         let key_spec = TestSpecifier::default();
         let ed_key_type = &KeyType::Ed25519Keypair;
         assert_eq!(
             key_store
-                .remove(&key_spec, ed_key_type)
+                .remove(&key_spec, ed_key_type, &OpContext::default())
                 .unwrap_err()
                 .to_string(),
             format!(
@@ -491,7 +847,7 @@ mod tests {
             &KeyType::Ed25519Keypair,
             false
         );
-        assert!(key_store.list().unwrap().is_empty());
+        assert!(key_store.list(&OpContext::default()).unwrap().is_empty());
 
         // Initialize a key store with some test keys
         let (key_store, _keystore_dir) = init_keystore(true);
@@ -504,7 +860,52 @@ mod tests {
             true
         );
 
-        assert_contains_arti_paths!([TestSpecifier::path_prefix(),], key_store.list().unwrap());
+        assert_contains_arti_paths!(
+            [TestSpecifier::path_prefix(),],
+            key_store.list(&OpContext::default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn op_context() {
+        let (key_store, _keystore_dir) = init_keystore(true);
+        let key_spec = TestSpecifier::default();
+        let key_type = KeyType::Ed25519Keypair;
+
+        // Operations whose deadline has passed fail without touching the key store.
+        let expired = OpContext::new().with_deadline(std::time::Instant::now());
+        let res = key_store.get(&key_spec, &key_type, &expired).map(|_| ());
+        assert!(matches!(res, Err(crate::Error::Timeout)), "{res:?}");
+        let res = key_store.remove(&key_spec, &key_type, &expired);
+        assert!(matches!(res, Err(crate::Error::Timeout)), "{res:?}");
+        let res = key_store.contains(&key_spec, &key_type, &expired);
+        assert!(matches!(res, Err(crate::Error::Timeout)), "{res:?}");
+        let res = key_store.list(&expired);
+        assert!(matches!(res, Err(crate::Error::Timeout)), "{res:?}");
+        let key_path: KeyPath = key_spec.arti_path().unwrap().into();
+        let res = key_store.get_raw(&key_path, &key_type, &expired);
+        assert!(matches!(res, Err(crate::Error::Timeout)), "{res:?}");
+        let res = key_store.metadata(&key_path, &key_type, &expired);
+        assert!(matches!(res, Err(crate::Error::Timeout)), "{res:?}");
+        let res = key_store
+            .lock_entry(&key_spec, &key_type, &expired)
+            .map(|_| ());
+        assert!(matches!(res, Err(crate::Error::Timeout)), "{res:?}");
+
+        let cancel = crate::CancellationToken::new();
+        let ctx = OpContext::new().with_cancel(cancel.clone());
+        cancel.cancel();
+        let res = key_store.get(&key_spec, &key_type, &ctx).map(|_| ());
+        assert!(matches!(res, Err(crate::Error::Cancelled)), "{res:?}");
+
+        // Operations that complete in time succeed.
+        let ctx = OpContext::new().with_timeout(std::time::Duration::from_secs(60));
+        assert!(key_store.get(&key_spec, &key_type, &ctx).unwrap().is_some());
+        assert_eq!(
+            key_store.remove(&key_spec, &key_type, &ctx).unwrap(),
+            Some(())
+        );
+        assert!(key_store.get(&key_spec, &key_type, &ctx).unwrap().is_none());
     }
 
     #[test]
@@ -519,7 +920,7 @@ mod tests {
             &KeyType::Ed25519Keypair,
             false
         );
-        assert!(key_store.list().unwrap().is_empty());
+        assert!(key_store.list(&OpContext::default()).unwrap().is_empty());
 
         // Insert the key
         let key = UnparsedOpenSshKey::new(OPENSSH_ED25519.into(), PathBuf::from("/test/path"));
@@ -542,7 +943,9 @@ mod tests {
 
         // The key and its parent directories don't exist yet.
         assert!(!path.parent().unwrap().try_exists().unwrap());
-        assert!(key_store.insert(&*key, &key_spec, ed_key_type).is_ok());
+        assert!(key_store
+            .insert(&*key, &key_spec, ed_key_type, &OpContext::default())
+            .is_ok());
         // insert() is supposed to create the missing directories
         assert!(path.parent().unwrap().try_exists().unwrap());
 
@@ -553,7 +956,10 @@ mod tests {
             &KeyType::Ed25519Keypair,
             true
         );
-        assert_contains_arti_paths!([TestSpecifier::path_prefix(),], key_store.list().unwrap());
+        assert_contains_arti_paths!(
+            [TestSpecifier::path_prefix(),],
+            key_store.list(&OpContext::default()).unwrap()
+        );
     }
 
     #[test]
@@ -571,11 +977,15 @@ mod tests {
         // Now remove the key... remove() should indicate success by returning Ok(Some(()))
         assert_eq!(
             key_store
-                .remove(&TestSpecifier::default(), &KeyType::Ed25519Keypair)
+                .remove(
+                    &TestSpecifier::default(),
+                    &KeyType::Ed25519Keypair,
+                    &OpContext::default()
+                )
                 .unwrap(),
             Some(())
         );
-        assert!(key_store.list().unwrap().is_empty());
+        assert!(key_store.list(&OpContext::default()).unwrap().is_empty());
 
         // Can't find it anymore!
         assert_found!(
@@ -587,17 +997,24 @@ mod tests {
 
         // remove() returns Ok(None) now.
         assert!(key_store
-            .remove(&TestSpecifier::default(), &KeyType::Ed25519Keypair)
+            .remove(
+                &TestSpecifier::default(),
+                &KeyType::Ed25519Keypair,
+                &OpContext::default()
+            )
             .unwrap()
             .is_none());
-        assert!(key_store.list().unwrap().is_empty());
+        assert!(key_store.list(&OpContext::default()).unwrap().is_empty());
     }
 
     #[test]
     fn list() {
         // Initialize the key store
         let (key_store, _keystore_dir) = init_keystore(true);
-        assert_contains_arti_paths!([TestSpecifier::path_prefix(),], key_store.list().unwrap());
+        assert_contains_arti_paths!(
+            [TestSpecifier::path_prefix(),],
+            key_store.list(&OpContext::default()).unwrap()
+        );
 
         // Insert another key
         let key = UnparsedOpenSshKey::new(OPENSSH_ED25519.into(), PathBuf::from("/test/path"));
@@ -612,17 +1029,148 @@ mod tests {
         let key_spec = TestSpecifier::new("-i-am-a-suffix");
         let ed_key_type = KeyType::Ed25519Keypair;
 
-        assert!(key_store.insert(&*key, &key_spec, &ed_key_type).is_ok());
+        assert!(key_store
+            .insert(&*key, &key_spec, &ed_key_type, &OpContext::default())
+            .is_ok());
 
         assert_contains_arti_paths!(
             [
                 TestSpecifier::path_prefix(),
                 format!("{}-i-am-a-suffix", TestSpecifier::path_prefix()),
             ],
-            key_store.list().unwrap()
+            key_store.list(&OpContext::default()).unwrap()
         );
     }
 
+    #[test]
+    fn lock_entry() {
+        use std::sync::mpsc;
+        use std::time::Duration;
+
+        let (key_store, _keystore_dir) = init_keystore(true);
+        let key_spec = TestSpecifier::default();
+        let key_type = KeyType::Ed25519Keypair;
+
+        let lock = key_store
+            .lock_entry(&key_spec, &key_type, &OpContext::default())
+            .unwrap();
+        assert!(lock.is_some());
+        // The lock file is not a keystore entry
+        assert_contains_arti_paths!(
+            [TestSpecifier::path_prefix(),],
+            key_store.list(&OpContext::default()).unwrap()
+        );
+
+        let (tx, rx) = mpsc::channel();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let lock = key_store
+                    .lock_entry(&key_spec, &key_type, &OpContext::default())
+                    .unwrap();
+                tx.send(lock.is_some()).unwrap();
+            });
+
+            // The other thread can't take the lock while we're holding it...
+            assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+            drop(lock);
+            // ...but it can once we release it.
+            assert!(rx.recv_timeout(Duration::from_secs(10)).unwrap());
+        });
+    }
+
+    #[test]
+    fn raw_entries() {
+        let (key_store, _keystore_dir) = init_keystore(true);
+        let key_path: KeyPath = TestSpecifier::default().arti_path().unwrap().into();
+
+        // A known key can be read back as raw data, and parsed
+        let raw = key_store
+            .get_raw(&key_path, &KeyType::Ed25519Keypair, &OpContext::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(raw.as_bytes(), OPENSSH_ED25519.as_bytes());
+        assert!(raw.to_ssh_key_data().is_ok());
+
+        // A key of a type we don't know about can be written and read back
+        let unknown_key_type = KeyType::from("future_key_type");
+        let data = RawKeyData::new(b"who knows what this is".to_vec());
+        assert!(key_store
+            .get_raw(&key_path, &unknown_key_type, &OpContext::default())
+            .unwrap()
+            .is_none());
+        key_store
+            .insert_raw(&data, &key_path, &unknown_key_type, &OpContext::default())
+            .unwrap();
+        let raw = key_store
+            .get_raw(&key_path, &unknown_key_type, &OpContext::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(raw.as_bytes(), data.as_bytes());
+        assert!(raw.to_ssh_key_data().is_err());
+
+        let listed = key_store.list(&OpContext::default()).unwrap();
+        assert!(listed.contains(&(key_path.clone(), unknown_key_type)));
+
+        assert!(key_store
+            .created(&key_path, &KeyType::Ed25519Keypair, &OpContext::default())
+            .unwrap()
+            .is_some());
+        assert!(key_store
+            .created(
+                &key_path,
+                &KeyType::X25519StaticKeypair,
+                &OpContext::default()
+            )
+            .unwrap()
+            .is_none());
+
+        let metadata = key_store
+            .metadata(&key_path, &KeyType::Ed25519Keypair, &OpContext::default())
+            .unwrap()
+            .unwrap();
+        assert!(metadata.modified.is_some());
+        assert!(metadata.created.is_some());
+        let path = metadata.path.unwrap();
+        assert!(path.starts_with(key_store.dir().unwrap()));
+        assert_eq!(
+            path.extension().unwrap(),
+            KeyType::Ed25519Keypair.arti_extension().as_str()
+        );
+        assert!(key_store
+            .metadata(
+                &key_path,
+                &KeyType::X25519StaticKeypair,
+                &OpContext::default()
+            )
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn ed25519_signer() {
+        use tor_llcrypto::pk::ed25519::Verifier as _;
+
+        let (key_store, _keystore_dir) = init_keystore(false);
+        let spec = TestSpecifier::default();
+        assert!(key_store
+            .ed25519_signer(&spec, &KeyType::Ed25519Keypair, &OpContext::default())
+            .unwrap()
+            .is_none());
+
+        let (key_store, _keystore_dir) = init_keystore(true);
+        let signer = key_store
+            .ed25519_signer(&spec, &KeyType::Ed25519Keypair, &OpContext::default())
+            .unwrap()
+            .unwrap();
+        let sig = signer.sign(b"hello").unwrap();
+        signer.public_key().verify(b"hello", &sig).unwrap();
+
+        // Not an ed25519 keypair.
+        assert!(key_store
+            .ed25519_signer(&spec, &KeyType::Ed25519PublicKey, &OpContext::default())
+            .is_err());
+    }
+
     #[test]
     fn key_path_not_regular_file() {
         let (key_store, _keystore_dir) = init_keystore(false);
@@ -636,7 +1184,11 @@ mod tests {
         fs::set_permissions(parent, fs::Permissions::from_mode(0o700)).unwrap();
 
         let err = key_store
-            .contains(&TestSpecifier::default(), &KeyType::Ed25519Keypair)
+            .contains(
+                &TestSpecifier::default(),
+                &KeyType::Ed25519Keypair,
+                &OpContext::default(),
+            )
             .unwrap_err();
         assert!(err.to_string().contains("not a regular file"), "{err}");
     }
@@ -0,0 +1,829 @@
+//! A variant of the Arti key store that encrypts keys at rest.
+//!
+//! See the [`ArtiEncryptedKeystore`] docs for more details.
+
+pub(crate) mod err;
+#[cfg(feature = "os-keystore")]
+pub(crate) mod os;
+
+use std::io;
+use std::path::Path;
+use std::result::Result as StdResult;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use data_encoding::BASE64;
+use fs_mistrust::Mistrust;
+use rand::RngCore as _;
+use tor_config::{CfgSecret, CfgSecretError};
+use tor_error::{bad_api_usage, internal};
+use tor_key_forge::{EncodableKey, ErasedKey, KeyType, SecretBuffer};
+use zeroize::Zeroizing;
+
+use super::migrate::ENCRYPTION_FILE;
+use super::ssh::UnparsedOpenSshKey;
+use super::{arti_key_path, ArtiNativeKeystore};
+use crate::keystore::fs_utils::{FilesystemAction, FilesystemError};
+use crate::keystore::{EntryLock, EntryMetadata, OpContext, RawKeyData};
+use crate::{KeyPath, KeySpecifier, Keystore, KeystoreId, Result};
+use err::ArtiEncryptedKeystoreError;
+
+/// The length of the key derived from the passphrase, in bytes.
+const KEY_LEN: usize = 32;
+
+/// The length of the salt used when deriving the key from the passphrase, in bytes.
+const SALT_LEN: usize = 16;
+
+/// The length of an XChaCha20-Poly1305 nonce, in bytes.
+const NONCE_LEN: usize = 24;
+
+/// The largest Argon2id memory cost (in KiB) we accept from an [`ENCRYPTION_FILE`].
+///
+/// The header is read from disk before the passphrase can be checked,
+/// so without a limit a tampered header could make us allocate an unbounded amount of memory.
+const MAX_M_COST: u32 = 1024 * 1024;
+
+/// The largest Argon2id iteration count we accept from an [`ENCRYPTION_FILE`].
+const MAX_T_COST: u32 = 64;
+
+/// The largest Argon2id degree of parallelism we accept from an [`ENCRYPTION_FILE`].
+const MAX_P_COST: u32 = 16;
+
+/// The first line of the [`ENCRYPTION_FILE`].
+const HEADER_MAGIC: &str = "arti-encrypted-keystore 1";
+
+/// The prefix of every encrypted key file.
+const ENTRY_MAGIC: &[u8] = b"arti-encrypted-key 1\n";
+
+/// The associated data of the ciphertext used to check the passphrase.
+const CHECK_AAD: &[u8] = b"arti-encrypted-keystore passphrase check";
+
+/// A hook for asking the user for the passphrase of an [`ArtiEncryptedKeystore`].
+///
+/// Used by [`ArtiEncryptedKeystore::unlock_with`].
+pub trait PassphrasePrompt: Send + Sync {
+    /// Ask for the passphrase of the key store identified by `id`.
+    ///
+    /// If `new` is true, the key store does not have a passphrase yet,
+    /// and the one returned will be used to encrypt its keys from now on
+    /// (so implementations may want to ask for it twice).
+    fn passphrase(&self, id: &KeystoreId, new: bool) -> io::Result<Zeroizing<String>>;
+}
+
+/// A [`PassphrasePrompt`] that supplies a passphrase from the configuration,
+/// instead of asking the user for it.
+///
+/// See `storage.keystore.primary.passphrase`
+/// ([`ArtiKeystoreConfig::primary_passphrase`](crate::config::ArtiKeystoreConfig::primary_passphrase)).
+pub struct ConfiguredPassphrase(Zeroizing<String>);
+
+impl ConfiguredPassphrase {
+    /// Look up the passphrase designated by `secret`.
+    ///
+    /// Call this when the configuration is loaded,
+    /// so that a passphrase that can't be found is reported straight away.
+    pub fn resolve(secret: &CfgSecret) -> StdResult<Self, CfgSecretError> {
+        secret.resolve().map(Self)
+    }
+}
+
+impl PassphrasePrompt for ConfiguredPassphrase {
+    fn passphrase(&self, _id: &KeystoreId, _new: bool) -> io::Result<Zeroizing<String>> {
+        Ok(self.0.clone())
+    }
+}
+
+impl std::fmt::Debug for ConfiguredPassphrase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConfiguredPassphrase")
+            .finish_non_exhaustive()
+    }
+}
+
+/// The Arti key store, with the keys encrypted under a passphrase.
+///
+/// This key store uses the same on-disk layout as the [`ArtiNativeKeystore`],
+/// but the contents of each key file are encrypted with XChaCha20-Poly1305,
+/// using a key derived from a passphrase with Argon2id.
+/// The names of the files (and hence the [`KeyPath`]s and [`KeyType`]s of the keys)
+/// are **not** encrypted.
+/// Each ciphertext is bound to the path of its file,
+/// so encrypted keys cannot be swapped around without detection.
+///
+/// The parameters of the key derivation function, the salt,
+/// and a value used to check the passphrase
+/// are stored in a file at the root of the key store.
+///
+/// The key store starts out locked.
+/// While it is locked, the keys can be listed and removed, but not read or written.
+/// Use [`unlock`](Keystore::unlock) (or [`KeyMgr::unlock`](crate::KeyMgr::unlock))
+/// to unlock it.
+/// The first time a key store is unlocked,
+/// the passphrase it was unlocked with becomes its passphrase.
+///
+/// This key store cannot read keys written by the [`ArtiNativeKeystore`]:
+/// such keys must be re-inserted into this key store to encrypt them.
+#[derive(Debug)]
+pub struct ArtiEncryptedKeystore {
+    /// The underlying key store, which holds the encrypted keys.
+    inner: ArtiNativeKeystore,
+    /// The key derivation parameters to use when setting the passphrase.
+    kdf_params: KdfParams,
+    /// The key derived from our passphrase, or `None` if we are locked.
+    key: Mutex<Option<Zeroizing<[u8; KEY_LEN]>>>,
+}
+
+/// The parameters of Argon2id.
+///
+/// See [`argon2::Params`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct KdfParams {
+    /// The memory cost, in KiB.
+    m_cost: u32,
+    /// The number of iterations.
+    t_cost: u32,
+    /// The degree of parallelism.
+    p_cost: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl KdfParams {
+    /// Derive a key from `passphrase` and `salt`.
+    fn derive_key(
+        &self,
+        passphrase: &str,
+        salt: &[u8],
+    ) -> StdResult<Zeroizing<[u8; KEY_LEN]>, ArtiEncryptedKeystoreError> {
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, Some(KEY_LEN))
+            .map_err(|e| ArtiEncryptedKeystoreError::MalformedHeader(e.to_string()))?;
+        let mut key = Zeroizing::new([0_u8; KEY_LEN]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), salt, &mut *key)
+            .map_err(|e| internal!("key derivation failed: {e}"))?;
+        Ok(key)
+    }
+}
+
+/// The contents of the [`ENCRYPTION_FILE`].
+///
+/// The file is made of the following lines:
+///
+/// ```text
+/// arti-encrypted-keystore 1
+/// argon2id <m_cost> <t_cost> <p_cost>
+/// salt <base64>
+/// check <base64>
+/// ```
+///
+/// where `check` is the encryption of the empty string with [`CHECK_AAD`],
+/// which we use to tell whether a passphrase is correct.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Header {
+    /// The parameters of the key derivation function.
+    kdf_params: KdfParams,
+    /// The salt for the key derivation function.
+    salt: Vec<u8>,
+    /// The encrypted passphrase check value.
+    check: Vec<u8>,
+}
+
+impl Header {
+    /// Create a new header for `passphrase`, returning it along with the derived key.
+    fn new(
+        kdf_params: KdfParams,
+        passphrase: &str,
+    ) -> StdResult<(Self, Zeroizing<[u8; KEY_LEN]>), ArtiEncryptedKeystoreError> {
+        let mut salt = vec![0_u8; SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let key = kdf_params.derive_key(passphrase, &salt)?;
+        let check = encrypt(&key, CHECK_AAD, &[])?;
+        let header = Header {
+            kdf_params,
+            salt,
+            check,
+        };
+        Ok((header, key))
+    }
+
+    /// Derive the key for `passphrase`, checking that the passphrase is correct.
+    fn unlock(
+        &self,
+        passphrase: &str,
+    ) -> StdResult<Zeroizing<[u8; KEY_LEN]>, ArtiEncryptedKeystoreError> {
+        let key = self.kdf_params.derive_key(passphrase, &self.salt)?;
+        decrypt(&key, CHECK_AAD, &self.check)
+            .map_err(|()| ArtiEncryptedKeystoreError::WrongPassphrase)?;
+        Ok(key)
+    }
+
+    /// Encode this header.
+    fn encode(&self) -> String {
+        let KdfParams {
+            m_cost,
+            t_cost,
+            p_cost,
+        } = self.kdf_params;
+        format!(
+            "{HEADER_MAGIC}\nargon2id {m_cost} {t_cost} {p_cost}\nsalt {}\ncheck {}\n",
+            BASE64.encode(&self.salt),
+            BASE64.encode(&self.check),
+        )
+    }
+
+    /// Parse a header.
+    fn parse(s: &str) -> StdResult<Self, ArtiEncryptedKeystoreError> {
+        let malformed = |problem: &str| ArtiEncryptedKeystoreError::MalformedHeader(problem.into());
+        let mut lines = s.lines();
+
+        if lines.next() != Some(HEADER_MAGIC) {
+            return Err(malformed("unrecognized format"));
+        }
+
+        let mut field = |name: &str| {
+            lines
+                .next()
+                .and_then(|l| l.strip_prefix(name))
+                .and_then(|l| l.strip_prefix(' '))
+                .ok_or_else(|| malformed(&format!("missing {name}")))
+        };
+
+        let kdf_params: Vec<u32> = field("argon2id")?
+            .split(' ')
+            .map(|n| n.parse())
+            .collect::<StdResult<_, _>>()
+            .map_err(|_| malformed("invalid argon2id parameters"))?;
+        let [m_cost, t_cost, p_cost] = kdf_params[..] else {
+            return Err(malformed("wrong number of argon2id parameters"));
+        };
+        if m_cost > MAX_M_COST || t_cost > MAX_T_COST || p_cost > MAX_P_COST {
+            return Err(malformed("argon2id parameters too large"));
+        }
+        let salt = BASE64
+            .decode(field("salt")?.as_bytes())
+            .map_err(|_| malformed("invalid salt"))?;
+        let check = BASE64
+            .decode(field("check")?.as_bytes())
+            .map_err(|_| malformed("invalid check value"))?;
+
+        Ok(Header {
+            kdf_params: KdfParams {
+                m_cost,
+                t_cost,
+                p_cost,
+            },
+            salt,
+            check,
+        })
+    }
+}
+
+/// Encrypt `msg` under `key`, binding it to `aad`.
+///
+/// Returns the nonce followed by the ciphertext.
+fn encrypt(
+    key: &[u8; KEY_LEN],
+    aad: &[u8],
+    msg: &[u8],
+) -> StdResult<Vec<u8>, ArtiEncryptedKeystoreError> {
+    let mut nonce = [0_u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let ciphertext = XChaCha20Poly1305::new(Key::from_slice(key))
+        .encrypt(XNonce::from_slice(&nonce), Payload { msg, aad })
+        .map_err(|_| internal!("encryption failed"))?;
+
+    let mut out = nonce.to_vec();
+    out.extend(ciphertext);
+    Ok(out)
+}
+
+/// Decrypt the output of [`encrypt`].
+///
+/// Returns `Err(())` if `data` was not encrypted under `key` with `aad`,
+/// or has been tampered with.
+fn decrypt(key: &[u8; KEY_LEN], aad: &[u8], data: &[u8]) -> StdResult<Zeroizing<Vec<u8>>, ()> {
+    if data.len() < NONCE_LEN {
+        return Err(());
+    }
+    let (nonce, msg) = data.split_at(NONCE_LEN);
+    XChaCha20Poly1305::new(Key::from_slice(key))
+        .decrypt(XNonce::from_slice(nonce), Payload { msg, aad })
+        .map(Zeroizing::new)
+        .map_err(|_| ())
+}
+
+/// Return the associated data to use when encrypting the key at `key_path`.
+fn entry_aad(key_path: &KeyPath, key_type: &KeyType) -> Result<Vec<u8>> {
+    let arti_path = key_path
+        .arti_path()
+        .map_err(|e| bad_api_usage!("cannot encrypt key {key_path}: {e}"))?;
+    Ok(format!("{arti_path}.{}", key_type.arti_extension()).into_bytes())
+}
+
+impl ArtiEncryptedKeystore {
+    /// Create a new [`ArtiEncryptedKeystore`] rooted at the specified `keystore_dir` directory.
+    ///
+    /// The key store is initially locked.
+    ///
+    /// The `keystore_dir` directory is created if it doesn't exist.
+    /// See [`ArtiNativeKeystore::from_path_and_mistrust`] for the possible errors.
+    pub fn from_path_and_mistrust(
+        keystore_dir: impl AsRef<Path>,
+        mistrust: &Mistrust,
+    ) -> Result<Self> {
+        Self::from_path_and_mistrust_with_ctx(keystore_dir, mistrust, &OpContext::default())
+    }
+
+    /// Like [`from_path_and_mistrust`](Self::from_path_and_mistrust),
+    /// but giving up if `ctx` is cancelled or its deadline passes.
+    pub fn from_path_and_mistrust_with_ctx(
+        keystore_dir: impl AsRef<Path>,
+        mistrust: &Mistrust,
+        ctx: &OpContext,
+    ) -> Result<Self> {
+        Ok(Self::with_kdf_params(
+            ArtiNativeKeystore::from_path_and_mistrust_with_ctx(keystore_dir, mistrust, ctx)?,
+            KdfParams::default(),
+        ))
+    }
+
+    /// Create a new [`ArtiEncryptedKeystore`] storing its keys in `inner`,
+    /// which will use `kdf_params` if its passphrase needs to be set.
+    fn with_kdf_params(inner: ArtiNativeKeystore, kdf_params: KdfParams) -> Self {
+        Self {
+            inner,
+            kdf_params,
+            key: Mutex::new(None),
+        }
+    }
+
+    /// Return true if a passphrase has been set for this key store.
+    ///
+    /// If this returns false, the next call to [`unlock`](Keystore::unlock)
+    /// will set the passphrase.
+    pub fn has_passphrase(&self) -> Result<bool> {
+        Ok(self.read_header()?.is_some())
+    }
+
+    /// Unlock this key store with a passphrase obtained from `prompt`.
+    pub fn unlock_with(&self, prompt: &dyn PassphrasePrompt) -> Result<()> {
+        let new = !self.has_passphrase()?;
+        let passphrase = prompt
+            .passphrase(self.id(), new)
+            .map_err(|e| ArtiEncryptedKeystoreError::Prompt(e.into()))?;
+        self.unlock(&passphrase)
+    }
+
+    /// Read the [`ENCRYPTION_FILE`], if there is one.
+    fn read_header(&self) -> Result<Option<Header>> {
+        match self.inner.keystore_dir.read_to_string(ENCRYPTION_FILE) {
+            Ok(s) => Ok(Some(Header::parse(&s)?)),
+            Err(fs_mistrust::Error::NotFound(_)) => Ok(None),
+            Err(e) => Err(ArtiEncryptedKeystoreError::Filesystem(
+                FilesystemError::FsMistrust {
+                    action: FilesystemAction::Read,
+                    path: ENCRYPTION_FILE.into(),
+                    err: e.into(),
+                },
+            ))?,
+        }
+    }
+
+    /// Write the [`ENCRYPTION_FILE`].
+    fn write_header(&self, header: &Header) -> Result<()> {
+        self.inner
+            .keystore_dir
+            .write_and_replace(ENCRYPTION_FILE, header.encode())
+            .map_err(|e| {
+                ArtiEncryptedKeystoreError::Filesystem(FilesystemError::FsMistrust {
+                    action: FilesystemAction::Write,
+                    path: ENCRYPTION_FILE.into(),
+                    err: e.into(),
+                })
+            })?;
+        Ok(())
+    }
+
+    /// Return the key derived from our passphrase,
+    /// or an error if we are locked.
+    fn key(&self) -> Result<Zeroizing<[u8; KEY_LEN]>> {
+        self.key
+            .lock()
+            .expect("lock poisoned")
+            .clone()
+            .ok_or_else(|| ArtiEncryptedKeystoreError::Locked.into())
+    }
+}
+
+impl Keystore for ArtiEncryptedKeystore {
+    fn id(&self) -> &KeystoreId {
+        self.inner.id()
+    }
+
+    fn contains(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<bool> {
+        self.inner.contains(key_spec, key_type, ctx)
+    }
+
+    fn get(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<ErasedKey>> {
+        let Some(key_path) = arti_key_path(key_spec)? else {
+            return Ok(None);
+        };
+        let Some(data) = self.get_raw(&key_path, key_type, ctx)? else {
+            return Ok(None);
+        };
+
+        let path = self
+            .inner
+            .rel_path(key_spec, key_type)
+            .map_err(|e| internal!("{e}"))?
+            .rel_path_unchecked()
+            .to_path_buf();
+        let data = data.into_secret();
+        if data.as_str().is_err() {
+            return Err(ArtiEncryptedKeystoreError::MalformedEntry(path).into());
+        }
+        UnparsedOpenSshKey::new(data, path)
+            .parse_ssh_format_erased(key_type)
+            .map(Some)
+    }
+
+    fn insert(
+        &self,
+        key: &dyn EncodableKey,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<()> {
+        let key_path = arti_key_path(key_spec)?
+            .ok_or_else(|| internal!("cannot insert key without an ArtiPath"))?;
+
+        // TODO (#1095): decide what information, if any, to put in the comment
+        let openssh_key = key.as_ssh_key_data()?.to_openssh_string("")?;
+
+        self.insert_raw(
+            &RawKeyData::from(SecretBuffer::from(openssh_key)),
+            &key_path,
+            key_type,
+            ctx,
+        )
+    }
+
+    fn remove(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<()>> {
+        self.inner.remove(key_spec, key_type, ctx)
+    }
+
+    fn list(&self, ctx: &OpContext) -> Result<Vec<(KeyPath, KeyType)>> {
+        self.inner.list(ctx)
+    }
+
+    fn get_raw(
+        &self,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<RawKeyData>> {
+        let key = self.key()?;
+        let Some(data) = self.inner.get_raw(key_path, key_type, ctx)? else {
+            return Ok(None);
+        };
+
+        let malformed = || ArtiEncryptedKeystoreError::MalformedEntry(key_path.to_string().into());
+        let ciphertext = data
+            .as_bytes()
+            .strip_prefix(ENTRY_MAGIC)
+            .ok_or_else(malformed)?;
+        let plaintext =
+            decrypt(&key, &entry_aad(key_path, key_type)?, ciphertext).map_err(|()| malformed())?;
+
+        Ok(Some(RawKeyData::from(SecretBuffer::from(plaintext))))
+    }
+
+    fn insert_raw(
+        &self,
+        data: &RawKeyData,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<()> {
+        let key = self.key()?;
+        let ciphertext = encrypt(&key, &entry_aad(key_path, key_type)?, data.as_bytes())?;
+
+        let mut contents = ENTRY_MAGIC.to_vec();
+        contents.extend(ciphertext);
+        self.inner
+            .insert_raw(&RawKeyData::new(contents), key_path, key_type, ctx)
+    }
+
+    fn created(
+        &self,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<SystemTime>> {
+        // File metadata isn't encrypted, so we can report it even while locked.
+        self.inner.created(key_path, key_type, ctx)
+    }
+
+    fn metadata(
+        &self,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<EntryMetadata>> {
+        // Likewise, the metadata is that of the (encrypted) file.
+        self.inner.metadata(key_path, key_type, ctx)
+    }
+
+    fn expires(
+        &self,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<SystemTime>> {
+        // Expiration times are not secret, so they aren't encrypted.
+        self.inner.expires(key_path, key_type, ctx)
+    }
+
+    fn set_expiry(
+        &self,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        expires: Option<SystemTime>,
+        ctx: &OpContext,
+    ) -> Result<Option<()>> {
+        self.inner.set_expiry(key_path, key_type, expires, ctx)
+    }
+
+    fn dir(&self) -> Option<&Path> {
+        self.inner.dir()
+    }
+
+    fn is_locked(&self) -> bool {
+        self.key.lock().expect("lock poisoned").is_none()
+    }
+
+    fn unlock(&self, passphrase: &str) -> Result<()> {
+        let key = match self.read_header()? {
+            Some(header) => header.unlock(passphrase)?,
+            None => {
+                let (header, key) = Header::new(self.kdf_params, passphrase)?;
+                self.write_header(&header)?;
+                key
+            }
+        };
+
+        *self.key.lock().expect("lock poisoned") = Some(key);
+        Ok(())
+    }
+
+    fn lock(&self) {
+        *self.key.lock().expect("lock poisoned") = None;
+    }
+
+    fn lock_entry(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<EntryLock>> {
+        // Lock files don't contain any secrets, so we can lock entries even while locked.
+        self.inner.lock_entry(key_spec, key_type, ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use crate::test_utils::TestSpecifier;
+    use std::fs;
+    use tempfile::{tempdir, TempDir};
+    use tor_error::{ErrorKind, HasKind};
+    use tor_llcrypto::pk::ed25519;
+
+    #[cfg(unix)]
+    use std::os::unix::fs::PermissionsExt;
+
+    /// Key derivation parameters that are cheap enough for tests.
+    const TEST_KDF_PARAMS: KdfParams = KdfParams {
+        m_cost: 8,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    pub(super) fn init_keystore() -> (ArtiEncryptedKeystore, TempDir) {
+        let keystore_dir = tempdir().unwrap();
+
+        #[cfg(unix)]
+        fs::set_permissions(&keystore_dir, fs::Permissions::from_mode(0o700)).unwrap();
+
+        let inner = ArtiNativeKeystore::from_path_and_mistrust(&keystore_dir, &Mistrust::default())
+            .unwrap();
+
+        (
+            ArtiEncryptedKeystore::with_kdf_params(inner, TEST_KDF_PARAMS),
+            keystore_dir,
+        )
+    }
+
+    pub(super) fn reopen(dir: &TempDir) -> ArtiEncryptedKeystore {
+        let inner = ArtiNativeKeystore::from_path_and_mistrust(dir, &Mistrust::default()).unwrap();
+        ArtiEncryptedKeystore::with_kdf_params(inner, TEST_KDF_PARAMS)
+    }
+
+    pub(super) fn keypair() -> ed25519::Keypair {
+        let mut rng = tor_basic_utils::test_rng::testing_rng();
+        ed25519::Keypair::generate(&mut rng)
+    }
+
+    #[test]
+    fn header_roundtrip() {
+        let (header, key) = Header::new(TEST_KDF_PARAMS, "hunter2").unwrap();
+        let parsed = Header::parse(&header.encode()).unwrap();
+        assert_eq!(parsed, header);
+        assert_eq!(*parsed.unlock("hunter2").unwrap(), *key);
+        assert!(matches!(
+            parsed.unlock("hunter3"),
+            Err(ArtiEncryptedKeystoreError::WrongPassphrase)
+        ));
+
+        assert!(Header::parse("arti-encrypted-keystore 2\n").is_err());
+        assert!(Header::parse("arti-encrypted-keystore 1\nargon2id 8 1\n").is_err());
+
+        for (m_cost, t_cost, p_cost) in [
+            (MAX_M_COST + 1, 1, 1),
+            (8, MAX_T_COST + 1, 1),
+            (8, 1, MAX_P_COST + 1),
+        ] {
+            let encoded = header.encode().replacen(
+                "argon2id 8 1 1",
+                &format!("argon2id {m_cost} {t_cost} {p_cost}"),
+                1,
+            );
+            assert!(matches!(
+                Header::parse(&encoded),
+                Err(ArtiEncryptedKeystoreError::MalformedHeader(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn locked() {
+        let (key_store, _dir) = init_keystore();
+        let key_type = KeyType::Ed25519Keypair;
+        assert!(key_store.is_locked());
+        assert!(!key_store.has_passphrase().unwrap());
+
+        let err = key_store
+            .insert(
+                &keypair(),
+                &TestSpecifier::default(),
+                &key_type,
+                &OpContext::default(),
+            )
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::KeystoreAccessFailed);
+        assert!(key_store
+            .get(&TestSpecifier::default(), &key_type, &OpContext::default())
+            .is_err());
+
+        key_store.unlock("hunter2").unwrap();
+        assert!(!key_store.is_locked());
+        assert!(key_store.has_passphrase().unwrap());
+
+        key_store.lock();
+        assert!(key_store.is_locked());
+        let err = key_store.unlock("hunter3").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::KeystoreAccessFailed);
+        assert!(key_store.is_locked());
+    }
+
+    #[test]
+    fn insert_and_get() {
+        let (key_store, dir) = init_keystore();
+        let key_spec = TestSpecifier::default();
+        let key_type = KeyType::Ed25519Keypair;
+        let key = keypair();
+
+        key_store.unlock("hunter2").unwrap();
+        key_store
+            .insert(&key, &key_spec, &key_type, &OpContext::default())
+            .unwrap();
+        assert!(key_store
+            .contains(&key_spec, &key_type, &OpContext::default())
+            .unwrap());
+        assert_eq!(key_store.list(&OpContext::default()).unwrap().len(), 1);
+
+        // The key is not stored in the clear
+        let path = key_store
+            .inner
+            .rel_path(&key_spec, &key_type)
+            .unwrap()
+            .checked_path()
+            .unwrap();
+        let contents = fs::read(&path).unwrap();
+        assert!(contents.starts_with(ENTRY_MAGIC));
+        assert!(!String::from_utf8_lossy(&contents).contains("OPENSSH"));
+
+        // It can be read back after reopening the key store
+        let key_store = reopen(&dir);
+        assert!(key_store
+            .contains(&key_spec, &key_type, &OpContext::default())
+            .unwrap());
+        key_store.unlock("hunter2").unwrap();
+        let erased_kp = key_store
+            .get(&key_spec, &key_type, &OpContext::default())
+            .unwrap()
+            .unwrap();
+        let Ok(found) = erased_kp.downcast::<ed25519::Keypair>() else {
+            panic!("failed to downcast key to ed25519::Keypair")
+        };
+        assert_eq!(found.verifying_key(), key.verifying_key());
+
+        let raw = key_store
+            .get_raw(
+                &key_spec.arti_path().unwrap().into(),
+                &key_type,
+                &OpContext::default(),
+            )
+            .unwrap()
+            .unwrap();
+        assert!(raw.to_ssh_key_data().is_ok());
+
+        assert_eq!(
+            key_store
+                .remove(&key_spec, &key_type, &OpContext::default())
+                .unwrap(),
+            Some(())
+        );
+        assert!(key_store
+            .get(&key_spec, &key_type, &OpContext::default())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn tampering() {
+        let (key_store, _dir) = init_keystore();
+        let key_spec = TestSpecifier::default();
+        let key_type = KeyType::Ed25519Keypair;
+        key_store.unlock("hunter2").unwrap();
+        key_store
+            .insert(&keypair(), &key_spec, &key_type, &OpContext::default())
+            .unwrap();
+
+        let path = key_store
+            .inner
+            .rel_path(&key_spec, &key_type)
+            .unwrap()
+            .checked_path()
+            .unwrap();
+        let mut contents = fs::read(&path).unwrap();
+        let last = contents.len() - 1;
+        contents[last] ^= 1;
+        fs::write(&path, contents).unwrap();
+
+        let Err(err) = key_store.get(&key_spec, &key_type, &OpContext::default()) else {
+            panic!("decrypted a key that was tampered with")
+        };
+        assert_eq!(err.kind(), ErrorKind::KeystoreCorrupted);
+    }
+}
@@ -0,0 +1,67 @@
+//! An error type for [`ArtiEncryptedKeystore`](crate::ArtiEncryptedKeystore).
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use tor_error::{ErrorKind, HasKind};
+
+use crate::keystore::fs_utils::FilesystemError;
+use crate::KeystoreError;
+
+/// An error returned by [`ArtiEncryptedKeystore`](crate::ArtiEncryptedKeystore)'s
+/// [`Keystore`](crate::Keystore) implementation.
+#[derive(thiserror::Error, Debug, Clone)]
+pub(crate) enum ArtiEncryptedKeystoreError {
+    /// An error that occurred while accessing the filesystem.
+    #[error("{0}")]
+    Filesystem(#[from] FilesystemError),
+
+    /// The keystore must be unlocked before its keys can be accessed.
+    #[error("Keystore is locked")]
+    Locked,
+
+    /// The passphrase we were given is not the passphrase of the keystore.
+    #[error("Incorrect keystore passphrase")]
+    WrongPassphrase,
+
+    /// We could not obtain a passphrase.
+    #[error("Unable to obtain keystore passphrase")]
+    Prompt(#[source] Arc<io::Error>),
+
+    /// The file describing how the keystore is encrypted could not be parsed.
+    #[error("Malformed keystore encryption header: {0}")]
+    MalformedHeader(String),
+
+    /// A key could not be decrypted with our passphrase.
+    ///
+    /// The key was either not written by an encrypted keystore,
+    /// or has been tampered with.
+    #[error("Unable to decrypt key {0:?}")]
+    MalformedEntry(PathBuf),
+
+    /// An internal error.
+    #[error("Internal error")]
+    Bug(#[from] tor_error::Bug),
+}
+
+impl KeystoreError for ArtiEncryptedKeystoreError {}
+
+impl HasKind for ArtiEncryptedKeystoreError {
+    fn kind(&self) -> ErrorKind {
+        use ArtiEncryptedKeystoreError as KE;
+
+        match self {
+            KE::Filesystem(e) => e.kind(),
+            KE::Locked | KE::WrongPassphrase | KE::Prompt(_) => ErrorKind::KeystoreAccessFailed,
+            KE::MalformedHeader(_) | KE::MalformedEntry(_) => ErrorKind::KeystoreCorrupted,
+            KE::Bug(e) => e.kind(),
+        }
+    }
+}
+
+impl From<ArtiEncryptedKeystoreError> for crate::Error {
+    fn from(e: ArtiEncryptedKeystoreError) -> Self {
+        crate::Error::Keystore(Arc::new(e))
+    }
+}
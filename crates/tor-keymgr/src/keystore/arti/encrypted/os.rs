@@ -0,0 +1,229 @@
+//! Keeping the passphrase of an [`ArtiEncryptedKeystore`](crate::ArtiEncryptedKeystore) in the OS credential store.
+//!
+//! See the [`OsCredentialStore`] docs for more details.
+
+use std::io;
+
+use data_encoding::BASE64;
+use keyring::credential::CredentialPersistence;
+use keyring::Entry;
+use rand::RngCore as _;
+use zeroize::Zeroizing;
+
+use super::PassphrasePrompt;
+use crate::KeystoreId;
+
+/// The service name under which the passphrases are stored.
+const SERVICE: &str = "org.torproject.arti.keystore";
+
+/// The length of the random passphrases we generate, in bytes (before encoding them).
+const PASSPHRASE_LEN: usize = 32;
+
+/// A [`PassphrasePrompt`] that keeps the passphrase of an
+/// [`ArtiEncryptedKeystore`](crate::ArtiEncryptedKeystore)
+/// in the credential store of the operating system,
+/// instead of asking the user for it.
+///
+/// This is not a key store in its own right:
+/// the keys themselves are still stored, encrypted, in the `ArtiEncryptedKeystore` directory,
+/// and only the passphrase that encrypts them is kept in the credential store.
+///
+/// The first time the key store is unlocked, a random passphrase is generated
+/// and saved in the credential store.
+/// From then on, the key store is unlocked with the saved passphrase,
+/// so the keys are encrypted at rest
+/// without the user (or the application embedding Arti) having to manage a passphrase.
+///
+/// The credential store depends on the platform:
+///
+///   * on macOS, the passphrase is kept in the user's login Keychain;
+///   * on Windows, it is kept in the Windows Credential Manager,
+///     which protects it with DPAPI;
+///   * on Linux and the BSDs, it is kept in the default collection of the
+///     freedesktop Secret Service (GNOME Keyring, KWallet, ...), which is reached over D-Bus.
+///
+/// On other platforms, and on systems where no credential store is available
+/// (for instance, headless Linux systems without a D-Bus session or a Secret Service),
+/// [`OsCredentialStore::new`] fails.
+/// Applications that may run on such systems should fall back to another [`PassphrasePrompt`],
+/// such as a [`ConfiguredPassphrase`](crate::ConfiguredPassphrase)
+/// or one that asks the user.
+/// (The passphrase generated by an `OsCredentialStore` can't be recovered
+/// without the credential store, so a key store should keep using the same kind of prompt.)
+///
+/// The keys are only as safe as the credential store:
+/// anyone who can read the passphrase from it (typically, any process running as the same user
+/// while the user is logged in) can decrypt them.
+/// If the passphrase is removed from the credential store,
+/// the keys can no longer be decrypted.
+pub struct OsCredentialStore {
+    /// The credential store entry that holds the passphrase.
+    entry: Entry,
+}
+
+impl OsCredentialStore {
+    /// Create an [`OsCredentialStore`] for the passphrase of the key store
+    /// identified by `account`.
+    ///
+    /// `account` must uniquely identify the key store among the encrypted key stores
+    /// of the current user: the absolute path of the key store directory is a good choice.
+    ///
+    /// Returns an error if the platform has no credential store
+    /// that persists across restarts, or if the credential store can't be reached.
+    pub fn new(account: &str) -> io::Result<Self> {
+        let persistence = keyring::default::default_credential_builder().persistence();
+        if !matches!(persistence, CredentialPersistence::UntilDelete) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "no persistent credential store on this platform",
+            ));
+        }
+
+        let entry = Entry::new(SERVICE, account).map_err(to_io_error)?;
+        Self::with_entry(entry)
+    }
+
+    /// Create an [`OsCredentialStore`] that keeps the passphrase in `entry`.
+    ///
+    /// Returns an error if the credential store can't be reached.
+    fn with_entry(entry: Entry) -> io::Result<Self> {
+        // Creating the entry doesn't contact the credential store
+        // (the Secret Service, for instance, is only reached over D-Bus when the entry is used),
+        // so look the entry up now, rather than failing when the key store is unlocked.
+        match entry.get_password() {
+            Ok(passphrase) => drop(Zeroizing::new(passphrase)),
+            Err(keyring::Error::NoEntry) => {}
+            Err(e) => return Err(to_io_error(e)),
+        }
+
+        Ok(Self { entry })
+    }
+}
+
+impl PassphrasePrompt for OsCredentialStore {
+    fn passphrase(&self, id: &KeystoreId, new: bool) -> io::Result<Zeroizing<String>> {
+        if new {
+            let mut bytes = Zeroizing::new([0_u8; PASSPHRASE_LEN]);
+            rand::thread_rng().fill_bytes(&mut *bytes);
+            let passphrase = Zeroizing::new(BASE64.encode(&*bytes));
+            self.entry.set_password(&passphrase).map_err(to_io_error)?;
+            return Ok(passphrase);
+        }
+
+        match self.entry.get_password() {
+            Ok(passphrase) => Ok(Zeroizing::new(passphrase)),
+            Err(keyring::Error::NoEntry) => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("the passphrase of keystore {id} is missing from the credential store"),
+            )),
+            Err(e) => Err(to_io_error(e)),
+        }
+    }
+}
+
+impl std::fmt::Debug for OsCredentialStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OsCredentialStore").finish_non_exhaustive()
+    }
+}
+
+/// Convert a credential store error into an [`io::Error`].
+fn to_io_error(e: keyring::Error) -> io::Error {
+    let kind = match e {
+        keyring::Error::NoStorageAccess(_) => io::ErrorKind::PermissionDenied,
+        keyring::Error::NoEntry => io::ErrorKind::NotFound,
+        _ => io::ErrorKind::Other,
+    };
+    io::Error::new(kind, e)
+}
+
+#[cfg(test)]
+mod tests {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::super::tests::{init_keystore, keypair, reopen};
+    use super::super::ConfiguredPassphrase;
+    use super::*;
+    use crate::test_utils::TestSpecifier;
+    use crate::{Keystore, OpContext};
+    use tor_key_forge::KeyType;
+    use tor_llcrypto::pk::ed25519;
+
+    /// Return an in-memory credential.
+    fn mock_credential() -> Box<keyring::credential::Credential> {
+        keyring::mock::default_credential_builder()
+            .build(None, SERVICE, "test")
+            .unwrap()
+    }
+
+    /// Return an [`OsCredentialStore`] backed by an in-memory credential.
+    fn mock_store() -> OsCredentialStore {
+        OsCredentialStore::with_entry(Entry::new_with_credential(mock_credential())).unwrap()
+    }
+
+    #[test]
+    fn unlock() {
+        let (keystore, dir) = init_keystore();
+        let creds = mock_store();
+
+        // The first unlock generates the passphrase...
+        keystore.unlock_with(&creds).unwrap();
+        let passphrase = creds.entry.get_password().unwrap();
+        assert_eq!(BASE64.decode(passphrase.as_bytes()).unwrap().len(), 32);
+        let key_spec = TestSpecifier::default();
+        let key_type = KeyType::Ed25519Keypair;
+        let key = keypair();
+        keystore
+            .insert(&key, &key_spec, &key_type, &OpContext::default())
+            .unwrap();
+
+        // ...which unlocks the key store from then on.
+        let keystore = reopen(&dir);
+        keystore.unlock_with(&creds).unwrap();
+        assert_eq!(creds.entry.get_password().unwrap(), passphrase);
+        let erased_kp = keystore
+            .get(&key_spec, &key_type, &OpContext::default())
+            .unwrap()
+            .unwrap();
+        let Ok(found) = erased_kp.downcast::<ed25519::Keypair>() else {
+            panic!("failed to downcast key to ed25519::Keypair")
+        };
+        assert_eq!(found.verifying_key(), key.verifying_key());
+
+        // A key store whose passphrase was lost can't be unlocked.
+        let keystore = reopen(&dir);
+        let err = keystore.unlock_with(&mock_store()).unwrap_err();
+        assert!(err.to_string().contains("passphrase"), "{err}");
+        assert!(keystore.is_locked());
+    }
+
+    #[test]
+    fn unavailable() {
+        // On a system without a D-Bus session, the Secret Service can't be reached.
+        let credential = mock_credential();
+        let mock: &keyring::mock::MockCredential = credential.as_any().downcast_ref().unwrap();
+        mock.set_error(keyring::Error::PlatformFailure(
+            "no D-Bus session bus".into(),
+        ));
+        let err =
+            OsCredentialStore::with_entry(Entry::new_with_credential(credential)).unwrap_err();
+        assert!(err.to_string().contains("D-Bus"), "{err}");
+
+        // The key store can still be unlocked with another prompt.
+        let (keystore, _dir) = init_keystore();
+        let configured = ConfiguredPassphrase(Zeroizing::new("correct horse".into()));
+        keystore.unlock_with(&configured).unwrap();
+        assert!(!keystore.is_locked());
+    }
+}
@@ -53,6 +53,26 @@ pub(crate) enum ArtiNativeKeystoreError {
         found_key_algo: SshKeyAlgorithm,
     },
 
+    /// The keystore uses a newer layout than we support.
+    #[error(
+        "Keystore format version {0} is not supported (newest supported version is {})",
+        super::migrate::CURRENT_VERSION
+    )]
+    UnsupportedVersion(u32),
+
+    /// The keystore version marker could not be parsed.
+    #[error("Malformed keystore version marker {0:?}")]
+    MalformedVersion(String),
+
+    /// The expiration time recorded for an entry could not be parsed.
+    #[error("Malformed expiration time {value:?} in {path}")]
+    MalformedExpiry {
+        /// The path of the file recording the expiration time.
+        path: PathBuf,
+        /// The contents of that file.
+        value: String,
+    },
+
     /// An internal error.
     #[error("Internal error")]
     Bug(#[from] tor_error::Bug),
@@ -92,6 +112,9 @@ impl HasKind for ArtiNativeKeystoreError {
             KE::SshKeyParse { .. } | KE::UnexpectedSshKeyType { .. } => {
                 ErrorKind::KeystoreCorrupted
             }
+            KE::UnsupportedVersion(_) => ErrorKind::KeystoreAccessFailed,
+            KE::MalformedVersion(_) => ErrorKind::KeystoreCorrupted,
+            KE::MalformedExpiry { .. } => ErrorKind::KeystoreCorrupted,
             KE::Bug(e) => e.kind(),
         }
     }
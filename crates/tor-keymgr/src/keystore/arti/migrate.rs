@@ -0,0 +1,853 @@
+//! Versioning and migration of the on-disk layout of the Arti keystore.
+//!
+//! The version of the layout used by a keystore is recorded in its [`VERSION_DIR`],
+//! as an empty directory named after the version.
+//! (Older versions of Arti refuse to list a keystore containing any file that isn't a key,
+//! but they skip directories, so this marker doesn't stop them from reading the keystore.)
+//! Keystores without a version marker are version 0.
+//!
+//! When a keystore is opened, it is upgraded to [`CURRENT_VERSION`]
+//! by applying each of the registered [`Migration`]s in turn.
+//! A keystore that is already up to date is not modified:
+//! in particular, the version marker is only written when a keystore is upgraded.
+//! Before a migration is applied, the files it modifies are copied to
+//! `BACKUP_DIR/v<old version>/`.
+//! If the migration fails partway through,
+//! the changes it made so far are undone using that backup,
+//! and the version marker is left untouched
+//! (as is the backup, in case the rollback failed too).
+//! The version marker is only updated once all the changes
+//! of the migration have been applied,
+//! after which the backup is removed.
+//! Migrations that don't modify any files (such as the one that adds the version marker)
+//! make no backup at all,
+//! so that we don't leave stray copies of the secret keys on disk.
+//!
+//! A keystore whose version is newer than [`CURRENT_VERSION`]
+//! was written by a newer version of Arti, and is refused.
+//!
+//! [`plan_upgrade`] computes the changes an upgrade would make,
+//! without making them.
+
+use std::path::{Path, PathBuf};
+
+use fs_mistrust::CheckedDir;
+use itertools::Itertools as _;
+use tor_basic_utils::PathExt as _;
+use tracing::{debug, info, warn};
+use walkdir::WalkDir;
+
+use crate::keystore::arti::err::ArtiNativeKeystoreError;
+use crate::keystore::fs_utils::{FilesystemAction, FilesystemError};
+
+/// The name of the directory recording the version of the keystore layout.
+///
+/// The version is the name of the (empty) directory it contains.
+pub(super) const VERSION_DIR: &str = ".arti_keystore_version";
+
+/// The name of the directory containing backups made before migrations.
+pub(super) const BACKUP_DIR: &str = ".arti_keystore_backup";
+
+/// The name of the file recording the passphrase parameters of an encrypted keystore.
+///
+/// Only used by `ArtiEncryptedKeystore`.
+pub(super) const ENCRYPTION_FILE: &str = ".arti_keystore_encryption";
+
+/// The name of the directory containing the lock files of the keystore entries.
+///
+/// See `Keystore::lock_entry`.
+pub(super) const LOCK_DIR: &str = ".arti_keystore_locks";
+
+/// The name of the directory recording the expiration times of the keystore entries.
+///
+/// See `Keystore::set_expiry`.
+pub(super) const EXPIRY_DIR: &str = ".arti_keystore_expiry";
+
+/// The current version of the keystore layout.
+pub(crate) const CURRENT_VERSION: u32 = 0;
+
+/// A single change to the on-disk layout of the keystore.
+///
+/// All paths are relative to the root of the keystore.
+#[derive(Clone, Debug)]
+pub(super) enum MigrationOp {
+    /// Move the file at `from` to `to`.
+    #[allow(dead_code)] // Not yet used by any of our migrations
+    Rename {
+        /// The old location of the file.
+        from: PathBuf,
+        /// The new location of the file.
+        to: PathBuf,
+    },
+    /// Create the file at `path`, or replace its contents.
+    #[allow(dead_code)] // Not yet used by any of our migrations
+    Write {
+        /// The location of the file.
+        path: PathBuf,
+        /// The new contents of the file.
+        contents: Vec<u8>,
+    },
+}
+
+/// An upgrade of the keystore layout from one version to the next.
+pub(super) trait Migration: Send + Sync {
+    /// The version this migration upgrades from.
+    ///
+    /// The migration upgrades the keystore to `source_version() + 1`.
+    fn source_version(&self) -> u32;
+
+    /// Return the changes needed to upgrade a keystore containing `files`.
+    ///
+    /// `files` are the paths of all the files in the keystore,
+    /// relative to its root.
+    fn plan(&self, files: &[PathBuf]) -> Vec<MigrationOp>;
+}
+
+/// The registered migrations, ordered by [`Migration::source_version`].
+///
+/// There is one migration for each version before [`CURRENT_VERSION`].
+static MIGRATIONS: &[&dyn Migration] = &[];
+
+/// The changes that upgrading a keystore to the latest layout would make.
+///
+/// Returned by [`ArtiNativeKeystore::plan_upgrade`](crate::ArtiNativeKeystore::plan_upgrade).
+#[derive(Clone, Debug, amplify::Getters)]
+pub struct KeystoreUpgradePlan {
+    /// The version of the keystore layout.
+    from_version: u32,
+    /// The version the keystore would be upgraded to.
+    to_version: u32,
+    /// The changes the upgrade would make to the keys in the keystore, in order.
+    ///
+    /// This doesn't include the backups made before each migration,
+    /// or the updates to the version marker.
+    steps: Vec<KeystoreMigrationStep>,
+}
+
+impl KeystoreUpgradePlan {
+    /// Return true if the keystore is already up to date.
+    pub fn is_up_to_date(&self) -> bool {
+        self.from_version == self.to_version
+    }
+}
+
+/// A single change an upgrade would make to the keystore.
+///
+/// All paths are relative to the root of the keystore.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum KeystoreMigrationStep {
+    /// Move the file at `from` to `to`.
+    Rename {
+        /// The old location of the file.
+        from: PathBuf,
+        /// The new location of the file.
+        to: PathBuf,
+    },
+    /// Create the file at `path`, or replace its contents.
+    Write {
+        /// The location of the file.
+        path: PathBuf,
+    },
+}
+
+impl From<&MigrationOp> for KeystoreMigrationStep {
+    fn from(op: &MigrationOp) -> Self {
+        match op {
+            MigrationOp::Rename { from, to } => KeystoreMigrationStep::Rename {
+                from: from.clone(),
+                to: to.clone(),
+            },
+            MigrationOp::Write { path, .. } => KeystoreMigrationStep::Write { path: path.clone() },
+        }
+    }
+}
+
+/// Return true if `name` is the name of one of the non-key files
+/// we keep at the root of the keystore.
+pub(super) fn is_reserved_name(name: &std::ffi::OsStr) -> bool {
+    name == VERSION_DIR
+        || name == BACKUP_DIR
+        || name == ENCRYPTION_FILE
+        || name == LOCK_DIR
+        || name == EXPIRY_DIR
+}
+
+/// Return the version of the keystore rooted at `dir`.
+pub(super) fn read_version(dir: &CheckedDir) -> Result<u32, ArtiNativeKeystoreError> {
+    let entries = match dir.read_directory(VERSION_DIR) {
+        Ok(entries) => entries,
+        Err(fs_mistrust::Error::NotFound(_)) => return Ok(0),
+        Err(e) => return Err(fs_err(FilesystemAction::Read, VERSION_DIR, e)),
+    };
+
+    // If we were interrupted while updating the marker,
+    // there may be more than one version here:
+    // the newest one is only added once the upgrade to it is complete.
+    let mut version = None;
+    for entry in entries {
+        let entry = entry.map_err(|e| {
+            ArtiNativeKeystoreError::Filesystem(FilesystemError::Io {
+                action: FilesystemAction::Read,
+                path: VERSION_DIR.into(),
+                err: e.into(),
+            })
+        })?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let v = name
+            .parse()
+            .map_err(|_| ArtiNativeKeystoreError::MalformedVersion(name))?;
+        version = version.max(Some(v));
+    }
+
+    Ok(version.unwrap_or(0))
+}
+
+/// Upgrade the keystore rooted at `dir` to [`CURRENT_VERSION`].
+///
+/// Returns the resulting version.
+pub(super) fn upgrade(dir: &CheckedDir) -> Result<u32, ArtiNativeKeystoreError> {
+    upgrade_with(dir, MIGRATIONS, CURRENT_VERSION)
+}
+
+/// Return the changes [`upgrade`] would make to the keystore rooted at `dir`,
+/// without making them.
+pub(super) fn plan_upgrade(
+    dir: &CheckedDir,
+) -> Result<KeystoreUpgradePlan, ArtiNativeKeystoreError> {
+    plan_upgrade_with(dir, MIGRATIONS, CURRENT_VERSION)
+}
+
+/// Return the changes [`upgrade_with`] would make to the keystore rooted at `dir`,
+/// without making them.
+fn plan_upgrade_with(
+    dir: &CheckedDir,
+    migrations: &[&dyn Migration],
+    to_version: u32,
+) -> Result<KeystoreUpgradePlan, ArtiNativeKeystoreError> {
+    let from_version = read_version(dir)?;
+
+    if from_version > to_version {
+        return Err(ArtiNativeKeystoreError::UnsupportedVersion(from_version));
+    }
+
+    let mut files = list_files(dir)?;
+    let mut steps = vec![];
+    // As in upgrade_with, an empty keystore is upgraded without running the migrations.
+    if !files.is_empty() {
+        for version in from_version..to_version {
+            let ops = find_migration(migrations, version)?.plan(&files);
+            // Each migration is planned based on the files
+            // left by the previous ones.
+            for op in &ops {
+                simulate_op(&mut files, op);
+            }
+            steps.extend(ops.iter().map(KeystoreMigrationStep::from));
+        }
+    }
+
+    Ok(KeystoreUpgradePlan {
+        from_version,
+        to_version,
+        steps,
+    })
+}
+
+/// Update the list of `files` in the keystore as if `op` had been applied.
+fn simulate_op(files: &mut Vec<PathBuf>, op: &MigrationOp) {
+    match op {
+        MigrationOp::Rename { from, to } => {
+            files.retain(|f| f != from && f != to);
+            files.push(to.clone());
+        }
+        MigrationOp::Write { path, .. } => {
+            if !files.contains(path) {
+                files.push(path.clone());
+            }
+        }
+    }
+}
+
+/// Return the migration from `version` to the next version.
+fn find_migration<'m>(
+    migrations: &[&'m dyn Migration],
+    version: u32,
+) -> Result<&'m dyn Migration, ArtiNativeKeystoreError> {
+    migrations
+        .iter()
+        .find(|m| m.source_version() == version)
+        .copied()
+        .ok_or_else(|| {
+            tor_error::internal!("no keystore migration from version {version}?!").into()
+        })
+}
+
+/// Upgrade the keystore rooted at `dir` to `to_version`, using `migrations`.
+fn upgrade_with(
+    dir: &CheckedDir,
+    migrations: &[&dyn Migration],
+    to_version: u32,
+) -> Result<u32, ArtiNativeKeystoreError> {
+    let mut version = read_version(dir)?;
+
+    if version > to_version {
+        return Err(ArtiNativeKeystoreError::UnsupportedVersion(version));
+    }
+
+    if version == to_version {
+        return Ok(version);
+    }
+
+    let files = list_files(dir)?;
+    if files.is_empty() {
+        // There is nothing to migrate.
+        write_version(dir, to_version)?;
+        return Ok(to_version);
+    }
+
+    while version < to_version {
+        let migration = find_migration(migrations, version)?;
+
+        let files = list_files(dir)?;
+        info!(
+            "Upgrading keystore at {} from version {} to {}",
+            dir.as_path().display_lossy(),
+            version,
+            version + 1
+        );
+        apply(dir, version, &files, &migration.plan(&files))?;
+        version += 1;
+        write_version(dir, version)?;
+        remove_backup(dir, version - 1);
+    }
+
+    Ok(version)
+}
+
+/// Return the directory holding the backup made before migrating from `from_version`,
+/// relative to the root of the keystore.
+fn backup_dir(from_version: u32) -> PathBuf {
+    Path::new(BACKUP_DIR).join(format!("v{from_version}"))
+}
+
+/// Back up the `files` that `ops` modify, then apply `ops`.
+///
+/// If any of the `ops` fails, undo the ones that were already applied.
+fn apply(
+    dir: &CheckedDir,
+    from_version: u32,
+    files: &[PathBuf],
+    ops: &[MigrationOp],
+) -> Result<(), ArtiNativeKeystoreError> {
+    let backup = backup_dir(from_version);
+    let touched = ops
+        .iter()
+        .flat_map(|op| match op {
+            MigrationOp::Rename { from, to } => vec![from, to],
+            MigrationOp::Write { path, .. } => vec![path],
+        })
+        .filter(|path| files.contains(path))
+        .unique();
+    for f in touched {
+        copy(dir, f, &backup.join(f))?;
+    }
+
+    for (n, op) in ops.iter().enumerate() {
+        debug!("Applying keystore migration step {:?}", op);
+        if let Err(e) = apply_op(dir, op) {
+            warn!("Keystore migration failed; rolling back");
+            for op in ops[..n].iter().rev() {
+                if let Err(e) = undo_op(dir, &backup, op) {
+                    warn!(
+                        "Failed to roll back keystore migration step {:?}: {}; backup is in {}",
+                        op,
+                        e,
+                        backup.display_lossy()
+                    );
+                }
+            }
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove the backup made before migrating from `from_version`, if there is one.
+///
+/// Failing to remove the backup doesn't make the migration fail,
+/// so this only logs a warning.
+fn remove_backup(dir: &CheckedDir, from_version: u32) {
+    let backup = backup_dir(from_version);
+    let remove = || -> Result<(), ArtiNativeKeystoreError> {
+        let abs_backup = dir
+            .join(&backup)
+            .map_err(|e| fs_err(FilesystemAction::Remove, &backup, e))?;
+        match std::fs::remove_dir_all(abs_backup) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => {
+                return Err(ArtiNativeKeystoreError::Filesystem(FilesystemError::Io {
+                    action: FilesystemAction::Remove,
+                    path: backup.clone(),
+                    err: std::sync::Arc::new(e),
+                }))
+            }
+        }
+        // Remove the backup directory itself, unless it contains other backups
+        // (left behind by earlier migrations that failed).
+        if let Ok(abs_root) = dir.join(BACKUP_DIR) {
+            let _ = std::fs::remove_dir(abs_root);
+        }
+        Ok(())
+    };
+
+    if let Err(e) = remove() {
+        warn!(
+            "Failed to remove keystore backup {}: {}",
+            backup.display_lossy(),
+            e
+        );
+    }
+}
+
+/// Apply a single migration step.
+fn apply_op(dir: &CheckedDir, op: &MigrationOp) -> Result<(), ArtiNativeKeystoreError> {
+    match op {
+        MigrationOp::Rename { from, to } => rename(dir, from, to),
+        MigrationOp::Write { path, contents } => write(dir, path, contents),
+    }
+}
+
+/// Undo a single migration step, using the files in `backup`.
+fn undo_op(
+    dir: &CheckedDir,
+    backup: &Path,
+    op: &MigrationOp,
+) -> Result<(), ArtiNativeKeystoreError> {
+    match op {
+        MigrationOp::Rename { from, to } => rename(dir, to, from),
+        MigrationOp::Write { path, .. } => match dir.read(backup.join(path)) {
+            Ok(old) => write(dir, path, old),
+            Err(fs_mistrust::Error::NotFound(_)) => dir
+                .remove_file(path)
+                .map_err(|e| fs_err(FilesystemAction::Remove, path, e)),
+            Err(e) => Err(fs_err(FilesystemAction::Read, path, e)),
+        },
+    }
+}
+
+/// Write the version marker.
+fn write_version(dir: &CheckedDir, version: u32) -> Result<(), ArtiNativeKeystoreError> {
+    let name = version.to_string();
+    let marker = Path::new(VERSION_DIR).join(&name);
+    dir.make_directory(&marker)
+        .map_err(|e| fs_err(FilesystemAction::Write, &marker, e))?;
+
+    // Only remove the old markers once the new one is in place,
+    // so that the keystore never appears to be at version 0.
+    let abs_version_dir = dir
+        .join(VERSION_DIR)
+        .map_err(|e| fs_err(FilesystemAction::Remove, VERSION_DIR, e))?;
+    let io_err = |e| {
+        ArtiNativeKeystoreError::Filesystem(FilesystemError::Io {
+            action: FilesystemAction::Remove,
+            path: VERSION_DIR.into(),
+            err: std::sync::Arc::new(e),
+        })
+    };
+    for entry in std::fs::read_dir(&abs_version_dir).map_err(io_err)? {
+        let entry = entry.map_err(io_err)?;
+        if entry.file_name() != name.as_str() {
+            std::fs::remove_dir(entry.path()).map_err(io_err)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Copy the file at `from` to `to`.
+fn copy(dir: &CheckedDir, from: &Path, to: &Path) -> Result<(), ArtiNativeKeystoreError> {
+    let contents = dir
+        .read(from)
+        .map_err(|e| fs_err(FilesystemAction::Read, from, e))?;
+    write(dir, to, contents)
+}
+
+/// Move the file at `from` to `to`, creating the parent directories of `to` as needed.
+fn rename(dir: &CheckedDir, from: &Path, to: &Path) -> Result<(), ArtiNativeKeystoreError> {
+    make_parent(dir, to)?;
+    let io_err = |path: &Path, e| {
+        ArtiNativeKeystoreError::Filesystem(FilesystemError::Io {
+            action: FilesystemAction::Write,
+            path: path.into(),
+            err: std::sync::Arc::new(e),
+        })
+    };
+    let abs_from = dir
+        .join(from)
+        .map_err(|e| fs_err(FilesystemAction::Read, from, e))?;
+    let abs_to = dir
+        .join(to)
+        .map_err(|e| fs_err(FilesystemAction::Write, to, e))?;
+    std::fs::rename(abs_from, abs_to).map_err(|e| io_err(from, e))
+}
+
+/// Write `contents` to the file at `path`, creating its parent directories as needed.
+fn write(
+    dir: &CheckedDir,
+    path: impl AsRef<Path>,
+    contents: impl AsRef<[u8]>,
+) -> Result<(), ArtiNativeKeystoreError> {
+    let path = path.as_ref();
+    make_parent(dir, path)?;
+    dir.write_and_replace(path, contents)
+        .map_err(|e| fs_err(FilesystemAction::Write, path, e))
+}
+
+/// Create the parent directories of `path`, if needed.
+fn make_parent(dir: &CheckedDir, path: &Path) -> Result<(), ArtiNativeKeystoreError> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => dir
+            .make_directory(parent)
+            .map_err(|e| fs_err(FilesystemAction::Write, parent, e)),
+        _ => Ok(()),
+    }
+}
+
+/// Return the paths of all the files in the keystore rooted at `dir`,
+/// relative to `dir`.
+///
+/// The version marker and the backup directory are not included.
+pub(super) fn list_files(dir: &CheckedDir) -> Result<Vec<PathBuf>, ArtiNativeKeystoreError> {
+    let root = dir.as_path();
+    WalkDir::new(root)
+        .into_iter()
+        .filter_entry(|e| !(e.depth() == 1 && is_reserved_name(e.file_name())))
+        .filter(|e| !e.as_ref().is_ok_and(|e| e.file_type().is_dir()))
+        .map(|e| {
+            let e = e.map_err(|e| {
+                let msg = e.to_string();
+                ArtiNativeKeystoreError::Filesystem(FilesystemError::Io {
+                    action: FilesystemAction::Read,
+                    path: root.into(),
+                    err: e
+                        .into_io_error()
+                        .unwrap_or_else(|| std::io::Error::other(msg))
+                        .into(),
+                })
+            })?;
+            e.path()
+                .strip_prefix(root)
+                .map(Path::to_path_buf)
+                .map_err(|_| tor_error::internal!("found file outside of keystore_dir?!").into())
+        })
+        .collect()
+}
+
+/// Build a [`FilesystemError::FsMistrust`] for `path`.
+fn fs_err(
+    action: FilesystemAction,
+    path: impl AsRef<Path>,
+    err: fs_mistrust::Error,
+) -> ArtiNativeKeystoreError {
+    ArtiNativeKeystoreError::Filesystem(FilesystemError::FsMistrust {
+        action,
+        path: path.as_ref().into(),
+        err: err.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use fs_mistrust::Mistrust;
+    use std::fs;
+    use tempfile::{tempdir, TempDir};
+
+    #[cfg(unix)]
+    use std::os::unix::fs::PermissionsExt;
+
+    fn checked_dir() -> (CheckedDir, TempDir) {
+        let tmp = tempdir().unwrap();
+        #[cfg(unix)]
+        fs::set_permissions(&tmp, fs::Permissions::from_mode(0o700)).unwrap();
+        let dir = Mistrust::default()
+            .verifier()
+            .check_content()
+            .make_secure_dir(&tmp)
+            .unwrap();
+        (dir, tmp)
+    }
+
+    /// A migration that renames every `.old` file to `.new`.
+    struct RenameOldToNew;
+
+    impl Migration for RenameOldToNew {
+        fn source_version(&self) -> u32 {
+            0
+        }
+
+        fn plan(&self, files: &[PathBuf]) -> Vec<MigrationOp> {
+            files
+                .iter()
+                .filter(|f| f.extension().is_some_and(|e| e == "old"))
+                .map(|f| MigrationOp::Rename {
+                    from: f.clone(),
+                    to: f.with_extension("new"),
+                })
+                .collect()
+        }
+    }
+
+    /// A migration that writes an index of the files in the keystore.
+    struct WriteIndex;
+
+    impl Migration for WriteIndex {
+        fn source_version(&self) -> u32 {
+            1
+        }
+
+        fn plan(&self, files: &[PathBuf]) -> Vec<MigrationOp> {
+            let index = files
+                .iter()
+                .map(|f| format!("{}\n", f.display_lossy()))
+                .sorted()
+                .collect::<String>();
+            vec![MigrationOp::Write {
+                path: "index".into(),
+                contents: index.into_bytes(),
+            }]
+        }
+    }
+
+    /// A migration that renames a file, then fails.
+    struct RenameThenFail;
+
+    impl Migration for RenameThenFail {
+        fn source_version(&self) -> u32 {
+            0
+        }
+
+        fn plan(&self, files: &[PathBuf]) -> Vec<MigrationOp> {
+            let mut ops = RenameOldToNew.plan(files);
+            ops.push(MigrationOp::Rename {
+                from: "does-not-exist".into(),
+                to: "whatever".into(),
+            });
+            ops
+        }
+    }
+
+    /// Return the paths of all the files under `dir`, including the reserved ones.
+    fn all_files(dir: &CheckedDir) -> Vec<PathBuf> {
+        WalkDir::new(dir.as_path())
+            .into_iter()
+            .map(|e| e.unwrap())
+            .filter(|e| !e.file_type().is_dir())
+            .map(|e| e.path().strip_prefix(dir.as_path()).unwrap().to_path_buf())
+            .sorted()
+            .collect()
+    }
+
+    #[test]
+    fn fresh_keystore() {
+        let (dir, _tmp) = checked_dir();
+        assert_eq!(read_version(&dir).unwrap(), 0);
+        assert_eq!(upgrade(&dir).unwrap(), CURRENT_VERSION);
+        assert_eq!(read_version(&dir).unwrap(), CURRENT_VERSION);
+
+        // A fresh keystore is upgraded without running the migrations.
+        let (dir, _tmp) = checked_dir();
+        assert_eq!(upgrade_with(&dir, &[], 3).unwrap(), 3);
+        assert_eq!(read_version(&dir).unwrap(), 3);
+        // The marker isn't listed as a file of the keystore
+        assert!(list_files(&dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn up_to_date() {
+        let (dir, _tmp) = checked_dir();
+        write(&dir, "a/key.private", "secret").unwrap();
+
+        // Opening a keystore that doesn't need upgrading doesn't write to it.
+        assert_eq!(upgrade(&dir).unwrap(), CURRENT_VERSION);
+        assert_eq!(all_files(&dir), vec![PathBuf::from("a/key.private")]);
+        assert!(!dir.as_path().join(VERSION_DIR).try_exists().unwrap());
+        assert!(!dir.as_path().join(BACKUP_DIR).try_exists().unwrap());
+    }
+
+    #[test]
+    fn version_marker() {
+        let (dir, _tmp) = checked_dir();
+        write_version(&dir, 1).unwrap();
+        assert_eq!(read_version(&dir).unwrap(), 1);
+        write_version(&dir, 2).unwrap();
+        assert_eq!(read_version(&dir).unwrap(), 2);
+
+        // The marker contains no files, so older versions of Arti,
+        // which skip directories, can still list the keystore.
+        assert!(all_files(&dir).is_empty());
+
+        // If we were interrupted before removing the old marker,
+        // the newest version wins.
+        dir.make_directory(Path::new(VERSION_DIR).join("1"))
+            .unwrap();
+        assert_eq!(read_version(&dir).unwrap(), 2);
+    }
+
+    #[test]
+    fn newer_version() {
+        let (dir, _tmp) = checked_dir();
+        write_version(&dir, CURRENT_VERSION + 1).unwrap();
+        assert!(matches!(
+            upgrade(&dir),
+            Err(ArtiNativeKeystoreError::UnsupportedVersion(v)) if v == CURRENT_VERSION + 1
+        ));
+
+        dir.make_directory(Path::new(VERSION_DIR).join("not-a-number"))
+            .unwrap();
+        assert!(matches!(
+            upgrade(&dir),
+            Err(ArtiNativeKeystoreError::MalformedVersion(_))
+        ));
+    }
+
+    #[test]
+    fn migrate_with_backup() {
+        let (dir, _tmp) = checked_dir();
+        write(&dir, "a/key.old", "hello").unwrap();
+
+        write(&dir, "b/untouched.key", "world").unwrap();
+
+        // Stop right after the backup is made, to check what it contains.
+        let files = list_files(&dir).unwrap();
+        apply(&dir, 0, &files, &RenameOldToNew.plan(&files)).unwrap();
+        assert_eq!(
+            dir.read_to_string(Path::new(BACKUP_DIR).join("v0/a/key.old"))
+                .unwrap(),
+            "hello"
+        );
+        // Only the files the migration modifies are backed up.
+        assert!(dir
+            .read(Path::new(BACKUP_DIR).join("v0/b/untouched.key"))
+            .is_err());
+
+        // Once the migration is complete, the backup is removed.
+        let (dir, _tmp) = checked_dir();
+        write(&dir, "a/key.old", "hello").unwrap();
+        assert_eq!(upgrade_with(&dir, &[&RenameOldToNew], 1).unwrap(), 1);
+        assert_eq!(dir.read_to_string("a/key.new").unwrap(), "hello");
+        assert!(dir.read("a/key.old").is_err());
+        assert!(!dir.as_path().join(BACKUP_DIR).try_exists().unwrap());
+        assert_eq!(list_files(&dir).unwrap(), vec![PathBuf::from("a/key.new")]);
+    }
+
+    #[test]
+    fn dry_run() {
+        let (dir, _tmp) = checked_dir();
+        let plan = plan_upgrade(&dir).unwrap();
+        assert_eq!((plan.from_version, plan.to_version), (0, CURRENT_VERSION));
+        assert!(plan.is_up_to_date());
+        assert!(plan.steps().is_empty());
+
+        let migrations: &[&dyn Migration] = &[&RenameOldToNew, &WriteIndex];
+        write(&dir, "a/key.old", "hello").unwrap();
+        write(&dir, "b/key.new", "world").unwrap();
+        let plan = plan_upgrade_with(&dir, migrations, 2).unwrap();
+        assert_eq!((plan.from_version, plan.to_version), (0, 2));
+        assert!(!plan.is_up_to_date());
+        // The second migration is planned based on the files left by the first.
+        assert_eq!(
+            plan.steps(),
+            &[
+                KeystoreMigrationStep::Rename {
+                    from: "a/key.old".into(),
+                    to: "a/key.new".into(),
+                },
+                KeystoreMigrationStep::Write {
+                    path: "index".into(),
+                },
+            ]
+        );
+
+        // Nothing was changed.
+        assert_eq!(read_version(&dir).unwrap(), 0);
+        assert_eq!(
+            all_files(&dir),
+            vec![PathBuf::from("a/key.old"), PathBuf::from("b/key.new")]
+        );
+
+        // The upgrade makes the changes we planned.
+        assert_eq!(upgrade_with(&dir, migrations, 2).unwrap(), 2);
+        assert_eq!(read_version(&dir).unwrap(), 2);
+        assert_eq!(dir.read_to_string("a/key.new").unwrap(), "hello");
+        assert_eq!(
+            dir.read_to_string("index").unwrap(),
+            "a/key.new\nb/key.new\n"
+        );
+        assert_eq!(
+            list_files(&dir).unwrap().into_iter().sorted().collect_vec(),
+            vec![
+                PathBuf::from("a/key.new"),
+                PathBuf::from("b/key.new"),
+                PathBuf::from("index")
+            ]
+        );
+        assert!(plan_upgrade_with(&dir, migrations, 2)
+            .unwrap()
+            .is_up_to_date());
+
+        // Upgrading from version 1 only runs the second migration.
+        let (dir, _tmp) = checked_dir();
+        write(&dir, "a/key.old", "hello").unwrap();
+        write_version(&dir, 1).unwrap();
+        let plan = plan_upgrade_with(&dir, migrations, 2).unwrap();
+        assert_eq!(
+            plan.steps(),
+            &[KeystoreMigrationStep::Write {
+                path: "index".into(),
+            }]
+        );
+        assert_eq!(upgrade_with(&dir, migrations, 2).unwrap(), 2);
+        assert_eq!(dir.read_to_string("a/key.old").unwrap(), "hello");
+        assert_eq!(dir.read_to_string("index").unwrap(), "a/key.old\n");
+
+        write_version(&dir, 3).unwrap();
+        assert!(matches!(
+            plan_upgrade_with(&dir, migrations, 2),
+            Err(ArtiNativeKeystoreError::UnsupportedVersion(3))
+        ));
+    }
+
+    #[test]
+    fn migrate_rollback() {
+        let (dir, _tmp) = checked_dir();
+        write(&dir, "a/key.old", "hello").unwrap();
+
+        assert!(upgrade_with(&dir, &[&RenameThenFail], 1).is_err());
+        // The rename was undone, and the version was not bumped.
+        assert_eq!(dir.read_to_string("a/key.old").unwrap(), "hello");
+        assert!(dir.read("a/key.new").is_err());
+        assert_eq!(read_version(&dir).unwrap(), 0);
+        // The backup is kept, in case the rollback had failed.
+        assert_eq!(
+            dir.read_to_string(Path::new(BACKUP_DIR).join("v0/a/key.old"))
+                .unwrap(),
+            "hello"
+        );
+    }
+}
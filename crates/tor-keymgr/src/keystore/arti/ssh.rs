@@ -4,13 +4,13 @@
 // handle such keys, we will eventually need to support them (this will be a breaking API change).
 
 use tor_error::internal;
-use tor_key_forge::{ErasedKey, KeyType, SshKeyAlgorithm, SshKeyData};
+use tor_key_forge::custom::{self, CustomKeyType};
+use tor_key_forge::{ErasedKey, KeyType, SecretBuffer, SshKeyAlgorithm, SshKeyData};
 
 use crate::keystore::arti::err::ArtiNativeKeystoreError;
 use crate::Result;
 
 use std::path::PathBuf;
-use zeroize::Zeroizing;
 
 use crate::UnknownKeyTypeError;
 
@@ -22,7 +22,7 @@ use crate::UnknownKeyTypeError;
 /// The inner value is zeroed on drop.
 pub(super) struct UnparsedOpenSshKey {
     /// The contents of an OpenSSH key file.
-    inner: Zeroizing<String>,
+    inner: SecretBuffer,
     /// The path of the file (for error reporting).
     path: PathBuf,
 }
@@ -46,7 +46,7 @@ macro_rules! parse_openssh {
     }};
 
     ($key:expr, $key_type:expr, $parse_fn:path) => {{
-        let key = $parse_fn(&*$key.inner).map_err(|e| {
+        let key = $key.inner.as_str().map_err(ssh_key::Error::from).and_then($parse_fn).map_err(|e| {
             ArtiNativeKeystoreError::SshKeyParse {
                 // TODO: rust thinks this clone is necessary because key.path is also used below (but
                 // if we get to this point, we're going to return an error and never reach the other
@@ -77,6 +77,12 @@ fn ssh_algorithm(key_type: &KeyType) -> Result<SshKeyAlgorithm> {
         KeyType::Ed25519Keypair | KeyType::Ed25519PublicKey => Ok(SshKeyAlgorithm::Ed25519),
         KeyType::X25519StaticKeypair | KeyType::X25519PublicKey => Ok(SshKeyAlgorithm::X25519),
         KeyType::Ed25519ExpandedKeypair => Ok(SshKeyAlgorithm::Ed25519Expanded),
+        KeyType::Custom { arti_extension } => {
+            let custom = custom_key_type(arti_extension)?;
+            let name = ssh_key::AlgorithmName::new(custom.algorithm_name())
+                .map_err(|_| internal!("registered custom key type has a bad algorithm name"))?;
+            Ok(SshKeyAlgorithm::from(ssh_key::Algorithm::Other(name)))
+        }
         KeyType::Unknown { arti_extension } => Err(ArtiNativeKeystoreError::UnknownKeyType(
             UnknownKeyTypeError {
                 arti_extension: arti_extension.clone(),
@@ -89,15 +95,22 @@ fn ssh_algorithm(key_type: &KeyType) -> Result<SshKeyAlgorithm> {
     }
 }
 
+/// Look up the registered custom key type with the extension `arti_extension`.
+fn custom_key_type(arti_extension: &str) -> Result<CustomKeyType> {
+    custom::registered_key_type(arti_extension).ok_or_else(|| {
+        ArtiNativeKeystoreError::UnknownKeyType(UnknownKeyTypeError {
+            arti_extension: arti_extension.into(),
+        })
+        .into()
+    })
+}
+
 impl UnparsedOpenSshKey {
     /// Create a new [`UnparsedOpenSshKey`].
     ///
     /// The contents of `inner` are erased on drop.
-    pub(crate) fn new(inner: String, path: PathBuf) -> Self {
-        Self {
-            inner: Zeroizing::new(inner),
-            path,
-        }
+    pub(crate) fn new(inner: SecretBuffer, path: PathBuf) -> Self {
+        Self { inner, path }
     }
 
     /// Parse an OpenSSH key, convert the key material into a known key type, and return the
@@ -114,6 +127,13 @@ impl UnparsedOpenSshKey {
             KeyType::Ed25519PublicKey | KeyType::X25519PublicKey => {
                 Ok(parse_openssh!(PUBLIC self, key_type).into_erased()?)
             }
+            KeyType::Custom { arti_extension } => {
+                if custom_key_type(arti_extension)?.is_keypair() {
+                    Ok(parse_openssh!(PRIVATE self, key_type).into_erased()?)
+                } else {
+                    Ok(parse_openssh!(PUBLIC self, key_type).into_erased()?)
+                }
+            }
             KeyType::Unknown { arti_extension } => Err(ArtiNativeKeystoreError::UnknownKeyType(
                 UnknownKeyTypeError {
                     arti_extension: arti_extension.clone(),
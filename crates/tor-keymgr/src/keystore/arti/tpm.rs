@@ -0,0 +1,507 @@
+//! A variant of the Arti key store that seals keys to a TPM 2.0 device.
+//!
+//! See the [`ArtiTpmKeystore`] docs for more details.
+
+pub(crate) mod err;
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use fs_mistrust::Mistrust;
+use tor_error::internal;
+use tor_key_forge::{EncodableKey, ErasedKey, KeyType, SecretBuffer};
+use zeroize::Zeroizing;
+
+use super::ssh::UnparsedOpenSshKey;
+use super::{arti_key_path, ArtiNativeKeystore};
+use crate::keystore::{EntryLock, EntryMetadata, OpContext, RawKeyData};
+use crate::{KeyPath, KeySpecifier, Keystore, KeystoreId, Result};
+use err::{ArtiTpmKeystoreError, TpmError};
+
+/// The prefix of every sealed key file.
+const ENTRY_MAGIC: &[u8] = b"arti-tpm-sealed-key 1\n";
+
+/// A TPM 2.0 device that can seal data to itself.
+///
+/// Data sealed by a device can only be unsealed by the same device
+/// (and, if the implementation binds its sealed objects to a PCR policy,
+/// only while that policy is satisfied).
+///
+/// Arti does not talk to the TPM itself:
+/// implementations of this trait are expected to use a TPM software stack
+/// such as [`tss-esapi`](https://crates.io/crates/tss-esapi),
+/// for instance by creating a sealed data object
+/// under a storage primary key derived from the owner hierarchy.
+pub trait TpmSealer: Send + Sync + 'static {
+    /// Seal `data` to this device.
+    ///
+    /// Returns an opaque blob (such as the marshalled public and private areas
+    /// of a sealed data object) that can be passed to [`unseal`](TpmSealer::unseal).
+    fn seal(&self, data: &[u8]) -> std::result::Result<Vec<u8>, TpmError>;
+
+    /// Unseal a blob returned by [`seal`](TpmSealer::seal).
+    fn unseal(&self, sealed: &[u8]) -> std::result::Result<Zeroizing<Vec<u8>>, TpmError>;
+}
+
+/// A key store whose keys are sealed to a TPM 2.0 device.
+///
+/// This is an [`ArtiNativeKeystore`] whose entries are sealed
+/// with a [`TpmSealer`] before being written to disk,
+/// so that they can only be read on the machine they were written on.
+/// It is meant for keys that should be bound to a specific machine,
+/// such as relay identity keys and onion service identity keys.
+///
+/// The names of the files in the key store,
+/// and the contents of the files used to lock entries and record their expiration times,
+/// are **not** sealed.
+/// Each sealed key is bound to the path of its file,
+/// so sealed keys cannot be swapped around without detection.
+///
+/// # Limitations
+///
+/// This key store only protects keys *at rest*.
+/// It does **not** keep private keys inside the TPM:
+/// the keys are unsealed into memory whenever they are used,
+/// exactly as with the other key stores,
+/// and signatures are made in software, not by the TPM.
+/// (Almost all of our keys are ed25519 or x25519 keys,
+/// and we don't know of any widely deployed TPMs that implement EdDSA.)
+/// Anyone who can run code as the Arti process can still extract the keys.
+///
+/// Arti does not link against a TPM software stack:
+/// the [`TpmSealer`] must be provided by the application.
+///
+/// This key store cannot read keys written by the [`ArtiNativeKeystore`]:
+/// such keys must be re-inserted into this key store to seal them.
+pub struct ArtiTpmKeystore {
+    /// The underlying key store, which holds the sealed keys.
+    inner: ArtiNativeKeystore,
+    /// The device our keys are sealed to.
+    tpm: Arc<dyn TpmSealer>,
+}
+
+impl std::fmt::Debug for ArtiTpmKeystore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArtiTpmKeystore")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Return the data to seal for the key at `key_path`, whose contents are `data`.
+///
+/// The name of the entry is sealed along with its contents,
+/// so that [`get_raw`](Keystore::get_raw) can check that the entry hasn't been moved.
+fn entry_plaintext(
+    key_path: &KeyPath,
+    key_type: &KeyType,
+    data: &[u8],
+) -> Result<Zeroizing<Vec<u8>>> {
+    let mut plaintext = Zeroizing::new(entry_name(key_path, key_type)?);
+    plaintext.push(b'\n');
+    plaintext.extend_from_slice(data);
+    Ok(plaintext)
+}
+
+/// Return the name of the entry at `key_path`, as it is recorded in its sealed contents.
+fn entry_name(key_path: &KeyPath, key_type: &KeyType) -> Result<Vec<u8>> {
+    let arti_path = key_path
+        .arti_path()
+        .map_err(|e| tor_error::bad_api_usage!("cannot seal key {key_path}: {e}"))?;
+    Ok(format!("{arti_path}.{}", key_type.arti_extension()).into_bytes())
+}
+
+impl ArtiTpmKeystore {
+    /// Create a new [`ArtiTpmKeystore`] rooted at the specified `keystore_dir` directory,
+    /// whose keys are sealed to `tpm`.
+    ///
+    /// The `keystore_dir` directory is created if it doesn't exist.
+    /// See [`ArtiNativeKeystore::from_path_and_mistrust`] for the possible errors.
+    pub fn from_path_and_mistrust(
+        keystore_dir: impl AsRef<Path>,
+        mistrust: &Mistrust,
+        tpm: Arc<dyn TpmSealer>,
+    ) -> Result<Self> {
+        Ok(Self {
+            inner: ArtiNativeKeystore::from_path_and_mistrust(keystore_dir, mistrust)?,
+            tpm,
+        })
+    }
+}
+
+impl Keystore for ArtiTpmKeystore {
+    fn id(&self) -> &KeystoreId {
+        self.inner.id()
+    }
+
+    fn contains(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<bool> {
+        self.inner.contains(key_spec, key_type, ctx)
+    }
+
+    fn get(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<ErasedKey>> {
+        let Some(key_path) = arti_key_path(key_spec)? else {
+            return Ok(None);
+        };
+        let Some(data) = self.get_raw(&key_path, key_type, ctx)? else {
+            return Ok(None);
+        };
+
+        let path = self
+            .inner
+            .rel_path(key_spec, key_type)
+            .map_err(|e| internal!("{e}"))?
+            .rel_path_unchecked()
+            .to_path_buf();
+        let data = data.into_secret();
+        if data.as_str().is_err() {
+            return Err(ArtiTpmKeystoreError::MalformedEntry(path).into());
+        }
+        UnparsedOpenSshKey::new(data, path)
+            .parse_ssh_format_erased(key_type)
+            .map(Some)
+    }
+
+    fn insert(
+        &self,
+        key: &dyn EncodableKey,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<()> {
+        let key_path = arti_key_path(key_spec)?
+            .ok_or_else(|| internal!("cannot insert key without an ArtiPath"))?;
+
+        // TODO (#1095): decide what information, if any, to put in the comment
+        let openssh_key = key.as_ssh_key_data()?.to_openssh_string("")?;
+
+        self.insert_raw(
+            &RawKeyData::from(SecretBuffer::from(openssh_key)),
+            &key_path,
+            key_type,
+            ctx,
+        )
+    }
+
+    fn remove(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<()>> {
+        self.inner.remove(key_spec, key_type, ctx)
+    }
+
+    fn list(&self, ctx: &OpContext) -> Result<Vec<(KeyPath, KeyType)>> {
+        self.inner.list(ctx)
+    }
+
+    fn get_raw(
+        &self,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<RawKeyData>> {
+        let Some(data) = self.inner.get_raw(key_path, key_type, ctx)? else {
+            return Ok(None);
+        };
+
+        let malformed = || ArtiTpmKeystoreError::MalformedEntry(key_path.to_string().into());
+        let sealed = data
+            .as_bytes()
+            .strip_prefix(ENTRY_MAGIC)
+            .ok_or_else(malformed)?;
+        let plaintext = self
+            .tpm
+            .unseal(sealed)
+            .map_err(|err| ArtiTpmKeystoreError::Tpm {
+                action: "unseal",
+                err,
+            })?;
+
+        let mut name = entry_name(key_path, key_type)?;
+        name.push(b'\n');
+        let contents = plaintext.strip_prefix(&name[..]).ok_or_else(malformed)?;
+
+        Ok(Some(RawKeyData::from(SecretBuffer::new(contents.to_vec()))))
+    }
+
+    fn insert_raw(
+        &self,
+        data: &RawKeyData,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<()> {
+        let plaintext = entry_plaintext(key_path, key_type, data.as_bytes())?;
+        let sealed = self
+            .tpm
+            .seal(&plaintext)
+            .map_err(|err| ArtiTpmKeystoreError::Tpm {
+                action: "seal",
+                err,
+            })?;
+
+        let mut contents = ENTRY_MAGIC.to_vec();
+        contents.extend(sealed);
+        self.inner
+            .insert_raw(&RawKeyData::new(contents), key_path, key_type, ctx)
+    }
+
+    fn created(
+        &self,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<SystemTime>> {
+        // File metadata isn't sealed, so we can report it without the TPM.
+        self.inner.created(key_path, key_type, ctx)
+    }
+
+    fn metadata(
+        &self,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<EntryMetadata>> {
+        // Likewise, the metadata is that of the (sealed) file.
+        self.inner.metadata(key_path, key_type, ctx)
+    }
+
+    fn expires(
+        &self,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<SystemTime>> {
+        // Expiration times are not secret, so they aren't sealed.
+        self.inner.expires(key_path, key_type, ctx)
+    }
+
+    fn set_expiry(
+        &self,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        expires: Option<SystemTime>,
+        ctx: &OpContext,
+    ) -> Result<Option<()>> {
+        self.inner.set_expiry(key_path, key_type, expires, ctx)
+    }
+
+    fn dir(&self) -> Option<&Path> {
+        self.inner.dir()
+    }
+
+    fn lock_entry(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<EntryLock>> {
+        self.inner.lock_entry(key_spec, key_type, ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use crate::test_utils::TestSpecifier;
+    use std::fs;
+    use tempfile::{tempdir, TempDir};
+    use tor_error::{ErrorKind, HasKind};
+    use tor_llcrypto::pk::ed25519;
+
+    #[cfg(unix)]
+    use std::os::unix::fs::PermissionsExt;
+
+    /// A pretend TPM, which "seals" data by XORing it with a per-device secret,
+    /// followed by a checksum.
+    ///
+    /// This is of course not secure: it only lets us check which device sealed the data.
+    struct FakeTpm {
+        /// The secret of this device.
+        secret: u8,
+    }
+
+    impl FakeTpm {
+        /// Return the checksum of `data` under our secret.
+        fn checksum(&self, data: &[u8]) -> u8 {
+            data.iter()
+                .fold(self.secret, |acc, b| acc.rotate_left(1) ^ b)
+        }
+    }
+
+    impl TpmSealer for FakeTpm {
+        fn seal(&self, data: &[u8]) -> std::result::Result<Vec<u8>, TpmError> {
+            let mut sealed: Vec<u8> = data.iter().map(|b| b ^ self.secret).collect();
+            sealed.push(self.checksum(data));
+            Ok(sealed)
+        }
+
+        fn unseal(&self, sealed: &[u8]) -> std::result::Result<Zeroizing<Vec<u8>>, TpmError> {
+            let (checksum, data) = sealed.split_last().ok_or(TpmError::IntegrityCheckFailed)?;
+            let data: Zeroizing<Vec<u8>> =
+                Zeroizing::new(data.iter().map(|b| b ^ self.secret).collect());
+            if self.checksum(&data) != *checksum {
+                return Err(TpmError::IntegrityCheckFailed);
+            }
+            Ok(data)
+        }
+    }
+
+    fn open(dir: &Path, secret: u8) -> ArtiTpmKeystore {
+        ArtiTpmKeystore::from_path_and_mistrust(
+            dir,
+            &Mistrust::default(),
+            Arc::new(FakeTpm { secret }),
+        )
+        .unwrap()
+    }
+
+    fn init_keystore() -> (ArtiTpmKeystore, TempDir) {
+        let keystore_dir = tempdir().unwrap();
+
+        #[cfg(unix)]
+        fs::set_permissions(&keystore_dir, fs::Permissions::from_mode(0o700)).unwrap();
+
+        (open(keystore_dir.path(), 0x5a), keystore_dir)
+    }
+
+    fn keypair() -> ed25519::Keypair {
+        let mut rng = tor_basic_utils::test_rng::testing_rng();
+        ed25519::Keypair::generate(&mut rng)
+    }
+
+    #[test]
+    fn insert_and_get() {
+        let (key_store, dir) = init_keystore();
+        let key_spec = TestSpecifier::default();
+        let key_type = KeyType::Ed25519Keypair;
+        let key = keypair();
+
+        key_store
+            .insert(&key, &key_spec, &key_type, &OpContext::default())
+            .unwrap();
+        assert!(key_store
+            .contains(&key_spec, &key_type, &OpContext::default())
+            .unwrap());
+        assert_eq!(key_store.list(&OpContext::default()).unwrap().len(), 1);
+
+        // The key is not stored in the clear
+        let path = key_store
+            .inner
+            .rel_path(&key_spec, &key_type)
+            .unwrap()
+            .checked_path()
+            .unwrap();
+        let contents = fs::read(&path).unwrap();
+        assert!(contents.starts_with(ENTRY_MAGIC));
+        assert!(!String::from_utf8_lossy(&contents).contains("OPENSSH"));
+
+        // It can be read back after reopening the key store with the same TPM
+        let key_store = open(dir.path(), 0x5a);
+        let erased_kp = key_store
+            .get(&key_spec, &key_type, &OpContext::default())
+            .unwrap()
+            .unwrap();
+        let Ok(found) = erased_kp.downcast::<ed25519::Keypair>() else {
+            panic!("failed to downcast key to ed25519::Keypair")
+        };
+        assert_eq!(found.verifying_key(), key.verifying_key());
+
+        let raw = key_store
+            .get_raw(
+                &key_spec.arti_path().unwrap().into(),
+                &key_type,
+                &OpContext::default(),
+            )
+            .unwrap()
+            .unwrap();
+        assert!(raw.to_ssh_key_data().is_ok());
+
+        assert_eq!(
+            key_store
+                .remove(&key_spec, &key_type, &OpContext::default())
+                .unwrap(),
+            Some(())
+        );
+        assert!(key_store
+            .get(&key_spec, &key_type, &OpContext::default())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn other_machine() {
+        let (key_store, dir) = init_keystore();
+        let key_spec = TestSpecifier::default();
+        let key_type = KeyType::Ed25519Keypair;
+        key_store
+            .insert(&keypair(), &key_spec, &key_type, &OpContext::default())
+            .unwrap();
+
+        // The keys can still be listed, but not read, with another TPM.
+        let key_store = open(dir.path(), 0xa5);
+        assert!(key_store
+            .contains(&key_spec, &key_type, &OpContext::default())
+            .unwrap());
+        let Err(err) = key_store.get(&key_spec, &key_type, &OpContext::default()) else {
+            panic!("unsealed a key with the wrong TPM")
+        };
+        assert_eq!(err.kind(), ErrorKind::KeystoreAccessFailed);
+    }
+
+    #[test]
+    fn moved_entry() {
+        let (key_store, _dir) = init_keystore();
+        let key_spec = TestSpecifier::default();
+        let key_type = KeyType::Ed25519Keypair;
+        key_store
+            .insert(&keypair(), &key_spec, &key_type, &OpContext::default())
+            .unwrap();
+
+        // Copy the sealed key to another path.
+        let raw = key_store
+            .inner
+            .get_raw(
+                &key_spec.arti_path().unwrap().into(),
+                &key_type,
+                &OpContext::default(),
+            )
+            .unwrap()
+            .unwrap();
+        let other_path: KeyPath = crate::ArtiPath::new("other/key".into()).unwrap().into();
+        key_store
+            .inner
+            .insert_raw(&raw, &other_path, &key_type, &OpContext::default())
+            .unwrap();
+
+        let err = key_store
+            .get_raw(&other_path, &key_type, &OpContext::default())
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::KeystoreCorrupted);
+    }
+}
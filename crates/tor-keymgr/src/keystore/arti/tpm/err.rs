@@ -0,0 +1,72 @@
+//! Error types for [`ArtiTpmKeystore`](crate::ArtiTpmKeystore).
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use tor_error::{ErrorKind, HasKind};
+
+use crate::KeystoreError;
+
+/// An error returned by a [`TpmSealer`](crate::TpmSealer).
+#[derive(thiserror::Error, Debug, Clone)]
+#[non_exhaustive]
+pub enum TpmError {
+    /// We couldn't reach the TPM.
+    #[error("Unable to reach TPM")]
+    Unavailable(#[source] Arc<dyn std::error::Error + Send + Sync>),
+
+    /// The TPM refused to unseal the data,
+    /// because it was sealed by another device or has been tampered with.
+    #[error("TPM integrity check failed")]
+    IntegrityCheckFailed,
+
+    /// The TPM refused to unseal the data,
+    /// because the policy it was sealed under (such as a PCR policy) is not satisfied.
+    #[error("TPM policy check failed")]
+    PolicyCheckFailed,
+
+    /// The TPM failed in some other way.
+    #[error("TPM error")]
+    Other(#[source] Arc<dyn std::error::Error + Send + Sync>),
+}
+
+/// An error returned by [`ArtiTpmKeystore`](crate::ArtiTpmKeystore)'s
+/// [`Keystore`](crate::Keystore) implementation.
+#[derive(thiserror::Error, Debug, Clone)]
+pub(crate) enum ArtiTpmKeystoreError {
+    /// The TPM failed.
+    #[error("Failed to {action} key with TPM")]
+    Tpm {
+        /// What we were trying to do.
+        action: &'static str,
+        /// The underlying error.
+        #[source]
+        err: TpmError,
+    },
+
+    /// A key was unsealed, but it was not sealed for the path it was found at.
+    ///
+    /// The key was either not written by a TPM keystore,
+    /// or has been tampered with.
+    #[error("Malformed sealed key {0:?}")]
+    MalformedEntry(PathBuf),
+}
+
+impl KeystoreError for ArtiTpmKeystoreError {}
+
+impl HasKind for ArtiTpmKeystoreError {
+    fn kind(&self) -> ErrorKind {
+        use ArtiTpmKeystoreError as KE;
+
+        match self {
+            KE::Tpm { .. } => ErrorKind::KeystoreAccessFailed,
+            KE::MalformedEntry(_) => ErrorKind::KeystoreCorrupted,
+        }
+    }
+}
+
+impl From<ArtiTpmKeystoreError> for crate::Error {
+    fn from(e: ArtiTpmKeystoreError) -> Self {
+        crate::Error::Keystore(Arc::new(e))
+    }
+}
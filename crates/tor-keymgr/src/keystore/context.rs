@@ -0,0 +1,218 @@
+//! Deadlines and cancellation for [`Keystore`](crate::Keystore) operations.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+use tor_error::{internal, into_internal};
+
+use crate::{Error, Result};
+
+mod pool;
+
+use pool::Pool;
+
+/// How often [`OpContext::run_blocking`] checks whether it has been cancelled.
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The context of a [`Keystore`](crate::Keystore) operation.
+///
+/// Carries an optional deadline, and an optional [`CancellationToken`].
+/// Key stores are expected to give up with [`Error::Timeout`] once the deadline has passed,
+/// and with [`Error::Cancelled`] once the token has been cancelled.
+///
+/// Key stores backed by a filesystem that may hang (such as a network mount)
+/// stop *waiting* for the filesystem when the operation times out,
+/// but can't interrupt the system call itself:
+/// a timed out write may still take effect later.
+///
+/// The default `OpContext` has no deadline, and can't be cancelled.
+#[derive(Clone, Debug, Default)]
+pub struct OpContext {
+    /// The time by which the operation must complete.
+    deadline: Option<Instant>,
+    /// A token for cancelling the operation.
+    cancel: Option<CancellationToken>,
+}
+
+impl OpContext {
+    /// Create a new `OpContext` with no deadline, that can't be cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the time by which the operation must complete.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Set the time by which the operation must complete to `timeout` from now.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        // If the deadline can't be represented, it's so far in the future
+        // that it might as well not exist.
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.with_deadline(deadline),
+            None => self,
+        }
+    }
+
+    /// Allow the operation to be cancelled with `cancel`.
+    pub fn with_cancel(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Return the time by which the operation must complete, if any.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Return an error if the operation has been cancelled, or if its deadline has passed.
+    pub fn check(&self) -> Result<()> {
+        if self.cancel.as_ref().is_some_and(|c| c.is_cancelled()) {
+            return Err(Error::Cancelled);
+        }
+        if self.deadline.is_some_and(|d| Instant::now() >= d) {
+            return Err(Error::Timeout);
+        }
+        Ok(())
+    }
+
+    /// Run the blocking operation `op`, giving up if it is cancelled,
+    /// or if it doesn't complete by the deadline.
+    ///
+    /// If there is a deadline or a cancellation token,
+    /// `op` runs on a thread from a bounded pool shared by all key stores,
+    /// and we stop waiting for it if we give up on it.
+    /// If every thread of the pool is busy, `op` waits in a queue,
+    /// and is skipped if we have given up on it by the time a thread is available.
+    pub(crate) fn run_blocking<T, F>(&self, op: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        self.check()?;
+
+        if self.deadline.is_none() && self.cancel.is_none() {
+            return Ok(op());
+        }
+
+        // The channel has room for the result, so the thread never blocks on sending it,
+        // even if we've stopped waiting.
+        let (tx, rx) = mpsc::sync_channel(1);
+        let ctx = self.clone();
+        Pool::global()
+            .spawn(move || {
+                // If we gave up while the operation was queued, don't bother running it.
+                if ctx.check().is_ok() {
+                    // If we've given up, nobody wants the result.
+                    let _ = tx.send(op());
+                }
+            })
+            .map_err(into_internal!("failed to spawn keystore thread"))?;
+
+        loop {
+            let wait = match self.deadline {
+                Some(deadline) => deadline
+                    .saturating_duration_since(Instant::now())
+                    .min(CANCEL_POLL_INTERVAL),
+                None => CANCEL_POLL_INTERVAL,
+            };
+            match rx.recv_timeout(wait) {
+                Ok(res) => return Ok(res),
+                Err(mpsc::RecvTimeoutError::Timeout) => self.check()?,
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    // The operation was skipped because we gave up on it,
+                    // or it panicked.
+                    self.check()?;
+                    return Err(internal!("keystore operation exited without a result").into());
+                }
+            }
+        }
+    }
+}
+
+/// A token for cancelling [`Keystore`](crate::Keystore) operations.
+///
+/// Clones of a `CancellationToken` share its state:
+/// cancelling one cancels all the operations whose [`OpContext`] holds any of them.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Create a new `CancellationToken`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the operations using this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    /// Return true if this token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn no_deadline() {
+        let ctx = OpContext::new();
+        assert!(ctx.check().is_ok());
+        assert_eq!(ctx.run_blocking(|| 7).unwrap(), 7);
+    }
+
+    #[test]
+    fn timeout() {
+        let ctx = OpContext::new().with_timeout(Duration::from_millis(50));
+        // A slow operation is abandoned when the deadline passes.
+        let (_tx, rx) = mpsc::channel::<()>();
+        let res = ctx.run_blocking(move || rx.recv());
+        assert!(matches!(res, Err(Error::Timeout)));
+
+        // Once the deadline has passed, operations aren't even attempted.
+        let res = ctx.run_blocking(|| panic!("should not run"));
+        assert!(matches!(res, Err(Error::Timeout)));
+
+        let ctx = OpContext::new().with_timeout(Duration::from_secs(60));
+        assert_eq!(ctx.run_blocking(|| 7).unwrap(), 7);
+    }
+
+    #[test]
+    fn cancel() {
+        let cancel = CancellationToken::new();
+        let ctx = OpContext::new().with_cancel(cancel.clone());
+        assert_eq!(ctx.run_blocking(|| 7).unwrap(), 7);
+
+        let (_tx, rx) = mpsc::channel::<()>();
+        let canceller = cancel.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            canceller.cancel();
+        });
+        let res = ctx.run_blocking(move || rx.recv());
+        assert!(matches!(res, Err(Error::Cancelled)));
+        assert!(cancel.is_cancelled());
+        assert!(matches!(ctx.check(), Err(Error::Cancelled)));
+    }
+}
@@ -0,0 +1,177 @@
+//! A pool of threads for running blocking key store operations.
+//!
+//! [`OpContext::run_blocking`](super::OpContext::run_blocking) must be able to stop waiting
+//! for an operation that hangs (for instance, on an unresponsive network filesystem),
+//! so it hands the operation off to the [`Pool`], which runs it on a thread of its own.
+//!
+//! The pool starts out empty.
+//! A new thread is started whenever work arrives and every existing thread is busy
+//! (up to [`MAX_THREADS`]; beyond that, work waits in a queue),
+//! and threads exit after they have been idle for [`IDLE_TIMEOUT`].
+//!
+//! Bounding the number of threads means that a filesystem that hangs
+//! ties up at most [`MAX_THREADS`] threads, however many operations are attempted on it.
+
+use std::collections::VecDeque;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+/// The largest number of threads the pool will run at once.
+const MAX_THREADS: usize = 8;
+
+/// How long a thread waits for more work before exiting.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A unit of work for the pool.
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A pool of threads that run [`Job`]s.
+#[derive(Default)]
+pub(super) struct Pool {
+    /// The state of the pool.
+    state: Mutex<State>,
+    /// Signalled when a job is added to the queue.
+    work_available: Condvar,
+}
+
+/// The mutable state of a [`Pool`].
+#[derive(Default)]
+struct State {
+    /// Jobs that no thread has started yet.
+    queue: VecDeque<Job>,
+    /// The number of threads currently running.
+    threads: usize,
+    /// The number of threads currently waiting for a job.
+    idle: usize,
+}
+
+impl Pool {
+    /// Return the pool shared by every key store.
+    pub(super) fn global() -> &'static Pool {
+        /// The global pool.
+        static POOL: OnceLock<Pool> = OnceLock::new();
+        POOL.get_or_init(Pool::default)
+    }
+
+    /// Arrange for `job` to be run on one of the pool's threads.
+    ///
+    /// Returns an error if the job could not be queued because we couldn't start a thread,
+    /// in which case `job` is dropped without being run.
+    pub(super) fn spawn(&'static self, job: impl FnOnce() + Send + 'static) -> std::io::Result<()> {
+        let mut state = self.state.lock().expect("poisoned lock");
+
+        // An idle thread only stops counting as idle once it wakes up and takes a job,
+        // so some of the idle threads may already be spoken for by jobs in the queue.
+        // We need a new thread unless there's an idle thread left over for this job.
+        if state.queue.len() >= state.idle && state.threads < MAX_THREADS {
+            thread::Builder::new()
+                .name("keystore-op".into())
+                .spawn(move || self.run_worker())?;
+            state.threads += 1;
+        }
+
+        state.queue.push_back(Box::new(job));
+        drop(state);
+        self.work_available.notify_one();
+        Ok(())
+    }
+
+    /// Run jobs from the queue, until we have been idle for [`IDLE_TIMEOUT`].
+    fn run_worker(&self) {
+        let mut state = self.state.lock().expect("poisoned lock");
+        loop {
+            if let Some(job) = state.queue.pop_front() {
+                drop(state);
+                // A panicking job mustn't take the thread (and our count of threads) with it.
+                // The caller notices the panic, because the job never sends its result.
+                let _ = catch_unwind(AssertUnwindSafe(job));
+                state = self.state.lock().expect("poisoned lock");
+                continue;
+            }
+
+            state.idle += 1;
+            let (new_state, timeout) = self
+                .work_available
+                .wait_timeout(state, IDLE_TIMEOUT)
+                .expect("poisoned lock");
+            state = new_state;
+            state.idle -= 1;
+
+            if timeout.timed_out() && state.queue.is_empty() {
+                state.threads -= 1;
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+
+    use super::*;
+    use std::sync::{mpsc, Arc};
+
+    #[test]
+    fn bounded() {
+        let pool: &'static Pool = Box::leak(Box::default());
+        let (tx, rx) = mpsc::channel();
+
+        // More jobs than threads, all blocked until we say so.
+        let (go_tx, go_rx) = mpsc::channel::<()>();
+        let go_rx = Arc::new(Mutex::new(go_rx));
+        let n_jobs = MAX_THREADS * 2;
+        for i in 0..n_jobs {
+            let tx = tx.clone();
+            let go_rx = go_rx.clone();
+            pool.spawn(move || {
+                go_rx.lock().unwrap().recv().unwrap();
+                tx.send(i).unwrap();
+            })
+            .unwrap();
+        }
+        assert_eq!(pool.state.lock().unwrap().threads, MAX_THREADS);
+
+        for _ in 0..n_jobs {
+            go_tx.send(()).unwrap();
+        }
+        let mut done: Vec<usize> = rx.iter().take(n_jobs).collect();
+        done.sort_unstable();
+        assert_eq!(done, (0..n_jobs).collect::<Vec<_>>());
+
+        // A panicking job doesn't stop the pool.
+        pool.spawn(|| panic!("oops")).unwrap();
+        pool.spawn(move || tx.send(n_jobs).unwrap()).unwrap();
+        assert_eq!(rx.recv().unwrap(), n_jobs);
+    }
+
+    #[test]
+    fn reuses_threads() {
+        let pool: &'static Pool = Box::leak(Box::default());
+
+        for i in 0..10 {
+            let (tx, rx) = mpsc::channel();
+            pool.spawn(move || tx.send(i).unwrap()).unwrap();
+            assert_eq!(rx.recv().unwrap(), i);
+            // Wait for the thread to become idle again.
+            while pool.state.lock().unwrap().idle == 0 {
+                thread::yield_now();
+            }
+        }
+        assert_eq!(pool.state.lock().unwrap().threads, 1);
+    }
+}
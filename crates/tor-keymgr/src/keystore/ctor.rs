@@ -1,10 +1,14 @@
-//! Read-only C Tor key store support.
+//! C Tor key store support.
+//!
+//! The keystores in this module read and write keys in the formats and
+//! directory layouts used by C Tor, so that keys can be moved between
+//! Arti and C Tor deployments.
 
 pub(crate) mod client;
 pub(crate) mod err;
 pub(crate) mod service;
 
-use crate::keystore::fs_utils::{FilesystemAction, FilesystemError, RelKeyPath};
+use crate::keystore::fs_utils::{checked_op, FilesystemAction, FilesystemError, RelKeyPath};
 use crate::{KeystoreId, Result};
 use fs_mistrust::{CheckedDir, Mistrust};
 
@@ -19,7 +23,7 @@ pub use service::CTorServiceKeystore;
 struct CTorKeystore {
     /// The root of the key store.
     ///
-    /// All the keys are read from this directory.
+    /// All the keys are read from, and written to, this directory.
     keystore_dir: CheckedDir,
     /// The unique identifier of this instance.
     id: KeystoreId,
@@ -53,4 +57,30 @@ impl CTorKeystore {
     fn rel_path(&self, rel_path: PathBuf) -> RelKeyPath {
         RelKeyPath::from_parts(&self.keystore_dir, rel_path)
     }
+
+    /// Atomically write `contents` to the file at `path`.
+    fn write_file(&self, path: &RelKeyPath, contents: impl AsRef<[u8]>) -> Result<()> {
+        checked_op!(write_and_replace, path, contents)
+            .map_err(|err| FilesystemError::FsMistrust {
+                action: FilesystemAction::Write,
+                path: path.rel_path_unchecked().into(),
+                err: err.into(),
+            })
+            .map_err(|e| CTorKeystoreError::Filesystem(e).into())
+    }
+
+    /// Remove the file at `path`.
+    ///
+    /// Returns `Ok(None)` if the file doesn't exist.
+    fn remove_file(&self, path: &RelKeyPath) -> Result<Option<()>> {
+        match checked_op!(remove_file, path) {
+            Ok(()) => Ok(Some(())),
+            Err(fs_mistrust::Error::NotFound(_)) => Ok(None),
+            Err(err) => Err(CTorKeystoreError::Filesystem(FilesystemError::FsMistrust {
+                action: FilesystemAction::Remove,
+                path: path.rel_path_unchecked().into(),
+                err: err.into(),
+            }))?,
+        }
+    }
 }
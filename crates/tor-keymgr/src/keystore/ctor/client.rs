@@ -1,4 +1,4 @@
-//! Read-only C Tor client key store implementation
+//! C Tor client key store implementation
 //!
 //! See [`CTorClientKeystore`] for more details.
 
@@ -10,21 +10,21 @@ use std::str::FromStr as _;
 use crate::keystore::ctor::err::{CTorKeystoreError, MalformedClientKeyError};
 use crate::keystore::ctor::CTorKeystore;
 use crate::keystore::fs_utils::{checked_op, FilesystemAction, FilesystemError, RelKeyPath};
-use crate::keystore::{EncodableKey, ErasedKey, KeySpecifier, Keystore};
+use crate::keystore::{EncodableKey, ErasedKey, KeySpecifier, Keystore, OpContext, RawKeyData};
 use crate::{CTorPath, KeyPath, KeystoreId, Result};
 
 use fs_mistrust::Mistrust;
 use itertools::Itertools as _;
 use tor_basic_utils::PathExt as _;
-use tor_error::debug_report;
-use tor_hscrypto::pk::{HsClientDescEncKeypair, HsId};
+use tor_error::{bad_api_usage, debug_report};
+use tor_hscrypto::pk::{HsClientDescEncKeypair, HsId, HSID_ONION_SUFFIX};
 use tor_key_forge::KeyType;
 use tor_llcrypto::pk::curve25519;
 use tracing::debug;
 
-/// A read-only C Tor client keystore.
+/// A C Tor client keystore.
 ///
-/// This keystore provides read-only access to the client restricted discovery keys
+/// This keystore provides access to the client restricted discovery keys
 /// rooted at a given `ClientOnionAuthDir` directory (see `ClientOnionAuthDir` in `tor(1)`).
 ///
 /// The key files must be in the
@@ -32,14 +32,19 @@ use tracing::debug;
 /// and have the `.auth_private` extension.
 /// Invalid keys, and keys that don't have the expected extension, will be ignored.
 ///
-/// The only supported [`Keystore`] operations are [`contains`](Keystore::contains),
-/// [`get`](Keystore::get), and [`list`](Keystore::list). All other keystore operations
-/// will return an error.
+/// Keys [`insert`](Keystore::insert)ed into this keystore are written to `<hsid>.auth_private`
+/// (where `<hsid>` is the onion address of the service, without the `.onion` suffix),
+/// replacing any other entries for the same service.
+/// [`remove`](Keystore::remove) deletes every entry for the specified service.
+///
+/// The [`get_raw`](Keystore::get_raw) and [`insert_raw`](Keystore::insert_raw)
+/// operations are not supported, and will return an error.
 ///
 /// This keystore implementation uses the [`CTorPath`] of the requested [`KeySpecifier`]
 /// and the [`KeyType`] to identify the appropriate restricted discovery keypair.
 /// If the requested `CTorPath` is not [`ClientHsDescEncKey`](CTorPath::ClientHsDescEncKey),
-/// the keystore will declare the key not found.
+/// the keystore will declare the key not found, and trying to [`insert`](Keystore::insert) it
+/// will return an error.
 /// If the requested `CTorPath` is [`ClientHsDescEncKey`](CTorPath::ClientHsDescEncKey),
 /// but the `KeyType` is not [`X25519StaticKeypair`](KeyType::X25519StaticKeypair),
 /// an error is returned.
@@ -128,8 +133,11 @@ impl CTorClientKeystore {
         Ok(Some(content))
     }
 
-    /// List all entries in this store
-    fn list_keys(&self) -> Result<impl Iterator<Item = (HsId, HsClientDescEncKeypair)> + '_> {
+    /// List all entries in this store,
+    /// along with the names of the files they were read from.
+    fn list_keys(
+        &self,
+    ) -> Result<impl Iterator<Item = (PathBuf, HsId, HsClientDescEncKeypair)> + '_> {
         let dir = self.0.rel_path(PathBuf::from("."));
         Ok(self.list_entries(&dir)?.filter_map(|entry| {
             let entry = entry
@@ -179,11 +187,43 @@ impl CTorClientKeystore {
                 })
                 .ok()?;
 
-            Some((hsid, key))
+            Some((path.into(), hsid, key))
         }))
     }
 }
 
+impl CTorClientKeystore {
+    /// Remove all the keys for `hsid`, except the one stored in `keep` (if any).
+    ///
+    /// Returns `Ok(None)` if there were no keys to remove.
+    fn remove_keys(&self, hsid: &HsId, keep: Option<&Path>) -> Result<Option<()>> {
+        let to_remove = self
+            .list_keys()?
+            .filter_map(|(path, key_hsid, _)| {
+                (key_hsid == *hsid && Some(path.as_path()) != keep).then_some(path)
+            })
+            .collect_vec();
+
+        let mut removed = None;
+        for path in to_remove {
+            removed = removed.or(self.0.remove_file(&self.0.rel_path(path))?);
+        }
+
+        Ok(removed)
+    }
+}
+
+/// Return the onion address of `hsid`, without the `.onion` suffix.
+///
+/// This is how services are named in C Tor's client key files.
+fn onion_name(hsid: &HsId) -> String {
+    let onion = hsid.to_string();
+    onion
+        .strip_suffix(HSID_ONION_SUFFIX)
+        .unwrap_or(&onion)
+        .to_owned()
+}
+
 /// Parse a client restricted discovery keypair,
 /// returning the [`HsId`] of the service the key is meant for,
 /// and the corresponding [`HsClientDescEncKeypair`].
@@ -235,35 +275,80 @@ impl Keystore for CTorClientKeystore {
         &self.0.id
     }
 
-    fn contains(&self, key_spec: &dyn KeySpecifier, key_type: &KeyType) -> Result<bool> {
-        self.get(key_spec, key_type).map(|k| k.is_some())
+    fn dir(&self) -> Option<&Path> {
+        Some(self.0.keystore_dir.as_path())
     }
 
-    fn get(&self, key_spec: &dyn KeySpecifier, key_type: &KeyType) -> Result<Option<ErasedKey>> {
+    fn contains(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<bool> {
+        self.get(key_spec, key_type, ctx).map(|k| k.is_some())
+    }
+
+    fn get(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<ErasedKey>> {
+        ctx.check()?;
         let want_hsid = hsid_if_supported!(key_spec, Ok(None), key_type);
         Ok(self
             .list_keys()?
-            .find_map(|(hsid, key)| (hsid == want_hsid).then(|| key.into()))
+            .find_map(|(_, hsid, key)| (hsid == want_hsid).then(|| key.into()))
             .map(|k: curve25519::StaticKeypair| Box::new(k) as ErasedKey))
     }
 
     fn insert(
         &self,
-        _key: &dyn EncodableKey,
-        _key_spec: &dyn KeySpecifier,
-        _key_type: &KeyType,
+        key: &dyn EncodableKey,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
     ) -> Result<()> {
-        Err(CTorKeystoreError::NotSupported { action: "insert" }.into())
+        ctx.check()?;
+        let unsupported = CTorKeystoreError::UnsupportedKey(
+            "key that is not a client restricted discovery key".into(),
+        );
+        let hsid = hsid_if_supported!(key_spec, Err(unsupported.into()), key_type);
+
+        let key = key
+            .downcast_ref::<curve25519::StaticKeypair>()
+            .ok_or_else(|| bad_api_usage!("key does not have the specified type {key_type:?}"))?;
+
+        let onion = onion_name(&hsid);
+        let file_name = PathBuf::from(format!("{onion}.{KEY_EXTENSION}"));
+
+        // C Tor uses the first key it finds for a given service,
+        // so we need to get rid of any other keys for this service.
+        self.remove_keys(&hsid, Some(&file_name))?;
+
+        let encoded_key = data_encoding::BASE32_NOPAD.encode(&key.secret.to_bytes());
+        let contents = format!("{onion}:descriptor:x25519:{encoded_key}\n");
+
+        self.0.write_file(&self.0.rel_path(file_name), contents)
     }
 
-    fn remove(&self, _key_spec: &dyn KeySpecifier, _key_type: &KeyType) -> Result<Option<()>> {
-        Err(CTorKeystoreError::NotSupported { action: "remove" }.into())
+    fn remove(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<()>> {
+        ctx.check()?;
+        let hsid = hsid_if_supported!(key_spec, Ok(None), key_type);
+
+        self.remove_keys(&hsid, None)
     }
 
-    fn list(&self) -> Result<Vec<(KeyPath, KeyType)>> {
+    fn list(&self, ctx: &OpContext) -> Result<Vec<(KeyPath, KeyType)>> {
+        ctx.check()?;
         let keys = self
             .list_keys()?
-            .map(|(hsid, _)| {
+            .map(|(_, hsid, _)| {
                 (
                     CTorPath::ClientHsDescEncKey(hsid).into(),
                     KeyType::X25519StaticKeypair,
@@ -273,6 +358,28 @@ impl Keystore for CTorClientKeystore {
 
         Ok(keys)
     }
+
+    fn get_raw(
+        &self,
+        _key_path: &KeyPath,
+        _key_type: &KeyType,
+        _ctx: &OpContext,
+    ) -> Result<Option<RawKeyData>> {
+        Err(CTorKeystoreError::NotSupported { action: "get_raw" }.into())
+    }
+
+    fn insert_raw(
+        &self,
+        _data: &RawKeyData,
+        _key_path: &KeyPath,
+        _key_type: &KeyType,
+        _ctx: &OpContext,
+    ) -> Result<()> {
+        Err(CTorKeystoreError::NotSupported {
+            action: "insert_raw",
+        }
+        .into())
+    }
 }
 
 #[cfg(test)]
@@ -373,7 +480,7 @@ mod tests {
             );
         }
 
-        let keys: Vec<_> = keystore.list().unwrap();
+        let keys: Vec<_> = keystore.list(&OpContext::default()).unwrap();
 
         assert_eq!(keys.len(), 2);
         assert!(keys
@@ -387,23 +494,81 @@ mod tests {
         let path = CTorPath::ClientHsDescEncKey(HsId::from_str(HSID).unwrap());
 
         let err = keystore
-            .remove(
-                &TestCTorSpecifier(path.clone()),
+            .get_raw(
+                &path.into(),
                 &KeyType::X25519StaticKeypair,
+                &OpContext::default(),
             )
             .unwrap_err();
 
-        assert_eq!(err.to_string(), "Operation not supported: remove");
+        assert_eq!(err.to_string(), "Operation not supported: get_raw");
+    }
+
+    #[test]
+    fn insert_remove() {
+        let (keystore, keystore_dir) = init_keystore("foo");
+
+        let onion = ALICE_AUTH_PRIVATE_VALID.split(":").next().unwrap();
+        let hsid = HsId::from_str(&format!("{onion}.onion")).unwrap();
+        let spec = TestCTorSpecifier(CTorPath::ClientHsDescEncKey(hsid));
+        let key_type = &KeyType::X25519StaticKeypair;
+
+        let key = keystore
+            .get(&spec, key_type, &OpContext::default())
+            .unwrap()
+            .unwrap();
+
+        // Inserting the key replaces alice.auth_private with a file named after the service.
+        keystore
+            .insert(&*key, &spec, key_type, &OpContext::default())
+            .unwrap();
+        let file = keystore_dir.path().join(format!("{onion}.auth_private"));
+        assert!(!keystore_dir
+            .path()
+            .join("alice.auth_private")
+            .try_exists()
+            .unwrap());
+        assert_eq!(
+            parse_client_keypair(fs::read_to_string(&file).unwrap().trim())
+                .unwrap()
+                .0,
+            hsid
+        );
+        assert_found!(keystore, &spec, key_type, true);
+        assert_eq!(keystore.list(&OpContext::default()).unwrap().len(), 2);
+
+        assert_eq!(
+            keystore
+                .remove(&spec, key_type, &OpContext::default())
+                .unwrap(),
+            Some(())
+        );
+        assert!(!file.try_exists().unwrap());
+        assert_found!(keystore, &spec, key_type, false);
+        assert_eq!(
+            keystore
+                .remove(&spec, key_type, &OpContext::default())
+                .unwrap(),
+            None
+        );
+        assert_eq!(keystore.list(&OpContext::default()).unwrap().len(), 1);
 
+        // Only client restricted discovery keys can be stored here.
         let err = keystore
             .insert(
                 &DummyKey,
-                &TestCTorSpecifier(path),
-                &KeyType::X25519StaticKeypair,
+                &TestCTorSpecifier(CTorPath::Service {
+                    nickname: tor_persist::hsnickname::HsNickname::from_str("allium-cepa").unwrap(),
+                    path: crate::CTorServicePath::PublicKey,
+                }),
+                &KeyType::Ed25519PublicKey,
+                &OpContext::default(),
             )
             .unwrap_err();
-
-        assert_eq!(err.to_string(), "Operation not supported: insert");
+        assert_eq!(
+            err.to_string(),
+            "Cannot store key that is not a client restricted discovery key in this keystore"
+        );
     }
 
     #[test]
@@ -412,7 +577,11 @@ mod tests {
         let path = CTorPath::ClientHsDescEncKey(HsId::from_str(HSID).unwrap());
 
         let err = keystore
-            .get(&TestCTorSpecifier(path.clone()), &KeyType::Ed25519PublicKey)
+            .get(
+                &TestCTorSpecifier(path.clone()),
+                &KeyType::Ed25519PublicKey,
+                &OpContext::default(),
+            )
             .map(|_| ())
             .unwrap_err();
 
@@ -33,6 +33,10 @@ pub(crate) enum CTorKeystoreError {
         action: &'static str,
     },
 
+    /// Tried to store a key that has no place in this keystore.
+    #[error("Cannot store {0} in this keystore")]
+    UnsupportedKey(String),
+
     /// Key type and specifier mismatch.
     #[error("Invalid key type {key_type:?} for {key}")]
     InvalidKeyType {
@@ -136,6 +140,7 @@ impl HasKind for CTorKeystoreError {
             KE::Filesystem(e) => e.kind(),
             KE::MalformedKey { .. } => ErrorKind::KeystoreCorrupted,
             KE::NotSupported { .. } => ErrorKind::BadApiUsage,
+            KE::UnsupportedKey(_) => ErrorKind::BadApiUsage,
             KE::InvalidKeyType { .. } => ErrorKind::BadApiUsage,
             KE::Bug(e) => e.kind(),
         }
@@ -1,16 +1,18 @@
-//! Read-only C Tor service key store implementation
+//! C Tor service key store implementation
 //!
 //! See [`CTorServiceKeystore`] for more details.
 
 use crate::keystore::ctor::err::{CTorKeystoreError, MalformedServiceKeyError};
 use crate::keystore::ctor::CTorKeystore;
 use crate::keystore::fs_utils::{checked_op, FilesystemAction, FilesystemError};
-use crate::keystore::{EncodableKey, ErasedKey, KeySpecifier, Keystore, KeystoreId};
+use crate::keystore::{
+    EncodableKey, ErasedKey, KeySpecifier, Keystore, KeystoreId, OpContext, RawKeyData,
+};
 use crate::{CTorPath, CTorServicePath, KeyPath, Result};
 
 use fs_mistrust::Mistrust;
 use tor_basic_utils::PathExt as _;
-use tor_error::internal;
+use tor_error::{bad_api_usage, internal};
 use tor_key_forge::KeyType;
 use tor_llcrypto::pk::ed25519;
 use tor_persist::hsnickname::HsNickname;
@@ -19,27 +21,30 @@ use std::path::{Path, PathBuf};
 use std::result::Result as StdResult;
 use std::sync::Arc;
 
-/// A read-only C Tor service keystore.
+/// A C Tor service keystore.
 ///
-/// This keystore provides read-only access to the hidden service keys
+/// This keystore provides access to the hidden service keys
 /// rooted at a given `HiddenServiceDirectory` directory
 /// (see `HiddenServiceDirectory` in `tor(1)`).
 ///
-/// This keystore can be used to read the `HiddenServiceDirectory/private_key`
-/// and `HiddenServiceDirectory/public_key` C Tor keys, specified by
+/// This keystore can be used to read and write the `HiddenServiceDirectory/hs_ed25519_secret_key`
+/// and `HiddenServiceDirectory/hs_ed25519_public_key` C Tor keys, specified by
 /// [`CTorServicePath::PrivateKey`] (with [`KeyType::Ed25519ExpandedKeypair`])
 /// and [`CTorServicePath::PublicKey`] (with [`KeyType::Ed25519PublicKey`]),
 /// respectively. Any other files stored in `HiddenServiceDirectory` will be ignored.
 ///
-/// The only supported [`Keystore`] operations are [`contains`](Keystore::contains),
-/// [`get`](Keystore::get), and [`list`](Keystore::list). All other keystore operations
-/// will return an error.
+/// Keys are written in the same format C Tor uses,
+/// so a `HiddenServiceDirectory` populated by this keystore
+/// can be used by C Tor, and vice versa.
+/// Note that C Tor also expects to find a `hostname` file in `HiddenServiceDirectory`;
+/// this keystore does not create one.
 ///
 /// This keystore implementation uses the [`CTorPath`] of the requested [`KeySpecifier`]
 /// and the [`KeyType`] to identify the appropriate key.
 /// If the requested `CTorPath` is not [`Service`](CTorPath::Service),
 /// or if the [`HsNickname`] specified in the `CTorPath` does not match the nickname of this store,
-/// the key will be declared not found.
+/// the key will be declared not found, and trying to [`insert`](Keystore::insert) it
+/// will return an error.
 /// If the requested `CTorPath` is [`Service`](CTorPath::Service),
 /// but the `KeyType` and [`CTorServicePath`] are mismatched,
 /// an error is returned.
@@ -110,7 +115,17 @@ impl Keystore for CTorServiceKeystore {
         &self.keystore.id
     }
 
-    fn contains(&self, key_spec: &dyn KeySpecifier, key_type: &KeyType) -> Result<bool> {
+    fn dir(&self) -> Option<&Path> {
+        Some(self.keystore.keystore_dir.as_path())
+    }
+
+    fn contains(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<bool> {
+        ctx.check()?;
         let path = rel_path_if_supported!(self, key_spec, Ok(false), key_type);
 
         let meta = match checked_op!(metadata, path) {
@@ -139,7 +154,13 @@ impl Keystore for CTorServiceKeystore {
         }
     }
 
-    fn get(&self, key_spec: &dyn KeySpecifier, key_type: &KeyType) -> Result<Option<ErasedKey>> {
+    fn get(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<ErasedKey>> {
+        ctx.check()?;
         let path = rel_path_if_supported!(self, key_spec, Ok(None), key_type);
 
         let key = match checked_op!(read, path) {
@@ -177,18 +198,47 @@ impl Keystore for CTorServiceKeystore {
 
     fn insert(
         &self,
-        _key: &dyn EncodableKey,
-        _key_spec: &dyn KeySpecifier,
-        _key_type: &KeyType,
+        key: &dyn EncodableKey,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
     ) -> Result<()> {
-        Err(CTorKeystoreError::NotSupported { action: "insert" }.into())
+        ctx.check()?;
+        let path = rel_path_if_supported!(self, key_spec, Err(self.unsupported_key()), key_type);
+
+        let encoded = match key_type {
+            KeyType::Ed25519ExpandedKeypair => key
+                .downcast_ref::<ed25519::ExpandedKeypair>()
+                .map(encode_ed25519_keypair),
+            KeyType::Ed25519PublicKey => key
+                .downcast_ref::<ed25519::PublicKey>()
+                .map(encode_ed25519_public),
+            _ => {
+                return Err(
+                    internal!("key type was not validated by rel_path_if_supported?!").into(),
+                );
+            }
+        };
+
+        let encoded = encoded
+            .ok_or_else(|| bad_api_usage!("key does not have the specified type {key_type:?}"))?;
+
+        self.keystore.write_file(&path, encoded)
     }
 
-    fn remove(&self, _key_spec: &dyn KeySpecifier, _key_type: &KeyType) -> Result<Option<()>> {
-        Err(CTorKeystoreError::NotSupported { action: "remove" }.into())
+    fn remove(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<()>> {
+        ctx.check()?;
+        let path = rel_path_if_supported!(self, key_spec, Ok(None), key_type);
+
+        self.keystore.remove_file(&path)
     }
 
-    fn list(&self) -> Result<Vec<(KeyPath, KeyType)>> {
+    fn list(&self, ctx: &OpContext) -> Result<Vec<(KeyPath, KeyType)>> {
         use crate::CTorServicePath::*;
         use itertools::Itertools;
 
@@ -214,14 +264,65 @@ impl Keystore for CTorServiceKeystore {
         all_keys
             .into_iter()
             .map(|(path, key_type)| {
-                self.contains(&path, &key_type)
+                self.contains(&path, &key_type, ctx)
                     .map(|res: bool| (path, key_type, res))
             })
             .filter_map_ok(|(path, key_type, res)| res.then_some((path.into(), key_type)))
             .collect()
     }
+
+    fn get_raw(
+        &self,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<RawKeyData>> {
+        ctx.check()?;
+        let path = rel_path_if_supported!(self, key_path, Ok(None), key_type);
+
+        match checked_op!(read, path) {
+            Ok(data) => Ok(Some(RawKeyData::new(data))),
+            Err(fs_mistrust::Error::NotFound(_)) => Ok(None),
+            Err(err) => Err(CTorKeystoreError::Filesystem(FilesystemError::FsMistrust {
+                action: FilesystemAction::Read,
+                path: path.rel_path_unchecked().into(),
+                err: err.into(),
+            }))?,
+        }
+    }
+
+    fn insert_raw(
+        &self,
+        data: &RawKeyData,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<()> {
+        ctx.check()?;
+        let path = rel_path_if_supported!(self, key_path, Err(self.unsupported_key()), key_type);
+
+        self.keystore.write_file(&path, data.as_bytes())
+    }
+}
+
+impl CTorServiceKeystore {
+    /// Return the error to use when asked to store a key
+    /// that doesn't belong to the service of this keystore.
+    fn unsupported_key(&self) -> crate::Error {
+        CTorKeystoreError::UnsupportedKey(format!(
+            "key that is not a C Tor key of service {}",
+            self.nickname
+        ))
+        .into()
+    }
 }
 
+/// The tag C Tor ed25519 public keys are expected to begin with.
+const PUBKEY_TAG: &[u8] = b"== ed25519v1-public: type0 ==\0\0\0";
+
+/// The tag C Tor ed25519 keypairs are expected to begin with.
+const KEYPAIR_TAG: &[u8] = b"== ed25519v1-secret: type0 ==\0\0\0";
+
 /// Helper for parsing C Tor's ed25519 key format.
 macro_rules! parse_ed25519 {
     ($key:expr, $parse_fn:expr, $tag:expr, $key_len:expr) => {{
@@ -249,8 +350,6 @@ macro_rules! parse_ed25519 {
 
 /// Helper for parsing C Tor's ed25519 public key format.
 fn parse_ed25519_public(key: &[u8]) -> StdResult<ed25519::PublicKey, MalformedServiceKeyError> {
-    /// The tag C Tor ed25519 public keys are expected to begin with.
-    const PUBKEY_TAG: &[u8] = b"== ed25519v1-public: type0 ==\0\0\0";
     /// The size of an ed25519 public key.
     const PUBKEY_LEN: usize = 32;
 
@@ -267,8 +366,6 @@ fn parse_ed25519_public(key: &[u8]) -> StdResult<ed25519::PublicKey, MalformedSe
 fn parse_ed25519_keypair(
     key: &[u8],
 ) -> StdResult<ed25519::ExpandedKeypair, MalformedServiceKeyError> {
-    /// The tag C Tor ed25519 keypairs are expected to begin with.
-    const KEYPAIR_TAG: &[u8] = b"== ed25519v1-secret: type0 ==\0\0\0";
     /// The size of an ed25519 keypair.
     const KEYPAIR_LEN: usize = 64;
 
@@ -286,6 +383,16 @@ fn parse_ed25519_keypair(
     )
 }
 
+/// Encode `key` in C Tor's ed25519 public key format.
+fn encode_ed25519_public(key: &ed25519::PublicKey) -> Vec<u8> {
+    [PUBKEY_TAG, key.as_bytes()].concat()
+}
+
+/// Encode `key` in C Tor's ed25519 keypair format.
+fn encode_ed25519_keypair(key: &ed25519::ExpandedKeypair) -> Vec<u8> {
+    [KEYPAIR_TAG, &key.to_secret_key_bytes()].concat()
+}
+
 #[cfg(test)]
 mod tests {
     // @@ begin test lint list maintained by maint/add_warning @@
@@ -306,6 +413,7 @@ mod tests {
     use std::fs;
     use std::str::FromStr as _;
     use tempfile::{tempdir, TempDir};
+    use tor_error::HasKind as _;
 
     use crate::test_utils::{assert_found, DummyKey, TestCTorSpecifier};
     use crate::CTorServicePath;
@@ -388,28 +496,95 @@ mod tests {
     }
 
     #[test]
-    fn unsupported_operation() {
-        let (keystore, _keystore_dir) = init_keystore("foo", "allium-cepa");
-        let path = CTorPath::Service {
-            nickname: keystore.nickname.clone(),
-            path: CTorServicePath::PublicKey,
-        };
+    fn insert_remove() {
+        let (keystore, keystore_dir) = init_keystore("foo", "allium-cepa");
 
-        let err = keystore
-            .remove(&TestCTorSpecifier(path.clone()), &KeyType::Ed25519PublicKey)
-            .unwrap_err();
+        let keys = [
+            (
+                CTorServicePath::PublicKey,
+                KeyType::Ed25519PublicKey,
+                PUBKEY,
+            ),
+            (
+                CTorServicePath::PrivateKey,
+                KeyType::Ed25519ExpandedKeypair,
+                PRIVKEY,
+            ),
+        ];
+
+        for (path, key_type, encoded) in keys {
+            let file = keystore_dir.path().join(path.to_string());
+            let spec = TestCTorSpecifier(CTorPath::Service {
+                nickname: keystore.nickname.clone(),
+                path,
+            });
+            let key = keystore
+                .get(&spec, &key_type, &OpContext::default())
+                .unwrap()
+                .unwrap();
+
+            assert_eq!(
+                keystore
+                    .remove(&spec, &key_type, &OpContext::default())
+                    .unwrap(),
+                Some(())
+            );
+            assert!(!file.try_exists().unwrap());
+            assert_found!(keystore, &spec, &key_type, false);
+            assert_eq!(
+                keystore
+                    .remove(&spec, &key_type, &OpContext::default())
+                    .unwrap(),
+                None
+            );
+
+            // Writing the key back gives us a file that C Tor can read.
+            keystore
+                .insert(&*key, &spec, &key_type, &OpContext::default())
+                .unwrap();
+            assert_found!(keystore, &spec, &key_type, true);
+            let written = fs::read(&file).unwrap();
+            if key_type == KeyType::Ed25519PublicKey {
+                assert_eq!(written, encoded);
+            } else {
+                // Our copy of the secret scalar is reduced,
+                // so it isn't necessarily byte-for-byte identical to the original.
+                assert_eq!(
+                    parse_ed25519_keypair(&written).unwrap().public(),
+                    parse_ed25519_keypair(encoded).unwrap().public()
+                );
+            }
 
-        assert_eq!(err.to_string(), "Operation not supported: remove");
+            // The key type needs to match the key...
+            let err = keystore
+                .insert(&DummyKey, &spec, &key_type, &OpContext::default())
+                .unwrap_err();
+            assert_eq!(err.kind(), tor_error::ErrorKind::BadApiUsage);
+        }
 
+        // ...and the service needs to match the keystore.
+        let spec = TestCTorSpecifier(CTorPath::Service {
+            nickname: HsNickname::new("acutus-cepa".into()).unwrap(),
+            path: CTorServicePath::PublicKey,
+        });
         let err = keystore
             .insert(
                 &DummyKey,
-                &TestCTorSpecifier(path.clone()),
+                &spec,
                 &KeyType::Ed25519PublicKey,
+                &OpContext::default(),
             )
             .unwrap_err();
-
-        assert_eq!(err.to_string(), "Operation not supported: insert");
+        assert_eq!(
+            err.to_string(),
+            "Cannot store key that is not a C Tor key of service allium-cepa in this keystore"
+        );
+        assert_eq!(
+            keystore
+                .remove(&spec, &KeyType::Ed25519PublicKey, &OpContext::default())
+                .unwrap(),
+            None
+        );
     }
 
     #[test]
@@ -425,6 +600,7 @@ mod tests {
             .get(
                 &TestCTorSpecifier(path.clone()),
                 &KeyType::X25519StaticKeypair,
+                &OpContext::default(),
             )
             .map(|_| ())
             .unwrap_err();
@@ -438,7 +614,7 @@ mod tests {
     #[test]
     fn list() {
         let (keystore, _keystore_dir) = init_keystore("foo", "allium-cepa");
-        let keys: Vec<_> = keystore.list().unwrap();
+        let keys: Vec<_> = keystore.list(&OpContext::default()).unwrap();
 
         assert_eq!(keys.len(), 2);
 
@@ -6,9 +6,10 @@ use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
 use tor_error::internal;
-use tor_key_forge::{EncodableKey, ErasedKey, KeyType, SshKeyData};
+use tor_key_forge::{EncodableKey, ErasedKey, KeyType, SecretBuffer};
 
 use crate::keystore::ephemeral::err::ArtiEphemeralKeystoreError;
+use crate::keystore::{OpContext, RawKeyData};
 use crate::Error;
 use crate::{ArtiPath, KeyPath, KeySpecifier, Keystore, KeystoreId};
 
@@ -18,7 +19,9 @@ type KeyIdent = (ArtiPath, KeyType);
 /// The Ephemeral Arti key store
 ///
 /// This is a purely in-memory key store. Keys written to this store
-/// are never written to disk, and are stored in-memory as [`SshKeyData`].
+/// are never written to disk, and are stored in-memory in their OpenSSH encoding,
+/// in buffers that are zeroed when the keys are removed or replaced,
+/// and when the key store is dropped.
 /// Keys saved in this Keystore do not persist between restarts!
 ///
 /// While Arti never writes the keys for this key store to disk, the operating
@@ -29,8 +32,11 @@ type KeyIdent = (ArtiPath, KeyType);
 pub struct ArtiEphemeralKeystore {
     /// Identifier hard-coded to 'ephemeral'
     id: KeystoreId,
-    /// Keys stored as [`SshKeyData`].
-    key_dictionary: Arc<Mutex<HashMap<KeyIdent, SshKeyData>>>,
+    /// Keys stored in their OpenSSH encoding.
+    ///
+    /// We don't store the parsed [`SshKeyData`](tor_key_forge::SshKeyData) here,
+    /// because not all of its key types are zeroed on drop.
+    key_dictionary: Arc<Mutex<HashMap<KeyIdent, RawKeyData>>>,
 }
 
 impl ArtiEphemeralKeystore {
@@ -41,6 +47,11 @@ impl ArtiEphemeralKeystore {
             key_dictionary: Default::default(),
         }
     }
+
+    /// Remove (and zero) every key in this key store.
+    pub fn clear(&self) {
+        self.key_dictionary.lock().expect("lock poisoned").clear();
+    }
 }
 
 impl Keystore for ArtiEphemeralKeystore {
@@ -48,7 +59,13 @@ impl Keystore for ArtiEphemeralKeystore {
         &self.id
     }
 
-    fn contains(&self, key_spec: &dyn KeySpecifier, key_type: &KeyType) -> Result<bool, Error> {
+    fn contains(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<bool, Error> {
+        ctx.check()?;
         let arti_path = key_spec
             .arti_path()
             .map_err(ArtiEphemeralKeystoreError::ArtiPathUnavailableError)?;
@@ -61,14 +78,16 @@ impl Keystore for ArtiEphemeralKeystore {
         &self,
         key_spec: &dyn KeySpecifier,
         key_type: &KeyType,
+        ctx: &OpContext,
     ) -> Result<Option<ErasedKey>, Error> {
+        ctx.check()?;
         let arti_path = key_spec
             .arti_path()
             .map_err(ArtiEphemeralKeystoreError::ArtiPathUnavailableError)?;
         let key_dictionary = self.key_dictionary.lock().expect("lock poisoned");
         match key_dictionary.get(&(arti_path.clone(), key_type.clone())) {
             Some(key) => {
-                let key: ErasedKey = key.clone().into_erased()?;
+                let key: ErasedKey = key.to_ssh_key_data()?.into_erased()?;
                 Ok(Some(key))
             }
             None => Ok(None),
@@ -80,7 +99,9 @@ impl Keystore for ArtiEphemeralKeystore {
         key: &dyn EncodableKey,
         key_spec: &dyn KeySpecifier,
         key_type: &KeyType,
+        ctx: &OpContext,
     ) -> Result<(), Error> {
+        ctx.check()?;
         let arti_path = key_spec
             .arti_path()
             .map_err(ArtiEphemeralKeystoreError::ArtiPathUnavailableError)?;
@@ -104,13 +125,22 @@ impl Keystore for ArtiEphemeralKeystore {
             .into());
         }
 
+        // TODO (#1095): decide what information, if any, to put in the comment
+        let key_data = RawKeyData::from(SecretBuffer::from(key_data.to_openssh_string("")?));
+
         // save to dictionary
         let mut key_dictionary = self.key_dictionary.lock().expect("lock poisoned");
         let _ = key_dictionary.insert((arti_path, key_type.clone()), key_data);
         Ok(())
     }
 
-    fn remove(&self, key_spec: &dyn KeySpecifier, key_type: &KeyType) -> Result<Option<()>, Error> {
+    fn remove(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<()>, Error> {
+        ctx.check()?;
         let arti_path = key_spec
             .arti_path()
             .map_err(ArtiEphemeralKeystoreError::ArtiPathUnavailableError)?;
@@ -120,13 +150,47 @@ impl Keystore for ArtiEphemeralKeystore {
             .map(|_| ()))
     }
 
-    fn list(&self) -> Result<Vec<(KeyPath, KeyType)>, Error> {
+    fn list(&self, ctx: &OpContext) -> Result<Vec<(KeyPath, KeyType)>, Error> {
+        ctx.check()?;
         let key_dictionary = self.key_dictionary.lock().expect("lock poisoned");
         Ok(key_dictionary
             .keys()
             .map(|(arti_path, key_type)| (arti_path.clone().into(), key_type.clone()))
             .collect())
     }
+
+    fn get_raw(
+        &self,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<RawKeyData>, Error> {
+        ctx.check()?;
+        let arti_path = key_path
+            .arti_path()
+            .map_err(ArtiEphemeralKeystoreError::ArtiPathUnavailableError)?;
+        let key_dictionary = self.key_dictionary.lock().expect("lock poisoned");
+        Ok(key_dictionary.get(&(arti_path, key_type.clone())).cloned())
+    }
+
+    fn insert_raw(
+        &self,
+        data: &RawKeyData,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<(), Error> {
+        ctx.check()?;
+        let arti_path = key_path
+            .arti_path()
+            .map_err(ArtiEphemeralKeystoreError::ArtiPathUnavailableError)?;
+        // We only store raw data that we'll be able to parse later.
+        data.check_parseable(key_type)?;
+
+        let mut key_dictionary = self.key_dictionary.lock().expect("lock poisoned");
+        let _ = key_dictionary.insert((arti_path, key_type.clone()), data.clone());
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -146,7 +210,7 @@ mod tests {
     //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
 
     use tor_basic_utils::test_rng::testing_rng;
-    use tor_llcrypto::pk::ed25519;
+    use tor_llcrypto::pk::{curve25519, ed25519};
 
     use super::*;
 
@@ -186,13 +250,22 @@ mod tests {
         let key_store = ArtiEphemeralKeystore::new("test-ephemeral".to_string());
 
         // verify no key in store
-        assert!(!key_store.contains(key_spec().as_ref(), key_type()).unwrap());
+        assert!(!key_store
+            .contains(key_spec().as_ref(), key_type(), &OpContext::default())
+            .unwrap());
 
         // insert key and verify in store
         assert!(key_store
-            .insert(key().as_ref(), key_spec().as_ref(), key_type())
+            .insert(
+                key().as_ref(),
+                key_spec().as_ref(),
+                key_type(),
+                &OpContext::default()
+            )
             .is_ok());
-        assert!(key_store.contains(key_spec().as_ref(), key_type()).unwrap());
+        assert!(key_store
+            .contains(key_spec().as_ref(), key_type(), &OpContext::default())
+            .unwrap());
     }
 
     #[test]
@@ -201,17 +274,22 @@ mod tests {
 
         // verify no result to get
         assert!(key_store
-            .get(key_spec().as_ref(), key_type())
+            .get(key_spec().as_ref(), key_type(), &OpContext::default())
             .unwrap()
             .is_none());
 
         // insert and verify get is a result
         assert!(key_store
-            .insert(key().as_ref(), key_spec().as_ref(), key_type())
+            .insert(
+                key().as_ref(),
+                key_spec().as_ref(),
+                key_type(),
+                &OpContext::default()
+            )
             .is_ok());
 
         let key = key_store
-            .get(key_spec().as_ref(), key_type())
+            .get(key_spec().as_ref(), key_type(), &OpContext::default())
             .unwrap()
             .unwrap();
 
@@ -225,30 +303,42 @@ mod tests {
 
         // verify inserting a key with the wrong key type fails
         assert!(key_store
-            .insert(key().as_ref(), key_spec().as_ref(), key_type_bad())
+            .insert(
+                key().as_ref(),
+                key_spec().as_ref(),
+                key_type_bad(),
+                &OpContext::default()
+            )
             .is_err());
         // further ensure there are no side effects
         assert!(!key_store
-            .contains(key_spec().as_ref(), key_type_bad())
+            .contains(key_spec().as_ref(), key_type_bad(), &OpContext::default())
             .unwrap());
         assert!(key_store
-            .get(key_spec().as_ref(), key_type_bad())
+            .get(key_spec().as_ref(), key_type_bad(), &OpContext::default())
             .unwrap()
             .is_none());
-        assert!(key_store.list().unwrap().is_empty());
+        assert!(key_store.list(&OpContext::default()).unwrap().is_empty());
 
         // verify inserting a good key succeeds
         assert!(key_store
-            .insert(key().as_ref(), key_spec().as_ref(), key_type())
+            .insert(
+                key().as_ref(),
+                key_spec().as_ref(),
+                key_type(),
+                &OpContext::default()
+            )
             .is_ok());
 
         // further ensure correct side effects
-        assert!(key_store.contains(key_spec().as_ref(), key_type()).unwrap());
         assert!(key_store
-            .get(key_spec().as_ref(), key_type())
+            .contains(key_spec().as_ref(), key_type(), &OpContext::default())
+            .unwrap());
+        assert!(key_store
+            .get(key_spec().as_ref(), key_type(), &OpContext::default())
             .unwrap()
             .is_some());
-        assert_eq!(key_store.list().unwrap().len(), 1);
+        assert_eq!(key_store.list(&OpContext::default()).unwrap().len(), 1);
     }
 
     #[test]
@@ -257,31 +347,79 @@ mod tests {
 
         // verify removing from an empty store returns None
         assert!(key_store
-            .remove(key_spec().as_ref(), key_type())
+            .remove(key_spec().as_ref(), key_type(), &OpContext::default())
             .unwrap()
             .is_none());
 
         // verify inserting and removing results in Some(())
         assert!(key_store
-            .insert(key().as_ref(), key_spec().as_ref(), key_type())
+            .insert(
+                key().as_ref(),
+                key_spec().as_ref(),
+                key_type(),
+                &OpContext::default()
+            )
             .is_ok());
         assert!(key_store
-            .remove(key_spec().as_ref(), key_type())
+            .remove(key_spec().as_ref(), key_type(), &OpContext::default())
             .unwrap()
             .is_some());
     }
 
+    #[test]
+    fn clear() {
+        let key_store = ArtiEphemeralKeystore::new("test-ephemeral".to_string());
+        let secret = curve25519::StaticSecret::random_from_rng(testing_rng());
+        let public = curve25519::PublicKey::from(&secret);
+        let x25519: ErasedKey = Box::new(curve25519::StaticKeypair { secret, public });
+
+        key_store
+            .insert(
+                key().as_ref(),
+                key_spec().as_ref(),
+                key_type(),
+                &OpContext::default(),
+            )
+            .unwrap();
+        key_store
+            .insert(
+                x25519.as_ref(),
+                key_spec().as_ref(),
+                &KeyType::X25519StaticKeypair,
+                &OpContext::default(),
+            )
+            .unwrap();
+        assert_eq!(key_store.list(&OpContext::default()).unwrap().len(), 2);
+        let got = key_store
+            .get(
+                key_spec().as_ref(),
+                &KeyType::X25519StaticKeypair,
+                &OpContext::default(),
+            )
+            .unwrap()
+            .unwrap();
+        assert!(got.downcast::<curve25519::StaticKeypair>().is_ok());
+
+        key_store.clear();
+        assert!(key_store.list(&OpContext::default()).unwrap().is_empty());
+    }
+
     #[test]
     fn list() {
         let key_store = ArtiEphemeralKeystore::new("test-ephemeral".to_string());
 
         // verify empty by default
-        assert!(key_store.list().unwrap().is_empty());
+        assert!(key_store.list(&OpContext::default()).unwrap().is_empty());
 
         // verify size 1 after inserting a key
         assert!(key_store
-            .insert(key().as_ref(), key_spec().as_ref(), key_type())
+            .insert(
+                key().as_ref(),
+                key_spec().as_ref(),
+                key_type(),
+                &OpContext::default()
+            )
             .is_ok());
-        assert_eq!(key_store.list().unwrap().len(), 1);
+        assert_eq!(key_store.list(&OpContext::default()).unwrap().len(), 1);
     }
 }
@@ -130,6 +130,8 @@ pub(crate) enum FilesystemAction {
     Write,
     /// Filesystem remove
     Remove,
+    /// Filesystem lock
+    Lock,
 }
 
 impl HasKind for FilesystemError {
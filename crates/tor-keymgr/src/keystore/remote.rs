@@ -0,0 +1,450 @@
+//! RemoteKeystore implementation (keys held by an external secret store)
+
+pub(crate) mod err;
+
+use futures::executor::block_on;
+use futures::future::BoxFuture;
+use tor_error::internal;
+use tor_key_forge::{EncodableKey, ErasedKey, KeyType, SecretBuffer};
+
+use crate::keystore::remote::err::{RemoteKeystoreError, RemoteTransportError};
+use crate::keystore::{OpContext, RawKeyData};
+use crate::{ArtiPath, Error, KeyPath, KeySpecifier, Keystore, KeystoreId};
+
+/// A connection to an external secret store, such as HashiCorp Vault or a custom agent.
+///
+/// The secret store holds a flat collection of named entries.
+/// [`RemoteKeystore`] names each entry after the [`ArtiPath`] of the key,
+/// followed by a `.` and the [`arti_extension`](KeyType::arti_extension) of its type
+/// (for example, `hss/allium-cepa/ks_hs_id.ed25519_private`),
+/// and stores the key in its OpenSSH encoding.
+///
+/// Implementations are responsible for any namespacing (such as a path prefix
+/// or mount point), authentication, and retry policy that the secret store needs.
+///
+/// # Blocking
+///
+/// [`Keystore`] is a synchronous API, so [`RemoteKeystore`] waits for each of these
+/// futures to complete on the calling thread.
+/// The futures must therefore make progress without being polled by an async runtime
+/// (for instance, by doing their I/O on a thread of their own,
+/// or on a runtime that is not the one `KeyMgr` is called from).
+pub trait RemoteKeystoreTransport: Send + Sync + 'static {
+    /// Fetch the contents of the entry called `name`.
+    ///
+    /// Returns `Ok(None)` if there is no such entry.
+    fn fetch<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> BoxFuture<'a, Result<Option<RawKeyData>, RemoteTransportError>>;
+
+    /// Create the entry called `name`, or replace its contents, with `data`.
+    fn store<'a>(
+        &'a self,
+        name: &'a str,
+        data: &'a RawKeyData,
+    ) -> BoxFuture<'a, Result<(), RemoteTransportError>>;
+
+    /// Delete the entry called `name`.
+    ///
+    /// Returns `Ok(false)` if there was no such entry.
+    fn delete<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<bool, RemoteTransportError>>;
+
+    /// Return the names of all the entries in the secret store.
+    fn list(&self) -> BoxFuture<'_, Result<Vec<String>, RemoteTransportError>>;
+}
+
+/// A key store whose keys are held by an external secret store.
+///
+/// All access to the secret store goes through a [`RemoteKeystoreTransport`],
+/// so that deployments can keep (for instance) their onion service keys
+/// in a central place, while [`KeyMgr`](crate::KeyMgr) presents its usual API.
+///
+/// Keys are never written to disk by this key store,
+/// and are only held in memory (in buffers that are zeroed on drop)
+/// for as long as it takes to encode or decode them.
+pub struct RemoteKeystore {
+    /// The identifier of this key store.
+    id: KeystoreId,
+    /// The connection to the secret store.
+    transport: Box<dyn RemoteKeystoreTransport>,
+}
+
+impl RemoteKeystore {
+    /// Create a new [`RemoteKeystore`] that uses `transport` to reach its secret store.
+    pub fn new(id: String, transport: Box<dyn RemoteKeystoreTransport>) -> Self {
+        Self {
+            id: KeystoreId(id),
+            transport,
+        }
+    }
+
+    /// Return the name of the entry for the key at `path` of type `key_type`.
+    fn entry_name(path: &ArtiPath, key_type: &KeyType) -> String {
+        format!("{}.{}", path, key_type.arti_extension())
+    }
+
+    /// Return the name of the entry for the key identified by `key_spec` and `key_type`.
+    fn entry_name_for_spec(
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+    ) -> Result<String, Error> {
+        let arti_path = key_spec
+            .arti_path()
+            .map_err(RemoteKeystoreError::ArtiPathUnavailableError)?;
+        Ok(Self::entry_name(&arti_path, key_type))
+    }
+
+    /// Return the name of the entry for the key at `key_path` of type `key_type`.
+    fn entry_name_for_path(key_path: &KeyPath, key_type: &KeyType) -> Result<String, Error> {
+        let arti_path = key_path
+            .arti_path()
+            .map_err(RemoteKeystoreError::ArtiPathUnavailableError)?;
+        Ok(Self::entry_name(&arti_path, key_type))
+    }
+
+    /// Parse the name of an entry into the path and type of its key.
+    fn parse_entry_name(name: &str) -> Result<(KeyPath, KeyType), Error> {
+        let malformed = || RemoteKeystoreError::MalformedEntryName(name.to_string());
+        let (path, extension) = name.rsplit_once('.').ok_or_else(malformed)?;
+        let path = ArtiPath::new(path.to_string()).map_err(|_| malformed())?;
+        Ok((path.into(), KeyType::from(extension)))
+    }
+
+    /// Fetch the entry called `name`.
+    fn fetch(&self, name: &str) -> Result<Option<RawKeyData>, Error> {
+        block_on(self.transport.fetch(name)).map_err(|err| {
+            RemoteKeystoreError::Transport {
+                action: "fetch",
+                name: name.to_string(),
+                err,
+            }
+            .into()
+        })
+    }
+
+    /// Write `data` to the entry called `name`.
+    fn store(&self, name: &str, data: &RawKeyData) -> Result<(), Error> {
+        block_on(self.transport.store(name, data)).map_err(|err| {
+            RemoteKeystoreError::Transport {
+                action: "store",
+                name: name.to_string(),
+                err,
+            }
+            .into()
+        })
+    }
+}
+
+impl Keystore for RemoteKeystore {
+    fn id(&self) -> &KeystoreId {
+        &self.id
+    }
+
+    fn contains(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<bool, Error> {
+        ctx.check()?;
+        let name = Self::entry_name_for_spec(key_spec, key_type)?;
+        Ok(self.fetch(&name)?.is_some())
+    }
+
+    fn get(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<ErasedKey>, Error> {
+        ctx.check()?;
+        let name = Self::entry_name_for_spec(key_spec, key_type)?;
+        match self.fetch(&name)? {
+            Some(data) => Ok(Some(data.to_ssh_key_data()?.into_erased()?)),
+            None => Ok(None),
+        }
+    }
+
+    fn insert(
+        &self,
+        key: &dyn EncodableKey,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<(), Error> {
+        ctx.check()?;
+        let name = Self::entry_name_for_spec(key_spec, key_type)?;
+        let key_data = key.as_ssh_key_data()?;
+
+        if &key_data.key_type()? != key_type {
+            // See the corresponding check in ArtiEphemeralKeystore::insert.
+            return Err(internal!(
+                "the specified KeyType does not match key type of the inserted key?!"
+            )
+            .into());
+        }
+
+        // TODO (#1095): decide what information, if any, to put in the comment
+        let data = RawKeyData::from(SecretBuffer::from(key_data.to_openssh_string("")?));
+        self.store(&name, &data)
+    }
+
+    fn remove(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<()>, Error> {
+        ctx.check()?;
+        let name = Self::entry_name_for_spec(key_spec, key_type)?;
+        let removed = block_on(self.transport.delete(&name)).map_err(|err| {
+            RemoteKeystoreError::Transport {
+                action: "delete",
+                name: name.clone(),
+                err,
+            }
+        })?;
+        Ok(removed.then_some(()))
+    }
+
+    fn list(&self, ctx: &OpContext) -> Result<Vec<(KeyPath, KeyType)>, Error> {
+        ctx.check()?;
+        let names =
+            block_on(self.transport.list()).map_err(|err| RemoteKeystoreError::Transport {
+                action: "list",
+                name: String::new(),
+                err,
+            })?;
+        names
+            .iter()
+            .map(|name| Self::parse_entry_name(name))
+            .collect()
+    }
+
+    fn get_raw(
+        &self,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<Option<RawKeyData>, Error> {
+        ctx.check()?;
+        let name = Self::entry_name_for_path(key_path, key_type)?;
+        self.fetch(&name)
+    }
+
+    fn insert_raw(
+        &self,
+        data: &RawKeyData,
+        key_path: &KeyPath,
+        key_type: &KeyType,
+        ctx: &OpContext,
+    ) -> Result<(), Error> {
+        ctx.check()?;
+        let name = Self::entry_name_for_path(key_path, key_type)?;
+        // We only store raw data that we'll be able to parse later.
+        data.check_parseable(key_type)?;
+        self.store(&name, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+
+    use futures::FutureExt as _;
+    use tor_basic_utils::test_rng::testing_rng;
+    use tor_error::{ErrorKind, HasKind as _};
+    use tor_llcrypto::pk::ed25519;
+
+    use super::*;
+    use crate::test_utils::TestSpecifier;
+
+    /// A secret store that keeps its entries in memory.
+    #[derive(Clone, Default)]
+    struct MemoryTransport {
+        /// The entries, by name.
+        entries: Arc<Mutex<BTreeMap<String, RawKeyData>>>,
+        /// If true, every request fails.
+        unreachable: Arc<Mutex<bool>>,
+    }
+
+    impl MemoryTransport {
+        /// Return an error if this transport has been made unreachable.
+        fn check(&self) -> Result<(), RemoteTransportError> {
+            if *self.unreachable.lock().unwrap() {
+                return Err(RemoteTransportError::Unreachable(Arc::new(
+                    std::io::Error::from(std::io::ErrorKind::ConnectionRefused),
+                )));
+            }
+            Ok(())
+        }
+    }
+
+    impl RemoteKeystoreTransport for MemoryTransport {
+        fn fetch<'a>(
+            &'a self,
+            name: &'a str,
+        ) -> BoxFuture<'a, Result<Option<RawKeyData>, RemoteTransportError>> {
+            async move {
+                self.check()?;
+                Ok(self.entries.lock().unwrap().get(name).cloned())
+            }
+            .boxed()
+        }
+
+        fn store<'a>(
+            &'a self,
+            name: &'a str,
+            data: &'a RawKeyData,
+        ) -> BoxFuture<'a, Result<(), RemoteTransportError>> {
+            async move {
+                self.check()?;
+                let _ = self
+                    .entries
+                    .lock()
+                    .unwrap()
+                    .insert(name.to_string(), data.clone());
+                Ok(())
+            }
+            .boxed()
+        }
+
+        fn delete<'a>(
+            &'a self,
+            name: &'a str,
+        ) -> BoxFuture<'a, Result<bool, RemoteTransportError>> {
+            async move {
+                self.check()?;
+                Ok(self.entries.lock().unwrap().remove(name).is_some())
+            }
+            .boxed()
+        }
+
+        fn list(&self) -> BoxFuture<'_, Result<Vec<String>, RemoteTransportError>> {
+            async move {
+                self.check()?;
+                Ok(self.entries.lock().unwrap().keys().cloned().collect())
+            }
+            .boxed()
+        }
+    }
+
+    fn keystore() -> (RemoteKeystore, MemoryTransport) {
+        let transport = MemoryTransport::default();
+        let keystore = RemoteKeystore::new("vault".into(), Box::new(transport.clone()));
+        (keystore, transport)
+    }
+
+    #[test]
+    fn insert_get_remove() {
+        let (keystore, transport) = keystore();
+        let key_spec = TestSpecifier::default();
+        let key_type = KeyType::Ed25519Keypair;
+        let keypair = ed25519::Keypair::generate(&mut testing_rng());
+
+        assert_eq!(keystore.id().to_string(), "vault");
+        assert!(!keystore
+            .contains(&key_spec, &key_type, &OpContext::default())
+            .unwrap());
+        assert!(keystore
+            .get(&key_spec, &key_type, &OpContext::default())
+            .unwrap()
+            .is_none());
+
+        keystore
+            .insert(&keypair, &key_spec, &key_type, &OpContext::default())
+            .unwrap();
+        let name = format!("{}.ed25519_private", TestSpecifier::path_prefix());
+        let entries: Vec<_> = transport.entries.lock().unwrap().keys().cloned().collect();
+        assert_eq!(entries, [name]);
+
+        assert!(keystore
+            .contains(&key_spec, &key_type, &OpContext::default())
+            .unwrap());
+        let key = keystore
+            .get(&key_spec, &key_type, &OpContext::default())
+            .unwrap()
+            .unwrap();
+        let Ok(key) = key.downcast::<ed25519::Keypair>() else {
+            panic!("failed to downcast key to ed25519::Keypair")
+        };
+        assert_eq!(key.verifying_key(), keypair.verifying_key());
+
+        let listed = keystore.list(&OpContext::default()).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].0, key_spec.arti_path().unwrap().into());
+        assert_eq!(listed[0].1, key_type);
+
+        // The raw contents can be copied to another key path.
+        let raw = keystore
+            .get_raw(&listed[0].0, &key_type, &OpContext::default())
+            .unwrap()
+            .unwrap();
+        let other_path: KeyPath = ArtiPath::new("other/key".into()).unwrap().into();
+        keystore
+            .insert_raw(&raw, &other_path, &key_type, &OpContext::default())
+            .unwrap();
+        assert_eq!(keystore.list(&OpContext::default()).unwrap().len(), 2);
+        assert!(keystore
+            .insert_raw(
+                &RawKeyData::new(b"not a key".to_vec()),
+                &other_path,
+                &key_type,
+                &OpContext::default()
+            )
+            .is_err());
+
+        assert_eq!(
+            keystore
+                .remove(&key_spec, &key_type, &OpContext::default())
+                .unwrap(),
+            Some(())
+        );
+        assert_eq!(
+            keystore
+                .remove(&key_spec, &key_type, &OpContext::default())
+                .unwrap(),
+            None
+        );
+        assert!(!keystore
+            .contains(&key_spec, &key_type, &OpContext::default())
+            .unwrap());
+    }
+
+    #[test]
+    fn errors() {
+        let (keystore, transport) = keystore();
+        let key_spec = TestSpecifier::default();
+
+        let _ = transport
+            .entries
+            .lock()
+            .unwrap()
+            .insert("no-extension".into(), RawKeyData::new(vec![]));
+        let err = keystore.list(&OpContext::default()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::KeystoreCorrupted);
+
+        *transport.unreachable.lock().unwrap() = true;
+        let Err(err) = keystore.get(&key_spec, &KeyType::Ed25519Keypair, &OpContext::default())
+        else {
+            panic!("got a key from an unreachable keystore?!")
+        };
+        assert_eq!(err.kind(), ErrorKind::KeystoreAccessFailed);
+    }
+}
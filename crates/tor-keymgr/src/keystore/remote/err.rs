@@ -0,0 +1,70 @@
+//! Error types for [`RemoteKeystore`](crate::RemoteKeystore).
+
+use std::sync::Arc;
+
+use tor_error::{ErrorKind, HasKind};
+
+use crate::KeystoreError;
+
+/// An error returned by a [`RemoteKeystoreTransport`](crate::RemoteKeystoreTransport).
+#[derive(thiserror::Error, Debug, Clone)]
+#[non_exhaustive]
+pub enum RemoteTransportError {
+    /// We couldn't reach the secret store.
+    #[error("Unable to reach secret store")]
+    Unreachable(#[source] Arc<dyn std::error::Error + Send + Sync>),
+
+    /// The secret store refused to perform the operation.
+    #[error("Secret store denied access")]
+    PermissionDenied(#[source] Arc<dyn std::error::Error + Send + Sync>),
+
+    /// The secret store failed in some other way.
+    #[error("Secret store error")]
+    Other(#[source] Arc<dyn std::error::Error + Send + Sync>),
+}
+
+/// An error returned by [`RemoteKeystore`](crate::RemoteKeystore)'s
+/// [`Keystore`](crate::Keystore) implementation.
+#[derive(thiserror::Error, Debug, Clone)]
+pub(crate) enum RemoteKeystoreError {
+    /// An error that occurred building an ArtiPath from a KeySpecifier
+    #[error("unable to build ArtiPath from KeySpecifier")]
+    ArtiPathUnavailableError(#[from] crate::key_specifier::ArtiPathUnavailableError),
+
+    /// The transport failed.
+    #[error("Failed to {action} entry {name:?} in remote keystore")]
+    Transport {
+        /// What we were trying to do.
+        action: &'static str,
+        /// The name of the entry, or an empty string if we were listing the entries.
+        name: String,
+        /// The underlying error.
+        #[source]
+        err: RemoteTransportError,
+    },
+
+    /// The secret store contains an entry whose name
+    /// is not an `ArtiPath` followed by a key type extension.
+    #[error("Remote keystore entry has malformed name {0:?}")]
+    MalformedEntryName(String),
+}
+
+impl KeystoreError for RemoteKeystoreError {}
+
+impl HasKind for RemoteKeystoreError {
+    fn kind(&self) -> ErrorKind {
+        use RemoteKeystoreError as E;
+
+        match self {
+            E::ArtiPathUnavailableError(_) => ErrorKind::Other,
+            E::Transport { .. } => ErrorKind::KeystoreAccessFailed,
+            E::MalformedEntryName(_) => ErrorKind::KeystoreCorrupted,
+        }
+    }
+}
+
+impl From<RemoteKeystoreError> for crate::Error {
+    fn from(e: RemoteKeystoreError) -> Self {
+        crate::Error::Keystore(Arc::new(e))
+    }
+}
@@ -70,21 +70,33 @@ pub use err::{
     ArtiPathSyntaxError, Error, KeystoreCorruptionError, KeystoreError, UnknownKeyTypeError,
 };
 pub use key_specifier::{
-    ArtiPathRange, ArtiPathUnavailableError, CTorPath, CTorServicePath,
-    InvalidKeyPathComponentValue, KeyPath, KeyPathError, KeyPathInfo, KeyPathInfoBuilder,
-    KeyPathInfoExtractor, KeyPathPattern, KeySpecifier, KeySpecifierComponent,
-    KeySpecifierComponentViaDisplayFromStr, KeySpecifierPattern,
+    ArtiPathPatternBuilder, ArtiPathPatternError, ArtiPathRange, ArtiPathUnavailableError,
+    CTorPath, CTorServicePath, InvalidKeyPathComponentValue, KeyPath, KeyPathError, KeyPathInfo,
+    KeyPathInfoBuilder, KeyPathInfoExtractor, KeyPathPattern, KeyPathPatternSet, KeyPathTemplate,
+    KeyPathTemplateError, KeyPathTranslator, KeySpecifier, KeySpecifierBuilder,
+    KeySpecifierComponent, KeySpecifierComponentViaDisplayFromStr, KeySpecifierPattern,
+    PatternKeyInfoExtractor, TemplateMatch, TemplatedKeySpecifier,
 };
 
 #[cfg(feature = "keymgr")]
 #[cfg_attr(docsrs, doc(cfg(feature = "keymgr")))]
 pub use {
-    keystore::arti::ArtiNativeKeystore,
-    keystore::Keystore,
-    mgr::{KeyMgr, KeyMgrBuilder, KeyMgrBuilderError, KeystoreEntry},
+    keystore::arti::{ArtiNativeKeystore, KeystoreMigrationStep, KeystoreUpgradePlan},
+    keystore::{CancellationToken, EntryLock, EntryMetadata, Keystore, OpContext, RawKeyData},
+    mgr::{
+        BundledKey, ConflictPolicy, CopyOutcome, KeyAccessEvent, KeyAccessOutcome, KeyAuditor,
+        KeyBundle, KeyBundleError, KeyMgr, KeyMgrBuilder, KeyMgrBuilderError, KeyOperation,
+        KeystoreEntry, KeystoreEntryInfo, RotationEvent, RotationPolicy, RotationPolicyBuilder,
+        RotationPolicyBuilderError, SweepOutcome, SyncedEntry, TracingKeyAuditor,
+        UnrecognizedEntry,
+    },
     ssh_key,
 };
 
+#[cfg(feature = "watch")]
+#[cfg_attr(docsrs, doc(cfg(feature = "watch")))]
+pub use mgr::{KeyChange, KeyChangeKind, KeyChanges};
+
 #[cfg(all(feature = "keymgr", feature = "ephemeral-keystore"))]
 #[cfg_attr(
     docsrs,
@@ -92,6 +104,27 @@ pub use {
 )]
 pub use keystore::ephemeral::ArtiEphemeralKeystore;
 
+#[cfg(all(feature = "keymgr", feature = "remote-keystore"))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "keymgr", feature = "remote-keystore"))))]
+pub use keystore::remote::{err::RemoteTransportError, RemoteKeystore, RemoteKeystoreTransport};
+
+#[cfg(all(feature = "keymgr", feature = "encrypted-keystore"))]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(all(feature = "keymgr", feature = "encrypted-keystore")))
+)]
+pub use keystore::arti::encrypted::{
+    ArtiEncryptedKeystore, ConfiguredPassphrase, PassphrasePrompt,
+};
+
+#[cfg(all(feature = "keymgr", feature = "tpm-keystore"))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "keymgr", feature = "tpm-keystore"))))]
+pub use keystore::arti::tpm::{err::TpmError, ArtiTpmKeystore, TpmSealer};
+
+#[cfg(all(feature = "keymgr", feature = "os-keystore"))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "keymgr", feature = "os-keystore"))))]
+pub use keystore::arti::encrypted::os::OsCredentialStore;
+
 #[cfg(all(feature = "keymgr", feature = "ctor-keystore"))]
 #[cfg_attr(docsrs, doc(cfg(all(feature = "keymgr", feature = "ctor-keystore"))))]
 pub use keystore::ctor::{CTorClientKeystore, CTorServiceKeystore};
@@ -100,8 +133,8 @@ pub use keystore::ctor::{CTorClientKeystore, CTorServiceKeystore};
 pub use key_specifier::derive as key_specifier_derive;
 
 pub use tor_key_forge::{
-    EncodableKey, ErasedKey, KeyType, Keygen, KeygenRng, SshKeyAlgorithm, SshKeyData,
-    ToEncodableKey,
+    Ed25519Signer, Ed25519SignerAdapter, EncodableKey, ErasedKey, KeyType, Keygen, KeygenRng,
+    SshKeyAlgorithm, SshKeyData, ToEncodableKey,
 };
 
 derive_deftly::template_export_semver_check! { "0.12.1" }
@@ -2,16 +2,45 @@
 //!
 //! See the [`KeyMgr`] docs for more details.
 
+mod audit;
+mod bundle;
+#[cfg(feature = "cert")]
+mod cert;
+mod copy;
+mod expiry;
+mod gc;
+mod openssh;
+mod rotate;
+#[cfg(feature = "watch")]
+mod watch;
+
+pub use audit::{KeyAccessEvent, KeyAccessOutcome, KeyAuditor, KeyOperation, TracingKeyAuditor};
+pub use bundle::{BundledKey, KeyBundle, KeyBundleError};
+pub use copy::{ConflictPolicy, CopyOutcome, SyncedEntry};
+pub use gc::SweepOutcome;
+pub use rotate::{
+    RotationEvent, RotationPolicy, RotationPolicyBuilder, RotationPolicyBuilderError,
+};
+#[cfg(feature = "watch")]
+pub use watch::{KeyChange, KeyChangeKind, KeyChanges};
+
 use crate::{
-    BoxedKeystore, KeyPath, KeyPathError, KeyPathInfo, KeyPathInfoExtractor, KeyPathPattern,
-    KeySpecifier, KeystoreId, KeystoreSelector, Result,
+    BoxedKeystore, EntryMetadata, KeyPath, KeyPathError, KeyPathInfo, KeyPathInfoExtractor,
+    KeyPathPattern, KeyPathTranslator, KeySpecifier, KeystoreId, KeystoreSelector, OpContext,
+    RawKeyData, Result,
 };
 
+use audit::key_path_of;
 use itertools::Itertools;
 use std::iter;
+use std::panic::Location;
 use std::result::Result as StdResult;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tor_basic_utils::alloc_tag;
 use tor_error::{bad_api_usage, internal};
-use tor_key_forge::{EncodableKey, KeyType, Keygen, KeygenRng, ToEncodableKey};
+use tor_key_forge::{Ed25519Signer, EncodableKey, KeyType, Keygen, KeygenRng, ToEncodableKey};
+use tracing::debug;
 
 /// A key manager that acts as a frontend to a primary [`Keystore`](crate::Keystore) and
 /// any number of secondary [`Keystore`](crate::Keystore)s.
@@ -36,6 +65,45 @@ use tor_key_forge::{EncodableKey, KeyType, Keygen, KeygenRng, ToEncodableKey};
 /// their outcome depends on whether the selected key store
 /// [`contains`][crate::Keystore::contains]
 /// the specified key (and thus suffers from a TOCTOU race).
+/// If several processes (or tasks) might try to generate the same key at the same time,
+/// use [`KeyMgr::get_or_generate_with_lock`] instead.
+///
+/// ## Auditing
+///
+/// If a [`KeyAuditor`] is registered using [`KeyMgrBuilder::auditor`],
+/// every key read, write, and removal is reported to it as a [`KeyAccessEvent`].
+///
+/// ## Garbage collection
+///
+/// Keys that are no longer needed (such as the blinded keys of past time periods)
+/// can be removed using [`KeyMgr::sweep`].
+/// Entries this version of Arti doesn't recognize are only ever removed by
+/// [`KeyMgr::remove_unchecked`], which must be enabled using
+/// [`KeyMgrBuilder::allow_unchecked_removal`].
+///
+/// Entries can also be given an expiration time, using [`KeyMgr::set_expiry`]
+/// (for example, to make a client authorization key time-limited).
+/// Expired entries are removed by [`KeyMgr::sweep_expired`].
+///
+/// ## Certificates
+///
+/// With the `cert` feature, the key stores can also hold Tor ed25519 certificates
+/// (for example, the certificates binding a blinded onion service identity key
+/// to a descriptor signing key), in entries of type [`KeyType::Ed25519TorCert`].
+/// See [`KeyMgr::insert_cert`], [`KeyMgr::get_cert`], and [`KeyMgr::list_certs`].
+///
+/// ## Timeouts
+///
+/// Key store operations can be given a time limit,
+/// using [`KeyMgrBuilder::operation_timeout`].
+/// Operations that take longer (for example, because the key store is on
+/// an unreachable network mount) fail with [`Error::Timeout`](crate::Error::Timeout).
+///
+/// ## Watching for changes
+///
+/// With the `watch` feature,
+/// [`KeyMgr::watch`] returns a stream of the keys that are added, removed, or replaced,
+/// out of those matching a [`KeyPathPatternSet`](crate::KeyPathPatternSet).
 #[derive(derive_builder::Builder)]
 #[builder(pattern = "owned", build_fn(private, name = "build_unvalidated"))]
 pub struct KeyMgr {
@@ -50,6 +118,37 @@ pub struct KeyMgr {
     /// using `inventory`.
     #[builder(default, setter(skip))]
     key_info_extractors: Vec<&'static dyn KeyPathInfoExtractor>,
+    /// The key path translators.
+    ///
+    /// These are initialized internally by [`KeyMgrBuilder::build`], using the values collected
+    /// using `inventory`.
+    #[builder(default, setter(skip))]
+    key_path_translators: Vec<&'static dyn KeyPathTranslator>,
+    /// The key rotation policies, in the order they were registered.
+    #[builder(default, setter(custom))]
+    rotation_policies: Vec<(KeyPathPattern, RotationPolicy)>,
+    /// The key rotation state.
+    #[builder(default, setter(skip))]
+    rotation: rotate::RotationTracker,
+    /// The streams returned by [`KeyMgr::watch`].
+    #[cfg(feature = "watch")]
+    #[builder(default, setter(skip))]
+    watchers: watch::WatchTracker,
+    /// The auditor to report key accesses to, if any.
+    #[builder(default, setter(custom))]
+    auditor: Option<Arc<dyn KeyAuditor>>,
+    /// Whether to allow [`KeyMgr::remove_unchecked`] to remove
+    /// the entries this version of Arti doesn't recognize.
+    ///
+    /// Disabled by default.
+    #[builder(default)]
+    allow_unchecked_removal: bool,
+    /// How long each key store operation may take
+    /// before failing with [`Error::Timeout`](crate::Error::Timeout).
+    ///
+    /// Unlimited by default.
+    #[builder(default, setter(strip_option))]
+    operation_timeout: Option<Duration>,
 }
 
 /// A keystore entry descriptor.
@@ -70,6 +169,63 @@ pub struct KeystoreEntry<'a> {
     keystore_id: &'a KeystoreId,
 }
 
+/// Information about a keystore entry.
+///
+/// Returned from [`KeyMgr::list`] and [`KeyMgr::entry_info`].
+#[derive(Clone, Debug, PartialEq, amplify::Getters)]
+pub struct KeystoreEntryInfo<'a> {
+    /// The entry, including the ID of the keystore it is in.
+    entry: KeystoreEntry<'a>,
+    /// What the entry's keystore knows about it
+    /// (when it was created and last written, and where it is stored).
+    ///
+    /// See [`Keystore::metadata`](crate::Keystore::metadata).
+    metadata: EntryMetadata,
+    /// The time at which the entry expires, if it has an expiration time.
+    ///
+    /// See [`KeyMgr::set_expiry`].
+    #[getter(as_copy)]
+    expires: Option<SystemTime>,
+    /// Whether this version of Arti recognizes the entry.
+    ///
+    /// Entries that are not recognized are also returned by [`KeyMgr::list_unrecognized`].
+    #[getter(as_copy)]
+    recognized: bool,
+}
+
+impl KeystoreEntryInfo<'_> {
+    /// The time at which the entry was created,
+    /// if its keystore knows.
+    ///
+    /// See [`Keystore::created`](crate::Keystore::created).
+    pub fn created(&self) -> Option<SystemTime> {
+        self.metadata.created
+    }
+}
+
+/// A keystore entry this version of Arti does not know how to use.
+///
+/// This is an entry with a valid [`KeyPath`], but whose [`KeyType`] is unknown,
+/// and/or whose [`KeyPath`] is not recognized by any of the registered
+/// [`KeyPathInfoExtractor`]s (typically, because it was written by a newer version of Arti).
+///
+/// Such entries cannot be retrieved using [`KeyMgr::get`]:
+/// use [`KeyMgr::get_raw_entry`] instead.
+///
+/// Returned from [`KeyMgr::list_unrecognized`].
+#[derive(Clone, Debug, PartialEq, amplify::Getters)]
+pub struct UnrecognizedEntry<'a> {
+    /// The entry.
+    entry: KeystoreEntry<'a>,
+    /// Whether the [`KeyType`] of the entry is unknown.
+    #[getter(as_copy)]
+    unknown_key_type: bool,
+    /// Whether the [`KeyPath`] of the entry is not recognized
+    /// by any of the registered [`KeyPathInfoExtractor`]s.
+    #[getter(as_copy)]
+    unknown_key_path: bool,
+}
+
 impl KeyMgrBuilder {
     /// Construct a [`KeyMgr`] from this builder.
     pub fn build(self) -> StdResult<KeyMgr, KeyMgrBuilderError> {
@@ -87,6 +243,10 @@ impl KeyMgrBuilder {
             .into_iter()
             .copied()
             .collect();
+        keymgr.key_path_translators = inventory::iter::<&'static dyn KeyPathTranslator>
+            .into_iter()
+            .copied()
+            .collect();
 
         Ok(keymgr)
     }
@@ -125,9 +285,29 @@ impl KeyMgrBuilder {
     pub fn opt_secondary_stores_mut(&mut self) -> &mut Option<Vec<BoxedKeystore>> {
         &mut self.secondary_stores
     }
+
+    /// Register a [`RotationPolicy`] for the keys whose paths match `pattern`.
+    ///
+    /// If a key matches the patterns of several policies,
+    /// the policy registered first is used.
+    pub fn rotation_policy(mut self, pattern: KeyPathPattern, policy: RotationPolicy) -> Self {
+        self.rotation_policies
+            .get_or_insert(Default::default())
+            .push((pattern, policy));
+        self
+    }
+
+    /// Report every key access to `auditor`.
+    ///
+    /// See [`KeyAuditor`].
+    pub fn auditor(mut self, auditor: Arc<dyn KeyAuditor>) -> Self {
+        self.auditor = Some(Some(auditor));
+        self
+    }
 }
 
 inventory::collect!(&'static dyn crate::KeyPathInfoExtractor);
+inventory::collect!(&'static dyn crate::KeyPathTranslator);
 
 impl KeyMgr {
     /// Read a key from one of the key stores, and try to deserialize it as `K::Key`.
@@ -136,13 +316,43 @@ impl KeyMgr {
     /// specifier.
     ///
     /// Returns `Ok(None)` if none of the key stores have the requested key.
+    #[track_caller]
     pub fn get<K: ToEncodableKey>(&self, key_spec: &dyn KeySpecifier) -> Result<Option<K>> {
-        let result = self.get_from_store(key_spec, &K::Key::key_type(), self.all_stores())?;
+        let _tag = alloc_tag::enter(alloc_tag::Subsystem::Keystore);
+        let caller = Location::caller();
+        let result = self.get_unaudited::<K>(key_spec);
+        let keystore_id = match &result {
+            Ok(Some((_, id))) => Some(*id),
+            _ => None,
+        };
+
+        self.audit(
+            KeyOperation::Get,
+            || key_path_of(key_spec),
+            &K::Key::key_type(),
+            keystore_id,
+            result.map(|k| k.map(|(k, _)| k)),
+            Option::is_some,
+            caller,
+        )
+    }
+
+    /// Like [`KeyMgr::get`], but without reporting the access to our [`KeyAuditor`].
+    ///
+    /// Also returns the ID of the key store the key was found in.
+    fn get_unaudited<K: ToEncodableKey>(
+        &self,
+        key_spec: &dyn KeySpecifier,
+    ) -> Result<Option<(K, &KeystoreId)>> {
+        let result =
+            self.get_from_store_with_id(key_spec, &K::Key::key_type(), self.all_stores())?;
         if result.is_none() {
             // If the key_spec is the specifier for the public part of a keypair,
             // try getting the pair and extracting the public portion from it.
             if let Some(key_pair_spec) = key_spec.keypair_specifier() {
-                return Ok(self.get::<K::KeyPair>(&*key_pair_spec)?.map(|k| k.into()));
+                return Ok(self
+                    .get_unaudited::<K::KeyPair>(&*key_pair_spec)?
+                    .map(|(k, id)| (k.into(), id)));
             }
         }
         Ok(result)
@@ -155,10 +365,45 @@ impl KeyMgr {
     /// Returns `Ok(None)` if the key store does not contain the requested entry.
     ///
     /// Returns an error if the specified `key_type` does not match `K::Key::key_type()`.
+    #[track_caller]
     pub fn get_entry<K: ToEncodableKey>(&self, entry: &KeystoreEntry) -> Result<Option<K>> {
+        let caller = Location::caller();
         let selector = entry.keystore_id().into();
-        let store = self.select_keystore(&selector)?;
-        self.get_from_store(entry.key_path(), entry.key_type(), [store].into_iter())
+        let result = self.select_keystore(&selector).and_then(|store| {
+            self.get_from_store(entry.key_path(), entry.key_type(), [store].into_iter())
+        });
+
+        self.audit(
+            KeyOperation::Get,
+            || Some(entry.key_path().clone()),
+            entry.key_type(),
+            Some(entry.keystore_id()),
+            result,
+            Option::is_some,
+            caller,
+        )
+    }
+
+    /// Return an [`Ed25519Signer`] for the ed25519 keypair identified by `key_spec`.
+    ///
+    /// The signer is obtained from the first key store that contains an entry for the given
+    /// specifier. Unlike [`get`](KeyMgr::get), this works with key stores that do not allow
+    /// their keys to be extracted, so it is the preferred way to sign certificates
+    /// (see `tor_key_forge::cert`).
+    ///
+    /// Returns `Ok(None)` if the key does not exist in any of the key stores,
+    /// and an error if `K::Key` is not an ed25519 keypair type.
+    pub fn get_ed25519_signer<K: ToEncodableKey>(
+        &self,
+        key_spec: &dyn KeySpecifier,
+    ) -> Result<Option<Box<dyn Ed25519Signer>>> {
+        let key_type = K::Key::key_type();
+        for store in self.all_stores() {
+            if let Some(signer) = store.ed25519_signer(key_spec, &key_type, &self.op_ctx())? {
+                return Ok(Some(signer));
+            }
+        }
+        Ok(None)
     }
 
     /// Read the key identified by `key_spec`.
@@ -172,6 +417,7 @@ impl KeyMgr {
     ///
     /// This is a convenience wrapper around [`get()`](KeyMgr::get) and
     /// [`generate()`](KeyMgr::generate).
+    #[track_caller]
     pub fn get_or_generate<K>(
         &self,
         key_spec: &dyn KeySpecifier,
@@ -188,6 +434,43 @@ impl KeyMgr {
         }
     }
 
+    /// Like [`get_or_generate()`](KeyMgr::get_or_generate), but hold an advisory lock
+    /// on the key in the key store specified by `selector` while doing so.
+    ///
+    /// This prevents several `KeyMgr`s (possibly in different processes) that share
+    /// a key store from each generating a different key, and overwriting one another's.
+    /// Every user of the key must use this function for this to work.
+    ///
+    /// This blocks until the lock is available.
+    ///
+    /// The lock is taken using [`Keystore::lock_entry`](crate::Keystore::lock_entry).
+    /// If the selected key store doesn't support locking, this is equivalent
+    /// to [`get_or_generate()`](KeyMgr::get_or_generate).
+    #[track_caller]
+    pub fn get_or_generate_with_lock<K>(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        selector: KeystoreSelector,
+        rng: &mut dyn KeygenRng,
+    ) -> Result<K>
+    where
+        K: ToEncodableKey,
+        K::Key: Keygen,
+    {
+        let store = self.select_keystore(&selector)?;
+        let lock = store.lock_entry(key_spec, &K::Key::key_type(), &self.op_ctx())?;
+        if lock.is_none() {
+            debug!(
+                "keystore {} doesn't support locking; generating key without a lock",
+                store.id()
+            );
+        }
+
+        let key = self.get_or_generate(key_spec, selector, rng);
+        drop(lock);
+        key
+    }
+
     /// Generate a new key of type `K`, and insert it into the key store specified by `selector`.
     ///
     /// If the key already exists in the specified key store, the `overwrite` flag is used to
@@ -204,10 +487,11 @@ impl KeyMgr {
     /// [`contains`][crate::Keystore::contains] the specified key, and thus suffers from a TOCTOU race.
     //
     // TODO (#1119): can we make this less racy without a lock? Perhaps we should say we'll always
-    // overwrite any existing keys.
+    // overwrite any existing keys. (get_or_generate_with_lock uses a lock.)
     //
     // TODO: consider replacing the overwrite boolean with a GenerateOptions type
     // (sort of like std::fs::OpenOptions)
+    #[track_caller]
     pub fn generate<K>(
         &self,
         key_spec: &dyn KeySpecifier,
@@ -219,17 +503,32 @@ impl KeyMgr {
         K: ToEncodableKey,
         K::Key: Keygen,
     {
-        let store = self.select_keystore(&selector)?;
+        let _tag = alloc_tag::enter(alloc_tag::Subsystem::Keystore);
+        let caller = Location::caller();
         let key_type = K::Key::key_type();
+        let store = self.select_keystore(&selector);
+        let keystore_id = store.as_ref().ok().map(|&store| store.id());
 
-        if overwrite || !store.contains(key_spec, &key_type)? {
-            let key = K::Key::generate(rng)?;
-            store.insert(&key, key_spec, &key_type)?;
+        let result = store.and_then(|store| {
+            if overwrite || !store.contains(key_spec, &key_type, &self.op_ctx())? {
+                let key = K::Key::generate(rng)?;
+                store.insert(&key, key_spec, &key_type, &self.op_ctx())?;
 
-            Ok(K::from_encodable_key(key))
-        } else {
-            Err(crate::Error::KeyAlreadyExists)
-        }
+                Ok(K::from_encodable_key(key))
+            } else {
+                Err(crate::Error::KeyAlreadyExists)
+            }
+        });
+
+        self.audit(
+            KeyOperation::Generate,
+            || key_path_of(key_spec),
+            &key_type,
+            keystore_id,
+            result,
+            |_| true,
+            caller,
+        )
     }
 
     /// Insert `key` into the [`Keystore`](crate::Keystore) specified by `selector`.
@@ -244,6 +543,7 @@ impl KeyMgr {
     ///
     /// Returns an error if the selected keystore is not the primary keystore or one of the
     /// configured secondary stores.
+    #[track_caller]
     pub fn insert<K: ToEncodableKey>(
         &self,
         key: K,
@@ -251,17 +551,34 @@ impl KeyMgr {
         selector: KeystoreSelector,
         overwrite: bool,
     ) -> Result<Option<K>> {
+        let _tag = alloc_tag::enter(alloc_tag::Subsystem::Keystore);
+        let caller = Location::caller();
         let key = key.to_encodable_key();
-        let store = self.select_keystore(&selector)?;
         let key_type = K::Key::key_type();
-        let old_key: Option<K> = self.get_from_store(key_spec, &key_type, [store].into_iter())?;
+        let store = self.select_keystore(&selector);
+        let keystore_id = store.as_ref().ok().map(|&store| store.id());
+
+        let result = store.and_then(|store| {
+            let old_key: Option<K> =
+                self.get_from_store(key_spec, &key_type, [store].into_iter())?;
+
+            if old_key.is_some() && !overwrite {
+                Err(crate::Error::KeyAlreadyExists)
+            } else {
+                let () = store.insert(&key, key_spec, &key_type, &self.op_ctx())?;
+                Ok(old_key)
+            }
+        });
 
-        if old_key.is_some() && !overwrite {
-            Err(crate::Error::KeyAlreadyExists)
-        } else {
-            let () = store.insert(&key, key_spec, &key_type)?;
-            Ok(old_key)
-        }
+        self.audit(
+            KeyOperation::Insert,
+            || key_path_of(key_spec),
+            &key_type,
+            keystore_id,
+            result,
+            |_| true,
+            caller,
+        )
     }
 
     /// Remove the key identified by `key_spec` from the [`Keystore`](crate::Keystore)
@@ -274,18 +591,35 @@ impl KeyMgr {
     /// or `Ok(None)` if the key does not exist in the requested keystore.
     ///
     /// Returns `Err` if an error occurred while trying to remove the key.
+    #[track_caller]
     pub fn remove<K: ToEncodableKey>(
         &self,
         key_spec: &dyn KeySpecifier,
         selector: KeystoreSelector,
     ) -> Result<Option<K>> {
-        let store = self.select_keystore(&selector)?;
+        let caller = Location::caller();
         let key_type = K::Key::key_type();
-        let old_key: Option<K> = self.get_from_store(key_spec, &key_type, [store].into_iter())?;
+        let store = self.select_keystore(&selector);
+        let keystore_id = store.as_ref().ok().map(|&store| store.id());
+
+        let result = store.and_then(|store| {
+            let old_key: Option<K> =
+                self.get_from_store(key_spec, &key_type, [store].into_iter())?;
 
-        store.remove(key_spec, &key_type)?;
+            store.remove(key_spec, &key_type, &self.op_ctx())?;
 
-        Ok(old_key)
+            Ok(old_key)
+        });
+
+        self.audit(
+            KeyOperation::Remove,
+            || key_path_of(key_spec),
+            &key_type,
+            keystore_id,
+            result,
+            Option::is_some,
+            caller,
+        )
     }
 
     /// Remove the specified keystore entry.
@@ -299,11 +633,33 @@ impl KeyMgr {
     //
     // This probably will involve changing the return type of Keystore::remove
     // to Result<Option<ErasedKey>>.
+    #[track_caller]
     pub fn remove_entry(&self, entry: &KeystoreEntry) -> Result<Option<()>> {
-        let selector = entry.keystore_id().into();
-        let store = self.select_keystore(&selector)?;
+        self.remove_entry_from(entry, Location::caller())
+    }
 
-        store.remove(entry.key_path(), entry.key_type())
+    /// Remove the specified keystore entry, on behalf of `caller`.
+    ///
+    /// This is the implementation of [`KeyMgr::remove_entry`].
+    fn remove_entry_from(
+        &self,
+        entry: &KeystoreEntry,
+        caller: &'static Location<'static>,
+    ) -> Result<Option<()>> {
+        let selector = entry.keystore_id().into();
+        let result = self
+            .select_keystore(&selector)
+            .and_then(|store| store.remove(entry.key_path(), entry.key_type(), &self.op_ctx()));
+
+        self.audit(
+            KeyOperation::Remove,
+            || Some(entry.key_path().clone()),
+            entry.key_type(),
+            Some(entry.keystore_id()),
+            result,
+            Option::is_some,
+            caller,
+        )
     }
 
     /// Return the keystore entry descriptors of the keys matching the specified [`KeyPathPattern`].
@@ -313,7 +669,7 @@ impl KeyMgr {
         self.all_stores()
             .map(|store| -> Result<Vec<_>> {
                 Ok(store
-                    .list()?
+                    .list(&self.op_ctx())?
                     .into_iter()
                     .filter(|(key_path, _): &(KeyPath, KeyType)| key_path.matches(pat))
                     .map(|(path, key_type)| KeystoreEntry {
@@ -327,6 +683,232 @@ impl KeyMgr {
             .collect::<Result<Vec<_>>>()
     }
 
+    /// Return information about every entry in every keystore.
+    ///
+    /// Entries are listed keystore by keystore,
+    /// starting with the primary keystore.
+    /// Unlike [`KeyMgr::list_matching`],
+    /// this includes entries this version of Arti does not recognize.
+    pub fn list(&self) -> Result<Vec<KeystoreEntryInfo<'_>>> {
+        self.all_stores()
+            .map(|store| -> Result<Vec<_>> {
+                store
+                    .list(&self.op_ctx())?
+                    .into_iter()
+                    .map(|(key_path, key_type)| {
+                        // If the entry was removed since we listed it,
+                        // report it anyway, without any metadata.
+                        let metadata = store
+                            .metadata(&key_path, &key_type, &self.op_ctx())?
+                            .unwrap_or_default();
+                        self.entry_info_in(store, key_path, key_type, metadata)
+                    })
+                    .collect()
+            })
+            .flatten_ok()
+            .collect()
+    }
+
+    /// Return information about the specified keystore entry,
+    /// such as when it was created and last written, and where it is stored.
+    ///
+    /// This is the same information [`KeyMgr::list`] returns for the entry,
+    /// so it can be used to learn about a key obtained with [`KeyMgr::get_entry`].
+    ///
+    /// Returns `Ok(None)` if the key store does not contain the specified entry.
+    pub fn entry_info(&self, entry: &KeystoreEntry) -> Result<Option<KeystoreEntryInfo<'_>>> {
+        let store = self.select_keystore(&entry.keystore_id().into())?;
+        let Some(metadata) = store.metadata(entry.key_path(), entry.key_type(), &self.op_ctx())?
+        else {
+            return Ok(None);
+        };
+
+        self.entry_info_in(
+            store,
+            entry.key_path().clone(),
+            entry.key_type().clone(),
+            metadata,
+        )
+        .map(Some)
+    }
+
+    /// Build the [`KeystoreEntryInfo`] of an entry of `store`, given its `metadata`.
+    fn entry_info_in<'a>(
+        &self,
+        store: &'a BoxedKeystore,
+        key_path: KeyPath,
+        key_type: KeyType,
+        metadata: EntryMetadata,
+    ) -> Result<KeystoreEntryInfo<'a>> {
+        let expires = store.expires(&key_path, &key_type, &self.op_ctx())?;
+        let entry = KeystoreEntry {
+            key_path,
+            key_type,
+            keystore_id: store.id(),
+        };
+        let (unknown_key_type, unknown_key_path) = self.unrecognized(&entry);
+        Ok(KeystoreEntryInfo {
+            entry,
+            metadata,
+            expires,
+            recognized: !(unknown_key_type || unknown_key_path),
+        })
+    }
+
+    /// Return the entries that this version of Arti does not recognize.
+    ///
+    /// See [`UnrecognizedEntry`] for what makes an entry unrecognized.
+    ///
+    /// NOTE: This searches for unrecognized entries in _all_ keystores.
+    pub fn list_unrecognized(&self) -> Result<Vec<UnrecognizedEntry<'_>>> {
+        let entries = self
+            .all_stores()
+            .map(|store| -> Result<Vec<_>> {
+                Ok(store
+                    .list(&self.op_ctx())?
+                    .into_iter()
+                    .filter(|(key_path, _)| key_path.arti().is_some())
+                    .map(|(key_path, key_type)| KeystoreEntry {
+                        key_path,
+                        key_type,
+                        keystore_id: store.id(),
+                    })
+                    .collect())
+            })
+            .flatten_ok()
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(entries
+            .into_iter()
+            .filter_map(|entry| {
+                let (unknown_key_type, unknown_key_path) = self.unrecognized(&entry);
+
+                (unknown_key_type || unknown_key_path).then_some(UnrecognizedEntry {
+                    entry,
+                    unknown_key_type,
+                    unknown_key_path,
+                })
+            })
+            .collect())
+    }
+
+    /// Return whether the [`KeyType`] and the [`KeyPath`] of `entry` are unknown to us.
+    ///
+    /// Only entries with an [`ArtiPath`](crate::ArtiPath) can have an unknown [`KeyPath`].
+    fn unrecognized(&self, entry: &KeystoreEntry) -> (bool, bool) {
+        let unknown_key_type = matches!(entry.key_type, KeyType::Unknown { .. });
+        let unknown_key_path =
+            entry.key_path.arti().is_some() && self.describe(&entry.key_path).is_err();
+
+        (unknown_key_type, unknown_key_path)
+    }
+
+    /// Retrieve the raw, unparsed contents of the specified keystore entry.
+    ///
+    /// Unlike [`KeyMgr::get_entry`], this works for any entry,
+    /// including entries of an unknown [`KeyType`].
+    ///
+    /// Returns `Ok(None)` if the key store does not contain the requested entry.
+    #[track_caller]
+    pub fn get_raw_entry(&self, entry: &KeystoreEntry) -> Result<Option<RawKeyData>> {
+        let caller = Location::caller();
+        let selector = entry.keystore_id().into();
+        let result = self
+            .select_keystore(&selector)
+            .and_then(|store| store.get_raw(entry.key_path(), entry.key_type(), &self.op_ctx()));
+
+        self.audit(
+            KeyOperation::Get,
+            || Some(entry.key_path().clone()),
+            entry.key_type(),
+            Some(entry.keystore_id()),
+            result,
+            Option::is_some,
+            caller,
+        )
+    }
+
+    /// Copy the specified keystore entry, as-is, to the key store specified by `selector`.
+    ///
+    /// The entry is copied without being parsed,
+    /// so this can be used to migrate entries this version of Arti doesn't recognize.
+    ///
+    /// If the entry already exists in the destination key store,
+    /// the `overwrite` flag is used to decide whether to overwrite it.
+    ///
+    /// Returns `Ok(None)` if the source key store does not contain the requested entry,
+    /// and `Ok(Some(()))` if the entry was copied.
+    ///
+    /// Returns [`Error::KeyAlreadyExists`](crate::Error::KeyAlreadyExists)
+    /// if the entry already exists in the destination key store and `overwrite` is `false`.
+    #[track_caller]
+    pub fn copy_raw_entry(
+        &self,
+        entry: &KeystoreEntry,
+        selector: KeystoreSelector,
+        overwrite: bool,
+    ) -> Result<Option<()>> {
+        let caller = Location::caller();
+        let Some(data) = self.get_raw_entry(entry)? else {
+            return Ok(None);
+        };
+
+        let dest = self.select_keystore(&selector);
+        let keystore_id = dest.as_ref().ok().map(|&dest| dest.id());
+
+        let result = dest.and_then(|dest| {
+            if !overwrite
+                && dest
+                    .get_raw(entry.key_path(), entry.key_type(), &self.op_ctx())?
+                    .is_some()
+            {
+                return Err(crate::Error::KeyAlreadyExists);
+            }
+
+            dest.insert_raw(&data, entry.key_path(), entry.key_type(), &self.op_ctx())?;
+            Ok(Some(()))
+        });
+
+        self.audit(
+            KeyOperation::Insert,
+            || Some(entry.key_path().clone()),
+            entry.key_type(),
+            keystore_id,
+            result,
+            |_| true,
+            caller,
+        )
+    }
+
+    /// Unlock the key store specified by `selector`, using `passphrase`.
+    ///
+    /// Returns an error if `passphrase` is incorrect.
+    /// Key stores that don't encrypt their keys are always unlocked,
+    /// and ignore the passphrase.
+    ///
+    /// See [`Keystore::unlock`](crate::Keystore::unlock).
+    pub fn unlock(&self, selector: KeystoreSelector, passphrase: &str) -> Result<()> {
+        self.select_keystore(&selector)?.unlock(passphrase)
+    }
+
+    /// Lock every key store that supports locking.
+    ///
+    /// See [`Keystore::lock`](crate::Keystore::lock).
+    pub fn lock_all(&self) {
+        self.all_stores().for_each(|store| store.lock());
+    }
+
+    /// Return the identifiers of the key stores that are currently locked.
+    ///
+    /// The keys of these stores can't be read or written until they are
+    /// [unlocked](KeyMgr::unlock).
+    pub fn locked_keystores(&self) -> Vec<&KeystoreId> {
+        self.all_stores()
+            .filter(|store| store.is_locked())
+            .map(|store| store.id())
+            .collect()
+    }
+
     /// Describe the specified key.
     ///
     /// Returns [`KeyPathError::Unrecognized`] if none of the registered
@@ -354,6 +936,19 @@ impl KeyMgr {
         key_type: &KeyType,
         stores: impl Iterator<Item = &'a BoxedKeystore>,
     ) -> Result<Option<K>> {
+        Ok(self
+            .get_from_store_with_id(key_spec, key_type, stores)?
+            .map(|(k, _)| k))
+    }
+
+    /// Like [`KeyMgr::get_from_store`], but also return the ID of the key store
+    /// the key was found in.
+    fn get_from_store_with_id<'a, K: ToEncodableKey>(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        key_type: &KeyType,
+        stores: impl Iterator<Item = &'a BoxedKeystore>,
+    ) -> Result<Option<(K, &'a KeystoreId)>> {
         let static_key_type = K::Key::key_type();
         if key_type != &static_key_type {
             return Err(internal!(
@@ -365,7 +960,7 @@ impl KeyMgr {
         }
 
         for store in stores {
-            let key = match store.get(key_spec, &K::Key::key_type()) {
+            let key = match store.get(key_spec, &K::Key::key_type(), &self.op_ctx()) {
                 Ok(None) => {
                     // The key doesn't exist in this store, so we check the next one...
                     continue;
@@ -383,12 +978,23 @@ impl KeyMgr {
                 .map(|k| *k)
                 .map_err(|_| internal!("failed to downcast key to requested type"))?;
 
-            return Ok(Some(K::from_encodable_key(key)));
+            return Ok(Some((K::from_encodable_key(key), store.id())));
         }
 
         Ok(None)
     }
 
+    /// Return the [`OpContext`] for a new key store operation.
+    ///
+    /// Each key store operation gets its own deadline,
+    /// so this should be called once per operation.
+    fn op_ctx(&self) -> OpContext {
+        match self.operation_timeout {
+            Some(timeout) => OpContext::new().with_timeout(timeout),
+            None => OpContext::new(),
+        }
+    }
+
     /// Return an iterator over all configured stores.
     fn all_stores(&self) -> impl Iterator<Item = &BoxedKeystore> {
         iter::once(&self.primary_store).chain(self.secondary_stores.iter())
@@ -436,9 +1042,9 @@ mod tests {
     use std::collections::HashMap;
     use std::result::Result as StdResult;
     use std::str::FromStr;
-    use std::sync::RwLock;
+    use std::sync::{mpsc, Mutex, RwLock};
     use tor_basic_utils::test_rng::testing_rng;
-    use tor_key_forge::{EncodableKey, ErasedKey, SshKeyData};
+    use tor_key_forge::{EncodableKey, ErasedKey, SecretBuffer, SshKeyData};
     use tor_llcrypto::pk::ed25519;
 
     /// The type of "key" stored in the test key stores.
@@ -569,6 +1175,7 @@ mod tests {
                     &self,
                     key_spec: &dyn KeySpecifier,
                     key_type: &KeyType,
+                    _ctx: &OpContext,
                 ) -> Result<bool> {
                     Ok(self
                         .inner
@@ -585,6 +1192,7 @@ mod tests {
                     &self,
                     key_spec: &dyn KeySpecifier,
                     key_type: &KeyType,
+                    _ctx: &OpContext,
                 ) -> Result<Option<ErasedKey>> {
                     Ok(self
                         .inner
@@ -599,6 +1207,7 @@ mod tests {
                     key: &dyn EncodableKey,
                     key_spec: &dyn KeySpecifier,
                     key_type: &KeyType,
+                    _ctx: &OpContext,
                 ) -> Result<()> {
                     let key = key.downcast_ref::<TestKey>().unwrap();
                     let value = &key.meta;
@@ -619,6 +1228,7 @@ mod tests {
                     &self,
                     key_spec: &dyn KeySpecifier,
                     key_type: &KeyType,
+                    _ctx: &OpContext,
                 ) -> Result<Option<()>> {
                     Ok(self
                         .inner
@@ -628,7 +1238,7 @@ mod tests {
                         .map(|_| ()))
                 }
 
-                fn list(&self) -> Result<Vec<(KeyPath, KeyType)>> {
+                fn list(&self, _ctx: &OpContext) -> Result<Vec<(KeyPath, KeyType)>> {
                     Ok(self
                         .inner
                         .read()
@@ -639,6 +1249,43 @@ mod tests {
                         })
                         .collect())
                 }
+
+                fn get_raw(
+                    &self,
+                    key_path: &KeyPath,
+                    key_type: &KeyType,
+                    _ctx: &OpContext,
+                ) -> Result<Option<RawKeyData>> {
+                    Ok(self
+                        .inner
+                        .read()
+                        .unwrap()
+                        .get(&(key_path.arti_path().unwrap(), key_type.clone()))
+                        .map(|k| {
+                            let s = k.key.to_openssh_string("").unwrap();
+                            RawKeyData::from(SecretBuffer::from(s))
+                        }))
+                }
+
+                fn insert_raw(
+                    &self,
+                    data: &RawKeyData,
+                    key_path: &KeyPath,
+                    key_type: &KeyType,
+                    _ctx: &OpContext,
+                ) -> Result<()> {
+                    let key = TestKey {
+                        key: data.to_ssh_key_data()?,
+                        meta: format!("{}_raw", self.id()),
+                    };
+
+                    self.inner
+                        .write()
+                        .unwrap()
+                        .insert((key_path.arti_path().unwrap(), key_type.clone()), key);
+
+                    Ok(())
+                }
             }
         };
     }
@@ -674,6 +1321,9 @@ mod tests {
 
     impl_specifier!(TestPublicKeySpecifier1, "pub-spec1");
 
+    impl_specifier!(GcKeySpecifierOld, "gc/old");
+    impl_specifier!(GcKeySpecifierNew, "gc/new");
+
     /// Create a test `KeystoreEntry`.
     fn entry_descriptor(specifier: impl KeySpecifier, keystore_id: &KeystoreId) -> KeystoreEntry {
         KeystoreEntry {
@@ -817,7 +1467,11 @@ mod tests {
         let mgr = builder.build().unwrap();
 
         assert!(!mgr.secondary_stores[0]
-            .contains(&TestKeySpecifier1, &TestKey::key_type())
+            .contains(
+                &TestKeySpecifier1,
+                &TestKey::key_type(),
+                &OpContext::default()
+            )
             .unwrap());
 
         // Insert a key into Keystore2
@@ -844,7 +1498,11 @@ mod tests {
             .is_err());
         // The key still exists in Keystore2
         assert!(mgr.secondary_stores[0]
-            .contains(&TestKeySpecifier1, &TestKey::key_type())
+            .contains(
+                &TestKeySpecifier1,
+                &TestKey::key_type(),
+                &OpContext::default()
+            )
             .unwrap());
 
         // Try to remove the key from the primary key store
@@ -855,7 +1513,11 @@ mod tests {
 
         // The key still exists in Keystore2
         assert!(mgr.secondary_stores[0]
-            .contains(&TestKeySpecifier1, &TestKey::key_type())
+            .contains(
+                &TestKeySpecifier1,
+                &TestKey::key_type(),
+                &OpContext::default()
+            )
             .unwrap());
 
         // Removing from Keystore2 should succeed.
@@ -871,7 +1533,11 @@ mod tests {
 
         // The key doesn't exist in Keystore2 anymore
         assert!(!mgr.secondary_stores[0]
-            .contains(&TestKeySpecifier1, &TestKey::key_type())
+            .contains(
+                &TestKeySpecifier1,
+                &TestKey::key_type(),
+                &OpContext::default()
+            )
             .unwrap());
     }
 
@@ -948,6 +1614,51 @@ mod tests {
             .is_none(),);
     }
 
+    #[test]
+    fn get_or_generate_with_lock() {
+        use crate::test_utils::TestSpecifier;
+        use crate::ArtiNativeKeystore;
+        use tor_hscrypto::pk::HsIdKeypair;
+
+        let dir = tempfile::tempdir().unwrap();
+        let new_mgr = || {
+            let store = ArtiNativeKeystore::from_path_and_mistrust(
+                dir.path(),
+                &fs_mistrust::Mistrust::new_dangerously_trust_everyone(),
+            )
+            .unwrap();
+            KeyMgrBuilder::default()
+                .primary_store(Box::new(store))
+                .build()
+                .unwrap()
+        };
+
+        // Several key managers sharing a key store, as if they were in different processes.
+        let mgrs = (0..4).map(|_| new_mgr()).collect_vec();
+        let keys = std::thread::scope(|s| {
+            let handles = mgrs
+                .iter()
+                .map(|mgr| {
+                    s.spawn(move || {
+                        mgr.get_or_generate_with_lock::<HsIdKeypair>(
+                            &TestSpecifier::default(),
+                            KeystoreSelector::Primary,
+                            &mut rand::thread_rng(),
+                        )
+                        .unwrap()
+                    })
+                })
+                .collect_vec();
+            handles.into_iter().map(|h| h.join().unwrap()).collect_vec()
+        });
+
+        // They all got the same key, which is the one in the key store.
+        let stored: HsIdKeypair = mgrs[0].get(&TestSpecifier::default()).unwrap().unwrap();
+        for key in keys {
+            assert_eq!(key.as_ref().public(), stored.as_ref().public());
+        }
+    }
+
     #[test]
     fn get_or_generate() {
         let mut builder = KeyMgrBuilder::default().primary_store(Box::<Keystore1>::default());
@@ -1029,4 +1740,318 @@ mod tests {
         assert!(mgr.get_entry::<TestKey>(&entry_desc2).unwrap().is_none());
         assert!(mgr.remove_entry(&entry_desc2).unwrap().is_none());
     }
+
+    /// A key store whose operations hang until it is dropped.
+    struct HungKeystore {
+        /// The identifier of this key store.
+        id: KeystoreId,
+        /// Dropped along with the key store, which unblocks the hung operations.
+        _unblock: mpsc::Sender<()>,
+        /// The operations of this key store wait on this receiver.
+        blocked: Arc<Mutex<mpsc::Receiver<()>>>,
+    }
+
+    impl HungKeystore {
+        fn new_boxed() -> BoxedKeystore {
+            let (tx, rx) = mpsc::channel();
+            Box::new(Self {
+                id: KeystoreId::from_str("hung").unwrap(),
+                _unblock: tx,
+                blocked: Arc::new(Mutex::new(rx)),
+            })
+        }
+
+        /// Wait until the key store is dropped, or until `ctx` gives up.
+        fn hang(&self, ctx: &OpContext) -> Result<()> {
+            let blocked = Arc::clone(&self.blocked);
+            let _ = ctx.run_blocking(move || blocked.lock().unwrap().recv())?;
+            Ok(())
+        }
+    }
+
+    impl crate::Keystore for HungKeystore {
+        fn id(&self) -> &KeystoreId {
+            &self.id
+        }
+
+        fn contains(&self, _: &dyn KeySpecifier, _: &KeyType, ctx: &OpContext) -> Result<bool> {
+            self.hang(ctx).map(|()| false)
+        }
+
+        fn get(
+            &self,
+            _: &dyn KeySpecifier,
+            _: &KeyType,
+            ctx: &OpContext,
+        ) -> Result<Option<ErasedKey>> {
+            self.hang(ctx).map(|()| None)
+        }
+
+        fn insert(
+            &self,
+            _: &dyn EncodableKey,
+            _: &dyn KeySpecifier,
+            _: &KeyType,
+            ctx: &OpContext,
+        ) -> Result<()> {
+            self.hang(ctx)
+        }
+
+        fn remove(&self, _: &dyn KeySpecifier, _: &KeyType, ctx: &OpContext) -> Result<Option<()>> {
+            self.hang(ctx).map(|()| None)
+        }
+
+        fn list(&self, ctx: &OpContext) -> Result<Vec<(KeyPath, KeyType)>> {
+            self.hang(ctx).map(|()| vec![])
+        }
+
+        fn get_raw(&self, _: &KeyPath, _: &KeyType, ctx: &OpContext) -> Result<Option<RawKeyData>> {
+            self.hang(ctx).map(|()| None)
+        }
+
+        fn insert_raw(
+            &self,
+            _: &RawKeyData,
+            _: &KeyPath,
+            _: &KeyType,
+            ctx: &OpContext,
+        ) -> Result<()> {
+            self.hang(ctx)
+        }
+    }
+
+    #[test]
+    fn operation_timeout() {
+        let mgr = KeyMgrBuilder::default()
+            .primary_store(HungKeystore::new_boxed())
+            .operation_timeout(Duration::from_millis(100))
+            .build()
+            .unwrap();
+
+        let res = mgr.get_or_generate::<TestKey>(
+            &TestKeySpecifier1,
+            KeystoreSelector::Primary,
+            &mut testing_rng(),
+        );
+        assert!(matches!(res, Err(crate::Error::Timeout)), "{res:?}");
+        let res = mgr.list();
+        assert!(matches!(res, Err(crate::Error::Timeout)), "{res:?}");
+    }
+
+    #[test]
+    fn audit() {
+        use std::sync::Mutex;
+
+        let events = Arc::new(Mutex::new(vec![]));
+        let events2 = Arc::clone(&events);
+        let mgr = KeyMgrBuilder::default()
+            .primary_store(Box::<Keystore1>::default())
+            .auditor(Arc::new(move |event: &KeyAccessEvent| {
+                events2.lock().unwrap().push(event.clone());
+            }))
+            .build()
+            .unwrap();
+
+        let keystore1 = KeystoreId::from_str("keystore1").unwrap();
+        let spec1_path = || Some(KeyPath::Arti(TestKeySpecifier1.arti_path().unwrap()));
+        let expect_line = line!() + 1;
+        assert!(mgr.get::<TestKey>(&TestKeySpecifier1).unwrap().is_none());
+        let _: TestKey = mgr
+            .get_or_generate(
+                &TestKeySpecifier1,
+                KeystoreSelector::Primary,
+                &mut testing_rng(),
+            )
+            .unwrap();
+        assert!(mgr
+            .insert(
+                TestKey::new("coot"),
+                &TestKeySpecifier1,
+                KeystoreSelector::Primary,
+                false
+            )
+            .is_err());
+        assert!(mgr
+            .remove::<TestKey>(&TestKeySpecifier1, KeystoreSelector::Primary)
+            .unwrap()
+            .is_some());
+
+        let events = events.lock().unwrap();
+        let summary = events
+            .iter()
+            .map(|e| (e.operation(), e.keystore_id().clone(), e.outcome()))
+            .collect_vec();
+        assert_eq!(
+            summary,
+            vec![
+                (KeyOperation::Get, None, KeyAccessOutcome::NotFound),
+                (KeyOperation::Get, None, KeyAccessOutcome::NotFound),
+                (
+                    KeyOperation::Generate,
+                    Some(keystore1.clone()),
+                    KeyAccessOutcome::Success
+                ),
+                (
+                    KeyOperation::Insert,
+                    Some(keystore1.clone()),
+                    KeyAccessOutcome::Failed
+                ),
+                (
+                    KeyOperation::Remove,
+                    Some(keystore1.clone()),
+                    KeyAccessOutcome::Success
+                ),
+            ]
+        );
+
+        for event in events.iter() {
+            assert_eq!(event.key_path(), &spec1_path());
+            assert_eq!(event.key_type(), &TestKey::key_type());
+            // The events report the location of the code that called the KeyMgr
+            assert_eq!(event.caller().file(), file!());
+        }
+        assert_eq!(events[0].caller().line(), expect_line);
+        // get_or_generate reports the location of its own caller
+        // (the location of a method call is that of the method name)
+        assert_eq!(events[1].caller().line(), expect_line + 2);
+        assert_eq!(events[2].caller().line(), expect_line + 2);
+    }
+
+    #[test]
+    fn unrecognized_entries() {
+        let mut builder = KeyMgrBuilder::default().primary_store(Box::<Keystore1>::default());
+        builder.secondary_stores().push(Keystore2::new_boxed());
+        let mgr = builder.build().unwrap();
+
+        let keystore2 = KeystoreId::from_str("keystore2").unwrap();
+        mgr.insert(
+            TestKey::new("coot"),
+            &TestKeySpecifier1,
+            KeystoreSelector::Id(&keystore2),
+            false,
+        )
+        .unwrap();
+
+        // No KeyPathInfoExtractor knows about our test specifiers,
+        // so the key is unrecognized.
+        let unrecognized = mgr.list_unrecognized().unwrap();
+        assert_eq!(unrecognized.len(), 1);
+        let listed = mgr.list().unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].entry(), unrecognized[0].entry());
+        assert!(!listed[0].recognized());
+        // Our test key stores don't know when their keys were created.
+        assert!(listed[0].created().is_none());
+        assert!(listed[0].metadata().path.is_none());
+        let entry = unrecognized[0].entry().clone();
+        assert_eq!(entry, entry_descriptor(TestKeySpecifier1, &keystore2));
+        let info = mgr.entry_info(&entry).unwrap().unwrap();
+        assert_eq!(info, listed[0]);
+        assert_eq!(info.entry().keystore_id(), &keystore2);
+        assert!(unrecognized[0].unknown_key_path());
+        assert!(!unrecognized[0].unknown_key_type());
+
+        // Copy it, unparsed, to the primary store.
+        assert_eq!(
+            mgr.copy_raw_entry(&entry, KeystoreSelector::Primary, false)
+                .unwrap(),
+            Some(())
+        );
+        let copied = mgr.get::<TestKey>(&TestKeySpecifier1).unwrap().unwrap();
+        assert_eq!(copied.meta, "keystore1_raw");
+        assert!(matches!(
+            mgr.copy_raw_entry(&entry, KeystoreSelector::Primary, false),
+            Err(crate::Error::KeyAlreadyExists)
+        ));
+
+        // Copying an entry that doesn't exist is not an error.
+        let missing = entry_descriptor(TestKeySpecifier2, &keystore2);
+        assert!(mgr.entry_info(&missing).unwrap().is_none());
+        assert!(mgr.get_raw_entry(&missing).unwrap().is_none());
+        assert!(mgr
+            .copy_raw_entry(&missing, KeystoreSelector::Primary, true)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn sweep() {
+        use crate::{ArtiPathRange, KeyPathInfo, PatternKeyInfoExtractor};
+
+        #[allow(clippy::unnecessary_wraps)] // The signature is required by PatternKeyInfoExtractor
+        fn describe_gc(_: &ArtiPath, _: &[ArtiPathRange]) -> StdResult<KeyPathInfo, KeyPathError> {
+            Ok(KeyPathInfo::builder()
+                .summary("Test GC key".into())
+                .role("gc".into())
+                .build()
+                .unwrap())
+        }
+
+        crate::register_key_info_extractor!(PatternKeyInfoExtractor::new("gc/*", describe_gc));
+
+        let new_mgr = |allow_unchecked_removal| {
+            let mgr = KeyMgrBuilder::default()
+                .primary_store(Box::<Keystore1>::default())
+                .allow_unchecked_removal(allow_unchecked_removal)
+                .build()
+                .unwrap();
+
+            for spec in [
+                &GcKeySpecifierOld as &dyn KeySpecifier,
+                &GcKeySpecifierNew,
+                &TestKeySpecifier1,
+            ] {
+                mgr.insert(TestKey::new("coot"), spec, KeystoreSelector::Primary, false)
+                    .unwrap();
+            }
+            mgr
+        };
+
+        let mgr = new_mgr(false);
+        let keystore1 = KeystoreId::from_str("keystore1").unwrap();
+        let old = entry_descriptor(GcKeySpecifierOld, &keystore1);
+        let all = KeyPathPattern::Arti("**".into());
+
+        // Errors from the predicate are returned.
+        assert!(mgr.sweep(&all, |_| Err(internal!("nope").into())).is_err());
+
+        // Only the recognized entries are passed to the predicate.
+        let mut seen = vec![];
+        let outcome = mgr
+            .sweep(&all, |entry| {
+                seen.push(entry.key_path().clone());
+                Ok(entry == &old)
+            })
+            .unwrap();
+        seen.sort_by_key(|path| path.to_string());
+        assert_eq!(
+            seen,
+            [GcKeySpecifierNew.arti_path(), GcKeySpecifierOld.arti_path()]
+                .map(|path| KeyPath::Arti(path.unwrap()))
+        );
+        assert_eq!(outcome.removed(), &[old]);
+        assert_eq!(outcome.unrecognized().len(), 1);
+        let unrecognized = &outcome.unrecognized()[0];
+        assert_eq!(
+            unrecognized.entry(),
+            &entry_descriptor(TestKeySpecifier1, &keystore1)
+        );
+        assert!(mgr.get::<TestKey>(&GcKeySpecifierOld).unwrap().is_none());
+        assert!(mgr.get::<TestKey>(&GcKeySpecifierNew).unwrap().is_some());
+
+        // The unrecognized entry can only be removed if we opted in.
+        assert!(matches!(
+            mgr.remove_unchecked(unrecognized),
+            Err(crate::Error::Bug(_))
+        ));
+        assert!(mgr.get::<TestKey>(&TestKeySpecifier1).unwrap().is_some());
+
+        let mgr = new_mgr(true);
+        let outcome = mgr.sweep(&all, |_| Ok(false)).unwrap();
+        assert!(outcome.removed().is_empty());
+        let unrecognized = &outcome.unrecognized()[0];
+        assert_eq!(mgr.remove_unchecked(unrecognized).unwrap(), Some(()));
+        assert_eq!(mgr.remove_unchecked(unrecognized).unwrap(), None);
+        assert!(mgr.get::<TestKey>(&TestKeySpecifier1).unwrap().is_none());
+    }
 }
@@ -0,0 +1,211 @@
+//! Key access auditing.
+//!
+//! See [`KeyAuditor`] for more details.
+
+use std::fmt;
+use std::panic::Location;
+use std::time::SystemTime;
+
+use tor_key_forge::KeyType;
+
+use crate::{KeyMgr, KeyPath, KeySpecifier, KeystoreId, Result};
+
+/// A recipient of the [`KeyAccessEvent`]s of a [`KeyMgr`].
+///
+/// An auditor is registered with [`KeyMgrBuilder::auditor`](crate::KeyMgrBuilder::auditor),
+/// and is notified of every key read, write, and removal performed through the
+/// [`KeyMgr`] (whether or not it succeeded).
+/// It can be used to forward these events to syslog,
+/// or to some other audit pipeline.
+///
+/// Auditors are called synchronously, while the `KeyMgr` operation is in progress,
+/// so they should not block.
+///
+/// This trait is implemented for any `Fn(&KeyAccessEvent)` closure.
+pub trait KeyAuditor: Send + Sync {
+    /// Record the specified event.
+    fn record(&self, event: &KeyAccessEvent);
+}
+
+impl<F> KeyAuditor for F
+where
+    F: Fn(&KeyAccessEvent) + Send + Sync,
+{
+    fn record(&self, event: &KeyAccessEvent) {
+        self(event);
+    }
+}
+
+/// A [`KeyAuditor`] that logs the events it receives at `info` level.
+///
+/// The events are logged with the `tor_keymgr::audit` target,
+/// so they can be filtered separately from the rest of the logs.
+#[derive(Clone, Copy, Debug, Default)]
+#[non_exhaustive]
+pub struct TracingKeyAuditor;
+
+impl KeyAuditor for TracingKeyAuditor {
+    fn record(&self, event: &KeyAccessEvent) {
+        tracing::info!(target: "tor_keymgr::audit", "{event}");
+    }
+}
+
+/// An operation performed on a key by the [`KeyMgr`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum KeyOperation {
+    /// The key was read.
+    ///
+    /// Recorded by [`KeyMgr::get`], [`KeyMgr::get_entry`], and [`KeyMgr::get_raw_entry`].
+    Get,
+    /// The key was written.
+    ///
+    /// Recorded by [`KeyMgr::insert`] and [`KeyMgr::copy_raw_entry`].
+    Insert,
+    /// A new key was generated and written.
+    ///
+    /// Recorded by [`KeyMgr::generate`] and [`KeyMgr::get_or_generate`].
+    Generate,
+    /// The key was removed.
+    ///
+    /// Recorded by [`KeyMgr::remove`] and [`KeyMgr::remove_entry`].
+    Remove,
+}
+
+impl fmt::Display for KeyOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            KeyOperation::Get => "get",
+            KeyOperation::Insert => "insert",
+            KeyOperation::Generate => "generate",
+            KeyOperation::Remove => "remove",
+        };
+        write!(f, "{op}")
+    }
+}
+
+/// The outcome of a [`KeyOperation`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum KeyAccessOutcome {
+    /// The operation succeeded.
+    Success,
+    /// The key does not exist (in any of the key stores, in the case of [`KeyOperation::Get`]).
+    NotFound,
+    /// The operation failed.
+    ///
+    /// The error itself is returned to the caller of the [`KeyMgr`] function,
+    /// and is not included in the event.
+    Failed,
+}
+
+impl fmt::Display for KeyAccessOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let outcome = match self {
+            KeyAccessOutcome::Success => "success",
+            KeyAccessOutcome::NotFound => "not found",
+            KeyAccessOutcome::Failed => "failed",
+        };
+        write!(f, "{outcome}")
+    }
+}
+
+/// A record of a key access, passed to the [`KeyAuditor`] of a [`KeyMgr`].
+#[derive(Clone, Debug, amplify::Getters)]
+pub struct KeyAccessEvent {
+    /// The time at which the operation completed.
+    #[getter(as_copy)]
+    time: SystemTime,
+    /// The operation.
+    #[getter(as_copy)]
+    operation: KeyOperation,
+    /// The path of the key.
+    ///
+    /// This is `None` if the [`KeySpecifier`] of the key has neither
+    /// an [`ArtiPath`](crate::ArtiPath) nor a [`CTorPath`](crate::CTorPath).
+    key_path: Option<KeyPath>,
+    /// The type of the key.
+    key_type: KeyType,
+    /// The key store the operation was performed on.
+    ///
+    /// For [`KeyOperation::Get`], this is the key store the key was found in,
+    /// and is `None` if it wasn't found.
+    /// It is also `None` if the operation failed before a key store could be selected.
+    keystore_id: Option<KeystoreId>,
+    /// The outcome of the operation.
+    #[getter(as_copy)]
+    outcome: KeyAccessOutcome,
+    /// The location of the code that called the [`KeyMgr`].
+    #[getter(as_copy)]
+    caller: &'static Location<'static>,
+}
+
+impl fmt::Display for KeyAccessEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ", self.operation)?;
+        match &self.key_path {
+            Some(path) => write!(f, "{path}")?,
+            None => write!(f, "<unknown path>")?,
+        }
+        write!(f, " ({})", self.key_type.arti_extension())?;
+        if let Some(id) = &self.keystore_id {
+            write!(f, " in {id}")?;
+        }
+        write!(f, ": {} (caller: {})", self.outcome, self.caller)
+    }
+}
+
+/// Return the [`KeyPath`] of the key identified by `key_spec`, if it has one.
+pub(super) fn key_path_of(key_spec: &dyn KeySpecifier) -> Option<KeyPath> {
+    match key_spec.arti_path() {
+        Ok(path) => Some(KeyPath::Arti(path)),
+        Err(_) => key_spec.ctor_path().map(KeyPath::CTor),
+    }
+}
+
+impl KeyMgr {
+    /// Report the outcome of `operation` to our [`KeyAuditor`], if we have one.
+    ///
+    /// `result` is the result of the operation.
+    /// `found` says whether a successful `result` means the key was found.
+    ///
+    /// Returns `result` unchanged.
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn audit<T>(
+        &self,
+        operation: KeyOperation,
+        key_path: impl FnOnce() -> Option<KeyPath>,
+        key_type: &KeyType,
+        keystore_id: Option<&KeystoreId>,
+        result: Result<T>,
+        found: impl FnOnce(&T) -> bool,
+        caller: &'static Location<'static>,
+    ) -> Result<T> {
+        #[cfg(feature = "watch")]
+        if operation != KeyOperation::Get && result.is_ok() {
+            self.watchers.wake();
+        }
+
+        let Some(auditor) = &self.auditor else {
+            return result;
+        };
+
+        let outcome = match &result {
+            Ok(v) if found(v) => KeyAccessOutcome::Success,
+            Ok(_) => KeyAccessOutcome::NotFound,
+            Err(_) => KeyAccessOutcome::Failed,
+        };
+
+        auditor.record(&KeyAccessEvent {
+            time: SystemTime::now(),
+            operation,
+            key_path: key_path(),
+            key_type: key_type.clone(),
+            keystore_id: keystore_id.cloned(),
+            outcome,
+            caller,
+        });
+
+        result
+    }
+}
@@ -0,0 +1,291 @@
+//! Certificate storage.
+//!
+//! See [`KeyMgr::insert_cert`] and [`KeyMgr::get_cert`] for more details.
+
+use std::panic::Location;
+
+use tor_error::bad_api_usage;
+use tor_key_forge::cert::{EncodableCert, ToEncodableCert};
+
+use super::key_path_of;
+use crate::{
+    KeyMgr, KeyOperation, KeyPath, KeyPathPattern, KeySpecifier, KeystoreEntry, KeystoreSelector,
+    RawKeyData, Result,
+};
+
+impl KeyMgr {
+    /// Read the certificate identified by `cert_spec` from one of the key stores.
+    ///
+    /// The certificate returned is retrieved from the first key store that contains
+    /// a certificate for the given specifier.
+    ///
+    /// The certificate is not validated: for instance, use
+    /// [`StoredEd25519Cert::validate`](tor_key_forge::cert::StoredEd25519Cert::validate)
+    /// before relying on a `StoredEd25519Cert`.
+    ///
+    /// Returns `Ok(None)` if none of the key stores have the requested certificate.
+    #[track_caller]
+    pub fn get_cert<C: ToEncodableCert>(&self, cert_spec: &dyn KeySpecifier) -> Result<Option<C>> {
+        let caller = Location::caller();
+        let key_type = C::Cert::key_type();
+        let mut keystore_id = None;
+
+        let result = cert_path(cert_spec).and_then(|path| {
+            for store in self.all_stores() {
+                // Not all key stores support get_raw(), so we only call it
+                // on the key stores that have the certificate.
+                if !store.contains(cert_spec, &key_type, &self.op_ctx())? {
+                    continue;
+                }
+                if let Some(data) = store.get_raw(&path, &key_type, &self.op_ctx())? {
+                    keystore_id = Some(store.id());
+                    return Ok(Some(C::from_encodable_cert(data.to_cert()?)));
+                }
+            }
+            Ok(None)
+        });
+
+        self.audit(
+            KeyOperation::Get,
+            || key_path_of(cert_spec),
+            &key_type,
+            keystore_id,
+            result,
+            Option::is_some,
+            caller,
+        )
+    }
+
+    /// Retrieve the certificate in the specified keystore entry.
+    ///
+    /// Returns `Ok(None)` if the key store does not contain the requested entry,
+    /// and an error if the entry is not a certificate.
+    #[track_caller]
+    pub fn get_cert_entry<C: ToEncodableCert>(&self, entry: &KeystoreEntry) -> Result<Option<C>> {
+        if !entry.key_type().is_cert() {
+            return Err(bad_api_usage!("{:?} entry is not a certificate", entry.key_type()).into());
+        }
+
+        self.get_raw_entry(entry)?
+            .map(|data| Ok(C::from_encodable_cert(data.to_cert()?)))
+            .transpose()
+    }
+
+    /// Insert `cert` into the [`Keystore`](crate::Keystore) specified by `selector`,
+    /// under `cert_spec`.
+    ///
+    /// If the certificate already exists in the keystore,
+    /// the `overwrite` flag is used to decide whether to overwrite it.
+    /// If it is overwritten, the old value is returned.
+    ///
+    /// Returns [`Error::KeyAlreadyExists`](crate::Error::KeyAlreadyExists)
+    /// if the certificate already exists and `overwrite` is `false`.
+    #[track_caller]
+    pub fn insert_cert<C: ToEncodableCert>(
+        &self,
+        cert: C,
+        cert_spec: &dyn KeySpecifier,
+        selector: KeystoreSelector,
+        overwrite: bool,
+    ) -> Result<Option<C>> {
+        let caller = Location::caller();
+        let key_type = C::Cert::key_type();
+        let store = self.select_keystore(&selector);
+        let keystore_id = store.as_ref().ok().map(|&store| store.id());
+
+        let result = store.and_then(|store| {
+            let path = cert_path(cert_spec)?;
+            let old_cert = store
+                .get_raw(&path, &key_type, &self.op_ctx())?
+                .map(|data| data.to_cert::<C::Cert>())
+                .transpose()?
+                .map(C::from_encodable_cert);
+
+            if old_cert.is_some() && !overwrite {
+                Err(crate::Error::KeyAlreadyExists)
+            } else {
+                let data = RawKeyData::new(cert.to_encodable_cert().to_entry_bytes());
+                let () = store.insert_raw(&data, &path, &key_type, &self.op_ctx())?;
+                Ok(old_cert)
+            }
+        });
+
+        self.audit(
+            KeyOperation::Insert,
+            || key_path_of(cert_spec),
+            &key_type,
+            keystore_id,
+            result,
+            |_| true,
+            caller,
+        )
+    }
+
+    /// Remove the certificate identified by `cert_spec` from the
+    /// [`Keystore`](crate::Keystore) specified by `selector`.
+    ///
+    /// Returns `Ok(None)` if the certificate does not exist in the requested keystore,
+    /// and `Ok(Some(()))` if it was removed.
+    #[track_caller]
+    pub fn remove_cert<C: ToEncodableCert>(
+        &self,
+        cert_spec: &dyn KeySpecifier,
+        selector: KeystoreSelector,
+    ) -> Result<Option<()>> {
+        let caller = Location::caller();
+        let key_type = C::Cert::key_type();
+        let store = self.select_keystore(&selector);
+        let keystore_id = store.as_ref().ok().map(|&store| store.id());
+
+        let result = store.and_then(|store| store.remove(cert_spec, &key_type, &self.op_ctx()));
+
+        self.audit(
+            KeyOperation::Remove,
+            || key_path_of(cert_spec),
+            &key_type,
+            keystore_id,
+            result,
+            Option::is_some,
+            caller,
+        )
+    }
+
+    /// Return the keystore entry descriptors of the certificates
+    /// matching the specified [`KeyPathPattern`].
+    ///
+    /// Like [`KeyMgr::list_matching`], except the entries that aren't certificates
+    /// are left out.
+    pub fn list_certs(&self, pat: &KeyPathPattern) -> Result<Vec<KeystoreEntry<'_>>> {
+        Ok(self
+            .list_matching(pat)?
+            .into_iter()
+            .filter(|entry| entry.key_type().is_cert())
+            .collect())
+    }
+}
+
+/// Return the [`KeyPath`] of the certificate identified by `cert_spec`.
+fn cert_path(cert_spec: &dyn KeySpecifier) -> Result<KeyPath> {
+    key_path_of(cert_spec)
+        .ok_or_else(|| bad_api_usage!("certificate specifier has no key path").into())
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use crate::test_utils::TestSpecifier;
+    use crate::{ArtiNativeKeystore, KeyMgrBuilder};
+    use std::time::{Duration, SystemTime};
+    use tor_basic_utils::test_rng::testing_rng;
+    use tor_cert::{CertType, CertifiedKey};
+    use tor_hscrypto::pk::HsIdKeypair;
+    use tor_key_forge::cert::{create_ed25519_cert, StoredEd25519Cert};
+    use tor_key_forge::KeyType;
+    use tor_llcrypto::pk::ed25519;
+
+    #[test]
+    fn insert_get_list_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ArtiNativeKeystore::from_path_and_mistrust(
+            dir.path(),
+            &fs_mistrust::Mistrust::new_dangerously_trust_everyone(),
+        )
+        .unwrap();
+        let mgr = KeyMgrBuilder::default()
+            .primary_store(Box::new(store))
+            .build()
+            .unwrap();
+
+        let mut rng = testing_rng();
+        let signer = ed25519::Keypair::generate(&mut rng);
+        let now = SystemTime::now();
+        let new_cert = |subject: &ed25519::Keypair| -> StoredEd25519Cert {
+            create_ed25519_cert(
+                &signer,
+                CertType::HS_BLINDED_ID_V_SIGNING,
+                CertifiedKey::Ed25519(subject.verifying_key().into()),
+                now + Duration::from_secs(86400),
+            )
+            .unwrap()
+            .into()
+        };
+        let cert1 = new_cert(&ed25519::Keypair::generate(&mut rng));
+        let cert2 = new_cert(&ed25519::Keypair::generate(&mut rng));
+
+        let spec = TestSpecifier::new("-cert");
+        assert!(mgr.get_cert::<StoredEd25519Cert>(&spec).unwrap().is_none());
+        assert!(mgr
+            .insert_cert(cert1.clone(), &spec, KeystoreSelector::Primary, false)
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            mgr.get_cert::<StoredEd25519Cert>(&spec).unwrap().as_ref(),
+            Some(&cert1)
+        );
+
+        // The certificate is only replaced if we ask for it.
+        assert!(matches!(
+            mgr.insert_cert(cert2.clone(), &spec, KeystoreSelector::Primary, false),
+            Err(crate::Error::KeyAlreadyExists)
+        ));
+        assert_eq!(
+            mgr.insert_cert(cert2.clone(), &spec, KeystoreSelector::Primary, true)
+                .unwrap(),
+            Some(cert1)
+        );
+        let cert: StoredEd25519Cert = mgr.get_cert(&spec).unwrap().unwrap();
+        assert_eq!(cert, cert2);
+        let signer_id = signer.verifying_key().into();
+        assert!(cert
+            .validate(CertType::HS_BLINDED_ID_V_SIGNING, Some(&signer_id), now)
+            .is_ok());
+
+        // Keys are not listed as certificates.
+        mgr.insert(
+            HsIdKeypair::from(ed25519::ExpandedKeypair::from(&ed25519::Keypair::generate(
+                &mut rng,
+            ))),
+            &TestSpecifier::new("-key"),
+            KeystoreSelector::Primary,
+            false,
+        )
+        .unwrap();
+        let all = KeyPathPattern::Arti("**".into());
+        assert_eq!(mgr.list_matching(&all).unwrap().len(), 2);
+        let certs = mgr.list_certs(&all).unwrap();
+        assert_eq!(certs.len(), 1);
+        assert_eq!(certs[0].key_type(), &KeyType::Ed25519TorCert);
+        assert_eq!(mgr.get_cert_entry(&certs[0]).unwrap(), Some(cert2));
+        let key = mgr
+            .list_matching(&all)
+            .unwrap()
+            .into_iter()
+            .find(|entry| !entry.key_type().is_cert())
+            .unwrap();
+        assert!(mgr.get_cert_entry::<StoredEd25519Cert>(&key).is_err());
+
+        assert_eq!(
+            mgr.remove_cert::<StoredEd25519Cert>(&spec, KeystoreSelector::Primary)
+                .unwrap(),
+            Some(())
+        );
+        assert!(mgr.get_cert::<StoredEd25519Cert>(&spec).unwrap().is_none());
+        assert!(mgr
+            .remove_cert::<StoredEd25519Cert>(&spec, KeystoreSelector::Primary)
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,571 @@
+//! Copying keys between key stores.
+//!
+//! See [`KeyMgr::copy_key`] and [`KeyMgr::sync_stores`] for more details.
+
+use tor_error::bad_api_usage;
+use tor_key_forge::{EncodableKey as _, KeyType, ToEncodableKey};
+
+use crate::{
+    ArtiPath, ArtiPathUnavailableError, BoxedKeystore, CTorPath, KeyMgr, KeyPath, KeySpecifier,
+    KeystoreEntry, KeystoreSelector, OpContext, Result,
+};
+
+/// What to do when copying a key to a key store that already has it.
+///
+/// Used by [`KeyMgr::copy_key`] and [`KeyMgr::sync_stores`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ConflictPolicy {
+    /// Leave the existing key alone.
+    #[default]
+    Skip,
+    /// Replace the existing key.
+    Overwrite,
+    /// Fail with [`Error::KeyAlreadyExists`](crate::Error::KeyAlreadyExists).
+    Fail,
+}
+
+/// The result of copying a single key to another key store.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CopyOutcome {
+    /// The key was copied.
+    Copied,
+    /// The key was copied, replacing a key that was already in the destination key store.
+    Overwritten,
+    /// The key was not copied, because the destination key store already has it,
+    /// and the [`ConflictPolicy`] is [`Skip`](ConflictPolicy::Skip).
+    Skipped,
+}
+
+/// An entry considered by [`KeyMgr::sync_stores`], and what happened to it.
+#[derive(Clone, Debug, amplify::Getters)]
+pub struct SyncedEntry<'a> {
+    /// The entry, in the source key store.
+    entry: KeystoreEntry<'a>,
+    /// What happened (or, in a dry run, what would happen) to the entry.
+    ///
+    /// An error means the entry couldn't be copied:
+    /// for instance, because the destination key store can't represent it.
+    outcome: Result<CopyOutcome>,
+}
+
+/// A [`KeySpecifier`] for an entry copied from one key store to another.
+///
+/// Built from the [`KeyPath`] of the entry in the source key store,
+/// by using the registered [`KeyPathTranslator`](crate::KeyPathTranslator)s
+/// to fill in the path the source key store doesn't use.
+pub(super) struct TranslatedKeySpecifier {
+    /// The `ArtiPath` of the entry, if it has one.
+    pub(super) arti_path: Option<ArtiPath>,
+    /// The `CTorPath` of the entry, if it has one.
+    pub(super) ctor_path: Option<CTorPath>,
+}
+
+impl KeySpecifier for TranslatedKeySpecifier {
+    fn arti_path(&self) -> std::result::Result<ArtiPath, ArtiPathUnavailableError> {
+        self.arti_path
+            .clone()
+            .ok_or(ArtiPathUnavailableError::ArtiPathUnavailable)
+    }
+
+    fn ctor_path(&self) -> Option<CTorPath> {
+        self.ctor_path.clone()
+    }
+
+    fn keypair_specifier(&self) -> Option<Box<dyn KeySpecifier>> {
+        None
+    }
+}
+
+impl TranslatedKeySpecifier {
+    /// Return the [`KeyPath`] to use for raw copies of this entry.
+    fn key_path(&self) -> Option<KeyPath> {
+        match (&self.arti_path, &self.ctor_path) {
+            (Some(path), _) => Some(KeyPath::Arti(path.clone())),
+            (None, Some(path)) => Some(KeyPath::CTor(path.clone())),
+            (None, None) => None,
+        }
+    }
+}
+
+impl KeyMgr {
+    /// Copy the key specified by `key_spec` from the key store specified by `from`
+    /// to the key store specified by `to`.
+    ///
+    /// If the destination key store already has the key,
+    /// `policy` decides what happens.
+    ///
+    /// Returns `Ok(None)` if the source key store does not contain the requested key.
+    ///
+    /// Returns [`Error::KeyAlreadyExists`](crate::Error::KeyAlreadyExists)
+    /// if the destination key store already has the key
+    /// and `policy` is [`ConflictPolicy::Fail`].
+    pub fn copy_key<K: ToEncodableKey>(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        from: KeystoreSelector,
+        to: KeystoreSelector,
+        policy: ConflictPolicy,
+    ) -> Result<Option<CopyOutcome>> {
+        let key_type = K::Key::key_type();
+        let src = self.select_keystore(&from)?;
+        let dest = self.select_keystore(&to)?;
+
+        let Some(key) = src.get(key_spec, &key_type, &self.op_ctx())? else {
+            return Ok(None);
+        };
+
+        let exists = dest.contains(key_spec, &key_type, &self.op_ctx())?;
+        let outcome = conflict_outcome(exists, policy)?;
+        if outcome != CopyOutcome::Skipped {
+            dest.insert(&*key, key_spec, &key_type, &self.op_ctx())?;
+        }
+
+        Ok(Some(outcome))
+    }
+
+    /// Copy every entry of the key store specified by `from`
+    /// to the key store specified by `to`.
+    ///
+    /// This can be used to migrate keys between different kinds of key store,
+    /// for instance from a C Tor key store to the native Arti one.
+    /// The [`ArtiPath`] of a key read from a C Tor key store
+    /// (and the [`CTorPath`] of a key read from an Arti key store)
+    /// is worked out using the registered
+    /// [`KeyPathTranslator`](crate::KeyPathTranslator)s.
+    ///
+    /// Entries of a [`KeyType`] this version of Arti doesn't recognize are copied as-is
+    /// (see [`KeyMgr::copy_raw_entry`]).
+    ///
+    /// If the destination key store already has an entry,
+    /// `policy` decides what happens.
+    /// With [`ConflictPolicy::Fail`], any conflict causes this function to return
+    /// [`Error::KeyAlreadyExists`](crate::Error::KeyAlreadyExists)
+    /// before any entry is copied.
+    ///
+    /// If `dry_run` is `true`, nothing is written:
+    /// the returned entries describe what would have happened.
+    /// Note that a dry run can't detect the errors that the destination key store
+    /// only reports when an entry is written to it
+    /// (for instance, because it can't store keys of that type).
+    ///
+    /// Returns an entry for each entry of the source key store.
+    /// Failing to copy an individual entry does not cause this function to fail:
+    /// the error is reported in the [`outcome`](SyncedEntry::outcome) of the entry.
+    pub fn sync_stores(
+        &self,
+        from: KeystoreSelector,
+        to: KeystoreSelector,
+        policy: ConflictPolicy,
+        dry_run: bool,
+    ) -> Result<Vec<SyncedEntry<'_>>> {
+        let src = self.select_keystore(&from)?;
+        let dest = self.select_keystore(&to)?;
+        if src.id() == dest.id() {
+            return Err(bad_api_usage!("cannot sync keystore {} with itself", src.id()).into());
+        }
+
+        // Work out what to do with each entry before copying anything,
+        // so that ConflictPolicy::Fail leaves the destination untouched.
+        let plan = src
+            .list(&self.op_ctx())?
+            .into_iter()
+            .map(|(key_path, key_type)| {
+                let spec = self.translate(&key_path);
+                let entry = KeystoreEntry {
+                    key_path,
+                    key_type,
+                    keystore_id: src.id(),
+                };
+                let outcome = dest
+                    .contains(&spec, entry.key_type(), &self.op_ctx())
+                    .and_then(|exists| conflict_outcome(exists, policy));
+                if let Err(crate::Error::KeyAlreadyExists) = outcome {
+                    return Err(crate::Error::KeyAlreadyExists);
+                }
+                Ok((entry, spec, outcome))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(plan
+            .into_iter()
+            .map(|(entry, spec, outcome)| {
+                let outcome = match outcome {
+                    Ok(CopyOutcome::Skipped) => outcome,
+                    Ok(_) if dry_run => outcome,
+                    Ok(outcome) => {
+                        copy_entry(src, dest, &entry, &spec, &self.op_ctx()).map(|()| outcome)
+                    }
+                    Err(_) => outcome,
+                };
+                SyncedEntry { entry, outcome }
+            })
+            .collect())
+    }
+
+    /// Return a [`TranslatedKeySpecifier`] for the entry with the specified `key_path`.
+    pub(super) fn translate(&self, key_path: &KeyPath) -> TranslatedKeySpecifier {
+        match key_path {
+            KeyPath::Arti(path) => TranslatedKeySpecifier {
+                arti_path: Some(path.clone()),
+                ctor_path: self
+                    .key_path_translators
+                    .iter()
+                    .find_map(|t| t.ctor_path(path)),
+            },
+            KeyPath::CTor(path) => TranslatedKeySpecifier {
+                arti_path: self
+                    .key_path_translators
+                    .iter()
+                    .find_map(|t| t.arti_path(path)),
+                ctor_path: Some(path.clone()),
+            },
+        }
+    }
+}
+
+/// Decide what to do with a key, given whether the destination key store
+/// already has it, and the [`ConflictPolicy`].
+pub(super) fn conflict_outcome(exists: bool, policy: ConflictPolicy) -> Result<CopyOutcome> {
+    match (exists, policy) {
+        (false, _) => Ok(CopyOutcome::Copied),
+        (true, ConflictPolicy::Skip) => Ok(CopyOutcome::Skipped),
+        (true, ConflictPolicy::Overwrite) => Ok(CopyOutcome::Overwritten),
+        (true, ConflictPolicy::Fail) => Err(crate::Error::KeyAlreadyExists),
+    }
+}
+
+/// Copy `entry` from `src` to `dest`, where it will be stored under `spec`.
+fn copy_entry(
+    src: &BoxedKeystore,
+    dest: &BoxedKeystore,
+    entry: &KeystoreEntry,
+    spec: &TranslatedKeySpecifier,
+    ctx: &OpContext,
+) -> Result<()> {
+    if let KeyType::Unknown { .. } = entry.key_type() {
+        // We can't parse this entry, so copy it as-is.
+        let data = src
+            .get_raw(entry.key_path(), entry.key_type(), ctx)?
+            .ok_or_else(|| bad_api_usage!("entry {} disappeared", entry.key_path()))?;
+        let key_path = spec
+            .key_path()
+            .ok_or_else(|| bad_api_usage!("entry {} has no path", entry.key_path()))?;
+        return dest.insert_raw(&data, &key_path, entry.key_type(), ctx);
+    }
+
+    let key = src
+        .get(entry.key_path(), entry.key_type(), ctx)?
+        .ok_or_else(|| bad_api_usage!("entry {} disappeared", entry.key_path()))?;
+    dest.insert(&*key, spec, entry.key_type(), ctx)
+}
+
+#[cfg(all(test, feature = "ephemeral-keystore"))]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use crate::test_utils::keymgr_builder;
+    use crate::{ArtiEphemeralKeystore, ArtiNativeKeystore, KeyMgrBuilder, KeystoreId};
+    use std::str::FromStr;
+    use tempfile::{tempdir, TempDir};
+    use tor_basic_utils::test_rng::testing_rng;
+    use tor_hscrypto::pk::HsDescSigningKeypair;
+    use tor_llcrypto::pk::ed25519;
+
+    /// Return a `KeyMgr` with a native key store (the primary),
+    /// and an ephemeral key store with the ID "backup".
+    fn keymgr() -> (KeyMgr, TempDir) {
+        let (builder, dir) = keymgr_builder();
+        let mgr = builder
+            .set_secondary_stores(vec![Box::new(ArtiEphemeralKeystore::new("backup".into()))])
+            .build()
+            .unwrap();
+
+        (mgr, dir)
+    }
+
+    fn insert(mgr: &KeyMgr, path: &str, selector: KeystoreSelector) -> ed25519::PublicKey {
+        let key: HsDescSigningKeypair = ed25519::Keypair::generate(&mut testing_rng()).into();
+        let public = key.as_ref().verifying_key();
+        mgr.insert(key, &ArtiPath::new(path.into()).unwrap(), selector, true)
+            .unwrap();
+        public
+    }
+
+    fn get(mgr: &KeyMgr, path: &str, selector: KeystoreSelector) -> Option<ed25519::PublicKey> {
+        let path = ArtiPath::new(path.into()).unwrap();
+        let store = mgr.select_keystore(&selector).unwrap();
+        let key = store
+            .get(&path, &KeyType::Ed25519Keypair, &OpContext::default())
+            .unwrap()?
+            .downcast::<ed25519::Keypair>()
+            .ok()?;
+        Some(key.verifying_key())
+    }
+
+    #[test]
+    fn copy_key() {
+        let (mgr, _dir) = keymgr();
+        let backup_id = KeystoreId::from_str("backup").unwrap();
+        let backup = KeystoreSelector::Id(&backup_id);
+        let copy = |path: &str, policy| {
+            mgr.copy_key::<HsDescSigningKeypair>(
+                &ArtiPath::new(path.into()).unwrap(),
+                KeystoreSelector::Primary,
+                backup,
+                policy,
+            )
+        };
+
+        // Not in the source key store
+        assert_eq!(
+            copy("hss/foo/ks_hs_desc_sign", ConflictPolicy::Skip).unwrap(),
+            None
+        );
+
+        let key = insert(&mgr, "hss/foo/ks_hs_desc_sign", KeystoreSelector::Primary);
+        assert_eq!(
+            copy("hss/foo/ks_hs_desc_sign", ConflictPolicy::Fail).unwrap(),
+            Some(CopyOutcome::Copied)
+        );
+        assert_eq!(get(&mgr, "hss/foo/ks_hs_desc_sign", backup), Some(key));
+
+        // Now the destination has the key
+        let key = insert(&mgr, "hss/foo/ks_hs_desc_sign", KeystoreSelector::Primary);
+        assert!(matches!(
+            copy("hss/foo/ks_hs_desc_sign", ConflictPolicy::Fail),
+            Err(crate::Error::KeyAlreadyExists)
+        ));
+        assert_eq!(
+            copy("hss/foo/ks_hs_desc_sign", ConflictPolicy::Skip).unwrap(),
+            Some(CopyOutcome::Skipped)
+        );
+        assert_ne!(get(&mgr, "hss/foo/ks_hs_desc_sign", backup), Some(key));
+        assert_eq!(
+            copy("hss/foo/ks_hs_desc_sign", ConflictPolicy::Overwrite).unwrap(),
+            Some(CopyOutcome::Overwritten)
+        );
+        assert_eq!(get(&mgr, "hss/foo/ks_hs_desc_sign", backup), Some(key));
+    }
+
+    #[test]
+    fn sync_stores() {
+        let (mgr, _dir) = keymgr();
+        let backup_id = KeystoreId::from_str("backup").unwrap();
+        let backup = KeystoreSelector::Id(&backup_id);
+
+        let key1 = insert(&mgr, "hss/foo/ks_hs_desc_sign", KeystoreSelector::Primary);
+        let key2 = insert(&mgr, "hss/bar/ks_hs_desc_sign", KeystoreSelector::Primary);
+        let old_key2 = insert(&mgr, "hss/bar/ks_hs_desc_sign", backup);
+
+        let outcomes = |synced: Vec<SyncedEntry>| {
+            let mut outcomes = synced
+                .into_iter()
+                .map(|e| {
+                    let path = e.entry().key_path().arti().unwrap().to_string();
+                    (path, e.outcome().as_ref().ok().copied())
+                })
+                .collect::<Vec<_>>();
+            outcomes.sort_by(|a, b| a.0.cmp(&b.0));
+            outcomes
+        };
+
+        // A store can't be synced with itself
+        assert!(mgr
+            .sync_stores(backup, backup, ConflictPolicy::Skip, false)
+            .is_err());
+
+        // A conflict causes ConflictPolicy::Fail to fail before anything is copied
+        assert!(matches!(
+            mgr.sync_stores(
+                KeystoreSelector::Primary,
+                backup,
+                ConflictPolicy::Fail,
+                false
+            ),
+            Err(crate::Error::KeyAlreadyExists)
+        ));
+        assert_eq!(get(&mgr, "hss/foo/ks_hs_desc_sign", backup), None);
+
+        // A dry run doesn't copy anything
+        let synced = mgr
+            .sync_stores(
+                KeystoreSelector::Primary,
+                backup,
+                ConflictPolicy::Overwrite,
+                true,
+            )
+            .unwrap();
+        assert_eq!(
+            outcomes(synced),
+            [
+                (
+                    "hss/bar/ks_hs_desc_sign".into(),
+                    Some(CopyOutcome::Overwritten)
+                ),
+                ("hss/foo/ks_hs_desc_sign".into(), Some(CopyOutcome::Copied)),
+            ]
+        );
+        assert_eq!(get(&mgr, "hss/foo/ks_hs_desc_sign", backup), None);
+        assert_eq!(get(&mgr, "hss/bar/ks_hs_desc_sign", backup), Some(old_key2));
+
+        let synced = mgr
+            .sync_stores(
+                KeystoreSelector::Primary,
+                backup,
+                ConflictPolicy::Skip,
+                false,
+            )
+            .unwrap();
+        assert_eq!(
+            outcomes(synced),
+            [
+                ("hss/bar/ks_hs_desc_sign".into(), Some(CopyOutcome::Skipped)),
+                ("hss/foo/ks_hs_desc_sign".into(), Some(CopyOutcome::Copied)),
+            ]
+        );
+        assert_eq!(get(&mgr, "hss/foo/ks_hs_desc_sign", backup), Some(key1));
+        assert_eq!(get(&mgr, "hss/bar/ks_hs_desc_sign", backup), Some(old_key2));
+
+        let synced = mgr
+            .sync_stores(
+                KeystoreSelector::Primary,
+                backup,
+                ConflictPolicy::Overwrite,
+                false,
+            )
+            .unwrap();
+        assert_eq!(
+            outcomes(synced),
+            [
+                (
+                    "hss/bar/ks_hs_desc_sign".into(),
+                    Some(CopyOutcome::Overwritten)
+                ),
+                (
+                    "hss/foo/ks_hs_desc_sign".into(),
+                    Some(CopyOutcome::Overwritten)
+                ),
+            ]
+        );
+        assert_eq!(get(&mgr, "hss/bar/ks_hs_desc_sign", backup), Some(key2));
+    }
+
+    #[test]
+    #[cfg(feature = "ctor-keystore")]
+    fn sync_from_ctor() {
+        use crate::{CTorServiceKeystore, CTorServicePath, KeyPathTranslator};
+        use tor_persist::hsnickname::HsNickname;
+
+        /// Translates the paths of the identity keypairs of the services.
+        struct TestTranslator;
+
+        impl KeyPathTranslator for TestTranslator {
+            fn ctor_path(&self, path: &ArtiPath) -> Option<CTorPath> {
+                let nickname = path.strip_prefix("hss/")?.strip_suffix("/ks_hs_id")?;
+                let nickname = HsNickname::from_str(nickname).ok()?;
+                Some(CTorPath::service(nickname, CTorServicePath::PrivateKey))
+            }
+
+            fn arti_path(&self, path: &CTorPath) -> Option<ArtiPath> {
+                match path {
+                    CTorPath::Service {
+                        nickname,
+                        path: CTorServicePath::PrivateKey,
+                    } => ArtiPath::new(format!("hss/{nickname}/ks_hs_id")).ok(),
+                    _ => None,
+                }
+            }
+        }
+
+        inventory::submit!(&TestTranslator as &dyn KeyPathTranslator);
+
+        const PRIVKEY: &[u8] = include_bytes!("../../testdata/tor-service/hs_ed25519_secret_key");
+
+        let ctor_dir = tempdir().unwrap();
+        std::fs::write(ctor_dir.path().join("hs_ed25519_secret_key"), PRIVKEY).unwrap();
+        let nickname = HsNickname::from_str("allium-cepa").unwrap();
+        let ctor_id = KeystoreId::from_str("ctor").unwrap();
+        let ctor_store = CTorServiceKeystore::from_path_and_mistrust(
+            &ctor_dir,
+            &fs_mistrust::Mistrust::new_dangerously_trust_everyone(),
+            ctor_id.clone(),
+            nickname.clone(),
+        )
+        .unwrap();
+
+        let native_dir = tempdir().unwrap();
+        let native_store = ArtiNativeKeystore::from_path_and_mistrust(
+            native_dir.path(),
+            &fs_mistrust::Mistrust::new_dangerously_trust_everyone(),
+        )
+        .unwrap();
+        let mgr = KeyMgrBuilder::default()
+            .primary_store(Box::new(native_store))
+            .set_secondary_stores(vec![Box::new(ctor_store)])
+            .build()
+            .unwrap();
+
+        let synced = mgr
+            .sync_stores(
+                KeystoreSelector::Id(&ctor_id),
+                KeystoreSelector::Primary,
+                ConflictPolicy::Fail,
+                false,
+            )
+            .unwrap();
+        assert_eq!(synced.len(), 1);
+        assert_eq!(
+            synced[0].entry().key_type(),
+            &KeyType::Ed25519ExpandedKeypair
+        );
+        assert_eq!(
+            synced[0].outcome().as_ref().ok(),
+            Some(&CopyOutcome::Copied)
+        );
+
+        let ctor_path = CTorPath::service(nickname, CTorServicePath::PrivateKey);
+        let native: ed25519::ExpandedKeypair = mgr
+            .select_keystore(&KeystoreSelector::Primary)
+            .unwrap()
+            .get(
+                &ArtiPath::new("hss/allium-cepa/ks_hs_id".into()).unwrap(),
+                &KeyType::Ed25519ExpandedKeypair,
+                &OpContext::default(),
+            )
+            .unwrap()
+            .unwrap()
+            .downcast::<ed25519::ExpandedKeypair>()
+            .map(|k| *k)
+            .ok()
+            .unwrap();
+        let ctor: ed25519::ExpandedKeypair = mgr
+            .select_keystore(&KeystoreSelector::Id(&ctor_id))
+            .unwrap()
+            .get(
+                &ctor_path,
+                &KeyType::Ed25519ExpandedKeypair,
+                &OpContext::default(),
+            )
+            .unwrap()
+            .unwrap()
+            .downcast::<ed25519::ExpandedKeypair>()
+            .map(|k| *k)
+            .ok()
+            .unwrap();
+        assert_eq!(native.public(), ctor.public());
+    }
+}
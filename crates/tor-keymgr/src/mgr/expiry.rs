@@ -0,0 +1,224 @@
+//! Expiration times of keystore entries.
+//!
+//! See [`KeyMgr::set_expiry`] and [`KeyMgr::sweep_expired`] for more details.
+
+use std::panic::Location;
+use std::time::SystemTime;
+
+use crate::{KeyMgr, KeystoreEntry, Result};
+
+impl KeyMgr {
+    /// Return the time at which the specified keystore entry expires, if it has an expiration time.
+    ///
+    /// Returns `Ok(None)` if the entry doesn't exist in its key store,
+    /// or if it has no expiration time.
+    pub fn expires(&self, entry: &KeystoreEntry) -> Result<Option<SystemTime>> {
+        let store = self.select_keystore(&entry.keystore_id().into())?;
+        store.expires(entry.key_path(), entry.key_type(), &self.op_ctx())
+    }
+
+    /// Set the time at which the specified keystore entry expires,
+    /// or, if `expires` is `None`, remove its expiration time.
+    ///
+    /// Expired entries are not removed by this function,
+    /// and they can still be retrieved until they are removed
+    /// by [`KeyMgr::sweep_expired`].
+    /// The expiration time is discarded when the entry is removed.
+    ///
+    /// Returns `Ok(None)` if the entry doesn't exist in its key store,
+    /// and [`Error::ExpiryNotSupported`](crate::Error::ExpiryNotSupported)
+    /// if its key store can't record expiration times.
+    pub fn set_expiry(
+        &self,
+        entry: &KeystoreEntry,
+        expires: Option<SystemTime>,
+    ) -> Result<Option<()>> {
+        let store = self.select_keystore(&entry.keystore_id().into())?;
+        store.set_expiry(entry.key_path(), entry.key_type(), expires, &self.op_ctx())
+    }
+
+    /// Remove the entries that have expired by `now`, from all keystores.
+    ///
+    /// `report` is called for each expired entry (with its expiration time)
+    /// just before the entry is removed.
+    ///
+    /// As with [`KeyMgr::sweep`], entries this version of Arti doesn't recognize
+    /// are never removed, even if they have expired.
+    ///
+    /// Returns the entries that were removed.
+    #[track_caller]
+    pub fn sweep_expired<'a, F>(
+        &'a self,
+        now: SystemTime,
+        mut report: F,
+    ) -> Result<Vec<KeystoreEntry<'a>>>
+    where
+        F: FnMut(&KeystoreEntry<'a>, SystemTime),
+    {
+        let caller = Location::caller();
+        let mut removed = Vec::new();
+
+        for info in self.list()? {
+            let Some(expires) = info.expires() else {
+                continue;
+            };
+            if expires > now || !info.recognized() {
+                continue;
+            }
+
+            let entry = info.entry();
+            report(entry, expires);
+            if self.remove_entry_from(entry, caller)?.is_some() {
+                removed.push(entry.clone());
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use crate::{
+        ArtiPath, ArtiPathRange, KeyMgrBuilder, KeyPath, KeyPathError, KeyPathInfo,
+        KeystoreSelector, PatternKeyInfoExtractor,
+    };
+    use std::time::Duration;
+    use tempfile::TempDir;
+    use tor_basic_utils::test_rng::testing_rng;
+    use tor_hscrypto::pk::HsDescSigningKeypair;
+    use tor_llcrypto::pk::ed25519;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[allow(clippy::unnecessary_wraps)] // The signature is required by PatternKeyInfoExtractor
+    fn describe(
+        _: &ArtiPath,
+        _: &[ArtiPathRange],
+    ) -> std::result::Result<KeyPathInfo, KeyPathError> {
+        Ok(KeyPathInfo::builder()
+            .summary("Test expiring key".into())
+            .role("expiring".into())
+            .build()
+            .unwrap())
+    }
+
+    fn keymgr() -> (KeyMgr, TempDir) {
+        crate::register_key_info_extractor!(PatternKeyInfoExtractor::new("expiring/*", describe));
+
+        crate::test_utils::keymgr()
+    }
+
+    /// Insert a fresh key at `path`, and return its entry.
+    fn insert<'a>(mgr: &'a KeyMgr, path: &str) -> KeystoreEntry<'a> {
+        let path = ArtiPath::new(path.into()).unwrap();
+        let key = HsDescSigningKeypair::from(ed25519::Keypair::generate(&mut testing_rng()));
+        mgr.insert(key, &path, KeystoreSelector::Primary, true)
+            .unwrap();
+        let key_path = KeyPath::Arti(path);
+        mgr.list()
+            .unwrap()
+            .into_iter()
+            .find(|info| info.entry().key_path() == &key_path)
+            .unwrap()
+            .entry()
+            .clone()
+    }
+
+    #[test]
+    fn set_expiry() {
+        let (mgr, _dir) = keymgr();
+        let entry = insert(&mgr, "expiring/a");
+        // Expiration times are recorded to the second.
+        let expires = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        assert_eq!(mgr.expires(&entry).unwrap(), None);
+        assert_eq!(mgr.set_expiry(&entry, Some(expires)).unwrap(), Some(()));
+        assert_eq!(mgr.expires(&entry).unwrap(), Some(expires));
+        let info = mgr.list().unwrap();
+        assert_eq!(info.len(), 1);
+        assert_eq!(info[0].expires(), Some(expires));
+
+        // Overwriting the key keeps its expiration time...
+        insert(&mgr, "expiring/a");
+        assert_eq!(mgr.expires(&entry).unwrap(), Some(expires));
+
+        // ...but removing it doesn't.
+        mgr.remove_entry(&entry).unwrap().unwrap();
+        assert_eq!(mgr.set_expiry(&entry, Some(expires)).unwrap(), None);
+        let entry = insert(&mgr, "expiring/a");
+        assert_eq!(mgr.expires(&entry).unwrap(), None);
+
+        mgr.set_expiry(&entry, Some(expires)).unwrap();
+        mgr.set_expiry(&entry, None).unwrap();
+        assert_eq!(mgr.expires(&entry).unwrap(), None);
+    }
+
+    #[test]
+    fn sweep_expired() {
+        let (mgr, _dir) = keymgr();
+        let now = SystemTime::now();
+        let expired = insert(&mgr, "expiring/old");
+        let unexpired = insert(&mgr, "expiring/new");
+        let forever = insert(&mgr, "expiring/forever");
+        let unrecognized = insert(&mgr, "unknown/old");
+        for entry in [&expired, &unrecognized] {
+            mgr.set_expiry(entry, Some(now - HOUR)).unwrap();
+        }
+        mgr.set_expiry(&unexpired, Some(now + HOUR)).unwrap();
+
+        let mut reported = vec![];
+        let removed = mgr
+            .sweep_expired(now, |entry, _| {
+                // The entry is reported before it is removed.
+                assert!(mgr.get_raw_entry(entry).unwrap().is_some());
+                reported.push(entry.clone());
+            })
+            .unwrap();
+        assert_eq!(removed, vec![expired.clone()]);
+        assert_eq!(reported, removed);
+
+        for entry in [&unexpired, &forever, &unrecognized] {
+            assert!(mgr.get_raw_entry(entry).unwrap().is_some());
+        }
+        assert!(mgr.get_raw_entry(&expired).unwrap().is_none());
+
+        // Once its time has come, the other key expires too.
+        let removed = mgr.sweep_expired(now + HOUR, |_, _| ()).unwrap();
+        assert_eq!(removed, vec![unexpired]);
+    }
+
+    #[test]
+    #[cfg(feature = "ephemeral-keystore")]
+    fn unsupported() {
+        let mgr = KeyMgrBuilder::default()
+            .primary_store(Box::new(crate::ArtiEphemeralKeystore::new("eph".into())))
+            .build()
+            .unwrap();
+        let path = ArtiPath::new("expiring/a".into()).unwrap();
+        let key = HsDescSigningKeypair::from(ed25519::Keypair::generate(&mut testing_rng()));
+        mgr.insert(key, &path, KeystoreSelector::Primary, true)
+            .unwrap();
+        let entry = mgr.list().unwrap()[0].entry().clone();
+
+        assert_eq!(mgr.expires(&entry).unwrap(), None);
+        assert!(matches!(
+            mgr.set_expiry(&entry, Some(SystemTime::now())),
+            Err(crate::Error::ExpiryNotSupported(_))
+        ));
+    }
+}
@@ -0,0 +1,86 @@
+//! Garbage collection of keystore entries.
+//!
+//! See [`KeyMgr::sweep`] and [`KeyMgr::remove_unchecked`] for more details.
+
+use std::panic::Location;
+
+use tor_error::bad_api_usage;
+
+use crate::{KeyMgr, KeyPathPattern, KeystoreEntry, Result, UnrecognizedEntry};
+
+/// The outcome of [`KeyMgr::sweep`].
+#[derive(Clone, Debug, Default, PartialEq, amplify::Getters)]
+pub struct SweepOutcome<'a> {
+    /// The entries that were removed.
+    removed: Vec<KeystoreEntry<'a>>,
+    /// The entries that matched the pattern, but were left alone
+    /// because this version of Arti doesn't recognize them.
+    ///
+    /// These can be removed using [`KeyMgr::remove_unchecked`].
+    unrecognized: Vec<UnrecognizedEntry<'a>>,
+}
+
+impl KeyMgr {
+    /// Remove the entries matching `pat` that are no longer needed, from all keystores.
+    ///
+    /// `is_garbage` is called for each recognized entry that matches `pat`,
+    /// and should return `true` if the entry is expired or orphaned
+    /// (for example, if it's a blinded key for a time period that has passed).
+    /// If `is_garbage` returns an error, the sweep stops,
+    /// and the error is returned (the entries removed so far stay removed).
+    ///
+    /// Entries this version of Arti doesn't recognize
+    /// (see [`UnrecognizedEntry`]) are never removed,
+    /// since they might still be needed by a newer version of Arti.
+    /// They are returned in the [`SweepOutcome`] instead,
+    /// and can be removed using [`KeyMgr::remove_unchecked`].
+    #[track_caller]
+    pub fn sweep<F>(&self, pat: &KeyPathPattern, mut is_garbage: F) -> Result<SweepOutcome<'_>>
+    where
+        F: FnMut(&KeystoreEntry<'_>) -> Result<bool>,
+    {
+        let caller = Location::caller();
+        let mut outcome = SweepOutcome::default();
+
+        for entry in self.list_matching(pat)? {
+            let (unknown_key_type, unknown_key_path) = self.unrecognized(&entry);
+            if unknown_key_type || unknown_key_path {
+                outcome.unrecognized.push(UnrecognizedEntry {
+                    entry,
+                    unknown_key_type,
+                    unknown_key_path,
+                });
+                continue;
+            }
+
+            if is_garbage(&entry)? && self.remove_entry_from(&entry, caller)?.is_some() {
+                outcome.removed.push(entry);
+            }
+        }
+
+        Ok(outcome)
+    }
+
+    /// Remove the specified entry, even though this version of Arti doesn't recognize it.
+    ///
+    /// Unrecognized entries might have been written by a newer version of Arti,
+    /// so they should only be removed if the user has asked for it
+    /// (for example, after downgrading Arti for good).
+    ///
+    /// For this reason, this function returns an error unless this `KeyMgr` was built
+    /// with [`allow_unchecked_removal`](crate::KeyMgrBuilder::allow_unchecked_removal).
+    ///
+    /// Like [`KeyMgr::remove_entry`], returns `Ok(None)` if the entry was not found
+    /// in its key store, and `Ok(Some(()))` if it was removed.
+    #[track_caller]
+    pub fn remove_unchecked(&self, entry: &UnrecognizedEntry) -> Result<Option<()>> {
+        if !self.allow_unchecked_removal {
+            return Err(bad_api_usage!(
+                "remove_unchecked called, but unchecked removal is not enabled"
+            )
+            .into());
+        }
+
+        self.remove_entry_from(entry.entry(), Location::caller())
+    }
+}
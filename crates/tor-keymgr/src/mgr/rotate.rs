@@ -0,0 +1,564 @@
+//! Key rotation.
+//!
+//! See [`KeyMgr::rotate`] and [`RotationPolicy`] for more details.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use futures::channel::mpsc;
+use futures::Stream;
+use tor_error::{bad_api_usage, internal};
+use tor_key_forge::{EncodableKey as _, Keygen, KeygenRng, ToEncodableKey};
+
+use crate::{
+    ArtiPath, ArtiPathRange, KeyMgr, KeyPath, KeyPathError, KeyPathInfo, KeyPathInfoExtractor,
+    KeyPathPattern, KeySpecifier, KeystoreId, KeystoreSelector, PatternKeyInfoExtractor, Result,
+};
+
+/// A policy describing when a key should be rotated.
+///
+/// Rotation policies are registered with [`KeyMgrBuilder::rotation_policy`](crate::KeyMgrBuilder::rotation_policy),
+/// and apply to all the keys whose [`ArtiPath`] matches the specified [`KeyPathPattern`].
+/// Key types opt in to rotation by having a policy registered for them:
+/// for example, the pattern returned by the
+/// [`arti_pattern`](crate::KeySpecifierPattern::arti_pattern)
+/// of an onion service descriptor signing key specifier pattern
+/// matches all the descriptor signing keys of all services.
+///
+/// A key is due for rotation once it is older than [`max_age`](RotationPolicy::max_age),
+/// or once it has been used [`max_uses`](RotationPolicy::max_uses) times
+/// (see [`KeyMgr::rotation_due`]).
+/// A policy that has neither limit never considers its keys due for rotation,
+/// but still determines the [`grace_period`](RotationPolicy::grace_period)
+/// of keys rotated explicitly with [`KeyMgr::rotate`].
+#[derive(Clone, Debug, PartialEq, Eq, derive_builder::Builder, amplify::Getters)]
+#[builder(derive(Debug))]
+#[non_exhaustive]
+pub struct RotationPolicy {
+    /// The maximum age of a key.
+    #[builder(default, setter(strip_option))]
+    #[getter(as_copy)]
+    max_age: Option<Duration>,
+    /// The maximum number of times a key can be used.
+    ///
+    /// Uses are counted by [`KeyMgr::record_key_use`].
+    #[builder(default, setter(strip_option))]
+    #[getter(as_copy)]
+    max_uses: Option<u64>,
+    /// How long to keep a key around after it is rotated.
+    ///
+    /// During this period, the retired key can be retrieved
+    /// using [`KeyMgr::retired_keys`].
+    /// If this is zero, rotated keys are discarded immediately.
+    #[builder(default)]
+    #[getter(as_copy)]
+    grace_period: Duration,
+}
+
+impl RotationPolicy {
+    /// Start to build a [`RotationPolicy`]: return a fresh [`RotationPolicyBuilder`]
+    pub fn builder() -> RotationPolicyBuilder {
+        RotationPolicyBuilder::default()
+    }
+}
+
+/// An event emitted by [`KeyMgr`] when it rotates a key.
+///
+/// See [`KeyMgr::rotation_events`].
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum RotationEvent {
+    /// A key was replaced with a newly generated key.
+    Rotated {
+        /// The path of the key.
+        key_path: KeyPath,
+        /// The keystore containing the key.
+        keystore_id: KeystoreId,
+        /// The path of the retired key, if the old key was kept for a grace period.
+        retired_path: Option<KeyPath>,
+    },
+    /// The grace period of a retired key ended, and the key was removed.
+    RetiredKeyRemoved {
+        /// The path of the retired key.
+        retired_path: KeyPath,
+        /// The keystore that contained the retired key.
+        keystore_id: KeystoreId,
+    },
+}
+
+/// The first component of the [`ArtiPath`] of every retired key.
+///
+/// Retired keys are stored at `retired/<expiry>/<original ArtiPath>`,
+/// where `<expiry>` is the end of their grace period, in seconds since the Unix epoch.
+const RETIRED_PREFIX: &str = "retired";
+
+/// Return the [`ArtiPath`] for storing the key at `path` until `expiry`.
+fn retired_path(path: &ArtiPath, expiry: SystemTime) -> Result<ArtiPath> {
+    let secs = expiry
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    ArtiPath::new(format!("{RETIRED_PREFIX}/{secs}/{path}"))
+        .map_err(|e| internal!("invalid retired key path: {e}").into())
+}
+
+/// Parse the [`ArtiPath`] of a retired key.
+///
+/// Returns the end of its grace period, and its original path,
+/// or `None` if `path` is not the path of a retired key.
+fn parse_retired_path(path: &ArtiPath) -> Option<(SystemTime, ArtiPath)> {
+    let (secs, orig) = path
+        .strip_prefix(RETIRED_PREFIX)?
+        .strip_prefix('/')?
+        .split_once('/')?;
+    let expiry = SystemTime::UNIX_EPOCH + Duration::from_secs(secs.parse().ok()?);
+
+    Some((expiry, ArtiPath::new(orig.into()).ok()?))
+}
+
+/// Describe the [`ArtiPath`] of a retired key.
+///
+/// `ranges` are the parts of the path that matched [`RETIRED_KEY_PATTERN`].
+fn describe_retired(
+    path: &ArtiPath,
+    ranges: &[ArtiPathRange],
+) -> std::result::Result<KeyPathInfo, KeyPathError> {
+    let unrecognized = || KeyPathError::Unrecognized(path.clone().into());
+    let [secs, orig] = ranges else {
+        return Err(unrecognized());
+    };
+    let secs = path
+        .substring(secs)
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(unrecognized)?;
+    let expiry = SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+    let orig = path.substring(orig).ok_or_else(unrecognized)?;
+
+    KeyPathInfo::builder()
+        .summary(format!("Retired key (previously {orig})"))
+        .role("retired".into())
+        .extra_info(
+            "retired_until",
+            humantime::format_rfc3339(expiry).to_string(),
+        )
+        .build()
+        .map_err(|e| internal!("failed to build KeyPathInfo: {e}").into())
+}
+
+/// The pattern matching the [`ArtiPath`]s of retired keys.
+///
+/// The wildcards match the end of the grace period and the original path.
+/// (The literal part must agree with [`RETIRED_PREFIX`].)
+const RETIRED_KEY_PATTERN: &str = "retired/*/**";
+
+/// A [`KeyPathInfoExtractor`] for retired keys.
+static RETIRED_KEY_INFO_EXTRACTOR: PatternKeyInfoExtractor =
+    PatternKeyInfoExtractor::new(RETIRED_KEY_PATTERN, describe_retired);
+
+inventory::submit!(&RETIRED_KEY_INFO_EXTRACTOR as &dyn KeyPathInfoExtractor);
+
+/// The usage of a key, as tracked by a [`KeyMgr`].
+#[derive(Copy, Clone, Debug)]
+struct KeyUsage {
+    /// When we started tracking this key.
+    since: SystemTime,
+    /// The number of times the key was used since then.
+    uses: u64,
+}
+
+/// The key rotation state of a [`KeyMgr`].
+//
+// TODO: the key usage is only tracked in memory,
+// so the age of a key is measured from when this process first saw it.
+// We should store it alongside the key once keystores support key metadata.
+#[derive(Default)]
+pub(super) struct RotationTracker {
+    /// The usage of each key, by `ArtiPath`.
+    usage: Mutex<HashMap<ArtiPath, KeyUsage>>,
+    /// The senders for the streams returned by [`KeyMgr::rotation_events`].
+    subscribers: Mutex<Vec<mpsc::UnboundedSender<RotationEvent>>>,
+}
+
+impl RotationTracker {
+    /// Return the usage of the key at `path`, first recording `now` as its start time if needed.
+    fn usage(&self, path: &ArtiPath, now: SystemTime, add_uses: u64) -> KeyUsage {
+        let mut usage = self.usage.lock().expect("lock poisoned");
+        let usage = usage.entry(path.clone()).or_insert(KeyUsage {
+            since: now,
+            uses: 0,
+        });
+        usage.uses = usage.uses.saturating_add(add_uses);
+        *usage
+    }
+
+    /// Start tracking a freshly generated key at `path`.
+    fn reset(&self, path: ArtiPath, now: SystemTime) {
+        let mut usage = self.usage.lock().expect("lock poisoned");
+        usage.insert(
+            path,
+            KeyUsage {
+                since: now,
+                uses: 0,
+            },
+        );
+    }
+
+    /// Send `event` to all the subscribers, forgetting the ones that have gone away.
+    fn notify(&self, event: &RotationEvent) {
+        let mut subscribers = self.subscribers.lock().expect("lock poisoned");
+        subscribers.retain(|tx| tx.unbounded_send(event.clone()).is_ok());
+    }
+}
+
+/// Return the `ArtiPath` of `key_spec`, or an error if it doesn't have one.
+fn rotation_path(key_spec: &dyn KeySpecifier) -> Result<ArtiPath> {
+    key_spec
+        .arti_path()
+        .map_err(|e| bad_api_usage!("cannot rotate a key without an ArtiPath: {e}").into())
+}
+
+impl KeyMgr {
+    /// Return the [`RotationPolicy`] of the key identified by `key_spec`, if it has one.
+    ///
+    /// If several policies match the key, the first one registered wins.
+    pub fn rotation_policy(&self, key_spec: &dyn KeySpecifier) -> Option<&RotationPolicy> {
+        let path = KeyPath::Arti(key_spec.arti_path().ok()?);
+
+        self.rotation_policies
+            .iter()
+            .find_map(|(pat, policy)| path.matches(pat).then_some(policy))
+    }
+
+    /// Record that the key identified by `key_spec` was used once.
+    ///
+    /// This counts towards the [`max_uses`](RotationPolicy::max_uses) of its [`RotationPolicy`].
+    ///
+    /// Returns an error if `key_spec` does not have an [`ArtiPath`].
+    pub fn record_key_use(&self, key_spec: &dyn KeySpecifier, now: SystemTime) -> Result<()> {
+        let path = rotation_path(key_spec)?;
+        let _: KeyUsage = self.rotation.usage(&path, now, 1);
+        Ok(())
+    }
+
+    /// Check whether the key identified by `key_spec` is due for rotation at time `now`,
+    /// according to its [`RotationPolicy`].
+    ///
+    /// Returns `false` for keys that don't have a rotation policy.
+    ///
+    /// Note: the age of a key is currently measured from when this `KeyMgr`
+    /// first rotated it, or first saw it
+    /// (through this function, or [`KeyMgr::record_key_use`]).
+    pub fn rotation_due(&self, key_spec: &dyn KeySpecifier, now: SystemTime) -> Result<bool> {
+        let Some(policy) = self.rotation_policy(key_spec) else {
+            return Ok(false);
+        };
+        let usage = self.rotation.usage(&rotation_path(key_spec)?, now, 0);
+
+        let too_old = policy
+            .max_age()
+            .is_some_and(|max_age| now >= usage.since + max_age);
+        let overused = policy
+            .max_uses()
+            .is_some_and(|max_uses| usage.uses >= max_uses);
+
+        Ok(too_old || overused)
+    }
+
+    /// Replace the key identified by `key_spec` in the keystore specified by `selector`
+    /// with a newly generated key of type `K`.
+    ///
+    /// If the key has a [`RotationPolicy`] with a nonzero
+    /// [`grace_period`](RotationPolicy::grace_period),
+    /// the old key is retired rather than discarded:
+    /// it stays in the keystore until the end of the grace period,
+    /// and can be retrieved using [`KeyMgr::retired_keys`].
+    /// Retired keys whose grace period has ended are removed by
+    /// [`KeyMgr::remove_expired_keys`].
+    ///
+    /// This generates the new key even if no key existed before,
+    /// and regardless of whether the key is [due](KeyMgr::rotation_due) for rotation.
+    /// On success, a [`RotationEvent::Rotated`] is sent to the [`KeyMgr::rotation_events`] streams,
+    /// and the newly generated key is returned.
+    ///
+    /// Returns an error if `key_spec` does not have an [`ArtiPath`].
+    ///
+    /// **IMPORTANT**: like [`KeyMgr::generate`], this function should not be used
+    /// concurrently with any other `KeyMgr` operation that mutates the same key.
+    pub fn rotate<K>(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        selector: KeystoreSelector,
+        rng: &mut dyn KeygenRng,
+        now: SystemTime,
+    ) -> Result<K>
+    where
+        K: ToEncodableKey,
+        K::Key: Keygen,
+    {
+        let path = rotation_path(key_spec)?;
+        let store = self.select_keystore(&selector)?;
+        let key_type = K::Key::key_type();
+        let grace_period = self
+            .rotation_policy(key_spec)
+            .map(|policy| policy.grace_period())
+            .unwrap_or_default();
+
+        let old_key: Option<K> = self.get_from_store(key_spec, &key_type, [store].into_iter())?;
+        let retired_path = match old_key {
+            Some(old_key) if !grace_period.is_zero() => {
+                let retired_path = retired_path(&path, now + grace_period)?;
+                store.insert(
+                    &old_key.to_encodable_key(),
+                    &retired_path,
+                    &key_type,
+                    &self.op_ctx(),
+                )?;
+                Some(retired_path.into())
+            }
+            _ => None,
+        };
+
+        let key = K::Key::generate(rng)?;
+        store.insert(&key, key_spec, &key_type, &self.op_ctx())?;
+        self.rotation.reset(path.clone(), now);
+
+        self.rotation.notify(&RotationEvent::Rotated {
+            key_path: path.into(),
+            keystore_id: store.id().clone(),
+            retired_path,
+        });
+
+        Ok(K::from_encodable_key(key))
+    }
+
+    /// Return the retired versions of the key identified by `key_spec`
+    /// whose grace period has not ended by `now`.
+    ///
+    /// This searches _all_ keystores.
+    /// See [`KeyMgr::rotate`].
+    pub fn retired_keys<K: ToEncodableKey>(
+        &self,
+        key_spec: &dyn KeySpecifier,
+        now: SystemTime,
+    ) -> Result<Vec<K>> {
+        let path = rotation_path(key_spec)?;
+        let pattern = KeyPathPattern::Arti(format!("{RETIRED_PREFIX}/*/{path}"));
+        let key_type = K::Key::key_type();
+
+        let mut keys = vec![];
+        for entry in self.list_matching(&pattern)? {
+            let unexpired = entry
+                .key_path()
+                .arti()
+                .and_then(parse_retired_path)
+                .is_some_and(|(expiry, _)| expiry > now);
+
+            if unexpired && entry.key_type() == &key_type {
+                keys.extend(self.get_entry::<K>(&entry)?);
+            }
+        }
+
+        Ok(keys)
+    }
+
+    /// Remove all the retired keys whose grace period has ended by `now`, from all keystores.
+    ///
+    /// A [`RotationEvent::RetiredKeyRemoved`] is sent to the [`KeyMgr::rotation_events`] streams
+    /// for each removed key.
+    ///
+    /// Returns the number of keys removed.
+    pub fn remove_expired_keys(&self, now: SystemTime) -> Result<usize> {
+        let pattern = KeyPathPattern::Arti(format!("{RETIRED_PREFIX}/**"));
+
+        let mut removed = 0;
+        for entry in self.list_matching(&pattern)? {
+            let expired = entry
+                .key_path()
+                .arti()
+                .and_then(parse_retired_path)
+                .is_some_and(|(expiry, _)| expiry <= now);
+
+            if expired && self.remove_entry(&entry)?.is_some() {
+                removed += 1;
+                self.rotation.notify(&RotationEvent::RetiredKeyRemoved {
+                    retired_path: entry.key_path().clone(),
+                    keystore_id: entry.keystore_id().clone(),
+                });
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Return a stream of the key rotation events of this `KeyMgr`.
+    ///
+    /// The stream receives all the [`RotationEvent`]s that happen after it is created.
+    pub fn rotation_events(&self) -> impl Stream<Item = RotationEvent> + Send + Unpin + 'static {
+        let (tx, rx) = mpsc::unbounded();
+        self.rotation
+            .subscribers
+            .lock()
+            .expect("lock poisoned")
+            .push(tx);
+        rx
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use crate::test_utils::keymgr_builder;
+    use futures::{FutureExt as _, StreamExt as _};
+    use tempfile::TempDir;
+    use tor_basic_utils::test_rng::testing_rng;
+    use tor_hscrypto::pk::HsDescSigningKeypair;
+    use tor_llcrypto::pk::ed25519;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn keymgr(policy: RotationPolicy) -> (KeyMgr, TempDir) {
+        let (builder, dir) = keymgr_builder();
+        let mgr = builder
+            .rotation_policy(KeyPathPattern::Arti("hss/*/ks_hs_desc_sign".into()), policy)
+            .build()
+            .unwrap();
+
+        (mgr, dir)
+    }
+
+    fn public(key: &HsDescSigningKeypair) -> ed25519::PublicKey {
+        key.as_ref().verifying_key()
+    }
+
+    #[test]
+    fn paths() {
+        let path = ArtiPath::new("hss/foo/ks_hs_desc_sign+1".into()).unwrap();
+        let expiry = SystemTime::UNIX_EPOCH + Duration::from_secs(1234);
+        let retired = retired_path(&path, expiry).unwrap();
+        assert_eq!(retired.as_ref(), "retired/1234/hss/foo/ks_hs_desc_sign+1");
+        assert_eq!(parse_retired_path(&retired), Some((expiry, path.clone())));
+        assert_eq!(parse_retired_path(&path), None);
+
+        let info = RETIRED_KEY_INFO_EXTRACTOR
+            .describe(&retired.into())
+            .unwrap();
+        assert_eq!(info.role(), "retired");
+        assert_eq!(
+            info.extra_info().get("retired_until").unwrap(),
+            "1970-01-01T00:20:34Z"
+        );
+    }
+
+    #[test]
+    fn rotation_due() {
+        let policy = RotationPolicy::builder()
+            .max_age(HOUR)
+            .max_uses(2)
+            .build()
+            .unwrap();
+        let (mgr, _dir) = keymgr(policy);
+        let now = SystemTime::now();
+        let spec = ArtiPath::new("hss/foo/ks_hs_desc_sign".into()).unwrap();
+        let other = ArtiPath::new("hss/foo/ks_hs_id".into()).unwrap();
+
+        assert!(mgr.rotation_policy(&other).is_none());
+        assert!(!mgr.rotation_due(&other, now + HOUR * 10).unwrap());
+
+        assert!(!mgr.rotation_due(&spec, now).unwrap());
+        assert!(mgr.rotation_due(&spec, now + HOUR).unwrap());
+
+        let _: HsDescSigningKeypair = mgr
+            .rotate(&spec, KeystoreSelector::Primary, &mut testing_rng(), now)
+            .unwrap();
+        assert!(!mgr.rotation_due(&spec, now).unwrap());
+        mgr.record_key_use(&spec, now).unwrap();
+        assert!(!mgr.rotation_due(&spec, now).unwrap());
+        mgr.record_key_use(&spec, now).unwrap();
+        assert!(mgr.rotation_due(&spec, now).unwrap());
+    }
+
+    #[test]
+    fn rotate() {
+        let policy = RotationPolicy::builder()
+            .grace_period(HOUR)
+            .build()
+            .unwrap();
+        let (mgr, _dir) = keymgr(policy);
+        let mut events = mgr.rotation_events();
+        let now = SystemTime::now();
+        let spec = ArtiPath::new("hss/foo/ks_hs_desc_sign".into()).unwrap();
+        let mut rng = testing_rng();
+
+        // There is no old key to retire.
+        let first: HsDescSigningKeypair = mgr
+            .rotate(&spec, KeystoreSelector::Primary, &mut rng, now)
+            .unwrap();
+        let Some(RotationEvent::Rotated { retired_path, .. }) =
+            events.next().now_or_never().flatten()
+        else {
+            panic!("no rotation event");
+        };
+        assert!(retired_path.is_none());
+
+        let second: HsDescSigningKeypair = mgr
+            .rotate(&spec, KeystoreSelector::Primary, &mut rng, now)
+            .unwrap();
+        let current = mgr.get::<HsDescSigningKeypair>(&spec).unwrap().unwrap();
+        assert_eq!(public(&current), public(&second));
+        assert_ne!(public(&first), public(&second));
+        let Some(RotationEvent::Rotated { retired_path, .. }) =
+            events.next().now_or_never().flatten()
+        else {
+            panic!("no rotation event");
+        };
+        assert!(retired_path.is_some());
+
+        // The first key is retired until the end of the grace period.
+        let retired = mgr
+            .retired_keys::<HsDescSigningKeypair>(&spec, now)
+            .unwrap();
+        assert_eq!(retired.len(), 1);
+        assert_eq!(public(&retired[0]), public(&first));
+        assert!(mgr.describe(&retired_path.unwrap()).is_ok());
+
+        assert_eq!(mgr.remove_expired_keys(now).unwrap(), 0);
+        assert!(mgr
+            .retired_keys::<HsDescSigningKeypair>(&spec, now + HOUR)
+            .unwrap()
+            .is_empty());
+        assert_eq!(mgr.remove_expired_keys(now + HOUR).unwrap(), 1);
+        assert!(matches!(
+            events.next().now_or_never().flatten(),
+            Some(RotationEvent::RetiredKeyRemoved { .. })
+        ));
+        assert!(mgr
+            .retired_keys::<HsDescSigningKeypair>(&spec, now)
+            .unwrap()
+            .is_empty());
+
+        // Keys without an ArtiPath can't be rotated.
+        let ctor_spec = crate::CTorPath::ClientHsDescEncKey(
+            "mnyizjj7m3hpcr7i5afph3zt7maa65johyu2ruis6z7cmnjmaj3h6tad.onion"
+                .parse()
+                .unwrap(),
+        );
+        assert!(mgr
+            .rotate::<HsDescSigningKeypair>(&ctor_spec, KeystoreSelector::Primary, &mut rng, now)
+            .is_err());
+    }
+}
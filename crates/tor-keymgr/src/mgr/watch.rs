@@ -0,0 +1,374 @@
+//! Watching the key stores for changes.
+//!
+//! See [`KeyMgr::watch`] for more details.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll};
+
+use digest::Digest as _;
+use futures::channel::mpsc;
+use futures::{Stream, StreamExt as _};
+use notify::Watcher as _;
+use tor_key_forge::KeyType;
+use tor_llcrypto::d::Sha256;
+use tracing::debug;
+
+use crate::{Error, KeyMgr, KeyPath, KeyPathPatternSet, KeystoreId, Result};
+
+cfg_if::cfg_if! {
+    if #[cfg(any(target_os = "linux", target_os = "android", target_os = "windows"))] {
+        /// The concrete type of the underlying watcher.
+        type NotifyWatcher = notify::RecommendedWatcher;
+    } else {
+        /// The concrete type of the underlying watcher.
+        type NotifyWatcher = notify::PollWatcher;
+    }
+}
+
+/// A change to one of the keys watched using [`KeyMgr::watch`].
+#[derive(Clone, Debug, PartialEq, Eq, amplify::Getters)]
+pub struct KeyChange {
+    /// What happened to the key.
+    kind: KeyChangeKind,
+    /// The [`KeyPath`] of the key.
+    key_path: KeyPath,
+    /// The [`KeyType`] of the key.
+    key_type: KeyType,
+    /// The [`KeystoreId`] of the key store holding the key.
+    keystore_id: KeystoreId,
+}
+
+/// The kind of a [`KeyChange`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum KeyChangeKind {
+    /// The key was added to the key store.
+    Added,
+    /// The key was removed from the key store.
+    Removed,
+    /// The contents of the key were replaced.
+    ///
+    /// Only reported for key stores that support [`Keystore::get_raw`](crate::Keystore::get_raw).
+    Replaced,
+}
+
+/// The stream of [`KeyChange`]s returned by [`KeyMgr::watch`].
+#[must_use = "streams do nothing unless polled"]
+pub struct KeyChanges {
+    /// The key manager whose key stores we are watching.
+    keymgr: Weak<KeyMgr>,
+    /// The patterns of the keys we are interested in.
+    patterns: KeyPathPatternSet,
+    /// The keys we saw the last time we looked.
+    snapshot: Snapshot,
+    /// The changes we have found, but not yet returned.
+    pending: VecDeque<KeyChange>,
+    /// Receives a message whenever a key store might have changed.
+    wakeups: mpsc::Receiver<()>,
+    /// The filesystem watcher of the key store directories.
+    ///
+    /// Never read: we only hold on to it so that it keeps watching.
+    _watcher: NotifyWatcher,
+}
+
+/// The keys found in the key stores, with a digest of their contents, if available.
+type Snapshot = HashMap<SnapshotEntry, Option<[u8; 32]>>;
+
+/// The key of a [`Snapshot`].
+type SnapshotEntry = (KeystoreId, KeyPath, KeyType);
+
+/// The senders for the streams returned by [`KeyMgr::watch`].
+#[derive(Default)]
+pub(super) struct WatchTracker {
+    /// The wake-up senders of the [`KeyChanges`] streams.
+    subscribers: Mutex<Vec<mpsc::Sender<()>>>,
+}
+
+impl WatchTracker {
+    /// Tell all the subscribers to look for changes, forgetting the ones that have gone away.
+    pub(super) fn wake(&self) {
+        let mut subscribers = self.subscribers.lock().expect("lock poisoned");
+        // A full channel means the subscriber already has a wake-up pending.
+        subscribers.retain_mut(|tx| !matches!(tx.try_send(()), Err(e) if e.is_disconnected()));
+    }
+}
+
+impl KeyMgr {
+    /// Return a stream of the changes to the keys that match any of the specified `patterns`.
+    ///
+    /// The stream reports the keys that are added to, removed from, or replaced in
+    /// any of the key stores of this `KeyMgr`, after the stream is created.
+    ///
+    /// Changes made through this `KeyMgr` are noticed right away.
+    /// Changes made by other processes are noticed by watching the directories of the
+    /// key stores that have one (see [`Keystore::dir`](crate::Keystore::dir)):
+    /// changes to the other key stores made outside this `KeyMgr` are not reported.
+    ///
+    /// The changes are found by listing the key stores and comparing the result
+    /// to what they contained the last time we looked,
+    /// so a key that is changed and then changed back in quick succession
+    /// might not be reported at all.
+    /// Each change is reported once per key store, even if the key is shadowed by a key
+    /// with the same [`KeyPath`] in another store.
+    ///
+    /// The stream ends after this `KeyMgr` is dropped.
+    pub fn watch(self: &Arc<Self>, patterns: KeyPathPatternSet) -> Result<KeyChanges> {
+        let (tx, wakeups) = mpsc::channel(1);
+
+        let mut dirs = self
+            .all_stores()
+            .filter_map(|store| store.dir())
+            .filter(|dir| dir.is_dir())
+            .map(PathBuf::from)
+            .collect::<Vec<_>>();
+        dirs.sort();
+        dirs.dedup();
+
+        let mut watcher = new_watcher(tx.clone())?;
+        for dir in &dirs {
+            watcher
+                .watch(dir, notify::RecursiveMode::Recursive)
+                .map_err(|e| Error::Watch(Arc::new(e)))?;
+        }
+
+        let snapshot = scan(self, &patterns, &Snapshot::default());
+        self.watchers
+            .subscribers
+            .lock()
+            .expect("lock poisoned")
+            .push(tx);
+
+        Ok(KeyChanges {
+            keymgr: Arc::downgrade(self),
+            patterns,
+            snapshot,
+            pending: VecDeque::new(),
+            wakeups,
+            _watcher: watcher,
+        })
+    }
+}
+
+impl KeyChanges {
+    /// Compare the contents of the key stores to our snapshot, and queue the changes.
+    fn rescan(&mut self, keymgr: &KeyMgr) {
+        let snapshot = scan(keymgr, &self.patterns, &self.snapshot);
+
+        let change = |kind, (keystore_id, key_path, key_type): &SnapshotEntry| KeyChange {
+            kind,
+            key_path: key_path.clone(),
+            key_type: key_type.clone(),
+            keystore_id: keystore_id.clone(),
+        };
+
+        for (entry, digest) in &snapshot {
+            match self.snapshot.get(entry) {
+                None => self.pending.push_back(change(KeyChangeKind::Added, entry)),
+                Some(old) if old.is_some() && digest.is_some() && old != digest => {
+                    self.pending
+                        .push_back(change(KeyChangeKind::Replaced, entry));
+                }
+                Some(_) => {}
+            }
+        }
+        for entry in self.snapshot.keys() {
+            if !snapshot.contains_key(entry) {
+                self.pending
+                    .push_back(change(KeyChangeKind::Removed, entry));
+            }
+        }
+
+        self.snapshot = snapshot;
+    }
+}
+
+impl Stream for KeyChanges {
+    type Item = KeyChange;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<KeyChange>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(change) = this.pending.pop_front() {
+                return Poll::Ready(Some(change));
+            }
+
+            match this.wakeups.poll_next_unpin(cx) {
+                Poll::Ready(Some(())) => {}
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+            // Several wake-ups only need one rescan.
+            while let Ok(Some(())) = this.wakeups.try_next() {}
+
+            let Some(keymgr) = this.keymgr.upgrade() else {
+                this.wakeups.close();
+                return Poll::Ready(None);
+            };
+            // TODO: the key stores are accessed synchronously, like everywhere else in KeyMgr.
+            this.rescan(&keymgr);
+        }
+    }
+}
+
+/// Create a filesystem watcher that sends a wake-up to `tx` whenever something changes.
+fn new_watcher(tx: mpsc::Sender<()>) -> Result<NotifyWatcher> {
+    cfg_if::cfg_if! {
+        if #[cfg(any(target_os = "linux", target_os = "android", target_os = "windows"))] {
+            let config = notify::Config::default();
+        } else {
+            /// The polling frequency, for use with the `PollWatcher`.
+            const WATCHER_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+            let config = notify::Config::default()
+                .with_poll_interval(WATCHER_POLL_INTERVAL);
+        }
+    }
+
+    let mut tx = tx;
+    let handler = move |event: notify::Result<notify::Event>| {
+        let relevant = match event {
+            Ok(event) => !event.kind.is_access(),
+            // We might have missed an event, so look anyway.
+            Err(_) => true,
+        };
+        if relevant {
+            // If the channel is full, a wake-up is already pending;
+            // if it's disconnected, nobody is watching anymore.
+            let _ = tx.try_send(());
+        }
+    };
+
+    NotifyWatcher::new(handler, config).map_err(|e| Error::Watch(Arc::new(e)))
+}
+
+/// List the keys matching `patterns` in all the key stores of `keymgr`.
+///
+/// If a key store can't be listed, its entries from `old` are kept.
+fn scan(keymgr: &KeyMgr, patterns: &KeyPathPatternSet, old: &Snapshot) -> Snapshot {
+    let mut snapshot = Snapshot::default();
+
+    for store in keymgr.all_stores() {
+        let keystore_id = store.id();
+        let entries = match store.list(&keymgr.op_ctx()) {
+            Ok(entries) => entries,
+            Err(e) => {
+                debug!("failed to list keystore {keystore_id} while watching it: {e}");
+                snapshot.extend(
+                    old.iter()
+                        .filter(|((id, _, _), _)| id == keystore_id)
+                        .map(|(entry, digest)| (entry.clone(), *digest)),
+                );
+                continue;
+            }
+        };
+
+        for (key_path, key_type) in entries {
+            if !patterns.matches(&key_path) {
+                continue;
+            }
+            let digest = store
+                .get_raw(&key_path, &key_type, &keymgr.op_ctx())
+                .ok()
+                .flatten()
+                .map(|data| Sha256::digest(data.as_bytes()).into());
+            snapshot.insert((keystore_id.clone(), key_path, key_type), digest);
+        }
+    }
+
+    snapshot
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use crate::{ArtiNativeKeystore, ArtiPath, KeyMgrBuilder, KeyPathPattern, KeystoreSelector};
+    use futures::executor::block_on;
+    use futures::FutureExt as _;
+    use tempfile::tempdir;
+    use tor_basic_utils::test_rng::testing_rng;
+    use tor_hscrypto::pk::HsDescSigningKeypair;
+    use tor_key_forge::{EncodableKey as _, ToEncodableKey};
+    use tor_llcrypto::pk::ed25519;
+
+    fn keypair() -> HsDescSigningKeypair {
+        ed25519::Keypair::generate(&mut testing_rng()).into()
+    }
+
+    #[test]
+    fn watch() {
+        let dir = tempdir().unwrap();
+        let store = ArtiNativeKeystore::from_path_and_mistrust(
+            dir.path(),
+            &fs_mistrust::Mistrust::new_dangerously_trust_everyone(),
+        )
+        .unwrap();
+        let mgr = Arc::new(
+            KeyMgrBuilder::default()
+                .primary_store(Box::new(store))
+                .build()
+                .unwrap(),
+        );
+        let key_type = <HsDescSigningKeypair as ToEncodableKey>::Key::key_type();
+        let watched = ArtiPath::new("hss/foo/ks_hs_desc_sign".into()).unwrap();
+        let other = ArtiPath::new("hss/bar/ks_hs_desc_sign".into()).unwrap();
+
+        let mut changes = mgr
+            .watch(KeyPathPattern::Arti("hss/foo/*".into()).into())
+            .unwrap();
+        let mut expect = |kind| {
+            let change = block_on(changes.next()).unwrap();
+            assert_eq!(change.kind(), &kind);
+            assert_eq!(change.key_path(), &KeyPath::from(watched.clone()));
+            assert_eq!(change.key_type(), &key_type);
+            assert_eq!(change.keystore_id().as_ref(), "arti");
+        };
+
+        // Keys that don't match the patterns aren't reported.
+        mgr.insert(keypair(), &other, KeystoreSelector::Primary, true)
+            .unwrap();
+
+        mgr.insert(keypair(), &watched, KeystoreSelector::Primary, true)
+            .unwrap();
+        expect(KeyChangeKind::Added);
+
+        mgr.insert(keypair(), &watched, KeystoreSelector::Primary, true)
+            .unwrap();
+        expect(KeyChangeKind::Replaced);
+
+        mgr.remove::<HsDescSigningKeypair>(&watched, KeystoreSelector::Primary)
+            .unwrap()
+            .unwrap();
+        expect(KeyChangeKind::Removed);
+
+        // Changes made behind the KeyMgr's back are noticed too.
+        let file = format!("ks_hs_desc_sign.{}", key_type.arti_extension());
+        std::fs::copy(
+            dir.path().join("hss/bar").join(&file),
+            dir.path().join("hss/foo").join(&file),
+        )
+        .unwrap();
+        expect(KeyChangeKind::Added);
+
+        drop(mgr);
+        // Wake the stream up, so that it notices the KeyMgr is gone.
+        std::fs::remove_dir_all(dir.path().join("hss")).unwrap();
+        assert!(block_on(changes.next()).is_none());
+        assert!(changes.next().now_or_never().unwrap().is_none());
+    }
+}
@@ -170,9 +170,41 @@ mod key {
     }
 }
 
+/// A module exporting [`KeyMgr`](crate::KeyMgr) fixtures used for testing.
+#[cfg(all(test, feature = "keymgr"))]
+mod mgr {
+    use crate::{ArtiNativeKeystore, KeyMgr, KeyMgrBuilder};
+    use tempfile::{tempdir, TempDir};
+
+    /// Return a [`KeyMgrBuilder`] whose primary store is an [`ArtiNativeKeystore`]
+    /// in a new temporary directory.
+    ///
+    /// The directory is deleted when the returned [`TempDir`] is dropped.
+    pub(crate) fn keymgr_builder() -> (KeyMgrBuilder, TempDir) {
+        let dir = tempdir().unwrap();
+        let store = ArtiNativeKeystore::from_path_and_mistrust(
+            dir.path(),
+            &fs_mistrust::Mistrust::new_dangerously_trust_everyone(),
+        )
+        .unwrap();
+        let builder = KeyMgrBuilder::default().primary_store(Box::new(store));
+
+        (builder, dir)
+    }
+
+    /// Return a [`KeyMgr`] built from [`keymgr_builder`], with no other settings.
+    pub(crate) fn keymgr() -> (KeyMgr, TempDir) {
+        let (builder, dir) = keymgr_builder();
+        (builder.build().unwrap(), dir)
+    }
+}
+
 #[cfg(test)]
 pub(crate) use specifier::*;
 
+#[cfg(all(test, feature = "keymgr"))]
+pub(crate) use mgr::*;
+
 #[cfg(test)]
 pub(crate) use key::*;
 
@@ -185,11 +217,15 @@ mod internal {
     /// Assert that the specified key can be found (or not) in `key_store`.
     macro_rules! assert_found {
         ($key_store:expr, $key_spec:expr, $key_type:expr, $found:expr) => {{
-            let res = $key_store.get($key_spec, $key_type).unwrap();
+            let res = $key_store
+                .get($key_spec, $key_type, &$crate::OpContext::default())
+                .unwrap();
             if $found {
                 assert!(res.is_some());
                 // Ensure contains() agrees with get()
-                assert!($key_store.contains($key_spec, $key_type).unwrap());
+                assert!($key_store
+                    .contains($key_spec, $key_type, &$crate::OpContext::default())
+                    .unwrap());
             } else {
                 assert!(res.is_none());
             }
@@ -9,7 +9,7 @@ use std::fmt;
 
 use derive_deftly::Deftly;
 use derive_more::{Display, From};
-use safelog::Redactable;
+use safelog::{Category, Redactable};
 use tor_llcrypto::pk::{
     ed25519::{Ed25519Identity, ED25519_ID_LEN},
     rsa::{RsaIdentity, RSA_ID_LEN},
@@ -207,6 +207,10 @@ impl Redactable for RelayId {
     fn debug_redacted(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.as_ref().debug_redacted(f)
     }
+
+    fn category(&self) -> Category {
+        Category::RelayIdentity
+    }
 }
 
 impl<'a> Redactable for RelayIdRef<'a> {
@@ -224,6 +228,10 @@ impl<'a> Redactable for RelayIdRef<'a> {
             RelayIdRef::Rsa(k) => Debug::fmt(*k.redacted(), f),
         }
     }
+
+    fn category(&self) -> Category {
+        Category::RelayIdentity
+    }
 }
 
 /// Expand to an implementation for PartialEq for a given key type.
@@ -1,6 +1,6 @@
 //! Owned variants of [`ChanTarget`] and [`CircTarget`].
 
-use safelog::Redactable;
+use safelog::{Category, Redactable};
 use serde::{Deserialize, Serialize};
 use std::fmt::{self, Display};
 use std::net::SocketAddr;
@@ -87,6 +87,10 @@ impl Redactable for RelayIds {
     fn display_redacted(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.display_relay_ids().redacted())
     }
+
+    fn category(&self) -> Category {
+        Category::RelayIdentity
+    }
 }
 
 /// OwnedChanTarget is a summary of a [`ChanTarget`] that owns all of its
@@ -2,7 +2,7 @@
 //! that Tor can connect to, directly or indirectly.
 
 use derive_deftly::derive_deftly_adhoc;
-use safelog::Redactable;
+use safelog::{Category, Redactable};
 use std::{fmt, iter::FusedIterator, net::SocketAddr};
 use tor_llcrypto::pk;
 
@@ -222,6 +222,10 @@ impl<'a, T: HasRelayIds + ?Sized> Redactable for DisplayRelayIds<'a, T> {
     fn display_redacted(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.fmt_impl(f, true)
     }
+
+    fn category(&self) -> Category {
+        Category::RelayIdentity
+    }
 }
 
 /// An iterator over all of the relay identities held by a [`HasRelayIds`]
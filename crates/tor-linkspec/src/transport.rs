@@ -11,7 +11,7 @@ use std::net::SocketAddr;
 use std::slice;
 use std::str::FromStr;
 
-use safelog::Redactable;
+use safelog::{Category, Redactable};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
@@ -434,6 +434,10 @@ impl Redactable for BridgeAddr {
     fn display_redacted(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.0.display_redacted(f)
     }
+
+    fn category(&self) -> Category {
+        Category::Address
+    }
 }
 
 impl Redactable for PtTargetAddr {
@@ -443,6 +447,10 @@ impl Redactable for PtTargetAddr {
             None => write!(f, "{}", NONE_ADDR),
         }
     }
+
+    fn category(&self) -> Category {
+        Category::Address
+    }
 }
 
 /// A set of options to be passed along to a pluggable transport along with a
@@ -0,0 +1,213 @@
+//! Information about the cryptographic backends in use, and self-tests for them.
+//!
+//! Some of our algorithms can be provided by more than one implementation,
+//! chosen at compile time with the (non-additive) `with-openssl`,
+//! `with-aws-lc` and `with-ring` features.
+//! If more than one of them is enabled, each algorithm is taken from the
+//! first of OpenSSL, aws-lc and ring that provides it.
+//! [`self_test`] checks that whichever implementations were selected give the
+//! same answers as every other backend on a set of known test vectors,
+//! so that applications with platform-crypto requirements can verify
+//! their build at startup.
+//!
+//! The relay cell crypto in `tor-proto` uses the [`cipher`](crate::cipher)
+//! and [`d`](crate::d) implementations selected here.
+//! The TLS implementation used for channels is chosen by `tor-rtcompat`:
+//! its `aws-lc` feature makes the rustls runtimes use aws-lc instead of ring.
+
+use std::sync::OnceLock;
+
+use ctr::cipher::{KeyIvInit as _, StreamCipher as _};
+use digest::Digest as _;
+use hex_literal::hex;
+
+use crate::cipher::aes::{Aes128Ctr, Aes256Ctr};
+use crate::d::{Sha1, Sha256};
+
+/// An implementation of some of our cryptographic algorithms.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, derive_more::Display)]
+#[non_exhaustive]
+pub enum Backend {
+    /// The pure-Rust implementations from the RustCrypto project.
+    #[display("RustCrypto")]
+    RustCrypto,
+    /// OpenSSL.
+    #[display("OpenSSL")]
+    OpenSsl,
+    /// aws-lc.
+    #[display("aws-lc")]
+    AwsLc,
+    /// ring.
+    #[display("ring")]
+    Ring,
+}
+
+/// Return the backend that implements AES in counter mode.
+pub fn aes_backend() -> Backend {
+    if cfg!(feature = "with-openssl") {
+        Backend::OpenSsl
+    } else if cfg!(feature = "with-aws-lc") {
+        Backend::AwsLc
+    } else {
+        Backend::RustCrypto
+    }
+}
+
+/// Return the backend that implements SHA1.
+pub fn sha1_backend() -> Backend {
+    if cfg!(feature = "with-openssl") {
+        Backend::OpenSsl
+    } else if cfg!(feature = "with-aws-lc") {
+        Backend::AwsLc
+    } else if cfg!(feature = "with-ring") {
+        Backend::Ring
+    } else {
+        Backend::RustCrypto
+    }
+}
+
+/// An error returned when a cryptographic self-test fails.
+///
+/// This indicates a broken or miscompiled backend: the affected algorithm
+/// must not be used.
+#[derive(Clone, Debug, thiserror::Error)]
+#[error("Cryptographic self-test failed for {algorithm} (backend: {backend})")]
+#[non_exhaustive]
+pub struct SelfTestError {
+    /// The algorithm whose output was wrong.
+    pub algorithm: &'static str,
+    /// The backend that implements it.
+    pub backend: Backend,
+}
+
+/// Check every algorithm with more than one possible backend against known test vectors.
+///
+/// The tests are only run the first time this is called:
+/// later calls return the same result.
+pub fn self_test() -> Result<(), SelfTestError> {
+    /// The result of running the self-tests.
+    static RESULT: OnceLock<Result<(), SelfTestError>> = OnceLock::new();
+    RESULT.get_or_init(run_self_test).clone()
+}
+
+/// Run the self-tests for [`self_test`].
+fn run_self_test() -> Result<(), SelfTestError> {
+    // From NIST SP 800-38A, appendix F.5.
+    const CTR_IV: [u8; 16] = hex!("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+    const CTR_PLAINTEXT: [u8; 32] =
+        hex!("6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51");
+    const AES128_KEY: [u8; 16] = hex!("2b7e151628aed2a6abf7158809cf4f3c");
+    const AES128_CTR_CIPHERTEXT: [u8; 32] =
+        hex!("874d6191b620e3261bef6864990db6ce9806f66b7970fdff8617187bb9fffdff");
+    const AES256_KEY: [u8; 32] =
+        hex!("603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4");
+    const AES256_CTR_CIPHERTEXT: [u8; 32] =
+        hex!("601ec313775789a5b7a7f504bbf3d228f443e3ca4d62b59aca84e990cacaf5c5");
+    // From FIPS 180-2, appendix A.
+    const SHA1_ABC: [u8; 20] = hex!("a9993e364706816aba3e25717850c26c9cd0d89d");
+    const SHA256_ABC: [u8; 32] =
+        hex!("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+
+    let fail = |algorithm, backend| Err(SelfTestError { algorithm, backend });
+
+    let mut buf = CTR_PLAINTEXT;
+    // Apply the keystream in two uneven pieces, to check that the
+    // counter state is kept correctly between calls.
+    let mut aes = Aes128Ctr::new(&AES128_KEY.into(), &CTR_IV.into());
+    aes.apply_keystream(&mut buf[..7]);
+    aes.apply_keystream(&mut buf[7..]);
+    if buf != AES128_CTR_CIPHERTEXT {
+        return fail("AES-128-CTR", aes_backend());
+    }
+
+    let mut buf = CTR_PLAINTEXT;
+    let mut aes = Aes256Ctr::new(&AES256_KEY.into(), &CTR_IV.into());
+    aes.apply_keystream(&mut buf[..19]);
+    aes.apply_keystream(&mut buf[19..]);
+    if buf != AES256_CTR_CIPHERTEXT {
+        return fail("AES-256-CTR", aes_backend());
+    }
+
+    let mut sha1 = Sha1::new();
+    sha1.update(b"a");
+    sha1.update(b"bc");
+    if sha1.finalize()[..] != SHA1_ABC {
+        return fail("SHA1", sha1_backend());
+    }
+    if Sha256::digest(b"abc")[..] != SHA256_ABC {
+        return fail("SHA256", Backend::RustCrypto);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+
+    #[test]
+    fn self_test_passes() {
+        self_test().unwrap();
+    }
+
+    #[test]
+    fn backends() {
+        #[cfg(not(any(
+            feature = "with-openssl",
+            feature = "with-aws-lc",
+            feature = "with-ring"
+        )))]
+        assert_eq!(sha1_backend(), Backend::RustCrypto);
+        #[cfg(all(
+            feature = "with-ring",
+            not(any(feature = "with-openssl", feature = "with-aws-lc"))
+        ))]
+        assert_eq!(sha1_backend(), Backend::Ring);
+        #[cfg(all(feature = "with-aws-lc", not(feature = "with-openssl")))]
+        {
+            assert_eq!(aes_backend(), Backend::AwsLc);
+            assert_eq!(sha1_backend(), Backend::AwsLc);
+        }
+        #[cfg(feature = "with-openssl")]
+        assert_eq!(aes_backend(), Backend::OpenSsl);
+    }
+
+    #[test]
+    fn aes_matches_rustcrypto() {
+        use cipher::{KeyIvInit, StreamCipher};
+
+        // Check the selected backend against the RustCrypto implementation,
+        // applying the keystream in pieces that don't line up with AES blocks.
+        let mut rng = tor_basic_utils::test_rng::testing_rng();
+        let mut key = [0_u8; 16];
+        let mut iv = [0_u8; 16];
+        let mut data = vec![0_u8; 1000];
+        rand::RngCore::fill_bytes(&mut rng, &mut key);
+        rand::RngCore::fill_bytes(&mut rng, &mut iv);
+        rand::RngCore::fill_bytes(&mut rng, &mut data);
+
+        let mut expected = data.clone();
+        ctr::Ctr128BE::<aes::Aes128>::new(&key.into(), &iv.into()).apply_keystream(&mut expected);
+
+        let mut aes = Aes128Ctr::new(&key.into(), &iv.into());
+        for chunk in data.chunks_mut(509) {
+            let (a, b) = chunk.split_at_mut(3);
+            aes.apply_keystream(a);
+            aes.apply_keystream(b);
+        }
+        assert_eq!(data, expected);
+    }
+}
@@ -8,7 +8,7 @@
 /// These ciphers implement the `cipher::StreamCipher` trait, so use
 /// the [`cipher`](https://docs.rs/cipher) crate to access them.
 #[cfg_attr(docsrs, doc(cfg(all())))]
-#[cfg(not(feature = "with-openssl"))]
+#[cfg(not(any(feature = "with-openssl", feature = "with-aws-lc")))]
 pub mod aes {
     // These implement StreamCipher.
     /// AES128 in counter mode as used by Tor.
@@ -126,3 +126,131 @@ pub mod aes {
         }
     }
 }
+
+/// Compatibility layer between aws-lc and `cipher::StreamCipher`.
+///
+/// These ciphers implement the `cipher::StreamCipher` trait, so use
+/// the [`cipher`](https://docs.rs/cipher) crate to access them.
+#[cfg_attr(docsrs, doc(cfg(all())))]
+#[cfg(all(feature = "with-aws-lc", not(feature = "with-openssl")))]
+pub mod aes {
+    use aws_lc_rs::cipher::{
+        EncryptionContext, StreamingEncryptingKey, UnboundCipherKey, AES_128, AES_256,
+    };
+    use aws_lc_rs::iv::FixedLength;
+    use cipher::generic_array::GenericArray;
+    use cipher::inout::InOutBuf;
+    use cipher::{InnerIvInit, IvSizeUser, StreamCipher, StreamCipherError};
+    use digest::crypto_common::{InnerUser, KeyInit, KeySizeUser};
+    use zeroize::{Zeroize, ZeroizeOnDrop};
+
+    /// Apply the keystream of `key` to `buf`.
+    fn apply_keystream(
+        key: &mut StreamingEncryptingKey,
+        mut buf: InOutBuf<'_, '_, u8>,
+    ) -> Result<(), StreamCipherError> {
+        // aws-lc insists on room for an extra block of output,
+        // although in counter mode it never produces more output than input.
+        let mut out = vec![0_u8; buf.len() + key.algorithm().block_len() - 1];
+        let written = key
+            .update(buf.get_in(), &mut out)
+            .map_err(|_| StreamCipherError)?
+            .written()
+            .len();
+        if written != buf.len() {
+            return Err(StreamCipherError);
+        }
+        buf.get_out().copy_from_slice(&out[..written]);
+        Ok(())
+    }
+
+    /// AES 128 in counter mode as used by Tor.
+    pub struct Aes128Ctr(StreamingEncryptingKey);
+
+    /// AES 128 key
+    #[derive(Zeroize, ZeroizeOnDrop)]
+    pub struct Aes128Key([u8; 16]);
+
+    impl KeySizeUser for Aes128Key {
+        type KeySize = typenum::consts::U16;
+    }
+
+    impl KeyInit for Aes128Key {
+        fn new(key: &GenericArray<u8, Self::KeySize>) -> Self {
+            Aes128Key((*key).into())
+        }
+    }
+
+    impl InnerUser for Aes128Ctr {
+        type Inner = Aes128Key;
+    }
+
+    impl IvSizeUser for Aes128Ctr {
+        type IvSize = typenum::consts::U16;
+    }
+
+    impl StreamCipher for Aes128Ctr {
+        fn try_apply_keystream_inout(
+            &mut self,
+            buf: InOutBuf<'_, '_, u8>,
+        ) -> Result<(), StreamCipherError> {
+            apply_keystream(&mut self.0, buf)
+        }
+    }
+
+    impl InnerIvInit for Aes128Ctr {
+        fn inner_iv_init(inner: Self::Inner, iv: &GenericArray<u8, Self::IvSize>) -> Self {
+            let key = UnboundCipherKey::new(&AES_128, &inner.0)
+                .expect("aws-lc error while initializing Aes128Ctr");
+            let context = EncryptionContext::Iv128(FixedLength::from(<[u8; 16]>::from(*iv)));
+            let key = StreamingEncryptingKey::less_safe_ctr(key, context)
+                .expect("aws-lc error while initializing Aes128Ctr");
+            Aes128Ctr(key)
+        }
+    }
+
+    /// AES 256 in counter mode as used by Tor.
+    pub struct Aes256Ctr(StreamingEncryptingKey);
+
+    /// AES 256 key
+    #[derive(Zeroize, ZeroizeOnDrop)]
+    pub struct Aes256Key([u8; 32]);
+
+    impl KeySizeUser for Aes256Key {
+        type KeySize = typenum::consts::U32;
+    }
+
+    impl KeyInit for Aes256Key {
+        fn new(key: &GenericArray<u8, Self::KeySize>) -> Self {
+            Aes256Key((*key).into())
+        }
+    }
+
+    impl InnerUser for Aes256Ctr {
+        type Inner = Aes256Key;
+    }
+
+    impl IvSizeUser for Aes256Ctr {
+        type IvSize = typenum::consts::U16;
+    }
+
+    impl StreamCipher for Aes256Ctr {
+        fn try_apply_keystream_inout(
+            &mut self,
+            buf: InOutBuf<'_, '_, u8>,
+        ) -> Result<(), StreamCipherError> {
+            apply_keystream(&mut self.0, buf)
+        }
+    }
+
+    impl InnerIvInit for Aes256Ctr {
+        fn inner_iv_init(inner: Self::Inner, iv: &GenericArray<u8, Self::IvSize>) -> Self {
+            let key = UnboundCipherKey::new(&AES_256, &inner.0)
+                .expect("aws-lc error while initializing Aes256Ctr");
+            let context = EncryptionContext::Iv128(FixedLength::from(<[u8; 16]>::from(*iv)));
+            let key = StreamingEncryptingKey::less_safe_ctr(key, context)
+                .expect("aws-lc error while initializing Aes256Ctr");
+            Aes256Ctr(key)
+        }
+    }
+}
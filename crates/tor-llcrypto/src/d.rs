@@ -7,9 +7,20 @@
 //! Other code should access these digests via the traits in the
 //! [`digest`] crate.
 
+#[cfg(all(feature = "with-aws-lc", not(feature = "with-openssl")))]
+pub use aws_lc_compat::Sha1;
 #[cfg(feature = "with-openssl")]
 pub use openssl_compat::Sha1;
-#[cfg(not(feature = "with-openssl"))]
+#[cfg(all(
+    feature = "with-ring",
+    not(any(feature = "with-openssl", feature = "with-aws-lc"))
+))]
+pub use ring_compat::Sha1;
+#[cfg(not(any(
+    feature = "with-openssl",
+    feature = "with-aws-lc",
+    feature = "with-ring"
+)))]
 pub use sha1::Sha1;
 
 pub use sha2::{Sha256, Sha512};
@@ -44,3 +55,78 @@ mod openssl_compat {
 
     impl HashMarker for Sha1 {}
 }
+
+/// Compatibility layer between aws-lc and `digest`
+#[cfg(all(feature = "with-aws-lc", not(feature = "with-openssl")))]
+mod aws_lc_compat {
+    use aws_lc_rs::digest::{Context, SHA1_FOR_LEGACY_USE_ONLY};
+
+    use digest::{FixedOutput, HashMarker, Output, OutputSizeUser, Update};
+
+    /// Wrapper around aws-lc's Sha1 to make it compatible with `digest`
+    #[derive(Clone)]
+    pub struct Sha1(Context);
+
+    impl Default for Sha1 {
+        fn default() -> Self {
+            Sha1(Context::new(&SHA1_FOR_LEGACY_USE_ONLY))
+        }
+    }
+
+    impl Update for Sha1 {
+        fn update(&mut self, data: &[u8]) {
+            self.0.update(data);
+        }
+    }
+
+    impl OutputSizeUser for Sha1 {
+        type OutputSize = typenum::consts::U20;
+    }
+
+    impl FixedOutput for Sha1 {
+        fn finalize_into(self, out: &mut Output<Self>) {
+            out.copy_from_slice(self.0.finish().as_ref());
+        }
+    }
+
+    impl HashMarker for Sha1 {}
+}
+
+/// Compatibility layer between ring and `digest`
+#[cfg(all(
+    feature = "with-ring",
+    not(any(feature = "with-openssl", feature = "with-aws-lc"))
+))]
+mod ring_compat {
+    use ring::digest::{Context, SHA1_FOR_LEGACY_USE_ONLY};
+
+    use digest::{FixedOutput, HashMarker, Output, OutputSizeUser, Update};
+
+    /// Wrapper around ring's Sha1 to make it compatible with `digest`
+    #[derive(Clone)]
+    pub struct Sha1(Context);
+
+    impl Default for Sha1 {
+        fn default() -> Self {
+            Sha1(Context::new(&SHA1_FOR_LEGACY_USE_ONLY))
+        }
+    }
+
+    impl Update for Sha1 {
+        fn update(&mut self, data: &[u8]) {
+            self.0.update(data);
+        }
+    }
+
+    impl OutputSizeUser for Sha1 {
+        type OutputSize = typenum::consts::U20;
+    }
+
+    impl FixedOutput for Sha1 {
+        fn finalize_into(self, out: &mut Output<Self>) {
+            out.copy_from_slice(self.0.finish().as_ref());
+        }
+    }
+
+    impl HashMarker for Sha1 {}
+}
@@ -23,7 +23,7 @@ use ed25519_dalek::hazmat::ExpandedSecretKey;
 // our variable names, and with the nomenclature we use elsewhere for public
 // keys.
 pub use ed25519_dalek::{
-    Signature, Signer, SigningKey as Keypair, Verifier, VerifyingKey as PublicKey,
+    Signature, SignatureError, Signer, SigningKey as Keypair, Verifier, VerifyingKey as PublicKey,
 };
 
 use crate::util::ct::CtByteArray;
@@ -246,6 +246,10 @@ impl safelog::Redactable for Ed25519Identity {
     fn debug_redacted(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "Ed25519Identity {{ {} }}", self.redacted())
     }
+
+    fn category(&self) -> safelog::Category {
+        safelog::Category::RelayIdentity
+    }
 }
 
 impl serde::Serialize for Ed25519Identity {
@@ -78,6 +78,10 @@ impl safelog::Redactable for RsaIdentity {
     fn debug_redacted(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "RsaIdentity {{ {} }}", self.redacted())
     }
+
+    fn category(&self) -> safelog::Category {
+        safelog::Category::RelayIdentity
+    }
 }
 
 impl serde::Serialize for RsaIdentity {
@@ -65,6 +65,25 @@ pub struct ConfigBuilder {
     ///
     /// Default is 75% of the maximum.
     low_water: Option<Qty>,
+
+    /// Hard limit on the memory used by the queues of any one circuit
+    ///
+    /// This includes the queues of the circuit's streams.
+    /// Memory use beyond this is refused, rather than causing reclamation.
+    /// The default is no limit.
+    ///
+    /// Applied by `tor-proto`, to circuits created after it is set.
+    /// See [`LimitClass::Circuit`](crate::LimitClass::Circuit).
+    max_per_circuit: Option<Qty>,
+
+    /// Hard limit on the memory used by the queues of any one stream
+    ///
+    /// Memory use beyond this is refused, rather than causing reclamation.
+    /// The default is no limit.
+    ///
+    /// Applied by `tor-proto`, to streams created after it is set.
+    /// See [`LimitClass::Stream`](crate::LimitClass::Stream).
+    max_per_stream: Option<Qty>,
 }
 
 /// Configuration, if enabled
@@ -84,6 +103,12 @@ pub(crate) struct ConfigInner {
     ///
     /// Guaranteed to be enough lower than `max`
     pub low_water: Qty,
+
+    /// Hard limit for each circuit's account, if any
+    pub max_per_circuit: Option<Qty>,
+
+    /// Hard limit for each stream's account, if any
+    pub max_per_stream: Option<Qty>,
 }
 
 impl Config {
@@ -113,12 +138,18 @@ impl ConfigBuilder {
         let max = self.max.unwrap_or(Qty::MAX);
 
         if max == Qty::MAX {
-            if self.low_water.is_some() {
-                return Err(ConfigBuildError::Inconsistent {
-                    fields: vec!["max".into(), "low_water".into()],
-                    problem: "low_water supplied, but max omitted".into(),
-                });
-            };
+            for (field, value) in [
+                ("low_water", self.low_water),
+                ("max_per_circuit", self.max_per_circuit),
+                ("max_per_stream", self.max_per_stream),
+            ] {
+                if value.is_some() {
+                    return Err(ConfigBuildError::Inconsistent {
+                        fields: vec!["max".into(), field.into()],
+                        problem: format!("{field} supplied, but max omitted"),
+                    });
+                }
+            }
             return Ok(Config(IfEnabled::Noop));
         }
 
@@ -134,7 +165,12 @@ impl ConfigBuilder {
             || Qty((*max as f32 * 0.75) as _),
         );
 
-        let config = ConfigInner { max, low_water };
+        let config = ConfigInner {
+            max,
+            low_water,
+            max_per_circuit: self.max_per_circuit,
+            max_per_stream: self.max_per_stream,
+        };
 
         /// Minimum low water.  `const` so that overflows are compile-time.
         const MIN_LOW_WATER: usize = crate::mtracker::MAX_CACHE.as_usize() * MIN_MAX_PARTICIPANTS;
@@ -146,6 +182,21 @@ impl ConfigBuilder {
             });
         }
 
+        // Each participant may go over its account's limit by up to what it has cached,
+        // so a limit smaller than a cache is meaningless.
+        let min_limit = *crate::mtracker::MAX_CACHE;
+        for (field, limit) in [
+            ("max_per_circuit", config.max_per_circuit),
+            ("max_per_stream", config.max_per_stream),
+        ] {
+            if limit.is_some_and(|limit| *limit < min_limit) {
+                return Err(ConfigBuildError::Invalid {
+                    field: field.into(),
+                    problem: format!("must be at least {min_limit}"),
+                });
+            }
+        }
+
         let ratio: f32 = *config.low_water as f32 / *config.max as f32;
         if ratio > MAX_LOW_WATER_RATIO {
             return Err(ConfigBuildError::Inconsistent {
@@ -193,6 +244,8 @@ mod test {
                 ConfigInner {
                     max: Qty(max * M),
                     low_water: Qty(low_water * M),
+                    max_per_circuit: None,
+                    max_per_stream: None,
                 },
                 EnabledToken::new(),
             );
@@ -227,5 +280,24 @@ mod test {
             json! {{ "max": "8 MiB", "low_water": "8 MiB" }},
             "inconsistent: low_water / max",
         );
+
+        #[cfg(feature = "memquota")]
+        {
+            let b: ConfigBuilder = serde_json::from_value(
+                json! {{ "max": "8 MiB", "max_per_circuit": "1 MiB", "max_per_stream": "64 KiB" }},
+            )
+            .unwrap();
+            let inner = b.build().unwrap().0.into_enabled().unwrap();
+            assert_eq!(inner.max_per_circuit, Some(Qty(1024 * 1024)));
+            assert_eq!(inner.max_per_stream, Some(Qty(64 * 1024)));
+        }
+        chk_err(
+            json! {{ "max_per_stream": "1 MiB" }},
+            "max_per_stream supplied, but max omitted",
+        );
+        chk_err(
+            json! {{ "max": "8 MiB", "max_per_circuit": "1 KiB" }},
+            "must be at least 16384",
+        );
     }
 }
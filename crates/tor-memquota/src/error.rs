@@ -22,6 +22,13 @@ pub enum Error {
     #[error("memquota - attempt to allocate by torn-down memory tracking participant")]
     ParticipantShutdown,
 
+    /// The claim would take an Account over its hard limit
+    ///
+    /// See [`Account::set_limit`](crate::mtracker::Account::set_limit).
+    /// The claim was refused; nothing was reclaimed.
+    #[error("memquota - memory tracking account would exceed its hard limit")]
+    AccountLimitExceeded,
+
     /// Previous bug, memory quota tracker is corrupted
     #[error("{TrackerCorrupted}")]
     TrackerCorrupted,
@@ -146,6 +153,7 @@ impl HasKind for Error {
             E::TrackerShutdown => EK::ArtiShuttingDown,
             E::AccountClosed => EK::LocalResourceExhausted,
             E::ParticipantShutdown => EK::LocalResourceExhausted,
+            E::AccountLimitExceeded => EK::LocalResourceExhausted,
             E::TrackerCorrupted => EK::Internal,
             E::Bug(e) => e.kind(),
         }
@@ -226,6 +234,7 @@ mod test {
             TrackerShutdown {};
             AccountClosed {};
             ParticipantShutdown {};
+            AccountLimitExceeded {};
             TrackerCorrupted {};
             Bug(bug.clone());
         }
@@ -54,6 +54,14 @@
 //!    The account structure and reclamation strategy for Arti is defined in
 //!    `tor-proto`, and documented in `tor_proto::memquota`.
 //!
+//!  * **Account limit**:
+//!    An Account may optionally have a hard limit,
+//!    set with [`Account::set_limit`](mtracker::Account::set_limit).
+//!    A claim which would take the memory used by that Account and its Children
+//!    over the limit is refused with [`Error::AccountLimitExceeded`],
+//!    rather than triggering reclamation from other Accounts.
+//!    Like the rest of the system, the limit is [approximate](#is-approximate).
+//!
 //!  * **Data age**:
 //!    Each Participant must be able to say what the oldest data is, that it is storing.
 //!    The reclamation policy is to try to free the oldest data.
@@ -268,7 +276,7 @@ pub use error::{Error, MemoryReclaimedError, StartupError};
 pub use if_enabled::EnabledToken;
 pub use memory_cost::HasMemoryCost;
 pub use memory_cost_derive::{assert_copy_static, HasMemoryCostStructural};
-pub use mtracker::{Account, MemoryQuotaTracker};
+pub use mtracker::{Account, LimitClass, MemoryQuotaTracker};
 pub use utils::ArcMemoryQuotaTrackerExt;
 
 #[doc(hidden)]
@@ -73,6 +73,8 @@ use crate::internal_prelude::*;
 
 use IfEnabled::*;
 
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
 mod bookkeeping;
 mod reclaim;
 mod total_qty_notifier;
@@ -135,6 +137,18 @@ pub struct AccountInner {
     tracker: Arc<MemoryQuotaTracker>,
 }
 
+/// A kind of [`Account`] that can be given a hard limit in the [`Config`]
+///
+/// See [`Account::set_configured_limit`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum LimitClass {
+    /// The account for a single circuit (`max_per_circuit`)
+    Circuit,
+    /// The account for a single stream (`max_per_stream`)
+    Stream,
+}
+
 /// Weak handle onto an Account
 ///
 /// Like [`Account`], but doesn't keep the account alive.
@@ -410,6 +424,24 @@ struct ARecord {
     /// Child accounts
     children: Vec<AId>,
 
+    /// Parent account, if any
+    ///
+    /// Used only for enforcing `limit`s.
+    /// Might refer to an account which has since been torn down.
+    parent: Option<AId>,
+
+    /// Hard limit on memory use by this account and its descendants, if any
+    ///
+    /// See [`Account::set_limit`].
+    limit: Option<Qty>,
+
+    /// Memory used by this account's participants, and those of its descendants
+    ///
+    /// Followed by the corresponding counters for each of our ancestors, nearest first.
+    /// Shared with our [`PRecord`]s, which keep all of them up to date,
+    /// so that [`State::limit_headroom`] needn't walk the descendants.
+    subtree_used: Vec<SubtreeUsed>,
+
     /// Participants linked to this Account
     ps: SlotMap<PId, PRecord>,
 
@@ -418,6 +450,11 @@ struct ARecord {
     enabled: EnabledToken,
 }
 
+/// Memory used by an account and its descendants, in `ARecord.subtree_used`
+///
+/// Only accessed with the `State` lock held, so `Relaxed` ordering suffices.
+type SubtreeUsed = Arc<AtomicUsize>;
+
 /// Participant record, within `ARecord.ps`
 #[derive(Debug)]
 #[must_use = "don't just drop, call auto_release"]
@@ -433,6 +470,11 @@ struct PRecord {
     /// The hooks provided by the Participant
     particip: drop_reentrancy::ProtectedWeak<dyn IsParticipant>,
 
+    /// Copy of our account's `ARecord.subtree_used`
+    ///
+    /// Each counter is adjusted whenever `used` changes.
+    subtree_used: Vec<SubtreeUsed>,
+
     /// Make this type uninhabited if memory tracking is compiled out
     #[allow(dead_code)]
     enabled: EnabledToken,
@@ -551,7 +593,7 @@ impl MemoryQuotaTracker {
         let (reclaim_tx, reclaim_rx) =
             mpsc_channel_no_memquota(0 /* plus num_senders, ie 1 */);
         let total_used = TotalQtyNotifier::new_zero(reclaim_tx);
-        let ConfigInner { max, low_water } = config; // for logging
+        let ConfigInner { max, low_water, .. } = config; // for logging
 
         let global = Global {
             total_used,
@@ -600,6 +642,8 @@ impl MemoryQuotaTracker {
                     ConfigInner {
                         max: Qty::MAX,
                         low_water: Qty::MAX,
+                        max_per_circuit: None,
+                        max_per_stream: None,
                     },
                 );
 
@@ -712,9 +756,21 @@ impl MemoryQuotaTracker {
 
         // commitment - infallible IEFE assures that so we don't do half of it
         Ok((|| {
+            let subtree_used = chain!(
+                [SubtreeUsed::default()],
+                parent_aid_good
+                    .and_then(|parent_aid| state.accounts.get(parent_aid))
+                    .into_iter()
+                    .flat_map(|parent| parent.subtree_used.iter().cloned()),
+            )
+            .collect();
+
             let aid = refcount::slotmap_insert(&mut state.accounts, |refcount| ARecord {
                 refcount,
                 children: vec![],
+                parent: parent_aid_good,
+                limit: None,
+                subtree_used,
                 ps: SlotMap::default(),
                 enabled,
             });
@@ -773,11 +829,13 @@ impl Account {
             ?Error
         }
 
+        let subtree_used = arecord.subtree_used.clone();
         let (pid, cache) = refcount::slotmap_try_insert(&mut arecord.ps, |refcount| {
             let mut precord = PRecord {
                 refcount,
                 used: ParticipQty::ZERO,
                 particip: drop_reentrancy::ProtectedWeak::new(particip),
+                subtree_used,
                 enabled: *enabled,
             };
             let cache =
@@ -924,6 +982,65 @@ impl Account {
         Ok(Ok((particip, xdata)))
     }
 
+    /// Set (or clear) a hard limit on memory use by this `Account`
+    ///
+    /// The limit applies to the total memory claimed by this Account's Participants,
+    /// and those of all of its descendants.
+    ///
+    /// A claim which would exceed the limit of the Account
+    /// (or that of any of its ancestors)
+    /// fails with [`Error::AccountLimitExceeded`].
+    /// Reaching the limit does not cause any memory to be reclaimed,
+    /// either from this Account or from any other.
+    ///
+    /// The limit is in addition to the global quota,
+    /// which continues to apply as usual.
+    ///
+    /// Like everything else, the limit is [approximate](crate#is-approximate):
+    /// each Participant may go over by up to the amount it has cached.
+    /// Limits well below a few tens of kilobytes per Participant are not useful.
+    ///
+    /// Limits aren't copied to new children; setting a limit
+    /// doesn't affect any other Account's own limit.
+    pub fn set_limit(&self, limit: Option<usize>) -> crate::Result<()> {
+        let Enabled(self_, enabled) = &self.0 else {
+            return Ok(());
+        };
+        find_in_tracker! {
+            enabled;
+            self_.tracker => state;
+            *self_.aid => arecord;
+            ?Error
+        }
+        arecord.limit = limit.map(Qty);
+        Ok(())
+    }
+
+    /// Set the hard limit on memory use by this `Account` from the configuration
+    ///
+    /// Looks up the limit for accounts of this `class` in the tracker's [`Config`],
+    /// and applies it as if with [`set_limit`](Account::set_limit).
+    ///
+    /// The limit is taken from the configuration at the time of the call:
+    /// reconfiguring the tracker doesn't change the limits of existing accounts.
+    pub fn set_configured_limit(&self, class: LimitClass) -> crate::Result<()> {
+        let Enabled(self_, enabled) = &self.0 else {
+            return Ok(());
+        };
+        find_in_tracker! {
+            enabled;
+            self_.tracker => state;
+            *self_.aid => arecord;
+            ?Error
+        }
+        let config = &state.global.config;
+        arecord.limit = match class {
+            LimitClass::Circuit => config.max_per_circuit,
+            LimitClass::Stream => config.max_per_stream,
+        };
+        Ok(())
+    }
+
     /// Obtain a new `Account` which is a child of this one
     ///
     /// Equivalent to
@@ -1129,11 +1246,24 @@ impl Participation {
         find_in_tracker! {
             enabled;
             self_.tracker => + tracker, state;
-            self_.aid => arecord;
-            *self_.pid => precord;
+            self_.aid => _arecord;
             ?Error
         };
 
+        // `None` means no limit applies.
+        let headroom = state.limit_headroom(self_.aid);
+        if headroom.is_some_and(|headroom| want > headroom) {
+            return Err(Error::AccountLimitExceeded);
+        }
+
+        let precord = state
+            .accounts
+            .get_mut(self_.aid)
+            .ok_or(Error::AccountClosed)?
+            .ps
+            .get_mut(*self_.pid)
+            .ok_or(Error::ParticipantShutdown)?;
+
         let mut claim = |want| -> Result<ClaimedQty, _> {
             state
                 .global
@@ -1149,7 +1279,12 @@ impl Participation {
             let want_more_cache = TARGET_CACHE_CLAIMING
                 .checked_sub(*self_.cache.as_raw())
                 .expect("but cache < want");
-            let want_more_cache = Qty(want_more_cache);
+            let mut want_more_cache = Qty(want_more_cache);
+            if let Some(headroom) = headroom {
+                // Don't let the cache take us over the limit, either.
+                // Doesn't saturate: we checked want <= headroom above.
+                want_more_cache = want_more_cache.min(Qty(headroom.saturating_sub(*want)));
+            }
             if let Ok(add_cache) = claim(want_more_cache) {
                 // On error, just don't do this; presumably the error will show up later
                 // (we mustn't early exit here, because we've got the claim in our hand).
@@ -1378,6 +1513,33 @@ impl State {
         }
         out
     }
+
+    /// Find how much more `aid` may claim, according to the account limits
+    ///
+    /// Considers the `limit` of `aid` and of each of its ancestors.
+    /// Returns `None` if none of them has a limit.
+    ///
+    /// Only walks the ancestors: each account's usage is kept in `ARecord.subtree_used`.
+    fn limit_headroom(&self, aid: AId) -> Option<Qty> {
+        let mut headroom = None::<Qty>;
+        let mut next = Some(aid);
+        while let Some(aid) = next {
+            let Some(arecord) = self.accounts.get(aid) else {
+                // Ancestor has been torn down; its limit no longer applies.
+                break;
+            };
+            if let Some(limit) = arecord.limit {
+                let used = arecord
+                    .subtree_used
+                    .first()
+                    .map_or(0, |used| used.load(AtomicOrdering::Relaxed));
+                let here = Qty(limit.saturating_sub(used));
+                headroom = Some(headroom.map_or(here, |headroom| headroom.min(here)));
+            }
+            next = arecord.parent;
+        }
+        headroom
+    }
 }
 
 impl ARecord {
@@ -1395,4 +1557,17 @@ impl PRecord {
         let for_teardown = self.used.for_participant_teardown();
         global.total_used.release(self, for_teardown);
     }
+
+    /// Adjust each of our `subtree_used`, now that `used` has changed from `before`
+    fn update_subtree_used(&self, before: Qty) {
+        let after = self.used.as_raw();
+        let change = after.abs_diff(*before);
+        for subtree_used in &self.subtree_used {
+            if after >= before {
+                subtree_used.fetch_add(change, AtomicOrdering::Relaxed);
+            } else {
+                subtree_used.fetch_sub(change, AtomicOrdering::Relaxed);
+            }
+        }
+    }
 }
@@ -18,7 +18,7 @@
 
 use super::*;
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{Display, Write as _};
 use std::time::Duration;
 
@@ -159,6 +159,23 @@ mod consistency {
             expected, got,
             "\n----- dump (start) -----\n{debug_dump}----- dump (end) -----",
         );
+
+        // Each account's subtree_used is the sum of the usage of the participants under it
+        let mut subtree_exp = HashMap::<*const AtomicUsize, usize>::new();
+        for precord in state.accounts.values().flat_map(|arecord| arecord.ps.values()) {
+            for subtree_used in &precord.subtree_used {
+                *subtree_exp.entry(Arc::as_ptr(subtree_used)).or_default() +=
+                    *precord.used.as_raw();
+            }
+        }
+        for (aid, arecord) in state.accounts.iter() {
+            let subtree_used = &arecord.subtree_used[0];
+            assert_eq!(
+                subtree_used.load(AtomicOrdering::Relaxed),
+                subtree_exp.get(&Arc::as_ptr(subtree_used)).copied().unwrap_or(0),
+                "subtree_used of {aid:?}",
+            );
+        }
     }
 }
 
@@ -405,6 +422,98 @@ fn parent() {
     });
 }
 
+#[traced_test]
+#[test]
+fn limit() {
+    test_with_various_mocks(|rt| async move {
+        let trk = mk_tracker(&rt);
+
+        let mk_p = |parent, show| UnifiedP::new(&rt, &trk, parent, secs(0), show);
+
+        let parent = mk_p(None, "parent");
+        let child = mk_p(Some(&parent.acct), "child");
+        let other = mk_p(None, "other");
+        let ps = [&parent, &child, &other];
+
+        parent.acct.set_limit(Some(mbytes(2))).unwrap();
+
+        parent.lock().claim(mbytes(1)).unwrap();
+        // The child's usage counts towards the parent's limit.
+        assert!(matches!(
+            child.lock().claim(mbytes(2)),
+            Err(Error::AccountLimitExceeded),
+        ));
+        child.lock().claim(mbytes(1) / 2).unwrap();
+        UnifiedP::settle_check_consistency(&rt, &trk, ps).await;
+
+        // The limit doesn't affect unrelated accounts,
+        other.lock().claim(mbytes(7)).unwrap();
+        // and running into it doesn't reclaim anything.
+        assert!(matches!(
+            parent.lock().claim(mbytes(1)),
+            Err(Error::AccountLimitExceeded),
+        ));
+        UnifiedP::settle_check_consistency(&rt, &trk, ps).await;
+        assert!(ps.iter().all(|p| p.is_reclaimed().is_ok()));
+
+        // A limit on a child doesn't affect its parent.
+        child.acct.set_limit(Some(mbytes(1))).unwrap();
+        parent.acct.set_limit(None).unwrap();
+        parent.lock().claim(mbytes(3)).unwrap();
+        assert!(matches!(
+            child.lock().claim(mbytes(1)),
+            Err(Error::AccountLimitExceeded),
+        ));
+
+        // Releasing memory makes room again.
+        child.lock().release(mbytes(1) / 2);
+        child.lock().claim(mbytes(1) / 2).unwrap();
+        UnifiedP::settle_check_consistency(&rt, &trk, ps).await;
+        assert!(ps.iter().all(|p| p.is_reclaimed().is_ok()));
+    });
+}
+
+#[traced_test]
+#[test]
+fn configured_limit() {
+    test_with_various_mocks(|rt| async move {
+        let config = Config::builder()
+            .max(TEST_DEFAULT_LIMIT)
+            .max_per_circuit(mbytes(2))
+            .max_per_stream(mbytes(1))
+            .build()
+            .unwrap();
+        let trk = MemoryQuotaTracker::new(&rt, config).unwrap();
+
+        let mk_p = |parent, show| UnifiedP::new(&rt, &trk, parent, secs(0), show);
+
+        let circ = mk_p(None, "circ");
+        let stream = mk_p(Some(&circ.acct), "stream");
+        let grandchild = mk_p(Some(&stream.acct), "grandchild");
+        circ.acct.set_configured_limit(LimitClass::Circuit).unwrap();
+        stream.acct.set_configured_limit(LimitClass::Stream).unwrap();
+
+        // Usage deep in the tree counts towards the limits of all the ancestors.
+        grandchild.lock().claim(mbytes(1) / 2).unwrap();
+        stream.lock().claim(mbytes(1) / 4).unwrap();
+        assert!(matches!(
+            stream.lock().claim(mbytes(1) / 2),
+            Err(Error::AccountLimitExceeded),
+        ));
+        circ.lock().claim(mbytes(1)).unwrap();
+        assert!(matches!(
+            circ.lock().claim(mbytes(1) / 2),
+            Err(Error::AccountLimitExceeded),
+        ));
+        UnifiedP::settle_check_consistency(&rt, &trk, [&circ, &stream, &grandchild]).await;
+
+        // Tearing down a participant makes room.
+        drop(grandchild);
+        stream.lock().claim(mbytes(1) / 2).unwrap();
+        UnifiedP::settle_check_consistency(&rt, &trk, [&circ, &stream]).await;
+    });
+}
+
 #[traced_test]
 #[test]
 fn cache() {
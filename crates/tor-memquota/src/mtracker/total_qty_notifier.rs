@@ -41,10 +41,12 @@ impl TotalQtyNotifier {
         want: Qty,
         config: &ConfigInner,
     ) -> crate::Result<ClaimedQty> {
+        let before = precord.used.as_raw();
         let got = self
             .total_used
             .claim(&mut precord.used, want)
             .ok_or_else(|| internal!("integer overflow attempting to add claim {}", want))?;
+        precord.update_subtree_used(before);
         self.maybe_wakeup(config);
         Ok(got)
     }
@@ -71,6 +73,8 @@ impl TotalQtyNotifier {
     {
         // TODO if the participant's usage underflows, tell it to reclaim
         // (and log some kind of internal error)
+        let before = precord.used.as_raw();
         self.total_used.release(&mut precord.used, have);
+        precord.update_subtree_used(before);
     }
 }
@@ -74,6 +74,18 @@ impl<'a> RelayDetails<'a> {
     pub fn is_flagged_stable(&self) -> bool {
         self.0.rs.is_flagged_stable()
     }
+    /// Return the bandwidth weight that the consensus lists for this relay.
+    ///
+    /// This is the relay's measured bandwidth if it has one,
+    /// and its self-reported bandwidth otherwise.
+    /// It is not the same as the weight we use when choosing relays for a given role.
+    pub fn consensus_bandwidth_weight(&self) -> u32 {
+        match self.0.rs.weight() {
+            netstatus::RelayWeight::Measured(w) | netstatus::RelayWeight::Unmeasured(w) => *w,
+            // An unrecognized kind of weight tells us nothing about the relay.
+            _ => 0,
+        }
+    }
     /// Return true if this relay is a potential HS introduction point
     pub fn is_hs_intro_point(&self) -> bool {
         self.is_flagged_fast() && self.0.rs.is_flagged_stable()
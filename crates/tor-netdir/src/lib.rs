@@ -91,6 +91,9 @@ use {
 
 pub use err::Error;
 pub use weight::WeightRole;
+#[cfg(feature = "experimental-api")]
+#[cfg_attr(docsrs, doc(cfg(feature = "experimental-api")))]
+pub use weight::{BandwidthFn, RelayWeightDetails, WeightSetInfo};
 /// A Result using the Error type from the tor-netdir crate
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -1482,6 +1485,26 @@ impl NetDir {
         RelayWeight(self.weights.weight_rs_for_role(relay.rs, role))
     }
 
+    /// Return a summary of how this directory weights relays.
+    ///
+    /// This is meant for debugging, and for checking our path selection
+    /// probabilities against those of other implementations.
+    #[cfg(feature = "experimental-api")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "experimental-api")))]
+    pub fn weight_set_info(&self) -> WeightSetInfo {
+        self.weights.info()
+    }
+
+    /// Return the details of how `relay` is weighted, for every [`WeightRole`].
+    ///
+    /// This is meant for debugging, and for checking our path selection
+    /// probabilities against those of other implementations.
+    #[cfg(feature = "experimental-api")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "experimental-api")))]
+    pub fn relay_weight_details(&self, relay: &Relay<'_>) -> RelayWeightDetails {
+        self.weights.details_for_rs(relay.rs)
+    }
+
     /// Compute the total weight with which any relay matching `usable`
     /// will be selected for a given `role`.
     ///
@@ -47,10 +47,14 @@ where
     }
 }
 
-/// Internal: how should we find the base bandwidth of each relay?  This
+/// How should we find the base bandwidth of each relay?  This
 /// value is global over a whole directory, and depends on the bandwidth
 /// weights in the consensus.
+///
+/// (Only exposed, as part of a [`WeightSetInfo`], with the `experimental-api` feature.)
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "experimental-api", visibility::make(pub))]
+#[non_exhaustive]
 enum BandwidthFn {
     /// There are no weights at all in the consensus: weight every
     /// relay as 1.
@@ -245,12 +249,28 @@ impl WeightSet {
         let ws = &self.w[kind.idx()];
 
         let router_bw = self.bandwidth_fn.apply(relay_weight);
-        // Note a subtlety here: we multiply the two values _before_
-        // we shift, to improve accuracy.  We know that this will be
-        // safe, since the inputs are both u32, and so cannot overflow
-        // a u64.
-        let router_weight = u64::from(router_bw) * u64::from(ws.for_role(role));
-        router_weight >> self.shift
+        apply_weight(router_bw, ws.for_role(role), self.shift)
+    }
+
+    /// Return a summary of this `WeightSet`.
+    #[cfg(feature = "experimental-api")]
+    pub(crate) fn info(&self) -> WeightSetInfo {
+        WeightSetInfo {
+            bandwidth_fn: self.bandwidth_fn,
+            shift: self.shift,
+        }
+    }
+
+    /// Return the details of how we weight the relay with routerstatus `rs`.
+    #[cfg(feature = "experimental-api")]
+    pub(crate) fn details_for_rs(&self, rs: &MdConsensusRouterStatus) -> RelayWeightDetails {
+        let kind = WeightKind::for_rs(rs);
+        RelayWeightDetails {
+            kind,
+            bandwidth: self.bandwidth_fn.apply(rs.weight()),
+            factors: self.w[kind.idx()],
+            shift: self.shift,
+        }
     }
 
     /// Compute the correct WeightSet for a provided MdConsensus.
@@ -357,6 +377,108 @@ impl WeightSet {
     }
 }
 
+/// Return the weight of a relay with bandwidth `router_bw`, given the `factor` for
+/// its kind and role, and the `shift` of its [`WeightSet`].
+fn apply_weight(router_bw: u32, factor: u32, shift: u8) -> u64 {
+    // Note a subtlety here: we multiply the two values _before_
+    // we shift, to improve accuracy.  We know that this will be
+    // safe, since the inputs are both u32, and so cannot overflow
+    // a u64.
+    let router_weight = u64::from(router_bw) * u64::from(factor);
+    router_weight >> shift
+}
+
+/// A summary of how a [`NetDir`](crate::NetDir) weights relays.
+///
+/// Returned by [`NetDir::weight_set_info`](crate::NetDir::weight_set_info).
+///
+/// This is meant for debugging, and for comparing our path selection
+/// with that of other implementations.
+#[cfg(feature = "experimental-api")]
+#[derive(Clone, Debug)]
+pub struct WeightSetInfo {
+    /// How we find the bandwidth of each relay.
+    bandwidth_fn: BandwidthFn,
+    /// How many bits we right-shift each weighted bandwidth by.
+    shift: u8,
+}
+
+#[cfg(feature = "experimental-api")]
+impl WeightSetInfo {
+    /// Return how we find the bandwidth of each relay.
+    pub fn bandwidth_fn(&self) -> BandwidthFn {
+        self.bandwidth_fn
+    }
+
+    /// Return how many bits we right-shift each weighted bandwidth by,
+    /// so that the total weight of all the relays fits in a `u64`.
+    ///
+    /// This is almost always zero.
+    pub fn shift(&self) -> u8 {
+        self.shift
+    }
+}
+
+/// The details of how a [`NetDir`](crate::NetDir) weights a single relay.
+///
+/// Returned by [`NetDir::relay_weight_details`](crate::NetDir::relay_weight_details).
+///
+/// The weight of a relay for a given role is its [`bandwidth`](Self::bandwidth),
+/// multiplied by the bandwidth-weight [`factor`](Self::factor) for that role
+/// (`Wgg`, `Wmd`, `Wee`, and so on, depending on the relay's flags),
+/// and shifted right by [`WeightSetInfo::shift`].
+#[cfg(feature = "experimental-api")]
+#[derive(Clone, Debug)]
+pub struct RelayWeightDetails {
+    /// The flags that decide which bandwidth weights apply to the relay.
+    kind: WeightKind,
+    /// The bandwidth of the relay, as found by the [`BandwidthFn`].
+    bandwidth: u32,
+    /// The bandwidth weights that apply to the relay.
+    factors: RelayWeight,
+    /// See [`WeightSetInfo::shift`].
+    shift: u8,
+}
+
+#[cfg(feature = "experimental-api")]
+impl RelayWeightDetails {
+    /// Return true if the relay is weighted as having the Guard flag.
+    pub fn has_guard_flag(&self) -> bool {
+        self.kind.contains(WeightKind::GUARD)
+    }
+
+    /// Return true if the relay is weighted as having the Exit flag.
+    pub fn has_exit_flag(&self) -> bool {
+        self.kind.contains(WeightKind::EXIT)
+    }
+
+    /// Return true if the relay is weighted as having the V2Dir flag.
+    pub fn has_v2dir_flag(&self) -> bool {
+        self.kind.contains(WeightKind::DIR)
+    }
+
+    /// Return the bandwidth of the relay, as found by the [`BandwidthFn`]
+    /// of the directory.
+    pub fn bandwidth(&self) -> u32 {
+        self.bandwidth
+    }
+
+    /// Return the bandwidth weight that we apply to the relay for `role`.
+    ///
+    /// This is in units of the `bwweightscale` consensus parameter,
+    /// except for [`WeightRole::Unweighted`], for which it is always 1.
+    pub fn factor(&self, role: WeightRole) -> u32 {
+        self.factors.for_role(role)
+    }
+
+    /// Return the weight with which the relay is selected for `role`.
+    ///
+    /// This is the same as [`NetDir::relay_weight`](crate::NetDir::relay_weight).
+    pub fn weight(&self, role: WeightRole) -> crate::RelayWeight {
+        apply_weight(self.bandwidth, self.factor(role), self.shift).into()
+    }
+}
+
 /// The value to return if a weight parameter is absent.
 ///
 /// (If there are no weights at all, then it's correct to set them all to 1,
@@ -606,6 +728,40 @@ mod test {
         assert_eq!(ws.weight_rs_for_role(&rs, WeightRole::Unweighted), 7777);
     }
 
+    #[test]
+    #[cfg(feature = "experimental-api")]
+    fn relay_weight_details() {
+        let params = TESTVEC_PARAMS.parse().unwrap();
+        let ws = WeightSet::from_parts(BandwidthFn::MeasuredOnly, 1_000_000_000, 10000, &params);
+        let rs = rs_builder()
+            .set_flags(RelayFlags::GUARD | RelayFlags::V2DIR)
+            .weight(RW::Measured(7777))
+            .build()
+            .unwrap();
+
+        let details = ws.details_for_rs(&rs);
+        assert!(details.has_guard_flag());
+        assert!(!details.has_exit_flag());
+        assert!(details.has_v2dir_flag());
+        assert_eq!(details.bandwidth(), 7777);
+        assert_eq!(details.factor(WeightRole::BeginDir), 4096);
+        assert_eq!(details.factor(WeightRole::Unweighted), 1);
+        for role in [
+            WeightRole::Guard,
+            WeightRole::Middle,
+            WeightRole::Exit,
+            WeightRole::BeginDir,
+            WeightRole::Unweighted,
+        ] {
+            assert_eq!(
+                details.weight(role),
+                ws.weight_rs_for_role(&rs, role).into()
+            );
+        }
+        assert_eq!(ws.info().bandwidth_fn(), BandwidthFn::MeasuredOnly);
+        assert_eq!(ws.info().shift(), 0);
+    }
+
     /// Return a routerstatus builder set up to deliver a routerstatus
     /// with most features disabled.
     fn rs_builder() -> RouterStatusBuilder<[u8; 32]> {
@@ -44,7 +44,7 @@ pub use {inner::HsDescInner, middle::HsDescMiddle, outer::HsDescOuter};
 
 #[cfg(feature = "hs-service")]
 #[cfg_attr(docsrs, doc(cfg(feature = "hs-service")))]
-pub use build::{create_desc_sign_key_cert, HsDescBuilder};
+pub use build::{create_desc_sign_key_cert, HsDescBuilder, HsDescSigner};
 
 /// Metadata about an onion service descriptor, as stored at an HsDir.
 ///
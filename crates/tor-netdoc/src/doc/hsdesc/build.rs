@@ -21,6 +21,7 @@ use derive_builder::Builder;
 use smallvec::SmallVec;
 
 use std::borrow::{Borrow, Cow};
+use std::fmt::Debug;
 use std::time::SystemTime;
 
 use self::inner::HsDescInner;
@@ -43,7 +44,7 @@ struct HsDesc<'a> {
     /// (See rend-spec v3 2.5.1.1 and 2.5.2.1.)
     blinded_id: &'a HsBlindIdKey,
     /// The short-term descriptor signing key (KP_hs_desc_sign, KS_hs_desc_sign).
-    hs_desc_sign: &'a ed25519::Keypair,
+    hs_desc_sign: &'a dyn HsDescSigner,
     /// The descriptor signing key certificate.
     ///
     /// This certificate can be created using [`create_desc_sign_key_cert`].
@@ -85,6 +86,36 @@ struct HsDesc<'a> {
     subcredential: Subcredential,
 }
 
+/// A descriptor signing key (KP_hs_desc_sign, KS_hs_desc_sign), for use with [`HsDescBuilder`].
+///
+/// This is implemented by [`ed25519::Keypair`],
+/// and by anything else that has an ed25519 public key and can make signatures with it,
+/// such as a handle to a key held by a hardware token.
+/// If such a key fails to make a signature,
+/// [`HsDescBuilder::build_sign`](NetdocBuilder::build_sign)
+/// returns [`EncodeError::Signing`].
+pub trait HsDescSigner:
+    ed25519::Ed25519PublicKey + ed25519::Signer<ed25519::Signature> + Debug
+{
+}
+
+impl<T> HsDescSigner for T where
+    T: ed25519::Ed25519PublicKey + ed25519::Signer<ed25519::Signature> + Debug
+{
+}
+
+/// Return a function that converts an error from signing a certificate
+/// with the descriptor signing key into an [`EncodeError`].
+///
+/// A failure of the signing key itself is reported as [`EncodeError::Signing`].
+/// Anything else means we were given bad parameters: `what` says what we were doing.
+fn cert_sign_error(what: &'static str) -> impl FnOnce(CertEncodeError) -> EncodeError {
+    move |e| match e {
+        CertEncodeError::Signing(e) => EncodeError::Signing(e),
+        e => into_bad_api_usage!("{}", what)(e).into(),
+    }
+}
+
 /// Restricted discovery parameters.
 #[derive(Debug)]
 pub(super) struct ClientAuth<'a> {
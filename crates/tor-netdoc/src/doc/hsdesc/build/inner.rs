@@ -5,6 +5,7 @@
 //! hidden service descriptors.
 
 use crate::build::NetdocEncoder;
+use crate::doc::hsdesc::build::{cert_sign_error, HsDescSigner};
 use crate::doc::hsdesc::inner::HsInnerKwd;
 use crate::doc::hsdesc::IntroAuthType;
 use crate::doc::hsdesc::IntroPointDesc;
@@ -31,7 +32,7 @@ use smallvec::SmallVec;
 #[derive(Debug)]
 pub(super) struct HsDescInner<'a> {
     /// The descriptor signing key.
-    pub(super) hs_desc_sign: &'a ed25519::Keypair,
+    pub(super) hs_desc_sign: &'a dyn HsDescSigner,
     /// A list of recognized CREATE handshakes that this onion service supports.
     pub(super) create2_formats: &'a [HandshakeType],
     /// A list of authentication types that this onion service supports.
@@ -121,10 +122,10 @@ impl<'a> NetdocBuilder for HsDescInner<'a> {
             let signed_auth_key = Ed25519Cert::constructor()
                 .cert_type(CertType::HS_IP_V_SIGNING)
                 .expiration(intro_auth_key_cert_expiry)
-                .signing_key(ed25519::Ed25519Identity::from(hs_desc_sign.verifying_key()))
+                .signing_key(ed25519::Ed25519Identity::from(hs_desc_sign.public_key()))
                 .cert_key(CertifiedKey::Ed25519((*intro_point.ipt_sid_key).into()))
                 .encode_and_sign(hs_desc_sign)
-                .map_err(into_bad_api_usage!("failed to sign the intro auth key"))?;
+                .map_err(cert_sign_error("failed to sign the intro auth key"))?;
 
             encoder
                 .item(AUTH_KEY)
@@ -162,14 +163,12 @@ impl<'a> NetdocBuilder for HsDescInner<'a> {
             let signed_enc_key = Ed25519Cert::constructor()
                 .cert_type(CertType::HS_IP_CC_SIGNING)
                 .expiration(intro_enc_key_cert_expiry)
-                .signing_key(ed25519::Ed25519Identity::from(hs_desc_sign.verifying_key()))
+                .signing_key(ed25519::Ed25519Identity::from(hs_desc_sign.public_key()))
                 .cert_key(CertifiedKey::Ed25519(ed25519::Ed25519Identity::from(
                     &ed_svc_ntor_key,
                 )))
                 .encode_and_sign(hs_desc_sign)
-                .map_err(into_bad_api_usage!(
-                    "failed to sign the intro encryption key"
-                ))?;
+                .map_err(cert_sign_error("failed to sign the intro encryption key"))?;
 
             encoder
                 .item(ENC_KEY_CERT)
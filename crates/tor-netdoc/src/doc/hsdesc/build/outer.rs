@@ -5,24 +5,26 @@
 //! hidden service descriptors.
 
 use crate::build::{NetdocBuilder, NetdocEncoder};
+use crate::doc::hsdesc::build::HsDescSigner;
 use crate::doc::hsdesc::outer::{HsOuterKwd, HS_DESC_SIGNATURE_PREFIX, HS_DESC_VERSION_CURRENT};
 
 use rand::{CryptoRng, RngCore};
 use tor_bytes::EncodeError;
 use tor_cert::EncodedEd25519Cert;
 use tor_hscrypto::RevisionCounter;
-use tor_llcrypto::pk::ed25519;
 use tor_units::IntegerMinutes;
 
 use base64ct::{Base64Unpadded, Encoding};
 
+use std::sync::Arc;
+
 /// The representation of the outer wrapper of an onion service descriptor.
 ///
 /// The format of this document is described in section 2.4. of rend-spec-v3.
 #[derive(Debug)]
 pub(super) struct HsDescOuter<'a> {
     /// The short-term descriptor signing key.
-    pub(super) hs_desc_sign: &'a ed25519::Keypair,
+    pub(super) hs_desc_sign: &'a dyn HsDescSigner,
     /// The descriptor signing key certificate.
     pub(super) hs_desc_sign_cert: EncodedEd25519Cert,
     /// The lifetime of this descriptor, in minutes.
@@ -45,7 +47,6 @@ pub(super) struct HsDescOuter<'a> {
 
 impl<'a> NetdocBuilder for HsDescOuter<'a> {
     fn build_sign<R: RngCore + CryptoRng>(self, _: &mut R) -> Result<String, EncodeError> {
-        use tor_llcrypto::pk::ed25519::Signer as _;
         use HsOuterKwd::*;
 
         let HsDescOuter {
@@ -72,7 +73,9 @@ impl<'a> NetdocBuilder for HsDescOuter<'a> {
 
         let mut text = HS_DESC_SIGNATURE_PREFIX.to_vec();
         text.extend_from_slice(encoder.slice(beginning, end)?.as_bytes());
-        let signature = hs_desc_sign.sign(&text);
+        let signature = hs_desc_sign
+            .try_sign(&text)
+            .map_err(|e| EncodeError::Signing(Arc::new(e)))?;
 
         // TODO SPEC encoding of this signature is completely unspecified (rend-spec-v3 2.4)
         // TODO SPEC base64 is sometimes padded, sometimes unpadded, but NONE of the specs ever say!
@@ -108,7 +111,7 @@ mod test {
     use tor_basic_utils::test_rng::Config;
     use tor_hscrypto::pk::HsIdKeypair;
     use tor_hscrypto::time::TimePeriod;
-    use tor_llcrypto::pk::ed25519::ExpandedKeypair;
+    use tor_llcrypto::pk::ed25519::{self, ExpandedKeypair};
     use tor_units::IntegerMinutes;
 
     // Some dummy bytes, not actually encrypted.
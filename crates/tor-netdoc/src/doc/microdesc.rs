@@ -22,6 +22,7 @@ use crate::util;
 use crate::util::str::Extent;
 use crate::util::PeekableIterator;
 use crate::{AllowAnnotations, Error, NetdocErrorKind as EK, Result};
+use tor_basic_utils::alloc_tag;
 use tor_error::internal;
 use tor_llcrypto::d;
 use tor_llcrypto::pk::{curve25519, ed25519, rsa};
@@ -229,6 +230,7 @@ impl MicrodescAnnotation {
 impl Microdesc {
     /// Parse a string into a new microdescriptor.
     pub fn parse(s: &str) -> Result<Microdesc> {
+        let _tag = alloc_tag::enter(alloc_tag::Subsystem::Netdoc);
         let mut items = crate::parse::tokenize::NetDocReader::new(s);
         let (result, _) = Self::parse_from_reader(&mut items).map_err(|e| e.within(s))?;
         items.should_be_exhausted()?;
@@ -455,6 +457,7 @@ impl<'a> Iterator for MicrodescReader<'a> {
         // If there is no next token, we're at the end.
         self.reader.peek()?;
 
+        let _tag = alloc_tag::enter(alloc_tag::Subsystem::Netdoc);
         Some(
             self.take_annotated_microdesc()
                 .map_err(|e| e.within(self.reader.str())),
@@ -58,6 +58,7 @@ use crate::util::PeekableIterator;
 use crate::{Error, NetdocErrorKind as EK, Pos, Result};
 use std::collections::{HashMap, HashSet};
 use std::{net, result, time};
+use tor_basic_utils::alloc_tag;
 use tor_error::internal;
 use tor_protover::Protocols;
 
@@ -1397,6 +1398,7 @@ impl<RS: RouterStatus + ParseRouterStatus> Consensus<RS> {
 
     /// Try to parse a single networkstatus document from a string.
     pub fn parse(s: &str) -> Result<(&str, &str, UncheckedConsensus<RS>)> {
+        let _tag = alloc_tag::enter(alloc_tag::Subsystem::Netdoc);
         let mut reader = NetDocReader::new(s);
         Self::parse_from_reader(&mut reader).map_err(|e| e.within(s))
     }
@@ -1581,6 +1583,7 @@ impl<RS: RouterStatus + ParseRouterStatus> Consensus<RS> {
             consensus,
             siggroup,
             n_authorities: None,
+            signature_threshold: None,
         };
         let lifetime = unval.consensus.header.hdr.lifetime.clone();
         let delay = unval.consensus.header.hdr.voting_delay.unwrap_or((0, 0));
@@ -1617,6 +1620,11 @@ pub struct UnvalidatedConsensus<RS> {
     /// determines how many signatures we need to find valid in `siggroup`.
     #[cfg_attr(docsrs, doc(cfg(feature = "dangerous-expose-struct-fields")))]
     n_authorities: Option<u16>,
+    /// The number of authorities whose signatures we require, if we were told.
+    ///
+    /// If this is None, we require signatures from more than half of the authorities.
+    #[cfg_attr(docsrs, doc(cfg(feature = "dangerous-expose-struct-fields")))]
+    signature_threshold: Option<u16>,
 }
 
 impl<RS> UnvalidatedConsensus<RS> {
@@ -1631,6 +1639,27 @@ impl<RS> UnvalidatedConsensus<RS> {
         }
     }
 
+    /// Tell the unvalidated consensus how many authorities must have signed it.
+    ///
+    /// By default, we require signatures from more than half of the authorities
+    /// we believe in.  Private networks may need a different rule.
+    /// (A threshold of zero is treated as one.)
+    #[must_use]
+    pub fn set_signature_threshold(self, threshold: u16) -> Self {
+        UnvalidatedConsensus {
+            signature_threshold: Some(threshold.max(1)),
+            ..self
+        }
+    }
+
+    /// Return the number of signatures we require, if there are `n_authorities` authorities.
+    fn n_required_signatures(&self, n_authorities: usize) -> usize {
+        match self.signature_threshold {
+            Some(threshold) => threshold.into(),
+            None => n_authorities / 2 + 1,
+        }
+    }
+
     /// Return an iterator of all the certificate IDs that we might use
     /// to validate this consensus.
     pub fn signing_cert_ids(&self) -> impl Iterator<Item = AuthCertKeyIds> {
@@ -1651,9 +1680,11 @@ impl<RS> UnvalidatedConsensus<RS> {
     /// well-signed.
     ///
     /// (This is the case if the consensus claims to be signed by more than
-    /// half of the authorities in the list.)
+    /// half of the authorities in the list,
+    /// or by as many as we were told to require with `set_signature_threshold`.)
     pub fn authorities_are_correct(&self, authorities: &[&RsaIdentity]) -> bool {
-        self.siggroup.could_validate(authorities)
+        self.siggroup
+            .could_validate(authorities, self.n_required_signatures(authorities.len()))
     }
 
     /// Return the number of relays in this unvalidated consensus.
@@ -1690,7 +1721,7 @@ impl<RS> ExternallySigned<Consensus<RS>> for UnvalidatedConsensus<RS> {
     fn key_is_correct(&self, k: &Self::Key) -> result::Result<(), Self::KeyHint> {
         let (n_ok, missing) = self.siggroup.list_missing(k);
         match self.n_authorities {
-            Some(n) if n_ok > (n / 2) as usize => Ok(()),
+            Some(n) if n_ok >= self.n_required_signatures(n.into()) => Ok(()),
             _ => Err(missing.iter().map(|cert| cert.key_ids).collect()),
         }
     }
@@ -1699,8 +1730,11 @@ impl<RS> ExternallySigned<Consensus<RS>> for UnvalidatedConsensus<RS> {
             None => Err(Error::from(internal!(
                 "Didn't set authorities on consensus"
             ))),
-            Some(authority) => {
-                if self.siggroup.validate(authority, k) {
+            Some(n) => {
+                if self
+                    .siggroup
+                    .validate(self.n_required_signatures(n.into()), k)
+                {
                     Ok(())
                 } else {
                     Err(EK::BadSignature.err())
@@ -1740,8 +1774,8 @@ impl SignatureGroup {
 
     /// Given a list of authority identity key fingerprints, return true if
     /// this signature group is _potentially_ well-signed according to those
-    /// authorities.
-    fn could_validate(&self, authorities: &[&RsaIdentity]) -> bool {
+    /// authorities, when we require signatures from `n_required` of them.
+    fn could_validate(&self, authorities: &[&RsaIdentity], n_required: usize) -> bool {
         let mut signed_by: HashSet<RsaIdentity> = HashSet::new();
         for sig in &self.signatures {
             let id_fp = &sig.key_ids.id_fingerprint;
@@ -1754,16 +1788,15 @@ impl SignatureGroup {
             }
         }
 
-        signed_by.len() > (authorities.len() / 2)
+        signed_by.len() >= n_required
     }
 
     /// Return true if the signature group defines a valid signature.
     ///
-    /// A signature is valid if it signed by more than half of the
-    /// authorities.  This API requires that `n_authorities` is the number of
-    /// authorities we believe in, and that every cert in `certs` belongs
+    /// A signature is valid if it signed by at least `n_required`
+    /// authorities.  This API requires that every cert in `certs` belongs
     /// to a real authority.
-    fn validate(&self, n_authorities: u16, certs: &[AuthCert]) -> bool {
+    fn validate(&self, n_required: usize, certs: &[AuthCert]) -> bool {
         // A set of the authorities (by identity) who have have signed
         // this document.  We use a set here in case `certs` has more
         // than one certificate for a single authority.
@@ -1798,7 +1831,7 @@ impl SignatureGroup {
             }
         }
 
-        ok.len() > (n_authorities / 2) as usize
+        ok.len() >= n_required
     }
 }
 
@@ -1879,6 +1912,19 @@ mod test {
         assert_eq!(2, missing.len());
         assert!(consensus.is_well_signed(&same_three_times).is_err());
 
+        {
+            // With a lower threshold, fewer signatures will do...
+            let lenient = consensus.clone().set_signature_threshold(1);
+            assert!(lenient.key_is_correct(&certs[0..1]).is_ok());
+            assert!(lenient.is_well_signed(&certs[0..1]).is_ok());
+            // ... and with a higher one, we need more.
+            let strict = consensus.clone().set_signature_threshold(3);
+            assert!(strict.key_is_correct(&certs[0..2]).is_err());
+            assert!(strict.is_well_signed(&certs[0..2]).is_err());
+            assert!(!strict.authorities_are_correct(&auth_ids[0..2]));
+            assert!(strict.is_well_signed(&certs).is_ok());
+        }
+
         assert!(consensus.key_is_correct(&certs).is_ok());
         let consensus = consensus.check_signature(&certs)?;
 
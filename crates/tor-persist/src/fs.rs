@@ -153,6 +153,39 @@ impl FsStateMgr {
             .expect("No parent directory even after path.join?")
     }
 
+    /// Return the keys of all the items in this storage manager.
+    ///
+    /// Keys are returned in their fs-safe form: see "Limitations" section on
+    /// [`FsStateMgr`].  Loading any of them will yield the stored item.
+    pub fn keys(&self) -> Result<Vec<String>> {
+        let dir_err = |e: ErrorSource| {
+            Error::new(
+                e,
+                Action::Loading,
+                Resource::Directory {
+                    dir: self.inner.statepath.as_path().to_path_buf(),
+                },
+            )
+        };
+        let mut keys = Vec::new();
+        for entry in self
+            .inner
+            .statepath
+            .read_directory(".")
+            .map_err(|e| dir_err(e.into()))?
+        {
+            let path = entry.map_err(|e| dir_err(e.into()))?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            if let Some(key) = path.file_stem().and_then(|stem| stem.to_str()) {
+                keys.push(key.to_owned());
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
     /// Remove old and/or obsolete items from this storage manager.
     ///
     /// Requires that we hold the lock.
@@ -297,6 +330,7 @@ mod test {
 
         assert_eq!(Some(stuff), stuff2);
         assert!(nothing.is_none());
+        assert_eq!(store.keys()?, vec!["xyz".to_string()]);
 
         assert_eq!(dir.path(), store.path());
 
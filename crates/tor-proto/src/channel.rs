@@ -72,6 +72,7 @@ use crate::util::ts::AtomicOptTimestamp;
 use crate::{circuit, ClockSkew};
 use crate::{Error, Result};
 use safelog::sensitive as sv;
+use std::net::IpAddr;
 use std::pin::Pin;
 use std::sync::{Mutex, MutexGuard};
 use std::time::Duration;
@@ -114,8 +115,14 @@ use tracing::trace;
 
 // reexport
 use crate::channel::unique_id::CircUniqIdContext;
+#[cfg(feature = "cell-crypto-offload")]
+pub use crate::crypto::cell::offload::{
+    CellCryptoEngine, SoftwareCellCrypto, Tor1Algorithms, Tor1CellState,
+};
 #[cfg(test)]
 pub(crate) use codec::CodecError;
+#[cfg(feature = "relay")]
+pub use handshake::InboundRelayHandshake;
 pub use handshake::{OutboundClientHandshake, UnverifiedChannel, VerifiedChannel};
 
 restricted_msg! {
@@ -195,6 +202,9 @@ pub struct Channel {
     /// The declared clock skew on this channel, at the time when this channel was
     /// created.
     clock_skew: ClockSkew,
+    /// The address the other side of this channel reported seeing us at,
+    /// in its NETINFO cell.
+    observed_addr: Option<IpAddr>,
     /// The time when this channel was successfully completed
     opened_at: coarsetime::Instant,
     /// Mutable state used by the `Channel.
@@ -242,6 +252,11 @@ pub(crate) struct ChannelDetails {
 struct MutableDetails {
     /// State used to control padding
     padding: PaddingControlState,
+    /// The engine that circuits on this channel use for relay cell cryptography.
+    ///
+    /// If this is None, they use our own implementation.
+    #[cfg(feature = "cell-crypto-offload")]
+    cell_crypto_engine: Option<Arc<dyn CellCryptoEngine>>,
 }
 
 /// State used to control padding
@@ -436,6 +451,31 @@ impl ChannelBuilder {
     {
         handshake::OutboundClientHandshake::new(tls, self.target, sleep_prov, memquota)
     }
+
+    /// Accept a new channel handshake from an initiator, over a TLS stream.
+    ///
+    /// `peer_addr` is the address the initiator connected from, if known.
+    /// `certs` is the CERTS cell that proves our identity as a relay,
+    /// and `my_addrs` are the addresses at which we believe we can be reached.
+    ///
+    /// After calling this function, you'll need to call `accept()` on
+    /// the result to answer the handshake.
+    #[cfg(feature = "relay")]
+    pub fn accept<T, S>(
+        self,
+        tls: T,
+        peer_addr: Option<std::net::SocketAddr>,
+        certs: msg::Certs,
+        my_addrs: Vec<IpAddr>,
+        sleep_prov: S,
+        memquota: ChannelAccount,
+    ) -> InboundRelayHandshake<T, S>
+    where
+        T: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+        S: CoarseTimeProvider + SleepProvider,
+    {
+        InboundRelayHandshake::new(tls, peer_addr, certs, my_addrs, sleep_prov, memquota)
+    }
 }
 
 impl Channel {
@@ -452,6 +492,7 @@ impl Channel {
         unique_id: UniqId,
         peer_id: OwnedChanTarget,
         clock_skew: ClockSkew,
+        observed_addr: Option<IpAddr>,
         sleep_prov: S,
         memquota: ChannelAccount,
     ) -> Result<(Arc<Self>, reactor::Reactor<S>)>
@@ -487,6 +528,7 @@ impl Channel {
             unique_id,
             peer_id,
             clock_skew,
+            observed_addr,
             opened_at: coarsetime::Instant::now(),
             mutable: Mutex::new(mutable),
             details: Arc::clone(&details),
@@ -548,6 +590,18 @@ impl Channel {
         self.clock_skew
     }
 
+    /// Return the address that the other side of this channel claimed to see
+    /// our connection coming from, if it told us.
+    ///
+    /// This comes from the relay's NETINFO cell, so it is only as trustworthy
+    /// as the relay: it might be mistaken, or lying.
+    /// If we're behind a NAT, this is (at best) the address of the NAT,
+    /// and if this channel goes through a proxy or a pluggable transport,
+    /// it is the address of the proxy or of the transport's far end.
+    pub fn observed_addr(&self) -> Option<IpAddr> {
+        self.observed_addr
+    }
+
     /// Send a control message
     fn send_control(&self, msg: CtrlMsg) -> StdResult<(), ChannelClosed> {
         self.control
@@ -561,6 +615,27 @@ impl Channel {
         self.mutable.lock().expect("channel details poisoned")
     }
 
+    /// Use `engine` for the relay cell cryptography of circuits on this channel.
+    ///
+    /// This only affects hops that are added after this function is called.
+    /// If `engine` doesn't implement the algorithms that a hop needs,
+    /// that hop uses our own implementation.
+    #[cfg(feature = "cell-crypto-offload")]
+    pub fn set_cell_crypto_engine(&self, engine: Arc<dyn CellCryptoEngine>) {
+        trace!(
+            "{}: using {} engine for cell crypto",
+            self.unique_id,
+            engine.name()
+        );
+        self.mutable().cell_crypto_engine = Some(engine);
+    }
+
+    /// Return the engine that circuits on this channel use for relay cell cryptography, if any.
+    #[cfg(feature = "cell-crypto-offload")]
+    pub(crate) fn cell_crypto_engine(&self) -> Option<Arc<dyn CellCryptoEngine>> {
+        self.mutable().cell_crypto_engine.clone()
+    }
+
     /// Specify that this channel should do activities related to channel padding
     ///
     /// Initially, the channel does nothing related to channel padding:
@@ -761,6 +836,7 @@ impl Channel {
             unique_id,
             peer_id,
             clock_skew: ClockSkew::None,
+            observed_addr: None,
             opened_at: coarsetime::Instant::now(),
             mutable: Default::default(),
             details,
@@ -860,6 +936,7 @@ pub(crate) mod test {
             unique_id,
             peer_id,
             clock_skew: ClockSkew::None,
+            observed_addr: None,
             opened_at: coarsetime::Instant::now(),
             mutable: Default::default(),
             details,
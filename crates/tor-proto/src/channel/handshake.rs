@@ -15,7 +15,7 @@ use crate::{Error, Result};
 use tor_cell::chancell::{msg, ChanCmd, ChanMsg};
 use tor_rtcompat::{CoarseTimeProvider, SleepProvider};
 
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use std::time::SystemTime;
 
@@ -31,6 +31,12 @@ use super::CellFrame;
 
 use tracing::{debug, trace};
 
+#[cfg(feature = "relay")]
+mod relay;
+
+#[cfg(feature = "relay")]
+pub use relay::InboundRelayHandshake;
+
 /// A list of the link protocols that we support.
 static LINK_PROTOCOLS: &[u16] = &[4, 5];
 
@@ -78,7 +84,6 @@ pub struct UnverifiedChannel<
     /// Declared target method for this channel, if any.
     target_method: Option<ChannelMethod>,
     /// The netinfo cell that we got from the relay.
-    #[allow(dead_code)] // Relays will need this.
     netinfo_cell: msg::Netinfo,
     /// How much clock skew did we detect in this handshake?
     ///
@@ -118,6 +123,8 @@ pub struct VerifiedChannel<
     rsa_id: RsaIdentity,
     /// Authenticated clock skew for this peer.
     clock_skew: ClockSkew,
+    /// The address this peer reported seeing us at.
+    observed_addr: Option<IpAddr>,
 }
 
 restricted_msg! {
@@ -152,6 +159,59 @@ fn codec_err_to_handshake(err: CodecError) -> Error {
     }
 }
 
+/// Helper: wrap an IoError as a HandshakeIoErr.
+fn io_err_to_handshake(err: std::io::Error) -> Error {
+    Error::HandshakeIoErr(Arc::new(err))
+}
+
+/// Send a VERSIONS cell listing our [`LINK_PROTOCOLS`] on `tls`, and flush it.
+async fn send_versions_cell<T: AsyncWrite + Unpin>(tls: &mut T) -> Result<()> {
+    let my_versions = msg::Versions::new(LINK_PROTOCOLS)
+        .map_err(|e| Error::from_cell_enc(e, "versions message"))?;
+    tls.write_all(
+        &my_versions
+            .encode_for_handshake()
+            .map_err(|e| Error::from_cell_enc(e.into(), "versions message"))?,
+    )
+    .await
+    .map_err(io_err_to_handshake)?;
+    tls.flush().await.map_err(io_err_to_handshake)?;
+    Ok(())
+}
+
+/// Read a VERSIONS cell from `tls`.
+///
+/// `not_tor` is the error message to give if the other party doesn't seem to be
+/// speaking the Tor protocol at all.
+async fn read_versions_cell<T: AsyncRead + Unpin>(
+    tls: &mut T,
+    not_tor: &'static str,
+) -> Result<msg::Versions> {
+    let mut hdr = [0_u8; 5];
+    let not_tor = || Err(Error::HandshakeProto(not_tor.into()));
+    match tls.read_exact(&mut hdr).await {
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return not_tor(),
+        otherwise => otherwise,
+    }
+    .map_err(io_err_to_handshake)?;
+    if hdr[0..3] != [0, 0, ChanCmd::VERSIONS.into()] {
+        return not_tor();
+    }
+    let msglen = u16::from_be_bytes(
+        hdr[3..5]
+            .try_into()
+            .expect("Two-byte field was not two bytes!?"),
+    );
+    let mut msg = vec![0; msglen as usize];
+    tls.read_exact(&mut msg)
+        .await
+        .map_err(io_err_to_handshake)?;
+    let mut reader = Reader::from_slice(&msg);
+    reader
+        .extract()
+        .map_err(|e| Error::from_bytes_err(e, "versions cell"))
+}
+
 impl<T: AsyncRead + AsyncWrite + Send + Unpin + 'static, S: CoarseTimeProvider + SleepProvider>
     OutboundClientHandshake<T, S>
 {
@@ -180,11 +240,6 @@ impl<T: AsyncRead + AsyncWrite + Send + Unpin + 'static, S: CoarseTimeProvider +
     where
         F: FnOnce() -> SystemTime,
     {
-        /// Helper: wrap an IoError as a HandshakeIoErr.
-        fn io_err_to_handshake(err: std::io::Error) -> Error {
-            Error::HandshakeIoErr(Arc::new(err))
-        }
-
         match &self.target_method {
             Some(method) => debug!(
                 "{}: starting Tor handshake with {:?}",
@@ -194,55 +249,14 @@ impl<T: AsyncRead + AsyncWrite + Send + Unpin + 'static, S: CoarseTimeProvider +
         }
         trace!("{}: sending versions", self.unique_id);
         // Send versions cell
-        {
-            let my_versions = msg::Versions::new(LINK_PROTOCOLS)
-                .map_err(|e| Error::from_cell_enc(e, "versions message"))?;
-            self.tls
-                .write_all(
-                    &my_versions
-                        .encode_for_handshake()
-                        .map_err(|e| Error::from_cell_enc(e.into(), "versions message"))?,
-                )
-                .await
-                .map_err(io_err_to_handshake)?;
-            self.tls.flush().await.map_err(io_err_to_handshake)?;
-        }
+        send_versions_cell(&mut self.tls).await?;
         let versions_flushed_at = coarsetime::Instant::now();
         let versions_flushed_wallclock = now_fn();
 
         // Get versions cell.
         trace!("{}: waiting for versions", self.unique_id);
-        let their_versions: msg::Versions = {
-            // TODO: this could be turned into another function, I suppose.
-            let mut hdr = [0_u8; 5];
-            let not_relay = || {
-                Err(Error::HandshakeProto(
-                    "Doesn't seem to be a tor relay".into(),
-                ))
-            };
-            match self.tls.read_exact(&mut hdr).await {
-                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return not_relay(),
-                otherwise => otherwise,
-            }
-            .map_err(io_err_to_handshake)?;
-            if hdr[0..3] != [0, 0, ChanCmd::VERSIONS.into()] {
-                return not_relay();
-            }
-            let msglen = u16::from_be_bytes(
-                hdr[3..5]
-                    .try_into()
-                    .expect("Two-byte field was not two bytes!?"),
-            );
-            let mut msg = vec![0; msglen as usize];
-            self.tls
-                .read_exact(&mut msg)
-                .await
-                .map_err(io_err_to_handshake)?;
-            let mut reader = Reader::from_slice(&msg);
-            reader
-                .extract()
-                .map_err(|e| Error::from_bytes_err(e, "versions cell"))?
-        };
+        let their_versions =
+            read_versions_cell(&mut self.tls, "Doesn't seem to be a tor relay").await?;
         trace!("{}: received {:?}", self.unique_id, their_versions);
 
         // Determine which link protocol we negotiated.
@@ -584,6 +598,7 @@ impl<T: AsyncRead + AsyncWrite + Send + Unpin + 'static, S: CoarseTimeProvider +
             ed25519_id: *identity_key,
             rsa_id,
             clock_skew: self.clock_skew,
+            observed_addr: self.netinfo_cell.their_addr().copied(),
             sleep_prov: self.sleep_prov,
             memquota: self.memquota,
         })
@@ -652,6 +667,7 @@ impl<T: AsyncRead + AsyncWrite + Send + Unpin + 'static, S: CoarseTimeProvider +
             self.unique_id,
             peer_id,
             self.clock_skew,
+            self.observed_addr,
             self.sleep_prov,
             self.memquota,
         )
@@ -890,7 +906,7 @@ pub(super) mod test {
     }
 
     // Timestamp when the example certificates were all valid.
-    fn cert_timestamp() -> SystemTime {
+    pub(super) fn cert_timestamp() -> SystemTime {
         use humantime::parse_rfc3339;
         parse_rfc3339("2020-09-26T18:01:20Z").unwrap()
     }
@@ -954,7 +970,11 @@ pub(super) mod test {
             certs::PEER_CERT_DIGEST,
             &rt,
         );
-        let _ = res.unwrap();
+        let verified = res.unwrap();
+        assert_eq!(
+            verified.observed_addr,
+            Some(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST))
+        );
     }
 
     #[test]
@@ -1114,7 +1134,7 @@ pub(super) mod test {
     /// cell test vector in the tor-cell crate.
     ///
     /// The names are taken from the type of the certificate.
-    mod certs {
+    pub(super) mod certs {
         use hex_literal::hex;
 
         pub(crate) const CERT_T2: &[u8] = &hex!("308201B930820122A0030201020208607C28BE6C390943300D06092A864886F70D01010B0500301F311D301B06035504030C147777772E74636A76356B766A646472322E636F6D301E170D3230303831303030303030305A170D3231303831303030303030305A301F311D301B06035504030C147777772E74636A76356B766A646472322E636F6D30819F300D06092A864886F70D010101050003818D0030818902818100D38B1E6CEB946E0DB0751F4CBACE3DCB9688B6C25304227B4710C35AFB73627E50500F5913E158B621802612D1C75827003703338375237552EB3CD3C12F6AB3604E60C1A2D26BB1FBAD206FF023969A90909D6A65A5458A5312C26EBD3A3DAD30302D4515CDCD264146AC18E6FC60A04BD3EC327F04294D96BA5AA25B464C3F0203010001300D06092A864886F70D01010B0500038181003BCE561EA7F95CC00B78AAB5D69573FF301C282A751D4A651921D042F1BECDBA24D918A6D8A5E138DC07BBA0B335478AE37ABD2C93A93932442AE9084329E846170FE0FC4A50AAFC804F311CC3CA4F41D845A7BA5901CBBC3E021E9794AAC70CE1F37B0A951592DB1B64F2B4AFB81AE52DBD9B6FEDE96A5FB8125EB6251EE50A");
@@ -1146,6 +1166,7 @@ pub(super) mod test {
                 ed25519_id,
                 rsa_id,
                 clock_skew: ClockSkew::None,
+                observed_addr: None,
                 sleep_prov: rt,
                 memquota: fake_mq(),
             };
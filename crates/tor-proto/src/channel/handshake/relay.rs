@@ -0,0 +1,354 @@
+//! The responder side of the channel handshake, as performed by a relay.
+//!
+//! When a relay accepts a connection on its ORPort,
+//! it waits for the initiator's VERSIONS cell,
+//! answers with VERSIONS, CERTS, AUTH_CHALLENGE, and NETINFO cells,
+//! and then waits for the initiator's NETINFO cell.
+//!
+//! For now, we only accept initiators that do not authenticate (that is, clients):
+//! an initiator that sends a CERTS or AUTHENTICATE cell is rejected.
+
+use asynchronous_codec as futures_codec;
+use futures::io::{AsyncRead, AsyncWrite};
+use futures::sink::SinkExt;
+use futures::stream::StreamExt;
+use rand::Rng;
+use safelog::sensitive as sv;
+use tor_cell::chancell::msg::{self, AnyChanMsg};
+use tor_cell::chancell::{AnyChanCell, ChanMsg};
+use tor_cell::restricted_msg;
+use tor_linkspec::OwnedChanTargetBuilder;
+use tor_rtcompat::{CoarseTimeProvider, SleepProvider};
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use tracing::{debug, trace};
+
+use super::{codec_err_to_handshake, read_versions_cell, send_versions_cell, LINK_PROTOCOLS};
+use crate::channel::codec::{self, ChannelCodec};
+use crate::channel::reactor::Reactor;
+use crate::channel::{CellFrame, Channel, UniqId};
+use crate::memquota::ChannelAccount;
+use crate::util::skew::ClockSkew;
+use crate::{Error, Result};
+
+restricted_msg! {
+    /// A restricted subset of ChanMsg that can arrive from the initiator
+    /// during a handshake.
+    ///
+    /// (These are messages that come after the VERSIONS cell, up to and
+    /// including the NETINFO.)
+    #[derive(Clone,Debug)]
+    enum InboundHandshakeMsg : ChanMsg {
+        Padding,
+        Vpadding,
+        Certs,
+        Authenticate,
+        Netinfo
+    }
+}
+
+/// The authentication methods that we list in our AUTH_CHALLENGE cells.
+///
+/// (3 is "Ed25519-SHA256-RFC5705".)
+///
+/// We don't yet accept AUTHENTICATE cells at all,
+/// but the cell must list at least one method.
+const AUTH_METHODS: &[u16] = &[3];
+
+/// A raw channel that a relay has accepted, on which nothing has been done.
+pub struct InboundRelayHandshake<
+    T: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    S: CoarseTimeProvider + SleepProvider,
+> {
+    /// Runtime handle (insofar as we need it)
+    sleep_prov: S,
+
+    /// Memory quota account
+    memquota: ChannelAccount,
+
+    /// Underlying TLS stream.
+    ///
+    /// (We don't enforce that this is actually TLS, but if it isn't, the
+    /// connection won't be secure.)
+    tls: T,
+
+    /// The address that the initiator connected from, if known.
+    peer_addr: Option<SocketAddr>,
+
+    /// The CERTS cell that proves our identity to the initiator.
+    certs: msg::Certs,
+
+    /// The addresses at which we believe that we can be reached.
+    my_addrs: Vec<IpAddr>,
+
+    /// Logging identifier for this stream.  (Used for logging only.)
+    unique_id: UniqId,
+}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin + 'static, S: CoarseTimeProvider + SleepProvider>
+    InboundRelayHandshake<T, S>
+{
+    /// Construct a new InboundRelayHandshake.
+    pub(crate) fn new(
+        tls: T,
+        peer_addr: Option<SocketAddr>,
+        certs: msg::Certs,
+        my_addrs: Vec<IpAddr>,
+        sleep_prov: S,
+        memquota: ChannelAccount,
+    ) -> Self {
+        Self {
+            sleep_prov,
+            memquota,
+            tls,
+            peer_addr,
+            certs,
+            my_addrs,
+            unique_id: UniqId::new(),
+        }
+    }
+
+    /// Answer the initiator's handshake, and create an open channel and reactor.
+    ///
+    /// Takes a function that reports the current time.  In theory, this can just be
+    /// `SystemTime::now()`.
+    ///
+    /// The resulting channel is not authenticated: its target has the
+    /// initiator's address (if we know it), but no relay identities.
+    ///
+    /// Note that we don't yet handle any circuit traffic on inbound channels:
+    /// a CREATE cell from the initiator will close the channel.
+    pub async fn accept<F>(mut self, now_fn: F) -> Result<(Arc<Channel>, Reactor<S>)>
+    where
+        F: FnOnce() -> SystemTime,
+    {
+        match &self.peer_addr {
+            Some(addr) => debug!(
+                "{}: accepting Tor handshake from {}",
+                self.unique_id,
+                sv(addr)
+            ),
+            None => debug!("{}: accepting Tor handshake", self.unique_id),
+        }
+
+        // Get versions cell.
+        trace!("{}: waiting for versions", self.unique_id);
+        let their_versions =
+            read_versions_cell(&mut self.tls, "Doesn't seem to be a tor client").await?;
+        trace!("{}: received {:?}", self.unique_id, their_versions);
+
+        // Send versions cell.
+        trace!("{}: sending versions", self.unique_id);
+        send_versions_cell(&mut self.tls).await?;
+
+        // Determine which link protocol we negotiated.
+        let link_protocol = their_versions
+            .best_shared_link_protocol(LINK_PROTOCOLS)
+            .ok_or_else(|| Error::HandshakeProto("No shared link protocols".into()))?;
+        trace!("{}: negotiated version {}", self.unique_id, link_protocol);
+
+        let codec = ChannelCodec::<InboundHandshakeMsg, AnyChanMsg>::new(link_protocol);
+        let mut tls = futures_codec::Framed::new(self.tls, codec);
+
+        // Send the rest of our side of the handshake.
+        trace!(
+            "{}: sending certs, auth_challenge, and netinfo",
+            self.unique_id
+        );
+        let challenge: [u8; 32] = rand::thread_rng().gen();
+        let timestamp = now_fn()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs().try_into().unwrap_or(u32::MAX))
+            .unwrap_or(0);
+        let netinfo = msg::Netinfo::from_relay(
+            timestamp,
+            self.peer_addr.map(|addr| addr.ip()),
+            self.my_addrs,
+        );
+        let cells: [AnyChanCell; 3] = [
+            self.certs.into(),
+            msg::AuthChallenge::new(challenge, AUTH_METHODS).into(),
+            netinfo.into(),
+        ];
+        for cell in cells {
+            tls.feed(cell).await.map_err(codec_err_to_handshake)?;
+        }
+        tls.flush().await.map_err(codec_err_to_handshake)?;
+
+        // Read until we have the netinfo cell.
+        trace!("{}: waiting for rest of handshake.", self.unique_id);
+        let mut netinfo = None;
+        while let Some(m) = tls.next().await {
+            use InboundHandshakeMsg::*;
+            let (_, m) = m.map_err(codec_err_to_handshake)?.into_circid_and_msg();
+            trace!("{}: received a {} cell.", self.unique_id, m.cmd());
+            match m {
+                Padding(_) | Vpadding(_) => (),
+                Certs(_) | Authenticate(_) => {
+                    return Err(Error::HandshakeProto(
+                        "Authenticating initiators are not yet supported".into(),
+                    ));
+                }
+                Netinfo(n) => {
+                    netinfo = Some(n);
+                    break;
+                }
+            }
+        }
+        let netinfo = netinfo
+            .ok_or_else(|| Error::HandshakeProto("Missing netinfo or closed stream".into()))?;
+
+        // We treat a completed channel as incoming traffic, as we do for
+        // outbound channels.
+        crate::note_incoming_traffic();
+        debug!("{}: Completed handshake with client", self.unique_id);
+
+        let tls: CellFrame<T> = codec::change_message_types(tls);
+        let (tls_sink, tls_stream) = tls.split();
+
+        let mut peer_builder = OwnedChanTargetBuilder::default();
+        if let Some(addr) = self.peer_addr {
+            peer_builder.addrs(vec![addr]);
+        }
+        let peer_id = peer_builder
+            .build()
+            .expect("OwnedChanTarget builder failed");
+
+        Channel::new(
+            link_protocol,
+            Box::new(tls_sink),
+            Box::new(tls_stream),
+            self.unique_id,
+            peer_id,
+            // Clients don't report their time.
+            ClockSkew::None,
+            netinfo.their_addr().copied(),
+            self.sleep_prov,
+            self.memquota,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use crate::channel::handshake::test::{cert_timestamp, certs};
+    use crate::channel::handshake::OutboundClientHandshake;
+    use crate::util::fake_mq;
+    use tor_linkspec::{HasAddrs, HasRelayIds, OwnedChanTarget};
+    use tor_llcrypto::pk::ed25519::Ed25519Identity;
+    use tor_llcrypto::pk::rsa::RsaIdentity;
+    use tor_rtmock::io::stream_pair;
+
+    fn relay_certs() -> msg::Certs {
+        let mut certs = msg::Certs::new_empty();
+        certs.push_cert_body(2.into(), certs::CERT_T2);
+        certs.push_cert_body(5.into(), certs::CERT_T5);
+        certs.push_cert_body(7.into(), certs::CERT_T7);
+        certs.push_cert_body(4.into(), certs::CERT_T4);
+        certs
+    }
+
+    #[test]
+    fn loopback() {
+        tor_rtcompat::test_with_one_runtime!(|rt| async move {
+            let (client_stream, relay_stream) = stream_pair();
+            let client_addr: SocketAddr = "192.0.2.7:4321".parse().unwrap();
+            let relay_addr: IpAddr = "198.51.100.9".parse().unwrap();
+
+            let relay = InboundRelayHandshake::new(
+                relay_stream,
+                Some(client_addr),
+                relay_certs(),
+                vec![relay_addr],
+                rt.clone(),
+                fake_mq(),
+            );
+            let client = OutboundClientHandshake::new(client_stream, None, rt.clone(), fake_mq());
+
+            let ed = Ed25519Identity::from_bytes(certs::PEER_ED).unwrap();
+            let rsa = RsaIdentity::from_bytes(certs::PEER_RSA).unwrap();
+            let target = OwnedChanTarget::builder()
+                .ed_identity(ed)
+                .rsa_identity(rsa)
+                .build()
+                .unwrap();
+            let client_side = async {
+                client
+                    .connect(cert_timestamp)
+                    .await?
+                    .check_internal(&target, certs::PEER_CERT_DIGEST, Some(cert_timestamp()))?
+                    .finish()
+                    .await
+            };
+
+            let (client_res, relay_res) = futures::join!(client_side, relay.accept(cert_timestamp));
+            let (client_chan, _client_reactor) = client_res.unwrap();
+            let (relay_chan, _relay_reactor) = relay_res.unwrap();
+
+            // The client authenticated the relay, and learned its address as the relay saw it.
+            assert_eq!(client_chan.target().ed_identity(), Some(&ed));
+            assert_eq!(client_chan.target().rsa_identity(), Some(&rsa));
+            assert_eq!(client_chan.observed_addr(), Some(client_addr.ip()));
+            assert_eq!(client_chan.clock_skew(), ClockSkew::None);
+
+            // The relay knows the client only by its address.
+            assert_eq!(relay_chan.target().addrs(), &[client_addr]);
+            assert!(relay_chan.target().ed_identity().is_none());
+            assert!(relay_chan.target().rsa_identity().is_none());
+        });
+    }
+
+    #[test]
+    fn reject_authenticating_initiator() {
+        use futures::io::{AsyncReadExt as _, AsyncWriteExt as _};
+        use hex_literal::hex;
+
+        tor_rtcompat::test_with_one_runtime!(|rt| async move {
+            let (mut client_stream, relay_stream) = stream_pair();
+            let relay = InboundRelayHandshake::new(
+                relay_stream,
+                None,
+                relay_certs(),
+                vec![],
+                rt.clone(),
+                fake_mq(),
+            );
+            // A VERSIONS cell for link protocol 4, followed by an empty CERTS cell.
+            client_stream
+                .write_all(&hex!("0000 07 0002 0004  00000000 81 0001 00"))
+                .await
+                .unwrap();
+            client_stream.flush().await.unwrap();
+
+            // (We have to keep reading the relay's side of the handshake,
+            // or the relay will block trying to send it.)
+            let mut relay_output = Vec::new();
+            let (relay_res, _) = futures::join!(
+                relay.accept(SystemTime::now),
+                client_stream.read_to_end(&mut relay_output),
+            );
+            let err = relay_res.err().unwrap();
+            assert_eq!(
+                err.to_string(),
+                "Handshake protocol violation: Authenticating initiators are not yet supported"
+            );
+        });
+    }
+}
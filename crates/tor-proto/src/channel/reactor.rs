@@ -513,6 +513,7 @@ pub(crate) mod test {
             unique_id,
             dummy_target,
             crate::ClockSkew::None,
+            None,
             runtime,
             fake_mq(),
         )
@@ -48,12 +48,14 @@ mod path;
 pub(crate) mod reactor;
 pub(crate) mod sendme;
 mod streammap;
+#[cfg(any(test, feature = "testing"))]
+pub mod trace;
 mod unique_id;
 
 use crate::channel::Channel;
 use crate::circuit::celltypes::*;
 use crate::circuit::reactor::{
-    CircuitHandshake, CtrlMsg, Reactor, RECV_WINDOW_INIT, STREAM_READER_BUFFER,
+    CircuitHandshake, CtrlMsg, Reactor, RECV_WINDOW_INIT, SEND_WINDOW_INIT, STREAM_READER_BUFFER,
 };
 pub use crate::circuit::unique_id::UniqId;
 pub use crate::crypto::binding::CircuitBinding;
@@ -63,8 +65,8 @@ use crate::crypto::handshake::ntor_v3::NtorV3PublicKey;
 pub use crate::memquota::StreamAccount;
 use crate::memquota::{CircuitAccount, SpecificAccount as _};
 use crate::stream::{
-    AnyCmdChecker, DataCmdChecker, DataStream, ResolveCmdChecker, ResolveStream, StreamParameters,
-    StreamReader,
+    AnyCmdChecker, DataCmdChecker, DataStream, FlowStatsRecorder, ResolveCmdChecker, ResolveStream,
+    StreamParameters, StreamReader,
 };
 use crate::{Error, ResolveError, Result};
 use educe::Educe;
@@ -91,9 +93,12 @@ use futures::{FutureExt as _, SinkExt as _};
 use std::net::IpAddr;
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tor_async_utils::SinkCloseChannel as _;
 use tor_cell::relaycell::StreamId;
 use tor_memquota::mq_queue::{self, ChannelSpec as _, MpscSpec};
+use tor_rtcompat::{SleepProvider as _, SleepProviderExt as _};
+use tracing::debug;
 // use std::time::Duration;
 
 use crate::crypto::handshake::ntor::NtorPublicKey;
@@ -103,6 +108,11 @@ pub use reactor::syncview::ClientCircSyncView;
 /// The size of the buffer for communication between `ClientCirc` and its reactor.
 pub const CIRCUIT_BUFFER_SIZE: usize = 128;
 
+/// The address we ask relays to resolve in liveness probes.
+///
+/// Relays answer requests to resolve an address literal without doing any DNS lookups.
+const LIVENESS_PROBE_ADDR: &str = "127.0.0.1";
+
 #[cfg(feature = "send-control-msg")]
 use reactor::MetaCellHandler;
 
@@ -307,6 +317,8 @@ pub(crate) struct StreamTarget {
     tx: StreamMpscSender<AnyRelayMsg>,
     /// Reference to the circuit that this stream is on.
     circ: Arc<ClientCirc>,
+    /// A record of the flow-control state of this stream.
+    flow_stats: FlowStatsRecorder,
 }
 
 impl ClientCirc {
@@ -612,6 +624,7 @@ impl ClientCirc {
                 receiver,
                 msg_tx,
                 memquota,
+                flow_stats,
             } = req_ctx;
 
             // We already enforce this in handle_incoming_stream_request; this
@@ -625,6 +638,7 @@ impl ClientCirc {
                 tx: msg_tx,
                 hop_num,
                 stream_id,
+                flow_stats,
             };
 
             let reader = StreamReader {
@@ -775,16 +789,14 @@ impl ClientCirc {
     ///
     /// The caller will typically want to see the first cell in response,
     /// to see whether it is e.g. an END or a CONNECTED.
+    ///
+    /// The stream will start with a send window of `send_window` cells.
     async fn begin_stream_impl(
         self: &Arc<ClientCirc>,
         begin_msg: AnyRelayMsg,
         cmd_checker: AnyCmdChecker,
+        send_window: u16,
     ) -> Result<(StreamReader, StreamTarget, StreamAccount)> {
-        // TODO: Possibly this should take a hop, rather than just
-        // assuming it's the last hop.
-
-        let time_prov = self.channel().time_provider().clone();
-
         let hop_num = self
             .mutable
             .lock()
@@ -793,12 +805,28 @@ impl ClientCirc {
             .last_hop_num()
             .ok_or_else(|| Error::from(internal!("Can't begin a stream at the 0th hop")))?;
 
+        self.begin_stream_impl_at(hop_num, begin_msg, cmd_checker, send_window)
+            .await
+    }
+
+    /// Like [`begin_stream_impl`](Self::begin_stream_impl), but begins the stream
+    /// at the hop `hop_num` rather than at the last hop.
+    async fn begin_stream_impl_at(
+        self: &Arc<ClientCirc>,
+        hop_num: HopNum,
+        begin_msg: AnyRelayMsg,
+        cmd_checker: AnyCmdChecker,
+        send_window: u16,
+    ) -> Result<(StreamReader, StreamTarget, StreamAccount)> {
+        let time_prov = self.channel().time_provider().clone();
+
         let memquota = StreamAccount::new(self.mq_account())?;
         let (sender, receiver) = MpscSpec::new(STREAM_READER_BUFFER)
             .new_mq(time_prov.clone(), memquota.as_raw_account())?;
         let (tx, rx) = oneshot::channel();
         let (msg_tx, msg_rx) =
             MpscSpec::new(CIRCUIT_BUFFER_SIZE).new_mq(time_prov, memquota.as_raw_account())?;
+        let flow_stats = FlowStatsRecorder::new(send_window, RECV_WINDOW_INIT);
 
         self.control
             .unbounded_send(CtrlMsg::BeginStream {
@@ -808,6 +836,8 @@ impl ClientCirc {
                 rx: msg_rx,
                 done: tx,
                 cmd_checker,
+                send_window,
+                flow_stats: flow_stats.clone(),
             })
             .map_err(|_| Error::CircuitClosed)?;
 
@@ -818,6 +848,7 @@ impl ClientCirc {
             tx: msg_tx,
             hop_num,
             stream_id,
+            flow_stats,
         };
 
         let reader = StreamReader {
@@ -836,9 +867,10 @@ impl ClientCirc {
         self: &Arc<ClientCirc>,
         msg: AnyRelayMsg,
         optimistic: bool,
+        send_window: u16,
     ) -> Result<DataStream> {
         let (reader, target, memquota) = self
-            .begin_stream_impl(msg, DataCmdChecker::new_any())
+            .begin_stream_impl(msg, DataCmdChecker::new_any(), send_window)
             .await?;
         let mut stream = DataStream::new(reader, target, memquota);
         if !optimistic {
@@ -868,7 +900,9 @@ impl ClientCirc {
         };
         let beginmsg = Begin::new(target, port, begin_flags)
             .map_err(|e| Error::from_cell_enc(e, "begin message"))?;
-        self.begin_data_stream(beginmsg.into(), optimistic).await
+        let send_window = parameters.send_window_init().unwrap_or(SEND_WINDOW_INIT);
+        self.begin_data_stream(beginmsg.into(), optimistic, send_window)
+            .await
     }
 
     /// Start a new stream to the last relay in the circuit, using
@@ -878,8 +912,41 @@ impl ClientCirc {
         // Since they are local to a relay that we've already authenticated
         // with and built a circuit to, there should be no additional checks
         // we need to perform to see whether the BEGINDIR will succeed.
-        self.begin_data_stream(AnyRelayMsg::BeginDir(Default::default()), true)
-            .await
+        self.begin_data_stream(
+            AnyRelayMsg::BeginDir(Default::default()),
+            true,
+            SEND_WINDOW_INIT,
+        )
+        .await
+    }
+
+    /// Start a UDP stream to the given address and port, using a
+    /// CONNECT_UDP cell.
+    ///
+    /// As with [`begin_stream`](Self::begin_stream), you should pass a hostname
+    /// rather than an address where possible, and let the exit look it up.
+    ///
+    /// Waits for the exit to acknowledge the stream with a CONNECTED_UDP message.
+    /// Only exits that implement proposal 339 support this.
+    #[cfg(feature = "experimental-udp")]
+    pub async fn begin_udp_stream(
+        self: &Arc<ClientCirc>,
+        target: &str,
+        port: u16,
+        parameters: Option<StreamParameters>,
+    ) -> Result<crate::stream::DatagramStream> {
+        use crate::stream::{DatagramStream, UdpCmdChecker};
+        use tor_cell::relaycell::msg::ConnectUdp;
+
+        let parameters = parameters.unwrap_or_default();
+        let msg = ConnectUdp::new(target, port, parameters.begin_flags())
+            .map_err(|e| Error::from_cell_enc(e, "connect_udp message"))?;
+        let (reader, target, memquota) = self
+            .begin_stream_impl(msg.into(), UdpCmdChecker::new_any(), SEND_WINDOW_INIT)
+            .await?;
+        let mut stream = DatagramStream::new(reader, target, memquota);
+        stream.wait_for_connection().await?;
+        Ok(stream)
     }
 
     /// Perform a DNS lookup, using a RESOLVE cell with the last relay
@@ -931,12 +998,121 @@ impl ClientCirc {
     /// resolve stream.
     async fn try_resolve(self: &Arc<ClientCirc>, msg: Resolve) -> Result<Resolved> {
         let (reader, _target, memquota) = self
-            .begin_stream_impl(msg.into(), ResolveCmdChecker::new_any())
+            .begin_stream_impl(msg.into(), ResolveCmdChecker::new_any(), SEND_WINDOW_INIT)
             .await?;
         let mut resolve_stream = ResolveStream::new(reader, memquota);
         resolve_stream.read_msg().await
     }
 
+    /// Check that the hop `hop_num` of this circuit is still reachable,
+    /// and return the round-trip time to it.
+    ///
+    /// This asks the hop to resolve an IP address literal, using a RESOLVE message.
+    /// Relays answer such requests right away, without doing any DNS lookups,
+    /// either with a RESOLVED message or (if they refuse) with an END message:
+    /// either answer shows that the hop is alive.
+    ///
+    /// `hop_num` must be a relay: virtual hops (such as the onion service
+    /// at the end of a rendezvous circuit) don't answer RESOLVE messages,
+    /// so probing one returns an error without sending anything.
+    ///
+    /// Note that this function does not check for timeouts; that's
+    /// the caller's responsibility.
+    /// (If the circuit is dead, the answer never arrives.)
+    /// [`ClientCirc::keepalive`] handles timeouts for you.
+    pub async fn probe_liveness(self: &Arc<Self>, hop_num: HopNum) -> Result<Duration> {
+        {
+            let mutable = self.mutable.lock().expect("poisoned lock");
+            if usize::from(hop_num) >= mutable.path.n_hops() {
+                return Err(Error::NoSuchHop);
+            }
+            if mutable.path.is_virtual_hop(hop_num) {
+                return Err(bad_api_usage!("Can't probe the liveness of a virtual hop").into());
+            }
+        }
+
+        let time_prov = self.channel().time_provider().clone();
+        let start = time_prov.now();
+        let resolve_msg = Resolve::new(LIVENESS_PROBE_ADDR);
+        let (reader, _target, memquota) = self
+            .begin_stream_impl_at(
+                hop_num,
+                resolve_msg.into(),
+                ResolveCmdChecker::new_any(),
+                SEND_WINDOW_INIT,
+            )
+            .await?;
+        let mut resolve_stream = ResolveStream::new(reader, memquota);
+        match resolve_stream.read_msg().await {
+            Ok(_) | Err(Error::EndReceived(_)) => {}
+            Err(e) => return Err(e),
+        }
+        Ok(time_prov.now().saturating_duration_since(start))
+    }
+
+    /// Probe the last relay of this circuit every `interval`, until the circuit closes,
+    /// and close the circuit if a probe goes unanswered for `timeout`.
+    ///
+    /// This is meant for circuits that carry streams which can stay idle for a long time
+    /// (for instance, an IMAP connection waiting in `IDLE`):
+    /// if the circuit has silently died, its streams would only notice
+    /// when they next try to use it.
+    /// With a keepalive running, they get an error soon after the circuit stops responding,
+    /// so the application can reconnect before it needs to write.
+    ///
+    /// Virtual hops are never probed:
+    /// on an onion service circuit, the probes go to the rendezvous point.
+    /// See [`ClientCirc::probe_liveness`].
+    ///
+    /// The returned future must be spawned (or otherwise polled) for the probes to be sent.
+    /// It only holds a weak reference to the circuit:
+    /// it resolves to `Ok(())` once the circuit is closed or dropped for other reasons,
+    /// and to an error (after closing the circuit) if a probe fails or times out.
+    pub fn keepalive(
+        self: &Arc<Self>,
+        interval: Duration,
+        timeout: Duration,
+    ) -> impl futures::Future<Output = Result<()>> + Send + 'static {
+        let circ = Arc::downgrade(self);
+        let time_prov = self.channel().time_provider().clone();
+
+        async move {
+            loop {
+                time_prov.sleep(interval).await;
+
+                let Some(circ) = circ.upgrade() else {
+                    return Ok(());
+                };
+                if circ.is_closing() {
+                    return Ok(());
+                }
+                let hop_num = circ
+                    .mutable
+                    .lock()
+                    .expect("poisoned lock")
+                    .path
+                    .last_relay_hop_num()
+                    .ok_or_else(|| internal!("no relay hop to probe"))?;
+                let outcome = match time_prov
+                    .timeout(timeout, circ.probe_liveness(hop_num))
+                    .await
+                {
+                    Ok(Ok(_rtt)) => continue,
+                    Ok(Err(Error::CircuitClosed)) => return Ok(()),
+                    Ok(Err(e)) => e,
+                    Err(_) => Error::CircuitUnresponsive(timeout),
+                };
+                debug!(
+                    "{}: Closing circuit after failed liveness probe: {}",
+                    circ.unique_id(),
+                    outcome
+                );
+                circ.terminate();
+                return Err(outcome);
+            }
+        }
+    }
+
     /// Shut down this circuit, along with all streams that are using it.
     /// Happens asynchronously (i.e. the circuit won't necessarily be done shutting down
     /// immediately after this function returns!).
@@ -972,6 +1148,18 @@ impl ClientCirc {
         self.unique_id
     }
 
+    /// Install `hook` to observe every relay message sent or received on
+    /// this circuit, replacing any previously installed hook.
+    ///
+    /// Messages that the reactor handles before it processes this request
+    /// are not reported.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn set_relay_msg_hook(&self, hook: Box<dyn trace::RelayMsgHook>) -> Result<()> {
+        self.control
+            .unbounded_send(CtrlMsg::SetRelayMsgHook { hook })
+            .map_err(|_| Error::CircuitClosed)
+    }
+
     /// Return the number of hops in this circuit.
     ///
     /// NOTE: This function will currently return only the number of hops
@@ -1245,8 +1433,14 @@ impl StreamTarget {
     /// The StreamTarget will set the correct stream ID and pick the
     /// right hop, but will not validate that the message is well-formed
     /// or meaningful in context.
+    ///
+    /// Fails with [`Error::Memquota`] if the message would take this stream
+    /// (or its circuit) over its configured memory limit.
     pub(crate) async fn send(&mut self, msg: AnyRelayMsg) -> Result<()> {
-        self.tx.send(msg).await.map_err(|_| Error::CircuitClosed)?;
+        self.tx.send(msg).await.map_err(|e| match e {
+            mq_queue::SendError::Memquota(e) => Error::Memquota(e),
+            _ => Error::CircuitClosed,
+        })?;
         Ok(())
     }
 
@@ -1329,6 +1523,11 @@ impl StreamTarget {
         Ok(())
     }
 
+    /// Return the record of this stream's flow-control state.
+    pub(crate) fn flow_stats(&self) -> &FlowStatsRecorder {
+        &self.flow_stats
+    }
+
     /// Return a reference to the circuit that this `StreamTarget` is using.
     #[cfg(any(feature = "experimental-api", feature = "stream-ctrl"))]
     pub(crate) fn circuit(&self) -> &Arc<ClientCirc> {
@@ -1662,20 +1861,24 @@ mod test {
         rt: &R,
         chan: Arc<Channel>,
         next_msg_from: HopNum,
+    ) -> (Arc<ClientCirc>, CircuitRxSender) {
+        newcirc_with_account(rt, chan, next_msg_from, CircuitAccount::new_noop()).await
+    }
+
+    // Helper: like newcirc_ext, but accounting the circuit's memory to `account`
+    async fn newcirc_with_account<R: Runtime>(
+        rt: &R,
+        chan: Arc<Channel>,
+        next_msg_from: HopNum,
+        account: CircuitAccount,
     ) -> (Arc<ClientCirc>, CircuitRxSender) {
         let circid = CircId::new(128).unwrap();
         let (_created_send, created_recv) = oneshot::channel();
         let (circmsg_send, circmsg_recv) = fake_mpsc(64);
         let unique_id = UniqId::new(23, 17);
 
-        let (pending, reactor) = PendingClientCirc::new(
-            circid,
-            chan,
-            created_recv,
-            circmsg_recv,
-            unique_id,
-            CircuitAccount::new_noop(),
-        );
+        let (pending, reactor) =
+            PendingClientCirc::new(circid, chan, created_recv, circmsg_recv, unique_id, account);
 
         rt.spawn(async {
             let _ignore = reactor.run().await;
@@ -1743,6 +1946,129 @@ mod test {
         });
     }
 
+    /// Answer the next liveness probe sent on the channel `rx`.
+    ///
+    /// Panics if the next message sent on `rx` isn't a liveness probe.
+    async fn answer_probe(rx: &mut Receiver<AnyChanCell>, sink: &mut CircuitRxSender) {
+        let (_id, chmsg) = rx.next().await.unwrap().into_circid_and_msg();
+        let rmsg = match chmsg {
+            AnyChanMsg::Relay(r) => {
+                AnyRelayMsgOuter::decode_singleton(RelayCellFormat::V0, r.into_relay_body())
+                    .unwrap()
+            }
+            other => panic!("{:?}", other),
+        };
+        let (streamid, rmsg) = rmsg.into_streamid_and_msg();
+        let AnyRelayMsg::Resolve(_) = rmsg else {
+            panic!("unexpected message {rmsg:?}");
+        };
+
+        let mut resolved = relaymsg::Resolved::new_empty();
+        resolved.add_answer(
+            relaymsg::ResolvedVal::Ip(LIVENESS_PROBE_ADDR.parse().unwrap()),
+            0,
+        );
+        sink.send(rmsg_to_ccmsg(streamid, resolved.into()))
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn probe_liveness() {
+        tor_rtcompat::test_with_all_runtimes!(|rt| async move {
+            let (chan, mut rx, _sink) = working_fake_channel(&rt);
+            let (circ, mut sink) = newcirc(&rt, chan).await;
+
+            let (rtt, ()) = futures::join!(
+                circ.probe_liveness(2.into()),
+                answer_probe(&mut rx, &mut sink)
+            );
+            rtt.unwrap();
+
+            // A refusal shows that the hop is alive, too.
+            let refuse = async {
+                let (_id, chmsg) = rx.next().await.unwrap().into_circid_and_msg();
+                let AnyChanMsg::Relay(r) = chmsg else {
+                    panic!("{:?}", chmsg);
+                };
+                let rmsg =
+                    AnyRelayMsgOuter::decode_singleton(RelayCellFormat::V0, r.into_relay_body())
+                        .unwrap();
+                let end = relaymsg::End::new_with_reason(relaymsg::EndReason::EXITPOLICY);
+                sink.send(rmsg_to_ccmsg(rmsg.stream_id(), end.into()))
+                    .await
+                    .unwrap();
+            };
+            let (rtt, ()) = futures::join!(circ.probe_liveness(2.into()), refuse);
+            rtt.unwrap();
+
+            assert!(matches!(
+                circ.probe_liveness(7.into()).await,
+                Err(Error::NoSuchHop)
+            ));
+            assert!(!circ.is_closing());
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "hs-common")]
+    fn keepalive_onion_circuit() {
+        use crate::crypto::handshake::ShakeKeyGenerator;
+        use handshake::{HandshakeRole, RelayProtocol};
+
+        tor_rtcompat::test_with_all_runtimes!(|rt| async move {
+            let (chan, mut rx, _sink) = working_fake_channel(&rt);
+            let (circ, mut sink) = newcirc(&rt, chan).await;
+            circ.extend_virtual(
+                RelayProtocol::HsV3,
+                HandshakeRole::Initiator,
+                ShakeKeyGenerator::new(vec![7; 32].into()),
+                CircParameters::default(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(circ.n_hops(), 4);
+
+            // Virtual hops can't be probed.
+            assert!(circ.probe_liveness(3.into()).await.is_err());
+
+            // The keepalive probes the rendezvous point (hop 2),
+            // which answers, so the circuit stays open.
+            let keepalive = circ.keepalive(Duration::from_millis(10), Duration::from_secs(60));
+            let answer = async {
+                for _ in 0..3 {
+                    answer_probe(&mut rx, &mut sink).await;
+                }
+            };
+            futures::select! {
+                res = keepalive.fuse() => panic!("keepalive stopped: {res:?}"),
+                () = answer.fuse() => {}
+            }
+            assert!(!circ.is_closing());
+        });
+    }
+
+    #[test]
+    fn keepalive_unresponsive() {
+        tor_rtcompat::test_with_all_runtimes!(|rt| async move {
+            let (chan, _rx, _sink) = working_fake_channel(&rt);
+            let (circ, _send) = newcirc(&rt, chan).await;
+
+            // Nobody answers our probes.
+            let timeout = Duration::from_millis(50);
+            let outcome = circ.keepalive(Duration::from_millis(10), timeout).await;
+            assert!(matches!(outcome, Err(Error::CircuitUnresponsive(t)) if t == timeout));
+            while !circ.is_closing() {
+                rt.sleep(Duration::from_millis(10)).await;
+            }
+
+            // Once the circuit is closed, the keepalive stops.
+            circ.keepalive(Duration::from_millis(10), timeout)
+                .await
+                .unwrap();
+        });
+    }
+
     async fn test_extend<R: Runtime>(rt: &R, handshake_type: HandshakeType) {
         use crate::crypto::handshake::{ntor::NtorServer, ServerHandshake};
 
@@ -1750,6 +2076,7 @@ mod test {
         let (circ, mut sink) = newcirc(rt, chan).await;
         let circid = circ.peek_circid();
         let params = CircParameters::default();
+        let trace = record_relay_msgs(&circ);
 
         let extend_fut = async move {
             let target = example_target();
@@ -1813,6 +2140,17 @@ mod test {
         // Did we really add another hop?
         assert_eq!(circ.n_hops(), 4);
 
+        // Like C Tor, we send EXTEND2 to the last hop, in a RELAY_EARLY cell.
+        let trace = trace.lock().unwrap();
+        assert_transcript(
+            &trace,
+            &[
+                (OUT, 2, false, RelayCmd::EXTEND2),
+                (IN, 2, false, RelayCmd::EXTENDED2),
+            ],
+        );
+        assert!(trace[0].early);
+
         // Do the path accessors report a reasonable outcome?
         #[allow(deprecated)]
         {
@@ -1936,6 +2274,67 @@ mod test {
         });
     }
 
+    #[test]
+    #[cfg(feature = "experimental-udp")]
+    fn udp_stream() {
+        use tor_cell::relaycell::udp::AddressPort;
+
+        tor_rtcompat::test_with_all_runtimes!(|rt| async move {
+            let (chan, mut rx, _sink) = working_fake_channel(&rt);
+            let (circ, mut sink) = newcirc(&rt, chan).await;
+
+            let begin_and_send_fut = async move {
+                let stream = circ
+                    .begin_udp_stream("example.com", 443, None)
+                    .await
+                    .unwrap();
+                let (mut r, mut w) = stream.split();
+                assert!(w.send(b"").await.is_err());
+                w.send(b"ping").await.unwrap();
+                assert_eq!(r.recv().await.unwrap(), b"pong");
+                (r, w)
+            };
+            let reply_fut = async move {
+                // We've disabled encryption on this circuit, so we can just
+                // read the cells.
+                macro_rules! next_msg {
+                    () => {
+                        match rx.next().await.unwrap().into_circid_and_msg().1 {
+                            AnyChanMsg::Relay(r) => AnyRelayMsgOuter::decode_singleton(
+                                RelayCellFormat::V0,
+                                r.into_relay_body(),
+                            )
+                            .unwrap()
+                            .into_streamid_and_msg(),
+                            other => panic!("{:?}", other),
+                        }
+                    };
+                }
+
+                let (streamid, rmsg) = next_msg!();
+                assert!(matches!(rmsg, AnyRelayMsg::ConnectUdp(_)));
+                let addr = |a: &str, p| AddressPort::try_from((a, p)).unwrap();
+                let connected =
+                    relaymsg::ConnectedUdp::new(addr("192.0.2.1", 5000), addr("198.51.100.7", 443))
+                        .unwrap()
+                        .into();
+                sink.send(rmsg_to_ccmsg(streamid, connected)).await.unwrap();
+
+                let (streamid_2, rmsg) = next_msg!();
+                assert_eq!(streamid_2, streamid);
+                match rmsg {
+                    AnyRelayMsg::Datagram(d) => assert_eq!(d.as_ref(), b"ping"),
+                    other => panic!("{:?}", other),
+                }
+                let pong = relaymsg::Datagram::new(b"pong").unwrap().into();
+                sink.send(rmsg_to_ccmsg(streamid, pong)).await.unwrap();
+                sink
+            };
+
+            let (_streams, _sink) = futures::join!(begin_and_send_fut, reply_fut);
+        });
+    }
+
     #[test]
     fn begindir() {
         tor_rtcompat::test_with_all_runtimes!(|rt| async move {
@@ -2010,6 +2409,82 @@ mod test {
         });
     }
 
+    #[test]
+    fn stream_memquota_limit() {
+        tor_rtcompat::test_with_all_runtimes!(|rt| async move {
+            use crate::memquota::{ChannelAccount, SpecificAccount as _};
+            use tor_memquota::{Config, MemoryQuotaTracker};
+
+            let config = Config::builder()
+                .max(1024 * 1024 * 1024)
+                .max_per_stream(16384)
+                .build()
+                .unwrap();
+            let tracker = MemoryQuotaTracker::new(&rt, config).unwrap();
+            let chan_account = ChannelAccount::new(&tracker).unwrap();
+            let account = CircuitAccount::new(&chan_account).unwrap();
+
+            let (chan, mut rx, _sink) = working_fake_channel(&rt);
+            let (circ, mut sink) = newcirc_with_account(&rt, chan, 2.into(), account).await;
+
+            let stream_fut = async move {
+                let stream = circ
+                    .begin_stream("www.example.com", 80, None)
+                    .await
+                    .unwrap();
+                (stream, circ) // don't read from the stream
+            };
+            let handler_fut = async {
+                let (_, msg) = rx.next().await.unwrap().into_circid_and_msg();
+                let rmsg = match msg {
+                    AnyChanMsg::Relay(r) => {
+                        AnyRelayMsgOuter::decode_singleton(RelayCellFormat::V0, r.into_relay_body())
+                            .unwrap()
+                    }
+                    other => panic!("{:?}", other),
+                };
+                let (streamid, rmsg) = rmsg.into_streamid_and_msg();
+                assert_eq!(rmsg.cmd(), RelayCmd::BEGIN);
+
+                let connected =
+                    relaymsg::Connected::new_with_addr("10.0.0.1".parse().unwrap(), 1234).into();
+                sink.send(rmsg_to_ccmsg(streamid, connected)).await.unwrap();
+
+                // Send more data than the stream may queue, without it being read.
+                for _ in 0..100 {
+                    let data = relaymsg::Data::new(&[0x42; 400]).unwrap().into();
+                    sink.send(rmsg_to_ccmsg(streamid, data)).await.unwrap();
+                }
+
+                // We close the stream, rather than queueing it all.
+                loop {
+                    let (_, msg) = rx.next().await.unwrap().into_circid_and_msg();
+                    let rmsg = match msg {
+                        AnyChanMsg::Relay(r) => AnyRelayMsgOuter::decode_singleton(
+                            RelayCellFormat::V0,
+                            r.into_relay_body(),
+                        )
+                        .unwrap(),
+                        other => panic!("{:?}", other),
+                    };
+                    match rmsg.into_streamid_and_msg() {
+                        (_, AnyRelayMsg::Data(_)) => continue,
+                        (id, AnyRelayMsg::End(end)) => {
+                            assert_eq!(id, streamid);
+                            assert_eq!(end.reason(), relaymsg::EndReason::RESOURCELIMIT);
+                            break;
+                        }
+                        (_, other) => panic!("{:?}", other),
+                    }
+                }
+
+                (rx, sink) // keep these alive or the reactor will exit.
+            };
+
+            let ((_stream, _circ), (_rx, _sink)) = futures::join!(stream_fut, handler_fut);
+        });
+    }
+
     // Test: close a stream, either by dropping it or by calling AsyncWriteExt::close.
     fn close_stream_helper(by_drop: bool) {
         tor_rtcompat::test_with_all_runtimes!(|rt| async move {
@@ -2238,6 +2713,32 @@ mod test {
         });
     }
 
+    #[cfg(feature = "stream-ctrl")]
+    #[test]
+    fn stream_flow_stats() {
+        tor_rtmock::MockRuntime::test_with_various(|rt| async move {
+            let (_circ, stream, mut sink, streamid, cells_received, _rx, _sink2) =
+                setup_incoming_sendme_case(&rt, 300 * 498 + 3).await;
+            assert_eq!(cells_received, 301);
+
+            let stats = stream.ctrl().flow_stats();
+            assert_eq!(stats.send_window, 500 - 301);
+            assert_eq!(stats.recv_window, 500);
+            assert_eq!(stats.write_buffered_bytes, 0);
+            assert_eq!(stats.read_buffered_bytes, 0);
+            assert!(stats.blocked_on_sendme.is_none());
+            assert_eq!(stats.n_blocked_on_sendme, 0);
+
+            // A stream-level sendme opens the window again.
+            let s_sendme = relaymsg::Sendme::new_empty().into();
+            sink.send(rmsg_to_ccmsg(streamid, s_sendme)).await.unwrap();
+            rt.advance_until_stalled().await;
+
+            let stats = stream.ctrl().flow_stats();
+            assert_eq!(stats.send_window, 500 - 301 + 50);
+        });
+    }
+
     #[test]
     fn invalid_circ_sendme() {
         tor_rtcompat::test_with_all_runtimes!(|rt| async move {
@@ -2673,4 +3174,329 @@ mod test {
             let (_circ, _send) = futures::join!(simulate_service, simulate_client);
         });
     }
+
+    // Helpers for checking our relay message transcripts against the
+    // behavior of C Tor.
+    //
+    // Each transcript entry is (direction, hop, is on a stream, command).
+
+    use trace::{RelayMsgDirection, RelayMsgTrace};
+    const OUT: RelayMsgDirection = RelayMsgDirection::Outbound;
+    const IN: RelayMsgDirection = RelayMsgDirection::Inbound;
+
+    /// Install a hook on `circ` that records every relay message.
+    fn record_relay_msgs(circ: &ClientCirc) -> Arc<Mutex<Vec<RelayMsgTrace>>> {
+        let log = Arc::new(Mutex::new(vec![]));
+        let log2 = Arc::clone(&log);
+        circ.set_relay_msg_hook(Box::new(move |msg: &RelayMsgTrace| {
+            log2.lock().unwrap().push(msg.clone());
+        }))
+        .unwrap();
+        log
+    }
+
+    /// Assert that `log` matches `expected`.
+    fn assert_transcript(
+        log: &[RelayMsgTrace],
+        expected: &[(RelayMsgDirection, u8, bool, RelayCmd)],
+    ) {
+        let found: Vec<_> = log
+            .iter()
+            .map(|m| (m.direction, u8::from(m.hop), m.stream_id.is_some(), m.cmd))
+            .collect();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn transcript_circ_sendme() {
+        tor_rtcompat::test_with_all_runtimes!(|rt| async move {
+            let (chan, mut rx, _sink) = working_fake_channel(&rt);
+            let (circ, mut sink) = newcirc(&rt, chan).await;
+            let trace = record_relay_msgs(&circ);
+
+            let begin_fut = async move {
+                let stream = circ
+                    .begin_stream("www.example.com", 443, None)
+                    .await
+                    .unwrap();
+                (circ, stream)
+            };
+            let relay_fut = async move {
+                let (_id, chmsg) = rx.next().await.unwrap().into_circid_and_msg();
+                let rmsg = match chmsg {
+                    AnyChanMsg::Relay(r) => {
+                        AnyRelayMsgOuter::decode_singleton(RelayCellFormat::V0, r.into_relay_body())
+                            .unwrap()
+                    }
+                    other => panic!("{:?}", other),
+                };
+                let (streamid, _) = rmsg.into_streamid_and_msg();
+                let connected = relaymsg::Connected::new_empty().into();
+                sink.send(rmsg_to_ccmsg(streamid, connected)).await.unwrap();
+                (rx, sink, streamid)
+            };
+            let ((_circ, _stream), (mut rx, mut sink, streamid)) =
+                futures::join!(begin_fut, relay_fut);
+
+            // Send 100 DATA cells, which nobody reads.
+            for _ in 0..100 {
+                let data = relaymsg::Data::new(&[0x55; 400]).unwrap().into();
+                sink.send(rmsg_to_ccmsg(streamid, data)).await.unwrap();
+            }
+
+            // After the 100th cell, C Tor sends an authenticated circuit-level SENDME.
+            let (_id, chmsg) = rx.next().await.unwrap().into_circid_and_msg();
+            let rmsg = match chmsg {
+                AnyChanMsg::Relay(r) => {
+                    AnyRelayMsgOuter::decode_singleton(RelayCellFormat::V0, r.into_relay_body())
+                        .unwrap()
+                }
+                other => panic!("{:?}", other),
+            };
+            match rmsg.into_streamid_and_msg() {
+                (None, AnyRelayMsg::Sendme(sendme)) => assert!(sendme.into_tag().is_some()),
+                other => panic!("{:?}", other),
+            }
+
+            let mut expected = vec![
+                (OUT, 2, true, RelayCmd::BEGIN),
+                (IN, 2, true, RelayCmd::CONNECTED),
+            ];
+            expected.extend([(IN, 2, true, RelayCmd::DATA); 100]);
+            // No stream-level SENDME yet, since the application hasn't read anything.
+            expected.push((OUT, 2, false, RelayCmd::SENDME));
+            assert_transcript(&trace.lock().unwrap(), &expected);
+        });
+    }
+
+    /// A line of a transcript of C Tor's behavior:
+    /// (direction, hop, is on a stream, command, is sent in a RELAY_EARLY cell).
+    type CtorTranscriptLine = (RelayMsgDirection, u8, bool, RelayCmd, bool);
+
+    /// Parse a transcript of C Tor's behavior, in the format of the
+    /// `testdata/ctor-transcript-*.txt` files.
+    fn parse_transcript(text: &str) -> Vec<CtorTranscriptLine> {
+        let mut transcript = vec![];
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let words: Vec<_> = line.split_whitespace().collect();
+            let direction = match words[0] {
+                "out" => OUT,
+                "in" => IN,
+                other => panic!("bad direction {:?}", other),
+            };
+            let hop: u8 = words[1].parse().unwrap();
+            let on_stream = match words[2] {
+                "stream" => true,
+                "circ" => false,
+                other => panic!("bad target {:?}", other),
+            };
+            let cmd = (0..=u8::MAX)
+                .map(RelayCmd::from)
+                .find(|cmd| cmd.to_string() == words[3])
+                .unwrap_or_else(|| panic!("bad command {:?}", words[3]));
+            let mut count = 1;
+            let mut early = false;
+            for word in &words[4..] {
+                match (*word, word.strip_prefix('x')) {
+                    ("early", _) => early = true,
+                    (_, Some(n)) => count = n.parse().unwrap(),
+                    (other, None) => panic!("bad annotation {:?}", other),
+                }
+            }
+            transcript.extend(std::iter::repeat_n(
+                (direction, hop, on_stream, cmd, early),
+                count,
+            ));
+        }
+        transcript
+    }
+
+    /// Assert that `log` matches the C Tor transcript `expected`,
+    /// including which messages were sent in RELAY_EARLY cells.
+    fn assert_ctor_transcript(log: &[RelayMsgTrace], expected: &[CtorTranscriptLine]) {
+        let without_early: Vec<_> = expected
+            .iter()
+            .map(|&(direction, hop, on_stream, cmd, _)| (direction, hop, on_stream, cmd))
+            .collect();
+        assert_transcript(log, &without_early);
+        let found_early: Vec<_> = log.iter().map(|m| m.early).collect();
+        let expected_early: Vec<_> = expected.iter().map(|&(.., early)| early).collect();
+        assert_eq!(found_early, expected_early);
+    }
+
+    #[test]
+    fn transcript_ctor_stream() {
+        tor_rtcompat::test_with_all_runtimes!(|rt| async move {
+            let expected = parse_transcript(include_str!("../testdata/ctor-transcript-stream.txt"));
+
+            let (chan, mut rx, _sink) = working_fake_channel(&rt);
+            let (circ, mut sink) = newcirc(&rt, chan).await;
+            let trace = record_relay_msgs(&circ);
+
+            let client_fut = async move {
+                let mut stream = circ
+                    .begin_stream("www.example.com", 443, None)
+                    .await
+                    .unwrap();
+                let mut data = vec![];
+                stream.read_to_end(&mut data).await.unwrap();
+                (circ, data)
+            };
+            // Play the part of the relays, replaying the inbound messages
+            // and checking the outbound ones as they arrive.
+            let relay_fut = async move {
+                let mut streamid = None;
+                for &(direction, _hop, on_stream, cmd, _early) in &expected {
+                    if direction == OUT {
+                        let (_id, chmsg) = rx.next().await.unwrap().into_circid_and_msg();
+                        let rmsg = match chmsg {
+                            AnyChanMsg::Relay(r) => AnyRelayMsgOuter::decode_singleton(
+                                RelayCellFormat::V0,
+                                r.into_relay_body(),
+                            )
+                            .unwrap(),
+                            other => panic!("{:?}", other),
+                        };
+                        assert_eq!(rmsg.cmd(), cmd);
+                        let (id, _) = rmsg.into_streamid_and_msg();
+                        assert_eq!(id.is_some(), on_stream);
+                        streamid = streamid.or(id);
+                        continue;
+                    }
+                    let msg: AnyRelayMsg = match cmd {
+                        RelayCmd::CONNECTED => relaymsg::Connected::new_empty().into(),
+                        RelayCmd::DATA => relaymsg::Data::new(&[0x55; 400]).unwrap().into(),
+                        RelayCmd::END => {
+                            relaymsg::End::new_with_reason(relaymsg::EndReason::DONE).into()
+                        }
+                        other => panic!("can't replay {}", other),
+                    };
+                    let id = if on_stream { streamid } else { None };
+                    sink.send(rmsg_to_ccmsg(id, msg)).await.unwrap();
+                }
+                (rx, sink, expected)
+            };
+            let ((_circ, data), (_rx, _sink, expected)) = futures::join!(client_fut, relay_fut);
+
+            assert_eq!(data.len(), 50 * 400);
+            assert_ctor_transcript(&trace.lock().unwrap(), &expected);
+        });
+    }
+
+    #[test]
+    fn transcript_ctor_extend() {
+        use crate::crypto::handshake::{ntor::NtorServer, ServerHandshake};
+
+        tor_rtcompat::test_with_all_runtimes!(|rt| async move {
+            let expected = parse_transcript(include_str!("../testdata/ctor-transcript-extend.txt"));
+
+            let (chan, mut rx, _sink) = working_fake_channel(&rt);
+            let (circ, mut sink) = newcirc(&rt, chan).await;
+            let trace = record_relay_msgs(&circ);
+
+            let extend_fut = async move {
+                circ.extend_ntor(&example_target(), &CircParameters::default())
+                    .await
+                    .unwrap();
+                circ
+            };
+            // Play the part of the relays, replaying the inbound messages
+            // and checking the outbound ones as they arrive.
+            let relay_fut = async move {
+                let mut reply = None;
+                for &(direction, _hop, on_stream, cmd, early) in &expected {
+                    if direction == OUT {
+                        let (_id, chmsg) = rx.next().await.unwrap().into_circid_and_msg();
+                        let body = match chmsg {
+                            AnyChanMsg::Relay(r) if !early => r.into_relay_body(),
+                            AnyChanMsg::RelayEarly(r) if early => r.into_relay_body(),
+                            other => panic!("{:?}", other),
+                        };
+                        let rmsg =
+                            AnyRelayMsgOuter::decode_singleton(RelayCellFormat::V0, body).unwrap();
+                        assert_eq!(rmsg.cmd(), cmd);
+                        let (id, msg) = rmsg.into_streamid_and_msg();
+                        assert_eq!(id.is_some(), on_stream);
+                        if let AnyRelayMsg::Extend2(e2) = msg {
+                            let (_keygen, server_reply) = NtorServer::server(
+                                &mut testing_rng(),
+                                &mut |_: &()| Some(()),
+                                &[example_ntor_key()],
+                                e2.handshake(),
+                            )
+                            .unwrap();
+                            reply = Some(server_reply);
+                        }
+                        continue;
+                    }
+                    let msg: AnyRelayMsg = match cmd {
+                        RelayCmd::EXTENDED2 => {
+                            relaymsg::Extended2::new(reply.take().unwrap()).into()
+                        }
+                        other => panic!("can't replay {}", other),
+                    };
+                    sink.send(rmsg_to_ccmsg(None, msg)).await.unwrap();
+                }
+                (rx, sink, expected)
+            };
+            let (circ, (_rx, _sink, expected)) = futures::join!(extend_fut, relay_fut);
+
+            assert_eq!(circ.n_hops(), 4);
+            assert_ctor_transcript(&trace.lock().unwrap(), &expected);
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "send-control-msg")]
+    fn transcript_establish_rendezvous() {
+        use tor_cell::relaycell::msg::Unrecognized;
+
+        /// A handler that finishes the conversation at the first reply.
+        struct Finish(Option<oneshot::Sender<RelayCmd>>);
+        impl MsgHandler for Finish {
+            fn handle_msg(
+                &mut self,
+                _conversation: ConversationInHandler<'_, '_, '_>,
+                msg: AnyRelayMsg,
+            ) -> Result<MetaCellDisposition> {
+                if let Some(tx) = self.0.take() {
+                    let _ = tx.send(msg.cmd());
+                }
+                Ok(MetaCellDisposition::ConversationFinished)
+            }
+        }
+
+        tor_rtcompat::test_with_all_runtimes!(|rt| async move {
+            let (chan, mut rx, _sink) = working_fake_channel(&rt);
+            let (circ, mut sink) = newcirc(&rt, chan).await;
+            let trace = record_relay_msgs(&circ);
+
+            let (tx, done) = oneshot::channel();
+            let cookie = Unrecognized::new(RelayCmd::ESTABLISH_RENDEZVOUS, [7_u8; 20]);
+            let _conversation = circ
+                .start_conversation(Some(cookie.into()), Finish(Some(tx)), 2.into())
+                .await
+                .unwrap();
+
+            let (_id, chmsg) = rx.next().await.unwrap().into_circid_and_msg();
+            assert!(matches!(chmsg, AnyChanMsg::Relay(_)));
+            let established = Unrecognized::new(RelayCmd::RENDEZVOUS_ESTABLISHED, []).into();
+            sink.send(rmsg_to_ccmsg(None, established)).await.unwrap();
+            assert_eq!(done.await.unwrap(), RelayCmd::RENDEZVOUS_ESTABLISHED);
+
+            assert_transcript(
+                &trace.lock().unwrap(),
+                &[
+                    (OUT, 2, false, RelayCmd::ESTABLISH_RENDEZVOUS),
+                    (IN, 2, false, RelayCmd::RENDEZVOUS_ESTABLISHED),
+                ],
+            );
+            assert!(!trace.lock().unwrap()[0].early);
+            drop((rx, sink));
+        });
+    }
 }
@@ -11,10 +11,14 @@
 // that can wait IMO until we have a second circuit creation mechanism for use
 // with onion services.
 
+#[cfg(feature = "cell-crypto-offload")]
+use tor_cell::relaycell::RelayCellFormatTrait;
 use tor_cell::relaycell::{RelayCellFormat, RelayCellFormatV0};
 use tor_error::internal;
 
 use crate::crypto::binding::CircuitBinding;
+#[cfg(feature = "cell-crypto-offload")]
+use crate::crypto::cell::offload::{CellCryptoEngine, OffloadedCryptStatePair, Tor1Algorithms};
 #[cfg(feature = "hs-common")]
 use crate::crypto::cell::Tor1Hsv3RelayCrypto;
 use crate::crypto::cell::{
@@ -112,6 +116,40 @@ impl RelayCryptLayerProtocol {
         }
     }
 
+    /// Construct the cell-crypto layers that are needed for a given set of
+    /// circuit hop parameters, using `engine` to do the cryptography.
+    ///
+    /// If `engine` doesn't implement the algorithms we need,
+    /// we use our own implementation instead.
+    #[cfg(feature = "cell-crypto-offload")]
+    pub(crate) fn construct_offloaded_layers(
+        self,
+        role: HandshakeRole,
+        keygen: impl KeyGenerator,
+        engine: &dyn CellCryptoEngine,
+    ) -> Result<BoxedClientLayer> {
+        use RelayCellFormat::*;
+        use RelayCryptLayerProtocol::*;
+        use Tor1Algorithms::*;
+
+        match self {
+            Tor1(V0) => {
+                construct_offloaded::<Tor1RelayCrypto<RelayCellFormatV0>, _, RelayCellFormatV0>(
+                    keygen, role, Aes128Sha1, engine,
+                )
+            }
+            Tor1(_) => Err(internal!("protocol not implemented").into()),
+            #[cfg(feature = "hs-common")]
+            HsV3(V0) => construct_offloaded::<
+                Tor1Hsv3RelayCrypto<RelayCellFormatV0>,
+                _,
+                RelayCellFormatV0,
+            >(keygen, role, Aes256Sha3_256, engine),
+            #[cfg(feature = "hs-common")]
+            HsV3(_) => Err(internal!("protocol not implemented").into()),
+        }
+    }
+
     /// Return the cell format used by this protocol.
     pub(crate) fn relay_cell_format(&self) -> RelayCellFormat {
         match self {
@@ -130,13 +168,53 @@ where
     F: OutboundClientLayer + InboundClientLayer + Send + 'static,
 {
     let layer = L::construct(keygen)?;
-    let (mut fwd, mut back, binding) = layer.split();
+    let (fwd, back, binding) = layer.split();
+    Ok(boxed(fwd, back, binding, role))
+}
+
+/// Helper: Construct a BoxedClientLayer for a layer type L whose inbound and outbound
+/// cryptographic states are the same type, using `engine` if it supports `algs`.
+///
+/// The cell format `RCF` must be the one that `L` uses.
+#[cfg(feature = "cell-crypto-offload")]
+fn construct_offloaded<L, F, RCF>(
+    keygen: impl KeyGenerator,
+    role: HandshakeRole,
+    algs: Tor1Algorithms,
+    engine: &dyn CellCryptoEngine,
+) -> Result<BoxedClientLayer>
+where
+    L: CryptInit + ClientLayer<F, F>,
+    F: OutboundClientLayer + InboundClientLayer + Send + 'static,
+    RCF: RelayCellFormatTrait + Send + 'static,
+{
+    let seed = keygen.expand(L::seed_len())?;
+    match OffloadedCryptStatePair::<RCF>::initialize(algs, &seed, engine)? {
+        Some(pair) => Ok(boxed(pair.fwd, pair.back, pair.binding, role)),
+        None => {
+            let (fwd, back, binding) = L::initialize(&seed)?.split();
+            Ok(boxed(fwd, back, binding, role))
+        }
+    }
+}
+
+/// Helper: Box up the layers for a single hop,
+/// swapping them if we are the responder.
+fn boxed<F>(
+    mut fwd: F,
+    mut back: F,
+    binding: CircuitBinding,
+    role: HandshakeRole,
+) -> BoxedClientLayer
+where
+    F: OutboundClientLayer + InboundClientLayer + Send + 'static,
+{
     if role == HandshakeRole::Responder {
         std::mem::swap(&mut fwd, &mut back);
     }
-    Ok(BoxedClientLayer {
+    BoxedClientLayer {
         fwd: Box::new(fwd),
         back: Box::new(back),
         binding: Some(binding),
-    })
+    }
 }
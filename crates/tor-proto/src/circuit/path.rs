@@ -127,4 +127,25 @@ impl Path {
         let idx: u8 = n.checked_sub(1)?.try_into().ok()?;
         Some(idx.into())
     }
+
+    /// Return the index of the last hop on this path that is a relay,
+    /// rather than a [virtual](HopDetail::Virtual) hop,
+    /// or `None` if there is no such hop.
+    pub(super) fn last_relay_hop_num(&self) -> Option<HopNum> {
+        let idx = self
+            .hops
+            .iter()
+            .rposition(|ent| matches!(ent.inner, HopDetail::Relay(_)))?;
+        let idx: u8 = idx.try_into().ok()?;
+        Some(idx.into())
+    }
+
+    /// Return true if `hop` is a [virtual](HopDetail::Virtual) hop on this path.
+    pub(super) fn is_virtual_hop(&self, hop: HopNum) -> bool {
+        match self.hops.get(usize::from(hop)).map(|ent| &ent.inner) {
+            #[cfg(feature = "hs-common")]
+            Some(HopDetail::Virtual) => true,
+            _ => false,
+        }
+    }
 }
@@ -30,27 +30,25 @@ use crate::circuit::{
 };
 use crate::crypto::binding::CircuitBinding;
 use crate::crypto::cell::{
-    ClientLayer, CryptInit, HopNum, InboundClientCrypt, InboundClientLayer, OutboundClientCrypt,
-    OutboundClientLayer, RelayCellBody, Tor1RelayCrypto,
+    HopNum, InboundClientCrypt, InboundClientLayer, OutboundClientCrypt, OutboundClientLayer,
+    RelayCellBody,
 };
 use crate::crypto::handshake::fast::CreateFastClient;
 #[cfg(feature = "ntor_v3")]
 use crate::crypto::handshake::ntor_v3::{NtorV3Client, NtorV3PublicKey};
 use crate::memquota::{CircuitAccount, SpecificAccount as _, StreamAccount};
-use crate::stream::{AnyCmdChecker, StreamStatus};
+use crate::stream::{AnyCmdChecker, FlowStatsRecorder, StreamStatus};
 use crate::util::err::{ChannelClosed, ReactorError};
 use crate::util::sometimes_unbounded_sink::SometimesUnboundedSink;
 use crate::util::SinkExt as _;
 use crate::{Error, Result};
 use std::borrow::Borrow;
-use std::marker::PhantomData;
 use std::mem::size_of;
 use std::pin::Pin;
 use tor_cell::chancell::msg::{AnyChanMsg, HandshakeType, Relay};
-use tor_cell::relaycell::msg::{AnyRelayMsg, End, Sendme};
+use tor_cell::relaycell::msg::{AnyRelayMsg, End, EndReason, Sendme};
 use tor_cell::relaycell::{
-    AnyRelayMsgOuter, RelayCellDecoder, RelayCellFormat, RelayCellFormatTrait, RelayCellFormatV0,
-    RelayCmd, StreamId, UnparsedRelayMsg,
+    AnyRelayMsgOuter, RelayCellDecoder, RelayCellFormat, RelayCmd, StreamId, UnparsedRelayMsg,
 };
 use tor_error::internal;
 #[cfg(feature = "hs-service")]
@@ -72,12 +70,14 @@ use crate::circuit::path;
 #[cfg(test)]
 use crate::circuit::sendme::CircTag;
 use crate::circuit::sendme::StreamSendWindow;
+#[cfg(any(test, feature = "testing"))]
+use crate::circuit::trace::{RelayMsgDirection, RelayMsgHook, RelayMsgTrace};
 use crate::circuit::{StreamMpscReceiver, StreamMpscSender};
 use crate::crypto::handshake::ntor::{NtorClient, NtorPublicKey};
 use crate::crypto::handshake::{ClientHandshake, KeyGenerator};
 use derive_deftly::Deftly;
 use safelog::sensitive as sv;
-use tor_async_utils::{SinkTrySend as _, SinkTrySendError as _};
+use tor_async_utils::{ErasedSinkTrySendError, SinkTrySend as _, SinkTrySendError as _};
 use tor_cell::chancell::{self, BoxedCellBody, ChanMsg};
 use tor_cell::chancell::{AnyChanCell, CircId};
 use tor_cell::relaycell::extend::NtorV3Extension;
@@ -235,6 +235,10 @@ pub(super) enum CtrlMsg {
         done: ReactorResultChannel<StreamId>,
         /// A `CmdChecker` to keep track of which message types are acceptable.
         cmd_checker: AnyCmdChecker,
+        /// The initial send window of the stream.
+        send_window: u16,
+        /// A record of the stream's flow-control state, to keep up to date.
+        flow_stats: FlowStatsRecorder,
     },
     /// Close the specified pending incoming stream, sending the provided END message.
     ///
@@ -305,6 +309,13 @@ pub(super) enum CtrlMsg {
     },
     /// Shut down the reactor.
     Shutdown,
+    /// Install a hook to observe relay messages on this circuit.
+    #[cfg(any(test, feature = "testing"))]
+    SetRelayMsgHook {
+        /// The hook to install.
+        #[educe(Debug(ignore))]
+        hook: Box<dyn RelayMsgHook>,
+    },
     /// (tests only) Add a hop to the list of hops on this circuit, with dummy cryptography.
     #[cfg(test)]
     AddFakeHop {
@@ -433,7 +444,7 @@ pub(super) enum MetaCellDisposition {
 ///
 /// Yes, I know having trait bounds on structs is bad, but in this case it's necessary
 /// since we want to be able to use `H::KeyType`.
-struct CircuitExtender<H, L, FWD, REV>
+struct CircuitExtender<H>
 where
     H: ClientHandshake,
 {
@@ -449,25 +460,15 @@ where
     unique_id: UniqId,
     /// The hop we're expecting the EXTENDED2 cell to come back from.
     expected_hop: HopNum,
-    /// The relay cell format we intend to use for this hop.
-    relay_cell_format: RelayCellFormat,
+    /// The relay cell crypto protocol (and cell format) we intend to use for this hop.
+    protocol: RelayCryptLayerProtocol,
     /// A oneshot channel that we should inform when we are done with this extend operation.
     operation_finished: Option<oneshot::Sender<Result<()>>>,
-    /// `PhantomData` used to make the other type parameters required for a circuit extension
-    /// part of the `struct`, instead of having them be provided during a function call.
-    ///
-    /// This is done this way so we can implement `MetaCellHandler` for this type, which
-    /// doesn't include any generic type parameters; we need them to be part of the type
-    /// so we know what they are for that `impl` block.
-    phantom: PhantomData<(L, FWD, REV)>,
 }
-impl<H, L, FWD, REV> CircuitExtender<H, L, FWD, REV>
+impl<H> CircuitExtender<H>
 where
     H: ClientHandshake + HandshakeAuxDataHandler,
     H::KeyGen: KeyGenerator,
-    L: CryptInit + ClientLayer<FWD, REV>,
-    FWD: OutboundClientLayer + 'static + Send,
-    REV: InboundClientLayer + 'static + Send,
 {
     /// Start extending a circuit, sending the necessary EXTEND cell and returning a
     /// new `CircuitExtender` to be called when the reply arrives.
@@ -481,7 +482,7 @@ where
     #[allow(clippy::blocks_in_conditions)]
     fn begin(
         cx: &mut Context<'_>,
-        relay_cell_format: RelayCellFormat,
+        protocol: RelayCryptLayerProtocol,
         peer_id: OwnedChanTarget,
         handshake_id: HandshakeType,
         key: &H::KeyType,
@@ -519,15 +520,14 @@ where
             trace!("{}: waiting for EXTENDED2 cell", unique_id);
             // ... and now we wait for a response.
 
-            Ok::<CircuitExtender<_, _, _, _>, Error>(Self {
+            Ok::<CircuitExtender<_>, Error>(Self {
                 peer_id,
                 state: Some(state),
                 params,
                 unique_id,
                 expected_hop: hop,
                 operation_finished: None,
-                phantom: Default::default(),
-                relay_cell_format,
+                protocol,
             })
         })() {
             Ok(mut result) => {
@@ -574,32 +574,29 @@ where
         // requested extensions have been acknowledged.
         H::handle_server_aux_data(reactor, &self.params, &server_aux_data)?;
 
-        let layer = L::construct(keygen)?;
+        let BoxedClientLayer { fwd, back, binding } =
+            reactor.construct_layers(self.protocol, HandshakeRole::Initiator, keygen)?;
 
         trace!("{}: Handshake complete; circuit extended.", self.unique_id);
 
         // If we get here, it succeeded.  Add a new hop to the circuit.
-        let (layer_fwd, layer_back, binding) = layer.split();
         reactor.add_hop(
-            self.relay_cell_format,
+            self.protocol.relay_cell_format(),
             path::HopDetail::Relay(self.peer_id.clone()),
-            Box::new(layer_fwd),
-            Box::new(layer_back),
-            Some(binding),
+            fwd,
+            back,
+            binding,
             &self.params,
         );
         Ok(MetaCellDisposition::ConversationFinished)
     }
 }
 
-impl<H, L, FWD, REV> MetaCellHandler for CircuitExtender<H, L, FWD, REV>
+impl<H> MetaCellHandler for CircuitExtender<H>
 where
     H: ClientHandshake + HandshakeAuxDataHandler,
     H::StateType: Send,
     H::KeyGen: KeyGenerator,
-    L: CryptInit + ClientLayer<FWD, REV> + Send,
-    FWD: OutboundClientLayer + 'static + Send,
-    REV: InboundClientLayer + 'static + Send,
 {
     fn expected_hop(&self) -> HopNum {
         self.expected_hop
@@ -739,6 +736,9 @@ pub struct Reactor {
     /// Memory quota account
     #[allow(dead_code)] // Partly here to keep it alive as long as the circuit
     memquota: CircuitAccount,
+    /// An observer for every relay message on this circuit, if one is installed.
+    #[cfg(any(test, feature = "testing"))]
+    relay_msg_hook: Option<Box<dyn RelayMsgHook>>,
 }
 
 /// Information about an incoming stream request.
@@ -767,6 +767,9 @@ pub(super) struct StreamReqInfo {
     /// The memory quota account to be used for this stream
     #[deftly(has_memory_cost(indirect_size = "0"))] // estimate (it contains an Arc)
     pub(super) memquota: StreamAccount,
+    /// A record of the stream's flow-control state
+    #[deftly(has_memory_cost(indirect_size = "0"))] // estimate (it contains an Arc)
+    pub(super) flow_stats: FlowStatsRecorder,
 }
 
 /// Data required for handling an incoming stream request.
@@ -830,6 +833,8 @@ impl Reactor {
             meta_handler: None,
             #[cfg(feature = "hs-service")]
             incoming_stream_req_handler: None,
+            #[cfg(any(test, feature = "testing"))]
+            relay_msg_hook: None,
             mutable: mutable.clone(),
             memquota,
         };
@@ -1099,6 +1104,22 @@ impl Reactor {
         let _ = done.send(Ok(()));
     }
 
+    /// Construct the cell-crypto layers for a new hop on this circuit.
+    ///
+    /// If our channel has a cell crypto engine, we use it.
+    fn construct_layers(
+        &self,
+        protocol: RelayCryptLayerProtocol,
+        role: HandshakeRole,
+        keygen: impl KeyGenerator,
+    ) -> Result<BoxedClientLayer> {
+        #[cfg(feature = "cell-crypto-offload")]
+        if let Some(engine) = self.channel.cell_crypto_engine() {
+            return protocol.construct_offloaded_layers(role, keygen, engine.as_ref());
+        }
+        protocol.construct_layers(role, keygen)
+    }
+
     /// Helper: create the first hop of a circuit.
     ///
     /// This is parameterized not just on the RNG, but a wrapper object to
@@ -1148,7 +1169,7 @@ impl Reactor {
 
         let relay_cell_format = cell_protocol.relay_cell_format();
         let BoxedClientLayer { fwd, back, binding } =
-            cell_protocol.construct_layers(HandshakeRole::Initiator, keygen)?;
+            self.construct_layers(cell_protocol, HandshakeRole::Initiator, keygen)?;
 
         trace!("{}: Handshake complete; circuit created.", self.unique_id);
 
@@ -1439,7 +1460,9 @@ impl Reactor {
         early: bool,
         msg: AnyRelayMsgOuter,
     ) -> Result<()> {
-        let c_t_w = sendme::cmd_counts_towards_windows(msg.cmd());
+        let cmd = msg.cmd();
+        let c_t_w = sendme::cmd_counts_towards_windows(cmd);
+        let circ_c_t_w = sendme::cmd_counts_towards_circ_windows(cmd);
         let stream_id = msg.stream_id();
         let hop_num = Into::<usize>::into(hop);
         let circhop = &mut self.hops[hop_num];
@@ -1474,12 +1497,29 @@ impl Reactor {
         };
         // If the cell counted towards our sendme window, decrement
         // that window, and maybe remember the authentication tag.
-        if c_t_w {
+        if circ_c_t_w {
             circhop.sendwindow.take(tag)?;
         }
+        #[cfg(any(test, feature = "testing"))]
+        self.trace_relay_msg(RelayMsgDirection::Outbound, hop, stream_id, cmd, early);
         self.send_msg_direct(cx, msg)
     }
 
+    /// Report a relay message to our [`RelayMsgHook`], if we have one.
+    #[cfg(any(test, feature = "testing"))]
+    fn trace_relay_msg(
+        &mut self,
+        direction: RelayMsgDirection,
+        hop: HopNum,
+        stream_id: Option<StreamId>,
+        cmd: RelayCmd,
+        early: bool,
+    ) {
+        if let Some(hook) = self.relay_msg_hook.as_mut() {
+            hook.observe(&RelayMsgTrace::new(direction, hop, stream_id, cmd, early));
+        }
+    }
+
     /// Try to install a given meta-cell handler to receive any unusual cells on
     /// this circuit, along with a result channel to notify on completion.
     fn set_meta_handler(&mut self, handler: Box<dyn MetaCellHandler + Send>) -> Result<()> {
@@ -1525,12 +1565,11 @@ impl Reactor {
                 done,
             } => {
                 // ntor handshake only supports V0.
-                /// Local type alias to ensure consistency below.
-                type Rcf = RelayCellFormatV0;
+                let protocol = RelayCryptLayerProtocol::Tor1(RelayCellFormat::V0);
 
-                let extender = CircuitExtender::<NtorClient, Tor1RelayCrypto<Rcf>, _, _>::begin(
+                let extender = CircuitExtender::<NtorClient>::begin(
                     cx,
-                    Rcf::FORMAT,
+                    protocol,
                     peer_id,
                     HandshakeType::NTOR,
                     &public_key,
@@ -1551,15 +1590,14 @@ impl Reactor {
                 done,
             } => {
                 // TODO #1067: support negotiating other formats.
-                /// Local type alias to ensure consistency below.
-                type Rcf = RelayCellFormatV0;
+                let protocol = RelayCryptLayerProtocol::Tor1(RelayCellFormat::V0);
 
                 // TODO: Set extensions, e.g. based on `params`.
                 let client_extensions = [];
 
-                let extender = CircuitExtender::<NtorV3Client, Tor1RelayCrypto<Rcf>, _, _>::begin(
+                let extender = CircuitExtender::<NtorV3Client>::begin(
                     cx,
-                    Rcf::FORMAT,
+                    protocol,
                     peer_id,
                     HandshakeType::NTOR_V3,
                     &public_key,
@@ -1595,8 +1633,19 @@ impl Reactor {
                 rx,
                 done,
                 cmd_checker,
+                send_window,
+                flow_stats,
             } => {
-                let ret = self.begin_stream(cx, hop_num, message, sender, rx, cmd_checker);
+                let ret = self.begin_stream(
+                    cx,
+                    hop_num,
+                    message,
+                    sender,
+                    rx,
+                    cmd_checker,
+                    send_window,
+                    flow_stats,
+                );
                 let _ = done.send(ret); // don't care if sender goes away
             }
             #[cfg(feature = "hs-service")]
@@ -1700,6 +1749,10 @@ impl Reactor {
                     )))
                 });
             }
+            #[cfg(any(test, feature = "testing"))]
+            CtrlMsg::SetRelayMsgHook { hook } => {
+                self.relay_msg_hook = Some(hook);
+            }
             #[cfg(test)]
             CtrlMsg::SendRelayCell { hop, early, cell } => {
                 self.send_relay_cell(cx, hop, early, cell)?;
@@ -1710,6 +1763,7 @@ impl Reactor {
 
     /// Start a stream. Creates an entry in the stream map with the given channels, and sends the
     /// `message` to the provided hop.
+    #[allow(clippy::too_many_arguments)]
     fn begin_stream(
         &mut self,
         cx: &mut Context<'_>,
@@ -1718,12 +1772,16 @@ impl Reactor {
         sender: StreamMpscSender<UnparsedRelayMsg>,
         rx: StreamMpscReceiver<AnyRelayMsg>,
         cmd_checker: AnyCmdChecker,
+        send_window: u16,
+        flow_stats: FlowStatsRecorder,
     ) -> Result<StreamId> {
         let hop = self
             .hop_mut(hopnum)
             .ok_or_else(|| Error::from(internal!("No such hop {}", hopnum.display())))?;
-        let send_window = StreamSendWindow::new(SEND_WINDOW_INIT);
-        let r = hop.map.add_ent(sender, rx, send_window, cmd_checker)?;
+        let send_window = StreamSendWindow::new(send_window);
+        let r = hop
+            .map
+            .add_ent(sender, rx, send_window, cmd_checker, flow_stats)?;
         let cell = AnyRelayMsgOuter::new(Some(r), message);
         self.send_relay_cell(cx, hopnum, false, cell)?;
         Ok(r)
@@ -1824,10 +1882,31 @@ impl Reactor {
             .map_err(|e| Error::from_bytes_err(e, "relay cell"))?;
 
         let c_t_w = decode_res.cmds().any(sendme::cmd_counts_towards_windows);
+        let circ_c_t_w = decode_res
+            .cmds()
+            .any(sendme::cmd_counts_towards_circ_windows);
+        let (msgs, incomplete) = decode_res.into_parts();
+
+        // Report the messages before we react to them, since that may
+        // involve sending a SENDME.
+        #[cfg(any(test, feature = "testing"))]
+        let msgs = {
+            let msgs: Vec<_> = msgs.collect();
+            for msg in &msgs {
+                self.trace_relay_msg(
+                    RelayMsgDirection::Inbound,
+                    hopnum,
+                    msg.stream_id(),
+                    msg.cmd(),
+                    false,
+                );
+            }
+            msgs.into_iter()
+        };
 
         // Decrement the circuit sendme windows, and see if we need to
         // send a sendme cell.
-        let send_circ_sendme = if c_t_w {
+        let send_circ_sendme = if circ_c_t_w {
             let hop = self
                 .hop_mut(hopnum)
                 .ok_or_else(|| Error::CircProto("Sendme from nonexistent hop".into()))?;
@@ -1855,7 +1934,7 @@ impl Reactor {
                 .put()?;
         }
 
-        let (mut msgs, incomplete) = decode_res.into_parts();
+        let mut msgs = msgs;
         while let Some(msg) = msgs.next() {
             let msg_status = self.handle_relay_msg(cx, hopnum, c_t_w, msg)?;
             match msg_status {
@@ -1925,6 +2004,7 @@ impl Reactor {
                 let message_closes_stream =
                     ent.cmd_checker.check_msg(&msg)? == StreamStatus::Closed;
 
+                let mut refused_by_memquota = false;
                 if let Err(e) = Pin::new(&mut ent.sink).try_send(msg) {
                     if e.is_full() {
                         // If we get here, we either have a logic bug (!), or an attacker
@@ -1941,6 +2021,28 @@ impl Reactor {
                         // Later this value will be recorded in a half-stream.
                         ent.dropped += 1;
                     }
+                    if let ErasedSinkTrySendError::Other(_) = e {
+                        // The memory quota refused the cell: this stream, or its circuit,
+                        // is over its limit, or the queue has been reclaimed.
+                        if cell_counts_toward_windows {
+                            ent.dropped += 1;
+                        }
+                        refused_by_memquota = true;
+                    }
+                }
+                if refused_by_memquota {
+                    // We can't deliver the data, so give up on the stream, as C Tor
+                    // does when it can't buffer it.
+                    self.close_stream(
+                        cx,
+                        hopnum,
+                        streamid,
+                        CloseStreamBehavior::SendEnd(End::new_with_reason(
+                            EndReason::RESOURCELIMIT,
+                        )),
+                        streammap::TerminateReason::ExplicitEnd,
+                    )?;
+                    return Ok(CellStatus::Continue);
                 }
                 if message_closes_stream {
                     hop.map.ending_msg_received(streamid)?;
@@ -1995,7 +2097,6 @@ impl Reactor {
         hop_num: HopNum,
     ) -> Result<CellStatus> {
         use syncview::ClientCircSyncView;
-        use tor_cell::relaycell::msg::EndReason;
         use tor_error::into_internal;
         use tor_log_ratelim::log_ratelim;
 
@@ -2077,8 +2178,15 @@ impl Reactor {
 
         let send_window = StreamSendWindow::new(SEND_WINDOW_INIT);
         let cmd_checker = DataCmdChecker::new_connected();
-        hop.map
-            .add_ent_with_id(sender, msg_rx, send_window, stream_id, cmd_checker)?;
+        let flow_stats = FlowStatsRecorder::new(SEND_WINDOW_INIT, RECV_WINDOW_INIT);
+        hop.map.add_ent_with_id(
+            sender,
+            msg_rx,
+            send_window,
+            stream_id,
+            cmd_checker,
+            flow_stats.clone(),
+        )?;
 
         let outcome = Pin::new(&mut handler.incoming_sender).try_send(StreamReqInfo {
             req,
@@ -2087,6 +2195,7 @@ impl Reactor {
             msg_tx,
             receiver,
             memquota,
+            flow_stats,
         });
 
         log_ratelim!("Delivering message to incoming stream handler"; outcome);
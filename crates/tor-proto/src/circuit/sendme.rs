@@ -265,6 +265,11 @@ impl<P: WindowParams> RecvWindow<P> {
         }
     }
 
+    /// Return the current receive window value.
+    pub(crate) fn window(&self) -> u16 {
+        self.window
+    }
+
     /// Called when we've just sent a SENDME.
     pub(crate) fn put(&mut self) -> Result<()> {
         self.window = self
@@ -285,6 +290,15 @@ pub(crate) fn cmd_counts_towards_windows(cmd: RelayCmd) -> bool {
     cmd == RelayCmd::DATA
 }
 
+/// Return true if this message type is counted by circuit-level flow-control windows.
+///
+/// This is everything that [`cmd_counts_towards_windows`] counts,
+/// and also DATAGRAM messages (proposal 339), which are flow-controlled
+/// only at the circuit level.
+pub(crate) fn cmd_counts_towards_circ_windows(cmd: RelayCmd) -> bool {
+    cmd_counts_towards_windows(cmd) || cmd == RelayCmd::DATAGRAM
+}
+
 /// Return true if this message is counted by flow-control windows.
 #[cfg(test)]
 pub(crate) fn msg_counts_towards_windows(msg: &tor_cell::relaycell::msg::AnyRelayMsg) -> bool {
@@ -344,6 +358,11 @@ mod test {
             )
             .unwrap()
         ));
+
+        assert!(cmd_counts_towards_circ_windows(RelayCmd::DATA));
+        assert!(cmd_counts_towards_circ_windows(RelayCmd::DATAGRAM));
+        assert!(!cmd_counts_towards_windows(RelayCmd::DATAGRAM));
+        assert!(!cmd_counts_towards_circ_windows(RelayCmd::SENDME));
     }
 
     #[test]
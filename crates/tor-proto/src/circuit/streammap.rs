@@ -3,7 +3,7 @@
 use crate::circuit::halfstream::HalfStream;
 use crate::circuit::sendme;
 use crate::circuit::{StreamMpscReceiver, StreamMpscSender};
-use crate::stream::{AnyCmdChecker, StreamSendFlowControl};
+use crate::stream::{AnyCmdChecker, FlowStatsRecorder, StreamSendFlowControl};
 use crate::util::stream_poll_set::{KeyAlreadyInsertedError, StreamPollSet};
 use crate::{Error, Result};
 use pin_project::pin_project;
@@ -52,6 +52,8 @@ pub(super) struct OpenStreamEnt {
     /// Waker to be woken when more sending capacity becomes available (e.g.
     /// receiving a SENDME).
     flow_ctrl_waker: Option<Waker>,
+    /// A record of the flow-control state of this stream, for the stream's owner.
+    flow_stats: FlowStatsRecorder,
 }
 
 impl OpenStreamEnt {
@@ -68,6 +70,8 @@ impl OpenStreamEnt {
     /// circuit with a protocol error.
     pub(crate) fn put_for_incoming_sendme(&mut self) -> Result<u16> {
         let res = self.flow_ctrl.put_for_incoming_sendme()?;
+        self.flow_stats.note_send_window(res);
+        self.flow_stats.note_unblocked();
         // Wake the stream if it was blocked on flow control.
         if let Some(waker) = self.flow_ctrl_waker.take() {
             waker.wake();
@@ -82,7 +86,9 @@ impl OpenStreamEnt {
     // TODO: Consider not exposing this, and instead taking the capacity in
     // `StreamMap::take_ready_msg`.
     pub(crate) fn take_capacity_to_send<M: RelayMsg>(&mut self, msg: &M) -> Result<()> {
-        self.flow_ctrl.take_capacity_to_send(msg)
+        self.flow_ctrl.take_capacity_to_send(msg)?;
+        self.flow_stats.note_send_window(self.flow_ctrl.window());
+        Ok(())
     }
 }
 
@@ -132,6 +138,7 @@ impl PeekableStream for OpenStreamEntStream {
             Poll::Pending => return Poll::Pending,
         };
         if !inner.flow_ctrl.can_send(m) {
+            inner.flow_stats.note_blocked();
             inner.flow_ctrl_waker.replace(cx.waker().clone());
             return Poll::Pending;
         }
@@ -274,6 +281,7 @@ impl StreamMap {
         rx: StreamMpscReceiver<AnyRelayMsg>,
         send_window: sendme::StreamSendWindow,
         cmd_checker: AnyCmdChecker,
+        flow_stats: FlowStatsRecorder,
     ) -> Result<StreamId> {
         let mut stream_ent = OpenStreamEntStream {
             inner: OpenStreamEnt {
@@ -283,6 +291,7 @@ impl StreamMap {
                 cmd_checker,
                 rx: StreamUnobtrusivePeeker::new(rx),
                 flow_ctrl_waker: None,
+                flow_stats,
             },
         };
         let priority = self.take_next_priority();
@@ -315,6 +324,7 @@ impl StreamMap {
         send_window: sendme::StreamSendWindow,
         id: StreamId,
         cmd_checker: AnyCmdChecker,
+        flow_stats: FlowStatsRecorder,
     ) -> Result<()> {
         let stream_ent = OpenStreamEntStream {
             inner: OpenStreamEnt {
@@ -324,6 +334,7 @@ impl StreamMap {
                 cmd_checker,
                 rx: StreamUnobtrusivePeeker::new(rx),
                 flow_ctrl_waker: None,
+                flow_stats,
             },
         };
         let priority = self.take_next_priority();
@@ -487,7 +498,8 @@ pub(super) enum TerminateReason {
     /// corresponding senders were all dropped.
     StreamTargetClosed,
     /// Closing a stream because we were explicitly told to end it via
-    /// [`StreamTarget::close_pending`](crate::circuit::StreamTarget::close_pending).
+    /// [`StreamTarget::close_pending`](crate::circuit::StreamTarget::close_pending),
+    /// or because the reactor decided to end it (for example, to enforce a memory limit).
     ExplicitEnd,
 }
 
@@ -545,6 +557,7 @@ mod test {
                 rx,
                 StreamSendWindow::new(500),
                 DataCmdChecker::new_any(),
+                FlowStatsRecorder::new(500, 500),
             )?;
             let expect_id: StreamId = next_id;
             assert_eq!(expect_id, id);
@@ -0,0 +1,78 @@
+//! Hooks for observing the relay messages sent and received on a circuit.
+//!
+//! These exist so that a test harness can check the exact sequence of relay
+//! messages that we exchange with each hop, for example to compare our
+//! behavior against a transcript of what C Tor does in the same situation.
+//!
+//! This API is only available with the `testing` feature,
+//! and is not covered by semver.
+
+use crate::crypto::cell::HopNum;
+use tor_cell::relaycell::{RelayCmd, StreamId};
+
+/// The direction in which a traced relay message was traveling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RelayMsgDirection {
+    /// We sent this message to a hop on the circuit.
+    Outbound,
+    /// We received this message from a hop on the circuit.
+    Inbound,
+}
+
+/// A description of one relay message, as passed to a [`RelayMsgHook`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RelayMsgTrace {
+    /// Whether we sent or received this message.
+    pub direction: RelayMsgDirection,
+    /// The hop that we sent this message to, or received it from.
+    pub hop: HopNum,
+    /// The stream that this message was for, if any.
+    pub stream_id: Option<StreamId>,
+    /// The command of this message.
+    pub cmd: RelayCmd,
+    /// True if we sent this message in a RELAY_EARLY cell.
+    ///
+    /// Always false for inbound messages.
+    pub early: bool,
+}
+
+impl RelayMsgTrace {
+    /// Construct a new `RelayMsgTrace`.
+    pub fn new(
+        direction: RelayMsgDirection,
+        hop: HopNum,
+        stream_id: Option<StreamId>,
+        cmd: RelayCmd,
+        early: bool,
+    ) -> Self {
+        Self {
+            direction,
+            hop,
+            stream_id,
+            cmd,
+            early,
+        }
+    }
+}
+
+/// An observer for the relay messages on a circuit.
+///
+/// Install one with [`ClientCirc::set_relay_msg_hook`](super::ClientCirc::set_relay_msg_hook).
+///
+/// The hook is called from the circuit reactor, for every relay message,
+/// so it must not block.
+/// Outbound messages are reported just before they are encrypted and sent to
+/// the channel. Inbound messages are reported once they have been decrypted
+/// and decoded, before we act on them.
+pub trait RelayMsgHook: Send {
+    /// Called for each relay message sent or received on the circuit.
+    fn observe(&mut self, msg: &RelayMsgTrace);
+}
+
+impl<F: FnMut(&RelayMsgTrace) + Send> RelayMsgHook for F {
+    fn observe(&mut self, msg: &RelayMsgTrace) {
+        self(msg);
+    }
+}
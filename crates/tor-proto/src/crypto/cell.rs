@@ -29,6 +29,7 @@
 //! This `crypto::cell` module itself provides traits and implementations that
 //! should work for all current future versions of the relay cell crypto design.
 //! The current Tor protocols are instantiated in a `tor1` submodule.
+//! The `offload` submodule lets an external engine perform the `tor1` cryptography.
 
 use crate::{Error, Result};
 use derive_deftly::Deftly;
@@ -40,6 +41,9 @@ use digest::generic_array::GenericArray;
 
 use super::binding::CircuitBinding;
 
+#[cfg(feature = "cell-crypto-offload")]
+pub(crate) mod offload;
+
 /// Type for the body of a relay cell.
 #[derive(Clone, derive_more::From, derive_more::Into)]
 pub(crate) struct RelayCellBody(BoxedCellBody);
@@ -469,19 +473,19 @@ pub(crate) mod tor1 {
     /// These operations is described in tor-spec section 6.1 "Relay cells"
     impl RelayCellBody {
         /// Returns the byte slice of the `recognized` field.
-        fn recognized<RCF: RelayCellFormatTrait>(&self) -> &[u8] {
+        pub(super) fn recognized<RCF: RelayCellFormatTrait>(&self) -> &[u8] {
             &self.0[RCF::FIELDS::RECOGNIZED_RANGE]
         }
         /// Returns the mut byte slice of the `recognized` field.
-        fn recognized_mut<RCF: RelayCellFormatTrait>(&mut self) -> &mut [u8] {
+        pub(super) fn recognized_mut<RCF: RelayCellFormatTrait>(&mut self) -> &mut [u8] {
             &mut self.0[RCF::FIELDS::RECOGNIZED_RANGE]
         }
         /// Returns the byte slice of the `digest` field.
-        fn digest<RCF: RelayCellFormatTrait>(&self) -> &[u8] {
+        pub(super) fn digest<RCF: RelayCellFormatTrait>(&self) -> &[u8] {
             &self.0[RCF::FIELDS::DIGEST_RANGE]
         }
         /// Returns the mut byte slice of the `digest` field.
-        fn digest_mut<RCF: RelayCellFormatTrait>(&mut self) -> &mut [u8] {
+        pub(super) fn digest_mut<RCF: RelayCellFormatTrait>(&mut self) -> &mut [u8] {
             &mut self.0[RCF::FIELDS::DIGEST_RANGE]
         }
         /// Prepare a cell body by setting its digest and recognized field.
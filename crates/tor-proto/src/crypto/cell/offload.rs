@@ -0,0 +1,505 @@
+//! Support for performing relay cell cryptography in an external engine.
+//!
+//! Most of the CPU time that a busy Tor instance spends on cryptography
+//! goes to the `tor1` relay cell crypto: AES-CTR and a running digest,
+//! applied to every relay cell at every layer.
+//! A [`CellCryptoEngine`] lets some other implementation do that work:
+//! for example, the kernel crypto API, batched routines for a particular CPU,
+//! or (someday) a network card.
+//!
+//! An engine is selected per channel,
+//! with [`Channel::set_cell_crypto_engine`](crate::channel::Channel::set_cell_crypto_engine).
+//! Channels without an engine use our own implementation, as before.
+//!
+//! Here we only provide the engine with the keyed primitives:
+//! the cell formats, the "recognized" logic, and SENDME tags stay our responsibility.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use cipher::{KeyIvInit, StreamCipher};
+use digest::Digest;
+use tor_cell::relaycell::{RelayCellFields, RelayCellFormatTrait};
+use tor_error::internal;
+
+use super::{InboundClientLayer, OutboundClientLayer, RelayCellBody, SENDME_TAG_LEN};
+use crate::crypto::binding::{CircuitBinding, CIRC_BINDING_LEN};
+use crate::util::ct;
+use crate::{Error, Result};
+
+/// The largest digest that any [`Tor1Algorithms`] uses.
+const MAX_DIGEST_LEN: usize = 32;
+
+/// The algorithms used by one hop of `tor1` relay cell cryptography.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Tor1Algorithms {
+    /// AES-128-CTR and SHA-1, as used on ordinary circuits.
+    Aes128Sha1,
+    /// AES-256-CTR and SHA3-256, as used on the virtual hop to an onion service.
+    Aes256Sha3_256,
+}
+
+impl Tor1Algorithms {
+    /// Return the length of the stream cipher's key, in bytes.
+    pub fn key_len(self) -> usize {
+        match self {
+            Tor1Algorithms::Aes128Sha1 => 16,
+            Tor1Algorithms::Aes256Sha3_256 => 32,
+        }
+    }
+
+    /// Return the length of the digest's output, in bytes.
+    pub fn digest_len(self) -> usize {
+        match self {
+            Tor1Algorithms::Aes128Sha1 => 20,
+            Tor1Algorithms::Aes256Sha3_256 => 32,
+        }
+    }
+}
+
+/// An implementation of relay cell cryptography.
+///
+/// See the [module documentation](self) for more information.
+pub trait CellCryptoEngine: Send + Sync + fmt::Debug {
+    /// Return a short name for this engine, for use in log messages.
+    fn name(&self) -> &str;
+
+    /// Return the state for one direction of one hop of `tor1` relay cell cryptography.
+    ///
+    /// The stream cipher is keyed with `key`, with an all-zero IV,
+    /// and the running digest is initialized by adding `digest_seed` to it.
+    /// `key` and `digest_seed` are `algs.key_len()` and `algs.digest_len()` bytes long.
+    ///
+    /// Return `None` if this engine doesn't implement `algs`:
+    /// in that case, we use our own implementation for this hop.
+    fn tor1_state(
+        &self,
+        algs: Tor1Algorithms,
+        key: &[u8],
+        digest_seed: &[u8],
+    ) -> Option<Box<dyn Tor1CellState>>;
+}
+
+/// One direction of one hop of `tor1` relay cell cryptography:
+/// a keyed stream cipher, and a running digest.
+///
+/// Returned by [`CellCryptoEngine::tor1_state`].
+pub trait Tor1CellState: Send {
+    /// Apply the next `body.len()` bytes of the keystream to `body`.
+    fn apply_keystream(&mut self, body: &mut [u8]);
+
+    /// Add `data` to the running digest,
+    /// and write the digest of everything added so far to `out`.
+    ///
+    /// The running digest is not reset.
+    /// `out` is exactly as long as the digest.
+    fn add_to_digest(&mut self, data: &[u8], out: &mut [u8]);
+
+    /// Compute the digest that we would get by adding `data` to the running digest,
+    /// and write it to `out`.
+    ///
+    /// Then, if `accept(out)` returns true, add `data` to the running digest and return true.
+    /// Otherwise, leave the running digest unchanged and return false.
+    ///
+    /// `out` is exactly as long as the digest.
+    fn add_to_digest_if(
+        &mut self,
+        data: &[u8],
+        out: &mut [u8],
+        accept: &mut dyn FnMut(&[u8]) -> bool,
+    ) -> bool;
+}
+
+/// A [`CellCryptoEngine`] that uses the same implementation as we do by default.
+///
+/// This is mainly useful for comparing against other engines.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct SoftwareCellCrypto;
+
+impl SoftwareCellCrypto {
+    /// Return a new `SoftwareCellCrypto`.
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl CellCryptoEngine for SoftwareCellCrypto {
+    fn name(&self) -> &str {
+        "software"
+    }
+
+    fn tor1_state(
+        &self,
+        algs: Tor1Algorithms,
+        key: &[u8],
+        digest_seed: &[u8],
+    ) -> Option<Box<dyn Tor1CellState>> {
+        use tor_llcrypto::cipher::aes::{Aes128Ctr, Aes256Ctr};
+        use tor_llcrypto::d::{Sha1, Sha3_256};
+
+        match algs {
+            Tor1Algorithms::Aes128Sha1 => {
+                SoftwareState::<Aes128Ctr, Sha1>::new(key, digest_seed).map(|s| Box::new(s) as _)
+            }
+            Tor1Algorithms::Aes256Sha3_256 => {
+                SoftwareState::<Aes256Ctr, Sha3_256>::new(key, digest_seed)
+                    .map(|s| Box::new(s) as _)
+            }
+        }
+    }
+}
+
+/// The [`Tor1CellState`] returned by [`SoftwareCellCrypto`].
+struct SoftwareState<SC, D> {
+    /// The stream cipher.
+    cipher: SC,
+    /// The running digest.
+    digest: D,
+}
+
+impl<SC: StreamCipher + KeyIvInit, D: Digest + Clone> SoftwareState<SC, D> {
+    /// Construct a new `SoftwareState`, or return None if `key` is the wrong length.
+    fn new(key: &[u8], digest_seed: &[u8]) -> Option<Self> {
+        Some(Self {
+            cipher: SC::new_from_slices(key, &cipher::Iv::<SC>::default()).ok()?,
+            digest: D::new().chain_update(digest_seed),
+        })
+    }
+}
+
+impl<SC, D> Tor1CellState for SoftwareState<SC, D>
+where
+    SC: StreamCipher + Send,
+    D: Digest + Clone + Send,
+{
+    fn apply_keystream(&mut self, body: &mut [u8]) {
+        self.cipher.apply_keystream(body);
+    }
+
+    fn add_to_digest(&mut self, data: &[u8], out: &mut [u8]) {
+        self.digest.update(data);
+        out.copy_from_slice(&self.digest.clone().finalize());
+    }
+
+    fn add_to_digest_if(
+        &mut self,
+        data: &[u8],
+        out: &mut [u8],
+        accept: &mut dyn FnMut(&[u8]) -> bool,
+    ) -> bool {
+        let mut digest = self.digest.clone();
+        digest.update(data);
+        out.copy_from_slice(&digest.clone().finalize());
+        if accept(out) {
+            self.digest = digest;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// One layer of `tor1` relay cell cryptography, in one direction,
+/// as performed by a [`CellCryptoEngine`].
+///
+/// This is the counterpart of [`tor1::CryptState`](super::tor1::CryptState).
+pub(crate) struct OffloadedCryptState<RCF: RelayCellFormatTrait> {
+    /// The engine's state for this layer.
+    state: Box<dyn Tor1CellState>,
+    /// Most recent digest value generated by this crypto.
+    ///
+    /// Only the first `digest_len` bytes are used.
+    last_digest_val: [u8; MAX_DIGEST_LEN],
+    /// The length of the digest.
+    digest_len: usize,
+    /// The format used for relay cells at this layer.
+    relay_cell_format: PhantomData<RCF>,
+}
+
+/// A pair of [`OffloadedCryptState`]s for a single hop.
+///
+/// This is the counterpart of [`tor1::CryptStatePair`](super::tor1::CryptStatePair).
+pub(crate) struct OffloadedCryptStatePair<RCF: RelayCellFormatTrait> {
+    /// State for en/decrypting cells sent away from the client.
+    pub(crate) fwd: OffloadedCryptState<RCF>,
+    /// State for en/decrypting cells sent towards the client.
+    pub(crate) back: OffloadedCryptState<RCF>,
+    /// A circuit binding key.
+    pub(crate) binding: CircuitBinding,
+}
+
+impl<RCF: RelayCellFormatTrait> OffloadedCryptStatePair<RCF> {
+    /// Return the number of bytes of seed that we need for `algs`.
+    pub(crate) fn seed_len(algs: Tor1Algorithms) -> usize {
+        algs.key_len() * 2 + algs.digest_len() * 2 + CIRC_BINDING_LEN
+    }
+
+    /// Construct a new pair of states for `algs` with `engine`, from `seed`.
+    ///
+    /// Return `Ok(None)` if `engine` doesn't implement `algs`.
+    ///
+    /// The seed is divided up in the same way as for
+    /// [`tor1::CryptStatePair`](super::tor1::CryptStatePair).
+    pub(crate) fn initialize(
+        algs: Tor1Algorithms,
+        mut seed: &[u8],
+        engine: &dyn CellCryptoEngine,
+    ) -> Result<Option<Self>> {
+        if seed.len() != Self::seed_len(algs) {
+            return Err(Error::from(internal!(
+                "seed length {} was invalid",
+                seed.len()
+            )));
+        }
+
+        // Advances `seed` by `n` bytes, returning the advanced bytes
+        let mut take_seed = |n: usize| -> &[u8] {
+            let res = &seed[..n];
+            seed = &seed[n..];
+            res
+        };
+
+        let dlen = algs.digest_len();
+        let keylen = algs.key_len();
+
+        let df = take_seed(dlen);
+        let db = take_seed(dlen);
+        let kf = take_seed(keylen);
+        let kb = take_seed(keylen);
+        let binding_key = take_seed(CIRC_BINDING_LEN);
+
+        let layer = |state| OffloadedCryptState {
+            state,
+            last_digest_val: [0; MAX_DIGEST_LEN],
+            digest_len: dlen,
+            relay_cell_format: PhantomData,
+        };
+        let (Some(fwd), Some(back)) = (
+            engine.tor1_state(algs, kf, df),
+            engine.tor1_state(algs, kb, db),
+        ) else {
+            return Ok(None);
+        };
+        let binding = CircuitBinding::try_from(binding_key)?;
+
+        Ok(Some(OffloadedCryptStatePair {
+            fwd: layer(fwd),
+            back: layer(back),
+            binding,
+        }))
+    }
+}
+
+impl<RCF: RelayCellFormatTrait> OutboundClientLayer for OffloadedCryptState<RCF> {
+    fn originate_for(&mut self, cell: &mut RelayCellBody) -> &[u8] {
+        // This does the same as RelayCellBody::set_digest.
+        cell.recognized_mut::<RCF>().fill(0);
+        cell.digest_mut::<RCF>().fill(0);
+        let digest = &mut self.last_digest_val[..self.digest_len];
+        self.state.add_to_digest(cell.as_ref(), digest);
+        let digest_prefix = &digest[..RCF::FIELDS::DIGEST_RANGE.len()];
+        cell.digest_mut::<RCF>().copy_from_slice(digest_prefix);
+
+        self.encrypt_outbound(cell);
+        &self.last_digest_val[..SENDME_TAG_LEN]
+    }
+    fn encrypt_outbound(&mut self, cell: &mut RelayCellBody) {
+        self.state.apply_keystream(cell.as_mut());
+    }
+}
+
+impl<RCF: RelayCellFormatTrait> InboundClientLayer for OffloadedCryptState<RCF> {
+    fn decrypt_inbound(&mut self, cell: &mut RelayCellBody) -> Option<&[u8]> {
+        self.state.apply_keystream(cell.as_mut());
+
+        // This does the same as RelayCellBody::is_recognized.
+        if !ct::is_zero(cell.recognized::<RCF>()) {
+            return None;
+        }
+        // The sender computed the digest with the digest field set to zero:
+        // so we do the same, and then put the field back.
+        let digest_len = RCF::FIELDS::DIGEST_RANGE.len();
+        let mut received = [0_u8; MAX_DIGEST_LEN];
+        received[..digest_len].copy_from_slice(cell.digest::<RCF>());
+        cell.digest_mut::<RCF>().fill(0);
+
+        let mut result = [0_u8; MAX_DIGEST_LEN];
+        let mut matches = |d: &[u8]| ct::bytes_eq(&received[..digest_len], &d[..digest_len]);
+        let recognized = self.state.add_to_digest_if(
+            cell.as_ref(),
+            &mut result[..self.digest_len],
+            &mut matches,
+        );
+        cell.digest_mut::<RCF>()
+            .copy_from_slice(&received[..digest_len]);
+
+        if recognized {
+            self.last_digest_val = result;
+            Some(&self.last_digest_val[..SENDME_TAG_LEN])
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+    use crate::crypto::cell::tor1::CryptStatePair;
+    use crate::crypto::cell::{
+        ClientLayer, CryptInit, InboundClientCrypt, OutboundClientCrypt, RelayCrypt,
+    };
+    use rand::RngCore;
+    use tor_basic_utils::test_rng::testing_rng;
+    use tor_cell::relaycell::RelayCellFormatV0;
+    use tor_llcrypto::cipher::aes::{Aes128Ctr, Aes256Ctr};
+    use tor_llcrypto::d::{Sha1, Sha3_256};
+
+    /// An engine that doesn't implement anything.
+    #[derive(Debug)]
+    struct NoEngine;
+
+    impl CellCryptoEngine for NoEngine {
+        fn name(&self) -> &str {
+            "nothing"
+        }
+        fn tor1_state(
+            &self,
+            _algs: Tor1Algorithms,
+            _key: &[u8],
+            _digest_seed: &[u8],
+        ) -> Option<Box<dyn Tor1CellState>> {
+            None
+        }
+    }
+
+    /// Check that layers built by [`SoftwareCellCrypto`] from `seeds` behave exactly like
+    /// our own layers built from the same seeds, with cipher `SC` and digest `D`.
+    fn check_equivalence<SC, D>(algs: Tor1Algorithms, seeds: &[&[u8]])
+    where
+        SC: StreamCipher + KeyIvInit + Send + 'static,
+        D: Digest + Clone + Send + 'static,
+    {
+        type Rcf = RelayCellFormatV0;
+        let engine = SoftwareCellCrypto::new();
+
+        let mut sw_out = OutboundClientCrypt::new();
+        let mut sw_in = InboundClientCrypt::new();
+        let mut off_out = OutboundClientCrypt::new();
+        let mut off_in = InboundClientCrypt::new();
+        let mut relays = vec![];
+        for seed in seeds {
+            assert_eq!(
+                OffloadedCryptStatePair::<Rcf>::seed_len(algs),
+                CryptStatePair::<SC, D, Rcf>::seed_len()
+            );
+            let (fwd, back, _) = CryptStatePair::<SC, D, Rcf>::initialize(seed)
+                .unwrap()
+                .split();
+            sw_out.add_layer(Box::new(fwd));
+            sw_in.add_layer(Box::new(back));
+            let pair = OffloadedCryptStatePair::<Rcf>::initialize(algs, seed, &engine)
+                .unwrap()
+                .unwrap();
+            off_out.add_layer(Box::new(pair.fwd));
+            off_in.add_layer(Box::new(pair.back));
+            relays.push(CryptStatePair::<SC, D, Rcf>::initialize(seed).unwrap());
+        }
+        let last_hop = u8::try_from(seeds.len() - 1).unwrap().into();
+
+        let mut rng = testing_rng();
+        for _ in 0..100 {
+            // Outbound: both produce the same bytes, and the same tag.
+            let mut body = Box::new([0_u8; 509]);
+            rng.fill_bytes(&mut body[..]);
+            let mut sw_cell: RelayCellBody = body.clone().into();
+            let mut off_cell: RelayCellBody = body.into();
+            let sw_tag = *sw_out.encrypt(&mut sw_cell, last_hop).unwrap();
+            let off_tag = *off_out.encrypt(&mut off_cell, last_hop).unwrap();
+            assert_eq!(sw_cell.as_ref(), off_cell.as_ref());
+            assert_eq!(sw_tag, off_tag);
+
+            // Inbound, from the last hop: both recognize the cell at the same hop,
+            // with the same tag.
+            let mut body = Box::new([0_u8; 509]);
+            rng.fill_bytes(&mut body[..]);
+            let mut cell: RelayCellBody = body.into();
+            let (last, others) = relays.split_last_mut().unwrap();
+            last.originate(&mut cell);
+            last.encrypt_inbound(&mut cell);
+            for relay in others.iter_mut().rev() {
+                relay.encrypt_inbound(&mut cell);
+            }
+            let mut sw_cell = cell.clone();
+            let mut off_cell = cell;
+            let (sw_hop, sw_tag) = sw_in.decrypt(&mut sw_cell).unwrap();
+            let sw_tag = sw_tag.to_vec();
+            let (off_hop, off_tag) = off_in.decrypt(&mut off_cell).unwrap();
+            assert_eq!(sw_hop, off_hop);
+            assert_eq!(sw_tag, off_tag);
+            assert_eq!(sw_cell.as_ref(), off_cell.as_ref());
+        }
+
+        // Junk isn't recognized by either.
+        let mut junk: RelayCellBody = Box::new([0_u8; 509]).into();
+        assert!(sw_in.decrypt(&mut junk.clone()).is_err());
+        assert!(off_in.decrypt(&mut junk).is_err());
+    }
+
+    #[test]
+    fn equivalent_tor1() {
+        let seeds: Vec<Vec<u8>> = (0..3_u8)
+            .map(|i| {
+                let len = OffloadedCryptStatePair::<RelayCellFormatV0>::seed_len(
+                    Tor1Algorithms::Aes128Sha1,
+                );
+                vec![i + 1; len]
+            })
+            .collect();
+        let seeds: Vec<&[u8]> = seeds.iter().map(Vec::as_slice).collect();
+        check_equivalence::<Aes128Ctr, Sha1>(Tor1Algorithms::Aes128Sha1, &seeds);
+    }
+
+    #[test]
+    fn equivalent_hsv3() {
+        let len =
+            OffloadedCryptStatePair::<RelayCellFormatV0>::seed_len(Tor1Algorithms::Aes256Sha3_256);
+        let seed = vec![7_u8; len];
+        check_equivalence::<Aes256Ctr, Sha3_256>(Tor1Algorithms::Aes256Sha3_256, &[&seed[..]]);
+    }
+
+    #[test]
+    fn unsupported() {
+        let seed = vec![0_u8; 92];
+        let pair = OffloadedCryptStatePair::<RelayCellFormatV0>::initialize(
+            Tor1Algorithms::Aes128Sha1,
+            &seed,
+            &NoEngine,
+        )
+        .unwrap();
+        assert!(pair.is_none());
+
+        // A seed of the wrong length is a bug.
+        let err = OffloadedCryptStatePair::<RelayCellFormatV0>::initialize(
+            Tor1Algorithms::Aes128Sha1,
+            &seed[1..],
+            &SoftwareCellCrypto::new(),
+        );
+        assert!(err.is_err());
+    }
+}
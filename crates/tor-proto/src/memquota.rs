@@ -80,6 +80,19 @@
 //!     for discussion of this behaviour.)
 //!
 //! Thus, killing a single queue will reclaim the memory associated with several other queues.
+//!
+//! ## Per-circuit and per-stream limits
+//!
+//! Each [`CircuitAccount`] and [`StreamAccount`] is given the hard limit
+//! configured for it (`max_per_circuit` and `max_per_stream` in [`tor_memquota::Config`]),
+//! if any.
+//! Unlike the global quota, reaching one of these doesn't reclaim anything;
+//! instead, the queue refuses the data:
+//!
+//!  * Outbound data on a stream is refused with [`Error::Memquota`](crate::Error::Memquota),
+//!    which is reported to the stream's user.
+//!  * Inbound data for a stream that would exceed a limit
+//!    causes us to close the stream, with an `END` cell with reason `RESOURCELIMIT`.
 
 use derive_deftly::{define_derive_deftly, Deftly};
 use std::sync::Arc;
@@ -133,6 +146,13 @@ define_derive_deftly! {
     ///    `type ConstructedFrom = PARENT_ACCOUNT`
     ///    (and PARENT_ACCOUNT must itself impl `SpecificAccount`).
     ///
+    /// Optionally, the account can be given a hard limit from the configuration:
+    ///
+    ///  * **`#[deftly(account_newtype(limit_class = "LIMIT_CLASS"))]`**:
+    ///    `new()` applies the configured limit for
+    ///    `tor_memquota::LimitClass::LIMIT_CLASS`
+    ///    (see [`Account::set_configured_limit`]).
+    ///
     /// Applicable to newtype tuple structs, containing an [`Account`], only.
     export SpecificAccount for struct, expect items:
 
@@ -156,6 +176,14 @@ define_derive_deftly! {
             HAS_PARENT  { $crate::memquota::SpecificAccount::as_raw_account(src).new_child() }
             IS_TOPLEVEL { src.new_account(None) }
           }
+              ${if tmeta(account_newtype(limit_class)) {
+                .and_then(|account| {
+                    account.set_configured_limit(
+                        $crate::tor_memquota::LimitClass::${tmeta(account_newtype(limit_class)) as ident}
+                    )?;
+                    Ok(account)
+                })
+              }}
                 .map(Self::from_raw_account)
         }
 
@@ -200,7 +228,7 @@ pub struct ChannelAccount(Account);
 /// See the [`memquota`](self) module documentation.
 #[derive(Deftly, Clone, Debug)]
 #[derive_deftly(SpecificAccount)]
-#[deftly(account_newtype(parent = "ChannelAccount"))]
+#[deftly(account_newtype(parent = "ChannelAccount", limit_class = "Circuit"))]
 pub struct CircuitAccount(Account);
 
 /// [`Account`] for a Tor Stream
@@ -209,5 +237,5 @@ pub struct CircuitAccount(Account);
 /// See the [`memquota`](self) module documentation.
 #[derive(Deftly, Clone, Debug)]
 #[derive_deftly(SpecificAccount)]
-#[deftly(account_newtype(parent = "CircuitAccount"))]
+#[deftly(account_newtype(parent = "CircuitAccount", limit_class = "Stream"))]
 pub struct StreamAccount(Account);
@@ -19,6 +19,8 @@ mod incoming;
 mod params;
 mod raw;
 mod resolve;
+#[cfg(feature = "experimental-udp")]
+mod udp;
 
 pub(crate) use cmdcheck::{AnyCmdChecker, CmdChecker, StreamStatus};
 pub use data::{DataReader, DataStream, DataWriter};
@@ -34,12 +36,17 @@ pub use incoming::{
 pub use params::StreamParameters;
 pub use raw::StreamReader;
 pub use resolve::ResolveStream;
+#[cfg(feature = "experimental-udp")]
+pub(crate) use udp::UdpCmdChecker;
+#[cfg(feature = "experimental-udp")]
+#[cfg_attr(docsrs, doc(cfg(feature = "experimental-udp")))]
+pub use udp::{DatagramReader, DatagramStream, DatagramWriter};
 pub(crate) use {data::DataCmdChecker, resolve::ResolveCmdChecker};
 
 pub use tor_cell::relaycell::msg::IpVersionPreference;
 
 #[cfg(feature = "stream-ctrl")]
 #[cfg_attr(docsrs, doc(cfg(feature = "stream-ctrl")))]
-pub use {ctrl::ClientStreamCtrl, data::DataStreamCtrl};
+pub use {ctrl::ClientStreamCtrl, data::DataStreamCtrl, flow_control::StreamFlowStats};
 
-pub(crate) use flow_control::StreamSendFlowControl;
+pub(crate) use flow_control::{FlowStatsRecorder, StreamSendFlowControl};
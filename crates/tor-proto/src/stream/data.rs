@@ -25,6 +25,9 @@ use std::sync::Arc;
 #[cfg(feature = "stream-ctrl")]
 use std::sync::{Mutex, Weak};
 
+#[cfg(feature = "stream-ctrl")]
+use crate::stream::{FlowStatsRecorder, StreamFlowStats};
+
 use educe::Educe;
 
 #[cfg(any(feature = "experimental-api", feature = "stream-ctrl"))]
@@ -167,6 +170,9 @@ pub struct DataStreamCtrl {
     #[cfg(feature = "stream-ctrl")]
     status: Arc<Mutex<DataStreamStatus>>,
 
+    /// A record of the flow-control state of this stream.
+    flow_stats: FlowStatsRecorder,
+
     /// The memory quota account that should be used for this stream's data
     ///
     /// Exists to keep the account alive
@@ -350,6 +356,15 @@ impl DataStreamCtrl {
         s.received_connected && !(s.sent_end || s.received_end || s.received_err)
     }
 
+    /// Return a snapshot of the flow-control state of this stream.
+    ///
+    /// This can help to tell why a stream is slow:
+    /// whether it is waiting for SENDMEs from the other end,
+    /// or whether data is piling up locally.
+    pub fn flow_stats(&self) -> StreamFlowStats {
+        self.flow_stats.stats()
+    }
+
     // TODO RPC: Add more functions once we have the desired API more nailed
     // down.
 }
@@ -397,6 +412,7 @@ impl DataStream {
         let ctrl = Arc::new(DataStreamCtrl {
             circuit: Arc::downgrade(target.circuit()),
             status: status.clone(),
+            flow_stats: target.flow_stats().clone(),
             _memquota: memquota.clone(),
         });
         #[cfg(feature = "experimental-api")]
@@ -749,6 +765,7 @@ impl DataWriterImpl {
                 // this invariant will become false.
                 assert!(remainder.is_empty());
                 self.n_pending = 0;
+                self.s.flow_stats().note_write_buffered(0);
                 self.s.send(cell.into()).await
             } else {
                 Ok(())
@@ -769,6 +786,7 @@ impl DataWriterImpl {
         let n_to_copy = std::cmp::min(b.len(), empty_space.len());
         empty_space[..n_to_copy].copy_from_slice(&b[..n_to_copy]);
         self.n_pending += n_to_copy;
+        self.s.flow_stats().note_write_buffered(self.n_pending);
         n_to_copy
     }
 }
@@ -914,10 +932,19 @@ impl DataReaderImpl {
         let n_to_copy = std::cmp::min(buf.len(), remainder.len());
         buf[..n_to_copy].copy_from_slice(&remainder[..n_to_copy]);
         self.offset += n_to_copy;
+        self.note_read_buffered();
 
         n_to_copy
     }
 
+    /// Record how many bytes are buffered here.
+    fn note_read_buffered(&self) {
+        self.s
+            .target
+            .flow_stats()
+            .note_read_buffered(self.pending.len() - self.offset);
+    }
+
     /// Return true iff there are no buffered bytes here to yield
     fn buf_is_empty(&self) -> bool {
         self.pending.len() == self.offset
@@ -990,6 +1017,7 @@ impl DataReaderImpl {
             // future, we'll have to be careful here.
             self.pending.append(&mut d);
         }
+        self.note_read_buffered();
     }
 }
 
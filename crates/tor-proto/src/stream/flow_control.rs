@@ -5,6 +5,11 @@ use tor_cell::relaycell::RelayMsg;
 use crate::circuit::sendme;
 use crate::Result;
 
+#[cfg(feature = "stream-ctrl")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "stream-ctrl")]
+use std::time::Duration;
+
 /// Private internals of [`StreamSendFlowControl`].
 #[derive(Debug)]
 enum StreamSendFlowControlEnum {
@@ -76,5 +81,167 @@ impl StreamSendFlowControl {
         }
     }
 
+    /// Return the number of cells we can send before we need a SENDME.
+    pub(crate) fn window(&self) -> u16 {
+        match &self.e {
+            StreamSendFlowControlEnum::WindowBased(w) => w.window(),
+        }
+    }
+
     // TODO: Add methods for handling incoming xon, xoff.
 }
+
+/// A snapshot of the flow-control state of a stream.
+///
+/// Returned by [`DataStreamCtrl::flow_stats`](crate::stream::DataStreamCtrl::flow_stats).
+#[cfg(feature = "stream-ctrl")]
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct StreamFlowStats {
+    /// The number of DATA cells we can send before we need a SENDME
+    /// from the other end of the stream.
+    pub send_window: u16,
+    /// The number of DATA cells the other end of the stream can send
+    /// before it needs a SENDME from us.
+    pub recv_window: u16,
+    /// The number of bytes written to the stream but not yet packaged into a cell.
+    pub write_buffered_bytes: usize,
+    /// The number of bytes received on the stream but not yet read.
+    ///
+    /// This doesn't count cells that the circuit reactor has queued for the stream,
+    /// but that the stream hasn't looked at yet.
+    pub read_buffered_bytes: usize,
+    /// If the stream has data to send but is waiting for a SENDME,
+    /// how long it has been waiting.
+    pub blocked_on_sendme: Option<Duration>,
+    /// The total time the stream has spent waiting for SENDMEs,
+    /// including `blocked_on_sendme`.
+    pub total_blocked_on_sendme: Duration,
+    /// The number of times the stream has had to wait for a SENDME.
+    pub n_blocked_on_sendme: u64,
+}
+
+/// A shared record of the flow-control state of a stream.
+///
+/// The circuit reactor and both halves of the stream each hold a clone,
+/// and update it as the state changes.
+///
+/// Without the `stream-ctrl` feature, nothing can read this record,
+/// so nothing is recorded.
+#[derive(Clone, Debug)]
+pub(crate) struct FlowStatsRecorder {
+    /// The recorded state.
+    #[cfg(feature = "stream-ctrl")]
+    state: Arc<Mutex<FlowState>>,
+}
+
+/// The state recorded by a [`FlowStatsRecorder`].
+#[cfg(feature = "stream-ctrl")]
+#[derive(Debug)]
+struct FlowState {
+    /// See [`StreamFlowStats::send_window`].
+    send_window: u16,
+    /// See [`StreamFlowStats::recv_window`].
+    recv_window: u16,
+    /// See [`StreamFlowStats::write_buffered_bytes`].
+    write_buffered_bytes: usize,
+    /// See [`StreamFlowStats::read_buffered_bytes`].
+    read_buffered_bytes: usize,
+    /// When the stream last became blocked on a SENDME, if it is blocked now.
+    blocked_since: Option<coarsetime::Instant>,
+    /// The total time spent blocked on SENDMEs, not counting the current stall.
+    total_blocked: coarsetime::Duration,
+    /// See [`StreamFlowStats::n_blocked_on_sendme`].
+    n_blocked: u64,
+}
+
+#[cfg_attr(not(feature = "stream-ctrl"), allow(unused_variables))]
+impl FlowStatsRecorder {
+    /// Create a new `FlowStatsRecorder` for a stream with the specified initial windows.
+    pub(crate) fn new(send_window: u16, recv_window: u16) -> Self {
+        Self {
+            #[cfg(feature = "stream-ctrl")]
+            state: Arc::new(Mutex::new(FlowState {
+                send_window,
+                recv_window,
+                write_buffered_bytes: 0,
+                read_buffered_bytes: 0,
+                blocked_since: None,
+                total_blocked: coarsetime::Duration::from_secs(0),
+                n_blocked: 0,
+            })),
+        }
+    }
+
+    /// Run `f` on the recorded state.
+    #[cfg(feature = "stream-ctrl")]
+    fn update(&self, f: impl FnOnce(&mut FlowState)) {
+        f(&mut self.state.lock().expect("poisoned lock"));
+    }
+
+    /// Record the current send window of the stream.
+    pub(crate) fn note_send_window(&self, window: u16) {
+        #[cfg(feature = "stream-ctrl")]
+        self.update(|s| s.send_window = window);
+    }
+
+    /// Record the current receive window of the stream.
+    pub(crate) fn note_recv_window(&self, window: u16) {
+        #[cfg(feature = "stream-ctrl")]
+        self.update(|s| s.recv_window = window);
+    }
+
+    /// Record how many bytes are waiting to be packaged into a cell.
+    pub(crate) fn note_write_buffered(&self, n_bytes: usize) {
+        #[cfg(feature = "stream-ctrl")]
+        self.update(|s| s.write_buffered_bytes = n_bytes);
+    }
+
+    /// Record how many bytes have been received, but not yet read.
+    pub(crate) fn note_read_buffered(&self, n_bytes: usize) {
+        #[cfg(feature = "stream-ctrl")]
+        self.update(|s| s.read_buffered_bytes = n_bytes);
+    }
+
+    /// Record that the stream has something to send, but can't send it
+    /// until it gets a SENDME.
+    ///
+    /// Does nothing if the stream is already recorded as blocked.
+    pub(crate) fn note_blocked(&self) {
+        #[cfg(feature = "stream-ctrl")]
+        self.update(|s| {
+            if s.blocked_since.is_none() {
+                s.blocked_since = Some(coarsetime::Instant::now());
+                s.n_blocked += 1;
+            }
+        });
+    }
+
+    /// Record that the stream has received a SENDME, and is no longer blocked.
+    pub(crate) fn note_unblocked(&self) {
+        #[cfg(feature = "stream-ctrl")]
+        self.update(|s| {
+            if let Some(since) = s.blocked_since.take() {
+                s.total_blocked += coarsetime::Instant::now().duration_since(since);
+            }
+        });
+    }
+
+    /// Return a snapshot of the recorded state.
+    #[cfg(feature = "stream-ctrl")]
+    pub(crate) fn stats(&self) -> StreamFlowStats {
+        let s = self.state.lock().expect("poisoned lock");
+        let blocked = s
+            .blocked_since
+            .map(|since| coarsetime::Instant::now().duration_since(since));
+        StreamFlowStats {
+            send_window: s.send_window,
+            recv_window: s.recv_window,
+            write_buffered_bytes: s.write_buffered_bytes,
+            read_buffered_bytes: s.read_buffered_bytes,
+            blocked_on_sendme: blocked.map(Into::into),
+            total_blocked_on_sendme: (s.total_blocked + blocked.unwrap_or_default()).into(),
+            n_blocked_on_sendme: s.n_blocked,
+        }
+    }
+}
@@ -2,6 +2,8 @@
 
 use tor_cell::relaycell::msg::{BeginFlags, IpVersionPreference};
 
+use crate::circuit::sendme::{StreamParams, WindowParams as _};
+
 /// A set of preferences used to declare how a new stream should be opened.
 #[derive(Clone, Debug, Default)]
 pub struct StreamParameters {
@@ -13,6 +15,8 @@ pub struct StreamParameters {
     suppress_hostname: bool,
     /// True if we are suppressing flags.
     suppress_begin_flags: bool,
+    /// The initial send window to use, if not the default.
+    send_window_init: Option<u16>,
 }
 
 impl StreamParameters {
@@ -70,6 +74,31 @@ impl StreamParameters {
         self
     }
 
+    /// Configure how many DATA cells the stream may send before it waits
+    /// for the first SENDME from the other end.
+    ///
+    /// With SENDME-window flow control, the other end always assumes an
+    /// initial window of 500 cells, and this can't be negotiated.
+    /// But we can choose to send less than that:
+    /// a smaller window limits how much data can be in flight on the stream,
+    /// at the cost of throughput on long or slow circuits.
+    ///
+    /// The window is rounded down to a multiple of 50 cells (the SENDME increment),
+    /// and clamped to be between 50 and 500 cells.
+    ///
+    /// The default is 500 cells.
+    pub fn initial_send_window(&mut self, cells: u16) -> &mut Self {
+        let increment = StreamParams::increment();
+        let cells = cells.clamp(increment, StreamParams::maximum());
+        self.send_window_init = Some(cells / increment * increment);
+        self
+    }
+
+    /// Crate-internal: Return the initial send window to use, if it isn't the default.
+    pub(crate) fn send_window_init(&self) -> Option<u16> {
+        self.send_window_init
+    }
+
     /// Crate-internal: Return true if the stream is optimistic.
     pub(crate) fn is_optimistic(&self) -> bool {
         self.optimistic
@@ -89,3 +118,32 @@ impl StreamParameters {
         self.suppress_hostname
     }
 }
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+    use super::*;
+
+    #[test]
+    fn initial_send_window() {
+        let mut params = StreamParameters::new();
+        assert_eq!(params.send_window_init(), None);
+
+        for (cells, expected) in [(0, 50), (50, 50), (120, 100), (500, 500), (9000, 500)] {
+            params.initial_send_window(cells);
+            assert_eq!(params.send_window_init(), Some(expected));
+        }
+    }
+}
@@ -50,9 +50,14 @@ impl StreamReader {
                 Error::StreamProto("stream channel disappeared without END cell?".into())
             })?;
 
-        if sendme::cell_counts_towards_windows(&msg) && self.recv_window.take()? {
-            self.target.send_sendme()?;
-            self.recv_window.put()?;
+        if sendme::cell_counts_towards_windows(&msg) {
+            if self.recv_window.take()? {
+                self.target.send_sendme()?;
+                self.recv_window.put()?;
+            }
+            self.target
+                .flow_stats()
+                .note_recv_window(self.recv_window.window());
         }
 
         Ok(msg)
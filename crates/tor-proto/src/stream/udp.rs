@@ -0,0 +1,222 @@
+//! Declare a type for UDP streams, opened with a CONNECT_UDP message.
+//!
+//! This implements the client side of proposal 339.  It is experimental:
+//! few (if any) exits support it yet.
+//!
+//! # Flow control
+//!
+//! DATAGRAM messages count towards the circuit-level SENDME windows, like
+//! DATA messages, but a UDP stream has no stream-level window of its own.
+//! So a sender is held back only when the whole circuit's window is
+//! exhausted: callers are expected to run their own congestion control on
+//! top of these streams (as QUIC does).  Datagrams that cannot be sent
+//! immediately are queued in the circuit's buffers, which are bounded by
+//! the memory quota.
+
+use crate::circuit::StreamTarget;
+use crate::memquota::StreamAccount;
+use crate::stream::StreamReader;
+use crate::{Error, Result};
+use tor_cell::relaycell::msg::{AnyRelayMsg, Datagram};
+use tor_cell::relaycell::{RelayCmd, UnparsedRelayMsg};
+use tor_cell::restricted_msg;
+
+use super::AnyCmdChecker;
+
+restricted_msg! {
+    /// An allowable incoming message on a UDP stream.
+    enum UdpStreamMsg : RelayMsg {
+        ConnectedUdp,
+        Datagram,
+        End,
+    }
+}
+
+/// A UDP stream, on which we can send and receive datagrams.
+///
+/// Use [`ClientCirc::begin_udp_stream`](crate::circuit::ClientCirc::begin_udp_stream)
+/// to create one.
+#[derive(Debug)]
+pub struct DatagramStream {
+    /// The reading half of this stream.
+    r: DatagramReader,
+    /// The writing half of this stream.
+    w: DatagramWriter,
+}
+
+/// The reading half of a [`DatagramStream`].
+#[derive(Debug)]
+pub struct DatagramReader {
+    /// The underlying StreamReader.
+    s: StreamReader,
+    /// True if we have received a CONNECTED_UDP message.
+    connected: bool,
+    /// The memory quota account that should be used for this stream's data
+    ///
+    /// Exists to keep the account alive
+    _memquota: StreamAccount,
+}
+
+/// The writing half of a [`DatagramStream`].
+#[derive(Debug)]
+pub struct DatagramWriter {
+    /// The underlying StreamTarget.
+    s: StreamTarget,
+}
+
+impl DatagramStream {
+    /// The largest datagram that can be sent or received on a `DatagramStream`.
+    pub const MAX_DATAGRAM_LEN: usize = Datagram::MAXLEN;
+
+    /// Wrap a StreamReader and StreamTarget into a DatagramStream.
+    ///
+    /// Call only after sending a CONNECT_UDP message.
+    pub(crate) fn new(reader: StreamReader, target: StreamTarget, memquota: StreamAccount) -> Self {
+        DatagramStream {
+            r: DatagramReader {
+                s: reader,
+                connected: false,
+                _memquota: memquota,
+            },
+            w: DatagramWriter { s: target },
+        }
+    }
+
+    /// Wait until a CONNECTED_UDP message is received, or some other message
+    /// is received to indicate an error.
+    ///
+    /// Does nothing if this stream is already connected.
+    pub async fn wait_for_connection(&mut self) -> Result<()> {
+        if self.r.connected {
+            return Ok(());
+        }
+        match self.r.read_msg().await? {
+            UdpStreamMsg::ConnectedUdp(_) => {
+                self.r.connected = true;
+                Ok(())
+            }
+            // The command checker doesn't let a DATAGRAM through before CONNECTED_UDP.
+            UdpStreamMsg::Datagram(_) => Err(Error::from(tor_error::internal!(
+                "Received DATAGRAM before CONNECTED_UDP"
+            ))),
+            UdpStreamMsg::End(e) => Err(Error::EndReceived(e.reason())),
+        }
+    }
+
+    /// Send a single datagram on this stream.
+    ///
+    /// Returns an error if `datagram` is empty or longer than [`Self::MAX_DATAGRAM_LEN`].
+    pub async fn send(&mut self, datagram: &[u8]) -> Result<()> {
+        self.w.send(datagram).await
+    }
+
+    /// Receive a single datagram from this stream.
+    pub async fn recv(&mut self) -> Result<Vec<u8>> {
+        self.r.recv().await
+    }
+
+    /// Divide this DatagramStream into its constituent parts,
+    /// so that datagrams can be sent and received concurrently.
+    pub fn split(self) -> (DatagramReader, DatagramWriter) {
+        (self.r, self.w)
+    }
+}
+
+impl DatagramReader {
+    /// Read and decode the next message on this stream.
+    async fn read_msg(&mut self) -> Result<UdpStreamMsg> {
+        let msg = self.s.recv().await?;
+        match msg.decode::<UdpStreamMsg>() {
+            Ok(msg) => Ok(msg.into_msg()),
+            Err(e) => {
+                self.s.protocol_error();
+                Err(Error::from_bytes_err(e, "message on a UDP stream"))
+            }
+        }
+    }
+
+    /// Receive a single datagram from this stream.
+    ///
+    /// If the stream has not yet been connected, this waits for
+    /// the CONNECTED_UDP message first.
+    pub async fn recv(&mut self) -> Result<Vec<u8>> {
+        loop {
+            match self.read_msg().await? {
+                UdpStreamMsg::ConnectedUdp(_) => self.connected = true,
+                UdpStreamMsg::Datagram(d) => return Ok(d.into()),
+                UdpStreamMsg::End(e) => return Err(Error::EndReceived(e.reason())),
+            }
+        }
+    }
+}
+
+impl DatagramWriter {
+    /// Send a single datagram on this stream.
+    ///
+    /// Returns an error if `datagram` is empty or longer than
+    /// [`DatagramStream::MAX_DATAGRAM_LEN`].
+    pub async fn send(&mut self, datagram: &[u8]) -> Result<()> {
+        if datagram.is_empty() {
+            return Err(Error::from(tor_error::bad_api_usage!(
+                "Tried to send an empty datagram"
+            )));
+        }
+        let msg = Datagram::new(datagram).map_err(|e| Error::from_cell_enc(e, "datagram"))?;
+        self.s.send(AnyRelayMsg::from(msg)).await
+    }
+}
+
+/// A `CmdChecker` that enforces correctness for incoming commands on an
+/// outbound UDP stream.
+#[derive(Debug)]
+pub(crate) struct UdpCmdChecker {
+    /// True if we are expecting to receive a CONNECTED_UDP message on this stream.
+    expecting_connected: bool,
+}
+
+impl Default for UdpCmdChecker {
+    fn default() -> Self {
+        Self {
+            expecting_connected: true,
+        }
+    }
+}
+
+impl super::CmdChecker for UdpCmdChecker {
+    fn check_msg(&mut self, msg: &UnparsedRelayMsg) -> Result<super::StreamStatus> {
+        use super::StreamStatus::*;
+        match (msg.cmd(), self.expecting_connected) {
+            (RelayCmd::CONNECTED_UDP, true) => {
+                self.expecting_connected = false;
+                Ok(Open)
+            }
+            (RelayCmd::CONNECTED_UDP, false) => Err(Error::StreamProto(
+                "Received CONNECTED_UDP twice on a stream.".into(),
+            )),
+            (RelayCmd::DATAGRAM, false) => Ok(Open),
+            (RelayCmd::DATAGRAM, true) => Err(Error::StreamProto(
+                "Received DATAGRAM before CONNECTED_UDP on a stream".into(),
+            )),
+            (RelayCmd::END, _) => Ok(Closed),
+            _ => Err(Error::StreamProto(format!(
+                "Unexpected {} on a UDP stream!",
+                msg.cmd()
+            ))),
+        }
+    }
+
+    fn consume_checked_msg(&mut self, msg: UnparsedRelayMsg) -> Result<()> {
+        let _ = msg
+            .decode::<UdpStreamMsg>()
+            .map_err(|err| Error::from_bytes_err(err, "message on half-closed UDP stream"))?;
+        Ok(())
+    }
+}
+
+impl UdpCmdChecker {
+    /// Return a new boxed `UdpCmdChecker` in a state suitable for a newly
+    /// constructed stream.
+    pub(crate) fn new_any() -> AnyCmdChecker {
+        Box::<Self>::default()
+    }
+}
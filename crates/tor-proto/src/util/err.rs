@@ -106,6 +106,9 @@ pub enum Error {
     /// operation.
     #[error("Circuit closed")]
     CircuitClosed,
+    /// A hop of the circuit did not answer a liveness probe in time.
+    #[error("Circuit did not answer a liveness probe within {0:?}")]
+    CircuitUnresponsive(std::time::Duration),
     /// Can't allocate any more circuit or stream IDs on a channel.
     #[error("Too many entries in map: can't allocate ID")]
     IdRangeFull,
@@ -223,6 +226,8 @@ impl From<Error> for std::io::Error {
 
             CircuitClosed => ErrorKind::ConnectionReset,
 
+            CircuitUnresponsive(_) => ErrorKind::TimedOut,
+
             Memquota { .. } => ErrorKind::OutOfMemory,
 
             BytesErr { .. }
@@ -278,6 +283,7 @@ impl HasKind for Error {
             E::CircProto(_) => EK::TorProtocolViolation,
             E::ChannelClosed(e) => e.kind(),
             E::CircuitClosed => EK::CircuitCollapse,
+            E::CircuitUnresponsive(_) => EK::TorNetworkTimeout,
             E::IdRangeFull => EK::BadApiUsage,
             E::CircRefused(_) => EK::CircuitRefused,
             E::BadStreamAddress => EK::BadApiUsage,
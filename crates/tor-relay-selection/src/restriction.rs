@@ -50,6 +50,13 @@ enum RestrictionInner<'a> {
     /// Require that the relay has a given country code.
     #[cfg(feature = "geoip")]
     RequireCountry(tor_geoip::CountryCode),
+    /// Require that the relay is in none of the given countries.
+    #[cfg(feature = "geoip")]
+    ExcludeCountries(Vec<tor_geoip::CountryCode>),
+    /// Require that the relay has the Stable flag.
+    RequireStable,
+    /// Require that the relay has at least this consensus bandwidth weight.
+    MinBandwidthWeight(u32),
 }
 
 impl<'a> RelayRestriction<'a> {
@@ -79,6 +86,32 @@ impl<'a> RelayRestriction<'a> {
         }
     }
 
+    /// Require a relay that does not appear to be in any of the provided countries,
+    /// according to our geoip subsystem.
+    ///
+    /// Relays whose country we don't know are permitted.
+    #[cfg(feature = "geoip")]
+    pub fn exclude_country_codes(ccs: Vec<tor_geoip::CountryCode>) -> Self {
+        RelayRestriction {
+            inner: RestrictionInner::ExcludeCountries(ccs),
+        }
+    }
+
+    /// Require a relay that has the Stable flag,
+    /// even if our usage would not otherwise require one.
+    pub fn require_stable() -> Self {
+        RelayRestriction {
+            inner: RestrictionInner::RequireStable,
+        }
+    }
+
+    /// Require a relay whose consensus bandwidth weight is at least `min`.
+    pub fn require_min_bandwidth_weight(min: u32) -> Self {
+        RelayRestriction {
+            inner: RestrictionInner::MinBandwidthWeight(min),
+        }
+    }
+
     /// Require that a relay has at least one address
     /// listed in `addr_patterns`.
     pub fn require_address(addr_patterns: Vec<AddrPortPattern>) -> Self {
@@ -123,6 +156,10 @@ impl<'a> RelayRestriction<'a> {
             HasAddrInSet(_) => Some("not reachable (according to address filter)"),
             #[cfg(feature = "geoip")]
             RequireCountry(_) => Some("not in correct country"),
+            #[cfg(feature = "geoip")]
+            ExcludeCountries(_) => Some("in an excluded country"),
+            RequireStable => Some("not stable"),
+            MinBandwidthWeight(_) => Some("too little bandwidth"),
         }
     }
 }
@@ -137,6 +174,12 @@ impl<'a> LowLevelRelayPredicate for RelayRestriction<'a> {
             HasAddrInSet(patterns) => relay_has_addr_in_set(relay, patterns),
             #[cfg(feature = "geoip")]
             RequireCountry(cc) => relay.country_code() == Some(*cc),
+            #[cfg(feature = "geoip")]
+            ExcludeCountries(ccs) => relay.country_code().map_or(true, |cc| !ccs.contains(&cc)),
+            RequireStable => relay.low_level_details().is_flagged_stable(),
+            MinBandwidthWeight(min) => {
+                relay.low_level_details().consensus_bandwidth_weight() >= *min
+            }
         }
     }
 }
@@ -521,5 +564,29 @@ mod test {
         assert!(no.iter().all(|r| !p(r)));
     }
 
+    #[test]
+    fn require_stable() {
+        let nd = testnet();
+        let (yes, no) = split_netdir(&nd, &RelayRestriction::require_stable());
+        // In the test network, every fifth relay is not Stable.
+        assert_eq!(yes.len(), 32);
+        assert_eq!(no.len(), 8);
+        assert!(yes
+            .iter()
+            .all(|r| r.low_level_details().is_flagged_stable()));
+    }
+
+    #[test]
+    fn require_min_bandwidth_weight() {
+        let nd = testnet();
+        let (yes, no) = split_netdir(&nd, &RelayRestriction::require_min_bandwidth_weight(5000));
+        // In the test network, weights are 1000, 2000, ... 10000 in each group of ten.
+        assert_eq!(yes.len(), 24);
+        assert_eq!(no.len(), 16);
+        assert!(yes
+            .iter()
+            .all(|r| r.low_level_details().consensus_bandwidth_weight() >= 5000));
+    }
+
     // TODO: Write a geoip test?
 }
@@ -800,12 +800,16 @@ pub(crate) mod test {
     // Define 2 methods.
     #[derive(Debug, serde::Deserialize, Deftly)]
     #[derive_deftly(DynMethod)]
-    #[deftly(rpc(method_name = "x-test:getname"))]
+    #[deftly(rpc(method_name = "x-test:getname", introduced_in = "1.0.0"))]
     pub(crate) struct GetName;
 
     #[derive(Debug, serde::Deserialize, Deftly)]
     #[derive_deftly(DynMethod)]
-    #[deftly(rpc(method_name = "x-test:getkids"))]
+    #[deftly(rpc(
+        method_name = "x-test:getkids",
+        deprecated_since = "1.4.0",
+        deprecation_note = "Count them yourself."
+    ))]
     pub(crate) struct GetKids;
 
     impl RpcMethod for GetName {
@@ -1243,6 +1247,32 @@ pub(crate) mod test {
         table.insert(ent);
     }
 
+    #[test]
+    fn method_names() {
+        assert_eq!(crate::method_name(&GetName), Some("x-test:getname"));
+        assert_eq!(crate::method_name(&GetKids), Some("x-test:getkids"));
+    }
+
+    #[test]
+    fn method_versions() {
+        let v = crate::method_version_info(&GetName).unwrap();
+        assert_eq!(v.introduced_in(), Some("1.0.0"));
+        assert!(!v.is_deprecated());
+        assert_eq!(v.deprecation_note(), None);
+
+        let v = crate::method_version_info(&GetKids).unwrap();
+        assert_eq!(v.introduced_in(), None);
+        assert!(v.is_deprecated());
+        assert_eq!(v.deprecated_since(), Some("1.4.0"));
+        assert_eq!(v.deprecation_note(), Some("Count them yourself."));
+
+        let deprecated: Vec<_> = crate::iter_deprecated_methods()
+            .map(|(name, _)| name)
+            .collect();
+        assert!(deprecated.contains(&"x-test:getkids"));
+        assert!(!deprecated.contains(&"x-test:getname"));
+    }
+
     #[test]
     #[should_panic]
     fn conflicting_invoker_ents() {
@@ -4,7 +4,7 @@ use std::collections::{BTreeMap, BTreeSet};
 
 use serde::Serialize;
 
-use crate::{method::method_info_by_typeid, MethodInfo_, NoUpdates};
+use crate::{method::method_info_by_typeid, MethodInfo_, MethodVersionInfo, NoUpdates};
 
 /// A table describing, for a single RPC method,
 /// which types it expects and returns, and which objects it applies to.
@@ -24,6 +24,9 @@ struct MethodDescription {
     update_type: Option<String>,
     /// A list of the types of Objects that this method can be applied to.
     applies_to_object_types: BTreeSet<String>,
+    /// When this method was introduced, and whether it is deprecated.
+    #[serde(flatten)]
+    version: MethodVersionInfo,
 }
 
 /// A table describing the the set of RPC methods available,
@@ -102,6 +105,7 @@ impl MethodDescription {
             output_type: output_type_name,
             update_type: update_type_name,
             applies_to_object_types: Default::default(),
+            version: info.version_info(),
         }
     }
 
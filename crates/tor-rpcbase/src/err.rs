@@ -33,8 +33,24 @@ impl RpcError {
     }
 
     /// Change the declared kind of this error to `kind`.
+    ///
+    /// This also replaces any remediation advice, to match `kind`.
     pub fn set_kind(&mut self, kind: tor_error::ErrorKind) {
         self.kinds = AnyErrorKind::Tor(kind);
+        self.set_remediation(kind.remediation());
+    }
+
+    /// Set (or clear) the `arti:remediation` datum,
+    /// which tells the client what the user might do about this error.
+    fn set_remediation(&mut self, remediation: Option<tor_error::Remediation>) {
+        match remediation {
+            Some(r) => self.set_datum(REMEDIATION_KEYWORD.to_string(), r.as_str()),
+            None => {
+                if let Some(data) = &mut self.data {
+                    data.remove(REMEDIATION_KEYWORD);
+                }
+            }
+        }
     }
 
     /// Replace the `data` field named `keyword`, if any, with the object `datum`.
@@ -70,15 +86,20 @@ where
         let message = value.report().to_string();
         let code = kind_to_code(value.kind());
         let kinds = AnyErrorKind::Tor(value.kind());
-        RpcError {
+        let mut err = RpcError {
             message,
             code,
             kinds,
             data: None,
-        }
+        };
+        err.set_remediation(value.kind().remediation());
+        err
     }
 }
 
+/// The `data` keyword under which we report a [`tor_error::Remediation`].
+const REMEDIATION_KEYWORD: &str = "arti:remediation";
+
 /// Helper: Serialize an AnyErrorKind in RpcError.
 fn ser_kind<S: serde::Serializer>(kind: &AnyErrorKind, s: S) -> Result<S::Ok, S::Error> {
     // Our spec says that `kinds` is a list.  Any tor_error::ErrorKind is prefixed with `arti:`,
@@ -268,7 +289,10 @@ mod test {
         {
             "message": "error: I don't feel up to it today",
             "code": -32603,
-            "kinds": ["arti:Internal"]
+            "kinds": ["arti:Internal"],
+            "data": {
+                "arti:remediation": "report-bug"
+            }
          }
         "#;
         assert_json_eq!(&serialized, expected);
@@ -291,5 +315,20 @@ mod test {
         }
         "#;
         assert_json_eq!(&serialized, expected);
+
+        e.set_kind(tor_error::ErrorKind::TorNetworkTimeout);
+        let serialized = serde_json::to_string(&e).unwrap();
+        let expected = r#"
+        {
+            "message": "Example error",
+            "code": 2,
+            "kinds": ["arti:TorNetworkTimeout"],
+            "data": {
+                "rpc:example": "Hello world",
+                "arti:remediation": "retry-later"
+            }
+        }
+        "#;
+        assert_json_eq!(&serialized, expected);
     }
 }
@@ -0,0 +1,105 @@
+//! Deterministic JSON encoding for RPC messages.
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Encode `value` as canonical JSON.
+///
+/// The output has no insignificant whitespace,
+/// and every object's members appear in order of their (UTF-8) keys.
+/// Two values that are equal as JSON will always encode to the same string,
+/// no matter how their maps were built or in what order their fields were declared.
+///
+/// This is useful for test harnesses, and for clients that cache or compare responses.
+/// Arti's RPC server uses it for connections that ask for deterministic output.
+pub fn to_canonical_json<T: Serialize + ?Sized>(value: &T) -> serde_json::Result<String> {
+    let value = serde_json::to_value(value)?;
+    let mut out = String::new();
+    write_canonical(&value, &mut out)?;
+    Ok(out)
+}
+
+/// Append the canonical encoding of `value` to `out`.
+///
+/// (We don't rely on the ordering of `serde_json::Map`, since it depends on
+/// whether anybody in the dependency graph has enabled serde_json's
+/// `preserve_order` feature.)
+fn write_canonical(value: &Value, out: &mut String) -> serde_json::Result<()> {
+    match value {
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i != 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out)?;
+            }
+            out.push(']');
+        }
+        Value::Object(members) => {
+            let mut members: Vec<_> = members.iter().collect();
+            members.sort_by_key(|(k, _)| *k);
+            out.push('{');
+            for (i, (k, v)) in members.into_iter().enumerate() {
+                if i != 0 {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::to_string(k)?);
+                out.push(':');
+                write_canonical(v, out)?;
+            }
+            out.push('}');
+        }
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => {
+            out.push_str(&serde_json::to_string(value)?);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    // @@ begin test lint list maintained by maint/add_warning @@
+    #![allow(clippy::bool_assert_comparison)]
+    #![allow(clippy::clone_on_copy)]
+    #![allow(clippy::dbg_macro)]
+    #![allow(clippy::mixed_attributes_style)]
+    #![allow(clippy::print_stderr)]
+    #![allow(clippy::print_stdout)]
+    #![allow(clippy::single_char_pattern)]
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::unchecked_duration_subtraction)]
+    #![allow(clippy::useless_vec)]
+    #![allow(clippy::needless_pass_by_value)]
+    //! <!-- @@ end test lint list maintained by maint/add_warning @@ -->
+
+    use super::*;
+    use std::collections::HashMap;
+
+    #[derive(Serialize)]
+    struct Example {
+        zebra: u32,
+        apple: Vec<Option<bool>>,
+        mango: HashMap<String, String>,
+    }
+
+    #[test]
+    fn canonical() {
+        let mut mango = HashMap::new();
+        for k in ["z", "é", "a", "B", "\n"] {
+            mango.insert(k.to_string(), k.to_uppercase());
+        }
+        let ex = Example {
+            zebra: 7,
+            apple: vec![Some(true), None],
+            mango,
+        };
+        assert_eq!(
+            to_canonical_json(&ex).unwrap(),
+            r#"{"apple":[true,null],"mango":{"\n":"\n","B":"B","a":"A","z":"Z","é":"É"},"zebra":7}"#
+        );
+
+        assert_eq!(to_canonical_json("x").unwrap(), r#""x""#);
+        assert_eq!(to_canonical_json(&[1.5, -2.0]).unwrap(), "[1.5,-2.0]");
+    }
+}
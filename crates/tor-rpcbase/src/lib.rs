@@ -43,6 +43,7 @@
 
 pub mod dispatch;
 mod err;
+mod json;
 mod method;
 mod obj;
 
@@ -50,9 +51,11 @@ use std::{convert::Infallible, sync::Arc};
 
 pub use dispatch::{DispatchTable, InvokeError, UpdateSink};
 pub use err::{RpcError, RpcErrorKind};
+pub use json::to_canonical_json;
 pub use method::{
-    check_method_names, is_method_name, iter_method_names, DeserMethod, DynMethod,
-    InvalidMethodName, Method, NoUpdates, RpcMethod,
+    check_method_names, is_method_name, iter_deprecated_methods, iter_method_names, method_name,
+    method_version_info, DeserMethod, DynMethod, InvalidMethodName, Method, MethodVersionInfo,
+    NoUpdates, RpcMethod,
 };
 pub use obj::{Object, ObjectArcExt, ObjectId};
 
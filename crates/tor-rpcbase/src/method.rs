@@ -135,6 +135,65 @@ pub struct MethodInfo_ {
     pub output_name: fn() -> &'static str,
     /// A function returning the name for this method's update type.
     pub update_name: fn() -> &'static str,
+    /// The version of Arti that introduced this method, if recorded.
+    pub introduced_in: Option<&'static str>,
+    /// The version of Arti that deprecated this method, if it is deprecated.
+    pub deprecated_since: Option<&'static str>,
+    /// An explanation of what to use instead of this method, if it is deprecated.
+    pub deprecation_note: Option<&'static str>,
+}
+
+impl MethodInfo_ {
+    /// Return the [`MethodVersionInfo`] for this method.
+    pub(crate) fn version_info(&self) -> MethodVersionInfo {
+        MethodVersionInfo {
+            introduced_in: self.introduced_in,
+            deprecated_since: self.deprecated_since,
+            deprecation_note: self.deprecation_note,
+        }
+    }
+}
+
+/// When an RPC method was introduced, and whether it is deprecated.
+///
+/// Declared with the `introduced_in`, `deprecated_since`, and `deprecation_note`
+/// attributes of [`derive_deftly(DynMethod)`](derive_deftly_template_DynMethod).
+///
+/// A deprecated method still works,
+/// but clients should stop using it: it may be removed in a future version.
+#[derive(Clone, Debug, Default, Eq, PartialEq, serde::Serialize)]
+pub struct MethodVersionInfo {
+    /// The version of Arti that introduced this method, if recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    introduced_in: Option<&'static str>,
+    /// The version of Arti that deprecated this method, if it is deprecated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deprecated_since: Option<&'static str>,
+    /// An explanation of what to use instead of this method, if it is deprecated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deprecation_note: Option<&'static str>,
+}
+
+impl MethodVersionInfo {
+    /// Return the version of Arti that introduced this method, if recorded.
+    pub fn introduced_in(&self) -> Option<&'static str> {
+        self.introduced_in
+    }
+
+    /// Return the version of Arti that deprecated this method, if it is deprecated.
+    pub fn deprecated_since(&self) -> Option<&'static str> {
+        self.deprecated_since
+    }
+
+    /// Return an explanation of what to use instead of this method, if it is deprecated.
+    pub fn deprecation_note(&self) -> Option<&'static str> {
+        self.deprecation_note
+    }
+
+    /// Return true if this method is deprecated.
+    pub fn is_deprecated(&self) -> bool {
+        self.deprecated_since.is_some()
+    }
 }
 
 inventory::collect!(MethodInfo_);
@@ -163,6 +222,25 @@ define_derive_deftly! {
 ///     type Update = rpc::NoUpdates;
 /// }
 /// ```
+///
+/// # Versioning
+///
+/// A method with a `method_name` may also say when it was introduced,
+/// and whether it is deprecated:
+///
+/// ```ignore
+/// #[deftly(rpc(
+///     method_name = "x-example:scold",
+///     introduced_in = "1.2.0",
+///     deprecated_since = "1.4.0",
+///     deprecation_note = "Use x-example:castigate instead."
+/// ))]
+/// ```
+///
+/// This information is reported by [`method_version_info`],
+/// and in the [`RpcDispatchInformation`](crate::RpcDispatchInformation)
+/// that lists every method.
+/// `deprecation_note` requires `deprecated_since`.
     export DynMethod:
     const _: () = {
         ${if not(tmeta(rpc(bypass_method_dispatch))) {
@@ -188,6 +266,24 @@ define_derive_deftly! {
                     typeid : std::any::TypeId::of::<$ttype>,
                     output_name: std::any::type_name::<<$ttype as $crate::RpcMethod>::Output>,
                     update_name: std::any::type_name::<<$ttype as $crate::RpcMethod>::Update>,
+                    introduced_in: ${if tmeta(rpc(introduced_in)) {
+                        Some(${tmeta(rpc(introduced_in)) as str})
+                    } else {
+                        None
+                    }},
+                    deprecated_since: ${if tmeta(rpc(deprecated_since)) {
+                        Some(${tmeta(rpc(deprecated_since)) as str})
+                    } else {
+                        None
+                    }},
+                    deprecation_note: ${if tmeta(rpc(deprecation_note)) {
+                        ${if not(tmeta(rpc(deprecated_since))) {
+                            ${error "deprecation_note requires deprecated_since."}
+                        }}
+                        Some(${tmeta(rpc(deprecation_note)) as str})
+                    } else {
+                        None
+                    }},
                 }
             }
         } else if tmeta(rpc(no_method_name)) {
@@ -213,6 +309,25 @@ pub fn iter_method_names() -> impl Iterator<Item = &'static str> {
     inventory::iter::<MethodInfo_>().map(|mi| mi.method_name)
 }
 
+/// Return the name under which `method` can be invoked over RPC, if it has one.
+pub fn method_name(method: &dyn DynMethod) -> Option<&'static str> {
+    method_info_by_typeid(method.as_any().type_id()).map(|mi| mi.method_name)
+}
+
+/// Return when `method` was introduced, and whether it is deprecated,
+/// if it can be invoked over RPC.
+pub fn method_version_info(method: &dyn DynMethod) -> Option<MethodVersionInfo> {
+    method_info_by_typeid(method.as_any().type_id()).map(MethodInfo_::version_info)
+}
+
+/// Return an iterator that yields the name of every deprecated method,
+/// along with its [`MethodVersionInfo`].
+pub fn iter_deprecated_methods() -> impl Iterator<Item = (&'static str, MethodVersionInfo)> {
+    inventory::iter::<MethodInfo_>()
+        .filter(|mi| mi.deprecated_since.is_some())
+        .map(|mi| (mi.method_name, mi.version_info()))
+}
+
 /// Given a type ID, return its RPC MethodInfo_ (if any).
 pub(crate) fn method_info_by_typeid(typeid: any::TypeId) -> Option<&'static MethodInfo_> {
     /// Lazy map from TypeId to RPC method name.
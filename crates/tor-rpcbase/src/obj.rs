@@ -51,6 +51,16 @@ pub trait Object: DowncastSync + Send + Sync + 'static {
     fn delegate(&self) -> Option<Arc<dyn Object>> {
         None
     }
+
+    /// Return the name of this object's concrete type.
+    ///
+    /// This is meant for diagnostics, such as log messages:
+    /// its exact format is not specified, and may change between releases.
+    ///
+    /// You should not implement this method yourself.
+    fn object_type_name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
 }
 downcast_rs::impl_downcast!(sync Object);
 
@@ -478,6 +488,17 @@ mod test {
         assert!(Arc::ptr_eq(&err_arc, &erased_arc_bytes));
     }
 
+    #[test]
+    fn type_name() {
+        let pogo: Arc<dyn Object> = Arc::new(Opossum {});
+        assert!(pogo.object_type_name().ends_with("::Opossum"));
+
+        let bikes: Box<dyn Object> = Box::new(Crowd {
+            members: vec![Bicycle {}],
+        });
+        assert!(bikes.object_type_name().contains("::Crowd<"));
+    }
+
     #[derive(Deftly)]
     #[derive_deftly(Object)]
     #[deftly(rpc(delegate_with = "|cage: &Self| Some(cage.possum.clone())"))]
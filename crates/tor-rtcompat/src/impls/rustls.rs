@@ -20,7 +20,7 @@ use std::{
 ///
 /// The application is responsible for calling `CryptoProvider::install_default_provider()`
 /// before constructing one of these providers.  If they do not, we will issue a warning,
-/// and install a default (ring) provider.
+/// and install a default provider (aws-lc if the `aws-lc` feature is enabled, and ring otherwise).
 #[cfg_attr(
     docsrs,
     doc(cfg(all(feature = "rustls", any(feature = "tokio", feature = "async-std"))))
@@ -102,16 +102,14 @@ impl RustlsProvider {
     pub(crate) fn new() -> Self {
         if futures_rustls::rustls::crypto::CryptoProvider::get_default().is_none() {
             // If we haven't installed a CryptoProvider at this point, we warn and install
-            // the `ring` provider.  That isn't great, but the alternative would be to
+            // our default provider.  That isn't great, but the alternative would be to
             // panic.  Right now, that would cause many of our tests to fail.
             tracing::warn!(
                 "Creating a RustlsRuntime, but no CryptoProvider is installed. The application \
                             should call CryptoProvider::install_default()"
             );
             let _idempotent_ignore =
-                futures_rustls::rustls::crypto::CryptoProvider::install_default(
-                    futures_rustls::rustls::crypto::ring::default_provider(),
-                );
+                futures_rustls::rustls::crypto::CryptoProvider::install_default(default_provider());
         }
 
         // Be afraid: we are overriding the default certificate verification and
@@ -223,12 +221,24 @@ impl danger::ServerCertVerifier for Verifier {
     }
 
     fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
-        rustls::crypto::ring::default_provider()
+        default_provider()
             .signature_verification_algorithms
             .supported_schemes()
     }
 }
 
+/// Return the `CryptoProvider` we use if the application hasn't installed one.
+fn default_provider() -> rustls::crypto::CryptoProvider {
+    #[cfg(feature = "aws-lc")]
+    {
+        rustls::crypto::aws_lc_rs::default_provider()
+    }
+    #[cfg(not(feature = "aws-lc"))]
+    {
+        rustls::crypto::ring::default_provider()
+    }
+}
+
 /// Parse a `rustls::Certificate` as an `x509_signature::X509Certificate`, if possible.
 fn get_cert<'a>(c: &'a Certificate<'a>) -> Result<x509_signature::X509Certificate<'a>, TLSError> {
     x509_signature::parse_certificate(c.as_ref())
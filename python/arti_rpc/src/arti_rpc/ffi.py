@@ -102,6 +102,29 @@ def _annotate_library(lib: ctypes.CDLL):
     ]
     lib.arti_rpc_conn_execute.restype = _ArtiRpcStatus
 
+    lib.arti_rpc_conn_execute_with_timeout.argtypes = [
+        POINTER(ArtiRpcConn),
+        c_char_p,
+        c_uint64,
+        _RpcStrOut,
+        _ErrorOut,
+    ]
+    lib.arti_rpc_conn_execute_with_timeout.restype = _ArtiRpcStatus
+
+    lib.arti_rpc_conn_set_default_timeout.argtypes = [
+        POINTER(ArtiRpcConn),
+        c_uint64,
+        _ErrorOut,
+    ]
+    lib.arti_rpc_conn_set_default_timeout.restype = _ArtiRpcStatus
+
+    lib.arti_rpc_conn_get_proxy_info.argtypes = [
+        POINTER(ArtiRpcConn),
+        _RpcStrOut,
+        _ErrorOut,
+    ]
+    lib.arti_rpc_conn_get_proxy_info.restype = _ArtiRpcStatus
+
     lib.arti_rpc_conn_execute_with_handle.argtypes = [
         POINTER(ArtiRpcConn),
         c_char_p,
@@ -128,12 +151,22 @@ def _annotate_library(lib: ctypes.CDLL):
     lib.arti_rpc_err_os_error_code.argtypes = [POINTER(ArtiRpcError)]
     lib.arti_rpc_err_os_error_code.restype = c_int
 
+    lib.arti_rpc_err_remediation.argtypes = [POINTER(ArtiRpcError)]
+    lib.arti_rpc_err_remediation.restype = c_char_p
+
     lib.arti_rpc_err_response.argtypes = [POINTER(ArtiRpcError)]
     lib.arti_rpc_err_response.restype = c_char_p
 
     lib.arti_rpc_err_status.argtypes = [POINTER(ArtiRpcError)]
     lib.arti_rpc_err_status.restype = _ArtiRpcStatus
 
+    lib.arti_rpc_handle_cancel.argtypes = [
+        POINTER(ArtiRpcConn),
+        POINTER(ArtiRpcHandle),
+        _ErrorOut,
+    ]
+    lib.arti_rpc_handle_cancel.restype = _ArtiRpcStatus
+
     lib.arti_rpc_handle_free.argtypes = [POINTER(ArtiRpcHandle)]
     lib.arti_rpc_handle_free.restype = None
 
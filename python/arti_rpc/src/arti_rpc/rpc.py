@@ -102,6 +102,17 @@ def _into_json_str(o: Union[str, dict]) -> str:
         return o
 
 
+def _timeout_to_msec(timeout: Optional[float]) -> int:
+    """
+    Convert a timeout in seconds, or None for "no timeout",
+    into the milliseconds (or 0) that our FFI functions expect.
+    """
+    if timeout is None:
+        return 0
+    # Never round a real timeout down to 0, which would mean "no timeout".
+    return max(1, round(timeout * 1000))
+
+
 class ArtiRpcConn(_RpcBase):
     """
     An open connection to Arti.
@@ -162,7 +173,40 @@ class ArtiRpcConn(_RpcBase):
         """
         return self._session
 
-    def execute(self, request: Union[str, dict]) -> dict:
+    def set_default_timeout(self, timeout: Optional[float]) -> None:
+        """
+        Set how many seconds `execute` waits for a response by default,
+        before raising an error with the status `TIMED_OUT`.
+
+        If `timeout` is None, wait for as long as it takes.
+        """
+        error = POINTER(arti_rpc.ffi.ArtiRpcError)()
+        rv = self._rpc.arti_rpc_conn_set_default_timeout(
+            self._conn, _timeout_to_msec(timeout), byref(error)
+        )
+        self._handle_error(rv, error)
+
+    def get_proxy_info(self) -> dict:
+        """
+        Return a description of the proxies that Arti offers to RPC applications.
+
+        The result is a dict with a single member, `proxies`:
+        a list of proxies, each with a `listener` describing
+        where it listens and what protocol it speaks
+        (for example, `{"socks5": {"tcp_address": "127.0.0.1:9150"}}`,
+        or `{"dns": {"udp_address": "127.0.0.1:9053"}}`).
+        """
+        out = POINTER(arti_rpc.ffi.ArtiRpcStr)()
+        error = POINTER(arti_rpc.ffi.ArtiRpcError)()
+        rv = self._rpc.arti_rpc_conn_get_proxy_info(
+            self._conn, byref(out), byref(error)
+        )
+        self._handle_error(rv, error)
+        return json.loads(self._consume_rpc_str(out))
+
+    def execute(
+        self, request: Union[str, dict], timeout: Optional[float] = None
+    ) -> dict:
         """
         Run an RPC request on this connection.
 
@@ -174,13 +218,27 @@ class ArtiRpcConn(_RpcBase):
 
         The request may be a string, or a dict that will be encoded
         as a json object.
+
+        If `timeout` is provided, wait no more than that many seconds
+        for the response, instead of the default set with `set_default_timeout`.
+        If the response doesn't arrive in time, raise an error with the status
+        `TIMED_OUT`, and ask Arti to cancel the request.
         """
         msg = _into_json_str(request)
         response = POINTER(arti_rpc.ffi.ArtiRpcStr)()
         error = POINTER(arti_rpc.ffi.ArtiRpcError)()
-        rv = self._rpc.arti_rpc_conn_execute(
-            self._conn, msg.encode("utf-8"), byref(response), byref(error)
-        )
+        if timeout is None:
+            rv = self._rpc.arti_rpc_conn_execute(
+                self._conn, msg.encode("utf-8"), byref(response), byref(error)
+            )
+        else:
+            rv = self._rpc.arti_rpc_conn_execute_with_timeout(
+                self._conn,
+                msg.encode("utf-8"),
+                _timeout_to_msec(timeout),
+                byref(response),
+                byref(error),
+            )
         self._handle_error(rv, error)
         r = ArtiRpcResponse(self._consume_rpc_str(response))
         assert r.kind() == ArtiRpcResponseKind.RESULT
@@ -287,6 +345,8 @@ class ArtiRpcErrorStatus(Enum):
     PROXY_IO = 10
     STREAM_FAILED = 11
     NOT_AUTHENTICATED = 12
+    REQUEST_CANCELLED = 13
+    TIMED_OUT = 14
 
 
 def _error_status_from_int(status: int) -> Union[ArtiRpcErrorStatus, int]:
@@ -348,6 +408,17 @@ class ArtiRpcError(Exception):
         else:
             return code
 
+    def remediation(self) -> Optional[str]:
+        """
+        Return a short identifier for what the user might do about this error,
+        like "retry-later" or "fix-config", if there is one.
+        """
+        remediation = self._rpc.arti_rpc_err_remediation(self._err)
+        if remediation is None:
+            return None
+        else:
+            return remediation.decode("utf-8")
+
     def response_str(self) -> Optional[str]:
         """
         Return the RPC response string associated with this error,